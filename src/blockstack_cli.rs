@@ -20,6 +20,8 @@
 #![allow(non_upper_case_globals)]
 
 extern crate blockstack_lib;
+#[macro_use]
+extern crate serde_json;
 
 use std::convert::TryFrom;
 use std::io::prelude::*;
@@ -33,9 +35,11 @@ use blockstack_lib::burnchains::bitcoin::address::{
 };
 use blockstack_lib::burnchains::Address;
 use blockstack_lib::chainstate::stacks::{
+    AssetInfo, FungibleConditionCode, NonfungibleConditionCode, PostConditionPrincipal,
     StacksBlock, StacksMicroblock, StacksPrivateKey, StacksPublicKey, StacksTransaction,
     StacksTransactionSigner, TokenTransferMemo, TransactionAnchorMode, TransactionAuth,
-    TransactionContractCall, TransactionPayload, TransactionSmartContract,
+    TransactionAuthField, TransactionContractCall, TransactionPayload, TransactionPostCondition,
+    TransactionPostConditionMode, TransactionPublicKeyEncoding, TransactionSmartContract,
     TransactionSpendingCondition, TransactionVersion, C32_ADDRESS_VERSION_MAINNET_SINGLESIG,
     C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
 };
@@ -49,9 +53,10 @@ use blockstack_lib::util::{
 use blockstack_lib::vm;
 use blockstack_lib::vm::{
     errors::{Error as ClarityError, RuntimeErrorType},
-    types::PrincipalData,
+    types::{PrincipalData, QualifiedContractIdentifier},
     ClarityName, ContractName, Value,
 };
+use serde_json::Value as JsonValue;
 
 const USAGE: &str = "blockstack-cli (options) [method] [args...]
 
@@ -68,6 +73,8 @@ This CLI has these methods:
   decode-tx          used to decode a hex-encoded transaction into a human-readable representation
   decode-block       used to decode a hex-encoded Stacks block into a human-readable representation
   decode-microblock  used to decode a hex-encoded Stacks microblock into a human-readable representation
+  explain-tx         used to decode a hex-encoded transaction into readable JSON, with addresses and Clarity values spelled out
+  make-tx            used to generate and sign a token-transfer or contract-call transaction described in JSON
 
 For usage information on those methods, call `blockstack-cli [method] -h`
 
@@ -164,6 +171,48 @@ raw binary microblock will be read from stdin.
 N.B. Stacks microblocks are not stored as files in the Stacks chainstate -- they are stored in 
 block's sqlite database.";
 
+const EXPLAIN_TRANSACTION_USAGE: &str =
+    "blockstack-cli (options) explain-tx [transaction-hex-or-stdin]
+
+The explain-tx command decodes a serialized Stacks transaction and prints it to stdout as JSON.
+Unlike decode-tx, the JSON spells out addresses, principals, post-condition codes and Clarity
+values (both as Clarity literals and hex-serialized), and reports whether the transaction's
+signatures verify.  The transaction, if given, must be a hex string.  Alternatively, you may pass
+- instead, and the raw binary transaction will be read from stdin.";
+
+const MAKE_TRANSACTION_USAGE: &str = "blockstack-cli (options) make-tx [origin-secret-key-hex] [spec-path-or-stdin]
+
+The make-tx command generates and signs a transaction described by a JSON document, read from
+the given file (or from stdin, if - is given).  If successful, this command outputs the hex string
+encoding of the transaction to stdout, and exits with code 0.  The document has the form:
+
+  {
+    \"type\": \"token-transfer\" or \"contract-call\",
+    \"fee\": 200,
+    \"nonce\": 0,
+    \"anchor_mode\": \"any\", \"on-chain-only\" or \"off-chain-only\"     (optional; default is any)
+    \"post_condition_mode\": \"allow\" or \"deny\"                    (optional; default is deny)
+    \"post_conditions\": [ ... ]                                    (optional)
+
+    for token-transfer:
+    \"recipient\": \"SP...\", \"amount\": 1000, \"memo\": \"68656c6c6f\"   (memo is optional hex)
+
+    for contract-call:
+    \"contract\": \"SP....contract-name\", \"function\": \"function-name\",
+    \"args\": [ {\"hex\": \"0100..\"}, {\"eval\": \"(+ 1 2)\"} ]
+  }
+
+Each post-condition is one of:
+
+  {\"type\": \"stx\", \"principal\": P, \"condition\": C, \"amount\": 1000}
+  {\"type\": \"ft\", \"principal\": P, \"asset\": \"SP....contract-name::token-name\", \"condition\": C, \"amount\": 1000}
+  {\"type\": \"nft\", \"principal\": P, \"asset\": \"SP....contract-name::token-name\", \"value\": {\"hex\": ..}, \"condition\": \"sent\" or \"not-sent\"}
+
+where P is \"origin\", a standard address, or a contract principal, and C is one of
+sent-equal-to, sent-greater-than, sent-greater-than-or-equal-to, sent-less-than, or
+sent-less-than-or-equal-to.  The explain-tx command prints transactions in the same terms, along
+with a token-transfer's memo as text (`memo_text`) if it is printable.";
+
 #[derive(Debug)]
 enum CliError {
     ClarityRuntimeError(RuntimeErrorType),
@@ -737,6 +786,456 @@ fn decode_microblock(args: &[String], _version: TransactionVersion) -> Result<St
     }
 }
 
+fn fungible_condition_name(code: &FungibleConditionCode) -> &'static str {
+    match code {
+        FungibleConditionCode::SentEq => "sent-equal-to",
+        FungibleConditionCode::SentGt => "sent-greater-than",
+        FungibleConditionCode::SentGe => "sent-greater-than-or-equal-to",
+        FungibleConditionCode::SentLt => "sent-less-than",
+        FungibleConditionCode::SentLe => "sent-less-than-or-equal-to",
+    }
+}
+
+fn nonfungible_condition_name(code: &NonfungibleConditionCode) -> &'static str {
+    match code {
+        NonfungibleConditionCode::Sent => "sent",
+        NonfungibleConditionCode::NotSent => "not-sent",
+    }
+}
+
+fn explain_value(value: &Value) -> JsonValue {
+    let mut bytes = vec![];
+    value
+        .serialize_write(&mut bytes)
+        .expect("FATAL: failed to serialize Clarity value");
+    json!({ "repr": value.to_string(), "hex": to_hex(&bytes) })
+}
+
+/// A memo, in hex, and as text too if it is printable once its zero padding is trimmed
+fn explain_memo(memo: &TokenTransferMemo) -> (String, Option<String>) {
+    let len = memo.0.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+    let text = String::from_utf8_lossy(&memo.0[0..len]);
+    let printable = len > 0 && text.chars().all(|c| !c.is_control());
+    (
+        to_hex(&memo.0),
+        if printable {
+            Some(text.into_owned())
+        } else {
+            None
+        },
+    )
+}
+
+fn explain_spending_condition(
+    condition: &TransactionSpendingCondition,
+    version: TransactionVersion,
+) -> JsonValue {
+    let address = match version {
+        TransactionVersion::Mainnet => condition.address_mainnet(),
+        TransactionVersion::Testnet => condition.address_testnet(),
+    };
+    match condition {
+        TransactionSpendingCondition::Singlesig(ref data) => json!({
+            "address": address.to_string(),
+            "hash_mode": format!("{:?}", &data.hash_mode),
+            "nonce": data.nonce,
+            "fee": data.tx_fee,
+            "key_encoding": format!("{:?}", &data.key_encoding),
+            "signature": to_hex(&data.signature.0),
+        }),
+        TransactionSpendingCondition::Multisig(ref data) => {
+            let fields: Vec<_> = data
+                .fields
+                .iter()
+                .map(|field| match field {
+                    TransactionAuthField::PublicKey(ref pubk) => {
+                        json!({ "public_key": pubk.to_hex() })
+                    }
+                    TransactionAuthField::Signature(ref key_encoding, ref sig) => json!({
+                        "key_encoding": format!("{:?}", key_encoding),
+                        "signature": to_hex(&sig.0),
+                    }),
+                })
+                .collect();
+            json!({
+                "address": address.to_string(),
+                "hash_mode": format!("{:?}", &data.hash_mode),
+                "nonce": data.nonce,
+                "fee": data.tx_fee,
+                "signatures_required": data.signatures_required,
+                "fields": fields,
+            })
+        }
+    }
+}
+
+fn explain_post_condition_principal(principal: &PostConditionPrincipal) -> String {
+    match principal {
+        PostConditionPrincipal::Origin => "origin".to_string(),
+        PostConditionPrincipal::Standard(ref addr) => addr.to_string(),
+        PostConditionPrincipal::Contract(ref addr, ref name) => format!("{}.{}", addr, name),
+    }
+}
+
+fn explain_asset(asset: &AssetInfo) -> String {
+    format!(
+        "{}.{}::{}",
+        &asset.contract_address, &asset.contract_name, &asset.asset_name
+    )
+}
+
+fn explain_transaction_json(tx: &StacksTransaction) -> JsonValue {
+    let auth = match tx.auth {
+        TransactionAuth::Standard(ref origin) => json!({
+            "type": "standard",
+            "origin": explain_spending_condition(origin, tx.version),
+        }),
+        TransactionAuth::Sponsored(ref origin, ref sponsor) => json!({
+            "type": "sponsored",
+            "origin": explain_spending_condition(origin, tx.version),
+            "sponsor": explain_spending_condition(sponsor, tx.version),
+        }),
+    };
+
+    let payload = match tx.payload {
+        TransactionPayload::TokenTransfer(ref recipient, ref amount, ref memo) => {
+            let (memo_hex, memo_text) = explain_memo(memo);
+            json!({
+                "type": "token-transfer",
+                "recipient": recipient.to_string(),
+                "amount": amount,
+                "memo": memo_hex,
+                "memo_text": memo_text,
+            })
+        }
+        TransactionPayload::ContractCall(ref call) => json!({
+            "type": "contract-call",
+            "contract": call.to_clarity_contract_id().to_string(),
+            "function": call.function_name.to_string(),
+            "args": call.function_args.iter().map(explain_value).collect::<Vec<_>>(),
+        }),
+        TransactionPayload::SmartContract(ref contract) => json!({
+            "type": "smart-contract",
+            "name": contract.name.to_string(),
+            "code_body": contract.code_body.to_string(),
+        }),
+        TransactionPayload::PoisonMicroblock(ref h1, ref h2) => json!({
+            "type": "poison-microblock",
+            "microblock_1": h1.block_hash().to_string(),
+            "microblock_2": h2.block_hash().to_string(),
+        }),
+        TransactionPayload::Coinbase(ref payload) => json!({
+            "type": "coinbase",
+            "payload": to_hex(&payload.0),
+        }),
+    };
+
+    let post_conditions: Vec<_> = tx
+        .post_conditions
+        .iter()
+        .map(|pc| match pc {
+            TransactionPostCondition::STX(ref principal, ref code, ref amount) => json!({
+                "type": "stx",
+                "principal": explain_post_condition_principal(principal),
+                "condition": fungible_condition_name(code),
+                "amount": amount,
+            }),
+            TransactionPostCondition::Fungible(ref principal, ref asset, ref code, ref amount) => {
+                json!({
+                    "type": "ft",
+                    "principal": explain_post_condition_principal(principal),
+                    "asset": explain_asset(asset),
+                    "condition": fungible_condition_name(code),
+                    "amount": amount,
+                })
+            }
+            TransactionPostCondition::Nonfungible(
+                ref principal,
+                ref asset,
+                ref value,
+                ref code,
+            ) => {
+                json!({
+                    "type": "nft",
+                    "principal": explain_post_condition_principal(principal),
+                    "asset": explain_asset(asset),
+                    "value": explain_value(value),
+                    "condition": nonfungible_condition_name(code),
+                })
+            }
+        })
+        .collect();
+
+    json!({
+        "txid": tx.txid().to_string(),
+        "version": match tx.version {
+            TransactionVersion::Mainnet => "mainnet",
+            TransactionVersion::Testnet => "testnet",
+        },
+        "chain_id": format!("0x{:08x}", tx.chain_id),
+        "anchor_mode": match tx.anchor_mode {
+            TransactionAnchorMode::OnChainOnly => "on-chain-only",
+            TransactionAnchorMode::OffChainOnly => "off-chain-only",
+            TransactionAnchorMode::Any => "any",
+        },
+        "post_condition_mode": match tx.post_condition_mode {
+            TransactionPostConditionMode::Allow => "allow",
+            TransactionPostConditionMode::Deny => "deny",
+        },
+        "auth": auth,
+        "payload": payload,
+        "post_conditions": post_conditions,
+        "verified": tx.verify().is_ok(),
+    })
+}
+
+fn explain_transaction(args: &[String], _version: TransactionVersion) -> Result<String, CliError> {
+    if (args.len() >= 1 && args[0] == "-h") || args.len() != 1 {
+        return Err(CliError::Message(format!(
+            "Usage: {}\n",
+            EXPLAIN_TRANSACTION_USAGE
+        )));
+    }
+
+    let tx_bytes = if args[0] == "-" {
+        let mut tx_bytes = Vec::new();
+        io::stdin().read_to_end(&mut tx_bytes)?;
+        tx_bytes
+    } else {
+        hex_bytes(&args[0])?
+    };
+
+    let tx = StacksTransaction::consensus_deserialize(&mut io::Cursor::new(&tx_bytes))?;
+    Ok(serde_json::to_string_pretty(&explain_transaction_json(&tx))
+        .expect("Failed to serialize transaction to JSON"))
+}
+
+fn spec_str<'a>(spec: &'a JsonValue, key: &str) -> Result<&'a str, CliError> {
+    spec.get(key)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| CliError::Message(format!("Missing or non-string field `{}`", key)))
+}
+
+fn spec_u64(spec: &JsonValue, key: &str) -> Result<u64, CliError> {
+    spec.get(key)
+        .and_then(|v| v.as_u64())
+        .ok_or_else(|| CliError::Message(format!("Missing or non-integer field `{}`", key)))
+}
+
+fn parse_spec_value(spec: &JsonValue) -> Result<Value, CliError> {
+    if let Some(hex) = spec.get("hex").and_then(|v| v.as_str()) {
+        Ok(Value::try_deserialize_hex_untyped(hex)?)
+    } else if let Some(program) = spec.get("eval").and_then(|v| v.as_str()) {
+        Ok(vm::execute(program)?.ok_or("Supplied argument did not evaluate to a Value")?)
+    } else {
+        Err("Clarity values must be given as {\"hex\": ...} or {\"eval\": ...}".into())
+    }
+}
+
+fn parse_spec_principal(principal: &str) -> Result<PostConditionPrincipal, CliError> {
+    if principal == "origin" {
+        return Ok(PostConditionPrincipal::Origin);
+    }
+    match PrincipalData::parse(principal)
+        .map_err(|_| CliError::Message(format!("Failed to parse principal `{}`", principal)))?
+    {
+        PrincipalData::Standard(addr) => Ok(PostConditionPrincipal::Standard(addr.into())),
+        PrincipalData::Contract(contract_id) => Ok(PostConditionPrincipal::Contract(
+            contract_id.issuer.into(),
+            contract_id.name,
+        )),
+    }
+}
+
+fn parse_spec_asset(asset: &str) -> Result<AssetInfo, CliError> {
+    let parts: Vec<_> = asset.split("::").collect();
+    if parts.len() != 2 {
+        return Err(CliError::Message(format!(
+            "Asset `{}` must be of the form <contract>::<asset-name>",
+            asset
+        )));
+    }
+    let contract_id = QualifiedContractIdentifier::parse(parts[0])?;
+    Ok(AssetInfo {
+        contract_address: contract_id.issuer.into(),
+        contract_name: contract_id.name,
+        asset_name: ClarityName::try_from(parts[1].to_string())?,
+    })
+}
+
+fn parse_spec_post_condition(spec: &JsonValue) -> Result<TransactionPostCondition, CliError> {
+    let principal = parse_spec_principal(spec_str(spec, "principal")?)?;
+    let condition = spec_str(spec, "condition")?;
+    let fungible_code = || match condition {
+        "sent-equal-to" => Ok(FungibleConditionCode::SentEq),
+        "sent-greater-than" => Ok(FungibleConditionCode::SentGt),
+        "sent-greater-than-or-equal-to" => Ok(FungibleConditionCode::SentGe),
+        "sent-less-than" => Ok(FungibleConditionCode::SentLt),
+        "sent-less-than-or-equal-to" => Ok(FungibleConditionCode::SentLe),
+        _ => Err(CliError::Message(format!(
+            "Unknown fungible post-condition `{}`",
+            condition
+        ))),
+    };
+
+    match spec_str(spec, "type")? {
+        "stx" => Ok(TransactionPostCondition::STX(
+            principal,
+            fungible_code()?,
+            spec_u64(spec, "amount")?,
+        )),
+        "ft" => Ok(TransactionPostCondition::Fungible(
+            principal,
+            parse_spec_asset(spec_str(spec, "asset")?)?,
+            fungible_code()?,
+            spec_u64(spec, "amount")?,
+        )),
+        "nft" => {
+            let code = match condition {
+                "sent" => NonfungibleConditionCode::Sent,
+                "not-sent" => NonfungibleConditionCode::NotSent,
+                _ => {
+                    return Err(CliError::Message(format!(
+                        "Unknown non-fungible post-condition `{}`",
+                        condition
+                    )))
+                }
+            };
+            let value = parse_spec_value(spec.get("value").ok_or("Missing field `value`")?)?;
+            Ok(TransactionPostCondition::Nonfungible(
+                principal,
+                parse_spec_asset(spec_str(spec, "asset")?)?,
+                value,
+                code,
+            ))
+        }
+        other => Err(CliError::Message(format!(
+            "Unknown post-condition type `{}`",
+            other
+        ))),
+    }
+}
+
+fn handle_make_transaction(
+    args: &[String],
+    version: TransactionVersion,
+    chain_id: u32,
+) -> Result<String, CliError> {
+    if (args.len() >= 1 && args[0] == "-h") || args.len() != 2 {
+        return Err(CliError::Message(format!(
+            "USAGE:\n {}",
+            MAKE_TRANSACTION_USAGE
+        )));
+    }
+
+    let sk_origin = StacksPrivateKey::from_hex(&args[0])?;
+    let spec_str_contents = if args[1] == "-" {
+        let mut buffer = String::new();
+        io::stdin().read_to_string(&mut buffer)?;
+        buffer
+    } else {
+        fs::read_to_string(&args[1])?
+    };
+    let spec: JsonValue = serde_json::from_str(&spec_str_contents)
+        .map_err(|e| CliError::Message(format!("Failed to parse transaction spec: {}", e)))?;
+
+    let payload = match spec_str(&spec, "type")? {
+        "token-transfer" => {
+            let recipient = PrincipalData::parse(spec_str(&spec, "recipient")?)
+                .map_err(|_e| "Failed to parse recipient")?;
+            let mut memo = [0u8; 34];
+            if let Some(memo_hex) = spec.get("memo").and_then(|v| v.as_str()) {
+                let memo_bytes = hex_bytes(memo_hex)
+                    .map_err(|_e| "Memo must be a hex string of at most 34 bytes")?;
+                if memo_bytes.len() > memo.len() {
+                    return Err("Memo must be at most 34 bytes".into());
+                }
+                memo[0..memo_bytes.len()].copy_from_slice(&memo_bytes);
+            }
+            TransactionPayload::TokenTransfer(
+                recipient,
+                spec_u64(&spec, "amount")?,
+                TokenTransferMemo(memo),
+            )
+        }
+        "contract-call" => {
+            let contract_id = QualifiedContractIdentifier::parse(spec_str(&spec, "contract")?)?;
+            let function_args = match spec.get("args") {
+                Some(JsonValue::Array(args)) => args
+                    .iter()
+                    .map(parse_spec_value)
+                    .collect::<Result<Vec<_>, _>>()?,
+                Some(_) => return Err("Field `args` must be a list".into()),
+                None => vec![],
+            };
+            TransactionPayload::ContractCall(TransactionContractCall {
+                address: contract_id.issuer.into(),
+                contract_name: contract_id.name,
+                function_name: ClarityName::try_from(spec_str(&spec, "function")?.to_string())?,
+                function_args,
+            })
+        }
+        other => {
+            return Err(CliError::Message(format!(
+                "Unsupported transaction type `{}`: expected token-transfer or contract-call",
+                other
+            )))
+        }
+    };
+
+    let mut unsigned_tx = make_standard_single_sig_tx(
+        version,
+        chain_id,
+        payload,
+        &StacksPublicKey::from_private(&sk_origin),
+        spec_u64(&spec, "nonce")?,
+        spec_u64(&spec, "fee")?,
+    );
+
+    unsigned_tx.anchor_mode = match spec.get("anchor_mode").and_then(|v| v.as_str()) {
+        None | Some("any") => TransactionAnchorMode::Any,
+        Some("on-chain-only") => TransactionAnchorMode::OnChainOnly,
+        Some("off-chain-only") => TransactionAnchorMode::OffChainOnly,
+        Some(other) => {
+            return Err(CliError::Message(format!(
+                "Unknown anchor mode `{}`",
+                other
+            )));
+        }
+    };
+    unsigned_tx.post_condition_mode = match spec.get("post_condition_mode").and_then(|v| v.as_str())
+    {
+        None | Some("deny") => TransactionPostConditionMode::Deny,
+        Some("allow") => TransactionPostConditionMode::Allow,
+        Some(other) => {
+            return Err(CliError::Message(format!(
+                "Unknown post-condition mode `{}`",
+                other
+            )));
+        }
+    };
+    unsigned_tx.post_conditions = match spec.get("post_conditions") {
+        Some(JsonValue::Array(pcs)) => pcs
+            .iter()
+            .map(parse_spec_post_condition)
+            .collect::<Result<Vec<_>, _>>()?,
+        Some(_) => return Err("Field `post_conditions` must be a list".into()),
+        None => vec![],
+    };
+
+    let mut unsigned_tx_bytes = vec![];
+    unsigned_tx
+        .consensus_serialize(&mut unsigned_tx_bytes)
+        .expect("FATAL: invalid transaction");
+    let signed_tx = sign_transaction_single_sig_standard(&to_hex(&unsigned_tx_bytes), &sk_origin)?;
+
+    let mut signed_tx_bytes = vec![];
+    signed_tx
+        .consensus_serialize(&mut signed_tx_bytes)
+        .expect("FATAL: invalid signed transaction");
+    Ok(to_hex(&signed_tx_bytes))
+}
+
 fn main() {
     let mut argv: Vec<String> = env::args().collect();
 
@@ -777,6 +1276,8 @@ fn main_handler(mut argv: Vec<String>) -> Result<String, CliError> {
             "decode-tx" => decode_transaction(args, tx_version),
             "decode-block" => decode_block(args, tx_version),
             "decode-microblock" => decode_microblock(args, tx_version),
+            "explain-tx" => explain_transaction(args, tx_version),
+            "make-tx" => handle_make_transaction(args, tx_version, chain_id),
             _ => Err(CliError::Usage),
         }
     } else {
@@ -1111,4 +1612,131 @@ mod test {
         let result = main_handler(to_string_vec(&block_args)).unwrap();
         eprintln!("result:\n{}", result);
     }
+
+    #[test]
+    fn simple_explain_tx() {
+        let tx_args = [
+            "explain-tx",
+            "8080000000040021a3c334fc0ee50359353799e8b2605ac6be1fe4000000000000000100000000000000000100c90ae0235365f3a73c595f8c6ab3c529807feb3cb269247329c9a24218d50d3f34c7eef5d28ba26831affa652a73ec32f098fec4bf1decd1ceb3fde4b8ce216b030200000000021a21a3c334fc0ee50359353799e8b2605ac6be1fe40573746f7265096765742d76616c7565000000010d00000003666f6f"
+        ];
+
+        let result = main_handler(to_string_vec(&tx_args)).unwrap();
+        let explained: JsonValue = serde_json::from_str(&result).unwrap();
+        assert_eq!(explained["version"], "testnet");
+        assert_eq!(explained["auth"]["type"], "standard");
+        assert_eq!(explained["auth"]["origin"]["nonce"], 1);
+        assert_eq!(explained["payload"]["type"], "contract-call");
+        assert_eq!(explained["payload"]["function"], "get-value");
+        assert_eq!(explained["payload"]["args"][0]["repr"], "\"foo\"");
+        assert_eq!(explained["payload"]["args"][0]["hex"], "0d00000003666f6f");
+
+        assert!(main_handler(to_string_vec(&["explain-tx", "-h"])).is_err());
+        assert!(main_handler(to_string_vec(&["explain-tx", "deadbeef"])).is_err());
+    }
+
+    #[test]
+    fn explain_memo_text() {
+        let mut memo = [0u8; 34];
+        memo[0..5].copy_from_slice(b"hello");
+        assert_eq!(
+            explain_memo(&TokenTransferMemo(memo)),
+            (to_hex(&memo), Some("hello".to_string()))
+        );
+        assert_eq!(
+            explain_memo(&TokenTransferMemo([0u8; 34])),
+            (to_hex(&[0u8; 34]), None)
+        );
+        assert_eq!(
+            explain_memo(&TokenTransferMemo([0x07; 34])),
+            (to_hex(&[0x07; 34]), None)
+        );
+    }
+
+    #[test]
+    fn make_tx_round_trip() {
+        let sk = "043ff5004e3d695060fa48ac94c96049b8c14ef441c50a184a6a3875d2a000f3";
+        let spec_path = "/tmp/blockstack-cli-make-tx-spec.json";
+
+        fs::write(
+            spec_path,
+            r#"{
+                "type": "token-transfer",
+                "fee": 180,
+                "nonce": 3,
+                "recipient": "ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM",
+                "amount": 1000,
+                "memo": "68656c6c6f",
+                "post_conditions": [
+                    {"type": "stx", "principal": "origin", "condition": "sent-equal-to", "amount": 1000}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let tx_hex = main_handler(to_string_vec(&["--testnet", "make-tx", sk, spec_path])).unwrap();
+        let result = main_handler(to_string_vec(&["explain-tx", &tx_hex])).unwrap();
+        let explained: JsonValue = serde_json::from_str(&result).unwrap();
+
+        assert_eq!(explained["verified"], true);
+        assert_eq!(explained["version"], "testnet");
+        assert_eq!(explained["post_condition_mode"], "deny");
+        assert_eq!(explained["auth"]["origin"]["nonce"], 3);
+        assert_eq!(explained["auth"]["origin"]["fee"], 180);
+        assert_eq!(explained["payload"]["type"], "token-transfer");
+        assert_eq!(
+            explained["payload"]["recipient"],
+            "ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM"
+        );
+        assert_eq!(explained["payload"]["amount"], 1000);
+        assert_eq!(
+            explained["payload"]["memo"],
+            "68656c6c6f0000000000000000000000000000000000000000000000000000000000"
+        );
+        assert_eq!(explained["payload"]["memo_text"], "hello");
+        assert_eq!(explained["post_conditions"][0]["type"], "stx");
+        assert_eq!(explained["post_conditions"][0]["principal"], "origin");
+        assert_eq!(
+            explained["post_conditions"][0]["condition"],
+            "sent-equal-to"
+        );
+
+        fs::write(
+            spec_path,
+            r#"{
+                "type": "contract-call",
+                "fee": 1,
+                "nonce": 0,
+                "anchor_mode": "on-chain-only",
+                "post_condition_mode": "allow",
+                "contract": "ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM.foo-contract",
+                "function": "transfer-fookens",
+                "args": [{"eval": "(+ 1 2)"}, {"hex": "0d00000003666f6f"}]
+            }"#,
+        )
+        .unwrap();
+
+        let tx_hex = main_handler(to_string_vec(&["--testnet", "make-tx", sk, spec_path])).unwrap();
+        let result = main_handler(to_string_vec(&["explain-tx", &tx_hex])).unwrap();
+        let explained: JsonValue = serde_json::from_str(&result).unwrap();
+
+        assert_eq!(explained["verified"], true);
+        assert_eq!(explained["anchor_mode"], "on-chain-only");
+        assert_eq!(explained["post_condition_mode"], "allow");
+        assert_eq!(
+            explained["payload"]["contract"],
+            "ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM.foo-contract"
+        );
+        assert_eq!(explained["payload"]["args"][0]["repr"], "3");
+        assert_eq!(explained["payload"]["args"][1]["repr"], "\"foo\"");
+
+        fs::write(spec_path, r#"{"type": "coinbase", "fee": 0, "nonce": 0}"#).unwrap();
+        assert!(main_handler(to_string_vec(&["make-tx", sk, spec_path])).is_err());
+
+        fs::write(
+            spec_path,
+            r#"{"type": "token-transfer", "fee": 0, "nonce": 0, "amount": 1}"#,
+        )
+        .unwrap();
+        assert!(main_handler(to_string_vec(&["make-tx", sk, spec_path])).is_err());
+    }
 }
@@ -46,6 +46,7 @@ use burnchains::BLOCKSTACK_MAGIC_MAINNET;
 use deps::bitcoin::blockdata::block::LoneBlockHeader;
 use deps::bitcoin::network::message::NetworkMessage;
 use deps::bitcoin::network::serialize::BitcoinHash;
+use deps::bitcoin::util::hash::Sha256dHash;
 
 use util::log;
 
@@ -87,6 +88,8 @@ pub struct BitcoinIndexerConfig {
     pub spv_headers_path: String,
     pub first_block: u64,
     pub magic_bytes: MagicBytes,
+    /// trusted (height, hash) pairs the downloaded headers must match
+    pub header_checkpoints: Vec<(u64, Sha256dHash)>,
 }
 
 #[derive(Debug)]
@@ -120,6 +123,7 @@ impl BitcoinIndexerConfig {
             spv_headers_path: "./headers.sqlite".to_string(),
             first_block,
             magic_bytes: BLOCKSTACK_MAGIC_MAINNET.clone(),
+            header_checkpoints: vec![],
         }
     }
 
@@ -135,6 +139,7 @@ impl BitcoinIndexerConfig {
             spv_headers_path: spv_headers_path,
             first_block: 0,
             magic_bytes: BLOCKSTACK_MAGIC_MAINNET.clone(),
+            header_checkpoints: vec![],
         }
     }
 
@@ -287,6 +292,7 @@ impl BitcoinIndexerConfig {
                     spv_headers_path: spv_headers_path,
                     first_block: first_block,
                     magic_bytes: blockstack_magic,
+                    header_checkpoints: vec![],
                 };
 
                 Ok(cfg)
@@ -522,7 +528,8 @@ impl BitcoinIndexer {
             self.runtime.network_id,
             true,
             false,
-        )?;
+        )?
+        .with_checkpoints(&self.config.header_checkpoints);
         spv_client
             .run(self)
             .and_then(|_r| Ok(spv_client.end_block_height.unwrap()))
@@ -550,7 +557,8 @@ impl BitcoinIndexer {
             self.runtime.network_id,
             true,
             true,
-        )?;
+        )?
+        .with_checkpoints(&self.config.header_checkpoints);
         if start_block > 0 {
             let start_header = canonical_spv_client
                 .read_block_header(start_block)?
@@ -1404,6 +1412,7 @@ mod test {
             spv_headers_path: "/tmp/test_indexer_sync_headers.sqlite".to_string(),
             first_block: 0,
             magic_bytes: MagicBytes([105, 100]),
+            header_checkpoints: vec![],
         };
 
         if fs::metadata(&indexer_conf.spv_headers_path).is_ok() {
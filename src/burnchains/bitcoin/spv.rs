@@ -15,7 +15,7 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::cmp;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::fs;
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::ops::Deref;
@@ -91,6 +91,8 @@ pub struct SpvClient {
     readwrite: bool,
     reverse_order: bool,
     headers_db: DBConn,
+    /// trusted block hashes, by height
+    checkpoints: BTreeMap<u64, Sha256dHash>,
 }
 
 impl FromSql for Sha256dHash {
@@ -153,6 +155,7 @@ impl SpvClient {
             readwrite: readwrite,
             reverse_order: reverse_order,
             headers_db: conn,
+            checkpoints: BTreeMap::new(),
         };
 
         if readwrite {
@@ -162,6 +165,16 @@ impl SpvClient {
         Ok(client)
     }
 
+    /// Require the headers at the given heights to have the given hashes.  Headers that conflict
+    /// with a checkpoint are rejected, so the client will not follow a fork off of a checkpointed
+    /// block however much (or little) work it has.  Once a checkpointed header is stored, the
+    /// difficulty retargets of the intervals below it are not checked, since those headers chain
+    /// up to the checkpoint; each header's hash must still meet its own target.
+    pub fn with_checkpoints(mut self, checkpoints: &[(u64, Sha256dHash)]) -> SpvClient {
+        self.checkpoints = checkpoints.iter().cloned().collect();
+        self
+    }
+
     pub fn conn(&self) -> &DBConn {
         &self.headers_db
     }
@@ -281,8 +294,49 @@ impl SpvClient {
         return Ok(());
     }
 
+    /// Verify that a run of headers, the first of which is at the given height, matches any
+    /// checkpoints in its range
+    fn validate_header_checkpoints(
+        &self,
+        height: u64,
+        headers: &Vec<LoneBlockHeader>,
+    ) -> Result<(), btc_error> {
+        for (checkpoint_height, checkpoint_hash) in self
+            .checkpoints
+            .range(height..(height + headers.len() as u64))
+        {
+            let header_hash = headers[(checkpoint_height - height) as usize]
+                .header
+                .bitcoin_hash();
+            if header_hash != *checkpoint_hash {
+                warn!(
+                    "Bad SPV header for block {}: hash {} != checkpoint {}",
+                    checkpoint_height, header_hash, checkpoint_hash
+                );
+                return Err(btc_error::CheckpointMismatch);
+            }
+        }
+        Ok(())
+    }
+
+    /// The height of the highest checkpoint whose header we have stored, if that header matches
+    /// the checkpoint.  A stored header that conflicts with its checkpoint vouches for nothing.
+    fn get_stored_checkpoint_height(&self) -> Result<Option<u64>, btc_error> {
+        for (height, hash) in self.checkpoints.iter().rev() {
+            if let Some(header) = self.read_block_header(*height)? {
+                if header.header.bitcoin_hash() == *hash {
+                    return Ok(Some(*height));
+                }
+                return Ok(None);
+            }
+        }
+        Ok(None)
+    }
+
     /// Verify that the given headers have the correct amount of work to be appended to our
-    /// local header chain.  Checks the difficulty between [interval, interval+1]
+    /// local header chain.  Checks the difficulty between [interval, interval+1].  Every header's
+    /// hash must meet the target in its `bits`; intervals that end at or below a stored,
+    /// matching checkpoint are not checked for the right retargeted difficulty.
     fn validate_header_work(
         &self,
         interval_start: u64,
@@ -298,7 +352,16 @@ impl SpvClient {
             return Ok(());
         }
 
+        let checkpointed_height = self.get_stored_checkpoint_height()?;
         for i in interval_start..interval_end {
+            // vouched for by a checkpoint?
+            let checkpointed = match checkpointed_height {
+                Some(checkpointed_height) => {
+                    (i + 1) * BLOCK_DIFFICULTY_CHUNK_SIZE - 1 <= checkpointed_height
+                }
+                None => false,
+            };
+
             let mut headers = VecDeque::new();
             for block_height in
                 (i * BLOCK_DIFFICULTY_CHUNK_SIZE)..((i + 1) * BLOCK_DIFFICULTY_CHUNK_SIZE)
@@ -308,6 +371,21 @@ impl SpvClient {
                     Some(res) => res.header,
                 };
 
+                let header_hash = header_i.bitcoin_hash().into_le();
+                if header_i.target() < header_hash {
+                    error!(
+                        "block {} hash {} does not meet its own target {:08x} in {}",
+                        block_height,
+                        header_i.bitcoin_hash(),
+                        header_i.bits,
+                        self.headers_path
+                    );
+                    return Err(btc_error::InvalidPoW);
+                }
+                if checkpointed {
+                    continue;
+                }
+
                 let (bits, difficulty) =
                     match self.get_target(block_height, &header_i, &headers, i)? {
                         Some(x) => x,
@@ -322,7 +400,6 @@ impl SpvClient {
                             block_height, self.headers_path, block_height % BLOCK_DIFFICULTY_CHUNK_SIZE, i, interval_start, interval_end, header_i.bits, bits);
                     return Err(btc_error::InvalidPoW);
                 }
-                let header_hash = header_i.bitcoin_hash().into_le();
                 if difficulty <= header_hash {
                     error!(
                         "block {} hash {} has less work than difficulty {} in {}",
@@ -607,6 +684,7 @@ impl SpvClient {
             error!("Received invalid headers: {:?}", &e);
             e
         })?;
+        self.validate_header_checkpoints(start_height + 1, &block_headers)?;
 
         let parent_header = match self.read_block_header(start_height)? {
             Some(header) => header,
@@ -660,6 +738,7 @@ impl SpvClient {
             error!("Received invalid headers: {:?}", &e);
             e
         })?;
+        self.validate_header_checkpoints(start_height + 1, &block_headers)?;
 
         match self.read_block_header(end_height)? {
             Some(child_header) => {
@@ -1296,6 +1375,88 @@ mod test {
             .unwrap();
     }
 
+    #[test]
+    fn test_spv_store_headers_checkpoints() {
+        let path = "/tmp/test-spv-store_headers_checkpoints.dat";
+        if fs::metadata(path).is_ok() {
+            fs::remove_file(path).unwrap();
+        }
+        let headers = vec![
+            LoneBlockHeader {
+                header: BlockHeader {
+                    bits: 545259519,
+                    merkle_root: Sha256dHash::from_hex(
+                        "20bee96458517fc5082a9720ce6207b5742f2b18e4e0a7e7373342725d80f88c",
+                    )
+                    .unwrap(),
+                    nonce: 2,
+                    prev_blockhash: Sha256dHash::from_hex(
+                        "0f9188f13cb7b2c71f2a335e3a4fc328bf5beb436012afca590b1a11466e2206",
+                    )
+                    .unwrap(),
+                    time: 1587626881,
+                    version: 0x20000000,
+                },
+                tx_count: VarInt(0),
+            },
+            LoneBlockHeader {
+                header: BlockHeader {
+                    bits: 545259519,
+                    merkle_root: Sha256dHash::from_hex(
+                        "39d1a6f1ee7a5903797f92ec89e4c58549013f38114186fc2eb6e5218cb2d0ac",
+                    )
+                    .unwrap(),
+                    nonce: 1,
+                    prev_blockhash: Sha256dHash::from_hex(
+                        "606d31daaaa5919f3720d8440dd99d31f2a4e4189c65879f19ae43268425e74b",
+                    )
+                    .unwrap(),
+                    time: 1587626882,
+                    version: 0x20000000,
+                },
+                tx_count: VarInt(0),
+            },
+        ];
+        let block_2_hash = headers[1].header.bitcoin_hash();
+        let other_hash = headers[0].header.bitcoin_hash();
+
+        // a checkpoint on a different block turns the headers away, in either order
+        let mut spv_client =
+            SpvClient::new(path, 0, None, BitcoinNetworkType::Regtest, true, false)
+                .unwrap()
+                .with_checkpoints(&[(2, other_hash)]);
+        if let Err(btc_error::CheckpointMismatch) =
+            spv_client.insert_block_headers_after(0, headers.clone())
+        {
+        } else {
+            assert!(false);
+        }
+        if let Err(btc_error::CheckpointMismatch) =
+            spv_client.insert_block_headers_before(0, headers.clone())
+        {
+        } else {
+            assert!(false);
+        }
+        assert_eq!(spv_client.get_headers_height().unwrap(), 1);
+
+        // checkpoints outside of the headers' range don't matter
+        let mut spv_client =
+            SpvClient::new(path, 0, None, BitcoinNetworkType::Regtest, true, false)
+                .unwrap()
+                .with_checkpoints(&[(2, block_2_hash), (3, other_hash)]);
+        spv_client
+            .insert_block_headers_after(0, headers.clone())
+            .unwrap();
+        assert_eq!(spv_client.read_block_headers(1, 10).unwrap(), headers);
+
+        // only a stored header that matches its checkpoint vouches for the headers below it
+        assert_eq!(spv_client.get_stored_checkpoint_height().unwrap(), Some(2));
+        let spv_client = SpvClient::new(path, 0, None, BitcoinNetworkType::Regtest, false, false)
+            .unwrap()
+            .with_checkpoints(&[(1, block_2_hash), (3, other_hash)]);
+        assert_eq!(spv_client.get_stored_checkpoint_height().unwrap(), None);
+    }
+
     #[test]
     fn test_spv_check_pow() {
         if !env::var("BLOCKSTACK_SPV_HEADERS_DB").is_ok() {
@@ -497,9 +497,19 @@ impl Burnchain {
     }
 
     pub fn reward_cycle_to_block_height(&self, reward_cycle: u64) -> u64 {
+        self.checked_reward_cycle_to_block_height(reward_cycle)
+            .expect("Overflowed u64 in calculating reward cycle start height")
+    }
+
+    /// The height of the first block of the reward cycle, or None if it doesn't fit in a u64
+    /// (e.g. because the reward cycle was given by a client)
+    pub fn checked_reward_cycle_to_block_height(&self, reward_cycle: u64) -> Option<u64> {
         // NOTE: the `+ 1` is because the height of the first block of a reward cycle is mod 1, not
         // mod 0.
-        self.first_block_height + reward_cycle * (self.pox_constants.reward_cycle_length as u64) + 1
+        reward_cycle
+            .checked_mul(self.pox_constants.reward_cycle_length as u64)?
+            .checked_add(self.first_block_height)?
+            .checked_add(1)
     }
 
     pub fn block_height_to_reward_cycle(&self, block_height: u64) -> Option<u64> {
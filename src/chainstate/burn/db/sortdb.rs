@@ -581,6 +581,21 @@ const SORTITION_DB_INITIAL_SCHEMA: &'static [&'static str] = &[
     "CREATE TABLE db_config(version TEXT NOT NULL);",
 ];
 
+// operator-installed PoX anchor overrides, and the PoX anchor blocks found to be missing at each
+// reward cycle start.  Created on every open as well, so sortition DBs that predate these tables
+// pick them up without a migration.
+const SORTITION_DB_POX_ANCHOR_SCHEMA: &'static str = r#"
+    -- reward cycles whose PoX anchor block the operator has decided to treat as not selected.
+    CREATE TABLE IF NOT EXISTS pox_anchor_overrides(
+        reward_cycle INTEGER PRIMARY KEY NOT NULL
+    );
+    -- the (reward cycle, anchor block hash) pairs that get_missing_pox_anchors() returned for
+    -- a reward cycle's first sortition, as a JSON list.
+    CREATE TABLE IF NOT EXISTS missing_pox_anchors(
+        sortition_id TEXT PRIMARY KEY NOT NULL,
+        missing_anchors TEXT NOT NULL
+    );"#;
+
 pub struct SortitionDB {
     pub readwrite: bool,
     pub marf: MARF<SortitionId>,
@@ -2004,9 +2019,33 @@ impl SortitionDB {
             first_block_height: first_snapshot.block_height,
             first_burn_header_hash: first_snapshot.burn_header_hash.clone(),
         };
+        db.instantiate_pox_anchor_tables()?;
         Ok(db)
     }
 
+    /// Open an existing database with read-only connections, e.g. to serve queries from it while
+    /// another process writes to it.  Unlike `open()`, nothing is created.
+    pub fn open_readonly(path: &str) -> Result<SortitionDB, db_error> {
+        let (db_path, index_path) = db_mkdirs(path)?;
+        debug!(
+            "Open sortdb '{}' with read-only connections, with index as '{}'",
+            db_path, index_path
+        );
+
+        let marf = MARF::from_path_readonly(&index_path).map_err(|e| match e {
+            MARFError::NotFoundError => db_error::NoDBError,
+            e => db_error::IndexError(e),
+        })?;
+        let first_snapshot = SortitionDB::get_first_block_snapshot(marf.sqlite_conn())?;
+
+        Ok(SortitionDB {
+            marf,
+            readwrite: false,
+            first_block_height: first_snapshot.block_height,
+            first_burn_header_hash: first_snapshot.burn_header_hash.clone(),
+        })
+    }
+
     /// Open the burn database at the given path.  Open read-only or read/write.
     /// If opened for read/write and it doesn't exist, instantiate it.
     pub fn connect(
@@ -2068,6 +2107,7 @@ impl SortitionDB {
                        snapshot.is_initial(), snapshot.block_height, &snapshot.burn_header_hash, first_block_height, first_burn_hash);
                 return Err(db_error::Corruption);
             }
+            db.instantiate_pox_anchor_tables()?;
         }
 
         Ok(db)
@@ -2122,6 +2162,7 @@ impl SortitionDB {
         for row_text in SORTITION_DB_INITIAL_SCHEMA {
             db_tx.execute_batch(row_text)?;
         }
+        db_tx.execute_batch(SORTITION_DB_POX_ANCHOR_SCHEMA)?;
 
         db_tx.execute(
             "INSERT INTO db_config (version) VALUES (?1)",
@@ -2398,6 +2439,167 @@ impl SortitionDB {
         handle.get_pox_id().map_err(BurnchainError::from)
     }
 
+    /// Find the reward cycles in the sortition history of `sortition_tip` whose PoX anchor block
+    /// was selected, but which this node does not have.  Returns (reward cycle, anchor block
+    /// hash) pairs, in reward cycle order.  If this list is not empty, this node will not be able
+    /// to agree with the rest of the network on the Stacks chain tip until it obtains each
+    /// listed block.
+    pub fn get_missing_pox_anchors(
+        &self,
+        burnchain: &Burnchain,
+        sortition_tip: &SortitionId,
+    ) -> Result<Vec<(u64, BlockHeaderHash)>, db_error> {
+        let handle = self.index_handle(sortition_tip);
+        let pox_id = handle.get_pox_id()?;
+        let mut missing = vec![];
+
+        // bit 0 is the initial bit; bit i > 0 is for the reward cycle i - 1
+        for i in 1..pox_id.len() {
+            if pox_id.has_ith_anchor_block(i) {
+                continue;
+            }
+            let reward_cycle = (i - 1) as u64;
+            let start_height = burnchain.reward_cycle_to_block_height(reward_cycle);
+            let reward_start_sn = match handle.get_block_snapshot_by_height(start_height)? {
+                Some(sn) => sn,
+                None => {
+                    warn!(
+                        "No sortition at the start of reward cycle {} (height {})",
+                        reward_cycle, start_height
+                    );
+                    continue;
+                }
+            };
+            let anchor_block_hash = SortitionDB::parse_last_anchor_block_hash(
+                self.index_handle(&reward_start_sn.sortition_id)
+                    .get_tip_indexed(&db_keys::pox_last_anchor())?,
+            );
+            if let Some(anchor_block_hash) = anchor_block_hash {
+                missing.push((reward_cycle, anchor_block_hash));
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Cache the result of `get_missing_pox_anchors()` for the first sortition of a reward
+    /// cycle.  The set of missing anchor blocks can only change at a reward cycle start, so this
+    /// holds for every later sortition in that reward cycle.
+    pub fn set_missing_pox_anchors(
+        &mut self,
+        reward_start_sortition: &SortitionId,
+        missing: &[(u64, BlockHeaderHash)],
+    ) -> Result<(), db_error> {
+        let missing_json = serde_json::to_string(missing).map_err(db_error::SerializationError)?;
+        self.conn().execute(
+            "INSERT OR REPLACE INTO missing_pox_anchors (sortition_id, missing_anchors) VALUES (?1, ?2)",
+            &[reward_start_sortition as &dyn ToSql, &missing_json],
+        )?;
+        Ok(())
+    }
+
+    /// Look up the missing PoX anchor blocks that were cached when the first sortition of
+    /// `sortition_tip`'s reward cycle was processed.  Returns None if they were not cached, e.g.
+    /// because that sortition was processed by an older version of this node.
+    pub fn get_cached_missing_pox_anchors(
+        &self,
+        burnchain: &Burnchain,
+        sortition_tip: &SortitionId,
+    ) -> Result<Option<Vec<(u64, BlockHeaderHash)>>, db_error> {
+        let handle = self.index_handle(sortition_tip);
+        let tip_height = match SortitionDB::get_block_snapshot(self.conn(), sortition_tip)? {
+            Some(sn) => sn.block_height,
+            None => return Ok(None),
+        };
+        // the first block of a reward cycle is the one whose effective height is 1 mod the
+        // reward cycle length
+        let effective_height = tip_height.saturating_sub(burnchain.first_block_height);
+        if effective_height == 0 {
+            return Ok(None);
+        }
+        let reward_cycle =
+            (effective_height - 1) / (burnchain.pox_constants.reward_cycle_length as u64);
+        let start_height = burnchain.reward_cycle_to_block_height(reward_cycle);
+        let reward_start_sn = match handle.get_block_snapshot_by_height(start_height)? {
+            Some(sn) => sn,
+            None => return Ok(None),
+        };
+        let missing_json: Option<String> = self
+            .conn()
+            .query_row(
+                "SELECT missing_anchors FROM missing_pox_anchors WHERE sortition_id = ?1",
+                &[&reward_start_sn.sortition_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match missing_json {
+            Some(json) => serde_json::from_str(&json)
+                .map(Some)
+                .map_err(db_error::SerializationError),
+            None => Ok(None),
+        }
+    }
+
+    fn instantiate_pox_anchor_tables(&self) -> Result<(), db_error> {
+        self.conn()
+            .execute_batch(SORTITION_DB_POX_ANCHOR_SCHEMA)
+            .map_err(db_error::SqliteError)
+    }
+
+    /// Instruct the chains coordinator to treat `reward_cycle` as if it had no PoX anchor block,
+    /// regardless of what the prepare phase selected.  Only takes effect the next time the
+    /// reward cycle's first sortition is evaluated -- see `invalidate_reward_cycle()`.
+    pub fn set_pox_anchor_override(&mut self, reward_cycle: u64) -> Result<(), db_error> {
+        self.conn().execute(
+            "INSERT OR REPLACE INTO pox_anchor_overrides (reward_cycle) VALUES (?1)",
+            &[&u64_to_sql(reward_cycle)?],
+        )?;
+        Ok(())
+    }
+
+    /// Remove an operator-installed PoX anchor override.  Returns true if there was one.
+    pub fn clear_pox_anchor_override(&mut self, reward_cycle: u64) -> Result<bool, db_error> {
+        let num_rows = self.conn().execute(
+            "DELETE FROM pox_anchor_overrides WHERE reward_cycle = ?1",
+            &[&u64_to_sql(reward_cycle)?],
+        )?;
+        Ok(num_rows > 0)
+    }
+
+    /// Has the operator overridden the PoX anchor block of this reward cycle?
+    pub fn has_pox_anchor_override(conn: &Connection, reward_cycle: u64) -> Result<bool, db_error> {
+        let count = query_count(
+            conn,
+            "SELECT COUNT(*) FROM pox_anchor_overrides WHERE reward_cycle = ?1",
+            &[&u64_to_sql(reward_cycle)?],
+        )?;
+        Ok(count > 0)
+    }
+
+    /// List all reward cycles whose PoX anchor block the operator has overridden
+    pub fn get_pox_anchor_overrides(conn: &Connection) -> Result<Vec<u64>, db_error> {
+        query_rows(
+            conn,
+            "SELECT reward_cycle FROM pox_anchor_overrides ORDER BY reward_cycle",
+            NO_PARAMS,
+        )
+    }
+
+    /// Mark every sortition from the start of `reward_cycle` onwards as invalid on every burnchain
+    /// fork, so the chains coordinator re-evaluates them (and re-decides the reward cycle's PoX
+    /// anchor block) the next time it runs.  Returns the number of sortitions invalidated.
+    pub fn invalidate_reward_cycle(
+        &mut self,
+        burnchain: &Burnchain,
+        reward_cycle: u64,
+    ) -> Result<usize, db_error> {
+        let start_height = burnchain.reward_cycle_to_block_height(reward_cycle);
+        let num_rows = self.conn().execute(
+            "UPDATE snapshots SET pox_valid = 0 WHERE block_height >= ?1",
+            &[&u64_to_sql(start_height)?],
+        )?;
+        Ok(num_rows)
+    }
+
     pub fn get_sortition_result(
         &self,
         id: &SortitionId,
@@ -3953,6 +4155,36 @@ pub mod tests {
         let _db = SortitionDB::connect_test(123, &first_burn_hash).unwrap();
     }
 
+    #[test]
+    fn test_pox_anchor_overrides() {
+        let first_burn_hash = BurnchainHeaderHash::from_hex(
+            "0000000000000000000000000000000000000000000000000000000000000000",
+        )
+        .unwrap();
+        let mut db = SortitionDB::connect_test(123, &first_burn_hash).unwrap();
+        assert!(SortitionDB::get_pox_anchor_overrides(db.conn())
+            .unwrap()
+            .is_empty());
+        assert!(!SortitionDB::has_pox_anchor_override(db.conn(), 3).unwrap());
+
+        db.set_pox_anchor_override(3).unwrap();
+        db.set_pox_anchor_override(1).unwrap();
+        db.set_pox_anchor_override(3).unwrap();
+        assert!(SortitionDB::has_pox_anchor_override(db.conn(), 3).unwrap());
+        assert!(!SortitionDB::has_pox_anchor_override(db.conn(), 2).unwrap());
+        assert_eq!(
+            SortitionDB::get_pox_anchor_overrides(db.conn()).unwrap(),
+            vec![1, 3]
+        );
+
+        assert!(db.clear_pox_anchor_override(3).unwrap());
+        assert!(!db.clear_pox_anchor_override(3).unwrap());
+        assert_eq!(
+            SortitionDB::get_pox_anchor_overrides(db.conn()).unwrap(),
+            vec![1]
+        );
+    }
+
     #[test]
     fn test_tx_begin_end() {
         let first_burn_hash = BurnchainHeaderHash::from_hex(
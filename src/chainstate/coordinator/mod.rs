@@ -14,6 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::cmp;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::convert::{TryFrom, TryInto};
 use std::sync::mpsc::SyncSender;
@@ -41,6 +42,8 @@ use chainstate::stacks::{
 };
 use monitoring::{
     increment_contract_calls_processed, increment_stx_blocks_processed_counter,
+    update_missing_pox_anchors, update_pox_anchor_block_known, update_pox_reward_cycle,
+    update_pox_reward_slots_filled, update_pox_stacked_ustx, update_pox_stackers,
     update_stacks_tip_height,
 };
 use net::atlas::{AtlasConfig, AttachmentInstance};
@@ -136,6 +139,19 @@ pub trait BlockEventDispatcher {
     );
 
     fn dispatch_boot_receipts(&mut self, receipts: Vec<StacksTransactionReceipt>);
+
+    /// called once the coordinator has handled a new Stacks block or burn block notice, if the
+    ///  canonical Stacks chain tip moved.  unlike `announce_block`, this is not called for
+    ///  blocks off the canonical fork, or for each block of a batch.  `previous` is the tip it
+    ///  moved from (its index block hash and height), and `reorg` is true if the new tip does
+    ///  not descend from it.
+    fn announce_canonical_stacks_tip(
+        &self,
+        _tip: &StacksHeaderInfo,
+        _previous: Option<(&StacksBlockId, u64)>,
+        _reorg: bool,
+    ) {
+    }
 }
 
 pub struct ChainsCoordinator<
@@ -221,6 +237,9 @@ impl RewardSetProvider for OnChainRewardSetProvider {
             liquid_ustx,
         );
 
+        update_pox_stacked_ustx(cmp::min(participation, i64::max_value() as u128) as i64);
+        update_pox_stackers(registered_addrs.len() as i64);
+
         if !burnchain
             .pox_constants
             .enough_participation(participation, liquid_ustx)
@@ -290,6 +309,11 @@ impl<'a, T: BlockEventDispatcher>
             atlas_config,
         };
 
+        let mut canonical_stacks_tip = inst
+            .get_canonical_stacks_tip()
+            .unwrap_or(None)
+            .map(|header| (header.index_block_hash(), header.block_height));
+
         loop {
             // timeout so that we handle Ctrl-C a little gracefully
             match comms.wait_on() {
@@ -298,12 +322,14 @@ impl<'a, T: BlockEventDispatcher>
                     if let Err(e) = inst.handle_new_stacks_block() {
                         warn!("Error processing new stacks block: {:?}", e);
                     }
+                    inst.announce_canonical_stacks_tip(&mut canonical_stacks_tip);
                 }
                 CoordinatorEvents::NEW_BURN_BLOCK => {
                     debug!("Received new burn block notice");
                     if let Err(e) = inst.handle_new_burnchain_block() {
                         warn!("Error processing new burn block: {:?}", e);
                     }
+                    inst.announce_canonical_stacks_tip(&mut canonical_stacks_tip);
                 }
                 CoordinatorEvents::STOP => {
                     debug!("Received stop notice");
@@ -357,6 +383,12 @@ impl<'a, T: BlockEventDispatcher, U: RewardSetProvider> ChainsCoordinator<'a, T,
             atlas_config: AtlasConfig::default(false),
         }
     }
+
+    /// The chainstate new blocks are processed into
+    #[cfg(test)]
+    pub fn chainstate(&mut self) -> &mut StacksChainState {
+        &mut self.chain_state_db
+    }
 }
 
 pub fn get_next_recipients<U: RewardSetProvider>(
@@ -406,18 +438,31 @@ pub fn get_reward_cycle_info<U: RewardSetProvider>(
               "reward_cycle_length" => burnchain.pox_constants.reward_cycle_length,
               "prepare_phase_length" => burnchain.pox_constants.prepare_length);
 
+        let reward_cycle = burnchain
+            .block_height_to_reward_cycle(burn_height)
+            .expect("FATAL: reward cycle start is before the first burnchain block");
         let reward_cycle_info = {
             let ic = sort_db.index_handle(sortition_tip);
             ic.get_chosen_pox_anchor(&parent_bhh, &burnchain.pox_constants)
         }?;
-        if let Some((consensus_hash, stacks_block_hash)) = reward_cycle_info {
-            info!("Anchor block selected: {}", stacks_block_hash);
-            let anchor_block_known = StacksChainState::is_stacks_block_processed(
+        let anchor_status = if let Some((consensus_hash, stacks_block_hash)) = reward_cycle_info {
+            info!("Anchor block selected";
+                  "anchor_block_hash" => %stacks_block_hash,
+                  "reward_cycle" => reward_cycle,
+                  "consensus_hash" => %consensus_hash);
+
+            if SortitionDB::has_pox_anchor_override(sort_db.conn(), reward_cycle)? {
+                warn!("PoX anchor block overridden by the node operator; treating the reward cycle as if no anchor block was selected";
+                      "reward_cycle" => reward_cycle,
+                      "burn_height" => burn_height,
+                      "anchor_block_hash" => %stacks_block_hash,
+                      "consensus_hash" => %consensus_hash);
+                PoxAnchorBlockStatus::NotSelected
+            } else if StacksChainState::is_stacks_block_processed(
                 &chain_state.db(),
                 &consensus_hash,
                 &stacks_block_hash,
-            )?;
-            let anchor_status = if anchor_block_known {
+            )? {
                 let block_id =
                     StacksBlockHeader::make_index_block_hash(&consensus_hash, &stacks_block_hash);
                 let reward_set = provider.get_reward_set(
@@ -429,19 +474,283 @@ pub fn get_reward_cycle_info<U: RewardSetProvider>(
                 )?;
                 PoxAnchorBlockStatus::SelectedAndKnown(stacks_block_hash, reward_set)
             } else {
+                warn!("PoX anchor block selected, but not known to this node. Stacks blocks that build on it will not be processed until it arrives";
+                      "reward_cycle" => reward_cycle,
+                      "burn_height" => burn_height,
+                      "anchor_block_hash" => %stacks_block_hash,
+                      "consensus_hash" => %consensus_hash);
                 PoxAnchorBlockStatus::SelectedAndUnknown(stacks_block_hash)
-            };
-            Ok(Some(RewardCycleInfo { anchor_status }))
+            }
         } else {
-            Ok(Some(RewardCycleInfo {
-                anchor_status: PoxAnchorBlockStatus::NotSelected,
-            }))
-        }
+            PoxAnchorBlockStatus::NotSelected
+        };
+
+        report_reward_cycle_metrics(reward_cycle, &anchor_status);
+        Ok(Some(RewardCycleInfo { anchor_status }))
     } else {
         Ok(None)
     }
 }
 
+/// Update the PoX metrics for the reward cycle that is just starting.  The stacking totals are
+/// reported by the reward set provider when it computes the reward set; if it didn't (because
+/// there's no known anchor block), they are zeroed here.
+fn report_reward_cycle_metrics(reward_cycle: u64, anchor_status: &PoxAnchorBlockStatus) {
+    update_pox_reward_cycle(reward_cycle as i64);
+    match anchor_status {
+        PoxAnchorBlockStatus::SelectedAndKnown(_, ref reward_set) => {
+            update_pox_anchor_block_known(true);
+            update_pox_reward_slots_filled(reward_set.len() as i64);
+        }
+        PoxAnchorBlockStatus::SelectedAndUnknown(_) => {
+            update_pox_anchor_block_known(false);
+            update_pox_reward_slots_filled(0);
+            update_pox_stacked_ustx(0);
+            update_pox_stackers(0);
+        }
+        PoxAnchorBlockStatus::NotSelected => {
+            update_pox_anchor_block_known(true);
+            update_pox_reward_slots_filled(0);
+            update_pox_stacked_ustx(0);
+            update_pox_stackers(0);
+        }
+    }
+}
+
+/// The reward set of a reward cycle, along with the stacking data it was computed from
+#[derive(Debug, Clone, PartialEq)]
+pub struct RewardCycleRewardSet {
+    pub reward_cycle: u64,
+    pub anchor_consensus_hash: ConsensusHash,
+    pub anchor_block_hash: BlockHeaderHash,
+    /// minimum uSTX per reward slot
+    pub threshold: u128,
+    /// total uSTX stacked in this reward cycle
+    pub participation: u128,
+    /// each PoX address that registered for this reward cycle, and how many uSTX it stacked.
+    pub registered_addrs: Vec<(StacksAddress, u128)>,
+    /// the reward set itself -- each PoX address appears once per reward slot it claimed.  Empty
+    /// if there was not enough participation for PoX to be active.
+    pub reward_set: Vec<StacksAddress>,
+}
+
+/// Recompute the reward set of `reward_cycle` on the sortition fork `sortition_tip`, the same way
+/// `get_reward_cycle_info()` does when the reward cycle begins.  Works for any past reward
+/// cycle, and for the upcoming reward cycle once its prepare phase has ended.
+/// Returns Ok(None) if no anchor block was chosen for this reward cycle (e.g. because its
+/// prepare phase hasn't ended yet, or because PoX has sunset), or if the anchor block has not
+/// been processed.
+pub fn get_reward_set_for_cycle(
+    reward_cycle: u64,
+    sortition_tip: &SortitionId,
+    burnchain: &Burnchain,
+    chain_state: &mut StacksChainState,
+    sort_db: &SortitionDB,
+) -> Result<Option<RewardCycleRewardSet>, Error> {
+    let reward_start_height = match burnchain.checked_reward_cycle_to_block_height(reward_cycle) {
+        Some(height) => height,
+        None => {
+            return Ok(None);
+        }
+    };
+    if reward_start_height >= burnchain.pox_constants.sunset_end {
+        return Ok(None);
+    }
+
+    let (consensus_hash, stacks_block_hash) = {
+        let ic = sort_db.index_handle(sortition_tip);
+        // the anchor block is chosen at the end of the prepare phase, which is the block right
+        // before the reward cycle starts
+        let prepare_end = match SortitionDB::get_ancestor_snapshot(
+            &ic,
+            reward_start_height - 1,
+            sortition_tip,
+        )? {
+            Some(sn) => sn,
+            None => {
+                return Ok(None);
+            }
+        };
+        match ic.get_chosen_pox_anchor(&prepare_end.burn_header_hash, &burnchain.pox_constants)? {
+            Some(anchor) => anchor,
+            None => {
+                return Ok(None);
+            }
+        }
+    };
+
+    if !StacksChainState::is_stacks_block_processed(
+        &chain_state.db(),
+        &consensus_hash,
+        &stacks_block_hash,
+    )? {
+        return Ok(None);
+    }
+
+    let block_id = StacksBlockHeader::make_index_block_hash(&consensus_hash, &stacks_block_hash);
+    let registered_addrs =
+        chain_state.get_reward_addresses(burnchain, sort_db, reward_start_height, &block_id)?;
+    let liquid_ustx = chain_state.get_liquid_ustx(&block_id);
+    let (threshold, participation) = StacksChainState::get_reward_threshold_and_participation(
+        &burnchain.pox_constants,
+        &registered_addrs,
+        liquid_ustx,
+    );
+
+    let reward_set = if burnchain
+        .pox_constants
+        .enough_participation(participation, liquid_ustx)
+    {
+        StacksChainState::make_reward_set(threshold, registered_addrs.clone())
+    } else {
+        vec![]
+    };
+
+    Ok(Some(RewardCycleRewardSet {
+        reward_cycle,
+        anchor_consensus_hash: consensus_hash,
+        anchor_block_hash: stacks_block_hash,
+        threshold,
+        participation,
+        registered_addrs,
+        reward_set,
+    }))
+}
+
+/// How a PoX address was paid during a reward cycle's reward phase, compared to what the reward
+/// set entitled it to
+#[derive(Debug, Clone, PartialEq)]
+pub struct RewardCyclePayout {
+    pub address: StacksAddress,
+    /// number of reward slots the reward set allotted to this address
+    pub expected_slots: u64,
+    /// number of reward slots in which the winning block commits paid this address
+    pub paid_slots: u64,
+    /// total satoshis sent to this address by all accepted block commits
+    pub paid_sats: u64,
+}
+
+/// The expected and the observed PoX payouts of a reward cycle
+#[derive(Debug, Clone, PartialEq)]
+pub struct RewardCyclePayouts {
+    pub reward_set: RewardCycleRewardSet,
+    /// one entry per address in the reward set, in reward set order, followed by any address
+    /// that was paid despite not being in the reward set
+    pub payouts: Vec<RewardCyclePayout>,
+    /// reward phase burn block heights with no accepted block commits.  The reward slots of
+    /// these blocks were not paid to anyone.
+    pub blocks_without_commits: Vec<u64>,
+    /// false if this node hasn't processed the whole reward phase yet
+    pub complete: bool,
+}
+
+/// Cross-reference the reward set of `reward_cycle` against the block commits that were accepted
+/// during its reward phase, on the sortition fork `sortition_tip`.  Returns Ok(None) if there is
+/// no reward set for this reward cycle (see `get_reward_set_for_cycle()`).
+pub fn get_reward_cycle_payouts(
+    reward_cycle: u64,
+    sortition_tip: &SortitionId,
+    burnchain: &Burnchain,
+    chain_state: &mut StacksChainState,
+    sort_db: &SortitionDB,
+) -> Result<Option<RewardCyclePayouts>, Error> {
+    let reward_set = match get_reward_set_for_cycle(
+        reward_cycle,
+        sortition_tip,
+        burnchain,
+        chain_state,
+        sort_db,
+    )? {
+        Some(reward_set) => reward_set,
+        None => {
+            return Ok(None);
+        }
+    };
+
+    fn payout_for<'a>(
+        payouts: &'a mut Vec<RewardCyclePayout>,
+        payout_index: &mut HashMap<StacksAddress, usize>,
+        addr: &StacksAddress,
+    ) -> &'a mut RewardCyclePayout {
+        let ix = match payout_index.get(addr) {
+            Some(ix) => *ix,
+            None => {
+                payouts.push(RewardCyclePayout {
+                    address: addr.clone(),
+                    expected_slots: 0,
+                    paid_slots: 0,
+                    paid_sats: 0,
+                });
+                payout_index.insert(addr.clone(), payouts.len() - 1);
+                payouts.len() - 1
+            }
+        };
+        &mut payouts[ix]
+    }
+
+    let mut payouts = vec![];
+    let mut payout_index = HashMap::new();
+    for addr in reward_set.reward_set.iter() {
+        payout_for(&mut payouts, &mut payout_index, addr).expected_slots += 1;
+    }
+
+    let reward_start_height = burnchain.reward_cycle_to_block_height(reward_cycle);
+    let reward_phase_length = (burnchain.pox_constants.reward_cycle_length
+        - burnchain.pox_constants.prepare_length) as u64;
+
+    let tip_height = SortitionDB::get_block_snapshot(sort_db.conn(), sortition_tip)?
+        .ok_or(Error::DBError(DBError::NotFoundError))?
+        .block_height;
+
+    let mut blocks_without_commits = vec![];
+    let mut complete = true;
+    let ic = sort_db.index_handle(sortition_tip);
+    for block_height in reward_start_height..(reward_start_height + reward_phase_length) {
+        if block_height > tip_height {
+            complete = false;
+            break;
+        }
+        let snapshot = match SortitionDB::get_ancestor_snapshot(&ic, block_height, sortition_tip)? {
+            Some(sn) => sn,
+            None => {
+                complete = false;
+                break;
+            }
+        };
+        let commits =
+            SortitionDB::get_block_commits_by_block(sort_db.conn(), &snapshot.sortition_id)?;
+        if commits.len() == 0 {
+            blocks_without_commits.push(block_height);
+            continue;
+        }
+
+        // every accepted commit in a block pays the same reward slots -- only the amounts differ
+        for addr in commits[0].commit_outs.iter() {
+            if !addr.is_burn() {
+                payout_for(&mut payouts, &mut payout_index, addr).paid_slots += 1;
+            }
+        }
+        for commit in commits.iter() {
+            if commit.commit_outs.len() == 0 {
+                continue;
+            }
+            let amt_per_address = commit.burn_fee / (commit.commit_outs.len() as u64);
+            for addr in commit.commit_outs.iter() {
+                if !addr.is_burn() {
+                    payout_for(&mut payouts, &mut payout_index, addr).paid_sats += amt_per_address;
+                }
+            }
+        }
+    }
+
+    Ok(Some(RewardCyclePayouts {
+        reward_set,
+        payouts,
+        blocks_without_commits,
+        complete,
+    }))
+}
+
 struct PaidRewards {
     pox: Vec<(StacksAddress, u64)>,
     burns: u64,
@@ -500,6 +809,60 @@ fn dispatcher_announce_burn_ops<T: BlockEventDispatcher>(
 impl<'a, T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>
     ChainsCoordinator<'a, T, N, U>
 {
+    /// The canonical Stacks chain tip, as of the canonical sortition
+    fn get_canonical_stacks_tip(&self) -> Result<Option<StacksHeaderInfo>, Error> {
+        let (consensus_hash, block_hash) =
+            SortitionDB::get_canonical_stacks_chain_tip_hash(self.sortition_db.conn())?;
+        let tip = StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_hash);
+        Ok(
+            StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+                self.chain_state_db.db(),
+                &tip,
+            )?,
+        )
+    }
+
+    /// Tell the dispatcher about the canonical Stacks chain tip, if it is no longer
+    /// `last_announced`, and remember it as the last one announced
+    fn announce_canonical_stacks_tip(&self, last_announced: &mut Option<(StacksBlockId, u64)>) {
+        let dispatcher = match self.dispatcher {
+            Some(dispatcher) => dispatcher,
+            None => return,
+        };
+        let tip = match self.get_canonical_stacks_tip() {
+            Ok(Some(tip)) => tip,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Failed to load the canonical Stacks chain tip: {:?}", &e);
+                return;
+            }
+        };
+        let tip_id = tip.index_block_hash();
+        if last_announced.as_ref().map(|(id, _)| id) == Some(&tip_id) {
+            return;
+        }
+
+        let reorg = match last_announced.as_ref() {
+            Some((previous_id, _)) => match self.chain_state_db.index_conn() {
+                Ok(conn) => conn
+                    .get_ancestor_block_height(previous_id, &tip_id)
+                    .unwrap_or(None)
+                    .is_none(),
+                Err(e) => {
+                    warn!("Failed to open the chainstate index: {:?}", &e);
+                    false
+                }
+            },
+            None => false,
+        };
+        dispatcher.announce_canonical_stacks_tip(
+            &tip,
+            last_announced.as_ref().map(|(id, height)| (id, *height)),
+            reorg,
+        );
+        *last_announced = Some((tip_id, tip.block_height));
+    }
+
     pub fn handle_new_stacks_block(&mut self) -> Result<(), Error> {
         if let Some(pox_anchor) = self.process_ready_blocks()? {
             self.process_new_pox_anchor(pox_anchor)
@@ -568,6 +931,7 @@ impl<'a, T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>
             // at this point, we need to figure out if the sortition we are
             //  about to process is the first block in reward cycle.
             let reward_cycle_info = self.get_reward_cycle_info(&header)?;
+            let is_reward_cycle_start = reward_cycle_info.is_some();
             let (next_snapshot, _, reward_set_info) = self
                 .sortition_db
                 .evaluate_sortition(
@@ -588,6 +952,10 @@ impl<'a, T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>
 
             let sortition_id = next_snapshot.sortition_id;
 
+            if is_reward_cycle_start {
+                self.report_missing_pox_anchors(&sortition_id)?;
+            }
+
             self.notifier.notify_sortition_processed();
 
             debug!(
@@ -611,6 +979,26 @@ impl<'a, T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>
         Ok(())
     }
 
+    /// Log (and export) all PoX anchor blocks that this node is missing in the sortition
+    /// history of `sortition_id`, and cache them for `/v2/info`.  A node missing an anchor block
+    /// that the rest of the network has cannot agree with it on the canonical Stacks chain tip, so
+    /// this is worth shouting about.
+    fn report_missing_pox_anchors(&mut self, sortition_id: &SortitionId) -> Result<(), Error> {
+        let missing = self
+            .sortition_db
+            .get_missing_pox_anchors(&self.burnchain, sortition_id)?;
+        self.sortition_db
+            .set_missing_pox_anchors(sortition_id, &missing)?;
+        update_missing_pox_anchors(missing.len() as i64);
+        for (reward_cycle, anchor_block_hash) in missing.iter() {
+            warn!("Missing PoX anchor block. If it does not arrive, see the PoX anchor block recovery procedure in docs/pox-anchor-recovery.md";
+                  "reward_cycle" => *reward_cycle,
+                  "anchor_block_hash" => %anchor_block_hash,
+                  "sortition_id" => %sortition_id);
+        }
+        Ok(())
+    }
+
     /// returns None if this burnchain block is _not_ the start of a reward cycle
     ///         otherwise, returns the required reward cycle info for this burnchain block
     ///                     in our current sortition view:
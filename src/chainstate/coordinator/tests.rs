@@ -1960,6 +1960,88 @@ fn test_pox_btc_ops() {
                    "111111111111",
                    "PoX ID should reflect the 5 reward cycles _with_ a known anchor block, plus the 'initial' known reward cycle at genesis");
     }
+
+    // the reward sets can be recomputed after the fact, and only ever pay out to the stacker's
+    // reward address
+    let sortition_tip = SortitionDB::get_canonical_sortition_tip(sort_db.conn()).unwrap();
+    let mut cycles_with_rewards = 0;
+    for reward_cycle in 0..12 {
+        let reward_set = get_reward_set_for_cycle(
+            reward_cycle,
+            &sortition_tip,
+            &burnchain_conf,
+            &mut chainstate,
+            &sort_db,
+        )
+        .unwrap();
+        let reward_set = match reward_set {
+            Some(reward_set) => reward_set,
+            None => {
+                continue;
+            }
+        };
+        assert_eq!(reward_set.reward_cycle, reward_cycle);
+        if reward_set.reward_set.len() > 0 {
+            cycles_with_rewards += 1;
+            assert!(reward_set.reward_set.iter().all(|addr| addr == &rewards));
+            assert_eq!(
+                reward_set.registered_addrs,
+                vec![(rewards.clone(), stacked_amt)]
+            );
+            assert_eq!(reward_set.participation, stacked_amt);
+            assert_eq!(
+                reward_set.reward_set.len() as u128,
+                stacked_amt / reward_set.threshold
+            );
+        }
+    }
+    assert!(cycles_with_rewards > 0);
+    // no anchor block has been chosen for reward cycles that haven't begun yet
+    assert!(get_reward_set_for_cycle(
+        20,
+        &sortition_tip,
+        &burnchain_conf,
+        &mut chainstate,
+        &sort_db
+    )
+    .unwrap()
+    .is_none());
+
+    // every block in each completed reward phase paid out the reward set in full
+    let mut cycles_verified = 0;
+    for reward_cycle in 0..12 {
+        let payouts = match get_reward_cycle_payouts(
+            reward_cycle,
+            &sortition_tip,
+            &burnchain_conf,
+            &mut chainstate,
+            &sort_db,
+        )
+        .unwrap()
+        {
+            Some(payouts) => payouts,
+            None => {
+                continue;
+            }
+        };
+        if !payouts.complete || payouts.reward_set.reward_set.len() == 0 {
+            continue;
+        }
+        cycles_verified += 1;
+        assert!(payouts.blocks_without_commits.is_empty());
+        assert_eq!(payouts.payouts.len(), 1);
+        assert_eq!(payouts.payouts[0].address, rewards);
+        assert_eq!(
+            payouts.payouts[0].expected_slots,
+            payouts.reward_set.reward_set.len() as u64
+        );
+        assert_eq!(
+            payouts.payouts[0].paid_slots,
+            payouts.payouts[0].expected_slots
+        );
+        assert!(payouts.payouts[0].paid_sats > 0);
+    }
+    assert!(cycles_verified > 0);
 }
 
 #[test]
@@ -3188,6 +3270,21 @@ fn test_pox_no_anchor_selected() {
         assert_eq!(&pox_id.to_string(), "1101");
     }
 
+    // the blinded node knows that it's missing the anchor block of reward cycle 1
+    {
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db_blind.conn()).unwrap();
+        let missing = sort_db_blind
+            .get_missing_pox_anchors(&get_burnchain(path_blinded, None), &tip.sortition_id)
+            .unwrap();
+        assert_eq!(missing, vec![(1, anchor_blocks[0].clone())]);
+
+        // ...and cached it for /v2/info when it processed the reward cycle start
+        let cached = sort_db_blind
+            .get_cached_missing_pox_anchors(&get_burnchain(path_blinded, None), &tip.sortition_id)
+            .unwrap();
+        assert_eq!(cached, Some(missing));
+    }
+
     for (sort_id, block) in stacks_blocks.iter() {
         reveal_block(
             path_blinded,
@@ -3204,6 +3301,19 @@ fn test_pox_no_anchor_selected() {
         assert_eq!(&pox_id.to_string(), "1111");
     }
 
+    {
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db_blind.conn()).unwrap();
+        let missing = sort_db_blind
+            .get_missing_pox_anchors(&get_burnchain(path_blinded, None), &tip.sortition_id)
+            .unwrap();
+        assert!(missing.is_empty());
+
+        let cached = sort_db_blind
+            .get_cached_missing_pox_anchors(&get_burnchain(path_blinded, None), &tip.sortition_id)
+            .unwrap();
+        assert_eq!(cached, Some(vec![]));
+    }
+
     let block_height = eval_at_chain_tip(path_blinded, &sort_db_blind, "block-height");
     assert_eq!(block_height, Value::UInt(7));
 }
@@ -8,6 +8,7 @@ use chainstate::stacks::boot::{
     BOOT_CODE_COST_VOTING_TESTNET as BOOT_CODE_COST_VOTING, BOOT_CODE_POX_TESTNET,
 };
 use chainstate::stacks::db::{MinerPaymentSchedule, StacksHeaderInfo};
+use chainstate::stacks::events::{PoxEventType, StacksTransactionEvent};
 use chainstate::stacks::index::MarfTrieId;
 use chainstate::stacks::C32_ADDRESS_VERSION_TESTNET_SINGLESIG;
 use chainstate::stacks::*;
@@ -887,6 +888,150 @@ fn delegation_tests() {
     });
 }
 
+fn pox_events(events: Vec<StacksTransactionEvent>) -> Vec<PoxEventType> {
+    events
+        .into_iter()
+        .filter_map(|event| match event {
+            StacksTransactionEvent::PoxEvent(pox_event) => Some(pox_event),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn pox_events_tests() {
+    let mut sim = ClarityTestSim::new();
+    let delegator = StacksPrivateKey::new();
+
+    sim.execute_next_block(|env| {
+        env.initialize_contract(POX_CONTRACT_TESTNET.clone(), &BOOT_CODE_POX_TESTNET)
+            .unwrap()
+    });
+    sim.execute_next_block(|env| {
+        let burn_height = env.eval_raw("burn-block-height").unwrap().0;
+
+        // a failed call emits no PoX event
+        let (result, _, events) = env
+            .execute_transaction(
+                (&USER_KEYS[0]).into(),
+                POX_CONTRACT_TESTNET.clone(),
+                "stack-aggregation-commit",
+                &symbols_from_values(vec![POX_ADDRS[0].clone(), Value::UInt(1)]),
+            )
+            .unwrap();
+        assert_eq!(result.to_string(), "(err 4)".to_string());
+        assert!(pox_events(events).is_empty());
+
+        let (result, _, events) = env
+            .execute_transaction(
+                (&USER_KEYS[0]).into(),
+                POX_CONTRACT_TESTNET.clone(),
+                "stack-stx",
+                &symbols_from_values(vec![
+                    Value::UInt(*MIN_THRESHOLD),
+                    POX_ADDRS[0].clone(),
+                    burn_height.clone(),
+                    Value::UInt(1),
+                ]),
+            )
+            .unwrap();
+        assert!(result.clone().expect_result().is_ok());
+        match pox_events(events).as_slice() {
+            [PoxEventType::StackStx(data)] => {
+                assert_eq!(data.stacker, Value::from(&USER_KEYS[0]).expect_principal());
+                assert_eq!(data.locked_amount, *MIN_THRESHOLD);
+                assert_eq!(data.pox_addr.version, 0);
+                assert_eq!(data.pox_addr.hashbytes, vec![0u8; 20]);
+                assert!(data.pox_addr.address.is_some());
+                assert_eq!(data.start_burn_height, burn_height.clone().expect_u128());
+                assert_eq!(data.lock_period, 1);
+                assert_eq!(
+                    data.json_serialize()["locked_amount"],
+                    json!(format!("{}", *MIN_THRESHOLD))
+                );
+            }
+            events => panic!("Unexpected PoX events: {:?}", events),
+        }
+
+        let (result, _, events) = env
+            .execute_transaction(
+                (&USER_KEYS[1]).into(),
+                POX_CONTRACT_TESTNET.clone(),
+                "delegate-stx",
+                &symbols_from_values(vec![
+                    Value::UInt(*MIN_THRESHOLD),
+                    (&delegator).into(),
+                    Value::none(),
+                    Value::some(POX_ADDRS[1].clone()).unwrap(),
+                ]),
+            )
+            .unwrap();
+        assert_eq!(result, Value::okay_true());
+        match pox_events(events).as_slice() {
+            [PoxEventType::DelegateStx(data)] => {
+                assert_eq!(data.stacker, Value::from(&USER_KEYS[1]).expect_principal());
+                assert_eq!(data.delegate_to, Value::from(&delegator).expect_principal());
+                assert_eq!(data.amount_ustx, *MIN_THRESHOLD);
+                assert_eq!(data.until_burn_height, None);
+                assert_eq!(data.pox_addr.as_ref().unwrap().version, 0);
+            }
+            events => panic!("Unexpected PoX events: {:?}", events),
+        }
+
+        let (result, _, events) = env
+            .execute_transaction(
+                (&delegator).into(),
+                POX_CONTRACT_TESTNET.clone(),
+                "delegate-stack-stx",
+                &symbols_from_values(vec![
+                    (&USER_KEYS[1]).into(),
+                    Value::UInt(*MIN_THRESHOLD),
+                    POX_ADDRS[1].clone(),
+                    burn_height.clone(),
+                    Value::UInt(1),
+                ]),
+            )
+            .unwrap();
+        assert!(result.clone().expect_result().is_ok());
+        match pox_events(events).as_slice() {
+            [PoxEventType::DelegateStackStx(data)] => {
+                assert_eq!(data.delegator, Value::from(&delegator).expect_principal());
+                assert_eq!(data.stacker, Value::from(&USER_KEYS[1]).expect_principal());
+                assert_eq!(data.locked_amount, *MIN_THRESHOLD);
+                assert_eq!(data.lock_period, 1);
+            }
+            events => panic!("Unexpected PoX events: {:?}", events),
+        }
+
+        let (result, _, events) = env
+            .execute_transaction(
+                (&delegator).into(),
+                POX_CONTRACT_TESTNET.clone(),
+                "stack-aggregation-commit",
+                &symbols_from_values(vec![POX_ADDRS[1].clone(), Value::UInt(1)]),
+            )
+            .unwrap();
+        assert_eq!(result, Value::okay_true());
+        match pox_events(events).as_slice() {
+            [PoxEventType::StackAggregationCommit(data)] => {
+                assert_eq!(data.delegator, Value::from(&delegator).expect_principal());
+                assert_eq!(data.reward_cycle, 1);
+                assert_eq!(
+                    data.pox_addr.hashbytes,
+                    POX_ADDRS[1]
+                        .clone()
+                        .expect_tuple()
+                        .get("hashbytes")
+                        .unwrap()
+                        .to_owned()
+                        .expect_buff(20)
+                );
+            }
+            events => panic!("Unexpected PoX events: {:?}", events),
+        }
+    });
+}
+
 #[test]
 fn test_vote_withdrawal() {
     let mut sim = ClarityTestSim::new();
@@ -117,7 +117,37 @@ impl StacksAddress {
     }
 }
 
-/// Extract a PoX address from its tuple representation
+/// Render a PoX address tuple as a base58 burnchain address.  Returns None if the tuple's
+/// version byte is not a recognized address hash mode, which can happen for delegations
+/// (the PoX contract only validates the version when the tokens are actually stacked).
+pub fn pox_addr_tuple_to_b58(mainnet: bool, pox_addr: &TupleData) -> Option<String> {
+    let version_byte = pox_addr
+        .get("version")
+        .ok()?
+        .to_owned()
+        .expect_buff_padded(1, 0)[0];
+    let hash_mode = AddressHashMode::try_from(version_byte).ok()?;
+    let hashbytes = pox_addr
+        .get("hashbytes")
+        .ok()?
+        .to_owned()
+        .expect_buff_padded(20, 0);
+    let version = if mainnet {
+        hash_mode.to_version_mainnet()
+    } else {
+        hash_mode.to_version_testnet()
+    };
+    let hash = Hash160::from_bytes(&hashbytes[0..20])?;
+    Some(StacksAddress::new(version, hash).to_b58())
+}
+
+/// Extract a PoX address from its tuple representation.
+///
+/// The PoX contract accepts versions 0x00 through 0x03 (`AddressHashMode`) with a 20-byte
+/// `hashbytes`.  On the burnchain, 0x00 is paid to a p2pkh output, and 0x01 (p2sh), 0x02
+/// (p2sh-wrapped p2wpkh) and 0x03 (p2sh-wrapped p2wsh) are all paid to a p2sh output of the same
+/// hash.  Native segwit outputs -- p2wsh and taproot in particular, with their 32-byte programs
+/// -- can't be expressed as a pox-addr, so supporting them needs a new PoX contract.
 fn tuple_to_pox_addr(tuple_data: TupleData) -> (AddressHashMode, Hash160) {
     let version_value = tuple_data
         .get("version")
@@ -142,6 +172,17 @@ fn tuple_to_pox_addr(tuple_data: TupleData) -> (AddressHashMode, Hash160) {
     (version, hashbytes)
 }
 
+/// Extract a PoX address from its tuple representation, as the address that block-commits must
+/// pay
+fn tuple_to_pox_reward_address(mainnet: bool, tuple_data: TupleData) -> StacksAddress {
+    let (hash_mode, hash) = tuple_to_pox_addr(tuple_data);
+    let version = match mainnet {
+        true => hash_mode.to_version_mainnet(),
+        false => hash_mode.to_version_testnet(),
+    };
+    StacksAddress::new(version, hash)
+}
+
 impl StacksChainState {
     fn eval_boot_code_read_only(
         &mut self,
@@ -401,7 +442,7 @@ impl StacksChainState {
                 .to_owned()
                 .expect_tuple();
 
-            let (hash_mode, hash) = tuple_to_pox_addr(pox_addr_tuple);
+            let reward_address = tuple_to_pox_reward_address(self.mainnet, pox_addr_tuple);
 
             let total_ustx = tuple_data
                 .get("total-ustx")
@@ -409,17 +450,12 @@ impl StacksChainState {
                 .to_owned()
                 .expect_u128();
 
-            let version = match self.mainnet {
-                true => hash_mode.to_version_mainnet(),
-                false => hash_mode.to_version_testnet(),
-            };
-
             test_debug!(
                 "PoX reward address (for {} ustx): {:?}",
                 total_ustx,
-                &StacksAddress::new(version, hash)
+                &reward_address
             );
-            ret.push((StacksAddress::new(version, hash), total_ustx));
+            ret.push((reward_address, total_ustx));
         }
 
         Ok(ret)
@@ -431,7 +467,7 @@ mod contract_tests;
 
 #[cfg(test)]
 pub mod test {
-    use std::collections::{HashMap, HashSet};
+    use std::collections::{BTreeSet, HashMap, HashSet};
     use std::convert::From;
     use std::fs;
 
@@ -459,6 +495,89 @@ pub mod test {
 
     use super::*;
 
+    #[test]
+    fn pox_reward_address_roundtrip() {
+        use burnchains::bitcoin::address::{BitcoinAddress, BitcoinAddressType};
+        use burnchains::bitcoin::BitcoinNetworkType;
+
+        let make_tuple = |version: u8, hashbytes: &[u8]| {
+            TupleData::from_data(vec![
+                ("version".into(), Value::buff_from_byte(version)),
+                (
+                    "hashbytes".into(),
+                    Value::buff_from(hashbytes.to_vec()).unwrap(),
+                ),
+            ])
+            .unwrap()
+        };
+
+        let hash = Hash160([0x11; 20]);
+        let modes = [
+            (
+                AddressHashMode::SerializeP2PKH,
+                BitcoinAddressType::PublicKeyHash,
+            ),
+            (
+                AddressHashMode::SerializeP2SH,
+                BitcoinAddressType::ScriptHash,
+            ),
+            (
+                AddressHashMode::SerializeP2WPKH,
+                BitcoinAddressType::ScriptHash,
+            ),
+            (
+                AddressHashMode::SerializeP2WSH,
+                BitcoinAddressType::ScriptHash,
+            ),
+        ];
+        for (mainnet, network_id) in [
+            (true, BitcoinNetworkType::Mainnet),
+            (false, BitcoinNetworkType::Testnet),
+        ]
+        .iter()
+        {
+            for (hash_mode, btc_addrtype) in modes.iter() {
+                let tuple = make_tuple(*hash_mode as u8, hash.as_bytes());
+                let reward_addr = tuple_to_pox_reward_address(*mainnet, tuple.clone());
+                assert_eq!(reward_addr.bytes, hash);
+
+                // the output a miner builds pays the address the reward set expects...
+                let tx_out = reward_addr.to_bitcoin_tx_out(1000);
+                let btc_addr = BitcoinAddress::from_scriptpubkey(
+                    *network_id,
+                    &tx_out.script_pubkey.to_bytes(),
+                )
+                .unwrap();
+                assert_eq!(btc_addr.addrtype, *btc_addrtype);
+                assert_eq!(StacksAddress::from_bitcoin_address(&btc_addr), reward_addr);
+
+                // ...and it's the address we report to stackers
+                assert_eq!(
+                    pox_addr_tuple_to_b58(*mainnet, &tuple),
+                    Some(btc_addr.to_b58())
+                );
+                assert_eq!(
+                    BitcoinAddress::from_b58(&btc_addr.to_b58()).unwrap().bytes,
+                    hash
+                );
+            }
+
+            // versions the PoX contract rejects aren't reward addresses
+            let tuple = make_tuple(0x04, hash.as_bytes());
+            assert_eq!(pox_addr_tuple_to_b58(*mainnet, &tuple), None);
+            assert!(AddressHashMode::try_from(0x04u8).is_err());
+        }
+
+        // short hashbytes are zero-padded, as the PoX contract allows
+        let tuple = make_tuple(0x00, &[0x11; 19]);
+        let mut padded = [0x11; 20];
+        padded[19] = 0;
+        assert_eq!(
+            tuple_to_pox_reward_address(false, tuple).bytes,
+            Hash160(padded)
+        );
+    }
+
     #[test]
     fn make_reward_set_units() {
         let threshold = 1_000;
@@ -702,6 +821,30 @@ pub mod test {
         }
     }
 
+    fn get_vesting_schedule(
+        peer: &mut TestPeer,
+        addr: &PrincipalData,
+        unlock_heights: &BTreeSet<u64>,
+    ) -> Vec<(u64, u128)> {
+        let sortdb = peer.sortdb.take().unwrap();
+        let (consensus_hash, block_bhh) =
+            SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn()).unwrap();
+        let stacks_block_id = StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_bhh);
+        let schedule = peer
+            .chainstate()
+            .with_read_only_clarity_tx(&sortdb.index_conn(), &stacks_block_id, |clarity_tx| {
+                StacksChainState::get_account_vesting_schedule(
+                    clarity_tx,
+                    false,
+                    addr,
+                    unlock_heights,
+                )
+            })
+            .unwrap();
+        peer.sortdb = Some(sortdb);
+        schedule
+    }
+
     fn get_stacker_info(
         peer: &mut TestPeer,
         addr: &PrincipalData,
@@ -1157,6 +1300,11 @@ pub mod test {
             ChainstateAccountLockup::new(alice, 1000, 6),
             ChainstateAccountLockup::new(alice, 1000, 7),
         ];
+        let unlock_heights: BTreeSet<u64> = peer_config
+            .initial_lockups
+            .iter()
+            .map(|lockup| lockup.block_height)
+            .collect();
         let mut peer = TestPeer::new(peer_config);
 
         let num_blocks = 8;
@@ -1203,6 +1351,23 @@ pub mod test {
                     assert_eq!(bob_balance, 4000);
                 }
             }
+            if tenure_id > 0 {
+                // whatever has not unlocked yet is still on the schedule
+                for (addr, balance, total) in
+                    [(&alice, alice_balance, 5000), (&bob, bob_balance, 4000)].iter()
+                {
+                    let schedule = get_vesting_schedule(
+                        &mut peer,
+                        &addr.to_account_principal(),
+                        &unlock_heights,
+                    );
+                    assert!(schedule
+                        .iter()
+                        .all(|(height, _)| *height > tenure_id as u64));
+                    let vesting: u128 = schedule.iter().map(|(_, amount)| *amount).sum();
+                    assert_eq!(*balance + vesting, *total);
+                }
+            }
             let microblock_privkey = StacksPrivateKey::new();
             let microblock_pubkeyhash =
                 Hash160::from_node_public_key(&StacksPublicKey::from_private(&microblock_privkey));
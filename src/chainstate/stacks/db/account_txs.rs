@@ -0,0 +1,392 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020-2021 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Index of the transactions each account was involved in, maintained only if the node runs with
+//! `STACKS_ACCOUNT_TX_INDEX=1`.  A transaction involves an account if the account sent or
+//! sponsored it, is the recipient of its STX transfer, is the contract it calls or deploys, or
+//! sent or received assets in any of its events.  Rows are kept for every fork, so they must be
+//! checked against the MARF before they are reported for a given chain tip.
+
+use std::collections::{HashMap, HashSet};
+
+use rusqlite::types::ToSql;
+use rusqlite::Row;
+
+use burnchains::Txid;
+use chainstate::stacks::db::*;
+use chainstate::stacks::events::*;
+use chainstate::stacks::Error;
+use chainstate::stacks::*;
+use util::db::Error as db_error;
+use util::db::*;
+use util::hash::to_hex;
+use vm::database::ClaritySerializable;
+use vm::types::{PrincipalData, QualifiedContractIdentifier, StandardPrincipalData};
+
+use crate::types::chainstate::StacksBlockId;
+
+/// A transaction that involved an account, as recorded in the index
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountTransaction {
+    pub txid: Txid,
+    pub index_block_hash: StacksBlockId,
+    pub block_height: u64,
+    /// Position of the transaction among the ones the block processed, including the ones in the
+    /// microblocks it confirmed
+    pub tx_index: u32,
+    /// The hex-encoded transaction, or None for a burnchain operation
+    pub tx_hex: Option<String>,
+    /// The hex-encoded Clarity result of the transaction
+    pub result_hex: String,
+}
+
+impl FromRow<AccountTransaction> for AccountTransaction {
+    fn from_row<'a>(row: &'a Row) -> Result<AccountTransaction, db_error> {
+        let txid = Txid::from_column(row, "txid")?;
+        let index_block_hash = StacksBlockId::from_column(row, "index_block_hash")?;
+        let block_height = u64::from_column(row, "block_height")?;
+        let tx_index: u32 = row.get_unwrap("tx_index");
+        let tx_hex: Option<String> = row.get_unwrap("tx_hex");
+        let result_hex: String = row.get_unwrap("result_hex");
+
+        Ok(AccountTransaction {
+            txid,
+            index_block_hash,
+            block_height,
+            tx_index,
+            tx_hex,
+            result_hex,
+        })
+    }
+}
+
+/// The accounts involved in a processed transaction
+fn involved_principals(receipt: &StacksTransactionReceipt) -> HashSet<PrincipalData> {
+    let mut principals = HashSet::new();
+    if let TransactionOrigin::Stacks(ref tx) = receipt.transaction {
+        principals.insert(tx.origin_address().to_account_principal());
+        if let Some(sponsor) = tx.sponsor_address() {
+            principals.insert(sponsor.to_account_principal());
+        }
+        match tx.payload {
+            TransactionPayload::TokenTransfer(ref recipient, ..) => {
+                principals.insert(recipient.clone());
+            }
+            TransactionPayload::ContractCall(ref contract_call) => {
+                principals.insert(PrincipalData::Contract(
+                    contract_call.to_clarity_contract_id(),
+                ));
+            }
+            TransactionPayload::SmartContract(ref smart_contract) => {
+                principals.insert(PrincipalData::Contract(QualifiedContractIdentifier::new(
+                    StandardPrincipalData::from(tx.origin_address()),
+                    smart_contract.name.clone(),
+                )));
+            }
+            _ => {}
+        }
+    }
+
+    for event in receipt.events.iter() {
+        match event {
+            StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(ref data)) => {
+                principals.insert(data.sender.clone());
+                principals.insert(data.recipient.clone());
+            }
+            StacksTransactionEvent::STXEvent(STXEventType::STXMintEvent(ref data)) => {
+                principals.insert(data.recipient.clone());
+            }
+            StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(ref data)) => {
+                principals.insert(data.sender.clone());
+            }
+            StacksTransactionEvent::STXEvent(STXEventType::STXLockEvent(ref data)) => {
+                principals.insert(data.locked_address.clone());
+            }
+            StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(ref data)) => {
+                principals.insert(data.sender.clone());
+                principals.insert(data.recipient.clone());
+            }
+            StacksTransactionEvent::FTEvent(FTEventType::FTMintEvent(ref data)) => {
+                principals.insert(data.recipient.clone());
+            }
+            StacksTransactionEvent::FTEvent(FTEventType::FTBurnEvent(ref data)) => {
+                principals.insert(data.sender.clone());
+            }
+            StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(ref data)) => {
+                principals.insert(data.sender.clone());
+                principals.insert(data.recipient.clone());
+            }
+            StacksTransactionEvent::NFTEvent(NFTEventType::NFTMintEvent(ref data)) => {
+                principals.insert(data.recipient.clone());
+            }
+            StacksTransactionEvent::NFTEvent(NFTEventType::NFTBurnEvent(ref data)) => {
+                principals.insert(data.sender.clone());
+            }
+            _ => {}
+        }
+    }
+    principals
+}
+
+impl<'a> ChainstateTx<'a> {
+    /// Record the accounts involved in each of a block's transactions into the
+    /// `account_transactions` index, if it is enabled
+    pub fn index_account_transactions(
+        &self,
+        block_id: &StacksBlockId,
+        block_height: u64,
+        events: &[StacksTransactionReceipt],
+    ) -> Result<(), Error> {
+        if !self.indexes.account_txs {
+            return Ok(());
+        }
+        let insert_body = "INSERT OR IGNORE INTO account_transaction_bodies (txid, index_block_hash, tx_hex, result_hex) VALUES (?1, ?2, ?3, ?4)";
+        let insert = "INSERT OR IGNORE INTO account_transactions (principal, txid, index_block_hash, block_height, tx_index) VALUES (?1, ?2, ?3, ?4, ?5)";
+        let block_height = u64_to_sql(block_height).map_err(Error::DBError)?;
+        for (tx_index, tx_event) in events.iter().enumerate() {
+            let txid = tx_event.transaction.txid();
+            let tx_hex = match tx_event.transaction {
+                TransactionOrigin::Stacks(ref tx) => Some(to_hex(&tx.serialize_to_vec())),
+                TransactionOrigin::Burn(_) => None,
+            };
+            let body_params: &[&dyn ToSql] =
+                &[&txid, block_id, &tx_hex, &tx_event.result.serialize()];
+            self.tx
+                .tx()
+                .execute(insert_body, body_params)
+                .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+
+            let tx_index = tx_index as u32;
+            for principal in involved_principals(tx_event).iter() {
+                let params: &[&dyn ToSql] = &[
+                    &principal.to_string(),
+                    &txid,
+                    block_id,
+                    &block_height,
+                    &tx_index,
+                ];
+                self.tx
+                    .tx()
+                    .execute(insert, params)
+                    .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StacksChainState {
+    /// Get up to `limit` of the transactions that involved `principal` in `tip`'s fork, most
+    /// recent first.  If `before` is given as (block height, tx index), only the transactions that
+    /// precede that position are returned; pass the position of the last transaction in a page to
+    /// get the next page.  Returns an empty page if the index is not enabled.
+    pub fn get_account_transactions(
+        &self,
+        tip: &StacksBlockId,
+        principal: &PrincipalData,
+        before: Option<(u64, u32)>,
+        limit: u64,
+    ) -> Result<Vec<AccountTransaction>, Error> {
+        let index_conn = self.index_conn()?;
+        let tip_height = match index_conn
+            .get_ancestor_block_height(tip, tip)
+            .map_err(Error::DBError)?
+        {
+            Some(height) => height,
+            None => return Err(Error::NoSuchBlockError),
+        };
+
+        // nothing above the tip is in its fork
+        let (before_height, before_tx_index) = match before {
+            Some((height, tx_index)) if height <= tip_height => (height, tx_index),
+            _ => (tip_height + 1, 0),
+        };
+        let sql = "SELECT a.txid, a.index_block_hash, a.block_height, a.tx_index, b.tx_hex, b.result_hex FROM account_transactions a JOIN account_transaction_bodies b ON a.txid = b.txid AND a.index_block_hash = b.index_block_hash WHERE a.principal = ?1 AND (a.block_height < ?2 OR (a.block_height = ?2 AND a.tx_index < ?3)) ORDER BY a.block_height DESC, a.tx_index DESC";
+        let args: &[&dyn ToSql] = &[
+            &principal.to_string(),
+            &u64_to_sql(before_height).map_err(Error::DBError)?,
+            &before_tx_index,
+        ];
+        let mut stmt = self
+            .db()
+            .prepare(sql)
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        let mut rows = stmt
+            .query(args)
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+
+        // the block in `tip`'s fork at each height we've looked at so far
+        let mut fork_blocks: HashMap<u64, Option<StacksBlockId>> = HashMap::new();
+        let mut page = vec![];
+        while let Some(row) = rows
+            .next()
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?
+        {
+            if page.len() as u64 >= limit {
+                break;
+            }
+            let entry = AccountTransaction::from_row(row).map_err(Error::DBError)?;
+            if !fork_blocks.contains_key(&entry.block_height) {
+                let block_id = index_conn
+                    .get_ancestor_block_hash(entry.block_height, tip)
+                    .map_err(Error::DBError)?;
+                fork_blocks.insert(entry.block_height, block_id);
+            }
+            if fork_blocks[&entry.block_height].as_ref() != Some(&entry.index_block_hash) {
+                continue;
+            }
+            page.push(entry);
+        }
+        Ok(page)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chainstate::stacks::db::test::instantiate_chainstate;
+    use core::{FIRST_BURNCHAIN_CONSENSUS_HASH, FIRST_STACKS_BLOCK_HASH};
+    use util::hash::Hash160;
+    use vm::costs::ExecutionCost;
+    use vm::Value;
+
+    use crate::types::chainstate::{StacksAddress, StacksBlockHeader};
+
+    #[test]
+    fn involved_principals_of_transfer() {
+        let privk = StacksPrivateKey::from_hex(
+            "6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001",
+        )
+        .unwrap();
+        let auth = TransactionAuth::from_p2pkh(&privk).unwrap();
+        let sender = auth.origin().address_testnet().to_account_principal();
+        let recipient = PrincipalData::from(StacksAddress {
+            version: 26,
+            bytes: Hash160([0xff; 20]),
+        });
+        let tx = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            auth,
+            TransactionPayload::TokenTransfer(recipient.clone(), 123, TokenTransferMemo([0u8; 34])),
+        );
+        let mut receipt = StacksTransactionReceipt::from_stx_transfer(
+            tx,
+            vec![],
+            Value::okay_true(),
+            ExecutionCost::zero(),
+        );
+        let principals: Vec<_> = involved_principals(&receipt).into_iter().collect();
+        assert_eq!(principals.len(), 2);
+        assert!(principals.contains(&sender));
+        assert!(principals.contains(&recipient));
+
+        // principals in asset events count too
+        let minted = PrincipalData::from(StacksAddress {
+            version: 26,
+            bytes: Hash160([0xee; 20]),
+        });
+        receipt.events.push(StacksTransactionEvent::STXEvent(
+            STXEventType::STXMintEvent(STXMintEventData {
+                recipient: minted.clone(),
+                amount: 1,
+            }),
+        ));
+        assert!(involved_principals(&receipt).contains(&minted));
+    }
+
+    #[test]
+    fn account_transactions_follow_the_fork() {
+        let mut chainstate =
+            instantiate_chainstate(false, 0x80000000, "account_transactions_follow_the_fork");
+        let principal = PrincipalData::from(StacksAddress {
+            version: 26,
+            bytes: Hash160([0x11; 20]),
+        });
+
+        // two forks off of the boot block: A (heights 1 and 2) and B (height 1)
+        let boot_block = StacksBlockHeader::make_index_block_hash(
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+        );
+        let block_a1 = StacksBlockId([0xa1; 32]);
+        let block_a2 = StacksBlockId([0xa2; 32]);
+        let block_b1 = StacksBlockId([0xb1; 32]);
+        for (parent, child) in [
+            (&boot_block, &block_a1),
+            (&block_a1, &block_a2),
+            (&boot_block, &block_b1),
+        ]
+        .iter()
+        {
+            let mut tx = chainstate.index_tx_begin().unwrap();
+            tx.put_indexed_begin(parent, child).unwrap();
+            tx.put_indexed_all(&vec![], &vec![]).unwrap();
+            tx.commit().unwrap();
+        }
+        // block A2 has two transactions, and B1 has the same transaction as A1
+        for (txid, block_id, height, tx_index) in [
+            (Txid([0; 32]), &block_a1, 1i64, 0u32),
+            (Txid([1; 32]), &block_a2, 2, 0),
+            (Txid([3; 32]), &block_a2, 2, 1),
+            (Txid([0; 32]), &block_b1, 1, 0),
+        ]
+        .iter()
+        {
+            let params: &[&dyn ToSql] =
+                &[&principal.to_string(), txid, *block_id, height, tx_index];
+            chainstate.db().execute("INSERT INTO account_transactions (principal, txid, index_block_hash, block_height, tx_index) VALUES (?1, ?2, ?3, ?4, ?5)", params).unwrap();
+            let params: &[&dyn ToSql] = &[
+                txid,
+                *block_id,
+                &Some("00".to_string()),
+                &Value::okay_true().serialize(),
+            ];
+            chainstate.db().execute("INSERT INTO account_transaction_bodies (txid, index_block_hash, tx_hex, result_hex) VALUES (?1, ?2, ?3, ?4)", params).unwrap();
+        }
+
+        let txids = |tip: &StacksBlockId, before: Option<(u64, u32)>, limit: u64| -> Vec<Txid> {
+            chainstate
+                .get_account_transactions(tip, &principal, before, limit)
+                .unwrap()
+                .into_iter()
+                .map(|entry| entry.txid)
+                .collect()
+        };
+        assert_eq!(
+            txids(&block_a2, None, 10),
+            vec![Txid([3; 32]), Txid([1; 32]), Txid([0; 32])]
+        );
+        assert_eq!(txids(&block_a2, None, 1), vec![Txid([3; 32])]);
+        // pages pick up where the last one left off, even within a block
+        assert_eq!(txids(&block_a2, Some((2, 1)), 1), vec![Txid([1; 32])]);
+        assert_eq!(txids(&block_a2, Some((2, 0)), 10), vec![Txid([0; 32])]);
+        assert_eq!(txids(&block_a2, Some((1, 0)), 10), vec![]);
+        // a position above the tip is the same as no position
+        assert_eq!(txids(&block_a1, Some((2, 1)), 10), vec![Txid([0; 32])]);
+        assert_eq!(txids(&block_b1, None, 10), vec![Txid([0; 32])]);
+        assert_eq!(
+            chainstate
+                .get_account_transactions(&block_b1, &principal, None, 10)
+                .unwrap()[0]
+                .index_block_hash,
+            block_b1
+        );
+        assert!(chainstate
+            .get_account_transactions(&StacksBlockId([0xff; 32]), &principal, None, 10)
+            .is_err());
+    }
+}
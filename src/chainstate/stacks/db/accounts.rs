@@ -14,6 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 
 use rusqlite::types::ToSql;
@@ -25,6 +26,7 @@ use chainstate::stacks::db::*;
 use chainstate::stacks::Error;
 use chainstate::stacks::*;
 use clarity_vm::clarity::{ClarityConnection, ClarityTransactionConnection};
+use util::boot::boot_code_id;
 use util::db::Error as db_error;
 use util::db::*;
 use vm::database::clarity_store::*;
@@ -153,6 +155,55 @@ impl StacksChainState {
         })
     }
 
+    /// The genesis lockups `principal` has yet to receive, as (Stacks block height, amount)
+    /// pairs in height order.  The lockup contract's table is keyed by height alone, so
+    /// `unlock_heights` must name the heights it has entries for; only those after the current
+    /// block are read.
+    pub fn get_account_vesting_schedule<T: ClarityConnection>(
+        clarity_tx: &mut T,
+        mainnet: bool,
+        principal: &PrincipalData,
+        unlock_heights: &BTreeSet<u64>,
+    ) -> Vec<(u64, u128)> {
+        let lockup_contract_id = boot_code_id("lockup", mainnet);
+        clarity_tx.with_clarity_db_readonly(|ref mut db| {
+            let current_height = db.get_current_block_height() as u64;
+            let mut schedule = vec![];
+            for height in unlock_heights.range(current_height + 1..) {
+                let entries = match db.fetch_entry_unknown_descriptor(
+                    &lockup_contract_id,
+                    "lockups",
+                    &Value::UInt((*height).into()),
+                ) {
+                    Ok(Value::Optional(OptionalData {
+                        data: Some(entries),
+                    })) => match *entries {
+                        Value::Sequence(SequenceData::List(entries)) => entries.data,
+                        _ => continue,
+                    },
+                    _ => continue,
+                };
+                for entry in entries.into_iter() {
+                    let lockup = entry.expect_tuple();
+                    let recipient = lockup
+                        .get("recipient")
+                        .expect("Lockup malformed")
+                        .to_owned()
+                        .expect_principal();
+                    if recipient == *principal {
+                        let amount = lockup
+                            .get("amount")
+                            .expect("Lockup malformed")
+                            .to_owned()
+                            .expect_u128();
+                        schedule.push((*height, amount));
+                    }
+                }
+            }
+            schedule
+        })
+    }
+
     pub fn get_account_ft<'a>(
         clarity_tx: &mut ClarityTx<'a>,
         contract_id: &QualifiedContractIdentifier,
@@ -23,6 +23,9 @@ use std::io;
 use std::io::prelude::*;
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
 
 use rand::thread_rng;
 use rand::Rng;
@@ -38,6 +41,7 @@ use chainstate::burn::BlockSnapshot;
 use chainstate::stacks::db::accounts::MinerReward;
 use chainstate::stacks::db::transactions::TransactionNonceMismatch;
 use chainstate::stacks::db::*;
+use chainstate::stacks::index::marf::MarfConnection;
 use chainstate::stacks::index::MarfTrieId;
 use chainstate::stacks::Error;
 use chainstate::stacks::*;
@@ -46,8 +50,10 @@ use chainstate::stacks::{
     C32_ADDRESS_VERSION_TESTNET_MULTISIG, C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
 };
 use clarity_vm::clarity::{ClarityBlockConnection, ClarityConnection, ClarityInstance};
-use core::mempool::MAXIMUM_MEMPOOL_TX_CHAINING;
+use clarity_vm::database::marf::MarfedKV;
+use core::mempool::{ContractDeployLimits, MAXIMUM_MEMPOOL_TX_CHAINING};
 use core::*;
+use monitoring;
 use net::BlocksInvData;
 use net::Error as net_error;
 use util::db::u64_to_sql;
@@ -58,16 +64,19 @@ use util::db::{
 };
 use util::get_epoch_time_ms;
 use util::get_epoch_time_secs;
-use util::hash::to_hex;
+use util::hash::{hex_bytes, to_hex};
 use util::retry::BoundReader;
 use util::strings::StacksString;
 pub use vm::analysis::errors::{CheckError, CheckErrors};
 use vm::analysis::run_analysis;
 use vm::ast::build_ast;
+use vm::ast::errors::ParseErrors;
 use vm::contexts::AssetMap;
 use vm::contracts::Contract;
 use vm::costs::LimitedCostTracker;
 use vm::database::{BurnStateDB, ClarityDatabase, NULL_BURN_STATE_DB, NULL_HEADER_DB};
+use vm::profiler;
+use vm::representations::{SymbolicExpression, SymbolicExpressionType};
 use vm::types::{
     AssetIdentifier, PrincipalData, QualifiedContractIdentifier, SequenceData,
     StandardPrincipalData, TupleData, TypeSignature, Value,
@@ -76,6 +85,7 @@ use vm::types::{
 use crate::types::chainstate::{
     StacksAddress, StacksBlockHeader, StacksBlockId, StacksMicroblockHeader,
 };
+use crate::types::proof::TrieHash;
 use crate::{types, util};
 
 #[derive(Debug, Clone, PartialEq)]
@@ -90,6 +100,30 @@ pub struct StagingMicroblock {
     pub block_data: Vec<u8>,
 }
 
+/// Two conflicting microblocks signed by the same leader: they build off of the same anchored
+/// block, at the same sequence number and with the same parent, but differ.  The pair of headers
+/// is proof of the fork for a `PoisonMicroblock` transaction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MicroblockEquivocation {
+    /// Local ID, increasing in the order this node detected equivocations
+    pub id: i64,
+    /// Index block hash of the anchored block the forked stream builds off of
+    pub parent_index_block_hash: StacksBlockId,
+    pub sequence: u16,
+    /// The header with the lower hash of the two
+    pub header_1: StacksMicroblockHeader,
+    pub header_2: StacksMicroblockHeader,
+    /// When this node detected the equivocation
+    pub detected_at: u64,
+}
+
+impl MicroblockEquivocation {
+    /// The payload of a transaction reporting this equivocation
+    pub fn poison_payload(&self) -> TransactionPayload {
+        TransactionPayload::PoisonMicroblock(self.header_1.clone(), self.header_2.clone())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct StagingBlock {
     pub consensus_hash: ConsensusHash,
@@ -117,6 +151,30 @@ pub struct StagingUserBurnSupport {
     pub vtxindex: u32,
 }
 
+/// What running a block in Clarity produced, before its header is stored
+struct BlockStateChanges {
+    /// receipts for the burnchain operations and anchored transactions, followed by those for the
+    /// parent microblock stream's transactions
+    tx_receipts: Vec<StacksTransactionReceipt>,
+    microblock_fees: u128,
+    block_fees: u128,
+    total_burnt: u128,
+    microblock_cost: ExecutionCost,
+    block_cost: ExecutionCost,
+    matured_rewards: Vec<MinerReward>,
+    matured_rewards_info: Option<MinerRewardInfo>,
+}
+
+/// The outcome of re-executing an already-processed block with `replay_block()`
+#[derive(Debug, Clone)]
+pub struct StacksBlockReplayReceipt {
+    pub tx_receipts: Vec<StacksTransactionReceipt>,
+    pub parent_microblocks_cost: ExecutionCost,
+    pub anchored_block_cost: ExecutionCost,
+    /// the state root the replay arrived at
+    pub state_root: TrieHash,
+}
+
 #[derive(Debug)]
 pub enum MemPoolRejection {
     SerializationFailure(codec_error),
@@ -145,6 +203,9 @@ pub enum MemPoolRejection {
     BadTransactionVersion,
     TransferRecipientIsSender(PrincipalData),
     TransferAmountMustBePositive,
+    /// The smart contract breaks one of the node's `ContractDeployLimits`: the limit's name, the
+    /// contract's measure, and the limit
+    ContractDeployLimitExceeded(&'static str, u64, u64),
     DBError(db_error),
     Other(String),
 }
@@ -227,6 +288,13 @@ impl MemPoolRejection {
             InvalidMicroblocks => ("PoisonMicroblockIsInvalid", None),
             BadAddressVersionByte => ("BadAddressVersionByte", None),
             NoCoinbaseViaMempool => ("NoCoinbaseViaMempool", None),
+            ContractDeployLimitExceeded(limit, actual, max) => (
+                "ContractDeployLimitExceeded",
+                Some(json!({
+                    "limit": limit,
+                    "actual": actual,
+                    "max": max})),
+            ),
             // this should never happen via the RPC interface
             NoSuchChainTip(..) => ("ServerFailureNoSuchChainTip", None),
             DBError(e) => (
@@ -263,6 +331,18 @@ impl From<db_error> for MemPoolRejection {
 pub const MINIMUM_TX_FEE: u64 = 1;
 pub const MINIMUM_TX_FEE_RATE_PER_BYTE: u64 = 1;
 
+/// Default number of threads a block's transaction signatures are verified across
+pub const DEFAULT_SIGNATURE_VERIFICATION_THREADS: usize = 4;
+
+static SIGNATURE_VERIFICATION_THREADS: AtomicUsize =
+    AtomicUsize::new(DEFAULT_SIGNATURE_VERIFICATION_THREADS);
+
+/// Set how many threads a block's transaction signatures are verified across before its
+/// transactions are run (0 or 1 to verify each one only as it is run).
+pub fn set_signature_verification_threads(num_threads: usize) {
+    SIGNATURE_VERIFICATION_THREADS.store(num_threads, Ordering::SeqCst);
+}
+
 impl StagingBlock {
     pub fn is_first_mined(&self) -> bool {
         self.parent_anchored_block_hash == FIRST_STACKS_BLOCK_HASH
@@ -298,6 +378,49 @@ impl FromRow<StagingMicroblock> for StagingMicroblock {
     }
 }
 
+/// How deeply lists nest in a contract's expressions, counting a top-level definition as depth 1.
+/// Parsing already caps this at the consensus limit, so this can't recurse too far.
+fn expressions_depth(expressions: &[SymbolicExpression]) -> u64 {
+    expressions
+        .iter()
+        .map(|expr| match expr.expr {
+            SymbolicExpressionType::List(ref exprs) => 1 + expressions_depth(exprs),
+            _ => 0,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn microblock_header_from_column(
+    row: &Row,
+    column_name: &str,
+) -> Result<StacksMicroblockHeader, db_error> {
+    let header_hex: String = row.get_unwrap(column_name);
+    let header_bytes = hex_bytes(&header_hex).map_err(|_e| db_error::ParseError)?;
+    StacksMicroblockHeader::consensus_deserialize(&mut &header_bytes[..])
+        .map_err(|_e| db_error::ParseError)
+}
+
+impl FromRow<MicroblockEquivocation> for MicroblockEquivocation {
+    fn from_row<'a>(row: &'a Row) -> Result<MicroblockEquivocation, db_error> {
+        let id: i64 = row.get_unwrap("rowid");
+        let parent_index_block_hash = StacksBlockId::from_column(row, "parent_index_block_hash")?;
+        let sequence: u16 = row.get_unwrap("sequence");
+        let header_1 = microblock_header_from_column(row, "header_1")?;
+        let header_2 = microblock_header_from_column(row, "header_2")?;
+        let detected_at = u64::from_column(row, "detected_at")?;
+
+        Ok(MicroblockEquivocation {
+            id,
+            parent_index_block_hash,
+            sequence,
+            header_1,
+            header_2,
+            detected_at,
+        })
+    }
+}
+
 impl FromRow<StagingBlock> for StagingBlock {
     fn from_row<'a>(row: &'a Row) -> Result<StagingBlock, db_error> {
         let anchored_block_hash: BlockHeaderHash =
@@ -1167,6 +1290,104 @@ impl StacksChainState {
         query_row::<StagingMicroblock, _>(blocks_conn, sql, args).map_err(Error::DBError)
     }
 
+    /// Find a stored microblock that conflicts with `microblock`: one off of the same anchored
+    /// block, with the same sequence number, parent and version, but a different hash.  Its
+    /// signature was checked against the same leader's key when it was stored.
+    /// Ignores orphaned microblocks.
+    fn find_conflicting_microblock(
+        blocks_conn: &DBConn,
+        parent_index_block_hash: &StacksBlockId,
+        microblock: &StacksMicroblock,
+    ) -> Result<Option<StacksMicroblockHeader>, Error> {
+        let sql = "SELECT microblock_hash FROM staging_microblocks WHERE index_block_hash = ?1 AND sequence = ?2 AND parent_hash = ?3 AND microblock_hash != ?4 AND orphaned = 0";
+        let args: &[&dyn ToSql] = &[
+            parent_index_block_hash,
+            &microblock.header.sequence,
+            &microblock.header.prev_block,
+            &microblock.block_hash(),
+        ];
+        let candidates: Vec<BlockHeaderHash> =
+            query_row_columns(blocks_conn, sql, args, "microblock_hash")?;
+        for candidate in candidates.iter() {
+            let bytes =
+                match StacksChainState::load_staging_microblock_bytes(blocks_conn, candidate)? {
+                    Some(bytes) => bytes,
+                    None => continue,
+                };
+            let header = StacksMicroblockHeader::consensus_deserialize(&mut &bytes[..])
+                .map_err(Error::CodecError)?;
+            if header.version == microblock.header.version {
+                return Ok(Some(header));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Record that `header_a` and `header_b` conflict.  Recording the same pair twice is a no-op.
+    fn record_microblock_equivocation<'a>(
+        tx: &mut DBTx<'a>,
+        parent_index_block_hash: &StacksBlockId,
+        header_a: &StacksMicroblockHeader,
+        header_b: &StacksMicroblockHeader,
+    ) -> Result<(), Error> {
+        let (header_1, header_2) = if header_a.block_hash() < header_b.block_hash() {
+            (header_a, header_b)
+        } else {
+            (header_b, header_a)
+        };
+        let sql = "INSERT OR IGNORE INTO microblock_equivocations (parent_index_block_hash, sequence, microblock_hash_1, microblock_hash_2, header_1, header_2, detected_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)";
+        let args: &[&dyn ToSql] = &[
+            parent_index_block_hash,
+            &header_1.sequence,
+            &header_1.block_hash(),
+            &header_2.block_hash(),
+            &to_hex(&header_1.serialize_to_vec()),
+            &to_hex(&header_2.serialize_to_vec()),
+            &u64_to_sql(get_epoch_time_secs())?,
+        ];
+        tx.execute(sql, args)
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        Ok(())
+    }
+
+    /// Load the microblock equivocations detected after the one with ID `after_id`, oldest first
+    pub fn get_microblock_equivocations_after(
+        blocks_conn: &DBConn,
+        after_id: i64,
+        limit: u32,
+    ) -> Result<Vec<MicroblockEquivocation>, Error> {
+        let sql = "SELECT rowid,* FROM microblock_equivocations WHERE rowid > ?1 ORDER BY rowid ASC LIMIT ?2";
+        let args: &[&dyn ToSql] = &[&after_id, &limit];
+        query_rows(blocks_conn, sql, args).map_err(Error::DBError)
+    }
+
+    /// Load the most recently detected microblock equivocations, newest first
+    pub fn get_recent_microblock_equivocations(
+        blocks_conn: &DBConn,
+        limit: u32,
+    ) -> Result<Vec<MicroblockEquivocation>, Error> {
+        let sql = "SELECT rowid,* FROM microblock_equivocations ORDER BY rowid DESC LIMIT ?1";
+        let args: &[&dyn ToSql] = &[&limit];
+        query_rows(blocks_conn, sql, args).map_err(Error::DBError)
+    }
+
+    /// Load the microblock equivocations in the stream off of the given anchored block, oldest
+    /// first
+    pub fn get_microblock_equivocations_for_block(
+        blocks_conn: &DBConn,
+        parent_index_block_hash: &StacksBlockId,
+    ) -> Result<Vec<MicroblockEquivocation>, Error> {
+        let sql = "SELECT rowid,* FROM microblock_equivocations WHERE parent_index_block_hash = ?1 ORDER BY rowid ASC";
+        let args: &[&dyn ToSql] = &[parent_index_block_hash];
+        query_rows(blocks_conn, sql, args).map_err(Error::DBError)
+    }
+
+    /// ID of the most recently detected microblock equivocation, or 0 if there are none
+    pub fn get_last_microblock_equivocation_id(blocks_conn: &DBConn) -> Result<i64, Error> {
+        let sql = "SELECT IFNULL(MAX(rowid), 0) FROM microblock_equivocations";
+        query_int(blocks_conn, sql, NO_PARAMS).map_err(Error::DBError)
+    }
+
     /// Load up a preprocessed microblock's staging info (processed or not), via its index
     /// microblock hash.
     /// Don't load the microblock itself.
@@ -2169,7 +2390,8 @@ impl StacksChainState {
                         )
                     );
                 } else {
-                    info!("Reject block {}/{}", consensus_hash, anchored_block_hash);
+                    info!("Reject block {}/{}", consensus_hash, anchored_block_hash;
+                          "block_hash" => %anchored_block_hash, "consensus_hash" => %consensus_hash);
                 }
             } else {
                 debug!(
@@ -3415,6 +3637,29 @@ impl StacksChainState {
             return Err(Error::InvalidStacksMicroblock(msg, microblock.block_hash()));
         }
 
+        // a leader that signs two different microblocks at the same place in its stream has
+        // forked it, and can be poisoned
+        if let Some(conflicting_header) = StacksChainState::find_conflicting_microblock(
+            &blocks_tx,
+            &parent_index_hash,
+            microblock,
+        )? {
+            warn!(
+                "Microblock equivocation: {} and {} both build off of {} at sequence {}",
+                microblock.block_hash(),
+                conflicting_header.block_hash(),
+                &microblock.header.prev_block,
+                microblock.header.sequence;
+                "parent_index_block_hash" => %parent_index_hash
+            );
+            StacksChainState::record_microblock_equivocation(
+                &mut blocks_tx,
+                &parent_index_hash,
+                &microblock.header,
+                &conflicting_header,
+            )?;
+        }
+
         // add to staging
         StacksChainState::store_staging_microblock(
             &mut blocks_tx,
@@ -3863,11 +4108,85 @@ impl StacksChainState {
         Ok(None)
     }
 
-    /// Process a stream of microblocks
+    /// Verify the signatures of the transactions in a block and in the microblocks it confirms,
+    /// across the configured number of threads, so they need not be verified one at a time as
+    /// the transactions are run.  Returns the txids of the transactions whose signatures are
+    /// valid.  The rest are verified again as they are run, so a block with a bad signature is
+    /// rejected at the same transaction, with the same error, as it otherwise would be.
+    pub fn verify_transaction_signatures(
+        block: &StacksBlock,
+        microblocks: &Vec<StacksMicroblock>,
+    ) -> HashSet<Txid> {
+        let num_txs = block.txs.len()
+            + microblocks
+                .iter()
+                .map(|mblock| mblock.txs.len())
+                .sum::<usize>();
+        let num_threads = cmp::min(
+            SIGNATURE_VERIFICATION_THREADS.load(Ordering::SeqCst),
+            num_txs,
+        );
+        if num_threads <= 1 {
+            return HashSet::new();
+        }
+
+        // the threads outlive this call as far as the borrow checker knows, so they get copies
+        let txs: Arc<Vec<StacksTransaction>> = Arc::new(
+            microblocks
+                .iter()
+                .flat_map(|mblock| mblock.txs.iter())
+                .chain(block.txs.iter())
+                .cloned()
+                .collect(),
+        );
+        let workers: Vec<_> = (0..num_threads)
+            .map(|worker| {
+                let txs = txs.clone();
+                thread::spawn(move || {
+                    txs.iter()
+                        .skip(worker)
+                        .step_by(num_threads)
+                        .filter(|tx| tx.verify().is_ok())
+                        .map(|tx| tx.txid())
+                        .collect::<Vec<Txid>>()
+                })
+            })
+            .collect();
+
+        let mut verified = HashSet::new();
+        for worker in workers.into_iter() {
+            match worker.join() {
+                Ok(txids) => verified.extend(txids),
+                Err(_) => {
+                    // its transactions will be verified as they are run
+                    warn!("Signature verification thread panicked");
+                }
+            }
+        }
+        verified
+    }
+
+    /// Run a transaction, skipping its signature check if it is one of the already-verified
+    /// ones
+    fn process_block_transaction(
+        clarity_tx: &mut ClarityTx,
+        tx: &StacksTransaction,
+        verified_txids: &HashSet<Txid>,
+    ) -> Result<(u64, StacksTransactionReceipt), Error> {
+        if verified_txids.contains(&tx.txid()) {
+            StacksChainState::process_verified_transaction(clarity_tx, tx, false)
+        } else {
+            StacksChainState::process_transaction(clarity_tx, tx, false)
+        }
+    }
+
+    /// Process a stream of microblocks.  Transactions in `verified_txids` have had their
+    /// signatures verified already.
     /// Return the fees and burns.
     pub fn process_microblocks_transactions(
         clarity_tx: &mut ClarityTx,
         microblocks: &Vec<StacksMicroblock>,
+        verified_txids: &HashSet<Txid>,
     ) -> Result<(u128, u128, Vec<StacksTransactionReceipt>), (Error, BlockHeaderHash)> {
         let mut fees = 0u128;
         let mut burns = 0u128;
@@ -3876,7 +4195,7 @@ impl StacksChainState {
             debug!("Process microblock {}", &microblock.block_hash());
             for tx in microblock.txs.iter() {
                 let (tx_fee, tx_receipt) =
-                    StacksChainState::process_transaction(clarity_tx, tx, false)
+                    StacksChainState::process_block_transaction(clarity_tx, tx, verified_txids)
                         .map_err(|e| (e, microblock.block_hash()))?;
 
                 fees = fees.checked_add(tx_fee as u128).expect("Fee overflow");
@@ -3946,6 +4265,7 @@ impl StacksChainState {
                             stx_burned: 0,
                             contract_analysis: None,
                             execution_cost,
+                            vm_error: None,
                         };
 
                         all_receipts.push(receipt);
@@ -3999,6 +4319,7 @@ impl StacksChainState {
                                 stx_burned: 0,
                                 contract_analysis: None,
                                 execution_cost: ExecutionCost::zero(),
+                                vm_error: None,
                             }),
                             Err(e) => {
                                 info!("TransferStx burn op processing error.";
@@ -4015,18 +4336,20 @@ impl StacksChainState {
         all_receipts
     }
 
-    /// Process a single anchored block.
+    /// Process a single anchored block.  Transactions in `verified_txids` have had their
+    /// signatures verified already.
     /// Return the fees and burns.
     fn process_block_transactions(
         clarity_tx: &mut ClarityTx,
         block: &StacksBlock,
+        verified_txids: &HashSet<Txid>,
     ) -> Result<(u128, u128, Vec<StacksTransactionReceipt>), Error> {
         let mut fees = 0u128;
         let mut burns = 0u128;
         let mut receipts = vec![];
         for tx in block.txs.iter() {
             let (tx_fee, tx_receipt) =
-                StacksChainState::process_transaction(clarity_tx, tx, false)?;
+                StacksChainState::process_block_transaction(clarity_tx, tx, verified_txids)?;
             fees = fees.checked_add(tx_fee as u128).expect("Fee overflow");
             burns = burns
                 .checked_add(tx_receipt.stx_burned as u128)
@@ -4178,6 +4501,221 @@ impl StacksChainState {
         Ok(parent_miner)
     }
 
+    /// Run a block's parent microblock stream, the burnchain operations that precede it, and its
+    /// anchored transactions in `clarity_tx`, on top of its parent's state, and then make the rest
+    /// of the block's state changes: granting matured miner rewards, unlocking STX, and recording
+    /// its microblock public key hash.  This is shared by `append_block()` and `replay_block()`.
+    /// On error, the caller must roll back `clarity_tx`.
+    fn process_block_state_changes(
+        clarity_tx: &mut ClarityTx,
+        parent_chain_tip: &StacksHeaderInfo,
+        chain_tip_consensus_hash: &ConsensusHash,
+        block: &StacksBlock,
+        microblocks: &Vec<StacksMicroblock>,
+        verified_txids: &HashSet<Txid>,
+        parent_block_cost: &ExecutionCost,
+        latest_matured_miners: Vec<MinerPaymentSchedule>,
+        matured_miner_parent: MinerPaymentSchedule,
+        stacking_burn_ops: Vec<StackStxOp>,
+        transfer_burn_ops: Vec<TransferStxOp>,
+    ) -> Result<BlockStateChanges, Error> {
+        clarity_tx.reset_cost(parent_block_cost.clone());
+
+        let matured_miner_rewards_opt = match StacksChainState::find_mature_miner_rewards(
+            clarity_tx,
+            parent_chain_tip,
+            latest_matured_miners,
+            matured_miner_parent,
+        ) {
+            Ok(miner_rewards_opt) => miner_rewards_opt,
+            Err(e) => {
+                let msg = format!("Failed to load miner rewards: {:?}", &e);
+                warn!("{}", &msg);
+                return Err(Error::InvalidStacksBlock(msg));
+            }
+        };
+
+        // validation check -- is this microblock public key hash new to this fork?  It must
+        // be, or this block is invalid.
+        match StacksChainState::has_microblock_pubkey_hash(
+            clarity_tx,
+            &block.header.microblock_pubkey_hash,
+        ) {
+            Ok(Some(height)) => {
+                // already used
+                let msg = format!(
+                    "Invalid stacks block {}/{} -- already used microblock pubkey hash {} at height {}",
+                    chain_tip_consensus_hash,
+                    block.block_hash(),
+                    &block.header.microblock_pubkey_hash,
+                    height
+                );
+                warn!("{}", &msg);
+                return Err(Error::InvalidStacksBlock(msg));
+            }
+            Ok(None) => {}
+            Err(e) => {
+                let msg = format!(
+                    "Failed to determine microblock if public key hash {} is used: {:?}",
+                    &block.header.microblock_pubkey_hash, &e
+                );
+                warn!("{}", &msg);
+                return Err(e);
+            }
+        }
+
+        // process microblock stream.
+        // If we go over-budget, then we can't process this block either (which is by design)
+        let (microblock_fees, microblock_burns, microblock_txs_receipts) =
+            match StacksChainState::process_microblocks_transactions(
+                clarity_tx,
+                &microblocks,
+                verified_txids,
+            ) {
+                Err((e, offending_mblock_header_hash)) => {
+                    let msg = format!(
+                        "Invalid Stacks microblocks {},{} (offender {}): {:?}",
+                        block.header.parent_microblock,
+                        block.header.parent_microblock_sequence,
+                        offending_mblock_header_hash,
+                        &e
+                    );
+                    warn!("{}", &msg);
+                    return Err(Error::InvalidStacksMicroblock(
+                        msg,
+                        offending_mblock_header_hash,
+                    ));
+                }
+                Ok((fees, burns, events)) => (fees, burns, events),
+            };
+
+        // find microblock cost
+        let mut microblock_cost = clarity_tx.cost_so_far();
+        microblock_cost
+            .sub(parent_block_cost)
+            .expect("BUG: block_cost + microblock_cost < block_cost");
+
+        // if we get here, then we need to reset the block-cost back to 0 since this begins the
+        // epoch defined by this miner.
+        clarity_tx.reset_cost(ExecutionCost::zero());
+
+        // process stacking operations from bitcoin ops
+        let mut receipts = StacksChainState::process_stacking_ops(clarity_tx, stacking_burn_ops);
+
+        receipts.extend(StacksChainState::process_transfer_ops(
+            clarity_tx,
+            transfer_burn_ops,
+        ));
+
+        // process anchored block
+        let (block_fees, block_burns, txs_receipts) =
+            match StacksChainState::process_block_transactions(clarity_tx, &block, verified_txids) {
+                Err(e) => {
+                    let msg = format!("Invalid Stacks block {}: {:?}", block.block_hash(), &e);
+                    warn!("{}", &msg);
+                    return Err(Error::InvalidStacksBlock(msg));
+                }
+                Ok((block_fees, block_burns, txs_receipts)) => {
+                    (block_fees, block_burns, txs_receipts)
+                }
+            };
+
+        receipts.extend(txs_receipts.into_iter());
+
+        let block_cost = clarity_tx.cost_so_far();
+
+        // grant matured miner rewards
+        let new_liquid_miner_ustx =
+            if let Some((ref miner_reward, ref user_rewards, ref parent_miner_reward, _)) =
+                matured_miner_rewards_opt.as_ref()
+            {
+                // grant in order by miner, then users
+                StacksChainState::process_matured_miner_rewards(
+                    clarity_tx,
+                    miner_reward,
+                    user_rewards,
+                    parent_miner_reward,
+                )?
+            } else {
+                0
+            };
+
+        clarity_tx.increment_ustx_liquid_supply(new_liquid_miner_ustx);
+
+        // obtain reward info for receipt
+        let (matured_rewards, matured_rewards_info) =
+            if let Some((miner_reward, mut user_rewards, parent_reward, reward_ptr)) =
+                matured_miner_rewards_opt
+            {
+                let mut ret = vec![];
+                ret.push(miner_reward);
+                ret.append(&mut user_rewards);
+                ret.push(parent_reward);
+                (ret, Some(reward_ptr))
+            } else {
+                (vec![], None)
+            };
+
+        // total burns
+        let total_burnt = block_burns
+            .checked_add(microblock_burns)
+            .expect("Overflow: Too many STX burnt");
+
+        // unlock any uSTX
+        let (new_unlocked_ustx, mut lockup_events) =
+            StacksChainState::process_stx_unlocks(clarity_tx)?;
+
+        // if any, append lockups events to the coinbase receipt
+        if lockup_events.len() > 0 {
+            // Receipts are appended in order, so the first receipt should be
+            // the one of the coinbase transaction
+            if let Some(receipt) = receipts.get_mut(0) {
+                if receipt.is_coinbase_tx() {
+                    receipt.events.append(&mut lockup_events);
+                }
+            } else {
+                warn!("Unable to attach lockups events, first block's transaction is not a coinbase transaction")
+            }
+        }
+
+        clarity_tx.increment_ustx_liquid_supply(new_unlocked_ustx);
+
+        // record that this microblock public key hash was used at this height
+        match StacksChainState::insert_microblock_pubkey_hash(
+            clarity_tx,
+            block.header.total_work.work as u32,
+            &block.header.microblock_pubkey_hash,
+        ) {
+            Ok(_) => {
+                debug!(
+                    "Added microblock public key {} at height {}",
+                    &block.header.microblock_pubkey_hash, block.header.total_work.work
+                );
+            }
+            Err(e) => {
+                let msg = format!(
+                    "Failed to insert microblock pubkey hash {} at height {}: {:?}",
+                    &block.header.microblock_pubkey_hash, block.header.total_work.work, &e
+                );
+                warn!("{}", &msg);
+                return Err(Error::InvalidStacksBlock(msg));
+            }
+        };
+
+        receipts.extend(microblock_txs_receipts.into_iter());
+
+        Ok(BlockStateChanges {
+            tx_receipts: receipts,
+            microblock_fees,
+            block_fees,
+            total_burnt,
+            microblock_cost,
+            block_cost,
+            matured_rewards,
+            matured_rewards_info,
+        })
+    }
+
     /// Process the next pre-processed staging block.
     /// We've already processed parent_chain_tip.  chain_tip refers to a block we have _not_
     /// processed yet.
@@ -4212,6 +4750,9 @@ impl StacksChainState {
         let mainnet = chainstate_tx.get_config().mainnet;
         let next_block_height = block.header.total_work.work;
 
+        // check signatures up front, in parallel, instead of one at a time as transactions run
+        let verified_txids = StacksChainState::verify_transaction_signatures(block, microblocks);
+
         // find matured miner rewards, so we can grant them within the Clarity DB tx.
         let latest_matured_miners = StacksChainState::get_scheduled_block_rewards(
             chainstate_tx.deref_mut(),
@@ -4299,6 +4840,15 @@ impl StacksChainState {
                 parent_consensus_hash, parent_block_hash
             ));
 
+            debug!("Append block";
+                   "block" => %format!("{}/{}", chain_tip_consensus_hash, block.block_hash()),
+                   "parent_block" => %format!("{}/{}", parent_consensus_hash, parent_block_hash),
+                   "stacks_height" => %block.header.total_work.work,
+                   "total_burns" => %block.header.total_work.burn,
+                   "microblock_parent" => %last_microblock_hash,
+                   "microblock_parent_seq" => %last_microblock_seq,
+                   "microblock_parent_count" => %microblocks.len());
+
             let mut clarity_tx = StacksChainState::chainstate_block_begin(
                 chainstate_tx,
                 clarity_instance,
@@ -4313,207 +4863,24 @@ impl StacksChainState {
                 "Parent block {}/{} cost {:?}",
                 &parent_consensus_hash, &parent_block_hash, &parent_block_cost
             );
-            clarity_tx.reset_cost(parent_block_cost.clone());
 
-            let matured_miner_rewards_opt = match StacksChainState::find_mature_miner_rewards(
+            let block_results = match StacksChainState::process_block_state_changes(
                 &mut clarity_tx,
                 parent_chain_tip,
+                chain_tip_consensus_hash,
+                block,
+                microblocks,
+                &verified_txids,
+                &parent_block_cost,
                 latest_matured_miners,
                 matured_miner_parent,
-            ) {
-                Ok(miner_rewards_opt) => miner_rewards_opt,
-                Err(e) => {
-                    let msg = format!("Failed to load miner rewards: {:?}", &e);
-                    warn!("{}", &msg);
-
-                    clarity_tx.rollback_block();
-                    return Err(Error::InvalidStacksBlock(msg));
-                }
-            };
-
-            // validation check -- is this microblock public key hash new to this fork?  It must
-            // be, or this block is invalid.
-            match StacksChainState::has_microblock_pubkey_hash(
-                &mut clarity_tx,
-                &block.header.microblock_pubkey_hash,
-            ) {
-                Ok(Some(height)) => {
-                    // already used
-                    let msg = format!(
-                        "Invalid stacks block {}/{} -- already used microblock pubkey hash {} at height {}",
-                        chain_tip_consensus_hash,
-                        block.block_hash(),
-                        &block.header.microblock_pubkey_hash,
-                        height
-                    );
-                    warn!("{}", &msg);
-
-                    clarity_tx.rollback_block();
-                    return Err(Error::InvalidStacksBlock(msg));
-                }
-                Ok(None) => {}
-                Err(e) => {
-                    let msg = format!(
-                        "Failed to determine microblock if public key hash {} is used: {:?}",
-                        &block.header.microblock_pubkey_hash, &e
-                    );
-                    warn!("{}", &msg);
-
-                    clarity_tx.rollback_block();
-                    return Err(e);
-                }
-            }
-
-            // process microblock stream.
-            // If we go over-budget, then we can't process this block either (which is by design)
-            let (microblock_fees, microblock_burns, microblock_txs_receipts) =
-                match StacksChainState::process_microblocks_transactions(
-                    &mut clarity_tx,
-                    &microblocks,
-                ) {
-                    Err((e, offending_mblock_header_hash)) => {
-                        let msg = format!(
-                            "Invalid Stacks microblocks {},{} (offender {}): {:?}",
-                            block.header.parent_microblock,
-                            block.header.parent_microblock_sequence,
-                            offending_mblock_header_hash,
-                            &e
-                        );
-                        warn!("{}", &msg);
-
-                        clarity_tx.rollback_block();
-                        return Err(Error::InvalidStacksMicroblock(
-                            msg,
-                            offending_mblock_header_hash,
-                        ));
-                    }
-                    Ok((fees, burns, events)) => (fees, burns, events),
-                };
-
-            // find microblock cost
-            let mut microblock_cost = clarity_tx.cost_so_far();
-            microblock_cost
-                .sub(&parent_block_cost)
-                .expect("BUG: block_cost + microblock_cost < block_cost");
-
-            // if we get here, then we need to reset the block-cost back to 0 since this begins the
-            // epoch defined by this miner.
-            clarity_tx.reset_cost(ExecutionCost::zero());
-
-            debug!("Append block";
-                   "block" => %format!("{}/{}", chain_tip_consensus_hash, block.block_hash()),
-                   "parent_block" => %format!("{}/{}", parent_consensus_hash, parent_block_hash),
-                   "stacks_height" => %block.header.total_work.work,
-                   "total_burns" => %block.header.total_work.burn,
-                   "microblock_parent" => %last_microblock_hash,
-                   "microblock_parent_seq" => %last_microblock_seq,
-                   "microblock_parent_count" => %microblocks.len());
-
-            // process stacking operations from bitcoin ops
-            let mut receipts =
-                StacksChainState::process_stacking_ops(&mut clarity_tx, stacking_burn_ops);
-
-            receipts.extend(StacksChainState::process_transfer_ops(
-                &mut clarity_tx,
+                stacking_burn_ops,
                 transfer_burn_ops,
-            ));
-
-            // process anchored block
-            let (block_fees, block_burns, txs_receipts) =
-                match StacksChainState::process_block_transactions(&mut clarity_tx, &block) {
-                    Err(e) => {
-                        let msg = format!("Invalid Stacks block {}: {:?}", block.block_hash(), &e);
-                        warn!("{}", &msg);
-
-                        clarity_tx.rollback_block();
-                        return Err(Error::InvalidStacksBlock(msg));
-                    }
-                    Ok((block_fees, block_burns, txs_receipts)) => {
-                        (block_fees, block_burns, txs_receipts)
-                    }
-                };
-
-            receipts.extend(txs_receipts.into_iter());
-
-            let block_cost = clarity_tx.cost_so_far();
-
-            // grant matured miner rewards
-            let new_liquid_miner_ustx =
-                if let Some((ref miner_reward, ref user_rewards, ref parent_miner_reward, _)) =
-                    matured_miner_rewards_opt.as_ref()
-                {
-                    // grant in order by miner, then users
-                    StacksChainState::process_matured_miner_rewards(
-                        &mut clarity_tx,
-                        miner_reward,
-                        user_rewards,
-                        parent_miner_reward,
-                    )?
-                } else {
-                    0
-                };
-
-            clarity_tx.increment_ustx_liquid_supply(new_liquid_miner_ustx);
-
-            // obtain reward info for receipt
-            let (matured_rewards, matured_rewards_info) =
-                if let Some((miner_reward, mut user_rewards, parent_reward, reward_ptr)) =
-                    matured_miner_rewards_opt
-                {
-                    let mut ret = vec![];
-                    ret.push(miner_reward);
-                    ret.append(&mut user_rewards);
-                    ret.push(parent_reward);
-                    (ret, Some(reward_ptr))
-                } else {
-                    (vec![], None)
-                };
-
-            // total burns
-            let total_burnt = block_burns
-                .checked_add(microblock_burns)
-                .expect("Overflow: Too many STX burnt");
-
-            // unlock any uSTX
-            let (new_unlocked_ustx, mut lockup_events) =
-                StacksChainState::process_stx_unlocks(&mut clarity_tx)?;
-
-            // if any, append lockups events to the coinbase receipt
-            if lockup_events.len() > 0 {
-                // Receipts are appended in order, so the first receipt should be
-                // the one of the coinbase transaction
-                if let Some(receipt) = receipts.get_mut(0) {
-                    if receipt.is_coinbase_tx() {
-                        receipt.events.append(&mut lockup_events);
-                    }
-                } else {
-                    warn!("Unable to attach lockups events, first block's transaction is not a coinbase transaction")
-                }
-            }
-
-            clarity_tx.increment_ustx_liquid_supply(new_unlocked_ustx);
-
-            // record that this microblock public key hash was used at this height
-            match StacksChainState::insert_microblock_pubkey_hash(
-                &mut clarity_tx,
-                block.header.total_work.work as u32,
-                &block.header.microblock_pubkey_hash,
             ) {
-                Ok(_) => {
-                    debug!(
-                        "Added microblock public key {} at height {}",
-                        &block.header.microblock_pubkey_hash, block.header.total_work.work
-                    );
-                }
+                Ok(block_results) => block_results,
                 Err(e) => {
-                    let msg = format!(
-                        "Failed to insert microblock pubkey hash {} at height {}: {:?}",
-                        &block.header.microblock_pubkey_hash, block.header.total_work.work, &e
-                    );
-                    warn!("{}", &msg);
-
                     clarity_tx.rollback_block();
-                    return Err(Error::InvalidStacksBlock(msg));
+                    return Err(e);
                 }
             };
 
@@ -4532,8 +4899,8 @@ impl StacksChainState {
             }
 
             debug!("Reached state root {}", root_hash;
-                   "microblock cost" => %microblock_cost,
-                   "block cost" => %block_cost);
+                   "microblock cost" => %block_results.microblock_cost,
+                   "block cost" => %block_results.block_cost);
 
             // good to go!
             clarity_tx.commit_to_block(chain_tip_consensus_hash, &block.block_hash());
@@ -4562,24 +4929,22 @@ impl StacksChainState {
                 &block,
                 chain_tip_consensus_hash,
                 next_block_height,
-                block_fees,
-                microblock_fees,
-                total_burnt,
+                block_results.block_fees,
+                block_results.microblock_fees,
+                block_results.total_burnt,
                 burnchain_commit_burn,
                 burnchain_sortition_burn,
                 total_coinbase,
             )
             .expect("FATAL: parsed and processed a block without a coinbase");
 
-            receipts.extend(microblock_txs_receipts.into_iter());
-
             (
                 scheduled_miner_reward,
-                receipts,
-                microblock_cost,
-                block_cost,
-                matured_rewards,
-                matured_rewards_info,
+                block_results.tx_receipts,
+                block_results.microblock_cost,
+                block_results.block_cost,
+                block_results.matured_rewards,
+                block_results.matured_rewards_info,
             )
         };
 
@@ -4588,35 +4953,249 @@ impl StacksChainState {
             x => Some(microblocks[x - 1].header.clone()),
         };
 
-        let new_tip = StacksChainState::advance_tip(
-            &mut chainstate_tx.tx,
-            &parent_chain_tip.anchored_header,
-            &parent_chain_tip.consensus_hash,
-            &block.header,
-            chain_tip_consensus_hash,
-            chain_tip_burn_header_hash,
-            chain_tip_burn_header_height,
-            chain_tip_burn_header_timestamp,
-            microblock_tail_opt,
-            &scheduled_miner_reward,
-            user_burns,
-            &block_execution_cost,
-            block_size,
-        )
-        .expect("FATAL: failed to advance chain tip");
+        let new_tip = StacksChainState::advance_tip(
+            &mut chainstate_tx.tx,
+            &parent_chain_tip.anchored_header,
+            &parent_chain_tip.consensus_hash,
+            &block.header,
+            chain_tip_consensus_hash,
+            chain_tip_burn_header_hash,
+            chain_tip_burn_header_height,
+            chain_tip_burn_header_timestamp,
+            microblock_tail_opt,
+            &scheduled_miner_reward,
+            user_burns,
+            &block_execution_cost,
+            block_size,
+        )
+        .expect("FATAL: failed to advance chain tip");
+
+        chainstate_tx.log_transactions_processed(&new_tip.index_block_hash(), &tx_receipts);
+        chainstate_tx
+            .index_pox_delegations(&new_tip.index_block_hash(), &tx_receipts)
+            .expect("FATAL: failed to index PoX delegations");
+        chainstate_tx
+            .index_account_transactions(
+                &new_tip.index_block_hash(),
+                new_tip.block_height,
+                &tx_receipts,
+            )
+            .expect("FATAL: failed to index account transactions");
+        chainstate_tx
+            .index_transaction_receipts(
+                &new_tip.index_block_hash(),
+                new_tip.block_height,
+                &tx_receipts,
+            )
+            .expect("FATAL: failed to index transaction receipts");
+        chainstate_tx
+            .index_print_events(
+                &new_tip.index_block_hash(),
+                new_tip.block_height,
+                &tx_receipts,
+            )
+            .expect("FATAL: failed to index print events");
+        chainstate_tx
+            .finish_caught_up_migration()
+            .expect("FATAL: failed to check on the chainstate migration");
+
+        let epoch_receipt = StacksEpochReceipt {
+            header: new_tip,
+            tx_receipts,
+            matured_rewards,
+            matured_rewards_info,
+            parent_microblocks_cost: microblock_execution_cost,
+            anchored_block_cost: block_execution_cost,
+        };
+
+        Ok(epoch_receipt)
+    }
+
+    /// Copy this chainstate's Clarity MARF into `scratch_dir`, and open the copy.  Blocks can then
+    /// be re-executed against the copy with `replay_block()`, without writing to this chainstate.
+    /// Anything already at `scratch_dir` is replaced.
+    pub fn open_scratch_clarity_state(
+        &mut self,
+        scratch_dir: &str,
+    ) -> Result<ClarityInstance, Error> {
+        let scratch_marf_path = PathBuf::from(scratch_dir).join("marf.sqlite");
+        let scratch_marf_path_str = scratch_marf_path
+            .to_str()
+            .ok_or_else(|| Error::DBError(db_error::ParseError))?
+            .to_string();
+        if fs::metadata(&scratch_marf_path).is_ok() {
+            fs::remove_file(&scratch_marf_path)
+                .map_err(|e| Error::DBError(db_error::IOError(e)))?;
+        }
+        fs::create_dir_all(scratch_dir).map_err(|e| Error::DBError(db_error::IOError(e)))?;
+
+        // VACUUM INTO copies a consistent snapshot, even while the node has it open
+        self.clarity_state
+            .with_marf(|marf| {
+                marf.sqlite_conn()
+                    .execute("VACUUM INTO ?1", &[&scratch_marf_path_str])
+            })
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+
+        let vm_state =
+            MarfedKV::open(scratch_dir, None).map_err(|e| Error::ClarityError(e.into()))?;
+        Ok(ClarityInstance::new(
+            self.mainnet,
+            vm_state,
+            self.block_limit.clone(),
+        ))
+    }
+
+    /// Re-execute an already-processed anchored block, along with the parent microblocks it
+    /// confirms, on top of its parent's state in `scratch_clarity_state` -- a copy of this
+    /// chainstate's Clarity MARF (see `open_scratch_clarity_state()`).  The block's state changes
+    /// are made the same way `append_block()` makes them, and then rolled back from the scratch
+    /// MARF; nothing is written to this chainstate.
+    pub fn replay_block(
+        &mut self,
+        sortdb: &SortitionDB,
+        scratch_clarity_state: &mut ClarityInstance,
+        index_block_hash: &StacksBlockId,
+    ) -> Result<StacksBlockReplayReceipt, Error> {
+        let mainnet = self.mainnet;
+        let header_info = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            self.db(),
+            index_block_hash,
+        )?
+        .ok_or(Error::NoSuchBlockError)?;
+        let parent_block_id = StacksChainState::get_parent_block_id(self.db(), index_block_hash)?
+            .ok_or(Error::NoSuchBlockError)?;
+        let parent_chain_tip = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            self.db(),
+            &parent_block_id,
+        )?
+        .ok_or(Error::NoSuchBlockError)?;
+
+        let block = StacksChainState::load_block(
+            &self.blocks_path,
+            &header_info.consensus_hash,
+            &header_info.anchored_header.block_hash(),
+        )?
+        .ok_or(Error::NoSuchBlockError)?;
+
+        let (parent_consensus_hash, parent_block_hash) = if block.is_first_mined() {
+            (
+                FIRST_BURNCHAIN_CONSENSUS_HASH.clone(),
+                FIRST_STACKS_BLOCK_HASH.clone(),
+            )
+        } else {
+            (
+                parent_chain_tip.consensus_hash.clone(),
+                parent_chain_tip.anchored_header.block_hash(),
+            )
+        };
+
+        let microblocks = if block.header.parent_microblock == EMPTY_MICROBLOCK_PARENT_HASH {
+            vec![]
+        } else {
+            StacksChainState::load_processed_microblock_stream_fork(
+                self.db(),
+                &parent_consensus_hash,
+                &parent_block_hash,
+                &block.header.parent_microblock,
+            )?
+            .ok_or(Error::NoSuchBlockError)?
+        };
+
+        let parent_burn_hash =
+            SortitionDB::get_block_snapshot_consensus(sortdb.conn(), &header_info.consensus_hash)?
+                .ok_or(Error::NoSuchBlockError)?
+                .parent_burn_header_hash;
+        let stacking_burn_ops = SortitionDB::get_stack_stx_ops(sortdb.conn(), &parent_burn_hash)?;
+        let transfer_burn_ops =
+            SortitionDB::get_transfer_stx_ops(sortdb.conn(), &parent_burn_hash)?;
+
+        let verified_txids = StacksChainState::verify_transaction_signatures(&block, &microblocks);
+
+        let burn_dbconn = sortdb.index_conn();
+
+        // only read from the headers DB; the transaction is dropped (rolled back) at the end
+        let (mut chainstate_tx, _) = self.chainstate_tx_begin()?;
+
+        let latest_matured_miners = StacksChainState::get_scheduled_block_rewards(
+            chainstate_tx.deref_mut(),
+            &parent_chain_tip,
+        )?;
+        let matured_miner_parent = StacksChainState::get_parent_matured_miner(
+            chainstate_tx.deref_mut(),
+            mainnet,
+            &latest_matured_miners,
+        )?;
+        let parent_block_cost = StacksChainState::get_stacks_block_anchored_cost(
+            &chainstate_tx.deref().deref(),
+            &StacksBlockHeader::make_index_block_hash(&parent_consensus_hash, &parent_block_hash),
+        )?
+        .ok_or(Error::NoSuchBlockError)?;
+
+        let mut clarity_tx = StacksChainState::chainstate_block_begin(
+            &chainstate_tx,
+            scratch_clarity_state,
+            &burn_dbconn,
+            &parent_consensus_hash,
+            &parent_block_hash,
+            &MINER_BLOCK_CONSENSUS_HASH,
+            &MINER_BLOCK_HEADER_HASH,
+        );
+
+        let block_results = match StacksChainState::process_block_state_changes(
+            &mut clarity_tx,
+            &parent_chain_tip,
+            &header_info.consensus_hash,
+            &block,
+            &microblocks,
+            &verified_txids,
+            &parent_block_cost,
+            latest_matured_miners,
+            matured_miner_parent,
+            stacking_burn_ops,
+            transfer_burn_ops,
+        ) {
+            Ok(block_results) => block_results,
+            Err(e) => {
+                clarity_tx.rollback_block();
+                return Err(e);
+            }
+        };
+
+        let state_root = clarity_tx.get_root_hash();
+        clarity_tx.rollback_block();
 
-        chainstate_tx.log_transactions_processed(&new_tip.index_block_hash(), &tx_receipts);
+        Ok(StacksBlockReplayReceipt {
+            tx_receipts: block_results.tx_receipts,
+            parent_microblocks_cost: block_results.microblock_cost,
+            anchored_block_cost: block_results.block_cost,
+            state_root,
+        })
+    }
 
-        let epoch_receipt = StacksEpochReceipt {
-            header: new_tip,
-            tx_receipts,
-            matured_rewards,
-            matured_rewards_info,
-            parent_microblocks_cost: microblock_execution_cost,
-            anchored_block_cost: block_execution_cost,
-        };
+    /// Get the results of the transactions in a block, as recorded in the transaction log.
+    /// Empty unless the node that processed the block ran with `STACKS_TRANSACTION_LOG` set.
+    pub fn get_logged_transaction_results(
+        conn: &Connection,
+        index_block_hash: &StacksBlockId,
+    ) -> Result<HashMap<Txid, String>, Error> {
+        let mut stmt = conn
+            .prepare("SELECT txid, result FROM transactions WHERE index_block_hash = ?1")
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        let mut rows = stmt
+            .query(&[index_block_hash])
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
 
-        Ok(epoch_receipt)
+        let mut results = HashMap::new();
+        while let Some(row) = rows
+            .next()
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?
+        {
+            let txid: Txid = row.get_unwrap(0);
+            let result: String = row.get_unwrap(1);
+            results.insert(txid, result);
+        }
+        Ok(results)
     }
 
     /// Verify that a Stacks anchored block attaches to its parent anchored block.
@@ -4781,6 +5360,10 @@ impl StacksChainState {
                 }
             };
 
+        let mut span = monitoring::trace_span("chainstate.process_block");
+        span.set_attribute("block_hash", &next_staging_block.anchored_block_hash);
+        span.set_attribute("consensus_hash", &next_staging_block.consensus_hash);
+
         let (burn_header_hash, burn_header_height, burn_header_timestamp) =
             match SortitionDB::get_block_snapshot_consensus(
                 sort_tx,
@@ -4805,7 +5388,9 @@ impl StacksChainState {
             next_staging_block.consensus_hash,
             next_staging_block.anchored_block_hash,
             &burn_header_hash,
-            &next_staging_block.parent_microblock_hash,
+            &next_staging_block.parent_microblock_hash;
+            "block_hash" => %next_staging_block.anchored_block_hash,
+            "consensus_hash" => %next_staging_block.consensus_hash
         );
 
         let parent_header_info = match StacksChainState::get_parent_header_info(
@@ -4857,7 +5442,9 @@ impl StacksChainState {
                 parent_header_info.anchored_header.block_hash(),
                 &parent_header_info.consensus_hash
             );
-            warn!("{}", &msg);
+            warn!("{}", &msg;
+                  "block_hash" => %next_staging_block.anchored_block_hash,
+                  "consensus_hash" => %next_staging_block.consensus_hash);
 
             // clear out
             StacksChainState::set_block_processed(
@@ -4910,6 +5497,7 @@ impl StacksChainState {
         // attach the block to the chain state and calculate the next chain tip.
         // Execute the confirmed microblocks' transactions against the chain state, and then
         // execute the anchored block's transactions against the chain state.
+        profiler::begin_block();
         let epoch_receipt = match StacksChainState::append_block(
             &mut chainstate_tx,
             clarity_instance,
@@ -4926,8 +5514,16 @@ impl StacksChainState {
             next_staging_block.sortition_burn,
             &user_supports,
         ) {
-            Ok(next_chain_tip_info) => next_chain_tip_info,
+            Ok(next_chain_tip_info) => {
+                profiler::finish_block(
+                    &next_chain_tip_info.header.index_block_hash(),
+                    next_chain_tip_info.header.block_height,
+                );
+                next_chain_tip_info
+            }
             Err(e) => {
+                profiler::discard_block();
+
                 // something's wrong with this epoch -- either a microblock was invalid, or the
                 // anchored block was invalid.  Either way, the anchored block will _never be_
                 // valid, so we can drop it from the chunk store and orphan all of its descendants.
@@ -5007,7 +5603,9 @@ impl StacksChainState {
             epoch_receipt.header.consensus_hash,
             epoch_receipt.header.anchored_header.block_hash(),
             next_staging_block.parent_consensus_hash,
-            next_staging_block.parent_anchored_block_hash
+            next_staging_block.parent_anchored_block_hash;
+            "block_hash" => %epoch_receipt.header.anchored_header.block_hash(),
+            "consensus_hash" => %epoch_receipt.header.consensus_hash
         );
 
         if next_staging_block.parent_microblock_hash != EMPTY_MICROBLOCK_PARENT_HASH
@@ -5187,6 +5785,33 @@ impl StacksChainState {
         }
     }
 
+    /// Load an account's nonce and the STX it can spend as of a given chain tip, for the mempool's
+    /// admission checks.
+    pub fn get_mempool_account_funds(
+        &mut self,
+        current_consensus_hash: &ConsensusHash,
+        current_block: &BlockHeaderHash,
+        principal: &PrincipalData,
+    ) -> Result<(u64, u128), MemPoolRejection> {
+        let current_tip =
+            StacksChainState::get_parent_index_block(current_consensus_hash, current_block);
+        self.with_read_only_clarity_tx(&NULL_BURN_STATE_DB, &current_tip, |conn| {
+            let account = StacksChainState::get_account(conn, principal);
+            let block_height = conn.with_clarity_db_readonly(|ref mut db| {
+                db.get_current_burnchain_block_height() as u64
+            });
+            (
+                account.nonce,
+                account
+                    .stx_balance
+                    .get_available_balance_at_burn_block(block_height),
+            )
+        })
+        .ok_or_else(|| {
+            MemPoolRejection::NoSuchChainTip(current_consensus_hash.clone(), current_block.clone())
+        })
+    }
+
     /// Check to see if a transaction can be (potentially) appended on top of a given chain tip.
     /// Note that this only checks the transaction against the _anchored chain tip_, not the
     /// unconfirmed microblock stream trailing off of it.
@@ -5280,6 +5905,113 @@ impl StacksChainState {
         }
     }
 
+    /// Check a smart contract against the node's limits on the contracts it takes into its
+    /// mempool.  Parsing and analyzing the contract is charged against `limits`, so a contract past
+    /// the analysis cost limit costs no more than the limit to turn away.  A contract that fails
+    /// to parse or analyze for any other reason is left for mining to deal with, the same as it
+    /// would be without limits.
+    pub fn check_contract_deploy_limits(
+        &mut self,
+        current_consensus_hash: &ConsensusHash,
+        current_block: &BlockHeaderHash,
+        tx: &StacksTransaction,
+        limits: &ContractDeployLimits,
+    ) -> Result<(), MemPoolRejection> {
+        let contract = match tx.payload {
+            TransactionPayload::SmartContract(ref contract) => contract,
+            _ => return Ok(()),
+        };
+        let code = contract.code_body.to_string();
+        if limits.max_len > 0 && code.len() as u64 > limits.max_len {
+            return Err(MemPoolRejection::ContractDeployLimitExceeded(
+                "size",
+                code.len() as u64,
+                limits.max_len,
+            ));
+        }
+        if limits.max_depth == 0 && limits.max_analysis_runtime == 0 {
+            return Ok(());
+        }
+
+        let contract_identifier =
+            QualifiedContractIdentifier::new(tx.origin_address().into(), contract.name.clone());
+        let mainnet = self.mainnet;
+        let current_tip =
+            StacksChainState::get_parent_index_block(current_consensus_hash, current_block);
+        self.with_read_only_clarity_tx(&NULL_BURN_STATE_DB, &current_tip, |conn| {
+            let mut cost_track = if limits.max_analysis_runtime > 0 {
+                let limit = ExecutionCost {
+                    runtime: limits.max_analysis_runtime,
+                    ..ExecutionCost::max_value()
+                };
+                conn.with_clarity_db_readonly(|clarity_db| {
+                    LimitedCostTracker::new_mid_block(mainnet, limit, clarity_db)
+                })
+                .map_err(|e| {
+                    MemPoolRejection::Other(format!("Failed to load cost functions: {:?}", &e))
+                })?
+            } else {
+                LimitedCostTracker::new_free()
+            };
+
+            let mut contract_ast = match build_ast(&contract_identifier, &code, &mut cost_track) {
+                Ok(contract_ast) => contract_ast,
+                Err(e) => {
+                    return match e.err {
+                        ParseErrors::CostBalanceExceeded(total, _) => {
+                            Err(MemPoolRejection::ContractDeployLimitExceeded(
+                                "analysis_cost",
+                                total.runtime,
+                                limits.max_analysis_runtime,
+                            ))
+                        }
+                        _ => Ok(()),
+                    };
+                }
+            };
+
+            let depth = expressions_depth(&contract_ast.expressions);
+            if limits.max_depth > 0 && depth > limits.max_depth {
+                return Err(MemPoolRejection::ContractDeployLimitExceeded(
+                    "depth",
+                    depth,
+                    limits.max_depth,
+                ));
+            }
+            if limits.max_analysis_runtime == 0 {
+                return Ok(());
+            }
+
+            conn.with_analysis_db_readonly(|db| {
+                match run_analysis(
+                    &contract_identifier,
+                    &mut contract_ast.expressions,
+                    db,
+                    false,
+                    cost_track,
+                ) {
+                    Ok(_) => Ok(()),
+                    Err((e, _)) => match e.err {
+                        CheckErrors::CostBalanceExceeded(total, _) => {
+                            Err(MemPoolRejection::ContractDeployLimitExceeded(
+                                "analysis_cost",
+                                total.runtime,
+                                limits.max_analysis_runtime,
+                            ))
+                        }
+                        _ => Ok(()),
+                    },
+                }
+            })
+        })
+        .unwrap_or_else(|| {
+            Err(MemPoolRejection::NoSuchChainTip(
+                current_consensus_hash.clone(),
+                current_block.clone(),
+            ))
+        })
+    }
+
     /// Given an outstanding clarity connection, can we append the tx to the chain state?
     /// Used when mining transactions.
     fn can_include_tx<T: ClarityConnection>(
@@ -5703,6 +6435,7 @@ pub mod test {
                 .unwrap(),
             );
 
+            tx_big_contract.chain_id = 0x80000000;
             tx_big_contract.anchor_mode = TransactionAnchorMode::OffChainOnly;
             let mut tx_signer = StacksTransactionSigner::new(&tx_big_contract);
             tx_signer.sign_origin(&privk).unwrap();
@@ -9078,6 +9811,35 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_verify_transaction_signatures() {
+        let privk = StacksPrivateKey::new();
+        let mut block = make_empty_coinbase_block(&privk);
+        let microblocks = make_sample_microblock_stream(&privk, &block.block_hash());
+
+        // a signed transaction whose fee was changed after it was signed
+        let mut tampered = microblocks[0].txs[0].clone();
+        tampered.set_tx_fee(tampered.get_tx_fee() + 1);
+        assert!(tampered.verify().is_err());
+        block.txs.push(tampered.clone());
+
+        let verified = StacksChainState::verify_transaction_signatures(&block, &microblocks);
+        assert!(verified.contains(&block.txs[0].txid()));
+        for mblock in microblocks.iter() {
+            for tx in mblock.txs.iter() {
+                assert!(verified.contains(&tx.txid()));
+            }
+        }
+        assert!(!verified.contains(&tampered.txid()));
+        assert_eq!(
+            verified.len(),
+            1 + microblocks
+                .iter()
+                .map(|mblock| mblock.txs.len())
+                .sum::<usize>()
+        );
+    }
+
     #[test]
     fn test_get_parent_block_header() {
         let peer_config = TestPeerConfig::new("test_get_parent_block_header", 21313, 21314);
@@ -9204,6 +9966,114 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_replay_block() {
+        let peer_config = TestPeerConfig::new("test_replay_block", 21315, 21316);
+        let mut peer = TestPeer::new(peer_config);
+
+        let chainstate_path = peer.chainstate_path.clone();
+        let scratch_path = format!("{}.replay-scratch", &chainstate_path);
+
+        let num_blocks = 5;
+        for tenure_id in 0..num_blocks {
+            let tip =
+                SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+                    .unwrap();
+
+            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+                |ref mut miner,
+                 ref mut sortdb,
+                 ref mut chainstate,
+                 vrf_proof,
+                 ref parent_opt,
+                 ref parent_microblock_header_opt| {
+                    let parent_tip = match parent_opt {
+                        None => StacksChainState::get_genesis_header_info(chainstate.db()).unwrap(),
+                        Some(block) => {
+                            let ic = sortdb.index_conn();
+                            let snapshot =
+                                SortitionDB::get_block_snapshot_for_winning_stacks_block(
+                                    &ic,
+                                    &tip.sortition_id,
+                                    &block.block_hash(),
+                                )
+                                .unwrap()
+                                .unwrap(); // succeeds because we don't fork
+                            StacksChainState::get_anchored_block_header_info(
+                                chainstate.db(),
+                                &snapshot.consensus_hash,
+                                &snapshot.winning_stacks_block_hash,
+                            )
+                            .unwrap()
+                            .unwrap()
+                        }
+                    };
+
+                    let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+                    let coinbase_tx = make_coinbase(miner, tenure_id);
+
+                    let anchored_block = StacksBlockBuilder::build_anchored_block(
+                        chainstate,
+                        &sortdb.index_conn(),
+                        &mut mempool,
+                        &parent_tip,
+                        tip.total_burn,
+                        vrf_proof,
+                        Hash160([tenure_id as u8; 20]),
+                        &coinbase_tx,
+                        ExecutionCost::max_value(),
+                        None,
+                    )
+                    .unwrap();
+                    (anchored_block.0, vec![])
+                },
+            );
+
+            let (_, _, consensus_hash) = peer.next_burnchain_block(burn_ops.clone());
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+            let index_block_hash = stacks_block.header.index_block_hash(&consensus_hash);
+            let sortdb = peer.sortdb.take().unwrap();
+            let mut scratch = peer
+                .chainstate()
+                .open_scratch_clarity_state(&scratch_path)
+                .unwrap();
+            let receipt = peer
+                .chainstate()
+                .replay_block(&sortdb, &mut scratch, &index_block_hash)
+                .unwrap();
+
+            // same state, same cost, same transactions
+            assert_eq!(receipt.state_root, stacks_block.header.state_index_root);
+            assert_eq!(
+                Some(receipt.anchored_block_cost),
+                StacksChainState::get_stacks_block_anchored_cost(
+                    peer.chainstate().db(),
+                    &index_block_hash
+                )
+                .unwrap()
+            );
+            assert_eq!(receipt.tx_receipts.len(), stacks_block.txs.len());
+
+            // the replay was rolled back from the scratch MARF, so it can be replayed again
+            let receipt_again = peer
+                .chainstate()
+                .replay_block(&sortdb, &mut scratch, &index_block_hash)
+                .unwrap();
+            assert_eq!(receipt_again.state_root, receipt.state_root);
+
+            match peer
+                .chainstate()
+                .replay_block(&sortdb, &mut scratch, &StacksBlockId([0x11; 32]))
+            {
+                Err(chainstate_error::NoSuchBlockError) => {}
+                res => panic!("Expected NoSuchBlockError, got {:?}", &res),
+            }
+
+            peer.sortdb = Some(sortdb);
+        }
+    }
+
     #[test]
     fn stacks_db_staging_microblocks_fork() {
         // multiple anchored blocks build off of a forked microblock stream
@@ -9364,6 +10234,106 @@ pub mod test {
         );
     }
 
+    #[test]
+    fn stacks_db_detect_microblock_equivocation() {
+        let mut chainstate = instantiate_chainstate(
+            false,
+            0x80000000,
+            "stacks_db_detect_microblock_equivocation",
+        );
+        let privk = StacksPrivateKey::from_hex(
+            "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+        )
+        .unwrap();
+
+        let block_1 = make_empty_coinbase_block(&privk);
+        let consensus_hash = ConsensusHash([2u8; 20]);
+        let parent_index_hash =
+            StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_1.block_hash());
+
+        store_staging_block(
+            &mut chainstate,
+            &consensus_hash,
+            &block_1,
+            &ConsensusHash([1u8; 20]),
+            1,
+            2,
+        );
+
+        // two streams off of block_1, which fork at seq 0
+        let mut mblocks_1 = make_sample_microblock_stream(&privk, &block_1.block_hash());
+        mblocks_1.truncate(3);
+
+        let mut mblocks_2 = make_sample_microblock_stream(&privk, &block_1.block_hash());
+        mblocks_2.truncate(3);
+
+        for mblock in mblocks_1.iter() {
+            assert!(chainstate
+                .preprocess_streamed_microblock(&consensus_hash, &block_1.block_hash(), mblock)
+                .unwrap());
+        }
+        assert_eq!(
+            StacksChainState::get_last_microblock_equivocation_id(chainstate.db()).unwrap(),
+            0
+        );
+
+        for mblock in mblocks_2.iter() {
+            assert!(chainstate
+                .preprocess_streamed_microblock(&consensus_hash, &block_1.block_hash(), mblock)
+                .unwrap());
+        }
+
+        // only the first microblocks conflict; the rest have different parents
+        let equivocations =
+            StacksChainState::get_recent_microblock_equivocations(chainstate.db(), 10).unwrap();
+        assert_eq!(equivocations.len(), 1);
+
+        let equivocation = &equivocations[0];
+        assert_eq!(equivocation.parent_index_block_hash, parent_index_hash);
+        assert_eq!(equivocation.sequence, 0);
+
+        let mut headers = vec![mblocks_1[0].header.clone(), mblocks_2[0].header.clone()];
+        headers.sort_by_key(|header| header.block_hash());
+        assert_eq!(equivocation.header_1, headers[0]);
+        assert_eq!(equivocation.header_2, headers[1]);
+
+        match equivocation.poison_payload() {
+            TransactionPayload::PoisonMicroblock(ref h1, ref h2) => {
+                assert_eq!(
+                    h1.check_recover_pubkey().unwrap(),
+                    h2.check_recover_pubkey().unwrap()
+                );
+            }
+            _ => panic!("not a poison-microblock payload"),
+        }
+
+        assert_eq!(
+            StacksChainState::get_last_microblock_equivocation_id(chainstate.db()).unwrap(),
+            equivocation.id
+        );
+        assert_eq!(
+            StacksChainState::get_microblock_equivocations_after(chainstate.db(), 0, 10).unwrap(),
+            equivocations
+        );
+        assert_eq!(
+            StacksChainState::get_microblock_equivocations_after(
+                chainstate.db(),
+                equivocation.id,
+                10
+            )
+            .unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            StacksChainState::get_microblock_equivocations_for_block(
+                chainstate.db(),
+                &parent_index_hash
+            )
+            .unwrap(),
+            equivocations
+        );
+    }
+
     #[test]
     fn stacks_db_staging_microblocks_multiple_forks() {
         // multiple anchored blocks build off of a microblock stream that gets forked multiple
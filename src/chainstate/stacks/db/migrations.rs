@@ -0,0 +1,695 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020-2021 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Migrations of the chainstate database from one `CHAINSTATE_VERSION` to the next, for upgrades
+//! that change the format of its tables.  Instead of converting everything while the node is
+//! down, a migration converts the rows of the tables it replaces into staging tables alongside
+//! them, one block at a time, while the node keeps serving (and writing) the old tables.  Blocks
+//! processed in the meantime are converted in turn.  Once there are only a few blocks left to
+//! convert, the node converts them as part of processing its next block, and in the same
+//! transaction renames the staging tables over the old ones and bumps the version.  Readers
+//! therefore see either the old format or the new one, and the switch to the new one happens at
+//! a block boundary.
+//!
+//! Until the switch, the node must keep reading and writing the old format, so code whose
+//! behavior depends on the format checks the version in `db_config` instead of assuming
+//! `CHAINSTATE_VERSION`.  A node can only open a chainstate that is at `CHAINSTATE_VERSION`, or
+//! one migration behind it; a node more than one migration behind must be upgraded through each
+//! intermediate version in turn.
+
+use rusqlite::types::ToSql;
+use rusqlite::OptionalExtension;
+
+use chainstate::stacks::db::*;
+use chainstate::stacks::Error;
+use util::db::Error as db_error;
+use util::db::*;
+
+use crate::types::chainstate::StacksBlockId;
+
+/// A conversion of some of the chainstate's tables into a new format.
+///
+/// Each block's rows are converted once, so the tables a migration replaces must be append-only:
+/// a row must never be updated or deleted once its block has been processed, or the change would
+/// be lost in the conversion.  Until the migration is finished, the chainstate refuses any
+/// `UPDATE` or `DELETE` on them.
+pub trait ChainstateMigration: Sync {
+    /// The chainstate version this migration converts from
+    fn from_version(&self) -> &'static str;
+    /// The chainstate version this migration converts to
+    fn to_version(&self) -> &'static str;
+    /// The tables this migration replaces, which must be append-only.  Each is converted into
+    /// the table named by `staging_table()`, which replaces it when the migration is finished.
+    fn tables(&self) -> &'static [&'static str];
+    /// Statements that create the staging tables, and their indexes, in the new format.  They
+    /// must be idempotent (`CREATE ... IF NOT EXISTS`), since they are run every time the
+    /// chainstate is opened until the migration is finished.
+    fn staging_schema(&self) -> &'static [&'static str];
+    /// Convert the rows the given block added to the replaced tables into the staging tables.
+    /// Blocks are converted lowest first, and each only once.
+    fn convert_block(
+        &self,
+        tx: &DBTx,
+        index_block_hash: &StacksBlockId,
+        block_height: u64,
+    ) -> Result<(), Error>;
+}
+
+/// The migrations from each previous chainstate version.  Nothing has needed one yet.
+pub static CHAINSTATE_MIGRATIONS: &[&dyn ChainstateMigration] = &[];
+
+/// A pending migration is finished as part of processing a block once no more than this many
+/// blocks are left to convert
+pub const MIGRATION_SWITCH_OVER_MAX_BLOCKS: u64 = 10;
+
+/// The blocks each pending migration has converted so far, and how many it has left.  The count
+/// of blocks left is computed once, when the migration is set up, and then kept up to date as
+/// blocks are added and converted, so checking on it doesn't scan `block_headers`.
+const CHAINSTATE_MIGRATION_PROGRESS_SCHEMA: &'static [&'static str] = &[
+    r#"
+    CREATE TABLE IF NOT EXISTS chainstate_migration_progress(
+        to_version TEXT NOT NULL,
+        index_block_hash TEXT NOT NULL,
+        PRIMARY KEY(to_version, index_block_hash)
+    );"#,
+    r#"
+    CREATE TABLE IF NOT EXISTS chainstate_migration_status(
+        to_version TEXT PRIMARY KEY NOT NULL,
+        unconverted_blocks INTEGER NOT NULL
+    );"#,
+];
+
+/// The name of the table a replaced table is converted into
+pub fn staging_table(table: &str) -> String {
+    format!("migrating_{}", table)
+}
+
+/// The version of the chainstate in this database, as opposed to the version this node writes
+pub fn chainstate_version(conn: &DBConn) -> Result<String, Error> {
+    let version = conn
+        .query_row("SELECT version FROM db_config LIMIT 1", NO_PARAMS, |row| {
+            row.get(0)
+        })
+        .optional()?
+        .expect("CORRUPTION: no db_config found");
+    Ok(version)
+}
+
+/// The migration that converts a chainstate at the given version to `CHAINSTATE_VERSION`, if it
+/// is not already there and there is one
+pub fn pending_migration(db_version: &str) -> Option<&'static dyn ChainstateMigration> {
+    if db_version == CHAINSTATE_VERSION {
+        return None;
+    }
+    find_migration(CHAINSTATE_MIGRATIONS, db_version, CHAINSTATE_VERSION)
+}
+
+/// The migration among `migrations` that converts a chainstate at the given version, if any
+fn migration_from(
+    migrations: &[&'static dyn ChainstateMigration],
+    db_version: &str,
+) -> Option<&'static dyn ChainstateMigration> {
+    migrations
+        .iter()
+        .find(|migration| migration.from_version() == db_version)
+        .map(|migration| *migration)
+}
+
+fn find_migration(
+    migrations: &[&'static dyn ChainstateMigration],
+    from_version: &str,
+    to_version: &str,
+) -> Option<&'static dyn ChainstateMigration> {
+    migrations
+        .iter()
+        .find(|migration| {
+            migration.from_version() == from_version && migration.to_version() == to_version
+        })
+        .map(|migration| *migration)
+}
+
+/// Create the migration's staging tables, if they don't exist yet, count the blocks it has left
+/// to convert, and make the tables it replaces append-only until it is finished
+pub fn setup_migration(conn: &DBConn, migration: &dyn ChainstateMigration) -> Result<(), Error> {
+    conn.execute_batch("SAVEPOINT setup_chainstate_migration")?;
+    match setup_migration_tables(conn, migration) {
+        Ok(()) => {
+            conn.execute_batch("RELEASE setup_chainstate_migration")?;
+            Ok(())
+        }
+        Err(e) => {
+            conn.execute_batch(
+                "ROLLBACK TO setup_chainstate_migration; RELEASE setup_chainstate_migration",
+            )?;
+            Err(e)
+        }
+    }
+}
+
+fn setup_migration_tables(conn: &DBConn, migration: &dyn ChainstateMigration) -> Result<(), Error> {
+    for cmd in CHAINSTATE_MIGRATION_PROGRESS_SCHEMA {
+        conn.execute_batch(cmd)?;
+    }
+    for cmd in migration.staging_schema() {
+        conn.execute_batch(cmd)?;
+    }
+
+    let has_status = conn
+        .query_row(
+            "SELECT 1 FROM chainstate_migration_status WHERE to_version = ?1",
+            &[&migration.to_version()],
+            |_| Ok(()),
+        )
+        .optional()?
+        .is_some();
+    if !has_status {
+        let unconverted = count_unconverted_blocks(conn, migration)?;
+        let args: &[&dyn ToSql] = &[&migration.to_version(), &u64_to_sql(unconverted)?];
+        conn.execute(
+            "INSERT INTO chainstate_migration_status (to_version, unconverted_blocks) VALUES (?1, ?2)",
+            args,
+        )?;
+    }
+    conn.execute_batch(
+        "CREATE TRIGGER IF NOT EXISTS chainstate_migration_new_block AFTER INSERT ON block_headers
+         BEGIN
+            UPDATE chainstate_migration_status SET unconverted_blocks = unconverted_blocks + 1;
+         END;",
+    )?;
+
+    for table in migration.tables().iter() {
+        for op in ["UPDATE", "DELETE"].iter() {
+            conn.execute_batch(&format!(
+                "CREATE TRIGGER IF NOT EXISTS chainstate_migration_no_{}_{} BEFORE {} ON {}
+                 BEGIN
+                    SELECT RAISE(ABORT, '{} is append-only while it is being migrated');
+                 END;",
+                op.to_lowercase(),
+                table,
+                op,
+                table,
+                table
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// The blocks the migration has yet to convert, lowest first
+fn unconverted_blocks(
+    conn: &DBConn,
+    migration: &dyn ChainstateMigration,
+    limit: Option<u64>,
+) -> Result<Vec<(StacksBlockId, u64)>, Error> {
+    let sql = "SELECT index_block_hash, block_height FROM block_headers WHERE index_block_hash NOT IN (SELECT index_block_hash FROM chainstate_migration_progress WHERE to_version = ?1) ORDER BY block_height ASC LIMIT ?2";
+    let limit = match limit {
+        Some(limit) => u64_to_sql(limit)?,
+        None => -1,
+    };
+    let args: &[&dyn ToSql] = &[&migration.to_version(), &limit];
+    let mut stmt = conn.prepare(sql)?;
+    let mut rows = stmt.query(args)?;
+    let mut blocks = vec![];
+    while let Some(row) = rows.next()? {
+        let index_block_hash = StacksBlockId::from_column(row, "index_block_hash")?;
+        let block_height = u64::from_column(row, "block_height")?;
+        blocks.push((index_block_hash, block_height));
+    }
+    Ok(blocks)
+}
+
+/// How many blocks the migration has yet to convert, as last counted
+pub fn cached_unconverted_blocks(
+    conn: &DBConn,
+    migration: &dyn ChainstateMigration,
+) -> Result<u64, Error> {
+    let unconverted = conn
+        .query_row(
+            "SELECT unconverted_blocks FROM chainstate_migration_status WHERE to_version = ?1",
+            &[&migration.to_version()],
+            |row| row.get::<_, i64>(0),
+        )
+        .optional()?
+        .ok_or_else(|| Error::DBError(db_error::NotFoundError))?;
+    Ok(unconverted as u64)
+}
+
+/// How many blocks the migration has yet to convert, counted from scratch
+pub fn count_unconverted_blocks(
+    conn: &DBConn,
+    migration: &dyn ChainstateMigration,
+) -> Result<u64, Error> {
+    let sql = "SELECT COUNT(*) FROM block_headers WHERE index_block_hash NOT IN (SELECT index_block_hash FROM chainstate_migration_progress WHERE to_version = ?1)";
+    let args: &[&dyn ToSql] = &[&migration.to_version()];
+    let count = query_count(conn, sql, args)?;
+    Ok(count as u64)
+}
+
+fn convert_blocks(
+    tx: &DBTx,
+    migration: &dyn ChainstateMigration,
+    blocks: &[(StacksBlockId, u64)],
+) -> Result<(), Error> {
+    for (index_block_hash, block_height) in blocks.iter() {
+        migration.convert_block(tx, index_block_hash, *block_height)?;
+        let args: &[&dyn ToSql] = &[&migration.to_version(), index_block_hash];
+        tx.execute(
+            "INSERT INTO chainstate_migration_progress (to_version, index_block_hash) VALUES (?1, ?2)",
+            args,
+        )?;
+    }
+    let args: &[&dyn ToSql] = &[&migration.to_version(), &u64_to_sql(blocks.len() as u64)?];
+    tx.execute(
+        "UPDATE chainstate_migration_status SET unconverted_blocks = unconverted_blocks - ?2 WHERE to_version = ?1",
+        args,
+    )?;
+    Ok(())
+}
+
+/// Convert up to `max_blocks` more blocks, in a transaction of their own so the node can keep
+/// processing blocks in between.  Returns how many blocks are left to convert, or None if the
+/// migration has been finished.
+pub fn migrate_step(
+    chainstate: &mut StacksChainState,
+    migration: &dyn ChainstateMigration,
+    max_blocks: u64,
+) -> Result<Option<u64>, Error> {
+    let tx = chainstate.db_tx_begin()?;
+    if chainstate_version(&tx)? != migration.from_version() {
+        return Ok(None);
+    }
+    let blocks = unconverted_blocks(&tx, migration, Some(max_blocks))?;
+    convert_blocks(&tx, migration, &blocks)?;
+    let remaining = cached_unconverted_blocks(&tx, migration)?;
+    tx.commit()?;
+    Ok(Some(remaining))
+}
+
+/// Finish the migration within the given transaction: convert the blocks that are left, replace
+/// the old tables with the staging tables, and set the chainstate's version.  Nothing changes
+/// for readers until the transaction commits.
+pub fn switch_over(tx: &DBTx, migration: &dyn ChainstateMigration) -> Result<(), Error> {
+    let blocks = unconverted_blocks(tx, migration, None)?;
+    convert_blocks(tx, migration, &blocks)?;
+    for table in migration.tables().iter() {
+        tx.execute_batch(&format!(
+            "DROP TABLE {}; ALTER TABLE {} RENAME TO {};",
+            table,
+            staging_table(table),
+            table
+        ))?;
+    }
+    tx.execute(
+        "UPDATE db_config SET version = ?1",
+        &[&migration.to_version()],
+    )?;
+    tx.execute(
+        "DELETE FROM chainstate_migration_progress WHERE to_version = ?1",
+        &[&migration.to_version()],
+    )?;
+    tx.execute(
+        "DELETE FROM chainstate_migration_status WHERE to_version = ?1",
+        &[&migration.to_version()],
+    )?;
+    tx.execute_batch("DROP TRIGGER IF EXISTS chainstate_migration_new_block")?;
+    Ok(())
+}
+
+/// Run the whole migration at once, for a node that is not serving anything in the meantime
+pub fn migrate_offline(
+    chainstate: &mut StacksChainState,
+    migration: &dyn ChainstateMigration,
+) -> Result<(), Error> {
+    let tx = chainstate.db_tx_begin()?;
+    if chainstate_version(&tx)? == migration.from_version() {
+        switch_over(&tx, migration)?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+impl<'a> ChainstateTx<'a> {
+    /// Finish the pending chainstate migration along with the block being processed, if few
+    /// enough blocks are left to convert.  If it fails, the chainstate is left in the old format
+    /// and the block is processed as usual.  Returns whether the migration was finished.
+    pub fn finish_caught_up_migration(&self) -> Result<bool, Error> {
+        if self.migrations.is_empty() {
+            return Ok(false);
+        }
+        let tx = self.tx.tx();
+        let migration = match migration_from(self.migrations, &chainstate_version(tx)?) {
+            Some(migration) => migration,
+            None => return Ok(false),
+        };
+        if cached_unconverted_blocks(tx, migration)? > MIGRATION_SWITCH_OVER_MAX_BLOCKS {
+            return Ok(false);
+        }
+
+        tx.execute_batch("SAVEPOINT chainstate_migration")?;
+        match switch_over(tx, migration) {
+            Ok(()) => {
+                tx.execute_batch("RELEASE chainstate_migration")?;
+                info!(
+                    "Migrated chainstate from version {} to {}",
+                    migration.from_version(),
+                    migration.to_version()
+                );
+                Ok(true)
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to migrate chainstate from version {} to {}: {:?}",
+                    migration.from_version(),
+                    migration.to_version(),
+                    &e
+                );
+                tx.execute_batch("ROLLBACK TO chainstate_migration; RELEASE chainstate_migration")?;
+                Ok(false)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chainstate::stacks::db::test::instantiate_chainstate;
+    use util::hash::to_hex;
+
+    /// Replaces `notes(index_block_hash, note)` with a table that also stores each note's length
+    struct NoteLengths;
+
+    impl ChainstateMigration for NoteLengths {
+        fn from_version(&self) -> &'static str {
+            "test-1"
+        }
+        fn to_version(&self) -> &'static str {
+            "test-2"
+        }
+        fn tables(&self) -> &'static [&'static str] {
+            &["notes"]
+        }
+        fn staging_schema(&self) -> &'static [&'static str] {
+            &["CREATE TABLE IF NOT EXISTS migrating_notes(index_block_hash TEXT NOT NULL, note TEXT NOT NULL, note_len INTEGER NOT NULL);"]
+        }
+        fn convert_block(
+            &self,
+            tx: &DBTx,
+            index_block_hash: &StacksBlockId,
+            _block_height: u64,
+        ) -> Result<(), Error> {
+            tx.execute(
+                "INSERT INTO migrating_notes (index_block_hash, note, note_len) SELECT index_block_hash, note, LENGTH(note) FROM notes WHERE index_block_hash = ?1",
+                &[index_block_hash],
+            )?;
+            Ok(())
+        }
+    }
+
+    static NOTE_LENGTHS: NoteLengths = NoteLengths;
+
+    /// Like `NoteLengths`, but fails to convert the block at height 3
+    struct BrokenNoteLengths;
+
+    impl ChainstateMigration for BrokenNoteLengths {
+        fn from_version(&self) -> &'static str {
+            NOTE_LENGTHS.from_version()
+        }
+        fn to_version(&self) -> &'static str {
+            NOTE_LENGTHS.to_version()
+        }
+        fn tables(&self) -> &'static [&'static str] {
+            NOTE_LENGTHS.tables()
+        }
+        fn staging_schema(&self) -> &'static [&'static str] {
+            NOTE_LENGTHS.staging_schema()
+        }
+        fn convert_block(
+            &self,
+            tx: &DBTx,
+            index_block_hash: &StacksBlockId,
+            block_height: u64,
+        ) -> Result<(), Error> {
+            NOTE_LENGTHS.convert_block(tx, index_block_hash, block_height)?;
+            if block_height == 3 {
+                return Err(Error::InvalidChainstateDB);
+            }
+            Ok(())
+        }
+    }
+
+    static BROKEN_NOTE_LENGTHS: BrokenNoteLengths = BrokenNoteLengths;
+
+    /// A chainstate at version `test-1`, with a `notes` table to migrate, the boot block and
+    /// blocks at heights 1 through 3
+    fn setup_notes_chainstate(
+        test_name: &str,
+        migrations: &'static [&'static dyn ChainstateMigration],
+    ) -> StacksChainState {
+        let mut chainstate = instantiate_chainstate(false, 0x80000000, test_name);
+        chainstate.migrations = migrations;
+        chainstate
+            .db()
+            .execute_batch(
+                "CREATE TABLE notes(index_block_hash TEXT NOT NULL, note TEXT NOT NULL); UPDATE db_config SET version = 'test-1';",
+            )
+            .unwrap();
+        setup_migration(chainstate.db(), migrations[0]).unwrap();
+        for (byte, height) in [(0x01, 1), (0x02, 2), (0x03, 3)].iter() {
+            add_block(&mut chainstate, *byte, *height);
+        }
+        chainstate
+    }
+
+    /// Add a block header at the given height, copied from the boot block's, along with a note
+    fn add_block(chainstate: &mut StacksChainState, byte: u8, height: u64) -> StacksBlockId {
+        let tx = chainstate.db_tx_begin().unwrap();
+        let index_block_hash = insert_block(&tx, byte, height);
+        tx.commit().unwrap();
+        index_block_hash
+    }
+
+    fn insert_block(tx: &DBTx, byte: u8, height: u64) -> StacksBlockId {
+        let index_block_hash = StacksBlockId([byte; 32]);
+        tx.execute_batch("CREATE TEMP TABLE new_header AS SELECT * FROM block_headers LIMIT 1")
+            .unwrap();
+        let args: &[&dyn ToSql] = &[
+            &index_block_hash,
+            &to_hex(&[byte; 20]),
+            &u64_to_sql(height).unwrap(),
+        ];
+        tx.execute(
+            "UPDATE new_header SET index_block_hash = ?1, consensus_hash = ?2, block_height = ?3",
+            args,
+        )
+        .unwrap();
+        tx.execute_batch(
+            "INSERT INTO block_headers SELECT * FROM new_header; DROP TABLE new_header",
+        )
+        .unwrap();
+        let args: &[&dyn ToSql] = &[&index_block_hash, &"x".repeat(height as usize + 1)];
+        tx.execute(
+            "INSERT INTO notes (index_block_hash, note) VALUES (?1, ?2)",
+            args,
+        )
+        .unwrap();
+        index_block_hash
+    }
+
+    fn note_lengths(chainstate: &StacksChainState) -> Vec<i64> {
+        let mut stmt = chainstate
+            .db()
+            .prepare("SELECT note_len FROM notes ORDER BY note_len")
+            .unwrap();
+        let rows = stmt.query_map(NO_PARAMS, |row| row.get(0)).unwrap();
+        rows.map(|row| row.unwrap()).collect()
+    }
+
+    #[test]
+    fn migration_converts_in_steps_and_switches_over() {
+        let mut chainstate =
+            instantiate_chainstate(false, 0x80000000, "migration_converts_in_steps");
+        assert!(pending_migration(CHAINSTATE_VERSION).is_none());
+        let migrations: &[&'static dyn ChainstateMigration] = &[&NOTE_LENGTHS];
+        assert!(find_migration(migrations, "test-1", "test-2").is_some());
+        assert!(find_migration(migrations, "test-0", "test-2").is_none());
+
+        chainstate
+            .db()
+            .execute_batch(
+                "CREATE TABLE notes(index_block_hash TEXT NOT NULL, note TEXT NOT NULL); UPDATE db_config SET version = 'test-1';",
+            )
+            .unwrap();
+        setup_migration(chainstate.db(), &NOTE_LENGTHS).unwrap();
+        // idempotent
+        setup_migration(chainstate.db(), &NOTE_LENGTHS).unwrap();
+
+        // the boot block, plus three more
+        for (byte, height) in [(0x01, 1), (0x02, 2), (0x03, 3)].iter() {
+            add_block(&mut chainstate, *byte, *height);
+        }
+        assert_eq!(
+            count_unconverted_blocks(chainstate.db(), &NOTE_LENGTHS).unwrap(),
+            4
+        );
+        assert_eq!(
+            cached_unconverted_blocks(chainstate.db(), &NOTE_LENGTHS).unwrap(),
+            4
+        );
+        assert_eq!(
+            migrate_step(&mut chainstate, &NOTE_LENGTHS, 2).unwrap(),
+            Some(2)
+        );
+
+        // a block processed in the meantime is converted too
+        add_block(&mut chainstate, 0x04, 4);
+        assert_eq!(
+            migrate_step(&mut chainstate, &NOTE_LENGTHS, 2).unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            chainstate_version(chainstate.db()).unwrap(),
+            "test-1".to_string()
+        );
+
+        // the old format is served until the switch
+        assert!(chainstate
+            .db()
+            .prepare("SELECT note_len FROM notes")
+            .is_err());
+
+        let tx = chainstate.db_tx_begin().unwrap();
+        switch_over(&tx, &NOTE_LENGTHS).unwrap();
+        tx.commit().unwrap();
+
+        assert_eq!(
+            chainstate_version(chainstate.db()).unwrap(),
+            "test-2".to_string()
+        );
+        assert_eq!(note_lengths(&chainstate), vec![2, 3, 4, 5]);
+
+        // nothing more to do once it's finished
+        assert_eq!(
+            migrate_step(&mut chainstate, &NOTE_LENGTHS, 2).unwrap(),
+            None
+        );
+        migrate_offline(&mut chainstate, &NOTE_LENGTHS).unwrap();
+        assert_eq!(note_lengths(&chainstate), vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn migration_finishes_while_processing_a_block() {
+        static MIGRATIONS: &[&dyn ChainstateMigration] = &[&NOTE_LENGTHS];
+        let mut chainstate =
+            setup_notes_chainstate("migration_finishes_while_processing_a_block", MIGRATIONS);
+        assert_eq!(
+            cached_unconverted_blocks(chainstate.db(), &NOTE_LENGTHS).unwrap(),
+            4
+        );
+
+        // the block is converted along with the ones left over, and the switch happens with it
+        let (chainstate_tx, _) = chainstate.chainstate_tx_begin().unwrap();
+        insert_block(chainstate_tx.tx.tx(), 0x04, 4);
+        assert!(chainstate_tx.finish_caught_up_migration().unwrap());
+        chainstate_tx.commit().unwrap();
+
+        assert_eq!(
+            chainstate_version(chainstate.db()).unwrap(),
+            "test-2".to_string()
+        );
+        assert_eq!(note_lengths(&chainstate), vec![2, 3, 4, 5]);
+        assert!(cached_unconverted_blocks(chainstate.db(), &NOTE_LENGTHS).is_err());
+
+        // the new table is no longer held to being append-only
+        chainstate
+            .db()
+            .execute_batch("UPDATE notes SET note_len = note_len")
+            .unwrap();
+
+        // and the next block has nothing to finish
+        let (chainstate_tx, _) = chainstate.chainstate_tx_begin().unwrap();
+        assert!(!chainstate_tx.finish_caught_up_migration().unwrap());
+        chainstate_tx.commit().unwrap();
+    }
+
+    #[test]
+    fn failed_migration_keeps_old_format() {
+        static MIGRATIONS: &[&dyn ChainstateMigration] = &[&BROKEN_NOTE_LENGTHS];
+        let mut chainstate =
+            setup_notes_chainstate("failed_migration_keeps_old_format", MIGRATIONS);
+
+        let (chainstate_tx, _) = chainstate.chainstate_tx_begin().unwrap();
+        insert_block(chainstate_tx.tx.tx(), 0x04, 4);
+        assert!(!chainstate_tx.finish_caught_up_migration().unwrap());
+        chainstate_tx.commit().unwrap();
+
+        // the block was still processed...
+        assert_eq!(
+            query_count(
+                chainstate.db(),
+                "SELECT COUNT(*) FROM block_headers",
+                NO_PARAMS
+            )
+            .unwrap(),
+            5
+        );
+        // ...but the chainstate is still in the old format, with nothing converted
+        assert_eq!(
+            chainstate_version(chainstate.db()).unwrap(),
+            "test-1".to_string()
+        );
+        assert!(chainstate
+            .db()
+            .prepare("SELECT note_len FROM notes")
+            .is_err());
+        assert_eq!(
+            query_count(
+                chainstate.db(),
+                "SELECT COUNT(*) FROM migrating_notes",
+                NO_PARAMS
+            )
+            .unwrap(),
+            0
+        );
+        assert_eq!(
+            cached_unconverted_blocks(chainstate.db(), &BROKEN_NOTE_LENGTHS).unwrap(),
+            5
+        );
+        assert_eq!(
+            count_unconverted_blocks(chainstate.db(), &BROKEN_NOTE_LENGTHS).unwrap(),
+            5
+        );
+    }
+
+    #[test]
+    fn migrated_tables_are_append_only() {
+        static MIGRATIONS: &[&dyn ChainstateMigration] = &[&NOTE_LENGTHS];
+        let chainstate = setup_notes_chainstate("migrated_tables_are_append_only", MIGRATIONS);
+        assert!(chainstate
+            .db()
+            .execute_batch("UPDATE notes SET note = 'changed'")
+            .is_err());
+        assert!(chainstate.db().execute_batch("DELETE FROM notes").is_err());
+        chainstate
+            .db()
+            .execute(
+                "INSERT INTO notes (index_block_hash, note) VALUES (?1, ?2)",
+                &[&to_hex(&[0x09; 32]), &"new".to_string()],
+            )
+            .unwrap();
+    }
+}
@@ -60,15 +60,15 @@ use net::atlas::BNS_CHARS_REGEX;
 use net::Error as net_error;
 use util::db::Error as db_error;
 use util::db::{
-    db_mkdirs, query_count, query_row, tx_begin_immediate, tx_busy_handler, DBConn, DBTx,
-    FromColumn, FromRow, IndexDBConn, IndexDBTx,
+    db_mkdirs, query_count, query_row, query_row_columns, tx_begin_immediate, tx_busy_handler,
+    DBConn, DBTx, FromColumn, FromRow, IndexDBConn, IndexDBTx,
 };
 use util::hash::to_hex;
 use vm::analysis::analysis_db::AnalysisDatabase;
 use vm::analysis::run_analysis;
 use vm::ast::build_ast;
 use vm::contexts::OwnedEnvironment;
-use vm::costs::{ExecutionCost, LimitedCostTracker};
+use vm::costs::{check_boot_costs_contract, ExecutionCost, LimitedCostTracker};
 use vm::database::{
     BurnStateDB, ClarityDatabase, HeadersDB, STXBalance, SqliteConnection, NULL_BURN_STATE_DB,
 };
@@ -85,16 +85,49 @@ use crate::types::proof::{ClarityMarfTrieId, TrieHash};
 use crate::util::boot::{boot_code_addr, boot_code_id};
 use vm::Value;
 
+pub mod account_txs;
 pub mod accounts;
 pub mod blocks;
 pub mod contracts;
 pub mod headers;
+pub mod migrations;
+pub mod print_events;
 pub mod transactions;
+pub mod tx_receipts;
 pub mod unconfirmed;
 
 lazy_static! {
     pub static ref TRANSACTION_LOG: bool =
         std::env::var("STACKS_TRANSACTION_LOG") == Ok("1".into());
+    pub static ref ACCOUNT_TX_INDEX: bool =
+        std::env::var("STACKS_ACCOUNT_TX_INDEX") == Ok("1".into());
+    pub static ref TX_RECEIPT_INDEX: bool =
+        std::env::var("STACKS_TX_RECEIPT_INDEX") == Ok("1".into());
+    pub static ref PRINT_EVENT_INDEX: bool =
+        std::env::var("STACKS_PRINT_EVENT_INDEX") == Ok("1".into());
+}
+
+/// The optional indexes a chainstate maintains as it processes blocks, and serves over RPC
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChainstateIndexes {
+    /// `account_transactions`, see `account_txs`
+    pub account_txs: bool,
+    /// `transaction_receipts`, see `tx_receipts`
+    pub tx_receipts: bool,
+    /// `print_events`, see `print_events`
+    pub print_events: bool,
+}
+
+impl ChainstateIndexes {
+    /// The indexes the node was started with, from `STACKS_ACCOUNT_TX_INDEX`,
+    /// `STACKS_TX_RECEIPT_INDEX` and `STACKS_PRINT_EVENT_INDEX`
+    pub fn from_env() -> ChainstateIndexes {
+        ChainstateIndexes {
+            account_txs: *ACCOUNT_TX_INDEX,
+            tx_receipts: *TX_RECEIPT_INDEX,
+            print_events: *PRINT_EVENT_INDEX,
+        }
+    }
 }
 
 pub struct StacksChainState {
@@ -108,6 +141,9 @@ pub struct StacksChainState {
     pub root_path: String,
     pub block_limit: ExecutionCost,
     pub unconfirmed_state: Option<UnconfirmedState>,
+    /// The migrations this chainstate may finish while processing blocks
+    pub migrations: &'static [&'static dyn migrations::ChainstateMigration],
+    pub indexes: ChainstateIndexes,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -374,6 +410,8 @@ pub struct ChainstateTx<'a> {
     pub blocks_path: String,
     pub tx: StacksDBTx<'a>,
     pub root_path: String,
+    pub migrations: &'static [&'static dyn migrations::ChainstateMigration],
+    pub indexes: ChainstateIndexes,
 }
 
 impl<'a> ChainstateTx<'a> {
@@ -382,12 +420,16 @@ impl<'a> ChainstateTx<'a> {
         blocks_path: String,
         root_path: String,
         config: DBConfig,
+        migrations: &'static [&'static dyn migrations::ChainstateMigration],
+        indexes: ChainstateIndexes,
     ) -> ChainstateTx<'a> {
         ChainstateTx {
             config,
             blocks_path,
             tx,
             root_path,
+            migrations,
+            indexes,
         }
     }
 
@@ -428,6 +470,32 @@ impl<'a> ChainstateTx<'a> {
             }
         }
     }
+
+    /// Record the delegations made in a block's transactions into the `pox_delegations` index
+    pub fn index_pox_delegations(
+        &self,
+        block_id: &StacksBlockId,
+        events: &[StacksTransactionReceipt],
+    ) -> Result<(), Error> {
+        let insert = "INSERT OR IGNORE INTO pox_delegations (delegate_to, stacker, index_block_hash) VALUES (?1, ?2, ?3)";
+        for tx_event in events.iter() {
+            for event in tx_event.events.iter() {
+                if let StacksTransactionEvent::PoxEvent(PoxEventType::DelegateStx(ref data)) = event
+                {
+                    let params: &[&dyn ToSql] = &[
+                        &data.delegate_to.to_string(),
+                        &data.stacker.to_string(),
+                        block_id,
+                    ];
+                    self.tx
+                        .tx()
+                        .execute(insert, params)
+                        .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<'a> Deref for ChainstateTx<'a> {
@@ -613,6 +681,100 @@ const CHAINSTATE_INITIAL_SCHEMA: &'static [&'static str] = &[
     "CREATE INDEX index_block_hash_tx_index ON transactions(index_block_hash);",
 ];
 
+/// Index of every `delegate-stx` call this node has processed, so the principals that have
+/// delegated to a given delegatee can be looked up.  Rows are kept for every fork; callers must
+/// check each delegation against the PoX contract state at their chain tip.  Applied to existing
+/// chainstate databases when they are opened.
+const CHAINSTATE_POX_DELEGATIONS_SCHEMA: &'static [&'static str] = &[r#"
+    CREATE TABLE IF NOT EXISTS pox_delegations(
+        delegate_to TEXT NOT NULL,
+        stacker TEXT NOT NULL,
+        index_block_hash TEXT NOT NULL,
+        PRIMARY KEY(delegate_to, stacker, index_block_hash)
+    );"#];
+
+/// Microblock equivocations this node has detected: pairs of conflicting microblocks signed by
+/// the same leader, at the same place in its stream.  The headers are stored consensus-serialized,
+/// as hex, with the lower-hashed one first.  Applied to existing chainstate databases when they
+/// are opened.
+const CHAINSTATE_MICROBLOCK_EQUIVOCATIONS_SCHEMA: &'static [&'static str] = &[
+    r#"
+    CREATE TABLE IF NOT EXISTS microblock_equivocations(
+        parent_index_block_hash TEXT NOT NULL,
+        sequence INTEGER NOT NULL,
+        microblock_hash_1 TEXT NOT NULL,
+        microblock_hash_2 TEXT NOT NULL,
+        header_1 TEXT NOT NULL,
+        header_2 TEXT NOT NULL,
+        detected_at INTEGER NOT NULL,
+        PRIMARY KEY(microblock_hash_1, microblock_hash_2)
+    );"#,
+    "CREATE INDEX IF NOT EXISTS microblock_equivocations_parent ON microblock_equivocations(parent_index_block_hash);",
+];
+
+/// Index of the transactions each account was involved in (see `account_txs`).  Rows are kept for
+/// every fork.  Each transaction is stored once per block that processed it, and referenced by
+/// txid from the rows of each account it involved.  Applied to existing chainstate databases when
+/// they are opened; only populated if the node runs with `STACKS_ACCOUNT_TX_INDEX=1`.
+const CHAINSTATE_ACCOUNT_TRANSACTIONS_SCHEMA: &'static [&'static str] = &[
+    r#"
+    CREATE TABLE IF NOT EXISTS account_transactions(
+        principal TEXT NOT NULL,
+        txid TEXT NOT NULL,
+        index_block_hash TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+        tx_index INTEGER NOT NULL,      -- position among the transactions the block processed
+        PRIMARY KEY(principal, index_block_hash, txid)
+    );"#,
+    "CREATE INDEX IF NOT EXISTS account_transactions_by_height ON account_transactions(principal, block_height, tx_index);",
+    r#"
+    CREATE TABLE IF NOT EXISTS account_transaction_bodies(
+        txid TEXT NOT NULL,
+        index_block_hash TEXT NOT NULL,
+        tx_hex TEXT,                    -- NULL for burnchain operations
+        result_hex TEXT NOT NULL,
+        PRIMARY KEY(txid, index_block_hash)
+    );"#,
+];
+
+/// Receipts of processed transactions (see `tx_receipts`).  Rows are kept for every fork.  Applied
+/// to existing chainstate databases when they are opened; only populated if the node runs with
+/// `STACKS_TX_RECEIPT_INDEX=1`.
+const CHAINSTATE_TRANSACTION_RECEIPTS_SCHEMA: &'static [&'static str] = &[r#"
+    CREATE TABLE IF NOT EXISTS transaction_receipts(
+        txid TEXT NOT NULL,
+        index_block_hash TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+        tx_index INTEGER NOT NULL,      -- position among the transactions the block processed
+        result_hex TEXT NOT NULL,
+        post_condition_aborted INTEGER NOT NULL,
+        stx_burned TEXT NOT NULL,
+        execution_cost TEXT NOT NULL,   -- JSON
+        vm_error INTEGER,               -- a TransactionErrorCode, if the VM aborted it
+        events TEXT NOT NULL,           -- JSON array, in the form sent to event observers
+        PRIMARY KEY(txid, index_block_hash)
+    );"#];
+
+/// Index of the values contracts printed (see `print_events`).  Rows are kept for every fork.
+/// Applied to existing chainstate databases when they are opened; only populated if the node runs
+/// with `STACKS_PRINT_EVENT_INDEX=1`.
+const CHAINSTATE_PRINT_EVENTS_SCHEMA: &'static [&'static str] = &[
+    r#"
+    CREATE TABLE IF NOT EXISTS print_events(
+        contract_identifier TEXT NOT NULL,
+        topic TEXT,                     -- name of the first key of a printed tuple
+        txid TEXT NOT NULL,
+        index_block_hash TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+        tx_index INTEGER NOT NULL,      -- position among the transactions the block processed
+        event_index INTEGER NOT NULL,   -- position among the transaction's events
+        value_hex TEXT NOT NULL,
+        PRIMARY KEY(index_block_hash, tx_index, event_index)
+    );"#,
+    "CREATE INDEX IF NOT EXISTS print_events_by_contract ON print_events(contract_identifier, block_height, tx_index, event_index);",
+    "CREATE INDEX IF NOT EXISTS print_events_by_topic ON print_events(contract_identifier, topic, block_height, tx_index, event_index);",
+];
+
 #[cfg(test)]
 pub const MINER_REWARD_MATURITY: u64 = 2; // small for testing purposes
 
@@ -683,6 +845,9 @@ pub struct ChainStateBootData {
         Option<Box<dyn FnOnce() -> Box<dyn Iterator<Item = ChainstateBNSNamespace>>>>,
     pub get_bulk_initial_names:
         Option<Box<dyn FnOnce() -> Box<dyn Iterator<Item = ChainstateBNSName>>>>,
+    /// Replacement code for the boot `costs` contract, so private networks can run (and rehearse
+    /// upgrades to) a custom cost schedule.  Not allowed on mainnet.
+    pub custom_costs_contract: Option<String>,
 }
 
 impl ChainStateBootData {
@@ -702,6 +867,7 @@ impl ChainStateBootData {
             get_bulk_initial_balances: None,
             get_bulk_initial_namespaces: None,
             get_bulk_initial_names: None,
+            custom_costs_contract: None,
         }
     }
 }
@@ -721,6 +887,21 @@ impl StacksChainState {
             for cmd in CHAINSTATE_INITIAL_SCHEMA {
                 tx.execute_batch(cmd)?;
             }
+            for cmd in CHAINSTATE_POX_DELEGATIONS_SCHEMA {
+                tx.execute_batch(cmd)?;
+            }
+            for cmd in CHAINSTATE_MICROBLOCK_EQUIVOCATIONS_SCHEMA {
+                tx.execute_batch(cmd)?;
+            }
+            for cmd in CHAINSTATE_ACCOUNT_TRANSACTIONS_SCHEMA {
+                tx.execute_batch(cmd)?;
+            }
+            for cmd in CHAINSTATE_TRANSACTION_RECEIPTS_SCHEMA {
+                tx.execute_batch(cmd)?;
+            }
+            for cmd in CHAINSTATE_PRINT_EVENTS_SCHEMA {
+                tx.execute_batch(cmd)?;
+            }
 
             tx.execute(
                 "INSERT INTO db_config (version,mainnet,chain_id) VALUES (?1,?2,?3)",
@@ -749,42 +930,78 @@ impl StacksChainState {
             StacksChainState::instantiate_db(mainnet, chain_id, index_path)
         } else {
             let marf = StacksChainState::open_index(index_path)?;
-            // sanity check
-            let db_config = query_row::<DBConfig, _>(
-                marf.sqlite_conn(),
-                &"SELECT * FROM db_config LIMIT 1".to_string(),
-                NO_PARAMS,
-            )?
-            .expect("CORRUPTION: no db_config found");
-
-            if db_config.mainnet != mainnet {
-                error!(
-                    "Invalid chain state database: expected mainnet = {}, got {}",
-                    mainnet, db_config.mainnet
-                );
-                return Err(Error::InvalidChainstateDB);
-            }
+            StacksChainState::check_db_config(&marf, mainnet, chain_id)?;
 
-            if db_config.version != CHAINSTATE_VERSION {
-                error!(
-                    "Invalid chain state database: expected version = {}, got {}",
-                    CHAINSTATE_VERSION, db_config.version
-                );
-                return Err(Error::InvalidChainstateDB);
+            for cmd in CHAINSTATE_POX_DELEGATIONS_SCHEMA {
+                marf.sqlite_conn().execute_batch(cmd)?;
+            }
+            for cmd in CHAINSTATE_MICROBLOCK_EQUIVOCATIONS_SCHEMA {
+                marf.sqlite_conn().execute_batch(cmd)?;
+            }
+            for cmd in CHAINSTATE_ACCOUNT_TRANSACTIONS_SCHEMA {
+                marf.sqlite_conn().execute_batch(cmd)?;
+            }
+            for cmd in CHAINSTATE_TRANSACTION_RECEIPTS_SCHEMA {
+                marf.sqlite_conn().execute_batch(cmd)?;
+            }
+            for cmd in CHAINSTATE_PRINT_EVENTS_SCHEMA {
+                marf.sqlite_conn().execute_batch(cmd)?;
             }
 
-            if db_config.chain_id != chain_id {
-                error!(
-                    "Invalid chain ID: expected {}, got {}",
-                    chain_id, db_config.chain_id
-                );
-                return Err(Error::InvalidChainstateDB);
+            let db_version = migrations::chainstate_version(marf.sqlite_conn())?;
+            if let Some(migration) = migrations::pending_migration(&db_version) {
+                migrations::setup_migration(marf.sqlite_conn(), migration)?;
             }
 
             Ok(marf)
         }
     }
 
+    /// Sanity-check that an existing chainstate DB is for this network and version
+    fn check_db_config(
+        marf: &MARF<StacksBlockId>,
+        mainnet: bool,
+        chain_id: u32,
+    ) -> Result<(), Error> {
+        let db_config = query_row::<DBConfig, _>(
+            marf.sqlite_conn(),
+            &"SELECT * FROM db_config LIMIT 1".to_string(),
+            NO_PARAMS,
+        )?
+        .expect("CORRUPTION: no db_config found");
+
+        if db_config.mainnet != mainnet {
+            error!(
+                "Invalid chain state database: expected mainnet = {}, got {}",
+                mainnet, db_config.mainnet
+            );
+            return Err(Error::InvalidChainstateDB);
+        }
+
+        if let Some(migration) = migrations::pending_migration(&db_config.version) {
+            info!(
+                "Chainstate database is at version {}; it will be migrated to version {}",
+                db_config.version,
+                migration.to_version()
+            );
+        } else if db_config.version != CHAINSTATE_VERSION {
+            error!(
+                "Invalid chain state database: expected version = {}, got {}",
+                CHAINSTATE_VERSION, db_config.version
+            );
+            return Err(Error::InvalidChainstateDB);
+        }
+
+        if db_config.chain_id != chain_id {
+            error!(
+                "Invalid chain ID: expected {}, got {}",
+                chain_id, db_config.chain_id
+            );
+            return Err(Error::InvalidChainstateDB);
+        }
+        Ok(())
+    }
+
     pub fn open_index(marf_path: &str) -> Result<MARF<StacksBlockId>, Error> {
         test_debug!("Open MARF index at {}", marf_path);
         let marf =
@@ -840,6 +1057,27 @@ impl StacksChainState {
         return principal;
     }
 
+    /// Make sure that a custom costs contract defines every cost function the VM needs, before
+    /// installing it.  A costs contract that fails this check would cause every transaction to
+    /// fail to evaluate.
+    fn check_custom_costs_contract(
+        clarity_tx: &mut ClarityTx,
+        mainnet: bool,
+        custom_costs: &str,
+    ) -> Result<(), Error> {
+        let costs_contract_id = boot_code_id("costs", mainnet);
+        let (_, analysis) = clarity_tx.connection().as_transaction(|clarity| {
+            clarity.analyze_smart_contract(&costs_contract_id, custom_costs)
+        })?;
+        check_boot_costs_contract(&analysis).map_err(|msg| {
+            error!("Invalid custom costs contract: {}", &msg);
+            Error::ClarityError(clarity_error::BadTransaction(format!(
+                "Invalid custom costs contract: {}",
+                msg
+            )))
+        })
+    }
+
     /// Install the boot code into the chain history.
     fn install_boot_code(
         chainstate: &mut StacksChainState,
@@ -848,6 +1086,11 @@ impl StacksChainState {
     ) -> Result<Vec<StacksTransactionReceipt>, Error> {
         info!("Building genesis block");
 
+        if mainnet && boot_data.custom_costs_contract.is_some() {
+            error!("Refusing to instantiate a custom costs contract on mainnet");
+            return Err(Error::InvalidChainstateDB);
+        }
+
         let tx_version = if mainnet {
             TransactionVersion::Mainnet
         } else {
@@ -890,6 +1133,21 @@ impl StacksChainState {
                 *boot::STACKS_BOOT_CODE_TESTNET
             };
             for (boot_code_name, boot_code_contract) in boot_code.iter() {
+                let boot_code_contract = match boot_data.custom_costs_contract {
+                    Some(ref custom_costs) if *boot_code_name == "costs" => {
+                        StacksChainState::check_custom_costs_contract(
+                            &mut clarity_tx,
+                            mainnet,
+                            custom_costs,
+                        )?;
+                        info!(
+                            "Instantiating custom costs contract ({} bytes)",
+                            custom_costs.len()
+                        );
+                        custom_costs.as_str()
+                    }
+                    _ => boot_code_contract,
+                };
                 debug!(
                     "Instantiate boot code contract '{}' ({} bytes)...",
                     boot_code_name,
@@ -1323,6 +1581,60 @@ impl StacksChainState {
         )
     }
 
+    /// Open an existing chainstate read-only, e.g. to serve queries from it while another process
+    /// writes to it.  Nothing is created or migrated, so the chainstate must have been opened
+    /// read/write by this version of the node at least once.
+    /// `block_limit` bounds whatever the reader evaluates against the chainstate; since such a
+    /// reader usually serves RPC queries, this should be the node's read-only call limit.
+    pub fn open_readonly(
+        mainnet: bool,
+        chain_id: u32,
+        path_str: &str,
+        block_limit: ExecutionCost,
+    ) -> Result<StacksChainState, Error> {
+        let path = PathBuf::from(path_str);
+        let path_to_string = |path: PathBuf| -> Result<String, Error> {
+            path.to_str()
+                .ok_or_else(|| Error::DBError(db_error::ParseError))
+                .map(|s| s.to_string())
+        };
+        let blocks_path_root = path_to_string(path.join("blocks"))?;
+        let clarity_state_index_root = path_to_string(path.join("vm").join("clarity"))?;
+        let clarity_state_index_marf =
+            path_to_string(path.join("vm").join("clarity").join("marf.sqlite"))?;
+        let header_index_root = path_to_string(path.join("vm").join("index.sqlite"))?;
+
+        let state_index = MARF::from_path_readonly(&header_index_root)
+            .map_err(|e| Error::DBError(db_error::IndexError(e)))?;
+        StacksChainState::check_db_config(&state_index, mainnet, chain_id)?;
+
+        let vm_state = MarfedKV::open_readonly(
+            &clarity_state_index_root,
+            Some(&StacksBlockHeader::make_index_block_hash(
+                &MINER_BLOCK_CONSENSUS_HASH,
+                &MINER_BLOCK_HEADER_HASH,
+            )),
+        )
+        .map_err(|e| Error::ClarityError(e.into()))?;
+
+        let clarity_state = ClarityInstance::new(mainnet, vm_state, block_limit.clone());
+
+        Ok(StacksChainState {
+            mainnet: mainnet,
+            chain_id: chain_id,
+            clarity_state: clarity_state,
+            state_index: state_index,
+            blocks_path: blocks_path_root,
+            clarity_state_index_path: clarity_state_index_marf,
+            clarity_state_index_root: clarity_state_index_root,
+            root_path: path_str.to_string(),
+            block_limit: block_limit,
+            unconfirmed_state: None,
+            migrations: migrations::CHAINSTATE_MIGRATIONS,
+            indexes: ChainstateIndexes::from_env(),
+        })
+    }
+
     /// Re-open the chainstate -- i.e. to get a new handle to it using an existing chain state's
     /// parameters
     pub fn reopen(&self) -> Result<(StacksChainState, Vec<StacksTransactionReceipt>), Error> {
@@ -1432,6 +1744,8 @@ impl StacksChainState {
             root_path: path_str.to_string(),
             block_limit: block_limit,
             unconfirmed_state: None,
+            migrations: migrations::CHAINSTATE_MIGRATIONS,
+            indexes: ChainstateIndexes::from_env(),
         };
 
         let mut receipts = vec![];
@@ -1486,8 +1800,14 @@ impl StacksChainState {
         let clarity_instance = &mut self.clarity_state;
         let inner_tx = StacksDBTx::new(&mut self.state_index, ());
 
-        let chainstate_tx =
-            ChainstateTx::new(inner_tx, blocks_path, self.root_path.clone(), config);
+        let chainstate_tx = ChainstateTx::new(
+            inner_tx,
+            blocks_path,
+            self.root_path.clone(),
+            config,
+            self.migrations,
+            self.indexes,
+        );
 
         Ok((chainstate_tx, clarity_instance))
     }
@@ -1533,6 +1853,18 @@ impl StacksChainState {
         self.state_index.sqlite_conn()
     }
 
+    /// Get up to `limit` of the principals that this node has seen delegate to `delegate_to`, in
+    /// any fork.  The delegations may since have been revoked or expired.
+    pub fn get_pox_delegation_candidates(
+        conn: &DBConn,
+        delegate_to: &PrincipalData,
+        limit: u32,
+    ) -> Result<Vec<PrincipalData>, Error> {
+        let sql = "SELECT DISTINCT stacker FROM pox_delegations WHERE delegate_to = ?1 ORDER BY stacker LIMIT ?2";
+        let args: &[&dyn ToSql] = &[&delegate_to.to_string(), &limit];
+        query_row_columns(conn, sql, args, "stacker").map_err(Error::DBError)
+    }
+
     /// Begin processing an epoch's transactions within the context of a chainstate transaction
     pub fn chainstate_block_begin<'a>(
         chainstate_tx: &'a ChainstateTx<'a>,
@@ -2026,6 +2358,7 @@ pub mod test {
             get_bulk_initial_balances: None,
             get_bulk_initial_names: None,
             get_bulk_initial_namespaces: None,
+            custom_costs_contract: None,
         };
 
         StacksChainState::open_and_exec(
@@ -2072,6 +2405,129 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_open_chainstate_readonly() {
+        let path = chainstate_path("open-chainstate-readonly");
+        let _ = fs::remove_dir_all(&path);
+        assert!(
+            StacksChainState::open_readonly(false, 0x80000000, &path, BLOCK_LIMIT_MAINNET).is_err()
+        );
+
+        // the node that writes the chainstate stays open alongside the read-only handle
+        let _chainstate = instantiate_chainstate(false, 0x80000000, "open-chainstate-readonly");
+        let readonly =
+            StacksChainState::open_readonly(false, 0x80000000, &path, BLOCK_LIMIT_MAINNET).unwrap();
+
+        let boot_header = StacksChainState::get_anchored_block_header_info(
+            readonly.db(),
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+        )
+        .unwrap();
+        assert!(boot_header.is_some());
+        assert_eq!(readonly.block_limit, BLOCK_LIMIT_MAINNET);
+
+        assert!(readonly
+            .db()
+            .execute("DELETE FROM block_headers", NO_PARAMS)
+            .is_err());
+
+        // still checks that it's the right chainstate
+        match StacksChainState::open_readonly(true, 0x80000000, &path, BLOCK_LIMIT_MAINNET) {
+            Err(Error::InvalidChainstateDB) => {}
+            Err(e) => panic!("Unexpected error {:?}", &e),
+            Ok(_) => panic!("Opened a testnet chainstate as mainnet"),
+        }
+    }
+
+    fn make_custom_costs_boot_data(custom_costs: &str) -> ChainStateBootData {
+        ChainStateBootData {
+            initial_balances: vec![],
+            post_flight_callback: None,
+            first_burnchain_block_hash: BurnchainHeaderHash::zero(),
+            first_burnchain_block_height: 0,
+            first_burnchain_block_timestamp: 0,
+            pox_constants: PoxConstants::testnet_default(),
+            get_bulk_initial_lockups: None,
+            get_bulk_initial_balances: None,
+            get_bulk_initial_names: None,
+            get_bulk_initial_namespaces: None,
+            custom_costs_contract: Some(custom_costs.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_instantiate_chainstate_custom_costs() {
+        let custom_costs = BOOT_CODE_COSTS.replacen(
+            "(define-read-only (cost_analysis_visit (n uint))\n    (runtime u1000))",
+            "(define-read-only (cost_analysis_visit (n uint))\n    (runtime u2000))",
+            1,
+        );
+        assert_ne!(custom_costs, BOOT_CODE_COSTS);
+
+        let path = chainstate_path("instantiate-chainstate-custom-costs");
+        if fs::metadata(&path).is_ok() {
+            fs::remove_dir_all(&path).unwrap();
+        }
+        let mut boot_data = make_custom_costs_boot_data(&custom_costs);
+        let (mut chainstate, _) = StacksChainState::open_and_exec(
+            false,
+            0x80000000,
+            &path,
+            Some(&mut boot_data),
+            ExecutionCost::max_value(),
+        )
+        .unwrap();
+
+        let mut conn = chainstate.block_begin(
+            &NULL_BURN_STATE_DB,
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+            &MINER_BLOCK_CONSENSUS_HASH,
+            &MINER_BLOCK_HEADER_HASH,
+        );
+        let costs_src = conn.connection().with_clarity_db_readonly(|clarity_db| {
+            clarity_db.get_contract_src(&boot_code_id("costs", false))
+        });
+        assert_eq!(costs_src, Some(custom_costs.clone()));
+        conn.rollback_block();
+
+        // a costs contract that doesn't define every cost function is rejected
+        let bad_costs = BOOT_CODE_COSTS.replacen(
+            "(define-read-only (cost_analysis_visit (n uint))",
+            "(define-read-only (cost_analysis_visit_renamed (n uint))",
+            1,
+        );
+        let path = chainstate_path("instantiate-chainstate-bad-custom-costs");
+        if fs::metadata(&path).is_ok() {
+            fs::remove_dir_all(&path).unwrap();
+        }
+        let mut boot_data = make_custom_costs_boot_data(&bad_costs);
+        assert!(StacksChainState::open_and_exec(
+            false,
+            0x80000000,
+            &path,
+            Some(&mut boot_data),
+            ExecutionCost::max_value(),
+        )
+        .is_err());
+
+        // custom costs contracts are not allowed on mainnet
+        let path = chainstate_path("instantiate-chainstate-mainnet-custom-costs");
+        if fs::metadata(&path).is_ok() {
+            fs::remove_dir_all(&path).unwrap();
+        }
+        let mut boot_data = make_custom_costs_boot_data(&custom_costs);
+        assert!(StacksChainState::open_and_exec(
+            true,
+            0x00000001,
+            &path,
+            Some(&mut boot_data),
+            ExecutionCost::max_value(),
+        )
+        .is_err());
+    }
+
     #[test]
     fn test_chainstate_sampled_genesis_consistency() {
         // Test root hash for the test chainstate data set
@@ -2124,6 +2580,7 @@ pub mod test {
                         }),
                 )
             })),
+            custom_costs_contract: None,
         };
 
         let path = chainstate_path("genesis-consistency-chainstate-test");
@@ -2219,6 +2676,7 @@ pub mod test {
                         }),
                 )
             })),
+            custom_costs_contract: None,
         };
 
         let path = chainstate_path("genesis-consistency-chainstate");
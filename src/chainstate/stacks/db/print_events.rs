@@ -0,0 +1,435 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020-2021 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Index of the values contracts `print`, maintained only if the node runs with
+//! `STACKS_PRINT_EVENT_INDEX=1`.  Each printed value is recorded under the contract that printed
+//! it and a topic: by convention, a contract that prints a tuple names what happened with the
+//! tuple's first key (e.g. `{ a-transfer: ..., amount: u10 }`), so the topic of a tuple is the
+//! name of its first key, in the order tuples are serialized (sorted by name).  Values that are
+//! not tuples have no topic.  Events of transactions a post-condition aborted were never
+//! committed, so they are not indexed.  Rows are kept for every fork, so they must be checked
+//! against the MARF before they are reported for a given chain tip.
+
+use std::collections::HashMap;
+
+use rusqlite::types::ToSql;
+use rusqlite::Row;
+
+use burnchains::Txid;
+use chainstate::stacks::db::*;
+use chainstate::stacks::events::*;
+use chainstate::stacks::Error;
+use util::db::Error as db_error;
+use util::db::*;
+use vm::database::ClaritySerializable;
+use vm::types::QualifiedContractIdentifier;
+use vm::{ClarityName, Value};
+
+use crate::types::chainstate::StacksBlockId;
+
+/// The topic of a printed value: the name of its first key, if it is a tuple
+pub fn print_topic(value: &Value) -> Option<ClarityName> {
+    match value {
+        Value::Tuple(ref tuple) => tuple.data_map.keys().next().cloned(),
+        _ => None,
+    }
+}
+
+/// A printed value, as recorded in the index
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrintEventEntry {
+    pub txid: Txid,
+    pub index_block_hash: StacksBlockId,
+    pub block_height: u64,
+    /// Position of the transaction among the ones the block processed, including the ones in the
+    /// microblocks it confirmed
+    pub tx_index: u32,
+    /// Position of the event among the transaction's events
+    pub event_index: u32,
+    pub topic: Option<String>,
+    /// The hex-encoded Clarity value that was printed
+    pub value_hex: String,
+}
+
+impl FromRow<PrintEventEntry> for PrintEventEntry {
+    fn from_row<'a>(row: &'a Row) -> Result<PrintEventEntry, db_error> {
+        let txid = Txid::from_column(row, "txid")?;
+        let index_block_hash = StacksBlockId::from_column(row, "index_block_hash")?;
+        let block_height = u64::from_column(row, "block_height")?;
+        let tx_index: u32 = row.get_unwrap("tx_index");
+        let event_index: u32 = row.get_unwrap("event_index");
+        let topic: Option<String> = row.get_unwrap("topic");
+        let value_hex: String = row.get_unwrap("value_hex");
+
+        Ok(PrintEventEntry {
+            txid,
+            index_block_hash,
+            block_height,
+            tx_index,
+            event_index,
+            topic,
+            value_hex,
+        })
+    }
+}
+
+impl<'a> ChainstateTx<'a> {
+    /// Record the values printed by each of a block's transactions into the `print_events`
+    /// index, if it is enabled
+    pub fn index_print_events(
+        &self,
+        block_id: &StacksBlockId,
+        block_height: u64,
+        receipts: &[StacksTransactionReceipt],
+    ) -> Result<(), Error> {
+        if !self.indexes.print_events {
+            return Ok(());
+        }
+        let insert = "INSERT OR REPLACE INTO print_events (contract_identifier, topic, txid, index_block_hash, block_height, tx_index, event_index, value_hex) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)";
+        let block_height = u64_to_sql(block_height).map_err(Error::DBError)?;
+        for (tx_index, receipt) in receipts.iter().enumerate() {
+            if receipt.post_condition_aborted {
+                continue;
+            }
+            let txid = receipt.transaction.txid();
+            for (event_index, event) in receipt.events.iter().enumerate() {
+                let data = match event {
+                    StacksTransactionEvent::SmartContractEvent(ref data)
+                        if data.key.1 == "print" =>
+                    {
+                        data
+                    }
+                    _ => continue,
+                };
+                let params: &[&dyn ToSql] = &[
+                    &data.key.0.to_string(),
+                    &print_topic(&data.value).map(|topic| topic.to_string()),
+                    &txid,
+                    block_id,
+                    &block_height,
+                    &(tx_index as u32),
+                    &(event_index as u32),
+                    &data.value.serialize(),
+                ];
+                self.tx
+                    .tx()
+                    .execute(insert, params)
+                    .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StacksChainState {
+    /// Get up to `limit` of the values `contract` printed in `tip`'s fork, optionally only the
+    /// ones with the given topic, most recent first.  If `before` is given as (block height, tx
+    /// index, event index), only the values printed before that position are returned; pass the
+    /// position of the last value in a page to get the next page.  Returns an empty page if the
+    /// index is not enabled.
+    pub fn get_print_events(
+        &self,
+        tip: &StacksBlockId,
+        contract: &QualifiedContractIdentifier,
+        topic: Option<&ClarityName>,
+        before: Option<(u64, u32, u32)>,
+        limit: u64,
+    ) -> Result<Vec<PrintEventEntry>, Error> {
+        let index_conn = self.index_conn()?;
+        let tip_height = match index_conn
+            .get_ancestor_block_height(tip, tip)
+            .map_err(Error::DBError)?
+        {
+            Some(height) => height,
+            None => return Err(Error::NoSuchBlockError),
+        };
+
+        // nothing above the tip is in its fork
+        let (before_height, before_tx_index, before_event_index) = match before {
+            Some((height, tx_index, event_index)) if height <= tip_height => {
+                (height, tx_index, event_index)
+            }
+            _ => (tip_height + 1, 0, 0),
+        };
+        let contract_identifier = contract.to_string();
+        let before_height = u64_to_sql(before_height).map_err(Error::DBError)?;
+        let topic = topic.map(|topic| topic.to_string());
+        let (sql, args): (&str, Vec<&dyn ToSql>) = match topic {
+            Some(ref topic) => (
+                "SELECT * FROM print_events WHERE contract_identifier = ?1 AND topic = ?2 AND (block_height < ?3 OR (block_height = ?3 AND (tx_index < ?4 OR (tx_index = ?4 AND event_index < ?5)))) ORDER BY block_height DESC, tx_index DESC, event_index DESC",
+                vec![
+                    &contract_identifier as &dyn ToSql,
+                    topic,
+                    &before_height,
+                    &before_tx_index,
+                    &before_event_index,
+                ],
+            ),
+            None => (
+                "SELECT * FROM print_events WHERE contract_identifier = ?1 AND (block_height < ?2 OR (block_height = ?2 AND (tx_index < ?3 OR (tx_index = ?3 AND event_index < ?4)))) ORDER BY block_height DESC, tx_index DESC, event_index DESC",
+                vec![
+                    &contract_identifier as &dyn ToSql,
+                    &before_height,
+                    &before_tx_index,
+                    &before_event_index,
+                ],
+            ),
+        };
+        let mut stmt = self
+            .db()
+            .prepare(sql)
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        let mut rows = stmt
+            .query(args.as_slice())
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+
+        // the block in `tip`'s fork at each height we've looked at so far
+        let mut fork_blocks: HashMap<u64, Option<StacksBlockId>> = HashMap::new();
+        let mut page = vec![];
+        while let Some(row) = rows
+            .next()
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?
+        {
+            if page.len() as u64 >= limit {
+                break;
+            }
+            let entry = PrintEventEntry::from_row(row).map_err(Error::DBError)?;
+            if !fork_blocks.contains_key(&entry.block_height) {
+                let block_id = index_conn
+                    .get_ancestor_block_hash(entry.block_height, tip)
+                    .map_err(Error::DBError)?;
+                fork_blocks.insert(entry.block_height, block_id);
+            }
+            if fork_blocks[&entry.block_height].as_ref() != Some(&entry.index_block_hash) {
+                continue;
+            }
+            page.push(entry);
+        }
+        Ok(page)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::convert::TryFrom;
+
+    use chainstate::stacks::db::test::instantiate_chainstate;
+    use core::{FIRST_BURNCHAIN_CONSENSUS_HASH, FIRST_STACKS_BLOCK_HASH};
+    use vm::types::TupleData;
+
+    use vm::costs::ExecutionCost;
+
+    use crate::types::chainstate::StacksBlockHeader;
+
+    fn tuple(topic: &str) -> Value {
+        Value::Tuple(TupleData::from_data(vec![(topic.into(), Value::UInt(1))]).unwrap())
+    }
+
+    fn print_receipt(
+        contract: &QualifiedContractIdentifier,
+        values: Vec<Value>,
+        post_condition_aborted: bool,
+    ) -> StacksTransactionReceipt {
+        StacksTransactionReceipt {
+            transaction: TransactionOrigin::Burn(Txid([0x01; 32])),
+            events: values
+                .into_iter()
+                .map(|value| {
+                    StacksTransactionEvent::SmartContractEvent(SmartContractEventData {
+                        key: (contract.clone(), "print".to_string()),
+                        value,
+                    })
+                })
+                .collect(),
+            post_condition_aborted,
+            result: Value::okay_true(),
+            stx_burned: 0,
+            contract_analysis: None,
+            execution_cost: ExecutionCost::zero(),
+            vm_error: None,
+        }
+    }
+
+    #[test]
+    fn print_topics() {
+        let tuple = Value::Tuple(
+            TupleData::from_data(vec![
+                ("amount".into(), Value::UInt(10)),
+                ("a-transfer".into(), Value::Bool(true)),
+            ])
+            .unwrap(),
+        );
+        assert_eq!(
+            print_topic(&tuple),
+            Some(ClarityName::try_from("a-transfer".to_string()).unwrap())
+        );
+        assert_eq!(print_topic(&Value::UInt(10)), None);
+    }
+
+    #[test]
+    fn print_events_follow_the_fork() {
+        let mut chainstate =
+            instantiate_chainstate(false, 0x80000000, "print_events_follow_the_fork");
+        let contract =
+            QualifiedContractIdentifier::parse("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R.market")
+                .unwrap();
+
+        // two forks off of the boot block: A (heights 1 and 2) and B (height 1)
+        let boot_block = StacksBlockHeader::make_index_block_hash(
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+        );
+        let block_a1 = StacksBlockId([0xa1; 32]);
+        let block_a2 = StacksBlockId([0xa2; 32]);
+        let block_b1 = StacksBlockId([0xb1; 32]);
+        for (parent, child) in [
+            (&boot_block, &block_a1),
+            (&block_a1, &block_a2),
+            (&boot_block, &block_b1),
+        ]
+        .iter()
+        {
+            let mut tx = chainstate.index_tx_begin().unwrap();
+            tx.put_indexed_begin(parent, child).unwrap();
+            tx.put_indexed_all(&vec![], &vec![]).unwrap();
+            tx.commit().unwrap();
+        }
+
+        // nothing is indexed unless the index is enabled
+        chainstate.indexes.print_events = false;
+        let (chainstate_tx, _) = chainstate.chainstate_tx_begin().unwrap();
+        chainstate_tx
+            .index_print_events(
+                &block_a1,
+                1,
+                &[print_receipt(&contract, vec![tuple("listed")], false)],
+            )
+            .unwrap();
+        chainstate_tx.commit().unwrap();
+        assert_eq!(
+            chainstate
+                .get_print_events(&block_a1, &contract, None, None, 10)
+                .unwrap(),
+            vec![]
+        );
+
+        // A1 prints a listing and a sale, A2 a sale (and one a post-condition aborted), B1 a
+        // listing and a value with no topic
+        chainstate.indexes.print_events = true;
+        for (block_id, height, receipts) in [
+            (
+                &block_a1,
+                1u64,
+                vec![print_receipt(
+                    &contract,
+                    vec![tuple("listed"), tuple("sold")],
+                    false,
+                )],
+            ),
+            (
+                &block_a2,
+                2,
+                vec![
+                    print_receipt(&contract, vec![tuple("sold")], false),
+                    print_receipt(&contract, vec![tuple("sold")], true),
+                ],
+            ),
+            (
+                &block_b1,
+                1,
+                vec![print_receipt(
+                    &contract,
+                    vec![tuple("listed"), Value::UInt(1)],
+                    false,
+                )],
+            ),
+        ]
+        .iter()
+        {
+            let (chainstate_tx, _) = chainstate.chainstate_tx_begin().unwrap();
+            chainstate_tx
+                .index_print_events(block_id, *height, receipts)
+                .unwrap();
+            chainstate_tx.commit().unwrap();
+        }
+
+        let events = chainstate
+            .get_print_events(&block_a2, &contract, None, None, 10)
+            .unwrap();
+        let positions: Vec<_> = events
+            .iter()
+            .map(|entry| (entry.index_block_hash.clone(), entry.event_index))
+            .collect();
+        assert_eq!(
+            positions,
+            vec![
+                (block_a2.clone(), 0),
+                (block_a1.clone(), 1),
+                (block_a1.clone(), 0)
+            ]
+        );
+
+        let sold = ClarityName::try_from("sold".to_string()).unwrap();
+        let events = chainstate
+            .get_print_events(&block_a2, &contract, Some(&sold), None, 10)
+            .unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .all(|entry| entry.topic.as_deref() == Some("sold")));
+
+        // pages pick up where the last one left off, even within a transaction
+        let events = chainstate
+            .get_print_events(&block_a2, &contract, None, Some((2, 0, 0)), 1)
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].index_block_hash, block_a1);
+        assert_eq!(events[0].event_index, 1);
+        let events = chainstate
+            .get_print_events(&block_a2, &contract, None, Some((1, 0, 1)), 10)
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].index_block_hash, block_a1);
+        assert_eq!(events[0].event_index, 0);
+        assert_eq!(
+            chainstate
+                .get_print_events(&block_a2, &contract, Some(&sold), Some((1, 0, 1)), 10)
+                .unwrap(),
+            vec![]
+        );
+
+        // fork B only sees its own
+        let events = chainstate
+            .get_print_events(&block_b1, &contract, None, None, 10)
+            .unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events
+            .iter()
+            .all(|entry| entry.index_block_hash == block_b1));
+        assert_eq!(
+            chainstate
+                .get_print_events(&block_b1, &contract, Some(&sold), None, 10)
+                .unwrap(),
+            vec![]
+        );
+
+        assert!(chainstate
+            .get_print_events(&StacksBlockId([0xff; 32]), &contract, None, None, 10)
+            .is_err());
+    }
+}
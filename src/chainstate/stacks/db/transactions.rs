@@ -67,6 +67,22 @@ impl std::hash::Hash for Value {
     }
 }
 
+/// How a transaction's post-conditions fare against the assets it moved
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostConditionsCheck {
+    /// Whether each post-condition holds, in the order the transaction lists them
+    pub condition_results: Vec<bool>,
+    /// In deny mode, the assets that were moved without a post-condition covering them
+    pub unchecked_assets: Vec<String>,
+}
+
+impl PostConditionsCheck {
+    /// Whether the transaction's post-conditions let it go ahead
+    pub fn passed(&self) -> bool {
+        self.condition_results.iter().all(|passed| *passed) && self.unchecked_assets.is_empty()
+    }
+}
+
 impl StacksTransactionReceipt {
     pub fn from_stx_transfer(
         tx: StacksTransaction,
@@ -82,6 +98,7 @@ impl StacksTransactionReceipt {
             contract_analysis: None,
             transaction: tx.into(),
             execution_cost: cost,
+            vm_error: None,
         }
     }
 
@@ -100,6 +117,7 @@ impl StacksTransactionReceipt {
             stx_burned: burned,
             contract_analysis: None,
             execution_cost: cost,
+            vm_error: None,
         }
     }
 
@@ -118,6 +136,7 @@ impl StacksTransactionReceipt {
             stx_burned: burned,
             contract_analysis: None,
             execution_cost: cost,
+            vm_error: None,
         }
     }
 
@@ -136,6 +155,7 @@ impl StacksTransactionReceipt {
             stx_burned: burned,
             contract_analysis: Some(analysis),
             execution_cost: cost,
+            vm_error: None,
         }
     }
 
@@ -154,6 +174,7 @@ impl StacksTransactionReceipt {
             stx_burned: burned,
             contract_analysis: Some(analysis),
             execution_cost: cost,
+            vm_error: None,
         }
     }
 
@@ -166,6 +187,7 @@ impl StacksTransactionReceipt {
             stx_burned: 0,
             contract_analysis: None,
             execution_cost: ExecutionCost::zero(),
+            vm_error: None,
         }
     }
 
@@ -181,6 +203,7 @@ impl StacksTransactionReceipt {
             stx_burned: 0,
             contract_analysis: None,
             execution_cost: analysis_cost,
+            vm_error: Some(TransactionErrorCode::ContractAnalysisFailure),
         }
     }
 
@@ -197,6 +220,7 @@ impl StacksTransactionReceipt {
             stx_burned: 0,
             contract_analysis: None,
             execution_cost: cost,
+            vm_error: None,
         }
     }
 
@@ -388,6 +412,14 @@ impl StacksChainState {
         // valid auth?
         tx.verify().map_err(Error::NetError)?;
 
+        StacksChainState::process_verified_transaction_precheck(config, tx)
+    }
+
+    /// Pre-check a transaction whose signatures have already been verified
+    fn process_verified_transaction_precheck(
+        config: &DBConfig,
+        tx: &StacksTransaction,
+    ) -> Result<(), Error> {
         // destined for us?
         if config.chain_id != tx.chain_id {
             let msg = format!(
@@ -432,6 +464,23 @@ impl StacksChainState {
         origin_account: &StacksAccount,
         asset_map: &AssetMap,
     ) -> bool {
+        StacksChainState::evaluate_transaction_postconditions(
+            post_conditions,
+            post_condition_mode,
+            &origin_account.principal,
+            asset_map,
+        )
+        .passed()
+    }
+
+    /// Evaluate each of a transaction's post-conditions against the assets it moved, and (in deny
+    /// mode) find the moved assets that no post-condition covers.
+    pub fn evaluate_transaction_postconditions(
+        post_conditions: &Vec<TransactionPostCondition>,
+        post_condition_mode: &TransactionPostConditionMode,
+        origin_principal: &PrincipalData,
+        asset_map: &AssetMap,
+    ) -> PostConditionsCheck {
         let mut checked_fungible_assets: HashMap<PrincipalData, HashSet<AssetIdentifier>> =
             HashMap::new();
         let mut checked_nonfungible_assets: HashMap<
@@ -439,6 +488,8 @@ impl StacksChainState {
             HashMap<AssetIdentifier, HashSet<Value>>,
         > = HashMap::new();
         let allow_unchecked_assets = *post_condition_mode == TransactionPostConditionMode::Allow;
+        let mut condition_results = Vec::with_capacity(post_conditions.len());
+        let mut unchecked_assets = vec![];
 
         for postcond in post_conditions {
             match postcond {
@@ -447,7 +498,7 @@ impl StacksChainState {
                     ref condition_code,
                     ref amount_sent_condition,
                 ) => {
-                    let account_principal = principal.to_principal_data(&origin_account.principal);
+                    let account_principal = principal.to_principal_data(origin_principal);
 
                     let amount_transferred = asset_map.get_stx(&account_principal).unwrap_or(0);
                     let amount_burned = asset_map.get_stx_burned(&account_principal).unwrap_or(0);
@@ -456,13 +507,14 @@ impl StacksChainState {
                         .checked_add(amount_burned)
                         .expect("FATAL: sent waaaaay too much STX");
 
-                    if !condition_code.check(*amount_sent_condition as u128, amount_sent) {
+                    let passed = condition_code.check(*amount_sent_condition as u128, amount_sent);
+                    if !passed {
                         info!(
                             "Post-condition check failure on STX owned by {}: {:?} {:?} {}",
                             account_principal, amount_sent_condition, condition_code, amount_sent
                         );
-                        return false;
                     }
+                    condition_results.push(passed);
 
                     if let Some(ref mut asset_ids) =
                         checked_fungible_assets.get_mut(&account_principal)
@@ -490,7 +542,7 @@ impl StacksChainState {
                     ref condition_code,
                     ref amount_sent_condition,
                 ) => {
-                    let account_principal = principal.to_principal_data(&origin_account.principal);
+                    let account_principal = principal.to_principal_data(origin_principal);
                     let asset_id = AssetIdentifier {
                         contract_identifier: QualifiedContractIdentifier::new(
                             StandardPrincipalData::from(asset_info.contract_address.clone()),
@@ -502,10 +554,11 @@ impl StacksChainState {
                     let amount_sent = asset_map
                         .get_fungible_tokens(&account_principal, &asset_id)
                         .unwrap_or(0);
-                    if !condition_code.check(*amount_sent_condition as u128, amount_sent) {
+                    let passed = condition_code.check(*amount_sent_condition as u128, amount_sent);
+                    if !passed {
                         info!("Post-condition check failure on fungible asset {} owned by {}: {} {:?} {}", &asset_id, account_principal, amount_sent_condition, condition_code, amount_sent);
-                        return false;
                     }
+                    condition_results.push(passed);
 
                     if let Some(ref mut asset_ids) =
                         checked_fungible_assets.get_mut(&account_principal)
@@ -523,7 +576,7 @@ impl StacksChainState {
                     ref asset_value,
                     ref condition_code,
                 ) => {
-                    let account_principal = principal.to_principal_data(&origin_account.principal);
+                    let account_principal = principal.to_principal_data(origin_principal);
                     let asset_id = AssetIdentifier {
                         contract_identifier: QualifiedContractIdentifier::new(
                             StandardPrincipalData::from(asset_info.contract_address.clone()),
@@ -536,10 +589,11 @@ impl StacksChainState {
                     let assets_sent = asset_map
                         .get_nonfungible_tokens(&account_principal, &asset_id)
                         .unwrap_or(&empty_assets);
-                    if !condition_code.check(asset_value, assets_sent) {
+                    let passed = condition_code.check(asset_value, assets_sent);
+                    if !passed {
                         info!("Post-condition check failure on non-fungible asset {} owned by {}: {:?} {:?}", &asset_id, account_principal, &asset_value, condition_code);
-                        return false;
                     }
+                    condition_results.push(passed);
 
                     if let Some(ref mut asset_id_map) =
                         checked_nonfungible_assets.get_mut(&account_principal)
@@ -580,18 +634,25 @@ impl StacksChainState {
                                     for v in values {
                                         if !nfts.contains(&v) {
                                             info!("Post-condition check failure: Non-fungible asset {} value {:?} was moved by {} but not checked", &asset_identifier, &v, &principal);
-                                            return false;
+                                            unchecked_assets.push(format!(
+                                                "{} {} sent by {}",
+                                                &asset_identifier, &v, &principal
+                                            ));
                                         }
                                     }
                                 } else {
                                     // no values covered
                                     info!("Post-condition check failure: No checks for non-fungible asset type {} moved by {}", &asset_identifier, &principal);
-                                    return false;
+                                    unchecked_assets.push(format!(
+                                        "{} sent by {}",
+                                        &asset_identifier, &principal
+                                    ));
                                 }
                             } else {
                                 // no NFT for this principal
                                 info!("Post-condition check failure: No checks for any non-fungible assets, but moved {} by {}", &asset_identifier, &principal);
-                                return false;
+                                unchecked_assets
+                                    .push(format!("{} sent by {}", &asset_identifier, &principal));
                             }
                         }
                         _ => {
@@ -601,18 +662,26 @@ impl StacksChainState {
                             {
                                 if !checked_ft_asset_ids.contains(&asset_identifier) {
                                     info!("Post-condition check failure: checks did not cover transfer of {} by {}", &asset_identifier, &principal);
-                                    return false;
+                                    unchecked_assets.push(format!(
+                                        "{} sent by {}",
+                                        &asset_identifier, &principal
+                                    ));
                                 }
                             } else {
                                 info!("Post-condition check failure: No checks for fungible token type {} moved by {}", &asset_identifier, &principal);
-                                return false;
+                                unchecked_assets
+                                    .push(format!("{} sent by {}", &asset_identifier, &principal));
                             }
                         }
                     }
                 }
             }
         }
-        return true;
+
+        PostConditionsCheck {
+            condition_results,
+            unchecked_assets,
+        }
     }
 
     /// Given two microblock headers, were they signed by the same key?
@@ -879,7 +948,7 @@ impl StacksChainState {
                     .sub(&cost_before)
                     .expect("BUG: total block cost decreased");
 
-                let (result, asset_map, events) = match contract_call_resp {
+                let (result, asset_map, events, vm_error) = match contract_call_resp {
                     Ok((return_value, asset_map, events)) => {
                         info!("Contract-call successfully processed";
                               "contract_name" => %contract_id,
@@ -887,16 +956,18 @@ impl StacksChainState {
                               "function_args" => %VecDisplay(&contract_call.function_args),
                               "return_value" => %return_value,
                               "cost" => ?total_cost);
-                        (return_value, asset_map, events)
+                        (return_value, asset_map, events, None)
                     }
                     Err(e) => match handle_clarity_runtime_error(e) {
                         ClarityRuntimeTxError::Acceptable { error, err_type } => {
+                            let vm_error = TransactionErrorCode::from_clarity_error(&error);
                             info!("Contract-call processed with {}", err_type;
                                       "contract_name" => %contract_id,
                                       "function_name" => %contract_call.function_name,
                                       "function_args" => %VecDisplay(&contract_call.function_args),
-                                      "error" => ?error);
-                            (Value::err_none(), AssetMap::new(), vec![])
+                                      "error" => ?error,
+                                      "vm_error" => ?vm_error);
+                            (Value::err_none(), AssetMap::new(), vec![], vm_error)
                         }
                         ClarityRuntimeTxError::AbortedByCallback(value, assets, events) => {
                             info!("Contract-call aborted by post-condition";
@@ -926,13 +997,14 @@ impl StacksChainState {
                     },
                 };
 
-                let receipt = StacksTransactionReceipt::from_contract_call(
+                let mut receipt = StacksTransactionReceipt::from_contract_call(
                     tx.clone(),
                     events,
                     result,
                     asset_map.get_stx_burned_total(),
                     total_cost,
                 );
+                receipt.vm_error = vm_error;
                 Ok(receipt)
             }
             TransactionPayload::SmartContract(ref smart_contract) => {
@@ -1026,21 +1098,23 @@ impl StacksChainState {
                     .sub(&cost_before)
                     .expect("BUG: total block cost decreased");
 
-                let (asset_map, events) = match initialize_resp {
-                    Ok(x) => {
+                let (asset_map, events, vm_error) = match initialize_resp {
+                    Ok((asset_map, events)) => {
                         // store analysis -- if this fails, then the have some pretty bad problems
                         clarity_tx
                             .save_analysis(&contract_id, &contract_analysis)
                             .expect("FATAL: failed to store contract analysis");
-                        x
+                        (asset_map, events, None)
                     }
                     Err(e) => match handle_clarity_runtime_error(e) {
                         ClarityRuntimeTxError::Acceptable { error, err_type } => {
+                            let vm_error = TransactionErrorCode::from_clarity_error(&error);
                             info!("Smart-contract processed with {}", err_type;
                                       "contract" => %contract_id,
                                       "code" => %contract_code_str,
-                                      "error" => ?error);
-                            (AssetMap::new(), vec![])
+                                      "error" => ?error,
+                                      "vm_error" => ?vm_error);
+                            (AssetMap::new(), vec![], vm_error)
                         }
                         ClarityRuntimeTxError::AbortedByCallback(_, assets, events) => {
                             let receipt =
@@ -1070,13 +1144,14 @@ impl StacksChainState {
                     },
                 };
 
-                let receipt = StacksTransactionReceipt::from_smart_contract(
+                let mut receipt = StacksTransactionReceipt::from_smart_contract(
                     tx.clone(),
                     events,
                     asset_map.get_stx_burned_total(),
                     contract_analysis,
                     total_cost,
                 );
+                receipt.vm_error = vm_error;
                 Ok(receipt)
             }
             TransactionPayload::PoisonMicroblock(ref mblock_header_1, ref mblock_header_2) => {
@@ -1123,7 +1198,32 @@ impl StacksChainState {
         debug!("Process transaction {} ({})", tx.txid(), tx.payload.name());
 
         StacksChainState::process_transaction_precheck(&clarity_block.config, tx)?;
+        StacksChainState::apply_transaction(clarity_block, tx, quiet)
+    }
+
+    /// Process a transaction whose signatures have already been verified, such as by
+    /// `verify_transaction_signatures()`.  Otherwise the same as `process_transaction()`.
+    pub fn process_verified_transaction(
+        clarity_block: &mut ClarityTx,
+        tx: &StacksTransaction,
+        quiet: bool,
+    ) -> Result<(u64, StacksTransactionReceipt), Error> {
+        debug!(
+            "Process verified transaction {} ({})",
+            tx.txid(),
+            tx.payload.name()
+        );
+
+        StacksChainState::process_verified_transaction_precheck(&clarity_block.config, tx)?;
+        StacksChainState::apply_transaction(clarity_block, tx, quiet)
+    }
 
+    /// Run a pre-checked transaction, and charge its fee
+    fn apply_transaction(
+        clarity_block: &mut ClarityTx,
+        tx: &StacksTransaction,
+        quiet: bool,
+    ) -> Result<(u64, StacksTransactionReceipt), Error> {
         let mut transaction = clarity_block.connection().start_transaction_processing();
         let (origin_account, payer_account) =
             StacksChainState::check_transaction_nonces(&mut transaction, tx, quiet)?;
@@ -1792,6 +1892,11 @@ pub mod test {
         ];
 
         let contract_names = vec!["hello-world-0", "hello-world-1", "hello-world-2"];
+        let expected_vm_errors = vec![
+            None,
+            Some(TransactionErrorCode::DivisionByZero),
+            Some(TransactionErrorCode::DivisionByZero),
+        ];
 
         for i in 0..contracts.len() {
             let contract_name = contract_names[i].to_string();
@@ -1824,8 +1929,9 @@ pub mod test {
             assert_eq!(account.nonce, i as u64);
 
             // runtime error should be handled
-            let (_fee, _) =
+            let (_fee, receipt) =
                 StacksChainState::process_transaction(&mut conn, &signed_tx, false).unwrap();
+            assert_eq!(receipt.vm_error, expected_vm_errors[i]);
 
             // account nonce should increment
             let account = StacksChainState::get_account(&mut conn, &addr.to_account_principal());
@@ -2100,8 +2206,13 @@ pub mod test {
 
         // contract-calls that don't commit
         let contract_calls = vec![
-            ("hello-world", "set-bar", vec![Value::Int(1), Value::Int(0)]), // divide-by-zero
-            ("hello-world", "return-error", vec![]),                        // returns an (err ...)
+            (
+                "hello-world",
+                "set-bar",
+                vec![Value::Int(1), Value::Int(0)],
+                Some(TransactionErrorCode::DivisionByZero),
+            ), // divide-by-zero
+            ("hello-world", "return-error", vec![], None), // returns an (err ...)
         ];
 
         // do contract-calls
@@ -2115,7 +2226,8 @@ pub mod test {
         let mut next_nonce = 0;
 
         for contract_call in contract_calls {
-            let (contract_name, contract_function, contract_args) = contract_call;
+            let (contract_name, contract_function, contract_args, expected_vm_error) =
+                contract_call;
             let mut tx_contract_call = StacksTransaction::new(
                 TransactionVersion::Testnet,
                 auth_2.clone(),
@@ -2141,9 +2253,12 @@ pub mod test {
                 StacksChainState::get_account(&mut conn, &addr_2.to_account_principal());
             assert_eq!(account_2.nonce, next_nonce);
 
-            let (_fee, _) =
+            let (_fee, receipt) =
                 StacksChainState::process_transaction(&mut conn, &signed_tx_2, false).unwrap();
 
+            // runtime errors are reported in the receipt
+            assert_eq!(receipt.vm_error, expected_vm_error);
+
             // nonce should have incremented
             next_nonce += 1;
             let account_2 =
@@ -6203,6 +6318,75 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_evaluate_postconditions() {
+        let privk = StacksPrivateKey::from_hex(
+            "6d430bb91222408e7706c9001cfaeb91b08c2be6d5ac95779ab52c6b431950e001",
+        )
+        .unwrap();
+        let auth = TransactionAuth::from_p2pkh(&privk).unwrap();
+        let origin = auth.origin().address_testnet().to_account_principal();
+        let contract_addr = StacksAddress {
+            version: 1,
+            bytes: Hash160([0x01; 20]),
+        };
+        let asset_info = AssetInfo {
+            contract_address: contract_addr.clone(),
+            contract_name: ContractName::try_from("hello-world").unwrap(),
+            asset_name: ClarityName::try_from("test-asset").unwrap(),
+        };
+        let asset_id = AssetIdentifier {
+            contract_identifier: QualifiedContractIdentifier::new(
+                StandardPrincipalData::from(contract_addr),
+                asset_info.contract_name.clone(),
+            ),
+            asset_name: asset_info.asset_name.clone(),
+        };
+
+        let mut asset_map = AssetMap::new();
+        asset_map.add_stx_transfer(&origin, 123).unwrap();
+        asset_map
+            .add_token_transfer(&origin, asset_id.clone(), 10)
+            .unwrap();
+
+        // every condition is evaluated, not just up to the first failure
+        let post_conditions = vec![
+            TransactionPostCondition::STX(
+                PostConditionPrincipal::Origin,
+                FungibleConditionCode::SentGt,
+                123,
+            ),
+            TransactionPostCondition::STX(
+                PostConditionPrincipal::Origin,
+                FungibleConditionCode::SentEq,
+                123,
+            ),
+        ];
+        let check = StacksChainState::evaluate_transaction_postconditions(
+            &post_conditions,
+            &TransactionPostConditionMode::Deny,
+            &origin,
+            &asset_map,
+        );
+        assert_eq!(check.condition_results, vec![false, true]);
+        assert_eq!(
+            check.unchecked_assets,
+            vec![format!("{} sent by {}", &asset_id, &origin)]
+        );
+        assert!(!check.passed());
+
+        // in allow mode, only the listed conditions matter
+        let check = StacksChainState::evaluate_transaction_postconditions(
+            &post_conditions[1..].to_vec(),
+            &TransactionPostConditionMode::Allow,
+            &origin,
+            &asset_map,
+        );
+        assert_eq!(check.condition_results, vec![true]);
+        assert!(check.unchecked_assets.is_empty());
+        assert!(check.passed());
+    }
+
     #[test]
     fn test_check_postconditions_stx() {
         let privk = StacksPrivateKey::from_hex(
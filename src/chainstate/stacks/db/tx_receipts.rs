@@ -0,0 +1,317 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020-2021 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Receipts of the transactions in processed blocks, stored only if the node runs with
+//! `STACKS_TX_RECEIPT_INDEX=1`.  A receipt records what the transaction did: its result, its
+//! events, and what it cost.  The same transaction can be mined in more than one fork, so there
+//! is a receipt for each block it was processed in, and the one reported for a given chain tip is
+//! the one in the tip's fork.
+
+use rusqlite::types::ToSql;
+use rusqlite::Row;
+
+use burnchains::Txid;
+use chainstate::stacks::db::*;
+use chainstate::stacks::events::*;
+use chainstate::stacks::Error;
+use util::db::Error as db_error;
+use util::db::*;
+use vm::costs::ExecutionCost;
+use vm::database::ClaritySerializable;
+
+use crate::types::chainstate::StacksBlockId;
+
+/// A processed transaction's receipt, as stored in the index
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransactionReceiptEntry {
+    pub txid: Txid,
+    pub index_block_hash: StacksBlockId,
+    pub block_height: u64,
+    /// Position of the transaction among the ones the block processed, including the ones in the
+    /// microblocks it confirmed
+    pub tx_index: u32,
+    /// The hex-encoded Clarity result of the transaction
+    pub result_hex: String,
+    pub post_condition_aborted: bool,
+    pub stx_burned: u128,
+    pub execution_cost: ExecutionCost,
+    pub vm_error: Option<TransactionErrorCode>,
+    /// The transaction's events, in the JSON form event observers are sent
+    pub events: Vec<serde_json::Value>,
+}
+
+impl FromRow<TransactionReceiptEntry> for TransactionReceiptEntry {
+    fn from_row<'a>(row: &'a Row) -> Result<TransactionReceiptEntry, db_error> {
+        let txid = Txid::from_column(row, "txid")?;
+        let index_block_hash = StacksBlockId::from_column(row, "index_block_hash")?;
+        let block_height = u64::from_column(row, "block_height")?;
+        let tx_index: u32 = row.get_unwrap("tx_index");
+        let result_hex: String = row.get_unwrap("result_hex");
+        let post_condition_aborted: bool = row.get_unwrap("post_condition_aborted");
+        let stx_burned_str: String = row.get_unwrap("stx_burned");
+        let stx_burned = stx_burned_str
+            .parse::<u128>()
+            .map_err(|_e| db_error::ParseError)?;
+        let execution_cost_json: String = row.get_unwrap("execution_cost");
+        let execution_cost =
+            serde_json::from_str(&execution_cost_json).map_err(db_error::SerializationError)?;
+        let vm_error_code: Option<u32> = row.get_unwrap("vm_error");
+        let vm_error = match vm_error_code {
+            Some(code) => Some(TransactionErrorCode::from_code(code).ok_or(db_error::ParseError)?),
+            None => None,
+        };
+        let events_json: String = row.get_unwrap("events");
+        let events = serde_json::from_str(&events_json).map_err(db_error::SerializationError)?;
+
+        Ok(TransactionReceiptEntry {
+            txid,
+            index_block_hash,
+            block_height,
+            tx_index,
+            result_hex,
+            post_condition_aborted,
+            stx_burned,
+            execution_cost,
+            vm_error,
+            events,
+        })
+    }
+}
+
+/// A receipt's events, in the JSON form event observers are sent.  Events of a transaction that
+/// a post-condition aborted are reported, but not committed.
+fn events_json(receipt: &StacksTransactionReceipt) -> Vec<serde_json::Value> {
+    let txid = receipt.transaction.txid();
+    receipt
+        .events
+        .iter()
+        .enumerate()
+        .map(|(i, event)| event.json_serialize(i, &txid, !receipt.post_condition_aborted))
+        .collect()
+}
+
+impl<'a> ChainstateTx<'a> {
+    /// Store the receipts of a block's transactions into the `transaction_receipts` index, if
+    /// it is enabled
+    pub fn index_transaction_receipts(
+        &self,
+        block_id: &StacksBlockId,
+        block_height: u64,
+        receipts: &[StacksTransactionReceipt],
+    ) -> Result<(), Error> {
+        if !self.indexes.tx_receipts {
+            return Ok(());
+        }
+        let insert = "INSERT OR REPLACE INTO transaction_receipts (txid, index_block_hash, block_height, tx_index, result_hex, post_condition_aborted, stx_burned, execution_cost, vm_error, events) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)";
+        let block_height = u64_to_sql(block_height).map_err(Error::DBError)?;
+        for (tx_index, receipt) in receipts.iter().enumerate() {
+            let execution_cost = serde_json::to_string(&receipt.execution_cost)
+                .map_err(|e| Error::DBError(db_error::SerializationError(e)))?;
+            let events = serde_json::to_string(&events_json(receipt))
+                .map_err(|e| Error::DBError(db_error::SerializationError(e)))?;
+            let params: &[&dyn ToSql] = &[
+                &receipt.transaction.txid(),
+                block_id,
+                &block_height,
+                &(tx_index as u32),
+                &receipt.result.serialize(),
+                &receipt.post_condition_aborted,
+                &receipt.stx_burned.to_string(),
+                &execution_cost,
+                &receipt.vm_error.map(|vm_error| vm_error.code()),
+                &events,
+            ];
+            self.tx
+                .tx()
+                .execute(insert, params)
+                .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        }
+        Ok(())
+    }
+}
+
+impl StacksChainState {
+    /// Get the receipt of the transaction with the given txid in `tip`'s fork, if it was mined
+    /// there.  Returns None if the index is not enabled.
+    pub fn get_transaction_receipt(
+        &self,
+        tip: &StacksBlockId,
+        txid: &Txid,
+    ) -> Result<Option<TransactionReceiptEntry>, Error> {
+        let index_conn = self.index_conn()?;
+        let tip_height = match index_conn
+            .get_ancestor_block_height(tip, tip)
+            .map_err(Error::DBError)?
+        {
+            Some(height) => height,
+            None => return Err(Error::NoSuchBlockError),
+        };
+
+        let sql = "SELECT * FROM transaction_receipts WHERE txid = ?1 AND block_height <= ?2 ORDER BY block_height DESC";
+        let args: &[&dyn ToSql] = &[txid, &u64_to_sql(tip_height).map_err(Error::DBError)?];
+        let entries: Vec<TransactionReceiptEntry> =
+            query_rows(self.db(), sql, args).map_err(Error::DBError)?;
+        for entry in entries.into_iter() {
+            let block_id = index_conn
+                .get_ancestor_block_hash(entry.block_height, tip)
+                .map_err(Error::DBError)?;
+            if block_id.as_ref() == Some(&entry.index_block_hash) {
+                return Ok(Some(entry));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chainstate::stacks::db::test::instantiate_chainstate;
+    use core::{FIRST_BURNCHAIN_CONSENSUS_HASH, FIRST_STACKS_BLOCK_HASH};
+    use vm::types::PrincipalData;
+    use vm::Value;
+
+    use crate::types::chainstate::{StacksAddress, StacksBlockHeader};
+    use util::hash::Hash160;
+
+    #[test]
+    fn transaction_receipts_follow_the_fork() {
+        let mut chainstate =
+            instantiate_chainstate(false, 0x80000000, "transaction_receipts_follow_the_fork");
+        let txid = Txid([0x01; 32]);
+
+        // two forks off of the boot block: A (heights 1 and 2) and B (height 1)
+        let boot_block = StacksBlockHeader::make_index_block_hash(
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+        );
+        let block_a1 = StacksBlockId([0xa1; 32]);
+        let block_a2 = StacksBlockId([0xa2; 32]);
+        let block_b1 = StacksBlockId([0xb1; 32]);
+        for (parent, child) in [
+            (&boot_block, &block_a1),
+            (&block_a1, &block_a2),
+            (&boot_block, &block_b1),
+        ]
+        .iter()
+        {
+            let mut tx = chainstate.index_tx_begin().unwrap();
+            tx.put_indexed_begin(parent, child).unwrap();
+            tx.put_indexed_all(&vec![], &vec![]).unwrap();
+            tx.commit().unwrap();
+        }
+
+        // the transaction was mined in A2, after three others, and in B1, where it was aborted
+        let recipient = PrincipalData::from(StacksAddress {
+            version: 26,
+            bytes: Hash160([0x11; 20]),
+        });
+        let receipt = |txid: &Txid, aborted: bool| StacksTransactionReceipt {
+            transaction: TransactionOrigin::Burn(txid.clone()),
+            events: vec![StacksTransactionEvent::STXEvent(
+                STXEventType::STXMintEvent(STXMintEventData {
+                    recipient: recipient.clone(),
+                    amount: 1,
+                }),
+            )],
+            post_condition_aborted: aborted,
+            result: Value::okay_true(),
+            stx_burned: 0,
+            contract_analysis: None,
+            execution_cost: ExecutionCost::zero(),
+            vm_error: if aborted {
+                Some(TransactionErrorCode::ShortReturnAssertionFailed)
+            } else {
+                None
+            },
+        };
+        let a2_receipts: Vec<_> = (0..3u8)
+            .map(|i| receipt(&Txid([0x10 + i; 32]), false))
+            .chain(Some(receipt(&txid, false)))
+            .collect();
+
+        // nothing is stored unless the index is enabled
+        chainstate.indexes.tx_receipts = false;
+        let (chainstate_tx, _) = chainstate.chainstate_tx_begin().unwrap();
+        chainstate_tx
+            .index_transaction_receipts(&block_a2, 2, &a2_receipts)
+            .unwrap();
+        chainstate_tx.commit().unwrap();
+        assert!(chainstate
+            .get_transaction_receipt(&block_a2, &txid)
+            .unwrap()
+            .is_none());
+
+        chainstate.indexes.tx_receipts = true;
+        let (chainstate_tx, _) = chainstate.chainstate_tx_begin().unwrap();
+        chainstate_tx
+            .index_transaction_receipts(&block_a2, 2, &a2_receipts)
+            .unwrap();
+        chainstate_tx
+            .index_transaction_receipts(&block_b1, 1, &[receipt(&txid, true)])
+            .unwrap();
+        chainstate_tx.commit().unwrap();
+
+        let events = vec![json!({
+            "txid": format!("0x{:?}", &txid),
+            "event_index": 0,
+            "committed": true,
+            "type": "stx_mint_event",
+            "stx_mint_event": { "recipient": recipient.to_string(), "amount": "1" },
+        })];
+
+        let receipt = chainstate
+            .get_transaction_receipt(&block_a2, &txid)
+            .unwrap()
+            .unwrap();
+        assert_eq!(receipt.index_block_hash, block_a2);
+        assert_eq!(receipt.block_height, 2);
+        assert_eq!(receipt.tx_index, 3);
+        assert_eq!(receipt.result_hex, Value::okay_true().serialize());
+        assert_eq!(receipt.stx_burned, 0);
+        assert!(!receipt.post_condition_aborted);
+        assert_eq!(receipt.vm_error, None);
+        assert_eq!(receipt.execution_cost, ExecutionCost::zero());
+        assert_eq!(receipt.events, events);
+
+        let receipt = chainstate
+            .get_transaction_receipt(&block_b1, &txid)
+            .unwrap()
+            .unwrap();
+        assert_eq!(receipt.index_block_hash, block_b1);
+        assert!(receipt.post_condition_aborted);
+        // the events of an aborted transaction were not committed
+        assert_eq!(receipt.events[0]["committed"], json!(false));
+        assert_eq!(
+            receipt.vm_error,
+            Some(TransactionErrorCode::ShortReturnAssertionFailed)
+        );
+
+        // not mined yet as of A1
+        assert!(chainstate
+            .get_transaction_receipt(&block_a1, &txid)
+            .unwrap()
+            .is_none());
+        assert!(chainstate
+            .get_transaction_receipt(&block_a2, &Txid([0x02; 32]))
+            .unwrap()
+            .is_none());
+        assert!(chainstate
+            .get_transaction_receipt(&StacksBlockId([0xff; 32]), &txid)
+            .is_err());
+    }
+}
@@ -215,6 +215,7 @@ impl UnconfirmedState {
                     match StacksChainState::process_microblocks_transactions(
                         &mut clarity_tx,
                         &vec![mblock.clone()],
+                        &HashSet::new(),
                     ) {
                         Ok(x) => x,
                         Err((Error::InvalidStacksMicroblock(msg, _), hdr)) => {
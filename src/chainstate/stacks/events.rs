@@ -18,8 +18,11 @@ use crate::codec::StacksMessageCodec;
 use crate::types::chainstate::StacksAddress;
 use burnchains::Txid;
 use chainstate::stacks::StacksTransaction;
+use clarity_vm::clarity::Error as clarity_error;
+use util::hash::to_hex;
 use vm::analysis::ContractAnalysis;
 use vm::costs::ExecutionCost;
+use vm::errors::{Error as InterpreterError, RuntimeErrorType, ShortReturnType};
 use vm::types::{
     AssetIdentifier, PrincipalData, QualifiedContractIdentifier, StandardPrincipalData, Value,
 };
@@ -60,6 +63,202 @@ pub struct StacksTransactionReceipt {
     pub stx_burned: u128,
     pub contract_analysis: Option<ContractAnalysis>,
     pub execution_cost: ExecutionCost,
+    /// Why the transaction failed to materialize, if it was mined but aborted by the VM.
+    pub vm_error: Option<TransactionErrorCode>,
+}
+
+/// Version of the `TransactionErrorCode` space.  Codes are append-only: a code, once assigned,
+/// is never renumbered or reused.  Bump this whenever a new code is added, so consumers can
+/// tell whether they know about every code they may receive.
+pub const TRANSACTION_ERROR_CODES_VERSION: u32 = 1;
+
+/// Stable, enumerated reasons for a mined transaction to have been aborted by the Clarity VM.
+/// These are reported in transaction receipts and event payloads, so the discriminants are
+/// part of the node's external interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum TransactionErrorCode {
+    Arithmetic = 1,
+    ArithmeticOverflow = 2,
+    ArithmeticUnderflow = 3,
+    SupplyOverflow = 4,
+    SupplyUnderflow = 5,
+    DivisionByZero = 6,
+    ParseError = 7,
+    ASTError = 8,
+    MaxStackDepthReached = 9,
+    MaxContextDepthReached = 10,
+    ListDimensionTooHigh = 11,
+    BadTypeConstruction = 12,
+    ValueTooLarge = 13,
+    BadBlockHeight = 14,
+    TransferNonPositiveAmount = 15,
+    NoSuchToken = 16,
+    NotImplemented = 17,
+    NoSenderInContext = 18,
+    NonPositiveTokenSupply = 19,
+    JSONParseError = 20,
+    AttemptToFetchInTransientContext = 21,
+    BadNameValue = 22,
+    UnknownBlockHeaderHash = 23,
+    BadBlockHash = 24,
+    UnwrapFailure = 25,
+    /// `(unwrap! ...)`, `(try! ...)` and friends returned early
+    ShortReturnExpectedValue = 100,
+    /// `(asserts! ...)` failed
+    ShortReturnAssertionFailed = 101,
+    /// the contract in a smart-contract transaction failed to parse or type-check
+    ContractAnalysisFailure = 200,
+}
+
+impl TransactionErrorCode {
+    pub const ALL: &'static [TransactionErrorCode] = &[
+        TransactionErrorCode::Arithmetic,
+        TransactionErrorCode::ArithmeticOverflow,
+        TransactionErrorCode::ArithmeticUnderflow,
+        TransactionErrorCode::SupplyOverflow,
+        TransactionErrorCode::SupplyUnderflow,
+        TransactionErrorCode::DivisionByZero,
+        TransactionErrorCode::ParseError,
+        TransactionErrorCode::ASTError,
+        TransactionErrorCode::MaxStackDepthReached,
+        TransactionErrorCode::MaxContextDepthReached,
+        TransactionErrorCode::ListDimensionTooHigh,
+        TransactionErrorCode::BadTypeConstruction,
+        TransactionErrorCode::ValueTooLarge,
+        TransactionErrorCode::BadBlockHeight,
+        TransactionErrorCode::TransferNonPositiveAmount,
+        TransactionErrorCode::NoSuchToken,
+        TransactionErrorCode::NotImplemented,
+        TransactionErrorCode::NoSenderInContext,
+        TransactionErrorCode::NonPositiveTokenSupply,
+        TransactionErrorCode::JSONParseError,
+        TransactionErrorCode::AttemptToFetchInTransientContext,
+        TransactionErrorCode::BadNameValue,
+        TransactionErrorCode::UnknownBlockHeaderHash,
+        TransactionErrorCode::BadBlockHash,
+        TransactionErrorCode::UnwrapFailure,
+        TransactionErrorCode::ShortReturnExpectedValue,
+        TransactionErrorCode::ShortReturnAssertionFailed,
+        TransactionErrorCode::ContractAnalysisFailure,
+    ];
+
+    pub fn code(&self) -> u32 {
+        *self as u32
+    }
+
+    pub fn from_code(code: u32) -> Option<TransactionErrorCode> {
+        TransactionErrorCode::ALL
+            .iter()
+            .find(|ecode| ecode.code() == code)
+            .copied()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransactionErrorCode::Arithmetic => "arithmetic",
+            TransactionErrorCode::ArithmeticOverflow => "arithmetic_overflow",
+            TransactionErrorCode::ArithmeticUnderflow => "arithmetic_underflow",
+            TransactionErrorCode::SupplyOverflow => "supply_overflow",
+            TransactionErrorCode::SupplyUnderflow => "supply_underflow",
+            TransactionErrorCode::DivisionByZero => "division_by_zero",
+            TransactionErrorCode::ParseError => "parse_error",
+            TransactionErrorCode::ASTError => "ast_error",
+            TransactionErrorCode::MaxStackDepthReached => "max_stack_depth_reached",
+            TransactionErrorCode::MaxContextDepthReached => "max_context_depth_reached",
+            TransactionErrorCode::ListDimensionTooHigh => "list_dimension_too_high",
+            TransactionErrorCode::BadTypeConstruction => "bad_type_construction",
+            TransactionErrorCode::ValueTooLarge => "value_too_large",
+            TransactionErrorCode::BadBlockHeight => "bad_block_height",
+            TransactionErrorCode::TransferNonPositiveAmount => "transfer_non_positive_amount",
+            TransactionErrorCode::NoSuchToken => "no_such_token",
+            TransactionErrorCode::NotImplemented => "not_implemented",
+            TransactionErrorCode::NoSenderInContext => "no_sender_in_context",
+            TransactionErrorCode::NonPositiveTokenSupply => "non_positive_token_supply",
+            TransactionErrorCode::JSONParseError => "json_parse_error",
+            TransactionErrorCode::AttemptToFetchInTransientContext => {
+                "attempt_to_fetch_in_transient_context"
+            }
+            TransactionErrorCode::BadNameValue => "bad_name_value",
+            TransactionErrorCode::UnknownBlockHeaderHash => "unknown_block_header_hash",
+            TransactionErrorCode::BadBlockHash => "bad_block_hash",
+            TransactionErrorCode::UnwrapFailure => "unwrap_failure",
+            TransactionErrorCode::ShortReturnExpectedValue => "short_return_expected_value",
+            TransactionErrorCode::ShortReturnAssertionFailed => "short_return_assertion_failed",
+            TransactionErrorCode::ContractAnalysisFailure => "contract_analysis_failure",
+        }
+    }
+
+    pub fn from_runtime_error(error: &RuntimeErrorType) -> TransactionErrorCode {
+        match error {
+            RuntimeErrorType::Arithmetic(_) => TransactionErrorCode::Arithmetic,
+            RuntimeErrorType::ArithmeticOverflow => TransactionErrorCode::ArithmeticOverflow,
+            RuntimeErrorType::ArithmeticUnderflow => TransactionErrorCode::ArithmeticUnderflow,
+            RuntimeErrorType::SupplyOverflow(..) => TransactionErrorCode::SupplyOverflow,
+            RuntimeErrorType::SupplyUnderflow(..) => TransactionErrorCode::SupplyUnderflow,
+            RuntimeErrorType::DivisionByZero => TransactionErrorCode::DivisionByZero,
+            RuntimeErrorType::ParseError(_) => TransactionErrorCode::ParseError,
+            RuntimeErrorType::ASTError(_) => TransactionErrorCode::ASTError,
+            RuntimeErrorType::MaxStackDepthReached => TransactionErrorCode::MaxStackDepthReached,
+            RuntimeErrorType::MaxContextDepthReached => {
+                TransactionErrorCode::MaxContextDepthReached
+            }
+            RuntimeErrorType::ListDimensionTooHigh => TransactionErrorCode::ListDimensionTooHigh,
+            RuntimeErrorType::BadTypeConstruction => TransactionErrorCode::BadTypeConstruction,
+            RuntimeErrorType::ValueTooLarge => TransactionErrorCode::ValueTooLarge,
+            RuntimeErrorType::BadBlockHeight(_) => TransactionErrorCode::BadBlockHeight,
+            RuntimeErrorType::TransferNonPositiveAmount => {
+                TransactionErrorCode::TransferNonPositiveAmount
+            }
+            RuntimeErrorType::NoSuchToken => TransactionErrorCode::NoSuchToken,
+            RuntimeErrorType::NotImplemented => TransactionErrorCode::NotImplemented,
+            RuntimeErrorType::NoSenderInContext => TransactionErrorCode::NoSenderInContext,
+            RuntimeErrorType::NonPositiveTokenSupply => {
+                TransactionErrorCode::NonPositiveTokenSupply
+            }
+            RuntimeErrorType::JSONParseError(_) => TransactionErrorCode::JSONParseError,
+            RuntimeErrorType::AttemptToFetchInTransientContext => {
+                TransactionErrorCode::AttemptToFetchInTransientContext
+            }
+            RuntimeErrorType::BadNameValue(..) => TransactionErrorCode::BadNameValue,
+            RuntimeErrorType::UnknownBlockHeaderHash(_) => {
+                TransactionErrorCode::UnknownBlockHeaderHash
+            }
+            RuntimeErrorType::BadBlockHash(_) => TransactionErrorCode::BadBlockHash,
+            RuntimeErrorType::UnwrapFailure => TransactionErrorCode::UnwrapFailure,
+        }
+    }
+
+    /// Get the error code for a VM error that still allows the transaction to be mined.
+    /// Returns None for errors that would invalidate the transaction instead.
+    pub fn from_clarity_error(error: &clarity_error) -> Option<TransactionErrorCode> {
+        match error {
+            clarity_error::Interpreter(InterpreterError::Runtime(ref e, _)) => {
+                Some(TransactionErrorCode::from_runtime_error(e))
+            }
+            clarity_error::Interpreter(InterpreterError::ShortReturn(
+                ShortReturnType::ExpectedValue(_),
+            )) => Some(TransactionErrorCode::ShortReturnExpectedValue),
+            clarity_error::Interpreter(InterpreterError::ShortReturn(
+                ShortReturnType::AssertionFailed(_),
+            )) => Some(TransactionErrorCode::ShortReturnAssertionFailed),
+            _ => None,
+        }
+    }
+
+    pub fn json_serialize(&self) -> serde_json::Value {
+        json!({
+            "code": self.code(),
+            "name": self.name(),
+            "version": TRANSACTION_ERROR_CODES_VERSION,
+        })
+    }
+}
+
+impl std::fmt::Display for TransactionErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name(), self.code())
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -68,6 +267,7 @@ pub enum StacksTransactionEvent {
     STXEvent(STXEventType),
     NFTEvent(NFTEventType),
     FTEvent(FTEventType),
+    PoxEvent(PoxEventType),
 }
 
 impl StacksTransactionEvent {
@@ -155,6 +355,13 @@ impl StacksTransactionEvent {
                 "type": "ft_burn_event",
                 "ft_burn_event": event_data.json_serialize()
             }),
+            StacksTransactionEvent::PoxEvent(event_data) => json!({
+                "txid": format!("0x{:?}", txid),
+                "event_index": event_index,
+                "committed": committed,
+                "type": "pox_event",
+                "pox_event": event_data.json_serialize()
+            }),
         }
     }
 }
@@ -181,6 +388,164 @@ pub enum FTEventType {
     FTBurnEvent(FTBurnEventData),
 }
 
+/// Stacking operations, parsed out of successful calls to the PoX contract
+#[derive(Debug, Clone, PartialEq)]
+pub enum PoxEventType {
+    StackStx(PoxStackStxEventData),
+    DelegateStackStx(PoxDelegateStackStxEventData),
+    DelegateStx(PoxDelegateStxEventData),
+    StackAggregationCommit(PoxStackAggregationCommitEventData),
+    Unlock(PoxUnlockEventData),
+}
+
+impl PoxEventType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PoxEventType::StackStx(_) => "stack-stx",
+            PoxEventType::DelegateStackStx(_) => "delegate-stack-stx",
+            PoxEventType::DelegateStx(_) => "delegate-stx",
+            PoxEventType::StackAggregationCommit(_) => "stack-aggregation-commit",
+            PoxEventType::Unlock(_) => "unlock",
+        }
+    }
+
+    pub fn json_serialize(&self) -> serde_json::Value {
+        let mut data = match self {
+            PoxEventType::StackStx(event_data) => event_data.json_serialize(),
+            PoxEventType::DelegateStackStx(event_data) => event_data.json_serialize(),
+            PoxEventType::DelegateStx(event_data) => event_data.json_serialize(),
+            PoxEventType::StackAggregationCommit(event_data) => event_data.json_serialize(),
+            PoxEventType::Unlock(event_data) => event_data.json_serialize(),
+        };
+        data["name"] = json!(self.name());
+        data
+    }
+}
+
+/// The PoX address of a stacking operation.  `address` is the base58 burnchain address, if the
+/// version byte is a known address hash mode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoxAddressEventData {
+    pub version: u8,
+    pub hashbytes: Vec<u8>,
+    pub address: Option<String>,
+}
+
+impl PoxAddressEventData {
+    pub fn json_serialize(&self) -> serde_json::Value {
+        json!({
+            "version": format!("0x{:02x}", self.version),
+            "hashbytes": format!("0x{}", to_hex(&self.hashbytes)),
+            "address": self.address,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoxStackStxEventData {
+    pub stacker: PrincipalData,
+    pub locked_amount: u128,
+    pub pox_addr: PoxAddressEventData,
+    pub start_burn_height: u128,
+    pub lock_period: u128,
+    pub unlock_height: u64,
+}
+
+impl PoxStackStxEventData {
+    pub fn json_serialize(&self) -> serde_json::Value {
+        json!({
+            "stacker": format!("{}", self.stacker),
+            "locked_amount": format!("{}", self.locked_amount),
+            "pox_addr": self.pox_addr.json_serialize(),
+            "start_burn_height": format!("{}", self.start_burn_height),
+            "lock_period": format!("{}", self.lock_period),
+            "unlock_height": format!("{}", self.unlock_height),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoxDelegateStackStxEventData {
+    pub delegator: PrincipalData,
+    pub stacker: PrincipalData,
+    pub locked_amount: u128,
+    pub pox_addr: PoxAddressEventData,
+    pub start_burn_height: u128,
+    pub lock_period: u128,
+    pub unlock_height: u64,
+}
+
+impl PoxDelegateStackStxEventData {
+    pub fn json_serialize(&self) -> serde_json::Value {
+        json!({
+            "delegator": format!("{}", self.delegator),
+            "stacker": format!("{}", self.stacker),
+            "locked_amount": format!("{}", self.locked_amount),
+            "pox_addr": self.pox_addr.json_serialize(),
+            "start_burn_height": format!("{}", self.start_burn_height),
+            "lock_period": format!("{}", self.lock_period),
+            "unlock_height": format!("{}", self.unlock_height),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoxDelegateStxEventData {
+    pub stacker: PrincipalData,
+    pub delegate_to: PrincipalData,
+    pub amount_ustx: u128,
+    pub until_burn_height: Option<u128>,
+    pub pox_addr: Option<PoxAddressEventData>,
+}
+
+impl PoxDelegateStxEventData {
+    pub fn json_serialize(&self) -> serde_json::Value {
+        json!({
+            "stacker": format!("{}", self.stacker),
+            "delegate_to": format!("{}", self.delegate_to),
+            "amount_ustx": format!("{}", self.amount_ustx),
+            "until_burn_height": self.until_burn_height.map(|ht| format!("{}", ht)),
+            "pox_addr": self.pox_addr.as_ref().map(|addr| addr.json_serialize()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoxStackAggregationCommitEventData {
+    pub delegator: PrincipalData,
+    pub pox_addr: PoxAddressEventData,
+    pub reward_cycle: u128,
+}
+
+impl PoxStackAggregationCommitEventData {
+    pub fn json_serialize(&self) -> serde_json::Value {
+        json!({
+            "delegator": format!("{}", self.delegator),
+            "pox_addr": self.pox_addr.json_serialize(),
+            "reward_cycle": format!("{}", self.reward_cycle),
+        })
+    }
+}
+
+/// Tokens whose lock expired at `unlock_height`, and which were folded back into the stacker's
+/// unlocked balance because the stacker stacked again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoxUnlockEventData {
+    pub stacker: PrincipalData,
+    pub unlocked_amount: u128,
+    pub unlock_height: u64,
+}
+
+impl PoxUnlockEventData {
+    pub fn json_serialize(&self) -> serde_json::Value {
+        json!({
+            "stacker": format!("{}", self.stacker),
+            "unlocked_amount": format!("{}", self.unlocked_amount),
+            "unlock_height": format!("{}", self.unlock_height),
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct STXTransferEventData {
     pub sender: PrincipalData,
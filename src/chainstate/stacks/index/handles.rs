@@ -0,0 +1,391 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A pool of the MARF's read-only SQLite handles.  Every read-only view of a MARF -- each
+//! read-only Clarity connection, each index transaction's read-only view of its fork -- used to
+//! open a connection of its own, which holds up to three file descriptors (the database, its WAL,
+//! and its shared-memory index).  A busy node opens and closes thousands of these a minute, and
+//! can hold enough at once to run into its file descriptor limit.
+//!
+//! Instead, a read-only view takes an idle handle for its database from this pool if there is
+//! one, and gives it back when it is dropped.  The pool keeps no more than a set number of
+//! handles open: once it is at the cap, the least-recently used idle handle, for any database, is
+//! closed to make room for a new one.  Handles in use cannot be closed, so if every handle is in
+//! use, a request waits for one to be given back, and fails if none is within
+//! `HANDLE_WAIT_TIMEOUT`.
+//!
+//! Each pooled handle gets its share of the node's MARF page cache budget, like any other MARF
+//! connection (see `util::memory`).
+
+use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use rusqlite::{Connection, OpenFlags};
+
+use chainstate::stacks::index::Error;
+use monitoring;
+use util::db::tx_busy_handler;
+use util::memory;
+
+/// Default cap on the number of read-only MARF handles open at once
+pub const DEFAULT_MAX_OPEN_HANDLES: u64 = 256;
+
+/// How long a request for a handle waits for one to be given back when every handle is in use
+pub const HANDLE_WAIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Outcome of a request for a handle, for the handle requests metric
+pub const HANDLE_REUSED: &str = "reused";
+pub const HANDLE_OPENED: &str = "opened";
+
+static MAX_OPEN_HANDLES: AtomicU64 = AtomicU64::new(DEFAULT_MAX_OPEN_HANDLES);
+
+/// Cap the number of read-only MARF handles open at once (0 for no cap).  Idle handles over a
+/// lower cap are closed as handles are next opened or given back.
+pub fn set_max_open_handles(max_open: u64) {
+    MAX_OPEN_HANDLES.store(max_open, Ordering::SeqCst);
+}
+
+struct HandlePool {
+    /// least-recently used first
+    idle: VecDeque<(String, Connection)>,
+    /// idle handles, plus the handles in use
+    open: u64,
+}
+
+impl HandlePool {
+    fn new() -> HandlePool {
+        HandlePool {
+            idle: VecDeque::new(),
+            open: 0,
+        }
+    }
+
+    /// Take the most-recently used idle handle for the given database, if there is one
+    fn take(&mut self, db_path: &str) -> Option<Connection> {
+        let i = self.idle.iter().rposition(|(path, _)| path == db_path)?;
+        self.idle.remove(i).map(|(_, conn)| conn)
+    }
+
+    /// Close idle handles, least-recently used first, until a new handle can be opened without
+    /// going over the cap.  Returns how many were closed.
+    fn make_room(&mut self, max_open: u64) -> u64 {
+        let mut closed = 0;
+        while max_open > 0 && self.open >= max_open {
+            if self.idle.pop_front().is_none() {
+                break;
+            }
+            self.open -= 1;
+            closed += 1;
+        }
+        closed
+    }
+
+    /// Close every idle handle for the given database.  Returns how many were closed.
+    fn close_idle(&mut self, db_path: &str) -> u64 {
+        let before = self.idle.len();
+        self.idle.retain(|(path, _)| path != db_path);
+        let closed = (before - self.idle.len()) as u64;
+        self.open -= closed;
+        closed
+    }
+
+    /// Take back a handle that is no longer in use.  It is closed instead of kept if the pool is
+    /// over the cap, or if it was left in the middle of a transaction.
+    fn give_back(&mut self, db_path: String, conn: Connection, max_open: u64) {
+        if !conn.is_autocommit() || (max_open > 0 && self.open > max_open) {
+            self.open -= 1;
+            return;
+        }
+        self.idle.push_back((db_path, conn));
+    }
+
+    fn is_full(&self, max_open: u64) -> bool {
+        max_open > 0 && self.open >= max_open
+    }
+}
+
+lazy_static! {
+    static ref HANDLE_POOL: Mutex<HandlePool> = Mutex::new(HandlePool::new());
+    /// Signalled whenever a handle is given back or closed
+    static ref HANDLE_RETURNED: Condvar = Condvar::new();
+}
+
+/// Take an idle handle for the given database, or else make room to open a new one, waiting up
+/// to `wait` for a handle to be given back if every handle is in use.  On success, the pool is
+/// returned still locked, so the caller can count the handle it opens.
+fn take_or_make_room<'a>(
+    mut pool: MutexGuard<'a, HandlePool>,
+    returned: &Condvar,
+    db_path: &str,
+    max_open: u64,
+    wait: Duration,
+) -> Result<(MutexGuard<'a, HandlePool>, Option<Connection>), Error> {
+    let deadline = Instant::now() + wait;
+    loop {
+        if let Some(conn) = pool.take(db_path) {
+            return Ok((pool, Some(conn)));
+        }
+        for _ in 0..pool.make_room(max_open) {
+            monitoring::increment_marf_handle_evictions();
+        }
+        if !pool.is_full(max_open) {
+            return Ok((pool, None));
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            warn!(
+                "All {} read-only MARF handles are in use; could not open one for {}",
+                pool.open, db_path
+            );
+            return Err(Error::HandlesExhaustedError(max_open));
+        }
+        debug!(
+            "All {} read-only MARF handles are in use; waiting for one for {}",
+            pool.open, db_path
+        );
+        // the pool only holds counts and idle handles, so it is still usable if poisoned
+        pool = match returned.wait_timeout(pool, deadline - now) {
+            Ok((pool, _)) => pool,
+            Err(poisoned) => poisoned.into_inner().0,
+        };
+    }
+}
+
+fn open_readonly_connection(db_path: &str) -> Result<Connection, Error> {
+    let mut db = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    db.busy_handler(Some(tx_busy_handler))?;
+    memory::apply_marf_cache_size(&db)?;
+    monitoring::instrument_sqlite_connection(&mut db);
+    Ok(db)
+}
+
+/// Close the idle read-only handles on the given database.  Called whenever a MARF is opened for
+/// writing, since the database may have been deleted and created anew at the same path, and an
+/// old handle would still read the deleted one.
+pub fn close_idle_handles(db_path: &str) {
+    if let Ok(mut pool) = HANDLE_POOL.lock() {
+        if pool.close_idle(db_path) > 0 {
+            monitoring::update_marf_readonly_handles(pool.open as i64);
+            HANDLE_RETURNED.notify_all();
+        }
+    }
+}
+
+/// A MARF's SQLite connection.  A read-only one is given back to the pool when it is dropped.
+pub struct MarfHandle {
+    conn: Option<Connection>,
+    /// the database a pooled handle is for
+    pooled_path: Option<String>,
+}
+
+impl MarfHandle {
+    /// Wrap a connection that is not shared through the pool
+    pub fn unpooled(conn: Connection) -> MarfHandle {
+        MarfHandle {
+            conn: Some(conn),
+            pooled_path: None,
+        }
+    }
+
+    /// Get a read-only handle on the given database, reusing an idle one if there is one.  If
+    /// every handle is in use, this waits for one to be given back, and fails with
+    /// `Error::HandlesExhaustedError` if none is within `HANDLE_WAIT_TIMEOUT`.
+    pub fn open_readonly(db_path: &str) -> Result<MarfHandle, Error> {
+        let max_open = MAX_OPEN_HANDLES.load(Ordering::SeqCst);
+        let pool = match HANDLE_POOL.lock() {
+            Ok(pool) => pool,
+            Err(_) => return Ok(MarfHandle::unpooled(open_readonly_connection(db_path)?)),
+        };
+        let (mut pool, idle) = take_or_make_room(
+            pool,
+            &HANDLE_RETURNED,
+            db_path,
+            max_open,
+            HANDLE_WAIT_TIMEOUT,
+        )?;
+        if let Some(conn) = idle {
+            monitoring::increment_marf_handle_requests(HANDLE_REUSED);
+            return Ok(MarfHandle {
+                conn: Some(conn),
+                pooled_path: Some(db_path.to_string()),
+            });
+        }
+
+        let conn = open_readonly_connection(db_path)?;
+        pool.open += 1;
+        monitoring::increment_marf_handle_requests(HANDLE_OPENED);
+        monitoring::update_marf_readonly_handles(pool.open as i64);
+        Ok(MarfHandle {
+            conn: Some(conn),
+            pooled_path: Some(db_path.to_string()),
+        })
+    }
+}
+
+impl Deref for MarfHandle {
+    type Target = Connection;
+    fn deref(&self) -> &Connection {
+        self.conn
+            .as_ref()
+            .expect("BUG: MARF handle used after it was given back")
+    }
+}
+
+impl DerefMut for MarfHandle {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn
+            .as_mut()
+            .expect("BUG: MARF handle used after it was given back")
+    }
+}
+
+impl Drop for MarfHandle {
+    fn drop(&mut self) {
+        if let (Some(db_path), Some(conn)) = (self.pooled_path.take(), self.conn.take()) {
+            if let Ok(mut pool) = HANDLE_POOL.lock() {
+                pool.give_back(db_path, conn, MAX_OPEN_HANDLES.load(Ordering::SeqCst));
+                monitoring::update_marf_readonly_handles(pool.open as i64);
+            }
+            HANDLE_RETURNED.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    fn open(pool: &mut HandlePool, max_open: u64) -> (u64, Connection) {
+        let closed = pool.make_room(max_open);
+        pool.open += 1;
+        (closed, Connection::open_in_memory().unwrap())
+    }
+
+    #[test]
+    fn test_handle_pool_reuse_and_cap() {
+        let mut pool = HandlePool::new();
+        assert!(pool.take("a.sqlite").is_none());
+
+        let (closed, a1) = open(&mut pool, 2);
+        assert_eq!(closed, 0);
+        let (closed, b1) = open(&mut pool, 2);
+        assert_eq!(closed, 0);
+        pool.give_back("a.sqlite".to_string(), a1, 2);
+        pool.give_back("b.sqlite".to_string(), b1, 2);
+        assert_eq!(pool.open, 2);
+
+        // an idle handle is reused, for its own database only
+        let a1 = pool.take("a.sqlite").unwrap();
+        assert!(pool.take("a.sqlite").is_none());
+        assert_eq!(pool.open, 2);
+
+        // at the cap, the least-recently used idle handle is closed to make room
+        let (closed, c1) = open(&mut pool, 2);
+        assert_eq!(closed, 1);
+        assert!(pool.take("b.sqlite").is_none());
+        assert_eq!(pool.open, 2);
+
+        // with every handle in use, the pool is full
+        assert_eq!(pool.make_room(2), 0);
+        assert!(pool.is_full(2));
+
+        // if the cap is lowered, a handle over it is closed once it is given back
+        let (_, c2) = open(&mut pool, 3);
+        assert_eq!(pool.open, 3);
+        pool.give_back("c.sqlite".to_string(), c2, 2);
+        assert_eq!(pool.open, 2);
+        assert!(pool.idle.is_empty());
+
+        // a handle left in a transaction is closed rather than reused
+        a1.execute_batch("BEGIN").unwrap();
+        pool.give_back("a.sqlite".to_string(), a1, 2);
+        assert_eq!(pool.open, 1);
+        assert!(pool.take("a.sqlite").is_none());
+
+        pool.give_back("c.sqlite".to_string(), c1, 2);
+        assert_eq!(pool.close_idle("b.sqlite"), 0);
+        assert_eq!(pool.close_idle("c.sqlite"), 1);
+        assert!(pool.take("c.sqlite").is_none());
+        assert_eq!(pool.open, 0);
+
+        // no cap
+        assert_eq!(pool.make_room(0), 0);
+    }
+
+    #[test]
+    fn test_handle_pool_waits_at_cap() {
+        let lock = Arc::new(Mutex::new(HandlePool::new()));
+        let returned = Arc::new(Condvar::new());
+
+        let (mut pool, idle) = take_or_make_room(
+            lock.lock().unwrap(),
+            &returned,
+            "a.sqlite",
+            1,
+            Duration::from_millis(0),
+        )
+        .unwrap();
+        assert!(idle.is_none());
+        pool.open += 1;
+        drop(pool);
+
+        // every handle is in use, and none is given back
+        match take_or_make_room(
+            lock.lock().unwrap(),
+            &returned,
+            "a.sqlite",
+            1,
+            Duration::from_millis(50),
+        ) {
+            Err(Error::HandlesExhaustedError(1)) => {}
+            Err(e) => panic!("unexpected error {:?}", &e),
+            Ok(_) => panic!("opened a handle over the cap"),
+        }
+
+        // a handle given back while waiting is taken
+        let giver = {
+            let lock = lock.clone();
+            let returned = returned.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                lock.lock().unwrap().give_back(
+                    "a.sqlite".to_string(),
+                    Connection::open_in_memory().unwrap(),
+                    1,
+                );
+                returned.notify_one();
+            })
+        };
+        let (pool, idle) = take_or_make_room(
+            lock.lock().unwrap(),
+            &returned,
+            "a.sqlite",
+            1,
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        assert!(idle.is_some());
+        assert_eq!(pool.open, 1);
+        drop(pool);
+        giver.join().unwrap();
+    }
+}
@@ -36,6 +36,7 @@ use chainstate::stacks::index::storage::{
 use chainstate::stacks::index::trie::Trie;
 use chainstate::stacks::index::Error;
 use chainstate::stacks::index::MarfTrieId;
+use monitoring;
 use util::db::Error as db_error;
 use util::hash::Sha512Trunc256Sum;
 use util::log;
@@ -82,6 +83,16 @@ pub trait MarfConnection<T: MarfTrieId> {
         self.with_conn(|c| MARF::get_by_key(c, block_hash, key))
     }
 
+    /// Resolve a key from the MARF with respect to the given block, and also return the block in
+    /// which the key was last written, if it is known (see `MARF::get_by_key_with_origin()`).
+    fn get_with_origin(
+        &mut self,
+        block_hash: &T,
+        key: &str,
+    ) -> Result<Option<(MARFValue, Option<T>)>, Error> {
+        self.with_conn(|c| MARF::get_by_key_with_origin(c, block_hash, key))
+    }
+
     fn get_with_proof(
         &mut self,
         block_hash: &T,
@@ -978,6 +989,7 @@ impl<T: MarfTrieId> MARF<T> {
         );
 
         Trie::add_value(storage, &mut cursor, &mut value)?;
+        storage.note_leaf_write(path);
 
         if update_skiplist {
             Trie::update_root_hash(storage, &cursor)?;
@@ -1037,11 +1049,20 @@ impl<T: MarfTrieId> MARF<T> {
         Ok(MARF::from_storage(file_storage))
     }
 
+    /// Instantiate a read-only MARF from the given path on disk, which must already exist.
+    pub fn from_path_readonly(path: &str) -> Result<MARF<T>, Error> {
+        let file_storage = TrieFileStorage::open_readonly(path)?;
+        Ok(MARF::from_storage(file_storage))
+    }
+
     pub fn get_by_key(
         storage: &mut TrieStorageConnection<T>,
         block_hash: &T,
         key: &str,
     ) -> Result<Option<MARFValue>, Error> {
+        let mut span = monitoring::trace_child_span("marf.get");
+        span.set_attribute("marf.key", key);
+
         let (cur_block_hash, cur_block_id) = storage.get_cur_block_and_id();
 
         let path = TriePath::from_key(key);
@@ -1066,6 +1087,68 @@ impl<T: MarfTrieId> MARF<T> {
         result.map(|option_result| option_result.map(|leaf| leaf.data))
     }
 
+    /// Like `get_by_key()`, but also return the most recent ancestor of `block_hash` (inclusive)
+    /// that wrote the key.  This is not necessarily the block whose trie holds the key's leaf,
+    /// since inserting a key whose path shares a prefix with an existing leaf copies that leaf
+    /// into the new block's trie; the writing block is looked up among the blocks this MARF
+    /// recorded writing the key's path instead.  It is None if this MARF does not record its
+    /// writes (see `MARF_LEAF_WRITE_INDEX`), or if the key was written before it started to.
+    pub fn get_by_key_with_origin(
+        storage: &mut TrieStorageConnection<T>,
+        block_hash: &T,
+        key: &str,
+    ) -> Result<Option<(MARFValue, Option<T>)>, Error> {
+        let (cur_block_hash, cur_block_id) = storage.get_cur_block_and_id();
+
+        let path = TriePath::from_key(key);
+
+        // get_path() leaves the storage opened to the block in which the leaf was found
+        let result = MARF::get_path(storage, block_hash, &path)
+            .and_then(|leaf_opt| match leaf_opt {
+                Some(leaf) => {
+                    let holder = storage.get_cur_block();
+                    let origin = MARF::get_leaf_origin(storage, &holder, &path)?;
+                    Ok(Some((leaf.data, origin)))
+                }
+                None => Ok(None),
+            })
+            .or_else(|e| match e {
+                Error::NotFoundError => Ok(None),
+                _ => Err(e),
+            });
+
+        // restore
+        storage.open_block_maybe_id(&cur_block_hash, cur_block_id)?;
+
+        result
+    }
+
+    /// Find the block that wrote the leaf at `path` which is held in the trie of `holder`: the
+    /// most recent of `holder` and its ancestors recorded as writing `path`.
+    fn get_leaf_origin(
+        storage: &mut TrieStorageConnection<T>,
+        holder: &T,
+        path: &TriePath,
+    ) -> Result<Option<T>, Error> {
+        if storage.extending_trie_wrote_leaf(holder, path) {
+            return Ok(Some(holder.clone()));
+        }
+
+        // the writer was stored no later than the trie holding its leaf
+        let holder_id = storage.get_block_identifier(holder);
+        let writer_ids = match storage.get_leaf_writers(path, holder_id)? {
+            Some(writer_ids) => writer_ids,
+            None => return Ok(None),
+        };
+        for writer_id in writer_ids.into_iter() {
+            let writer = storage.get_block_from_local_id(writer_id)?.clone();
+            if writer == *holder || MARF::get_block_height(storage, &writer, holder)?.is_some() {
+                return Ok(Some(writer));
+            }
+        }
+        Ok(None)
+    }
+
     pub fn get_block_height_miner_tip(
         storage: &mut TrieStorageConnection<T>,
         block_hash: &T,
@@ -1635,6 +1718,154 @@ mod test {
         }
     }
 
+    #[test]
+    fn marf_get_with_origin() {
+        let f = TrieFileStorage::new_memory_with_leaf_write_index().unwrap();
+        let mut marf = MARF::from_storage(f);
+
+        let blocks: Vec<_> = (1..6)
+            .map(|i| BlockHeaderHash::from_bytes(&[i as u8; 32]).unwrap())
+            .collect();
+
+        let mut parent = BlockHeaderHash::sentinel();
+        for (i, block) in blocks.iter().enumerate() {
+            marf.begin(&parent, block).unwrap();
+            match i {
+                0 => {
+                    marf.insert("a", MARFValue::from_value("a0")).unwrap();
+                    marf.insert("b", MARFValue::from_value("b0")).unwrap();
+                }
+                2 => {
+                    marf.insert("a", MARFValue::from_value("a2")).unwrap();
+                }
+                _ => {
+                    marf.insert("c", MARFValue::from_value(&format!("c{}", i)))
+                        .unwrap();
+                }
+            }
+            marf.commit().unwrap();
+            parent = block.clone();
+        }
+
+        // "a" was last written in the third block, and "b" in the first
+        assert_eq!(
+            marf.get_with_origin(&blocks[4], "a").unwrap(),
+            Some((MARFValue::from_value("a2"), Some(blocks[2].clone())))
+        );
+        assert_eq!(
+            marf.get_with_origin(&blocks[1], "a").unwrap(),
+            Some((MARFValue::from_value("a0"), Some(blocks[0].clone())))
+        );
+        assert_eq!(
+            marf.get_with_origin(&blocks[4], "b").unwrap(),
+            Some((MARFValue::from_value("b0"), Some(blocks[0].clone())))
+        );
+        assert_eq!(
+            marf.get_with_origin(&blocks[4], "c").unwrap(),
+            Some((MARFValue::from_value("c4"), Some(blocks[4].clone())))
+        );
+        assert_eq!(marf.get_with_origin(&blocks[4], "d").unwrap(), None);
+
+        // lookups agree with get()
+        for block in blocks.iter() {
+            for key in ["a", "b", "c"].iter() {
+                assert_eq!(
+                    marf.get(block, key).unwrap(),
+                    marf.get_with_origin(block, key).unwrap().map(|(v, _)| v)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn marf_get_with_origin_unindexed() {
+        let f = TrieFileStorage::new_memory().unwrap();
+        let mut marf = MARF::from_storage(f);
+
+        let block = BlockHeaderHash::from_bytes(&[1u8; 32]).unwrap();
+        marf.begin(&BlockHeaderHash::sentinel(), &block).unwrap();
+        marf.insert("a", MARFValue::from_value("a0")).unwrap();
+        marf.commit().unwrap();
+
+        // without the index, the value is found but not the block that wrote it
+        assert_eq!(
+            marf.get_with_origin(&block, "a").unwrap(),
+            Some((MARFValue::from_value("a0"), None))
+        );
+    }
+
+    #[test]
+    fn marf_get_with_origin_colliding_prefixes() {
+        let f = TrieFileStorage::new_memory_with_leaf_write_index().unwrap();
+        let mut marf = MARF::from_storage(f);
+
+        // a key whose path shares its first two bytes with the path of "a", so that inserting it
+        // splits "a"'s leaf and copies it into the inserting block's trie
+        let a_path = TriePath::from_key("a");
+        let colliding = (0..)
+            .map(|i| format!("collides-with-a-{}", i))
+            .find(|key| TriePath::from_key(key).as_bytes()[0..2] == a_path.as_bytes()[0..2])
+            .unwrap();
+
+        let blocks: Vec<_> = (1..4)
+            .map(|i| BlockHeaderHash::from_bytes(&[i as u8; 32]).unwrap())
+            .collect();
+
+        marf.begin(&BlockHeaderHash::sentinel(), &blocks[0])
+            .unwrap();
+        marf.insert("a", MARFValue::from_value("a0")).unwrap();
+        marf.commit().unwrap();
+
+        marf.begin(&blocks[0], &blocks[1]).unwrap();
+        marf.insert(&colliding, MARFValue::from_value("x1"))
+            .unwrap();
+        marf.commit().unwrap();
+
+        marf.begin(&blocks[1], &blocks[2]).unwrap();
+        marf.insert("c", MARFValue::from_value("c2")).unwrap();
+        marf.commit().unwrap();
+
+        // "a"'s leaf now lives in the second block's trie...
+        {
+            let mut storage = marf.borrow_storage_backend();
+            MARF::get_path(&mut storage, &blocks[2], &a_path)
+                .unwrap()
+                .unwrap();
+            assert_eq!(storage.get_cur_block(), blocks[1]);
+        }
+
+        // ...but it was written in the first
+        assert_eq!(
+            marf.get_with_origin(&blocks[2], "a").unwrap(),
+            Some((MARFValue::from_value("a0"), Some(blocks[0].clone())))
+        );
+        assert_eq!(
+            marf.get_with_origin(&blocks[2], &colliding).unwrap(),
+            Some((MARFValue::from_value("x1"), Some(blocks[1].clone())))
+        );
+
+        // the same holds while the block that copies the leaf is still being built
+        let fork = BlockHeaderHash::from_bytes(&[0x44; 32]).unwrap();
+        marf.begin(&blocks[0], &fork).unwrap();
+        marf.insert(&colliding, MARFValue::from_value("x-fork"))
+            .unwrap();
+        assert_eq!(
+            marf.get_with_origin(&fork, "a").unwrap(),
+            Some((MARFValue::from_value("a0"), Some(blocks[0].clone())))
+        );
+        assert_eq!(
+            marf.get_with_origin(&fork, &colliding).unwrap(),
+            Some((MARFValue::from_value("x-fork"), Some(fork.clone())))
+        );
+        marf.commit().unwrap();
+
+        // a write in a sibling fork is not an origin
+        assert_eq!(
+            marf.get_with_origin(&blocks[2], &colliding).unwrap(),
+            Some((MARFValue::from_value("x1"), Some(blocks[1].clone())))
+        );
+    }
+
     #[test]
     fn marf_insert_leaf_sequence_2() {
         let path = "/tmp/rust_marf_insert_leaf_sequence_2";
@@ -23,6 +23,7 @@ use std::io;
 use std::io::{BufWriter, Cursor, Read, Seek, SeekFrom, Write};
 use std::iter::FromIterator;
 use std::marker::PhantomData;
+use std::mem;
 use std::ops::{Deref, DerefMut};
 use std::os;
 use std::path::{Path, PathBuf};
@@ -39,21 +40,33 @@ use chainstate::stacks::index::bits::{
     get_node_byte_len, get_node_hash, read_block_identifier, read_hash_bytes, read_node_hash_bytes,
     read_nodetype, read_root_hash, write_nodetype_bytes,
 };
+use chainstate::stacks::index::handles::{self, MarfHandle};
 use chainstate::stacks::index::node::{
     clear_backptr, is_backptr, set_backptr, TrieNode, TrieNode16, TrieNode256, TrieNode4,
     TrieNode48, TrieNodeID, TrieNodeType, TriePath, TriePtr,
 };
 use chainstate::stacks::index::Error;
 use chainstate::stacks::index::{trie_sql, BlockMap, MarfTrieId};
+use monitoring;
 use util::db::tx_begin_immediate;
 use util::db::tx_busy_handler;
 use util::db::Error as db_error;
 use util::log;
+use util::memory;
 
 use crate::types::chainstate::BlockHeaderHash;
 use crate::types::chainstate::BLOCK_HEADER_HASH_ENCODED_SIZE;
 use crate::types::proof::{ClarityMarfTrieId, TrieHash, TrieLeaf, TRIEHASH_ENCODED_SIZE};
 
+lazy_static! {
+    /// Whether to record which tries write each leaf path (in `marf_leaf_writes`), so that
+    /// `stacks-inspect marf-key-history` can find the blocks that wrote a key.  Only maintained if
+    /// the node runs with `STACKS_MARF_LEAF_WRITE_INDEX=1`; a MARF opened for writing without it
+    /// drops the index, since it could no longer tell a write it missed from no write at all.
+    pub static ref MARF_LEAF_WRITE_INDEX: bool =
+        std::env::var("STACKS_MARF_LEAF_WRITE_INDEX") == Ok("1".into());
+}
+
 pub fn ftell<F: Seek>(f: &mut F) -> Result<u64, Error> {
     f.seek(SeekFrom::Current(0)).map_err(Error::IOError)
 }
@@ -168,6 +181,9 @@ pub struct TrieRAM<T: MarfTrieId> {
 
     total_bytes: usize,
 
+    /// paths of the leaves written (not just copied) into this trie
+    leaf_writes: HashSet<TriePath>,
+
     parent: T,
 }
 
@@ -190,6 +206,8 @@ impl<T: MarfTrieId> TrieRAM<T> {
 
             total_bytes: 0,
 
+            leaf_writes: HashSet::new(),
+
             parent: parent.clone(),
         }
     }
@@ -211,10 +229,17 @@ impl<T: MarfTrieId> TrieRAM<T> {
 
             total_bytes: 0,
 
+            leaf_writes: HashSet::new(),
+
             parent: parent,
         }
     }
 
+    /// Remember that a leaf was written at this path
+    fn note_leaf_write(&mut self, path: &TriePath) {
+        self.leaf_writes.insert(path.clone());
+    }
+
     #[cfg(test)]
     #[allow(dead_code)]
     pub fn stats(&mut self) -> (u64, u64) {
@@ -703,6 +728,8 @@ struct TrieStorageTransientData<T: MarfTrieId> {
 
     readonly: bool,
     unconfirmed: bool,
+    /// whether this MARF has a `marf_leaf_writes` index, checked once at open
+    leaf_write_index: bool,
 }
 
 // disk-backed Trie.
@@ -711,7 +738,7 @@ struct TrieStorageTransientData<T: MarfTrieId> {
 pub struct TrieFileStorage<T: MarfTrieId> {
     pub db_path: String,
 
-    db: Connection,
+    db: MarfHandle,
     data: TrieStorageTransientData<T>,
 
     // used in testing in order to short-circuit block-height lookups
@@ -760,6 +787,7 @@ impl<T: MarfTrieId> TrieFileStorage<T> {
         db_path: &str,
         readonly: bool,
         unconfirmed: bool,
+        leaf_write_index: bool,
     ) -> Result<TrieFileStorage<T>, Error> {
         let mut create_flag = false;
         let open_flags = if db_path != ":memory:" {
@@ -795,20 +823,32 @@ impl<T: MarfTrieId> TrieFileStorage<T> {
             }
         };
 
+        if !readonly {
+            handles::close_idle_handles(db_path);
+        }
         let mut db = Connection::open_with_flags(db_path, open_flags)?;
         db.busy_handler(Some(tx_busy_handler))?;
+        monitoring::instrument_sqlite_connection(&mut db);
+        memory::apply_marf_cache_size(&db)?;
 
         let db_path = db_path.to_string();
 
-        if create_flag {
+        if create_flag || !readonly {
+            // tables added since this MARF was created are made on any open that can write
             trie_sql::create_tables_if_needed(&mut db)?;
+            if leaf_write_index {
+                trie_sql::create_leaf_writes_table(&db)?;
+            } else {
+                trie_sql::drop_leaf_writes_table(&db)?;
+            }
         }
+        let leaf_write_index = trie_sql::has_leaf_writes_table(&db)?;
 
         debug!("Opened TrieFileStorage {};", db_path);
 
         let ret = TrieFileStorage {
             db_path,
-            db,
+            db: MarfHandle::unpooled(db),
 
             data: TrieStorageTransientData {
                 last_extended: None,
@@ -829,6 +869,7 @@ impl<T: MarfTrieId> TrieFileStorage<T> {
 
                 readonly: readonly,
                 unconfirmed: unconfirmed,
+                leaf_write_index,
             },
 
             // used in testing in order to short-circuit block-height lookups
@@ -845,16 +886,22 @@ impl<T: MarfTrieId> TrieFileStorage<T> {
         TrieFileStorage::open(":memory:")
     }
 
+    /// An in-memory MARF that records its leaf writes, whatever `MARF_LEAF_WRITE_INDEX` says
+    #[cfg(test)]
+    pub fn new_memory_with_leaf_write_index() -> Result<TrieFileStorage<T>, Error> {
+        TrieFileStorage::open_opts(":memory:", false, false, true)
+    }
+
     pub fn open(db_path: &str) -> Result<TrieFileStorage<T>, Error> {
-        TrieFileStorage::open_opts(db_path, false, false)
+        TrieFileStorage::open_opts(db_path, false, false, *MARF_LEAF_WRITE_INDEX)
     }
 
     pub fn open_readonly(db_path: &str) -> Result<TrieFileStorage<T>, Error> {
-        TrieFileStorage::open_opts(db_path, true, false)
+        TrieFileStorage::open_opts(db_path, true, false, false)
     }
 
     pub fn open_unconfirmed(db_path: &str) -> Result<TrieFileStorage<T>, Error> {
-        TrieFileStorage::open_opts(db_path, false, true)
+        TrieFileStorage::open_opts(db_path, false, true, *MARF_LEAF_WRITE_INDEX)
     }
 
     pub fn readonly(&self) -> bool {
@@ -866,8 +913,7 @@ impl<T: MarfTrieId> TrieFileStorage<T> {
     }
 
     pub fn reopen_readonly(&self) -> Result<TrieFileStorage<T>, Error> {
-        let db = Connection::open_with_flags(&self.db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
-        db.busy_handler(Some(tx_busy_handler))?;
+        let db = MarfHandle::open_readonly(&self.db_path)?;
 
         trace!("Make read-only view of TrieFileStorage: {}", &self.db_path);
 
@@ -895,6 +941,7 @@ impl<T: MarfTrieId> TrieFileStorage<T> {
 
                 readonly: true,
                 unconfirmed: true,
+                leaf_write_index: self.data.leaf_write_index,
             },
 
             // used in testing in order to short-circuit block-height lookups
@@ -911,8 +958,7 @@ impl<'a, T: MarfTrieId> TrieStorageTransaction<'a, T> {
     /// reopen this transaction as a read-only marf.
     ///  _does not_ preserve the cur_block/open tip
     pub fn reopen_readonly(&self) -> Result<TrieFileStorage<T>, Error> {
-        let db = Connection::open_with_flags(&self.db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
-        db.busy_handler(Some(tx_busy_handler))?;
+        let db = MarfHandle::open_readonly(&self.db_path)?;
 
         trace!(
             "Make read-only view of TrieStorageTransaction: {}",
@@ -943,6 +989,7 @@ impl<'a, T: MarfTrieId> TrieStorageTransaction<'a, T> {
 
                 readonly: true,
                 unconfirmed: true,
+                leaf_write_index: self.data.leaf_write_index,
             },
 
             // used in testing in order to short-circuit block-height lookups
@@ -964,7 +1011,8 @@ impl<'a, T: MarfTrieId> TrieStorageTransaction<'a, T> {
         if self.data.readonly {
             return Err(Error::ReadOnlyError);
         }
-        if let Some((bhh, trie_ram)) = self.data.last_extended.take() {
+        if let Some((bhh, mut trie_ram)) = self.data.last_extended.take() {
+            let leaf_writes = mem::replace(&mut trie_ram.leaf_writes, HashSet::new());
             trace!("Buffering block flush started.");
             let mut buffer = Cursor::new(Vec::new());
             trie_ram.dump(&mut buffer, &bhh)?;
@@ -1014,6 +1062,11 @@ impl<'a, T: MarfTrieId> TrieStorageTransaction<'a, T> {
                 }
             };
 
+            if let FlushOptions::MinedTable(_) = flush_options {
+            } else if self.data.leaf_write_index {
+                trie_sql::write_leaf_writes(&self.db, block_id, &leaf_writes)?;
+            }
+
             trie_sql::drop_lock(&self.db, &bhh)?;
 
             debug!("Flush: identifier of {} is {}", flush_options, block_id);
@@ -1054,7 +1107,7 @@ impl<'a, T: MarfTrieId> TrieStorageTransaction<'a, T> {
     pub fn drop_unconfirmed_trie(&mut self, bhh: &T) {
         self.clear_cached_ancestor_hashes_bytes();
         if !self.data.readonly && self.data.unconfirmed {
-            trie_sql::drop_unconfirmed_trie(&self.db, bhh)
+            trie_sql::drop_unconfirmed_trie(&self.db, bhh, self.data.leaf_write_index)
                 .expect("Corruption: Failed to drop unconfirmed trie");
             trie_sql::drop_lock(&self.db, bhh)
                 .expect("Corruption: Failed to drop the extended trie lock");
@@ -1149,7 +1202,8 @@ impl<'a, T: MarfTrieId> TrieStorageTransaction<'a, T> {
         debug!("Format TrieFileStorage");
 
         // blow away db
-        trie_sql::clear_tables(self.sqlite_tx())?;
+        let leaf_write_index = self.data.leaf_write_index;
+        trie_sql::clear_tables(self.sqlite_tx(), leaf_write_index)?;
 
         match self.data.last_extended {
             Some((_, ref mut trie_storage)) => trie_storage.format()?,
@@ -1448,6 +1502,40 @@ impl<'a, T: MarfTrieId> TrieStorageConnection<'a, T> {
         self.data.cur_block.clone()
     }
 
+    /// Remember that the trie being extended wrote a leaf at this path, rather than copying it
+    /// from an ancestor
+    pub fn note_leaf_write(&mut self, path: &TriePath) {
+        if !self.data.leaf_write_index {
+            return;
+        }
+        if let Some((_, ref mut trie_ram)) = self.data.last_extended {
+            trie_ram.note_leaf_write(path);
+        }
+    }
+
+    /// Is `bhh` the trie being extended, and did it write a leaf at `path`?
+    pub fn extending_trie_wrote_leaf(&self, bhh: &T, path: &TriePath) -> bool {
+        match self.data.last_extended {
+            Some((ref last_extended, ref trie_ram)) => {
+                last_extended == bhh && trie_ram.leaf_writes.contains(path)
+            }
+            None => false,
+        }
+    }
+
+    /// Identifiers of the stored tries that wrote a leaf at `path`, most recently stored first,
+    /// leaving out any stored after `max_block_id` (if given).  None if writes are not tracked.
+    pub fn get_leaf_writers(
+        &self,
+        path: &TriePath,
+        max_block_id: Option<u32>,
+    ) -> Result<Option<Vec<u32>>, Error> {
+        if !self.data.leaf_write_index {
+            return Ok(None);
+        }
+        trie_sql::get_leaf_writers(&self.db, path, max_block_id).map(Some)
+    }
+
     pub fn get_cur_block_and_id(&self) -> (T, Option<u32>) {
         (self.data.cur_block.clone(), self.data.cur_block_id.clone())
     }
@@ -1982,4 +2070,37 @@ pub mod test {
     fn load_store_trie_4_256_unique() {
         load_store_trie_m_n_same(4, 256, false);
     }
+
+    #[test]
+    fn leaf_write_index_is_dropped_when_disabled() {
+        let path = "/tmp/rust_leaf_write_index_is_dropped_when_disabled";
+        if fs::metadata(path).is_ok() {
+            fs::remove_file(path).unwrap();
+        }
+
+        let block = BlockHeaderHash([1u8; 32]);
+        {
+            let f = TrieFileStorage::open_opts(path, false, false, true).unwrap();
+            let mut marf = MARF::from_storage(f);
+            marf.begin(&BlockHeaderHash::sentinel(), &block).unwrap();
+            marf.insert("a", MARFValue::from_value("a0")).unwrap();
+            marf.commit().unwrap();
+            assert_eq!(
+                trie_sql::get_leaf_writers(marf.sqlite_conn(), &TriePath::from_key("a"), None)
+                    .unwrap()
+                    .len(),
+                1
+            );
+        }
+
+        // readers use the index as it is
+        let f: TrieFileStorage<BlockHeaderHash> = TrieFileStorage::open_readonly(path).unwrap();
+        assert!(f.data.leaf_write_index);
+
+        // a writer without it drops it, so it never misses writes
+        let f: TrieFileStorage<BlockHeaderHash> =
+            TrieFileStorage::open_opts(path, false, false, false).unwrap();
+        assert!(!f.data.leaf_write_index);
+        assert!(!trie_sql::has_leaf_writes_table(f.sqlite_conn()).unwrap());
+    }
 }
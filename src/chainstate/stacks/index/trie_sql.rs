@@ -82,6 +82,18 @@ static SQL_EXTENSION_LOCKS_TABLE: &str = "
 CREATE TABLE IF NOT EXISTS block_extension_locks (block_hash TEXT PRIMARY KEY);
 ";
 
+/// The tries in which each leaf path was written.  A trie can hold leaves that were written in an
+/// earlier block -- an insert whose path shares a prefix with an existing leaf copies that leaf
+/// into the new trie -- so the trie holding a leaf is not necessarily the one that wrote it.
+/// Tries in the mined-blocks table are not tracked.  Only kept if `MARF_LEAF_WRITE_INDEX` is set.
+static SQL_MARF_LEAF_WRITES_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS marf_leaf_writes (
+   path BLOB NOT NULL,
+   block_id INTEGER NOT NULL,
+   PRIMARY KEY(path, block_id)
+);
+";
+
 pub fn create_tables_if_needed(conn: &mut Connection) -> Result<(), Error> {
     sql_pragma(conn, "PRAGMA journal_mode = WAL;")?;
 
@@ -94,6 +106,27 @@ pub fn create_tables_if_needed(conn: &mut Connection) -> Result<(), Error> {
     tx.commit().map_err(|e| e.into())
 }
 
+pub fn create_leaf_writes_table(conn: &Connection) -> Result<(), Error> {
+    conn.execute_batch(SQL_MARF_LEAF_WRITES_TABLE)?;
+    Ok(())
+}
+
+pub fn drop_leaf_writes_table(conn: &Connection) -> Result<(), Error> {
+    conn.execute_batch("DROP TABLE IF EXISTS marf_leaf_writes")?;
+    Ok(())
+}
+
+pub fn has_leaf_writes_table(conn: &Connection) -> Result<bool, Error> {
+    let has_table: Option<i64> = conn
+        .query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'marf_leaf_writes'",
+            NO_PARAMS,
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(has_table.is_some())
+}
+
 pub fn get_block_identifier<T: MarfTrieId>(conn: &Connection, bhh: &T) -> Result<u32, Error> {
     conn.query_row(
         "SELECT block_id FROM marf_data WHERE block_hash = ?",
@@ -231,6 +264,42 @@ pub fn write_trie_blob_to_unconfirmed<T: MarfTrieId>(
     Ok(block_id)
 }
 
+/// Record that the trie with the given identifier wrote leaves at these paths
+pub fn write_leaf_writes<'a, I: IntoIterator<Item = &'a TriePath>>(
+    conn: &Connection,
+    block_id: u32,
+    paths: I,
+) -> Result<(), Error> {
+    let mut s =
+        conn.prepare("INSERT OR IGNORE INTO marf_leaf_writes (path, block_id) VALUES (?, ?)")?;
+    for path in paths.into_iter() {
+        let args: &[&dyn ToSql] = &[&path.as_bytes().to_vec(), &block_id];
+        s.execute(args)?;
+    }
+    Ok(())
+}
+
+/// Get the identifiers of the tries that wrote a leaf at `path`, most recently stored first,
+/// leaving out any stored after `max_block_id` (if given).  The `marf_leaf_writes` table must
+/// exist.
+pub fn get_leaf_writers(
+    conn: &Connection,
+    path: &TriePath,
+    max_block_id: Option<u32>,
+) -> Result<Vec<u32>, Error> {
+    let max_block_id = max_block_id.unwrap_or(u32::MAX);
+    let args: &[&dyn ToSql] = &[&path.as_bytes().to_vec(), &max_block_id];
+    let mut s = conn.prepare(
+        "SELECT block_id FROM marf_leaf_writes WHERE path = ? AND block_id <= ? ORDER BY block_id DESC",
+    )?;
+    let rows = s.query_map(args, |row| row.get(0))?;
+    let mut block_ids = vec![];
+    for block_id in rows {
+        block_ids.push(block_id?);
+    }
+    Ok(block_ids)
+}
+
 pub fn open_trie_blob<'a>(conn: &'a Connection, block_id: u32) -> Result<Blob<'a>, Error> {
     let blob = conn.blob_open(
         rusqlite::DatabaseName::Main,
@@ -417,8 +486,19 @@ pub fn drop_lock<T: MarfTrieId>(conn: &Connection, bhh: &T) -> Result<(), Error>
     Ok(())
 }
 
-pub fn drop_unconfirmed_trie<T: MarfTrieId>(conn: &Connection, bhh: &T) -> Result<(), Error> {
+pub fn drop_unconfirmed_trie<T: MarfTrieId>(
+    conn: &Connection,
+    bhh: &T,
+    leaf_write_index: bool,
+) -> Result<(), Error> {
     debug!("Drop unconfirmed trie sqlite blob {}", bhh);
+    if leaf_write_index {
+        conn.execute(
+            "DELETE FROM marf_leaf_writes WHERE block_id IN
+                (SELECT block_id FROM marf_data WHERE block_hash = ? AND unconfirmed = 1)",
+            &[bhh],
+        )?;
+    }
     conn.execute(
         "DELETE FROM marf_data WHERE block_hash = ? AND unconfirmed = 1",
         &[bhh],
@@ -432,9 +512,12 @@ pub fn clear_lock_data(conn: &Connection) -> Result<(), Error> {
     Ok(())
 }
 
-pub fn clear_tables(tx: &Transaction) -> Result<(), Error> {
+pub fn clear_tables(tx: &Transaction, leaf_write_index: bool) -> Result<(), Error> {
     tx.execute("DELETE FROM block_extension_locks", NO_PARAMS)?;
     tx.execute("DELETE FROM marf_data", NO_PARAMS)?;
     tx.execute("DELETE FROM mined_blocks", NO_PARAMS)?;
+    if leaf_write_index {
+        tx.execute("DELETE FROM marf_leaf_writes", NO_PARAMS)?;
+    }
     Ok(())
 }
@@ -16,7 +16,9 @@
 
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::convert::From;
+use std::fmt;
 use std::fs;
 use std::mem;
 
@@ -70,6 +72,128 @@ enum BlockLimitFunction {
     LIMIT_REACHED,
 }
 
+/// A reason a proposed block would not be accepted on top of its parent
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockProposalRejection {
+    /// The block takes up more than an epoch's worth of bytes
+    TooBig(u64),
+    /// The transactions fail the static checks: duplicates, the wrong network or chain ID, the
+    /// wrong anchor mode, or a missing or misplaced coinbase
+    InvalidTransactions,
+    /// The header's transaction Merkle root does not match the transactions
+    BadTxMerkleRoot,
+    /// The node has not processed the parent block
+    UnknownParent,
+    /// The block's height is not one more than its parent's: (expected, got)
+    BadHeight(u64, u64),
+    /// The node does not have the parent microblock the block confirms
+    UnknownParentMicroblock,
+    /// The parent microblocks the block confirms are invalid
+    InvalidParentMicroblocks(String),
+    /// An ancestor already used the microblock public key hash, at this height
+    MicroblockPubkeyHashReused(u32),
+    /// A transaction could not be applied
+    BadTransaction(Txid, String),
+    /// The block exceeds its execution budget, starting with this transaction
+    BudgetExceeded(Txid),
+    /// The header's state root does not match the state the block produces: (expected, got)
+    BadStateRoot(TrieHash, TrieHash),
+}
+
+impl BlockProposalRejection {
+    /// A short, stable name for the rejection, for clients to match on
+    pub fn reason(&self) -> &'static str {
+        match self {
+            BlockProposalRejection::TooBig(..) => "too_big",
+            BlockProposalRejection::InvalidTransactions => "invalid_transactions",
+            BlockProposalRejection::BadTxMerkleRoot => "bad_tx_merkle_root",
+            BlockProposalRejection::UnknownParent => "unknown_parent",
+            BlockProposalRejection::BadHeight(..) => "bad_height",
+            BlockProposalRejection::UnknownParentMicroblock => "unknown_parent_microblock",
+            BlockProposalRejection::InvalidParentMicroblocks(..) => "invalid_parent_microblocks",
+            BlockProposalRejection::MicroblockPubkeyHashReused(..) => {
+                "microblock_pubkey_hash_reused"
+            }
+            BlockProposalRejection::BadTransaction(..) => "bad_transaction",
+            BlockProposalRejection::BudgetExceeded(..) => "budget_exceeded",
+            BlockProposalRejection::BadStateRoot(..) => "bad_state_root",
+        }
+    }
+
+    /// The transaction the rejection is about, if it is about one
+    pub fn txid(&self) -> Option<&Txid> {
+        match self {
+            BlockProposalRejection::BadTransaction(txid, _)
+            | BlockProposalRejection::BudgetExceeded(txid) => Some(txid),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BlockProposalRejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BlockProposalRejection::TooBig(size) => write!(
+                f,
+                "Block is {} bytes; at most {} are allowed",
+                size, MAX_EPOCH_SIZE
+            ),
+            BlockProposalRejection::InvalidTransactions => {
+                write!(f, "Transactions fail the static block checks")
+            }
+            BlockProposalRejection::BadTxMerkleRoot => {
+                write!(f, "Transaction Merkle root does not match the transactions")
+            }
+            BlockProposalRejection::UnknownParent => write!(f, "Parent block is not processed"),
+            BlockProposalRejection::BadHeight(expected, got) => {
+                write!(f, "Block height is {}, but must be {}", got, expected)
+            }
+            BlockProposalRejection::UnknownParentMicroblock => {
+                write!(f, "Parent microblock is not known")
+            }
+            BlockProposalRejection::InvalidParentMicroblocks(msg) => {
+                write!(f, "Parent microblocks are invalid: {}", msg)
+            }
+            BlockProposalRejection::MicroblockPubkeyHashReused(height) => write!(
+                f,
+                "Microblock public key hash was already used at height {}",
+                height
+            ),
+            BlockProposalRejection::BadTransaction(txid, msg) => {
+                write!(f, "Transaction {} is invalid: {}", txid, msg)
+            }
+            BlockProposalRejection::BudgetExceeded(txid) => write!(
+                f,
+                "Block exceeds its execution budget at transaction {}",
+                txid
+            ),
+            BlockProposalRejection::BadStateRoot(expected, got) => {
+                write!(f, "State root is {}, but must be {}", got, expected)
+            }
+        }
+    }
+}
+
+/// The outcome of checking a proposed block
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockProposalValidation {
+    /// Everything wrong with the block; empty if it would be accepted
+    pub rejections: Vec<BlockProposalRejection>,
+    /// The block's parent, if the node has processed it
+    pub parent: Option<StacksHeaderInfo>,
+    /// How much of the execution budget the block and the microblocks it confirms take up, if
+    /// the transactions were run
+    pub cost: Option<ExecutionCost>,
+    /// The fees the block's transactions pay
+    pub fees: u64,
+}
+
+impl BlockProposalValidation {
+    pub fn accepted(&self) -> bool {
+        self.rejections.is_empty()
+    }
+}
+
 impl From<&UnconfirmedState> for MicroblockMinerRuntime {
     fn from(unconfirmed: &UnconfirmedState) -> MicroblockMinerRuntime {
         let considered = unconfirmed
@@ -882,33 +1006,7 @@ impl StacksBlockBuilder {
     pub fn mine_anchored_block(&mut self, clarity_tx: &mut ClarityTx) -> StacksBlock {
         assert!(!self.anchored_done);
 
-        // add miner payments
-        if let Some((ref miner_reward, ref user_rewards, ref parent_reward)) = self.miner_payouts {
-            // grant in order by miner, then users
-            let matured_ustx = StacksChainState::process_matured_miner_rewards(
-                clarity_tx,
-                miner_reward,
-                user_rewards,
-                parent_reward,
-            )
-            .expect("FATAL: failed to process miner rewards");
-
-            clarity_tx.increment_ustx_liquid_supply(matured_ustx);
-        }
-
-        // process unlocks
-        let (new_unlocked_ustx, _) =
-            StacksChainState::process_stx_unlocks(clarity_tx).expect("FATAL: failed to unlock STX");
-
-        clarity_tx.increment_ustx_liquid_supply(new_unlocked_ustx);
-
-        // mark microblock public key as used
-        StacksChainState::insert_microblock_pubkey_hash(
-            clarity_tx,
-            self.header.total_work.work as u32,
-            &self.header.microblock_pubkey_hash,
-        )
-        .expect("FATAL: failed to insert microblock pubkey hash");
+        self.apply_end_of_block(clarity_tx);
 
         // done!  Calculate state root and tx merkle root
         let txid_vecs = self
@@ -959,6 +1057,38 @@ impl StacksBlockBuilder {
         block
     }
 
+    /// Grant the matured miner rewards, unlock STX, and mark the microblock public key hash as
+    /// used, as the end of each anchored block does
+    fn apply_end_of_block(&self, clarity_tx: &mut ClarityTx) {
+        // add miner payments
+        if let Some((ref miner_reward, ref user_rewards, ref parent_reward)) = self.miner_payouts {
+            // grant in order by miner, then users
+            let matured_ustx = StacksChainState::process_matured_miner_rewards(
+                clarity_tx,
+                miner_reward,
+                user_rewards,
+                parent_reward,
+            )
+            .expect("FATAL: failed to process miner rewards");
+
+            clarity_tx.increment_ustx_liquid_supply(matured_ustx);
+        }
+
+        // process unlocks
+        let (new_unlocked_ustx, _) =
+            StacksChainState::process_stx_unlocks(clarity_tx).expect("FATAL: failed to unlock STX");
+
+        clarity_tx.increment_ustx_liquid_supply(new_unlocked_ustx);
+
+        // mark microblock public key as used
+        StacksChainState::insert_microblock_pubkey_hash(
+            clarity_tx,
+            self.header.total_work.work as u32,
+            &self.header.microblock_pubkey_hash,
+        )
+        .expect("FATAL: failed to insert microblock pubkey hash");
+    }
+
     /// Cut the next microblock.
     pub fn mine_next_microblock<'a>(&mut self) -> Result<StacksMicroblock, Error> {
         let txid_vecs = self
@@ -1018,6 +1148,10 @@ impl StacksBlockBuilder {
         parent_index_hash: &StacksBlockId,
     ) -> Result<Vec<StacksMicroblock>, Error> {
         if let Some(microblock_parent_hash) = self.parent_microblock_hash.as_ref() {
+            if *microblock_parent_hash == EMPTY_MICROBLOCK_PARENT_HASH {
+                // confirms no microblocks
+                return Ok(vec![]);
+            }
             // load up a microblock fork
             let microblocks = StacksChainState::load_microblock_stream_fork(
                 &chainstate.db(),
@@ -1153,7 +1287,11 @@ impl StacksBlockBuilder {
         if parent_microblocks.len() == 0 {
             self.set_parent_microblock(&EMPTY_MICROBLOCK_PARENT_HASH, 0);
         } else {
-            match StacksChainState::process_microblocks_transactions(&mut tx, &parent_microblocks) {
+            match StacksChainState::process_microblocks_transactions(
+                &mut tx,
+                &parent_microblocks,
+                &HashSet::new(),
+            ) {
                 Ok((fees, ..)) => {
                     self.total_confirmed_streamed_fees += fees as u64;
                 }
@@ -1350,6 +1488,29 @@ impl StacksBlockBuilder {
         Ok(builder)
     }
 
+    /// If `tx` is sponsored, and its sponsor nonce is ahead of the sponsor's nonce in the block
+    /// being built, return the sponsor: the transaction must wait for the sponsor's earlier
+    /// transactions.  The mempool is walked in origin nonce order, so a sponsor's transactions can
+    /// come up out of sponsor nonce order.
+    fn get_sponsor_to_wait_on(
+        clarity_tx: &mut ClarityTx,
+        tx: &StacksTransaction,
+    ) -> Option<StacksAddress> {
+        let (sponsor_addr, sponsor_nonce) = match (tx.sponsor_address(), tx.get_sponsor_nonce()) {
+            (Some(addr), Some(nonce)) => (addr, nonce),
+            _ => {
+                return None;
+            }
+        };
+        let account =
+            StacksChainState::get_account(clarity_tx, &sponsor_addr.to_account_principal());
+        if sponsor_nonce > account.nonce {
+            Some(sponsor_addr)
+        } else {
+            None
+        }
+    }
+
     /// Given access to the mempool, mine an anchored block with no more than the given execution cost.
     ///   returns the assembled block, and the consumed execution budget.
     pub fn build_anchored_block(
@@ -1400,6 +1561,7 @@ impl StacksBlockBuilder {
         let mut considered = HashSet::new(); // txids of all transactions we looked at
         let mut mined_origin_nonces: HashMap<StacksAddress, u64> = HashMap::new(); // map addrs of mined transaction origins to the nonces we used
         let mut mined_sponsor_nonces: HashMap<StacksAddress, u64> = HashMap::new(); // map addrs of mined transaction sponsors to the nonces we used
+        let mut waiting_on_sponsors: HashMap<StacksAddress, Vec<MemPoolTxInfo>> = HashMap::new(); // sponsored transactions waiting on their sponsors' earlier transactions
 
         let mut invalidated_txs = vec![];
 
@@ -1410,7 +1572,8 @@ impl StacksBlockBuilder {
                 return Ok(());
             }
 
-            for txinfo in available_txs.into_iter() {
+            let mut queue: VecDeque<MemPoolTxInfo> = available_txs.into_iter().collect();
+            while let Some(txinfo) = queue.pop_front() {
                 // skip transactions early if we can
                 if considered.contains(&txinfo.tx.txid()) {
                     continue;
@@ -1430,6 +1593,17 @@ impl StacksBlockBuilder {
                     }
                 }
 
+                if let Some(sponsor_addr) =
+                    StacksBlockBuilder::get_sponsor_to_wait_on(&mut epoch_tx, &txinfo.tx)
+                {
+                    // try it again once the sponsor's nonce catches up
+                    waiting_on_sponsors
+                        .entry(sponsor_addr)
+                        .or_insert_with(Vec::new)
+                        .push(txinfo);
+                    continue;
+                }
+
                 considered.insert(txinfo.tx.txid());
 
                 match builder.try_mine_tx_with_len(
@@ -1476,6 +1650,20 @@ impl StacksBlockBuilder {
                 {
                     mined_sponsor_nonces.insert(sponsor_addr, sponsor_nonce);
                 }
+
+                // this transaction advanced its origin's and its sponsor's nonces, so anything
+                // waiting on either of them can be tried again
+                let mut advanced = vec![txinfo.tx.origin_address()];
+                if let Some(sponsor_addr) = txinfo.tx.sponsor_address() {
+                    advanced.push(sponsor_addr);
+                }
+                for addr in advanced.iter() {
+                    if let Some(waiting) = waiting_on_sponsors.remove(addr) {
+                        for waiting_txinfo in waiting.into_iter().rev() {
+                            queue.push_front(waiting_txinfo);
+                        }
+                    }
+                }
             }
             Ok(())
         });
@@ -1519,6 +1707,176 @@ impl StacksBlockBuilder {
 
         Ok((block, consumed, size))
     }
+
+    /// Check a block that someone else assembled, as if this node were about to mine it on top
+    /// of the parent block `parent_consensus_hash`/`block.header.parent_block`.  The block's
+    /// transactions are run against the parent's state (after the parent microblocks the block
+    /// confirms, and the burnchain operations at the canonical burnchain tip), and then thrown
+    /// away; nothing is stored.
+    ///
+    /// The checks that need the block's sortition -- that its VRF proof is valid, that it won,
+    /// and that it was committed to -- cannot be made ahead of time, and are not.
+    pub fn validate_block_proposal(
+        chainstate_handle: &StacksChainState, // not directly used; used as a handle to open other chainstates
+        burn_dbconn: &SortitionDBConn,
+        parent_consensus_hash: &ConsensusHash,
+        block: &StacksBlock,
+    ) -> Result<BlockProposalValidation, Error> {
+        let mut validation = BlockProposalValidation {
+            rejections: vec![],
+            parent: None,
+            cost: None,
+            fees: 0,
+        };
+
+        let size = block.serialize_to_vec().len() as u64;
+        if size > MAX_EPOCH_SIZE as u64 {
+            validation
+                .rejections
+                .push(BlockProposalRejection::TooBig(size));
+        }
+        if !block
+            .validate_transactions_static(chainstate_handle.mainnet, chainstate_handle.chain_id)
+        {
+            validation
+                .rejections
+                .push(BlockProposalRejection::InvalidTransactions);
+        }
+        let txid_vecs = block
+            .txs
+            .iter()
+            .map(|tx| tx.txid().as_bytes().to_vec())
+            .collect();
+        if MerkleTree::<Sha512Trunc256Sum>::new(&txid_vecs).root() != block.header.tx_merkle_root {
+            validation
+                .rejections
+                .push(BlockProposalRejection::BadTxMerkleRoot);
+        }
+
+        let parent = match StacksChainState::get_anchored_block_header_info(
+            chainstate_handle.db(),
+            parent_consensus_hash,
+            &block.header.parent_block,
+        )? {
+            Some(parent) => parent,
+            None => {
+                validation
+                    .rejections
+                    .push(BlockProposalRejection::UnknownParent);
+                return Ok(validation);
+            }
+        };
+        if block.header.total_work.work != parent.block_height + 1 {
+            validation
+                .rejections
+                .push(BlockProposalRejection::BadHeight(
+                    parent.block_height + 1,
+                    block.header.total_work.work,
+                ));
+        }
+        validation.parent = Some(parent.clone());
+        if !validation.rejections.is_empty() {
+            // no point running transactions that can't make an acceptable block
+            return Ok(validation);
+        }
+
+        let (mut chainstate, _) =
+            chainstate_handle.reopen_limited(chainstate_handle.block_limit.clone())?; // used for processing a block up to the given limit
+
+        let mut builder = StacksBlockBuilder::from_parent_pubkey_hash(
+            0,
+            &parent,
+            &block.header.total_work,
+            &block.header.proof,
+            block.header.microblock_pubkey_hash.clone(),
+        );
+        builder.set_parent_block(&block.header.parent_block);
+        builder.parent_microblock_hash = Some(block.header.parent_microblock.clone());
+
+        let mut epoch_tx = match builder.epoch_begin(&mut chainstate, burn_dbconn) {
+            Ok(epoch_tx) => epoch_tx,
+            Err(Error::InvalidStacksMicroblock(msg, _)) => {
+                validation
+                    .rejections
+                    .push(BlockProposalRejection::InvalidParentMicroblocks(msg));
+                return Ok(validation);
+            }
+            Err(e) => return Err(e),
+        };
+
+        // epoch_begin() confirms no microblocks if it can't find the ones asked for
+        if builder.header.parent_microblock != block.header.parent_microblock
+            || builder.header.parent_microblock_sequence != block.header.parent_microblock_sequence
+        {
+            validation
+                .rejections
+                .push(BlockProposalRejection::UnknownParentMicroblock);
+        }
+
+        match StacksChainState::has_microblock_pubkey_hash(
+            &mut epoch_tx,
+            &block.header.microblock_pubkey_hash,
+        ) {
+            Ok(Some(height)) => validation
+                .rejections
+                .push(BlockProposalRejection::MicroblockPubkeyHashReused(height)),
+            Ok(None) => {}
+            Err(e) => {
+                epoch_tx.rollback_block();
+                return Err(e);
+            }
+        }
+
+        if validation.rejections.is_empty() {
+            for tx in block.txs.iter() {
+                match StacksChainState::process_transaction(&mut epoch_tx, tx, true) {
+                    Ok((fee, _receipt)) => {
+                        validation.fees += fee;
+                    }
+                    Err(Error::CostOverflowError(cost_before, _, _)) => {
+                        epoch_tx.reset_cost(cost_before);
+                        validation
+                            .rejections
+                            .push(BlockProposalRejection::BudgetExceeded(tx.txid()));
+                        break;
+                    }
+                    Err(e) => {
+                        validation
+                            .rejections
+                            .push(BlockProposalRejection::BadTransaction(
+                                tx.txid(),
+                                format!("{:?}", &e),
+                            ));
+                    }
+                }
+            }
+        }
+
+        if validation.rejections.is_empty() {
+            builder.apply_end_of_block(&mut epoch_tx);
+            let state_root = epoch_tx.get_root_hash();
+            if state_root != block.header.state_index_root {
+                validation
+                    .rejections
+                    .push(BlockProposalRejection::BadStateRoot(
+                        state_root,
+                        block.header.state_index_root.clone(),
+                    ));
+            }
+        }
+
+        validation.cost = Some(epoch_tx.cost_so_far());
+        epoch_tx.rollback_block();
+
+        debug!(
+            "Validated proposed block {} on {}/{}: {} rejection(s)",
+            block.block_hash(),
+            parent_consensus_hash,
+            &block.header.parent_block,
+            validation.rejections.len()
+        );
+        Ok(validation)
+    }
 }
 
 #[cfg(test)]
@@ -6196,6 +6554,51 @@ pub mod test {
         sign_standard_singlesig_tx(payload.into(), sender, nonce, tx_fee)
     }
 
+    pub fn make_sponsored_stacks_transfer(
+        sender: &StacksPrivateKey,
+        nonce: u64,
+        sponsor: &StacksPrivateKey,
+        sponsor_nonce: u64,
+        tx_fee: u64,
+        recipient: &PrincipalData,
+        amount: u64,
+    ) -> StacksTransaction {
+        let payload = TransactionPayload::TokenTransfer(
+            recipient.clone(),
+            amount,
+            TokenTransferMemo([0; 34]),
+        );
+        let mut origin_condition = TransactionSpendingCondition::new_singlesig_p2pkh(
+            StacksPublicKey::from_private(sender),
+        )
+        .expect("Failed to create p2pkh spending condition from public key.");
+        origin_condition.set_nonce(nonce);
+        let auth = TransactionAuth::Sponsored(
+            origin_condition,
+            TransactionSpendingCondition::new_initial_sighash(),
+        );
+        let mut unsigned_tx = StacksTransaction::new(TransactionVersion::Testnet, auth, payload);
+
+        unsigned_tx.chain_id = 0x80000000;
+        unsigned_tx.post_condition_mode = TransactionPostConditionMode::Allow;
+
+        let mut tx_signer = StacksTransactionSigner::new(&unsigned_tx);
+        tx_signer.sign_origin(sender).unwrap();
+
+        let mut sponsor_condition = TransactionSpendingCondition::new_singlesig_p2pkh(
+            StacksPublicKey::from_private(sponsor),
+        )
+        .expect("Failed to create p2pkh spending condition from public key.");
+        sponsor_condition.set_nonce(sponsor_nonce);
+        sponsor_condition.set_tx_fee(tx_fee);
+        let mut sponsor_signer =
+            StacksTransactionSigner::new_sponsor(&tx_signer.get_tx_incomplete(), sponsor_condition)
+                .unwrap();
+        sponsor_signer.sign_sponsor(sponsor).unwrap();
+
+        sponsor_signer.get_tx().unwrap()
+    }
+
     pub fn make_user_coinbase(
         sender: &StacksPrivateKey,
         nonce: u64,
@@ -6325,6 +6728,134 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_validate_block_proposal() {
+        let peer_config = TestPeerConfig::new("test_validate_block_proposal", 2020, 2021);
+        let mut peer = TestPeer::new(peer_config);
+
+        let chainstate_path = peer.chainstate_path.clone();
+
+        let num_blocks = 3;
+        for tenure_id in 0..num_blocks {
+            let tip =
+                SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+                    .unwrap();
+
+            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+                |ref mut miner,
+                 ref mut sortdb,
+                 ref mut chainstate,
+                 vrf_proof,
+                 ref parent_opt,
+                 ref parent_microblock_header_opt| {
+                    let parent_tip = match parent_opt {
+                        None => StacksChainState::get_genesis_header_info(chainstate.db()).unwrap(),
+                        Some(block) => {
+                            let ic = sortdb.index_conn();
+                            let snapshot =
+                                SortitionDB::get_block_snapshot_for_winning_stacks_block(
+                                    &ic,
+                                    &tip.sortition_id,
+                                    &block.block_hash(),
+                                )
+                                .unwrap()
+                                .unwrap(); // succeeds because we don't fork
+                            StacksChainState::get_anchored_block_header_info(
+                                chainstate.db(),
+                                &snapshot.consensus_hash,
+                                &snapshot.winning_stacks_block_hash,
+                            )
+                            .unwrap()
+                            .unwrap()
+                        }
+                    };
+
+                    let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+
+                    let coinbase_tx = make_coinbase(miner, tenure_id);
+
+                    let (anchored_block, ..) = StacksBlockBuilder::build_anchored_block(
+                        chainstate,
+                        &sortdb.index_conn(),
+                        &mut mempool,
+                        &parent_tip,
+                        tip.total_burn,
+                        vrf_proof,
+                        Hash160([tenure_id as u8; 20]),
+                        &coinbase_tx,
+                        ExecutionCost::max_value(),
+                        None,
+                    )
+                    .unwrap();
+
+                    let chainstate: &StacksChainState = chainstate;
+                    let sortdb: &SortitionDB = sortdb;
+                    let validate = |block: &StacksBlock, parent_consensus_hash: &ConsensusHash| {
+                        StacksBlockBuilder::validate_block_proposal(
+                            chainstate,
+                            &sortdb.index_conn(),
+                            parent_consensus_hash,
+                            block,
+                        )
+                        .unwrap()
+                    };
+
+                    // the block the miner built is acceptable
+                    let validation = validate(&anchored_block, &parent_tip.consensus_hash);
+                    assert!(validation.accepted(), "{:?}", &validation.rejections);
+                    assert_eq!(validation.parent, Some(parent_tip.clone()));
+                    assert!(validation.cost.is_some());
+
+                    // ...and validating it stored nothing, so it's still acceptable
+                    let validation = validate(&anchored_block, &parent_tip.consensus_hash);
+                    assert!(validation.accepted(), "{:?}", &validation.rejections);
+
+                    let mut bad_state_root = anchored_block.clone();
+                    bad_state_root.header.state_index_root = TrieHash([0x11; 32]);
+                    assert_eq!(
+                        validate(&bad_state_root, &parent_tip.consensus_hash).rejections,
+                        vec![BlockProposalRejection::BadStateRoot(
+                            anchored_block.header.state_index_root.clone(),
+                            TrieHash([0x11; 32])
+                        )]
+                    );
+
+                    let mut no_coinbase = anchored_block.clone();
+                    no_coinbase.txs.clear();
+                    assert_eq!(
+                        validate(&no_coinbase, &parent_tip.consensus_hash).rejections,
+                        vec![
+                            BlockProposalRejection::InvalidTransactions,
+                            BlockProposalRejection::BadTxMerkleRoot
+                        ]
+                    );
+
+                    let mut bad_height = anchored_block.clone();
+                    bad_height.header.total_work.work += 1;
+                    assert_eq!(
+                        validate(&bad_height, &parent_tip.consensus_hash).rejections,
+                        vec![BlockProposalRejection::BadHeight(
+                            parent_tip.block_height + 1,
+                            parent_tip.block_height + 2
+                        )]
+                    );
+
+                    let validation = validate(&anchored_block, &ConsensusHash([0x22; 20]));
+                    assert_eq!(
+                        validation.rejections,
+                        vec![BlockProposalRejection::UnknownParent]
+                    );
+                    assert_eq!(validation.parent, None);
+
+                    (anchored_block, vec![])
+                },
+            );
+
+            peer.next_burnchain_block(burn_ops.clone());
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+        }
+    }
+
     #[test]
     fn test_build_anchored_blocks_stx_transfers_single() {
         let privk = StacksPrivateKey::from_hex(
@@ -6464,6 +6995,160 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_build_anchored_blocks_sponsor_nonces_out_of_order() {
+        let privks: Vec<_> = (0..4).map(|_| StacksPrivateKey::new()).collect();
+        let addrs: Vec<_> = privks
+            .iter()
+            .map(|privk| {
+                StacksAddress::from_public_keys(
+                    C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+                    &AddressHashMode::SerializeP2PKH,
+                    1,
+                    &vec![StacksPublicKey::from_private(privk)],
+                )
+                .unwrap()
+            })
+            .collect();
+        let sponsor = &privks[3];
+
+        let mut peer_config = TestPeerConfig::new(
+            "test_build_anchored_blocks_sponsor_nonces_out_of_order",
+            2016,
+            2017,
+        );
+        peer_config.initial_balances = addrs
+            .iter()
+            .map(|addr| (addr.to_account_principal(), 1000000000))
+            .collect();
+
+        let mut peer = TestPeer::new(peer_config);
+
+        let chainstate_path = peer.chainstate_path.clone();
+
+        let recipient_addr_str = "ST1RFD5Q2QPK3E0F08HG9XDX7SSC7CNRS0QR0SGEV";
+        let recipient = StacksAddress::from_string(recipient_addr_str).unwrap();
+
+        // The mempool is walked in origin nonce order.  The sponsor's first transaction is its
+        // origin's second, so the sponsor's later transactions come up before it does.
+        let txs = vec![
+            make_user_stacks_transfer(&privks[0], 0, 1000, &recipient.to_account_principal(), 1),
+            make_sponsored_stacks_transfer(
+                &privks[1],
+                0,
+                sponsor,
+                1,
+                1000,
+                &recipient.to_account_principal(),
+                1,
+            ),
+            make_sponsored_stacks_transfer(
+                &privks[2],
+                0,
+                sponsor,
+                2,
+                1000,
+                &recipient.to_account_principal(),
+                1,
+            ),
+            make_sponsored_stacks_transfer(
+                &privks[0],
+                1,
+                sponsor,
+                0,
+                1000,
+                &recipient.to_account_principal(),
+                1,
+            ),
+        ];
+
+        for tenure_id in 0..2 {
+            let tip =
+                SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+                    .unwrap();
+
+            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+                |ref mut miner,
+                 ref mut sortdb,
+                 ref mut chainstate,
+                 vrf_proof,
+                 ref parent_opt,
+                 ref parent_microblock_header_opt| {
+                    let parent_tip = match parent_opt {
+                        None => StacksChainState::get_genesis_header_info(chainstate.db()).unwrap(),
+                        Some(block) => {
+                            let ic = sortdb.index_conn();
+                            let snapshot =
+                                SortitionDB::get_block_snapshot_for_winning_stacks_block(
+                                    &ic,
+                                    &tip.sortition_id,
+                                    &block.block_hash(),
+                                )
+                                .unwrap()
+                                .unwrap(); // succeeds because we don't fork
+                            StacksChainState::get_anchored_block_header_info(
+                                chainstate.db(),
+                                &snapshot.consensus_hash,
+                                &snapshot.winning_stacks_block_hash,
+                            )
+                            .unwrap()
+                            .unwrap()
+                        }
+                    };
+
+                    let parent_header_hash = parent_tip.anchored_header.block_hash();
+                    let parent_consensus_hash = parent_tip.consensus_hash.clone();
+
+                    let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+
+                    let coinbase_tx = make_coinbase(miner, tenure_id);
+
+                    // initial balances aren't materialized if the tip is the genesis header
+                    if tenure_id > 0 {
+                        for tx in txs.iter() {
+                            mempool
+                                .submit(
+                                    chainstate,
+                                    &parent_consensus_hash,
+                                    &parent_header_hash,
+                                    tx,
+                                    None,
+                                )
+                                .unwrap();
+                        }
+                    }
+                    let anchored_block = StacksBlockBuilder::build_anchored_block(
+                        chainstate,
+                        &sortdb.index_conn(),
+                        &mut mempool,
+                        &parent_tip,
+                        tip.total_burn,
+                        vrf_proof,
+                        Hash160([tenure_id as u8; 20]),
+                        &coinbase_tx,
+                        ExecutionCost::max_value(),
+                        None,
+                    )
+                    .unwrap();
+                    (anchored_block.0, vec![])
+                },
+            );
+
+            peer.next_burnchain_block(burn_ops.clone());
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+
+            if tenure_id > 0 {
+                // every transaction was mined, each sponsored one once the sponsor's nonce
+                // caught up to it
+                let mined: Vec<_> = stacks_block.txs[1..].iter().map(|tx| tx.txid()).collect();
+                assert_eq!(
+                    mined,
+                    vec![txs[0].txid(), txs[3].txid(), txs[1].txid(), txs[2].txid()]
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_build_anchored_blocks_stx_transfers_multi() {
         let mut privks = vec![];
@@ -8563,6 +9248,7 @@ pub mod test {
             get_bulk_initial_balances: None,
             get_bulk_initial_names: None,
             get_bulk_initial_namespaces: None,
+            custom_costs_contract: None,
         };
 
         StacksChainState::open_and_exec(
@@ -70,6 +70,7 @@ pub mod events;
 pub mod index;
 pub mod miner;
 pub mod transaction;
+pub mod vectors;
 
 pub type StacksPublicKey = secp256k1::Secp256k1PublicKey;
 pub type StacksPrivateKey = secp256k1::Secp256k1PrivateKey;
@@ -0,0 +1,585 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020-2021 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Test vectors for the consensus-critical encodings: transactions, blocks, MARF proofs and
+//! Clarity values.  Each vector pairs the consensus serialization of an object with what an
+//! implementation should decode from it, so another implementation can check that it encodes and
+//! decodes these objects exactly as this node does.  The vectors are built from fixed keys and
+//! data, so generating them twice gives the same bytes.
+//!
+//! A vector passes if its bytes decode, re-encode to the same bytes, and decode to what it
+//! expects.  A vector with the name of one of this node's own vectors must also have the same
+//! bytes as it.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+use serde_json::Value as JSONValue;
+
+use address::AddressHashMode;
+use chainstate::stacks::index::marf::MARF;
+use chainstate::stacks::index::node::TriePath;
+use chainstate::stacks::*;
+use codec::StacksMessageCodec;
+use core::{CHAIN_ID_MAINNET, CHAIN_ID_TESTNET};
+use types::chainstate::{MARFValue, StacksAddress, StacksBlockHeader, StacksBlockId};
+use types::proof::{ClarityMarfTrieId, TrieHash, TrieMerkleProof, TrieMerkleProofType};
+use util::hash::{hex_bytes, to_hex, Hash160, MerkleTree, Sha512Trunc256Sum};
+use vm::representations::{ClarityName, ContractName};
+use vm::types::{PrincipalData, QualifiedContractIdentifier, TupleData, Value};
+
+pub const VECTOR_KIND_TRANSACTION: &str = "transaction";
+pub const VECTOR_KIND_BLOCK: &str = "block";
+pub const VECTOR_KIND_MARF_PROOF: &str = "marf-proof";
+pub const VECTOR_KIND_CLARITY_VALUE: &str = "clarity-value";
+
+/// The keys the transaction vectors are signed with
+const VECTOR_PRIVATE_KEYS: &[&str] = &[
+    "edf9aee84d9b7abc145504dde6726c64f369d37ee34ded868fabd876c26570bc01",
+    "8289fe7a4b6e9fe1e8ae7c0ae0b8b3e13825f5c1e33c8294baa7a6dfb0d3b2d901",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestVector {
+    /// One of the `VECTOR_KIND_*` constants
+    pub kind: String,
+    pub name: String,
+    /// The consensus serialization, in hex
+    pub hex: String,
+    /// What decoding `hex` must give
+    pub expected: JSONValue,
+}
+
+impl TestVector {
+    fn new(kind: &str, name: &str, bytes: &[u8], expected: JSONValue) -> TestVector {
+        TestVector {
+            kind: kind.to_string(),
+            name: name.to_string(),
+            hex: to_hex(bytes),
+            expected,
+        }
+    }
+
+    /// The file this vector is written to
+    pub fn file_name(&self) -> String {
+        format!("{}-{}.json", &self.kind, &self.name)
+    }
+}
+
+fn vector_private_key(i: usize) -> StacksPrivateKey {
+    StacksPrivateKey::from_hex(VECTOR_PRIVATE_KEYS[i]).expect("BUG: bad test vector key")
+}
+
+fn vector_address(key_index: usize, mainnet: bool) -> StacksAddress {
+    let version = if mainnet {
+        C32_ADDRESS_VERSION_MAINNET_SINGLESIG
+    } else {
+        C32_ADDRESS_VERSION_TESTNET_SINGLESIG
+    };
+    StacksAddress::from_public_keys(
+        version,
+        &AddressHashMode::SerializeP2PKH,
+        1,
+        &vec![StacksPublicKey::from_private(&vector_private_key(
+            key_index,
+        ))],
+    )
+    .expect("BUG: bad test vector key")
+}
+
+fn vector_contract() -> QualifiedContractIdentifier {
+    QualifiedContractIdentifier::new(
+        vector_address(1, false).into(),
+        ContractName::try_from("vector-contract".to_string()).unwrap(),
+    )
+}
+
+fn make_signed_tx(
+    mainnet: bool,
+    key_index: usize,
+    nonce: u64,
+    fee: u64,
+    payload: TransactionPayload,
+) -> StacksTransaction {
+    let privk = vector_private_key(key_index);
+    let (version, chain_id) = if mainnet {
+        (TransactionVersion::Mainnet, CHAIN_ID_MAINNET)
+    } else {
+        (TransactionVersion::Testnet, CHAIN_ID_TESTNET)
+    };
+    let auth = TransactionAuth::from_p2pkh(&privk).expect("BUG: bad test vector key");
+    let mut tx = StacksTransaction::new(version, auth, payload);
+    tx.chain_id = chain_id;
+    tx.set_tx_fee(fee);
+    tx.set_origin_nonce(nonce);
+    let mut signer = StacksTransactionSigner::new(&tx);
+    signer
+        .sign_origin(&privk)
+        .expect("BUG: failed to sign test vector transaction");
+    signer
+        .get_tx()
+        .expect("BUG: failed to sign test vector transaction")
+}
+
+fn vector_transactions() -> Vec<(&'static str, StacksTransaction)> {
+    let recipient = PrincipalData::from(vector_address(1, false));
+    vec![
+        (
+            "coinbase",
+            make_signed_tx(
+                false,
+                0,
+                0,
+                0,
+                TransactionPayload::Coinbase(CoinbasePayload([0x11; 32])),
+            ),
+        ),
+        (
+            "token-transfer",
+            make_signed_tx(
+                false,
+                0,
+                1,
+                180,
+                TransactionPayload::TokenTransfer(recipient, 123456, TokenTransferMemo([0x22; 34])),
+            ),
+        ),
+        (
+            "token-transfer-mainnet",
+            make_signed_tx(
+                true,
+                1,
+                7,
+                200,
+                TransactionPayload::TokenTransfer(
+                    PrincipalData::from(vector_address(0, true)),
+                    1,
+                    TokenTransferMemo([0x00; 34]),
+                ),
+            ),
+        ),
+        (
+            "smart-contract",
+            make_signed_tx(
+                false,
+                1,
+                0,
+                1000,
+                TransactionPayload::new_smart_contract(
+                    "vector-contract",
+                    "(define-public (add (a int) (b int)) (ok (+ a b)))",
+                )
+                .expect("BUG: bad test vector contract"),
+            ),
+        ),
+        (
+            "contract-call",
+            make_signed_tx(
+                false,
+                0,
+                2,
+                300,
+                TransactionPayload::new_contract_call(
+                    vector_address(1, false),
+                    "vector-contract",
+                    "add",
+                    vec![Value::Int(1), Value::Int(-2)],
+                )
+                .expect("BUG: bad test vector contract call"),
+            ),
+        ),
+    ]
+}
+
+fn tx_expected(tx: &StacksTransaction) -> JSONValue {
+    json!({
+        "txid": format!("{}", tx.txid()),
+        "verified": tx.verify().is_ok(),
+        "payload": tx.payload.name(),
+    })
+}
+
+fn tx_merkle_root(txs: &[StacksTransaction]) -> Sha512Trunc256Sum {
+    let txids = txs.iter().map(|tx| tx.txid().as_bytes().to_vec()).collect();
+    MerkleTree::<Sha512Trunc256Sum>::new(&txids).root()
+}
+
+fn block_expected(block: &StacksBlock) -> JSONValue {
+    let txids: Vec<_> = block
+        .txs
+        .iter()
+        .map(|tx| format!("{}", tx.txid()))
+        .collect();
+    json!({
+        "block_hash": format!("{}", block.block_hash()),
+        "tx_merkle_root": format!("{}", &block.header.tx_merkle_root),
+        "txids": txids,
+    })
+}
+
+fn vector_blocks() -> Vec<(&'static str, StacksBlock)> {
+    let txs: Vec<_> = vector_transactions()
+        .into_iter()
+        .filter(|(_, tx)| tx.version == TransactionVersion::Testnet)
+        .map(|(_, tx)| tx)
+        .collect();
+    let mut header = StacksBlockHeader::genesis_block_header();
+    header.total_work.burn = 1000;
+    header.total_work.work = 1;
+    header.tx_merkle_root = tx_merkle_root(&txs);
+    header.microblock_pubkey_hash = Hash160([0x33; 20]);
+    vec![
+        ("empty", StacksBlock::genesis_block()),
+        ("with-transactions", StacksBlock { header, txs }),
+    ]
+}
+
+/// The keys and values stored in the test vectors' MARF
+fn vector_marf_entries() -> Vec<(&'static str, &'static str)> {
+    vec![
+        ("vector-key-1", "value-1"),
+        ("vector-key-2", "value-2"),
+        ("a-longer-vector-key", "a longer value at that key"),
+    ]
+}
+
+fn vector_marf_proofs() -> Vec<TestVector> {
+    let block = StacksBlockId([0x44; 32]);
+    let mut marf =
+        MARF::<StacksBlockId>::from_path(":memory:").expect("BUG: failed to open in-memory MARF");
+    let (keys, values): (Vec<String>, Vec<MARFValue>) = vector_marf_entries()
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), MARFValue::from_value(value)))
+        .unzip();
+    let mut tx = marf
+        .begin_tx()
+        .expect("BUG: failed to open MARF transaction");
+    tx.begin(&StacksBlockId::sentinel(), &block)
+        .expect("BUG: failed to begin MARF block");
+    tx.insert_batch(&keys, values)
+        .expect("BUG: failed to insert into MARF");
+    tx.commit().expect("BUG: failed to commit MARF block");
+    let root_hash = marf
+        .get_root_hash_at(&block)
+        .expect("BUG: failed to read MARF root hash");
+
+    vector_marf_entries()
+        .iter()
+        .map(|(key, value)| {
+            let (_, proof) = marf
+                .get_with_proof(&block, key)
+                .expect("BUG: failed to read MARF proof")
+                .expect("BUG: MARF key not found");
+            TestVector::new(
+                VECTOR_KIND_MARF_PROOF,
+                key,
+                &proof.0.serialize_to_vec(),
+                json!({
+                    "key": key,
+                    "value": value,
+                    "root_hash": format!("{}", &root_hash),
+                }),
+            )
+        })
+        .collect()
+}
+
+fn vector_clarity_values() -> Vec<(&'static str, Value)> {
+    let tuple = TupleData::from_data(vec![
+        (
+            ClarityName::try_from("amount".to_string()).unwrap(),
+            Value::UInt(100),
+        ),
+        (
+            ClarityName::try_from("memo".to_string()).unwrap(),
+            Value::some(Value::buff_from(vec![0xde, 0xad]).unwrap()).unwrap(),
+        ),
+    ])
+    .expect("BUG: bad test vector tuple");
+    vec![
+        ("int", Value::Int(i128::min_value())),
+        ("uint", Value::UInt(u128::max_value())),
+        ("bool", Value::Bool(true)),
+        ("none", Value::none()),
+        ("some", Value::some(Value::Int(3)).unwrap()),
+        ("ok", Value::okay(Value::UInt(1)).unwrap()),
+        ("err", Value::error(Value::Bool(false)).unwrap()),
+        (
+            "buff",
+            Value::buff_from(vec![0x00, 0x01, 0xfe, 0xff]).unwrap(),
+        ),
+        (
+            "string-ascii",
+            Value::string_ascii_from_bytes(b"hello, world".to_vec()).unwrap(),
+        ),
+        (
+            "string-utf8",
+            Value::string_utf8_from_bytes("héllo \u{1F44B}".as_bytes().to_vec()).unwrap(),
+        ),
+        (
+            "standard-principal",
+            Value::Principal(PrincipalData::from(vector_address(0, true))),
+        ),
+        (
+            "contract-principal",
+            Value::Principal(PrincipalData::Contract(vector_contract())),
+        ),
+        (
+            "list",
+            Value::list_from(vec![Value::Int(1), Value::Int(2), Value::Int(3)]).unwrap(),
+        ),
+        ("tuple", Value::from(tuple)),
+    ]
+}
+
+fn clarity_value_bytes(value: &Value) -> Vec<u8> {
+    let mut bytes = vec![];
+    value
+        .serialize_write(&mut bytes)
+        .expect("BUG: failed to write to a Vec");
+    bytes
+}
+
+/// This node's test vectors, in a fixed order
+pub fn generate_test_vectors() -> Vec<TestVector> {
+    let mut vectors = vec![];
+    for (name, tx) in vector_transactions().iter() {
+        vectors.push(TestVector::new(
+            VECTOR_KIND_TRANSACTION,
+            name,
+            &tx.serialize_to_vec(),
+            tx_expected(tx),
+        ));
+    }
+    for (name, block) in vector_blocks().iter() {
+        vectors.push(TestVector::new(
+            VECTOR_KIND_BLOCK,
+            name,
+            &block.serialize_to_vec(),
+            block_expected(block),
+        ));
+    }
+    vectors.append(&mut vector_marf_proofs());
+    for (name, value) in vector_clarity_values().iter() {
+        vectors.push(TestVector::new(
+            VECTOR_KIND_CLARITY_VALUE,
+            name,
+            &clarity_value_bytes(value),
+            json!({ "value": value.to_json() }),
+        ));
+    }
+    vectors
+}
+
+/// Decode all of `bytes` as a `T`
+fn decode_all<T: StacksMessageCodec>(bytes: &[u8]) -> Result<T, String> {
+    let mut cursor = Cursor::new(bytes);
+    let decoded = T::consensus_deserialize(&mut cursor).map_err(|e| format!("{:?}", &e))?;
+    if cursor.position() as usize != bytes.len() {
+        return Err(format!(
+            "{} trailing bytes",
+            bytes.len() - cursor.position() as usize
+        ));
+    }
+    Ok(decoded)
+}
+
+fn check_roundtrip(bytes: &[u8], reencoded: &[u8]) -> Result<(), String> {
+    if bytes != reencoded {
+        return Err(format!("re-encodes to {}", to_hex(reencoded)));
+    }
+    Ok(())
+}
+
+fn check_expected(got: JSONValue, expected: &JSONValue) -> Result<(), String> {
+    if got != *expected {
+        return Err(format!("decodes to {}", &got));
+    }
+    Ok(())
+}
+
+/// Err, with the reason, unless `vector` passes
+pub fn verify_test_vector(vector: &TestVector) -> Result<(), String> {
+    let bytes = hex_bytes(&vector.hex).map_err(|_| "`hex` is not hex".to_string())?;
+    match vector.kind.as_str() {
+        VECTOR_KIND_TRANSACTION => {
+            let tx: StacksTransaction = decode_all(&bytes)?;
+            check_roundtrip(&bytes, &tx.serialize_to_vec())?;
+            check_expected(tx_expected(&tx), &vector.expected)?;
+        }
+        VECTOR_KIND_BLOCK => {
+            let block: StacksBlock = decode_all(&bytes)?;
+            check_roundtrip(&bytes, &block.serialize_to_vec())?;
+            if !block.txs.is_empty() && tx_merkle_root(&block.txs) != block.header.tx_merkle_root {
+                return Err("transactions do not match the header's merkle root".to_string());
+            }
+            check_expected(block_expected(&block), &vector.expected)?;
+        }
+        VECTOR_KIND_MARF_PROOF => {
+            let proof: Vec<TrieMerkleProofType<StacksBlockId>> = decode_all(&bytes)?;
+            check_roundtrip(&bytes, &proof.serialize_to_vec())?;
+            let field = |name: &str| {
+                vector.expected[name]
+                    .as_str()
+                    .ok_or_else(|| format!("`expected.{}` is not a string", name))
+            };
+            let key = field("key")?;
+            let value = field("value")?;
+            let root_hash = TrieHash::from_hex(field("root_hash")?)
+                .map_err(|_| "`expected.root_hash` is not a hash".to_string())?;
+            if !TrieMerkleProof(proof).verify(
+                &TriePath::from_key(key),
+                &MARFValue::from_value(value),
+                &root_hash,
+                &HashMap::new(),
+            ) {
+                return Err("proof does not verify".to_string());
+            }
+        }
+        VECTOR_KIND_CLARITY_VALUE => {
+            let value =
+                Value::try_deserialize_bytes_untyped(&bytes).map_err(|e| format!("{:?}", &e))?;
+            check_roundtrip(&bytes, &clarity_value_bytes(&value))?;
+            check_expected(json!({ "value": value.to_json() }), &vector.expected)?;
+        }
+        other => return Err(format!("unknown kind '{}'", other)),
+    }
+
+    let ours = generate_test_vectors()
+        .into_iter()
+        .find(|ours| ours.kind == vector.kind && ours.name == vector.name);
+    if let Some(ours) = ours {
+        if ours.hex != vector.hex {
+            return Err(format!("this node encodes it as {}", &ours.hex));
+        }
+    }
+    Ok(())
+}
+
+/// Write each vector to its own file in `dir`, creating `dir` if need be
+pub fn write_test_vectors(dir: &Path, vectors: &[TestVector]) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), &e))?;
+    for vector in vectors.iter() {
+        let path = dir.join(vector.file_name());
+        let contents = serde_json::to_string_pretty(vector)
+            .map_err(|e| format!("Failed to encode {}: {}", &vector.name, &e))?;
+        fs::write(&path, contents + "\n")
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), &e))?;
+    }
+    Ok(())
+}
+
+/// Read every `.json` file in `dir` as a vector, in file name order
+pub fn read_test_vectors(dir: &Path) -> Result<Vec<(String, TestVector)>, String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), &e))?;
+    let mut paths = vec![];
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("Failed to read {}: {}", dir.display(), &e))?
+            .path();
+        if path.extension().map(|ext| ext == "json").unwrap_or(false) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut vectors = vec![];
+    for path in paths.into_iter() {
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), &e))?;
+        let vector = serde_json::from_str(&contents)
+            .map_err(|e| format!("Failed to parse {}: {}", path.display(), &e))?;
+        vectors.push((path.display().to_string(), vector));
+    }
+    Ok(vectors)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_vectors_verify() {
+        let vectors = generate_test_vectors();
+        assert_eq!(vectors, generate_test_vectors());
+        for vector in vectors.iter() {
+            assert_eq!(verify_test_vector(vector), Ok(()), "{}", &vector.name);
+        }
+
+        let dir_name = format!(
+            "/tmp/stacks-test-vectors-{}",
+            rand::thread_rng().gen::<u64>()
+        );
+        let dir = Path::new(&dir_name);
+        write_test_vectors(dir, &vectors).unwrap();
+        let read: Vec<_> = read_test_vectors(dir)
+            .unwrap()
+            .into_iter()
+            .map(|(_, vector)| vector)
+            .collect();
+        assert_eq!(read.len(), vectors.len());
+        for vector in vectors.iter() {
+            assert!(read.contains(vector));
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_vectors_reject_mismatches() {
+        let vectors = generate_test_vectors();
+        let tx = vectors
+            .iter()
+            .find(|v| v.kind == VECTOR_KIND_TRANSACTION)
+            .unwrap();
+
+        let mut trailing = tx.clone();
+        trailing.hex.push_str("00");
+        assert!(verify_test_vector(&trailing).is_err());
+
+        let mut wrong_txid = tx.clone();
+        wrong_txid.expected["txid"] = json!("00".repeat(32));
+        assert!(verify_test_vector(&wrong_txid).is_err());
+
+        let mut unknown = tx.clone();
+        unknown.kind = "widget".to_string();
+        assert!(verify_test_vector(&unknown).is_err());
+
+        let proof = vectors
+            .iter()
+            .find(|v| v.kind == VECTOR_KIND_MARF_PROOF)
+            .unwrap();
+        let mut wrong_value = proof.clone();
+        wrong_value.expected["value"] = json!("not the value");
+        assert_eq!(
+            verify_test_vector(&wrong_value),
+            Err("proof does not verify".to_string())
+        );
+
+        // a value this node doesn't generate is checked on its own terms
+        let mut renamed = vectors
+            .iter()
+            .find(|v| v.kind == VECTOR_KIND_CLARITY_VALUE)
+            .unwrap()
+            .clone();
+        renamed.name = "another-implementations-int".to_string();
+        assert_eq!(verify_test_vector(&renamed), Ok(()));
+    }
+}
@@ -16,6 +16,7 @@
 
 use std::error;
 use std::fmt;
+use std::mem;
 
 use chainstate::stacks::boot::{
     BOOT_CODE_COSTS, BOOT_CODE_COST_VOTING_TESTNET as BOOT_CODE_COST_VOTING, BOOT_CODE_POX_TESTNET,
@@ -29,7 +30,7 @@ use vm::analysis::AnalysisDatabase;
 use vm::analysis::{errors::CheckError, errors::CheckErrors, ContractAnalysis};
 use vm::ast;
 use vm::ast::{errors::ParseError, errors::ParseErrors, ContractAST};
-use vm::contexts::{AssetMap, Environment, OwnedEnvironment};
+use vm::contexts::{AssetMap, Environment, OwnedEnvironment, TraitResolutionCache};
 use vm::costs::{CostTracker, ExecutionCost, LimitedCostTracker};
 use vm::database::{
     BurnStateDB, ClarityDatabase, HeadersDB, RollbackWrapper, RollbackWrapperPersistedLog,
@@ -76,6 +77,7 @@ pub struct ClarityBlockConnection<'a> {
     header_db: &'a dyn HeadersDB,
     burn_state_db: &'a dyn BurnStateDB,
     cost_track: Option<LimitedCostTracker>,
+    trait_resolutions: TraitResolutionCache,
     mainnet: bool,
 }
 
@@ -90,6 +92,7 @@ pub struct ClarityTransactionConnection<'a, 'b> {
     header_db: &'a dyn HeadersDB,
     burn_state_db: &'a dyn BurnStateDB,
     cost_track: &'a mut Option<LimitedCostTracker>,
+    trait_resolutions: &'a mut TraitResolutionCache,
     mainnet: bool,
 }
 
@@ -277,6 +280,7 @@ impl ClarityInstance {
             header_db,
             burn_state_db,
             cost_track,
+            trait_resolutions: TraitResolutionCache::new(),
             mainnet: self.mainnet,
         }
     }
@@ -297,6 +301,7 @@ impl ClarityInstance {
             header_db,
             burn_state_db,
             cost_track,
+            trait_resolutions: TraitResolutionCache::new(),
             mainnet: self.mainnet,
         }
     }
@@ -319,6 +324,7 @@ impl ClarityInstance {
             header_db,
             burn_state_db,
             cost_track,
+            trait_resolutions: TraitResolutionCache::new(),
             mainnet: false,
         };
 
@@ -395,6 +401,7 @@ impl ClarityInstance {
             header_db,
             burn_state_db,
             cost_track,
+            trait_resolutions: TraitResolutionCache::new(),
             mainnet: self.mainnet,
         }
     }
@@ -610,6 +617,7 @@ impl<'a> ClarityBlockConnection<'a> {
     pub fn start_transaction_processing<'b>(&'b mut self) -> ClarityTransactionConnection<'b, 'a> {
         let store = &mut self.datastore;
         let cost_track = &mut self.cost_track;
+        let trait_resolutions = &mut self.trait_resolutions;
         let header_db = &self.header_db;
         let burn_state_db = &self.burn_state_db;
         let mainnet = self.mainnet;
@@ -618,6 +626,7 @@ impl<'a> ClarityBlockConnection<'a> {
         ClarityTransactionConnection {
             store,
             cost_track,
+            trait_resolutions,
             header_db,
             burn_state_db,
             log: Some(log),
@@ -791,7 +800,13 @@ impl<'a, 'b> ClarityTransactionConnection<'a, 'b> {
                 //   so we can abort on call_back's boolean retun
                 db.begin();
                 let mut vm_env = OwnedEnvironment::new_cost_limited(self.mainnet, db, cost_track);
+                vm_env.set_trait_resolutions(mem::replace(
+                    self.trait_resolutions,
+                    TraitResolutionCache::new(),
+                ));
                 let result = to_do(&mut vm_env);
+                let (trait_resolutions, new_trait_resolutions) = vm_env.take_trait_resolutions();
+                *self.trait_resolutions = trait_resolutions;
                 let (mut db, cost_track) = vm_env
                     .destruct()
                     .expect("Failed to recover database reference after executing transaction");
@@ -803,6 +818,8 @@ impl<'a, 'b> ClarityTransactionConnection<'a, 'b> {
                         if aborted {
                             db.roll_back();
                         } else {
+                            // only keep the trait resolutions of transactions that commit
+                            self.trait_resolutions.extend(new_trait_resolutions);
                             db.commit();
                         }
                         Ok((value, asset_map, events, aborted))
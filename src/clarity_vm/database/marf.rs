@@ -89,6 +89,30 @@ impl MarfedKV {
         Ok(MarfedKV { marf, chain_tip })
     }
 
+    /// Open an existing Clarity store read-only.  Unlike `open()`, nothing is created.
+    pub fn open_readonly(
+        path_str: &str,
+        miner_tip: Option<&StacksBlockId>,
+    ) -> InterpreterResult<MarfedKV> {
+        let mut path = PathBuf::from(path_str);
+        path.push("marf.sqlite");
+        let marf_path = path
+            .to_str()
+            .ok_or_else(|| InterpreterError::BadFileName)?
+            .to_string();
+
+        let marf = MARF::from_path_readonly(&marf_path)
+            .map_err(|err| InterpreterError::MarfFailure(IncomparableError { err }))?;
+        SqliteConnection::check_schema(&marf.sqlite_conn())?;
+
+        let chain_tip = match miner_tip {
+            Some(ref miner_tip) => *miner_tip.clone(),
+            None => StacksBlockId::sentinel(),
+        };
+
+        Ok(MarfedKV { marf, chain_tip })
+    }
+
     // used by benchmarks
     pub fn temporary() -> MarfedKV {
         use rand::Rng;
@@ -358,9 +382,9 @@ impl<'a> ClarityBackingStore for ReadOnlyMarfStore<'a> {
     }
 
     fn get_open_chain_tip_height(&mut self) -> u32 {
-        self.marf
-            .get_open_chain_tip_height()
-            .expect("Attempted to get the open chain tip from an unopened context.")
+        // nothing is ever committed through a read-only store, but a contract deployed in a
+        // simulation on top of it would land in the block after the chain tip
+        self.get_current_block_height() + 1
     }
 
     fn get_with_proof(&mut self, key: &str) -> Option<(String, TrieMerkleProof<StacksBlockId>)> {
@@ -49,6 +49,7 @@ use util::db::FromColumn;
 use util::db::{query_row, Error};
 use util::db::{sql_pragma, DBConn, DBTx, FromRow};
 use util::get_epoch_time_secs;
+use util::memory::apply_mempool_cache_size;
 use vm::types::PrincipalData;
 
 use crate::codec::StacksMessageCodec;
@@ -59,9 +60,24 @@ use crate::types::chainstate::{BlockHeaderHash, StacksAddress, StacksBlockHeader
 pub const MEMPOOL_MAX_TRANSACTION_AGE: u64 = 256;
 pub const MAXIMUM_MEMPOOL_TX_CHAINING: u64 = 25;
 
+/// Limits on the smart contracts this node takes into its mempool, on top of what consensus
+/// allows.  They spare the node the work of parsing, analyzing and mining pathological contracts
+/// that peers and clients send it.  A block that deploys a contract past them is processed as
+/// usual.  Each limit is off if it is 0.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ContractDeployLimits {
+    /// Longest contract source, in bytes
+    pub max_len: u64,
+    /// Deepest nesting of lists in the contract
+    pub max_depth: u64,
+    /// Most runtime cost units parsing and analyzing the contract may take
+    pub max_analysis_runtime: u64,
+}
+
 pub struct MemPoolAdmitter {
     cur_block: BlockHeaderHash,
     cur_consensus_hash: ConsensusHash,
+    contract_deploy_limits: ContractDeployLimits,
 }
 
 enum MemPoolWalkResult {
@@ -75,6 +91,7 @@ impl MemPoolAdmitter {
         MemPoolAdmitter {
             cur_block,
             cur_consensus_hash,
+            contract_deploy_limits: ContractDeployLimits::default(),
         }
     }
 
@@ -88,7 +105,13 @@ impl MemPoolAdmitter {
         tx: &StacksTransaction,
         tx_size: u64,
     ) -> Result<(), MemPoolRejection> {
-        chainstate.will_admit_mempool_tx(&self.cur_consensus_hash, &self.cur_block, tx, tx_size)
+        chainstate.will_admit_mempool_tx(&self.cur_consensus_hash, &self.cur_block, tx, tx_size)?;
+        chainstate.check_contract_deploy_limits(
+            &self.cur_consensus_hash,
+            &self.cur_block,
+            tx,
+            &self.contract_deploy_limits,
+        )
     }
 }
 
@@ -364,6 +387,7 @@ impl MemPoolDB {
             DBConn::open_with_flags(&db_path, open_flags).map_err(db_error::SqliteError)?;
         conn.busy_handler(Some(tx_busy_handler))
             .map_err(db_error::SqliteError)?;
+        apply_mempool_cache_size(&conn)?;
 
         if create_flag {
             // instantiate!
@@ -377,6 +401,28 @@ impl MemPoolDB {
         })
     }
 
+    /// Open the existing mempool db within the chainstate directory with a read-only connection,
+    /// e.g. to serve queries from it while another process writes to it.
+    pub fn open_readonly(chainstate_path: &str) -> Result<MemPoolDB, db_error> {
+        let db_path = MemPoolDB::db_path(chainstate_path)?;
+        if fs::metadata(&db_path).is_err() {
+            return Err(db_error::NoDBError);
+        }
+
+        let conn = DBConn::open_with_flags(&db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(db_error::SqliteError)?;
+        conn.busy_handler(Some(tx_busy_handler))
+            .map_err(db_error::SqliteError)?;
+        apply_mempool_cache_size(&conn)?;
+
+        let admitter = MemPoolAdmitter::new(BlockHeaderHash([0u8; 32]), ConsensusHash([0u8; 20]));
+        Ok(MemPoolDB {
+            db: conn,
+            path: db_path,
+            admitter: admitter,
+        })
+    }
+
     ///
     /// Iterate over candidates in the mempool
     ///  todo will be called once for each bundle of transactions at
@@ -572,6 +618,18 @@ impl MemPoolDB {
         Ok(rows)
     }
 
+    /// Get the IDs of up to `count` transactions accepted at or after `timestamp`, newest first
+    pub fn get_txids_accepted_since(
+        conn: &DBConn,
+        timestamp: u64,
+        count: u64,
+    ) -> Result<Vec<Txid>, db_error> {
+        let sql =
+            "SELECT txid FROM mempool WHERE accept_time >= ?1 ORDER BY accept_time DESC LIMIT ?2";
+        let args: &[&dyn ToSql] = &[&u64_to_sql(timestamp)?, &u64_to_sql(count)?];
+        query_rows(conn, sql, args)
+    }
+
     /// Get a transaction's metadata, given address and nonce, and whether the address is used as a sponsor or an origin.
     /// Faster than getting the MemPoolTxInfo, since no deserialization will be needed.
     /// Used to see if there exists a transaction with this info, so as to implement replace-by-fee
@@ -626,6 +684,77 @@ impl MemPoolDB {
         Ok(cmp::max(as_origin, as_sponsor))
     }
 
+    /// Get the number of transactions `sponsor` pays the fees of on other accounts' behalf, with
+    /// sponsor nonces of at least `min_nonce`, and the sum of those fees.  A transaction at
+    /// `except_nonce` is left out, since it is about to be replaced.
+    pub fn get_pending_sponsorships(
+        conn: &DBConn,
+        sponsor: &StacksAddress,
+        min_nonce: u64,
+        except_nonce: Option<u64>,
+    ) -> Result<(u64, u64), db_error> {
+        let except_nonce = match except_nonce {
+            None => -1,
+            Some(n) => u64_to_sql(n)?,
+        };
+        let sql = "SELECT COUNT(*) AS num_txs, ifnull(SUM(tx_fee), 0) AS total_fees FROM mempool WHERE sponsor_address = ?1 \
+        AND origin_address != sponsor_address AND sponsor_nonce >= ?2 AND sponsor_nonce != ?3";
+        let args: &[&dyn ToSql] = &[&sponsor.to_string(), &u64_to_sql(min_nonce)?, &except_nonce];
+        conn.query_row_and_then(sql, args, |row| -> Result<(u64, u64), db_error> {
+            Ok((
+                u64::from_column(row, "num_txs")?,
+                u64::from_column(row, "total_fees")?,
+            ))
+        })
+    }
+
+    /// Get the sum of the fees `payer` pays for pending transactions with sponsor nonces of at
+    /// least `min_nonce`: those it sends and pays for itself, as well as those it sponsors.  A
+    /// transaction at `except_nonce` is left out, since it is about to be replaced.
+    pub fn get_pending_fees(
+        conn: &DBConn,
+        payer: &StacksAddress,
+        min_nonce: u64,
+        except_nonce: Option<u64>,
+    ) -> Result<u64, db_error> {
+        let except_nonce = match except_nonce {
+            None => -1,
+            Some(n) => u64_to_sql(n)?,
+        };
+        let sql = "SELECT ifnull(SUM(tx_fee), 0) FROM mempool WHERE sponsor_address = ?1 \
+        AND sponsor_nonce >= ?2 AND sponsor_nonce != ?3";
+        let args: &[&dyn ToSql] = &[&payer.to_string(), &u64_to_sql(min_nonce)?, &except_nonce];
+        conn.query_row_and_then(sql, args, |row| u64::from_row(row))
+    }
+
+    /// Check that a sponsor can pay for the fees of every transaction it pays for in the
+    /// mempool, its own as well as those it sponsors, and for this one's.  Each is checked
+    /// against the sponsor's balance on its own when it is admitted, so without this a sponsor
+    /// could have more pending than it can pay for, and the ones after it runs out would never
+    /// be mined.
+    fn check_sponsor_funds(
+        conn: &DBConn,
+        chainstate: &mut StacksChainState,
+        consensus_hash: &ConsensusHash,
+        block_hash: &BlockHeaderHash,
+        sponsor_address: &StacksAddress,
+        sponsor_nonce: u64,
+        tx_fee: u64,
+    ) -> Result<(), MemPoolRejection> {
+        let (account_nonce, available) = chainstate.get_mempool_account_funds(
+            consensus_hash,
+            block_hash,
+            &sponsor_address.to_account_principal(),
+        )?;
+        let reserved =
+            MemPoolDB::get_pending_fees(conn, sponsor_address, account_nonce, Some(sponsor_nonce))?;
+        let needed = (reserved as u128) + (tx_fee as u128);
+        if needed > available {
+            return Err(MemPoolRejection::NotEnoughFunds(needed, available));
+        }
+        Ok(())
+    }
+
     fn are_blocks_in_same_fork(
         chainstate: &mut StacksChainState,
         first_consensus_hash: &ConsensusHash,
@@ -890,6 +1019,17 @@ impl MemPoolDB {
                 .admitter
                 .set_block(&block_hash, (*consensus_hash).clone());
             mempool_tx.admitter.will_admit_tx(chainstate, tx, len)?;
+            if tx.sponsor_address().is_some() {
+                MemPoolDB::check_sponsor_funds(
+                    mempool_tx,
+                    chainstate,
+                    consensus_hash,
+                    block_hash,
+                    &sponsor_address,
+                    sponsor_nonce,
+                    tx_fee,
+                )?;
+            }
         }
 
         MemPoolDB::try_add_tx(
@@ -915,6 +1055,11 @@ impl MemPoolDB {
         Ok(())
     }
 
+    /// Set the limits on the smart contracts submitted to this mempool
+    pub fn set_contract_deploy_limits(&mut self, limits: ContractDeployLimits) {
+        self.admitter.contract_deploy_limits = limits;
+    }
+
     /// One-shot submit
     pub fn submit(
         &mut self,
@@ -1001,6 +1146,8 @@ impl MemPoolDB {
 
 #[cfg(test)]
 mod tests {
+    use std::convert::TryFrom;
+
     use address::AddressHashMode;
     use burnchains::Address;
     use chainstate::burn::ConsensusHash;
@@ -1046,7 +1193,7 @@ mod tests {
         chainstate::stacks::db::StacksHeaderInfo, util::vrf::VRFProof, vm::costs::ExecutionCost,
     };
 
-    use super::MemPoolDB;
+    use super::{ContractDeployLimits, MemPoolDB};
 
     const FOO_CONTRACT: &'static str = "(define-public (foo) (ok 1))
                                         (define-public (bar (x uint)) (ok x))";
@@ -1908,4 +2055,229 @@ mod tests {
         assert_eq!(tx_info.metadata.len, second_len);
         assert_eq!(tx_info.metadata.tx_fee, 124);
     }
+
+    #[test]
+    fn mempool_pending_sponsorships() {
+        let mut chainstate = instantiate_chainstate_with_balances(
+            false,
+            0x80000000,
+            "mempool_pending_sponsorships",
+            vec![],
+        );
+        let b_1 = make_block(
+            &mut chainstate,
+            ConsensusHash([0x1; 20]),
+            &(
+                FIRST_BURNCHAIN_CONSENSUS_HASH.clone(),
+                FIRST_STACKS_BLOCK_HASH.clone(),
+            ),
+            1,
+            1,
+        );
+
+        let chainstate_path = chainstate_path("mempool_pending_sponsorships");
+        let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+
+        let mut txs = codec_all_transactions(
+            &TransactionVersion::Testnet,
+            0x80000000,
+            &TransactionAnchorMode::Any,
+            &TransactionPostConditionMode::Allow,
+        );
+
+        let sponsor_address = StacksAddress {
+            version: 26,
+            bytes: Hash160([0x1; 20]),
+        };
+        let mut mempool_tx = mempool.tx_begin().unwrap();
+        for ix in 0..4 {
+            let mut tx = txs.pop().unwrap();
+            tx.set_tx_fee(100 * (ix + 1));
+            let origin_address = StacksAddress {
+                version: 26,
+                bytes: Hash160([0x10 + ix as u8; 20]),
+            };
+            // the last one pays for itself
+            let (tx_sponsor_address, tx_sponsor_nonce) = if ix < 3 {
+                (sponsor_address.clone(), ix)
+            } else {
+                (origin_address.clone(), 0)
+            };
+            MemPoolDB::try_add_tx(
+                &mut mempool_tx,
+                &mut chainstate,
+                &b_1.0,
+                &b_1.1,
+                tx.txid(),
+                tx.serialize_to_vec(),
+                tx.get_tx_fee(),
+                1,
+                &origin_address,
+                0,
+                &tx_sponsor_address,
+                tx_sponsor_nonce,
+                None,
+            )
+            .unwrap();
+        }
+        mempool_tx.commit().unwrap();
+
+        // fees of 100, 200 and 300 at sponsor nonces 0, 1 and 2
+        assert_eq!(
+            MemPoolDB::get_pending_sponsorships(&mempool.db, &sponsor_address, 0, None).unwrap(),
+            (3, 600)
+        );
+        assert_eq!(
+            MemPoolDB::get_pending_sponsorships(&mempool.db, &sponsor_address, 1, None).unwrap(),
+            (2, 500)
+        );
+        assert_eq!(
+            MemPoolDB::get_pending_sponsorships(&mempool.db, &sponsor_address, 0, Some(2)).unwrap(),
+            (2, 300)
+        );
+
+        // paying for your own transaction is not sponsoring it
+        let self_payer = StacksAddress {
+            version: 26,
+            bytes: Hash160([0x13; 20]),
+        };
+        assert_eq!(
+            MemPoolDB::get_pending_sponsorships(&mempool.db, &self_payer, 0, None).unwrap(),
+            (0, 0)
+        );
+        assert_eq!(
+            MemPoolDB::get_next_nonce_for_address(&mempool.db, &sponsor_address).unwrap(),
+            3
+        );
+
+        // but its fees are still reserved, along with any it sponsors
+        assert_eq!(
+            MemPoolDB::get_pending_fees(&mempool.db, &self_payer, 0, None).unwrap(),
+            400
+        );
+        assert_eq!(
+            MemPoolDB::get_pending_fees(&mempool.db, &sponsor_address, 1, Some(2)).unwrap(),
+            200
+        );
+    }
+
+    #[test]
+    fn mempool_contract_deploy_limits() {
+        let mut chainstate = instantiate_chainstate_with_balances(
+            false,
+            0x80000000,
+            "mempool_contract_deploy_limits",
+            vec![],
+        );
+        let b_1 = make_block(
+            &mut chainstate,
+            ConsensusHash([0x1; 20]),
+            &(
+                FIRST_BURNCHAIN_CONSENSUS_HASH.clone(),
+                FIRST_STACKS_BLOCK_HASH.clone(),
+            ),
+            1,
+            1,
+        );
+
+        let privk = StacksPrivateKey::from_hex(SK_1).unwrap();
+        let deploy = |code: &str| {
+            StacksTransaction::new(
+                TransactionVersion::Testnet,
+                TransactionAuth::from_p2pkh(&privk).unwrap(),
+                TransactionPayload::SmartContract(TransactionSmartContract {
+                    name: ContractName::try_from("limited").unwrap(),
+                    code_body: StacksString::from_str(code).unwrap(),
+                }),
+            )
+        };
+        let check = |chainstate: &mut StacksChainState,
+                     tx: &StacksTransaction,
+                     limits: &ContractDeployLimits| {
+            chainstate.check_contract_deploy_limits(&b_1.0, &b_1.1, tx, limits)
+        };
+        fn exceeded(res: Result<(), MemPoolRejection>) -> (&'static str, u64, u64) {
+            match res {
+                Err(MemPoolRejection::ContractDeployLimitExceeded(limit, actual, max)) => {
+                    (limit, actual, max)
+                }
+                x => panic!(
+                    "Expected a contract deploy limit to be exceeded, got {:?}",
+                    &x
+                ),
+            }
+        }
+
+        // lists nest 5 deep: (define-public (ok (+ (+ (+ ...)))))
+        let code = "(define-public (foo) (ok (+ 1 (+ 2 (+ 3 4)))))";
+        let tx = deploy(code);
+        let code_len = code.len() as u64;
+
+        // no limits by default
+        check(&mut chainstate, &tx, &ContractDeployLimits::default()).unwrap();
+
+        let limits = ContractDeployLimits {
+            max_len: code_len,
+            max_depth: 5,
+            max_analysis_runtime: 1_000_000_000,
+        };
+        check(&mut chainstate, &tx, &limits).unwrap();
+
+        let res = check(
+            &mut chainstate,
+            &tx,
+            &ContractDeployLimits {
+                max_len: code_len - 1,
+                ..limits.clone()
+            },
+        );
+        assert_eq!(exceeded(res), ("size", code_len, code_len - 1));
+
+        let res = check(
+            &mut chainstate,
+            &tx,
+            &ContractDeployLimits {
+                max_depth: 4,
+                ..limits.clone()
+            },
+        );
+        assert_eq!(exceeded(res), ("depth", 5, 4));
+
+        let res = check(
+            &mut chainstate,
+            &tx,
+            &ContractDeployLimits {
+                max_analysis_runtime: 1,
+                ..limits.clone()
+            },
+        );
+        let (limit, actual, max) = exceeded(res);
+        assert_eq!(limit, "analysis_cost");
+        assert!(actual > 1);
+        assert_eq!(max, 1);
+
+        // a contract that doesn't analyze is left for mining to deal with
+        let tx = deploy("(define-public (foo) (ok (no-such-function)))");
+        check(&mut chainstate, &tx, &limits).unwrap();
+
+        // only contracts are limited
+        let strict = ContractDeployLimits {
+            max_len: 1,
+            max_depth: 1,
+            max_analysis_runtime: 1,
+        };
+        let tx = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&privk).unwrap(),
+            TransactionPayload::TokenTransfer(
+                PrincipalData::from(StacksAddress {
+                    version: 26,
+                    bytes: Hash160([0x11; 20]),
+                }),
+                123,
+                TokenTransferMemo([0u8; 34]),
+            ),
+        );
+        check(&mut chainstate, &tx, &strict).unwrap();
+    }
 }
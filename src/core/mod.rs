@@ -27,6 +27,7 @@ use crate::types::chainstate::{BlockHeaderHash, BurnchainHeaderHash};
 pub use self::mempool::MemPoolDB;
 
 pub mod mempool;
+pub mod schedule;
 
 // fork set identifier -- to be mixed with the consensus hash (encodes the version)
 pub const SYSTEM_FORK_SET_VERSION: [u8; 4] = [23u8, 0u8, 0u8, 0u8];
@@ -0,0 +1,183 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The consensus changes scheduled at fixed burnchain block heights.  Stacks 2.0 has no epoch
+//! table yet, so the schedule is assembled from the heights the consensus rules already key off
+//! of: the first burnchain block, the start and end of the PoX sunset, and (on mainnet) the last
+//! burnchain block a 2.0 node will process.  Past that last block, a 2.0 node stops following
+//! the chain, so that change is reported as unsupported -- operators need to upgrade before it
+//! activates.  Changes scheduled after it are reported as unsupported too, since a 2.0 node never
+//! gets that far.
+
+use burnchains::Burnchain;
+use core::STACKS_2_0_LAST_BLOCK_TO_PROCESS;
+
+/// How far ahead of an unsupported change to start warning that an upgrade is needed (about
+/// two weeks of burnchain blocks)
+pub const UPGRADE_WARNING_BLOCKS: u64 = 2016;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledConsensusChange {
+    pub name: String,
+    /// First burnchain block height at which the change is in effect
+    pub activation_height: u64,
+    /// Whether this binary implements the change.  A node that reaches an unsupported change
+    /// stops following the chain there.
+    pub supported: bool,
+}
+
+impl ScheduledConsensusChange {
+    fn new(name: &str, activation_height: u64, supported: bool) -> ScheduledConsensusChange {
+        ScheduledConsensusChange {
+            name: name.to_string(),
+            activation_height,
+            supported,
+        }
+    }
+}
+
+/// The consensus changes scheduled on `burnchain`, in activation order
+pub fn consensus_schedule(burnchain: &Burnchain) -> Vec<ScheduledConsensusChange> {
+    let mut schedule = vec![
+        ScheduledConsensusChange::new("stacks-2.0", burnchain.first_block_height, true),
+        ScheduledConsensusChange::new(
+            "pox-sunset-start",
+            burnchain.pox_constants.sunset_start,
+            true,
+        ),
+        ScheduledConsensusChange::new("pox-sunset-end", burnchain.pox_constants.sunset_end, true),
+    ];
+    if burnchain.is_mainnet() {
+        schedule.push(ScheduledConsensusChange::new(
+            "stacks-2.05",
+            STACKS_2_0_LAST_BLOCK_TO_PROCESS + 1,
+            false,
+        ));
+    }
+    schedule.sort_by_key(|change| change.activation_height);
+
+    let mut supported = true;
+    for change in schedule.iter_mut() {
+        supported = supported && change.supported;
+        change.supported = supported;
+    }
+    schedule
+}
+
+/// The most recent change in `schedule` to have activated at `burn_block_height`, if any
+pub fn active_change(
+    schedule: &[ScheduledConsensusChange],
+    burn_block_height: u64,
+) -> Option<&ScheduledConsensusChange> {
+    schedule
+        .iter()
+        .filter(|change| change.activation_height <= burn_block_height)
+        .last()
+}
+
+/// The next change in `schedule` to activate after `burn_block_height`, if any
+pub fn next_change(
+    schedule: &[ScheduledConsensusChange],
+    burn_block_height: u64,
+) -> Option<&ScheduledConsensusChange> {
+    schedule
+        .iter()
+        .find(|change| change.activation_height > burn_block_height)
+}
+
+/// The next unsupported change, if it activates within `UPGRADE_WARNING_BLOCKS` of
+/// `burn_block_height`
+pub fn upcoming_unsupported_change(
+    schedule: &[ScheduledConsensusChange],
+    burn_block_height: u64,
+) -> Option<&ScheduledConsensusChange> {
+    schedule.iter().find(|change| {
+        !change.supported
+            && change.activation_height > burn_block_height
+            && change.activation_height - burn_block_height <= UPGRADE_WARNING_BLOCKS
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use burnchains::PoxConstants;
+
+    #[test]
+    fn schedule_order() {
+        let mut burnchain = Burnchain::regtest("/tmp/stacks-schedule-test");
+        burnchain.first_block_height = 100;
+        burnchain.pox_constants = PoxConstants::new(10, 5, 3, 25, 5, 500, 1000);
+
+        let schedule = consensus_schedule(&burnchain);
+        let names: Vec<_> = schedule.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["stacks-2.0", "pox-sunset-start", "pox-sunset-end"]
+        );
+        assert!(schedule.iter().all(|c| c.supported));
+
+        assert_eq!(active_change(&schedule, 99), None);
+        assert_eq!(next_change(&schedule, 99).unwrap().name, "stacks-2.0");
+        assert_eq!(
+            active_change(&schedule, 500).unwrap().name,
+            "pox-sunset-start"
+        );
+        assert_eq!(next_change(&schedule, 500).unwrap().activation_height, 1000);
+        assert_eq!(
+            active_change(&schedule, 5000).unwrap().name,
+            "pox-sunset-end"
+        );
+        assert_eq!(next_change(&schedule, 5000), None);
+    }
+
+    #[test]
+    fn mainnet_schedule() {
+        let burnchain = Burnchain::new("/tmp/stacks-schedule-test", "bitcoin", "mainnet").unwrap();
+        let schedule = consensus_schedule(&burnchain);
+        let names: Vec<_> = schedule.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "stacks-2.0",
+                "stacks-2.05",
+                "pox-sunset-start",
+                "pox-sunset-end"
+            ]
+        );
+        assert_eq!(
+            schedule[1].activation_height,
+            STACKS_2_0_LAST_BLOCK_TO_PROCESS + 1
+        );
+
+        // a 2.0 node never reaches the sunset, so it doesn't support it either
+        let supported: Vec<_> = schedule.iter().map(|c| c.supported).collect();
+        assert_eq!(supported, vec![true, false, false, false]);
+
+        let upgrade_height = STACKS_2_0_LAST_BLOCK_TO_PROCESS + 1;
+        assert_eq!(
+            upcoming_unsupported_change(&schedule, upgrade_height - UPGRADE_WARNING_BLOCKS - 1),
+            None
+        );
+        assert_eq!(
+            upcoming_unsupported_change(&schedule, upgrade_height - UPGRADE_WARNING_BLOCKS)
+                .unwrap()
+                .name,
+            "stacks-2.05"
+        );
+    }
+}
@@ -40,21 +40,26 @@ use rusqlite::OpenFlags;
 use blockstack_lib::burnchains::bitcoin::spv;
 use blockstack_lib::burnchains::bitcoin::BitcoinNetworkType;
 use blockstack_lib::chainstate::burn::ConsensusHash;
+use blockstack_lib::chainstate::coordinator::get_reward_cycle_payouts;
 use blockstack_lib::chainstate::stacks::db::ChainStateBootData;
+use blockstack_lib::chainstate::stacks::events::TransactionOrigin;
 use blockstack_lib::chainstate::stacks::index::marf::MarfConnection;
 use blockstack_lib::chainstate::stacks::index::marf::MARF;
+use blockstack_lib::chainstate::stacks::vectors;
 use blockstack_lib::chainstate::stacks::*;
 use blockstack_lib::codec::StacksMessageCodec;
 use blockstack_lib::types::chainstate::{BlockHeaderHash, BurnchainHeaderHash, PoxId};
-use blockstack_lib::types::chainstate::{StacksBlockHeader, StacksBlockId};
+use blockstack_lib::types::chainstate::{
+    MARFValue, StacksBlockHeader, StacksBlockId, StacksMicroblockHeader,
+};
 use blockstack_lib::types::proof::ClarityMarfTrieId;
 use blockstack_lib::util::get_epoch_time_ms;
-use blockstack_lib::util::hash::{hex_bytes, to_hex};
+use blockstack_lib::util::hash::{hex_bytes, to_hex, MerkleTree, Sha512Trunc256Sum};
 use blockstack_lib::util::log;
 use blockstack_lib::util::retry::LogReader;
 use blockstack_lib::*;
 use blockstack_lib::{
-    burnchains::{db::BurnchainBlockData, PoxConstants},
+    burnchains::{db::BurnchainBlockData, Burnchain, PoxConstants},
     chainstate::{
         burn::db::sortdb::SortitionDB,
         stacks::db::{StacksChainState, StacksHeaderInfo},
@@ -62,9 +67,13 @@ use blockstack_lib::{
     core::MemPoolDB,
     util::{hash::Hash160, vrf::VRFProof},
     vm::costs::ExecutionCost,
+    vm::database::SqliteConnection,
+    vm::types::Value,
 };
 use blockstack_lib::{
-    net::{db::LocalPeer, p2p::PeerNetwork, PeerAddress},
+    net::{
+        db::LocalPeer, p2p::PeerNetwork, PeerAddress, RPCConsensusScheduleData, RPCRewardSetData,
+    },
     vm::representations::UrlString,
 };
 
@@ -202,6 +211,334 @@ fn main() {
         process::exit(0);
     }
 
+    if argv[1] == "test-vectors" {
+        if argv.len() < 4 || (argv[2] != "generate" && argv[2] != "verify") {
+            eprintln!(
+                "Usage: {} test-vectors generate|verify <dir>
+
+generate  write this node's serialization test vectors (transactions, blocks, MARF proofs and
+          Clarity values) to <dir>, one JSON file each
+verify    check every .json vector in <dir>: its bytes must decode, re-encode to the same
+          bytes, and decode to what the vector expects
+",
+                argv[0]
+            );
+            process::exit(1);
+        }
+        let dir = std::path::Path::new(&argv[3]);
+
+        if argv[2] == "generate" {
+            let test_vectors = vectors::generate_test_vectors();
+            if let Err(e) = vectors::write_test_vectors(dir, &test_vectors) {
+                eprintln!("{}", &e);
+                process::exit(1);
+            }
+            println!(
+                "Wrote {} test vectors to {}",
+                test_vectors.len(),
+                dir.display()
+            );
+            process::exit(0);
+        }
+
+        let test_vectors = vectors::read_test_vectors(dir).unwrap_or_else(|e| {
+            eprintln!("{}", &e);
+            process::exit(1);
+        });
+        let mut failures = 0;
+        for (path, vector) in test_vectors.iter() {
+            match vectors::verify_test_vector(vector) {
+                Ok(()) => println!("ok    {}", path),
+                Err(e) => {
+                    println!("FAIL  {}: {}", path, &e);
+                    failures += 1;
+                }
+            }
+        }
+        println!(
+            "{} of {} vectors passed",
+            test_vectors.len() - failures,
+            test_vectors.len()
+        );
+        process::exit(if failures == 0 { 0 } else { 1 });
+    }
+
+    if argv[1] == "inspect-block" {
+        let verify = argv.iter().any(|arg| arg == "--verify");
+        let args: Vec<&String> = argv[2..].iter().filter(|arg| *arg != "--verify").collect();
+        if args.len() < 1 || (args[0] == "--chainstate" && args.len() < 3) {
+            eprintln!(
+                "Usage: {} inspect-block [--verify] <block-path>
+       {} inspect-block [--verify] --chainstate <working-dir> <index-block-hash>
+
+Print an anchored block's header, its merkle roots, and its list of transactions.  The block is
+either read from a file of consensus-serialized bytes, or loaded from the mainnet chainstate in
+<working-dir>.  With --verify, also re-compute the transaction merkle root, re-check each
+transaction's signatures, and run the static transaction checks the node performs on receipt.
+A block read from a file is decoded leniently, so that a bad merkle root is reported instead of
+rejected outright.  Exits with code 2 if verification fails.
+",
+                argv[0], argv[0]
+            );
+            process::exit(1);
+        }
+
+        let block = if args[0] == "--chainstate" {
+            let chain_state_path = format!("{}/mainnet/chainstate/", &args[1]);
+            let index_block_hash =
+                StacksBlockId::from_hex(&args[2]).expect("Failed to parse <index-block-hash>");
+            let (chain_state, _) =
+                StacksChainState::open(true, core::CHAIN_ID_MAINNET, &chain_state_path)
+                    .expect("Failed to open stacks chain state");
+
+            let (consensus_hash, block_hash) = chain_state
+                .get_block_header_hashes(&index_block_hash)
+                .expect("Failed to query block header hashes")
+                .unwrap_or_else(|| {
+                    eprintln!("No such block {}", &index_block_hash);
+                    process::exit(1);
+                });
+            if let Some(header_info) = StacksChainState::get_anchored_block_header_info(
+                chain_state.db(),
+                &consensus_hash,
+                &block_hash,
+            )
+            .expect("Failed to load block header info")
+            {
+                println!("Height: {}", header_info.block_height);
+                println!("Burnchain block height: {}", header_info.burn_header_height);
+            } else {
+                println!("Height: (not processed)");
+            }
+            println!("Consensus hash: {}", &consensus_hash);
+            println!("Index block hash: {}", &index_block_hash);
+
+            StacksChainState::load_block(&chain_state.blocks_path, &consensus_hash, &block_hash)
+                .expect("Failed to load block")
+                .unwrap_or_else(|| {
+                    eprintln!("Block {} is not stored, or is invalid", &index_block_hash);
+                    process::exit(1);
+                })
+        } else {
+            let block_data = fs::read(args[0]).expect(&format!("Failed to open {}", args[0]));
+            let mut cursor = io::Cursor::new(&block_data);
+            let header = StacksBlockHeader::consensus_deserialize(&mut cursor)
+                .map_err(|e| {
+                    eprintln!("Failed to decode block header: {:?}", &e);
+                    process::exit(1);
+                })
+                .unwrap();
+            let txs: Vec<StacksTransaction> = Vec::consensus_deserialize(&mut cursor)
+                .map_err(|e| {
+                    eprintln!("Failed to decode block transactions: {:?}", &e);
+                    process::exit(1);
+                })
+                .unwrap();
+            StacksBlock { header, txs }
+        };
+
+        let header = &block.header;
+        println!("Block hash: {}", &block.block_hash());
+        println!("Version: {}", header.version);
+        println!(
+            "Total work: burn={}, work={}",
+            header.total_work.burn, header.total_work.work
+        );
+        println!("VRF proof: {}", header.proof.to_hex());
+        println!("Parent block: {}", &header.parent_block);
+        println!(
+            "Parent microblock: {} (sequence {})",
+            &header.parent_microblock, header.parent_microblock_sequence
+        );
+        println!("Tx merkle root: {}", &header.tx_merkle_root);
+        println!("State index root: {}", &header.state_index_root);
+        println!(
+            "Microblock public key hash: {}",
+            &header.microblock_pubkey_hash
+        );
+
+        let mut num_problems = print_block_transactions(&block.txs, verify);
+        if verify {
+            num_problems += verify_tx_merkle_root(&block.txs, &header.tx_merkle_root);
+            if let Some(first_tx) = block.txs.first() {
+                if !block.validate_transactions_static(
+                    first_tx.version == TransactionVersion::Mainnet,
+                    first_tx.chain_id,
+                ) {
+                    println!("Static transaction checks: FAILED (see log output)");
+                    num_problems += 1;
+                } else {
+                    println!("Static transaction checks: OK");
+                }
+            }
+        }
+
+        process::exit(if num_problems > 0 { 2 } else { 0 });
+    }
+
+    if argv[1] == "inspect-microblocks" {
+        let verify = argv.iter().any(|arg| arg == "--verify");
+        let args: Vec<&String> = argv[2..].iter().filter(|arg| *arg != "--verify").collect();
+        if args.len() < 1 || (args[0] == "--chainstate" && args.len() < 3) {
+            eprintln!(
+                "Usage: {} inspect-microblocks [--verify] <microblock-stream-path>
+       {} inspect-microblocks [--verify] --chainstate <working-dir> <parent-index-block-hash>
+
+Print each microblock header, merkle root, and list of transactions in a microblock stream.  The
+stream is either read from a file of consensus-serialized bytes, or is the stream stored in the
+mainnet chainstate in <working-dir> that builds on the anchored block <parent-index-block-hash>.
+With --verify, also re-compute each transaction merkle root, check that the microblocks form a
+chain and are all signed by the same key (the parent block's microblock key, if loaded from the
+chainstate), and re-check each transaction's signatures.  Exits with code 2 if verification fails.
+",
+                argv[0], argv[0]
+            );
+            process::exit(1);
+        }
+
+        let (mblocks, parent_header) = if args[0] == "--chainstate" {
+            let chain_state_path = format!("{}/mainnet/chainstate/", &args[1]);
+            let parent_index_block_hash = StacksBlockId::from_hex(&args[2])
+                .expect("Failed to parse <parent-index-block-hash>");
+            let (chain_state, _) =
+                StacksChainState::open(true, core::CHAIN_ID_MAINNET, &chain_state_path)
+                    .expect("Failed to open stacks chain state");
+
+            let (consensus_hash, block_hash) = chain_state
+                .get_block_header_hashes(&parent_index_block_hash)
+                .expect("Failed to query block header hashes")
+                .unwrap_or_else(|| {
+                    eprintln!("No such block {}", &parent_index_block_hash);
+                    process::exit(1);
+                });
+            let parent_header = StacksChainState::load_block_header(
+                &chain_state.blocks_path,
+                &consensus_hash,
+                &block_hash,
+            )
+            .expect("Failed to load parent block header");
+
+            let mblocks = StacksChainState::load_descendant_staging_microblock_stream(
+                chain_state.db(),
+                &parent_index_block_hash,
+                0,
+                u16::MAX,
+            )
+            .expect("Failed to load microblock stream")
+            .unwrap_or_else(|| {
+                eprintln!("No microblocks build on {}", &parent_index_block_hash);
+                process::exit(1);
+            });
+            (mblocks, parent_header)
+        } else {
+            let mblock_data = fs::read(args[0]).expect(&format!("Failed to open {}", args[0]));
+            let mut cursor = io::Cursor::new(&mblock_data);
+            let num_mblocks = u32::consensus_deserialize(&mut cursor)
+                .map_err(|e| {
+                    eprintln!("Failed to decode microblock stream length: {:?}", &e);
+                    process::exit(1);
+                })
+                .unwrap();
+            let mut mblocks = vec![];
+            for i in 0..num_mblocks {
+                let header = StacksMicroblockHeader::consensus_deserialize(&mut cursor)
+                    .map_err(|e| {
+                        eprintln!("Failed to decode header of microblock {}: {:?}", i, &e);
+                        process::exit(1);
+                    })
+                    .unwrap();
+                let txs: Vec<StacksTransaction> = Vec::consensus_deserialize(&mut cursor)
+                    .map_err(|e| {
+                        eprintln!(
+                            "Failed to decode transactions of microblock {}: {:?}",
+                            i, &e
+                        );
+                        process::exit(1);
+                    })
+                    .unwrap();
+                mblocks.push(StacksMicroblock { header, txs });
+            }
+            (mblocks, None)
+        };
+
+        let mut num_problems = 0;
+        let mut signer = parent_header
+            .as_ref()
+            .map(|header| header.microblock_pubkey_hash.clone());
+        for (i, mblock) in mblocks.iter().enumerate() {
+            let header = &mblock.header;
+            println!("Microblock hash: {}", &mblock.block_hash());
+            println!("Version: {}", header.version);
+            println!("Sequence: {}", header.sequence);
+            println!("Previous block: {}", &header.prev_block);
+            println!("Tx merkle root: {}", &header.tx_merkle_root);
+            println!("Signature: {}", to_hex(&header.signature.0));
+
+            num_problems += print_block_transactions(&mblock.txs, verify);
+            if !verify {
+                println!("");
+                continue;
+            }
+
+            num_problems += verify_tx_merkle_root(&mblock.txs, &header.tx_merkle_root);
+
+            let expected_prev_block = if i == 0 {
+                parent_header.as_ref().map(|header| header.block_hash())
+            } else {
+                Some(mblocks[i - 1].block_hash())
+            };
+            if let Some(expected_prev_block) = expected_prev_block {
+                if expected_prev_block != header.prev_block {
+                    println!(
+                        "Previous block: MISMATCH (expected {})",
+                        &expected_prev_block
+                    );
+                    num_problems += 1;
+                }
+            }
+            if i > 0 && header.sequence != mblocks[i - 1].header.sequence.wrapping_add(1) {
+                println!("Sequence: MISMATCH (not consecutive)");
+                num_problems += 1;
+            }
+
+            match header.check_recover_pubkey() {
+                Ok(pubkey_hash) => match signer {
+                    Some(ref signer) if signer != &pubkey_hash => {
+                        println!(
+                            "Signature: MISMATCH (signed by {}, expected {})",
+                            &pubkey_hash, signer
+                        );
+                        num_problems += 1;
+                    }
+                    Some(_) => println!("Signature: OK (signed by {})", &pubkey_hash),
+                    None => {
+                        println!("Signature: OK (signed by {})", &pubkey_hash);
+                        signer = Some(pubkey_hash);
+                    }
+                },
+                Err(e) => {
+                    println!("Signature: FAILED: {:?}", &e);
+                    num_problems += 1;
+                }
+            }
+
+            if let Some(first_tx) = mblock.txs.first() {
+                if !mblock.validate_transactions_static(
+                    first_tx.version == TransactionVersion::Mainnet,
+                    first_tx.chain_id,
+                ) {
+                    println!("Static transaction checks: FAILED (see log output)");
+                    num_problems += 1;
+                } else {
+                    println!("Static transaction checks: OK");
+                }
+            }
+            println!("");
+        }
+
+        process::exit(if num_problems > 0 { 2 } else { 0 });
+    }
+
     if argv[1] == "get-block-inventory" {
         if argv.len() < 3 {
             eprintln!(
@@ -416,6 +753,740 @@ check if the associated microblocks can be downloaded
         process::exit(0);
     }
 
+    if argv[1] == "get-consensus-schedule" {
+        if argv.len() < 3 {
+            eprintln!(
+                "Usage: {} get-consensus-schedule <working-dir>
+
+Given a mainnet <working-dir>, print the consensus changes scheduled by burnchain block height,
+which one is active at the node's burnchain tip, and how many blocks remain until the next one.
+Changes this binary does not support are marked; the node must be upgraded before they activate.
+",
+                argv[0]
+            );
+            process::exit(1);
+        }
+
+        let sort_db_path = format!("{}/mainnet/burnchain/sortition", &argv[2]);
+        let burnchain_path = format!("{}/mainnet/burnchain", &argv[2]);
+
+        let sort_db = SortitionDB::open(&sort_db_path, false)
+            .expect(&format!("Failed to open {}", &sort_db_path));
+        let burnchain = Burnchain::new(&burnchain_path, "bitcoin", "mainnet")
+            .expect("Failed to instantiate burnchain");
+
+        let data = RPCConsensusScheduleData::from_db(&sort_db, &burnchain)
+            .expect("Failed to load burnchain tip");
+
+        println!("Burnchain tip: {}", data.burn_block_height);
+        println!("Name, Activation height, Supported, Status");
+        for change in data.schedule.iter() {
+            let status = if change.activation_height <= data.burn_block_height {
+                "activated".to_string()
+            } else {
+                format!(
+                    "in {} blocks",
+                    change.activation_height - data.burn_block_height
+                )
+            };
+            println!(
+                "{}, {}, {}, {}",
+                &change.name,
+                change.activation_height,
+                if change.supported { "yes" } else { "no" },
+                status
+            );
+        }
+        if data.upgrade_required {
+            println!("This binary must be upgraded before its unsupported changes activate");
+        }
+
+        process::exit(0);
+    }
+
+    if argv[1] == "get-reward-set" {
+        if argv.len() < 4 {
+            eprintln!(
+                "Usage: {} get-reward-set <working-dir> <reward-cycle>
+
+Given a mainnet <working-dir>, print the reward set of <reward-cycle>: each PoX address,
+the number of reward slots it was allotted, and the uSTX stacked to it.
+",
+                argv[0]
+            );
+            process::exit(1);
+        }
+        let reward_cycle: u64 = argv[3]
+            .parse()
+            .expect("Failed to parse <reward-cycle> argument");
+
+        let sort_db_path = format!("{}/mainnet/burnchain/sortition", &argv[2]);
+        let chain_state_path = format!("{}/mainnet/chainstate/", &argv[2]);
+        let burnchain_path = format!("{}/mainnet/burnchain", &argv[2]);
+
+        let sort_db = SortitionDB::open(&sort_db_path, false)
+            .expect(&format!("Failed to open {}", &sort_db_path));
+        let (mut chain_state, _) =
+            StacksChainState::open(true, core::CHAIN_ID_MAINNET, &chain_state_path)
+                .expect("Failed to open stacks chain state");
+        let burnchain = Burnchain::new(&burnchain_path, "bitcoin", "mainnet")
+            .expect("Failed to instantiate burnchain");
+        if burnchain
+            .checked_reward_cycle_to_block_height(reward_cycle)
+            .is_none()
+        {
+            eprintln!("Reward cycle {} is out of range", reward_cycle);
+            process::exit(1);
+        }
+
+        let reward_set =
+            RPCRewardSetData::from_db(&sort_db, &mut chain_state, &burnchain, reward_cycle)
+                .expect("Failed to compute reward set");
+        let reward_set = match reward_set {
+            Some(reward_set) => reward_set,
+            None => {
+                eprintln!("No reward set for reward cycle {}", reward_cycle);
+                process::exit(1);
+            }
+        };
+
+        println!(
+            "Reward cycle {}: anchor block {}/{}, threshold {} uSTX, {} uSTX stacked, {} slots",
+            reward_set.reward_cycle,
+            &reward_set.anchor_consensus_hash,
+            &reward_set.anchor_block_hash,
+            reward_set.min_threshold_ustx,
+            reward_set.stacked_ustx,
+            reward_set.total_slots
+        );
+        println!("PoX address, Slots, Total uSTX");
+        for entry in reward_set.entries.iter() {
+            println!(
+                "{}, {}, {}",
+                &entry.pox_address, entry.slots, entry.total_ustx
+            );
+        }
+
+        process::exit(0);
+    }
+
+    if argv[1] == "verify-reward-payouts" {
+        if argv.len() < 4 {
+            eprintln!(
+                "Usage: {} verify-reward-payouts <working-dir> <reward-cycle>
+
+Given a mainnet <working-dir>, compare the PoX payouts that the reward set of <reward-cycle>
+called for against the Bitcoin outputs of the block commits accepted during its reward phase.
+For each PoX address, prints the reward slots it was allotted, the reward slots it was paid in,
+and the satoshis it received.
+",
+                argv[0]
+            );
+            process::exit(1);
+        }
+        let reward_cycle: u64 = argv[3]
+            .parse()
+            .expect("Failed to parse <reward-cycle> argument");
+
+        let sort_db_path = format!("{}/mainnet/burnchain/sortition", &argv[2]);
+        let chain_state_path = format!("{}/mainnet/chainstate/", &argv[2]);
+        let burnchain_path = format!("{}/mainnet/burnchain", &argv[2]);
+
+        let sort_db = SortitionDB::open(&sort_db_path, false)
+            .expect(&format!("Failed to open {}", &sort_db_path));
+        let (mut chain_state, _) =
+            StacksChainState::open(true, core::CHAIN_ID_MAINNET, &chain_state_path)
+                .expect("Failed to open stacks chain state");
+        let burnchain = Burnchain::new(&burnchain_path, "bitcoin", "mainnet")
+            .expect("Failed to instantiate burnchain");
+
+        let sortition_tip = SortitionDB::get_canonical_sortition_tip(sort_db.conn())
+            .expect("Failed to load canonical sortition tip");
+        let payouts = get_reward_cycle_payouts(
+            reward_cycle,
+            &sortition_tip,
+            &burnchain,
+            &mut chain_state,
+            &sort_db,
+        )
+        .expect("Failed to verify reward payouts");
+        let payouts = match payouts {
+            Some(payouts) => payouts,
+            None => {
+                eprintln!("No reward set for reward cycle {}", reward_cycle);
+                process::exit(1);
+            }
+        };
+
+        if !payouts.complete {
+            println!(
+                "Reward cycle {} is still in progress; only its processed blocks were checked",
+                reward_cycle
+            );
+        }
+        for block_height in payouts.blocks_without_commits.iter() {
+            println!(
+                "Burn block {} had no accepted block commits; its reward slots went unpaid",
+                block_height
+            );
+        }
+
+        let mut num_problems = 0;
+        println!("PoX address, Expected slots, Paid slots, Paid sats, Status");
+        for payout in payouts.payouts.iter() {
+            let status = if payout.expected_slots == 0 {
+                num_problems += 1;
+                "UNEXPECTED".to_string()
+            } else if payout.paid_slots < payout.expected_slots {
+                num_problems += 1;
+                format!("SHORT by {}", payout.expected_slots - payout.paid_slots)
+            } else if payout.paid_slots > payout.expected_slots {
+                num_problems += 1;
+                format!("OVER by {}", payout.paid_slots - payout.expected_slots)
+            } else {
+                "OK".to_string()
+            };
+            println!(
+                "{}, {}, {}, {}, {}",
+                payout.address.clone().to_b58(),
+                payout.expected_slots,
+                payout.paid_slots,
+                payout.paid_sats,
+                status
+            );
+        }
+
+        process::exit(if num_problems > 0 { 2 } else { 0 });
+    }
+
+    if argv[1] == "replay-block-range" {
+        if argv.len() < 5 {
+            eprintln!(
+                "Usage: {} replay-block-range <working-dir> <start-height> <end-height>
+
+Given a mainnet <working-dir>, re-execute each block of the canonical Stacks fork from
+<start-height> to <end-height>, inclusive, on top of its parent's state.  Blocks are replayed
+in a scratch copy of the Clarity state under <working-dir>/mainnet/replay-scratch, which is
+removed afterwards; nothing is written back to the chainstate.  For each block, prints how long it
+took to execute and the cost of its anchored transactions, and reports any divergence from the
+state root, the block cost, and (if the node ran with STACKS_TRANSACTION_LOG=1) the transaction
+results that were recorded when it was first processed.
+",
+                argv[0]
+            );
+            process::exit(1);
+        }
+        let start_height: u64 = argv[3]
+            .parse()
+            .expect("Failed to parse <start-height> argument");
+        let end_height: u64 = argv[4]
+            .parse()
+            .expect("Failed to parse <end-height> argument");
+
+        let sort_db_path = format!("{}/mainnet/burnchain/sortition", &argv[2]);
+        let chain_state_path = format!("{}/mainnet/chainstate/", &argv[2]);
+
+        let sort_db = SortitionDB::open(&sort_db_path, false)
+            .expect(&format!("Failed to open {}", &sort_db_path));
+        let (mut chain_state, _) =
+            StacksChainState::open(true, core::CHAIN_ID_MAINNET, &chain_state_path)
+                .expect("Failed to open stacks chain state");
+
+        let tip = chain_state
+            .get_stacks_chain_tip(&sort_db)
+            .expect("Failed to load Stacks chain tip")
+            .expect("No Stacks chain tip");
+        let tip_header = StacksChainState::get_anchored_block_header_info(
+            chain_state.db(),
+            &tip.consensus_hash,
+            &tip.anchored_block_hash,
+        )
+        .expect("Failed to load Stacks chain tip header")
+        .expect("No header for Stacks chain tip");
+
+        // the genesis block has nothing to replay
+        let mut headers = StacksChainState::get_ancestors_headers(
+            chain_state.db(),
+            tip_header,
+            start_height.max(1),
+        )
+        .expect("Failed to load Stacks block headers");
+        headers.retain(|header| header.block_height <= end_height);
+        headers.reverse();
+
+        let scratch_path = format!("{}/mainnet/replay-scratch", &argv[2]);
+        let mut scratch_clarity_state = chain_state
+            .open_scratch_clarity_state(&scratch_path)
+            .expect("Failed to copy the Clarity state into a scratch MARF");
+
+        let mut num_problems = 0;
+        println!("Height, Index block hash, Txs, Time (ms), Runtime, Read count, Read length, Write count, Write length, Status");
+        for header in headers.into_iter() {
+            let index_block_hash = header.index_block_hash();
+            let recorded_cost = StacksChainState::get_stacks_block_anchored_cost(
+                chain_state.db(),
+                &index_block_hash,
+            )
+            .expect("Failed to load block cost");
+            let recorded_results = StacksChainState::get_logged_transaction_results(
+                chain_state.db(),
+                &index_block_hash,
+            )
+            .expect("Failed to load transaction log");
+
+            let start_time = get_epoch_time_ms();
+            let replay_result =
+                chain_state.replay_block(&sort_db, &mut scratch_clarity_state, &index_block_hash);
+            let elapsed = get_epoch_time_ms().saturating_sub(start_time);
+
+            let receipt = match replay_result {
+                Ok(receipt) => receipt,
+                Err(e) => {
+                    num_problems += 1;
+                    println!(
+                        "{}, {}, -, {}, -, -, -, -, -, FAILED: {:?}",
+                        header.block_height, &index_block_hash, elapsed, &e
+                    );
+                    continue;
+                }
+            };
+
+            let mut divergences = vec![];
+            if receipt.state_root != header.anchored_header.state_index_root {
+                divergences.push(format!(
+                    "state root {} != {}",
+                    &receipt.state_root, &header.anchored_header.state_index_root
+                ));
+            }
+            if recorded_cost.as_ref() != Some(&receipt.anchored_block_cost) {
+                divergences.push(format!("block cost != {:?}", &recorded_cost));
+            }
+            for tx_receipt in receipt.tx_receipts.iter() {
+                let txid = tx_receipt.transaction.txid();
+                if let Some(recorded_result) = recorded_results.get(&txid) {
+                    let result = tx_receipt.result.to_string();
+                    if &result != recorded_result {
+                        divergences.push(format!(
+                            "tx {} result {} != {}",
+                            &txid, &result, recorded_result
+                        ));
+                    }
+                }
+            }
+
+            let status = if divergences.len() > 0 {
+                num_problems += 1;
+                format!("DIVERGED: {}", divergences.join("; "))
+            } else {
+                "OK".to_string()
+            };
+
+            let cost = &receipt.anchored_block_cost;
+            println!(
+                "{}, {}, {}, {}, {}, {}, {}, {}, {}, {}",
+                header.block_height,
+                &index_block_hash,
+                receipt.tx_receipts.len(),
+                elapsed,
+                cost.runtime,
+                cost.read_count,
+                cost.read_length,
+                cost.write_count,
+                cost.write_length,
+                status
+            );
+        }
+
+        drop(scratch_clarity_state);
+        if let Err(e) = fs::remove_dir_all(&scratch_path) {
+            eprintln!("Failed to remove {}: {:?}", &scratch_path, &e);
+        }
+        process::exit(if num_problems > 0 { 2 } else { 0 });
+    }
+
+    if argv[1] == "export-chain-data" {
+        if argv.len() < 6 {
+            eprintln!(
+                "Usage: {} export-chain-data <working-dir> <start-height> <end-height> <output-dir> [--events]
+
+Given a mainnet <working-dir>, write the blocks of the canonical Stacks fork from <start-height>
+to <end-height>, inclusive, as CSV files in <output-dir>:
+
+  blocks.csv        one row per anchored block
+  transactions.csv  one row per transaction, including the microblock transactions each block
+                    confirms, with the raw transaction in hex
+  events.csv        with --events, one row per transaction event, with the event as JSON
+
+Events are not stored in the chainstate, so --events re-executes each block in a scratch copy
+of the Clarity state under <working-dir>/mainnet/replay-scratch, without writing anything back.  The CSV files can be loaded directly, or
+converted to Parquet, by most data-science tools.
+",
+                argv[0]
+            );
+            process::exit(1);
+        }
+        let start_height: u64 = argv[3]
+            .parse()
+            .expect("Failed to parse <start-height> argument");
+        let end_height: u64 = argv[4]
+            .parse()
+            .expect("Failed to parse <end-height> argument");
+        let output_dir = &argv[5];
+        let export_events = argv.len() > 6 && argv[6] == "--events";
+
+        let sort_db_path = format!("{}/mainnet/burnchain/sortition", &argv[2]);
+        let chain_state_path = format!("{}/mainnet/chainstate/", &argv[2]);
+
+        let sort_db = SortitionDB::open(&sort_db_path, false)
+            .expect(&format!("Failed to open {}", &sort_db_path));
+        let (mut chain_state, _) =
+            StacksChainState::open(true, core::CHAIN_ID_MAINNET, &chain_state_path)
+                .expect("Failed to open stacks chain state");
+
+        let tip = chain_state
+            .get_stacks_chain_tip(&sort_db)
+            .expect("Failed to load Stacks chain tip")
+            .expect("No Stacks chain tip");
+        let tip_header = StacksChainState::get_anchored_block_header_info(
+            chain_state.db(),
+            &tip.consensus_hash,
+            &tip.anchored_block_hash,
+        )
+        .expect("Failed to load Stacks chain tip header")
+        .expect("No header for Stacks chain tip");
+
+        let mut headers =
+            StacksChainState::get_ancestors_headers(chain_state.db(), tip_header, start_height)
+                .expect("Failed to load Stacks block headers");
+        headers.retain(|header| header.block_height <= end_height);
+        headers.reverse();
+
+        fs::create_dir_all(output_dir).expect(&format!("Failed to create {}", output_dir));
+        let open_csv = |name: &str, columns: &[&str]| {
+            let path = format!("{}/{}", output_dir, name);
+            let mut file = fs::File::create(&path).expect(&format!("Failed to create {}", &path));
+            writeln!(file, "{}", columns.join(",")).expect("Failed to write CSV header");
+            io::BufWriter::new(file)
+        };
+
+        let mut blocks_csv = open_csv(
+            "blocks.csv",
+            &[
+                "height",
+                "index_block_hash",
+                "block_hash",
+                "consensus_hash",
+                "parent_block_hash",
+                "parent_microblock_hash",
+                "parent_microblock_sequence",
+                "burn_header_hash",
+                "burn_header_height",
+                "burn_header_timestamp",
+                "tx_count",
+                "microblock_tx_count",
+            ],
+        );
+        let mut txs_csv = open_csv(
+            "transactions.csv",
+            &[
+                "block_height",
+                "index_block_hash",
+                "microblock_hash",
+                "tx_index",
+                "txid",
+                "type",
+                "origin",
+                "sponsor",
+                "nonce",
+                "fee",
+                "anchor_mode",
+                "post_condition_mode",
+                "raw_tx",
+            ],
+        );
+        let mut events_csv = if export_events {
+            Some(open_csv(
+                "events.csv",
+                &[
+                    "block_height",
+                    "index_block_hash",
+                    "txid",
+                    "event_index",
+                    "committed",
+                    "type",
+                    "event",
+                ],
+            ))
+        } else {
+            None
+        };
+        let scratch_path = format!("{}/mainnet/replay-scratch", &argv[2]);
+        let mut scratch_clarity_state = if export_events {
+            Some(
+                chain_state
+                    .open_scratch_clarity_state(&scratch_path)
+                    .expect("Failed to copy the Clarity state into a scratch MARF"),
+            )
+        } else {
+            None
+        };
+
+        for header in headers.iter() {
+            let index_block_hash = header.index_block_hash();
+            let block_hash = header.anchored_header.block_hash();
+            let block = match StacksChainState::load_block(
+                &chain_state.blocks_path,
+                &header.consensus_hash,
+                &block_hash,
+            )
+            .expect("Failed to load block")
+            {
+                Some(block) => block,
+                None => {
+                    // e.g. the genesis block, which has no transactions
+                    eprintln!("No block data for {}; skipping", &index_block_hash);
+                    continue;
+                }
+            };
+
+            let microblocks =
+                if block.header.parent_microblock == core::EMPTY_MICROBLOCK_PARENT_HASH {
+                    vec![]
+                } else {
+                    let parent_block_id =
+                        StacksChainState::get_parent_block_id(chain_state.db(), &index_block_hash)
+                            .expect("Failed to load parent block ID")
+                            .expect("No parent block ID");
+                    let (parent_consensus_hash, parent_block_hash) = chain_state
+                        .get_block_header_hashes(&parent_block_id)
+                        .expect("Failed to load parent block hashes")
+                        .expect("No parent block hashes");
+                    StacksChainState::load_processed_microblock_stream_fork(
+                        chain_state.db(),
+                        &parent_consensus_hash,
+                        &parent_block_hash,
+                        &block.header.parent_microblock,
+                    )
+                    .expect("Failed to load confirmed microblocks")
+                    .unwrap_or(vec![])
+                };
+            let microblock_tx_count: usize = microblocks.iter().map(|mb| mb.txs.len()).sum();
+
+            writeln!(
+                blocks_csv,
+                "{}",
+                csv_row(&[
+                    header.block_height.to_string(),
+                    index_block_hash.to_string(),
+                    block_hash.to_string(),
+                    header.consensus_hash.to_string(),
+                    block.header.parent_block.to_string(),
+                    block.header.parent_microblock.to_string(),
+                    block.header.parent_microblock_sequence.to_string(),
+                    header.burn_header_hash.to_string(),
+                    header.burn_header_height.to_string(),
+                    header.burn_header_timestamp.to_string(),
+                    block.txs.len().to_string(),
+                    microblock_tx_count.to_string(),
+                ])
+            )
+            .expect("Failed to write blocks.csv");
+
+            let confirmed_txs = microblocks
+                .iter()
+                .flat_map(|mb| {
+                    let mblock_hash = mb.block_hash().to_string();
+                    mb.txs.iter().map(move |tx| (mblock_hash.clone(), tx))
+                })
+                .chain(block.txs.iter().map(|tx| ("".to_string(), tx)));
+            for (tx_index, (mblock_hash, tx)) in confirmed_txs.enumerate() {
+                let mut tx_bytes = vec![];
+                tx.consensus_serialize(&mut tx_bytes)
+                    .expect("BUG: failed to serialize transaction");
+                let sponsor = tx
+                    .sponsor_address()
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_default();
+                writeln!(
+                    txs_csv,
+                    "{}",
+                    csv_row(&[
+                        header.block_height.to_string(),
+                        index_block_hash.to_string(),
+                        mblock_hash,
+                        tx_index.to_string(),
+                        tx.txid().to_string(),
+                        tx.payload.name().to_string(),
+                        tx.origin_address().to_string(),
+                        sponsor,
+                        tx.get_origin_nonce().to_string(),
+                        tx.get_tx_fee().to_string(),
+                        format!("{:?}", &tx.anchor_mode),
+                        format!("{:?}", &tx.post_condition_mode),
+                        to_hex(&tx_bytes),
+                    ])
+                )
+                .expect("Failed to write transactions.csv");
+            }
+
+            if let Some(ref mut events_csv) = events_csv {
+                let scratch = scratch_clarity_state
+                    .as_mut()
+                    .expect("BUG: exporting events without a scratch MARF");
+                let receipt = chain_state
+                    .replay_block(&sort_db, scratch, &index_block_hash)
+                    .expect(&format!("Failed to re-execute block {}", &index_block_hash));
+                for tx_receipt in receipt.tx_receipts.iter() {
+                    let txid = match tx_receipt.transaction {
+                        TransactionOrigin::Stacks(ref tx) => tx.txid(),
+                        TransactionOrigin::Burn(ref txid) => txid.clone(),
+                    };
+                    let committed = !tx_receipt.post_condition_aborted
+                        && match tx_receipt.result {
+                            Value::Response(ref response) => response.committed,
+                            _ => true,
+                        };
+                    for (event_index, event) in tx_receipt.events.iter().enumerate() {
+                        let event_json = event.json_serialize(event_index, &txid, committed);
+                        writeln!(
+                            events_csv,
+                            "{}",
+                            csv_row(&[
+                                header.block_height.to_string(),
+                                index_block_hash.to_string(),
+                                txid.to_string(),
+                                event_index.to_string(),
+                                committed.to_string(),
+                                event_json["type"].as_str().unwrap_or("").to_string(),
+                                event_json.to_string(),
+                            ])
+                        )
+                        .expect("Failed to write events.csv");
+                    }
+                }
+            }
+        }
+
+        if scratch_clarity_state.take().is_some() {
+            if let Err(e) = fs::remove_dir_all(&scratch_path) {
+                eprintln!("Failed to remove {}: {:?}", &scratch_path, &e);
+            }
+        }
+
+        // process::exit() doesn't run destructors, so the buffered rows must be flushed here
+        let mut flushed = vec![
+            ("blocks.csv", blocks_csv.flush()),
+            ("transactions.csv", txs_csv.flush()),
+        ];
+        if let Some(ref mut events_csv) = events_csv {
+            flushed.push(("events.csv", events_csv.flush()));
+        }
+        for (name, result) in flushed.into_iter() {
+            if let Err(e) = result {
+                eprintln!("Failed to write {}/{}: {:?}", output_dir, name, &e);
+                process::exit(1);
+            }
+        }
+
+        println!("Exported {} block(s) to {}", headers.len(), output_dir);
+        process::exit(0);
+    }
+
+    if argv[1] == "pox-anchor-override" {
+        if argv.len() < 4 || (argv[3] != "list" && argv.len() < 5) {
+            eprintln!(
+                "Usage: {} pox-anchor-override <working-dir> list
+       {} pox-anchor-override <working-dir> set <reward-cycle>
+       {} pox-anchor-override <working-dir> clear <reward-cycle>
+
+Inspect or override the PoX anchor block view of a (stopped) mainnet node in <working-dir>.
+  list   prints the selected PoX anchor blocks this node is missing, and any overrides.
+  set    treats <reward-cycle> as if it had no PoX anchor block.  The reward cycle's
+         sortitions are re-evaluated the next time the node starts.
+  clear  removes the override for <reward-cycle>.
+
+Only use this as part of the PoX anchor block recovery procedure in docs/pox-anchor-recovery.md.
+",
+                argv[0], argv[0], argv[0]
+            );
+            process::exit(1);
+        }
+
+        let sort_db_path = format!("{}/mainnet/burnchain/sortition", &argv[2]);
+        let burnchain_path = format!("{}/mainnet/burnchain", &argv[2]);
+
+        let mut sort_db = SortitionDB::open(&sort_db_path, true)
+            .expect(&format!("Failed to open {}", &sort_db_path));
+        let burnchain = Burnchain::new(&burnchain_path, "bitcoin", "mainnet")
+            .expect("Failed to instantiate burnchain");
+
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sort_db.conn())
+            .expect("Failed to load canonical sortition tip");
+        let missing = sort_db
+            .get_missing_pox_anchors(&burnchain, &tip.sortition_id)
+            .expect("Failed to load missing PoX anchor blocks");
+
+        if argv[3] == "list" {
+            println!("Reward cycle, Missing anchor block");
+            for (reward_cycle, anchor_block_hash) in missing.iter() {
+                println!("{}, {}", reward_cycle, anchor_block_hash);
+            }
+            let overrides = SortitionDB::get_pox_anchor_overrides(sort_db.conn())
+                .expect("Failed to load PoX anchor overrides");
+            println!("Overridden reward cycles: {:?}", &overrides);
+            process::exit(0);
+        }
+
+        let reward_cycle: u64 = argv[4]
+            .parse()
+            .expect("Failed to parse <reward-cycle> argument");
+
+        match argv[3].as_str() {
+            "set" => {
+                // re-evaluating a reward cycle whose anchor block we have would just reproduce
+                // the same sortitions, so refuse to do it.
+                if !missing.iter().any(|(rc, _)| *rc == reward_cycle) {
+                    eprintln!(
+                        "This node is not missing the PoX anchor block of reward cycle {}",
+                        reward_cycle
+                    );
+                    process::exit(1);
+                }
+                sort_db
+                    .set_pox_anchor_override(reward_cycle)
+                    .expect("Failed to store PoX anchor override");
+                let num_invalidated = sort_db
+                    .invalidate_reward_cycle(&burnchain, reward_cycle)
+                    .expect("Failed to invalidate reward cycle");
+                println!(
+                    "Overrode the PoX anchor block of reward cycle {}; {} sortitions from burn height {} will be re-evaluated when the node starts",
+                    reward_cycle,
+                    num_invalidated,
+                    burnchain.reward_cycle_to_block_height(reward_cycle)
+                );
+            }
+            "clear" => {
+                let cleared = sort_db
+                    .clear_pox_anchor_override(reward_cycle)
+                    .expect("Failed to clear PoX anchor override");
+                if !cleared {
+                    eprintln!("No PoX anchor override for reward cycle {}", reward_cycle);
+                    process::exit(1);
+                }
+                println!(
+                    "Cleared the PoX anchor override of reward cycle {}",
+                    reward_cycle
+                );
+            }
+            cmd => {
+                eprintln!("Unrecognized command '{}'", cmd);
+                process::exit(1);
+            }
+        }
+
+        process::exit(0);
+    }
+
     if argv[1] == "try-mine" {
         if argv.len() < 3 {
             eprintln!(
@@ -617,6 +1688,100 @@ simulating a miner.
         return;
     }
 
+    if argv[1] == "marf-key-history" {
+        if argv.len() < 5 {
+            eprintln!(
+                "Usage: {} marf-key-history <working-dir> <index-block-hash> <key> [max-entries]
+
+Given a mainnet <working-dir>, look up the value of the Clarity MARF <key> as of the block
+<index-block-hash>, and then list the blocks in that block's fork in which <key> was written, most
+recent first, along with the value written in each.  Stops after [max-entries] blocks, if given.
+The blocks that wrote <key> are only known if the node runs with STACKS_MARF_LEAF_WRITE_INDEX=1.
+",
+                argv[0]
+            );
+            process::exit(1);
+        }
+        let marf_path = format!("{}/mainnet/chainstate/vm/clarity/marf.sqlite", &argv[2]);
+        let tip = StacksBlockId::from_hex(&argv[3]).expect("Failed to parse <index-block-hash>");
+        let key = &argv[4];
+        let max_entries: Option<usize> = argv
+            .get(5)
+            .map(|s| s.parse().expect("Failed to parse [max-entries] argument"));
+
+        let mut marf: MARF<StacksBlockId> =
+            MARF::from_path_readonly(&marf_path).expect(&format!("Failed to open {}", &marf_path));
+
+        // the side store holds the actual values; the MARF only holds their hashes
+        let load_value = |marf: &MARF<StacksBlockId>, marf_value: &MARFValue| {
+            let side_key = marf_value.to_hex();
+            let value = SqliteConnection::get(marf.sqlite_conn(), &side_key).expect(&format!(
+                "MARF contained value hash not found in side storage: {}",
+                &side_key
+            ));
+            match Value::try_deserialize_hex_untyped(&value) {
+                Ok(clarity_value) => format!("{} ({})", clarity_value, &value),
+                Err(_) => value,
+            }
+        };
+
+        let mut cursor = tip;
+        let mut num_entries = 0;
+        loop {
+            let (marf_value, origin_opt) = match marf.get_with_origin(&cursor, key) {
+                Ok(Some(x)) => x,
+                Ok(None) => break,
+                Err(e) => {
+                    eprintln!("MARF error looking up '{}' at {}: {:?}", key, &cursor, &e);
+                    process::exit(1);
+                }
+            };
+
+            if num_entries == 0 {
+                println!("Value at {}: {}", &tip, load_value(&marf, &marf_value));
+                println!("Height, Index block hash, Value");
+            }
+            let origin = match origin_opt {
+                Some(origin) => origin,
+                None => {
+                    println!(
+                        "?, ?, {} (not recorded; see STACKS_MARF_LEAF_WRITE_INDEX)",
+                        load_value(&marf, &marf_value)
+                    );
+                    num_entries += 1;
+                    break;
+                }
+            };
+            let height = marf
+                .get_block_height(&origin, &origin)
+                .expect("MARF error.")
+                .expect("No height for block");
+            println!(
+                "{}, {}, {}",
+                height,
+                &origin,
+                load_value(&marf, &marf_value)
+            );
+            num_entries += 1;
+
+            if height == 0 || max_entries.map(|max| num_entries >= max).unwrap_or(false) {
+                break;
+            }
+            cursor = match marf
+                .get_block_at_height(height - 1, &origin)
+                .expect("MARF error.")
+            {
+                Some(parent) => parent,
+                None => break,
+            };
+        }
+
+        if num_entries == 0 {
+            println!("Value at {}: None", &tip);
+        }
+        return;
+    }
+
     if argv[1] == "get-ancestors" {
         let path = &argv[2];
         let tip = BlockHeaderHash::from_hex(&argv[3]).unwrap();
@@ -778,6 +1943,7 @@ simulating a miner.
             get_bulk_initial_balances: None,
             get_bulk_initial_namespaces: None,
             get_bulk_initial_names: None,
+            custom_costs_contract: None,
         };
 
         let (mut new_chainstate, _) = StacksChainState::open_and_exec(
@@ -1000,3 +2166,66 @@ simulating a miner.
         process::exit(1);
     }
 }
+
+/// Print one line per transaction.  If `verify` is set, also check each transaction's
+/// signatures, and return how many failed.
+fn print_block_transactions(txs: &[StacksTransaction], verify: bool) -> usize {
+    let mut num_failed = 0;
+    println!("Transactions ({}):", txs.len());
+    for (i, tx) in txs.iter().enumerate() {
+        let origin = match tx.version {
+            TransactionVersion::Mainnet => tx.auth.origin().address_mainnet(),
+            TransactionVersion::Testnet => tx.auth.origin().address_testnet(),
+        };
+        let mut line = format!(
+            "  {}: {} {} origin={} nonce={} fee={} anchor_mode={:?}",
+            i,
+            &tx.txid(),
+            tx.payload.name(),
+            &origin,
+            tx.get_origin_nonce(),
+            tx.get_tx_fee(),
+            &tx.anchor_mode
+        );
+        if verify {
+            match tx.verify() {
+                Ok(_) => line.push_str(" signatures=OK"),
+                Err(e) => {
+                    num_failed += 1;
+                    line.push_str(&format!(" signatures=FAILED ({:?})", &e));
+                }
+            }
+        }
+        println!("{}", line);
+    }
+    num_failed
+}
+
+/// Re-compute the transaction merkle root and compare it to the one in the header.  Returns 1 on
+/// mismatch, and 0 otherwise.
+fn verify_tx_merkle_root(txs: &[StacksTransaction], expected: &Sha512Trunc256Sum) -> usize {
+    let txid_vecs = txs.iter().map(|tx| tx.txid().as_bytes().to_vec()).collect();
+    let tx_merkle_root = MerkleTree::<Sha512Trunc256Sum>::new(&txid_vecs).root();
+    if &tx_merkle_root == expected {
+        println!("Tx merkle root: OK");
+        0
+    } else {
+        println!("Tx merkle root: MISMATCH (computed {})", &tx_merkle_root);
+        1
+    }
+}
+
+/// Format one CSV row, quoting any field that needs it
+fn csv_row(fields: &[String]) -> String {
+    fields
+        .iter()
+        .map(|field| {
+            if field.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
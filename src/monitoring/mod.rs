@@ -16,7 +16,7 @@
 
 use std::{fmt, fs, path::PathBuf};
 
-use rusqlite::{OpenFlags, OptionalExtension};
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
 
 use crate::{
     burnchains::Txid,
@@ -37,6 +37,9 @@ use util::uint::{Uint256, Uint512};
 #[cfg(feature = "monitoring_prom")]
 mod prometheus;
 
+#[cfg(feature = "monitoring_otlp")]
+mod otlp;
+
 #[cfg(feature = "monitoring_prom")]
 lazy_static! {
     static ref GLOBAL_BURNCHAIN_SIGNER: Mutex<Option<BurnchainSigner>> = Mutex::new(None);
@@ -60,7 +63,12 @@ where
     #[cfg(feature = "monitoring_prom")]
     let timer = prometheus::new_rpc_call_timer(req.get_path());
 
+    let mut span = trace_span("rpc.request");
+    span.set_attribute("http.route", req.get_path());
     let res = handler(req);
+    if let Err(ref e) = res {
+        span.set_attribute("error", e);
+    }
 
     #[cfg(feature = "monitoring_prom")]
     timer.stop_and_record();
@@ -128,6 +136,90 @@ pub fn increment_errors_emitted_counter() {
     prometheus::ERRORS_EMITTED_COUNTER.inc();
 }
 
+/// A span of work, exported over OTLP when the node is built with `monitoring_otlp` and
+/// `start_otlp_exporter()` has been called.  Otherwise, this does nothing.  The span ends when
+/// it is dropped.
+pub struct TraceSpan {
+    #[cfg(feature = "monitoring_otlp")]
+    inner: Option<otlp::ActiveSpan>,
+}
+
+impl TraceSpan {
+    /// Whether this span will be exported.  Check this before building an attribute value that
+    /// is costly to compute.
+    pub fn is_recording(&self) -> bool {
+        #[cfg(feature = "monitoring_otlp")]
+        {
+            if let Some(ref span) = self.inner {
+                return span.is_recording();
+            }
+        }
+        false
+    }
+
+    #[allow(unused_variables)]
+    pub fn set_attribute<V: fmt::Display>(&mut self, key: &'static str, value: V) {
+        #[cfg(feature = "monitoring_otlp")]
+        {
+            if let Some(ref mut span) = self.inner {
+                if span.is_recording() {
+                    span.set_attribute(key, value.to_string());
+                }
+            }
+        }
+    }
+}
+
+/// Start a span.  If another span is open on this thread, the new span is its child; otherwise,
+/// it starts a new trace.
+#[allow(unused_variables)]
+pub fn trace_span(name: &'static str) -> TraceSpan {
+    TraceSpan {
+        #[cfg(feature = "monitoring_otlp")]
+        inner: if otlp::is_enabled() {
+            Some(otlp::start_span(name))
+        } else {
+            None
+        },
+    }
+}
+
+/// Start a span only as part of a trace that is already being recorded on this thread.  Use this
+/// for small, frequent operations like MARF reads.
+#[allow(unused_variables)]
+pub fn trace_child_span(name: &'static str) -> TraceSpan {
+    TraceSpan {
+        #[cfg(feature = "monitoring_otlp")]
+        inner: if otlp::is_enabled() {
+            otlp::start_child_span(name)
+        } else {
+            None
+        },
+    }
+}
+
+/// Record the SQL statements run on this connection as spans in the enclosing trace.
+#[allow(unused_variables)]
+pub fn instrument_sqlite_connection(conn: &mut Connection) {
+    #[cfg(feature = "monitoring_otlp")]
+    conn.profile(Some(otlp::record_sql_statement));
+}
+
+/// Start exporting trace spans to the OTLP/HTTP collector at `endpoint` (e.g.
+/// `http://localhost:4318`), recording `sample_ratio` of all traces.
+#[allow(unused_variables)]
+pub fn start_otlp_exporter(
+    endpoint: &str,
+    service_name: &str,
+    sample_ratio: f64,
+) -> Result<(), String> {
+    #[cfg(feature = "monitoring_otlp")]
+    return otlp::start_exporter(endpoint, service_name, sample_ratio);
+
+    #[cfg(not(feature = "monitoring_otlp"))]
+    Err("Tracing requires a build with the `monitoring_otlp` feature".to_string())
+}
+
 fn txid_tracking_db(chainstate_root_path: &str) -> Result<DBConn, DatabaseError> {
     let mut path = PathBuf::from(chainstate_root_path);
 
@@ -241,6 +333,60 @@ pub fn update_burnchain_height(value: i64) {
     prometheus::BURNCHAIN_HEIGHT_GAUGE.set(value);
 }
 
+#[allow(unused_variables)]
+pub fn update_missing_pox_anchors(value: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::MISSING_POX_ANCHORS_GAUGE.set(value);
+}
+
+#[allow(unused_variables)]
+pub fn update_pox_reward_cycle(value: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::POX_REWARD_CYCLE_GAUGE.set(value);
+}
+
+#[allow(unused_variables)]
+pub fn update_pox_stacked_ustx(value: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::POX_STACKED_USTX_GAUGE.set(value);
+}
+
+#[allow(unused_variables)]
+pub fn update_pox_stackers(value: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::POX_STACKERS_GAUGE.set(value);
+}
+
+#[allow(unused_variables)]
+pub fn update_pox_reward_slots_filled(value: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::POX_REWARD_SLOTS_FILLED_GAUGE.set(value);
+}
+
+#[allow(unused_variables)]
+pub fn update_pox_anchor_block_known(value: bool) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::POX_ANCHOR_BLOCK_KNOWN_GAUGE.set(if value { 1 } else { 0 });
+}
+
+#[allow(unused_variables)]
+pub fn update_disk_free_bytes(value: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::DISK_FREE_BYTES_GAUGE.set(value);
+}
+
+#[allow(unused_variables)]
+pub fn update_connection_buffer_bytes(value: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::CONNECTION_BUFFER_BYTES_GAUGE.set(value);
+}
+
+#[allow(unused_variables)]
+pub fn update_sqlite_memory_used(value: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::SQLITE_MEMORY_USED_GAUGE.set(value);
+}
+
 #[allow(unused_variables)]
 pub fn update_inbound_neighbors(value: i64) {
     #[cfg(feature = "monitoring_prom")]
@@ -290,6 +436,112 @@ pub fn increment_stx_mempool_gc() {
     prometheus::STX_MEMPOOL_GC.inc();
 }
 
+#[allow(unused_variables)]
+pub fn update_atlas_attachments_storage_bytes(value: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::ATLAS_ATTACHMENTS_STORAGE_BYTES_GAUGE.set(value);
+}
+
+#[allow(unused_variables)]
+pub fn update_atlas_attachments_count(value: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::ATLAS_ATTACHMENTS_COUNT_GAUGE.set(value);
+}
+
+#[allow(unused_variables)]
+pub fn increment_atlas_attachments_rejected(rule: &str) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::ATLAS_ATTACHMENTS_REJECTED_COUNTER_VEC
+        .with_label_values(&[rule])
+        .inc();
+}
+
+#[allow(unused_variables)]
+pub fn increment_rpc_request_timeouts(class: &str) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::RPC_REQUEST_TIMEOUTS_COUNTER_VEC
+        .with_label_values(&[class])
+        .inc();
+}
+
+pub fn increment_tx_relays_avoided() {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::TX_RELAYS_AVOIDED_COUNTER.inc();
+}
+
+#[allow(unused_variables)]
+pub fn increment_tx_relays_declined(rule: &str) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::TX_RELAYS_DECLINED_COUNTER_VEC
+        .with_label_values(&[rule])
+        .inc();
+}
+
+pub fn increment_tx_filters_sent() {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::TX_FILTERS_SENT_COUNTER.inc();
+}
+
+#[allow(unused_variables)]
+pub fn increment_hook_runs(hook: &str) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::HOOK_RUNS_COUNTER_VEC
+        .with_label_values(&[hook])
+        .inc();
+}
+
+#[allow(unused_variables)]
+pub fn increment_hook_failures(hook: &str, reason: &str) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::HOOK_FAILURES_COUNTER_VEC
+        .with_label_values(&[hook, reason])
+        .inc();
+}
+
+#[allow(unused_variables)]
+pub fn increment_atlas_attachments_evicted(value: u64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::ATLAS_ATTACHMENTS_EVICTED_COUNTER.inc_by(value as i64);
+}
+
+#[allow(unused_variables)]
+pub fn update_push_queue_depth(value: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::PUSH_QUEUE_DEPTH_GAUGE.set(value);
+}
+
+#[allow(unused_variables)]
+pub fn increment_push_queue_drops(reason: &str) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::PUSH_QUEUE_DROPS_COUNTER_VEC
+        .with_label_values(&[reason])
+        .inc();
+}
+
+pub fn increment_stale_tip_resets() {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::STALE_TIP_RESETS_COUNTER.inc();
+}
+
+#[allow(unused_variables)]
+pub fn update_marf_readonly_handles(value: i64) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::MARF_READONLY_HANDLES_GAUGE.set(value);
+}
+
+#[allow(unused_variables)]
+pub fn increment_marf_handle_requests(outcome: &str) {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::MARF_HANDLE_REQUESTS_COUNTER_VEC
+        .with_label_values(&[outcome])
+        .inc();
+}
+
+pub fn increment_marf_handle_evictions() {
+    #[cfg(feature = "monitoring_prom")]
+    prometheus::MARF_HANDLE_EVICTIONS_COUNTER.inc();
+}
+
 pub fn increment_contract_calls_processed() {
     #[cfg(feature = "monitoring_prom")]
     prometheus::CONTRACT_CALLS_PROCESSED_COUNT.inc();
@@ -383,6 +635,32 @@ pub fn update_miner_current_median_commitment(value: u128) {
     }
 }
 
+/// The current totals of the counters that can be carried across restarts (network traffic,
+/// blocks processed and mined, and the like), by metric name.  Empty without the
+/// `monitoring_prom` feature.
+pub fn get_persisted_counter_totals() -> Vec<(String, u64)> {
+    #[cfg(feature = "monitoring_prom")]
+    return prometheus::persisted_counters()
+        .into_iter()
+        .map(|(name, counter)| (name.to_string(), counter.get() as u64))
+        .collect();
+
+    #[cfg(not(feature = "monitoring_prom"))]
+    vec![]
+}
+
+/// Add the totals a previous run saved from `get_persisted_counter_totals()` to the counters of
+/// the same names.  Totals for counters this build does not have are ignored.
+#[allow(unused_variables)]
+pub fn restore_persisted_counter_totals(totals: &[(String, u64)]) {
+    #[cfg(feature = "monitoring_prom")]
+    for (name, counter) in prometheus::persisted_counters() {
+        if let Some((_, total)) = totals.iter().find(|(saved_name, _)| saved_name == name) {
+            counter.inc_by(*total as i64);
+        }
+    }
+}
+
 /// Function sets the global variable `GLOBAL_BURNCHAIN_SIGNER`.
 /// Fails if there are multiple attempts to set this variable.
 #[allow(unused_variables)]
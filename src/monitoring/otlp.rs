@@ -0,0 +1,489 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A small OpenTelemetry tracer.  Spans are kept on a per-thread stack, so a span started while
+//! another is open on the same thread becomes its child.  Finished spans are handed to an
+//! exporter thread, which batches them and POSTs them to an OTLP/HTTP collector as JSON.  The
+//! exporter never blocks the caller: if it falls behind, spans are dropped.
+
+use std::cell::RefCell;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rand::{thread_rng, Rng};
+use serde_json::Value as JsonValue;
+use url::Url;
+
+use util::hash::to_hex;
+
+/// How many finished spans can be waiting for the exporter before new ones are dropped
+const SPAN_QUEUE_LEN: usize = 8192;
+/// Most spans sent in one export request
+const MAX_EXPORT_BATCH: usize = 512;
+/// Longest a finished span waits before it is exported
+const EXPORT_INTERVAL: Duration = Duration::from_secs(2);
+/// SQL statements are cut down to this many bytes in span attributes
+const MAX_STATEMENT_LEN: usize = 256;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// Fraction of root spans to sample, scaled to u64::MAX
+static SAMPLE_THRESHOLD: AtomicU64 = AtomicU64::new(u64::max_value());
+static DROPPED_SPANS: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    static ref SPAN_SENDER: Mutex<Option<SyncSender<SpanData>>> = Mutex::new(None);
+}
+
+thread_local! {
+    /// The open spans on this thread, innermost last.  `None` marks a span that was not sampled,
+    /// so that its children are not sampled either.
+    static CONTEXT: RefCell<Vec<Option<SpanContext>>> = RefCell::new(vec![]);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SpanContext {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct SpanData {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    name: &'static str,
+    start_time_ns: u64,
+    end_time_ns: u64,
+    attributes: Vec<(&'static str, String)>,
+}
+
+/// An open span.  It is finished and queued for export when dropped.
+pub struct ActiveSpan {
+    data: Option<SpanData>,
+}
+
+impl ActiveSpan {
+    pub fn is_recording(&self) -> bool {
+        self.data.is_some()
+    }
+
+    pub fn set_attribute(&mut self, key: &'static str, value: String) {
+        if let Some(ref mut data) = self.data {
+            data.attributes.push((key, value));
+        }
+    }
+}
+
+impl Drop for ActiveSpan {
+    fn drop(&mut self) {
+        CONTEXT.with(|ctx| ctx.borrow_mut().pop());
+        if let Some(mut data) = self.data.take() {
+            data.end_time_ns = now_ns();
+            send_span(data);
+        }
+    }
+}
+
+fn now_ns() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+fn send_span(data: SpanData) {
+    let sender = match SPAN_SENDER.lock() {
+        Ok(sender) => sender,
+        Err(_) => return,
+    };
+    if let Some(ref sender) = *sender {
+        match sender.try_send(data) {
+            Ok(_) => {}
+            Err(TrySendError::Full(_)) => {
+                DROPPED_SPANS.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                ENABLED.store(false, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+fn current_context() -> Option<Option<SpanContext>> {
+    CONTEXT.with(|ctx| ctx.borrow().last().cloned())
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Open a span.  It is a child of the innermost open span on this thread if there is one, and
+/// otherwise starts a new trace (subject to sampling).
+pub fn start_span(name: &'static str) -> ActiveSpan {
+    let parent = current_context();
+    let sampled = match parent {
+        Some(parent) => parent.is_some(),
+        None => thread_rng().gen::<u64>() <= SAMPLE_THRESHOLD.load(Ordering::Relaxed),
+    };
+    open_span(name, parent.flatten(), sampled)
+}
+
+/// Open a span only if there is a sampled span open on this thread to be its parent.  This is for
+/// fine-grained work (MARF reads, SQL statements) that is only interesting as part of a larger
+/// operation.
+pub fn start_child_span(name: &'static str) -> Option<ActiveSpan> {
+    match current_context() {
+        Some(Some(parent)) => Some(open_span(name, Some(parent), true)),
+        _ => None,
+    }
+}
+
+fn open_span(name: &'static str, parent: Option<SpanContext>, sampled: bool) -> ActiveSpan {
+    if !sampled {
+        CONTEXT.with(|ctx| ctx.borrow_mut().push(None));
+        return ActiveSpan { data: None };
+    }
+    let context = SpanContext {
+        trace_id: match parent {
+            Some(ref parent) => parent.trace_id,
+            None => thread_rng().gen(),
+        },
+        span_id: thread_rng().gen(),
+    };
+    CONTEXT.with(|ctx| ctx.borrow_mut().push(Some(context)));
+    ActiveSpan {
+        data: Some(SpanData {
+            trace_id: context.trace_id,
+            span_id: context.span_id,
+            parent_span_id: parent.map(|p| p.span_id),
+            name,
+            start_time_ns: now_ns(),
+            end_time_ns: 0,
+            attributes: vec![],
+        }),
+    }
+}
+
+/// Profiling callback for SQLite connections: record a just-finished statement as a child of the
+/// innermost open span on this thread.
+pub fn record_sql_statement(sql: &str, duration: Duration) {
+    if !is_enabled() {
+        return;
+    }
+    let parent = match current_context() {
+        Some(Some(parent)) => parent,
+        _ => return,
+    };
+    let end_time_ns = now_ns();
+    let mut statement = sql.trim().to_string();
+    if statement.len() > MAX_STATEMENT_LEN {
+        let mut end = MAX_STATEMENT_LEN;
+        while !statement.is_char_boundary(end) {
+            end -= 1;
+        }
+        statement.truncate(end);
+    }
+    send_span(SpanData {
+        trace_id: parent.trace_id,
+        span_id: thread_rng().gen(),
+        parent_span_id: Some(parent.span_id),
+        name: "sqlite.statement",
+        start_time_ns: end_time_ns.saturating_sub(duration.as_nanos() as u64),
+        end_time_ns,
+        attributes: vec![
+            ("db.system", "sqlite".to_string()),
+            ("db.statement", statement),
+        ],
+    });
+}
+
+/// Where the exporter sends spans
+#[derive(Debug, Clone, PartialEq)]
+struct CollectorEndpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl CollectorEndpoint {
+    /// Parse an OTLP/HTTP endpoint, like `http://localhost:4318`.  Spans are POSTed to
+    /// `/v1/traces` under it, unless the URL already names a path.
+    fn parse(endpoint: &str) -> Result<CollectorEndpoint, String> {
+        let url = Url::parse(endpoint)
+            .map_err(|e| format!("Invalid OTLP endpoint '{}': {}", endpoint, &e))?;
+        if url.scheme() != "http" {
+            return Err(format!(
+                "Invalid OTLP endpoint '{}': only http:// collectors are supported",
+                endpoint
+            ));
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| format!("Invalid OTLP endpoint '{}': no host", endpoint))?
+            .to_string();
+        let path = match url.path() {
+            "" | "/" => "/v1/traces".to_string(),
+            path => path.to_string(),
+        };
+        Ok(CollectorEndpoint {
+            host,
+            port: url.port().unwrap_or(4318),
+            path,
+        })
+    }
+
+    fn post(&self, body: &[u8]) -> Result<(), String> {
+        let addr = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .map_err(|e| format!("Failed to resolve {}: {}", &self.host, &e))?
+            .next()
+            .ok_or_else(|| format!("Failed to resolve {}", &self.host))?;
+        let mut stream = TcpStream::connect_timeout(&addr, Duration::from_secs(5))
+            .map_err(|e| format!("Failed to connect to {}: {}", &addr, &e))?;
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .and_then(|_| stream.set_write_timeout(Some(Duration::from_secs(10))))
+            .map_err(|e| format!("Failed to set socket timeouts: {}", &e))?;
+
+        let request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            &self.path,
+            &self.host,
+            self.port,
+            body.len()
+        );
+        stream
+            .write_all(request.as_bytes())
+            .and_then(|_| stream.write_all(body))
+            .map_err(|e| format!("Failed to send spans to {}: {}", &addr, &e))?;
+
+        let mut response = vec![];
+        stream
+            .read_to_end(&mut response)
+            .map_err(|e| format!("Failed to read collector response: {}", &e))?;
+        let status_line = String::from_utf8_lossy(&response)
+            .lines()
+            .next()
+            .unwrap_or("")
+            .to_string();
+        let status = status_line.split_whitespace().nth(1).unwrap_or("");
+        if status.starts_with("2") {
+            Ok(())
+        } else {
+            Err(format!("Collector replied '{}'", &status_line))
+        }
+    }
+}
+
+fn attribute_json(key: &str, value: &str) -> JsonValue {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+/// Encode spans as an OTLP `ExportTraceServiceRequest`, in the protobuf JSON mapping.
+fn encode_spans(service_name: &str, spans: &[SpanData]) -> JsonValue {
+    let spans: Vec<JsonValue> = spans
+        .iter()
+        .map(|span| {
+            let mut json = json!({
+                "traceId": to_hex(&span.trace_id),
+                "spanId": to_hex(&span.span_id),
+                "name": span.name,
+                "kind": 1,
+                "startTimeUnixNano": format!("{}", span.start_time_ns),
+                "endTimeUnixNano": format!("{}", span.end_time_ns),
+                "attributes": span
+                    .attributes
+                    .iter()
+                    .map(|(key, value)| attribute_json(key, value))
+                    .collect::<Vec<_>>(),
+            });
+            if let Some(ref parent_span_id) = span.parent_span_id {
+                json["parentSpanId"] = JsonValue::String(to_hex(parent_span_id));
+            }
+            json
+        })
+        .collect();
+
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [attribute_json("service.name", service_name)]
+            },
+            "scopeSpans": [{
+                "scope": { "name": "stacks-blockchain" },
+                "spans": spans
+            }]
+        }]
+    })
+}
+
+fn export_loop(endpoint: CollectorEndpoint, service_name: String, spans: Receiver<SpanData>) {
+    let mut batch = vec![];
+    loop {
+        let disconnected = match spans.recv_timeout(EXPORT_INTERVAL) {
+            Ok(span) => {
+                batch.push(span);
+                while batch.len() < MAX_EXPORT_BATCH {
+                    match spans.try_recv() {
+                        Ok(span) => batch.push(span),
+                        Err(_) => break,
+                    }
+                }
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => true,
+        };
+
+        if !batch.is_empty() {
+            let body = encode_spans(&service_name, &batch).to_string();
+            if let Err(e) = endpoint.post(body.as_bytes()) {
+                warn!("Failed to export {} span(s): {}", batch.len(), &e);
+            }
+            batch.clear();
+        }
+
+        let dropped = DROPPED_SPANS.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            warn!(
+                "Dropped {} span(s): the OTLP exporter is falling behind",
+                dropped
+            );
+        }
+
+        if disconnected {
+            return;
+        }
+    }
+}
+
+/// Start exporting spans to the OTLP/HTTP collector at `endpoint`.  Only a fraction
+/// `sample_ratio` of traces are recorded.
+pub fn start_exporter(endpoint: &str, service_name: &str, sample_ratio: f64) -> Result<(), String> {
+    if !(sample_ratio >= 0.0 && sample_ratio <= 1.0) {
+        return Err(format!(
+            "Invalid trace sample ratio {}: expected a number between 0 and 1",
+            sample_ratio
+        ));
+    }
+    let endpoint = CollectorEndpoint::parse(endpoint)?;
+    let mut sender = SPAN_SENDER
+        .lock()
+        .map_err(|_| "OTLP exporter state is poisoned".to_string())?;
+    if sender.is_some() {
+        return Err("OTLP exporter is already running".to_string());
+    }
+
+    let (span_tx, span_rx) = sync_channel(SPAN_QUEUE_LEN);
+    let service_name = service_name.to_string();
+    let endpoint_desc = format!(
+        "http://{}:{}{}",
+        &endpoint.host, endpoint.port, &endpoint.path
+    );
+    thread::Builder::new()
+        .name("otlp-exporter".to_string())
+        .spawn(move || export_loop(endpoint, service_name, span_rx))
+        .map_err(|e| format!("Failed to start OTLP exporter thread: {}", &e))?;
+
+    *sender = Some(span_tx);
+    SAMPLE_THRESHOLD.store(
+        (sample_ratio * (u64::max_value() as f64)) as u64,
+        Ordering::SeqCst,
+    );
+    ENABLED.store(true, Ordering::SeqCst);
+    info!(
+        "Exporting traces to {} (sampling {}%)",
+        &endpoint_desc,
+        sample_ratio * 100.0
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_collector_endpoint() {
+        assert_eq!(
+            CollectorEndpoint::parse("http://localhost:4318").unwrap(),
+            CollectorEndpoint {
+                host: "localhost".to_string(),
+                port: 4318,
+                path: "/v1/traces".to_string()
+            }
+        );
+        assert_eq!(
+            CollectorEndpoint::parse("http://10.0.0.1/otlp/v1/traces").unwrap(),
+            CollectorEndpoint {
+                host: "10.0.0.1".to_string(),
+                port: 4318,
+                path: "/otlp/v1/traces".to_string()
+            }
+        );
+        assert!(CollectorEndpoint::parse("https://localhost:4318").is_err());
+        assert!(CollectorEndpoint::parse("localhost").is_err());
+    }
+
+    #[test]
+    fn spans_nest_on_a_thread() {
+        // the exporter is not running, so these spans go nowhere, but they are still recorded
+        let outer = start_span("outer");
+        let outer_data = outer.data.clone().unwrap();
+        assert!(outer_data.parent_span_id.is_none());
+
+        let inner = start_child_span("inner").unwrap();
+        let inner_data = inner.data.clone().unwrap();
+        assert_eq!(inner_data.trace_id, outer_data.trace_id);
+        assert_eq!(inner_data.parent_span_id, Some(outer_data.span_id));
+        drop(inner);
+        drop(outer);
+
+        // no open span, so no child span
+        assert!(start_child_span("orphan").is_none());
+        assert!(current_context().is_none());
+    }
+
+    #[test]
+    fn encode_otlp_json() {
+        let span = SpanData {
+            trace_id: [1; 16],
+            span_id: [2; 8],
+            parent_span_id: Some([3; 8]),
+            name: "rpc.request",
+            start_time_ns: 1000,
+            end_time_ns: 2000,
+            attributes: vec![("http.route", "/v2/info".to_string())],
+        };
+        let json = encode_spans("stacks-node", &[span]);
+        let resource_spans = &json["resourceSpans"][0];
+        assert_eq!(
+            resource_spans["resource"]["attributes"][0]["value"]["stringValue"],
+            "stacks-node"
+        );
+        let span = &resource_spans["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["traceId"], "01010101010101010101010101010101");
+        assert_eq!(span["spanId"], "0202020202020202");
+        assert_eq!(span["parentSpanId"], "0303030303030303");
+        assert_eq!(span["startTimeUnixNano"], "1000");
+        assert_eq!(span["attributes"][0]["key"], "http.route");
+    }
+}
@@ -25,6 +25,12 @@ lazy_static! {
         labels! {"handler" => "all",}
     )).unwrap();
 
+    pub static ref RPC_REQUEST_TIMEOUTS_COUNTER_VEC: IntCounterVec = register_int_counter_vec!(
+        "stacks_node_rpc_request_timeouts",
+        "Total count of RPC requests answered with a 503 for missing their deadline, by endpoint class",
+        &["class"]
+    ).unwrap();
+
     pub static ref RPC_CALL_LATENCIES_HISTOGRAM: HistogramVec = register_histogram_vec!(histogram_opts!(
         "stacks_node_rpc_call_latencies_histogram",
         "Time (seconds) measuring RPC calls latency"
@@ -106,6 +112,51 @@ lazy_static! {
         "Burnchain tip height"
     )).unwrap();
 
+    pub static ref MISSING_POX_ANCHORS_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_missing_pox_anchors",
+        "Number of selected PoX anchor blocks that this node does not have"
+    )).unwrap();
+
+    pub static ref POX_REWARD_CYCLE_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_pox_reward_cycle",
+        "The most recently started PoX reward cycle"
+    )).unwrap();
+
+    pub static ref POX_STACKED_USTX_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_pox_stacked_ustx",
+        "Total uSTX stacked in the current PoX reward cycle"
+    )).unwrap();
+
+    pub static ref POX_STACKERS_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_pox_stackers",
+        "Number of PoX address registrations in the current PoX reward cycle"
+    )).unwrap();
+
+    pub static ref POX_REWARD_SLOTS_FILLED_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_pox_reward_slots_filled",
+        "Number of reward slots claimed in the current PoX reward cycle"
+    )).unwrap();
+
+    pub static ref POX_ANCHOR_BLOCK_KNOWN_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_pox_anchor_block_known",
+        "1 if the current PoX reward cycle's anchor block is known (or none was selected), 0 otherwise"
+    )).unwrap();
+
+    pub static ref DISK_FREE_BYTES_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_disk_free_bytes",
+        "Free space, in bytes, on the volume holding the node's chainstate"
+    )).unwrap();
+
+    pub static ref CONNECTION_BUFFER_BYTES_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_connection_buffer_bytes",
+        "Bytes set aside in the read and write buffers of the node's p2p and HTTP connections"
+    )).unwrap();
+
+    pub static ref SQLITE_MEMORY_USED_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_sqlite_memory_used_bytes",
+        "Bytes allocated by SQLite, including the MARF and mempool page caches"
+    )).unwrap();
+
     pub static ref INBOUND_NEIGHBORS_GAUGE: IntGauge = register_int_gauge!(opts!(
         "stacks_node_neighbors_inbound",
         "Total count of current known inbound neighbors"
@@ -148,6 +199,87 @@ lazy_static! {
         "Total count of all mempool garbage collections"
     )).unwrap();
 
+    pub static ref TX_RELAYS_AVOIDED_COUNTER: IntCounter = register_int_counter!(opts!(
+        "stacks_node_tx_relays_avoided",
+        "Total count of transactions not relayed to a peer whose transactions filter showed it already had them"
+    )).unwrap();
+
+    pub static ref TX_RELAYS_DECLINED_COUNTER_VEC: IntCounterVec = register_int_counter_vec!(
+        "stacks_node_tx_relays_declined",
+        "Total count of new transactions the relay policy declined to forward to peers, by the rule they broke",
+        &["rule"]
+    ).unwrap();
+
+    pub static ref TX_FILTERS_SENT_COUNTER: IntCounter = register_int_counter!(opts!(
+        "stacks_node_tx_filters_sent",
+        "Total count of transactions filters sent to peers"
+    )).unwrap();
+
+    pub static ref HOOK_RUNS_COUNTER_VEC: IntCounterVec = register_int_counter_vec!(
+        "stacks_node_hook_runs",
+        "Total count of hook commands run, by hook",
+        &["hook"]
+    ).unwrap();
+
+    pub static ref HOOK_FAILURES_COUNTER_VEC: IntCounterVec = register_int_counter_vec!(
+        "stacks_node_hook_failures",
+        "Total count of hook commands that failed, timed out, or were never run, by hook and reason",
+        &["hook", "reason"]
+    ).unwrap();
+
+    pub static ref ATLAS_ATTACHMENTS_STORAGE_BYTES_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_atlas_attachments_storage_bytes",
+        "Bytes taken up by the contents of the attachments in the Atlas store"
+    )).unwrap();
+
+    pub static ref ATLAS_ATTACHMENTS_COUNT_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_atlas_attachments",
+        "Number of instantiated attachments in the Atlas store"
+    )).unwrap();
+
+    pub static ref ATLAS_ATTACHMENTS_REJECTED_COUNTER_VEC: IntCounterVec = register_int_counter_vec!(
+        "stacks_node_atlas_attachments_rejected",
+        "Total count of attachments rejected before being stored or served, by the rule they broke",
+        &["rule"]
+    ).unwrap();
+
+    pub static ref ATLAS_ATTACHMENTS_EVICTED_COUNTER: IntCounter = register_int_counter!(opts!(
+        "stacks_node_atlas_attachments_evicted",
+        "Total count of attachments evicted from the Atlas store"
+    )).unwrap();
+
+    pub static ref PUSH_QUEUE_DEPTH_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_push_queue_depth",
+        "Number of blocks and microblock streams pushed by peers and waiting to be processed"
+    )).unwrap();
+
+    pub static ref PUSH_QUEUE_DROPS_COUNTER_VEC: IntCounterVec = register_int_counter_vec!(
+        "stacks_node_push_queue_drops",
+        "Total count of blocks and microblock streams pushed by peers and dropped from the push queue, by reason",
+        &["reason"]
+    ).unwrap();
+
+    pub static ref STALE_TIP_RESETS_COUNTER: IntCounter = register_int_counter!(opts!(
+        "stacks_node_stale_tip_resets",
+        "Total count of times the Stacks tip stopped advancing and this node reset its block sync and outbound peers"
+    )).unwrap();
+
+    pub static ref MARF_READONLY_HANDLES_GAUGE: IntGauge = register_int_gauge!(opts!(
+        "stacks_node_marf_readonly_handles",
+        "Number of read-only MARF handles open, in use or idle in the handle pool"
+    )).unwrap();
+
+    pub static ref MARF_HANDLE_REQUESTS_COUNTER_VEC: IntCounterVec = register_int_counter_vec!(
+        "stacks_node_marf_handle_requests",
+        "Total count of read-only MARF handles requested, by whether an idle one was reused or a new one opened",
+        &["outcome"]
+    ).unwrap();
+
+    pub static ref MARF_HANDLE_EVICTIONS_COUNTER: IntCounter = register_int_counter!(opts!(
+        "stacks_node_marf_handle_evictions",
+        "Total count of idle read-only MARF handles closed to keep under the open handle cap"
+    )).unwrap();
+
     pub static ref CONTRACT_CALLS_PROCESSED_COUNT: IntCounter = register_int_counter!(opts!(
         "stacks_contract_calls_processed",
         "Total count of processed contract calls"
@@ -196,3 +328,52 @@ pub fn new_rpc_call_timer(path: &str) -> HistogramTimer {
     let histogram = RPC_CALL_LATENCIES_HISTOGRAM.with_label_values(&[path]);
     histogram.start_timer()
 }
+
+/// The counters whose totals `get_persisted_counter_totals()` and
+/// `restore_persisted_counter_totals()` carry across restarts, by metric name
+pub fn persisted_counters() -> Vec<(&'static str, &'static IntCounter)> {
+    vec![
+        ("stacks_node_rpc_requests_total", &*RPC_CALL_COUNTER),
+        (
+            "stacks_node_stx_blocks_received_total",
+            &*STX_BLOCKS_RECEIVED_COUNTER,
+        ),
+        (
+            "stacks_node_stx_micro_blocks_received_total",
+            &*STX_MICRO_BLOCKS_RECEIVED_COUNTER,
+        ),
+        (
+            "stacks_node_stx_blocks_served_total",
+            &*STX_BLOCKS_SERVED_COUNTER,
+        ),
+        (
+            "stacks_node_stx_micro_blocks_served_total",
+            &*STX_MICRO_BLOCKS_SERVED_COUNTER,
+        ),
+        (
+            "stacks_node_stx_confirmed_micro_blocks_served_total",
+            &*STX_CONFIRMED_MICRO_BLOCKS_SERVED_COUNTER,
+        ),
+        (
+            "stacks_node_transactions_received_total",
+            &*TXS_RECEIVED_COUNTER,
+        ),
+        (
+            "stacks_node_btc_blocks_received_total",
+            &*BTC_BLOCKS_RECEIVED_COUNTER,
+        ),
+        ("stacks_node_btc_ops_sent_total", &*BTC_OPS_SENT_COUNTER),
+        (
+            "stacks_node_stx_blocks_processed_total",
+            &*STX_BLOCKS_PROCESSED_COUNTER,
+        ),
+        (
+            "stacks_node_stx_blocks_mined_total",
+            &*STX_BLOCKS_MINED_COUNTER,
+        ),
+        (
+            "stacks_contract_calls_processed",
+            &*CONTRACT_CALLS_PROCESSED_COUNT,
+        ),
+    ]
+}
@@ -0,0 +1,436 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Runtime toggles an operator can flip through the admin RPC endpoints, without restarting the
+//! node: pausing mining, pausing block downloads, pausing transaction relay, and logging at debug
+//! level from specific subsystems.  The toggles are shared between the p2p thread (which serves
+//! the endpoints and runs the downloader) and the relayer (which relays transactions and mines).
+//!
+//! The node itself sets one more: `low_disk_space`, while the chainstate volume is nearly full.
+//! It stops the node from taking in new blocks, microblocks and transactions, and from mining, so
+//! that the databases are not left half-written when the disk fills up.
+//!
+//! The admin endpoints can also submit a transfer-STX burnchain operation from the node's own
+//! burnchain signer, if the node knows how to reach its burnchain.  The request is checked here;
+//! the node supplies the `BurnOpSubmitter` that queues it, to be built, funded and broadcast off
+//! the p2p thread.
+//!
+//! Likewise, a mining node can report its burnchain wallet through a `MinerWalletReader`, so that
+//! dashboards can tell how many more block-commits the miner can pay for.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use address::AddressHashMode;
+use burnchains::Address;
+use chainstate::stacks::{
+    StacksPublicKey, C32_ADDRESS_VERSION_MAINNET_SINGLESIG, C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+};
+use net::{
+    RPCAdminTogglesData, RPCAdminTogglesUpdate, RPCBurnOpSubmissionData, RPCMinerWalletData,
+    RPCTransferStxOpRequest,
+};
+use util::hash::hex_bytes;
+use util::log;
+
+use crate::types::chainstate::StacksAddress;
+
+/// The longest memo a transfer-STX operation can carry
+pub const TRANSFER_STX_MAX_MEMO_LEN: usize = 61;
+
+/// The highest fee rate, in satoshis per byte, a burn operation request may ask for.  This guards
+/// against a mistyped fee draining the sender's burnchain wallet.
+pub const MAX_BURN_OP_FEE_RATE: u64 = 1000;
+
+/// The token an admin request presents in its `Authorization: Bearer` header.  It is never
+/// printed, so requests can be logged without leaking it.
+#[derive(Clone, PartialEq)]
+pub struct BearerToken(pub String);
+
+impl fmt::Debug for BearerToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BearerToken(<redacted>)")
+    }
+}
+
+impl BearerToken {
+    /// Compare against the configured token in time that does not depend on where they differ
+    pub fn matches(&self, expected: &str) -> bool {
+        let given = self.0.as_bytes();
+        let expected = expected.as_bytes();
+        if given.len() != expected.len() {
+            return false;
+        }
+        given
+            .iter()
+            .zip(expected.iter())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RuntimeToggles {
+    mining_paused: AtomicBool,
+    downloads_paused: AtomicBool,
+    tx_relay_paused: AtomicBool,
+    low_disk_space: AtomicBool,
+}
+
+impl RuntimeToggles {
+    /// Whether the miner should refrain from starting new (micro)block tenures
+    pub fn mining_paused(&self) -> bool {
+        self.mining_paused.load(Ordering::SeqCst)
+    }
+
+    /// Whether the block and microblock downloader should stay idle
+    pub fn downloads_paused(&self) -> bool {
+        self.downloads_paused.load(Ordering::SeqCst)
+    }
+
+    /// Whether new transactions should be stored without being forwarded to neighbors
+    pub fn tx_relay_paused(&self) -> bool {
+        self.tx_relay_paused.load(Ordering::SeqCst)
+    }
+
+    /// Whether the node should refuse new blocks, microblocks and transactions, and stop mining,
+    /// because it is low on disk space
+    pub fn low_disk_space(&self) -> bool {
+        self.low_disk_space.load(Ordering::SeqCst)
+    }
+
+    pub fn set_low_disk_space(&self, low: bool) {
+        self.low_disk_space.store(low, Ordering::SeqCst);
+    }
+
+    pub fn snapshot(&self) -> RPCAdminTogglesData {
+        RPCAdminTogglesData {
+            mining_paused: self.mining_paused(),
+            block_downloads_paused: self.downloads_paused(),
+            tx_relay_paused: self.tx_relay_paused(),
+            debug_subsystems: log::get_debug_subsystems(),
+            low_disk_space: self.low_disk_space(),
+        }
+    }
+
+    /// Apply the toggles given in `update`, leaving the others as they are.
+    pub fn apply(&self, update: &RPCAdminTogglesUpdate) {
+        if let Some(paused) = update.mining_paused {
+            self.mining_paused.store(paused, Ordering::SeqCst);
+        }
+        if let Some(paused) = update.block_downloads_paused {
+            self.downloads_paused.store(paused, Ordering::SeqCst);
+        }
+        if let Some(paused) = update.tx_relay_paused {
+            self.tx_relay_paused.store(paused, Ordering::SeqCst);
+        }
+        if let Some(ref subsystems) = update.debug_subsystems {
+            log::set_debug_subsystems(subsystems.clone());
+        }
+    }
+}
+
+/// A checked request to move STX with a transfer-STX burnchain operation.  Who sends the STX,
+/// and pays the burnchain fees, is up to whoever submits it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransferStxOpRequest {
+    pub recipient: StacksAddress,
+    pub amount_ustx: u128,
+    pub memo: Vec<u8>,
+    /// Satoshis per byte; the node's configured fee rate if not given
+    pub fee_rate: Option<u64>,
+}
+
+impl TransferStxOpRequest {
+    pub fn new(
+        recipient: StacksAddress,
+        amount_ustx: u128,
+        memo: Vec<u8>,
+        fee_rate: Option<u64>,
+        mainnet: bool,
+    ) -> Result<TransferStxOpRequest, String> {
+        if amount_ustx == 0 {
+            return Err("amount_ustx must be positive".to_string());
+        }
+        if memo.len() > TRANSFER_STX_MAX_MEMO_LEN {
+            return Err(format!(
+                "memo is {} bytes; at most {} are allowed",
+                memo.len(),
+                TRANSFER_STX_MAX_MEMO_LEN
+            ));
+        }
+        if recipient.is_mainnet() != mainnet {
+            return Err(format!(
+                "recipient {} is not a {} address",
+                &recipient,
+                if mainnet { "mainnet" } else { "testnet" }
+            ));
+        }
+        match fee_rate {
+            Some(0) => return Err("fee_rate must be positive".to_string()),
+            Some(rate) if rate > MAX_BURN_OP_FEE_RATE => {
+                return Err(format!(
+                    "fee_rate {} is above the limit of {} sats/byte",
+                    rate, MAX_BURN_OP_FEE_RATE
+                ));
+            }
+            _ => {}
+        }
+        Ok(TransferStxOpRequest {
+            recipient,
+            amount_ustx,
+            memo,
+            fee_rate,
+        })
+    }
+
+    pub fn from_rpc(
+        request: &RPCTransferStxOpRequest,
+        mainnet: bool,
+    ) -> Result<TransferStxOpRequest, String> {
+        let recipient = StacksAddress::from_string(&request.recipient)
+            .ok_or_else(|| format!("recipient {} is not a Stacks address", &request.recipient))?;
+        let memo = match request.memo {
+            Some(ref memo) => hex_bytes(memo).map_err(|_| "memo is not hex".to_string())?,
+            None => vec![],
+        };
+        TransferStxOpRequest::new(
+            recipient,
+            request.amount_ustx as u128,
+            memo,
+            request.fee_rate,
+            mainnet,
+        )
+    }
+
+    /// Check that the transfer can be sent from `sender`
+    pub fn check_sender(&self, sender: &StacksAddress) -> Result<(), String> {
+        if *sender == self.recipient {
+            return Err("sender and recipient are the same address".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The STX address a transfer-STX operation signed with `public_key` is sent from
+pub fn transfer_stx_sender(public_key: &StacksPublicKey, mainnet: bool) -> StacksAddress {
+    let version = if mainnet {
+        C32_ADDRESS_VERSION_MAINNET_SINGLESIG
+    } else {
+        C32_ADDRESS_VERSION_TESTNET_SINGLESIG
+    };
+    StacksAddress::from_public_keys(
+        version,
+        &AddressHashMode::SerializeP2PKH,
+        1,
+        &vec![public_key.clone()],
+    )
+    .expect("FATAL: failed to make a single-sig address")
+}
+
+/// Something that can put burn operations on the burnchain with the node's own burnchain signer
+pub trait BurnOpSubmitter {
+    /// The STX address transfers are sent from: that of the node's burnchain signer, which also
+    /// pays the burnchain fees
+    fn sender(&self) -> StacksAddress;
+    /// Hand `request` off to be built, funded and broadcast.  This is called on the p2p thread,
+    /// so it must not block: the burnchain transactions are sent later, from another thread.
+    fn queue_transfer_stx(&self, request: TransferStxOpRequest) -> Result<(), String>;
+}
+
+/// The miner's burnchain wallet, as its burnchain node reports it
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinerWallet {
+    pub address: String,
+    /// The amounts of the wallet's UTXOs, in satoshis
+    pub utxo_amounts: Vec<u64>,
+    /// What one block-commit costs at the current fee settings, in satoshis
+    pub commit_cost: u64,
+}
+
+impl MinerWallet {
+    /// Each block-commit spends one UTXO and returns the change to the wallet as a new UTXO, so
+    /// a UTXO pays for as many commits in a row as its amount covers.  UTXOs smaller than one
+    /// commit pay for none.
+    pub fn to_rpc(&self) -> RPCMinerWalletData {
+        let commit_cost = self.commit_cost.max(1);
+        RPCMinerWalletData {
+            address: self.address.clone(),
+            balance_sats: self.utxo_amounts.iter().sum(),
+            utxo_count: self.utxo_amounts.len() as u64,
+            spendable_utxo_count: self
+                .utxo_amounts
+                .iter()
+                .filter(|amount| **amount >= commit_cost)
+                .count() as u64,
+            commit_cost_sats: self.commit_cost,
+            affordable_commits: self
+                .utxo_amounts
+                .iter()
+                .map(|amount| amount / commit_cost)
+                .sum(),
+        }
+    }
+}
+
+/// Something that can read the miner's burnchain wallet
+pub trait MinerWalletReader {
+    /// The wallet's UTXOs and the cost of a block-commit, or why they could not be read.  This is
+    /// called from the p2p thread, so it must not block on the burnchain: it reports the wallet
+    /// as it was last read by another thread.
+    fn read_miner_wallet(&self) -> Result<MinerWallet, String>;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use chainstate::stacks::StacksPrivateKey;
+
+    #[test]
+    fn bearer_token_matches() {
+        let token = BearerToken("s3cret".to_string());
+        assert!(token.matches("s3cret"));
+        assert!(!token.matches("s3cre"));
+        assert!(!token.matches("s3cres"));
+        assert!(!token.matches(""));
+        assert_eq!(format!("{:?}", &token), "BearerToken(<redacted>)");
+    }
+
+    #[test]
+    fn apply_runtime_toggles() {
+        let toggles = RuntimeToggles::default();
+        assert!(!toggles.mining_paused());
+        assert!(!toggles.downloads_paused());
+        assert!(!toggles.tx_relay_paused());
+
+        toggles.apply(&RPCAdminTogglesUpdate {
+            mining_paused: Some(true),
+            tx_relay_paused: Some(true),
+            ..RPCAdminTogglesUpdate::default()
+        });
+        assert!(toggles.mining_paused());
+        assert!(!toggles.downloads_paused());
+        assert!(toggles.tx_relay_paused());
+
+        toggles.apply(&RPCAdminTogglesUpdate {
+            mining_paused: Some(false),
+            block_downloads_paused: Some(true),
+            debug_subsystems: Some(vec!["net::download".to_string()]),
+            ..RPCAdminTogglesUpdate::default()
+        });
+        let snapshot = toggles.snapshot();
+        assert!(!snapshot.mining_paused);
+        assert!(snapshot.block_downloads_paused);
+        assert!(snapshot.tx_relay_paused);
+        assert_eq!(snapshot.debug_subsystems, vec!["net::download".to_string()]);
+
+        assert!(log::is_debug_subsystem("blockstack_lib::net::download"));
+        assert!(log::is_debug_subsystem(
+            "blockstack_lib::net::download::inner"
+        ));
+        assert!(!log::is_debug_subsystem("blockstack_lib::net::downloader"));
+        assert!(!log::is_debug_subsystem("blockstack_lib::net::p2p"));
+
+        toggles.apply(&RPCAdminTogglesUpdate {
+            debug_subsystems: Some(vec![]),
+            ..RPCAdminTogglesUpdate::default()
+        });
+        assert!(!log::is_debug_subsystem("blockstack_lib::net::download"));
+        assert_eq!(toggles.snapshot().debug_subsystems, Vec::<String>::new());
+    }
+
+    #[test]
+    fn miner_wallet_projection() {
+        let wallet = MinerWallet {
+            address: "mkvJ3xUHbjMFa3qjaXSRKtGbyrxe3JMeoi".to_string(),
+            utxo_amounts: vec![25_000, 9_999, 10_000, 100],
+            commit_cost: 10_000,
+        };
+        let data = wallet.to_rpc();
+        assert_eq!(data.balance_sats, 45_099);
+        assert_eq!(data.utxo_count, 4);
+        assert_eq!(data.spendable_utxo_count, 2);
+        assert_eq!(data.commit_cost_sats, 10_000);
+        // 2 commits from the first UTXO, 1 from the third, and none from the rest
+        assert_eq!(data.affordable_commits, 3);
+
+        let empty = MinerWallet {
+            utxo_amounts: vec![],
+            ..wallet
+        };
+        assert_eq!(empty.to_rpc().affordable_commits, 0);
+        assert_eq!(empty.to_rpc().balance_sats, 0);
+    }
+
+    #[test]
+    fn check_transfer_stx_op_request() {
+        let rpc_request = RPCTransferStxOpRequest {
+            recipient: "ST2QKZ4FKHAH1NQKYKYAYZPY440FEPK7GZ1R5HBP2".to_string(),
+            amount_ustx: 1000,
+            memo: Some("0102".to_string()),
+            fee_rate: Some(10),
+        };
+        let request = TransferStxOpRequest::from_rpc(&rpc_request, false).unwrap();
+        assert_eq!(request.amount_ustx, 1000);
+        assert_eq!(request.memo, vec![1, 2]);
+
+        let sender_key = StacksPrivateKey::from_hex(
+            "9f1f85a512a96a244e4c0d762788500687feb97481639572e3bffbd6860e6ab001",
+        )
+        .unwrap();
+        let sender = transfer_stx_sender(&StacksPublicKey::from_private(&sender_key), false);
+        assert_eq!(
+            sender,
+            StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R").unwrap()
+        );
+        assert!(request.check_sender(&sender).is_ok());
+        assert!(request.check_sender(&request.recipient).is_err());
+
+        // a testnet recipient can't be paid on mainnet
+        assert!(TransferStxOpRequest::from_rpc(&rpc_request, true).is_err());
+
+        let bad_requests = vec![
+            RPCTransferStxOpRequest {
+                amount_ustx: 0,
+                ..rpc_request.clone()
+            },
+            RPCTransferStxOpRequest {
+                memo: Some("00".repeat(TRANSFER_STX_MAX_MEMO_LEN + 1)),
+                ..rpc_request.clone()
+            },
+            RPCTransferStxOpRequest {
+                memo: Some("not hex".to_string()),
+                ..rpc_request.clone()
+            },
+            RPCTransferStxOpRequest {
+                fee_rate: Some(MAX_BURN_OP_FEE_RATE + 1),
+                ..rpc_request.clone()
+            },
+            RPCTransferStxOpRequest {
+                recipient: "not an address".to_string(),
+                ..rpc_request.clone()
+            },
+        ];
+        for bad_request in bad_requests.iter() {
+            assert!(
+                TransferStxOpRequest::from_rpc(bad_request, false).is_err(),
+                "{:?}",
+                bad_request
+            );
+        }
+    }
+}
@@ -19,15 +19,19 @@ use rusqlite::Row;
 use rusqlite::Transaction;
 use rusqlite::{Connection, OpenFlags, NO_PARAMS};
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::convert::From;
 use std::convert::TryFrom;
 use std::fs;
 
+use monitoring::increment_atlas_attachments_rejected;
 use util::db::tx_begin_immediate;
+use util::db::tx_busy_handler;
 use util::db::DBConn;
 use util::db::Error as db_error;
-use util::db::{query_count, query_int, query_row, query_rows, u64_to_sql, FromColumn, FromRow};
+use util::db::{
+    query_count, query_int, query_row, query_rows, sql_pragma, u64_to_sql, FromColumn, FromRow,
+};
 
 use util;
 use util::hash::{bin_bytes, hex_bytes, to_bin, to_hex, Hash160};
@@ -103,6 +107,15 @@ impl FromRow<AttachmentInstance> for AttachmentInstance {
     }
 }
 
+impl FromRow<(Hash160, u64)> for (Hash160, u64) {
+    fn from_row<'a>(row: &'a Row) -> Result<(Hash160, u64), db_error> {
+        let hex_hash: String = row.get_unwrap(0);
+        let hash = Hash160::from_hex(&hex_hash).map_err(|_| db_error::TypeError)?;
+        let size = u64::from_column(row, "size")?;
+        Ok((hash, size))
+    }
+}
+
 impl FromRow<(u32, u32)> for (u32, u32) {
     fn from_row<'a>(row: &'a Row) -> Result<(u32, u32), db_error> {
         let t1: u32 = row.get_unwrap(0);
@@ -166,13 +179,29 @@ impl AtlasDB {
             );
             return false;
         }
-        if attachment.content.len() as u32 > self.atlas_config.attachments_max_size {
-            info!("Atlas: will discard posted attachment - attachment too large");
+        if let Err(e) = self.validate_attachment(attachment) {
+            info!("Atlas: will discard posted attachment - {}", e);
             return false;
         }
         true
     }
 
+    /// Check an attachment against `attachments_max_size` and then each configured validator,
+    /// counting the rejection against the first rule it breaks
+    pub fn validate_attachment(&self, attachment: &Attachment) -> Result<(), String> {
+        if attachment.content.len() as u32 > self.atlas_config.attachments_max_size {
+            increment_atlas_attachments_rejected("size");
+            return Err("attachment too large".to_string());
+        }
+        for validator in self.atlas_config.attachment_validators.iter() {
+            if let Err(e) = validator.validate(attachment) {
+                increment_atlas_attachments_rejected(validator.name());
+                return Err(format!("rejected by rule '{}': {}", validator.name(), e));
+            }
+        }
+        Ok(())
+    }
+
     // Open the burn database at the given path.  Open read-only or read/write.
     // If opened for read/write and it doesn't exist, instantiate it.
     pub fn connect(
@@ -199,6 +228,12 @@ impl AtlasDB {
         };
         let conn =
             Connection::open_with_flags(path, open_flags).map_err(|e| db_error::SqliteError(e))?;
+        conn.busy_handler(Some(tx_busy_handler))?;
+        if readwrite {
+            // with a write-ahead log, read-only connections from other processes never hold up
+            // this one's writes
+            sql_pragma(&conn, "PRAGMA journal_mode = WAL;")?;
+        }
 
         let mut db = AtlasDB {
             atlas_config,
@@ -471,4 +506,144 @@ impl AtlasDB {
         tx.commit().map_err(db_error::SqliteError)?;
         Ok(())
     }
+
+    /// Total size, in bytes, of the attachments' contents, instantiated or not
+    pub fn get_attachments_storage_bytes(&self) -> Result<u64, db_error> {
+        let qry = "SELECT IFNULL(SUM(LENGTH(content)), 0) FROM attachments";
+        let bytes = query_int(&self.conn, qry, NO_PARAMS)?;
+        Ok(bytes as u64)
+    }
+
+    pub fn count_instantiated_attachments(&self) -> Result<u32, db_error> {
+        let qry = "SELECT COUNT(rowid) FROM attachments
+                   WHERE was_instantiated = 1";
+        let count = query_count(&self.conn, qry, NO_PARAMS)? as u32;
+        Ok(count)
+    }
+
+    /// The instantiated attachments that no BNS name refers to any more, oldest first, with their
+    /// sizes.  An attachment is unreferenced once each of its instances is in the canonical fork
+    /// (per `is_canonical`) and has been superseded by a later instance for the same name in the
+    /// canonical fork.  Attachments without any instances (like the genesis zonefiles), or with an
+    /// instance whose metadata does not name a BNS name, are always kept.
+    pub fn find_unreferenced_attachments<F>(
+        &self,
+        mut is_canonical: F,
+    ) -> Result<Vec<(Hash160, u64)>, db_error>
+    where
+        F: FnMut(&StacksBlockId) -> bool,
+    {
+        let qry = "SELECT * FROM attachment_instances WHERE is_available = 1";
+        let instances = query_rows::<AttachmentInstance, _>(&self.conn, qry, NO_PARAMS)?;
+
+        let mut canonical_blocks = HashMap::new();
+        let mut named_instances = vec![];
+        let mut latest_indexes = HashMap::new();
+        let mut referenced = HashSet::new();
+        let mut instantiated = HashSet::new();
+        for instance in instances.into_iter() {
+            instantiated.insert(instance.content_hash.clone());
+            let canonical = *canonical_blocks
+                .entry(instance.index_block_hash.clone())
+                .or_insert_with(|| is_canonical(&instance.index_block_hash));
+            match instance.get_bns_name() {
+                Some(name) if canonical => {
+                    let key = (instance.contract_id.clone(), name);
+                    let latest = latest_indexes.entry(key.clone()).or_insert(0);
+                    *latest = (*latest).max(instance.attachment_index);
+                    named_instances.push((key, instance));
+                }
+                _ => {
+                    // might still be (or become) the zonefile of some name
+                    referenced.insert(instance.content_hash.clone());
+                }
+            }
+        }
+        for (key, instance) in named_instances.into_iter() {
+            if latest_indexes.get(&key) == Some(&instance.attachment_index) {
+                referenced.insert(instance.content_hash);
+            }
+        }
+
+        let qry = "SELECT hash, LENGTH(content) AS size FROM attachments WHERE was_instantiated = 1 ORDER BY created_at ASC";
+        let attachments = query_rows::<(Hash160, u64), _>(&self.conn, qry, NO_PARAMS)?;
+        Ok(attachments
+            .into_iter()
+            .filter(|(hash, _)| instantiated.contains(hash) && !referenced.contains(hash))
+            .collect())
+    }
+
+    /// Delete attachments, along with the instances that resolved to them.  Our attachment
+    /// inventory reports those instances as missing from then on.
+    pub fn evict_attachments(&mut self, content_hashes: &[Hash160]) -> Result<(), db_error> {
+        let tx = self.tx_begin()?;
+        for content_hash in content_hashes.iter() {
+            let hex_content_hash = to_hex(&content_hash.0[..]);
+            tx.execute(
+                "DELETE FROM attachments WHERE hash = ?1",
+                &[&hex_content_hash as &dyn ToSql],
+            )
+            .map_err(db_error::SqliteError)?;
+            tx.execute(
+                "DELETE FROM attachment_instances WHERE content_hash = ?1 AND is_available = 1",
+                &[&hex_content_hash as &dyn ToSql],
+            )
+            .map_err(db_error::SqliteError)?;
+        }
+        tx.commit().map_err(db_error::SqliteError)?;
+        Ok(())
+    }
+
+    /// Evict unreferenced attachments, if configured to, and then bring the store under its
+    /// quota, if it has one: uninstantiated attachments go first, then unreferenced ones, oldest
+    /// first.  `is_canonical` says whether a Stacks block is in the canonical fork.  Returns how
+    /// many attachments were evicted.
+    pub fn gc_attachments_storage<F>(&mut self, is_canonical: F) -> Result<u32, db_error>
+    where
+        F: FnMut(&StacksBlockId) -> bool,
+    {
+        let evict_unreferenced = self.atlas_config.evict_unreferenced_attachments;
+        let max_bytes = self.atlas_config.max_attachments_storage_bytes;
+        if !evict_unreferenced && max_bytes.is_none() {
+            return Ok(0);
+        }
+
+        let mut unreferenced = self.find_unreferenced_attachments(is_canonical)?;
+        let mut evicted = vec![];
+        if evict_unreferenced {
+            evicted.extend(unreferenced.drain(..).map(|(hash, _)| hash));
+            self.evict_attachments(&evicted)?;
+        }
+
+        if let Some(max_bytes) = max_bytes {
+            let mut used_bytes = self.get_attachments_storage_bytes()?;
+            if used_bytes > max_bytes {
+                let qry = "SELECT hash, LENGTH(content) AS size FROM attachments WHERE was_instantiated = 0 ORDER BY created_at ASC";
+                let uninstantiated = query_rows::<(Hash160, u64), _>(&self.conn, qry, NO_PARAMS)?;
+
+                let mut over_quota = vec![];
+                for (hash, size) in uninstantiated.into_iter().chain(unreferenced.into_iter()) {
+                    if used_bytes <= max_bytes {
+                        break;
+                    }
+                    used_bytes = used_bytes.saturating_sub(size);
+                    over_quota.push(hash);
+                }
+                self.evict_attachments(&over_quota)?;
+                evicted.append(&mut over_quota);
+
+                if used_bytes > max_bytes {
+                    warn!(
+                        "Atlas: attachments still take up {} bytes, over the {} byte quota, after evicting every unreferenced attachment",
+                        used_bytes, max_bytes
+                    );
+                }
+            }
+        }
+
+        if evicted.len() > 0 {
+            debug!("Atlas: evicted {} attachments", evicted.len());
+        }
+        Ok(evicted.len() as u32)
+    }
 }
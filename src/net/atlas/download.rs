@@ -164,6 +164,18 @@ impl AttachmentsDownloader {
         match progress {
             AttachmentsBatchStateMachine::Done(ref mut context) => {
                 for attachment in context.attachments.drain() {
+                    if let Err(e) = network.atlasdb.validate_attachment(&attachment) {
+                        // Stop asking for it.  Its instances stay unresolved until they expire.
+                        warn!(
+                            "Atlas: discarding downloaded attachment {} - {}",
+                            attachment.hash(),
+                            e
+                        );
+                        context
+                            .attachments_batch
+                            .resolve_attachment(&attachment.hash());
+                        continue;
+                    }
                     let attachments_instances = network
                         .atlasdb
                         .find_all_attachment_instances(&attachment.hash())
@@ -17,6 +17,7 @@
 use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
 use regex::Regex;
 
@@ -26,19 +27,22 @@ use crate::util::boot::boot_code_id;
 use burnchains::Txid;
 use chainstate::burn::db::sortdb::SortitionDB;
 use chainstate::burn::ConsensusHash;
-use util::hash::{to_hex, Hash160, MerkleHashFunc};
+use util::hash::{hex_bytes, to_hex, Hash160, MerkleHashFunc};
 use vm::types::{QualifiedContractIdentifier, SequenceData, TupleData, Value};
 
 use crate::types::chainstate::{BlockHeaderHash, StacksBlockHeader};
 
 pub use self::db::AtlasDB;
 pub use self::download::AttachmentsDownloader;
+pub use self::validation::AttachmentValidator;
 
 pub mod db;
 pub mod download;
+pub mod validation;
 
 pub const MAX_ATTACHMENT_INV_PAGES_PER_REQUEST: usize = 8;
 pub const MAX_RETRY_DELAY: u64 = 600; // seconds
+pub const ATTACHMENTS_GC_INTERVAL: u64 = 600; // seconds
 
 lazy_static! {
     pub static ref BNS_CHARS_REGEX: Regex = Regex::new("^([a-z0-9]|[-_])*$").unwrap();
@@ -52,6 +56,17 @@ pub struct AtlasConfig {
     pub uninstantiated_attachments_expire_after: u32,
     pub unresolved_attachment_instances_expire_after: u32,
     pub genesis_attachments: Option<Vec<Attachment>>,
+    /// Evict attachments once their contents take up more than this many bytes: uninstantiated
+    /// attachments first, then attachments no BNS name refers to any more, oldest first.
+    /// Attachments that are still referenced are never evicted.  Unbounded if None.
+    pub max_attachments_storage_bytes: Option<u64>,
+    /// Evict attachments no BNS name refers to any more, even while under quota
+    pub evict_unreferenced_attachments: bool,
+    /// How often (in seconds) to garbage-collect the attachments store
+    pub attachments_gc_interval: u64,
+    /// Rules an attachment must pass, besides `attachments_max_size`, before it is stored.  None
+    /// by default.
+    pub attachment_validators: Vec<Arc<dyn AttachmentValidator>>,
 }
 
 impl AtlasConfig {
@@ -65,6 +80,10 @@ impl AtlasConfig {
             uninstantiated_attachments_expire_after: 3_600,
             unresolved_attachment_instances_expire_after: 172_800,
             genesis_attachments: None,
+            max_attachments_storage_bytes: None,
+            evict_unreferenced_attachments: false,
+            attachments_gc_interval: ATTACHMENTS_GC_INTERVAL,
+            attachment_validators: vec![],
         }
     }
 }
@@ -154,6 +173,22 @@ impl AttachmentInstance {
         }
         None
     }
+
+    /// The (namespace, name) pair this instance sets the zonefile of, if its metadata is a BNS
+    /// `{ name, namespace }` tuple
+    pub fn get_bns_name(&self) -> Option<(Vec<u8>, Vec<u8>)> {
+        let bytes = hex_bytes(&self.metadata).ok()?;
+        match Value::consensus_deserialize(&mut &bytes[..]).ok()? {
+            Value::Tuple(ref metadata) => match (metadata.get("namespace"), metadata.get("name")) {
+                (
+                    Ok(Value::Sequence(SequenceData::Buffer(namespace))),
+                    Ok(Value::Sequence(SequenceData::Buffer(name))),
+                ) => Some((namespace.data.clone(), name.data.clone())),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -16,9 +16,11 @@
 
 use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::convert::TryFrom;
+use std::sync::Arc;
 use std::thread;
 use std::time;
 
+use crate::codec::StacksMessageCodec;
 use crate::types::chainstate::StacksBlockId;
 use crate::util::boot::boot_code_id;
 use burnchains::Txid;
@@ -29,9 +31,9 @@ use net::{
     AttachmentPage, GetAttachmentsInvResponse, HttpResponseMetadata, HttpResponseType, HttpVersion,
     PeerHost, Requestable,
 };
-use util::hash::Hash160;
+use util::hash::{to_hex, Hash160};
 use vm::representations::UrlString;
-use vm::types::QualifiedContractIdentifier;
+use vm::types::{QualifiedContractIdentifier, TupleData, Value};
 
 use crate::types::chainstate::{BlockHeaderHash, StacksBlockHeader};
 
@@ -39,7 +41,8 @@ use super::download::{
     AttachmentRequest, AttachmentsBatch, AttachmentsBatchStateContext, AttachmentsInventoryRequest,
     BatchedRequestsResult, ReliabilityReport,
 };
-use super::{AtlasConfig, AtlasDB, Attachment, AttachmentInstance};
+use super::validation::{TextEncodingValidator, ZonefileValidator};
+use super::{AtlasConfig, AtlasDB, Attachment, AttachmentInstance, AttachmentValidator};
 
 fn new_attachment_from(content: &str) -> Attachment {
     Attachment {
@@ -63,6 +66,44 @@ fn new_attachment_instance_from(
     }
 }
 
+fn new_bns_attachment_instance_from(
+    attachment: &Attachment,
+    attachment_index: u32,
+    block_height: u64,
+    name: &str,
+) -> AttachmentInstance {
+    let metadata = Value::Tuple(
+        TupleData::from_data(vec![
+            (
+                "name".into(),
+                Value::buff_from(name.as_bytes().to_vec()).unwrap(),
+            ),
+            (
+                "namespace".into(),
+                Value::buff_from("id".as_bytes().to_vec()).unwrap(),
+            ),
+        ])
+        .unwrap(),
+    );
+    let mut serialized = vec![];
+    metadata.consensus_serialize(&mut serialized).unwrap();
+    let mut instance = new_attachment_instance_from(attachment, attachment_index, block_height);
+    // sqlite would store all-digit hex IDs as numbers in STRING columns
+    instance.index_block_hash = StacksBlockId([0xb0 | block_height as u8; 32]);
+    instance.tx_id = Txid([0xb0 | attachment_index as u8; 32]);
+    instance.metadata = to_hex(&serialized);
+    instance
+}
+
+fn insert_instantiated(atlas_db: &mut AtlasDB, instance: &AttachmentInstance, content: &str) {
+    atlas_db
+        .insert_uninstantiated_attachment_instance(instance, true)
+        .unwrap();
+    atlas_db
+        .insert_instantiated_attachment(&new_attachment_from(content))
+        .unwrap();
+}
+
 fn new_attachments_batch_from(
     attachment_instances: Vec<AttachmentInstance>,
     retry_count: u32,
@@ -732,6 +773,10 @@ fn test_keep_uninstantiated_attachments() {
         uninstantiated_attachments_expire_after: 10,
         unresolved_attachment_instances_expire_after: 10,
         genesis_attachments: None,
+        max_attachments_storage_bytes: None,
+        evict_unreferenced_attachments: false,
+        attachments_gc_interval: 0,
+        attachment_validators: vec![],
     };
 
     let atlas_db = AtlasDB::connect_memory(atlas_config).unwrap();
@@ -764,6 +809,10 @@ fn test_evict_k_oldest_uninstantiated_attachments() {
         uninstantiated_attachments_expire_after: 0,
         unresolved_attachment_instances_expire_after: 10,
         genesis_attachments: None,
+        max_attachments_storage_bytes: None,
+        evict_unreferenced_attachments: false,
+        attachments_gc_interval: 0,
+        attachment_validators: vec![],
     };
 
     let mut atlas_db = AtlasDB::connect_memory(atlas_config).unwrap();
@@ -912,6 +961,10 @@ fn test_evict_expired_uninstantiated_attachments() {
         uninstantiated_attachments_expire_after: 10,
         unresolved_attachment_instances_expire_after: 10,
         genesis_attachments: None,
+        max_attachments_storage_bytes: None,
+        evict_unreferenced_attachments: false,
+        attachments_gc_interval: 0,
+        attachment_validators: vec![],
     };
 
     let mut atlas_db = AtlasDB::connect_memory(atlas_config).unwrap();
@@ -981,6 +1034,10 @@ fn test_evict_expired_unresolved_attachment_instances() {
         uninstantiated_attachments_expire_after: 200,
         unresolved_attachment_instances_expire_after: 10,
         genesis_attachments: None,
+        max_attachments_storage_bytes: None,
+        evict_unreferenced_attachments: false,
+        attachments_gc_interval: 0,
+        attachment_validators: vec![],
     };
     let mut atlas_db = AtlasDB::connect_memory(atlas_config).unwrap();
 
@@ -1049,6 +1106,10 @@ fn test_get_minmax_heights_atlasdb() {
         uninstantiated_attachments_expire_after: 10,
         unresolved_attachment_instances_expire_after: 10,
         genesis_attachments: None,
+        max_attachments_storage_bytes: None,
+        evict_unreferenced_attachments: false,
+        attachments_gc_interval: 0,
+        attachment_validators: vec![],
     };
 
     let atlas_db = AtlasDB::connect_memory(atlas_config).unwrap();
@@ -1069,6 +1130,10 @@ fn test_bit_vectors() {
         uninstantiated_attachments_expire_after: 10,
         unresolved_attachment_instances_expire_after: 10,
         genesis_attachments: None,
+        max_attachments_storage_bytes: None,
+        evict_unreferenced_attachments: false,
+        attachments_gc_interval: 0,
+        attachment_validators: vec![],
     };
 
     let mut atlas_db = AtlasDB::connect_memory(atlas_config).unwrap();
@@ -1183,3 +1248,200 @@ fn test_attachments_inventory_requests_hashing() {
 
     println!("{:?}", requests);
 }
+
+#[test]
+fn test_get_bns_name() {
+    let attachment = new_attachment_from("facade01");
+    let instance = new_bns_attachment_instance_from(&attachment, 0, 1, "alice");
+    assert_eq!(
+        instance.get_bns_name(),
+        Some((b"id".to_vec(), b"alice".to_vec()))
+    );
+
+    let instance = new_attachment_instance_from(&attachment, 0, 1);
+    assert_eq!(instance.get_bns_name(), None);
+}
+
+#[test]
+fn test_evict_unreferenced_attachments() {
+    let atlas_config = AtlasConfig {
+        contracts: HashSet::new(),
+        attachments_max_size: 1024,
+        max_uninstantiated_attachments: 100,
+        uninstantiated_attachments_expire_after: 10,
+        unresolved_attachment_instances_expire_after: 10,
+        genesis_attachments: Some(vec![new_attachment_from("genesis0")]),
+        max_attachments_storage_bytes: None,
+        evict_unreferenced_attachments: true,
+        attachments_gc_interval: 0,
+        attachment_validators: vec![],
+    };
+    let mut atlas_db = AtlasDB::connect_memory(atlas_config).unwrap();
+    let orphaned_block = StacksBlockId([0xff; 32]);
+    let is_canonical = |block_id: &StacksBlockId| block_id != &orphaned_block;
+
+    // alice's first zonefile is superseded by her second
+    let alice_1 = new_bns_attachment_instance_from(&new_attachment_from("alice001"), 0, 1, "alice");
+    let alice_2 = new_bns_attachment_instance_from(&new_attachment_from("alice002"), 2, 3, "alice");
+    insert_instantiated(&mut atlas_db, &alice_1, "alice001");
+    insert_instantiated(&mut atlas_db, &alice_2, "alice002");
+
+    // bob's only zonefile is current
+    let bob_1 = new_bns_attachment_instance_from(&new_attachment_from("bob00001"), 1, 2, "bob");
+    insert_instantiated(&mut atlas_db, &bob_1, "bob00001");
+
+    // carol's later zonefile is in a fork that isn't canonical (yet)
+    let carol_1 = new_bns_attachment_instance_from(&new_attachment_from("carol001"), 3, 4, "carol");
+    let mut carol_2 =
+        new_bns_attachment_instance_from(&new_attachment_from("carol002"), 4, 9, "carol");
+    carol_2.index_block_hash = orphaned_block.clone();
+    insert_instantiated(&mut atlas_db, &carol_1, "carol001");
+    insert_instantiated(&mut atlas_db, &carol_2, "carol002");
+
+    // attachments that don't update a BNS name are kept
+    let mut other = new_attachment_instance_from(&new_attachment_from("other001"), 5, 5);
+    other.index_block_hash = StacksBlockId([0xb5; 32]);
+    other.tx_id = Txid([0xb5; 32]);
+    insert_instantiated(&mut atlas_db, &other, "other001");
+
+    let unreferenced = atlas_db
+        .find_unreferenced_attachments(is_canonical)
+        .unwrap();
+    assert_eq!(
+        unreferenced,
+        vec![(new_attachment_from("alice001").hash(), 8)]
+    );
+
+    assert_eq!(atlas_db.gc_attachments_storage(is_canonical).unwrap(), 1);
+    assert!(atlas_db
+        .find_attachment(&alice_1.content_hash)
+        .unwrap()
+        .is_none());
+    assert!(atlas_db
+        .find_all_attachment_instances(&alice_1.content_hash)
+        .unwrap()
+        .is_empty());
+    for kept in [&alice_2, &bob_1, &carol_1, &carol_2, &other].iter() {
+        assert!(atlas_db
+            .find_attachment(&kept.content_hash)
+            .unwrap()
+            .is_some());
+    }
+    assert!(atlas_db
+        .find_attachment(&new_attachment_from("genesis0").hash())
+        .unwrap()
+        .is_some());
+    assert_eq!(atlas_db.count_instantiated_attachments().unwrap(), 6);
+
+    // once carol's fork is canonical, her first zonefile is superseded too
+    assert_eq!(atlas_db.gc_attachments_storage(|_| true).unwrap(), 1);
+    assert!(atlas_db
+        .find_attachment(&carol_1.content_hash)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_attachments_storage_quota() {
+    let atlas_config = AtlasConfig {
+        contracts: HashSet::new(),
+        attachments_max_size: 1024,
+        max_uninstantiated_attachments: 100,
+        uninstantiated_attachments_expire_after: 10,
+        unresolved_attachment_instances_expire_after: 10,
+        genesis_attachments: None,
+        max_attachments_storage_bytes: Some(20),
+        evict_unreferenced_attachments: false,
+        attachments_gc_interval: 0,
+        attachment_validators: vec![],
+    };
+    let mut atlas_db = AtlasDB::connect_memory(atlas_config).unwrap();
+
+    let alice_1 = new_bns_attachment_instance_from(&new_attachment_from("alice001"), 0, 1, "alice");
+    let alice_2 = new_bns_attachment_instance_from(&new_attachment_from("alice002"), 1, 2, "alice");
+    insert_instantiated(&mut atlas_db, &alice_1, "alice001");
+    insert_instantiated(&mut atlas_db, &alice_2, "alice002");
+    atlas_db
+        .insert_uninstantiated_attachment(&new_attachment_from("facade01"))
+        .unwrap();
+    atlas_db
+        .insert_uninstantiated_attachment(&new_attachment_from("facade02"))
+        .unwrap();
+    assert_eq!(atlas_db.get_attachments_storage_bytes().unwrap(), 32);
+
+    // uninstantiated attachments go first
+    assert_eq!(atlas_db.gc_attachments_storage(|_| true).unwrap(), 2);
+    assert_eq!(atlas_db.count_uninstantiated_attachments().unwrap(), 0);
+    assert_eq!(atlas_db.get_attachments_storage_bytes().unwrap(), 16);
+
+    // then unreferenced ones
+    atlas_db.atlas_config.max_attachments_storage_bytes = Some(8);
+    assert_eq!(atlas_db.gc_attachments_storage(|_| true).unwrap(), 1);
+    assert!(atlas_db
+        .find_attachment(&alice_1.content_hash)
+        .unwrap()
+        .is_none());
+    assert_eq!(atlas_db.get_attachments_storage_bytes().unwrap(), 8);
+
+    // referenced attachments are never evicted, even over quota
+    atlas_db.atlas_config.max_attachments_storage_bytes = Some(4);
+    assert_eq!(atlas_db.gc_attachments_storage(|_| true).unwrap(), 0);
+    assert!(atlas_db
+        .find_attachment(&alice_2.content_hash)
+        .unwrap()
+        .is_some());
+}
+
+#[test]
+fn test_attachment_validators() {
+    let zonefile = new_attachment_from(
+        "$ORIGIN alice.id\n$TTL 3600\n; profile\n_http._tcp IN URI 10 1 \"https://example.com/alice.json\"\n\n",
+    );
+    assert!(TextEncodingValidator.validate(&zonefile).is_ok());
+    assert!(ZonefileValidator.validate(&zonefile).is_ok());
+
+    let binary = Attachment::new(vec![0xff, 0xfe, 0x00]);
+    assert!(TextEncodingValidator.validate(&binary).is_err());
+    assert!(TextEncodingValidator
+        .validate(&Attachment::new(b"alice\0".to_vec()))
+        .is_err());
+    assert!(ZonefileValidator.validate(&binary).is_err());
+
+    for bad in [
+        "_http._tcp IN URI 10 1 \"https://example.com/alice.json\"\n",
+        "$ORIGIN alice.id\n$INCLUDE /etc/passwd\n",
+        "$ORIGIN alice.id\nhello\n",
+        "{\"profile\": {}}",
+    ]
+    .iter()
+    {
+        assert!(ZonefileValidator
+            .validate(&new_attachment_from(bad))
+            .is_err());
+    }
+
+    let mut atlas_config = AtlasConfig::default(false);
+    atlas_config.attachments_max_size = 128;
+    atlas_config.attachment_validators =
+        vec![Arc::new(TextEncodingValidator), Arc::new(ZonefileValidator)];
+    let atlas_db = AtlasDB::connect_memory(atlas_config).unwrap();
+    let bns_contract_id = boot_code_id("bns", false);
+
+    assert!(atlas_db.validate_attachment(&zonefile).is_ok());
+    assert!(atlas_db.should_keep_attachment(&bns_contract_id, &zonefile));
+
+    let err = atlas_db.validate_attachment(&binary).unwrap_err();
+    assert!(err.contains("'encoding'"));
+    let err = atlas_db
+        .validate_attachment(&new_attachment_from("hello world"))
+        .unwrap_err();
+    assert!(err.contains("'zonefile'"));
+    assert!(!atlas_db.should_keep_attachment(&bns_contract_id, &new_attachment_from("hello world")));
+
+    // the size limit applies whatever the validators say
+    let large = new_attachment_from(&format!("$ORIGIN alice.id\n; {}\n", "x".repeat(128)));
+    assert!(atlas_db
+        .validate_attachment(&large)
+        .unwrap_err()
+        .contains("too large"));
+}
@@ -0,0 +1,89 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020-2021 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Checks an attachment's content must pass before the node stores it.  Each validator is a named
+//! rule; rejections are counted per rule.  No rule is enabled unless the node is configured to.
+
+use std::fmt;
+use std::str;
+
+use super::Attachment;
+
+pub trait AttachmentValidator: fmt::Debug + Send + Sync {
+    /// Short name of the rule, used in logs and in the rejection counters
+    fn name(&self) -> &'static str;
+
+    /// Err, with the reason, if the attachment breaks this rule
+    fn validate(&self, attachment: &Attachment) -> Result<(), String>;
+}
+
+/// Attachments must be UTF-8 text, without NUL bytes
+#[derive(Debug, Clone)]
+pub struct TextEncodingValidator;
+
+impl AttachmentValidator for TextEncodingValidator {
+    fn name(&self) -> &'static str {
+        "encoding"
+    }
+
+    fn validate(&self, attachment: &Attachment) -> Result<(), String> {
+        let text = str::from_utf8(&attachment.content)
+            .map_err(|e| format!("content is not UTF-8: {}", e))?;
+        if text.contains('\0') {
+            return Err("content contains NUL bytes".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Attachments must look like DNS zonefiles: an `$ORIGIN` directive, and otherwise only blank
+/// lines, comments, `$TTL` directives and resource records (a name, a type and some data)
+#[derive(Debug, Clone)]
+pub struct ZonefileValidator;
+
+impl AttachmentValidator for ZonefileValidator {
+    fn name(&self) -> &'static str {
+        "zonefile"
+    }
+
+    fn validate(&self, attachment: &Attachment) -> Result<(), String> {
+        let text =
+            str::from_utf8(&attachment.content).map_err(|_| "zonefile is not UTF-8".to_string())?;
+        let mut has_origin = false;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            let fields: Vec<_> = line.split_whitespace().collect();
+            match fields[0] {
+                "$ORIGIN" if fields.len() == 2 => has_origin = true,
+                "$TTL" if fields.len() == 2 => {}
+                directive if directive.starts_with('$') => {
+                    return Err(format!("line {}: unsupported directive", i + 1));
+                }
+                _ if fields.len() >= 3 => {}
+                _ => {
+                    return Err(format!("line {}: not a resource record", i + 1));
+                }
+            }
+        }
+        if !has_origin {
+            return Err("zonefile has no $ORIGIN".to_string());
+        }
+        Ok(())
+    }
+}
@@ -31,6 +31,7 @@ use rand::Rng;
 use burnchains::Burnchain;
 use burnchains::BurnchainView;
 use burnchains::PublicKey;
+use burnchains::Txid;
 use chainstate::burn::db::sortdb;
 use chainstate::burn::db::sortdb::{BlockHeaderCache, SortitionDB};
 use chainstate::stacks::db::StacksChainState;
@@ -55,6 +56,7 @@ use net::StacksMessage;
 use net::StacksP2P;
 use net::GETPOXINV_MAX_BITLEN;
 use net::*;
+use util::bloom::BloomFilter;
 use util::db::DBConn;
 use util::db::Error as db_error;
 use util::get_epoch_time_secs;
@@ -318,6 +320,9 @@ pub struct ConversationP2P {
 
     // outbound replies
     pub reply_handles: VecDeque<ReplyHandleP2P>,
+
+    // the peer's most recent TransactionsFilters, oldest first
+    pub peer_tx_filters: VecDeque<BloomFilter>,
 }
 
 impl fmt::Display for ConversationP2P {
@@ -509,6 +514,28 @@ impl ConversationP2P {
 
             stats: NeighborStats::new(outbound),
             reply_handles: VecDeque::new(),
+
+            peer_tx_filters: VecDeque::new(),
+        }
+    }
+
+    /// Does the remote peer understand TransactionsFilter messages?
+    pub fn supports_tx_filters(&self) -> bool {
+        self.peer_services & (ServiceFlags::TX_FILTER as u16) != 0
+    }
+
+    /// Has the remote peer recently told us it probably has this transaction?
+    pub fn peer_probably_has_tx(&self, txid: &Txid) -> bool {
+        self.peer_tx_filters
+            .iter()
+            .any(|filter| filter.contains(txid.as_bytes()))
+    }
+
+    /// Remember a TransactionsFilter from the remote peer, forgetting the oldest if need be
+    fn add_peer_tx_filter(&mut self, filter: BloomFilter) {
+        self.peer_tx_filters.push_back(filter);
+        while self.peer_tx_filters.len() > MAX_PEER_TX_FILTERS {
+            self.peer_tx_filters.pop_front();
         }
     }
 
@@ -1990,6 +2017,16 @@ impl ConversationP2P {
                 test_debug!("{:?}: Got NatPunchReply({})", &self, _m.nonce);
                 Ok(None)
             }
+            StacksMessageType::TransactionsFilter(ref filter) => {
+                test_debug!(
+                    "{:?}: Got TransactionsFilter ({} bytes)",
+                    &self,
+                    filter.bits.len()
+                );
+                consume = true;
+                self.add_peer_tx_filter(filter.clone());
+                Ok(None)
+            }
             _ => {
                 test_debug!(
                     "{:?}: Got a data-plane message (type {})",
@@ -2139,6 +2176,12 @@ impl ConversationP2P {
                 continue;
             }
 
+            let mut span = monitoring::trace_span("p2p.message");
+            if span.is_recording() {
+                span.set_attribute("p2p.message", msg.payload.get_message_name());
+                span.set_attribute("p2p.peer", self.to_neighbor_key());
+            }
+
             let (reply_opt, consumed) = if self.connection.has_public_key() {
                 // we already have this remote peer's public key, so the message signature will
                 // have been verified by the underlying ConnectionP2P.
@@ -4182,6 +4225,35 @@ mod test {
             assert_eq!(stats.num_bytes, (msg.preamble.payload_len - 1) as u64);
         }
     }
+
+    #[test]
+    fn convo_peer_tx_filters() {
+        let conn_opts = ConnectionOptions::default();
+        let socketaddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8090);
+        let burnchain = testing_burnchain_config();
+
+        let mut convo =
+            ConversationP2P::new(123, 456, &burnchain, &socketaddr, &conn_opts, true, 0);
+        assert!(!convo.supports_tx_filters());
+        convo.peer_services = ServiceFlags::RELAY as u16 | ServiceFlags::TX_FILTER as u16;
+        assert!(convo.supports_tx_filters());
+
+        let txid_1 = Txid([0x01; 32]);
+        let txid_2 = Txid([0x02; 32]);
+        let txid_3 = Txid([0x03; 32]);
+        assert!(!convo.peer_probably_has_tx(&txid_1));
+
+        for txid in [&txid_1, &txid_2, &txid_3].iter() {
+            let mut filter = BloomFilter::new(1);
+            filter.insert(txid.as_bytes());
+            convo.add_peer_tx_filter(filter);
+        }
+
+        // only the most recent filters are remembered
+        assert_eq!(convo.peer_tx_filters.len(), MAX_PEER_TX_FILTERS);
+        assert!(convo.peer_probably_has_tx(&txid_2));
+        assert!(convo.peer_probably_has_tx(&txid_3));
+    }
 }
 
 // TODO: test bandwidth limits
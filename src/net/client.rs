@@ -0,0 +1,313 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A minimal, blocking client for a node's RPC API, built with the `stacks-node-client` feature.
+//!
+//! Requests and responses are the node's own `HttpRequestType` and `HttpResponseType`, written
+//! and parsed by the same code the node uses to parse and write them, so a client built against
+//! this crate can't drift from the wire format of a node built from the same version.  Each
+//! request goes over a connection of its own, which the node closes once it has answered.
+
+use std::error;
+use std::fmt;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use burnchains::Txid;
+use chainstate::stacks::{StacksBlock, StacksTransaction};
+use net::Error as net_error;
+use net::{
+    AccountEntryResponse, CallReadOnlyResponse, HttpRequestMetadata, HttpRequestType,
+    HttpResponseType, PeerHost, RPCPeerInfoData, RPCPoxInfoData, StacksHttp, StacksHttpMessage,
+    TipRequest,
+};
+use vm::types::PrincipalData;
+use vm::{ClarityName, ContractName, Value};
+
+use crate::types::chainstate::{StacksAddress, StacksBlockId};
+
+/// How long to wait on the node by default, in seconds
+pub const DEFAULT_RPC_CLIENT_TIMEOUT: u64 = 30;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Failed to reach the node, or to read its answer
+    Net(net_error),
+    /// The node answered with an error status, and this message
+    Http(u16, String),
+    /// The node answered with a response the request does not call for
+    UnexpectedResponse(HttpResponseType),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Net(ref e) => fmt::Display::fmt(e, f),
+            Error::Http(code, ref msg) => write!(f, "HTTP {}: {}", code, msg),
+            Error::UnexpectedResponse(ref resp) => write!(f, "Unexpected response: {:?}", resp),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::Net(ref e) => Some(e),
+            Error::Http(..) => None,
+            Error::UnexpectedResponse(_) => None,
+        }
+    }
+}
+
+impl From<net_error> for Error {
+    fn from(e: net_error) -> Error {
+        Error::Net(e)
+    }
+}
+
+/// The status and message of an error response, if this is one
+fn error_status(response: &HttpResponseType) -> Option<(u16, String)> {
+    match *response {
+        HttpResponseType::BadRequest(_, ref msg) => Some((400, msg.clone())),
+        HttpResponseType::BadRequestJSON(_, ref json) => Some((400, json.to_string())),
+        HttpResponseType::Unauthorized(_, ref msg) => Some((401, msg.clone())),
+        HttpResponseType::PaymentRequired(_, ref msg) => Some((402, msg.clone())),
+        HttpResponseType::Forbidden(_, ref msg) => Some((403, msg.clone())),
+        HttpResponseType::NotFound(_, ref msg) => Some((404, msg.clone())),
+        HttpResponseType::ServerError(_, ref msg) => Some((500, msg.clone())),
+        HttpResponseType::ServiceUnavailable(_, ref msg) => Some((503, msg.clone())),
+        HttpResponseType::Error(_, code, ref msg) => Some((code, msg.clone())),
+        _ => None,
+    }
+}
+
+pub struct RPCClient {
+    addr: SocketAddr,
+    timeout: Duration,
+}
+
+impl RPCClient {
+    /// A client for the node whose RPC interface is bound to the given address
+    pub fn new(addr: SocketAddr) -> RPCClient {
+        RPCClient {
+            addr,
+            timeout: Duration::from_secs(DEFAULT_RPC_CLIENT_TIMEOUT),
+        }
+    }
+
+    /// Give up on connecting to, writing to, or reading from the node after this long
+    pub fn with_timeout(mut self, timeout: Duration) -> RPCClient {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Metadata for a new request
+    pub fn metadata(&self) -> HttpRequestMetadata {
+        let mut md = HttpRequestMetadata::from_host(PeerHost::from_socketaddr(&self.addr));
+        md.keep_alive = false;
+        md
+    }
+
+    /// Send a request, and read back the node's response.  Error responses come back as
+    /// `Error::Http`.
+    pub fn send(&self, request: HttpRequestType) -> Result<HttpResponseType, Error> {
+        let request_path = request.request_path();
+        let request_bytes = StacksHttp::serialize_request(&request)?;
+
+        let mut sock = TcpStream::connect_timeout(&self.addr, self.timeout)
+            .map_err(|_e| net_error::ConnectionError)?;
+        sock.set_read_timeout(Some(self.timeout))
+            .and_then(|_| sock.set_write_timeout(Some(self.timeout)))
+            .map_err(|_e| net_error::SocketError)?;
+        sock.write_all(&request_bytes)
+            .map_err(net_error::WriteError)?;
+
+        let mut response_bytes = vec![];
+        sock.read_to_end(&mut response_bytes)
+            .map_err(net_error::ReadError)?;
+        if response_bytes.len() == 0 {
+            return Err(net_error::PermanentlyDrained.into());
+        }
+
+        let response = match StacksHttp::parse_response(&request_path, &response_bytes)? {
+            StacksHttpMessage::Response(response) => response,
+            StacksHttpMessage::Request(_) => {
+                return Err(net_error::DeserializeError(
+                    "Invalid HTTP message: got a request instead of a response".to_string(),
+                )
+                .into());
+            }
+        };
+        match error_status(&response) {
+            Some((code, msg)) => Err(Error::Http(code, msg)),
+            None => Ok(response),
+        }
+    }
+
+    /// `GET /v2/info`
+    pub fn get_info(&self) -> Result<RPCPeerInfoData, Error> {
+        match self.send(HttpRequestType::GetInfo(self.metadata()))? {
+            HttpResponseType::PeerInfo(_, info) => Ok(info),
+            other => Err(Error::UnexpectedResponse(other)),
+        }
+    }
+
+    /// `GET /v2/pox`
+    pub fn get_pox_info(&self) -> Result<RPCPoxInfoData, Error> {
+        match self.send(HttpRequestType::GetPoxInfo(self.metadata(), None))? {
+            HttpResponseType::PoxInfo(_, info) => Ok(info),
+            other => Err(Error::UnexpectedResponse(other)),
+        }
+    }
+
+    /// `GET /v2/accounts/:principal`
+    pub fn get_account(
+        &self,
+        principal: &PrincipalData,
+        tip: TipRequest,
+        with_proof: bool,
+    ) -> Result<AccountEntryResponse, Error> {
+        let request =
+            HttpRequestType::GetAccount(self.metadata(), principal.clone(), tip, with_proof);
+        match self.send(request)? {
+            HttpResponseType::GetAccount(_, account) => Ok(account),
+            other => Err(Error::UnexpectedResponse(other)),
+        }
+    }
+
+    /// `GET /v2/blocks/:block_id`
+    pub fn get_block(&self, index_block_hash: &StacksBlockId) -> Result<StacksBlock, Error> {
+        let request = HttpRequestType::GetBlock(self.metadata(), index_block_hash.clone());
+        match self.send(request)? {
+            HttpResponseType::Block(_, block) => Ok(block),
+            other => Err(Error::UnexpectedResponse(other)),
+        }
+    }
+
+    /// `POST /v2/transactions`.  Returns the transaction's ID once the node has accepted it.
+    pub fn post_transaction(&self, tx: &StacksTransaction) -> Result<Txid, Error> {
+        let request = HttpRequestType::PostTransaction(self.metadata(), tx.clone(), None, false);
+        match self.send(request)? {
+            HttpResponseType::TransactionID(_, txid) => Ok(txid),
+            other => Err(Error::UnexpectedResponse(other)),
+        }
+    }
+
+    /// `POST /v2/contracts/call-read/:address/:contract/:function`
+    pub fn call_read_only(
+        &self,
+        contract_address: &StacksAddress,
+        contract_name: &ContractName,
+        function_name: &ClarityName,
+        sender: &PrincipalData,
+        arguments: &[Value],
+        tip: TipRequest,
+    ) -> Result<CallReadOnlyResponse, Error> {
+        let request = HttpRequestType::CallReadOnlyFunction(
+            self.metadata(),
+            contract_address.clone(),
+            contract_name.clone(),
+            sender.clone(),
+            function_name.clone(),
+            arguments.to_vec(),
+            tip,
+        );
+        match self.send(request)? {
+            HttpResponseType::CallReadOnlyFunction(_, response) => Ok(response),
+            other => Err(Error::UnexpectedResponse(other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::TcpListener;
+    use std::thread;
+
+    use net::{HttpResponseMetadata, HttpVersion};
+    use util::hash::Hash160;
+
+    use super::*;
+
+    /// Answer each of the given number of connections with the next response, once the
+    /// request's headers are in
+    fn fake_node(responses: Vec<HttpResponseType>) -> (SocketAddr, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            for response in responses.into_iter() {
+                let (mut sock, _) = listener.accept().unwrap();
+                let mut request = vec![];
+                let mut buf = [0u8; 1024];
+                while !String::from_utf8_lossy(&request).contains("\r\n\r\n") {
+                    let nread = sock.read(&mut buf).unwrap();
+                    assert!(nread > 0);
+                    request.extend_from_slice(&buf[0..nread]);
+                }
+                assert!(String::from_utf8_lossy(&request).starts_with("GET /v2/accounts/"));
+
+                let mut http = StacksHttp::new(addr.clone());
+                response.send(&mut http, &mut sock).unwrap();
+            }
+        });
+        (addr, handle)
+    }
+
+    #[test]
+    fn test_rpc_client_get_account() {
+        let principal = PrincipalData::from(StacksAddress {
+            version: 26,
+            bytes: Hash160([0x11; 20]),
+        });
+        let account = AccountEntryResponse {
+            balance: "0x0000000000000000000000000000007b".to_string(),
+            locked: "0x00000000000000000000000000000000".to_string(),
+            unlock_height: 0,
+            nonce: 4,
+            balance_proof: None,
+            nonce_proof: None,
+        };
+        let md = HttpResponseMetadata::new(
+            HttpVersion::Http11,
+            HttpResponseMetadata::make_request_id(),
+            None,
+            false,
+        );
+        let (addr, handle) = fake_node(vec![
+            HttpResponseType::GetAccount(md.clone(), account.clone()),
+            HttpResponseType::NotFound(md.clone(), "No such account".to_string()),
+        ]);
+
+        let client = RPCClient::new(addr).with_timeout(Duration::from_secs(5));
+        assert_eq!(
+            client
+                .get_account(&principal, TipRequest::UseLatestAnchoredTip, false)
+                .unwrap(),
+            account
+        );
+
+        // error statuses come back as errors
+        match client.get_account(&principal, TipRequest::UseLatestUnconfirmedTip, false) {
+            Err(Error::Http(404, msg)) => assert_eq!(msg, "No such account"),
+            x => panic!("Expected a 404, got {:?}", &x),
+        }
+        handle.join().unwrap();
+
+        // nothing listening
+        assert!(client.get_info().is_err());
+    }
+}
@@ -40,6 +40,7 @@ use core::PEER_VERSION_TESTNET;
 use net::db::LocalPeer;
 use net::Error as net_error;
 use net::*;
+use util::bloom::{BloomFilter, BLOOM_MAX_HASHES};
 use util::hash::to_hex;
 use util::hash::DoubleSha256;
 use util::hash::Hash160;
@@ -761,6 +762,36 @@ impl StacksMessageCodec for NatPunchData {
     }
 }
 
+impl StacksMessageCodec for BloomFilter {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        write_next(fd, &self.seed)?;
+        write_next(fd, &self.num_hashes)?;
+        write_next(fd, &self.bits)?;
+        Ok(())
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<BloomFilter, codec_error> {
+        let seed: u32 = read_next(fd)?;
+        let num_hashes: u8 = read_next(fd)?;
+        if num_hashes == 0 || num_hashes > BLOOM_MAX_HASHES {
+            return Err(codec_error::DeserializeError(
+                "Invalid BloomFilter hash count".to_string(),
+            ));
+        }
+        let bits: Vec<u8> = read_next_at_most::<_, u8>(fd, TX_FILTER_MAX_BYTES)?;
+        if bits.is_empty() {
+            return Err(codec_error::DeserializeError(
+                "Empty BloomFilter".to_string(),
+            ));
+        }
+        Ok(BloomFilter {
+            seed,
+            num_hashes,
+            bits,
+        })
+    }
+}
+
 impl StacksMessageCodec for RelayData {
     fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
         write_next(fd, &self.peer)?;
@@ -799,6 +830,7 @@ impl StacksMessageType {
             StacksMessageType::Pong(ref _m) => StacksMessageID::Pong,
             StacksMessageType::NatPunchRequest(ref _m) => StacksMessageID::NatPunchRequest,
             StacksMessageType::NatPunchReply(ref _m) => StacksMessageID::NatPunchReply,
+            StacksMessageType::TransactionsFilter(ref _m) => StacksMessageID::TransactionsFilter,
         }
     }
 
@@ -823,6 +855,7 @@ impl StacksMessageType {
             StacksMessageType::Pong(ref _m) => "Pong",
             StacksMessageType::NatPunchRequest(ref _m) => "NatPunchRequest",
             StacksMessageType::NatPunchReply(ref _m) => "NatPunchReply",
+            StacksMessageType::TransactionsFilter(ref _m) => "TransactionsFilter",
         }
     }
 
@@ -881,6 +914,9 @@ impl StacksMessageType {
             StacksMessageType::NatPunchReply(ref m) => {
                 format!("NatPunchReply({},{}:{})", m.nonce, &m.addrbytes, m.port)
             }
+            StacksMessageType::TransactionsFilter(ref m) => {
+                format!("TransactionsFilter({} bytes)", m.bits.len())
+            }
         }
     }
 }
@@ -914,6 +950,9 @@ impl StacksMessageCodec for StacksMessageID {
             x if x == StacksMessageID::Pong as u8 => StacksMessageID::Pong,
             x if x == StacksMessageID::NatPunchRequest as u8 => StacksMessageID::NatPunchRequest,
             x if x == StacksMessageID::NatPunchReply as u8 => StacksMessageID::NatPunchReply,
+            x if x == StacksMessageID::TransactionsFilter as u8 => {
+                StacksMessageID::TransactionsFilter
+            }
             _ => {
                 return Err(codec_error::DeserializeError(
                     "Unknown message ID".to_string(),
@@ -947,6 +986,7 @@ impl StacksMessageCodec for StacksMessageType {
             StacksMessageType::Pong(ref m) => write_next(fd, m)?,
             StacksMessageType::NatPunchRequest(ref nonce) => write_next(fd, nonce)?,
             StacksMessageType::NatPunchReply(ref m) => write_next(fd, m)?,
+            StacksMessageType::TransactionsFilter(ref m) => write_next(fd, m)?,
         }
         Ok(())
     }
@@ -1024,6 +1064,10 @@ impl StacksMessageCodec for StacksMessageType {
                 let m: NatPunchData = read_next(fd)?;
                 StacksMessageType::NatPunchReply(m)
             }
+            StacksMessageID::TransactionsFilter => {
+                let m: BloomFilter = read_next(fd)?;
+                StacksMessageType::TransactionsFilter(m)
+            }
             StacksMessageID::Reserved => {
                 return Err(codec_error::DeserializeError(
                     "Unsupported message ID 'reserved'".to_string(),
@@ -1943,6 +1987,41 @@ pub mod test {
         check_codec_and_corruption::<NatPunchData>(&data, &bytes);
     }
 
+    #[test]
+    fn codec_BloomFilter() {
+        let data = BloomFilter {
+            seed: 0x01020304,
+            num_hashes: 7,
+            bits: vec![0xff, 0x00, 0x80],
+        };
+        let bytes = vec![
+            // seed
+            0x01, 0x02, 0x03, 0x04, // num hashes
+            0x07, // bits
+            0x00, 0x00, 0x00, 0x03, 0xff, 0x00, 0x80,
+        ];
+
+        check_codec_and_corruption::<BloomFilter>(&data, &bytes);
+
+        // should fail to decode with too many hashes, no hashes, no bits, or too many bits
+        assert!(check_deserialize_failure::<BloomFilter>(&BloomFilter {
+            num_hashes: BLOOM_MAX_HASHES + 1,
+            ..data.clone()
+        }));
+        assert!(check_deserialize_failure::<BloomFilter>(&BloomFilter {
+            num_hashes: 0,
+            ..data.clone()
+        }));
+        assert!(check_deserialize_failure::<BloomFilter>(&BloomFilter {
+            bits: vec![],
+            ..data.clone()
+        }));
+        assert!(check_deserialize_failure::<BloomFilter>(&BloomFilter {
+            bits: vec![0u8; (TX_FILTER_MAX_BYTES + 1) as usize],
+            ..data.clone()
+        }));
+    }
+
     #[test]
     fn codec_StacksMessage() {
         let payloads: Vec<StacksMessageType> = vec![
@@ -2059,6 +2138,11 @@ pub mod test {
                 port: 12345,
                 nonce: 0x12345678,
             }),
+            StacksMessageType::TransactionsFilter(BloomFilter {
+                seed: 0x12345678,
+                num_hashes: 7,
+                bits: vec![0x01, 0x02, 0x03, 0x04],
+            }),
         ];
 
         let mut maximal_relayers: Vec<RelayData> = vec![];
@@ -14,6 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::cmp;
 use std::collections::VecDeque;
 use std::convert::TryFrom;
 use std::io;
@@ -37,6 +38,7 @@ use mio::net as mio_net;
 use crate::codec::StacksMessageCodec;
 use crate::codec::MAX_MESSAGE_LEN;
 use net::codec::*;
+use net::http::MAX_HTTP_RESPONSE_SIZE;
 use net::Error as net_error;
 use net::HttpRequestPreamble;
 use net::HttpResponsePreamble;
@@ -44,6 +46,7 @@ use net::MessageSequence;
 use net::PeerAddress;
 use net::Preamble;
 use net::ProtocolFamily;
+use net::RPCEndpointClass;
 use net::RelayData;
 use net::StacksHttp;
 use net::StacksP2P;
@@ -54,11 +57,14 @@ use net::neighbors::{
     NEIGHBOR_REQUEST_TIMEOUT, NEIGHBOR_WALK_INTERVAL, NUM_INITIAL_WALKS, WALK_MAX_DURATION,
     WALK_MIN_DURATION, WALK_RESET_INTERVAL, WALK_RESET_PROB, WALK_RETRY_COUNT, WALK_STATE_TIMEOUT,
 };
+use net::relay::{BlockPushOrder, MicroblockAcceptance};
 
 use vm::{costs::ExecutionCost, types::BOUND_VALUE_SERIALIZATION_HEX};
 
 use chainstate::burn::ConsensusHash;
+use chainstate::stacks::MAX_BLOCK_LEN;
 
+use util::get_epoch_time_ms;
 use util::get_epoch_time_secs;
 use util::hash::to_hex;
 use util::log;
@@ -76,6 +82,7 @@ struct ReceiverNotify<P: ProtocolFamily> {
     expected_seq: u32,
     receiver_input: SyncSender<P::Message>,
     ttl: u64, // absolute deadline by which this message needs a reply (in seconds since the epoch)
+    sent_ms: u128, // when the request was made, to measure how long the reply took
 }
 
 impl<P: ProtocolFamily> ReceiverNotify<P> {
@@ -84,6 +91,7 @@ impl<P: ProtocolFamily> ReceiverNotify<P> {
             expected_seq: seq,
             receiver_input: input,
             ttl: ttl,
+            sent_ms: get_epoch_time_ms(),
         }
     }
 
@@ -358,6 +366,14 @@ pub struct ConnectionOptions {
     pub max_attachment_retry_count: u64,
     pub read_only_call_limit: ExecutionCost,
     pub maximum_call_argument_size: u32,
+    pub read_only_call_threads: usize,
+    pub rpc_metadata_deadline_ms: u64,
+    pub rpc_chain_state_deadline_ms: u64,
+    pub rpc_evaluation_deadline_ms: u64,
+    pub rpc_bulk_deadline_ms: u64,
+    /// Answer requests whose responses would be bigger than this many bytes with a 413, except
+    /// for blocks, microblocks and attachments, which are streamed (0 for no limit)
+    pub max_http_response_size: u64,
     pub max_block_push_bandwidth: u64,
     pub max_microblocks_push_bandwidth: u64,
     pub max_transaction_push_bandwidth: u64,
@@ -374,6 +390,45 @@ pub struct ConnectionOptions {
     pub max_buffered_microblocks_available: u64,
     pub max_buffered_blocks: u64,
     pub max_buffered_microblocks: u64,
+    pub read_only_replica: bool,
+    pub tx_filter_interval: u64,
+    pub tx_relay_min_fee_rate: u64,
+    pub tx_relay_max_len: u64,
+    pub tx_relay_allowed_payloads: Option<Vec<String>>,
+    pub contract_deploy_max_len: u64,
+    pub contract_deploy_max_depth: u64,
+    pub contract_deploy_max_analysis_runtime: u64,
+    /// How much of the unconfirmed microblock streams peers push to us to take in
+    pub microblock_acceptance: MicroblockAcceptance,
+    /// Reset block sync and disconnect outbound peers if the Stacks tip does not advance in this
+    /// many burn blocks (0 to never)
+    pub stale_tip_burn_blocks: u64,
+    /// How many pushed blocks and microblock streams can wait to be processed before the least
+    /// urgent ones are dropped
+    pub max_queued_pushes: u64,
+    /// How many pushed blocks and microblock streams to hand to the relayer in each pass of the
+    /// p2p state machine
+    pub max_pushes_per_pass: u64,
+    /// The order in which to push the blocks we mine to our peers
+    pub mined_block_push_order: BlockPushOrder,
+    /// Push a block we mined to this many peers at a time, in `mined_block_push_order` (0 to push
+    /// it to all of them at once)
+    pub mined_block_push_fanout: u64,
+    /// How long to wait, in milliseconds, before pushing a block we mined to the next
+    /// `mined_block_push_fanout` peers
+    pub mined_block_push_stagger_ms: u64,
+    /// Serve only the RPC endpoints whose answers never change (see
+    /// `HttpRequestType::is_immutable()`), as a public data mirror behind a CDN
+    pub http_mirror_mode: bool,
+    /// How long, in seconds, an HTTP mirror tells caches they may keep its answers
+    pub http_mirror_cache_max_age: u64,
+    /// How many inbound connections one host may open per `inbound_handshake_window` seconds
+    /// before the rest are dropped (0 for no limit).  Allowed peers are exempt.
+    pub max_inbound_handshakes_per_host: u64,
+    pub inbound_handshake_window: u64,
+    /// How many of the `num_clients` inbound slots only allowed peers may take.  At most half of
+    /// them are reserved, whatever this says (see `inbound_slots_reserved`).
+    pub reserved_inbound_slots: u64,
 
     // fault injection
     pub disable_neighbor_walk: bool,
@@ -440,6 +495,12 @@ impl std::default::Default for ConnectionOptions {
                 runtime: 1_000_000_000,
             },
             maximum_call_argument_size: 20 * BOUND_VALUE_SERIALIZATION_HEX,
+            read_only_call_threads: 0,
+            rpc_metadata_deadline_ms: 5_000, // how long RPC requests of each class may take (0 for no limit)
+            rpc_chain_state_deadline_ms: 15_000,
+            rpc_evaluation_deadline_ms: 30_000,
+            rpc_bulk_deadline_ms: 15_000,
+            max_http_response_size: MAX_HTTP_RESPONSE_SIZE,
             max_block_push_bandwidth: 0, // infinite upload bandwidth allowed
             max_microblocks_push_bandwidth: 0, // infinite upload bandwidth allowed
             max_transaction_push_bandwidth: 0, // infinite upload bandwidth allowed
@@ -456,6 +517,26 @@ impl std::default::Default for ConnectionOptions {
             max_buffered_microblocks_available: 1,
             max_buffered_blocks: 1,
             max_buffered_microblocks: 10,
+            read_only_replica: false, // serve RPC writes and attachments, as well as reads
+            tx_filter_interval: 30, // advertise our recent mempool transactions every 30s (0 to never advertise)
+            tx_relay_min_fee_rate: 0, // relay every transaction we accept into our mempool
+            tx_relay_max_len: 0,
+            tx_relay_allowed_payloads: None,
+            contract_deploy_max_len: 0, // take any contract consensus allows into the mempool
+            contract_deploy_max_depth: 0,
+            contract_deploy_max_analysis_runtime: 0,
+            microblock_acceptance: MicroblockAcceptance::Full,
+            stale_tip_burn_blocks: 0, // never reset block sync on our own
+            max_queued_pushes: 256,
+            max_pushes_per_pass: 16,
+            mined_block_push_order: BlockPushOrder::Relay,
+            mined_block_push_fanout: 0,
+            mined_block_push_stagger_ms: 0,
+            http_mirror_mode: false,
+            http_mirror_cache_max_age: 31_536_000, // a year
+            max_inbound_handshakes_per_host: 0, // test networks run all of their peers on one host
+            inbound_handshake_window: 60,
+            reserved_inbound_slots: 0,
 
             // no faults on by default
             disable_neighbor_walk: false,
@@ -475,12 +556,55 @@ impl std::default::Default for ConnectionOptions {
     }
 }
 
+/// How much a connection may hold in its read and write buffers at once: about a block's worth
+/// in each direction
+pub const CONNECTION_BUFFER_BYTES: u64 = 2 * (MAX_BLOCK_LEN as u64);
+
+impl ConnectionOptions {
+    /// How long requests to endpoints of `class` may take, in milliseconds (0 for no limit)
+    pub fn rpc_deadline_ms(&self, class: RPCEndpointClass) -> u64 {
+        match class {
+            RPCEndpointClass::Metadata => self.rpc_metadata_deadline_ms,
+            RPCEndpointClass::ChainState => self.rpc_chain_state_deadline_ms,
+            RPCEndpointClass::Evaluation => self.rpc_evaluation_deadline_ms,
+            RPCEndpointClass::Bulk => self.rpc_bulk_deadline_ms,
+            RPCEndpointClass::Write => 0,
+        }
+    }
+
+    /// How many inbound slots are held back for allowed peers: `reserved_inbound_slots`, but
+    /// never more than half of `num_clients`, so that a node with few inbound slots still takes
+    /// connections from the rest of the network
+    pub fn inbound_slots_reserved(&self) -> u64 {
+        cmp::min(self.reserved_inbound_slots, self.num_clients / 2)
+    }
+
+    /// Cap the number of connections so that their buffers fit in `max_bytes` (see
+    /// `CONNECTION_BUFFER_BYTES`).  Outbound neighbors keep their configured limit if it fits;
+    /// inbound p2p clients and HTTP clients split what is left in proportion to their limits.
+    pub fn limit_buffer_memory(&mut self, max_bytes: u64) {
+        let max_connections = cmp::max(max_bytes / CONNECTION_BUFFER_BYTES, 1);
+        self.num_neighbors = cmp::min(self.num_neighbors, max_connections);
+
+        let rest = max_connections - self.num_neighbors;
+        let wanted = self.num_clients + self.max_http_clients;
+        if wanted > rest {
+            self.num_clients = rest * self.num_clients / wanted;
+            self.max_http_clients = cmp::max(rest - self.num_clients, 1);
+        }
+        self.soft_num_neighbors = cmp::min(self.soft_num_neighbors, self.num_neighbors);
+        self.soft_num_clients = cmp::min(self.soft_num_clients, self.num_clients);
+    }
+}
+
 #[derive(Debug)]
 pub struct NetworkConnection<P: ProtocolFamily> {
     pub options: ConnectionOptions,
     pub protocol: P,
     inbox: ConnectionInbox<P>,
     outbox: ConnectionOutbox<P>,
+    /// smoothed time the remote peer takes to answer our requests, once it has answered one
+    reply_latency_ms: Option<u64>,
 }
 
 impl<P: ProtocolFamily> ConnectionInbox<P> {
@@ -954,6 +1078,11 @@ impl<P: ProtocolFamily> ConnectionInbox<P> {
     pub fn num_messages(&self) -> usize {
         self.inbox.len()
     }
+
+    /// How many bytes have we set aside for partially-read messages?
+    pub fn buffered_bytes(&self) -> usize {
+        self.buf.capacity()
+    }
 }
 
 impl<P: ProtocolFamily> ConnectionOutbox<P> {
@@ -1164,6 +1293,11 @@ impl<P: ProtocolFamily> ConnectionOutbox<P> {
     pub fn num_messages(&self) -> usize {
         self.outbox.len()
     }
+
+    /// How many bytes have we set aside for partially-sent messages?
+    pub fn buffered_bytes(&self) -> usize {
+        self.socket_out_buf.capacity()
+    }
 }
 
 impl<P: ProtocolFamily + Clone> NetworkConnection<P> {
@@ -1178,9 +1312,16 @@ impl<P: ProtocolFamily + Clone> NetworkConnection<P> {
 
             inbox: ConnectionInbox::new(options.inbox_maxlen, public_key_opt),
             outbox: ConnectionOutbox::new(options.outbox_maxlen),
+            reply_latency_ms: None,
         }
     }
 
+    /// How long, smoothed over its recent replies, the remote peer takes to answer our requests.
+    /// None if it has not answered one yet.
+    pub fn get_reply_latency_ms(&self) -> Option<u64> {
+        self.reply_latency_ms
+    }
+
     /// Determine if a (possibly unauthenticated) message was solicited
     pub fn is_solicited(&self, msg: &P::Message) -> bool {
         let mut solicited = false;
@@ -1214,6 +1355,11 @@ impl<P: ProtocolFamily + Clone> NetworkConnection<P> {
 
         if solicited {
             let fulfilled = self.outbox.inflight.remove(outbox_index).unwrap(); // safe since solicited
+            let latency = get_epoch_time_ms().saturating_sub(fulfilled.sent_ms) as u64;
+            self.reply_latency_ms = Some(match self.reply_latency_ms {
+                Some(smoothed) => (smoothed * 7 + latency) / 8,
+                None => latency,
+            });
             fulfilled.send(msg);
             None
         } else {
@@ -1335,6 +1481,11 @@ impl<P: ProtocolFamily + Clone> NetworkConnection<P> {
         self.outbox.num_messages()
     }
 
+    /// how many bytes are set aside in our read and write buffers?
+    pub fn buffered_bytes(&self) -> usize {
+        self.inbox.buffered_bytes() + self.outbox.buffered_bytes()
+    }
+
     /// get the next inbox message
     pub fn next_inbox_message(&mut self) -> Option<P::Message> {
         self.inbox.next_message()
@@ -2107,9 +2258,13 @@ mod test {
             }
 
             // all messages are solicited, so inbox should be empty
+            assert_eq!(conn.get_reply_latency_ms(), None);
             let msgs = conn.drain_inbox();
             assert_eq!(msgs, vec![]);
 
+            // answering them measured how long the "peer" took to reply
+            assert!(conn.get_reply_latency_ms().is_some());
+
             let mut recved = vec![];
             for rh in flushed_handles {
                 let res = rh.recv(0).unwrap();
@@ -2244,4 +2399,43 @@ mod test {
 
         pinger.join().unwrap();
     }
+
+    #[test]
+    fn connection_options_limit_buffer_memory() {
+        // plenty of memory: nothing changes
+        let mut opts = ConnectionOptions::default();
+        opts.limit_buffer_memory(1024 * CONNECTION_BUFFER_BYTES);
+        assert_eq!(opts, ConnectionOptions::default());
+
+        // outbound neighbors are kept, and inbound and HTTP clients are cut
+        let mut opts = ConnectionOptions::default();
+        opts.limit_buffer_memory(100 * CONNECTION_BUFFER_BYTES);
+        assert_eq!(opts.num_neighbors, 32);
+        assert_eq!(opts.soft_num_neighbors, 20);
+        assert_eq!(opts.num_clients, 65);
+        assert_eq!(opts.soft_num_clients, 65);
+        assert_eq!(opts.max_http_clients, 3);
+
+        // then everything else, but there's always room for a neighbor and an HTTP client
+        let mut opts = ConnectionOptions::default();
+        opts.limit_buffer_memory(0);
+        assert_eq!(opts.num_neighbors, 1);
+        assert_eq!(opts.soft_num_neighbors, 1);
+        assert_eq!(opts.max_http_clients, 1);
+        assert_eq!(opts.num_clients, 0);
+    }
+
+    #[test]
+    fn connection_options_inbound_slots_reserved() {
+        let mut opts = ConnectionOptions::default();
+        opts.num_clients = 256;
+        opts.reserved_inbound_slots = 16;
+        assert_eq!(opts.inbound_slots_reserved(), 16);
+
+        // at most half the inbound slots are reserved
+        opts.num_clients = 20;
+        assert_eq!(opts.inbound_slots_reserved(), 10);
+        opts.num_clients = 1;
+        assert_eq!(opts.inbound_slots_reserved(), 0);
+    }
 }
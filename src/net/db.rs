@@ -591,8 +591,24 @@ impl PeerDB {
         Ok(db)
     }
 
-    /// Open a burn database in memory (used for testing)
-    #[cfg(test)]
+    /// Open an existing peer database with a read-only connection, e.g. to inspect it while the
+    /// node that owns it is stopped or running.  Unlike `connect()`, nothing is created or updated.
+    pub fn open_readonly(path: &str) -> Result<PeerDB, db_error> {
+        if fs::metadata(path).is_err() {
+            return Err(db_error::NoDBError);
+        }
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(db_error::SqliteError)?;
+        conn.busy_handler(Some(tx_busy_handler))
+            .map_err(db_error::SqliteError)?;
+        Ok(PeerDB {
+            conn: conn,
+            readwrite: false,
+        })
+    }
+
+    /// Open a new peer database in memory, with a fresh local peer identity.  Used in tests, and
+    /// by processes that serve RPC without keeping any peer state (e.g. read-only replicas).
     pub fn connect_memory(
         network_id: u32,
         parent_network_id: u32,
@@ -1155,20 +1171,48 @@ impl PeerDB {
         PeerDB::get_cidr_prefixes(conn, "allowed_prefixes")
     }
 
-    /// Check to see if an address is denied by one of the CIDR deny rows
-    pub fn is_address_denied(conn: &DBConn, addr: &PeerAddress) -> Result<bool, db_error> {
-        let denied_rows = PeerDB::get_denied_cidrs(conn)?;
+    /// Does an address fall within one of the given CIDR prefixes?
+    fn is_address_in_cidrs(cidrs: Vec<(PeerAddress, u32)>, addr: &PeerAddress) -> bool {
         let addr_int = u128::from_be_bytes(addr.as_bytes().to_owned());
 
-        for (prefix, mask) in denied_rows.into_iter() {
+        for (prefix, mask) in cidrs.into_iter() {
             let addr_mask = !((1u128 << (128 - mask)) - 1);
             let mask_int = u128::from_be_bytes(prefix.as_bytes().to_owned()) & addr_mask;
             if mask_int == (addr_int & addr_mask) {
-                return Ok(true);
+                return true;
             }
         }
 
-        Ok(false)
+        false
+    }
+
+    /// Check to see if an address is denied by one of the CIDR deny rows
+    pub fn is_address_denied(conn: &DBConn, addr: &PeerAddress) -> Result<bool, db_error> {
+        let denied_rows = PeerDB::get_denied_cidrs(conn)?;
+        Ok(PeerDB::is_address_in_cidrs(denied_rows, addr))
+    }
+
+    /// Check to see if an address is allowed by one of the CIDR allow rows
+    pub fn is_address_allowed(conn: &DBConn, addr: &PeerAddress) -> Result<bool, db_error> {
+        let allowed_rows = PeerDB::get_allowed_cidrs(conn)?;
+        Ok(PeerDB::is_address_in_cidrs(allowed_rows, addr))
+    }
+
+    /// Is a host allowed, whether by a CIDR allow row or by an allowed peer at its address?
+    /// Unlike `is_peer_always_allowed()`, this ignores the port, since a peer connecting to us
+    /// does so from whatever port its OS picks.
+    pub fn is_host_allowed(
+        conn: &DBConn,
+        network_id: u32,
+        addr: &PeerAddress,
+    ) -> Result<bool, db_error> {
+        if PeerDB::is_address_allowed(conn, addr)? {
+            return Ok(true);
+        }
+        let qry = "SELECT * FROM frontier WHERE network_id = ?1 AND addrbytes = ?2";
+        let args = [&network_id as &dyn ToSql, &addr.to_bin() as &dyn ToSql];
+        let peers = query_rows::<Neighbor, _>(conn, qry, &args)?;
+        Ok(peers.iter().any(|peer| peer.is_allowed()))
     }
 
     /// Convert a prefix address and mask to its hex representation
@@ -1375,6 +1419,31 @@ impl PeerDB {
         let rows = query_rows::<Neighbor, _>(conn, &qry, NO_PARAMS)?;
         Ok(rows)
     }
+
+    /// Seed the peer database with peers, such as the ones another node exported.  A peer that
+    /// is already present keeps what the node has learned about it.  Returns how many peers were
+    /// added; a peer whose slots are all taken is left out.
+    pub fn import_peers<'a>(
+        tx: &mut Transaction<'a>,
+        peers: &[Neighbor],
+    ) -> Result<usize, db_error> {
+        let mut added = 0;
+        for peer in peers.iter() {
+            let present = PeerDB::get_peer(
+                tx,
+                peer.addr.network_id,
+                &peer.addr.addrbytes,
+                peer.addr.port,
+            )?;
+            if present.is_some() {
+                continue;
+            }
+            if PeerDB::try_insert_peer(tx, peer)? {
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
 }
 
 #[cfg(test)]
@@ -2044,6 +2113,39 @@ mod test {
         assert_eq!(n1.denied, i64::max_value());
         assert_eq!(n2.allowed, 12345);
         assert_eq!(n2.denied, 67890);
+
+        // allowed hosts, on any port, and whether or not they're in the frontier
+        assert!(
+            PeerDB::is_host_allowed(db.conn(), 0x9abcdef0, &neighbor_1.addr.addrbytes).unwrap()
+        );
+        assert!(PeerDB::is_host_allowed(
+            db.conn(),
+            0x9abcdef0,
+            &PeerAddress([
+                0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+                0xff, 0xff,
+            ])
+        )
+        .unwrap());
+        assert!(
+            !PeerDB::is_host_allowed(db.conn(), 0x9abcdef0, &neighbor_2.addr.addrbytes).unwrap()
+        );
+
+        {
+            let mut tx = db.tx_begin().unwrap();
+            PeerDB::set_allow_peer(
+                &mut tx,
+                neighbor_2.addr.network_id,
+                &neighbor_2.addr.addrbytes,
+                neighbor_2.addr.port,
+                -1,
+            )
+            .unwrap();
+            tx.commit().unwrap();
+        }
+        assert!(
+            PeerDB::is_host_allowed(db.conn(), 0x9abcdef0, &neighbor_2.addr.addrbytes).unwrap()
+        );
     }
 
     #[test]
@@ -2167,4 +2269,60 @@ mod test {
         assert_eq!(n1.allowed, -1);
         assert_eq!(n2.allowed, -1);
     }
+
+    #[test]
+    fn test_peer_import() {
+        let mut db = PeerDB::connect_memory(
+            0x9abcdef0,
+            12345,
+            0,
+            "http://foo.com".into(),
+            &vec![],
+            &vec![],
+        )
+        .unwrap();
+
+        let make_peer = |port: u16| {
+            let nk = NeighborKey {
+                peer_version: 0x12345678,
+                network_id: 0x9abcdef0,
+                addrbytes: PeerAddress::from_ipv4(10, 0, 0, 1),
+                port: port,
+            };
+            let pubk = Secp256k1PublicKey::from_private(&Secp256k1PrivateKey::new());
+            Neighbor::empty(&nk, &pubk, 100)
+        };
+        let peers = vec![make_peer(20444), make_peer(20445)];
+
+        {
+            let mut tx = db.tx_begin().unwrap();
+            assert_eq!(PeerDB::import_peers(&mut tx, &peers).unwrap(), 2);
+            tx.commit().unwrap();
+        }
+        assert_eq!(PeerDB::get_all_peers(db.conn()).unwrap().len(), 2);
+
+        // a peer the node already knows keeps what it has learned
+        {
+            let mut tx = db.tx_begin().unwrap();
+            let mut known = peers[0].clone();
+            known.last_contact_time = 1552509642;
+            PeerDB::update_peer(&mut tx, &known).unwrap();
+
+            let mut reseeds = vec![make_peer(20444), make_peer(20446)];
+            reseeds[0].addr = peers[0].addr.clone();
+            assert_eq!(PeerDB::import_peers(&mut tx, &reseeds).unwrap(), 1);
+            tx.commit().unwrap();
+        }
+        let neighbor = PeerDB::get_peer(
+            db.conn(),
+            0x9abcdef0,
+            &PeerAddress::from_ipv4(10, 0, 0, 1),
+            20444,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(neighbor.last_contact_time, 1552509642);
+        assert_eq!(neighbor.public_key, peers[0].public_key);
+        assert_eq!(PeerDB::get_all_peers(db.conn()).unwrap().len(), 3);
+    }
 }
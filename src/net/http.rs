@@ -40,8 +40,11 @@ use burnchains::{Address, Txid};
 use chainstate::burn::ConsensusHash;
 use chainstate::stacks::{StacksBlock, StacksMicroblock, StacksPublicKey, StacksTransaction};
 use deps::httparse;
+use monitoring;
+use net::admin::BearerToken;
 use net::atlas::Attachment;
 use net::CallReadOnlyRequestBody;
+use net::ClarityStateOverride;
 use net::ClientError;
 use net::Error as net_error;
 use net::Error::ClarityError;
@@ -57,16 +60,29 @@ use net::MessageSequence;
 use net::NeighborAddress;
 use net::PeerAddress;
 use net::PeerHost;
+use net::PostConditionsPreviewRequestBody;
 use net::ProtocolFamily;
+use net::RPCAdminTogglesUpdate;
+use net::RPCBlockProposalRequest;
+use net::RPCEndpointClass;
+use net::RPCRequestDeadline;
+use net::RPCTransferStxOpRequest;
+use net::SimulateContractCallRequestBody;
+use net::SortitionLookup;
 use net::StacksHttpMessage;
 use net::StacksHttpPreamble;
+use net::TipRequest;
 use net::UnconfirmedTransactionResponse;
 use net::UnconfirmedTransactionStatus;
 use net::HTTP_PREAMBLE_MAX_ENCODED_SIZE;
 use net::HTTP_PREAMBLE_MAX_NUM_HEADERS;
 use net::HTTP_REQUEST_ID_RESERVED;
 use net::MAX_MICROBLOCKS_UNCONFIRMED;
+use net::RPC_DEADLINE_EXCEEDED_MESSAGE;
 use net::{GetAttachmentResponse, GetAttachmentsInvResponse, PostTransactionRequestBody};
+use net::{ACCOUNT_TRANSACTIONS_DEFAULT_LIMIT, ACCOUNT_TRANSACTIONS_MAX_LIMIT};
+use net::{CONTRACT_EVENTS_DEFAULT_LIMIT, CONTRACT_EVENTS_MAX_LIMIT};
+use util::get_epoch_time_ms;
 use util::hash::hex_bytes;
 use util::hash::to_hex;
 use util::hash::Hash160;
@@ -86,7 +102,9 @@ use crate::codec::{
     read_next, write_next, Error as codec_error, StacksMessageCodec, MAX_MESSAGE_LEN,
     MAX_PAYLOAD_LEN,
 };
-use crate::types::chainstate::{BlockHeaderHash, StacksAddress, StacksBlockId};
+use crate::types::chainstate::{
+    BlockHeaderHash, BurnchainHeaderHash, StacksAddress, StacksBlockId,
+};
 
 lazy_static! {
     static ref PATH_GETINFO: Regex = Regex::new(r#"^/v2/info$"#).unwrap();
@@ -101,6 +119,8 @@ lazy_static! {
         Regex::new(r#"^/v2/microblocks/unconfirmed/([0-9a-f]{64})/([0-9]{1,5})$"#).unwrap();
     static ref PATH_GETTRANSACTION_UNCONFIRMED: Regex =
         Regex::new(r#"^/v2/transactions/unconfirmed/([0-9a-f]{64})$"#).unwrap();
+    static ref PATH_GET_TRANSACTION_RECEIPT: Regex =
+        Regex::new(r#"^/v2/receipts/(?P<txid>[0-9a-f]{64})$"#).unwrap();
     static ref PATH_POSTTRANSACTION: Regex = Regex::new(r#"^/v2/transactions$"#).unwrap();
     static ref PATH_POSTBLOCK: Regex = Regex::new(r#"^/v2/blocks/upload/([0-9a-f]{40})$"#).unwrap();
     static ref PATH_POSTMICROBLOCK: Regex = Regex::new(r#"^/v2/microblocks$"#).unwrap();
@@ -109,6 +129,49 @@ lazy_static! {
         *PRINCIPAL_DATA_REGEX
     ))
     .unwrap();
+    static ref PATH_GET_ACCOUNT_TRANSACTIONS: Regex = Regex::new(&format!(
+        "^/v2/accounts/(?P<principal>{})/transactions$",
+        *PRINCIPAL_DATA_REGEX
+    ))
+    .unwrap();
+    static ref PATH_GET_SPONSORSHIP: Regex = Regex::new(&format!(
+        "^/v2/accounts/(?P<principal>{})/sponsorship$",
+        *STANDARD_PRINCIPAL_REGEX
+    ))
+    .unwrap();
+    static ref PATH_GET_ACCOUNT_BALANCE: Regex = Regex::new(&format!(
+        "^/v2/accounts/(?P<principal>{})/balance$",
+        *PRINCIPAL_DATA_REGEX
+    ))
+    .unwrap();
+    static ref PATH_GET_STACKING_STATUS: Regex = Regex::new(&format!(
+        "^/v2/stacking/(?P<principal>{})$",
+        *PRINCIPAL_DATA_REGEX
+    ))
+    .unwrap();
+    static ref PATH_GET_DELEGATIONS: Regex = Regex::new(&format!(
+        "^/v2/delegations/(?P<principal>{})$",
+        *PRINCIPAL_DATA_REGEX
+    ))
+    .unwrap();
+    static ref PATH_GET_REWARD_SET: Regex =
+        Regex::new(r#"^/v2/pox/reward_set/(?P<reward_cycle>[0-9]{1,20})$"#).unwrap();
+    static ref PATH_GET_SORTITION: Regex = Regex::new(
+        r#"^/v2/sortitions/(?P<lookup>burn_height|burn_hash|stacks_block)/(?P<value>[0-9a-f]{1,64})$"#
+    )
+    .unwrap();
+    static ref PATH_GET_CONSENSUS_SCHEDULE: Regex =
+        Regex::new("^/v2/consensus_schedule$").unwrap();
+    static ref PATH_GET_MICROBLOCK_EQUIVOCATIONS: Regex =
+        Regex::new("^/v2/microblocks/equivocations$").unwrap();
+    static ref PATH_ADMIN_TOGGLES: Regex = Regex::new("^/v2/admin/toggles$").unwrap();
+    static ref PATH_POST_TRANSFER_STX_OP: Regex =
+        Regex::new("^/v2/admin/burn_ops/transfer_stx$").unwrap();
+    static ref PATH_GET_MINER_WALLET: Regex = Regex::new("^/v2/admin/miner/wallet$").unwrap();
+    static ref PATH_GET_CLARITY_PROFILES: Regex =
+        Regex::new("^/v2/admin/clarity_profiles$").unwrap();
+    static ref PATH_POST_BLOCK_PROPOSAL: Regex =
+        Regex::new("^/v2/admin/block_proposals$").unwrap();
     static ref PATH_GET_MAP_ENTRY: Regex = Regex::new(&format!(
         "^/v2/map_entry/(?P<address>{})/(?P<contract>{})/(?P<map>{})$",
         *STANDARD_PRINCIPAL_REGEX, *CONTRACT_NAME_REGEX, *CLARITY_NAME_REGEX
@@ -119,6 +182,13 @@ lazy_static! {
         *STANDARD_PRINCIPAL_REGEX, *CONTRACT_NAME_REGEX, *CLARITY_NAME_REGEX
     ))
     .unwrap();
+    static ref PATH_POST_SIMULATE_CALL: Regex = Regex::new(&format!(
+        "^/v2/contracts/simulate-call/(?P<address>{})/(?P<contract>{})/(?P<function>{})$",
+        *STANDARD_PRINCIPAL_REGEX, *CONTRACT_NAME_REGEX, *CLARITY_NAME_REGEX
+    ))
+    .unwrap();
+    static ref PATH_POST_POSTCONDITIONS_PREVIEW: Regex =
+        Regex::new("^/v2/transactions/postconditions$").unwrap();
     static ref PATH_GET_CONTRACT_SRC: Regex = Regex::new(&format!(
         "^/v2/contracts/source/(?P<address>{})/(?P<contract>{})$",
         *STANDARD_PRINCIPAL_REGEX, *CONTRACT_NAME_REGEX
@@ -134,6 +204,11 @@ lazy_static! {
         *STANDARD_PRINCIPAL_REGEX, *CONTRACT_NAME_REGEX
     ))
     .unwrap();
+    static ref PATH_GET_CONTRACT_EVENTS: Regex = Regex::new(&format!(
+        "^/v2/contracts/events/(?P<address>{})/(?P<contract>{})$",
+        *STANDARD_PRINCIPAL_REGEX, *CONTRACT_NAME_REGEX
+    ))
+    .unwrap();
     static ref PATH_GET_TRANSFER_COST: Regex = Regex::new("^/v2/fees/transfer$").unwrap();
     static ref PATH_GET_ATTACHMENTS_INV: Regex = Regex::new("^/v2/attachments/inv$").unwrap();
     static ref PATH_GET_ATTACHMENT: Regex =
@@ -141,6 +216,9 @@ lazy_static! {
     static ref PATH_OPTIONS_WILDCARD: Regex = Regex::new("^/v2/.{0,4096}$").unwrap();
 }
 
+/// Default maximum size of an HTTP response that is not streamed
+pub const MAX_HTTP_RESPONSE_SIZE: u64 = 8 * 1024 * 1024;
+
 /// HTTP headers that we really care about
 #[derive(Debug, Clone, PartialEq)]
 enum HttpReservedHeader {
@@ -157,6 +235,13 @@ struct StacksBlockAcceptedData {
     accepted: bool,
 }
 
+/// Transaction accepted struct, sent for `POST /v2/transactions?result=1`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TransactionAcceptedData {
+    txid: String,
+    accepted: bool,
+}
+
 impl FromStr for PeerHost {
     type Err = net_error;
 
@@ -670,6 +755,41 @@ impl<'a, 'state, W: Write> Write for HttpChunkedTransferWriter<'a, 'state, W> {
     }
 }
 
+/// Write adapter that buffers a response, and fails once it would be bigger than `limit` bytes
+struct BoundedResponseBuffer {
+    buf: Vec<u8>,
+    limit: u64,
+    overflowed: bool,
+}
+
+impl BoundedResponseBuffer {
+    fn new(limit: u64) -> BoundedResponseBuffer {
+        BoundedResponseBuffer {
+            buf: vec![],
+            limit,
+            overflowed: false,
+        }
+    }
+}
+
+impl Write for BoundedResponseBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if (self.buf.len() as u64).saturating_add(buf.len() as u64) > self.limit {
+            self.overflowed = true;
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "HTTP response is too large",
+            ));
+        }
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 impl HttpRequestPreamble {
     pub fn new(
         version: HttpVersion,
@@ -871,6 +991,17 @@ fn keep_alive_headers<W: Write>(fd: &mut W, md: &HttpResponseMetadata) -> Result
     Ok(())
 }
 
+/// Headers for a successful response: whether to keep the connection open, and how long caches
+/// may keep the response.  Errors never tell caches to keep them.
+fn ok_headers<W: Write>(fd: &mut W, md: &HttpResponseMetadata) -> Result<(), codec_error> {
+    keep_alive_headers(fd, md)?;
+    if let Some(max_age) = md.cache_max_age {
+        fd.write_all(format!("Cache-Control: public, max-age={}\r\n", max_age).as_bytes())
+            .map_err(codec_error::WriteError)?;
+    }
+    Ok(())
+}
+
 fn write_headers<W: Write>(
     fd: &mut W,
     headers: &HashMap<String, String>,
@@ -1095,7 +1226,7 @@ impl HttpResponsePreamble {
             md.content_length.clone(),
             &HttpContentType::JSON,
             md.request_id,
-            |ref mut fd| keep_alive_headers(fd, md),
+            |ref mut fd| ok_headers(fd, md),
         )
     }
 
@@ -1491,6 +1622,86 @@ impl HttpRequestType {
                 &PATH_GET_ACCOUNT,
                 &HttpRequestType::parse_get_account,
             ),
+            (
+                "GET",
+                &PATH_GET_ACCOUNT_TRANSACTIONS,
+                &HttpRequestType::parse_get_account_transactions,
+            ),
+            (
+                "GET",
+                &PATH_GET_TRANSACTION_RECEIPT,
+                &HttpRequestType::parse_get_transaction_receipt,
+            ),
+            (
+                "GET",
+                &PATH_GET_SPONSORSHIP,
+                &HttpRequestType::parse_get_sponsorship,
+            ),
+            (
+                "GET",
+                &PATH_GET_ACCOUNT_BALANCE,
+                &HttpRequestType::parse_get_account_balance,
+            ),
+            (
+                "GET",
+                &PATH_GET_STACKING_STATUS,
+                &HttpRequestType::parse_get_stacking_status,
+            ),
+            (
+                "GET",
+                &PATH_GET_DELEGATIONS,
+                &HttpRequestType::parse_get_delegations,
+            ),
+            (
+                "GET",
+                &PATH_GET_REWARD_SET,
+                &HttpRequestType::parse_get_reward_set,
+            ),
+            (
+                "GET",
+                &PATH_GET_SORTITION,
+                &HttpRequestType::parse_get_sortition,
+            ),
+            (
+                "GET",
+                &PATH_GET_CONSENSUS_SCHEDULE,
+                &HttpRequestType::parse_get_consensus_schedule,
+            ),
+            (
+                "GET",
+                &PATH_GET_MICROBLOCK_EQUIVOCATIONS,
+                &HttpRequestType::parse_get_microblock_equivocations,
+            ),
+            (
+                "GET",
+                &PATH_ADMIN_TOGGLES,
+                &HttpRequestType::parse_get_admin_toggles,
+            ),
+            (
+                "POST",
+                &PATH_ADMIN_TOGGLES,
+                &HttpRequestType::parse_post_admin_toggles,
+            ),
+            (
+                "POST",
+                &PATH_POST_TRANSFER_STX_OP,
+                &HttpRequestType::parse_post_transfer_stx_op,
+            ),
+            (
+                "GET",
+                &PATH_GET_MINER_WALLET,
+                &HttpRequestType::parse_get_miner_wallet,
+            ),
+            (
+                "GET",
+                &PATH_GET_CLARITY_PROFILES,
+                &HttpRequestType::parse_get_clarity_profiles,
+            ),
+            (
+                "POST",
+                &PATH_POST_BLOCK_PROPOSAL,
+                &HttpRequestType::parse_post_block_proposal,
+            ),
             (
                 "POST",
                 &PATH_GET_MAP_ENTRY,
@@ -1516,11 +1727,26 @@ impl HttpRequestType {
                 &PATH_GET_CONTRACT_ABI,
                 &HttpRequestType::parse_get_contract_abi,
             ),
+            (
+                "GET",
+                &PATH_GET_CONTRACT_EVENTS,
+                &HttpRequestType::parse_get_contract_events,
+            ),
             (
                 "POST",
                 &PATH_POST_CALL_READ_ONLY,
                 &HttpRequestType::parse_call_read_only,
             ),
+            (
+                "POST",
+                &PATH_POST_SIMULATE_CALL,
+                &HttpRequestType::parse_simulate_contract_call,
+            ),
+            (
+                "POST",
+                &PATH_POST_POSTCONDITIONS_PREVIEW,
+                &HttpRequestType::parse_preview_postconditions,
+            ),
             (
                 "OPTIONS",
                 &PATH_OPTIONS_WILDCARD,
@@ -1674,6 +1900,20 @@ impl HttpRequestType {
         !no_proof
     }
 
+    /// check whether the given option query string sets result=1, asking for the structured
+    /// result of a transaction's mempool admission.
+    /// Defaults to _false_
+    fn get_result_query(query: Option<&str>) -> bool {
+        if let Some(query_string) = query {
+            form_urlencoded::parse(query_string.as_bytes())
+                .find(|(key, _v)| key == "result")
+                .map(|(_k, value)| value == "1")
+                .unwrap_or(false)
+        } else {
+            false
+        }
+    }
+
     /// get the chain tip optional query argument (`tip`)
     /// Take the first value we can parse.
     fn get_chain_tip_query(query: Option<&str>) -> Option<StacksBlockId> {
@@ -1696,6 +1936,27 @@ impl HttpRequestType {
         }
     }
 
+    /// get the chain tip optional query argument (`tip`) for requests that can also be answered
+    /// against the unconfirmed state, with `tip=unconfirmed`.
+    /// Take the first value we can parse.
+    fn get_tip_request_query(query: Option<&str>) -> TipRequest {
+        if let Some(query_string) = query {
+            for (key, value) in form_urlencoded::parse(query_string.as_bytes()) {
+                if key != "tip" {
+                    continue;
+                }
+
+                if value == "unconfirmed" {
+                    return TipRequest::UseLatestUnconfirmedTip;
+                }
+                if let Ok(tip) = StacksBlockId::from_hex(&value) {
+                    return TipRequest::SpecificTip(tip);
+                }
+            }
+        }
+        TipRequest::UseLatestAnchoredTip
+    }
+
     fn parse_get_account<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
@@ -1714,7 +1975,7 @@ impl HttpRequestType {
         })?;
 
         let with_proof = HttpRequestType::get_proof_query(query);
-        let tip = HttpRequestType::get_chain_tip_query(query);
+        let tip = HttpRequestType::get_tip_request_query(query);
 
         Ok(HttpRequestType::GetAccount(
             HttpRequestMetadata::from_preamble(preamble),
@@ -1724,204 +1985,200 @@ impl HttpRequestType {
         ))
     }
 
-    fn parse_get_map_entry<R: Read>(
+    fn parse_get_account_transactions<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
         captures: &Captures,
         query: Option<&str>,
-        fd: &mut R,
+        _fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
-        let content_len = preamble.get_content_length();
-        if !(content_len > 0 && content_len < (BOUND_VALUE_SERIALIZATION_HEX)) {
-            return Err(net_error::DeserializeError(format!(
-                "Invalid Http request: invalid body length for GetMapEntry ({})",
-                content_len
-            )));
-        }
-
-        if preamble.content_type != Some(HttpContentType::JSON) {
+        if preamble.get_content_length() != 0 {
             return Err(net_error::DeserializeError(
-                "Invalid content-type: expected application/json".into(),
+                "Invalid Http request: expected 0-length body for GetAccountTransactions"
+                    .to_string(),
             ));
         }
 
-        let contract_addr = StacksAddress::from_string(&captures["address"]).ok_or_else(|| {
-            net_error::DeserializeError("Failed to parse contract address".into())
+        let principal = PrincipalData::parse(&captures["principal"]).map_err(|_e| {
+            net_error::DeserializeError("Failed to parse account principal".into())
         })?;
-        let contract_name = ContractName::try_from(captures["contract"].to_string())
-            .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
-        let map_name = ClarityName::try_from(captures["map"].to_string())
-            .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
-
-        let value_hex: String = serde_json::from_reader(fd)
-            .map_err(|_e| net_error::DeserializeError("Failed to parse JSON body".into()))?;
 
-        let value = Value::try_deserialize_hex_untyped(&value_hex)
-            .map_err(|_e| net_error::DeserializeError("Failed to deserialize key value".into()))?;
+        let mut before_height = None;
+        let mut before_tx_index = None;
+        let mut limit = ACCOUNT_TRANSACTIONS_DEFAULT_LIMIT;
+        if let Some(query_string) = query {
+            for (key, value) in form_urlencoded::parse(query_string.as_bytes()) {
+                if key == "before_height" {
+                    before_height = Some(value.parse::<u64>().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse before_height".into())
+                    })?);
+                } else if key == "before_tx_index" {
+                    before_tx_index = Some(value.parse::<u32>().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse before_tx_index".into())
+                    })?);
+                } else if key == "limit" {
+                    limit = value.parse::<u64>().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse limit".into())
+                    })?;
+                }
+            }
+        }
+        if limit == 0 || limit > ACCOUNT_TRANSACTIONS_MAX_LIMIT {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid limit: must be between 1 and {}",
+                ACCOUNT_TRANSACTIONS_MAX_LIMIT
+            )));
+        }
+        let before = match (before_height, before_tx_index) {
+            (Some(height), Some(tx_index)) => Some((height, tx_index)),
+            (None, None) => None,
+            _ => {
+                return Err(net_error::DeserializeError(
+                    "before_height and before_tx_index must be given together".to_string(),
+                ));
+            }
+        };
 
-        let with_proof = HttpRequestType::get_proof_query(query);
         let tip = HttpRequestType::get_chain_tip_query(query);
 
-        Ok(HttpRequestType::GetMapEntry(
+        Ok(HttpRequestType::GetAccountTransactions(
             HttpRequestMetadata::from_preamble(preamble),
-            contract_addr,
-            contract_name,
-            map_name,
-            value,
+            principal,
+            before,
+            limit,
             tip,
-            with_proof,
         ))
     }
 
-    fn parse_call_read_only<R: Read>(
-        protocol: &mut StacksHttp,
+    fn parse_get_sponsorship<R: Read>(
+        _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
         captures: &Captures,
         query: Option<&str>,
-        fd: &mut R,
+        _fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
-        let content_len = preamble.get_content_length();
-        if !(content_len > 0 && content_len < protocol.maximum_call_argument_size) {
-            return Err(net_error::DeserializeError(format!(
-                "Invalid Http request: invalid body length for CallReadOnly ({})",
-                content_len
-            )));
-        }
-
-        if preamble.content_type != Some(HttpContentType::JSON) {
+        if preamble.get_content_length() != 0 {
             return Err(net_error::DeserializeError(
-                "Invalid content-type: expected application/json".to_string(),
+                "Invalid Http request: expected 0-length body for GetSponsorship".to_string(),
             ));
         }
 
-        let contract_addr = StacksAddress::from_string(&captures["address"]).ok_or_else(|| {
-            net_error::DeserializeError("Failed to parse contract address".into())
-        })?;
-        let contract_name = ContractName::try_from(captures["contract"].to_string())
-            .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
-        let func_name = ClarityName::try_from(captures["function"].to_string())
-            .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
-
-        let body: CallReadOnlyRequestBody = serde_json::from_reader(fd)
-            .map_err(|_e| net_error::DeserializeError("Failed to parse JSON body".into()))?;
-
-        let sender = PrincipalData::parse(&body.sender)
-            .map_err(|_e| net_error::DeserializeError("Failed to parse sender principal".into()))?;
-
-        let arguments = body
-            .arguments
-            .into_iter()
-            .map(|hex| Value::try_deserialize_hex_untyped(&hex).ok())
-            .collect::<Option<Vec<Value>>>()
-            .ok_or_else(|| {
-                net_error::DeserializeError("Failed to deserialize argument value".into())
-            })?;
+        let sponsor = StacksAddress::from_string(&captures["principal"])
+            .ok_or_else(|| net_error::DeserializeError("Failed to parse sponsor address".into()))?;
 
         let tip = HttpRequestType::get_chain_tip_query(query);
 
-        Ok(HttpRequestType::CallReadOnlyFunction(
+        Ok(HttpRequestType::GetSponsorship(
             HttpRequestMetadata::from_preamble(preamble),
-            contract_addr,
-            contract_name,
-            sender,
-            func_name,
-            arguments,
+            sponsor,
             tip,
         ))
     }
 
-    fn parse_get_contract_arguments(
+    fn parse_get_account_balance<R: Read>(
+        _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
         captures: &Captures,
-    ) -> Result<(HttpRequestMetadata, StacksAddress, ContractName), net_error> {
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
         if preamble.get_content_length() != 0 {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: expected 0-length body".to_string(),
+                "Invalid Http request: expected 0-length body for GetAccountBalance".to_string(),
             ));
         }
 
-        let contract_addr = StacksAddress::from_string(&captures["address"]).ok_or_else(|| {
-            net_error::DeserializeError("Failed to parse contract address".into())
-        })?;
-        let contract_name = ContractName::try_from(captures["contract"].to_string())
-            .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
+        let principal = PrincipalData::parse(&captures["principal"])
+            .map_err(|_e| net_error::DeserializeError("Failed to parse principal".into()))?;
 
-        Ok((
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::GetAccountBalance(
             HttpRequestMetadata::from_preamble(preamble),
-            contract_addr,
-            contract_name,
+            principal,
+            tip,
         ))
     }
 
-    fn parse_get_contract_abi<R: Read>(
+    fn parse_get_transaction_receipt<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
         captures: &Captures,
         query: Option<&str>,
         _fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetTransactionReceipt"
+                    .to_string(),
+            ));
+        }
+
+        let txid = Txid::from_hex(&captures["txid"])
+            .map_err(|_e| net_error::DeserializeError("Failed to decode txid hex".to_string()))?;
+
         let tip = HttpRequestType::get_chain_tip_query(query);
-        HttpRequestType::parse_get_contract_arguments(preamble, captures).map(
-            |(preamble, addr, name)| HttpRequestType::GetContractABI(preamble, addr, name, tip),
-        )
+
+        Ok(HttpRequestType::GetTransactionReceipt(
+            HttpRequestMetadata::from_preamble(preamble),
+            txid,
+            tip,
+        ))
     }
 
-    fn parse_get_contract_source<R: Read>(
+    fn parse_get_stacking_status<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
         captures: &Captures,
         query: Option<&str>,
         _fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
-        let with_proof = HttpRequestType::get_proof_query(query);
-        let tip = HttpRequestType::get_chain_tip_query(query);
-        HttpRequestType::parse_get_contract_arguments(preamble, captures).map(
-            |(preamble, addr, name)| {
-                HttpRequestType::GetContractSrc(preamble, addr, name, tip, with_proof)
-            },
-        )
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetStackingStatus".to_string(),
+            ));
+        }
+
+        let principal = PrincipalData::parse(&captures["principal"]).map_err(|_e| {
+            net_error::DeserializeError("Failed to parse stacker principal".into())
+        })?;
+
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::GetStackingStatus(
+            HttpRequestMetadata::from_preamble(preamble),
+            principal,
+            tip,
+        ))
     }
 
-    fn parse_get_is_trait_implemented<R: Read>(
+    fn parse_get_delegations<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
         captures: &Captures,
         query: Option<&str>,
         _fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
-        let tip = HttpRequestType::get_chain_tip_query(query);
         if preamble.get_content_length() != 0 {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: expected 0-length body".to_string(),
+                "Invalid Http request: expected 0-length body for GetDelegations".to_string(),
             ));
         }
 
-        let contract_addr = StacksAddress::from_string(&captures["address"]).ok_or_else(|| {
-            net_error::DeserializeError("Failed to parse contract address".into())
+        let principal = PrincipalData::parse(&captures["principal"]).map_err(|_e| {
+            net_error::DeserializeError("Failed to parse delegatee principal".into())
         })?;
-        let contract_name = ContractName::try_from(captures["contract"].to_string())
-            .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
-        let trait_name = ClarityName::try_from(captures["traitName"].to_string())
-            .map_err(|_e| net_error::DeserializeError("Failed to parse trait name".into()))?;
-        let trait_contract_addr = StacksAddress::from_string(&captures["traitContractAddr"])
-            .ok_or_else(|| net_error::DeserializeError("Failed to parse contract address".into()))?
-            .into();
-        let trait_contract_name = ContractName::try_from(captures["traitContractName"].to_string())
-            .map_err(|_e| {
-                net_error::DeserializeError("Failed to parse trait contract name".into())
-            })?;
-        let trait_id = TraitIdentifier::new(trait_contract_addr, trait_contract_name, trait_name);
 
-        Ok(HttpRequestType::GetIsTraitImplemented(
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::GetDelegations(
             HttpRequestMetadata::from_preamble(preamble),
-            contract_addr,
-            contract_name,
-            trait_id,
+            principal,
             tip,
         ))
     }
 
-    fn parse_getblock<R: Read>(
+    fn parse_get_reward_set<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
         captures: &Captures,
@@ -1930,27 +2187,21 @@ impl HttpRequestType {
     ) -> Result<HttpRequestType, net_error> {
         if preamble.get_content_length() != 0 {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: expected 0-length body for GetBlock".to_string(),
+                "Invalid Http request: expected 0-length body for GetRewardSet".to_string(),
             ));
         }
 
-        let block_hash_str = captures
-            .get(1)
-            .ok_or(net_error::DeserializeError(
-                "Failed to match path to block hash group".to_string(),
-            ))?
-            .as_str();
-
-        let block_hash = StacksBlockId::from_hex(block_hash_str)
-            .map_err(|_e| net_error::DeserializeError("Failed to parse block hash".to_string()))?;
+        let reward_cycle = captures["reward_cycle"]
+            .parse::<u64>()
+            .map_err(|_e| net_error::DeserializeError("Failed to parse reward cycle".into()))?;
 
-        Ok(HttpRequestType::GetBlock(
+        Ok(HttpRequestType::GetRewardSet(
             HttpRequestMetadata::from_preamble(preamble),
-            block_hash,
+            reward_cycle,
         ))
     }
 
-    fn parse_getmicroblocks_indexed<R: Read>(
+    fn parse_get_sortition<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
         captures: &Captures,
@@ -1959,3085 +2210,4877 @@ impl HttpRequestType {
     ) -> Result<HttpRequestType, net_error> {
         if preamble.get_content_length() != 0 {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: expected 0-length body for GetMicroblocksIndexed"
-                    .to_string(),
+                "Invalid Http request: expected 0-length body for GetSortition".to_string(),
             ));
         }
 
-        let block_hash_str = captures
-            .get(1)
-            .ok_or(net_error::DeserializeError(
-                "Failed to match path to microblock hash group".to_string(),
-            ))?
-            .as_str();
-
-        let block_hash = StacksBlockId::from_hex(block_hash_str).map_err(|_e| {
-            net_error::DeserializeError("Failed to parse microblock hash".to_string())
-        })?;
+        let value = &captures["value"];
+        let lookup = match &captures["lookup"] {
+            "burn_height" => SortitionLookup::BurnHeight(value.parse::<u64>().map_err(|_e| {
+                net_error::DeserializeError("Failed to parse burn block height".into())
+            })?),
+            "burn_hash" => {
+                SortitionLookup::BurnHash(BurnchainHeaderHash::from_hex(value).map_err(|_e| {
+                    net_error::DeserializeError("Failed to parse burn block hash".into())
+                })?)
+            }
+            _ => SortitionLookup::StacksBlock(StacksBlockId::from_hex(value).map_err(|_e| {
+                net_error::DeserializeError("Failed to parse index block hash".into())
+            })?),
+        };
 
-        Ok(HttpRequestType::GetMicroblocksIndexed(
+        Ok(HttpRequestType::GetSortition(
             HttpRequestMetadata::from_preamble(preamble),
-            block_hash,
+            lookup,
         ))
     }
 
-    fn parse_getmicroblocks_confirmed<R: Read>(
+    fn parse_get_consensus_schedule<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
-        captures: &Captures,
+        _captures: &Captures,
         _query: Option<&str>,
         _fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
         if preamble.get_content_length() != 0 {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: expected 0-length body for GetMicrolocks".to_string(),
+                "Invalid Http request: expected 0-length body for GetConsensusSchedule".to_string(),
             ));
         }
 
-        let block_hash_str = captures
-            .get(1)
-            .ok_or(net_error::DeserializeError(
-                "Failed to match path to microblock hash group".to_string(),
-            ))?
-            .as_str();
-
-        let block_hash = StacksBlockId::from_hex(block_hash_str).map_err(|_e| {
-            net_error::DeserializeError("Failed to parse microblock hash".to_string())
-        })?;
-
-        Ok(HttpRequestType::GetMicroblocksConfirmed(
+        Ok(HttpRequestType::GetConsensusSchedule(
             HttpRequestMetadata::from_preamble(preamble),
-            block_hash,
         ))
     }
 
-    fn parse_getmicroblocks_unconfirmed<R: Read>(
+    fn parse_get_microblock_equivocations<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
-        captures: &Captures,
+        _captures: &Captures,
         _query: Option<&str>,
         _fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
         if preamble.get_content_length() != 0 {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: expected 0-length body for GetMicrolocksUnconfirmed"
+                "Invalid Http request: expected 0-length body for GetMicroblockEquivocations"
                     .to_string(),
             ));
         }
 
-        let block_hash_str = captures
-            .get(1)
-            .ok_or(net_error::DeserializeError(
-                "Failed to match path to microblock hash group".to_string(),
-            ))?
-            .as_str();
-
-        let min_seq_str = captures
-            .get(2)
-            .ok_or(net_error::DeserializeError(
-                "Failed to match path to microblock minimum sequence group".to_string(),
-            ))?
-            .as_str();
-
-        let block_hash = StacksBlockId::from_hex(block_hash_str).map_err(|_e| {
-            net_error::DeserializeError("Failed to parse microblock hash".to_string())
-        })?;
-
-        let min_seq = min_seq_str.parse::<u16>().map_err(|_e| {
-            net_error::DeserializeError("Failed to parse microblock minimum sequence".to_string())
-        })?;
-
-        Ok(HttpRequestType::GetMicroblocksUnconfirmed(
+        Ok(HttpRequestType::GetMicroblockEquivocations(
             HttpRequestMetadata::from_preamble(preamble),
-            block_hash,
-            min_seq,
         ))
     }
 
-    fn parse_gettransaction_unconfirmed<R: Read>(
+    /// Get the token from an `Authorization: Bearer <token>` header, if there is one
+    fn parse_bearer_token(preamble: &HttpRequestPreamble) -> Option<BearerToken> {
+        let value = preamble.headers.get("authorization")?;
+        let mut parts = value.trim().splitn(2, ' ');
+        match (parts.next(), parts.next()) {
+            (Some(scheme), Some(token)) if scheme.eq_ignore_ascii_case("bearer") => {
+                Some(BearerToken(token.trim().to_string()))
+            }
+            _ => None,
+        }
+    }
+
+    fn parse_get_admin_toggles<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
-        regex: &Captures,
+        _captures: &Captures,
         _query: Option<&str>,
         _fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
         if preamble.get_content_length() != 0 {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: expected 0-length body for GetMicrolocksUnconfirmed"
-                    .to_string(),
-            ));
-        }
-
-        let txid_hex = regex
-            .get(1)
-            .ok_or(net_error::DeserializeError(
-                "Failed to match path to txid group".to_string(),
-            ))?
-            .as_str();
-
-        if txid_hex.len() != 64 {
-            return Err(net_error::DeserializeError(
-                "Invalid txid: expected 64 bytes".to_string(),
+                "Invalid Http request: expected 0-length body for GetAdminToggles".to_string(),
             ));
         }
 
-        let txid = Txid::from_hex(&txid_hex)
-            .map_err(|_e| net_error::DeserializeError("Failed to decode txid hex".to_string()))?;
-
-        Ok(HttpRequestType::GetTransactionUnconfirmed(
+        Ok(HttpRequestType::GetAdminToggles(
             HttpRequestMetadata::from_preamble(preamble),
-            txid,
+            HttpRequestType::parse_bearer_token(preamble),
         ))
     }
 
-    fn parse_posttransaction<R: Read>(
-        _protocol: &mut StacksHttp,
+    fn parse_post_admin_toggles<R: Read>(
+        protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
-        _regex: &Captures,
+        _captures: &Captures,
         _query: Option<&str>,
         fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
-        if preamble.get_content_length() == 0 {
-            return Err(net_error::DeserializeError(
-                "Invalid Http request: expected non-zero-length body for PostTransaction"
-                    .to_string(),
-            ));
+        let content_len = preamble.get_content_length();
+        if !(content_len > 0 && content_len < protocol.maximum_call_argument_size) {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid Http request: invalid body length for PostAdminToggles ({})",
+                content_len
+            )));
         }
 
-        if preamble.get_content_length() > MAX_PAYLOAD_LEN {
+        if preamble.content_type != Some(HttpContentType::JSON) {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: PostTransaction body is too big".to_string(),
+                "Invalid content-type: expected application/json".to_string(),
             ));
         }
 
-        let mut bound_fd = BoundReader::from_reader(fd, preamble.get_content_length() as u64);
-
-        match preamble.content_type {
-            None => {
-                return Err(net_error::DeserializeError(
-                    "Missing Content-Type for transaction".to_string(),
-                ));
-            }
-            Some(HttpContentType::Bytes) => {
-                HttpRequestType::parse_posttransaction_octets(preamble, &mut bound_fd)
-            }
-            Some(HttpContentType::JSON) => {
-                HttpRequestType::parse_posttransaction_json(preamble, &mut bound_fd)
-            }
-            _ => {
-                return Err(net_error::DeserializeError(
-                    "Wrong Content-Type for transaction; expected application/json".to_string(),
-                ));
-            }
-        }
-    }
+        let update: RPCAdminTogglesUpdate = serde_json::from_reader(fd)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse JSON body".into()))?;
 
-    fn parse_posttransaction_octets<R: Read>(
-        preamble: &HttpRequestPreamble,
-        fd: &mut R,
-    ) -> Result<HttpRequestType, net_error> {
-        let tx = StacksTransaction::consensus_deserialize(fd).map_err(|e| {
-            if let codec_error::DeserializeError(msg) = e {
-                net_error::ClientError(ClientError::Message(format!(
-                    "Failed to deserialize posted transaction: {}",
-                    msg
-                )))
-            } else {
-                e.into()
-            }
-        })?;
-        Ok(HttpRequestType::PostTransaction(
+        Ok(HttpRequestType::PostAdminToggles(
             HttpRequestMetadata::from_preamble(preamble),
-            tx,
-            None,
+            HttpRequestType::parse_bearer_token(preamble),
+            update,
         ))
     }
 
-    fn parse_posttransaction_json<R: Read>(
+    fn parse_post_transfer_stx_op<R: Read>(
+        protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
+        _captures: &Captures,
+        _query: Option<&str>,
         fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
-        let body: PostTransactionRequestBody = serde_json::from_reader(fd)
-            .map_err(|_e| net_error::DeserializeError("Failed to parse body".into()))?;
+        let content_len = preamble.get_content_length();
+        if !(content_len > 0 && content_len < protocol.maximum_call_argument_size) {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid Http request: invalid body length for PostTransferStxOp ({})",
+                content_len
+            )));
+        }
 
-        let tx = {
-            let tx_bytes = hex_bytes(&body.tx)
-                .map_err(|_e| net_error::DeserializeError("Failed to parse tx".into()))?;
-            StacksTransaction::consensus_deserialize(&mut &tx_bytes[..]).map_err(|e| {
-                if let codec_error::DeserializeError(msg) = e {
-                    net_error::ClientError(ClientError::Message(format!(
-                        "Failed to deserialize posted transaction: {}",
-                        msg
-                    )))
-                } else {
-                    e.into()
-                }
-            })
-        }?;
+        if preamble.content_type != Some(HttpContentType::JSON) {
+            return Err(net_error::DeserializeError(
+                "Invalid content-type: expected application/json".to_string(),
+            ));
+        }
 
-        let attachment = match body.attachment {
-            None => None,
-            Some(attachment_content) => {
-                let content = hex_bytes(&attachment_content).map_err(|_e| {
-                    net_error::DeserializeError("Failed to parse attachment".into())
-                })?;
-                Some(Attachment::new(content))
-            }
-        };
+        let request: RPCTransferStxOpRequest = serde_json::from_reader(fd)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse JSON body".into()))?;
 
-        Ok(HttpRequestType::PostTransaction(
+        Ok(HttpRequestType::PostTransferStxOp(
             HttpRequestMetadata::from_preamble(preamble),
-            tx,
-            attachment,
+            HttpRequestType::parse_bearer_token(preamble),
+            request,
         ))
     }
 
-    fn parse_postblock<R: Read>(
+    fn parse_post_block_proposal<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
-        regex: &Captures,
+        _captures: &Captures,
         _query: Option<&str>,
         fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
-        if preamble.get_content_length() == 0 {
-            return Err(net_error::DeserializeError(
-                "Invalid Http request: expected non-zero-length body for PostBlock".to_string(),
-            ));
-        }
-
-        if preamble.get_content_length() > MAX_PAYLOAD_LEN {
+        let content_len = preamble.get_content_length();
+        if !(content_len > 0 && content_len <= MAX_PAYLOAD_LEN) {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid Http request: invalid body length for PostBlockProposal ({})",
+                content_len
+            )));
+        }
+
+        if preamble.content_type != Some(HttpContentType::JSON) {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: PostBlock body is too big".to_string(),
+                "Invalid content-type: expected application/json".to_string(),
             ));
         }
 
-        // content-type must be given, and must be application/octet-stream
-        match preamble.content_type {
-            None => {
-                return Err(net_error::DeserializeError(
-                    "Missing Content-Type for Stacks block".to_string(),
-                ));
-            }
-            Some(ref c) => {
-                if *c != HttpContentType::Bytes {
-                    return Err(net_error::DeserializeError(
-                        "Wrong Content-Type for Stacks block; expected application/octet-stream"
-                            .to_string(),
-                    ));
-                }
-            }
-        };
-
-        let consensus_hash_str = regex
-            .get(1)
-            .ok_or(net_error::DeserializeError(
-                "Failed to match consensus hash in path group".to_string(),
-            ))?
-            .as_str();
-
-        let consensus_hash: ConsensusHash =
-            ConsensusHash::from_hex(consensus_hash_str).map_err(|_| {
-                net_error::DeserializeError("Failed to parse consensus hash".to_string())
-            })?;
-
-        let mut bound_fd = BoundReader::from_reader(fd, preamble.get_content_length() as u64);
-        let stacks_block = StacksBlock::consensus_deserialize(&mut bound_fd)?;
+        let mut bound_fd = BoundReader::from_reader(fd, content_len as u64);
+        let request: RPCBlockProposalRequest = serde_json::from_reader(&mut bound_fd)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse JSON body".into()))?;
 
-        Ok(HttpRequestType::PostBlock(
+        Ok(HttpRequestType::PostBlockProposal(
             HttpRequestMetadata::from_preamble(preamble),
-            consensus_hash,
-            stacks_block,
+            HttpRequestType::parse_bearer_token(preamble),
+            request,
         ))
     }
 
-    fn parse_postmicroblock<R: Read>(
+    fn parse_get_miner_wallet<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
-        _regex: &Captures,
-        query: Option<&str>,
-        fd: &mut R,
+        _captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
-        if preamble.get_content_length() == 0 {
+        if preamble.get_content_length() != 0 {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: expected non-zero-length body for PostMicroblock"
-                    .to_string(),
+                "Invalid Http request: expected 0-length body for GetMinerWallet".to_string(),
             ));
         }
 
-        if preamble.get_content_length() > MAX_PAYLOAD_LEN {
+        Ok(HttpRequestType::GetMinerWallet(
+            HttpRequestMetadata::from_preamble(preamble),
+            HttpRequestType::parse_bearer_token(preamble),
+        ))
+    }
+
+    fn parse_get_clarity_profiles<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: PostMicroblock body is too big".to_string(),
+                "Invalid Http request: expected 0-length body for GetClarityProfiles".to_string(),
             ));
         }
 
-        // content-type must be given, and must be application/octet-stream
-        match preamble.content_type {
-            None => {
-                return Err(net_error::DeserializeError(
-                    "Missing Content-Type for microblock".to_string(),
-                ));
-            }
-            Some(ref c) => {
-                if *c != HttpContentType::Bytes {
-                    return Err(net_error::DeserializeError(
-                        "Wrong Content-Type for microblock; expected application/octet-stream"
-                            .to_string(),
-                    ));
-                }
-            }
-        };
-
-        let mut bound_fd = BoundReader::from_reader(fd, preamble.get_content_length() as u64);
-
-        let mb = StacksMicroblock::consensus_deserialize(&mut bound_fd)?;
-        let tip = HttpRequestType::get_chain_tip_query(query);
-
-        Ok(HttpRequestType::PostMicroblock(
+        Ok(HttpRequestType::GetClarityProfiles(
             HttpRequestMetadata::from_preamble(preamble),
-            mb,
-            tip,
+            HttpRequestType::parse_bearer_token(preamble),
         ))
     }
 
-    fn parse_get_attachment<R: Read>(
+    /// Decode a Clarity value given either as a hex string of its consensus serialization, or in
+    /// its canonical JSON encoding.
+    fn parse_clarity_value_json(value_json: &serde_json::Value) -> Option<Value> {
+        match value_json {
+            serde_json::Value::String(hex) => Value::try_deserialize_hex_untyped(hex).ok(),
+            _ => Value::try_from_json(value_json).ok(),
+        }
+    }
+
+    fn parse_get_map_entry<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
         captures: &Captures,
-        _query: Option<&str>,
-        _fd: &mut R,
+        query: Option<&str>,
+        fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
-        if preamble.get_content_length() != 0 {
+        let content_len = preamble.get_content_length();
+        if !(content_len > 0 && content_len < (BOUND_VALUE_SERIALIZATION_HEX)) {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid Http request: invalid body length for GetMapEntry ({})",
+                content_len
+            )));
+        }
+
+        if preamble.content_type != Some(HttpContentType::JSON) {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: expected 0-length body".to_string(),
+                "Invalid content-type: expected application/json".into(),
             ));
         }
-        let hex_content_hash = captures
-            .get(1)
-            .ok_or(net_error::DeserializeError(
-                "Failed to match path to attachment hash group".to_string(),
-            ))?
-            .as_str();
 
-        let content_hash = Hash160::from_hex(&hex_content_hash).map_err(|_| {
-            net_error::DeserializeError("Failed to construct hash160 from inputs".to_string())
+        let contract_addr = StacksAddress::from_string(&captures["address"]).ok_or_else(|| {
+            net_error::DeserializeError("Failed to parse contract address".into())
         })?;
+        let contract_name = ContractName::try_from(captures["contract"].to_string())
+            .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
+        let map_name = ClarityName::try_from(captures["map"].to_string())
+            .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
 
-        Ok(HttpRequestType::GetAttachment(
+        let value_json: serde_json::Value = serde_json::from_reader(fd)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse JSON body".into()))?;
+
+        let value = HttpRequestType::parse_clarity_value_json(&value_json)
+            .ok_or_else(|| net_error::DeserializeError("Failed to deserialize key value".into()))?;
+
+        let with_proof = HttpRequestType::get_proof_query(query);
+        let tip = HttpRequestType::get_tip_request_query(query);
+
+        Ok(HttpRequestType::GetMapEntry(
             HttpRequestMetadata::from_preamble(preamble),
-            content_hash,
+            contract_addr,
+            contract_name,
+            map_name,
+            value,
+            tip,
+            with_proof,
         ))
     }
 
-    fn parse_get_attachments_inv<R: Read>(
-        _protocol: &mut StacksHttp,
+    fn parse_call_read_only<R: Read>(
+        protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
-        _captures: &Captures,
+        captures: &Captures,
         query: Option<&str>,
-        _fd: &mut R,
+        fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
-        if preamble.get_content_length() != 0 {
+        let content_len = preamble.get_content_length();
+        if !(content_len > 0 && content_len < protocol.maximum_call_argument_size) {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid Http request: invalid body length for CallReadOnly ({})",
+                content_len
+            )));
+        }
+
+        if preamble.content_type != Some(HttpContentType::JSON) {
             return Err(net_error::DeserializeError(
-                "Invalid Http request: expected 0-length body".to_string(),
+                "Invalid content-type: expected application/json".to_string(),
             ));
         }
 
-        let (index_block_hash, pages_indexes) = match query {
-            None => {
-                return Err(net_error::DeserializeError(
-                    "Invalid Http request: expecting index_block_hash and pages_indexes"
-                        .to_string(),
-                ));
-            }
-            Some(query) => {
-                let mut index_block_hash = None;
-                let mut pages_indexes = HashSet::new();
+        let contract_addr = StacksAddress::from_string(&captures["address"]).ok_or_else(|| {
+            net_error::DeserializeError("Failed to parse contract address".into())
+        })?;
+        let contract_name = ContractName::try_from(captures["contract"].to_string())
+            .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
+        let func_name = ClarityName::try_from(captures["function"].to_string())
+            .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
 
-                for (key, value) in form_urlencoded::parse(query.as_bytes()) {
-                    if key == "index_block_hash" {
-                        index_block_hash = match StacksBlockId::from_hex(&value) {
-                            Ok(index_block_hash) => Some(index_block_hash),
-                            _ => None,
-                        };
-                    } else if key == "pages_indexes" {
-                        if let Ok(pages_indexes_value) = value.parse::<String>() {
-                            for entry in pages_indexes_value.split(",") {
-                                if let Ok(page_index) = entry.parse::<u32>() {
-                                    pages_indexes.insert(page_index);
-                                }
-                            }
-                        }
-                    }
-                }
+        let body: CallReadOnlyRequestBody = serde_json::from_reader(fd)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse JSON body".into()))?;
 
-                let index_block_hash = match index_block_hash {
-                    None => {
-                        return Err(net_error::DeserializeError(
-                            "Invalid Http request: expecting index_block_hash".to_string(),
-                        ));
-                    }
-                    Some(index_block_hash) => index_block_hash,
-                };
+        let sender = PrincipalData::parse(&body.sender)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse sender principal".into()))?;
 
-                if pages_indexes.is_empty() {
-                    return Err(net_error::DeserializeError(
-                        "Invalid Http request: expecting pages_indexes".to_string(),
-                    ));
-                }
+        let arguments = body
+            .arguments
+            .iter()
+            .map(HttpRequestType::parse_clarity_value_json)
+            .collect::<Option<Vec<Value>>>()
+            .ok_or_else(|| {
+                net_error::DeserializeError("Failed to deserialize argument value".into())
+            })?;
 
-                (index_block_hash, pages_indexes)
-            }
-        };
+        let tip = HttpRequestType::get_tip_request_query(query);
 
-        Ok(HttpRequestType::GetAttachmentsInv(
+        Ok(HttpRequestType::CallReadOnlyFunction(
             HttpRequestMetadata::from_preamble(preamble),
-            index_block_hash,
-            pages_indexes,
+            contract_addr,
+            contract_name,
+            sender,
+            func_name,
+            arguments,
+            tip,
         ))
     }
 
-    fn parse_options_preflight<R: Read>(
-        _protocol: &mut StacksHttp,
+    fn parse_simulate_contract_call<R: Read>(
+        protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
-        _regex: &Captures,
-        _query: Option<&str>,
-        _fd: &mut R,
+        captures: &Captures,
+        query: Option<&str>,
+        fd: &mut R,
     ) -> Result<HttpRequestType, net_error> {
-        Ok(HttpRequestType::OptionsPreflight(
-            HttpRequestMetadata::from_preamble(preamble),
-            preamble.path.to_string(),
-        ))
-    }
+        let content_len = preamble.get_content_length();
+        if !(content_len > 0 && content_len < protocol.maximum_call_argument_size) {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid Http request: invalid body length for SimulateContractCall ({})",
+                content_len
+            )));
+        }
 
-    pub fn metadata(&self) -> &HttpRequestMetadata {
-        match *self {
-            HttpRequestType::GetInfo(ref md) => md,
-            HttpRequestType::GetPoxInfo(ref md, _) => md,
-            HttpRequestType::GetNeighbors(ref md) => md,
-            HttpRequestType::GetBlock(ref md, _) => md,
-            HttpRequestType::GetMicroblocksIndexed(ref md, _) => md,
-            HttpRequestType::GetMicroblocksConfirmed(ref md, _) => md,
-            HttpRequestType::GetMicroblocksUnconfirmed(ref md, _, _) => md,
-            HttpRequestType::GetTransactionUnconfirmed(ref md, _) => md,
-            HttpRequestType::PostTransaction(ref md, _, _) => md,
-            HttpRequestType::PostBlock(ref md, ..) => md,
-            HttpRequestType::PostMicroblock(ref md, ..) => md,
-            HttpRequestType::GetAccount(ref md, ..) => md,
-            HttpRequestType::GetMapEntry(ref md, ..) => md,
-            HttpRequestType::GetTransferCost(ref md) => md,
-            HttpRequestType::GetContractABI(ref md, ..) => md,
-            HttpRequestType::GetContractSrc(ref md, ..) => md,
-            HttpRequestType::GetIsTraitImplemented(ref md, ..) => md,
-            HttpRequestType::CallReadOnlyFunction(ref md, ..) => md,
-            HttpRequestType::OptionsPreflight(ref md, ..) => md,
-            HttpRequestType::GetAttachmentsInv(ref md, ..) => md,
-            HttpRequestType::GetAttachment(ref md, ..) => md,
-            HttpRequestType::ClientError(ref md, ..) => md,
+        if preamble.content_type != Some(HttpContentType::JSON) {
+            return Err(net_error::DeserializeError(
+                "Invalid content-type: expected application/json".to_string(),
+            ));
         }
+
+        let contract_addr = StacksAddress::from_string(&captures["address"]).ok_or_else(|| {
+            net_error::DeserializeError("Failed to parse contract address".into())
+        })?;
+        let contract_name = ContractName::try_from(captures["contract"].to_string())
+            .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
+        let func_name = ClarityName::try_from(captures["function"].to_string())
+            .map_err(|_e| net_error::DeserializeError("Failed to parse function name".into()))?;
+
+        let body: SimulateContractCallRequestBody = serde_json::from_reader(fd)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse JSON body".into()))?;
+
+        let sender = PrincipalData::parse(&body.sender)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse sender principal".into()))?;
+
+        let arguments = body
+            .arguments
+            .into_iter()
+            .map(|hex| Value::try_deserialize_hex_untyped(&hex).ok())
+            .collect::<Option<Vec<Value>>>()
+            .ok_or_else(|| {
+                net_error::DeserializeError("Failed to deserialize argument value".into())
+            })?;
+
+        let overrides = body
+            .overrides
+            .into_iter()
+            .map(ClarityStateOverride::from_body)
+            .collect::<Option<Vec<ClarityStateOverride>>>()
+            .ok_or_else(|| net_error::DeserializeError("Failed to parse state override".into()))?;
+
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::SimulateContractCall(
+            HttpRequestMetadata::from_preamble(preamble),
+            contract_addr,
+            contract_name,
+            sender,
+            func_name,
+            arguments,
+            overrides,
+            tip,
+        ))
     }
 
-    pub fn metadata_mut(&mut self) -> &mut HttpRequestMetadata {
-        match *self {
-            HttpRequestType::GetInfo(ref mut md) => md,
-            HttpRequestType::GetPoxInfo(ref mut md, _) => md,
-            HttpRequestType::GetNeighbors(ref mut md) => md,
-            HttpRequestType::GetBlock(ref mut md, _) => md,
-            HttpRequestType::GetMicroblocksIndexed(ref mut md, _) => md,
-            HttpRequestType::GetMicroblocksConfirmed(ref mut md, _) => md,
-            HttpRequestType::GetMicroblocksUnconfirmed(ref mut md, _, _) => md,
-            HttpRequestType::GetTransactionUnconfirmed(ref mut md, _) => md,
-            HttpRequestType::PostTransaction(ref mut md, _, _) => md,
-            HttpRequestType::PostBlock(ref mut md, ..) => md,
-            HttpRequestType::PostMicroblock(ref mut md, ..) => md,
-            HttpRequestType::GetAccount(ref mut md, ..) => md,
-            HttpRequestType::GetMapEntry(ref mut md, ..) => md,
-            HttpRequestType::GetTransferCost(ref mut md) => md,
-            HttpRequestType::GetContractABI(ref mut md, ..) => md,
-            HttpRequestType::GetContractSrc(ref mut md, ..) => md,
-            HttpRequestType::GetIsTraitImplemented(ref mut md, ..) => md,
-            HttpRequestType::CallReadOnlyFunction(ref mut md, ..) => md,
-            HttpRequestType::OptionsPreflight(ref mut md, ..) => md,
-            HttpRequestType::GetAttachmentsInv(ref mut md, ..) => md,
-            HttpRequestType::GetAttachment(ref mut md, ..) => md,
-            HttpRequestType::ClientError(ref mut md, ..) => md,
+    fn parse_preview_postconditions<R: Read>(
+        protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _captures: &Captures,
+        query: Option<&str>,
+        fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let content_len = preamble.get_content_length();
+        if !(content_len > 0 && content_len < protocol.maximum_call_argument_size) {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid Http request: invalid body length for PreviewPostConditions ({})",
+                content_len
+            )));
+        }
+
+        if preamble.content_type != Some(HttpContentType::JSON) {
+            return Err(net_error::DeserializeError(
+                "Invalid content-type: expected application/json".to_string(),
+            ));
         }
+
+        let body: PostConditionsPreviewRequestBody = serde_json::from_reader(fd)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse JSON body".into()))?;
+        let tx_bytes = hex_bytes(&body.tx)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse tx".into()))?;
+        let tx = StacksTransaction::consensus_deserialize(&mut &tx_bytes[..]).map_err(|e| {
+            if let codec_error::DeserializeError(msg) = e {
+                net_error::ClientError(ClientError::Message(format!(
+                    "Failed to deserialize posted transaction: {}",
+                    msg
+                )))
+            } else {
+                e.into()
+            }
+        })?;
+
+        Ok(HttpRequestType::PreviewPostConditions(
+            HttpRequestMetadata::from_preamble(preamble),
+            tx,
+            HttpRequestType::get_tip_request_query(query),
+        ))
     }
 
-    fn make_query_string(tip_opt: Option<&StacksBlockId>, with_proof: bool) -> String {
-        if let Some(tip) = tip_opt {
-            format!("?tip={}{}", tip, if with_proof { "" } else { "&proof=0" })
-        } else if !with_proof {
-            format!("?proof=0")
-        } else {
-            "".to_string()
+    fn parse_get_contract_arguments(
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+    ) -> Result<(HttpRequestMetadata, StacksAddress, ContractName), net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body".to_string(),
+            ));
         }
+
+        let contract_addr = StacksAddress::from_string(&captures["address"]).ok_or_else(|| {
+            net_error::DeserializeError("Failed to parse contract address".into())
+        })?;
+        let contract_name = ContractName::try_from(captures["contract"].to_string())
+            .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
+
+        Ok((
+            HttpRequestMetadata::from_preamble(preamble),
+            contract_addr,
+            contract_name,
+        ))
     }
 
-    pub fn request_path(&self) -> String {
-        match self {
-            HttpRequestType::GetInfo(_md) => "/v2/info".to_string(),
-            HttpRequestType::GetPoxInfo(_md, tip_opt) => format!(
-                "/v2/pox{}",
-                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
-            ),
-            HttpRequestType::GetNeighbors(_md) => "/v2/neighbors".to_string(),
-            HttpRequestType::GetBlock(_md, block_hash) => {
-                format!("/v2/blocks/{}", block_hash.to_hex())
-            }
-            HttpRequestType::GetMicroblocksIndexed(_md, block_hash) => {
-                format!("/v2/microblocks/{}", block_hash.to_hex())
+    fn parse_get_contract_abi<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let tip = HttpRequestType::get_chain_tip_query(query);
+        HttpRequestType::parse_get_contract_arguments(preamble, captures).map(
+            |(preamble, addr, name)| HttpRequestType::GetContractABI(preamble, addr, name, tip),
+        )
+    }
+
+    fn parse_get_contract_events<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let (md, contract_addr, contract_name) =
+            HttpRequestType::parse_get_contract_arguments(preamble, captures)?;
+
+        let mut topic = None;
+        let mut before_height = None;
+        let mut before_tx_index = None;
+        let mut before_event_index = None;
+        let mut limit = CONTRACT_EVENTS_DEFAULT_LIMIT;
+        if let Some(query_string) = query {
+            for (key, value) in form_urlencoded::parse(query_string.as_bytes()) {
+                if key == "topic" {
+                    topic = Some(ClarityName::try_from(value.to_string()).map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse topic".into())
+                    })?);
+                } else if key == "before_height" {
+                    before_height = Some(value.parse::<u64>().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse before_height".into())
+                    })?);
+                } else if key == "before_tx_index" {
+                    before_tx_index = Some(value.parse::<u32>().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse before_tx_index".into())
+                    })?);
+                } else if key == "before_event_index" {
+                    before_event_index = Some(value.parse::<u32>().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse before_event_index".into())
+                    })?);
+                } else if key == "limit" {
+                    limit = value.parse::<u64>().map_err(|_e| {
+                        net_error::DeserializeError("Failed to parse limit".into())
+                    })?;
+                }
             }
-            HttpRequestType::GetMicroblocksConfirmed(_md, block_hash) => {
-                format!("/v2/microblocks/confirmed/{}", block_hash.to_hex())
+        }
+        if limit == 0 || limit > CONTRACT_EVENTS_MAX_LIMIT {
+            return Err(net_error::DeserializeError(format!(
+                "Invalid limit: must be between 1 and {}",
+                CONTRACT_EVENTS_MAX_LIMIT
+            )));
+        }
+        let before = match (before_height, before_tx_index, before_event_index) {
+            (Some(height), Some(tx_index), Some(event_index)) => {
+                Some((height, tx_index, event_index))
             }
-            HttpRequestType::GetMicroblocksUnconfirmed(_md, block_hash, min_seq) => format!(
-                "/v2/microblocks/unconfirmed/{}/{}",
-                block_hash.to_hex(),
-                min_seq
-            ),
-            HttpRequestType::GetTransactionUnconfirmed(_md, txid) => {
-                format!("/v2/transactions/unconfirmed/{}", txid)
+            (None, None, None) => None,
+            _ => {
+                return Err(net_error::DeserializeError(
+                    "before_height, before_tx_index and before_event_index must be given together"
+                        .to_string(),
+                ));
             }
-            HttpRequestType::PostTransaction(_md, ..) => "/v2/transactions".to_string(),
-            HttpRequestType::PostBlock(_md, ch, ..) => format!("/v2/blocks/upload/{}", &ch),
-            HttpRequestType::PostMicroblock(_md, _, tip_opt) => format!(
-                "/v2/microblocks{}",
-                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
-            ),
-            HttpRequestType::GetAccount(_md, principal, tip_opt, with_proof) => format!(
-                "/v2/accounts/{}{}",
-                &principal.to_string(),
-                HttpRequestType::make_query_string(tip_opt.as_ref(), *with_proof)
-            ),
-            HttpRequestType::GetMapEntry(
-                _md,
-                contract_addr,
-                contract_name,
-                map_name,
-                _key,
-                tip_opt,
-                with_proof,
-            ) => format!(
-                "/v2/map_entry/{}/{}/{}{}",
-                &contract_addr.to_string(),
-                contract_name.as_str(),
-                map_name.as_str(),
-                HttpRequestType::make_query_string(tip_opt.as_ref(), *with_proof)
-            ),
-            HttpRequestType::GetTransferCost(_md) => "/v2/fees/transfer".into(),
-            HttpRequestType::GetContractABI(_, contract_addr, contract_name, tip_opt) => format!(
-                "/v2/contracts/interface/{}/{}{}",
-                contract_addr,
-                contract_name.as_str(),
-                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
-            ),
-            HttpRequestType::GetContractSrc(
-                _,
-                contract_addr,
-                contract_name,
-                tip_opt,
-                with_proof,
-            ) => format!(
-                "/v2/contracts/source/{}/{}{}",
-                contract_addr,
-                contract_name.as_str(),
-                HttpRequestType::make_query_string(tip_opt.as_ref(), *with_proof)
-            ),
-            HttpRequestType::GetIsTraitImplemented(
-                _,
-                contract_addr,
-                contract_name,
-                trait_id,
-                tip_opt,
-            ) => format!(
-                "/v2/traits/{}/{}/{}/{}/{}{}",
-                contract_addr,
-                contract_name.as_str(),
-                trait_id.name.to_string(),
-                StacksAddress::from(trait_id.clone().contract_identifier.issuer),
-                trait_id.contract_identifier.name.as_str(),
-                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
-            ),
-            HttpRequestType::CallReadOnlyFunction(
-                _,
-                contract_addr,
-                contract_name,
-                _,
-                func_name,
-                _,
-                tip_opt,
-            ) => format!(
-                "/v2/contracts/call-read/{}/{}/{}{}",
-                contract_addr,
-                contract_name.as_str(),
-                func_name.as_str(),
-                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
-            ),
-            HttpRequestType::OptionsPreflight(_md, path) => path.to_string(),
-            HttpRequestType::GetAttachmentsInv(_md, index_block_hash, pages_indexes) => {
-                let pages_query = match pages_indexes.len() {
-                    0 => format!(""),
-                    _n => {
-                        let mut indexes = pages_indexes
-                            .iter()
-                            .map(|i| format!("{}", i))
-                            .collect::<Vec<String>>();
-                        indexes.sort();
-                        format!("&pages_indexes={}", indexes.join(","))
-                    }
-                };
-                let index_block_hash = format!("index_block_hash={}", index_block_hash);
-                format!("/v2/attachments/inv?{}{}", index_block_hash, pages_query,)
-            }
-            HttpRequestType::GetAttachment(_, content_hash) => {
-                format!("/v2/attachments/{}", to_hex(&content_hash.0[..]))
-            }
-            HttpRequestType::ClientError(_md, e) => match e {
-                ClientError::NotFound(path) => path.to_string(),
-                _ => "error path unknown".into(),
+        };
+
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::GetContractEvents(
+            md,
+            contract_addr,
+            contract_name,
+            topic,
+            before,
+            limit,
+            tip,
+        ))
+    }
+
+    fn parse_get_contract_source<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let with_proof = HttpRequestType::get_proof_query(query);
+        let tip = HttpRequestType::get_chain_tip_query(query);
+        HttpRequestType::parse_get_contract_arguments(preamble, captures).map(
+            |(preamble, addr, name)| {
+                HttpRequestType::GetContractSrc(preamble, addr, name, tip, with_proof)
             },
-        }
+        )
     }
 
-    pub fn get_path(&self) -> &str {
-        match self {
-            HttpRequestType::GetInfo(..) => "/v2/info",
-            HttpRequestType::GetPoxInfo(..) => "/v2/pox",
-            HttpRequestType::GetNeighbors(..) => "/v2/neighbors",
-            HttpRequestType::GetBlock(..) => "/v2/blocks/:hash",
-            HttpRequestType::GetMicroblocksIndexed(..) => "/v2/microblocks/:hash",
-            HttpRequestType::GetMicroblocksConfirmed(..) => "/v2/microblocks/confirmed/:hash",
-            HttpRequestType::GetMicroblocksUnconfirmed(..) => {
-                "/v2/microblocks/unconfirmed/:hash/:seq"
-            }
-            HttpRequestType::GetTransactionUnconfirmed(..) => "/v2/transactions/unconfirmed/:txid",
-            HttpRequestType::PostTransaction(..) => "/v2/transactions",
-            HttpRequestType::PostBlock(..) => "/v2/blocks/upload/:block",
-            HttpRequestType::PostMicroblock(..) => "/v2/microblocks",
-            HttpRequestType::GetAccount(..) => "/v2/accounts/:principal",
-            HttpRequestType::GetMapEntry(..) => "/v2/map_entry/:principal/:contract_name/:map_name",
-            HttpRequestType::GetTransferCost(..) => "/v2/fees/transfer",
-            HttpRequestType::GetContractABI(..) => {
-                "/v2/contracts/interface/:principal/:contract_name"
-            }
-            HttpRequestType::GetContractSrc(..) => "/v2/contracts/source/:principal/:contract_name",
-            HttpRequestType::CallReadOnlyFunction(..) => {
-                "/v2/contracts/call-read/:principal/:contract_name/:func_name"
-            }
-            HttpRequestType::GetAttachmentsInv(..) => "/v2/attachments/inv",
-            HttpRequestType::GetAttachment(..) => "/v2/attachments/:hash",
-            HttpRequestType::GetIsTraitImplemented(..) => "/v2/traits/:principal/:contract_name",
-            HttpRequestType::OptionsPreflight(..) | HttpRequestType::ClientError(..) => "/",
+    fn parse_get_is_trait_implemented<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let tip = HttpRequestType::get_chain_tip_query(query);
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body".to_string(),
+            ));
         }
+
+        let contract_addr = StacksAddress::from_string(&captures["address"]).ok_or_else(|| {
+            net_error::DeserializeError("Failed to parse contract address".into())
+        })?;
+        let contract_name = ContractName::try_from(captures["contract"].to_string())
+            .map_err(|_e| net_error::DeserializeError("Failed to parse contract name".into()))?;
+        let trait_name = ClarityName::try_from(captures["traitName"].to_string())
+            .map_err(|_e| net_error::DeserializeError("Failed to parse trait name".into()))?;
+        let trait_contract_addr = StacksAddress::from_string(&captures["traitContractAddr"])
+            .ok_or_else(|| net_error::DeserializeError("Failed to parse contract address".into()))?
+            .into();
+        let trait_contract_name = ContractName::try_from(captures["traitContractName"].to_string())
+            .map_err(|_e| {
+                net_error::DeserializeError("Failed to parse trait contract name".into())
+            })?;
+        let trait_id = TraitIdentifier::new(trait_contract_addr, trait_contract_name, trait_name);
+
+        Ok(HttpRequestType::GetIsTraitImplemented(
+            HttpRequestMetadata::from_preamble(preamble),
+            contract_addr,
+            contract_name,
+            trait_id,
+            tip,
+        ))
     }
 
-    pub fn send<W: Write>(&self, _protocol: &mut StacksHttp, fd: &mut W) -> Result<(), net_error> {
-        match self {
-            HttpRequestType::PostTransaction(md, tx, attachment) => {
-                let mut tx_bytes = vec![];
-                write_next(&mut tx_bytes, tx)?;
-                let tx_hex = to_hex(&tx_bytes[..]);
+    fn parse_getblock<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetBlock".to_string(),
+            ));
+        }
 
-                let (content_type, request_body_bytes) = match attachment {
-                    None => {
-                        // Transaction does not include an attachment: HttpContentType::Bytes (more compressed)
-                        (Some(&HttpContentType::Bytes), tx_bytes)
-                    }
-                    Some(attachment) => {
-                        // Transaction is including an attachment: HttpContentType::JSON
-                        let request_body = PostTransactionRequestBody {
-                            tx: tx_hex,
-                            attachment: Some(to_hex(&attachment.content[..])),
-                        };
+        let block_hash_str = captures
+            .get(1)
+            .ok_or(net_error::DeserializeError(
+                "Failed to match path to block hash group".to_string(),
+            ))?
+            .as_str();
 
-                        let mut request_body_bytes = vec![];
-                        serde_json::to_writer(&mut request_body_bytes, &request_body).map_err(
-                            |e| {
-                                net_error::SerializeError(format!(
-                                    "Failed to serialize read-only call to JSON: {:?}",
-                                    &e
-                                ))
-                            },
-                        )?;
-                        (Some(&HttpContentType::JSON), request_body_bytes)
-                    }
-                };
+        let block_hash = StacksBlockId::from_hex(block_hash_str)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse block hash".to_string()))?;
 
-                HttpRequestPreamble::new_serialized(
-                    fd,
-                    &md.version,
-                    "POST",
-                    &self.request_path(),
-                    &md.peer,
-                    md.keep_alive,
-                    Some(request_body_bytes.len() as u32),
-                    content_type,
-                    empty_headers,
-                )?;
-                fd.write_all(&request_body_bytes)
-                    .map_err(net_error::WriteError)?;
-            }
-            HttpRequestType::PostBlock(md, _ch, block) => {
-                let mut block_bytes = vec![];
-                write_next(&mut block_bytes, block)?;
+        Ok(HttpRequestType::GetBlock(
+            HttpRequestMetadata::from_preamble(preamble),
+            block_hash,
+        ))
+    }
 
-                HttpRequestPreamble::new_serialized(
-                    fd,
-                    &md.version,
-                    "POST",
-                    &self.request_path(),
-                    &md.peer,
-                    md.keep_alive,
-                    Some(block_bytes.len() as u32),
-                    Some(&HttpContentType::Bytes),
-                    empty_headers,
-                )?;
-                fd.write_all(&block_bytes).map_err(net_error::WriteError)?;
-            }
-            HttpRequestType::PostMicroblock(md, mb, ..) => {
-                let mut mb_bytes = vec![];
-                write_next(&mut mb_bytes, mb)?;
+    fn parse_getmicroblocks_indexed<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetMicroblocksIndexed"
+                    .to_string(),
+            ));
+        }
 
-                HttpRequestPreamble::new_serialized(
-                    fd,
-                    &md.version,
-                    "POST",
-                    &self.request_path(),
-                    &md.peer,
-                    md.keep_alive,
-                    Some(mb_bytes.len() as u32),
-                    Some(&HttpContentType::Bytes),
-                    empty_headers,
-                )?;
-                fd.write_all(&mb_bytes).map_err(net_error::WriteError)?;
-            }
-            HttpRequestType::GetMapEntry(
-                md,
-                _contract_addr,
-                _contract_name,
-                _map_name,
-                key,
-                ..,
-            ) => {
-                let mut request_bytes = vec![];
-                key.serialize_write(&mut request_bytes)
-                    .map_err(net_error::WriteError)?;
-                let request_json = format!("\"{}\"", to_hex(&request_bytes));
+        let block_hash_str = captures
+            .get(1)
+            .ok_or(net_error::DeserializeError(
+                "Failed to match path to microblock hash group".to_string(),
+            ))?
+            .as_str();
 
-                HttpRequestPreamble::new_serialized(
-                    fd,
-                    &md.version,
-                    "POST",
-                    &self.request_path(),
-                    &md.peer,
-                    md.keep_alive,
-                    Some(request_json.as_bytes().len() as u32),
-                    Some(&HttpContentType::JSON),
-                    empty_headers,
-                )?;
-                fd.write_all(&request_json.as_bytes())
-                    .map_err(net_error::WriteError)?;
-            }
-            HttpRequestType::CallReadOnlyFunction(
-                md,
-                _contract_addr,
-                _contract_name,
-                sender,
-                _func_name,
-                func_args,
-                ..,
-            ) => {
-                let mut args = vec![];
-                for arg in func_args.iter() {
-                    let mut arg_bytes = vec![];
-                    arg.serialize_write(&mut arg_bytes)
-                        .map_err(net_error::WriteError)?;
-                    args.push(to_hex(&arg_bytes));
-                }
-
-                let request_body = CallReadOnlyRequestBody {
-                    sender: sender.to_string(),
-                    arguments: args,
-                };
+        let block_hash = StacksBlockId::from_hex(block_hash_str).map_err(|_e| {
+            net_error::DeserializeError("Failed to parse microblock hash".to_string())
+        })?;
 
-                let mut request_body_bytes = vec![];
-                serde_json::to_writer(&mut request_body_bytes, &request_body).map_err(|e| {
-                    net_error::SerializeError(format!(
-                        "Failed to serialize read-only call to JSON: {:?}",
-                        &e
-                    ))
-                })?;
+        Ok(HttpRequestType::GetMicroblocksIndexed(
+            HttpRequestMetadata::from_preamble(preamble),
+            block_hash,
+        ))
+    }
 
-                HttpRequestPreamble::new_serialized(
-                    fd,
-                    &md.version,
-                    "POST",
-                    &self.request_path(),
-                    &md.peer,
-                    md.keep_alive,
-                    Some(request_body_bytes.len() as u32),
-                    Some(&HttpContentType::JSON),
-                    empty_headers,
-                )?;
-                fd.write_all(&request_body_bytes)
-                    .map_err(net_error::WriteError)?;
-            }
-            other_type => {
-                let md = other_type.metadata();
-                let request_path = other_type.request_path();
-                HttpRequestPreamble::new_serialized(
-                    fd,
-                    &md.version,
-                    "GET",
-                    &request_path,
-                    &md.peer,
-                    md.keep_alive,
-                    None,
-                    None,
-                    empty_headers,
-                )?;
-            }
+    fn parse_getmicroblocks_confirmed<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetMicrolocks".to_string(),
+            ));
         }
-        Ok(())
+
+        let block_hash_str = captures
+            .get(1)
+            .ok_or(net_error::DeserializeError(
+                "Failed to match path to microblock hash group".to_string(),
+            ))?
+            .as_str();
+
+        let block_hash = StacksBlockId::from_hex(block_hash_str).map_err(|_e| {
+            net_error::DeserializeError("Failed to parse microblock hash".to_string())
+        })?;
+
+        Ok(HttpRequestType::GetMicroblocksConfirmed(
+            HttpRequestMetadata::from_preamble(preamble),
+            block_hash,
+        ))
     }
-}
 
-impl HttpResponseType {
-    fn try_parse<R: Read, F>(
-        protocol: &mut StacksHttp,
-        regex: &Regex,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        request_path: &String,
-        fd: &mut R,
-        len_hint: Option<usize>,
-        parser: F,
-    ) -> Result<Option<HttpResponseType>, net_error>
-    where
-        F: Fn(
-            &mut StacksHttp,
-            HttpVersion,
-            &HttpResponsePreamble,
-            &mut R,
-            Option<usize>,
-        ) -> Result<HttpResponseType, net_error>,
-    {
-        if regex.is_match(request_path) {
-            let payload = parser(protocol, request_version, preamble, fd, len_hint)?;
-            Ok(Some(payload))
-        } else {
-            Ok(None)
+    fn parse_getmicroblocks_unconfirmed<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetMicrolocksUnconfirmed"
+                    .to_string(),
+            ));
         }
+
+        let block_hash_str = captures
+            .get(1)
+            .ok_or(net_error::DeserializeError(
+                "Failed to match path to microblock hash group".to_string(),
+            ))?
+            .as_str();
+
+        let min_seq_str = captures
+            .get(2)
+            .ok_or(net_error::DeserializeError(
+                "Failed to match path to microblock minimum sequence group".to_string(),
+            ))?
+            .as_str();
+
+        let block_hash = StacksBlockId::from_hex(block_hash_str).map_err(|_e| {
+            net_error::DeserializeError("Failed to parse microblock hash".to_string())
+        })?;
+
+        let min_seq = min_seq_str.parse::<u16>().map_err(|_e| {
+            net_error::DeserializeError("Failed to parse microblock minimum sequence".to_string())
+        })?;
+
+        Ok(HttpRequestType::GetMicroblocksUnconfirmed(
+            HttpRequestMetadata::from_preamble(preamble),
+            block_hash,
+            min_seq,
+        ))
     }
 
-    fn parse_error<R: Read>(
+    fn parse_gettransaction_unconfirmed<R: Read>(
         _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-    ) -> Result<HttpResponseType, net_error> {
-        if preamble.status_code < 400 || preamble.status_code > 599 {
+        preamble: &HttpRequestPreamble,
+        regex: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
             return Err(net_error::DeserializeError(
-                "Inavlid response: not an error".to_string(),
+                "Invalid Http request: expected 0-length body for GetMicrolocksUnconfirmed"
+                    .to_string(),
             ));
         }
 
-        if preamble.content_type != HttpContentType::Text {
+        let txid_hex = regex
+            .get(1)
+            .ok_or(net_error::DeserializeError(
+                "Failed to match path to txid group".to_string(),
+            ))?
+            .as_str();
+
+        if txid_hex.len() != 64 {
             return Err(net_error::DeserializeError(
-                "Invalid error response: expected text/plain".to_string(),
+                "Invalid txid: expected 64 bytes".to_string(),
             ));
         }
 
-        let mut error_text = String::new();
-        fd.read_to_string(&mut error_text)
-            .map_err(net_error::ReadError)?;
+        let txid = Txid::from_hex(&txid_hex)
+            .map_err(|_e| net_error::DeserializeError("Failed to decode txid hex".to_string()))?;
 
-        let md = HttpResponseMetadata::from_preamble(request_version, preamble);
-        let resp = match preamble.status_code {
-            400 => HttpResponseType::BadRequest(md, error_text),
-            401 => HttpResponseType::Unauthorized(md, error_text),
-            402 => HttpResponseType::PaymentRequired(md, error_text),
-            403 => HttpResponseType::Forbidden(md, error_text),
-            404 => HttpResponseType::NotFound(md, error_text),
-            500 => HttpResponseType::ServerError(md, error_text),
-            503 => HttpResponseType::ServiceUnavailable(md, error_text),
-            _ => HttpResponseType::Error(md, preamble.status_code, error_text),
-        };
-        Ok(resp)
+        Ok(HttpRequestType::GetTransactionUnconfirmed(
+            HttpRequestMetadata::from_preamble(preamble),
+            txid,
+        ))
     }
 
-    fn parse_bytestream<R: Read, T: StacksMessageCodec>(
-        preamble: &HttpResponsePreamble,
+    fn parse_posttransaction<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        query: Option<&str>,
         fd: &mut R,
-        len_hint: Option<usize>,
-        max_len: u64,
-    ) -> Result<T, net_error> {
-        // content-type has to be Bytes
-        if preamble.content_type != HttpContentType::Bytes {
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() == 0 {
             return Err(net_error::DeserializeError(
-                "Invalid content-type: expected application/octet-stream".to_string(),
+                "Invalid Http request: expected non-zero-length body for PostTransaction"
+                    .to_string(),
             ));
         }
 
-        let item: T = if preamble.is_chunked() && len_hint.is_none() {
-            let mut chunked_fd = HttpChunkedTransferReader::from_reader(fd, max_len);
-            read_next(&mut chunked_fd)?
-        } else {
-            let content_length_opt = match (preamble.content_length, len_hint) {
-                (Some(l), _) => Some(l as u32),
-                (None, Some(l)) => Some(l as u32),
-                (None, None) => None,
-            };
-            if let Some(content_length) = content_length_opt {
-                if (content_length as u64) > max_len {
-                    return Err(net_error::DeserializeError(
-                        "Invalid Content-Length header: too long".to_string(),
-                    ));
-                }
+        if preamble.get_content_length() > MAX_PAYLOAD_LEN {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: PostTransaction body is too big".to_string(),
+            ));
+        }
 
-                let mut bound_fd = BoundReader::from_reader(fd, content_length as u64);
-                read_next(&mut bound_fd)?
-            } else {
-                // unsupported headers
-                trace!("preamble: {:?}", preamble);
-                return Err(net_error::DeserializeError(
-                    "Invalid headers: need either Transfer-Encoding or Content-Length".to_string(),
+        let mut bound_fd = BoundReader::from_reader(fd, preamble.get_content_length() as u64);
+        let result = HttpRequestType::get_result_query(query);
+
+        match preamble.content_type {
+            None => {
+                return Err(net_error::DeserializeError(
+                    "Missing Content-Type for transaction".to_string(),
+                ));
+            }
+            Some(HttpContentType::Bytes) => {
+                HttpRequestType::parse_posttransaction_octets(preamble, &mut bound_fd, result)
+            }
+            Some(HttpContentType::JSON) => {
+                HttpRequestType::parse_posttransaction_json(preamble, &mut bound_fd, result)
+            }
+            _ => {
+                return Err(net_error::DeserializeError(
+                    "Wrong Content-Type for transaction; expected application/json".to_string(),
                 ));
             }
+        }
+    }
+
+    fn parse_posttransaction_octets<R: Read>(
+        preamble: &HttpRequestPreamble,
+        fd: &mut R,
+        result: bool,
+    ) -> Result<HttpRequestType, net_error> {
+        let tx = StacksTransaction::consensus_deserialize(fd).map_err(|e| {
+            if let codec_error::DeserializeError(msg) = e {
+                net_error::ClientError(ClientError::Message(format!(
+                    "Failed to deserialize posted transaction: {}",
+                    msg
+                )))
+            } else {
+                e.into()
+            }
+        })?;
+        Ok(HttpRequestType::PostTransaction(
+            HttpRequestMetadata::from_preamble(preamble),
+            tx,
+            None,
+            result,
+        ))
+    }
+
+    fn parse_posttransaction_json<R: Read>(
+        preamble: &HttpRequestPreamble,
+        fd: &mut R,
+        result: bool,
+    ) -> Result<HttpRequestType, net_error> {
+        let body: PostTransactionRequestBody = serde_json::from_reader(fd)
+            .map_err(|_e| net_error::DeserializeError("Failed to parse body".into()))?;
+
+        let tx = {
+            let tx_bytes = hex_bytes(&body.tx)
+                .map_err(|_e| net_error::DeserializeError("Failed to parse tx".into()))?;
+            StacksTransaction::consensus_deserialize(&mut &tx_bytes[..]).map_err(|e| {
+                if let codec_error::DeserializeError(msg) = e {
+                    net_error::ClientError(ClientError::Message(format!(
+                        "Failed to deserialize posted transaction: {}",
+                        msg
+                    )))
+                } else {
+                    e.into()
+                }
+            })
+        }?;
+
+        let attachment = match body.attachment {
+            None => None,
+            Some(attachment_content) => {
+                let content = hex_bytes(&attachment_content).map_err(|_e| {
+                    net_error::DeserializeError("Failed to parse attachment".into())
+                })?;
+                Some(Attachment::new(content))
+            }
         };
 
-        Ok(item)
+        Ok(HttpRequestType::PostTransaction(
+            HttpRequestMetadata::from_preamble(preamble),
+            tx,
+            attachment,
+            result,
+        ))
     }
 
-    fn parse_json<R: Read, T: serde::de::DeserializeOwned>(
-        preamble: &HttpResponsePreamble,
+    fn parse_postblock<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        regex: &Captures,
+        _query: Option<&str>,
         fd: &mut R,
-        len_hint: Option<usize>,
-        max_len: u64,
-    ) -> Result<T, net_error> {
-        // content-type has to be JSON
-        if preamble.content_type != HttpContentType::JSON {
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() == 0 {
             return Err(net_error::DeserializeError(
-                "Invalid content-type: expected application/json".to_string(),
+                "Invalid Http request: expected non-zero-length body for PostBlock".to_string(),
             ));
         }
 
-        let item_result: Result<T, serde_json::Error> = if preamble.is_chunked()
-            && len_hint.is_none()
-        {
-            let chunked_fd = HttpChunkedTransferReader::from_reader(fd, max_len);
-            serde_json::from_reader(chunked_fd)
-        } else {
-            let content_length_opt = match (preamble.content_length, len_hint) {
-                (Some(l), _) => Some(l as u32),
-                (None, Some(l)) => Some(l as u32),
-                (None, None) => None,
-            };
-            if let Some(content_length) = content_length_opt {
-                if (content_length as u64) > max_len {
+        if preamble.get_content_length() > MAX_PAYLOAD_LEN {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: PostBlock body is too big".to_string(),
+            ));
+        }
+
+        // content-type must be given, and must be application/octet-stream
+        match preamble.content_type {
+            None => {
+                return Err(net_error::DeserializeError(
+                    "Missing Content-Type for Stacks block".to_string(),
+                ));
+            }
+            Some(ref c) => {
+                if *c != HttpContentType::Bytes {
                     return Err(net_error::DeserializeError(
-                        "Invalid Content-Length header: too long".to_string(),
+                        "Wrong Content-Type for Stacks block; expected application/octet-stream"
+                            .to_string(),
                     ));
                 }
-                let bound_fd = BoundReader::from_reader(fd, content_length as u64);
-                serde_json::from_reader(bound_fd)
-            } else {
-                // unsupported headers
-                trace!("preamble: {:?}", preamble);
-                return Err(net_error::DeserializeError(
-                    "Invalid headers: need either Transfer-Encoding or Content-Length".to_string(),
-                ));
             }
         };
 
-        item_result.map_err(|e| {
-            if e.is_eof() {
-                net_error::UnderflowError(format!("Not enough bytes to parse JSON"))
-            } else {
-                net_error::DeserializeError(format!("Failed to parse JSON: {:?}", &e))
-            }
-        })
+        let consensus_hash_str = regex
+            .get(1)
+            .ok_or(net_error::DeserializeError(
+                "Failed to match consensus hash in path group".to_string(),
+            ))?
+            .as_str();
+
+        let consensus_hash: ConsensusHash =
+            ConsensusHash::from_hex(consensus_hash_str).map_err(|_| {
+                net_error::DeserializeError("Failed to parse consensus hash".to_string())
+            })?;
+
+        let mut bound_fd = BoundReader::from_reader(fd, preamble.get_content_length() as u64);
+        let stacks_block = StacksBlock::consensus_deserialize(&mut bound_fd)?;
+
+        Ok(HttpRequestType::PostBlock(
+            HttpRequestMetadata::from_preamble(preamble),
+            consensus_hash,
+            stacks_block,
+        ))
     }
 
-    fn parse_text<R: Read>(
-        preamble: &HttpResponsePreamble,
+    fn parse_postmicroblock<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        query: Option<&str>,
         fd: &mut R,
-        len_hint: Option<usize>,
-        max_len: u64,
-    ) -> Result<Vec<u8>, net_error> {
-        // content-type has to be text/plain
-        if preamble.content_type != HttpContentType::Text {
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() == 0 {
             return Err(net_error::DeserializeError(
-                "Invalid content-type: expected text/plain".to_string(),
+                "Invalid Http request: expected non-zero-length body for PostMicroblock"
+                    .to_string(),
             ));
         }
-        let buf = if preamble.is_chunked() && len_hint.is_none() {
-            let mut chunked_fd = HttpChunkedTransferReader::from_reader(fd, max_len);
-            let mut buf = vec![];
-            chunked_fd
-                .read_to_end(&mut buf)
-                .map_err(net_error::ReadError)?;
-            buf
-        } else {
-            let content_length_opt = match (preamble.content_length, len_hint) {
-                (Some(l), _) => Some(l as u32),
-                (None, Some(l)) => Some(l as u32),
-                (None, None) => None,
-            };
-            if let Some(len) = content_length_opt {
-                let mut buf = vec![0u8; len as usize];
-                fd.read_exact(&mut buf).map_err(net_error::ReadError)?;
-                buf
-            } else {
-                // unsupported headers
-                trace!("preamble: {:?}", preamble);
+
+        if preamble.get_content_length() > MAX_PAYLOAD_LEN {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: PostMicroblock body is too big".to_string(),
+            ));
+        }
+
+        // content-type must be given, and must be application/octet-stream
+        match preamble.content_type {
+            None => {
                 return Err(net_error::DeserializeError(
-                    "Invalid headers: need either Transfer-Encoding or Content-Length".to_string(),
+                    "Missing Content-Type for microblock".to_string(),
                 ));
             }
+            Some(ref c) => {
+                if *c != HttpContentType::Bytes {
+                    return Err(net_error::DeserializeError(
+                        "Wrong Content-Type for microblock; expected application/octet-stream"
+                            .to_string(),
+                    ));
+                }
+            }
         };
 
-        Ok(buf)
+        let mut bound_fd = BoundReader::from_reader(fd, preamble.get_content_length() as u64);
+
+        let mb = StacksMicroblock::consensus_deserialize(&mut bound_fd)?;
+        let tip = HttpRequestType::get_chain_tip_query(query);
+
+        Ok(HttpRequestType::PostMicroblock(
+            HttpRequestMetadata::from_preamble(preamble),
+            mb,
+            tip,
+        ))
     }
 
-    // len_hint is given by the StacksHttp protocol implementation
-    pub fn parse<R: Read>(
-        protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        request_path: String,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        if preamble.status_code >= 400 {
-            return HttpResponseType::parse_error(protocol, request_version, preamble, fd);
+    fn parse_get_attachment<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body".to_string(),
+            ));
         }
+        let hex_content_hash = captures
+            .get(1)
+            .ok_or(net_error::DeserializeError(
+                "Failed to match path to attachment hash group".to_string(),
+            ))?
+            .as_str();
 
-        // TODO: make this static somehow
-        let RESPONSE_METHODS: &[(
-            &Regex,
-            &dyn Fn(
-                &mut StacksHttp,
-                HttpVersion,
-                &HttpResponsePreamble,
-                &mut R,
-                Option<usize>,
-            ) -> Result<HttpResponseType, net_error>,
-        )] = &[
-            (&PATH_GETINFO, &HttpResponseType::parse_peerinfo),
-            (&PATH_GETPOXINFO, &HttpResponseType::parse_poxinfo),
-            (&PATH_GETNEIGHBORS, &HttpResponseType::parse_neighbors),
-            (&PATH_GETBLOCK, &HttpResponseType::parse_block),
-            (&PATH_GET_MAP_ENTRY, &HttpResponseType::parse_get_map_entry),
-            (
-                &PATH_GETMICROBLOCKS_INDEXED,
-                &HttpResponseType::parse_microblocks,
-            ),
-            (
-                &PATH_GETMICROBLOCKS_CONFIRMED,
-                &HttpResponseType::parse_microblocks,
-            ),
-            (
-                &PATH_GETMICROBLOCKS_UNCONFIRMED,
-                &HttpResponseType::parse_microblocks_unconfirmed,
-            ),
-            (
-                &PATH_GETTRANSACTION_UNCONFIRMED,
-                &HttpResponseType::parse_transaction_unconfirmed,
-            ),
-            (&PATH_POSTTRANSACTION, &HttpResponseType::parse_txid),
-            (
-                &PATH_POSTBLOCK,
-                &HttpResponseType::parse_stacks_block_accepted,
-            ),
-            (
-                &PATH_POSTMICROBLOCK,
-                &HttpResponseType::parse_microblock_hash,
-            ),
-            (&PATH_GET_ACCOUNT, &HttpResponseType::parse_get_account),
-            (
-                &PATH_GET_CONTRACT_SRC,
-                &HttpResponseType::parse_get_contract_src,
-            ),
-            (
-                &PATH_GET_IS_TRAIT_IMPLEMENTED,
-                &HttpResponseType::parse_get_is_trait_implemented,
-            ),
-            (
-                &PATH_GET_CONTRACT_ABI,
-                &HttpResponseType::parse_get_contract_abi,
-            ),
-            (
-                &PATH_POST_CALL_READ_ONLY,
-                &HttpResponseType::parse_call_read_only,
-            ),
-            (
-                &PATH_GET_ATTACHMENT,
-                &HttpResponseType::parse_get_attachment,
-            ),
-            (
-                &PATH_GET_ATTACHMENTS_INV,
-                &HttpResponseType::parse_get_attachments_inv,
-            ),
-        ];
-
-        // use url::Url to parse path and query string
-        //   Url will refuse to parse just a path, so create a dummy URL
-        let local_url = format!("http://local{}", &request_path);
-        let url = Url::parse(&local_url).map_err(|_e| {
-            net_error::DeserializeError("Http request path could not be parsed".to_string())
+        let content_hash = Hash160::from_hex(&hex_content_hash).map_err(|_| {
+            net_error::DeserializeError("Failed to construct hash160 from inputs".to_string())
         })?;
 
-        let decoded_path = percent_decode_str(url.path()).decode_utf8().map_err(|_e| {
-            net_error::DeserializeError(
-                "Http response path could not be parsed as UTF-8".to_string(),
-            )
-        })?;
+        Ok(HttpRequestType::GetAttachment(
+            HttpRequestMetadata::from_preamble(preamble),
+            content_hash,
+        ))
+    }
 
-        for (regex, parser) in RESPONSE_METHODS.iter() {
-            match HttpResponseType::try_parse(
-                protocol,
-                regex,
-                request_version,
-                preamble,
-                &decoded_path.to_string(),
-                fd,
-                len_hint,
-                parser,
-            ) {
-                Ok(Some(request)) => {
-                    return Ok(request);
-                }
-                Ok(None) => {
-                    continue;
+    fn parse_get_attachments_inv<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        _captures: &Captures,
+        query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body".to_string(),
+            ));
+        }
+
+        let (index_block_hash, pages_indexes) = match query {
+            None => {
+                return Err(net_error::DeserializeError(
+                    "Invalid Http request: expecting index_block_hash and pages_indexes"
+                        .to_string(),
+                ));
+            }
+            Some(query) => {
+                let mut index_block_hash = None;
+                let mut pages_indexes = HashSet::new();
+
+                for (key, value) in form_urlencoded::parse(query.as_bytes()) {
+                    if key == "index_block_hash" {
+                        index_block_hash = match StacksBlockId::from_hex(&value) {
+                            Ok(index_block_hash) => Some(index_block_hash),
+                            _ => None,
+                        };
+                    } else if key == "pages_indexes" {
+                        if let Ok(pages_indexes_value) = value.parse::<String>() {
+                            for entry in pages_indexes_value.split(",") {
+                                if let Ok(page_index) = entry.parse::<u32>() {
+                                    pages_indexes.insert(page_index);
+                                }
+                            }
+                        }
+                    }
                 }
-                Err(e) => {
-                    test_debug!("Failed to parse {}: {:?}", &request_path, &e);
-                    return Err(e);
+
+                let index_block_hash = match index_block_hash {
+                    None => {
+                        return Err(net_error::DeserializeError(
+                            "Invalid Http request: expecting index_block_hash".to_string(),
+                        ));
+                    }
+                    Some(index_block_hash) => index_block_hash,
+                };
+
+                if pages_indexes.is_empty() {
+                    return Err(net_error::DeserializeError(
+                        "Invalid Http request: expecting pages_indexes".to_string(),
+                    ));
                 }
-            }
-        }
 
-        test_debug!(
-            "Failed to match request path '{}' to a handler",
-            &request_path
-        );
-        return Err(net_error::DeserializeError(
-            "Http response could not be parsed".to_string(),
-        ));
-    }
+                (index_block_hash, pages_indexes)
+            }
+        };
 
-    fn parse_peerinfo<R: Read>(
-        _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        let peer_info =
-            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-        Ok(HttpResponseType::PeerInfo(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            peer_info,
+        Ok(HttpRequestType::GetAttachmentsInv(
+            HttpRequestMetadata::from_preamble(preamble),
+            index_block_hash,
+            pages_indexes,
         ))
     }
 
-    fn parse_poxinfo<R: Read>(
+    fn parse_options_preflight<R: Read>(
         _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        let pox_info =
-            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-        Ok(HttpResponseType::PoxInfo(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            pox_info,
+        preamble: &HttpRequestPreamble,
+        _regex: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        Ok(HttpRequestType::OptionsPreflight(
+            HttpRequestMetadata::from_preamble(preamble),
+            preamble.path.to_string(),
         ))
     }
 
-    fn parse_neighbors<R: Read>(
-        _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        let neighbors_data =
-            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-        Ok(HttpResponseType::Neighbors(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            neighbors_data,
-        ))
+    /// Whether handling this request only reads from the node's databases
+    pub fn is_read_only(&self) -> bool {
+        match *self {
+            HttpRequestType::PostTransaction(..)
+            | HttpRequestType::PostBlock(..)
+            | HttpRequestType::PostMicroblock(..)
+            | HttpRequestType::PostAdminToggles(..)
+            | HttpRequestType::PostTransferStxOp(..)
+            | HttpRequestType::PostBlockProposal(..) => false,
+            _ => true,
+        }
     }
 
-    fn parse_block<R: Read>(
-        _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        let block: StacksBlock =
-            HttpResponseType::parse_bytestream(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-        Ok(HttpResponseType::Block(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            block,
-        ))
+    /// Whether every answer to this request but an error is the same, no matter when it is
+    /// asked: it is for data named by its hash, or for state as of a given block.  Only these are
+    /// served by an HTTP mirror, and caches may keep the answers.  State as of the unconfirmed
+    /// chain tip (`unconfirmed_tip`) changes with each microblock, so it is not immutable.
+    pub fn is_immutable(&self, unconfirmed_tip: Option<&StacksBlockId>) -> bool {
+        match *self {
+            HttpRequestType::GetBlock(..)
+            | HttpRequestType::GetMicroblocksIndexed(..)
+            | HttpRequestType::GetMicroblocksConfirmed(..)
+            | HttpRequestType::GetAttachment(..)
+            | HttpRequestType::OptionsPreflight(..) => true,
+            HttpRequestType::GetContractSrc(_, _, _, Some(ref tip), _)
+            | HttpRequestType::GetContractABI(_, _, _, Some(ref tip))
+            | HttpRequestType::GetAccount(_, _, TipRequest::SpecificTip(ref tip), _)
+            | HttpRequestType::GetMapEntry(_, _, _, _, _, TipRequest::SpecificTip(ref tip), _)
+            | HttpRequestType::GetTransactionReceipt(_, _, Some(ref tip)) => {
+                Some(tip) != unconfirmed_tip
+            }
+            _ => false,
+        }
     }
 
-    fn parse_microblocks<R: Read>(
-        _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        let microblocks: Vec<StacksMicroblock> =
-            HttpResponseType::parse_bytestream(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-        Ok(HttpResponseType::Microblocks(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            microblocks,
-        ))
+    /// Which class of endpoint this request is for, which determines its deadline
+    pub fn endpoint_class(&self) -> RPCEndpointClass {
+        match *self {
+            HttpRequestType::GetInfo(..)
+            | HttpRequestType::GetPoxInfo(..)
+            | HttpRequestType::GetNeighbors(..)
+            | HttpRequestType::GetTransactionUnconfirmed(..)
+            | HttpRequestType::GetConsensusSchedule(..)
+            | HttpRequestType::GetMicroblockEquivocations(..)
+            | HttpRequestType::GetAdminToggles(..)
+            | HttpRequestType::GetClarityProfiles(..)
+            | HttpRequestType::GetTransferCost(..)
+            | HttpRequestType::OptionsPreflight(..)
+            | HttpRequestType::ClientError(..) => RPCEndpointClass::Metadata,
+            HttpRequestType::GetAccount(..)
+            | HttpRequestType::GetAccountTransactions(..)
+            | HttpRequestType::GetTransactionReceipt(..)
+            | HttpRequestType::GetSponsorship(..)
+            | HttpRequestType::GetAccountBalance(..)
+            | HttpRequestType::GetStackingStatus(..)
+            | HttpRequestType::GetDelegations(..)
+            | HttpRequestType::GetRewardSet(..)
+            | HttpRequestType::GetSortition(..)
+            | HttpRequestType::GetMapEntry(..)
+            | HttpRequestType::GetContractABI(..)
+            | HttpRequestType::GetContractEvents(..)
+            | HttpRequestType::GetContractSrc(..)
+            | HttpRequestType::GetIsTraitImplemented(..)
+            | HttpRequestType::GetMinerWallet(..) => RPCEndpointClass::ChainState,
+            HttpRequestType::CallReadOnlyFunction(..)
+            | HttpRequestType::SimulateContractCall(..)
+            | HttpRequestType::PreviewPostConditions(..)
+            | HttpRequestType::PostBlockProposal(..) => RPCEndpointClass::Evaluation,
+            HttpRequestType::GetBlock(..)
+            | HttpRequestType::GetMicroblocksIndexed(..)
+            | HttpRequestType::GetMicroblocksConfirmed(..)
+            | HttpRequestType::GetMicroblocksUnconfirmed(..)
+            | HttpRequestType::GetAttachmentsInv(..)
+            | HttpRequestType::GetAttachment(..) => RPCEndpointClass::Bulk,
+            HttpRequestType::PostTransaction(..)
+            | HttpRequestType::PostBlock(..)
+            | HttpRequestType::PostMicroblock(..)
+            | HttpRequestType::PostAdminToggles(..)
+            | HttpRequestType::PostTransferStxOp(..) => RPCEndpointClass::Write,
+        }
     }
 
-    fn parse_get_account<R: Read>(
-        _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        let account_entry =
-            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-        Ok(HttpResponseType::GetAccount(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            account_entry,
-        ))
-    }
-
-    fn parse_get_map_entry<R: Read>(
-        _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        let map_entry =
-            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-        Ok(HttpResponseType::GetMapEntry(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            map_entry,
-        ))
-    }
-
-    fn parse_get_contract_src<R: Read>(
-        _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        let src_data =
-            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-        Ok(HttpResponseType::GetContractSrc(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            src_data,
-        ))
+    pub fn metadata(&self) -> &HttpRequestMetadata {
+        match *self {
+            HttpRequestType::GetInfo(ref md) => md,
+            HttpRequestType::GetPoxInfo(ref md, _) => md,
+            HttpRequestType::GetNeighbors(ref md) => md,
+            HttpRequestType::GetBlock(ref md, _) => md,
+            HttpRequestType::GetMicroblocksIndexed(ref md, _) => md,
+            HttpRequestType::GetMicroblocksConfirmed(ref md, _) => md,
+            HttpRequestType::GetMicroblocksUnconfirmed(ref md, _, _) => md,
+            HttpRequestType::GetTransactionUnconfirmed(ref md, _) => md,
+            HttpRequestType::PostTransaction(ref md, ..) => md,
+            HttpRequestType::PostBlock(ref md, ..) => md,
+            HttpRequestType::PostMicroblock(ref md, ..) => md,
+            HttpRequestType::GetAccount(ref md, ..) => md,
+            HttpRequestType::GetAccountTransactions(ref md, ..) => md,
+            HttpRequestType::GetTransactionReceipt(ref md, ..) => md,
+            HttpRequestType::GetSponsorship(ref md, ..) => md,
+            HttpRequestType::GetAccountBalance(ref md, ..) => md,
+            HttpRequestType::GetStackingStatus(ref md, ..) => md,
+            HttpRequestType::GetDelegations(ref md, ..) => md,
+            HttpRequestType::GetRewardSet(ref md, ..) => md,
+            HttpRequestType::GetSortition(ref md, ..) => md,
+            HttpRequestType::GetConsensusSchedule(ref md) => md,
+            HttpRequestType::GetMicroblockEquivocations(ref md) => md,
+            HttpRequestType::GetAdminToggles(ref md, ..) => md,
+            HttpRequestType::PostAdminToggles(ref md, ..) => md,
+            HttpRequestType::PostTransferStxOp(ref md, ..) => md,
+            HttpRequestType::GetMinerWallet(ref md, ..) => md,
+            HttpRequestType::GetClarityProfiles(ref md, ..) => md,
+            HttpRequestType::PostBlockProposal(ref md, ..) => md,
+            HttpRequestType::GetMapEntry(ref md, ..) => md,
+            HttpRequestType::GetTransferCost(ref md) => md,
+            HttpRequestType::GetContractABI(ref md, ..) => md,
+            HttpRequestType::GetContractEvents(ref md, ..) => md,
+            HttpRequestType::GetContractSrc(ref md, ..) => md,
+            HttpRequestType::GetIsTraitImplemented(ref md, ..) => md,
+            HttpRequestType::CallReadOnlyFunction(ref md, ..) => md,
+            HttpRequestType::SimulateContractCall(ref md, ..) => md,
+            HttpRequestType::PreviewPostConditions(ref md, ..) => md,
+            HttpRequestType::OptionsPreflight(ref md, ..) => md,
+            HttpRequestType::GetAttachmentsInv(ref md, ..) => md,
+            HttpRequestType::GetAttachment(ref md, ..) => md,
+            HttpRequestType::ClientError(ref md, ..) => md,
+        }
     }
 
-    fn parse_get_is_trait_implemented<R: Read>(
-        _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        let src_data =
-            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-        Ok(HttpResponseType::GetIsTraitImplemented(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            src_data,
-        ))
+    pub fn metadata_mut(&mut self) -> &mut HttpRequestMetadata {
+        match *self {
+            HttpRequestType::GetInfo(ref mut md) => md,
+            HttpRequestType::GetPoxInfo(ref mut md, _) => md,
+            HttpRequestType::GetNeighbors(ref mut md) => md,
+            HttpRequestType::GetBlock(ref mut md, _) => md,
+            HttpRequestType::GetMicroblocksIndexed(ref mut md, _) => md,
+            HttpRequestType::GetMicroblocksConfirmed(ref mut md, _) => md,
+            HttpRequestType::GetMicroblocksUnconfirmed(ref mut md, _, _) => md,
+            HttpRequestType::GetTransactionUnconfirmed(ref mut md, _) => md,
+            HttpRequestType::PostTransaction(ref mut md, ..) => md,
+            HttpRequestType::PostBlock(ref mut md, ..) => md,
+            HttpRequestType::PostMicroblock(ref mut md, ..) => md,
+            HttpRequestType::GetAccount(ref mut md, ..) => md,
+            HttpRequestType::GetAccountTransactions(ref mut md, ..) => md,
+            HttpRequestType::GetTransactionReceipt(ref mut md, ..) => md,
+            HttpRequestType::GetSponsorship(ref mut md, ..) => md,
+            HttpRequestType::GetAccountBalance(ref mut md, ..) => md,
+            HttpRequestType::GetStackingStatus(ref mut md, ..) => md,
+            HttpRequestType::GetDelegations(ref mut md, ..) => md,
+            HttpRequestType::GetRewardSet(ref mut md, ..) => md,
+            HttpRequestType::GetSortition(ref mut md, ..) => md,
+            HttpRequestType::GetConsensusSchedule(ref mut md) => md,
+            HttpRequestType::GetMicroblockEquivocations(ref mut md) => md,
+            HttpRequestType::GetAdminToggles(ref mut md, ..) => md,
+            HttpRequestType::PostAdminToggles(ref mut md, ..) => md,
+            HttpRequestType::PostTransferStxOp(ref mut md, ..) => md,
+            HttpRequestType::GetMinerWallet(ref mut md, ..) => md,
+            HttpRequestType::GetClarityProfiles(ref mut md, ..) => md,
+            HttpRequestType::PostBlockProposal(ref mut md, ..) => md,
+            HttpRequestType::GetMapEntry(ref mut md, ..) => md,
+            HttpRequestType::GetTransferCost(ref mut md) => md,
+            HttpRequestType::GetContractABI(ref mut md, ..) => md,
+            HttpRequestType::GetContractEvents(ref mut md, ..) => md,
+            HttpRequestType::GetContractSrc(ref mut md, ..) => md,
+            HttpRequestType::GetIsTraitImplemented(ref mut md, ..) => md,
+            HttpRequestType::CallReadOnlyFunction(ref mut md, ..) => md,
+            HttpRequestType::SimulateContractCall(ref mut md, ..) => md,
+            HttpRequestType::PreviewPostConditions(ref mut md, ..) => md,
+            HttpRequestType::OptionsPreflight(ref mut md, ..) => md,
+            HttpRequestType::GetAttachmentsInv(ref mut md, ..) => md,
+            HttpRequestType::GetAttachment(ref mut md, ..) => md,
+            HttpRequestType::ClientError(ref mut md, ..) => md,
+        }
     }
 
-    fn parse_get_contract_abi<R: Read>(
-        _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        let abi = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-        Ok(HttpResponseType::GetContractABI(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            abi,
-        ))
+    fn make_query_string(tip_opt: Option<&StacksBlockId>, with_proof: bool) -> String {
+        if let Some(tip) = tip_opt {
+            format!("?tip={}{}", tip, if with_proof { "" } else { "&proof=0" })
+        } else if !with_proof {
+            format!("?proof=0")
+        } else {
+            "".to_string()
+        }
     }
 
-    fn parse_call_read_only<R: Read>(
-        _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        let call_data =
-            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-        Ok(HttpResponseType::CallReadOnlyFunction(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            call_data,
-        ))
+    fn make_tip_request_query_string(tip_req: &TipRequest, with_proof: bool) -> String {
+        match tip_req {
+            TipRequest::UseLatestAnchoredTip => {
+                HttpRequestType::make_query_string(None, with_proof)
+            }
+            TipRequest::UseLatestUnconfirmedTip => format!(
+                "?tip=unconfirmed{}",
+                if with_proof { "" } else { "&proof=0" }
+            ),
+            TipRequest::SpecificTip(tip) => {
+                HttpRequestType::make_query_string(Some(tip), with_proof)
+            }
+        }
     }
 
-    fn parse_microblocks_unconfirmed<R: Read>(
-        _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        // NOTE: there will be no length prefix on this, but we won't ever get more than
-        // MAX_MICROBLOCKS_UNCONFIRMED microblocks
-        let mut microblocks = vec![];
-        let max_len = len_hint.unwrap_or(MAX_MESSAGE_LEN as usize) as u64;
-        let mut bound_reader = BoundReader::from_reader(fd, max_len);
-        loop {
-            let mblock: StacksMicroblock = match read_next(&mut bound_reader) {
-                Ok(mblock) => Ok(mblock),
-                Err(e) => match e {
-                    codec_error::ReadError(ref ioe) => match ioe.kind() {
-                        io::ErrorKind::UnexpectedEof => {
-                            // end of stream -- this is fine
-                            break;
+    pub fn request_path(&self) -> String {
+        match self {
+            HttpRequestType::GetInfo(_md) => "/v2/info".to_string(),
+            HttpRequestType::GetPoxInfo(_md, tip_opt) => format!(
+                "/v2/pox{}",
+                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+            ),
+            HttpRequestType::GetNeighbors(_md) => "/v2/neighbors".to_string(),
+            HttpRequestType::GetBlock(_md, block_hash) => {
+                format!("/v2/blocks/{}", block_hash.to_hex())
+            }
+            HttpRequestType::GetMicroblocksIndexed(_md, block_hash) => {
+                format!("/v2/microblocks/{}", block_hash.to_hex())
+            }
+            HttpRequestType::GetMicroblocksConfirmed(_md, block_hash) => {
+                format!("/v2/microblocks/confirmed/{}", block_hash.to_hex())
+            }
+            HttpRequestType::GetMicroblocksUnconfirmed(_md, block_hash, min_seq) => format!(
+                "/v2/microblocks/unconfirmed/{}/{}",
+                block_hash.to_hex(),
+                min_seq
+            ),
+            HttpRequestType::GetTransactionUnconfirmed(_md, txid) => {
+                format!("/v2/transactions/unconfirmed/{}", txid)
+            }
+            HttpRequestType::PostTransaction(_md, _, _, result) => {
+                if *result {
+                    "/v2/transactions?result=1".to_string()
+                } else {
+                    "/v2/transactions".to_string()
+                }
+            }
+            HttpRequestType::PostBlock(_md, ch, ..) => format!("/v2/blocks/upload/{}", &ch),
+            HttpRequestType::PostMicroblock(_md, _, tip_opt) => format!(
+                "/v2/microblocks{}",
+                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+            ),
+            HttpRequestType::GetAccount(_md, principal, tip_req, with_proof) => format!(
+                "/v2/accounts/{}{}",
+                &principal.to_string(),
+                HttpRequestType::make_tip_request_query_string(tip_req, *with_proof)
+            ),
+            HttpRequestType::GetAccountTransactions(_md, principal, before, limit, tip_opt) => {
+                format!(
+                    "/v2/accounts/{}/transactions?limit={}{}{}",
+                    &principal.to_string(),
+                    limit,
+                    match before {
+                        Some((height, tx_index)) => {
+                            format!("&before_height={}&before_tx_index={}", height, tx_index)
                         }
-                        _ => Err(e),
+                        None => "".to_string(),
                     },
-                    _ => Err(e),
-                },
-            }?;
-
-            microblocks.push(mblock);
-            if microblocks.len() == MAX_MICROBLOCKS_UNCONFIRMED {
-                break;
+                    match tip_opt {
+                        Some(tip) => format!("&tip={}", tip),
+                        None => "".to_string(),
+                    }
+                )
             }
-        }
-        Ok(HttpResponseType::Microblocks(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            microblocks,
-        ))
-    }
-
-    fn parse_transaction_unconfirmed<R: Read>(
-        _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        let unconfirmed_status: UnconfirmedTransactionResponse =
-            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-
-        // tx payload must decode to a transaction
-        let tx_bytes = hex_bytes(&unconfirmed_status.tx).map_err(|_| {
-            net_error::DeserializeError("Unconfirmed transaction is not hex-encoded".to_string())
-        })?;
-        let _ = StacksTransaction::consensus_deserialize(&mut &tx_bytes[..]).map_err(|_| {
-            net_error::DeserializeError(
-                "Unconfirmed transaction is not a well-formed Stacks transaction".to_string(),
-            )
-        })?;
-
-        Ok(HttpResponseType::UnconfirmedTransaction(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            unconfirmed_status,
-        ))
-    }
-
-    fn parse_txid<R: Read>(
-        _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        let txid_hex: String = HttpResponseType::parse_json(preamble, fd, len_hint, 66)?;
-        if txid_hex.len() != 64 {
-            return Err(net_error::DeserializeError(
-                "Invalid txid: expected 64 bytes".to_string(),
-            ));
-        }
-
-        let txid = Txid::from_hex(&txid_hex)
-            .map_err(|_e| net_error::DeserializeError("Failed to decode txid hex".to_string()))?;
-        Ok(HttpResponseType::TransactionID(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            txid,
-        ))
-    }
-
-    fn parse_get_attachment<R: Read>(
-        _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        let res: GetAttachmentResponse =
-            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-
-        Ok(HttpResponseType::GetAttachment(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            res,
-        ))
-    }
-
-    fn parse_get_attachments_inv<R: Read>(
-        _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        let res: GetAttachmentsInvResponse =
-            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
-
-        Ok(HttpResponseType::GetAttachmentsInv(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            res,
-        ))
-    }
-
-    fn parse_stacks_block_accepted<R: Read>(
-        _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        let stacks_block_accepted: StacksBlockAcceptedData =
-            HttpResponseType::parse_json(preamble, fd, len_hint, 128)?;
-        Ok(HttpResponseType::StacksBlockAccepted(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            stacks_block_accepted.stacks_block_id,
-            stacks_block_accepted.accepted,
-        ))
+            HttpRequestType::GetSponsorship(_md, sponsor, tip_opt) => format!(
+                "/v2/accounts/{}/sponsorship{}",
+                sponsor,
+                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+            ),
+            HttpRequestType::GetAccountBalance(_md, principal, tip_opt) => format!(
+                "/v2/accounts/{}/balance{}",
+                &principal.to_string(),
+                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+            ),
+            HttpRequestType::GetTransactionReceipt(_md, txid, tip_opt) => format!(
+                "/v2/receipts/{}{}",
+                txid,
+                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+            ),
+            HttpRequestType::GetStackingStatus(_md, principal, tip_opt) => format!(
+                "/v2/stacking/{}{}",
+                &principal.to_string(),
+                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+            ),
+            HttpRequestType::GetDelegations(_md, principal, tip_opt) => format!(
+                "/v2/delegations/{}{}",
+                &principal.to_string(),
+                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+            ),
+            HttpRequestType::GetRewardSet(_md, reward_cycle) => {
+                format!("/v2/pox/reward_set/{}", reward_cycle)
+            }
+            HttpRequestType::GetSortition(_md, lookup) => match lookup {
+                SortitionLookup::BurnHeight(height) => {
+                    format!("/v2/sortitions/burn_height/{}", height)
+                }
+                SortitionLookup::BurnHash(hash) => format!("/v2/sortitions/burn_hash/{}", hash),
+                SortitionLookup::StacksBlock(index_block_hash) => {
+                    format!("/v2/sortitions/stacks_block/{}", index_block_hash)
+                }
+            },
+            HttpRequestType::GetConsensusSchedule(_md) => "/v2/consensus_schedule".to_string(),
+            HttpRequestType::GetMicroblockEquivocations(_md) => {
+                "/v2/microblocks/equivocations".to_string()
+            }
+            HttpRequestType::GetAdminToggles(..) | HttpRequestType::PostAdminToggles(..) => {
+                "/v2/admin/toggles".to_string()
+            }
+            HttpRequestType::PostTransferStxOp(..) => "/v2/admin/burn_ops/transfer_stx".to_string(),
+            HttpRequestType::GetMinerWallet(..) => "/v2/admin/miner/wallet".to_string(),
+            HttpRequestType::GetClarityProfiles(..) => "/v2/admin/clarity_profiles".to_string(),
+            HttpRequestType::PostBlockProposal(..) => "/v2/admin/block_proposals".to_string(),
+            HttpRequestType::GetMapEntry(
+                _md,
+                contract_addr,
+                contract_name,
+                map_name,
+                _key,
+                tip_req,
+                with_proof,
+            ) => format!(
+                "/v2/map_entry/{}/{}/{}{}",
+                &contract_addr.to_string(),
+                contract_name.as_str(),
+                map_name.as_str(),
+                HttpRequestType::make_tip_request_query_string(tip_req, *with_proof)
+            ),
+            HttpRequestType::GetTransferCost(_md) => "/v2/fees/transfer".into(),
+            HttpRequestType::GetContractABI(_, contract_addr, contract_name, tip_opt) => format!(
+                "/v2/contracts/interface/{}/{}{}",
+                contract_addr,
+                contract_name.as_str(),
+                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+            ),
+            HttpRequestType::GetContractEvents(
+                _,
+                contract_addr,
+                contract_name,
+                topic_opt,
+                before,
+                limit,
+                tip_opt,
+            ) => format!(
+                "/v2/contracts/events/{}/{}?limit={}{}{}{}",
+                contract_addr,
+                contract_name.as_str(),
+                limit,
+                match before {
+                    Some((height, tx_index, event_index)) => format!(
+                        "&before_height={}&before_tx_index={}&before_event_index={}",
+                        height, tx_index, event_index
+                    ),
+                    None => "".to_string(),
+                },
+                match topic_opt {
+                    Some(topic) => format!(
+                        "&topic={}",
+                        form_urlencoded::byte_serialize(topic.as_bytes()).collect::<String>()
+                    ),
+                    None => "".to_string(),
+                },
+                match tip_opt {
+                    Some(tip) => format!("&tip={}", tip),
+                    None => "".to_string(),
+                }
+            ),
+            HttpRequestType::GetContractSrc(
+                _,
+                contract_addr,
+                contract_name,
+                tip_opt,
+                with_proof,
+            ) => format!(
+                "/v2/contracts/source/{}/{}{}",
+                contract_addr,
+                contract_name.as_str(),
+                HttpRequestType::make_query_string(tip_opt.as_ref(), *with_proof)
+            ),
+            HttpRequestType::GetIsTraitImplemented(
+                _,
+                contract_addr,
+                contract_name,
+                trait_id,
+                tip_opt,
+            ) => format!(
+                "/v2/traits/{}/{}/{}/{}/{}{}",
+                contract_addr,
+                contract_name.as_str(),
+                trait_id.name.to_string(),
+                StacksAddress::from(trait_id.clone().contract_identifier.issuer),
+                trait_id.contract_identifier.name.as_str(),
+                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+            ),
+            HttpRequestType::CallReadOnlyFunction(
+                _,
+                contract_addr,
+                contract_name,
+                _,
+                func_name,
+                _,
+                tip_req,
+            ) => format!(
+                "/v2/contracts/call-read/{}/{}/{}{}",
+                contract_addr,
+                contract_name.as_str(),
+                func_name.as_str(),
+                HttpRequestType::make_tip_request_query_string(tip_req, true)
+            ),
+            HttpRequestType::SimulateContractCall(
+                _,
+                contract_addr,
+                contract_name,
+                _,
+                func_name,
+                _,
+                _,
+                tip_opt,
+            ) => format!(
+                "/v2/contracts/simulate-call/{}/{}/{}{}",
+                contract_addr,
+                contract_name.as_str(),
+                func_name.as_str(),
+                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+            ),
+            HttpRequestType::PreviewPostConditions(_md, _tx, tip_req) => format!(
+                "/v2/transactions/postconditions{}",
+                HttpRequestType::make_tip_request_query_string(tip_req, true)
+            ),
+            HttpRequestType::OptionsPreflight(_md, path) => path.to_string(),
+            HttpRequestType::GetAttachmentsInv(_md, index_block_hash, pages_indexes) => {
+                let pages_query = match pages_indexes.len() {
+                    0 => format!(""),
+                    _n => {
+                        let mut indexes = pages_indexes
+                            .iter()
+                            .map(|i| format!("{}", i))
+                            .collect::<Vec<String>>();
+                        indexes.sort();
+                        format!("&pages_indexes={}", indexes.join(","))
+                    }
+                };
+                let index_block_hash = format!("index_block_hash={}", index_block_hash);
+                format!("/v2/attachments/inv?{}{}", index_block_hash, pages_query,)
+            }
+            HttpRequestType::GetAttachment(_, content_hash) => {
+                format!("/v2/attachments/{}", to_hex(&content_hash.0[..]))
+            }
+            HttpRequestType::ClientError(_md, e) => match e {
+                ClientError::NotFound(path) => path.to_string(),
+                _ => "error path unknown".into(),
+            },
+        }
     }
 
-    fn parse_microblock_hash<R: Read>(
-        _protocol: &mut StacksHttp,
-        request_version: HttpVersion,
-        preamble: &HttpResponsePreamble,
-        fd: &mut R,
-        len_hint: Option<usize>,
-    ) -> Result<HttpResponseType, net_error> {
-        let mblock_hex: String = HttpResponseType::parse_json(preamble, fd, len_hint, 66)?;
-        if mblock_hex.len() != 64 {
-            return Err(net_error::DeserializeError(
-                "Invalid microblock hash: expected 64 bytes".to_string(),
-            ));
+    pub fn get_path(&self) -> &str {
+        match self {
+            HttpRequestType::GetInfo(..) => "/v2/info",
+            HttpRequestType::GetPoxInfo(..) => "/v2/pox",
+            HttpRequestType::GetNeighbors(..) => "/v2/neighbors",
+            HttpRequestType::GetBlock(..) => "/v2/blocks/:hash",
+            HttpRequestType::GetMicroblocksIndexed(..) => "/v2/microblocks/:hash",
+            HttpRequestType::GetMicroblocksConfirmed(..) => "/v2/microblocks/confirmed/:hash",
+            HttpRequestType::GetMicroblocksUnconfirmed(..) => {
+                "/v2/microblocks/unconfirmed/:hash/:seq"
+            }
+            HttpRequestType::GetTransactionUnconfirmed(..) => "/v2/transactions/unconfirmed/:txid",
+            HttpRequestType::PostTransaction(..) => "/v2/transactions",
+            HttpRequestType::PostBlock(..) => "/v2/blocks/upload/:block",
+            HttpRequestType::PostMicroblock(..) => "/v2/microblocks",
+            HttpRequestType::GetAccount(..) => "/v2/accounts/:principal",
+            HttpRequestType::GetAccountTransactions(..) => "/v2/accounts/:principal/transactions",
+            HttpRequestType::GetTransactionReceipt(..) => "/v2/receipts/:txid",
+            HttpRequestType::GetSponsorship(..) => "/v2/accounts/:principal/sponsorship",
+            HttpRequestType::GetAccountBalance(..) => "/v2/accounts/:principal/balance",
+            HttpRequestType::GetStackingStatus(..) => "/v2/stacking/:principal",
+            HttpRequestType::GetDelegations(..) => "/v2/delegations/:principal",
+            HttpRequestType::GetRewardSet(..) => "/v2/pox/reward_set/:reward_cycle",
+            HttpRequestType::GetSortition(..) => "/v2/sortitions/:lookup/:value",
+            HttpRequestType::GetConsensusSchedule(..) => "/v2/consensus_schedule",
+            HttpRequestType::GetMicroblockEquivocations(..) => "/v2/microblocks/equivocations",
+            HttpRequestType::GetAdminToggles(..) | HttpRequestType::PostAdminToggles(..) => {
+                "/v2/admin/toggles"
+            }
+            HttpRequestType::PostTransferStxOp(..) => "/v2/admin/burn_ops/transfer_stx",
+            HttpRequestType::GetMinerWallet(..) => "/v2/admin/miner/wallet",
+            HttpRequestType::GetClarityProfiles(..) => "/v2/admin/clarity_profiles",
+            HttpRequestType::PostBlockProposal(..) => "/v2/admin/block_proposals",
+            HttpRequestType::GetMapEntry(..) => "/v2/map_entry/:principal/:contract_name/:map_name",
+            HttpRequestType::GetTransferCost(..) => "/v2/fees/transfer",
+            HttpRequestType::GetContractABI(..) => {
+                "/v2/contracts/interface/:principal/:contract_name"
+            }
+            HttpRequestType::GetContractEvents(..) => {
+                "/v2/contracts/events/:principal/:contract_name"
+            }
+            HttpRequestType::GetContractSrc(..) => "/v2/contracts/source/:principal/:contract_name",
+            HttpRequestType::CallReadOnlyFunction(..) => {
+                "/v2/contracts/call-read/:principal/:contract_name/:func_name"
+            }
+            HttpRequestType::SimulateContractCall(..) => {
+                "/v2/contracts/simulate-call/:principal/:contract_name/:func_name"
+            }
+            HttpRequestType::PreviewPostConditions(..) => "/v2/transactions/postconditions",
+            HttpRequestType::GetAttachmentsInv(..) => "/v2/attachments/inv",
+            HttpRequestType::GetAttachment(..) => "/v2/attachments/:hash",
+            HttpRequestType::GetIsTraitImplemented(..) => "/v2/traits/:principal/:contract_name",
+            HttpRequestType::OptionsPreflight(..) | HttpRequestType::ClientError(..) => "/",
         }
-
-        let mblock_hash = BlockHeaderHash::from_hex(&mblock_hex).map_err(|_e| {
-            net_error::DeserializeError("Failed to decode microblock hash hex".to_string())
-        })?;
-        Ok(HttpResponseType::MicroblockHash(
-            HttpResponseMetadata::from_preamble(request_version, preamble),
-            mblock_hash,
-        ))
     }
 
-    fn error_reason(code: u16) -> &'static str {
-        match code {
-            400 => "Bad Request",
-            401 => "Unauthorized",
-            402 => "Payment Required",
-            403 => "Forbidden",
-            404 => "Not Found",
-            500 => "Internal Server Error",
-            503 => "Service Temporarily Unavailable",
-            _ => "Error",
+    fn bearer_token_headers(token: Option<&BearerToken>) -> HashMap<String, String> {
+        let mut headers = HashMap::new();
+        if let Some(token) = token {
+            headers.insert("Authorization".to_string(), format!("Bearer {}", &token.0));
         }
+        headers
     }
 
-    fn error_response<W: Write>(
-        &self,
-        fd: &mut W,
-        code: u16,
-        message: &str,
-    ) -> Result<(), net_error> {
-        let md = self.metadata();
-        HttpResponsePreamble::new_serialized(
-            fd,
-            code,
-            HttpResponseType::error_reason(code),
-            Some(message.len() as u32),
-            &HttpContentType::Text,
-            md.request_id,
-            |ref mut fd| keep_alive_headers(fd, md),
-        )?;
-        fd.write_all(message.as_bytes())
-            .map_err(net_error::WriteError)?;
-        Ok(())
-    }
-
-    pub fn metadata(&self) -> &HttpResponseMetadata {
-        match *self {
-            HttpResponseType::PeerInfo(ref md, _) => md,
-            HttpResponseType::PoxInfo(ref md, _) => md,
-            HttpResponseType::Neighbors(ref md, _) => md,
-            HttpResponseType::Block(ref md, _) => md,
-            HttpResponseType::BlockStream(ref md) => md,
-            HttpResponseType::Microblocks(ref md, _) => md,
-            HttpResponseType::MicroblockStream(ref md) => md,
-            HttpResponseType::TransactionID(ref md, _) => md,
-            HttpResponseType::StacksBlockAccepted(ref md, ..) => md,
-            HttpResponseType::MicroblockHash(ref md, _) => md,
-            HttpResponseType::TokenTransferCost(ref md, _) => md,
-            HttpResponseType::GetMapEntry(ref md, _) => md,
-            HttpResponseType::GetAccount(ref md, _) => md,
-            HttpResponseType::GetContractABI(ref md, _) => md,
-            HttpResponseType::GetContractSrc(ref md, _) => md,
-            HttpResponseType::GetIsTraitImplemented(ref md, _) => md,
-            HttpResponseType::CallReadOnlyFunction(ref md, _) => md,
-            HttpResponseType::UnconfirmedTransaction(ref md, _) => md,
-            HttpResponseType::GetAttachment(ref md, _) => md,
-            HttpResponseType::GetAttachmentsInv(ref md, _) => md,
-            HttpResponseType::OptionsPreflight(ref md) => md,
-            // errors
-            HttpResponseType::BadRequestJSON(ref md, _) => md,
-            HttpResponseType::BadRequest(ref md, _) => md,
-            HttpResponseType::Unauthorized(ref md, _) => md,
-            HttpResponseType::PaymentRequired(ref md, _) => md,
-            HttpResponseType::Forbidden(ref md, _) => md,
-            HttpResponseType::NotFound(ref md, _) => md,
-            HttpResponseType::ServerError(ref md, _) => md,
-            HttpResponseType::ServiceUnavailable(ref md, _) => md,
-            HttpResponseType::Error(ref md, _, _) => md,
-        }
-    }
-
-    fn send_bytestream<W: Write, T: StacksMessageCodec>(
-        protocol: &mut StacksHttp,
-        md: &HttpResponseMetadata,
-        fd: &mut W,
-        message: &T,
-    ) -> Result<(), codec_error> {
-        if md.content_length.is_some() {
-            // have explicit content-length, so we can send as-is
-            write_next(fd, message)
-        } else {
-            // no content-length, so send as chunk-encoded
-            let mut write_state = HttpChunkedTransferWriterState::new(protocol.chunk_size as usize);
-            let mut encoder = HttpChunkedTransferWriter::from_writer_state(fd, &mut write_state);
-            write_next(&mut encoder, message)?;
-            encoder.flush().map_err(codec_error::WriteError)?;
-            Ok(())
-        }
-    }
+    pub fn send<W: Write>(&self, _protocol: &mut StacksHttp, fd: &mut W) -> Result<(), net_error> {
+        match self {
+            HttpRequestType::PostTransaction(md, tx, attachment, _) => {
+                let mut tx_bytes = vec![];
+                write_next(&mut tx_bytes, tx)?;
+                let tx_hex = to_hex(&tx_bytes[..]);
 
-    fn send_text<W: Write>(
-        protocol: &mut StacksHttp,
-        md: &HttpResponseMetadata,
-        fd: &mut W,
-        text: &[u8],
-    ) -> Result<(), net_error> {
-        if md.content_length.is_some() {
-            // have explicit content-length, so we can send as-is
-            fd.write_all(text).map_err(net_error::WriteError)
-        } else {
-            // no content-length, so send as chunk-encoded
-            let mut write_state = HttpChunkedTransferWriterState::new(protocol.chunk_size as usize);
-            let mut encoder = HttpChunkedTransferWriter::from_writer_state(fd, &mut write_state);
-            encoder.write_all(text).map_err(net_error::WriteError)?;
-            encoder.flush().map_err(net_error::WriteError)?;
-            Ok(())
-        }
-    }
+                let (content_type, request_body_bytes) = match attachment {
+                    None => {
+                        // Transaction does not include an attachment: HttpContentType::Bytes (more compressed)
+                        (Some(&HttpContentType::Bytes), tx_bytes)
+                    }
+                    Some(attachment) => {
+                        // Transaction is including an attachment: HttpContentType::JSON
+                        let request_body = PostTransactionRequestBody {
+                            tx: tx_hex,
+                            attachment: Some(to_hex(&attachment.content[..])),
+                        };
 
-    fn send_json<W: Write, T: serde::ser::Serialize>(
-        protocol: &mut StacksHttp,
-        md: &HttpResponseMetadata,
-        fd: &mut W,
-        message: &T,
-    ) -> Result<(), net_error> {
-        if md.content_length.is_some() {
-            // have explicit content-length, so we can send as-is
-            serde_json::to_writer(fd, message)
-                .map_err(|e| net_error::SerializeError(format!("Failed to send as JSON: {:?}", &e)))
-        } else {
-            // no content-length, so send as chunk-encoded
-            let mut write_state = HttpChunkedTransferWriterState::new(protocol.chunk_size as usize);
-            let mut encoder = HttpChunkedTransferWriter::from_writer_state(fd, &mut write_state);
-            serde_json::to_writer(&mut encoder, message).map_err(|e| {
-                net_error::SerializeError(format!("Failed to send as chunk-encoded JSON: {:?}", &e))
-            })?;
-            encoder.flush().map_err(net_error::WriteError)?;
-            Ok(())
-        }
-    }
+                        let mut request_body_bytes = vec![];
+                        serde_json::to_writer(&mut request_body_bytes, &request_body).map_err(
+                            |e| {
+                                net_error::SerializeError(format!(
+                                    "Failed to serialize read-only call to JSON: {:?}",
+                                    &e
+                                ))
+                            },
+                        )?;
+                        (Some(&HttpContentType::JSON), request_body_bytes)
+                    }
+                };
 
-    pub fn send<W: Write>(&self, protocol: &mut StacksHttp, fd: &mut W) -> Result<(), net_error> {
-        match *self {
-            HttpResponseType::GetAccount(ref md, ref account_data) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
-                HttpResponseType::send_json(protocol, md, fd, account_data)?;
-            }
-            HttpResponseType::GetContractABI(ref md, ref data) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
-                HttpResponseType::send_json(protocol, md, fd, data)?;
-            }
-            HttpResponseType::GetContractSrc(ref md, ref data) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
-                HttpResponseType::send_json(protocol, md, fd, data)?;
-            }
-            HttpResponseType::GetIsTraitImplemented(ref md, ref data) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
-                HttpResponseType::send_json(protocol, md, fd, data)?;
-            }
-            HttpResponseType::TokenTransferCost(ref md, ref cost) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
-                HttpResponseType::send_json(protocol, md, fd, cost)?;
-            }
-            HttpResponseType::CallReadOnlyFunction(ref md, ref data) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
-                HttpResponseType::send_json(protocol, md, fd, data)?;
-            }
-            HttpResponseType::GetMapEntry(ref md, ref map_data) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
-                HttpResponseType::send_json(protocol, md, fd, map_data)?;
-            }
-            HttpResponseType::PeerInfo(ref md, ref peer_info) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
-                HttpResponseType::send_json(protocol, md, fd, peer_info)?;
-            }
-            HttpResponseType::PoxInfo(ref md, ref pox_info) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
-                HttpResponseType::send_json(protocol, md, fd, pox_info)?;
-            }
-            HttpResponseType::Neighbors(ref md, ref neighbor_data) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
-                HttpResponseType::send_json(protocol, md, fd, neighbor_data)?;
-            }
-            HttpResponseType::GetAttachment(ref md, ref zonefile_data) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
-                HttpResponseType::send_json(protocol, md, fd, zonefile_data)?;
-            }
-            HttpResponseType::GetAttachmentsInv(ref md, ref zonefile_data) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
-                HttpResponseType::send_json(protocol, md, fd, zonefile_data)?;
-            }
-            HttpResponseType::Block(ref md, ref block) => {
-                HttpResponsePreamble::new_serialized(
-                    fd,
-                    200,
-                    "OK",
-                    md.content_length.clone(),
-                    &HttpContentType::Bytes,
-                    md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
-                )?;
-                HttpResponseType::send_bytestream(protocol, md, fd, block)?;
-            }
-            HttpResponseType::BlockStream(ref md) => {
-                // only send the preamble.  The caller will need to figure out how to send along
-                // the block data itself.
-                HttpResponsePreamble::new_serialized(
+                HttpRequestPreamble::new_serialized(
                     fd,
-                    200,
-                    "OK",
-                    None,
-                    &HttpContentType::Bytes,
-                    md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(request_body_bytes.len() as u32),
+                    content_type,
+                    empty_headers,
                 )?;
+                fd.write_all(&request_body_bytes)
+                    .map_err(net_error::WriteError)?;
             }
-            HttpResponseType::Microblocks(ref md, ref microblocks) => {
-                HttpResponsePreamble::new_serialized(
+            HttpRequestType::PostBlock(md, _ch, block) => {
+                let mut block_bytes = vec![];
+                write_next(&mut block_bytes, block)?;
+
+                HttpRequestPreamble::new_serialized(
                     fd,
-                    200,
-                    "OK",
-                    md.content_length.clone(),
-                    &HttpContentType::Bytes,
-                    md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(block_bytes.len() as u32),
+                    Some(&HttpContentType::Bytes),
+                    empty_headers,
                 )?;
-                HttpResponseType::send_bytestream(protocol, md, fd, microblocks)?;
+                fd.write_all(&block_bytes).map_err(net_error::WriteError)?;
             }
-            HttpResponseType::MicroblockStream(ref md) => {
-                // only send the preamble.  The caller will need to figure out how to send along
-                // the microblock data itself.
-                HttpResponsePreamble::new_serialized(
+            HttpRequestType::PostMicroblock(md, mb, ..) => {
+                let mut mb_bytes = vec![];
+                write_next(&mut mb_bytes, mb)?;
+
+                HttpRequestPreamble::new_serialized(
                     fd,
-                    200,
-                    "OK",
-                    None,
-                    &HttpContentType::Bytes,
-                    md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(mb_bytes.len() as u32),
+                    Some(&HttpContentType::Bytes),
+                    empty_headers,
                 )?;
+                fd.write_all(&mb_bytes).map_err(net_error::WriteError)?;
             }
-            HttpResponseType::TransactionID(ref md, ref txid) => {
-                let txid_bytes = txid.to_hex();
-                HttpResponsePreamble::new_serialized(
+            HttpRequestType::GetMapEntry(
+                md,
+                _contract_addr,
+                _contract_name,
+                _map_name,
+                key,
+                ..,
+            ) => {
+                let mut request_bytes = vec![];
+                key.serialize_write(&mut request_bytes)
+                    .map_err(net_error::WriteError)?;
+                let request_json = format!("\"{}\"", to_hex(&request_bytes));
+
+                HttpRequestPreamble::new_serialized(
                     fd,
-                    200,
-                    "OK",
-                    md.content_length.clone(),
-                    &HttpContentType::JSON,
-                    md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(request_json.as_bytes().len() as u32),
+                    Some(&HttpContentType::JSON),
+                    empty_headers,
                 )?;
-                HttpResponseType::send_json(protocol, md, fd, &txid_bytes)?;
+                fd.write_all(&request_json.as_bytes())
+                    .map_err(net_error::WriteError)?;
             }
-            HttpResponseType::StacksBlockAccepted(ref md, ref stacks_block_id, ref accepted) => {
-                let accepted_data = StacksBlockAcceptedData {
-                    stacks_block_id: stacks_block_id.clone(),
-                    accepted: *accepted,
+            HttpRequestType::CallReadOnlyFunction(
+                md,
+                _contract_addr,
+                _contract_name,
+                sender,
+                _func_name,
+                func_args,
+                ..,
+            ) => {
+                let mut args = vec![];
+                for arg in func_args.iter() {
+                    let mut arg_bytes = vec![];
+                    arg.serialize_write(&mut arg_bytes)
+                        .map_err(net_error::WriteError)?;
+                    args.push(serde_json::Value::String(to_hex(&arg_bytes)));
+                }
+
+                let request_body = CallReadOnlyRequestBody {
+                    sender: sender.to_string(),
+                    arguments: args,
                 };
-                HttpResponsePreamble::new_serialized(
+
+                let mut request_body_bytes = vec![];
+                serde_json::to_writer(&mut request_body_bytes, &request_body).map_err(|e| {
+                    net_error::SerializeError(format!(
+                        "Failed to serialize read-only call to JSON: {:?}",
+                        &e
+                    ))
+                })?;
+
+                HttpRequestPreamble::new_serialized(
                     fd,
-                    200,
-                    "OK",
-                    md.content_length.clone(),
-                    &HttpContentType::JSON,
-                    md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(request_body_bytes.len() as u32),
+                    Some(&HttpContentType::JSON),
+                    empty_headers,
                 )?;
-                HttpResponseType::send_json(protocol, md, fd, &accepted_data)?;
+                fd.write_all(&request_body_bytes)
+                    .map_err(net_error::WriteError)?;
             }
-            HttpResponseType::MicroblockHash(ref md, ref mblock_hash) => {
-                let mblock_bytes = mblock_hash.to_hex();
-                HttpResponsePreamble::new_serialized(
+            HttpRequestType::SimulateContractCall(
+                md,
+                _contract_addr,
+                _contract_name,
+                sender,
+                _func_name,
+                func_args,
+                overrides,
+                ..,
+            ) => {
+                let mut args = vec![];
+                for arg in func_args.iter() {
+                    let mut arg_bytes = vec![];
+                    arg.serialize_write(&mut arg_bytes)
+                        .map_err(net_error::WriteError)?;
+                    args.push(to_hex(&arg_bytes));
+                }
+
+                let request_body = SimulateContractCallRequestBody {
+                    sender: sender.to_string(),
+                    arguments: args,
+                    overrides: overrides.iter().map(|o| o.to_body()).collect(),
+                };
+
+                let mut request_body_bytes = vec![];
+                serde_json::to_writer(&mut request_body_bytes, &request_body).map_err(|e| {
+                    net_error::SerializeError(format!(
+                        "Failed to serialize contract-call simulation to JSON: {:?}",
+                        &e
+                    ))
+                })?;
+
+                HttpRequestPreamble::new_serialized(
                     fd,
-                    200,
-                    "OK",
-                    md.content_length.clone(),
-                    &HttpContentType::JSON,
-                    md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(request_body_bytes.len() as u32),
+                    Some(&HttpContentType::JSON),
+                    empty_headers,
                 )?;
-                HttpResponseType::send_json(protocol, md, fd, &mblock_bytes)?;
+                fd.write_all(&request_body_bytes)
+                    .map_err(net_error::WriteError)?;
             }
-            HttpResponseType::UnconfirmedTransaction(ref md, ref unconfirmed_status) => {
-                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
-                HttpResponseType::send_json(protocol, md, fd, unconfirmed_status)?;
+            HttpRequestType::PreviewPostConditions(md, tx, ..) => {
+                let mut tx_bytes = vec![];
+                write_next(&mut tx_bytes, tx)?;
+                let request_body = PostConditionsPreviewRequestBody {
+                    tx: to_hex(&tx_bytes[..]),
+                };
+
+                let mut request_body_bytes = vec![];
+                serde_json::to_writer(&mut request_body_bytes, &request_body).map_err(|e| {
+                    net_error::SerializeError(format!(
+                        "Failed to serialize post-condition preview to JSON: {:?}",
+                        &e
+                    ))
+                })?;
+
+                HttpRequestPreamble::new_serialized(
+                    fd,
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(request_body_bytes.len() as u32),
+                    Some(&HttpContentType::JSON),
+                    empty_headers,
+                )?;
+                fd.write_all(&request_body_bytes)
+                    .map_err(net_error::WriteError)?;
             }
-            HttpResponseType::OptionsPreflight(ref md) => {
-                HttpResponsePreamble::new_serialized(
+            HttpRequestType::GetAdminToggles(md, token)
+            | HttpRequestType::GetMinerWallet(md, token)
+            | HttpRequestType::GetClarityProfiles(md, token) => {
+                let headers = HttpRequestType::bearer_token_headers(token.as_ref());
+                HttpRequestPreamble::new_serialized(
                     fd,
-                    200,
-                    "OK",
+                    &md.version,
+                    "GET",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
                     None,
-                    &HttpContentType::Text,
-                    md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    None,
+                    |ref mut fd| write_headers(fd, &headers),
                 )?;
-                HttpResponseType::send_text(protocol, md, fd, "".as_bytes())?;
             }
-            HttpResponseType::BadRequestJSON(ref md, ref data) => {
-                HttpResponsePreamble::new_serialized(
+            HttpRequestType::PostAdminToggles(md, token, update) => {
+                let headers = HttpRequestType::bearer_token_headers(token.as_ref());
+                let mut request_body_bytes = vec![];
+                serde_json::to_writer(&mut request_body_bytes, update).map_err(|e| {
+                    net_error::SerializeError(format!(
+                        "Failed to serialize admin toggles to JSON: {:?}",
+                        &e
+                    ))
+                })?;
+
+                HttpRequestPreamble::new_serialized(
                     fd,
-                    400,
-                    HttpResponseType::error_reason(400),
-                    md.content_length.clone(),
-                    &HttpContentType::JSON,
-                    md.request_id,
-                    |ref mut fd| keep_alive_headers(fd, md),
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(request_body_bytes.len() as u32),
+                    Some(&HttpContentType::JSON),
+                    |ref mut fd| write_headers(fd, &headers),
                 )?;
-                HttpResponseType::send_json(protocol, md, fd, data)?;
-            }
-            HttpResponseType::BadRequest(_, ref msg) => self.error_response(fd, 400, msg)?,
-            HttpResponseType::Unauthorized(_, ref msg) => self.error_response(fd, 401, msg)?,
-            HttpResponseType::PaymentRequired(_, ref msg) => self.error_response(fd, 402, msg)?,
-            HttpResponseType::Forbidden(_, ref msg) => self.error_response(fd, 403, msg)?,
-            HttpResponseType::NotFound(_, ref msg) => self.error_response(fd, 404, msg)?,
-            HttpResponseType::ServerError(_, ref msg) => self.error_response(fd, 500, msg)?,
-            HttpResponseType::ServiceUnavailable(_, ref msg) => {
-                self.error_response(fd, 503, msg)?
-            }
-            HttpResponseType::Error(_, ref error_code, ref msg) => {
-                self.error_response(fd, *error_code, msg)?
+                fd.write_all(&request_body_bytes)
+                    .map_err(net_error::WriteError)?;
             }
-        };
-        Ok(())
-    }
-}
-
-impl StacksMessageCodec for StacksHttpPreamble {
-    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
-        match *self {
-            StacksHttpPreamble::Request(ref req) => req.consensus_serialize(fd),
-            StacksHttpPreamble::Response(ref res) => res.consensus_serialize(fd),
-        }
-    }
+            HttpRequestType::PostTransferStxOp(md, token, request) => {
+                let headers = HttpRequestType::bearer_token_headers(token.as_ref());
+                let mut request_body_bytes = vec![];
+                serde_json::to_writer(&mut request_body_bytes, request).map_err(|e| {
+                    net_error::SerializeError(format!(
+                        "Failed to serialize transfer-STX request to JSON: {:?}",
+                        &e
+                    ))
+                })?;
 
-    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<StacksHttpPreamble, codec_error> {
-        let mut retry_fd = RetryReader::new(fd);
+                HttpRequestPreamble::new_serialized(
+                    fd,
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(request_body_bytes.len() as u32),
+                    Some(&HttpContentType::JSON),
+                    |ref mut fd| write_headers(fd, &headers),
+                )?;
+                fd.write_all(&request_body_bytes)
+                    .map_err(net_error::WriteError)?;
+            }
+            HttpRequestType::PostBlockProposal(md, token, request) => {
+                let headers = HttpRequestType::bearer_token_headers(token.as_ref());
+                let mut request_body_bytes = vec![];
+                serde_json::to_writer(&mut request_body_bytes, request).map_err(|e| {
+                    net_error::SerializeError(format!(
+                        "Failed to serialize block proposal to JSON: {:?}",
+                        &e
+                    ))
+                })?;
 
-        // the byte stream can decode to a http request or a http response, but not both.
-        match HttpRequestPreamble::consensus_deserialize(&mut retry_fd) {
-            Ok(request) => Ok(StacksHttpPreamble::Request(request)),
-            Err(e_request) => {
-                // maybe a http response?
-                retry_fd.set_position(0);
-                match HttpResponsePreamble::consensus_deserialize(&mut retry_fd) {
-                    Ok(response) => Ok(StacksHttpPreamble::Response(response)),
-                    Err(e) => {
-                        // underflow?
-                        match (e_request, e) {
-                            (codec_error::ReadError(ref ioe1), codec_error::ReadError(ref ioe2)) => {
-                                if ioe1.kind() == io::ErrorKind::UnexpectedEof && ioe2.kind() == io::ErrorKind::UnexpectedEof {
-                                    // out of bytes
-                                    Err(codec_error::UnderflowError("Not enough bytes to form a HTTP request or response".to_string()))
-                                }
-                                else {
-                                    Err(codec_error::DeserializeError(format!("Neither a HTTP request ({:?}) or HTTP response ({:?})", ioe1, ioe2)))
-                                }
-                            },
-                            (e_req, e_res) => Err(codec_error::DeserializeError(format!("Failed to decode HTTP request or HTTP response (request error: {:?}; response error: {:?})", &e_req, &e_res)))
-                        }
-                    }
-                }
+                HttpRequestPreamble::new_serialized(
+                    fd,
+                    &md.version,
+                    "POST",
+                    &self.request_path(),
+                    &md.peer,
+                    md.keep_alive,
+                    Some(request_body_bytes.len() as u32),
+                    Some(&HttpContentType::JSON),
+                    |ref mut fd| write_headers(fd, &headers),
+                )?;
+                fd.write_all(&request_body_bytes)
+                    .map_err(net_error::WriteError)?;
+            }
+            other_type => {
+                let md = other_type.metadata();
+                let request_path = other_type.request_path();
+                HttpRequestPreamble::new_serialized(
+                    fd,
+                    &md.version,
+                    "GET",
+                    &request_path,
+                    &md.peer,
+                    md.keep_alive,
+                    None,
+                    None,
+                    empty_headers,
+                )?;
             }
         }
+        Ok(())
     }
 }
 
-impl MessageSequence for StacksHttpMessage {
-    fn request_id(&self) -> u32 {
-        // there is at most one in-flight HTTP request, as far as a Connection<P> is concerned
-        HTTP_REQUEST_ID_RESERVED
-    }
-
-    fn get_message_name(&self) -> &'static str {
-        match *self {
-            StacksHttpMessage::Request(ref req) => match req {
-                HttpRequestType::GetInfo(_) => "HTTP(GetInfo)",
-                HttpRequestType::GetPoxInfo(_, _) => "HTTP(GetPoxInfo)",
-                HttpRequestType::GetNeighbors(_) => "HTTP(GetNeighbors)",
-                HttpRequestType::GetBlock(_, _) => "HTTP(GetBlock)",
-                HttpRequestType::GetMicroblocksIndexed(_, _) => "HTTP(GetMicroblocksIndexed)",
-                HttpRequestType::GetMicroblocksConfirmed(_, _) => "HTTP(GetMicroblocksConfirmed)",
-                HttpRequestType::GetMicroblocksUnconfirmed(_, _, _) => {
-                    "HTTP(GetMicroblocksUnconfirmed)"
-                }
-                HttpRequestType::GetTransactionUnconfirmed(_, _) => {
-                    "HTTP(GetTransactionUnconfirmed)"
-                }
-                HttpRequestType::PostTransaction(_, _, _) => "HTTP(PostTransaction)",
-                HttpRequestType::PostBlock(..) => "HTTP(PostBlock)",
-                HttpRequestType::PostMicroblock(..) => "HTTP(PostMicroblock)",
-                HttpRequestType::GetAccount(..) => "HTTP(GetAccount)",
-                HttpRequestType::GetMapEntry(..) => "HTTP(GetMapEntry)",
-                HttpRequestType::GetTransferCost(_) => "HTTP(GetTransferCost)",
-                HttpRequestType::GetContractABI(..) => "HTTP(GetContractABI)",
-                HttpRequestType::GetContractSrc(..) => "HTTP(GetContractSrc)",
-                HttpRequestType::GetIsTraitImplemented(..) => "HTTP(GetIsTraitImplemented)",
-                HttpRequestType::CallReadOnlyFunction(..) => "HTTP(CallReadOnlyFunction)",
-                HttpRequestType::GetAttachment(..) => "HTTP(GetAttachment)",
-                HttpRequestType::GetAttachmentsInv(..) => "HTTP(GetAttachmentsInv)",
-                HttpRequestType::OptionsPreflight(..) => "HTTP(OptionsPreflight)",
-                HttpRequestType::ClientError(..) => "HTTP(ClientError)",
-            },
-            StacksHttpMessage::Response(ref res) => match res {
-                HttpResponseType::TokenTransferCost(_, _) => "HTTP(TokenTransferCost)",
-                HttpResponseType::GetMapEntry(_, _) => "HTTP(GetMapEntry)",
-                HttpResponseType::GetAccount(_, _) => "HTTP(GetAccount)",
-                HttpResponseType::GetContractABI(..) => "HTTP(GetContractABI)",
-                HttpResponseType::GetContractSrc(..) => "HTTP(GetContractSrc)",
-                HttpResponseType::GetIsTraitImplemented(..) => "HTTP(GetIsTraitImplemented)",
-                HttpResponseType::CallReadOnlyFunction(..) => "HTTP(CallReadOnlyFunction)",
-                HttpResponseType::GetAttachment(_, _) => "HTTP(GetAttachment)",
-                HttpResponseType::GetAttachmentsInv(_, _) => "HTTP(GetAttachmentsInv)",
-                HttpResponseType::PeerInfo(_, _) => "HTTP(PeerInfo)",
-                HttpResponseType::PoxInfo(_, _) => "HTTP(PeerInfo)",
-                HttpResponseType::Neighbors(_, _) => "HTTP(Neighbors)",
-                HttpResponseType::Block(_, _) => "HTTP(Block)",
-                HttpResponseType::BlockStream(_) => "HTTP(BlockStream)",
-                HttpResponseType::Microblocks(_, _) => "HTTP(Microblocks)",
-                HttpResponseType::MicroblockStream(_) => "HTTP(MicroblockStream)",
-                HttpResponseType::TransactionID(_, _) => "HTTP(Transaction)",
-                HttpResponseType::StacksBlockAccepted(..) => "HTTP(StacksBlockAccepted)",
-                HttpResponseType::MicroblockHash(_, _) => "HTTP(MicroblockHash)",
-                HttpResponseType::UnconfirmedTransaction(_, _) => "HTTP(UnconfirmedTransaction)",
-                HttpResponseType::OptionsPreflight(_) => "HTTP(OptionsPreflight)",
-                HttpResponseType::BadRequestJSON(..) | HttpResponseType::BadRequest(..) => {
-                    "HTTP(400)"
-                }
-                HttpResponseType::Unauthorized(_, _) => "HTTP(401)",
-                HttpResponseType::PaymentRequired(_, _) => "HTTP(402)",
-                HttpResponseType::Forbidden(_, _) => "HTTP(403)",
-                HttpResponseType::NotFound(_, _) => "HTTP(404)",
-                HttpResponseType::ServerError(_, _) => "HTTP(500)",
-                HttpResponseType::ServiceUnavailable(_, _) => "HTTP(503)",
-                HttpResponseType::Error(_, _, _) => "HTTP(other)",
-            },
+impl HttpResponseType {
+    fn try_parse<R: Read, F>(
+        protocol: &mut StacksHttp,
+        regex: &Regex,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        request_path: &String,
+        fd: &mut R,
+        len_hint: Option<usize>,
+        parser: F,
+    ) -> Result<Option<HttpResponseType>, net_error>
+    where
+        F: Fn(
+            &mut StacksHttp,
+            HttpVersion,
+            &HttpResponsePreamble,
+            &mut R,
+            Option<usize>,
+        ) -> Result<HttpResponseType, net_error>,
+    {
+        if regex.is_match(request_path) {
+            let payload = parser(protocol, request_version, preamble, fd, len_hint)?;
+            Ok(Some(payload))
+        } else {
+            Ok(None)
         }
     }
-}
 
-/// A partially-decoded, streamed HTTP message (response) being received.
-/// Internally used by StacksHttp to keep track of chunk-decoding state.
-#[derive(Debug, Clone, PartialEq)]
-struct HttpRecvStream {
-    state: HttpChunkedTransferReaderState,
-    data: Vec<u8>,
-    total_consumed: usize, // number of *encoded* bytes consumed
-}
+    fn parse_error<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+    ) -> Result<HttpResponseType, net_error> {
+        if preamble.status_code < 400 || preamble.status_code > 599 {
+            return Err(net_error::DeserializeError(
+                "Inavlid response: not an error".to_string(),
+            ));
+        }
 
-impl HttpRecvStream {
-    pub fn new(max_size: u64) -> HttpRecvStream {
-        HttpRecvStream {
-            state: HttpChunkedTransferReaderState::new(max_size),
-            data: vec![],
-            total_consumed: 0,
+        if preamble.content_type != HttpContentType::Text {
+            return Err(net_error::DeserializeError(
+                "Invalid error response: expected text/plain".to_string(),
+            ));
         }
+
+        let mut error_text = String::new();
+        fd.read_to_string(&mut error_text)
+            .map_err(net_error::ReadError)?;
+
+        let md = HttpResponseMetadata::from_preamble(request_version, preamble);
+        let resp = match preamble.status_code {
+            400 => HttpResponseType::BadRequest(md, error_text),
+            401 => HttpResponseType::Unauthorized(md, error_text),
+            402 => HttpResponseType::PaymentRequired(md, error_text),
+            403 => HttpResponseType::Forbidden(md, error_text),
+            404 => HttpResponseType::NotFound(md, error_text),
+            500 => HttpResponseType::ServerError(md, error_text),
+            503 => HttpResponseType::ServiceUnavailable(md, error_text),
+            _ => HttpResponseType::Error(md, preamble.status_code, error_text),
+        };
+        Ok(resp)
     }
 
-    /// Feed data into our chunked transfer reader state.  If we finish reading a stream, return
-    /// the decoded bytes (as Some(Vec<u8>) and the total number of encoded bytes consumed).
-    /// Always returns the number of bytes consumed.
-    pub fn consume_data<R: Read>(
-        &mut self,
+    fn parse_bytestream<R: Read, T: StacksMessageCodec>(
+        preamble: &HttpResponsePreamble,
         fd: &mut R,
-    ) -> Result<(Option<(Vec<u8>, usize)>, usize), net_error> {
-        let mut consumed = 0;
-        let mut blocked = false;
-        while !blocked {
-            let mut decoded_buf = vec![0u8; 8192];
-            let (read_pass, consumed_pass) = match self.state.do_read(fd, &mut decoded_buf) {
-                Ok((0, num_consumed)) => {
-                    trace!(
-                        "consume_data blocked on 0 decoded bytes ({} consumed)",
-                        num_consumed
-                    );
-                    blocked = true;
-                    (0, num_consumed)
-                }
-                Ok((num_read, num_consumed)) => (num_read, num_consumed),
-                Err(e) => match e.kind() {
-                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
-                        trace!("consume_data blocked on read error");
-                        blocked = true;
-                        (0, 0)
-                    }
-                    _ => {
-                        return Err(net_error::ReadError(e));
-                    }
-                },
+        len_hint: Option<usize>,
+        max_len: u64,
+    ) -> Result<T, net_error> {
+        // content-type has to be Bytes
+        if preamble.content_type != HttpContentType::Bytes {
+            return Err(net_error::DeserializeError(
+                "Invalid content-type: expected application/octet-stream".to_string(),
+            ));
+        }
+
+        let item: T = if preamble.is_chunked() && len_hint.is_none() {
+            let mut chunked_fd = HttpChunkedTransferReader::from_reader(fd, max_len);
+            read_next(&mut chunked_fd)?
+        } else {
+            let content_length_opt = match (preamble.content_length, len_hint) {
+                (Some(l), _) => Some(l as u32),
+                (None, Some(l)) => Some(l as u32),
+                (None, None) => None,
             };
+            if let Some(content_length) = content_length_opt {
+                if (content_length as u64) > max_len {
+                    return Err(net_error::DeserializeError(
+                        "Invalid Content-Length header: too long".to_string(),
+                    ));
+                }
 
-            consumed += consumed_pass;
-            if read_pass > 0 {
-                self.data.extend_from_slice(&decoded_buf[0..read_pass]);
+                let mut bound_fd = BoundReader::from_reader(fd, content_length as u64);
+                read_next(&mut bound_fd)?
+            } else {
+                // unsupported headers
+                trace!("preamble: {:?}", preamble);
+                return Err(net_error::DeserializeError(
+                    "Invalid headers: need either Transfer-Encoding or Content-Length".to_string(),
+                ));
             }
-        }
+        };
 
-        self.total_consumed += consumed;
+        Ok(item)
+    }
 
-        // did we get a message?
-        if self.state.is_eof() {
-            // reset
-            let message_data = mem::replace(&mut self.data, vec![]);
-            let total_consumed = self.total_consumed;
-
-            self.state = HttpChunkedTransferReaderState::new(self.state.max_size);
-            self.total_consumed = 0;
-
-            Ok((Some((message_data, total_consumed)), consumed))
-        } else {
-            Ok((None, consumed))
-        }
-    }
-}
-
-/// Information about an in-flight request
-#[derive(Debug, Clone, PartialEq)]
-struct HttpReplyData {
-    request_id: u32,
-    stream: HttpRecvStream,
-}
-
-/// Stacks HTTP implementation, for bufferring up data.
-/// One of these exists per Connection<P: Protocol>.
-/// There can be at most one HTTP request in-flight (i.e. we don't do pipelining)
-#[derive(Debug, Clone, PartialEq)]
-pub struct StacksHttp {
-    /// Address of peer
-    peer_addr: SocketAddr,
-    /// Version of client
-    request_version: Option<HttpVersion>,
-    /// Path we requested
-    request_path: Option<String>,
-    /// Incoming reply
-    reply: Option<HttpReplyData>,
-    /// Size of HTTP chunks to write
-    chunk_size: usize,
-    /// Maximum size of call arguments
-    pub maximum_call_argument_size: u32,
-}
-
-impl StacksHttp {
-    pub fn new(peer_addr: SocketAddr) -> StacksHttp {
-        StacksHttp {
-            peer_addr,
-            reply: None,
-            request_version: None,
-            request_path: None,
-            chunk_size: 8192,
-            maximum_call_argument_size: 20 * BOUND_VALUE_SERIALIZATION_HEX,
-        }
-    }
-
-    pub fn set_chunk_size(&mut self, size: usize) -> () {
-        self.chunk_size = size;
-    }
-
-    pub fn num_pending(&self) -> usize {
-        if self.reply.is_some() {
-            1
-        } else {
-            0
-        }
-    }
-
-    pub fn has_pending_reply(&self) -> bool {
-        self.reply.is_some()
-    }
-
-    pub fn set_pending(&mut self, preamble: &HttpResponsePreamble) -> bool {
-        if self.reply.is_some() {
-            // already pending
-            return false;
-        }
-        self.reply = Some(HttpReplyData {
-            request_id: preamble.request_id,
-            stream: HttpRecvStream::new(MAX_MESSAGE_LEN as u64),
-        });
-        true
-    }
-
-    pub fn begin_request(&mut self, client_version: HttpVersion, request_path: String) -> () {
-        self.request_version = Some(client_version);
-        self.request_path = Some(request_path);
-    }
-
-    pub fn reset(&mut self) -> () {
-        self.request_version = None;
-        self.request_path = None;
-        self.reply = None;
-    }
-
-    /// Used for processing chunk-encoded streams.
-    /// Given the preamble and a Read, stream the bytes into a chunk-decoder.  Return the decoded
-    /// bytes if we decode an entire stream.  Always return the number of bytes consumed.
-    /// Returns Ok((Some(request path, decoded bytes we got, total number of encoded bytes), number of bytes gotten in this call))
-    pub fn consume_data<R: Read>(
-        &mut self,
+    fn parse_json<R: Read, T: serde::de::DeserializeOwned>(
         preamble: &HttpResponsePreamble,
         fd: &mut R,
-    ) -> Result<(Option<(HttpVersion, String, Vec<u8>, usize)>, usize), net_error> {
-        assert!(preamble.is_chunked());
-        assert!(self.reply.is_some());
-        assert!(self.request_path.is_some());
-        assert!(self.request_version.is_some());
+        len_hint: Option<usize>,
+        max_len: u64,
+    ) -> Result<T, net_error> {
+        // content-type has to be JSON
+        if preamble.content_type != HttpContentType::JSON {
+            return Err(net_error::DeserializeError(
+                "Invalid content-type: expected application/json".to_string(),
+            ));
+        }
 
-        let mut finished = false;
-        let res = match self.reply {
-            Some(ref mut reply) => {
-                assert_eq!(reply.request_id, preamble.request_id);
-                match reply.stream.consume_data(fd) {
-                    Ok(res) => {
-                        match res {
-                            (None, sz) => Ok((None, sz)),
-                            (Some((byte_vec, bytes_total)), sz) => {
-                                // done receiving
-                                finished = true;
-                                Ok((
-                                    Some((
-                                        self.request_version.clone().unwrap(),
-                                        self.request_path.clone().unwrap(),
-                                        byte_vec,
-                                        bytes_total,
-                                    )),
-                                    sz,
-                                ))
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        // broken stream
-                        finished = true;
-                        Err(e)
-                    }
+        let item_result: Result<T, serde_json::Error> = if preamble.is_chunked()
+            && len_hint.is_none()
+        {
+            let chunked_fd = HttpChunkedTransferReader::from_reader(fd, max_len);
+            serde_json::from_reader(chunked_fd)
+        } else {
+            let content_length_opt = match (preamble.content_length, len_hint) {
+                (Some(l), _) => Some(l as u32),
+                (None, Some(l)) => Some(l as u32),
+                (None, None) => None,
+            };
+            if let Some(content_length) = content_length_opt {
+                if (content_length as u64) > max_len {
+                    return Err(net_error::DeserializeError(
+                        "Invalid Content-Length header: too long".to_string(),
+                    ));
                 }
-            }
-            None => {
-                unreachable!();
+                let bound_fd = BoundReader::from_reader(fd, content_length as u64);
+                serde_json::from_reader(bound_fd)
+            } else {
+                // unsupported headers
+                trace!("preamble: {:?}", preamble);
+                return Err(net_error::DeserializeError(
+                    "Invalid headers: need either Transfer-Encoding or Content-Length".to_string(),
+                ));
             }
         };
 
-        if finished {
-            // if we fetch the whole message, or encounter an error, then we're done -- we can free
-            // up this stream.
-            self.reset();
-        }
-        res
-    }
-
-    /// Given a HTTP request, serialize it out
-    #[cfg(test)]
-    pub fn serialize_request(req: &HttpRequestType) -> Result<Vec<u8>, net_error> {
-        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
-        let mut ret = vec![];
-        req.send(&mut http, &mut ret)?;
-        Ok(ret)
+        item_result.map_err(|e| {
+            if e.is_eof() {
+                net_error::UnderflowError(format!("Not enough bytes to parse JSON"))
+            } else {
+                net_error::DeserializeError(format!("Failed to parse JSON: {:?}", &e))
+            }
+        })
     }
 
-    /// Given a fully-formed single HTTP response, parse it (used by clients).
-    #[cfg(test)]
-    pub fn parse_response(
-        request_path: &str,
-        response_buf: &[u8],
-    ) -> Result<StacksHttpMessage, net_error> {
-        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
-        http.reset();
-        http.begin_request(HttpVersion::Http11, request_path.to_string());
-
-        let (preamble, message_offset) = http.read_preamble(response_buf)?;
-        let is_chunked = match preamble {
-            StacksHttpPreamble::Response(ref resp) => resp.is_chunked(),
-            _ => {
+    fn parse_text<R: Read>(
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+        max_len: u64,
+    ) -> Result<Vec<u8>, net_error> {
+        // content-type has to be text/plain
+        if preamble.content_type != HttpContentType::Text {
+            return Err(net_error::DeserializeError(
+                "Invalid content-type: expected text/plain".to_string(),
+            ));
+        }
+        let buf = if preamble.is_chunked() && len_hint.is_none() {
+            let mut chunked_fd = HttpChunkedTransferReader::from_reader(fd, max_len);
+            let mut buf = vec![];
+            chunked_fd
+                .read_to_end(&mut buf)
+                .map_err(net_error::ReadError)?;
+            buf
+        } else {
+            let content_length_opt = match (preamble.content_length, len_hint) {
+                (Some(l), _) => Some(l as u32),
+                (None, Some(l)) => Some(l as u32),
+                (None, None) => None,
+            };
+            if let Some(len) = content_length_opt {
+                let mut buf = vec![0u8; len as usize];
+                fd.read_exact(&mut buf).map_err(net_error::ReadError)?;
+                buf
+            } else {
+                // unsupported headers
+                trace!("preamble: {:?}", preamble);
                 return Err(net_error::DeserializeError(
-                    "Invalid HTTP message: did not get a Response preamble".to_string(),
+                    "Invalid headers: need either Transfer-Encoding or Content-Length".to_string(),
                 ));
             }
         };
 
-        let mut message_bytes = &response_buf[message_offset..];
-
-        if is_chunked {
-            match http.stream_payload(&preamble, &mut message_bytes) {
-                Ok((Some((message, _)), _)) => Ok(message),
-                Ok((None, _)) => Err(net_error::UnderflowError(
-                    "Not enough bytes to form a streamed HTTP response".to_string(),
-                )),
-                Err(e) => Err(e),
-            }
-        } else {
-            let (message, _) = http.read_payload(&preamble, &mut message_bytes)?;
-            Ok(message)
-        }
+        Ok(buf)
     }
-}
 
-impl ProtocolFamily for StacksHttp {
-    type Preamble = StacksHttpPreamble;
-    type Message = StacksHttpMessage;
-
-    /// how big can a preamble get?
-    fn preamble_size_hint(&mut self) -> usize {
-        HTTP_PREAMBLE_MAX_ENCODED_SIZE as usize
-    }
-
-    /// how big is this message?  Might not know if we're dealing with chunked encoding.
-    fn payload_len(&mut self, preamble: &StacksHttpPreamble) -> Option<usize> {
-        match *preamble {
-            StacksHttpPreamble::Request(ref http_request_preamble) => {
-                Some(http_request_preamble.get_content_length() as usize)
-            }
-            StacksHttpPreamble::Response(ref http_response_preamble) => {
-                match http_response_preamble.content_length {
-                    Some(len) => Some(len as usize),
-                    None => None,
-                }
-            }
+    // len_hint is given by the StacksHttp protocol implementation
+    pub fn parse<R: Read>(
+        protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        request_path: String,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        if preamble.status_code >= 400 {
+            return HttpResponseType::parse_error(protocol, request_version, preamble, fd);
         }
-    }
 
-    /// StacksHttpMessage deals with HttpRequestPreambles and HttpResponsePreambles
-    fn read_preamble(&mut self, buf: &[u8]) -> Result<(StacksHttpPreamble, usize), net_error> {
-        let mut cursor = io::Cursor::new(buf);
+        // TODO: make this static somehow
+        let RESPONSE_METHODS: &[(
+            &Regex,
+            &dyn Fn(
+                &mut StacksHttp,
+                HttpVersion,
+                &HttpResponsePreamble,
+                &mut R,
+                Option<usize>,
+            ) -> Result<HttpResponseType, net_error>,
+        )] = &[
+            (&PATH_GETINFO, &HttpResponseType::parse_peerinfo),
+            (&PATH_GETPOXINFO, &HttpResponseType::parse_poxinfo),
+            (&PATH_GETNEIGHBORS, &HttpResponseType::parse_neighbors),
+            (&PATH_GETBLOCK, &HttpResponseType::parse_block),
+            (&PATH_GET_MAP_ENTRY, &HttpResponseType::parse_get_map_entry),
+            (
+                &PATH_GETMICROBLOCKS_INDEXED,
+                &HttpResponseType::parse_microblocks,
+            ),
+            (
+                &PATH_GETMICROBLOCKS_CONFIRMED,
+                &HttpResponseType::parse_microblocks,
+            ),
+            (
+                &PATH_GETMICROBLOCKS_UNCONFIRMED,
+                &HttpResponseType::parse_microblocks_unconfirmed,
+            ),
+            (
+                &PATH_GETTRANSACTION_UNCONFIRMED,
+                &HttpResponseType::parse_transaction_unconfirmed,
+            ),
+            (&PATH_POSTTRANSACTION, &HttpResponseType::parse_txid),
+            (
+                &PATH_POSTBLOCK,
+                &HttpResponseType::parse_stacks_block_accepted,
+            ),
+            (
+                &PATH_POSTMICROBLOCK,
+                &HttpResponseType::parse_microblock_hash,
+            ),
+            (&PATH_GET_ACCOUNT, &HttpResponseType::parse_get_account),
+            (
+                &PATH_GET_ACCOUNT_TRANSACTIONS,
+                &HttpResponseType::parse_get_account_transactions,
+            ),
+            (
+                &PATH_GET_TRANSACTION_RECEIPT,
+                &HttpResponseType::parse_get_transaction_receipt,
+            ),
+            (
+                &PATH_GET_SPONSORSHIP,
+                &HttpResponseType::parse_get_sponsorship,
+            ),
+            (
+                &PATH_GET_ACCOUNT_BALANCE,
+                &HttpResponseType::parse_get_account_balance,
+            ),
+            (
+                &PATH_GET_STACKING_STATUS,
+                &HttpResponseType::parse_get_stacking_status,
+            ),
+            (
+                &PATH_GET_DELEGATIONS,
+                &HttpResponseType::parse_get_delegations,
+            ),
+            (
+                &PATH_GET_REWARD_SET,
+                &HttpResponseType::parse_get_reward_set,
+            ),
+            (&PATH_GET_SORTITION, &HttpResponseType::parse_get_sortition),
+            (
+                &PATH_GET_CONSENSUS_SCHEDULE,
+                &HttpResponseType::parse_get_consensus_schedule,
+            ),
+            (
+                &PATH_GET_MICROBLOCK_EQUIVOCATIONS,
+                &HttpResponseType::parse_get_microblock_equivocations,
+            ),
+            (&PATH_ADMIN_TOGGLES, &HttpResponseType::parse_admin_toggles),
+            (
+                &PATH_POST_TRANSFER_STX_OP,
+                &HttpResponseType::parse_burn_op_submitted,
+            ),
+            (
+                &PATH_POST_BLOCK_PROPOSAL,
+                &HttpResponseType::parse_block_proposal_validated,
+            ),
+            (
+                &PATH_GET_MINER_WALLET,
+                &HttpResponseType::parse_miner_wallet,
+            ),
+            (
+                &PATH_GET_CLARITY_PROFILES,
+                &HttpResponseType::parse_clarity_profiles,
+            ),
+            (
+                &PATH_GET_CONTRACT_SRC,
+                &HttpResponseType::parse_get_contract_src,
+            ),
+            (
+                &PATH_GET_IS_TRAIT_IMPLEMENTED,
+                &HttpResponseType::parse_get_is_trait_implemented,
+            ),
+            (
+                &PATH_GET_CONTRACT_ABI,
+                &HttpResponseType::parse_get_contract_abi,
+            ),
+            (
+                &PATH_GET_CONTRACT_EVENTS,
+                &HttpResponseType::parse_get_contract_events,
+            ),
+            (
+                &PATH_POST_CALL_READ_ONLY,
+                &HttpResponseType::parse_call_read_only,
+            ),
+            (
+                &PATH_POST_SIMULATE_CALL,
+                &HttpResponseType::parse_simulate_contract_call,
+            ),
+            (
+                &PATH_POST_POSTCONDITIONS_PREVIEW,
+                &HttpResponseType::parse_postconditions_preview,
+            ),
+            (
+                &PATH_GET_ATTACHMENT,
+                &HttpResponseType::parse_get_attachment,
+            ),
+            (
+                &PATH_GET_ATTACHMENTS_INV,
+                &HttpResponseType::parse_get_attachments_inv,
+            ),
+        ];
 
-        let preamble = {
-            let mut rd = BoundReader::from_reader(&mut cursor, 4096);
-            let preamble: StacksHttpPreamble = read_next(&mut rd)?;
-            preamble
-        };
+        // use url::Url to parse path and query string
+        //   Url will refuse to parse just a path, so create a dummy URL
+        let local_url = format!("http://local{}", &request_path);
+        let url = Url::parse(&local_url).map_err(|_e| {
+            net_error::DeserializeError("Http request path could not be parsed".to_string())
+        })?;
 
-        let preamble_len = cursor.position() as usize;
+        let decoded_path = percent_decode_str(url.path()).decode_utf8().map_err(|_e| {
+            net_error::DeserializeError(
+                "Http response path could not be parsed as UTF-8".to_string(),
+            )
+        })?;
 
-        // if we already have a pending message, then this preamble cannot be processed (indicates an un-compliant client)
-        match preamble {
-            StacksHttpPreamble::Response(ref http_response_preamble) => {
-                // request path must have been set
-                if self.request_path.is_none() {
-                    return Err(net_error::DeserializeError(
-                        "Possible bug: did not set the request path".to_string(),
-                    ));
+        for (regex, parser) in RESPONSE_METHODS.iter() {
+            match HttpResponseType::try_parse(
+                protocol,
+                regex,
+                request_version,
+                preamble,
+                &decoded_path.to_string(),
+                fd,
+                len_hint,
+                parser,
+            ) {
+                Ok(Some(request)) => {
+                    return Ok(request);
                 }
-
-                if http_response_preamble.is_chunked() {
-                    // will stream this.  Make sure we're not doing so already (no collisions
-                    // allowed on in-flight request IDs!)
-                    if self.has_pending_reply() {
-                        test_debug!("Have pending reply already");
-                        return Err(net_error::InProgress);
-                    }
-
-                    // mark as pending -- we can stream this
-                    if !self.set_pending(http_response_preamble) {
-                        test_debug!("Have pending reply already");
-                        return Err(net_error::InProgress);
-                    }
+                Ok(None) => {
+                    continue;
+                }
+                Err(e) => {
+                    test_debug!("Failed to parse {}: {:?}", &request_path, &e);
+                    return Err(e);
                 }
             }
-            _ => {}
         }
 
-        Ok((preamble, preamble_len))
+        test_debug!(
+            "Failed to match request path '{}' to a handler",
+            &request_path
+        );
+        return Err(net_error::DeserializeError(
+            "Http response could not be parsed".to_string(),
+        ));
     }
 
-    /// Stream a payload of unknown length.  Only gets called if payload_len() returns None.
-    /// Returns the message if we get enough data to form one.
-    /// Always returns the number of bytes consumed.
-    fn stream_payload<R: Read>(
-        &mut self,
-        preamble: &StacksHttpPreamble,
+    fn parse_peerinfo<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
         fd: &mut R,
-    ) -> Result<(Option<(StacksHttpMessage, usize)>, usize), net_error> {
-        assert!(self.payload_len(preamble).is_none());
-        match preamble {
-            StacksHttpPreamble::Request(_) => {
-                // HTTP requests can't be chunk-encoded, so this should never be reached
-                unreachable!()
-            }
-            StacksHttpPreamble::Response(ref http_response_preamble) => {
-                assert!(http_response_preamble.is_chunked());
-                assert!(self.request_path.is_some());
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let peer_info =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::PeerInfo(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            peer_info,
+        ))
+    }
 
-                // message of unknown length.  Buffer up and maybe we can parse it.
-                let (message_bytes_opt, num_read) =
-                    self.consume_data(http_response_preamble, fd).map_err(|e| {
-                        self.reset();
-                        e
-                    })?;
+    fn parse_poxinfo<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let pox_info =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::PoxInfo(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            pox_info,
+        ))
+    }
 
-                match message_bytes_opt {
-                    Some((request_version, request_path, message_bytes, total_bytes_consumed)) => {
-                        // can parse!
-                        test_debug!(
-                            "read http response payload of {} bytes (just buffered {}) for {}",
-                            message_bytes.len(),
-                            num_read,
-                            &request_path
-                        );
-
-                        // we now know the content-length, so pass it into the parser.
-                        let len_hint = message_bytes.len();
-                        let parse_res = HttpResponseType::parse(
-                            self,
-                            request_version,
-                            http_response_preamble,
-                            request_path,
-                            &mut &message_bytes[..],
-                            Some(len_hint),
-                        );
-
-                        // done parsing
-                        self.reset();
-                        match parse_res {
-                            Ok(data_response) => Ok((
-                                Some((
-                                    StacksHttpMessage::Response(data_response),
-                                    total_bytes_consumed,
-                                )),
-                                num_read,
-                            )),
-                            Err(e) => {
-                                info!("Failed to parse HTTP response: {:?}", &e);
-                                Err(e)
-                            }
-                        }
-                    }
-                    None => {
-                        // need more data
-                        trace!(
-                            "did not read http response payload, but buffered {}",
-                            num_read
-                        );
-                        Ok((None, num_read))
-                    }
-                }
-            }
-        }
+    fn parse_neighbors<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let neighbors_data =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::Neighbors(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            neighbors_data,
+        ))
     }
 
-    /// Parse a payload of known length.
-    /// Only gets called if payload_len() returns Some(...)
-    fn read_payload(
-        &mut self,
-        preamble: &StacksHttpPreamble,
-        buf: &[u8],
-    ) -> Result<(StacksHttpMessage, usize), net_error> {
-        match preamble {
-            StacksHttpPreamble::Request(ref http_request_preamble) => {
-                // all requests have a known length
-                let len = http_request_preamble.get_content_length() as usize;
-                assert!(len <= buf.len(), "{} > {}", len, buf.len());
+    fn parse_block<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let block: StacksBlock =
+            HttpResponseType::parse_bytestream(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::Block(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            block,
+        ))
+    }
 
-                trace!("read http request payload of {} bytes", len);
+    fn parse_microblocks<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let microblocks: Vec<StacksMicroblock> =
+            HttpResponseType::parse_bytestream(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::Microblocks(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            microblocks,
+        ))
+    }
 
-                let mut cursor = io::Cursor::new(buf);
-                match HttpRequestType::parse(self, http_request_preamble, &mut cursor) {
-                    Ok(data_request) => Ok((
-                        StacksHttpMessage::Request(data_request),
-                        cursor.position() as usize,
-                    )),
-                    Err(e) => {
-                        info!("Failed to parse HTTP request: {:?}", &e);
-                        if let net_error::ClientError(client_err) = e {
-                            let req = HttpRequestType::ClientError(
-                                HttpRequestMetadata::from_preamble(http_request_preamble),
-                                client_err,
-                            );
-                            // consume any remaining HTTP request content by returning bytes read = len
-                            Ok((StacksHttpMessage::Request(req), len))
-                        } else {
-                            Err(e)
-                        }
-                    }
-                }
-            }
-            StacksHttpPreamble::Response(ref http_response_preamble) => {
-                assert!(!http_response_preamble.is_chunked());
-                assert!(self.request_path.is_some());
-                assert!(self.request_version.is_some());
+    fn parse_get_account<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let account_entry =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetAccount(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            account_entry,
+        ))
+    }
 
-                let request_path = self.request_path.take().unwrap();
-                let request_version = self.request_version.take().unwrap();
+    fn parse_get_account_transactions<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let history = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetAccountTransactions(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            history,
+        ))
+    }
 
-                // message of known length
-                test_debug!(
-                    "read http response payload of {} bytes for {}",
-                    buf.len(),
-                    &request_path
-                );
+    fn parse_get_transaction_receipt<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let receipt = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetTransactionReceipt(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            receipt,
+        ))
+    }
 
-                let mut cursor = io::Cursor::new(buf);
-                match HttpResponseType::parse(
-                    self,
-                    request_version,
-                    http_response_preamble,
-                    request_path,
-                    &mut cursor,
-                    None,
-                ) {
-                    Ok(data_response) => Ok((
-                        StacksHttpMessage::Response(data_response),
-                        cursor.position() as usize,
-                    )),
-                    Err(e) => Err(e),
-                }
-            }
-        }
+    fn parse_get_sponsorship<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let sponsorship =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetSponsorship(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            sponsorship,
+        ))
     }
 
-    fn verify_payload_bytes(
-        &mut self,
-        _key: &StacksPublicKey,
-        _preamble: &StacksHttpPreamble,
-        _bytes: &[u8],
-    ) -> Result<(), net_error> {
-        // not defined for HTTP messages, but maybe we could add a signature header at some point
-        // in the future if needed.
-        Ok(())
+    fn parse_get_account_balance<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let balance = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetAccountBalance(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            balance,
+        ))
     }
 
-    fn write_message<W: Write>(
-        &mut self,
-        fd: &mut W,
-        message: &StacksHttpMessage,
-    ) -> Result<(), net_error> {
-        match *message {
-            StacksHttpMessage::Request(ref req) => {
-                if self.request_path.is_some() {
-                    test_debug!("Have pending request already");
-                    return Err(net_error::InProgress);
-                }
-                req.send(self, fd)?;
+    fn parse_get_stacking_status<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let stacking_status =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetStackingStatus(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            stacking_status,
+        ))
+    }
 
-                self.reset();
-                self.begin_request(req.metadata().version, req.request_path());
-                Ok(())
-            }
-            StacksHttpMessage::Response(ref resp) => resp.send(self, fd),
-        }
+    fn parse_get_delegations<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let delegations =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetDelegations(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            delegations,
+        ))
     }
-}
 
-#[cfg(test)]
-mod test {
-    use std::error::Error;
+    fn parse_get_reward_set<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let reward_set =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetRewardSet(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            reward_set,
+        ))
+    }
 
-    use rand;
-    use rand::RngCore;
-
-    use burnchains::Txid;
-    use chainstate::stacks::db::blocks::test::make_sample_microblock_stream;
-    use chainstate::stacks::test::make_codec_test_block;
-    use chainstate::stacks::StacksBlock;
-    use chainstate::stacks::StacksMicroblock;
-    use chainstate::stacks::StacksPrivateKey;
-    use chainstate::stacks::StacksTransaction;
-    use chainstate::stacks::TokenTransferMemo;
-    use chainstate::stacks::TransactionAuth;
-    use chainstate::stacks::TransactionPayload;
-    use chainstate::stacks::TransactionPostConditionMode;
-    use chainstate::stacks::TransactionVersion;
-    use net::codec::test::check_codec_and_corruption;
-    use net::test::*;
-    use net::RPCNeighbor;
-    use net::RPCNeighborsInfo;
-    use util::hash::to_hex;
-    use util::hash::Hash160;
-    use util::hash::MerkleTree;
-    use util::hash::Sha512Trunc256Sum;
-
-    use crate::types::chainstate::StacksAddress;
-    use crate::types::chainstate::StacksBlockHeader;
+    fn parse_get_sortition<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let sortition =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetSortition(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            sortition,
+        ))
+    }
 
-    use super::*;
+    fn parse_get_consensus_schedule<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let schedule =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetConsensusSchedule(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            schedule,
+        ))
+    }
 
-    /// Simulate reading variable-length segments
-    struct SegmentReader {
-        segments: Vec<Vec<u8>>,
-        i: usize, // which segment
-        j: usize, // which offset in segment
+    fn parse_get_microblock_equivocations<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let equivocations =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetMicroblockEquivocations(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            equivocations,
+        ))
     }
 
-    impl SegmentReader {
-        pub fn new(segments: Vec<Vec<u8>>) -> SegmentReader {
-            SegmentReader {
-                segments: segments,
-                i: 0,
-                j: 0,
-            }
-        }
+    fn parse_admin_toggles<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let toggles = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::AdminToggles(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            toggles,
+        ))
     }
 
-    impl Read for SegmentReader {
-        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-            if self.i >= self.segments.len() {
-                return Ok(0);
-            }
-            let mut written = 0;
-            while written < buf.len() {
-                let to_copy = if self.segments[self.i][self.j..].len() < buf[written..].len() {
-                    self.segments[self.i][self.j..].len()
-                } else {
-                    buf[written..].len()
-                };
+    fn parse_burn_op_submitted<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let submission =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::BurnOpSubmitted(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            submission,
+        ))
+    }
 
-                buf[written..(written + to_copy)]
-                    .copy_from_slice(&self.segments[self.i][self.j..(self.j + to_copy)]);
+    fn parse_miner_wallet<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let wallet = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::MinerWallet(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            wallet,
+        ))
+    }
 
-                self.j += to_copy;
-                written += to_copy;
+    fn parse_clarity_profiles<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let profiles =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::ClarityProfiles(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            profiles,
+        ))
+    }
 
-                if self.j >= self.segments[self.i].len() {
-                    self.i += 1;
-                    self.j = 0;
-                }
-            }
-            Ok(written)
-        }
+    fn parse_block_proposal_validated<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let validation =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::BlockProposalValidated(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            validation,
+        ))
     }
 
-    fn vec_u8(mut v: Vec<&str>) -> Vec<Vec<u8>> {
-        let mut ret = vec![];
-        for s_vec in v.drain(..) {
-            let v_u8 = s_vec.as_bytes().to_vec();
-            ret.push(v_u8);
-        }
-        ret
+    fn parse_get_map_entry<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let map_entry =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetMapEntry(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            map_entry,
+        ))
     }
 
-    #[test]
-    fn test_segment_reader() {
-        let mut tests = vec![
-            (vec_u8(vec!["a", "b"]), "ab"),
-            (vec_u8(vec!["aa", "bbb", "cccc"]), "aabbbcccc"),
-            (vec_u8(vec!["aaaa", "bbb", "cc", "d", ""]), "aaaabbbccd"),
-            (vec_u8(vec!["", "a", "", "b", ""]), "ab"),
-            (vec_u8(vec![""]), ""),
-        ];
-        for (input_vec, expected) in tests.drain(..) {
-            let num_segments = input_vec.len();
-            let mut segment_io = SegmentReader::new(input_vec);
-            let mut output = vec![0u8; expected.len()];
-            let mut offset = 0;
-            for i in 0..num_segments {
-                let nw = segment_io.read(&mut output[offset..]).unwrap();
-                offset += nw;
-            }
-            assert_eq!(output, expected.as_bytes().to_vec());
-        }
+    fn parse_get_contract_src<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let src_data =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetContractSrc(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            src_data,
+        ))
     }
 
-    #[test]
-    fn test_http_chunked_encode() {
-        let tests = vec![
-            // (chunk size, byte string, expected encoding)
-            (10, "aaaaaaaaaabbbbbbbbbbccccccccccdddddddddd", "a\r\naaaaaaaaaa\r\na\r\nbbbbbbbbbb\r\na\r\ncccccccccc\r\na\r\ndddddddddd\r\n0\r\n\r\n"),
-            (10, "aaaaaaaaaabbbbbbbbbbccccccccccdddddddddde", "a\r\naaaaaaaaaa\r\na\r\nbbbbbbbbbb\r\na\r\ncccccccccc\r\na\r\ndddddddddd\r\n1\r\ne\r\n0\r\n\r\n"),
-            (10, "aaaaaaaaaabbbbbbbbbbccccccccccddddddddddeeeee", "a\r\naaaaaaaaaa\r\na\r\nbbbbbbbbbb\r\na\r\ncccccccccc\r\na\r\ndddddddddd\r\n5\r\neeeee\r\n0\r\n\r\n"),
-            (1, "abcd", "1\r\na\r\n1\r\nb\r\n1\r\nc\r\n1\r\nd\r\n0\r\n\r\n"),
-            (3, "abcd", "3\r\nabc\r\n1\r\nd\r\n0\r\n\r\n"),
-            (10, "", "0\r\n\r\n")
-        ];
-        for (chunk_size, input_bytes, encoding) in tests.iter() {
-            let mut bytes = vec![];
-            {
-                let mut write_state = HttpChunkedTransferWriterState::new(*chunk_size as usize);
-                let mut encoder =
-                    HttpChunkedTransferWriter::from_writer_state(&mut bytes, &mut write_state);
-                encoder.write_all(input_bytes.as_bytes()).unwrap();
-                encoder.flush().unwrap();
-            }
+    fn parse_get_is_trait_implemented<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let src_data =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetIsTraitImplemented(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            src_data,
+        ))
+    }
 
-            assert_eq!(bytes, encoding.as_bytes().to_vec());
-        }
+    fn parse_get_contract_abi<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let abi = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetContractABI(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            abi,
+        ))
     }
 
-    #[test]
-    fn test_http_chunked_encode_multi() {
-        let tests = vec![
-            // chunk size, sequence of writes, expected encoding
-            (10, vec!["aaaaaaaaaa", "bbbbb", "bbbbb", "ccc", "ccc", "ccc", "c", "dd", "ddddd", "ddd"], "a\r\naaaaaaaaaa\r\na\r\nbbbbbbbbbb\r\na\r\ncccccccccc\r\na\r\ndddddddddd\r\n0\r\n\r\n"),
-            (10, vec!["a", "a", "a", "a", "a", "a", "a", "a", "a", "a"], "a\r\naaaaaaaaaa\r\n0\r\n\r\n"),
-            (10, vec!["a", "", "a", "", "a", "", "a", "", "a", "", "a", "", "a", "", "a", "", "a", "", "a", ""], "a\r\naaaaaaaaaa\r\n0\r\n\r\n"),
-        ];
+    fn parse_get_contract_events<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let events = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::GetContractEvents(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            events,
+        ))
+    }
 
-        for (chunk_size, input_vec, encoding) in tests.iter() {
-            let mut bytes = vec![];
-            {
-                let mut write_state = HttpChunkedTransferWriterState::new(*chunk_size as usize);
-                let mut encoder =
-                    HttpChunkedTransferWriter::from_writer_state(&mut bytes, &mut write_state);
-                for input in input_vec.iter() {
-                    encoder.write_all(input.as_bytes()).unwrap();
-                }
-                encoder.flush().unwrap();
-            }
+    fn parse_call_read_only<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let call_data =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::CallReadOnlyFunction(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            call_data,
+        ))
+    }
 
-            assert_eq!(bytes, encoding.as_bytes().to_vec());
-        }
+    fn parse_simulate_contract_call<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let simulation =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::SimulateContractCall(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            simulation,
+        ))
     }
 
-    #[test]
-    fn test_http_chunked_decode() {
-        let tests = vec![
-            ("a\r\naaaaaaaaaa\r\na\r\nbbbbbbbbbb\r\na\r\ncccccccccc\r\na\r\ndddddddddd\r\n0\r\n\r\n", "aaaaaaaaaabbbbbbbbbbccccccccccdddddddddd"),
-            ("A\r\naaaaaaaaaa\r\nA\r\nbbbbbbbbbb\r\nA\r\ncccccccccc\r\nA\r\ndddddddddd\r\n0\r\n\r\n", "aaaaaaaaaabbbbbbbbbbccccccccccdddddddddd"),
-            ("1\r\na\r\n2\r\nbb\r\n3\r\nccc\r\n4\r\ndddd\r\n0\r\n\r\n", "abbcccdddd"),
-            ("1\r\na\r\n0\r\n\r\n", "a"),
-            ("1\r\na\r\n0\r\n\r\n1\r\nb\r\n0\r\n\r\n", "a"),     // stop reading after the first 0-length chunk encountered
-            ("1; a; b\r\na\r\n0; c\r\n\r\n", "a"),                          // ignore short extensions
-            ("1  ; a ; b \r\na\r\n0     ; extension003\r\n\r\n", "a"),      // ignore short extensions
-            ("1 \t; a\t;\tb ;\r\na\r\n0\t\t;c\r\n\r\n", "a"),               // ignore short extensions
-        ];
-        for (encoded, expected) in tests.iter() {
-            let mut cursor = io::Cursor::new(encoded.as_bytes());
-            let mut decoder = HttpChunkedTransferReader::from_reader(&mut cursor, 50);
-            let mut output = vec![0u8; expected.len()];
-            decoder.read_exact(&mut output).unwrap();
+    fn parse_postconditions_preview<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let preview = HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::PostConditionsPreview(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            preview,
+        ))
+    }
 
-            assert_eq!(output, expected.as_bytes().to_vec());
+    fn parse_microblocks_unconfirmed<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        // NOTE: there will be no length prefix on this, but we won't ever get more than
+        // MAX_MICROBLOCKS_UNCONFIRMED microblocks
+        let mut microblocks = vec![];
+        let max_len = len_hint.unwrap_or(MAX_MESSAGE_LEN as usize) as u64;
+        let mut bound_reader = BoundReader::from_reader(fd, max_len);
+        loop {
+            let mblock: StacksMicroblock = match read_next(&mut bound_reader) {
+                Ok(mblock) => Ok(mblock),
+                Err(e) => match e {
+                    codec_error::ReadError(ref ioe) => match ioe.kind() {
+                        io::ErrorKind::UnexpectedEof => {
+                            // end of stream -- this is fine
+                            break;
+                        }
+                        _ => Err(e),
+                    },
+                    _ => Err(e),
+                },
+            }?;
+
+            microblocks.push(mblock);
+            if microblocks.len() == MAX_MICROBLOCKS_UNCONFIRMED {
+                break;
+            }
         }
+        Ok(HttpResponseType::Microblocks(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            microblocks,
+        ))
     }
 
-    #[test]
-    fn test_http_chunked_decode_multi() {
-        let tests = vec![
-            (vec_u8(vec!["1\r\na", "\r\n", "0\r\n\r\n"]), "a"),
-            (vec_u8(vec!["1\r\na\r", "\n0\r\n\r\n"]), "a"),
-            (vec_u8(vec!["1\r\na\r\n", "0\r\n\r", "\n"]), "a"),
-            (vec_u8(vec!["1\r\na\r\n0\r\n", "\r\n"]), "a"),
-            (vec_u8(vec!["1\r\na\r\n0\r", "\n\r\n"]), "a"),
-            (vec_u8(vec!["1\r\na\r\n0\r", "\n", "\r\n"]), "a"),
-            (vec_u8(vec!["1\r\na\r\n0\r", "\n\r", "\n"]), "a"),
-            (vec_u8(vec!["1\r\na\r\n0\r", "\n", "\r", "\n"]), "a"),
-            (
-                vec_u8(vec![
-                    "1", "\r", "\n", "a", "\r", "\n", "0", "\r", "\n", "\r", "\n",
-                ]),
-                "a",
-            ),
-            (
-                vec_u8(vec![
-                    "a\r",
-                    "\n",
-                    "aaaa",
-                    "aaaaa",
-                    "a",
-                    "\r\n",
-                    "a\r\n",
-                    "bbbbbbbbbb\r",
-                    "\na\r\nccc",
-                    "ccccccc",
-                    "\r",
-                    "\na\r",
-                    "\ndddddd",
-                    "dddd",
-                    "\r\n0\r",
-                    "\n",
-                    "\r",
-                    "\n",
-                ]),
-                "aaaaaaaaaabbbbbbbbbbccccccccccdddddddddd",
-            ),
-            (
-                vec_u8(vec![
-                    "a\r\naaaaaaaaaa",
-                    "\r",
-                    "\n",
-                    "a\r\nbbbbbbbbbb\r",
-                    "\n",
-                    "a\r\ncccccccccc\r",
-                    "\na\r\nddddd",
-                    "ddddd\r",
-                    "\n0\r",
-                    "\n\r",
-                    "\n",
-                ]),
-                "aaaaaaaaaabbbbbbbbbbccccccccccdddddddddd",
-            ),
-            (
-                vec_u8(vec![
-                    "1",
-                    "\r",
-                    "\n",
-                    "",
-                    "a",
-                    "\r",
-                    "\n",
-                    "2",
-                    "\r\n",
-                    "bb",
-                    "\r\n",
-                    "3\r\n",
-                    "ccc\r",
-                    "\n4\r\n",
-                    "dddd\r\n",
-                    "0\r\n\r\n",
-                ]),
-                "abbcccdddd",
-            ),
-        ];
-        for (encoded_vec, expected) in tests.iter() {
-            test_debug!("expect {:?}", &expected);
+    fn parse_transaction_unconfirmed<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let unconfirmed_status: UnconfirmedTransactionResponse =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
 
-            let mut output = vec![];
-            let mut cursor = SegmentReader::new((*encoded_vec).clone());
-            let mut decoder = HttpChunkedTransferReader::from_reader(&mut cursor, 50);
+        // tx payload must decode to a transaction
+        let tx_bytes = hex_bytes(&unconfirmed_status.tx).map_err(|_| {
+            net_error::DeserializeError("Unconfirmed transaction is not hex-encoded".to_string())
+        })?;
+        let _ = StacksTransaction::consensus_deserialize(&mut &tx_bytes[..]).map_err(|_| {
+            net_error::DeserializeError(
+                "Unconfirmed transaction is not a well-formed Stacks transaction".to_string(),
+            )
+        })?;
 
-            for encoded in encoded_vec.iter() {
-                let mut tmp = vec![0u8; encoded.len()];
-                let nr = decoder.read(&mut tmp).unwrap();
+        Ok(HttpResponseType::UnconfirmedTransaction(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            unconfirmed_status,
+        ))
+    }
 
-                output.extend_from_slice(&tmp[0..nr]);
+    fn parse_txid<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        // a bare txid, or a `TransactionAcceptedData` if the request asked for `result=1`
+        let body: serde_json::Value = HttpResponseType::parse_json(preamble, fd, len_hint, 128)?;
+        let (txid_hex, accepted) = match body {
+            serde_json::Value::String(txid_hex) => (txid_hex, None),
+            body => {
+                let data: TransactionAcceptedData = serde_json::from_value(body).map_err(|_e| {
+                    net_error::DeserializeError("Failed to parse transaction result".to_string())
+                })?;
+                (data.txid, Some(data.accepted))
             }
+        };
+        if txid_hex.len() != 64 {
+            return Err(net_error::DeserializeError(
+                "Invalid txid: expected 64 bytes".to_string(),
+            ));
+        }
 
-            assert_eq!(output, expected.as_bytes().to_vec());
+        let txid = Txid::from_hex(&txid_hex)
+            .map_err(|_e| net_error::DeserializeError("Failed to decode txid hex".to_string()))?;
+        let md = HttpResponseMetadata::from_preamble(request_version, preamble);
+        match accepted {
+            Some(accepted) => Ok(HttpResponseType::TransactionAccepted(md, txid, accepted)),
+            None => Ok(HttpResponseType::TransactionID(md, txid)),
         }
     }
 
-    #[test]
-    fn test_http_chunked_decode_err() {
-        let tests = vec![
-            (
-                "1; reallyreallyreallyreallylongextension;\r\na\r\n0\r\n\r\n",
-                1,
-                "too long",
-            ),
-            ("ffffffff\r\n", 1, "too big"),
-            ("nope\r\n", 1, "could not parse"),
-            ("1\na\r\n0\r\n\r\n", 1, "could not parse"),
-            ("a\r\naaaaaaaaaa", 11, "failed to fill whole buffer"),
-            ("1\r\nab\r\n0\r\n\r\n", 2, "Invalid chunk trailer"),
-            (
-                "15\r\naaaaaaaaaabbbbbbbbbbb\r\n0\r\n\r\n",
-                21,
-                "HTTP body exceeds maximum expected length",
-            ),
-            (
-                "7\r\naaaaaaa\r\n8\r\nbbbbbbbb\r\n6\r\ncccccc\r\n0\r\n\r\n",
-                21,
-                "HTTP body exceeds maximum expected length",
-            ),
-        ];
-        for (encoded, expected_len, expected) in tests.iter() {
-            test_debug!("expect '{}'", expected);
-            let mut cursor = io::Cursor::new(encoded.as_bytes());
-            let mut decoder = HttpChunkedTransferReader::from_reader(&mut cursor, 20);
-            let mut output = vec![0u8; *expected_len as usize];
-
-            let err = decoder.read_exact(&mut output).unwrap_err();
-            let errstr = format!("{:?}", &err);
+    fn parse_get_attachment<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let res: GetAttachmentResponse =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
 
-            assert!(
-                errstr.find(expected).is_some(),
-                "Expected '{}' in '{:?}'",
-                expected,
-                errstr
-            );
-        }
+        Ok(HttpResponseType::GetAttachment(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            res,
+        ))
     }
 
-    #[test]
-    fn test_http_chunked_encode_decode_roundtrip() {
-        let mut rng = rand::thread_rng();
-        for i in 0..100 {
-            let mut data = vec![0u8; 256];
-            rng.fill_bytes(&mut data);
+    fn parse_get_attachments_inv<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let res: GetAttachmentsInvResponse =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
 
-            let mut encoded_data = vec![];
-            {
-                let mut write_state = HttpChunkedTransferWriterState::new(i + 1);
-                let mut encoder = HttpChunkedTransferWriter::from_writer_state(
-                    &mut encoded_data,
-                    &mut write_state,
-                );
-                encoder.write_all(&data).unwrap();
-                encoder.flush().unwrap();
-            }
+        Ok(HttpResponseType::GetAttachmentsInv(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            res,
+        ))
+    }
 
-            let mut decoded_data = vec![0u8; 256];
-            {
-                let mut cursor = io::Cursor::new(&encoded_data);
-                let mut decoder = HttpChunkedTransferReader::from_reader(&mut cursor, 256);
-                decoder.read_exact(&mut decoded_data).unwrap();
-            }
+    fn parse_stacks_block_accepted<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let stacks_block_accepted: StacksBlockAcceptedData =
+            HttpResponseType::parse_json(preamble, fd, len_hint, 128)?;
+        Ok(HttpResponseType::StacksBlockAccepted(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            stacks_block_accepted.stacks_block_id,
+            stacks_block_accepted.accepted,
+        ))
+    }
 
-            assert_eq!(data, decoded_data);
+    fn parse_microblock_hash<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let mblock_hex: String = HttpResponseType::parse_json(preamble, fd, len_hint, 66)?;
+        if mblock_hex.len() != 64 {
+            return Err(net_error::DeserializeError(
+                "Invalid microblock hash: expected 64 bytes".to_string(),
+            ));
         }
-    }
 
-    #[test]
-    fn test_parse_reserved_header() {
-        let tests = vec![
-            (
-                "Content-Length",
-                "123",
-                Some(HttpReservedHeader::ContentLength(123)),
-            ),
-            (
-                "Content-Type",
-                "text/plain",
-                Some(HttpReservedHeader::ContentType(HttpContentType::Text)),
-            ),
-            (
-                "Content-Type",
-                "application/octet-stream",
-                Some(HttpReservedHeader::ContentType(HttpContentType::Bytes)),
-            ),
-            (
-                "Content-Type",
-                "application/json",
-                Some(HttpReservedHeader::ContentType(HttpContentType::JSON)),
-            ),
-            (
-                "X-Request-Id",
-                "123",
-                Some(HttpReservedHeader::XRequestID(123)),
-            ),
-            (
-                "Host",
-                "foo:123",
-                Some(HttpReservedHeader::Host(PeerHost::DNS(
-                    "foo".to_string(),
-                    123,
-                ))),
-            ),
-            (
-                "Host",
-                "1.2.3.4:123",
-                Some(HttpReservedHeader::Host(PeerHost::IP(
-                    PeerAddress([
-                        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff,
-                        0x01, 0x02, 0x03, 0x04,
-                    ]),
-                    123,
-                ))),
-            ),
-            // errors
-            ("Content-Length", "-1", None),
-            ("Content-Length", "asdf", None),
-            ("Content-Length", "4294967296", None),
-            ("Content-Type", "blargh", None),
-            ("X-Request-Id", "-1", None),
-            ("X-Request-Id", "asdf", None),
-            ("X-Request-Id", "4294967296", None),
-            ("Unrecognized", "header", None),
-        ];
+        let mblock_hash = BlockHeaderHash::from_hex(&mblock_hex).map_err(|_e| {
+            net_error::DeserializeError("Failed to decode microblock hash hex".to_string())
+        })?;
+        Ok(HttpResponseType::MicroblockHash(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            mblock_hash,
+        ))
+    }
 
-        for (key, value, expected_result) in tests {
-            let result = HttpReservedHeader::try_from_str(key, value);
-            assert_eq!(result, expected_result);
+    fn error_reason(code: u16) -> &'static str {
+        match code {
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            402 => "Payment Required",
+            403 => "Forbidden",
+            404 => "Not Found",
+            413 => "Payload Too Large",
+            500 => "Internal Server Error",
+            503 => "Service Temporarily Unavailable",
+            _ => "Error",
         }
     }
 
-    #[test]
-    fn test_parse_http_request_preamble_ok() {
-        let tests = vec![
-            ("GET /foo HTTP/1.1\r\nHost: localhost:6270\r\n\r\n",
-             HttpRequestPreamble::from_headers(HttpVersion::Http11, "GET".to_string(), "/foo".to_string(), "localhost".to_string(), 6270, true, vec![], vec![])),
-            ("POST asdf HTTP/1.1\r\nHost: core.blockstack.org\r\nFoo: Bar\r\n\r\n",
-             HttpRequestPreamble::from_headers(HttpVersion::Http11, "POST".to_string(), "asdf".to_string(), "core.blockstack.org".to_string(), 80, true, vec!["foo".to_string()], vec!["Bar".to_string()])),
-            ("POST asdf HTTP/1.1\r\nHost: core.blockstack.org\r\nFoo: Bar\r\n\r\n",
-             HttpRequestPreamble::from_headers(HttpVersion::Http11, "POST".to_string(), "asdf".to_string(), "core.blockstack.org".to_string(), 80, true, vec!["foo".to_string()], vec!["Bar".to_string()])),
-            ("GET /foo HTTP/1.1\r\nConnection: close\r\nHost: localhost:6270\r\n\r\n",
-             HttpRequestPreamble::from_headers(HttpVersion::Http11, "GET".to_string(), "/foo".to_string(), "localhost".to_string(), 6270, false, vec![], vec![])),
-            ("POST asdf HTTP/1.1\r\nHost: core.blockstack.org\r\nConnection: close\r\nFoo: Bar\r\n\r\n",
-             HttpRequestPreamble::from_headers(HttpVersion::Http11, "POST".to_string(), "asdf".to_string(), "core.blockstack.org".to_string(), 80, false, vec!["foo".to_string()], vec!["Bar".to_string()])),
-            ("POST asdf HTTP/1.1\r\nHost: core.blockstack.org\r\nFoo: Bar\r\nConnection: close\r\n\r\n",
-             HttpRequestPreamble::from_headers(HttpVersion::Http11, "POST".to_string(), "asdf".to_string(), "core.blockstack.org".to_string(), 80, false, vec!["foo".to_string()], vec!["Bar".to_string()])) 
-        ];
+    fn error_response<W: Write>(
+        &self,
+        fd: &mut W,
+        code: u16,
+        message: &str,
+    ) -> Result<(), net_error> {
+        let md = self.metadata();
+        HttpResponsePreamble::new_serialized(
+            fd,
+            code,
+            HttpResponseType::error_reason(code),
+            Some(message.len() as u32),
+            &HttpContentType::Text,
+            md.request_id,
+            |ref mut fd| keep_alive_headers(fd, md),
+        )?;
+        fd.write_all(message.as_bytes())
+            .map_err(net_error::WriteError)?;
+        Ok(())
+    }
 
-        for (data, request) in tests.iter() {
-            let req = HttpRequestPreamble::consensus_deserialize(&mut data.as_bytes());
-            assert!(req.is_ok(), format!("{:?}", &req));
-            assert_eq!(req.unwrap(), *request);
+    pub fn metadata(&self) -> &HttpResponseMetadata {
+        match *self {
+            HttpResponseType::PeerInfo(ref md, _) => md,
+            HttpResponseType::PoxInfo(ref md, _) => md,
+            HttpResponseType::Neighbors(ref md, _) => md,
+            HttpResponseType::Block(ref md, _) => md,
+            HttpResponseType::BlockStream(ref md) => md,
+            HttpResponseType::Microblocks(ref md, _) => md,
+            HttpResponseType::MicroblockStream(ref md) => md,
+            HttpResponseType::TransactionID(ref md, _) => md,
+            HttpResponseType::TransactionAccepted(ref md, ..) => md,
+            HttpResponseType::StacksBlockAccepted(ref md, ..) => md,
+            HttpResponseType::MicroblockHash(ref md, _) => md,
+            HttpResponseType::TokenTransferCost(ref md, _) => md,
+            HttpResponseType::GetMapEntry(ref md, _) => md,
+            HttpResponseType::GetAccount(ref md, _) => md,
+            HttpResponseType::GetAccountTransactions(ref md, _) => md,
+            HttpResponseType::GetTransactionReceipt(ref md, _) => md,
+            HttpResponseType::GetSponsorship(ref md, _) => md,
+            HttpResponseType::GetAccountBalance(ref md, _) => md,
+            HttpResponseType::GetStackingStatus(ref md, _) => md,
+            HttpResponseType::GetDelegations(ref md, _) => md,
+            HttpResponseType::GetRewardSet(ref md, _) => md,
+            HttpResponseType::GetSortition(ref md, _) => md,
+            HttpResponseType::GetConsensusSchedule(ref md, _) => md,
+            HttpResponseType::GetMicroblockEquivocations(ref md, _) => md,
+            HttpResponseType::AdminToggles(ref md, _) => md,
+            HttpResponseType::BurnOpSubmitted(ref md, _) => md,
+            HttpResponseType::MinerWallet(ref md, _) => md,
+            HttpResponseType::ClarityProfiles(ref md, _) => md,
+            HttpResponseType::BlockProposalValidated(ref md, _) => md,
+            HttpResponseType::GetContractABI(ref md, _) => md,
+            HttpResponseType::GetContractEvents(ref md, _) => md,
+            HttpResponseType::GetContractSrc(ref md, _) => md,
+            HttpResponseType::GetIsTraitImplemented(ref md, _) => md,
+            HttpResponseType::CallReadOnlyFunction(ref md, _) => md,
+            HttpResponseType::SimulateContractCall(ref md, _) => md,
+            HttpResponseType::PostConditionsPreview(ref md, _) => md,
+            HttpResponseType::UnconfirmedTransaction(ref md, _) => md,
+            HttpResponseType::GetAttachment(ref md, _) => md,
+            HttpResponseType::GetAttachmentsInv(ref md, _) => md,
+            HttpResponseType::OptionsPreflight(ref md) => md,
+            // errors
+            HttpResponseType::BadRequestJSON(ref md, _) => md,
+            HttpResponseType::BadRequest(ref md, _) => md,
+            HttpResponseType::Unauthorized(ref md, _) => md,
+            HttpResponseType::PaymentRequired(ref md, _) => md,
+            HttpResponseType::Forbidden(ref md, _) => md,
+            HttpResponseType::NotFound(ref md, _) => md,
+            HttpResponseType::ServerError(ref md, _) => md,
+            HttpResponseType::ServiceUnavailable(ref md, _) => md,
+            HttpResponseType::Error(ref md, _, _) => md,
+        }
+    }
 
-            let sreq = StacksHttpPreamble::consensus_deserialize(&mut data.as_bytes());
-            assert!(sreq.is_ok(), format!("{:?}", &sreq));
-            assert_eq!(
-                sreq.unwrap(),
-                StacksHttpPreamble::Request((*request).clone())
-            );
+    pub fn metadata_mut(&mut self) -> &mut HttpResponseMetadata {
+        match *self {
+            HttpResponseType::PeerInfo(ref mut md, _) => md,
+            HttpResponseType::PoxInfo(ref mut md, _) => md,
+            HttpResponseType::Neighbors(ref mut md, _) => md,
+            HttpResponseType::Block(ref mut md, _) => md,
+            HttpResponseType::BlockStream(ref mut md) => md,
+            HttpResponseType::Microblocks(ref mut md, _) => md,
+            HttpResponseType::MicroblockStream(ref mut md) => md,
+            HttpResponseType::TransactionID(ref mut md, _) => md,
+            HttpResponseType::TransactionAccepted(ref mut md, ..) => md,
+            HttpResponseType::StacksBlockAccepted(ref mut md, ..) => md,
+            HttpResponseType::MicroblockHash(ref mut md, _) => md,
+            HttpResponseType::TokenTransferCost(ref mut md, _) => md,
+            HttpResponseType::GetMapEntry(ref mut md, _) => md,
+            HttpResponseType::GetAccount(ref mut md, _) => md,
+            HttpResponseType::GetAccountTransactions(ref mut md, _) => md,
+            HttpResponseType::GetTransactionReceipt(ref mut md, _) => md,
+            HttpResponseType::GetSponsorship(ref mut md, _) => md,
+            HttpResponseType::GetAccountBalance(ref mut md, _) => md,
+            HttpResponseType::GetStackingStatus(ref mut md, _) => md,
+            HttpResponseType::GetDelegations(ref mut md, _) => md,
+            HttpResponseType::GetRewardSet(ref mut md, _) => md,
+            HttpResponseType::GetSortition(ref mut md, _) => md,
+            HttpResponseType::GetConsensusSchedule(ref mut md, _) => md,
+            HttpResponseType::GetMicroblockEquivocations(ref mut md, _) => md,
+            HttpResponseType::AdminToggles(ref mut md, _) => md,
+            HttpResponseType::BurnOpSubmitted(ref mut md, _) => md,
+            HttpResponseType::MinerWallet(ref mut md, _) => md,
+            HttpResponseType::ClarityProfiles(ref mut md, _) => md,
+            HttpResponseType::BlockProposalValidated(ref mut md, _) => md,
+            HttpResponseType::GetContractABI(ref mut md, _) => md,
+            HttpResponseType::GetContractEvents(ref mut md, _) => md,
+            HttpResponseType::GetContractSrc(ref mut md, _) => md,
+            HttpResponseType::GetIsTraitImplemented(ref mut md, _) => md,
+            HttpResponseType::CallReadOnlyFunction(ref mut md, _) => md,
+            HttpResponseType::SimulateContractCall(ref mut md, _) => md,
+            HttpResponseType::PostConditionsPreview(ref mut md, _) => md,
+            HttpResponseType::UnconfirmedTransaction(ref mut md, _) => md,
+            HttpResponseType::GetAttachment(ref mut md, _) => md,
+            HttpResponseType::GetAttachmentsInv(ref mut md, _) => md,
+            HttpResponseType::OptionsPreflight(ref mut md) => md,
+            // errors
+            HttpResponseType::BadRequestJSON(ref mut md, _) => md,
+            HttpResponseType::BadRequest(ref mut md, _) => md,
+            HttpResponseType::Unauthorized(ref mut md, _) => md,
+            HttpResponseType::PaymentRequired(ref mut md, _) => md,
+            HttpResponseType::Forbidden(ref mut md, _) => md,
+            HttpResponseType::NotFound(ref mut md, _) => md,
+            HttpResponseType::ServerError(ref mut md, _) => md,
+            HttpResponseType::ServiceUnavailable(ref mut md, _) => md,
+            HttpResponseType::Error(ref mut md, _, _) => md,
         }
     }
 
-    #[test]
-    fn test_parse_http_request_options() {
-        let data = "OPTIONS /foo HTTP/1.1\r\nHost: localhost:6270\r\n\r\n";
-        let req = HttpRequestPreamble::consensus_deserialize(&mut data.as_bytes());
-        let preamble = HttpRequestPreamble::from_headers(
-            HttpVersion::Http11,
-            "OPTIONS".to_string(),
-            "/foo".to_string(),
-            "localhost".to_string(),
-            6270,
-            true,
-            vec![],
-            vec![],
-        );
-        assert_eq!(req.unwrap(), preamble);
+    fn send_bytestream<W: Write, T: StacksMessageCodec>(
+        protocol: &mut StacksHttp,
+        md: &HttpResponseMetadata,
+        fd: &mut W,
+        message: &T,
+    ) -> Result<(), codec_error> {
+        if md.content_length.is_some() {
+            // have explicit content-length, so we can send as-is
+            write_next(fd, message)
+        } else {
+            // no content-length, so send as chunk-encoded
+            let mut write_state = HttpChunkedTransferWriterState::new(protocol.chunk_size as usize);
+            let mut encoder = HttpChunkedTransferWriter::from_writer_state(fd, &mut write_state);
+            write_next(&mut encoder, message)?;
+            encoder.flush().map_err(codec_error::WriteError)?;
+            Ok(())
+        }
     }
 
-    #[test]
-    fn test_parse_http_request_preamble_case_ok() {
-        let tests = vec![
-            ("GET /foo HTTP/1.1\r\nhOsT: localhost:6270\r\n\r\n",
-             HttpRequestPreamble::from_headers(HttpVersion::Http11, "GET".to_string(), "/foo".to_string(), "localhost".to_string(), 6270, true, vec![], vec![])),
-            ("GET /foo HTTP/1.1\r\ncOnNeCtIoN: cLoSe\r\nhOsT: localhost:6270\r\n\r\n",
-             HttpRequestPreamble::from_headers(HttpVersion::Http11, "GET".to_string(), "/foo".to_string(), "localhost".to_string(), 6270, false, vec![], vec![])),
-            ("POST asdf HTTP/1.1\r\nhOsT: core.blockstack.org\r\nCOnNeCtIoN: kEeP-aLiVE\r\nFoo: Bar\r\n\r\n",
-             HttpRequestPreamble::from_headers(HttpVersion::Http11, "POST".to_string(), "asdf".to_string(), "core.blockstack.org".to_string(), 80, true, vec!["foo".to_string()], vec!["Bar".to_string()])),
-        ];
-
-        for (data, request) in tests.iter() {
-            let req = HttpRequestPreamble::consensus_deserialize(&mut data.as_bytes());
-            assert!(req.is_ok(), format!("{:?}", &req));
-            assert_eq!(req.unwrap(), *request);
-
-            let sreq = StacksHttpPreamble::consensus_deserialize(&mut data.as_bytes());
-            assert!(sreq.is_ok(), format!("{:?}", &sreq));
-            assert_eq!(
-                sreq.unwrap(),
-                StacksHttpPreamble::Request((*request).clone())
-            );
+    fn send_text<W: Write>(
+        protocol: &mut StacksHttp,
+        md: &HttpResponseMetadata,
+        fd: &mut W,
+        text: &[u8],
+    ) -> Result<(), net_error> {
+        if md.content_length.is_some() {
+            // have explicit content-length, so we can send as-is
+            fd.write_all(text).map_err(net_error::WriteError)
+        } else {
+            // no content-length, so send as chunk-encoded
+            let mut write_state = HttpChunkedTransferWriterState::new(protocol.chunk_size as usize);
+            let mut encoder = HttpChunkedTransferWriter::from_writer_state(fd, &mut write_state);
+            encoder.write_all(text).map_err(net_error::WriteError)?;
+            encoder.flush().map_err(net_error::WriteError)?;
+            Ok(())
         }
     }
 
-    #[test]
-    fn test_parse_http_request_preamble_err() {
-        let tests = vec![
-            ("GET /foo HTTP/1.1\r\n", "failed to fill whole buffer"),
-            ("GET /foo HTTP/1.1\r\n\r\n", "Missing Host header"),
-            (
-                "GET /foo HTTP/1.1\r\nFoo: Bar\r\n\r\n",
-                "Missing Host header",
-            ),
-            ("GET /foo HTTP/\r\n\r\n", "Failed to parse HTTP request"),
-            ("GET /foo HTTP/1.1\r\nHost:", "failed to fill whole buffer"),
-            (
-                "GET /foo HTTP/1.1\r\nHost: foo:80\r\nHost: bar:80\r\n\r\n",
-                "duplicate header",
-            ),
-            (
-                "GET /foo HTTP/1.1\r\nHost: localhost:6270\r\nfoo: \u{2764}\r\n\r\n",
-                "header value is not ASCII-US",
-            ),
-            (
-                "Get /foo HTTP/1.1\r\nHost: localhost:666666\r\n\r\n",
-                "Missing Host header",
-            ),
-            (
-                "GET /foo HTTP/1.1\r\nHost: localhost:8080\r\nConnection: foo\r\n\r\n",
-                "invalid Connection: header",
-            ),
-        ];
-
-        for (data, errstr) in tests.iter() {
-            let res = HttpRequestPreamble::consensus_deserialize(&mut data.as_bytes());
-            test_debug!("Expect '{}'", errstr);
-            let expected_errstr = format!("{:?}", &res);
-            assert!(res.is_err(), expected_errstr);
-            assert!(
-                res.unwrap_err().to_string().find(errstr).is_some(),
-                expected_errstr
-            );
+    fn send_json<W: Write, T: serde::ser::Serialize>(
+        protocol: &mut StacksHttp,
+        md: &HttpResponseMetadata,
+        fd: &mut W,
+        message: &T,
+    ) -> Result<(), net_error> {
+        if md.content_length.is_some() {
+            // have explicit content-length, so we can send as-is
+            serde_json::to_writer(fd, message)
+                .map_err(|e| net_error::SerializeError(format!("Failed to send as JSON: {:?}", &e)))
+        } else {
+            // no content-length, so send as chunk-encoded
+            let mut write_state = HttpChunkedTransferWriterState::new(protocol.chunk_size as usize);
+            let mut encoder = HttpChunkedTransferWriter::from_writer_state(fd, &mut write_state);
+            serde_json::to_writer(&mut encoder, message).map_err(|e| {
+                net_error::SerializeError(format!("Failed to send as chunk-encoded JSON: {:?}", &e))
+            })?;
+            encoder.flush().map_err(net_error::WriteError)?;
+            Ok(())
         }
     }
 
-    #[test]
-    fn test_parse_stacks_http_preamble_request_err() {
-        let tests = vec![
-            (
-                "GET /foo HTTP/1.1\r\n",
-                "Not enough bytes to form a HTTP request or response",
-            ),
-            (
-                "GET /foo HTTP/1.1\r\n\r\n",
-                "Failed to decode HTTP request or HTTP response",
-            ),
-            (
-                "GET /foo HTTP/1.1\r\nFoo: Bar\r\n\r\n",
-                "Failed to decode HTTP request or HTTP response",
-            ),
-            (
-                "GET /foo HTTP/\r\n\r\n",
-                "Failed to decode HTTP request or HTTP response",
-            ),
-            (
-                "GET /foo HTTP/1.1\r\nHost:",
-                "Not enough bytes to form a HTTP request or response",
-            ),
-            (
-                "GET /foo HTTP/1.1\r\nHost: foo:80\r\nHost: bar:80\r\n\r\n",
-                "Failed to decode HTTP request or HTTP response",
-            ),
-            (
-                "GET /foo HTTP/1.1\r\nHost: localhost:6270\r\nfoo: \u{2764}\r\n\r\n",
-                "Failed to decode HTTP request or HTTP response",
-            ),
-            (
-                "Get /foo HTTP/1.1\r\nHost: localhost:666666\r\n\r\n",
-                "Failed to decode HTTP request or HTTP response",
-            ),
-            (
-                "GET /foo HTTP/1.1\r\nHost: localhost:8080\r\nConnection: foo\r\n\r\n",
-                "Failed to decode HTTP request or HTTP response",
-            ),
-        ];
+    pub fn send<W: Write>(&self, protocol: &mut StacksHttp, fd: &mut W) -> Result<(), net_error> {
+        // a response that is ready too late is not worth serializing.  (Streams check their
+        // deadlines before they begin, since their data follows this preamble.)
+        match *self {
+            HttpResponseType::BlockStream(..) | HttpResponseType::MicroblockStream(..) => {}
+            _ => {
+                if let Some(response) = protocol.take_expired_request_deadline(self.metadata()) {
+                    return response.send(protocol, fd);
+                }
+            }
+        }
+        if protocol.max_response_size == 0 || self.is_consensus_bounded() {
+            return self.send_response(protocol, fd);
+        }
 
-        for (data, errstr) in tests.iter() {
-            let sres = StacksHttpPreamble::consensus_deserialize(&mut data.as_bytes());
-            let expected_serrstr = format!("{:?}", &sres);
-            test_debug!("Expect '{}'", errstr);
-            assert!(sres.is_err(), expected_serrstr);
-            assert!(
-                sres.unwrap_err().to_string().find(errstr).is_some(),
-                expected_serrstr
-            );
+        // serialize the response before any of it is sent, so that one that turns out to be too
+        // big can be answered with a 413 instead.  Handlers that load data of unbounded size check
+        // it against the limit before building their responses, so this only bounds their
+        // serialized forms.
+        let mut buffer = BoundedResponseBuffer::new(protocol.max_response_size);
+        match self.send_response(protocol, &mut buffer) {
+            Ok(()) => fd.write_all(&buffer.buf).map_err(net_error::WriteError),
+            Err(_) if buffer.overflowed => protocol
+                .check_response_size(self.metadata(), u64::MAX)
+                .expect("BUG: overflowed a response buffer without a size limit")
+                .send_response(protocol, fd),
+            Err(e) => Err(e),
         }
     }
 
-    #[test]
-    fn test_http_request_preamble_headers() {
-        let mut req = HttpRequestPreamble::new(
-            HttpVersion::Http11,
-            "GET".to_string(),
-            "/foo".to_string(),
-            "localhost".to_string(),
-            6270,
-            true,
-        );
-        let req_11 = HttpRequestPreamble::new(
-            HttpVersion::Http11,
-            "GET".to_string(),
-            "/foo".to_string(),
-            "localhost".to_string(),
-            6270,
-            false,
-        );
-        let req_10 = HttpRequestPreamble::new(
-            HttpVersion::Http10,
-            "GET".to_string(),
-            "/foo".to_string(),
-            "localhost".to_string(),
-            6270,
-            false,
-        );
-
-        req.add_header("foo".to_string(), "bar".to_string());
-
-        assert_eq!(req.content_type, None);
-        req.set_content_type(HttpContentType::JSON);
-        assert_eq!(req.content_type, Some(HttpContentType::JSON));
-
-        req.add_header(
-            "content-type".to_string(),
-            "application/octet-stream".to_string(),
-        );
-        assert_eq!(req.content_type, Some(HttpContentType::Bytes));
-
-        let mut bytes = vec![];
-        req.consensus_serialize(&mut bytes).unwrap();
-        let txt = String::from_utf8(bytes).unwrap();
-
-        test_debug!("headers:\n{}", txt);
-
-        assert!(txt.find("HTTP/1.1").is_some(), "HTTP version is missing");
-        assert!(
-            txt.find("User-Agent: stacks/2.0\r\n").is_some(),
-            "User-Agnet header is missing"
-        );
-        assert!(
-            txt.find("Host: localhost:6270\r\n").is_some(),
-            "Host header is missing"
-        );
-        assert!(txt.find("foo: bar\r\n").is_some(), "foo header is missing");
-        assert!(
-            txt.find("Content-Type: application/octet-stream\r\n")
-                .is_some(),
-            "content-type is missing"
-        );
-        assert!(txt.find("Connection: ").is_none()); // not sent if keep_alive is true (for HTTP/1.1)
-
-        let mut bytes_10 = vec![];
-        req_10.consensus_serialize(&mut bytes_10).unwrap();
-        let txt_10 = String::from_utf8(bytes_10).unwrap();
-
-        assert!(txt_10.find("HTTP/1.0").is_some(), "HTTP version is missing");
-
-        let mut bytes_11 = vec![];
-        req_11.consensus_serialize(&mut bytes_11).unwrap();
-        let txt_11 = String::from_utf8(bytes_11).unwrap();
-
-        assert!(txt_11.find("HTTP/1.1").is_some(), "HTTP version is wrong");
-        assert!(
-            txt_11.find("Connection: close").is_some(),
-            "Explicit Connection: close is missing"
-        );
+    /// Blocks, microblocks and attachments are sized by consensus rules, not by what a client
+    /// asks for, so they are never refused for being bigger than `max_response_size`, nor
+    /// buffered.  The RPC server sends blocks and microblocks as `BlockStream` and
+    /// `MicroblockStream` responses, whose bodies are read from the chainstate a chunk at a time
+    /// as the connection drains; only their preambles are written here.
+    fn is_consensus_bounded(&self) -> bool {
+        match *self {
+            HttpResponseType::Block(..)
+            | HttpResponseType::BlockStream(..)
+            | HttpResponseType::Microblocks(..)
+            | HttpResponseType::MicroblockStream(..)
+            | HttpResponseType::GetAttachment(..) => true,
+            _ => false,
+        }
     }
 
-    #[test]
-    fn test_parse_http_response_preamble_ok() {
-        let tests = vec![
+    fn send_response<W: Write>(
+        &self,
+        protocol: &mut StacksHttp,
+        fd: &mut W,
+    ) -> Result<(), net_error> {
+        match *self {
+            HttpResponseType::GetAccount(ref md, ref account_data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, account_data)?;
+            }
+            HttpResponseType::GetAccountTransactions(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::GetTransactionReceipt(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::GetSponsorship(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::GetAccountBalance(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::GetStackingStatus(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::GetDelegations(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::GetRewardSet(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::GetSortition(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::GetConsensusSchedule(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::GetMicroblockEquivocations(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::AdminToggles(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::BurnOpSubmitted(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::MinerWallet(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::ClarityProfiles(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::BlockProposalValidated(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::GetContractABI(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::GetContractEvents(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::GetContractSrc(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::GetIsTraitImplemented(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::TokenTransferCost(ref md, ref cost) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, cost)?;
+            }
+            HttpResponseType::CallReadOnlyFunction(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::SimulateContractCall(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::PostConditionsPreview(ref md, ref data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::GetMapEntry(ref md, ref map_data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, map_data)?;
+            }
+            HttpResponseType::PeerInfo(ref md, ref peer_info) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, peer_info)?;
+            }
+            HttpResponseType::PoxInfo(ref md, ref pox_info) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, pox_info)?;
+            }
+            HttpResponseType::Neighbors(ref md, ref neighbor_data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, neighbor_data)?;
+            }
+            HttpResponseType::GetAttachment(ref md, ref zonefile_data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, zonefile_data)?;
+            }
+            HttpResponseType::GetAttachmentsInv(ref md, ref zonefile_data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, zonefile_data)?;
+            }
+            HttpResponseType::Block(ref md, ref block) => {
+                HttpResponsePreamble::new_serialized(
+                    fd,
+                    200,
+                    "OK",
+                    md.content_length.clone(),
+                    &HttpContentType::Bytes,
+                    md.request_id,
+                    |ref mut fd| ok_headers(fd, md),
+                )?;
+                HttpResponseType::send_bytestream(protocol, md, fd, block)?;
+            }
+            HttpResponseType::BlockStream(ref md) => {
+                // only send the preamble.  The caller will need to figure out how to send along
+                // the block data itself.
+                HttpResponsePreamble::new_serialized(
+                    fd,
+                    200,
+                    "OK",
+                    None,
+                    &HttpContentType::Bytes,
+                    md.request_id,
+                    |ref mut fd| ok_headers(fd, md),
+                )?;
+            }
+            HttpResponseType::Microblocks(ref md, ref microblocks) => {
+                HttpResponsePreamble::new_serialized(
+                    fd,
+                    200,
+                    "OK",
+                    md.content_length.clone(),
+                    &HttpContentType::Bytes,
+                    md.request_id,
+                    |ref mut fd| ok_headers(fd, md),
+                )?;
+                HttpResponseType::send_bytestream(protocol, md, fd, microblocks)?;
+            }
+            HttpResponseType::MicroblockStream(ref md) => {
+                // only send the preamble.  The caller will need to figure out how to send along
+                // the microblock data itself.
+                HttpResponsePreamble::new_serialized(
+                    fd,
+                    200,
+                    "OK",
+                    None,
+                    &HttpContentType::Bytes,
+                    md.request_id,
+                    |ref mut fd| ok_headers(fd, md),
+                )?;
+            }
+            HttpResponseType::TransactionID(ref md, ref txid) => {
+                let txid_bytes = txid.to_hex();
+                HttpResponsePreamble::new_serialized(
+                    fd,
+                    200,
+                    "OK",
+                    md.content_length.clone(),
+                    &HttpContentType::JSON,
+                    md.request_id,
+                    |ref mut fd| ok_headers(fd, md),
+                )?;
+                HttpResponseType::send_json(protocol, md, fd, &txid_bytes)?;
+            }
+            HttpResponseType::TransactionAccepted(ref md, ref txid, ref accepted) => {
+                let accepted_data = TransactionAcceptedData {
+                    txid: txid.to_hex(),
+                    accepted: *accepted,
+                };
+                HttpResponsePreamble::new_serialized(
+                    fd,
+                    200,
+                    "OK",
+                    md.content_length.clone(),
+                    &HttpContentType::JSON,
+                    md.request_id,
+                    |ref mut fd| ok_headers(fd, md),
+                )?;
+                HttpResponseType::send_json(protocol, md, fd, &accepted_data)?;
+            }
+            HttpResponseType::StacksBlockAccepted(ref md, ref stacks_block_id, ref accepted) => {
+                let accepted_data = StacksBlockAcceptedData {
+                    stacks_block_id: stacks_block_id.clone(),
+                    accepted: *accepted,
+                };
+                HttpResponsePreamble::new_serialized(
+                    fd,
+                    200,
+                    "OK",
+                    md.content_length.clone(),
+                    &HttpContentType::JSON,
+                    md.request_id,
+                    |ref mut fd| ok_headers(fd, md),
+                )?;
+                HttpResponseType::send_json(protocol, md, fd, &accepted_data)?;
+            }
+            HttpResponseType::MicroblockHash(ref md, ref mblock_hash) => {
+                let mblock_bytes = mblock_hash.to_hex();
+                HttpResponsePreamble::new_serialized(
+                    fd,
+                    200,
+                    "OK",
+                    md.content_length.clone(),
+                    &HttpContentType::JSON,
+                    md.request_id,
+                    |ref mut fd| ok_headers(fd, md),
+                )?;
+                HttpResponseType::send_json(protocol, md, fd, &mblock_bytes)?;
+            }
+            HttpResponseType::UnconfirmedTransaction(ref md, ref unconfirmed_status) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, unconfirmed_status)?;
+            }
+            HttpResponseType::OptionsPreflight(ref md) => {
+                HttpResponsePreamble::new_serialized(
+                    fd,
+                    200,
+                    "OK",
+                    None,
+                    &HttpContentType::Text,
+                    md.request_id,
+                    |ref mut fd| ok_headers(fd, md),
+                )?;
+                HttpResponseType::send_text(protocol, md, fd, "".as_bytes())?;
+            }
+            HttpResponseType::BadRequestJSON(ref md, ref data) => {
+                HttpResponsePreamble::new_serialized(
+                    fd,
+                    400,
+                    HttpResponseType::error_reason(400),
+                    md.content_length.clone(),
+                    &HttpContentType::JSON,
+                    md.request_id,
+                    |ref mut fd| keep_alive_headers(fd, md),
+                )?;
+                HttpResponseType::send_json(protocol, md, fd, data)?;
+            }
+            HttpResponseType::BadRequest(_, ref msg) => self.error_response(fd, 400, msg)?,
+            HttpResponseType::Unauthorized(_, ref msg) => self.error_response(fd, 401, msg)?,
+            HttpResponseType::PaymentRequired(_, ref msg) => self.error_response(fd, 402, msg)?,
+            HttpResponseType::Forbidden(_, ref msg) => self.error_response(fd, 403, msg)?,
+            HttpResponseType::NotFound(_, ref msg) => self.error_response(fd, 404, msg)?,
+            HttpResponseType::ServerError(_, ref msg) => self.error_response(fd, 500, msg)?,
+            HttpResponseType::ServiceUnavailable(_, ref msg) => {
+                self.error_response(fd, 503, msg)?
+            }
+            HttpResponseType::Error(_, ref error_code, ref msg) => {
+                self.error_response(fd, *error_code, msg)?
+            }
+        };
+        Ok(())
+    }
+}
+
+impl StacksMessageCodec for StacksHttpPreamble {
+    fn consensus_serialize<W: Write>(&self, fd: &mut W) -> Result<(), codec_error> {
+        match *self {
+            StacksHttpPreamble::Request(ref req) => req.consensus_serialize(fd),
+            StacksHttpPreamble::Response(ref res) => res.consensus_serialize(fd),
+        }
+    }
+
+    fn consensus_deserialize<R: Read>(fd: &mut R) -> Result<StacksHttpPreamble, codec_error> {
+        let mut retry_fd = RetryReader::new(fd);
+
+        // the byte stream can decode to a http request or a http response, but not both.
+        match HttpRequestPreamble::consensus_deserialize(&mut retry_fd) {
+            Ok(request) => Ok(StacksHttpPreamble::Request(request)),
+            Err(e_request) => {
+                // maybe a http response?
+                retry_fd.set_position(0);
+                match HttpResponsePreamble::consensus_deserialize(&mut retry_fd) {
+                    Ok(response) => Ok(StacksHttpPreamble::Response(response)),
+                    Err(e) => {
+                        // underflow?
+                        match (e_request, e) {
+                            (codec_error::ReadError(ref ioe1), codec_error::ReadError(ref ioe2)) => {
+                                if ioe1.kind() == io::ErrorKind::UnexpectedEof && ioe2.kind() == io::ErrorKind::UnexpectedEof {
+                                    // out of bytes
+                                    Err(codec_error::UnderflowError("Not enough bytes to form a HTTP request or response".to_string()))
+                                }
+                                else {
+                                    Err(codec_error::DeserializeError(format!("Neither a HTTP request ({:?}) or HTTP response ({:?})", ioe1, ioe2)))
+                                }
+                            },
+                            (e_req, e_res) => Err(codec_error::DeserializeError(format!("Failed to decode HTTP request or HTTP response (request error: {:?}; response error: {:?})", &e_req, &e_res)))
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl MessageSequence for StacksHttpMessage {
+    fn request_id(&self) -> u32 {
+        // there is at most one in-flight HTTP request, as far as a Connection<P> is concerned
+        HTTP_REQUEST_ID_RESERVED
+    }
+
+    fn get_message_name(&self) -> &'static str {
+        match *self {
+            StacksHttpMessage::Request(ref req) => match req {
+                HttpRequestType::GetInfo(_) => "HTTP(GetInfo)",
+                HttpRequestType::GetPoxInfo(_, _) => "HTTP(GetPoxInfo)",
+                HttpRequestType::GetNeighbors(_) => "HTTP(GetNeighbors)",
+                HttpRequestType::GetBlock(_, _) => "HTTP(GetBlock)",
+                HttpRequestType::GetMicroblocksIndexed(_, _) => "HTTP(GetMicroblocksIndexed)",
+                HttpRequestType::GetMicroblocksConfirmed(_, _) => "HTTP(GetMicroblocksConfirmed)",
+                HttpRequestType::GetMicroblocksUnconfirmed(_, _, _) => {
+                    "HTTP(GetMicroblocksUnconfirmed)"
+                }
+                HttpRequestType::GetTransactionUnconfirmed(_, _) => {
+                    "HTTP(GetTransactionUnconfirmed)"
+                }
+                HttpRequestType::PostTransaction(..) => "HTTP(PostTransaction)",
+                HttpRequestType::PostBlock(..) => "HTTP(PostBlock)",
+                HttpRequestType::PostMicroblock(..) => "HTTP(PostMicroblock)",
+                HttpRequestType::GetAccount(..) => "HTTP(GetAccount)",
+                HttpRequestType::GetAccountTransactions(..) => "HTTP(GetAccountTransactions)",
+                HttpRequestType::GetTransactionReceipt(..) => "HTTP(GetTransactionReceipt)",
+                HttpRequestType::GetSponsorship(..) => "HTTP(GetSponsorship)",
+                HttpRequestType::GetAccountBalance(..) => "HTTP(GetAccountBalance)",
+                HttpRequestType::GetStackingStatus(..) => "HTTP(GetStackingStatus)",
+                HttpRequestType::GetDelegations(..) => "HTTP(GetDelegations)",
+                HttpRequestType::GetRewardSet(..) => "HTTP(GetRewardSet)",
+                HttpRequestType::GetSortition(..) => "HTTP(GetSortition)",
+                HttpRequestType::GetConsensusSchedule(..) => "HTTP(GetConsensusSchedule)",
+                HttpRequestType::GetMicroblockEquivocations(..) => {
+                    "HTTP(GetMicroblockEquivocations)"
+                }
+                HttpRequestType::GetAdminToggles(..) => "HTTP(GetAdminToggles)",
+                HttpRequestType::PostAdminToggles(..) => "HTTP(PostAdminToggles)",
+                HttpRequestType::PostTransferStxOp(..) => "HTTP(PostTransferStxOp)",
+                HttpRequestType::GetMinerWallet(..) => "HTTP(GetMinerWallet)",
+                HttpRequestType::GetClarityProfiles(..) => "HTTP(GetClarityProfiles)",
+                HttpRequestType::PostBlockProposal(..) => "HTTP(PostBlockProposal)",
+                HttpRequestType::GetMapEntry(..) => "HTTP(GetMapEntry)",
+                HttpRequestType::GetTransferCost(_) => "HTTP(GetTransferCost)",
+                HttpRequestType::GetContractABI(..) => "HTTP(GetContractABI)",
+                HttpRequestType::GetContractEvents(..) => "HTTP(GetContractEvents)",
+                HttpRequestType::GetContractSrc(..) => "HTTP(GetContractSrc)",
+                HttpRequestType::GetIsTraitImplemented(..) => "HTTP(GetIsTraitImplemented)",
+                HttpRequestType::CallReadOnlyFunction(..) => "HTTP(CallReadOnlyFunction)",
+                HttpRequestType::SimulateContractCall(..) => "HTTP(SimulateContractCall)",
+                HttpRequestType::PreviewPostConditions(..) => "HTTP(PreviewPostConditions)",
+                HttpRequestType::GetAttachment(..) => "HTTP(GetAttachment)",
+                HttpRequestType::GetAttachmentsInv(..) => "HTTP(GetAttachmentsInv)",
+                HttpRequestType::OptionsPreflight(..) => "HTTP(OptionsPreflight)",
+                HttpRequestType::ClientError(..) => "HTTP(ClientError)",
+            },
+            StacksHttpMessage::Response(ref res) => match res {
+                HttpResponseType::TokenTransferCost(_, _) => "HTTP(TokenTransferCost)",
+                HttpResponseType::GetMapEntry(_, _) => "HTTP(GetMapEntry)",
+                HttpResponseType::GetAccount(_, _) => "HTTP(GetAccount)",
+                HttpResponseType::GetAccountTransactions(_, _) => "HTTP(GetAccountTransactions)",
+                HttpResponseType::GetTransactionReceipt(_, _) => "HTTP(GetTransactionReceipt)",
+                HttpResponseType::GetSponsorship(_, _) => "HTTP(GetSponsorship)",
+                HttpResponseType::GetAccountBalance(_, _) => "HTTP(GetAccountBalance)",
+                HttpResponseType::GetStackingStatus(_, _) => "HTTP(GetStackingStatus)",
+                HttpResponseType::GetDelegations(_, _) => "HTTP(GetDelegations)",
+                HttpResponseType::GetRewardSet(_, _) => "HTTP(GetRewardSet)",
+                HttpResponseType::GetSortition(_, _) => "HTTP(GetSortition)",
+                HttpResponseType::GetConsensusSchedule(_, _) => "HTTP(GetConsensusSchedule)",
+                HttpResponseType::GetMicroblockEquivocations(_, _) => {
+                    "HTTP(GetMicroblockEquivocations)"
+                }
+                HttpResponseType::AdminToggles(_, _) => "HTTP(AdminToggles)",
+                HttpResponseType::BurnOpSubmitted(_, _) => "HTTP(BurnOpSubmitted)",
+                HttpResponseType::MinerWallet(_, _) => "HTTP(MinerWallet)",
+                HttpResponseType::ClarityProfiles(_, _) => "HTTP(ClarityProfiles)",
+                HttpResponseType::BlockProposalValidated(_, _) => "HTTP(BlockProposalValidated)",
+                HttpResponseType::GetContractABI(..) => "HTTP(GetContractABI)",
+                HttpResponseType::GetContractEvents(..) => "HTTP(GetContractEvents)",
+                HttpResponseType::GetContractSrc(..) => "HTTP(GetContractSrc)",
+                HttpResponseType::GetIsTraitImplemented(..) => "HTTP(GetIsTraitImplemented)",
+                HttpResponseType::CallReadOnlyFunction(..) => "HTTP(CallReadOnlyFunction)",
+                HttpResponseType::SimulateContractCall(..) => "HTTP(SimulateContractCall)",
+                HttpResponseType::PostConditionsPreview(..) => "HTTP(PostConditionsPreview)",
+                HttpResponseType::GetAttachment(_, _) => "HTTP(GetAttachment)",
+                HttpResponseType::GetAttachmentsInv(_, _) => "HTTP(GetAttachmentsInv)",
+                HttpResponseType::PeerInfo(_, _) => "HTTP(PeerInfo)",
+                HttpResponseType::PoxInfo(_, _) => "HTTP(PeerInfo)",
+                HttpResponseType::Neighbors(_, _) => "HTTP(Neighbors)",
+                HttpResponseType::Block(_, _) => "HTTP(Block)",
+                HttpResponseType::BlockStream(_) => "HTTP(BlockStream)",
+                HttpResponseType::Microblocks(_, _) => "HTTP(Microblocks)",
+                HttpResponseType::MicroblockStream(_) => "HTTP(MicroblockStream)",
+                HttpResponseType::TransactionID(_, _) => "HTTP(Transaction)",
+                HttpResponseType::TransactionAccepted(..) => "HTTP(TransactionAccepted)",
+                HttpResponseType::StacksBlockAccepted(..) => "HTTP(StacksBlockAccepted)",
+                HttpResponseType::MicroblockHash(_, _) => "HTTP(MicroblockHash)",
+                HttpResponseType::UnconfirmedTransaction(_, _) => "HTTP(UnconfirmedTransaction)",
+                HttpResponseType::OptionsPreflight(_) => "HTTP(OptionsPreflight)",
+                HttpResponseType::BadRequestJSON(..) | HttpResponseType::BadRequest(..) => {
+                    "HTTP(400)"
+                }
+                HttpResponseType::Unauthorized(_, _) => "HTTP(401)",
+                HttpResponseType::PaymentRequired(_, _) => "HTTP(402)",
+                HttpResponseType::Forbidden(_, _) => "HTTP(403)",
+                HttpResponseType::NotFound(_, _) => "HTTP(404)",
+                HttpResponseType::ServerError(_, _) => "HTTP(500)",
+                HttpResponseType::ServiceUnavailable(_, _) => "HTTP(503)",
+                HttpResponseType::Error(_, _, _) => "HTTP(other)",
+            },
+        }
+    }
+}
+
+/// A partially-decoded, streamed HTTP message (response) being received.
+/// Internally used by StacksHttp to keep track of chunk-decoding state.
+#[derive(Debug, Clone, PartialEq)]
+struct HttpRecvStream {
+    state: HttpChunkedTransferReaderState,
+    data: Vec<u8>,
+    total_consumed: usize, // number of *encoded* bytes consumed
+}
+
+impl HttpRecvStream {
+    pub fn new(max_size: u64) -> HttpRecvStream {
+        HttpRecvStream {
+            state: HttpChunkedTransferReaderState::new(max_size),
+            data: vec![],
+            total_consumed: 0,
+        }
+    }
+
+    /// Feed data into our chunked transfer reader state.  If we finish reading a stream, return
+    /// the decoded bytes (as Some(Vec<u8>) and the total number of encoded bytes consumed).
+    /// Always returns the number of bytes consumed.
+    pub fn consume_data<R: Read>(
+        &mut self,
+        fd: &mut R,
+    ) -> Result<(Option<(Vec<u8>, usize)>, usize), net_error> {
+        let mut consumed = 0;
+        let mut blocked = false;
+        while !blocked {
+            let mut decoded_buf = vec![0u8; 8192];
+            let (read_pass, consumed_pass) = match self.state.do_read(fd, &mut decoded_buf) {
+                Ok((0, num_consumed)) => {
+                    trace!(
+                        "consume_data blocked on 0 decoded bytes ({} consumed)",
+                        num_consumed
+                    );
+                    blocked = true;
+                    (0, num_consumed)
+                }
+                Ok((num_read, num_consumed)) => (num_read, num_consumed),
+                Err(e) => match e.kind() {
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+                        trace!("consume_data blocked on read error");
+                        blocked = true;
+                        (0, 0)
+                    }
+                    _ => {
+                        return Err(net_error::ReadError(e));
+                    }
+                },
+            };
+
+            consumed += consumed_pass;
+            if read_pass > 0 {
+                self.data.extend_from_slice(&decoded_buf[0..read_pass]);
+            }
+        }
+
+        self.total_consumed += consumed;
+
+        // did we get a message?
+        if self.state.is_eof() {
+            // reset
+            let message_data = mem::replace(&mut self.data, vec![]);
+            let total_consumed = self.total_consumed;
+
+            self.state = HttpChunkedTransferReaderState::new(self.state.max_size);
+            self.total_consumed = 0;
+
+            Ok((Some((message_data, total_consumed)), consumed))
+        } else {
+            Ok((None, consumed))
+        }
+    }
+}
+
+/// Information about an in-flight request
+#[derive(Debug, Clone, PartialEq)]
+struct HttpReplyData {
+    request_id: u32,
+    stream: HttpRecvStream,
+}
+
+/// Stacks HTTP implementation, for bufferring up data.
+/// One of these exists per Connection<P: Protocol>.
+/// There can be at most one HTTP request in-flight (i.e. we don't do pipelining)
+#[derive(Debug, Clone, PartialEq)]
+pub struct StacksHttp {
+    /// Address of peer
+    peer_addr: SocketAddr,
+    /// Version of client
+    request_version: Option<HttpVersion>,
+    /// Path we requested
+    request_path: Option<String>,
+    /// Incoming reply
+    reply: Option<HttpReplyData>,
+    /// Size of HTTP chunks to write
+    chunk_size: usize,
+    /// Maximum size of call arguments
+    pub maximum_call_argument_size: u32,
+    /// Maximum size of a response that is not streamed, in bytes (0 for no limit)
+    pub max_response_size: u64,
+    /// Deadline of the request being handled, if it has one
+    request_deadline: Option<RPCRequestDeadline>,
+}
+
+impl StacksHttp {
+    pub fn new(peer_addr: SocketAddr) -> StacksHttp {
+        StacksHttp {
+            peer_addr,
+            reply: None,
+            request_version: None,
+            request_path: None,
+            chunk_size: 8192,
+            maximum_call_argument_size: 20 * BOUND_VALUE_SERIALIZATION_HEX,
+            max_response_size: MAX_HTTP_RESPONSE_SIZE,
+            request_deadline: None,
+        }
+    }
+
+    pub fn set_request_deadline(&mut self, deadline: Option<RPCRequestDeadline>) {
+        self.request_deadline = deadline;
+    }
+
+    pub fn request_deadline(&self) -> Option<&RPCRequestDeadline> {
+        self.request_deadline.as_ref()
+    }
+
+    /// Has the request being handled missed its deadline?
+    pub fn is_past_request_deadline(&self) -> bool {
+        self.request_deadline
+            .map(|deadline| deadline.is_expired(get_epoch_time_ms()))
+            .unwrap_or(false)
+    }
+
+    /// If the request being handled has missed its deadline, count it, clear the deadline, and
+    /// return the 503 to answer it with instead
+    pub fn take_expired_request_deadline(
+        &mut self,
+        md: &HttpResponseMetadata,
+    ) -> Option<HttpResponseType> {
+        if !self.is_past_request_deadline() {
+            return None;
+        }
+        let deadline = self.request_deadline.take()?;
+        debug!(
+            "Request {} missed its {} deadline",
+            md.request_id,
+            deadline.class.as_str()
+        );
+        monitoring::increment_rpc_request_timeouts(deadline.class.as_str());
+        Some(HttpResponseType::ServiceUnavailable(
+            md.clone(),
+            RPC_DEADLINE_EXCEEDED_MESSAGE.to_string(),
+        ))
+    }
+
+    /// If a response body of `len` bytes would be bigger than `max_response_size`, return the 413
+    /// to answer the request with instead
+    pub fn check_response_size(
+        &self,
+        md: &HttpResponseMetadata,
+        len: u64,
+    ) -> Option<HttpResponseType> {
+        if self.max_response_size == 0 || len <= self.max_response_size {
+            return None;
+        }
+        debug!(
+            "HTTP response for request {} is larger than {} bytes",
+            md.request_id, self.max_response_size
+        );
+        Some(HttpResponseType::Error(
+            md.clone(),
+            413,
+            format!(
+                "Response is larger than this node's limit of {} bytes",
+                self.max_response_size
+            ),
+        ))
+    }
+
+    pub fn set_chunk_size(&mut self, size: usize) -> () {
+        self.chunk_size = size;
+    }
+
+    pub fn num_pending(&self) -> usize {
+        if self.reply.is_some() {
+            1
+        } else {
+            0
+        }
+    }
+
+    pub fn has_pending_reply(&self) -> bool {
+        self.reply.is_some()
+    }
+
+    pub fn set_pending(&mut self, preamble: &HttpResponsePreamble) -> bool {
+        if self.reply.is_some() {
+            // already pending
+            return false;
+        }
+        self.reply = Some(HttpReplyData {
+            request_id: preamble.request_id,
+            stream: HttpRecvStream::new(MAX_MESSAGE_LEN as u64),
+        });
+        true
+    }
+
+    pub fn begin_request(&mut self, client_version: HttpVersion, request_path: String) -> () {
+        self.request_version = Some(client_version);
+        self.request_path = Some(request_path);
+    }
+
+    pub fn reset(&mut self) -> () {
+        self.request_version = None;
+        self.request_path = None;
+        self.reply = None;
+    }
+
+    /// Used for processing chunk-encoded streams.
+    /// Given the preamble and a Read, stream the bytes into a chunk-decoder.  Return the decoded
+    /// bytes if we decode an entire stream.  Always return the number of bytes consumed.
+    /// Returns Ok((Some(request path, decoded bytes we got, total number of encoded bytes), number of bytes gotten in this call))
+    pub fn consume_data<R: Read>(
+        &mut self,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+    ) -> Result<(Option<(HttpVersion, String, Vec<u8>, usize)>, usize), net_error> {
+        assert!(preamble.is_chunked());
+        assert!(self.reply.is_some());
+        assert!(self.request_path.is_some());
+        assert!(self.request_version.is_some());
+
+        let mut finished = false;
+        let res = match self.reply {
+            Some(ref mut reply) => {
+                assert_eq!(reply.request_id, preamble.request_id);
+                match reply.stream.consume_data(fd) {
+                    Ok(res) => {
+                        match res {
+                            (None, sz) => Ok((None, sz)),
+                            (Some((byte_vec, bytes_total)), sz) => {
+                                // done receiving
+                                finished = true;
+                                Ok((
+                                    Some((
+                                        self.request_version.clone().unwrap(),
+                                        self.request_path.clone().unwrap(),
+                                        byte_vec,
+                                        bytes_total,
+                                    )),
+                                    sz,
+                                ))
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        // broken stream
+                        finished = true;
+                        Err(e)
+                    }
+                }
+            }
+            None => {
+                unreachable!();
+            }
+        };
+
+        if finished {
+            // if we fetch the whole message, or encounter an error, then we're done -- we can free
+            // up this stream.
+            self.reset();
+        }
+        res
+    }
+
+    /// Given a HTTP request, serialize it out
+    #[cfg(any(test, feature = "stacks-node-client"))]
+    pub fn serialize_request(req: &HttpRequestType) -> Result<Vec<u8>, net_error> {
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        let mut ret = vec![];
+        req.send(&mut http, &mut ret)?;
+        Ok(ret)
+    }
+
+    /// Given a fully-formed single HTTP response, parse it (used by clients).
+    #[cfg(any(test, feature = "stacks-node-client"))]
+    pub fn parse_response(
+        request_path: &str,
+        response_buf: &[u8],
+    ) -> Result<StacksHttpMessage, net_error> {
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.reset();
+        http.begin_request(HttpVersion::Http11, request_path.to_string());
+
+        let (preamble, message_offset) = http.read_preamble(response_buf)?;
+        let is_chunked = match preamble {
+            StacksHttpPreamble::Response(ref resp) => resp.is_chunked(),
+            _ => {
+                return Err(net_error::DeserializeError(
+                    "Invalid HTTP message: did not get a Response preamble".to_string(),
+                ));
+            }
+        };
+
+        let mut message_bytes = &response_buf[message_offset..];
+
+        if is_chunked {
+            match http.stream_payload(&preamble, &mut message_bytes) {
+                Ok((Some((message, _)), _)) => Ok(message),
+                Ok((None, _)) => Err(net_error::UnderflowError(
+                    "Not enough bytes to form a streamed HTTP response".to_string(),
+                )),
+                Err(e) => Err(e),
+            }
+        } else {
+            let (message, _) = http.read_payload(&preamble, &mut message_bytes)?;
+            Ok(message)
+        }
+    }
+}
+
+impl ProtocolFamily for StacksHttp {
+    type Preamble = StacksHttpPreamble;
+    type Message = StacksHttpMessage;
+
+    /// how big can a preamble get?
+    fn preamble_size_hint(&mut self) -> usize {
+        HTTP_PREAMBLE_MAX_ENCODED_SIZE as usize
+    }
+
+    /// how big is this message?  Might not know if we're dealing with chunked encoding.
+    fn payload_len(&mut self, preamble: &StacksHttpPreamble) -> Option<usize> {
+        match *preamble {
+            StacksHttpPreamble::Request(ref http_request_preamble) => {
+                Some(http_request_preamble.get_content_length() as usize)
+            }
+            StacksHttpPreamble::Response(ref http_response_preamble) => {
+                match http_response_preamble.content_length {
+                    Some(len) => Some(len as usize),
+                    None => None,
+                }
+            }
+        }
+    }
+
+    /// StacksHttpMessage deals with HttpRequestPreambles and HttpResponsePreambles
+    fn read_preamble(&mut self, buf: &[u8]) -> Result<(StacksHttpPreamble, usize), net_error> {
+        let mut cursor = io::Cursor::new(buf);
+
+        let preamble = {
+            let mut rd = BoundReader::from_reader(&mut cursor, 4096);
+            let preamble: StacksHttpPreamble = read_next(&mut rd)?;
+            preamble
+        };
+
+        let preamble_len = cursor.position() as usize;
+
+        // if we already have a pending message, then this preamble cannot be processed (indicates an un-compliant client)
+        match preamble {
+            StacksHttpPreamble::Response(ref http_response_preamble) => {
+                // request path must have been set
+                if self.request_path.is_none() {
+                    return Err(net_error::DeserializeError(
+                        "Possible bug: did not set the request path".to_string(),
+                    ));
+                }
+
+                if http_response_preamble.is_chunked() {
+                    // will stream this.  Make sure we're not doing so already (no collisions
+                    // allowed on in-flight request IDs!)
+                    if self.has_pending_reply() {
+                        test_debug!("Have pending reply already");
+                        return Err(net_error::InProgress);
+                    }
+
+                    // mark as pending -- we can stream this
+                    if !self.set_pending(http_response_preamble) {
+                        test_debug!("Have pending reply already");
+                        return Err(net_error::InProgress);
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        Ok((preamble, preamble_len))
+    }
+
+    /// Stream a payload of unknown length.  Only gets called if payload_len() returns None.
+    /// Returns the message if we get enough data to form one.
+    /// Always returns the number of bytes consumed.
+    fn stream_payload<R: Read>(
+        &mut self,
+        preamble: &StacksHttpPreamble,
+        fd: &mut R,
+    ) -> Result<(Option<(StacksHttpMessage, usize)>, usize), net_error> {
+        assert!(self.payload_len(preamble).is_none());
+        match preamble {
+            StacksHttpPreamble::Request(_) => {
+                // HTTP requests can't be chunk-encoded, so this should never be reached
+                unreachable!()
+            }
+            StacksHttpPreamble::Response(ref http_response_preamble) => {
+                assert!(http_response_preamble.is_chunked());
+                assert!(self.request_path.is_some());
+
+                // message of unknown length.  Buffer up and maybe we can parse it.
+                let (message_bytes_opt, num_read) =
+                    self.consume_data(http_response_preamble, fd).map_err(|e| {
+                        self.reset();
+                        e
+                    })?;
+
+                match message_bytes_opt {
+                    Some((request_version, request_path, message_bytes, total_bytes_consumed)) => {
+                        // can parse!
+                        test_debug!(
+                            "read http response payload of {} bytes (just buffered {}) for {}",
+                            message_bytes.len(),
+                            num_read,
+                            &request_path
+                        );
+
+                        // we now know the content-length, so pass it into the parser.
+                        let len_hint = message_bytes.len();
+                        let parse_res = HttpResponseType::parse(
+                            self,
+                            request_version,
+                            http_response_preamble,
+                            request_path,
+                            &mut &message_bytes[..],
+                            Some(len_hint),
+                        );
+
+                        // done parsing
+                        self.reset();
+                        match parse_res {
+                            Ok(data_response) => Ok((
+                                Some((
+                                    StacksHttpMessage::Response(data_response),
+                                    total_bytes_consumed,
+                                )),
+                                num_read,
+                            )),
+                            Err(e) => {
+                                info!("Failed to parse HTTP response: {:?}", &e);
+                                Err(e)
+                            }
+                        }
+                    }
+                    None => {
+                        // need more data
+                        trace!(
+                            "did not read http response payload, but buffered {}",
+                            num_read
+                        );
+                        Ok((None, num_read))
+                    }
+                }
+            }
+        }
+    }
+
+    /// Parse a payload of known length.
+    /// Only gets called if payload_len() returns Some(...)
+    fn read_payload(
+        &mut self,
+        preamble: &StacksHttpPreamble,
+        buf: &[u8],
+    ) -> Result<(StacksHttpMessage, usize), net_error> {
+        match preamble {
+            StacksHttpPreamble::Request(ref http_request_preamble) => {
+                // all requests have a known length
+                let len = http_request_preamble.get_content_length() as usize;
+                assert!(len <= buf.len(), "{} > {}", len, buf.len());
+
+                trace!("read http request payload of {} bytes", len);
+
+                let mut cursor = io::Cursor::new(buf);
+                match HttpRequestType::parse(self, http_request_preamble, &mut cursor) {
+                    Ok(data_request) => Ok((
+                        StacksHttpMessage::Request(data_request),
+                        cursor.position() as usize,
+                    )),
+                    Err(e) => {
+                        info!("Failed to parse HTTP request: {:?}", &e);
+                        if let net_error::ClientError(client_err) = e {
+                            let req = HttpRequestType::ClientError(
+                                HttpRequestMetadata::from_preamble(http_request_preamble),
+                                client_err,
+                            );
+                            // consume any remaining HTTP request content by returning bytes read = len
+                            Ok((StacksHttpMessage::Request(req), len))
+                        } else {
+                            Err(e)
+                        }
+                    }
+                }
+            }
+            StacksHttpPreamble::Response(ref http_response_preamble) => {
+                assert!(!http_response_preamble.is_chunked());
+                assert!(self.request_path.is_some());
+                assert!(self.request_version.is_some());
+
+                let request_path = self.request_path.take().unwrap();
+                let request_version = self.request_version.take().unwrap();
+
+                // message of known length
+                test_debug!(
+                    "read http response payload of {} bytes for {}",
+                    buf.len(),
+                    &request_path
+                );
+
+                let mut cursor = io::Cursor::new(buf);
+                match HttpResponseType::parse(
+                    self,
+                    request_version,
+                    http_response_preamble,
+                    request_path,
+                    &mut cursor,
+                    None,
+                ) {
+                    Ok(data_response) => Ok((
+                        StacksHttpMessage::Response(data_response),
+                        cursor.position() as usize,
+                    )),
+                    Err(e) => Err(e),
+                }
+            }
+        }
+    }
+
+    fn verify_payload_bytes(
+        &mut self,
+        _key: &StacksPublicKey,
+        _preamble: &StacksHttpPreamble,
+        _bytes: &[u8],
+    ) -> Result<(), net_error> {
+        // not defined for HTTP messages, but maybe we could add a signature header at some point
+        // in the future if needed.
+        Ok(())
+    }
+
+    fn write_message<W: Write>(
+        &mut self,
+        fd: &mut W,
+        message: &StacksHttpMessage,
+    ) -> Result<(), net_error> {
+        match *message {
+            StacksHttpMessage::Request(ref req) => {
+                if self.request_path.is_some() {
+                    test_debug!("Have pending request already");
+                    return Err(net_error::InProgress);
+                }
+                req.send(self, fd)?;
+
+                self.reset();
+                self.begin_request(req.metadata().version, req.request_path());
+                Ok(())
+            }
+            StacksHttpMessage::Response(ref resp) => resp.send(self, fd),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use rand;
+    use rand::RngCore;
+
+    use burnchains::Txid;
+    use chainstate::stacks::db::blocks::test::make_sample_microblock_stream;
+    use chainstate::stacks::test::make_codec_test_block;
+    use chainstate::stacks::StacksBlock;
+    use chainstate::stacks::StacksMicroblock;
+    use chainstate::stacks::StacksPrivateKey;
+    use chainstate::stacks::StacksTransaction;
+    use chainstate::stacks::TokenTransferMemo;
+    use chainstate::stacks::TransactionAuth;
+    use chainstate::stacks::TransactionPayload;
+    use chainstate::stacks::TransactionPostConditionMode;
+    use chainstate::stacks::TransactionVersion;
+    use core::schedule::ScheduledConsensusChange;
+    use net::codec::test::check_codec_and_corruption;
+    use net::connection::ConnectionOptions;
+    use net::test::*;
+    use net::ContractSrcResponse;
+    use net::RPCConsensusScheduleData;
+    use net::RPCNeighbor;
+    use net::RPCNeighborsInfo;
+    use net::{
+        RPCAccountBalanceData, RPCDelegationInfo, RPCSponsorshipData, RPCStackingStatusData,
+        RPCVestingEntry,
+    };
+    use net::{
+        RPCAccountTransaction, RPCAccountTransactionsData, RPCAccountTransactionsNextPage,
+        RPCTransactionReceiptData,
+    };
+    use net::{RPCAdminTogglesData, RPCRewardSetData, RPCRewardSetEntry, RPCSortitionData};
+    use net::{RPCBlockProposalData, RPCBlockProposalRejection};
+    use net::{RPCBurnOpSubmissionData, RPCMinerWalletData, RPCTransferStxOpRequest};
+    use net::{RPCContractEvent, RPCContractEventsData, RPCContractEventsNextPage};
+    use net::{RPCDelegationsData, RPCDelegatorInfo};
+    use net::{RPCMicroblockEquivocation, RPCMicroblockEquivocationsData};
+    use net::{RPCPostConditionResult, RPCPostConditionsPreviewData};
+    use util::hash::to_hex;
+    use util::hash::Hash160;
+    use util::hash::MerkleTree;
+    use util::hash::Sha512Trunc256Sum;
+    use vm::costs::ExecutionCost;
+    use vm::database::ClaritySerializable;
+    use vm::profiler::{BlockProfile, CallEdge, ContractProfile, FunctionKey, FunctionProfile};
+    use vm::types::{QualifiedContractIdentifier, TupleData};
+
+    use crate::types::chainstate::StacksAddress;
+    use crate::types::chainstate::StacksBlockHeader;
+
+    use super::*;
+
+    /// Simulate reading variable-length segments
+    struct SegmentReader {
+        segments: Vec<Vec<u8>>,
+        i: usize, // which segment
+        j: usize, // which offset in segment
+    }
+
+    impl SegmentReader {
+        pub fn new(segments: Vec<Vec<u8>>) -> SegmentReader {
+            SegmentReader {
+                segments: segments,
+                i: 0,
+                j: 0,
+            }
+        }
+    }
+
+    impl Read for SegmentReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.i >= self.segments.len() {
+                return Ok(0);
+            }
+            let mut written = 0;
+            while written < buf.len() {
+                let to_copy = if self.segments[self.i][self.j..].len() < buf[written..].len() {
+                    self.segments[self.i][self.j..].len()
+                } else {
+                    buf[written..].len()
+                };
+
+                buf[written..(written + to_copy)]
+                    .copy_from_slice(&self.segments[self.i][self.j..(self.j + to_copy)]);
+
+                self.j += to_copy;
+                written += to_copy;
+
+                if self.j >= self.segments[self.i].len() {
+                    self.i += 1;
+                    self.j = 0;
+                }
+            }
+            Ok(written)
+        }
+    }
+
+    fn vec_u8(mut v: Vec<&str>) -> Vec<Vec<u8>> {
+        let mut ret = vec![];
+        for s_vec in v.drain(..) {
+            let v_u8 = s_vec.as_bytes().to_vec();
+            ret.push(v_u8);
+        }
+        ret
+    }
+
+    #[test]
+    fn test_segment_reader() {
+        let mut tests = vec![
+            (vec_u8(vec!["a", "b"]), "ab"),
+            (vec_u8(vec!["aa", "bbb", "cccc"]), "aabbbcccc"),
+            (vec_u8(vec!["aaaa", "bbb", "cc", "d", ""]), "aaaabbbccd"),
+            (vec_u8(vec!["", "a", "", "b", ""]), "ab"),
+            (vec_u8(vec![""]), ""),
+        ];
+        for (input_vec, expected) in tests.drain(..) {
+            let num_segments = input_vec.len();
+            let mut segment_io = SegmentReader::new(input_vec);
+            let mut output = vec![0u8; expected.len()];
+            let mut offset = 0;
+            for i in 0..num_segments {
+                let nw = segment_io.read(&mut output[offset..]).unwrap();
+                offset += nw;
+            }
+            assert_eq!(output, expected.as_bytes().to_vec());
+        }
+    }
+
+    #[test]
+    fn test_http_chunked_encode() {
+        let tests = vec![
+            // (chunk size, byte string, expected encoding)
+            (10, "aaaaaaaaaabbbbbbbbbbccccccccccdddddddddd", "a\r\naaaaaaaaaa\r\na\r\nbbbbbbbbbb\r\na\r\ncccccccccc\r\na\r\ndddddddddd\r\n0\r\n\r\n"),
+            (10, "aaaaaaaaaabbbbbbbbbbccccccccccdddddddddde", "a\r\naaaaaaaaaa\r\na\r\nbbbbbbbbbb\r\na\r\ncccccccccc\r\na\r\ndddddddddd\r\n1\r\ne\r\n0\r\n\r\n"),
+            (10, "aaaaaaaaaabbbbbbbbbbccccccccccddddddddddeeeee", "a\r\naaaaaaaaaa\r\na\r\nbbbbbbbbbb\r\na\r\ncccccccccc\r\na\r\ndddddddddd\r\n5\r\neeeee\r\n0\r\n\r\n"),
+            (1, "abcd", "1\r\na\r\n1\r\nb\r\n1\r\nc\r\n1\r\nd\r\n0\r\n\r\n"),
+            (3, "abcd", "3\r\nabc\r\n1\r\nd\r\n0\r\n\r\n"),
+            (10, "", "0\r\n\r\n")
+        ];
+        for (chunk_size, input_bytes, encoding) in tests.iter() {
+            let mut bytes = vec![];
+            {
+                let mut write_state = HttpChunkedTransferWriterState::new(*chunk_size as usize);
+                let mut encoder =
+                    HttpChunkedTransferWriter::from_writer_state(&mut bytes, &mut write_state);
+                encoder.write_all(input_bytes.as_bytes()).unwrap();
+                encoder.flush().unwrap();
+            }
+
+            assert_eq!(bytes, encoding.as_bytes().to_vec());
+        }
+    }
+
+    #[test]
+    fn test_http_chunked_encode_multi() {
+        let tests = vec![
+            // chunk size, sequence of writes, expected encoding
+            (10, vec!["aaaaaaaaaa", "bbbbb", "bbbbb", "ccc", "ccc", "ccc", "c", "dd", "ddddd", "ddd"], "a\r\naaaaaaaaaa\r\na\r\nbbbbbbbbbb\r\na\r\ncccccccccc\r\na\r\ndddddddddd\r\n0\r\n\r\n"),
+            (10, vec!["a", "a", "a", "a", "a", "a", "a", "a", "a", "a"], "a\r\naaaaaaaaaa\r\n0\r\n\r\n"),
+            (10, vec!["a", "", "a", "", "a", "", "a", "", "a", "", "a", "", "a", "", "a", "", "a", "", "a", ""], "a\r\naaaaaaaaaa\r\n0\r\n\r\n"),
+        ];
+
+        for (chunk_size, input_vec, encoding) in tests.iter() {
+            let mut bytes = vec![];
+            {
+                let mut write_state = HttpChunkedTransferWriterState::new(*chunk_size as usize);
+                let mut encoder =
+                    HttpChunkedTransferWriter::from_writer_state(&mut bytes, &mut write_state);
+                for input in input_vec.iter() {
+                    encoder.write_all(input.as_bytes()).unwrap();
+                }
+                encoder.flush().unwrap();
+            }
+
+            assert_eq!(bytes, encoding.as_bytes().to_vec());
+        }
+    }
+
+    #[test]
+    fn test_http_chunked_decode() {
+        let tests = vec![
+            ("a\r\naaaaaaaaaa\r\na\r\nbbbbbbbbbb\r\na\r\ncccccccccc\r\na\r\ndddddddddd\r\n0\r\n\r\n", "aaaaaaaaaabbbbbbbbbbccccccccccdddddddddd"),
+            ("A\r\naaaaaaaaaa\r\nA\r\nbbbbbbbbbb\r\nA\r\ncccccccccc\r\nA\r\ndddddddddd\r\n0\r\n\r\n", "aaaaaaaaaabbbbbbbbbbccccccccccdddddddddd"),
+            ("1\r\na\r\n2\r\nbb\r\n3\r\nccc\r\n4\r\ndddd\r\n0\r\n\r\n", "abbcccdddd"),
+            ("1\r\na\r\n0\r\n\r\n", "a"),
+            ("1\r\na\r\n0\r\n\r\n1\r\nb\r\n0\r\n\r\n", "a"),     // stop reading after the first 0-length chunk encountered
+            ("1; a; b\r\na\r\n0; c\r\n\r\n", "a"),                          // ignore short extensions
+            ("1  ; a ; b \r\na\r\n0     ; extension003\r\n\r\n", "a"),      // ignore short extensions
+            ("1 \t; a\t;\tb ;\r\na\r\n0\t\t;c\r\n\r\n", "a"),               // ignore short extensions
+        ];
+        for (encoded, expected) in tests.iter() {
+            let mut cursor = io::Cursor::new(encoded.as_bytes());
+            let mut decoder = HttpChunkedTransferReader::from_reader(&mut cursor, 50);
+            let mut output = vec![0u8; expected.len()];
+            decoder.read_exact(&mut output).unwrap();
+
+            assert_eq!(output, expected.as_bytes().to_vec());
+        }
+    }
+
+    #[test]
+    fn test_http_chunked_decode_multi() {
+        let tests = vec![
+            (vec_u8(vec!["1\r\na", "\r\n", "0\r\n\r\n"]), "a"),
+            (vec_u8(vec!["1\r\na\r", "\n0\r\n\r\n"]), "a"),
+            (vec_u8(vec!["1\r\na\r\n", "0\r\n\r", "\n"]), "a"),
+            (vec_u8(vec!["1\r\na\r\n0\r\n", "\r\n"]), "a"),
+            (vec_u8(vec!["1\r\na\r\n0\r", "\n\r\n"]), "a"),
+            (vec_u8(vec!["1\r\na\r\n0\r", "\n", "\r\n"]), "a"),
+            (vec_u8(vec!["1\r\na\r\n0\r", "\n\r", "\n"]), "a"),
+            (vec_u8(vec!["1\r\na\r\n0\r", "\n", "\r", "\n"]), "a"),
+            (
+                vec_u8(vec![
+                    "1", "\r", "\n", "a", "\r", "\n", "0", "\r", "\n", "\r", "\n",
+                ]),
+                "a",
+            ),
+            (
+                vec_u8(vec![
+                    "a\r",
+                    "\n",
+                    "aaaa",
+                    "aaaaa",
+                    "a",
+                    "\r\n",
+                    "a\r\n",
+                    "bbbbbbbbbb\r",
+                    "\na\r\nccc",
+                    "ccccccc",
+                    "\r",
+                    "\na\r",
+                    "\ndddddd",
+                    "dddd",
+                    "\r\n0\r",
+                    "\n",
+                    "\r",
+                    "\n",
+                ]),
+                "aaaaaaaaaabbbbbbbbbbccccccccccdddddddddd",
+            ),
+            (
+                vec_u8(vec![
+                    "a\r\naaaaaaaaaa",
+                    "\r",
+                    "\n",
+                    "a\r\nbbbbbbbbbb\r",
+                    "\n",
+                    "a\r\ncccccccccc\r",
+                    "\na\r\nddddd",
+                    "ddddd\r",
+                    "\n0\r",
+                    "\n\r",
+                    "\n",
+                ]),
+                "aaaaaaaaaabbbbbbbbbbccccccccccdddddddddd",
+            ),
+            (
+                vec_u8(vec![
+                    "1",
+                    "\r",
+                    "\n",
+                    "",
+                    "a",
+                    "\r",
+                    "\n",
+                    "2",
+                    "\r\n",
+                    "bb",
+                    "\r\n",
+                    "3\r\n",
+                    "ccc\r",
+                    "\n4\r\n",
+                    "dddd\r\n",
+                    "0\r\n\r\n",
+                ]),
+                "abbcccdddd",
+            ),
+        ];
+        for (encoded_vec, expected) in tests.iter() {
+            test_debug!("expect {:?}", &expected);
+
+            let mut output = vec![];
+            let mut cursor = SegmentReader::new((*encoded_vec).clone());
+            let mut decoder = HttpChunkedTransferReader::from_reader(&mut cursor, 50);
+
+            for encoded in encoded_vec.iter() {
+                let mut tmp = vec![0u8; encoded.len()];
+                let nr = decoder.read(&mut tmp).unwrap();
+
+                output.extend_from_slice(&tmp[0..nr]);
+            }
+
+            assert_eq!(output, expected.as_bytes().to_vec());
+        }
+    }
+
+    #[test]
+    fn test_http_chunked_decode_err() {
+        let tests = vec![
+            (
+                "1; reallyreallyreallyreallylongextension;\r\na\r\n0\r\n\r\n",
+                1,
+                "too long",
+            ),
+            ("ffffffff\r\n", 1, "too big"),
+            ("nope\r\n", 1, "could not parse"),
+            ("1\na\r\n0\r\n\r\n", 1, "could not parse"),
+            ("a\r\naaaaaaaaaa", 11, "failed to fill whole buffer"),
+            ("1\r\nab\r\n0\r\n\r\n", 2, "Invalid chunk trailer"),
+            (
+                "15\r\naaaaaaaaaabbbbbbbbbbb\r\n0\r\n\r\n",
+                21,
+                "HTTP body exceeds maximum expected length",
+            ),
+            (
+                "7\r\naaaaaaa\r\n8\r\nbbbbbbbb\r\n6\r\ncccccc\r\n0\r\n\r\n",
+                21,
+                "HTTP body exceeds maximum expected length",
+            ),
+        ];
+        for (encoded, expected_len, expected) in tests.iter() {
+            test_debug!("expect '{}'", expected);
+            let mut cursor = io::Cursor::new(encoded.as_bytes());
+            let mut decoder = HttpChunkedTransferReader::from_reader(&mut cursor, 20);
+            let mut output = vec![0u8; *expected_len as usize];
+
+            let err = decoder.read_exact(&mut output).unwrap_err();
+            let errstr = format!("{:?}", &err);
+
+            assert!(
+                errstr.find(expected).is_some(),
+                "Expected '{}' in '{:?}'",
+                expected,
+                errstr
+            );
+        }
+    }
+
+    #[test]
+    fn test_http_chunked_encode_decode_roundtrip() {
+        let mut rng = rand::thread_rng();
+        for i in 0..100 {
+            let mut data = vec![0u8; 256];
+            rng.fill_bytes(&mut data);
+
+            let mut encoded_data = vec![];
+            {
+                let mut write_state = HttpChunkedTransferWriterState::new(i + 1);
+                let mut encoder = HttpChunkedTransferWriter::from_writer_state(
+                    &mut encoded_data,
+                    &mut write_state,
+                );
+                encoder.write_all(&data).unwrap();
+                encoder.flush().unwrap();
+            }
+
+            let mut decoded_data = vec![0u8; 256];
+            {
+                let mut cursor = io::Cursor::new(&encoded_data);
+                let mut decoder = HttpChunkedTransferReader::from_reader(&mut cursor, 256);
+                decoder.read_exact(&mut decoded_data).unwrap();
+            }
+
+            assert_eq!(data, decoded_data);
+        }
+    }
+
+    #[test]
+    fn test_parse_reserved_header() {
+        let tests = vec![
+            (
+                "Content-Length",
+                "123",
+                Some(HttpReservedHeader::ContentLength(123)),
+            ),
+            (
+                "Content-Type",
+                "text/plain",
+                Some(HttpReservedHeader::ContentType(HttpContentType::Text)),
+            ),
+            (
+                "Content-Type",
+                "application/octet-stream",
+                Some(HttpReservedHeader::ContentType(HttpContentType::Bytes)),
+            ),
+            (
+                "Content-Type",
+                "application/json",
+                Some(HttpReservedHeader::ContentType(HttpContentType::JSON)),
+            ),
+            (
+                "X-Request-Id",
+                "123",
+                Some(HttpReservedHeader::XRequestID(123)),
+            ),
+            (
+                "Host",
+                "foo:123",
+                Some(HttpReservedHeader::Host(PeerHost::DNS(
+                    "foo".to_string(),
+                    123,
+                ))),
+            ),
+            (
+                "Host",
+                "1.2.3.4:123",
+                Some(HttpReservedHeader::Host(PeerHost::IP(
+                    PeerAddress([
+                        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff,
+                        0x01, 0x02, 0x03, 0x04,
+                    ]),
+                    123,
+                ))),
+            ),
+            // errors
+            ("Content-Length", "-1", None),
+            ("Content-Length", "asdf", None),
+            ("Content-Length", "4294967296", None),
+            ("Content-Type", "blargh", None),
+            ("X-Request-Id", "-1", None),
+            ("X-Request-Id", "asdf", None),
+            ("X-Request-Id", "4294967296", None),
+            ("Unrecognized", "header", None),
+        ];
+
+        for (key, value, expected_result) in tests {
+            let result = HttpReservedHeader::try_from_str(key, value);
+            assert_eq!(result, expected_result);
+        }
+    }
+
+    #[test]
+    fn test_parse_http_request_preamble_ok() {
+        let tests = vec![
+            ("GET /foo HTTP/1.1\r\nHost: localhost:6270\r\n\r\n",
+             HttpRequestPreamble::from_headers(HttpVersion::Http11, "GET".to_string(), "/foo".to_string(), "localhost".to_string(), 6270, true, vec![], vec![])),
+            ("POST asdf HTTP/1.1\r\nHost: core.blockstack.org\r\nFoo: Bar\r\n\r\n",
+             HttpRequestPreamble::from_headers(HttpVersion::Http11, "POST".to_string(), "asdf".to_string(), "core.blockstack.org".to_string(), 80, true, vec!["foo".to_string()], vec!["Bar".to_string()])),
+            ("POST asdf HTTP/1.1\r\nHost: core.blockstack.org\r\nFoo: Bar\r\n\r\n",
+             HttpRequestPreamble::from_headers(HttpVersion::Http11, "POST".to_string(), "asdf".to_string(), "core.blockstack.org".to_string(), 80, true, vec!["foo".to_string()], vec!["Bar".to_string()])),
+            ("GET /foo HTTP/1.1\r\nConnection: close\r\nHost: localhost:6270\r\n\r\n",
+             HttpRequestPreamble::from_headers(HttpVersion::Http11, "GET".to_string(), "/foo".to_string(), "localhost".to_string(), 6270, false, vec![], vec![])),
+            ("POST asdf HTTP/1.1\r\nHost: core.blockstack.org\r\nConnection: close\r\nFoo: Bar\r\n\r\n",
+             HttpRequestPreamble::from_headers(HttpVersion::Http11, "POST".to_string(), "asdf".to_string(), "core.blockstack.org".to_string(), 80, false, vec!["foo".to_string()], vec!["Bar".to_string()])),
+            ("POST asdf HTTP/1.1\r\nHost: core.blockstack.org\r\nFoo: Bar\r\nConnection: close\r\n\r\n",
+             HttpRequestPreamble::from_headers(HttpVersion::Http11, "POST".to_string(), "asdf".to_string(), "core.blockstack.org".to_string(), 80, false, vec!["foo".to_string()], vec!["Bar".to_string()])) 
+        ];
+
+        for (data, request) in tests.iter() {
+            let req = HttpRequestPreamble::consensus_deserialize(&mut data.as_bytes());
+            assert!(req.is_ok(), format!("{:?}", &req));
+            assert_eq!(req.unwrap(), *request);
+
+            let sreq = StacksHttpPreamble::consensus_deserialize(&mut data.as_bytes());
+            assert!(sreq.is_ok(), format!("{:?}", &sreq));
+            assert_eq!(
+                sreq.unwrap(),
+                StacksHttpPreamble::Request((*request).clone())
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_http_request_options() {
+        let data = "OPTIONS /foo HTTP/1.1\r\nHost: localhost:6270\r\n\r\n";
+        let req = HttpRequestPreamble::consensus_deserialize(&mut data.as_bytes());
+        let preamble = HttpRequestPreamble::from_headers(
+            HttpVersion::Http11,
+            "OPTIONS".to_string(),
+            "/foo".to_string(),
+            "localhost".to_string(),
+            6270,
+            true,
+            vec![],
+            vec![],
+        );
+        assert_eq!(req.unwrap(), preamble);
+    }
+
+    #[test]
+    fn test_parse_http_request_preamble_case_ok() {
+        let tests = vec![
+            ("GET /foo HTTP/1.1\r\nhOsT: localhost:6270\r\n\r\n",
+             HttpRequestPreamble::from_headers(HttpVersion::Http11, "GET".to_string(), "/foo".to_string(), "localhost".to_string(), 6270, true, vec![], vec![])),
+            ("GET /foo HTTP/1.1\r\ncOnNeCtIoN: cLoSe\r\nhOsT: localhost:6270\r\n\r\n",
+             HttpRequestPreamble::from_headers(HttpVersion::Http11, "GET".to_string(), "/foo".to_string(), "localhost".to_string(), 6270, false, vec![], vec![])),
+            ("POST asdf HTTP/1.1\r\nhOsT: core.blockstack.org\r\nCOnNeCtIoN: kEeP-aLiVE\r\nFoo: Bar\r\n\r\n",
+             HttpRequestPreamble::from_headers(HttpVersion::Http11, "POST".to_string(), "asdf".to_string(), "core.blockstack.org".to_string(), 80, true, vec!["foo".to_string()], vec!["Bar".to_string()])),
+        ];
+
+        for (data, request) in tests.iter() {
+            let req = HttpRequestPreamble::consensus_deserialize(&mut data.as_bytes());
+            assert!(req.is_ok(), format!("{:?}", &req));
+            assert_eq!(req.unwrap(), *request);
+
+            let sreq = StacksHttpPreamble::consensus_deserialize(&mut data.as_bytes());
+            assert!(sreq.is_ok(), format!("{:?}", &sreq));
+            assert_eq!(
+                sreq.unwrap(),
+                StacksHttpPreamble::Request((*request).clone())
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_http_request_preamble_err() {
+        let tests = vec![
+            ("GET /foo HTTP/1.1\r\n", "failed to fill whole buffer"),
+            ("GET /foo HTTP/1.1\r\n\r\n", "Missing Host header"),
+            (
+                "GET /foo HTTP/1.1\r\nFoo: Bar\r\n\r\n",
+                "Missing Host header",
+            ),
+            ("GET /foo HTTP/\r\n\r\n", "Failed to parse HTTP request"),
+            ("GET /foo HTTP/1.1\r\nHost:", "failed to fill whole buffer"),
+            (
+                "GET /foo HTTP/1.1\r\nHost: foo:80\r\nHost: bar:80\r\n\r\n",
+                "duplicate header",
+            ),
+            (
+                "GET /foo HTTP/1.1\r\nHost: localhost:6270\r\nfoo: \u{2764}\r\n\r\n",
+                "header value is not ASCII-US",
+            ),
+            (
+                "Get /foo HTTP/1.1\r\nHost: localhost:666666\r\n\r\n",
+                "Missing Host header",
+            ),
+            (
+                "GET /foo HTTP/1.1\r\nHost: localhost:8080\r\nConnection: foo\r\n\r\n",
+                "invalid Connection: header",
+            ),
+        ];
+
+        for (data, errstr) in tests.iter() {
+            let res = HttpRequestPreamble::consensus_deserialize(&mut data.as_bytes());
+            test_debug!("Expect '{}'", errstr);
+            let expected_errstr = format!("{:?}", &res);
+            assert!(res.is_err(), expected_errstr);
+            assert!(
+                res.unwrap_err().to_string().find(errstr).is_some(),
+                expected_errstr
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_stacks_http_preamble_request_err() {
+        let tests = vec![
+            (
+                "GET /foo HTTP/1.1\r\n",
+                "Not enough bytes to form a HTTP request or response",
+            ),
+            (
+                "GET /foo HTTP/1.1\r\n\r\n",
+                "Failed to decode HTTP request or HTTP response",
+            ),
+            (
+                "GET /foo HTTP/1.1\r\nFoo: Bar\r\n\r\n",
+                "Failed to decode HTTP request or HTTP response",
+            ),
+            (
+                "GET /foo HTTP/\r\n\r\n",
+                "Failed to decode HTTP request or HTTP response",
+            ),
+            (
+                "GET /foo HTTP/1.1\r\nHost:",
+                "Not enough bytes to form a HTTP request or response",
+            ),
+            (
+                "GET /foo HTTP/1.1\r\nHost: foo:80\r\nHost: bar:80\r\n\r\n",
+                "Failed to decode HTTP request or HTTP response",
+            ),
+            (
+                "GET /foo HTTP/1.1\r\nHost: localhost:6270\r\nfoo: \u{2764}\r\n\r\n",
+                "Failed to decode HTTP request or HTTP response",
+            ),
+            (
+                "Get /foo HTTP/1.1\r\nHost: localhost:666666\r\n\r\n",
+                "Failed to decode HTTP request or HTTP response",
+            ),
+            (
+                "GET /foo HTTP/1.1\r\nHost: localhost:8080\r\nConnection: foo\r\n\r\n",
+                "Failed to decode HTTP request or HTTP response",
+            ),
+        ];
+
+        for (data, errstr) in tests.iter() {
+            let sres = StacksHttpPreamble::consensus_deserialize(&mut data.as_bytes());
+            let expected_serrstr = format!("{:?}", &sres);
+            test_debug!("Expect '{}'", errstr);
+            assert!(sres.is_err(), expected_serrstr);
+            assert!(
+                sres.unwrap_err().to_string().find(errstr).is_some(),
+                expected_serrstr
+            );
+        }
+    }
+
+    #[test]
+    fn test_http_request_preamble_headers() {
+        let mut req = HttpRequestPreamble::new(
+            HttpVersion::Http11,
+            "GET".to_string(),
+            "/foo".to_string(),
+            "localhost".to_string(),
+            6270,
+            true,
+        );
+        let req_11 = HttpRequestPreamble::new(
+            HttpVersion::Http11,
+            "GET".to_string(),
+            "/foo".to_string(),
+            "localhost".to_string(),
+            6270,
+            false,
+        );
+        let req_10 = HttpRequestPreamble::new(
+            HttpVersion::Http10,
+            "GET".to_string(),
+            "/foo".to_string(),
+            "localhost".to_string(),
+            6270,
+            false,
+        );
+
+        req.add_header("foo".to_string(), "bar".to_string());
+
+        assert_eq!(req.content_type, None);
+        req.set_content_type(HttpContentType::JSON);
+        assert_eq!(req.content_type, Some(HttpContentType::JSON));
+
+        req.add_header(
+            "content-type".to_string(),
+            "application/octet-stream".to_string(),
+        );
+        assert_eq!(req.content_type, Some(HttpContentType::Bytes));
+
+        let mut bytes = vec![];
+        req.consensus_serialize(&mut bytes).unwrap();
+        let txt = String::from_utf8(bytes).unwrap();
+
+        test_debug!("headers:\n{}", txt);
+
+        assert!(txt.find("HTTP/1.1").is_some(), "HTTP version is missing");
+        assert!(
+            txt.find("User-Agent: stacks/2.0\r\n").is_some(),
+            "User-Agnet header is missing"
+        );
+        assert!(
+            txt.find("Host: localhost:6270\r\n").is_some(),
+            "Host header is missing"
+        );
+        assert!(txt.find("foo: bar\r\n").is_some(), "foo header is missing");
+        assert!(
+            txt.find("Content-Type: application/octet-stream\r\n")
+                .is_some(),
+            "content-type is missing"
+        );
+        assert!(txt.find("Connection: ").is_none()); // not sent if keep_alive is true (for HTTP/1.1)
+
+        let mut bytes_10 = vec![];
+        req_10.consensus_serialize(&mut bytes_10).unwrap();
+        let txt_10 = String::from_utf8(bytes_10).unwrap();
+
+        assert!(txt_10.find("HTTP/1.0").is_some(), "HTTP version is missing");
+
+        let mut bytes_11 = vec![];
+        req_11.consensus_serialize(&mut bytes_11).unwrap();
+        let txt_11 = String::from_utf8(bytes_11).unwrap();
+
+        assert!(txt_11.find("HTTP/1.1").is_some(), "HTTP version is wrong");
+        assert!(
+            txt_11.find("Connection: close").is_some(),
+            "Explicit Connection: close is missing"
+        );
+    }
+
+    #[test]
+    fn test_parse_http_response_preamble_ok() {
+        let tests = vec![
             ("HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: 123\r\nX-Request-ID: 0\r\n\r\n",
              HttpResponsePreamble::from_headers(200, "OK".to_string(), true, Some(123), HttpContentType::Bytes, 0, vec![], vec![])),
             ("HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nContent-Length: 456\r\nFoo: Bar\r\nX-Request-ID: 0\r\n\r\n",
@@ -5336,11 +7379,13 @@ mod test {
                 12345,
             ),
             keep_alive: true,
+            cache_max_age: None,
         };
         let http_request_metadata_dns = HttpRequestMetadata {
             version: HttpVersion::Http11,
             peer: PeerHost::DNS("www.foo.com".to_string(), 80),
             keep_alive: true,
+            cache_max_age: None,
         };
 
         let tests = vec![
@@ -5354,6 +7399,13 @@ mod test {
                 http_request_metadata_dns.clone(),
                 make_test_transaction(),
                 None,
+                false,
+            ),
+            HttpRequestType::PostTransaction(
+                http_request_metadata_dns.clone(),
+                make_test_transaction(),
+                None,
+                true,
             ),
             HttpRequestType::OptionsPreflight(http_request_metadata_ip.clone(), "/".to_string()),
         ];
@@ -5626,6 +7678,14 @@ mod test {
                 ),
                 "/v2/transactions".to_string(),
             ),
+            (
+                HttpResponseType::TransactionAccepted(
+                    HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+                    Txid([0x1; 32]),
+                    false,
+                ),
+                "/v2/transactions?result=1".to_string(),
+            ),
             // errors without error messages
             (
                 HttpResponseType::BadRequest(
@@ -5706,636 +7766,1995 @@ mod test {
                 ),
                 "/v2/neighbors".to_string(),
             ),
-            (
-                HttpResponseType::Forbidden(
-                    HttpResponseMetadata::new(HttpVersion::Http11, 123, Some(3), true),
-                    "foo".to_string(),
-                ),
-                "/v2/neighbors".to_string(),
+            (
+                HttpResponseType::Forbidden(
+                    HttpResponseMetadata::new(HttpVersion::Http11, 123, Some(3), true),
+                    "foo".to_string(),
+                ),
+                "/v2/neighbors".to_string(),
+            ),
+            (
+                HttpResponseType::NotFound(
+                    HttpResponseMetadata::new(HttpVersion::Http11, 123, Some(3), true),
+                    "foo".to_string(),
+                ),
+                "/v2/neighbors".to_string(),
+            ),
+            (
+                HttpResponseType::ServerError(
+                    HttpResponseMetadata::new(HttpVersion::Http11, 123, Some(3), true),
+                    "foo".to_string(),
+                ),
+                "/v2/neighbors".to_string(),
+            ),
+            (
+                HttpResponseType::ServiceUnavailable(
+                    HttpResponseMetadata::new(HttpVersion::Http11, 123, Some(3), true),
+                    "foo".to_string(),
+                ),
+                "/v2/neighbors".to_string(),
+            ),
+            (
+                HttpResponseType::Error(
+                    HttpResponseMetadata::new(HttpVersion::Http11, 123, Some(3), true),
+                    502,
+                    "foo".to_string(),
+                ),
+                "/v2/neighbors".to_string(),
+            ),
+        ];
+
+        let expected_http_preambles = vec![
+            // length is known
+            HttpResponsePreamble::new(
+                200,
+                "OK".to_string(),
+                Some(serde_json::to_string(&test_neighbors_info).unwrap().len() as u32),
+                HttpContentType::JSON,
+                true,
+                123,
+            ),
+            HttpResponsePreamble::new(
+                200,
+                "OK".to_string(),
+                Some(test_block_info_bytes.len() as u32),
+                HttpContentType::Bytes,
+                true,
+                123,
+            ),
+            HttpResponsePreamble::new(
+                200,
+                "OK".to_string(),
+                Some(test_microblock_info_bytes.len() as u32),
+                HttpContentType::Bytes,
+                true,
+                123,
+            ),
+            HttpResponsePreamble::new(
+                200,
+                "OK".to_string(),
+                Some((Txid([0x1; 32]).to_hex().len() + 2) as u32),
+                HttpContentType::JSON,
+                true,
+                123,
+            ),
+            // length is unknown
+            HttpResponsePreamble::new(
+                200,
+                "OK".to_string(),
+                None,
+                HttpContentType::JSON,
+                true,
+                123,
             ),
-            (
-                HttpResponseType::NotFound(
-                    HttpResponseMetadata::new(HttpVersion::Http11, 123, Some(3), true),
-                    "foo".to_string(),
-                ),
-                "/v2/neighbors".to_string(),
+            HttpResponsePreamble::new(
+                200,
+                "OK".to_string(),
+                None,
+                HttpContentType::Bytes,
+                true,
+                123,
             ),
-            (
-                HttpResponseType::ServerError(
-                    HttpResponseMetadata::new(HttpVersion::Http11, 123, Some(3), true),
-                    "foo".to_string(),
-                ),
-                "/v2/neighbors".to_string(),
+            HttpResponsePreamble::new(
+                200,
+                "OK".to_string(),
+                None,
+                HttpContentType::Bytes,
+                true,
+                123,
             ),
-            (
-                HttpResponseType::ServiceUnavailable(
-                    HttpResponseMetadata::new(HttpVersion::Http11, 123, Some(3), true),
-                    "foo".to_string(),
-                ),
-                "/v2/neighbors".to_string(),
+            HttpResponsePreamble::new(
+                200,
+                "OK".to_string(),
+                None,
+                HttpContentType::JSON,
+                true,
+                123,
+            ),
+            HttpResponsePreamble::new(
+                200,
+                "OK".to_string(),
+                None,
+                HttpContentType::JSON,
+                true,
+                123,
             ),
+            // errors
+            HttpResponsePreamble::new_error(400, 123, None),
+            HttpResponsePreamble::new_error(401, 123, None),
+            HttpResponsePreamble::new_error(402, 123, None),
+            HttpResponsePreamble::new_error(403, 123, None),
+            HttpResponsePreamble::new_error(404, 123, None),
+            HttpResponsePreamble::new_error(500, 123, None),
+            HttpResponsePreamble::new_error(503, 123, None),
+            // generic error
+            HttpResponsePreamble::new_error(502, 123, None),
+            // errors with messages
+            HttpResponsePreamble::new_error(400, 123, Some("foo".to_string())),
+            HttpResponsePreamble::new_error(401, 123, Some("foo".to_string())),
+            HttpResponsePreamble::new_error(402, 123, Some("foo".to_string())),
+            HttpResponsePreamble::new_error(403, 123, Some("foo".to_string())),
+            HttpResponsePreamble::new_error(404, 123, Some("foo".to_string())),
+            HttpResponsePreamble::new_error(500, 123, Some("foo".to_string())),
+            HttpResponsePreamble::new_error(503, 123, Some("foo".to_string())),
+            HttpResponsePreamble::new_error(502, 123, Some("foo".to_string())),
+        ];
+
+        let expected_http_bodies = vec![
+            // with content-length
+            serde_json::to_string(&test_neighbors_info)
+                .unwrap()
+                .as_bytes()
+                .to_vec(),
+            test_block_info_bytes.clone(),
+            test_microblock_info_bytes.clone(),
+            Txid([0x1; 32]).to_hex().as_bytes().to_vec(),
+            // with transfer-encoding: chunked
+            serde_json::to_string(&test_neighbors_info)
+                .unwrap()
+                .as_bytes()
+                .to_vec(),
+            test_block_info_bytes,
+            test_microblock_info_bytes,
+            Txid([0x1; 32]).to_hex().as_bytes().to_vec(),
+            serde_json::to_string(&TransactionAcceptedData {
+                txid: Txid([0x1; 32]).to_hex(),
+                accepted: false,
+            })
+            .unwrap()
+            .as_bytes()
+            .to_vec(),
+            // errors
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+            // errors with messages
+            "foo".as_bytes().to_vec(),
+            "foo".as_bytes().to_vec(),
+            "foo".as_bytes().to_vec(),
+            "foo".as_bytes().to_vec(),
+            "foo".as_bytes().to_vec(),
+            "foo".as_bytes().to_vec(),
+            "foo".as_bytes().to_vec(),
+            "foo".as_bytes().to_vec(),
+        ];
+
+        for ((test, request_path), (expected_http_preamble, _expected_http_body)) in
+            tests.iter().zip(
+                expected_http_preambles
+                    .iter()
+                    .zip(expected_http_bodies.iter()),
+            )
+        {
+            let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+            let mut bytes = vec![];
+            test_debug!("write body:\n{:?}\n", test);
+
+            http.begin_request(HttpVersion::Http11, request_path.to_string());
+            http.write_message(&mut bytes, &StacksHttpMessage::Response((*test).clone()))
+                .unwrap();
+
+            let (mut preamble, offset) = match http.read_preamble(&bytes) {
+                Ok((p, o)) => (p, o),
+                Err(e) => {
+                    test_debug!("first 4096 bytes:\n{:?}\n", &bytes[0..].to_vec());
+                    test_debug!("error: {:?}", &e);
+                    assert!(false);
+                    unreachable!();
+                }
+            };
+
+            test_debug!("read preamble of {} bytes\n{:?}\n", offset, preamble);
+
+            test_debug!("read http body\n{:?}\n", &bytes[offset..].to_vec());
+
+            let (message, _total_len) = if expected_http_preamble.is_chunked() {
+                let (msg_opt, len) = http
+                    .stream_payload(&preamble, &mut &bytes[offset..])
+                    .unwrap();
+                (msg_opt.unwrap().0, len)
+            } else {
+                http.read_payload(&preamble, &bytes[offset..]).unwrap()
+            };
+
+            test_debug!("got message\n{:?}\n", &message);
+
+            // check everything in the parsed preamble except for the extra headers
+            match preamble {
+                StacksHttpPreamble::Response(ref mut req) => {
+                    assert_eq!(req.headers.len(), 5);
+                    assert!(req.headers.get("access-control-allow-headers").is_some());
+                    assert!(req.headers.get("access-control-allow-methods").is_some());
+                    assert!(req.headers.get("access-control-allow-origin").is_some());
+                    assert!(req.headers.get("server").is_some());
+                    assert!(req.headers.get("date").is_some());
+                    req.headers.clear();
+                }
+                StacksHttpPreamble::Request(_) => {
+                    panic!("parsed a request");
+                }
+            }
+
+            assert_eq!(
+                preamble,
+                StacksHttpPreamble::Response((*expected_http_preamble).clone())
+            );
+            assert_eq!(message, StacksHttpMessage::Response((*test).clone()));
+            assert_eq!(http.num_pending(), 0);
+        }
+    }
+
+    #[test]
+    fn test_http_response_type_codec_err() {
+        let request_paths = vec![
+            "/v2/blocks/1111111111111111111111111111111111111111111111111111111111111111",
+            "/v2/transactions",
+            "/v2/neighbors",
+            "/v2/neighbors",
+            "/v2/neighbors",
+        ];
+        let bad_request_payloads = vec![
+            "HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-Id: 123\r\nContent-Type: application/json\r\nContent-length: 2\r\n\r\nab",
+            "HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-Id: 123\r\nContent-Type: application/json\r\nContent-length: 4\r\n\r\n\"ab\"",
+            "HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-Id: 123\r\nContent-Type: application/json\r\nContent-length: 1\r\n\r\n{",
+            "HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-Id: 123\r\nContent-Type: application/json\r\nContent-length: 1\r\n\r\na",
+            "HTTP/1.1 400 Bad Request\r\nServer: stacks/v2.0\r\nX-Request-Id: 123\r\nContent-Type: application/json\r\nContent-length: 2\r\n\r\n{}",
+        ];
+        let expected_bad_request_payload_errors = vec![
+            "Invalid content-type",
+            "Invalid txid:",
+            "Not enough bytes",
+            "Failed to parse",
+            "expected text/plain",
+        ];
+        for (test, (expected_error, request_path)) in bad_request_payloads.iter().zip(
+            expected_bad_request_payload_errors
+                .iter()
+                .zip(request_paths),
+        ) {
+            test_debug!(
+                "Expect failure:\n{}\nExpected error: '{}'",
+                test,
+                expected_error
+            );
+
+            let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+            http.begin_request(HttpVersion::Http11, request_path.to_string());
+
+            let (preamble, offset) = http.read_preamble(test.as_bytes()).unwrap();
+            let e = http.read_payload(&preamble, &test.as_bytes()[offset..]);
+            let errstr = format!("{:?}", &e);
+            assert!(e.is_err());
+            assert!(
+                e.unwrap_err().to_string().find(expected_error).is_some(),
+                errstr
+            );
+        }
+    }
+
+    #[test]
+    fn test_http_headers_too_big() {
+        let bad_header_value = std::iter::repeat("A")
+            .take(HTTP_PREAMBLE_MAX_ENCODED_SIZE as usize)
+            .collect::<String>();
+        let bad_request_preamble = format!(
+            "GET /v2/neighbors HTTP/1.1\r\nHost: localhost:1234\r\nBad-Header: {}\r\n\r\n",
+            &bad_header_value
+        );
+        let bad_response_preamble = format!("HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-ID: 123\r\nContent-Type: text/plain\r\nContent-Length: 64\r\nBad-Header: {}\r\n\r\n", &bad_header_value);
+
+        let request_err =
+            HttpRequestPreamble::consensus_deserialize(&mut bad_request_preamble.as_bytes())
+                .unwrap_err();
+        let response_err =
+            HttpResponsePreamble::consensus_deserialize(&mut bad_response_preamble.as_bytes())
+                .unwrap_err();
+
+        let protocol_request_err =
+            StacksHttpPreamble::consensus_deserialize(&mut bad_request_preamble.as_bytes())
+                .unwrap_err();
+        let protocol_response_err =
+            StacksHttpPreamble::consensus_deserialize(&mut bad_response_preamble.as_bytes())
+                .unwrap_err();
+
+        eprintln!("request_err: {:?}", &request_err);
+        eprintln!("response_err: {:?}", &response_err);
+
+        eprintln!("protocol_request_err: {:?}", &protocol_request_err);
+        eprintln!("protocol_response_err: {:?}", &protocol_response_err);
+
+        assert!(request_err
+            .to_string()
+            .find("Not enough bytes to form a HTTP request preamble")
+            .is_some());
+        assert!(response_err
+            .to_string()
+            .find("Not enough bytes to form a HTTP response preamble")
+            .is_some());
+        assert!(protocol_request_err
+            .to_string()
+            .find("Failed to decode HTTP request or HTTP response")
+            .is_some());
+        assert!(protocol_response_err
+            .to_string()
+            .find("Failed to decode HTTP request or HTTP response")
+            .is_some());
+    }
+
+    #[test]
+    fn test_http_headers_too_many() {
+        let mut too_many_headers_list = vec![];
+        for i in 0..HTTP_PREAMBLE_MAX_NUM_HEADERS {
+            too_many_headers_list.push(format!("H{}: {}\r\n", i + 1, i + 1));
+        }
+        let too_many_headers = too_many_headers_list.join("");
+        let bad_request_preamble = format!(
+            "GET /v2/neighbors HTTP/1.1\r\nHost: localhost:1234\r\n{}\r\n",
+            &too_many_headers
+        );
+        let bad_response_preamble = format!("HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-ID: 123\r\nContent-Type: text/plain\r\nContent-Length: 64\r\n{}\r\n", &too_many_headers);
+
+        let request_err =
+            HttpRequestPreamble::consensus_deserialize(&mut bad_request_preamble.as_bytes())
+                .unwrap_err();
+        let response_err =
+            HttpResponsePreamble::consensus_deserialize(&mut bad_response_preamble.as_bytes())
+                .unwrap_err();
+
+        let protocol_request_err =
+            StacksHttpPreamble::consensus_deserialize(&mut bad_request_preamble.as_bytes())
+                .unwrap_err();
+        let protocol_response_err =
+            StacksHttpPreamble::consensus_deserialize(&mut bad_response_preamble.as_bytes())
+                .unwrap_err();
+
+        eprintln!("request_err: {:?}", &request_err);
+        eprintln!("response_err: {:?}", &response_err);
+
+        eprintln!("protocol_request_err: {:?}", &protocol_request_err);
+        eprintln!("protocol_response_err: {:?}", &protocol_response_err);
+
+        assert!(request_err
+            .to_string()
+            .find("Failed to parse HTTP request: TooManyHeaders")
+            .is_some());
+        assert!(response_err
+            .to_string()
+            .find("Failed to parse HTTP response: TooManyHeaders")
+            .is_some());
+        assert!(protocol_request_err
+            .to_string()
+            .find("Failed to decode HTTP request or HTTP response")
+            .is_some());
+        assert!(protocol_response_err
+            .to_string()
+            .find("Failed to decode HTTP request or HTTP response")
+            .is_some());
+    }
+
+    #[test]
+    fn test_http_duplicate_concurrent_streamed_response_fails() {
+        // do not permit multiple in-flight chunk-encoded HTTP responses with the same request ID.
+        let valid_neighbors_response = "HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-Id: 123\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n28\r\n{\"sample\":[],\"inbound\":[],\"outbound\":[]}\r\n0\r\n\r\n";
+        let invalid_neighbors_response = "HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-Id: 123\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n10\r\nxxxxxxxxxxxxxxxx\r\n0\r\n\r\n";
+        let invalid_chunked_response = "HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-Id: 123\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n29\r\n{\"sample\":[],\"inbound\":[],\"outbound\":[]}\r\n0\r\n\r\n";
+
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+
+        http.begin_request(HttpVersion::Http11, "/v2/neighbors".to_string());
+        let (preamble, offset) = http
+            .read_preamble(valid_neighbors_response.as_bytes())
+            .unwrap();
+        assert_eq!(http.num_pending(), 1);
+
+        let res = http.read_preamble(valid_neighbors_response.as_bytes());
+        assert!(res.is_err());
+        assert!(res.unwrap_err().to_string().find("in progress").is_some());
+
+        // finish reading the body
+        let msg = http
+            .stream_payload(
+                &preamble,
+                &mut &valid_neighbors_response.as_bytes()[offset..],
+            )
+            .unwrap();
+        match msg {
             (
-                HttpResponseType::Error(
-                    HttpResponseMetadata::new(HttpVersion::Http11, 123, Some(3), true),
-                    502,
-                    "foo".to_string(),
-                ),
-                "/v2/neighbors".to_string(),
+                Some((
+                    StacksHttpMessage::Response(HttpResponseType::Neighbors(_, neighbors_data)),
+                    _,
+                )),
+                _,
+            ) => assert_eq!(
+                neighbors_data,
+                RPCNeighborsInfo {
+                    sample: vec![],
+                    inbound: vec![],
+                    outbound: vec![]
+                }
             ),
-        ];
+            _ => {
+                error!("Got {:?}", &msg);
+                assert!(false);
+            }
+        }
+        assert_eq!(http.num_pending(), 0);
 
-        let expected_http_preambles = vec![
-            // length is known
-            HttpResponsePreamble::new(
-                200,
-                "OK".to_string(),
-                Some(serde_json::to_string(&test_neighbors_info).unwrap().len() as u32),
-                HttpContentType::JSON,
-                true,
-                123,
-            ),
-            HttpResponsePreamble::new(
-                200,
-                "OK".to_string(),
-                Some(test_block_info_bytes.len() as u32),
-                HttpContentType::Bytes,
-                true,
-                123,
-            ),
-            HttpResponsePreamble::new(
-                200,
-                "OK".to_string(),
-                Some(test_microblock_info_bytes.len() as u32),
-                HttpContentType::Bytes,
+        // can read the preamble again, but only once
+        http.begin_request(HttpVersion::Http11, "/v2/neighbors".to_string());
+        let (preamble, offset) = http
+            .read_preamble(invalid_neighbors_response.as_bytes())
+            .unwrap();
+        assert_eq!(http.num_pending(), 1);
+
+        let res = http.read_preamble(valid_neighbors_response.as_bytes());
+        assert!(res.is_err());
+        assert!(res.unwrap_err().to_string().find("in progress").is_some());
+
+        // reading a corrupt body unlocks the ability to read the preamble again
+        let res = http.stream_payload(
+            &preamble,
+            &mut &invalid_neighbors_response.as_bytes()[offset..],
+        );
+        assert!(res.unwrap_err().to_string().find("JSON").is_some());
+        assert_eq!(http.num_pending(), 0);
+
+        // can read the premable again, but only once
+        http.begin_request(HttpVersion::Http11, "/v2/neighbors".to_string());
+        let (preamble, offset) = http
+            .read_preamble(invalid_chunked_response.as_bytes())
+            .unwrap();
+        let res = http.read_preamble(valid_neighbors_response.as_bytes());
+
+        assert!(res.is_err());
+        assert!(res.unwrap_err().to_string().find("in progress").is_some());
+
+        // reading a corrupt chunk stream unlocks the ability to read the preamble again
+        let res = http.stream_payload(
+            &preamble,
+            &mut &invalid_chunked_response.as_bytes()[offset..],
+        );
+        assert!(res
+            .unwrap_err()
+            .to_string()
+            .find("Invalid chunk trailer")
+            .is_some());
+        assert_eq!(http.num_pending(), 0);
+    }
+
+    #[test]
+    fn test_http_request_version_keep_alive() {
+        let requests = vec![
+            HttpRequestPreamble::new(
+                HttpVersion::Http10,
+                "GET".to_string(),
+                "/v2/info".to_string(),
+                "localhost".to_string(),
+                8080,
                 true,
-                123,
             ),
-            HttpResponsePreamble::new(
-                200,
-                "OK".to_string(),
-                Some((Txid([0x1; 32]).to_hex().len() + 2) as u32),
-                HttpContentType::JSON,
-                true,
-                123,
+            HttpRequestPreamble::new(
+                HttpVersion::Http10,
+                "GET".to_string(),
+                "/v2/info".to_string(),
+                "localhost".to_string(),
+                8080,
+                false,
             ),
-            // length is unknown
-            HttpResponsePreamble::new(
-                200,
-                "OK".to_string(),
-                None,
-                HttpContentType::JSON,
+            HttpRequestPreamble::new(
+                HttpVersion::Http11,
+                "GET".to_string(),
+                "/v2/info".to_string(),
+                "localhost".to_string(),
+                8080,
                 true,
-                123,
             ),
-            HttpResponsePreamble::new(
-                200,
-                "OK".to_string(),
-                None,
-                HttpContentType::Bytes,
-                true,
-                123,
+            HttpRequestPreamble::new(
+                HttpVersion::Http11,
+                "GET".to_string(),
+                "/v2/info".to_string(),
+                "localhost".to_string(),
+                8080,
+                false,
             ),
-            HttpResponsePreamble::new(
+        ];
+
+        // (have 'connection' header?, have 'keep-alive' value?)
+        let requests_connection_expected =
+            vec![(true, true), (false, false), (false, false), (true, false)];
+
+        for (r, (has_connection, is_keep_alive)) in
+            requests.iter().zip(requests_connection_expected.iter())
+        {
+            let mut bytes = vec![];
+            r.consensus_serialize(&mut bytes).unwrap();
+            let txt = String::from_utf8(bytes).unwrap();
+
+            eprintln!(
+                "has_connection: {}, is_keep_alive: {}\n{}",
+                *has_connection, *is_keep_alive, &txt
+            );
+            if *has_connection {
+                if *is_keep_alive {
+                    assert!(txt.find("Connection: keep-alive\r\n").is_some());
+                } else {
+                    assert!(txt.find("Connection: close\r\n").is_some());
+                }
+            } else {
+                assert!(txt.find("Connection: ").is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn test_http_response_version_keep_alive() {
+        // (version, explicit keep-alive?)
+        let responses_args = vec![
+            (HttpVersion::Http10, true),
+            (HttpVersion::Http10, false),
+            (HttpVersion::Http11, true),
+            (HttpVersion::Http11, false),
+        ];
+
+        let mut responses = vec![];
+        for res in responses_args.iter() {
+            let mut bytes = vec![];
+            let md = HttpResponseMetadata::new(res.0.clone(), 123, None, res.1);
+            HttpResponsePreamble::new_serialized(
+                &mut bytes,
                 200,
-                "OK".to_string(),
+                "OK",
                 None,
-                HttpContentType::Bytes,
-                true,
+                &HttpContentType::JSON,
                 123,
+                |ref mut fd| keep_alive_headers(fd, &md),
+            )
+            .unwrap();
+            responses.push(String::from_utf8(bytes).unwrap());
+        }
+
+        for (response, (version, sent_keep_alive)) in responses.iter().zip(responses_args.iter()) {
+            test_debug!(
+                "version: {:?}, sent keep-alive: {}, response:\n{}",
+                version,
+                sent_keep_alive,
+                response
+            );
+            match version {
+                HttpVersion::Http10 => {
+                    // be explicit about Connection: with http/1.0 clients
+                    if *sent_keep_alive {
+                        assert!(response.find("Connection: keep-alive\r\n").is_some());
+                    } else {
+                        assert!(response.find("Connection: close\r\n").is_some());
+                    }
+                }
+                HttpVersion::Http11 => {
+                    if *sent_keep_alive {
+                        // we don't send connection: keep-alive if the client is 1.1 and it didn't
+                        // send its own connection: <option>
+                        assert!(response.find("Connection:").is_none());
+                    } else {
+                        assert!(response.find("Connection: close\r\n").is_some());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Read back a message written by `write_message`, whether or not it was chunk-encoded
+    fn read_test_payload(
+        http: &mut StacksHttp,
+        preamble: &StacksHttpPreamble,
+        bytes: &[u8],
+    ) -> StacksHttpMessage {
+        match preamble {
+            StacksHttpPreamble::Response(ref response_preamble)
+                if response_preamble.is_chunked() =>
+            {
+                let (msg_opt, _) = http.stream_payload(preamble, &mut &bytes[..]).unwrap();
+                msg_opt.unwrap().0
+            }
+            _ => http.read_payload(preamble, bytes).unwrap().0,
+        }
+    }
+
+    #[test]
+    fn test_http_parse_proof_tip_query() {
+        let query_txt = "tip=7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392";
+        assert_eq!(
+            HttpRequestType::get_chain_tip_query(Some(query_txt)).unwrap(),
+            StacksBlockId::from_hex(
+                "7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392"
+            )
+            .unwrap()
+        );
+
+        // first parseable tip is taken
+        let query_txt_dup = "tip=7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392&tip=03e26bd68a8722f8b3861e2058edcafde094ad059e152754986c3573306698f1";
+        assert_eq!(
+            HttpRequestType::get_chain_tip_query(Some(query_txt_dup)).unwrap(),
+            StacksBlockId::from_hex(
+                "7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392"
+            )
+            .unwrap()
+        );
+
+        // first parseable tip is taken
+        let query_txt_dup = "tip=bad&tip=7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392&tip=03e26bd68a8722f8b3861e2058edcafde094ad059e152754986c3573306698f1";
+        assert_eq!(
+            HttpRequestType::get_chain_tip_query(Some(query_txt_dup)).unwrap(),
+            StacksBlockId::from_hex(
+                "7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392"
+            )
+            .unwrap()
+        );
+
+        // tip can be skipped
+        let query_txt_bad = "tip=bad";
+        assert_eq!(
+            HttpRequestType::get_chain_tip_query(Some(query_txt_bad)),
+            None
+        );
+
+        // tip can be skipped
+        let query_txt_none = "tip=bad";
+        assert_eq!(
+            HttpRequestType::get_chain_tip_query(Some(query_txt_none)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_http_parse_tip_request_query() {
+        let tip = StacksBlockId::from_hex(
+            "7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392",
+        )
+        .unwrap();
+        assert_eq!(
+            HttpRequestType::get_tip_request_query(None),
+            TipRequest::UseLatestAnchoredTip
+        );
+        assert_eq!(
+            HttpRequestType::get_tip_request_query(Some("tip=unconfirmed&proof=0")),
+            TipRequest::UseLatestUnconfirmedTip
+        );
+        assert_eq!(
+            HttpRequestType::get_tip_request_query(Some(
+                "tip=7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392"
+            )),
+            TipRequest::SpecificTip(tip.clone())
+        );
+
+        // first parseable tip is taken
+        assert_eq!(
+            HttpRequestType::get_tip_request_query(Some(
+                "tip=bad&tip=unconfirmed&tip=7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392"
+            )),
+            TipRequest::UseLatestUnconfirmedTip
+        );
+
+        // tip can be skipped
+        assert_eq!(
+            HttpRequestType::get_tip_request_query(Some("tip=bad")),
+            TipRequest::UseLatestAnchoredTip
+        );
+
+        // tip=unconfirmed survives a round trip
+        let principal = PrincipalData::from(StacksAddress {
+            version: 1,
+            bytes: Hash160([0x22; 20]),
+        });
+        let request = HttpRequestType::GetAccount(
+            HttpRequestMetadata {
+                version: HttpVersion::Http11,
+                peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+                keep_alive: true,
+                cache_max_age: None,
+            },
+            principal.clone(),
+            TipRequest::UseLatestUnconfirmedTip,
+            false,
+        );
+        assert_eq!(
+            request.request_path(),
+            format!("/v2/accounts/{}?tip=unconfirmed&proof=0", &principal)
+        );
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+            .unwrap();
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Request(request));
+    }
+
+    #[test]
+    fn test_http_get_contract_events_roundtrip() {
+        let md = HttpRequestMetadata {
+            version: HttpVersion::Http11,
+            peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+            keep_alive: true,
+            cache_max_age: None,
+        };
+        let contract_addr = StacksAddress {
+            version: 1,
+            bytes: Hash160([0x22; 20]),
+        };
+        let contract_name = ContractName::try_from("market".to_string()).unwrap();
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        for (topic_opt, before, tip_opt) in [
+            (None, None, None),
+            (
+                Some(ClarityName::try_from("sold?".to_string()).unwrap()),
+                Some((40, 2, 1)),
+                Some(StacksBlockId([0x33; 32])),
             ),
-            HttpResponsePreamble::new(
-                200,
-                "OK".to_string(),
-                None,
-                HttpContentType::JSON,
-                true,
-                123,
+        ]
+        .iter()
+        {
+            let request = HttpRequestType::GetContractEvents(
+                md.clone(),
+                contract_addr.clone(),
+                contract_name.clone(),
+                topic_opt.clone(),
+                before.clone(),
+                CONTRACT_EVENTS_MAX_LIMIT,
+                tip_opt.clone(),
+            );
+            let mut bytes = vec![];
+            http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+                .unwrap();
+
+            let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+            http.reset();
+            let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+            assert_eq!(message, StacksHttpMessage::Request(request));
+        }
+
+        // the page size defaults, and is bounded; a page's start needs a height, a tx index and
+        // an event index
+        for (query, expected) in [
+            ("", Some((None, CONTRACT_EVENTS_DEFAULT_LIMIT))),
+            (
+                "?before_height=5&before_tx_index=1&before_event_index=0&limit=1",
+                Some((Some((5, 1, 0)), 1)),
             ),
-            // errors
-            HttpResponsePreamble::new_error(400, 123, None),
-            HttpResponsePreamble::new_error(401, 123, None),
-            HttpResponsePreamble::new_error(402, 123, None),
-            HttpResponsePreamble::new_error(403, 123, None),
-            HttpResponsePreamble::new_error(404, 123, None),
-            HttpResponsePreamble::new_error(500, 123, None),
-            HttpResponsePreamble::new_error(503, 123, None),
-            // generic error
-            HttpResponsePreamble::new_error(502, 123, None),
-            // errors with messages
-            HttpResponsePreamble::new_error(400, 123, Some("foo".to_string())),
-            HttpResponsePreamble::new_error(401, 123, Some("foo".to_string())),
-            HttpResponsePreamble::new_error(402, 123, Some("foo".to_string())),
-            HttpResponsePreamble::new_error(403, 123, Some("foo".to_string())),
-            HttpResponsePreamble::new_error(404, 123, Some("foo".to_string())),
-            HttpResponsePreamble::new_error(500, 123, Some("foo".to_string())),
-            HttpResponsePreamble::new_error(503, 123, Some("foo".to_string())),
-            HttpResponsePreamble::new_error(502, 123, Some("foo".to_string())),
-        ];
+            ("?limit=0", None),
+            ("?limit=51", None),
+            ("?before_height=5&before_tx_index=1", None),
+            ("?topic=not%20a%20name", None),
+        ]
+        .iter()
+        {
+            let request = format!(
+                "GET /v2/contracts/events/{}/market{} HTTP/1.1\r\nHost: www.foo.com:80\r\n\r\n",
+                &contract_addr, query
+            );
+            let (preamble, offset) = http.read_preamble(request.as_bytes()).unwrap();
+            let res = http.read_payload(&preamble, &request.as_bytes()[offset..]);
+            match (res, expected) {
+                (
+                    Ok((
+                        StacksHttpMessage::Request(HttpRequestType::GetContractEvents(
+                            _,
+                            _,
+                            _,
+                            None,
+                            before,
+                            limit,
+                            None,
+                        )),
+                        _,
+                    )),
+                    Some(expected),
+                ) => assert_eq!((before, limit), *expected),
+                (Err(_), None) => {}
+                (res, _) => panic!("Unexpected result for {}: {:?}", query, &res),
+            }
+        }
 
-        let expected_http_bodies = vec![
-            // with content-length
-            serde_json::to_string(&test_neighbors_info)
-                .unwrap()
-                .as_bytes()
-                .to_vec(),
-            test_block_info_bytes.clone(),
-            test_microblock_info_bytes.clone(),
-            Txid([0x1; 32]).to_hex().as_bytes().to_vec(),
-            // with transfer-encoding: chunked
-            serde_json::to_string(&test_neighbors_info)
-                .unwrap()
-                .as_bytes()
-                .to_vec(),
-            test_block_info_bytes,
-            test_microblock_info_bytes,
-            Txid([0x1; 32]).to_hex().as_bytes().to_vec(),
-            // errors
-            vec![],
-            vec![],
-            vec![],
-            vec![],
-            vec![],
-            vec![],
-            vec![],
-            vec![],
-            // errors with messages
-            "foo".as_bytes().to_vec(),
-            "foo".as_bytes().to_vec(),
-            "foo".as_bytes().to_vec(),
-            "foo".as_bytes().to_vec(),
-            "foo".as_bytes().to_vec(),
-            "foo".as_bytes().to_vec(),
-            "foo".as_bytes().to_vec(),
-            "foo".as_bytes().to_vec(),
-        ];
+        let response = HttpResponseType::GetContractEvents(
+            HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+            RPCContractEventsData {
+                limit: 1,
+                results: vec![RPCContractEvent {
+                    txid: "11".repeat(32),
+                    index_block_hash: "22".repeat(32),
+                    block_height: 10,
+                    tx_index: 1,
+                    event_index: 2,
+                    topic: Some("sold".to_string()),
+                    value: format!("0x{}", ClaritySerializable::serialize(&Value::UInt(10))),
+                }],
+                next: Some(RPCContractEventsNextPage {
+                    before_height: 10,
+                    before_tx_index: 1,
+                    before_event_index: 2,
+                }),
+            },
+        );
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.begin_request(
+            HttpVersion::Http11,
+            format!("/v2/contracts/events/{}/market", &contract_addr),
+        );
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
+            .unwrap();
 
-        for ((test, request_path), (expected_http_preamble, _expected_http_body)) in
-            tests.iter().zip(
-                expected_http_preambles
-                    .iter()
-                    .zip(expected_http_bodies.iter()),
-            )
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Response(response));
+    }
+
+    #[test]
+    fn test_http_get_account_transactions_roundtrip() {
+        let md = HttpRequestMetadata {
+            version: HttpVersion::Http11,
+            peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+            keep_alive: true,
+            cache_max_age: None,
+        };
+        let principal = PrincipalData::from(StacksAddress {
+            version: 1,
+            bytes: Hash160([0x22; 20]),
+        });
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        for (before, tip_opt) in [
+            (None, None),
+            (Some((40, 2)), Some(StacksBlockId([0x33; 32]))),
+        ]
+        .iter()
         {
-            let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+            let request = HttpRequestType::GetAccountTransactions(
+                md.clone(),
+                principal.clone(),
+                before.clone(),
+                ACCOUNT_TRANSACTIONS_MAX_LIMIT,
+                tip_opt.clone(),
+            );
             let mut bytes = vec![];
-            test_debug!("write body:\n{:?}\n", test);
+            http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+                .unwrap();
 
-            http.begin_request(HttpVersion::Http11, request_path.to_string());
-            http.write_message(&mut bytes, &StacksHttpMessage::Response((*test).clone()))
+            let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+            http.reset();
+            let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+            assert_eq!(message, StacksHttpMessage::Request(request));
+        }
+
+        // the page size defaults, and is bounded; a page's start needs both a height and a tx
+        // index
+        for (query, expected) in [
+            ("", Some((None, ACCOUNT_TRANSACTIONS_DEFAULT_LIMIT))),
+            (
+                "?before_height=5&before_tx_index=0&limit=1",
+                Some((Some((5, 0)), 1)),
+            ),
+            ("?limit=0", None),
+            ("?limit=51", None),
+            ("?before_height=5", None),
+            ("?before_tx_index=0", None),
+            ("?before_height=-1&before_tx_index=0", None),
+        ]
+        .iter()
+        {
+            let request = format!(
+                "GET /v2/accounts/{}/transactions{} HTTP/1.1\r\nHost: www.foo.com:80\r\n\r\n",
+                &principal, query
+            );
+            let (preamble, offset) = http.read_preamble(request.as_bytes()).unwrap();
+            let res = http.read_payload(&preamble, &request.as_bytes()[offset..]);
+            match (res, expected) {
+                (
+                    Ok((
+                        StacksHttpMessage::Request(HttpRequestType::GetAccountTransactions(
+                            _,
+                            _,
+                            before,
+                            limit,
+                            None,
+                        )),
+                        _,
+                    )),
+                    Some(expected),
+                ) => assert_eq!((before, limit), *expected),
+                (Err(_), None) => {}
+                (res, _) => panic!("Unexpected result for {}: {:?}", query, &res),
+            }
+        }
+
+        let response = HttpResponseType::GetAccountTransactions(
+            HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+            RPCAccountTransactionsData {
+                limit: 1,
+                results: vec![RPCAccountTransaction {
+                    txid: "11".repeat(32),
+                    index_block_hash: "22".repeat(32),
+                    block_height: 10,
+                    tx_index: 1,
+                    tx: Some("00".to_string()),
+                    result: format!("0x{}", ClaritySerializable::serialize(&Value::okay_true())),
+                }],
+                next: Some(RPCAccountTransactionsNextPage {
+                    before_height: 10,
+                    before_tx_index: 1,
+                }),
+            },
+        );
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.begin_request(
+            HttpVersion::Http11,
+            format!("/v2/accounts/{}/transactions", &principal),
+        );
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
+            .unwrap();
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Response(response));
+    }
+
+    #[test]
+    fn test_http_get_transaction_receipt_roundtrip() {
+        let md = HttpRequestMetadata {
+            version: HttpVersion::Http11,
+            peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+            keep_alive: true,
+            cache_max_age: None,
+        };
+        let txid = Txid([0x11; 32]);
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        for tip_opt in [None, Some(StacksBlockId([0x33; 32]))].iter() {
+            let request =
+                HttpRequestType::GetTransactionReceipt(md.clone(), txid.clone(), tip_opt.clone());
+            assert_eq!(request.is_immutable(None), tip_opt.is_some());
+            let mut bytes = vec![];
+            http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
                 .unwrap();
 
-            let (mut preamble, offset) = match http.read_preamble(&bytes) {
-                Ok((p, o)) => (p, o),
-                Err(e) => {
-                    test_debug!("first 4096 bytes:\n{:?}\n", &bytes[0..].to_vec());
-                    test_debug!("error: {:?}", &e);
-                    assert!(false);
-                    unreachable!();
-                }
-            };
+            let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+            http.reset();
+            let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+            assert_eq!(message, StacksHttpMessage::Request(request));
+        }
+
+        let response = HttpResponseType::GetTransactionReceipt(
+            HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+            RPCTransactionReceiptData {
+                txid: txid.to_string(),
+                index_block_hash: "22".repeat(32),
+                block_height: 10,
+                tx_index: 1,
+                result: format!("0x{}", ClaritySerializable::serialize(&Value::okay_true())),
+                post_condition_aborted: false,
+                stx_burned: "0".to_string(),
+                execution_cost: ExecutionCost::zero(),
+                vm_error: None,
+                events: vec![json!({"type": "contract_event"})],
+            },
+        );
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.begin_request(HttpVersion::Http11, format!("/v2/receipts/{}", &txid));
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
+            .unwrap();
 
-            test_debug!("read preamble of {} bytes\n{:?}\n", offset, preamble);
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Response(response));
+    }
 
-            test_debug!("read http body\n{:?}\n", &bytes[offset..].to_vec());
+    #[test]
+    fn test_http_simulate_contract_call_request_roundtrip() {
+        let contract_addr = StacksAddress {
+            version: 1,
+            bytes: Hash160([0x11; 20]),
+        };
+        let contract_id = QualifiedContractIdentifier::new(
+            contract_addr.clone().into(),
+            ContractName::try_from("hello-world").unwrap(),
+        );
+        let sender = PrincipalData::from(StacksAddress {
+            version: 1,
+            bytes: Hash160([0x22; 20]),
+        });
 
-            let (message, _total_len) = if expected_http_preamble.is_chunked() {
-                let (msg_opt, len) = http
-                    .stream_payload(&preamble, &mut &bytes[offset..])
-                    .unwrap();
-                (msg_opt.unwrap().0, len)
-            } else {
-                http.read_payload(&preamble, &bytes[offset..]).unwrap()
-            };
+        let request = HttpRequestType::SimulateContractCall(
+            HttpRequestMetadata {
+                version: HttpVersion::Http11,
+                peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+                keep_alive: true,
+                cache_max_age: None,
+            },
+            contract_addr.clone(),
+            ContractName::try_from("hello-world").unwrap(),
+            sender.clone(),
+            ClarityName::try_from("get-bar").unwrap(),
+            vec![Value::UInt(1), Value::Int(-2)],
+            vec![
+                ClarityStateOverride::DataVar(
+                    contract_id.clone(),
+                    ClarityName::try_from("bar").unwrap(),
+                    Value::Int(123),
+                ),
+                ClarityStateOverride::MapEntry(
+                    contract_id.clone(),
+                    ClarityName::try_from("unit-map").unwrap(),
+                    Value::Int(1),
+                    Value::UInt(2),
+                ),
+                ClarityStateOverride::STXBalance(sender.clone(), 1_000_000_000_000),
+            ],
+            Some(StacksBlockId([0x33; 32])),
+        );
 
-            test_debug!("got message\n{:?}\n", &message);
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+            .unwrap();
 
-            // check everything in the parsed preamble except for the extra headers
-            match preamble {
-                StacksHttpPreamble::Response(ref mut req) => {
-                    assert_eq!(req.headers.len(), 5);
-                    assert!(req.headers.get("access-control-allow-headers").is_some());
-                    assert!(req.headers.get("access-control-allow-methods").is_some());
-                    assert!(req.headers.get("access-control-allow-origin").is_some());
-                    assert!(req.headers.get("server").is_some());
-                    assert!(req.headers.get("date").is_some());
-                    req.headers.clear();
-                }
-                StacksHttpPreamble::Request(_) => {
-                    panic!("parsed a request");
-                }
-            }
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Request(request));
 
-            assert_eq!(
-                preamble,
-                StacksHttpPreamble::Response((*expected_http_preamble).clone())
+        // malformed overrides are rejected
+        let bad_body = r#"{"sender":"S1G2081040G2081040G2081040G208105NK8PE5","arguments":[],"overrides":[{"type":"stx_balance","principal":"S1G2081040G2081040G2081040G208105NK8PE5","amount":"-1"}]}"#;
+        let bad_request = format!("POST /v2/contracts/simulate-call/S1G2081040G2081040G2081040G208105NK8PE5/hello-world/get-bar HTTP/1.1\r\nHost: www.foo.com:80\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", bad_body.len(), bad_body);
+        let (preamble, offset) = http.read_preamble(bad_request.as_bytes()).unwrap();
+        let e = http.read_payload(&preamble, &bad_request.as_bytes()[offset..]);
+        assert!(e.is_err(), "{:?}", &e);
+    }
+
+    #[test]
+    fn test_http_preview_postconditions_roundtrip() {
+        let md = HttpRequestMetadata {
+            version: HttpVersion::Http11,
+            peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+            keep_alive: true,
+            cache_max_age: None,
+        };
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        for tip_req in [
+            TipRequest::UseLatestAnchoredTip,
+            TipRequest::UseLatestUnconfirmedTip,
+            TipRequest::SpecificTip(StacksBlockId([0x33; 32])),
+        ]
+        .iter()
+        {
+            let request = HttpRequestType::PreviewPostConditions(
+                md.clone(),
+                make_test_transaction(),
+                tip_req.clone(),
             );
-            assert_eq!(message, StacksHttpMessage::Response((*test).clone()));
-            assert_eq!(http.num_pending(), 0);
+            let mut bytes = vec![];
+            http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+                .unwrap();
+
+            let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+            http.reset();
+            let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+            assert_eq!(message, StacksHttpMessage::Request(request));
         }
+
+        let data = RPCPostConditionsPreviewData {
+            txid: make_test_transaction().txid().to_string(),
+            okay: true,
+            cause: None,
+            result: Some(format!(
+                "0x{}",
+                ClaritySerializable::serialize(&Value::okay_true())
+            )),
+            would_abort: true,
+            post_condition_mode: "deny".to_string(),
+            post_conditions: vec![
+                RPCPostConditionResult {
+                    index: 0,
+                    passed: true,
+                },
+                RPCPostConditionResult {
+                    index: 1,
+                    passed: false,
+                },
+            ],
+            unchecked_assets: vec![],
+        };
+        let response = HttpResponseType::PostConditionsPreview(
+            HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+            data,
+        );
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.begin_request(
+            HttpVersion::Http11,
+            "/v2/transactions/postconditions".to_string(),
+        );
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
+            .unwrap();
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Response(response));
     }
 
     #[test]
-    fn test_http_response_type_codec_err() {
-        let request_paths = vec![
-            "/v2/blocks/1111111111111111111111111111111111111111111111111111111111111111",
-            "/v2/transactions",
-            "/v2/neighbors",
-            "/v2/neighbors",
-            "/v2/neighbors",
-        ];
-        let bad_request_payloads = vec![
-            "HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-Id: 123\r\nContent-Type: application/json\r\nContent-length: 2\r\n\r\nab",
-            "HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-Id: 123\r\nContent-Type: application/json\r\nContent-length: 4\r\n\r\n\"ab\"",
-            "HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-Id: 123\r\nContent-Type: application/json\r\nContent-length: 1\r\n\r\n{",
-            "HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-Id: 123\r\nContent-Type: application/json\r\nContent-length: 1\r\n\r\na",
-            "HTTP/1.1 400 Bad Request\r\nServer: stacks/v2.0\r\nX-Request-Id: 123\r\nContent-Type: application/json\r\nContent-length: 2\r\n\r\n{}",
-        ];
-        let expected_bad_request_payload_errors = vec![
-            "Invalid content-type",
-            "Invalid txid:",
-            "Not enough bytes",
-            "Failed to parse",
-            "expected text/plain",
-        ];
-        for (test, (expected_error, request_path)) in bad_request_payloads.iter().zip(
-            expected_bad_request_payload_errors
-                .iter()
-                .zip(request_paths),
-        ) {
-            test_debug!(
-                "Expect failure:\n{}\nExpected error: '{}'",
-                test,
-                expected_error
-            );
+    fn test_http_parse_json_clarity_values() {
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        let key = Value::from(
+            TupleData::from_data(vec![(
+                ClarityName::try_from("a").unwrap(),
+                Value::some(Value::UInt(1)).unwrap(),
+            )])
+            .unwrap(),
+        );
 
-            let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
-            http.begin_request(HttpVersion::Http11, request_path.to_string());
+        // call-read arguments can be a mix of hex and JSON
+        let body = json!({
+            "sender": "S1G2081040G2081040G2081040G208105NK8PE5",
+            "arguments": [format!("0x{}", ClaritySerializable::serialize(&Value::Int(-2))), key.to_json()],
+        })
+        .to_string();
+        let request = format!("POST /v2/contracts/call-read/S1G2081040G2081040G2081040G208105NK8PE5/hello-world/get-bar HTTP/1.1\r\nHost: www.foo.com:80\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+        let (preamble, offset) = http.read_preamble(request.as_bytes()).unwrap();
+        let (message, _) = http
+            .read_payload(&preamble, &request.as_bytes()[offset..])
+            .unwrap();
+        match message {
+            StacksHttpMessage::Request(HttpRequestType::CallReadOnlyFunction(
+                _,
+                _,
+                _,
+                _,
+                _,
+                args,
+                _,
+            )) => {
+                assert_eq!(args, vec![Value::Int(-2), key.clone()]);
+            }
+            _ => panic!("Unexpected message: {:?}", &message),
+        }
 
-            let (preamble, offset) = http.read_preamble(test.as_bytes()).unwrap();
-            let e = http.read_payload(&preamble, &test.as_bytes()[offset..]);
-            let errstr = format!("{:?}", &e);
-            assert!(e.is_err());
-            assert!(
-                e.unwrap_err().to_string().find(expected_error).is_some(),
-                errstr
-            );
+        // map_entry keys can be JSON
+        let body = key.to_json().to_string();
+        let request = format!("POST /v2/map_entry/S1G2081040G2081040G2081040G208105NK8PE5/hello-world/unit-map HTTP/1.1\r\nHost: www.foo.com:80\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+        let (preamble, offset) = http.read_preamble(request.as_bytes()).unwrap();
+        let (message, _) = http
+            .read_payload(&preamble, &request.as_bytes()[offset..])
+            .unwrap();
+        match message {
+            StacksHttpMessage::Request(HttpRequestType::GetMapEntry(_, _, _, _, value, _, _)) => {
+                assert_eq!(value, key);
+            }
+            _ => panic!("Unexpected message: {:?}", &message),
         }
+
+        // malformed JSON values are rejected
+        let body = r#"{"sender":"S1G2081040G2081040G2081040G208105NK8PE5","arguments":[{"type":"uint","value":"-1"}]}"#;
+        let request = format!("POST /v2/contracts/call-read/S1G2081040G2081040G2081040G208105NK8PE5/hello-world/get-bar HTTP/1.1\r\nHost: www.foo.com:80\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", body.len(), body);
+        let (preamble, offset) = http.read_preamble(request.as_bytes()).unwrap();
+        let e = http.read_payload(&preamble, &request.as_bytes()[offset..]);
+        assert!(e.is_err(), "{:?}", &e);
     }
 
     #[test]
-    fn test_http_headers_too_big() {
-        let bad_header_value = std::iter::repeat("A")
-            .take(HTTP_PREAMBLE_MAX_ENCODED_SIZE as usize)
-            .collect::<String>();
-        let bad_request_preamble = format!(
-            "GET /v2/neighbors HTTP/1.1\r\nHost: localhost:1234\r\nBad-Header: {}\r\n\r\n",
-            &bad_header_value
+    fn test_http_stacking_status_roundtrip() {
+        let stacker = PrincipalData::from(StacksAddress {
+            version: 1,
+            bytes: Hash160([0x22; 20]),
+        });
+        let request = HttpRequestType::GetStackingStatus(
+            HttpRequestMetadata {
+                version: HttpVersion::Http11,
+                peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+                keep_alive: true,
+                cache_max_age: None,
+            },
+            stacker.clone(),
+            Some(StacksBlockId([0x33; 32])),
         );
-        let bad_response_preamble = format!("HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-ID: 123\r\nContent-Type: text/plain\r\nContent-Length: 64\r\nBad-Header: {}\r\n\r\n", &bad_header_value);
 
-        let request_err =
-            HttpRequestPreamble::consensus_deserialize(&mut bad_request_preamble.as_bytes())
-                .unwrap_err();
-        let response_err =
-            HttpResponsePreamble::consensus_deserialize(&mut bad_response_preamble.as_bytes())
-                .unwrap_err();
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+            .unwrap();
 
-        let protocol_request_err =
-            StacksHttpPreamble::consensus_deserialize(&mut bad_request_preamble.as_bytes())
-                .unwrap_err();
-        let protocol_response_err =
-            StacksHttpPreamble::consensus_deserialize(&mut bad_response_preamble.as_bytes())
-                .unwrap_err();
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Request(request));
+
+        let response = HttpResponseType::GetStackingStatus(
+            HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+            RPCStackingStatusData {
+                principal: stacker.to_string(),
+                is_stacking: true,
+                locked_ustx: 1_000_000,
+                unlock_height: 1234,
+                first_reward_cycle: Some(3),
+                lock_period: Some(2),
+                pox_address: Some("mnr2vXtNhuZjaKLnFdXpk2PtRctTmdt4HU".to_string()),
+                delegation: Some(RPCDelegationInfo {
+                    delegated_to: stacker.to_string(),
+                    amount_ustx: 1_000_000,
+                    until_burn_height: None,
+                    pox_address: None,
+                }),
+            },
+        );
 
-        eprintln!("request_err: {:?}", &request_err);
-        eprintln!("response_err: {:?}", &response_err);
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.begin_request(HttpVersion::Http11, format!("/v2/stacking/{}", &stacker));
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
+            .unwrap();
 
-        eprintln!("protocol_request_err: {:?}", &protocol_request_err);
-        eprintln!("protocol_response_err: {:?}", &protocol_response_err);
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Response(response));
+    }
 
-        assert!(request_err
-            .to_string()
-            .find("Not enough bytes to form a HTTP request preamble")
-            .is_some());
-        assert!(response_err
-            .to_string()
-            .find("Not enough bytes to form a HTTP response preamble")
-            .is_some());
-        assert!(protocol_request_err
-            .to_string()
-            .find("Failed to decode HTTP request or HTTP response")
-            .is_some());
-        assert!(protocol_response_err
-            .to_string()
-            .find("Failed to decode HTTP request or HTTP response")
-            .is_some());
+    #[test]
+    fn test_http_sponsorship_roundtrip() {
+        let sponsor = StacksAddress {
+            version: 26,
+            bytes: Hash160([0x22; 20]),
+        };
+        let request = HttpRequestType::GetSponsorship(
+            HttpRequestMetadata {
+                version: HttpVersion::Http11,
+                peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+                keep_alive: true,
+                cache_max_age: None,
+            },
+            sponsor.clone(),
+            Some(StacksBlockId([0x33; 32])),
+        );
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+            .unwrap();
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Request(request));
+
+        let response = HttpResponseType::GetSponsorship(
+            HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+            RPCSponsorshipData {
+                principal: sponsor.to_string(),
+                nonce: 3,
+                next_sponsor_nonce: 5,
+                pending_sponsored_txs: 2,
+                reserved_fees: 2000,
+                balance: format!("0x{}", to_hex(&1_000_000u128.to_be_bytes())),
+            },
+        );
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.begin_request(
+            HttpVersion::Http11,
+            format!("/v2/accounts/{}/sponsorship", &sponsor),
+        );
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
+            .unwrap();
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Response(response));
     }
 
     #[test]
-    fn test_http_headers_too_many() {
-        let mut too_many_headers_list = vec![];
-        for i in 0..HTTP_PREAMBLE_MAX_NUM_HEADERS {
-            too_many_headers_list.push(format!("H{}: {}\r\n", i + 1, i + 1));
-        }
-        let too_many_headers = too_many_headers_list.join("");
-        let bad_request_preamble = format!(
-            "GET /v2/neighbors HTTP/1.1\r\nHost: localhost:1234\r\n{}\r\n",
-            &too_many_headers
+    fn test_http_account_balance_roundtrip() {
+        let principal =
+            PrincipalData::parse("SP2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKNRV9EJ7.hello-world").unwrap();
+        let request = HttpRequestType::GetAccountBalance(
+            HttpRequestMetadata {
+                version: HttpVersion::Http11,
+                peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+                keep_alive: true,
+                cache_max_age: None,
+            },
+            principal.clone(),
+            Some(StacksBlockId([0x33; 32])),
         );
-        let bad_response_preamble = format!("HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-ID: 123\r\nContent-Type: text/plain\r\nContent-Length: 64\r\n{}\r\n", &too_many_headers);
 
-        let request_err =
-            HttpRequestPreamble::consensus_deserialize(&mut bad_request_preamble.as_bytes())
-                .unwrap_err();
-        let response_err =
-            HttpResponsePreamble::consensus_deserialize(&mut bad_response_preamble.as_bytes())
-                .unwrap_err();
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+            .unwrap();
 
-        let protocol_request_err =
-            StacksHttpPreamble::consensus_deserialize(&mut bad_request_preamble.as_bytes())
-                .unwrap_err();
-        let protocol_response_err =
-            StacksHttpPreamble::consensus_deserialize(&mut bad_response_preamble.as_bytes())
-                .unwrap_err();
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Request(request));
 
-        eprintln!("request_err: {:?}", &request_err);
-        eprintln!("response_err: {:?}", &response_err);
+        for vesting in [
+            None,
+            Some(vec![
+                RPCVestingEntry {
+                    block_height: 3132,
+                    amount_ustx: 4139394444,
+                },
+                RPCVestingEntry {
+                    block_height: 7452,
+                    amount_ustx: 4139394444,
+                },
+            ]),
+        ]
+        .iter()
+        {
+            let response = HttpResponseType::GetAccountBalance(
+                HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+                RPCAccountBalanceData {
+                    principal: principal.to_string(),
+                    balance: format!("0x{}", to_hex(&1_000_000u128.to_be_bytes())),
+                    locked: format!("0x{}", to_hex(&500_000u128.to_be_bytes())),
+                    unlock_height: 2100,
+                    stacks_block_height: 1000,
+                    burn_block_height: 2000,
+                    vesting: vesting.clone(),
+                    vesting_ustx: vesting
+                        .as_ref()
+                        .map(|entries| entries.iter().map(|e| e.amount_ustx).sum())
+                        .unwrap_or(0),
+                },
+            );
 
-        eprintln!("protocol_request_err: {:?}", &protocol_request_err);
-        eprintln!("protocol_response_err: {:?}", &protocol_response_err);
+            let mut bytes = vec![];
+            let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+            http.begin_request(
+                HttpVersion::Http11,
+                format!("/v2/accounts/{}/balance", &principal),
+            );
+            http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
+                .unwrap();
 
-        assert!(request_err
-            .to_string()
-            .find("Failed to parse HTTP request: TooManyHeaders")
-            .is_some());
-        assert!(response_err
-            .to_string()
-            .find("Failed to parse HTTP response: TooManyHeaders")
-            .is_some());
-        assert!(protocol_request_err
-            .to_string()
-            .find("Failed to decode HTTP request or HTTP response")
-            .is_some());
-        assert!(protocol_response_err
-            .to_string()
-            .find("Failed to decode HTTP request or HTTP response")
-            .is_some());
+            let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+            let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+            assert_eq!(message, StacksHttpMessage::Response(response));
+        }
     }
 
     #[test]
-    fn test_http_duplicate_concurrent_streamed_response_fails() {
-        // do not permit multiple in-flight chunk-encoded HTTP responses with the same request ID.
-        let valid_neighbors_response = "HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-Id: 123\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n28\r\n{\"sample\":[],\"inbound\":[],\"outbound\":[]}\r\n0\r\n\r\n";
-        let invalid_neighbors_response = "HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-Id: 123\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n10\r\nxxxxxxxxxxxxxxxx\r\n0\r\n\r\n";
-        let invalid_chunked_response = "HTTP/1.1 200 OK\r\nServer: stacks/v2.0\r\nX-Request-Id: 123\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n29\r\n{\"sample\":[],\"inbound\":[],\"outbound\":[]}\r\n0\r\n\r\n";
+    fn test_http_reward_set_roundtrip() {
+        let request = HttpRequestType::GetRewardSet(
+            HttpRequestMetadata {
+                version: HttpVersion::Http11,
+                peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+                keep_alive: true,
+                cache_max_age: None,
+            },
+            12,
+        );
 
+        let mut bytes = vec![];
         let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
-
-        http.begin_request(HttpVersion::Http11, "/v2/neighbors".to_string());
-        let (preamble, offset) = http
-            .read_preamble(valid_neighbors_response.as_bytes())
+        http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
             .unwrap();
-        assert_eq!(http.num_pending(), 1);
 
-        let res = http.read_preamble(valid_neighbors_response.as_bytes());
-        assert!(res.is_err());
-        assert!(res.unwrap_err().to_string().find("in progress").is_some());
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Request(request));
+
+        let response = HttpResponseType::GetRewardSet(
+            HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+            RPCRewardSetData {
+                reward_cycle: 12,
+                anchor_block_hash: BlockHeaderHash([0x11; 32]),
+                anchor_consensus_hash: ConsensusHash([0x22; 20]),
+                min_threshold_ustx: 10_000,
+                stacked_ustx: 25_000,
+                total_slots: 2,
+                entries: vec![
+                    RPCRewardSetEntry {
+                        pox_address: "mnr2vXtNhuZjaKLnFdXpk2PtRctTmdt4HU".to_string(),
+                        slots: 2,
+                        total_ustx: 20_000,
+                    },
+                    RPCRewardSetEntry {
+                        pox_address: "mpW4j9DRKh5ngbVABu2XzQV2dEny3tB5xd".to_string(),
+                        slots: 0,
+                        total_ustx: 5_000,
+                    },
+                ],
+            },
+        );
 
-        // finish reading the body
-        let msg = http
-            .stream_payload(
-                &preamble,
-                &mut &valid_neighbors_response.as_bytes()[offset..],
-            )
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.begin_request(HttpVersion::Http11, "/v2/pox/reward_set/12".to_string());
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
             .unwrap();
-        match msg {
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Response(response));
+    }
+
+    #[test]
+    fn test_http_sortition_roundtrip() {
+        let lookups = vec![
             (
-                Some((
-                    StacksHttpMessage::Response(HttpResponseType::Neighbors(_, neighbors_data)),
-                    _,
-                )),
-                _,
-            ) => assert_eq!(
-                neighbors_data,
-                RPCNeighborsInfo {
-                    sample: vec![],
-                    inbound: vec![],
-                    outbound: vec![]
-                }
+                SortitionLookup::BurnHeight(1234),
+                "/v2/sortitions/burn_height/1234".to_string(),
             ),
-            _ => {
-                error!("Got {:?}", &msg);
-                assert!(false);
-            }
-        }
-        assert_eq!(http.num_pending(), 0);
+            (
+                SortitionLookup::BurnHash(BurnchainHeaderHash([0x11; 32])),
+                format!(
+                    "/v2/sortitions/burn_hash/{}",
+                    &BurnchainHeaderHash([0x11; 32])
+                ),
+            ),
+            (
+                SortitionLookup::StacksBlock(StacksBlockId([0x22; 32])),
+                format!("/v2/sortitions/stacks_block/{}", &StacksBlockId([0x22; 32])),
+            ),
+        ];
+        for (lookup, path) in lookups.into_iter() {
+            let request = HttpRequestType::GetSortition(
+                HttpRequestMetadata {
+                    version: HttpVersion::Http11,
+                    peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+                    keep_alive: true,
+                    cache_max_age: None,
+                },
+                lookup,
+            );
+            assert_eq!(request.request_path(), path);
 
-        // can read the preamble again, but only once
-        http.begin_request(HttpVersion::Http11, "/v2/neighbors".to_string());
-        let (preamble, offset) = http
-            .read_preamble(invalid_neighbors_response.as_bytes())
+            let mut bytes = vec![];
+            let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+            http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+                .unwrap();
+
+            let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+            let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+            assert_eq!(message, StacksHttpMessage::Request(request));
+        }
+
+        let response = HttpResponseType::GetSortition(
+            HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+            RPCSortitionData {
+                burn_block_height: 1234,
+                burn_block_hash: format!("{}", &BurnchainHeaderHash([0x11; 32])),
+                burn_block_timestamp: 1_600_000_000,
+                parent_burn_block_hash: format!("{}", &BurnchainHeaderHash([0x10; 32])),
+                consensus_hash: ConsensusHash([0x33; 20]),
+                sortition_id: to_hex(&[0x44; 32]),
+                canonical: true,
+                sortition: true,
+                total_burn: 10_000,
+                winning_block_txid: Some(to_hex(&[0x55; 32])),
+                stacks_block_hash: Some(BlockHeaderHash([0x66; 32])),
+                index_block_hash: Some(StacksBlockId([0x22; 32])),
+                stacks_block_height: None,
+            },
+        );
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.begin_request(
+            HttpVersion::Http11,
+            "/v2/sortitions/burn_height/1234".to_string(),
+        );
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
             .unwrap();
-        assert_eq!(http.num_pending(), 1);
 
-        let res = http.read_preamble(valid_neighbors_response.as_bytes());
-        assert!(res.is_err());
-        assert!(res.unwrap_err().to_string().find("in progress").is_some());
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Response(response));
+    }
 
-        // reading a corrupt body unlocks the ability to read the preamble again
-        let res = http.stream_payload(
-            &preamble,
-            &mut &invalid_neighbors_response.as_bytes()[offset..],
+    #[test]
+    fn test_http_consensus_schedule_roundtrip() {
+        let request = HttpRequestType::GetConsensusSchedule(HttpRequestMetadata {
+            version: HttpVersion::Http11,
+            peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+            keep_alive: true,
+            cache_max_age: None,
+        });
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+            .unwrap();
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Request(request));
+
+        let genesis = ScheduledConsensusChange {
+            name: "stacks-2.0".to_string(),
+            activation_height: 100,
+            supported: true,
+        };
+        let upgrade = ScheduledConsensusChange {
+            name: "stacks-2.05".to_string(),
+            activation_height: 200,
+            supported: false,
+        };
+        let response = HttpResponseType::GetConsensusSchedule(
+            HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+            RPCConsensusScheduleData {
+                burn_block_height: 150,
+                active: Some(genesis.clone()),
+                next: Some(upgrade.clone()),
+                blocks_until_next: Some(50),
+                upgrade_required: true,
+                schedule: vec![genesis, upgrade],
+            },
         );
-        assert!(res.unwrap_err().to_string().find("JSON").is_some());
-        assert_eq!(http.num_pending(), 0);
 
-        // can read the premable again, but only once
-        http.begin_request(HttpVersion::Http11, "/v2/neighbors".to_string());
-        let (preamble, offset) = http
-            .read_preamble(invalid_chunked_response.as_bytes())
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.begin_request(HttpVersion::Http11, "/v2/consensus_schedule".to_string());
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
             .unwrap();
-        let res = http.read_preamble(valid_neighbors_response.as_bytes());
 
-        assert!(res.is_err());
-        assert!(res.unwrap_err().to_string().find("in progress").is_some());
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Response(response));
+    }
 
-        // reading a corrupt chunk stream unlocks the ability to read the preamble again
-        let res = http.stream_payload(
-            &preamble,
-            &mut &invalid_chunked_response.as_bytes()[offset..],
+    #[test]
+    fn test_http_microblock_equivocations_roundtrip() {
+        let request = HttpRequestType::GetMicroblockEquivocations(HttpRequestMetadata {
+            version: HttpVersion::Http11,
+            peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+            keep_alive: true,
+            cache_max_age: None,
+        });
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+            .unwrap();
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Request(request));
+
+        let response = HttpResponseType::GetMicroblockEquivocations(
+            HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+            RPCMicroblockEquivocationsData {
+                equivocations: vec![RPCMicroblockEquivocation {
+                    id: 1,
+                    parent_index_block_hash: StacksBlockId([0x11; 32]),
+                    sequence: 3,
+                    microblock_hash_1: BlockHeaderHash([0x22; 32]),
+                    microblock_hash_2: BlockHeaderHash([0x33; 32]),
+                    header_1: "00".to_string(),
+                    header_2: "01".to_string(),
+                    detected_at: 1234,
+                }],
+            },
         );
-        assert!(res
-            .unwrap_err()
-            .to_string()
-            .find("Invalid chunk trailer")
-            .is_some());
-        assert_eq!(http.num_pending(), 0);
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.begin_request(
+            HttpVersion::Http11,
+            "/v2/microblocks/equivocations".to_string(),
+        );
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
+            .unwrap();
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Response(response));
     }
 
     #[test]
-    fn test_http_request_version_keep_alive() {
-        let requests = vec![
-            HttpRequestPreamble::new(
-                HttpVersion::Http10,
-                "GET".to_string(),
-                "/v2/info".to_string(),
-                "localhost".to_string(),
-                8080,
-                true,
-            ),
-            HttpRequestPreamble::new(
-                HttpVersion::Http10,
-                "GET".to_string(),
-                "/v2/info".to_string(),
-                "localhost".to_string(),
-                8080,
-                false,
-            ),
-            HttpRequestPreamble::new(
-                HttpVersion::Http11,
-                "GET".to_string(),
-                "/v2/info".to_string(),
-                "localhost".to_string(),
-                8080,
-                true,
-            ),
-            HttpRequestPreamble::new(
-                HttpVersion::Http11,
-                "GET".to_string(),
-                "/v2/info".to_string(),
-                "localhost".to_string(),
-                8080,
-                false,
-            ),
-        ];
+    fn test_http_delegations_roundtrip() {
+        let delegatee = PrincipalData::from(StacksAddress {
+            version: 1,
+            bytes: Hash160([0x22; 20]),
+        });
+        let request = HttpRequestType::GetDelegations(
+            HttpRequestMetadata {
+                version: HttpVersion::Http11,
+                peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+                keep_alive: true,
+                cache_max_age: None,
+            },
+            delegatee.clone(),
+            None,
+        );
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+            .unwrap();
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Request(request));
+
+        let response = HttpResponseType::GetDelegations(
+            HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+            RPCDelegationsData {
+                delegated_to: delegatee.to_string(),
+                // larger than a u64
+                total_delegated_ustx: (u64::MAX as u128) + 2_000_001,
+                total_locked_ustx: 1_000_000,
+                delegators: vec![
+                    RPCDelegatorInfo {
+                        stacker: "ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R".to_string(),
+                        amount_ustx: 2_000_000,
+                        until_burn_height: Some(1234),
+                        pox_address: None,
+                        locked_ustx: 1_000_000,
+                        unlock_height: 1000,
+                    },
+                    RPCDelegatorInfo {
+                        stacker: "ST31DA6FTSJX2WGTZ69SFY11BH51NZMB0ZZ239N96".to_string(),
+                        amount_ustx: (u64::MAX as u128) + 1,
+                        until_burn_height: None,
+                        pox_address: Some("mnr2vXtNhuZjaKLnFdXpk2PtRctTmdt4HU".to_string()),
+                        locked_ustx: 0,
+                        unlock_height: 0,
+                    },
+                ],
+            },
+        );
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.begin_request(
+            HttpVersion::Http11,
+            format!("/v2/delegations/{}", &delegatee),
+        );
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
+            .unwrap();
 
-        // (have 'connection' header?, have 'keep-alive' value?)
-        let requests_connection_expected =
-            vec![(true, true), (false, false), (false, false), (true, false)];
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Response(response));
+    }
 
-        for (r, (has_connection, is_keep_alive)) in
-            requests.iter().zip(requests_connection_expected.iter())
-        {
+    #[test]
+    fn test_http_admin_toggles_roundtrip() {
+        let md = HttpRequestMetadata {
+            version: HttpVersion::Http11,
+            peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+            keep_alive: true,
+            cache_max_age: None,
+        };
+        let requests = vec![
+            HttpRequestType::GetAdminToggles(md.clone(), None),
+            HttpRequestType::GetAdminToggles(md.clone(), Some(BearerToken("s3cret".to_string()))),
+            HttpRequestType::PostAdminToggles(
+                md.clone(),
+                Some(BearerToken("s3cret".to_string())),
+                RPCAdminTogglesUpdate {
+                    tx_relay_paused: Some(true),
+                    debug_subsystems: Some(vec!["net::download".to_string()]),
+                    ..RPCAdminTogglesUpdate::default()
+                },
+            ),
+        ];
+        for request in requests.into_iter() {
             let mut bytes = vec![];
-            r.consensus_serialize(&mut bytes).unwrap();
-            let txt = String::from_utf8(bytes).unwrap();
+            let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+            http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+                .unwrap();
 
-            eprintln!(
-                "has_connection: {}, is_keep_alive: {}\n{}",
-                *has_connection, *is_keep_alive, &txt
-            );
-            if *has_connection {
-                if *is_keep_alive {
-                    assert!(txt.find("Connection: keep-alive\r\n").is_some());
-                } else {
-                    assert!(txt.find("Connection: close\r\n").is_some());
-                }
-            } else {
-                assert!(txt.find("Connection: ").is_none());
+            let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+            let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+            assert_eq!(message, StacksHttpMessage::Request(request));
+        }
+
+        // the token is taken from a standard Authorization header
+        let request_bytes = "POST /v2/admin/toggles HTTP/1.1\r\nHost: localhost:20443\r\nAuthorization: bearer s3cret\r\nContent-Type: application/json\r\nContent-Length: 22\r\n\r\n{\"mining_paused\":true}";
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        let (preamble, offset) = http.read_preamble(request_bytes.as_bytes()).unwrap();
+        let (message, _) = http
+            .read_payload(&preamble, &request_bytes.as_bytes()[offset..])
+            .unwrap();
+        match message {
+            StacksHttpMessage::Request(HttpRequestType::PostAdminToggles(_, token, update)) => {
+                assert_eq!(token, Some(BearerToken("s3cret".to_string())));
+                assert_eq!(update.mining_paused, Some(true));
+                assert_eq!(update.block_downloads_paused, None);
             }
+            _ => panic!("Unexpected message {:?}", &message),
         }
+
+        let response = HttpResponseType::AdminToggles(
+            HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+            RPCAdminTogglesData {
+                mining_paused: true,
+                block_downloads_paused: false,
+                tx_relay_paused: true,
+                debug_subsystems: vec!["net::download".to_string()],
+                low_disk_space: false,
+            },
+        );
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.begin_request(HttpVersion::Http11, "/v2/admin/toggles".to_string());
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
+            .unwrap();
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Response(response));
     }
 
     #[test]
-    fn test_http_response_version_keep_alive() {
-        // (version, explicit keep-alive?)
-        let responses_args = vec![
-            (HttpVersion::Http10, true),
-            (HttpVersion::Http10, false),
-            (HttpVersion::Http11, true),
-            (HttpVersion::Http11, false),
-        ];
+    fn test_http_transfer_stx_op_roundtrip() {
+        let md = HttpRequestMetadata {
+            version: HttpVersion::Http11,
+            peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+            keep_alive: true,
+            cache_max_age: None,
+        };
+        let body = RPCTransferStxOpRequest {
+            recipient: "ST2QKZ4FKHAH1NQKYKYAYZPY440FEPK7GZ1R5HBP2".to_string(),
+            amount_ustx: 1000,
+            memo: Some("00112233".to_string()),
+            fee_rate: None,
+        };
 
-        let mut responses = vec![];
-        for res in responses_args.iter() {
-            let mut bytes = vec![];
-            let md = HttpResponseMetadata::new(res.0.clone(), 123, None, res.1);
-            HttpResponsePreamble::new_serialized(
-                &mut bytes,
-                200,
-                "OK",
-                None,
-                &HttpContentType::JSON,
-                123,
-                |ref mut fd| keep_alive_headers(fd, &md),
-            )
+        let request = HttpRequestType::PostTransferStxOp(
+            md.clone(),
+            Some(BearerToken("s3cret".to_string())),
+            body,
+        );
+        assert!(!request.is_read_only());
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
             .unwrap();
-            responses.push(String::from_utf8(bytes).unwrap());
-        }
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Request(request));
+
+        let response = HttpResponseType::BurnOpSubmitted(
+            HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+            RPCBurnOpSubmissionData {
+                sender: "ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R".to_string(),
+                recipient: "ST2QKZ4FKHAH1NQKYKYAYZPY440FEPK7GZ1R5HBP2".to_string(),
+                amount_ustx: 1000,
+            },
+        );
 
-        for (response, (version, sent_keep_alive)) in responses.iter().zip(responses_args.iter()) {
-            test_debug!(
-                "version: {:?}, sent keep-alive: {}, response:\n{}",
-                version,
-                sent_keep_alive,
-                response
-            );
-            match version {
-                HttpVersion::Http10 => {
-                    // be explicit about Connection: with http/1.0 clients
-                    if *sent_keep_alive {
-                        assert!(response.find("Connection: keep-alive\r\n").is_some());
-                    } else {
-                        assert!(response.find("Connection: close\r\n").is_some());
-                    }
-                }
-                HttpVersion::Http11 => {
-                    if *sent_keep_alive {
-                        // we don't send connection: keep-alive if the client is 1.1 and it didn't
-                        // send its own connection: <option>
-                        assert!(response.find("Connection:").is_none());
-                    } else {
-                        assert!(response.find("Connection: close\r\n").is_some());
-                    }
-                }
-            }
-        }
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.begin_request(
+            HttpVersion::Http11,
+            "/v2/admin/burn_ops/transfer_stx".to_string(),
+        );
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
+            .unwrap();
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Response(response));
+
+        // the STX are always sent from the node's own signer, so a request naming a sender's key
+        // is refused instead of being sent from the wrong address
+        let json = r#"{"sender_key":"9e446f6b0c6a96cf2190e54bcd5a8569c3e386f091605499464389b8d4e0bfc201","recipient":"ST2QKZ4FKHAH1NQKYKYAYZPY440FEPK7GZ1R5HBP2","amount_ustx":1000}"#;
+        let raw = format!(
+            "POST /v2/admin/burn_ops/transfer_stx HTTP/1.1\r\nHost: www.foo.com:80\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            json.len(),
+            json
+        );
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        let (preamble, offset) = http.read_preamble(raw.as_bytes()).unwrap();
+        assert!(http
+            .read_payload(&preamble, &raw.as_bytes()[offset..])
+            .is_err());
     }
 
     #[test]
-    fn test_http_parse_proof_tip_query() {
-        let query_txt = "tip=7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392";
-        assert_eq!(
-            HttpRequestType::get_chain_tip_query(Some(query_txt)).unwrap(),
-            StacksBlockId::from_hex(
-                "7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392"
-            )
-            .unwrap()
+    fn test_http_miner_wallet_roundtrip() {
+        let md = HttpRequestMetadata {
+            version: HttpVersion::Http11,
+            peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+            keep_alive: true,
+            cache_max_age: None,
+        };
+        let request =
+            HttpRequestType::GetMinerWallet(md.clone(), Some(BearerToken("s3cret".to_string())));
+        assert!(request.is_read_only());
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+            .unwrap();
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Request(request));
+
+        let response = HttpResponseType::MinerWallet(
+            HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+            RPCMinerWalletData {
+                address: "mkvJ3xUHbjMFa3qjaXSRKtGbyrxe3JMeoi".to_string(),
+                balance_sats: 45_000,
+                utxo_count: 2,
+                spendable_utxo_count: 2,
+                commit_cost_sats: 10_000,
+                affordable_commits: 4,
+            },
         );
 
-        // first parseable tip is taken
-        let query_txt_dup = "tip=7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392&tip=03e26bd68a8722f8b3861e2058edcafde094ad059e152754986c3573306698f1";
-        assert_eq!(
-            HttpRequestType::get_chain_tip_query(Some(query_txt_dup)).unwrap(),
-            StacksBlockId::from_hex(
-                "7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392"
-            )
-            .unwrap()
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.begin_request(HttpVersion::Http11, "/v2/admin/miner/wallet".to_string());
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
+            .unwrap();
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Response(response));
+    }
+
+    #[test]
+    fn test_http_clarity_profiles_roundtrip() {
+        let md = HttpRequestMetadata {
+            version: HttpVersion::Http11,
+            peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+            keep_alive: true,
+            cache_max_age: None,
+        };
+        let request = HttpRequestType::GetClarityProfiles(
+            md.clone(),
+            Some(BearerToken("s3cret".to_string())),
         );
+        assert!(request.is_read_only());
 
-        // first parseable tip is taken
-        let query_txt_dup = "tip=bad&tip=7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392&tip=03e26bd68a8722f8b3861e2058edcafde094ad059e152754986c3573306698f1";
-        assert_eq!(
-            HttpRequestType::get_chain_tip_query(Some(query_txt_dup)).unwrap(),
-            StacksBlockId::from_hex(
-                "7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392"
-            )
-            .unwrap()
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+            .unwrap();
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Request(request));
+
+        let callee = FunctionKey {
+            contract: "SP000000000000000000002Q6VF78.pox".to_string(),
+            function: "stack-stx".to_string(),
+        };
+        let response = HttpResponseType::ClarityProfiles(
+            HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+            vec![BlockProfile {
+                index_block_hash: StacksBlockId([0x11; 32]),
+                block_height: 12,
+                processing_time_us: 4500,
+                contracts: vec![ContractProfile {
+                    contract: callee.contract.clone(),
+                    calls: 1,
+                    time_us: 1200,
+                    cost: ExecutionCost::runtime(3000),
+                }],
+                functions: vec![FunctionProfile {
+                    contract: callee.contract.clone(),
+                    function: callee.function.clone(),
+                    calls: 1,
+                    total_time_us: 1200,
+                    self_time_us: 1200,
+                    total_cost: ExecutionCost::runtime(3000),
+                    self_cost: ExecutionCost::runtime(3000),
+                }],
+                calls: vec![CallEdge {
+                    caller: None,
+                    callee: callee,
+                    calls: 1,
+                    total_time_us: 1200,
+                    total_cost: ExecutionCost::runtime(3000),
+                }],
+            }],
         );
 
-        // tip can be skipped
-        let query_txt_bad = "tip=bad";
-        assert_eq!(
-            HttpRequestType::get_chain_tip_query(Some(query_txt_bad)),
-            None
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.begin_request(
+            HttpVersion::Http11,
+            "/v2/admin/clarity_profiles".to_string(),
         );
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
+            .unwrap();
 
-        // tip can be skipped
-        let query_txt_none = "tip=bad";
-        assert_eq!(
-            HttpRequestType::get_chain_tip_query(Some(query_txt_none)),
-            None
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Response(response));
+    }
+
+    #[test]
+    fn test_http_block_proposal_roundtrip() {
+        let md = HttpRequestMetadata {
+            version: HttpVersion::Http11,
+            peer: PeerHost::DNS("www.foo.com".to_string(), 80),
+            keep_alive: true,
+            cache_max_age: None,
+        };
+        let request = HttpRequestType::PostBlockProposal(
+            md.clone(),
+            Some(BearerToken("s3cret".to_string())),
+            RPCBlockProposalRequest {
+                block: "00".repeat(100),
+                parent_consensus_hash: ConsensusHash([0x11; 20]),
+            },
+        );
+        assert!(!request.is_read_only());
+        assert_eq!(request.endpoint_class(), RPCEndpointClass::Evaluation);
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.write_message(&mut bytes, &StacksHttpMessage::Request(request.clone()))
+            .unwrap();
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Request(request));
+
+        let response = HttpResponseType::BlockProposalValidated(
+            HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true),
+            RPCBlockProposalData {
+                accepted: false,
+                block_hash: BlockHeaderHash([0x22; 32]),
+                parent_index_block_hash: Some(StacksBlockId([0x33; 32])),
+                stacks_block_height: 12,
+                tx_count: 2,
+                size: 1234,
+                fees_ustx: 300,
+                cost: Some(ExecutionCost::zero()),
+                rejections: vec![
+                    RPCBlockProposalRejection {
+                        reason: "bad_transaction".to_string(),
+                        message: "Transaction is invalid".to_string(),
+                        txid: Some("44".repeat(32)),
+                    },
+                    RPCBlockProposalRejection {
+                        reason: "bad_state_root".to_string(),
+                        message: "State root is wrong".to_string(),
+                        txid: None,
+                    },
+                ],
+            },
         );
+
+        let mut bytes = vec![];
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        http.begin_request(HttpVersion::Http11, "/v2/admin/block_proposals".to_string());
+        http.write_message(&mut bytes, &StacksHttpMessage::Response(response.clone()))
+            .unwrap();
+
+        let (preamble, offset) = http.read_preamble(&bytes).unwrap();
+        let message = read_test_payload(&mut http, &preamble, &bytes[offset..]);
+        assert_eq!(message, StacksHttpMessage::Response(response));
     }
 
     #[test]
@@ -6366,5 +9785,200 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_http_response_past_deadline() {
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        let md = HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true);
+        let response = HttpResponseType::TokenTransferCost(md, 1);
+
+        http.set_request_deadline(RPCRequestDeadline::new(
+            RPCEndpointClass::Metadata,
+            60_000,
+            get_epoch_time_ms(),
+        ));
+        let mut bytes = vec![];
+        response.send(&mut http, &mut bytes).unwrap();
+        assert!(String::from_utf8(bytes)
+            .unwrap()
+            .starts_with("HTTP/1.1 200"));
+        assert!(http.request_deadline().is_some());
+
+        // a response that's ready too late is replaced by a 503, and the deadline is used up
+        http.set_request_deadline(RPCRequestDeadline::new(RPCEndpointClass::Metadata, 1, 0));
+        let mut bytes = vec![];
+        response.send(&mut http, &mut bytes).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.starts_with("HTTP/1.1 503"));
+        assert!(text.ends_with(RPC_DEADLINE_EXCEEDED_MESSAGE));
+        assert!(http.request_deadline().is_none());
+
+        // a timeout of 0 means no deadline, and writes never get one
+        assert_eq!(
+            RPCRequestDeadline::new(RPCEndpointClass::Evaluation, 0, 0),
+            None
+        );
+        let tx_req = HttpRequestType::PostTransaction(
+            HttpRequestMetadata::new("127.0.0.1".to_string(), 20443),
+            make_codec_test_block(1).txs[0].clone(),
+            None,
+            false,
+        );
+        assert_eq!(tx_req.endpoint_class(), RPCEndpointClass::Write);
+        assert_eq!(
+            ConnectionOptions::default().rpc_deadline_ms(RPCEndpointClass::Write),
+            0
+        );
+    }
+
+    #[test]
+    fn test_http_response_size_limit() {
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        let md = HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true);
+        let response = HttpResponseType::GetContractSrc(
+            md.clone(),
+            ContractSrcResponse {
+                source: "(define-constant big 0x00)".repeat(1024),
+                publish_height: 1,
+                marf_proof: None,
+            },
+        );
+
+        let mut bytes = vec![];
+        response.send(&mut http, &mut bytes).unwrap();
+        let unlimited = String::from_utf8(bytes).unwrap();
+        assert!(unlimited.starts_with("HTTP/1.1 200"));
+
+        // a response over the limit is answered with a 413, and none of it is sent
+        http.max_response_size = 4096;
+        let mut bytes = vec![];
+        response.send(&mut http, &mut bytes).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.starts_with("HTTP/1.1 413 Payload Too Large"));
+        assert!(text.ends_with("Response is larger than this node's limit of 4096 bytes"));
+        assert!(!text.contains("define-constant"));
+
+        // one within the limit goes out as it would without one
+        http.max_response_size = unlimited.len() as u64;
+        let mut bytes = vec![];
+        response.send(&mut http, &mut bytes).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.starts_with("HTTP/1.1 200"));
+        assert_eq!(text.len(), unlimited.len());
+
+        // blocks are sized by consensus rules, so they go out however big
+        http.max_response_size = 16;
+        let block = make_codec_test_block(5);
+        let response = HttpResponseType::Block(md, block.clone());
+        let mut bytes = vec![];
+        response.send(&mut http, &mut bytes).unwrap();
+        assert!(String::from_utf8_lossy(&bytes).starts_with("HTTP/1.1 200"));
+        assert!(bytes.len() > block.serialize_to_vec().len());
+    }
+
+    #[test]
+    fn test_http_mirror_cache_headers() {
+        let md = HttpRequestMetadata::new("127.0.0.1".to_string(), 20443);
+        let addr = StacksAddress {
+            version: 1,
+            bytes: Hash160([0x11; 20]),
+        };
+        let name: ContractName = "hello-world".into();
+        let tip = StacksBlockId([0x22; 32]);
+
+        // data named by its hash, or state as of a given block, is immutable
+        assert!(HttpRequestType::GetBlock(md.clone(), tip.clone()).is_immutable(None));
+        assert!(HttpRequestType::GetAttachment(md.clone(), Hash160([0x33; 20])).is_immutable(None));
+        assert!(HttpRequestType::GetContractSrc(
+            md.clone(),
+            addr.clone(),
+            name.clone(),
+            Some(tip.clone()),
+            true
+        )
+        .is_immutable(None));
+
+        // state as of the unconfirmed chain tip changes with each microblock
+        assert!(!HttpRequestType::GetContractSrc(
+            md.clone(),
+            addr.clone(),
+            name.clone(),
+            Some(tip.clone()),
+            true
+        )
+        .is_immutable(Some(&tip)));
+        assert!(HttpRequestType::GetBlock(md.clone(), tip.clone()).is_immutable(Some(&tip)));
+
+        // state as of the chain tip, and writes, are not
+        assert!(!HttpRequestType::GetContractSrc(
+            md.clone(),
+            addr.clone(),
+            name.clone(),
+            None,
+            true
+        )
+        .is_immutable(None));
+        assert!(!HttpRequestType::GetAccount(
+            md.clone(),
+            addr.to_account_principal(),
+            TipRequest::UseLatestAnchoredTip,
+            true
+        )
+        .is_immutable(None));
+        assert!(!HttpRequestType::GetInfo(md.clone()).is_immutable(None));
+        assert!(!HttpRequestType::PostTransaction(
+            md.clone(),
+            make_codec_test_block(1).txs[0].clone(),
+            None,
+            false
+        )
+        .is_immutable(None));
+
+        let mut http = StacksHttp::new("127.0.0.1:20443".parse().unwrap());
+        let response_md = HttpResponseMetadata::new(HttpVersion::Http11, 123, None, true);
+        let response = HttpResponseType::GetContractSrc(
+            response_md.clone(),
+            ContractSrcResponse {
+                source: "(define-constant hello 0x00)".to_string(),
+                publish_height: 1,
+                marf_proof: None,
+            },
+        );
+        let mut bytes = vec![];
+        response.send(&mut http, &mut bytes).unwrap();
+        assert!(!String::from_utf8(bytes).unwrap().contains("Cache-Control"));
+
+        // caches may keep successful answers...
+        let mut cached_md = HttpRequestMetadata::new("127.0.0.1".to_string(), 20443);
+        cached_md.cache_max_age = Some(3600);
+        let response_md = HttpResponseMetadata::from(&HttpRequestType::GetContractSrc(
+            cached_md,
+            addr.clone(),
+            name.clone(),
+            Some(tip.clone()),
+            true,
+        ));
+        let response = HttpResponseType::GetContractSrc(
+            response_md.clone(),
+            ContractSrcResponse {
+                source: "(define-constant hello 0x00)".to_string(),
+                publish_height: 1,
+                marf_proof: None,
+            },
+        );
+        let mut bytes = vec![];
+        response.send(&mut http, &mut bytes).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.starts_with("HTTP/1.1 200"));
+        assert!(text.contains("Cache-Control: public, max-age=3600\r\n"));
+
+        // ...but not errors
+        let response = HttpResponseType::NotFound(response_md, "No such contract".to_string());
+        let mut bytes = vec![];
+        response.send(&mut http, &mut bytes).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.starts_with("HTTP/1.1 404"));
+        assert!(!text.contains("Cache-Control"));
+    }
+
     // TODO: test mismatch between request path and reply
 }
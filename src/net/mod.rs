@@ -55,8 +55,11 @@ use clarity_vm::clarity::Error as clarity_error;
 use codec::Error as codec_error;
 use codec::StacksMessageCodec;
 use core::mempool::*;
+use core::schedule::ScheduledConsensusChange;
 use core::POX_REWARD_CYCLE_LENGTH;
+use net::admin::BearerToken;
 use net::atlas::{Attachment, AttachmentInstance};
+use util::bloom::BloomFilter;
 use util::db::DBConn;
 use util::db::Error as db_error;
 use util::get_epoch_time_secs;
@@ -69,7 +72,10 @@ use util::secp256k1::MessageSignature;
 use util::secp256k1::Secp256k1PublicKey;
 use util::secp256k1::MESSAGE_SIGNATURE_ENCODED_SIZE;
 use util::strings::UrlString;
-use vm::types::TraitIdentifier;
+use vm::costs::ExecutionCost;
+use vm::database::ClaritySerializable;
+use vm::profiler::BlockProfile;
+use vm::types::{QualifiedContractIdentifier, TraitIdentifier};
 use vm::{
     analysis::contract_interface_builder::ContractInterface, types::PrincipalData, ClarityName,
     ContractName, Value,
@@ -85,9 +91,12 @@ use crate::util::hash::Sha256Sum;
 use self::dns::*;
 pub use self::http::StacksHttp;
 
+pub mod admin;
 pub mod asn;
 pub mod atlas;
 pub mod chat;
+#[cfg(any(test, feature = "stacks-node-client"))]
+pub mod client;
 pub mod codec;
 pub mod connection;
 pub mod db;
@@ -99,6 +108,8 @@ pub mod neighbors;
 pub mod p2p;
 pub mod poll;
 pub mod prune;
+pub mod push_queue;
+pub mod readonly_pool;
 pub mod relay;
 pub mod rpc;
 pub mod server;
@@ -790,6 +801,8 @@ pub struct HandshakeData {
 pub enum ServiceFlags {
     RELAY = 0x01,
     RPC = 0x02,
+    /// understands `TransactionsFilter` messages
+    TX_FILTER = 0x04,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -855,6 +868,8 @@ pub enum StacksMessageType {
     Pong(PongData),
     NatPunchRequest(u32),
     NatPunchReply(NatPunchData),
+    /// the transactions the sender recently accepted into its mempool
+    TransactionsFilter(BloomFilter),
 }
 
 /// Peer address variants
@@ -956,6 +971,24 @@ impl PeerHost {
     }
 }
 
+/// A selected PoX anchor block that this node does not have
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCMissingPoxAnchor {
+    pub reward_cycle: u64,
+    pub anchor_block_hash: BlockHeaderHash,
+}
+
+/// Whether or not this node's view of the PoX anchor blocks is complete
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCPoxAnchorStatus {
+    /// true if this node is missing at least one selected PoX anchor block, and so cannot agree
+    /// with the rest of the network on the canonical Stacks chain tip
+    pub stalled: bool,
+    pub missing_anchors: Vec<RPCMissingPoxAnchor>,
+    /// reward cycles whose PoX anchor block the node operator has overridden
+    pub overridden_reward_cycles: Vec<u64>,
+}
+
 /// The data we return on GET /v2/info
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RPCPeerInfoData {
@@ -974,6 +1007,9 @@ pub struct RPCPeerInfoData {
     pub unanchored_tip: StacksBlockId,
     pub unanchored_seq: u16,
     pub exit_at_block_height: Option<u64>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pox_anchor_status: Option<RPCPoxAnchorStatus>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -1020,6 +1056,346 @@ pub struct RPCPoxInfoData {
     pub next_reward_cycle_in: u64,
 }
 
+/// One PoX address in a reward cycle's reward set
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCRewardSetEntry {
+    pub pox_address: String,
+    pub slots: u64,
+    pub total_ustx: u64,
+}
+
+/// The data we return on GET /v2/pox/reward_set/:reward_cycle
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCRewardSetData {
+    pub reward_cycle: u64,
+    pub anchor_block_hash: BlockHeaderHash,
+    pub anchor_consensus_hash: ConsensusHash,
+    pub min_threshold_ustx: u64,
+    pub stacked_ustx: u64,
+    pub total_slots: u64,
+    pub entries: Vec<RPCRewardSetEntry>,
+}
+
+/// The data we return on GET /v2/sortitions/:lookup/:value
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCSortitionData {
+    pub burn_block_height: u64,
+    pub burn_block_hash: String,
+    pub burn_block_timestamp: u64,
+    pub parent_burn_block_hash: String,
+    pub consensus_hash: ConsensusHash,
+    pub sortition_id: String,
+    /// whether the burnchain block is on the canonical sortition fork
+    pub canonical: bool,
+    /// whether a block-commit won the sortition
+    pub sortition: bool,
+    pub total_burn: u64,
+    /// the winning block-commit, and the Stacks block it committed to
+    pub winning_block_txid: Option<String>,
+    pub stacks_block_hash: Option<BlockHeaderHash>,
+    pub index_block_hash: Option<StacksBlockId>,
+    /// the winning Stacks block's height, once this node has processed it
+    pub stacks_block_height: Option<u64>,
+}
+
+/// The data we return on GET /v2/consensus_schedule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCConsensusScheduleData {
+    pub burn_block_height: u64,
+    /// The most recent change to have activated, if any
+    pub active: Option<ScheduledConsensusChange>,
+    /// The next change to activate, if any
+    pub next: Option<ScheduledConsensusChange>,
+    pub blocks_until_next: Option<u64>,
+    /// Whether this binary will stop following the chain at an upcoming change
+    pub upgrade_required: bool,
+    pub schedule: Vec<ScheduledConsensusChange>,
+}
+
+/// A pair of conflicting microblocks signed by the same leader
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCMicroblockEquivocation {
+    pub id: i64,
+    /// The anchored block the forked stream builds off of
+    pub parent_index_block_hash: StacksBlockId,
+    pub sequence: u16,
+    pub microblock_hash_1: BlockHeaderHash,
+    pub microblock_hash_2: BlockHeaderHash,
+    /// The consensus-serialized headers, in hex.  Together they are the proof for a
+    /// `PoisonMicroblock` transaction.
+    pub header_1: String,
+    pub header_2: String,
+    pub detected_at: u64,
+}
+
+/// The data we return on GET /v2/microblocks/equivocations
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCMicroblockEquivocationsData {
+    /// Most recently detected first
+    pub equivocations: Vec<RPCMicroblockEquivocation>,
+}
+
+/// The data we return on GET and POST /v2/admin/toggles
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCAdminTogglesData {
+    pub mining_paused: bool,
+    pub block_downloads_paused: bool,
+    pub tx_relay_paused: bool,
+    pub debug_subsystems: Vec<String>,
+    /// Set by the node (not through the admin endpoints) while it is low on disk space
+    #[serde(default)]
+    pub low_disk_space: bool,
+}
+
+/// The body of a POST to /v2/admin/toggles.  Toggles that are not given are left as they are.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct RPCAdminTogglesUpdate {
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mining_paused: Option<bool>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_downloads_paused: Option<bool>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tx_relay_paused: Option<bool>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug_subsystems: Option<Vec<String>>,
+}
+
+/// The body of a POST to /v2/admin/burn_ops/transfer_stx.  The STX are sent from the node's own
+/// burnchain signer, so unknown fields (such as a sender's key) are refused rather than ignored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RPCTransferStxOpRequest {
+    pub recipient: String,
+    pub amount_ustx: u64,
+    /// Hex-encoded, at most 61 bytes
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+    /// Satoshis per byte; the node's configured fee rate if not given
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_rate: Option<u64>,
+}
+
+/// A burn operation request the node has queued for its burnchain thread to send
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCBurnOpSubmissionData {
+    pub sender: String,
+    pub recipient: String,
+    pub amount_ustx: u64,
+}
+
+/// The miner's burnchain wallet, and how many more block-commits it can pay for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCMinerWalletData {
+    pub address: String,
+    /// Total of the wallet's UTXOs, in satoshis
+    pub balance_sats: u64,
+    pub utxo_count: u64,
+    /// UTXOs big enough to pay for a block-commit on their own
+    pub spendable_utxo_count: u64,
+    /// What one block-commit costs at the current fee settings: the most the miner will burn,
+    /// plus the transaction fee
+    pub commit_cost_sats: u64,
+    /// How many block-commits the wallet can pay for before it runs dry
+    pub affordable_commits: u64,
+}
+
+/// The body of a POST to /v2/admin/block_proposals
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCBlockProposalRequest {
+    /// Hex-encoded, consensus-serialized anchored block
+    pub block: String,
+    /// Consensus hash of the parent block's sortition
+    pub parent_consensus_hash: ConsensusHash,
+}
+
+/// One reason a proposed block would not be accepted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCBlockProposalRejection {
+    /// Short, stable name for the rejection, e.g. `bad_state_root`
+    pub reason: String,
+    pub message: String,
+    /// The transaction the rejection is about, if it is about one
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub txid: Option<String>,
+}
+
+/// Whether a proposed block would be accepted on top of its parent, and if not, why not
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCBlockProposalData {
+    pub accepted: bool,
+    pub block_hash: BlockHeaderHash,
+    /// Index block hash of the parent, if the node has processed it
+    pub parent_index_block_hash: Option<StacksBlockId>,
+    pub stacks_block_height: u64,
+    pub tx_count: u64,
+    /// Consensus-serialized size in bytes
+    pub size: u64,
+    /// Fees paid by the block's transactions, if they were run
+    pub fees_ustx: u64,
+    /// Execution cost of the block and the microblocks it confirms, if they were run
+    pub cost: Option<ExecutionCost>,
+    pub rejections: Vec<RPCBlockProposalRejection>,
+}
+
+/// A principal's active delegation, as recorded in the PoX contract
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCDelegationInfo {
+    pub delegated_to: String,
+    pub amount_ustx: u64,
+    pub until_burn_height: Option<u64>,
+    pub pox_address: Option<String>,
+}
+
+/// The data we return on GET /v2/accounts/:principal/sponsorship
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCSponsorshipData {
+    pub principal: String,
+    /// the account's nonce at the chain tip
+    pub nonce: u64,
+    /// the nonce to sign the next transaction with, after those pending in the mempool
+    pub next_sponsor_nonce: u64,
+    /// how many transactions pending in the mempool the account sponsors
+    pub pending_sponsored_txs: u64,
+    /// the fees the account will pay for its pending transactions, its own as well as those it
+    /// sponsors, in microSTX
+    pub reserved_fees: u64,
+    /// the STX the account can spend at the chain tip, hex-encoded like `/v2/accounts`' balance
+    pub balance: String,
+}
+
+/// A genesis lockup an account has yet to receive
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCVestingEntry {
+    /// the Stacks block height it unlocks at
+    pub block_height: u64,
+    pub amount_ustx: u64,
+}
+
+/// The data we return on GET /v2/accounts/:principal/balance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCAccountBalanceData {
+    pub principal: String,
+    /// the STX the account can spend, hex-encoded like `/v2/accounts`' balance
+    pub balance: String,
+    /// the STX locked by stacking, hex-encoded
+    pub locked: String,
+    /// the burnchain block height the locked STX unlock at, or 0 if none are locked
+    pub unlock_height: u64,
+    /// the chain tip the balances are as of
+    pub stacks_block_height: u64,
+    pub burn_block_height: u64,
+    /// the genesis lockups the account has yet to receive, in unlock order, or None if this node
+    /// does not know the genesis lockup schedule
+    pub vesting: Option<Vec<RPCVestingEntry>>,
+    /// their total, in microSTX
+    pub vesting_ustx: u64,
+}
+
+/// The data we return on GET /v2/stacking/:principal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCStackingStatusData {
+    pub principal: String,
+    pub is_stacking: bool,
+    pub locked_ustx: u64,
+    pub unlock_height: u64,
+    pub first_reward_cycle: Option<u64>,
+    pub lock_period: Option<u64>,
+    pub pox_address: Option<String>,
+    pub delegation: Option<RPCDelegationInfo>,
+}
+
+/// A principal that has delegated to a delegatee, and how much of its stake the delegatee has
+/// locked so far
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCDelegatorInfo {
+    pub stacker: String,
+    pub amount_ustx: u128,
+    pub until_burn_height: Option<u128>,
+    pub pox_address: Option<String>,
+    pub locked_ustx: u128,
+    pub unlock_height: u64,
+}
+
+/// The data we return on GET /v2/delegations/:principal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCDelegationsData {
+    pub delegated_to: String,
+    pub total_delegated_ustx: u128,
+    pub total_locked_ustx: u128,
+    pub delegators: Vec<RPCDelegatorInfo>,
+}
+
+/// RPC endpoints, grouped by how much work answering them takes.  Each class gets its own
+/// request deadline (see `ConnectionOptions::rpc_deadline_ms()`).
+#[derive(Debug, Clone, PartialEq, Copy, Hash)]
+pub enum RPCEndpointClass {
+    /// Node and chain metadata that is cheap to look up
+    Metadata,
+    /// Reads of chain state at a tip, possibly with MARF proofs
+    ChainState,
+    /// Evaluating Clarity code: read-only calls, simulations and post-condition previews
+    Evaluation,
+    /// Blocks, microblocks and attachments (up to the point their responses begin streaming)
+    Bulk,
+    /// Requests that store new data.  These never have a deadline, so that a request whose data
+    /// was stored is never answered as if it had timed out.
+    Write,
+}
+
+impl RPCEndpointClass {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            RPCEndpointClass::Metadata => "metadata",
+            RPCEndpointClass::ChainState => "chain_state",
+            RPCEndpointClass::Evaluation => "evaluation",
+            RPCEndpointClass::Bulk => "bulk",
+            RPCEndpointClass::Write => "write",
+        }
+    }
+}
+
+/// When the RPC request being handled must be answered by.  Past its deadline, a request is
+/// answered with a 503 at the next stage boundary instead of finishing, so that slow queries do
+/// not tie up their connections.
+#[derive(Debug, Clone, PartialEq, Copy)]
+pub struct RPCRequestDeadline {
+    pub class: RPCEndpointClass,
+    /// Epoch time, in milliseconds
+    pub expires_at_ms: u128,
+}
+
+impl RPCRequestDeadline {
+    /// A deadline `timeout_ms` after `now_ms`, or None if `timeout_ms` is 0 (no deadline)
+    pub fn new(
+        class: RPCEndpointClass,
+        timeout_ms: u64,
+        now_ms: u128,
+    ) -> Option<RPCRequestDeadline> {
+        if timeout_ms == 0 {
+            return None;
+        }
+        Some(RPCRequestDeadline {
+            class,
+            expires_at_ms: now_ms + (timeout_ms as u128),
+        })
+    }
+
+    pub fn is_expired(&self, now_ms: u128) -> bool {
+        self.expires_at_ms <= now_ms
+    }
+}
+
+/// What a request that missed its deadline is answered with, along with a 503
+pub const RPC_DEADLINE_EXCEEDED_MESSAGE: &'static str =
+    "Request could not be answered before its deadline; try again later";
+
 #[derive(Debug, Clone, PartialEq, Copy, Hash)]
 #[repr(u8)]
 pub enum HttpVersion {
@@ -1032,11 +1408,18 @@ pub struct HttpRequestMetadata {
     pub version: HttpVersion,
     pub peer: PeerHost,
     pub keep_alive: bool,
+    /// If set, a successful response to this request tells caches they may keep it for this many
+    /// seconds.  Not part of the request itself; the node sets it when it decides to allow caching.
+    pub cache_max_age: Option<u64>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MapEntryResponse {
     pub data: String,
+    /// `data`, in the canonical JSON encoding of Clarity values
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_json: Option<serde_json::Value>,
     #[serde(rename = "proof")]
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -1064,11 +1447,66 @@ pub struct CallReadOnlyResponse {
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub result: Option<String>,
+    /// `result`, in the canonical JSON encoding of Clarity values
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_json: Option<serde_json::Value>,
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cause: Option<String>,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SimulateContractCallResponse {
+    pub okay: bool,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cause: Option<String>,
+    #[serde(default)]
+    pub events: Vec<serde_json::Value>,
+    pub execution_cost: ExecutionCost,
+}
+
+/// The body of a POST to /v2/transactions/postconditions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PostConditionsPreviewRequestBody {
+    /// The hex-encoded transaction.  It does not need to be signed.
+    pub tx: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCPostConditionResult {
+    /// Position of the post-condition in the transaction
+    pub index: usize,
+    pub passed: bool,
+}
+
+/// The data we return on POST /v2/transactions/postconditions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCPostConditionsPreviewData {
+    pub txid: String,
+    /// Whether the transaction ran in the preview.  If it did not, `cause` says why, and its
+    /// post-conditions were not evaluated.
+    pub okay: bool,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cause: Option<String>,
+    /// Hex-encoded result of the contract call or STX transfer
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+    /// Whether the post-conditions would abort the transaction
+    pub would_abort: bool,
+    /// "allow" or "deny"
+    pub post_condition_mode: String,
+    pub post_conditions: Vec<RPCPostConditionResult>,
+    /// In deny mode, the assets the transaction moves that no post-condition covers
+    pub unchecked_assets: Vec<String>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AccountEntryResponse {
     pub balance: String,
@@ -1083,6 +1521,102 @@ pub struct AccountEntryResponse {
     pub nonce_proof: Option<String>,
 }
 
+/// A transaction in an account's history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCAccountTransaction {
+    pub txid: String,
+    pub index_block_hash: String,
+    pub block_height: u64,
+    /// Position of the transaction among the ones its block processed
+    pub tx_index: u32,
+    /// Hex-encoded transaction; absent for burnchain operations
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub tx: Option<String>,
+    /// Hex-encoded Clarity result
+    pub result: String,
+}
+
+/// Where the next page of an account's history starts; the fields are the querystring
+/// parameters to pass to get it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCAccountTransactionsNextPage {
+    pub before_height: u64,
+    pub before_tx_index: u32,
+}
+
+/// The data we return on GET /v2/accounts/{principal}/transactions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCAccountTransactionsData {
+    pub limit: u64,
+    /// Most recent first
+    pub results: Vec<RPCAccountTransaction>,
+    /// Absent if this is the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub next: Option<RPCAccountTransactionsNextPage>,
+}
+
+/// The data we return on GET /v2/receipts/{txid}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCTransactionReceiptData {
+    pub txid: String,
+    pub index_block_hash: String,
+    pub block_height: u64,
+    /// Position of the transaction among the ones its block processed
+    pub tx_index: u32,
+    /// Hex-encoded Clarity result
+    pub result: String,
+    pub post_condition_aborted: bool,
+    /// Decimal number of microSTX burned
+    pub stx_burned: String,
+    pub execution_cost: ExecutionCost,
+    /// Why the VM aborted the transaction, if it did
+    #[serde(default)]
+    pub vm_error: Option<serde_json::Value>,
+    /// In the form sent to event observers
+    pub events: Vec<serde_json::Value>,
+}
+
+/// A value a contract printed
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCContractEvent {
+    pub txid: String,
+    pub index_block_hash: String,
+    pub block_height: u64,
+    /// Position of the transaction among the ones its block processed
+    pub tx_index: u32,
+    /// Position of the event among the transaction's events
+    pub event_index: u32,
+    /// Name of the first key of a printed tuple; absent for other values
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub topic: Option<String>,
+    /// Hex-encoded Clarity value
+    pub value: String,
+}
+
+/// Where the next page of a contract's printed values starts; the fields are the querystring
+/// parameters to pass to get it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCContractEventsNextPage {
+    pub before_height: u64,
+    pub before_tx_index: u32,
+    pub before_event_index: u32,
+}
+
+/// The data we return on GET /v2/contracts/events/{address}/{name}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCContractEventsData {
+    pub limit: u64,
+    /// Most recent first
+    pub results: Vec<RPCContractEvent>,
+    /// Absent if this is the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub next: Option<RPCContractEventsNextPage>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum UnconfirmedTransactionStatus {
     Microblock {
@@ -1150,6 +1684,7 @@ impl HttpRequestMetadata {
             version: HttpVersion::Http11,
             peer: PeerHost::from_host_port(host, port),
             keep_alive: true,
+            cache_max_age: None,
         }
     }
 
@@ -1158,6 +1693,7 @@ impl HttpRequestMetadata {
             version: HttpVersion::Http11,
             peer: peer_host,
             keep_alive: true,
+            cache_max_age: None,
         }
     }
 
@@ -1166,14 +1702,113 @@ impl HttpRequestMetadata {
             version: preamble.version,
             peer: preamble.host.clone(),
             keep_alive: preamble.keep_alive,
+            cache_max_age: None,
         }
     }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct CallReadOnlyRequestBody {
+    pub sender: String,
+    /// Each argument is either a hex string of the consensus-serialized value, or the value's
+    /// canonical JSON encoding.
+    pub arguments: Vec<serde_json::Value>,
+}
+
+/// A single piece of Clarity state to overwrite in the scratch fork of a simulated contract call
+#[derive(Debug, Clone, PartialEq)]
+pub enum ClarityStateOverride {
+    /// (contract, variable name, new value)
+    DataVar(QualifiedContractIdentifier, ClarityName, Value),
+    /// (contract, map name, key, new value)
+    MapEntry(QualifiedContractIdentifier, ClarityName, Value, Value),
+    /// (principal, new unlocked balance)
+    STXBalance(PrincipalData, u128),
+}
+
+/// JSON encoding of a ClarityStateOverride.  Clarity values are hex-encoded consensus
+/// serializations, just like the arguments to a read-only call.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StateOverrideBody {
+    DataVar {
+        contract: String,
+        name: String,
+        value: String,
+    },
+    MapEntry {
+        contract: String,
+        name: String,
+        key: String,
+        value: String,
+    },
+    StxBalance {
+        principal: String,
+        amount: String,
+    },
+}
+
+impl ClarityStateOverride {
+    pub fn from_body(body: StateOverrideBody) -> Option<ClarityStateOverride> {
+        match body {
+            StateOverrideBody::DataVar {
+                contract,
+                name,
+                value,
+            } => Some(ClarityStateOverride::DataVar(
+                QualifiedContractIdentifier::parse(&contract).ok()?,
+                ClarityName::try_from(name).ok()?,
+                Value::try_deserialize_hex_untyped(&value).ok()?,
+            )),
+            StateOverrideBody::MapEntry {
+                contract,
+                name,
+                key,
+                value,
+            } => Some(ClarityStateOverride::MapEntry(
+                QualifiedContractIdentifier::parse(&contract).ok()?,
+                ClarityName::try_from(name).ok()?,
+                Value::try_deserialize_hex_untyped(&key).ok()?,
+                Value::try_deserialize_hex_untyped(&value).ok()?,
+            )),
+            StateOverrideBody::StxBalance { principal, amount } => {
+                Some(ClarityStateOverride::STXBalance(
+                    PrincipalData::parse(&principal).ok()?,
+                    amount.parse::<u128>().ok()?,
+                ))
+            }
+        }
+    }
+
+    pub fn to_body(&self) -> StateOverrideBody {
+        match self {
+            ClarityStateOverride::DataVar(contract, name, value) => StateOverrideBody::DataVar {
+                contract: contract.to_string(),
+                name: name.to_string(),
+                value: ClaritySerializable::serialize(value),
+            },
+            ClarityStateOverride::MapEntry(contract, name, key, value) => {
+                StateOverrideBody::MapEntry {
+                    contract: contract.to_string(),
+                    name: name.to_string(),
+                    key: ClaritySerializable::serialize(key),
+                    value: ClaritySerializable::serialize(value),
+                }
+            }
+            ClarityStateOverride::STXBalance(principal, amount) => StateOverrideBody::StxBalance {
+                principal: principal.to_string(),
+                amount: amount.to_string(),
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SimulateContractCallRequestBody {
     pub sender: String,
     pub arguments: Vec<String>,
+    #[serde(default)]
+    pub overrides: Vec<StateOverrideBody>,
 }
 
 /// Items in the NeighborsInfo -- combines NeighborKey and NeighborAddress
@@ -1209,6 +1844,29 @@ pub struct RPCNeighborsInfo {
     pub outbound: Vec<RPCNeighbor>,
 }
 
+/// Which chain tip a request is answered against, as given by its `tip` query parameter
+#[derive(Debug, Clone, PartialEq)]
+pub enum TipRequest {
+    /// No `tip` given: the canonical anchored chain tip
+    UseLatestAnchoredTip,
+    /// `tip=unconfirmed`: the canonical chain tip's processed microblock stream, or the canonical
+    /// anchored chain tip if this node has no unconfirmed state for it
+    UseLatestUnconfirmedTip,
+    /// `tip=<index block hash>`
+    SpecificTip(StacksBlockId),
+}
+
+/// How GET /v2/sortitions names the sortition to look up
+#[derive(Debug, Clone, PartialEq)]
+pub enum SortitionLookup {
+    /// `burn_height/<height>`: the canonical burnchain block at this height
+    BurnHeight(u64),
+    /// `burn_hash/<hash>`: the burnchain block with this hash
+    BurnHash(BurnchainHeaderHash),
+    /// `stacks_block/<index block hash>`: the sortition this Stacks block won
+    StacksBlock(StacksBlockId),
+}
+
 /// All HTTP request paths we support, and the arguments they carry in their paths
 #[derive(Debug, Clone, PartialEq)]
 pub enum HttpRequestType {
@@ -1220,14 +1878,52 @@ pub enum HttpRequestType {
     GetMicroblocksConfirmed(HttpRequestMetadata, StacksBlockId),
     GetMicroblocksUnconfirmed(HttpRequestMetadata, StacksBlockId, u16),
     GetTransactionUnconfirmed(HttpRequestMetadata, Txid),
-    PostTransaction(HttpRequestMetadata, StacksTransaction, Option<Attachment>),
+    /// transaction, attachment, and whether to answer with a `TransactionAccepted`
+    PostTransaction(
+        HttpRequestMetadata,
+        StacksTransaction,
+        Option<Attachment>,
+        bool,
+    ),
     PostBlock(HttpRequestMetadata, ConsensusHash, StacksBlock),
     PostMicroblock(HttpRequestMetadata, StacksMicroblock, Option<StacksBlockId>),
-    GetAccount(
+    GetAccount(HttpRequestMetadata, PrincipalData, TipRequest, bool),
+    /// principal, (block height, tx index) to list the transactions before, limit, tip
+    GetAccountTransactions(
         HttpRequestMetadata,
         PrincipalData,
+        Option<(u64, u32)>,
+        u64,
         Option<StacksBlockId>,
-        bool,
+    ),
+    GetSponsorship(HttpRequestMetadata, StacksAddress, Option<StacksBlockId>),
+    GetAccountBalance(HttpRequestMetadata, PrincipalData, Option<StacksBlockId>),
+    /// txid, tip
+    GetTransactionReceipt(HttpRequestMetadata, Txid, Option<StacksBlockId>),
+    GetStackingStatus(HttpRequestMetadata, PrincipalData, Option<StacksBlockId>),
+    GetDelegations(HttpRequestMetadata, PrincipalData, Option<StacksBlockId>),
+    GetRewardSet(HttpRequestMetadata, u64),
+    GetSortition(HttpRequestMetadata, SortitionLookup),
+    GetConsensusSchedule(HttpRequestMetadata),
+    GetMicroblockEquivocations(HttpRequestMetadata),
+    /// Admin requests carry the bearer token from their `Authorization` header, if any
+    GetAdminToggles(HttpRequestMetadata, Option<BearerToken>),
+    PostAdminToggles(
+        HttpRequestMetadata,
+        Option<BearerToken>,
+        RPCAdminTogglesUpdate,
+    ),
+    PostTransferStxOp(
+        HttpRequestMetadata,
+        Option<BearerToken>,
+        RPCTransferStxOpRequest,
+    ),
+    GetMinerWallet(HttpRequestMetadata, Option<BearerToken>),
+    GetClarityProfiles(HttpRequestMetadata, Option<BearerToken>),
+    PostBlockProposal(
+        HttpRequestMetadata,
+        Option<BearerToken>,
+        RPCBlockProposalRequest,
     ),
     GetMapEntry(
         HttpRequestMetadata,
@@ -1235,7 +1931,7 @@ pub enum HttpRequestType {
         ContractName,
         ClarityName,
         Value,
-        Option<StacksBlockId>,
+        TipRequest,
         bool,
     ),
     CallReadOnlyFunction(
@@ -1245,8 +1941,19 @@ pub enum HttpRequestType {
         PrincipalData,
         ClarityName,
         Vec<Value>,
+        TipRequest,
+    ),
+    SimulateContractCall(
+        HttpRequestMetadata,
+        StacksAddress,
+        ContractName,
+        PrincipalData,
+        ClarityName,
+        Vec<Value>,
+        Vec<ClarityStateOverride>,
         Option<StacksBlockId>,
     ),
+    PreviewPostConditions(HttpRequestMetadata, StacksTransaction, TipRequest),
     GetTransferCost(HttpRequestMetadata),
     GetContractSrc(
         HttpRequestMetadata,
@@ -1261,6 +1968,17 @@ pub enum HttpRequestType {
         ContractName,
         Option<StacksBlockId>,
     ),
+    /// contract address, contract name, topic, (block height, tx index, event index) to list the
+    /// events before, limit, tip
+    GetContractEvents(
+        HttpRequestMetadata,
+        StacksAddress,
+        ContractName,
+        Option<ClarityName>,
+        Option<(u64, u32, u32)>,
+        u64,
+        Option<StacksBlockId>,
+    ),
     OptionsPreflight(HttpRequestMetadata, String),
     GetAttachment(HttpRequestMetadata, Hash160),
     GetAttachmentsInv(HttpRequestMetadata, StacksBlockId, HashSet<u32>),
@@ -1282,6 +2000,8 @@ pub struct HttpResponseMetadata {
     pub client_keep_alive: bool,
     pub request_id: u32,
     pub content_length: Option<u32>,
+    /// If set, a successful response tells caches they may keep it for this many seconds
+    pub cache_max_age: Option<u64>,
 }
 
 impl HttpResponseMetadata {
@@ -1305,6 +2025,7 @@ impl HttpResponseMetadata {
             client_keep_alive: client_keep_alive,
             request_id: request_id,
             content_length: content_length,
+            cache_max_age: None,
         }
     }
 
@@ -1317,6 +2038,7 @@ impl HttpResponseMetadata {
             client_keep_alive: preamble.keep_alive,
             request_id: preamble.request_id,
             content_length: preamble.content_length.clone(),
+            cache_max_age: None,
         }
     }
 
@@ -1326,6 +2048,7 @@ impl HttpResponseMetadata {
             client_keep_alive: false,
             request_id: HttpResponseMetadata::make_request_id(),
             content_length: Some(0),
+            cache_max_age: None,
         }
     }
 }
@@ -1333,12 +2056,14 @@ impl HttpResponseMetadata {
 impl From<&HttpRequestType> for HttpResponseMetadata {
     fn from(req: &HttpRequestType) -> HttpResponseMetadata {
         let metadata = req.metadata();
-        HttpResponseMetadata::new(
+        let mut response_metadata = HttpResponseMetadata::new(
             metadata.version,
             HttpResponseMetadata::make_request_id(),
             None,
             metadata.keep_alive,
-        )
+        );
+        response_metadata.cache_max_age = metadata.cache_max_age;
+        response_metadata
     }
 }
 
@@ -1353,13 +2078,33 @@ pub enum HttpResponseType {
     Microblocks(HttpResponseMetadata, Vec<StacksMicroblock>),
     MicroblockStream(HttpResponseMetadata),
     TransactionID(HttpResponseMetadata, Txid),
+    /// txid, and whether the transaction was new to the mempool
+    TransactionAccepted(HttpResponseMetadata, Txid, bool),
     StacksBlockAccepted(HttpResponseMetadata, StacksBlockId, bool),
     MicroblockHash(HttpResponseMetadata, BlockHeaderHash),
     TokenTransferCost(HttpResponseMetadata, u64),
     GetMapEntry(HttpResponseMetadata, MapEntryResponse),
     CallReadOnlyFunction(HttpResponseMetadata, CallReadOnlyResponse),
+    SimulateContractCall(HttpResponseMetadata, SimulateContractCallResponse),
+    PostConditionsPreview(HttpResponseMetadata, RPCPostConditionsPreviewData),
     GetAccount(HttpResponseMetadata, AccountEntryResponse),
+    GetAccountTransactions(HttpResponseMetadata, RPCAccountTransactionsData),
+    GetTransactionReceipt(HttpResponseMetadata, RPCTransactionReceiptData),
+    GetSponsorship(HttpResponseMetadata, RPCSponsorshipData),
+    GetAccountBalance(HttpResponseMetadata, RPCAccountBalanceData),
+    GetStackingStatus(HttpResponseMetadata, RPCStackingStatusData),
+    GetDelegations(HttpResponseMetadata, RPCDelegationsData),
+    GetRewardSet(HttpResponseMetadata, RPCRewardSetData),
+    GetSortition(HttpResponseMetadata, RPCSortitionData),
+    GetConsensusSchedule(HttpResponseMetadata, RPCConsensusScheduleData),
+    GetMicroblockEquivocations(HttpResponseMetadata, RPCMicroblockEquivocationsData),
+    AdminToggles(HttpResponseMetadata, RPCAdminTogglesData),
+    BurnOpSubmitted(HttpResponseMetadata, RPCBurnOpSubmissionData),
+    MinerWallet(HttpResponseMetadata, RPCMinerWalletData),
+    ClarityProfiles(HttpResponseMetadata, Vec<BlockProfile>),
+    BlockProposalValidated(HttpResponseMetadata, RPCBlockProposalData),
     GetContractABI(HttpResponseMetadata, ContractInterface),
+    GetContractEvents(HttpResponseMetadata, RPCContractEventsData),
     GetContractSrc(HttpResponseMetadata, ContractSrcResponse),
     GetIsTraitImplemented(HttpResponseMetadata, GetIsTraitImplementedResponse),
     UnconfirmedTransaction(HttpResponseMetadata, UnconfirmedTransactionResponse),
@@ -1406,6 +2151,7 @@ pub enum StacksMessageID {
     Pong = 16,
     NatPunchRequest = 17,
     NatPunchReply = 18,
+    TransactionsFilter = 19,
     Reserved = 255,
 }
 
@@ -1497,6 +2243,14 @@ pub const ARRAY_MAX_LEN: u32 = u32::max_value();
 // maximum number of neighbors in a NeighborsData
 pub const MAX_NEIGHBORS_DATA_LEN: u32 = 128;
 
+// default and maximum number of transactions in a page of an account's history
+pub const ACCOUNT_TRANSACTIONS_DEFAULT_LIMIT: u64 = 20;
+pub const ACCOUNT_TRANSACTIONS_MAX_LIMIT: u64 = 50;
+
+// default and maximum number of events in a page of a contract's print events
+pub const CONTRACT_EVENTS_DEFAULT_LIMIT: u64 = 20;
+pub const CONTRACT_EVENTS_MAX_LIMIT: u64 = 50;
+
 // number of peers to relay to, depending on outbound or inbound
 pub const MAX_BROADCAST_OUTBOUND_RECEIVERS: usize = 8;
 pub const MAX_BROADCAST_INBOUND_RECEIVERS: usize = 16;
@@ -1510,6 +2264,14 @@ pub const GETPOXINV_MAX_BITLEN: u64 = 4096;
 #[cfg(test)]
 pub const GETPOXINV_MAX_BITLEN: u64 = 8;
 
+// maximum size of a TransactionsFilter's bit field, and the most transactions one will describe
+// (at 10 bits per transaction)
+pub const TX_FILTER_MAX_BYTES: u32 = 16384;
+pub const TX_FILTER_MAX_TXS: u64 = (TX_FILTER_MAX_BYTES as u64) * 8 / 10;
+
+// number of a peer's most recent TransactionsFilters to remember
+pub const MAX_PEER_TX_FILTERS: usize = 2;
+
 // maximum number of blocks that can be pushed at once (even if the entire message is undersized).
 // This bound is needed since it bounds the amount of I/O a peer can be asked to do to validate the
 // message.
@@ -1729,6 +2491,37 @@ impl NetworkResult {
             .collect()
     }
 
+    /// Discard the blocks, microblocks and transactions in this result, so they will not be
+    /// stored.  Returns how many were discarded.
+    pub fn discard_new_data(&mut self) -> usize {
+        let mut num_discarded = self.blocks.len()
+            + self.confirmed_microblocks.len()
+            + self.uploaded_transactions.len()
+            + self.uploaded_blocks.len()
+            + self.uploaded_microblocks.len();
+        num_discarded += self
+            .pushed_transactions
+            .values()
+            .map(|v| v.len())
+            .sum::<usize>();
+        num_discarded += self.pushed_blocks.values().map(|v| v.len()).sum::<usize>();
+        num_discarded += self
+            .pushed_microblocks
+            .values()
+            .map(|v| v.len())
+            .sum::<usize>();
+
+        self.blocks.clear();
+        self.confirmed_microblocks.clear();
+        self.pushed_transactions.clear();
+        self.pushed_blocks.clear();
+        self.pushed_microblocks.clear();
+        self.uploaded_transactions.clear();
+        self.uploaded_blocks.clear();
+        self.uploaded_microblocks.clear();
+        num_discarded
+    }
+
     pub fn has_data_to_store(&self) -> bool {
         self.has_blocks()
             || self.has_microblocks()
@@ -2913,6 +3706,12 @@ pub mod test {
             &mut self.stacks_node.as_mut().unwrap().chainstate
         }
 
+        /// Set the optional indexes both this peer's chainstate and its coordinator's maintain
+        pub fn set_chainstate_indexes(&mut self, indexes: ChainstateIndexes) {
+            self.chainstate().indexes = indexes;
+            self.coord.chainstate().indexes = indexes;
+        }
+
         pub fn sortdb(&mut self) -> &mut SortitionDB {
             self.sortdb.as_mut().unwrap()
         }
@@ -28,6 +28,7 @@ use std::sync::mpsc::SendError;
 use std::sync::mpsc::SyncSender;
 use std::sync::mpsc::TryRecvError;
 use std::sync::mpsc::TrySendError;
+use std::sync::Arc;
 
 use mio;
 use mio::net as mio_net;
@@ -41,7 +42,14 @@ use burnchains::PublicKey;
 use chainstate::burn::db::sortdb::{BlockHeaderCache, SortitionDB};
 use chainstate::stacks::db::StacksChainState;
 use chainstate::stacks::{MAX_BLOCK_LEN, MAX_TRANSACTION_LEN};
-use monitoring::{update_inbound_neighbors, update_outbound_neighbors};
+use monitoring::{
+    increment_atlas_attachments_evicted, increment_push_queue_drops, increment_stale_tip_resets,
+    increment_tx_filters_sent, increment_tx_relays_avoided, update_atlas_attachments_count,
+    update_atlas_attachments_storage_bytes, update_connection_buffer_bytes,
+    update_inbound_neighbors, update_outbound_neighbors, update_push_queue_depth,
+    update_sqlite_memory_used,
+};
+use net::admin::RuntimeToggles;
 use net::asn::ASEntry4;
 use net::atlas::AtlasDB;
 use net::atlas::{AttachmentInstance, AttachmentsDownloader};
@@ -59,6 +67,7 @@ use net::neighbors::*;
 use net::poll::NetworkPollState;
 use net::poll::NetworkState;
 use net::prune::*;
+use net::push_queue::{PushQueue, PushedData, PUSH_DROP_INVALID};
 use net::relay::RelayerStats;
 use net::relay::*;
 use net::relay::*;
@@ -69,12 +78,15 @@ use net::Neighbor;
 use net::NeighborKey;
 use net::PeerAddress;
 use net::*;
+use util::bloom::BloomFilter;
 use util::db::DBConn;
 use util::db::Error as db_error;
+use util::get_epoch_time_ms;
 use util::get_epoch_time_secs;
 use util::hash::to_hex;
 use util::log;
-use util::secp256k1::Secp256k1PublicKey;
+use util::memory;
+use util::secp256k1::{Secp256k1PrivateKey, Secp256k1PublicKey};
 
 use crate::types::chainstate::{PoxId, SortitionId, StacksBlockHeader};
 
@@ -86,6 +98,15 @@ pub enum NetworkRequest {
     AdvertizeMicroblocks(BlocksAvailableMap), // announce to all wanting neighbors that we have these confirmed microblock streams
     Relay(NeighborKey, StacksMessage),
     Broadcast(Vec<RelayData>, StacksMessageType),
+    PushMinedBlock(ConsensusHash, StacksBlock), // push a block we mined, as `connection_opts.mined_block_push_*` say to
+}
+
+/// A block we mined, waiting to be pushed to its next wave of peers
+#[derive(Debug, Clone)]
+pub struct PendingBlockPush {
+    send_at_ms: u128,
+    recipients: Vec<NeighborKey>,
+    message: StacksMessageType,
 }
 
 /// Handle for other threads to use to issue p2p network requests.
@@ -163,6 +184,16 @@ impl NetworkHandle {
         let req = NetworkRequest::Broadcast(relay_hints, msg);
         self.send_request(req)
     }
+
+    /// Push a block we mined to our neighbors via the p2p network thread
+    pub fn push_mined_block(
+        &mut self,
+        consensus_hash: ConsensusHash,
+        block: StacksBlock,
+    ) -> Result<(), net_error> {
+        let req = NetworkRequest::PushMinedBlock(consensus_hash, block);
+        self.send_request(req)
+    }
 }
 
 impl NetworkHandleServer {
@@ -187,6 +218,112 @@ pub enum PeerNetworkWorkState {
     Prune,
 }
 
+/// Watches for the canonical Stacks tip going stale: not advancing for many burn blocks even
+/// though sortitions keep choosing blocks and we have peers to fetch them from.  That usually
+/// means the inventory sync or the downloader has wedged itself on bad peers, which used to take
+/// a restart to clear.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaleTipMonitor {
+    /// the canonical Stacks tip as of the last burn block we looked at
+    pub stacks_tip: (ConsensusHash, BlockHeaderHash),
+    /// burn height at which we started waiting for that tip to advance
+    pub since_burn_height: u64,
+    /// how many sortitions have chosen a block since then
+    pub sortitions_since: u64,
+    /// how many times the tip has gone stale
+    pub num_resets: u64,
+}
+
+impl StaleTipMonitor {
+    pub fn new() -> StaleTipMonitor {
+        StaleTipMonitor {
+            stacks_tip: (ConsensusHash([0x00; 20]), BlockHeaderHash([0x00; 32])),
+            since_burn_height: 0,
+            sortitions_since: 0,
+            num_resets: 0,
+        }
+    }
+
+    /// Look at the Stacks tip as of a new burn block.  Returns true if the tip has not advanced in
+    /// `threshold` burn blocks (never, if `threshold` is 0), in which case the wait starts over.
+    /// A tip that stalls while we have no peers, or while no sortition chose a block, is not
+    /// stale: there was nothing to fetch, or nowhere to fetch it from.
+    pub fn observe(
+        &mut self,
+        stacks_tip: &(ConsensusHash, BlockHeaderHash),
+        burn_height: u64,
+        sortition: bool,
+        have_peers: bool,
+        threshold: u64,
+    ) -> bool {
+        if *stacks_tip != self.stacks_tip || !have_peers || threshold == 0 {
+            self.stacks_tip = stacks_tip.clone();
+            self.since_burn_height = burn_height;
+            self.sortitions_since = 0;
+            return false;
+        }
+        if sortition {
+            self.sortitions_since += 1;
+        }
+        if burn_height < self.since_burn_height + threshold || self.sortitions_since == 0 {
+            return false;
+        }
+        self.since_burn_height = burn_height;
+        self.sortitions_since = 0;
+        self.num_resets += 1;
+        true
+    }
+}
+
+/// Counts the inbound connections each host has opened lately, so that one host can't take up
+/// our inbound capacity by reconnecting over and over.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct InboundHandshakeLimiter {
+    /// when each host's recent inbound connections were opened, oldest first
+    recent: HashMap<PeerAddress, VecDeque<u64>>,
+}
+
+impl InboundHandshakeLimiter {
+    pub fn new() -> InboundHandshakeLimiter {
+        InboundHandshakeLimiter {
+            recent: HashMap::new(),
+        }
+    }
+
+    /// Count a new inbound connection from the given host, unless it has already opened
+    /// `max_per_window` of them in the last `window` seconds (never, if `max_per_window` is 0).
+    /// Returns whether the connection may proceed.  Turned-away connections are not counted.
+    pub fn admit(
+        &mut self,
+        addrbytes: &PeerAddress,
+        max_per_window: u64,
+        window: u64,
+        now: u64,
+    ) -> bool {
+        if max_per_window == 0 {
+            return true;
+        }
+        let recent = self
+            .recent
+            .entry(addrbytes.clone())
+            .or_insert_with(VecDeque::new);
+        while recent.front().map(|ts| ts + window <= now).unwrap_or(false) {
+            recent.pop_front();
+        }
+        if (recent.len() as u64) >= max_per_window {
+            return false;
+        }
+        recent.push_back(now);
+        true
+    }
+
+    /// Forget the hosts that opened no connections in the last `window` seconds
+    pub fn prune(&mut self, window: u64, now: u64) {
+        self.recent
+            .retain(|_, recent| recent.back().map(|ts| ts + window > now).unwrap_or(false));
+    }
+}
+
 pub type PeerMap = HashMap<usize, ConversationP2P>;
 
 #[derive(Debug)]
@@ -256,6 +393,8 @@ pub struct PeerNetwork {
 
     // peer attachment downloader
     pub attachments_downloader: Option<AttachmentsDownloader>,
+    // when we last garbage-collected the attachments store
+    pub last_attachments_gc: u64,
 
     // how often we pruned a given inbound/outbound peer
     pub prune_outbound_counts: HashMap<NeighborKey, u64>,
@@ -293,10 +432,28 @@ pub struct PeerNetwork {
     antientropy_start_reward_cycle: u64,
     pub antientropy_last_push_ts: u64,
 
+    // when did we last send our neighbors a TransactionsFilter?
+    pub tx_filter_last_advertise_ts: u64,
+
     // pending messages (BlocksAvailable, MicroblocksAvailable, BlocksData, Microblocks) that we
     // can't process yet, but might be able to process on the next chain view update
     pub pending_messages: HashMap<usize, Vec<StacksMessage>>,
 
+    // operator-controlled toggles, set through the admin RPC endpoints
+    pub runtime_toggles: Arc<RuntimeToggles>,
+
+    // has the Stacks tip stopped advancing?
+    pub stale_tip: StaleTipMonitor,
+
+    // blocks and microblocks pushed to us, waiting to be handed to the relayer
+    pub push_queue: PushQueue,
+
+    // later waves of the pushes of blocks we mined
+    pub pending_block_pushes: Vec<PendingBlockPush>,
+
+    // inbound connections each host opened lately
+    pub inbound_handshakes: InboundHandshakeLimiter,
+
     // fault injection -- force disconnects
     fault_last_disconnect: u64,
 }
@@ -321,6 +478,7 @@ impl PeerNetwork {
         let pub_ip = connection_opts.public_ip_address.clone();
         let pub_ip_learned = pub_ip.is_none();
         local_peer.public_ip_address = pub_ip.clone();
+        local_peer.services |= ServiceFlags::TX_FILTER as u16;
 
         if connection_opts.disable_inbound_handshakes {
             debug!("{:?}: disable inbound handshakes", &local_peer);
@@ -328,6 +486,7 @@ impl PeerNetwork {
         if connection_opts.disable_inbound_walks {
             debug!("{:?}: disable inbound neighbor walks", &local_peer);
         }
+        let push_queue = PushQueue::new(connection_opts.max_queued_pushes as usize);
 
         let mut network = PeerNetwork {
             local_peer: local_peer,
@@ -374,6 +533,7 @@ impl PeerNetwork {
 
             block_downloader: None,
             attachments_downloader: None,
+            last_attachments_gc: 0,
 
             prune_outbound_counts: HashMap::new(),
             prune_inbound_counts: HashMap::new(),
@@ -402,8 +562,17 @@ impl PeerNetwork {
             antientropy_last_push_ts: 0,
             antientropy_start_reward_cycle: 0,
 
+            tx_filter_last_advertise_ts: 0,
+
             pending_messages: HashMap::new(),
 
+            runtime_toggles: Arc::new(RuntimeToggles::default()),
+
+            stale_tip: StaleTipMonitor::new(),
+            push_queue: push_queue,
+            pending_block_pushes: vec![],
+            inbound_handshakes: InboundHandshakeLimiter::new(),
+
             fault_last_disconnect: 0,
         };
 
@@ -631,6 +800,10 @@ impl PeerNetwork {
             neighbor_keys.len(),
             &relay_hints
         );
+        let txid_opt = match message_payload {
+            StacksMessageType::Transaction(ref tx) => Some(tx.txid()),
+            _ => None,
+        };
         for nk in neighbor_keys.drain(..) {
             if let Some(event_id) = self.events.get(&nk) {
                 let event_id = *event_id;
@@ -656,6 +829,21 @@ impl PeerNetwork {
                         continue;
                     }
 
+                    // don't send a transaction to someone who told us they already have it.
+                    // A false positive means this peer gets the transaction from someone else.
+                    if let Some(ref txid) = txid_opt {
+                        if convo.peer_probably_has_tx(txid) {
+                            debug!(
+                                "{:?}: Do not broadcast '{}' to {:?}: it probably has it already",
+                                &self.local_peer,
+                                message_payload.get_message_description(),
+                                &nk
+                            );
+                            increment_tx_relays_avoided();
+                            continue;
+                        }
+                    }
+
                     match convo.sign_and_forward(
                         &self.local_peer,
                         &self.chain_view,
@@ -713,6 +901,22 @@ impl PeerNetwork {
         ret
     }
 
+    /// Count how many bytes our p2p and HTTP conversations have set aside in their buffers
+    pub fn count_buffered_bytes(&self) -> u64 {
+        let p2p_bytes: usize = self
+            .peers
+            .values()
+            .map(|convo| convo.connection.buffered_bytes())
+            .sum();
+        let http_bytes: usize = self
+            .http
+            .peers
+            .values()
+            .map(|convo| convo.buffered_bytes())
+            .sum();
+        (p2p_bytes + http_bytes) as u64
+    }
+
     /// Count how many connections to a given IP address we have
     pub fn count_ip_connections(
         ipaddr: &SocketAddr,
@@ -992,6 +1196,98 @@ impl PeerNetwork {
                 self.broadcast_message(neighbor_keys, relay_hints, msg);
                 Ok(())
             }
+            NetworkRequest::PushMinedBlock(consensus_hash, block) => {
+                let recipients = self.mined_block_recipients(&block)?;
+                let msg = StacksMessageType::Blocks(BlocksData {
+                    blocks: vec![(consensus_hash, block)],
+                });
+                self.start_mined_block_push(recipients, msg);
+                Ok(())
+            }
+        }
+    }
+
+    /// Pick the peers to push a block we mined to, in the order to push it to them.  Other than in
+    /// `Relay` order, these are our best-ranked authenticated peers, up to as many as a relayed
+    /// block could be sent to.
+    fn mined_block_recipients(&self, block: &StacksBlock) -> Result<Vec<NeighborKey>, net_error> {
+        let order = self.connection_opts.mined_block_push_order;
+        if order == BlockPushOrder::Relay {
+            return self.sample_broadcast_peers(&vec![], block);
+        }
+
+        let mut candidates = vec![];
+        for (_, convo) in self.peers.iter() {
+            if !convo.is_authenticated() {
+                continue;
+            }
+            candidates.push(BlockPushCandidate {
+                neighbor: convo.to_neighbor_key(),
+                reply_latency_ms: convo.connection.get_reply_latency_ms(),
+                health_score: convo.stats.get_health_score(),
+            });
+        }
+
+        // don't push to both our inbound and outbound connection to the same peer
+        let mut seen = HashSet::new();
+        let mut recipients = vec![];
+        for nk in order.sort(candidates).into_iter() {
+            if seen.contains(&nk) {
+                continue;
+            }
+            if let Some(event_id) = self.events.get(&nk) {
+                if let Some(other_event_id) = self.find_reciprocal_event(*event_id) {
+                    if let Some(other_convo) = self.peers.get(&other_event_id) {
+                        seen.insert(other_convo.to_neighbor_key());
+                    }
+                }
+            }
+            seen.insert(nk.clone());
+            recipients.push(nk);
+        }
+        recipients.truncate(MAX_BROADCAST_OUTBOUND_RECEIVERS + MAX_BROADCAST_INBOUND_RECEIVERS);
+        Ok(recipients)
+    }
+
+    /// Push a block we mined to its first `mined_block_push_fanout` recipients, and queue up the
+    /// rest to be pushed to that many at a time, `mined_block_push_stagger_ms` apart.
+    fn start_mined_block_push(&mut self, recipients: Vec<NeighborKey>, msg: StacksMessageType) {
+        let fanout = self.connection_opts.mined_block_push_fanout as usize;
+        if fanout == 0 || recipients.len() <= fanout {
+            self.broadcast_message(recipients, vec![], msg);
+            return;
+        }
+
+        let now = get_epoch_time_ms();
+        let stagger = self.connection_opts.mined_block_push_stagger_ms as u128;
+        for (i, wave) in recipients.chunks(fanout).enumerate() {
+            self.pending_block_pushes.push(PendingBlockPush {
+                send_at_ms: now + stagger * (i as u128),
+                recipients: wave.to_vec(),
+                message: msg.clone(),
+            });
+        }
+        self.send_pending_block_pushes();
+    }
+
+    /// Push the blocks we mined to the waves of peers that are due to get them
+    fn send_pending_block_pushes(&mut self) {
+        if self.pending_block_pushes.is_empty() {
+            return;
+        }
+        let now = get_epoch_time_ms();
+        let (due, waiting): (Vec<_>, Vec<_>) = self
+            .pending_block_pushes
+            .drain(..)
+            .partition(|push| push.send_at_ms <= now);
+        self.pending_block_pushes = waiting;
+        for push in due.into_iter() {
+            debug!(
+                "{:?}: Push mined block to the next {} peers",
+                &self.local_peer,
+                push.recipients.len()
+            );
+            self.broadcast_message(push.recipients, vec![], push.message);
         }
     }
 
@@ -1200,7 +1496,8 @@ impl PeerNetwork {
 
     /// Check to see if we can register the given socket
     /// * we can't have registered this neighbor already
-    /// * if this is inbound, we can't add more than self.num_clients
+    /// * if this is inbound, we can't add more than self.num_clients, and only allowed peers can
+    /// take the last few of them (see `ConnectionOptions::inbound_slots_reserved`)
     pub fn can_register_peer(
         &mut self,
         neighbor_key: &NeighborKey,
@@ -1242,13 +1539,30 @@ impl PeerNetwork {
 
         // consider rate-limits on in-bound peers
         let num_outbound = PeerNetwork::count_outbound_conversations(&self.peers);
-        if !outbound && (self.peers.len() as u64) - num_outbound >= self.connection_opts.num_clients
-        {
+        let num_inbound = (self.peers.len() as u64) - num_outbound;
+        if !outbound && num_inbound >= self.connection_opts.num_clients {
             // too many inbounds
             info!("{:?}: Too many inbound connections", &self.local_peer);
             return Err(net_error::TooManyPeers);
         }
 
+        if !outbound
+            && num_inbound + self.connection_opts.inbound_slots_reserved()
+                >= self.connection_opts.num_clients
+            && !PeerDB::is_host_allowed(
+                &self.peerdb.conn(),
+                neighbor_key.network_id,
+                &neighbor_key.addrbytes,
+            )?
+        {
+            // only the reserved slots are left
+            info!(
+                "{:?}: Inbound slots left are reserved for allowed peers; dropping {:?}",
+                &self.local_peer, neighbor_key
+            );
+            return Err(net_error::TooManyPeers);
+        }
+
         Ok(())
     }
 
@@ -1347,6 +1661,24 @@ impl PeerNetwork {
             }
         }
 
+        if !outbound {
+            match self.admit_inbound_handshake(&neighbor_key) {
+                Ok(true) => {}
+                Ok(false) => {
+                    info!(
+                        "{:?}: Too many new inbound connections from {:?}; dropping",
+                        &self.local_peer, &neighbor_key.addrbytes
+                    );
+                    self.deregister_socket(event_id, socket);
+                    return Err(net_error::TooManyPeers);
+                }
+                Err(e) => {
+                    self.deregister_socket(event_id, socket);
+                    return Err(e);
+                }
+            }
+        }
+
         let mut new_convo = ConversationP2P::new(
             self.local_peer.network_id,
             self.peer_version,
@@ -1469,6 +1801,38 @@ impl PeerNetwork {
         self.deregister_peer(event_id);
     }
 
+    /// Disconnect from every p2p and HTTP peer and stop listening, so our sockets are closed
+    /// before the process exits instead of being left for remote peers to time out.  The network
+    /// cannot be used again after this.
+    pub fn shutdown(&mut self) -> () {
+        debug!("{:?}: Shut down network", &self.local_peer);
+        let p2p_event_ids: Vec<usize> = self
+            .sockets
+            .keys()
+            .chain(self.connecting.keys())
+            .map(|event_id| *event_id)
+            .collect();
+        for event_id in p2p_event_ids.into_iter() {
+            self.deregister_peer(event_id);
+        }
+
+        if let Some(ref mut network) = self.network {
+            let http_event_ids: Vec<usize> = self
+                .http
+                .sockets
+                .keys()
+                .chain(self.http.connecting.keys())
+                .map(|event_id| *event_id)
+                .collect();
+            for event_id in http_event_ids.into_iter() {
+                self.http.deregister_http(network, event_id);
+            }
+        }
+
+        // drops the listening sockets
+        self.network = None;
+    }
+
     /// Deregister and ban a neighbor
     pub fn deregister_and_ban_neighbor(&mut self, neighbor: &NeighborKey) -> () {
         debug!("Disconnect from and ban {:?}", neighbor);
@@ -1507,6 +1871,25 @@ impl PeerNetwork {
         }
     }
 
+    /// Can the host behind a new inbound connection open another one?  Allowed peers always can.
+    fn admit_inbound_handshake(&mut self, neighbor_key: &NeighborKey) -> Result<bool, net_error> {
+        if self.connection_opts.max_inbound_handshakes_per_host == 0
+            || PeerDB::is_host_allowed(
+                &self.peerdb.conn(),
+                neighbor_key.network_id,
+                &neighbor_key.addrbytes,
+            )?
+        {
+            return Ok(true);
+        }
+        Ok(self.inbound_handshakes.admit(
+            &neighbor_key.addrbytes,
+            self.connection_opts.max_inbound_handshakes_per_host,
+            self.connection_opts.inbound_handshake_window,
+            get_epoch_time_secs(),
+        ))
+    }
+
     /// Process new inbound TCP connections we just accepted.
     /// Returns the event IDs of sockets we need to register
     fn process_new_sockets(
@@ -1519,6 +1902,12 @@ impl PeerNetwork {
         }
 
         let mut registered = vec![];
+        if poll_state.new.len() > 0 {
+            self.inbound_handshakes.prune(
+                self.connection_opts.inbound_handshake_window,
+                get_epoch_time_secs(),
+            );
+        }
 
         for (hint_event_id, client_sock) in poll_state.new.drain() {
             let event_id = match self.network {
@@ -2617,6 +3006,68 @@ impl PeerNetwork {
         }
     }
 
+    /// Tell each authenticated neighbor that understands TransactionsFilter messages which
+    /// transactions we have accepted into our mempool lately, so it can skip relaying them back
+    /// to us.  Runs once every `tx_filter_interval` seconds.  Each filter covers the last two
+    /// intervals, so that a transaction that arrives just after one filter is built is in the next.
+    fn advertise_tx_filter(&mut self, mempool: &MemPoolDB) -> Result<(), net_error> {
+        let interval = self.connection_opts.tx_filter_interval;
+        let now = get_epoch_time_secs();
+        if interval == 0 || self.tx_filter_last_advertise_ts + interval > now {
+            return Ok(());
+        }
+        self.tx_filter_last_advertise_ts = now;
+
+        let recipients: Vec<NeighborKey> = self
+            .peers
+            .values()
+            .filter(|convo| convo.is_authenticated() && convo.supports_tx_filters())
+            .map(|convo| convo.to_neighbor_key())
+            .collect();
+        if recipients.is_empty() {
+            return Ok(());
+        }
+
+        let txids = MemPoolDB::get_txids_accepted_since(
+            mempool.conn(),
+            now.saturating_sub(2 * interval),
+            TX_FILTER_MAX_TXS,
+        )?;
+        if txids.is_empty() {
+            return Ok(());
+        }
+
+        let mut filter = BloomFilter::new(txids.len());
+        for txid in txids.iter() {
+            filter.insert(txid.as_bytes());
+        }
+
+        debug!(
+            "{:?}: Advertise {} recent transactions to {} neighbors",
+            &self.local_peer,
+            txids.len(),
+            recipients.len()
+        );
+        for nk in recipients.iter() {
+            let payload = StacksMessageType::TransactionsFilter(filter.clone());
+            match self
+                .sign_for_peer(nk, payload)
+                .and_then(|msg| self.relay_signed_message(nk, msg))
+            {
+                Ok(()) => {
+                    increment_tx_filters_sent();
+                }
+                Err(e) => {
+                    debug!(
+                        "{:?}: Failed to send transactions filter to {:?}: {:?}",
+                        &self.local_peer, nk, &e
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Push any blocks and microblock streams that we're holding onto out to our neighbors.
     /// Push all but the last arrived Stacks block (the block-push and blocks-available protocols
     /// should handle this, and we don't want the network to DDoS itself to death).
@@ -2999,8 +3450,11 @@ impl PeerNetwork {
                     // synchronize peer block inventories
                     let (inv_done, inv_throttled) = self.do_network_inv_sync(sortdb, ibd)?;
                     if inv_done {
-                        if !download_backpressure {
-                            // proceed to get blocks, if we're not backpressured
+                        if !download_backpressure
+                            && !self.runtime_toggles.downloads_paused()
+                            && !self.runtime_toggles.low_disk_space()
+                        {
+                            // proceed to get blocks, if we're not backpressured (or paused)
                             self.work_state = PeerNetworkWorkState::BlockDownload;
                         } else {
                             // skip downloads for now
@@ -3228,6 +3682,41 @@ impl PeerNetwork {
         Ok(())
     }
 
+    /// Every so often, evict attachments from the attachments store to keep it under its quota,
+    /// and report how much it holds.
+    fn do_attachments_storage_gc(
+        &mut self,
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+    ) -> Result<(), net_error> {
+        let now = get_epoch_time_secs();
+        if self.last_attachments_gc + self.atlasdb.atlas_config.attachments_gc_interval > now {
+            return Ok(());
+        }
+        self.last_attachments_gc = now;
+
+        let (tip_consensus_hash, tip_block_hash) =
+            SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn())?;
+        let tip = StacksBlockHeader::make_index_block_hash(&tip_consensus_hash, &tip_block_hash);
+        let index_conn = chainstate
+            .index_conn()
+            .map_err(|e| net_error::ChainstateError(format!("{:?}", &e)))?;
+        let evicted = self.atlasdb.gc_attachments_storage(|block_id| {
+            match index_conn.get_ancestor_block_height(block_id, &tip) {
+                Ok(height_opt) => height_opt.is_some(),
+                Err(e) => {
+                    warn!("Atlas: failed to look up block {}: {:?}", block_id, &e);
+                    false
+                }
+            }
+        })?;
+
+        increment_atlas_attachments_evicted(evicted as u64);
+        update_atlas_attachments_storage_bytes(self.atlasdb.get_attachments_storage_bytes()? as i64);
+        update_atlas_attachments_count(self.atlasdb.count_instantiated_attachments()? as i64);
+        Ok(())
+    }
+
     /// Given an event ID, find the other event ID corresponding
     /// to the same remote peer.  There will be at most two such events
     /// -- one registered as the inbound connection, and one registered as the
@@ -4097,6 +4586,7 @@ impl PeerNetwork {
     pub fn load_local_peer(&self) -> Result<LocalPeer, net_error> {
         let mut lp = PeerDB::get_local_peer(&self.peerdb.conn())?;
         lp.public_ip_address = self.local_peer.public_ip_address.clone();
+        lp.services |= ServiceFlags::TX_FILTER as u16;
         Ok(lp)
     }
 
@@ -4107,6 +4597,26 @@ impl PeerNetwork {
         Ok(())
     }
 
+    /// Switch to a new private key (e.g. one the operator rotated), and re-handshake with
+    /// everyone so they learn it.  The key keeps the current key's expiry.
+    pub fn set_local_private_key(
+        &mut self,
+        privkey: &Secp256k1PrivateKey,
+    ) -> Result<(), net_error> {
+        if self.local_peer.private_key == *privkey {
+            return Ok(());
+        }
+        {
+            let mut tx = self.peerdb.tx_begin()?;
+            PeerDB::set_local_private_key(&mut tx, privkey, self.local_peer.private_key_expire)?;
+            tx.commit().map_err(db_error::SqliteError)?;
+        }
+        let old_local_peer = self.local_peer.clone();
+        self.local_peer = self.load_local_peer()?;
+        self.rekey(Some(&old_local_peer));
+        Ok(())
+    }
+
     /// Refresh view of burnchain, if needed
     pub fn refresh_burnchain_view(
         &mut self,
@@ -4128,6 +4638,21 @@ impl PeerNetwork {
             self.hint_sync_invs(self.chain_view.burn_stable_block_height);
             self.hint_download_rescan(self.chain_view.burn_stable_block_height);
             self.chain_view = new_chain_view;
+
+            let stacks_tip = (
+                sn.canonical_stacks_tip_consensus_hash.clone(),
+                sn.canonical_stacks_tip_hash.clone(),
+            );
+            let have_peers = PeerNetwork::count_outbound_conversations(&self.peers) > 0;
+            if self.stale_tip.observe(
+                &stacks_tip,
+                sn.block_height,
+                sn.sortition,
+                have_peers,
+                self.connection_opts.stale_tip_burn_blocks,
+            ) {
+                self.reset_stale_tip_sync(&stacks_tip);
+            }
         }
 
         if sn.burn_header_hash != self.last_burnchain_tip {
@@ -4139,6 +4664,137 @@ impl PeerNetwork {
         Ok(ret)
     }
 
+    /// The Stacks tip has gone stale.  Throw away the inventory and downloader state, so both
+    /// start over from scratch, and disconnect from our outbound peers, so the next neighbor walk
+    /// finds us new ones to sync with.  Inbound peers are left alone; they reached out to us.
+    fn reset_stale_tip_sync(&mut self, stacks_tip: &(ConsensusHash, BlockHeaderHash)) {
+        let outbound: Vec<usize> = self
+            .peers
+            .iter()
+            .filter(|(_, convo)| convo.is_outbound())
+            .map(|(event_id, _)| *event_id)
+            .collect();
+
+        warn!(
+            "{:?}: Stacks tip {}/{} has not advanced in {} burn blocks; resetting block sync and disconnecting {} outbound peers",
+            &self.local_peer,
+            &stacks_tip.0,
+            &stacks_tip.1,
+            self.connection_opts.stale_tip_burn_blocks,
+            outbound.len()
+        );
+        increment_stale_tip_resets();
+
+        self.inv_state = None;
+        self.block_downloader = None;
+        self.walk = None;
+        for event_id in outbound.into_iter() {
+            self.deregister_peer(event_id);
+        }
+    }
+
+    /// Is this block the winner of the sortition with this consensus hash, on the valid PoX fork?
+    /// Pushed blocks that aren't are not worth queueing: the relayer would not store them.
+    fn is_sortition_winner(
+        sortdb: &SortitionDB,
+        consensus_hash: &ConsensusHash,
+        block: &StacksBlock,
+    ) -> Result<bool, net_error> {
+        match SortitionDB::get_block_snapshot_consensus(sortdb.conn(), consensus_hash)? {
+            Some(sn) => Ok(sn.pox_valid
+                && sn.sortition
+                && sn.winning_stacks_block_hash == block.block_hash()),
+            None => Ok(false),
+        }
+    }
+
+    /// Move the blocks and microblocks pushed to us in this pass into the push queue, and hand the
+    /// most urgent of everything queued back to the relayer in `network_result`.  Pushed blocks
+    /// that did not win their sortition are dropped.
+    fn queue_pushed_data(
+        &mut self,
+        sortdb: &SortitionDB,
+        network_result: &mut NetworkResult,
+    ) -> Result<(), net_error> {
+        if !self.connection_opts.microblock_acceptance.accepts_pushed() {
+            let num_dropped: usize = network_result
+                .pushed_microblocks
+                .drain()
+                .map(|(_, mblocks_datas)| mblocks_datas.len())
+                .sum();
+            if num_dropped > 0 {
+                debug!(
+                    "{:?}: Not accepting pushed microblocks; dropped {} stream(s)",
+                    &self.local_peer, num_dropped
+                );
+            }
+        }
+        if network_result.pushed_blocks.is_empty()
+            && network_result.pushed_microblocks.is_empty()
+            && self.push_queue.is_empty()
+        {
+            return Ok(());
+        }
+
+        let tip = SortitionDB::get_canonical_stacks_chain_tip_hash(sortdb.conn())?;
+        let mut pushed = vec![];
+        for (nk, blocks_datas) in network_result.pushed_blocks.drain() {
+            for blocks_data in blocks_datas.into_iter() {
+                for data in PushedData::from_blocks_data(blocks_data).into_iter() {
+                    if let PushedData::Block(ref consensus_hash, ref block) = data {
+                        if !PeerNetwork::is_sortition_winner(sortdb, consensus_hash, block)? {
+                            debug!(
+                                "{:?}: Dropped block {}/{} pushed by {:?}: not the winner of a known sortition",
+                                &self.local_peer,
+                                consensus_hash,
+                                &block.block_hash(),
+                                &nk
+                            );
+                            increment_push_queue_drops(PUSH_DROP_INVALID);
+                            continue;
+                        }
+                    }
+                    pushed.push((nk.clone(), data));
+                }
+            }
+        }
+        for (nk, mblocks_datas) in network_result.pushed_microblocks.drain() {
+            for (relayers, mblocks_data) in mblocks_datas.into_iter() {
+                pushed.push((nk.clone(), PushedData::Microblocks(relayers, mblocks_data)));
+            }
+        }
+        for (nk, data) in pushed.into_iter() {
+            let priority = data.priority(&tip);
+            if let Some(reason) = self.push_queue.push(nk.clone(), data, priority) {
+                debug!(
+                    "{:?}: Dropped data pushed by {:?} from the push queue: {}",
+                    &self.local_peer, &nk, reason
+                );
+                increment_push_queue_drops(reason);
+            }
+        }
+
+        for _ in 0..cmp::max(1, self.connection_opts.max_pushes_per_pass) {
+            match self.push_queue.pop() {
+                Some((nk, PushedData::Block(consensus_hash, block))) => network_result
+                    .pushed_blocks
+                    .entry(nk)
+                    .or_insert_with(Vec::new)
+                    .push(BlocksData {
+                        blocks: vec![(consensus_hash, block)],
+                    }),
+                Some((nk, PushedData::Microblocks(relayers, mblocks_data))) => network_result
+                    .pushed_microblocks
+                    .entry(nk)
+                    .or_insert_with(Vec::new)
+                    .push((relayers, mblocks_data)),
+                None => break,
+            }
+        }
+        update_push_queue_depth(self.push_queue.len() as i64);
+        Ok(())
+    }
+
     /// Update p2p networking state.
     /// -- accept new connections
     /// -- send data on ready sockets
@@ -4223,13 +4879,20 @@ impl PeerNetwork {
             let inbound_neighbors = self.peers.len() - outbound_neighbors as usize;
             update_outbound_neighbors(outbound_neighbors as i64);
             update_inbound_neighbors(inbound_neighbors as i64);
+            update_connection_buffer_bytes(self.count_buffered_bytes() as i64);
+            update_sqlite_memory_used(memory::sqlite_memory_used());
         }
 
         // In parallel, do a neighbor walk, but only if we're not doing the initial block download
         self.do_network_neighbor_walk()?;
 
-        // download attachments
-        self.do_attachment_downloads(chainstate, dns_client_opt, network_result)?;
+        // download attachments (a read-only replica serves the ones its primary stored)
+        if !self.connection_opts.read_only_replica {
+            self.do_attachment_downloads(chainstate, dns_client_opt, network_result)?;
+            if let Err(e) = self.do_attachments_storage_gc(sortdb, chainstate) {
+                warn!("Atlas: failed to garbage-collect attachments: {:?}", &e);
+            }
+        }
 
         // remove timed-out requests from other threads
         for (_, convo) in self.peers.iter_mut() {
@@ -4267,10 +4930,16 @@ impl PeerNetwork {
         // update our relay statistics, so we know who to forward messages to
         self.update_relayer_stats(&network_result);
 
+        // hold back pushed blocks and microblocks we can't get to this pass
+        self.queue_pushed_data(sortdb, network_result)?;
+
         // finally, handle network I/O requests from other threads, and get back reply handles to them.
         // do this after processing new sockets, so we don't accidentally re-use an event ID.
         self.dispatch_requests();
 
+        // push blocks we mined to the peers whose turn it is to get them
+        self.send_pending_block_pushes();
+
         // fault injection -- periodically disconnect from everyone
         if cfg!(test) {
             if let Some(disconnect_interval) = self.connection_opts.force_disconnect_interval {
@@ -4445,6 +5114,13 @@ impl PeerNetwork {
             p2p_poll_state,
         )?;
 
+        if let Err(e) = self.advertise_tx_filter(mempool) {
+            warn!(
+                "{:?}: Failed to advertise recent transactions: {:?}",
+                &self.local_peer, &e
+            );
+        }
+
         debug!("<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<< End Network Dispatch <<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<");
         Ok(network_result)
     }
@@ -4567,6 +5243,80 @@ mod test {
         p2p
     }
 
+    #[test]
+    fn test_stale_tip_monitor() {
+        let tip_1 = (ConsensusHash([0x01; 20]), BlockHeaderHash([0x01; 32]));
+        let tip_2 = (ConsensusHash([0x02; 20]), BlockHeaderHash([0x02; 32]));
+        let mut monitor = StaleTipMonitor::new();
+
+        // never fires if disabled
+        for height in 100..200 {
+            assert!(!monitor.observe(&tip_1, height, true, true, 0));
+        }
+
+        // fires once the tip goes 10 burn blocks without advancing
+        assert!(!monitor.observe(&tip_1, 200, true, true, 10));
+        for height in 201..210 {
+            assert!(!monitor.observe(&tip_1, height, true, true, 10));
+        }
+        assert!(monitor.observe(&tip_1, 210, true, true, 10));
+        assert_eq!(monitor.num_resets, 1);
+
+        // ...and then waits another 10 before firing again
+        for height in 211..220 {
+            assert!(!monitor.observe(&tip_1, height, true, true, 10));
+        }
+        assert!(monitor.observe(&tip_1, 220, true, true, 10));
+
+        // an advancing tip starts the wait over
+        assert!(!monitor.observe(&tip_2, 225, true, true, 10));
+        assert!(!monitor.observe(&tip_2, 234, true, true, 10));
+        assert!(monitor.observe(&tip_2, 235, true, true, 10));
+
+        // so does having no peers
+        assert!(!monitor.observe(&tip_2, 250, true, false, 10));
+        assert!(!monitor.observe(&tip_2, 259, true, true, 10));
+        assert!(monitor.observe(&tip_2, 260, true, true, 10));
+
+        // no sortitions, no blocks to fetch
+        assert!(!monitor.observe(&tip_2, 280, false, true, 10));
+        assert!(!monitor.observe(&tip_2, 300, false, true, 10));
+        assert!(monitor.observe(&tip_2, 301, true, true, 10));
+        assert_eq!(monitor.num_resets, 5);
+    }
+
+    #[test]
+    fn test_inbound_handshake_limiter() {
+        let host_1 = PeerAddress::from_ipv4(1, 2, 3, 4);
+        let host_2 = PeerAddress::from_ipv4(5, 6, 7, 8);
+        let mut limiter = InboundHandshakeLimiter::new();
+
+        // no limit
+        for _ in 0..100 {
+            assert!(limiter.admit(&host_1, 0, 60, 1000));
+        }
+
+        // three per minute, per host
+        for _ in 0..3 {
+            assert!(limiter.admit(&host_1, 3, 60, 1000));
+        }
+        assert!(!limiter.admit(&host_1, 3, 60, 1030));
+        assert!(limiter.admit(&host_2, 3, 60, 1030));
+
+        // room opens up as the window moves on
+        for _ in 0..3 {
+            assert!(limiter.admit(&host_1, 3, 60, 1060));
+        }
+        assert!(!limiter.admit(&host_1, 3, 60, 1061));
+
+        limiter.prune(60, 1089);
+        assert_eq!(limiter.recent.len(), 2);
+        limiter.prune(60, 1090);
+        assert_eq!(limiter.recent.len(), 1);
+        limiter.prune(60, 1120);
+        assert!(limiter.recent.is_empty());
+    }
+
     #[test]
     fn test_event_id_no_connecting_leaks() {
         with_timeout(100, || {
@@ -4630,6 +5380,42 @@ mod test {
         })
     }
 
+    #[test]
+    fn test_shutdown_closes_sockets() {
+        with_timeout(100, || {
+            let neighbor = make_test_neighbor(2310);
+            let mut p2p = make_test_p2p_network(&vec![]);
+
+            use std::io::Read;
+            use std::net::TcpListener;
+            let listener = TcpListener::bind("127.0.0.1:2310").unwrap();
+
+            p2p.bind(
+                &"127.0.0.1:2410".parse().unwrap(),
+                &"127.0.0.1:2411".parse().unwrap(),
+            )
+            .unwrap();
+            p2p.connect_peer(&neighbor.addr).unwrap();
+
+            let (mut sock, _) = listener.accept().unwrap();
+            assert_eq!(p2p.sockets.len() + p2p.connecting.len(), 1);
+
+            p2p.shutdown();
+            assert!(p2p.sockets.is_empty());
+            assert!(p2p.connecting.is_empty());
+            assert!(p2p.peers.is_empty());
+            assert!(p2p.network.is_none());
+
+            // the remote end sees the connection close
+            let mut buf = [0u8; 1];
+            assert_eq!(sock.read(&mut buf).unwrap(), 0);
+
+            // and our listening sockets are gone
+            TcpListener::bind("127.0.0.1:2410").unwrap();
+            TcpListener::bind("127.0.0.1:2411").unwrap();
+        })
+    }
+
     // tests relay_signed_message()
     #[test]
     #[ignore]
@@ -0,0 +1,271 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A bounded queue for the blocks and microblocks peers push to us.
+//!
+//! Every block and microblock stream pushed to us has to be checked and stored by the relayer,
+//! which also mines; a burst of pushes can keep it busy for a long time, and back up the p2p
+//! thread that serves RPC requests behind it.  So pushed data goes into this queue, and the p2p
+//! thread hands only a few items at a time on to the relayer.  Blocks that build on the canonical
+//! Stacks tip, and the tip's own microblocks, jump the queue.  Copies of data that is already
+//! queued are dropped, and once the queue is full, the lowest-priority, newest items go first.
+//! Blocks that did not win a sortition we know about are never queued at all.
+
+use std::collections::{BTreeMap, HashSet};
+
+use chainstate::burn::ConsensusHash;
+use chainstate::stacks::StacksBlock;
+use net::{BlocksData, MicroblocksData, NeighborKey, RelayData};
+
+use crate::types::chainstate::{BlockHeaderHash, StacksBlockHeader, StacksBlockId};
+
+/// Reason a pushed item was dropped, for the drops metric
+pub const PUSH_DROP_DUPLICATE: &str = "duplicate";
+pub const PUSH_DROP_FULL: &str = "full";
+pub const PUSH_DROP_EVICTED: &str = "evicted";
+pub const PUSH_DROP_INVALID: &str = "invalid";
+
+/// Lower is more urgent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PushPriority {
+    /// a block whose parent is the canonical Stacks tip, or a microblock stream off the tip
+    ExtendsTip,
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PushedData {
+    Block(ConsensusHash, StacksBlock),
+    Microblocks(Vec<RelayData>, MicroblocksData),
+}
+
+/// What a queued item is a copy of
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum PushId {
+    Block(ConsensusHash, BlockHeaderHash),
+    Microblocks(StacksBlockId, BlockHeaderHash),
+}
+
+impl PushedData {
+    /// Split up the blocks in a pushed message, so each can be queued on its own
+    pub fn from_blocks_data(blocks_data: BlocksData) -> Vec<PushedData> {
+        blocks_data
+            .blocks
+            .into_iter()
+            .map(|(consensus_hash, block)| PushedData::Block(consensus_hash, block))
+            .collect()
+    }
+
+    fn id(&self) -> PushId {
+        match self {
+            PushedData::Block(consensus_hash, block) => {
+                PushId::Block(consensus_hash.clone(), block.block_hash())
+            }
+            PushedData::Microblocks(_, data) => PushId::Microblocks(
+                data.index_anchor_block.clone(),
+                data.microblocks
+                    .last()
+                    .map(|mblock| mblock.block_hash())
+                    .unwrap_or(BlockHeaderHash([0x00; 32])),
+            ),
+        }
+    }
+
+    /// How urgently to process this, given the canonical Stacks tip
+    pub fn priority(&self, tip: &(ConsensusHash, BlockHeaderHash)) -> PushPriority {
+        let extends_tip = match self {
+            PushedData::Block(_, block) => block.header.parent_block == tip.1,
+            PushedData::Microblocks(_, data) => {
+                data.index_anchor_block == StacksBlockHeader::make_index_block_hash(&tip.0, &tip.1)
+            }
+        };
+        if extends_tip {
+            PushPriority::ExtendsTip
+        } else {
+            PushPriority::Other
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PushQueue {
+    capacity: usize,
+    next_seq: u64,
+    /// ordered most urgent first, then oldest first
+    queue: BTreeMap<(PushPriority, u64), (NeighborKey, PushedData)>,
+    queued: HashSet<PushId>,
+}
+
+impl PushQueue {
+    pub fn new(capacity: usize) -> PushQueue {
+        PushQueue {
+            capacity,
+            next_seq: 0,
+            queue: BTreeMap::new(),
+            queued: HashSet::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Queue up data a peer pushed.  Returns the reason something was dropped to make room, or
+    /// to avoid queuing a copy, if anything was.
+    pub fn push(
+        &mut self,
+        neighbor_key: NeighborKey,
+        data: PushedData,
+        priority: PushPriority,
+    ) -> Option<&'static str> {
+        let id = data.id();
+        if self.queued.contains(&id) {
+            return Some(PUSH_DROP_DUPLICATE);
+        }
+
+        let mut dropped = None;
+        if self.queue.len() >= self.capacity {
+            let lowest = match self.queue.keys().next_back() {
+                Some(key) => key.clone(),
+                None => return Some(PUSH_DROP_FULL),
+            };
+            if lowest.0 <= priority {
+                return Some(PUSH_DROP_FULL);
+            }
+            if let Some((_, evicted)) = self.queue.remove(&lowest) {
+                self.queued.remove(&evicted.id());
+            }
+            dropped = Some(PUSH_DROP_EVICTED);
+        }
+
+        self.queued.insert(id);
+        self.queue
+            .insert((priority, self.next_seq), (neighbor_key, data));
+        self.next_seq += 1;
+        dropped
+    }
+
+    /// Take the most urgent item off the queue
+    pub fn pop(&mut self) -> Option<(NeighborKey, PushedData)> {
+        let key = self.queue.keys().next()?.clone();
+        let (neighbor_key, data) = self.queue.remove(&key)?;
+        self.queued.remove(&data.id());
+        Some((neighbor_key, data))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chainstate::stacks::db::blocks::test::make_sample_microblock_stream;
+    use chainstate::stacks::test::make_codec_test_block;
+    use chainstate::stacks::StacksPrivateKey;
+    use net::PeerAddress;
+
+    use super::*;
+
+    fn neighbor_key(port: u16) -> NeighborKey {
+        NeighborKey {
+            peer_version: 0x18000000,
+            network_id: 0x80000000,
+            addrbytes: PeerAddress([0u8; 16]),
+            port,
+        }
+    }
+
+    fn block_with_parent(parent: &BlockHeaderHash, nonce: u8) -> StacksBlock {
+        let mut block = make_codec_test_block(1);
+        block.header.parent_block = parent.clone();
+        block.header.microblock_pubkey_hash.0[0] = nonce;
+        block
+    }
+
+    #[test]
+    fn test_push_queue_priority_and_shedding() {
+        let tip = (ConsensusHash([0x01; 20]), BlockHeaderHash([0x01; 32]));
+        let fork = BlockHeaderHash([0x02; 32]);
+        let mut queue = PushQueue::new(3);
+
+        let old_1 = PushedData::Block(ConsensusHash([0x11; 20]), block_with_parent(&fork, 1));
+        let old_2 = PushedData::Block(ConsensusHash([0x12; 20]), block_with_parent(&fork, 2));
+        let next = PushedData::Block(ConsensusHash([0x13; 20]), block_with_parent(&tip.1, 3));
+        let microblocks = PushedData::Microblocks(
+            vec![],
+            MicroblocksData {
+                index_anchor_block: StacksBlockHeader::make_index_block_hash(&tip.0, &tip.1),
+                microblocks: make_sample_microblock_stream(&StacksPrivateKey::new(), &tip.1),
+            },
+        );
+        assert_eq!(old_1.priority(&tip), PushPriority::Other);
+        assert_eq!(next.priority(&tip), PushPriority::ExtendsTip);
+        assert_eq!(microblocks.priority(&tip), PushPriority::ExtendsTip);
+
+        assert_eq!(
+            queue.push(neighbor_key(1), old_1.clone(), PushPriority::Other),
+            None
+        );
+        assert_eq!(
+            queue.push(neighbor_key(1), old_2.clone(), PushPriority::Other),
+            None
+        );
+
+        // a copy from another peer is dropped
+        assert_eq!(
+            queue.push(neighbor_key(2), old_1.clone(), PushPriority::Other),
+            Some(PUSH_DROP_DUPLICATE)
+        );
+        assert_eq!(queue.len(), 2);
+
+        assert_eq!(
+            queue.push(neighbor_key(2), next.clone(), PushPriority::ExtendsTip),
+            None
+        );
+
+        // full: an urgent push evicts the newest low-priority one...
+        assert_eq!(
+            queue.push(
+                neighbor_key(3),
+                microblocks.clone(),
+                PushPriority::ExtendsTip
+            ),
+            Some(PUSH_DROP_EVICTED)
+        );
+        assert_eq!(queue.len(), 3);
+
+        // ...but a low-priority push is turned away
+        let old_3 = PushedData::Block(ConsensusHash([0x14; 20]), block_with_parent(&fork, 4));
+        assert_eq!(
+            queue.push(neighbor_key(3), old_3, PushPriority::Other),
+            Some(PUSH_DROP_FULL)
+        );
+
+        // urgent data comes out first, in the order it arrived
+        assert_eq!(queue.pop(), Some((neighbor_key(2), next)));
+        assert_eq!(queue.pop(), Some((neighbor_key(3), microblocks)));
+        assert_eq!(queue.pop(), Some((neighbor_key(1), old_1.clone())));
+        assert_eq!(queue.pop(), None);
+        assert!(queue.is_empty());
+
+        // once it is out of the queue, the same data can be queued again
+        assert_eq!(
+            queue.push(neighbor_key(1), old_1, PushPriority::Other),
+            None
+        );
+    }
+}
@@ -0,0 +1,294 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A pool of worker threads that evaluate read-only contract calls on behalf of the RPC server.
+//!
+//! Each worker owns its own (read-only) connections to the chainstate MARF and the sortition DB,
+//! so read-only calls do not run on the p2p thread's chainstate connection and do not hold up
+//! block processing.  The p2p thread hands off a call with `submit()`, and polls the returned
+//! receiver for the response.
+
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+
+use chainstate::burn::db::sortdb::SortitionDB;
+use chainstate::stacks::db::StacksChainState;
+use monitoring;
+use net::rpc::{ConversationHttp, RPCReadSnapshot};
+use net::Error as net_error;
+use net::HttpResponseMetadata;
+use net::HttpResponseType;
+use net::RPCRequestDeadline;
+use net::RPC_DEADLINE_EXCEEDED_MESSAGE;
+use util::get_epoch_time_ms;
+use vm::costs::ExecutionCost;
+use vm::types::{PrincipalData, QualifiedContractIdentifier};
+use vm::{ClarityName, Value};
+
+use crate::types::chainstate::StacksBlockId;
+
+/// A read-only function call to evaluate
+#[derive(Debug, Clone)]
+pub struct ReadOnlyCall {
+    pub tip: StacksBlockId,
+    pub contract_identifier: QualifiedContractIdentifier,
+    pub function: ClarityName,
+    pub sender: PrincipalData,
+    pub args: Vec<Value>,
+    pub cost_limit: ExecutionCost,
+    pub response_metadata: HttpResponseMetadata,
+    /// Calls still queued up past their deadline are answered with a 503, unevaluated
+    pub deadline: Option<RPCRequestDeadline>,
+}
+
+struct ReadOnlyCallJob {
+    call: ReadOnlyCall,
+    reply: Sender<HttpResponseType>,
+}
+
+pub struct ReadOnlyCallPool {
+    job_sender: Option<Sender<ReadOnlyCallJob>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl ReadOnlyCallPool {
+    /// Start up `num_threads` workers, each with its own read-only connections to the chainstate
+    /// at `chainstate_path` and the sortition DB at `sortdb_path`.  `read_only_call_limit` is the
+    /// block limit the workers' chainstate is opened with.
+    pub fn new(
+        mainnet: bool,
+        chain_id: u32,
+        chainstate_path: &str,
+        sortdb_path: &str,
+        read_only_call_limit: ExecutionCost,
+        num_threads: usize,
+    ) -> Result<ReadOnlyCallPool, net_error> {
+        let (job_sender, job_receiver) = channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let mut workers = Vec::with_capacity(num_threads);
+        for i in 0..num_threads {
+            // open the databases here, so a misconfigured pool fails at startup instead of
+            // silently never answering
+            let sortdb = SortitionDB::open(sortdb_path, false).map_err(net_error::DBError)?;
+            let chainstate = StacksChainState::open_readonly(
+                mainnet,
+                chain_id,
+                chainstate_path,
+                read_only_call_limit.clone(),
+            )
+            .map_err(|e| net_error::ChainstateError(e.to_string()))?;
+
+            let job_receiver = job_receiver.clone();
+            let worker = thread::Builder::new()
+                .name(format!("readonly-call-{}", i))
+                .spawn(move || ReadOnlyCallPool::worker_main(sortdb, chainstate, job_receiver))
+                .map_err(|e| {
+                    net_error::ChainstateError(format!(
+                        "Failed to spawn read-only call worker: {:?}",
+                        &e
+                    ))
+                })?;
+            workers.push(worker);
+        }
+
+        debug!("Started {} read-only call worker(s)", num_threads);
+        Ok(ReadOnlyCallPool {
+            job_sender: Some(job_sender),
+            workers,
+        })
+    }
+
+    fn worker_main(
+        sortdb: SortitionDB,
+        mut chainstate: StacksChainState,
+        job_receiver: Arc<Mutex<Receiver<ReadOnlyCallJob>>>,
+    ) {
+        loop {
+            let job = match job_receiver.lock() {
+                Ok(receiver) => match receiver.recv() {
+                    Ok(job) => job,
+                    Err(_) => {
+                        // pool is shutting down
+                        break;
+                    }
+                },
+                Err(_) => {
+                    // another worker panicked while holding the lock
+                    error!("Read-only call job queue is poisoned");
+                    break;
+                }
+            };
+
+            let call = job.call;
+            if let Some(deadline) = call
+                .deadline
+                .filter(|deadline| deadline.is_expired(get_epoch_time_ms()))
+            {
+                let response = HttpResponseType::ServiceUnavailable(
+                    call.response_metadata,
+                    RPC_DEADLINE_EXCEEDED_MESSAGE.to_string(),
+                );
+                // only count it if the requester is still waiting; if not, it counted it
+                if job.reply.send(response).is_ok() {
+                    monitoring::increment_rpc_request_timeouts(deadline.class.as_str());
+                }
+                continue;
+            }
+
+            // answer from a consistent view, as ConversationHttp::handle_request() does
+            let snapshot = match RPCReadSnapshot::begin(&sortdb, &mut chainstate, None, None) {
+                Ok(snapshot) => Some(snapshot),
+                Err(e) => {
+                    warn!("Failed to begin read snapshot for read-only call: {:?}", &e);
+                    None
+                }
+            };
+            let response = ConversationHttp::eval_readonly_function_call(
+                &sortdb,
+                &mut chainstate,
+                &call.tip,
+                &call.contract_identifier,
+                &call.function,
+                &call.sender,
+                &call.args,
+                call.cost_limit,
+                call.response_metadata,
+            );
+            if let Some(snapshot) = snapshot {
+                if let Err(e) = snapshot.end(&sortdb, &mut chainstate, None, None) {
+                    warn!("Failed to end read snapshot for read-only call: {:?}", &e);
+                }
+            }
+
+            // the requester may have hung up in the meantime; that's fine
+            let _ = job.reply.send(response);
+        }
+    }
+
+    /// Queue up a read-only call.  Returns the receiver on which its response will arrive.
+    pub fn submit(&self, call: ReadOnlyCall) -> Result<Receiver<HttpResponseType>, net_error> {
+        let (reply, reply_receiver) = channel();
+        let sender = self
+            .job_sender
+            .as_ref()
+            .ok_or_else(|| net_error::SendError("Read-only call pool is shut down".into()))?;
+        sender
+            .send(ReadOnlyCallJob { call, reply })
+            .map_err(|_| net_error::SendError("Read-only call workers have exited".into()))?;
+        Ok(reply_receiver)
+    }
+
+    pub fn num_workers(&self) -> usize {
+        self.workers.len()
+    }
+}
+
+impl Drop for ReadOnlyCallPool {
+    fn drop(&mut self) {
+        // hang up on the workers, and wait for them to finish what they're doing
+        self.job_sender.take();
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryInto;
+    use std::fs;
+
+    use chainstate::stacks::db::test::{chainstate_path, instantiate_chainstate};
+    use net::HttpVersion;
+    use util::boot::boot_code_id;
+    use util::get_epoch_time_secs;
+
+    use crate::types::chainstate::BurnchainHeaderHash;
+
+    use super::*;
+
+    #[test]
+    fn test_readonly_call_pool() {
+        let test_name = "readonly-call-pool";
+        let chainstate = instantiate_chainstate(false, 0x80000000, test_name);
+        drop(chainstate);
+
+        let sortdb_path = format!("/tmp/blockstack-test-sortdb-{}", test_name);
+        if fs::metadata(&sortdb_path).is_ok() {
+            fs::remove_dir_all(&sortdb_path).unwrap();
+        }
+        SortitionDB::connect(
+            &sortdb_path,
+            0,
+            &BurnchainHeaderHash([0u8; 32]),
+            get_epoch_time_secs(),
+            true,
+        )
+        .unwrap();
+
+        let pool = ReadOnlyCallPool::new(
+            false,
+            0x80000000,
+            &chainstate_path(test_name),
+            &sortdb_path,
+            ExecutionCost::max_value(),
+            2,
+        )
+        .unwrap();
+        assert_eq!(pool.num_workers(), 2);
+
+        // submit a batch of calls against a tip that doesn't exist, and verify that each gets
+        // its own reply
+        let receivers: Vec<_> = (0..8)
+            .map(|i| {
+                let call = ReadOnlyCall {
+                    tip: StacksBlockId([0x11; 32]),
+                    contract_identifier: boot_code_id("costs", false),
+                    function: "cost_analysis_type_check".try_into().unwrap(),
+                    sender: PrincipalData::from(boot_code_id("costs", false).issuer),
+                    args: vec![Value::UInt(1)],
+                    cost_limit: ExecutionCost::max_value(),
+                    response_metadata: HttpResponseMetadata::new(
+                        HttpVersion::Http11,
+                        i,
+                        None,
+                        true,
+                    ),
+                    deadline: None,
+                };
+                (i, pool.submit(call).unwrap())
+            })
+            .collect();
+
+        for (i, receiver) in receivers.into_iter() {
+            match receiver.recv().unwrap() {
+                HttpResponseType::NotFound(md, msg) => {
+                    assert_eq!(md.request_id, i);
+                    assert_eq!(msg, "Chain tip not found");
+                }
+                resp => {
+                    panic!("Unexpected response: {:?}", &resp);
+                }
+            }
+        }
+
+        // workers exit once the pool is dropped
+        drop(pool);
+    }
+}
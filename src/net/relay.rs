@@ -22,6 +22,7 @@ use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
+use std::sync::Arc;
 
 use rand::prelude::*;
 use rand::thread_rng;
@@ -38,6 +39,8 @@ use chainstate::stacks::db::{StacksChainState, StacksEpochReceipt, StacksHeaderI
 use chainstate::stacks::events::StacksTransactionReceipt;
 use core::mempool::MemPoolDB;
 use core::mempool::*;
+use monitoring;
+use net::admin::RuntimeToggles;
 use net::chat::*;
 use net::connection::*;
 use net::db::*;
@@ -54,6 +57,7 @@ use vm::costs::ExecutionCost;
 use crate::chainstate::coordinator::BlockEventDispatcher;
 use crate::types::chainstate::{PoxId, SortitionId};
 use chainstate::stacks::db::unconfirmed::ProcessedUnconfirmedState;
+use codec::StacksMessageCodec;
 use codec::MAX_PAYLOAD_LEN;
 use types::chainstate::BurnchainHeaderHash;
 
@@ -67,6 +71,166 @@ pub const RELAY_DUPLICATE_INFERENCE_WARMUP: usize = 128;
 pub struct Relayer {
     /// Connection to the p2p thread
     p2p: NetworkHandle,
+    /// Toggles shared with the p2p thread
+    runtime_toggles: Arc<RuntimeToggles>,
+    /// Which new transactions to forward to our neighbors
+    tx_relay_policy: TxRelayPolicy,
+    /// Whether to build the unconfirmed chain state from the microblocks we take in
+    microblock_acceptance: MicroblockAcceptance,
+}
+
+/// The payload types a transaction relay policy can allow, as named by
+/// `TransactionPayload::name()`
+pub const TX_RELAY_PAYLOAD_NAMES: &[&str] = &[
+    "TokenTransfer",
+    "ContractCall",
+    "SmartContract",
+    "PoisonMicroblock",
+    "Coinbase",
+];
+
+/// Which of the transactions we accept into our mempool we go on to forward to our neighbors.
+/// This is separate from mempool admission: a transaction the policy declines is still stored,
+/// mined and served over RPC, just not relayed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TxRelayPolicy {
+    /// Lowest fee rate to relay, in uSTX per byte (0 for no floor)
+    pub min_fee_rate: u64,
+    /// Longest transaction to relay, in bytes (0 for no cap)
+    pub max_tx_len: u64,
+    /// Payload types to relay, from `TX_RELAY_PAYLOAD_NAMES`; all of them if None
+    pub allowed_payloads: Option<Vec<String>>,
+}
+
+impl TxRelayPolicy {
+    pub fn from_connection_options(opts: &ConnectionOptions) -> TxRelayPolicy {
+        TxRelayPolicy {
+            min_fee_rate: opts.tx_relay_min_fee_rate,
+            max_tx_len: opts.tx_relay_max_len,
+            allowed_payloads: opts.tx_relay_allowed_payloads.clone(),
+        }
+    }
+
+    /// The rule `tx` breaks, if the policy declines to relay it
+    pub fn declined_by(&self, tx: &StacksTransaction) -> Option<&'static str> {
+        if let Some(ref allowed) = self.allowed_payloads {
+            let name = tx.payload.name();
+            if !allowed.iter().any(|allowed_name| allowed_name == name) {
+                return Some("payload");
+            }
+        }
+        if self.max_tx_len == 0 && self.min_fee_rate == 0 {
+            return None;
+        }
+        let tx_len = cmp::max(tx.serialize_to_vec().len() as u64, 1);
+        if self.max_tx_len > 0 && tx_len > self.max_tx_len {
+            return Some("size");
+        }
+        if tx.get_tx_fee() / tx_len < self.min_fee_rate {
+            return Some("fee");
+        }
+        None
+    }
+}
+
+/// How much of the unconfirmed microblock streams that peers push to us, or that clients upload
+/// with `POST /v2/microblocks`, to take in.  Confirmed streams are always downloaded once an
+/// anchored block confirms them, since the anchored block cannot be processed without them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MicroblockAcceptance {
+    /// Check, store and relay them, and execute them to build the unconfirmed chain state
+    Full,
+    /// Check their headers, and store and relay them, but do not execute them.  Nothing is
+    /// answered from unconfirmed state, but the streams are on hand once they are confirmed.
+    HeadersOnly,
+    /// Drop them, and refuse uploads with a 403
+    Off,
+}
+
+/// The names `MicroblockAcceptance::from_name()` accepts
+pub const MICROBLOCK_ACCEPTANCE_NAMES: &[&str] = &["full", "headers-only", "off"];
+
+impl MicroblockAcceptance {
+    pub fn from_name(name: &str) -> Option<MicroblockAcceptance> {
+        match name {
+            "full" => Some(MicroblockAcceptance::Full),
+            "headers-only" => Some(MicroblockAcceptance::HeadersOnly),
+            "off" => Some(MicroblockAcceptance::Off),
+            _ => None,
+        }
+    }
+
+    /// Whether to take in the microblocks peers push to us at all
+    pub fn accepts_pushed(&self) -> bool {
+        *self != MicroblockAcceptance::Off
+    }
+
+    /// Whether to execute the microblocks we take in, to build the unconfirmed chain state
+    pub fn builds_unconfirmed_state(&self) -> bool {
+        *self == MicroblockAcceptance::Full
+    }
+}
+
+/// The order in which to push a block we mined to our peers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlockPushOrder {
+    /// Sample the peers as for any block we relay: outbound peers by AS diversity, and inbound
+    /// peers by how rarely they send us duplicates
+    Relay,
+    /// The peers that answer our requests the fastest first
+    Latency,
+    /// The peers that most reliably answer our requests first
+    Reputation,
+    /// A random order
+    Random,
+}
+
+/// The names `BlockPushOrder::from_name()` accepts
+pub const BLOCK_PUSH_ORDER_NAMES: &[&str] = &["relay", "latency", "reputation", "random"];
+
+/// A peer a block we mined may be pushed to
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockPushCandidate {
+    pub neighbor: NeighborKey,
+    pub reply_latency_ms: Option<u64>,
+    pub health_score: f64,
+}
+
+impl BlockPushOrder {
+    pub fn from_name(name: &str) -> Option<BlockPushOrder> {
+        match name {
+            "relay" => Some(BlockPushOrder::Relay),
+            "latency" => Some(BlockPushOrder::Latency),
+            "reputation" => Some(BlockPushOrder::Reputation),
+            "random" => Some(BlockPushOrder::Random),
+            _ => None,
+        }
+    }
+
+    /// Put the candidates in the order to push to them.  Peers that have not answered a request
+    /// yet go last by latency.  `Relay` order samples the peers instead, so it leaves them as
+    /// they are.
+    pub fn sort(&self, mut candidates: Vec<BlockPushCandidate>) -> Vec<NeighborKey> {
+        match self {
+            BlockPushOrder::Relay => {}
+            BlockPushOrder::Latency => candidates.sort_by_key(|candidate| {
+                (
+                    candidate.reply_latency_ms.is_none(),
+                    candidate.reply_latency_ms,
+                )
+            }),
+            BlockPushOrder::Reputation => candidates.sort_by(|a, b| {
+                b.health_score
+                    .partial_cmp(&a.health_score)
+                    .unwrap_or(cmp::Ordering::Equal)
+            }),
+            BlockPushOrder::Random => candidates.shuffle(&mut thread_rng()),
+        }
+        candidates
+            .into_iter()
+            .map(|candidate| candidate.neighbor)
+            .collect()
+    }
 }
 
 #[derive(Debug)]
@@ -438,12 +602,31 @@ impl RelayerStats {
 
 impl Relayer {
     pub fn new(handle: NetworkHandle) -> Relayer {
-        Relayer { p2p: handle }
+        Relayer::with_runtime_toggles(handle, Arc::new(RuntimeToggles::default()))
+    }
+
+    pub fn with_runtime_toggles(
+        handle: NetworkHandle,
+        runtime_toggles: Arc<RuntimeToggles>,
+    ) -> Relayer {
+        Relayer {
+            p2p: handle,
+            runtime_toggles,
+            tx_relay_policy: TxRelayPolicy::default(),
+            microblock_acceptance: MicroblockAcceptance::Full,
+        }
     }
 
     pub fn from_p2p(network: &mut PeerNetwork) -> Relayer {
         let handle = network.new_handle(1024);
-        Relayer::new(handle)
+        let mut relayer = Relayer::with_runtime_toggles(handle, network.runtime_toggles.clone());
+        relayer.tx_relay_policy = TxRelayPolicy::from_connection_options(&network.connection_opts);
+        relayer.microblock_acceptance = network.connection_opts.microblock_acceptance;
+        relayer
+    }
+
+    pub fn runtime_toggles(&self) -> &RuntimeToggles {
+        &self.runtime_toggles
     }
 
     /// Given blocks pushed to us, verify that they correspond to expected block data.
@@ -1086,6 +1269,16 @@ impl Relayer {
             .broadcast_message(vec![], StacksMessageType::Blocks(blocks_data))
     }
 
+    /// Push a block we mined to our peers, in the order and waves the `mined_block_push_*`
+    /// connection options set out
+    pub fn push_mined_block(
+        &mut self,
+        consensus_hash: ConsensusHash,
+        block: StacksBlock,
+    ) -> Result<(), net_error> {
+        self.p2p.push_mined_block(consensus_hash, block)
+    }
+
     pub fn broadcast_microblock(
         &mut self,
         block_consensus_hash: &ConsensusHash,
@@ -1188,6 +1381,28 @@ impl Relayer {
         coord_comms: Option<&CoordinatorChannels>,
         event_observer: Option<&dyn MemPoolEventDispatcher>,
     ) -> Result<ProcessedNetReceipts, net_error> {
+        if self.runtime_toggles.low_disk_space() {
+            let num_discarded = network_result.discard_new_data();
+            if num_discarded > 0 {
+                warn!(
+                    "{:?}: Low on disk space; discarding {} new block(s), microblock stream(s) and transaction(s)",
+                    &_local_peer, num_discarded
+                );
+            }
+        }
+
+        if !self.microblock_acceptance.accepts_pushed() {
+            // the RPC server refuses these, but drop any that got through anyway
+            let num_dropped = network_result.uploaded_microblocks.len();
+            network_result.uploaded_microblocks.clear();
+            if num_dropped > 0 {
+                debug!(
+                    "{:?}: Not accepting unconfirmed microblocks; dropped {} uploaded stream(s)",
+                    &_local_peer, num_dropped
+                );
+            }
+        }
+
         match Relayer::process_new_blocks(network_result, sortdb, chainstate, coord_comms) {
             Ok((new_blocks, new_confirmed_microblocks, new_microblocks, bad_block_neighbors)) => {
                 // attempt to relay messages (note that this is all best-effort).
@@ -1266,18 +1481,40 @@ impl Relayer {
             event_observer,
         )?;
 
+        let relay_paused = self.runtime_toggles.tx_relay_paused();
         if new_txs.len() > 0 {
-            debug!(
-                "{:?}: Send {} transactions to neighbors",
-                &_local_peer,
-                new_txs.len()
-            );
+            if relay_paused {
+                debug!(
+                    "{:?}: Transaction relay is paused; not sending {} transactions to neighbors",
+                    &_local_peer,
+                    new_txs.len()
+                );
+            } else {
+                debug!(
+                    "{:?}: Send {} transactions to neighbors",
+                    &_local_peer,
+                    new_txs.len()
+                );
+            }
         }
 
         let mut mempool_txs_added = vec![];
         for (relayers, tx) in new_txs.into_iter() {
-            debug!("{:?}: Broadcast tx {}", &_local_peer, &tx.txid());
             mempool_txs_added.push(tx.clone());
+            if relay_paused {
+                continue;
+            }
+            if let Some(rule) = self.tx_relay_policy.declined_by(&tx) {
+                debug!(
+                    "{:?}: Relay policy declines tx {} ({})",
+                    &_local_peer,
+                    &tx.txid(),
+                    rule
+                );
+                monitoring::increment_tx_relays_declined(rule);
+                continue;
+            }
+            debug!("{:?}: Broadcast tx {}", &_local_peer, &tx.txid());
             let msg = StacksMessageType::Transaction(tx);
             if let Err(e) = self.p2p.broadcast_message(relayers, msg) {
                 warn!("Failed to broadcast transaction: {:?}", &e);
@@ -1287,7 +1524,8 @@ impl Relayer {
         let mut processed_unconfirmed_state = Default::default();
 
         // finally, refresh the unconfirmed chainstate, if need be
-        if network_result.has_microblocks() {
+        if network_result.has_microblocks() && self.microblock_acceptance.builds_unconfirmed_state()
+        {
             processed_unconfirmed_state = Relayer::refresh_unconfirmed(chainstate, sortdb);
         }
 
@@ -1611,6 +1849,120 @@ mod test {
     use clarity_vm::clarity::ClarityConnection;
     use types::chainstate::BlockHeaderHash;
 
+    #[test]
+    fn test_tx_relay_policy() {
+        let all_transactions = codec_all_transactions(
+            &TransactionVersion::Testnet,
+            0x80000000,
+            &TransactionAnchorMode::Any,
+            &TransactionPostConditionMode::Allow,
+        );
+
+        let policy = TxRelayPolicy::from_connection_options(&ConnectionOptions::default());
+        assert_eq!(policy, TxRelayPolicy::default());
+        for tx in all_transactions.iter() {
+            assert_eq!(policy.declined_by(tx), None);
+        }
+
+        let policy = TxRelayPolicy {
+            allowed_payloads: Some(vec!["TokenTransfer".to_string()]),
+            ..TxRelayPolicy::default()
+        };
+        for tx in all_transactions.iter() {
+            match tx.payload {
+                TransactionPayload::TokenTransfer(..) => assert_eq!(policy.declined_by(tx), None),
+                _ => assert_eq!(policy.declined_by(tx), Some("payload")),
+            }
+        }
+
+        for tx in all_transactions.iter() {
+            let tx_len = tx.serialize_to_vec().len() as u64;
+            let policy = TxRelayPolicy {
+                max_tx_len: tx_len,
+                ..TxRelayPolicy::default()
+            };
+            assert_eq!(policy.declined_by(tx), None);
+            let policy = TxRelayPolicy {
+                max_tx_len: tx_len - 1,
+                ..TxRelayPolicy::default()
+            };
+            assert_eq!(policy.declined_by(tx), Some("size"));
+
+            let fee_rate = tx.get_tx_fee() / tx_len;
+            let policy = TxRelayPolicy {
+                min_fee_rate: fee_rate,
+                ..TxRelayPolicy::default()
+            };
+            assert_eq!(policy.declined_by(tx), None);
+            let policy = TxRelayPolicy {
+                min_fee_rate: fee_rate + 1,
+                ..TxRelayPolicy::default()
+            };
+            assert_eq!(policy.declined_by(tx), Some("fee"));
+        }
+    }
+
+    #[test]
+    fn test_microblock_acceptance() {
+        for name in MICROBLOCK_ACCEPTANCE_NAMES.iter() {
+            assert!(MicroblockAcceptance::from_name(name).is_some());
+        }
+        assert_eq!(MicroblockAcceptance::from_name("headers"), None);
+
+        let full = MicroblockAcceptance::Full;
+        assert!(full.accepts_pushed() && full.builds_unconfirmed_state());
+        let headers_only = MicroblockAcceptance::HeadersOnly;
+        assert!(headers_only.accepts_pushed() && !headers_only.builds_unconfirmed_state());
+        let off = MicroblockAcceptance::Off;
+        assert!(!off.accepts_pushed() && !off.builds_unconfirmed_state());
+        assert_eq!(ConnectionOptions::default().microblock_acceptance, full);
+    }
+
+    #[test]
+    fn test_block_push_order() {
+        for name in BLOCK_PUSH_ORDER_NAMES.iter() {
+            assert!(BlockPushOrder::from_name(name).is_some());
+        }
+        assert_eq!(BlockPushOrder::from_name("fastest"), None);
+        assert_eq!(
+            ConnectionOptions::default().mined_block_push_order,
+            BlockPushOrder::Relay
+        );
+
+        let candidate =
+            |port: u16, reply_latency_ms: Option<u64>, health_score: f64| BlockPushCandidate {
+                neighbor: NeighborKey {
+                    peer_version: 0x18000000,
+                    network_id: 0x80000000,
+                    addrbytes: PeerAddress([0u8; 16]),
+                    port,
+                },
+                reply_latency_ms,
+                health_score,
+            };
+        let candidates = vec![
+            candidate(1, None, 0.9),
+            candidate(2, Some(300), 0.5),
+            candidate(3, Some(20), 0.2),
+            candidate(4, Some(150), 1.0),
+        ];
+        let ports = |order: BlockPushOrder| -> Vec<u16> {
+            order
+                .sort(candidates.clone())
+                .into_iter()
+                .map(|nk| nk.port)
+                .collect()
+        };
+
+        assert_eq!(ports(BlockPushOrder::Latency), vec![3, 4, 2, 1]);
+        assert_eq!(ports(BlockPushOrder::Reputation), vec![4, 1, 2, 3]);
+        assert_eq!(ports(BlockPushOrder::Relay), vec![1, 2, 3, 4]);
+
+        let mut shuffled = ports(BlockPushOrder::Random);
+        shuffled.sort();
+        assert_eq!(shuffled, vec![1, 2, 3, 4]);
+    }
+
     #[test]
     fn test_relayer_stats_add_relyed_messages() {
         let mut relay_stats = RelayerStats::new();
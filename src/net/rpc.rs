@@ -17,6 +17,8 @@
  along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
 */
 
+use std::cmp;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
@@ -24,6 +26,7 @@ use std::io;
 use std::io::prelude::*;
 use std::io::{Read, Seek, SeekFrom, Write};
 use std::net::SocketAddr;
+use std::sync::mpsc::{Receiver, TryRecvError};
 use std::{convert::TryFrom, fmt};
 
 use rand::prelude::*;
@@ -36,15 +39,24 @@ use burnchains::BurnchainView;
 use burnchains::*;
 use chainstate::burn::db::sortdb::SortitionDB;
 use chainstate::burn::ConsensusHash;
+use chainstate::coordinator::get_reward_set_for_cycle;
+use chainstate::stacks::boot;
 use chainstate::stacks::db::blocks::CheckError;
+use chainstate::stacks::db::blocks::MicroblockEquivocation;
 use chainstate::stacks::db::{
     blocks::MINIMUM_TX_FEE_RATE_PER_BYTE, BlockStreamData, StacksChainState,
 };
+use chainstate::stacks::index::marf::MarfConnection;
+use chainstate::stacks::miner::BlockProposalValidation;
 use chainstate::stacks::Error as chain_error;
 use chainstate::stacks::*;
 use clarity_vm::clarity::ClarityConnection;
 use core::mempool::*;
+use core::schedule;
 use monitoring;
+use net::admin::{
+    BearerToken, BurnOpSubmitter, MinerWalletReader, RuntimeToggles, TransferStxOpRequest,
+};
 use net::atlas::{AtlasDB, Attachment, MAX_ATTACHMENT_INV_PAGES_PER_REQUEST};
 use net::connection::ConnectionHttp;
 use net::connection::ConnectionOptions;
@@ -53,6 +65,7 @@ use net::db::PeerDB;
 use net::http::*;
 use net::p2p::PeerMap;
 use net::p2p::PeerNetwork;
+use net::readonly_pool::{ReadOnlyCall, ReadOnlyCallPool};
 use net::relay::Relayer;
 use net::ClientError;
 use net::Error as net_error;
@@ -66,27 +79,55 @@ use net::NeighborsData;
 use net::PeerAddress;
 use net::PeerHost;
 use net::ProtocolFamily;
+use net::RPCBlockProposalRequest;
+use net::RPCBurnOpSubmissionData;
+use net::RPCConsensusScheduleData;
+use net::RPCRequestDeadline;
+use net::RPCTransferStxOpRequest;
 use net::StacksHttp;
 use net::StacksHttpMessage;
 use net::StacksMessageType;
+use net::TipRequest;
 use net::UnconfirmedTransactionResponse;
 use net::UnconfirmedTransactionStatus;
 use net::UrlString;
 use net::HTTP_REQUEST_ID_RESERVED;
 use net::MAX_NEIGHBORS_DATA_LEN;
+use net::RPC_DEADLINE_EXCEEDED_MESSAGE;
 use net::{
-    AccountEntryResponse, AttachmentPage, CallReadOnlyResponse, ContractSrcResponse,
-    GetAttachmentResponse, GetAttachmentsInvResponse, MapEntryResponse,
+    AccountEntryResponse, AttachmentPage, CallReadOnlyResponse, ClarityStateOverride,
+    ContractSrcResponse, GetAttachmentResponse, GetAttachmentsInvResponse, MapEntryResponse,
+    SimulateContractCallResponse,
 };
 use net::{BlocksData, GetIsTraitImplementedResponse};
+use net::{
+    RPCAccountBalanceData, RPCDelegationInfo, RPCSponsorshipData, RPCStackingStatusData,
+    RPCVestingEntry,
+};
+use net::{
+    RPCAccountTransaction, RPCAccountTransactionsData, RPCAccountTransactionsNextPage,
+    RPCTransactionReceiptData,
+};
+use net::{RPCAdminTogglesUpdate, RPCRewardSetData, RPCRewardSetEntry};
+use net::{RPCBlockProposalData, RPCBlockProposalRejection};
+use net::{RPCContractEvent, RPCContractEventsData, RPCContractEventsNextPage};
+use net::{RPCDelegationsData, RPCDelegatorInfo};
+use net::{RPCMicroblockEquivocation, RPCMicroblockEquivocationsData};
+use net::{RPCMissingPoxAnchor, RPCPoxAnchorStatus};
 use net::{RPCNeighbor, RPCNeighborsInfo};
 use net::{RPCPeerInfoData, RPCPoxInfoData};
+use net::{RPCPostConditionResult, RPCPostConditionsPreviewData};
+use net::{RPCSortitionData, SortitionLookup};
 use util::db::DBConn;
 use util::db::Error as db_error;
+use util::db::{begin_read_snapshot, end_read_snapshot};
+use util::get_epoch_time_ms;
 use util::get_epoch_time_secs;
 use util::hash::Hash160;
 use util::hash::{hex_bytes, to_hex};
+use vm::contexts::OwnedEnvironment;
 use vm::database::clarity_store::make_contract_hash_key;
+use vm::profiler;
 use vm::types::TraitIdentifier;
 use vm::{
     analysis::errors::CheckErrors,
@@ -97,10 +138,11 @@ use vm::{
     errors::Error as ClarityRuntimeError,
     errors::Error::Unchecked,
     errors::InterpreterError,
-    types::{PrincipalData, QualifiedContractIdentifier, StandardPrincipalData},
+    types::{PrincipalData, QualifiedContractIdentifier, StandardPrincipalData, TupleData},
     ClarityName, ContractName, SymbolicExpression, Value,
 };
 
+use crate::address::AddressHashMode;
 use crate::clarity_vm::database::marf::MarfedKV;
 use crate::types::chainstate::BlockHeaderHash;
 use crate::types::chainstate::{
@@ -115,11 +157,30 @@ use super::{RPCPoxCurrentCycleInfo, RPCPoxNextCycleInfo};
 
 pub const STREAM_CHUNK_SIZE: u64 = 4096;
 
+/// Most microblock equivocations to return on GET /v2/microblocks/equivocations
+pub const MAX_MICROBLOCK_EQUIVOCATIONS_RESULTS: u32 = 100;
+
+/// Most principals that GET /v2/delegations/:principal will check for a single delegatee
+pub const MAX_DELEGATION_CANDIDATES: u32 = 4096;
+
 #[derive(Default)]
 pub struct RPCHandlerArgs<'a> {
     pub exit_at_block_height: Option<&'a u64>,
     pub genesis_chainstate_hash: Sha256Sum,
     pub event_observer: Option<&'a dyn MemPoolEventDispatcher>,
+    /// If given, read-only function calls are evaluated on this pool's worker threads
+    pub readonly_call_pool: Option<&'a ReadOnlyCallPool>,
+    /// The token admin requests must present.  The admin endpoints are disabled without one.
+    pub admin_auth_token: Option<&'a str>,
+    /// The toggles the admin endpoints read and set
+    pub runtime_toggles: Option<&'a RuntimeToggles>,
+    /// Submits the burn operations requested through the admin endpoints, if the node can
+    pub burn_op_submitter: Option<&'a dyn BurnOpSubmitter>,
+    /// Reads the miner's burnchain wallet for the admin endpoints, if the node mines
+    pub miner_wallet_reader: Option<&'a dyn MinerWalletReader>,
+    /// The Stacks block heights genesis lockups unlock at.  Without them, account balances are
+    /// reported without vesting schedules.
+    pub genesis_unlock_heights: Option<&'a BTreeSet<u64>>,
 }
 
 pub struct ConversationHttp {
@@ -145,6 +206,15 @@ pub struct ConversationHttp {
         bool,
     )>,
 
+    // read-only call being evaluated by the read-only call pool, whose reply must be sent before
+    // any subsequent replies (or a 503, once the call's deadline passes)
+    pending_readonly_call: Option<(
+        ReplyHandleHttp,
+        Receiver<HttpResponseType>,
+        HttpResponseMetadata,
+        Option<RPCRequestDeadline>,
+    )>,
+
     // our outstanding request/response to the remote peer, if any
     pending_request: Option<ReplyHandleHttp>,
     pending_response: Option<HttpResponseType>,
@@ -219,6 +289,33 @@ impl RPCPeerInfoData {
             None => (StacksBlockId([0x00; 32]), 0),
         };
 
+        // best-effort: omitted if the chains coordinator has not cached the missing anchor
+        // blocks for this reward cycle yet, or if they can't be read
+        let pox_anchor_status = match (
+            sortdb.get_cached_missing_pox_anchors(burnchain, &burnchain_tip.sortition_id),
+            SortitionDB::get_pox_anchor_overrides(sortdb.conn()),
+        ) {
+            (Ok(Some(missing)), Ok(overridden_reward_cycles)) => {
+                let missing_anchors: Vec<_> = missing
+                    .into_iter()
+                    .map(|(reward_cycle, anchor_block_hash)| RPCMissingPoxAnchor {
+                        reward_cycle,
+                        anchor_block_hash,
+                    })
+                    .collect();
+                Some(RPCPoxAnchorStatus {
+                    stalled: missing_anchors.len() > 0,
+                    missing_anchors,
+                    overridden_reward_cycles,
+                })
+            }
+            (Ok(None), Ok(_)) => None,
+            (Err(e), _) | (_, Err(e)) => {
+                debug!("Failed to load PoX anchor status: {:?}", &e);
+                None
+            }
+        };
+
         Ok(RPCPeerInfoData {
             peer_version: burnchain.peer_version,
             pox_consensus: burnchain_tip.consensus_hash,
@@ -235,6 +332,7 @@ impl RPCPeerInfoData {
             unanchored_seq: unconfirmed_seq,
             exit_at_block_height: exit_at_block_height.cloned(),
             genesis_chainstate_hash: genesis_chainstate_hash.clone(),
+            pox_anchor_status,
         })
     }
 }
@@ -471,6 +569,707 @@ impl RPCNeighborsInfo {
     }
 }
 
+impl RPCRewardSetData {
+    /// Compute the reward set for `reward_cycle` on the canonical sortition fork.  Returns Ok(None)
+    /// if there is no reward set for this reward cycle (e.g. no anchor block was chosen, or it
+    /// has not been chosen yet).
+    pub fn from_db(
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        burnchain: &Burnchain,
+        reward_cycle: u64,
+    ) -> Result<Option<RPCRewardSetData>, net_error> {
+        let sortition_tip = SortitionDB::get_canonical_sortition_tip(sortdb.conn())?;
+        let reward_set = match get_reward_set_for_cycle(
+            reward_cycle,
+            &sortition_tip,
+            burnchain,
+            chainstate,
+            sortdb,
+        )
+        .map_err(|e| {
+            net_error::ChainstateError(format!("Failed to compute reward set: {:?}", &e))
+        })? {
+            Some(reward_set) => reward_set,
+            None => {
+                return Ok(None);
+            }
+        };
+
+        // one entry per PoX address, in the order in which they registered
+        let mut entries: Vec<RPCRewardSetEntry> = vec![];
+        let mut entry_index = HashMap::new();
+        for (addr, total_ustx) in reward_set.registered_addrs.iter() {
+            let ix = *entry_index.entry(addr.clone()).or_insert_with(|| {
+                entries.push(RPCRewardSetEntry {
+                    pox_address: addr.clone().to_b58(),
+                    slots: 0,
+                    total_ustx: 0,
+                });
+                entries.len() - 1
+            });
+            entries[ix].total_ustx += *total_ustx as u64;
+        }
+        for addr in reward_set.reward_set.iter() {
+            if let Some(ix) = entry_index.get(addr) {
+                entries[*ix].slots += 1;
+            }
+        }
+
+        Ok(Some(RPCRewardSetData {
+            reward_cycle,
+            anchor_block_hash: reward_set.anchor_block_hash,
+            anchor_consensus_hash: reward_set.anchor_consensus_hash,
+            min_threshold_ustx: reward_set.threshold as u64,
+            stacked_ustx: reward_set.participation as u64,
+            total_slots: reward_set.reward_set.len() as u64,
+            entries,
+        }))
+    }
+}
+
+/// Which of the databases an RPC request reads from are pinned to a read snapshot.  A connection
+/// that is already in a transaction is left alone.
+pub struct RPCReadSnapshot {
+    sortdb: bool,
+    chainstate: bool,
+    clarity_state: bool,
+    atlasdb: bool,
+    mempool: bool,
+}
+
+impl RPCReadSnapshot {
+    /// Pin the sortition DB, chainstate and Clarity state, and the Atlas DB and mempool if given,
+    /// to what they hold right now
+    pub fn begin(
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        atlasdb: Option<&AtlasDB>,
+        mempool: Option<&MemPoolDB>,
+    ) -> Result<RPCReadSnapshot, net_error> {
+        let mut snapshot = RPCReadSnapshot {
+            sortdb: false,
+            chainstate: false,
+            clarity_state: false,
+            atlasdb: false,
+            mempool: false,
+        };
+        if let Err(e) = snapshot.begin_all(sortdb, chainstate, atlasdb, mempool) {
+            // don't leave behind the snapshots that did begin
+            let _ = snapshot.end(sortdb, chainstate, atlasdb, mempool);
+            return Err(e);
+        }
+        Ok(snapshot)
+    }
+
+    fn begin_all(
+        &mut self,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        atlasdb: Option<&AtlasDB>,
+        mempool: Option<&MemPoolDB>,
+    ) -> Result<(), net_error> {
+        self.sortdb = begin_read_snapshot(sortdb.conn())?;
+        self.chainstate = begin_read_snapshot(chainstate.db())?;
+        self.clarity_state = chainstate
+            .clarity_state
+            .with_marf(|marf| begin_read_snapshot(marf.sqlite_conn()))?;
+        if let Some(atlasdb) = atlasdb {
+            self.atlasdb = begin_read_snapshot(atlasdb.conn())?;
+        }
+        if let Some(mempool) = mempool {
+            self.mempool = begin_read_snapshot(mempool.conn())?;
+        }
+        Ok(())
+    }
+
+    /// Release the snapshot.  Pass the same databases as to `begin()`.
+    pub fn end(
+        self,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        atlasdb: Option<&AtlasDB>,
+        mempool: Option<&MemPoolDB>,
+    ) -> Result<(), net_error> {
+        if self.sortdb {
+            end_read_snapshot(sortdb.conn())?;
+        }
+        if self.chainstate {
+            end_read_snapshot(chainstate.db())?;
+        }
+        if self.clarity_state {
+            chainstate
+                .clarity_state
+                .with_marf(|marf| end_read_snapshot(marf.sqlite_conn()))?;
+        }
+        if let (true, Some(atlasdb)) = (self.atlasdb, atlasdb) {
+            end_read_snapshot(atlasdb.conn())?;
+        }
+        if let (true, Some(mempool)) = (self.mempool, mempool) {
+            end_read_snapshot(mempool.conn())?;
+        }
+        Ok(())
+    }
+}
+
+impl RPCConsensusScheduleData {
+    pub fn from_burnchain(
+        burnchain: &Burnchain,
+        burn_block_height: u64,
+    ) -> RPCConsensusScheduleData {
+        let schedule = schedule::consensus_schedule(burnchain);
+        let active = schedule::active_change(&schedule, burn_block_height).cloned();
+        let next = schedule::next_change(&schedule, burn_block_height).cloned();
+        let upgrade_required = schedule
+            .iter()
+            .any(|change| change.activation_height > burn_block_height && !change.supported);
+        RPCConsensusScheduleData {
+            burn_block_height,
+            blocks_until_next: next
+                .as_ref()
+                .map(|change| change.activation_height - burn_block_height),
+            active,
+            next,
+            upgrade_required,
+            schedule,
+        }
+    }
+
+    /// The consensus schedule as of the canonical burnchain tip
+    pub fn from_db(
+        sortdb: &SortitionDB,
+        burnchain: &Burnchain,
+    ) -> Result<RPCConsensusScheduleData, net_error> {
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())?;
+        Ok(RPCConsensusScheduleData::from_burnchain(
+            burnchain,
+            tip.block_height,
+        ))
+    }
+}
+
+impl RPCMicroblockEquivocation {
+    pub fn from_equivocation(equivocation: &MicroblockEquivocation) -> RPCMicroblockEquivocation {
+        RPCMicroblockEquivocation {
+            id: equivocation.id,
+            parent_index_block_hash: equivocation.parent_index_block_hash.clone(),
+            sequence: equivocation.sequence,
+            microblock_hash_1: equivocation.header_1.block_hash(),
+            microblock_hash_2: equivocation.header_2.block_hash(),
+            header_1: to_hex(&equivocation.header_1.serialize_to_vec()),
+            header_2: to_hex(&equivocation.header_2.serialize_to_vec()),
+            detected_at: equivocation.detected_at,
+        }
+    }
+}
+
+impl RPCMicroblockEquivocationsData {
+    /// The most recently detected microblock equivocations
+    pub fn from_db(
+        chainstate: &StacksChainState,
+    ) -> Result<RPCMicroblockEquivocationsData, net_error> {
+        let equivocations = StacksChainState::get_recent_microblock_equivocations(
+            chainstate.db(),
+            MAX_MICROBLOCK_EQUIVOCATIONS_RESULTS,
+        )?;
+        Ok(RPCMicroblockEquivocationsData {
+            equivocations: equivocations
+                .iter()
+                .map(RPCMicroblockEquivocation::from_equivocation)
+                .collect(),
+        })
+    }
+}
+
+impl RPCBlockProposalData {
+    pub fn from_validation(
+        block: &StacksBlock,
+        validation: &BlockProposalValidation,
+    ) -> RPCBlockProposalData {
+        RPCBlockProposalData {
+            accepted: validation.accepted(),
+            block_hash: block.block_hash(),
+            parent_index_block_hash: validation
+                .parent
+                .as_ref()
+                .map(|parent| parent.index_block_hash()),
+            stacks_block_height: block.header.total_work.work,
+            tx_count: block.txs.len() as u64,
+            size: block.serialize_to_vec().len() as u64,
+            fees_ustx: validation.fees,
+            cost: validation.cost.clone(),
+            rejections: validation
+                .rejections
+                .iter()
+                .map(|rejection| RPCBlockProposalRejection {
+                    reason: rejection.reason().to_string(),
+                    message: rejection.to_string(),
+                    txid: rejection.txid().map(|txid| format!("{}", txid)),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl RPCSortitionData {
+    /// Look up a burnchain block's sortition on the canonical sortition fork, or the sortition a
+    /// processed Stacks block won.  Returns Ok(None) if there is no such sortition.
+    pub fn from_db(
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+        lookup: &SortitionLookup,
+    ) -> Result<Option<RPCSortitionData>, net_error> {
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())?;
+        let handle = sortdb.index_handle(&tip.sortition_id);
+        let snapshot_opt = match lookup {
+            SortitionLookup::BurnHeight(height) => {
+                if *height > tip.block_height {
+                    None
+                } else {
+                    handle.get_block_snapshot_by_height(*height)?
+                }
+            }
+            SortitionLookup::BurnHash(burn_hash) => handle.get_block_snapshot(burn_hash)?,
+            SortitionLookup::StacksBlock(index_block_hash) => {
+                match StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+                    chainstate.db(),
+                    index_block_hash,
+                )? {
+                    Some(header) => SortitionDB::get_block_snapshot_consensus(
+                        sortdb.conn(),
+                        &header.consensus_hash,
+                    )?,
+                    None => None,
+                }
+            }
+        };
+        let snapshot = match snapshot_opt {
+            Some(snapshot) => snapshot,
+            None => {
+                return Ok(None);
+            }
+        };
+
+        // a Stacks block can have won a sortition on a fork the canonical one replaced
+        let canonical = snapshot.block_height <= tip.block_height
+            && handle
+                .get_block_snapshot_by_height(snapshot.block_height)?
+                .map(|ancestor| ancestor.sortition_id)
+                == Some(snapshot.sortition_id.clone());
+
+        let (winning_block_txid, stacks_block_hash, index_block_hash, stacks_block_height) =
+            if snapshot.sortition {
+                let index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &snapshot.consensus_hash,
+                    &snapshot.winning_stacks_block_hash,
+                );
+                let stacks_block_height =
+                    StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+                        chainstate.db(),
+                        &index_block_hash,
+                    )?
+                    .map(|header| header.block_height);
+                (
+                    Some(format!("{}", &snapshot.winning_block_txid)),
+                    Some(snapshot.winning_stacks_block_hash.clone()),
+                    Some(index_block_hash),
+                    stacks_block_height,
+                )
+            } else {
+                (None, None, None, None)
+            };
+
+        Ok(Some(RPCSortitionData {
+            burn_block_height: snapshot.block_height,
+            burn_block_hash: format!("{}", &snapshot.burn_header_hash),
+            burn_block_timestamp: snapshot.burn_header_timestamp,
+            parent_burn_block_hash: format!("{}", &snapshot.parent_burn_header_hash),
+            consensus_hash: snapshot.consensus_hash,
+            sortition_id: format!("{}", &snapshot.sortition_id),
+            canonical,
+            sortition: snapshot.sortition,
+            total_burn: snapshot.total_burn,
+            winning_block_txid,
+            stacks_block_hash,
+            index_block_hash,
+            stacks_block_height,
+        }))
+    }
+}
+
+impl RPCSponsorshipData {
+    pub fn from_db(
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        mempool: &MemPoolDB,
+        tip: &StacksBlockId,
+        sponsor: &StacksAddress,
+    ) -> Result<RPCSponsorshipData, net_error> {
+        let principal = sponsor.to_account_principal();
+        let (nonce, balance) = chainstate
+            .maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly(|clarity_db| {
+                    let burn_block_height = clarity_db.get_current_burnchain_block_height() as u64;
+                    let balance = clarity_db
+                        .get_account_stx_balance(&principal)
+                        .get_available_balance_at_burn_block(burn_block_height);
+                    (clarity_db.get_account_nonce(&principal), balance)
+                })
+            })
+            .map_err(|_| net_error::NotFoundError)?
+            .ok_or(net_error::NotFoundError)?;
+
+        let (pending_sponsored_txs, _) =
+            MemPoolDB::get_pending_sponsorships(mempool.conn(), sponsor, nonce, None)?;
+        let reserved_fees = MemPoolDB::get_pending_fees(mempool.conn(), sponsor, nonce, None)?;
+        let next_sponsor_nonce = cmp::max(
+            nonce,
+            MemPoolDB::get_next_nonce_for_address(mempool.conn(), sponsor)?,
+        );
+
+        Ok(RPCSponsorshipData {
+            principal: principal.to_string(),
+            nonce,
+            next_sponsor_nonce,
+            pending_sponsored_txs,
+            reserved_fees,
+            balance: format!("0x{}", to_hex(&balance.to_be_bytes())),
+        })
+    }
+}
+
+impl RPCAccountBalanceData {
+    pub fn from_db(
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        principal: &PrincipalData,
+        unlock_heights: Option<&BTreeSet<u64>>,
+    ) -> Result<RPCAccountBalanceData, net_error> {
+        let mainnet = chainstate.mainnet;
+        chainstate
+            .maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                let (balance, stacks_block_height, burn_block_height) = clarity_tx
+                    .with_clarity_db_readonly(|clarity_db| {
+                        (
+                            clarity_db.get_account_stx_balance(principal),
+                            clarity_db.get_current_block_height() as u64,
+                            clarity_db.get_current_burnchain_block_height() as u64,
+                        )
+                    });
+                let vesting = unlock_heights.map(|unlock_heights| {
+                    StacksChainState::get_account_vesting_schedule(
+                        clarity_tx,
+                        mainnet,
+                        principal,
+                        unlock_heights,
+                    )
+                    .into_iter()
+                    .map(|(block_height, amount)| RPCVestingEntry {
+                        block_height,
+                        amount_ustx: amount as u64,
+                    })
+                    .collect::<Vec<_>>()
+                });
+
+                let unlocked = balance.get_available_balance_at_burn_block(burn_block_height);
+                let (locked, unlock_height) =
+                    balance.get_locked_balance_at_burn_block(burn_block_height);
+                let vesting_ustx = vesting
+                    .as_ref()
+                    .map(|entries| entries.iter().map(|entry| entry.amount_ustx).sum())
+                    .unwrap_or(0);
+
+                RPCAccountBalanceData {
+                    principal: principal.to_string(),
+                    balance: format!("0x{}", to_hex(&unlocked.to_be_bytes())),
+                    locked: format!("0x{}", to_hex(&locked.to_be_bytes())),
+                    unlock_height,
+                    stacks_block_height,
+                    burn_block_height,
+                    vesting,
+                    vesting_ustx,
+                }
+            })
+            .map_err(|_| net_error::NotFoundError)?
+            .ok_or(net_error::NotFoundError)
+    }
+}
+
+impl RPCStackingStatusData {
+    pub fn from_db(
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        principal: &PrincipalData,
+    ) -> Result<RPCStackingStatusData, net_error> {
+        let mainnet = chainstate.mainnet;
+        let contract_identifier = util::boot::boot_code_id("pox", mainnet);
+        let sender = PrincipalData::Standard(StandardPrincipalData::transient());
+
+        let data = chainstate
+            .maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                let (locked, unlock_height) = clarity_tx.with_clarity_db_readonly(|clarity_db| {
+                    let burn_block_height = clarity_db.get_current_burnchain_block_height() as u64;
+                    clarity_db
+                        .get_account_stx_balance(principal)
+                        .get_locked_balance_at_burn_block(burn_block_height)
+                });
+                // both of these return `none` if the stacking lock (or delegation) has expired
+                clarity_tx
+                    .with_readonly_clarity_env(
+                        mainnet,
+                        sender,
+                        LimitedCostTracker::new_free(),
+                        |env| {
+                            let stacker_info = env.eval_read_only(
+                                &contract_identifier,
+                                &format!("(get-stacker-info '{})", principal),
+                            )?;
+                            let delegation_info = env.eval_read_only(
+                                &contract_identifier,
+                                &format!("(get-check-delegation '{})", principal),
+                            )?;
+                            Ok((stacker_info, delegation_info))
+                        },
+                    )
+                    .map(|(stacker_info, delegation_info)| {
+                        (locked, unlock_height, stacker_info, delegation_info)
+                    })
+            })
+            .map_err(|_| net_error::NotFoundError)?;
+
+        let (locked, unlock_height, stacker_info, delegation_info) = match data {
+            Some(Ok(res)) => res,
+            Some(Err(e)) => {
+                return Err(net_error::ChainstateError(format!(
+                    "Failed to query PoX state: {:?}",
+                    &e
+                )));
+            }
+            None => return Err(net_error::NotFoundError),
+        };
+
+        let stacker_info = stacker_info.expect_optional().map(|v| v.expect_tuple());
+        let (first_reward_cycle, lock_period, pox_address) = match stacker_info {
+            Some(info) => (
+                Some(
+                    info.get("first-reward-cycle")
+                        .expect("FATAL: no 'first-reward-cycle'")
+                        .to_owned()
+                        .expect_u128() as u64,
+                ),
+                Some(
+                    info.get("lock-period")
+                        .expect("FATAL: no 'lock-period'")
+                        .to_owned()
+                        .expect_u128() as u64,
+                ),
+                boot::pox_addr_tuple_to_b58(
+                    mainnet,
+                    &info
+                        .get("pox-addr")
+                        .expect("FATAL: no 'pox-addr'")
+                        .to_owned()
+                        .expect_tuple(),
+                ),
+            ),
+            None => (None, None, None),
+        };
+
+        let delegation = delegation_info
+            .expect_optional()
+            .map(|v| v.expect_tuple())
+            .map(|info| RPCDelegationInfo {
+                delegated_to: info
+                    .get("delegated-to")
+                    .expect("FATAL: no 'delegated-to'")
+                    .to_owned()
+                    .expect_principal()
+                    .to_string(),
+                amount_ustx: info
+                    .get("amount-ustx")
+                    .expect("FATAL: no 'amount-ustx'")
+                    .to_owned()
+                    .expect_u128() as u64,
+                until_burn_height: info
+                    .get("until-burn-ht")
+                    .expect("FATAL: no 'until-burn-ht'")
+                    .to_owned()
+                    .expect_optional()
+                    .map(|v| v.expect_u128() as u64),
+                pox_address: info
+                    .get("pox-addr")
+                    .expect("FATAL: no 'pox-addr'")
+                    .to_owned()
+                    .expect_optional()
+                    .and_then(|v| boot::pox_addr_tuple_to_b58(mainnet, &v.expect_tuple())),
+            });
+
+        Ok(RPCStackingStatusData {
+            principal: principal.to_string(),
+            is_stacking: first_reward_cycle.is_some(),
+            locked_ustx: locked as u64,
+            unlock_height,
+            first_reward_cycle,
+            lock_period,
+            pox_address,
+            delegation,
+        })
+    }
+}
+
+impl RPCDelegationsData {
+    /// Find the principals with an unexpired delegation to `delegated_to` at `tip`.  The
+    /// candidates come from this node's index of processed `delegate-stx` calls; each one is
+    /// checked against the PoX contract's `delegation-state` map, and the checks are charged
+    /// against `cost_limit`.  A delegatee with more than `MAX_DELEGATION_CANDIDATES` candidates,
+    /// or whose checks cost more than `cost_limit`, gets a `ClientError`.
+    pub fn from_db(
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        delegated_to: &PrincipalData,
+        mut cost_limit: ExecutionCost,
+    ) -> Result<RPCDelegationsData, net_error> {
+        let mainnet = chainstate.mainnet;
+        let contract_identifier = util::boot::boot_code_id("pox", mainnet);
+        let sender = PrincipalData::Standard(StandardPrincipalData::transient());
+        cost_limit.write_length = 0;
+        cost_limit.write_count = 0;
+
+        let candidates = StacksChainState::get_pox_delegation_candidates(
+            chainstate.db(),
+            delegated_to,
+            MAX_DELEGATION_CANDIDATES + 1,
+        )
+        .map_err(|e| net_error::ChainstateError(format!("{:?}", &e)))?;
+        if candidates.len() > MAX_DELEGATION_CANDIDATES as usize {
+            return Err(net_error::ClientError(ClientError::Message(format!(
+                "More than {} principals have delegated to {}",
+                MAX_DELEGATION_CANDIDATES, delegated_to
+            ))));
+        }
+
+        let data = chainstate
+            .maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                let (balances, cost_track): (Vec<_>, _) =
+                    clarity_tx.with_clarity_db_readonly(|clarity_db| {
+                        let burn_block_height =
+                            clarity_db.get_current_burnchain_block_height() as u64;
+                        let balances = candidates
+                            .iter()
+                            .map(|stacker| {
+                                clarity_db
+                                    .get_account_stx_balance(stacker)
+                                    .get_locked_balance_at_burn_block(burn_block_height)
+                            })
+                            .collect();
+                        let cost_track =
+                            LimitedCostTracker::new_mid_block(mainnet, cost_limit, clarity_db);
+                        (balances, cost_track)
+                    });
+                let cost_track = cost_track.map_err(|_| {
+                    ClarityRuntimeError::from(InterpreterError::CostContractLoadFailure)
+                })?;
+                // returns `none` if the delegation has expired or been revoked
+                clarity_tx.with_readonly_clarity_env(mainnet, sender, cost_track, |env| {
+                    let mut delegations = vec![];
+                    for (stacker, (locked, unlock_height)) in
+                        candidates.iter().zip(balances.into_iter())
+                    {
+                        let delegation_info = env.eval_read_only(
+                            &contract_identifier,
+                            &format!("(get-check-delegation '{})", stacker),
+                        )?;
+                        delegations.push((stacker, locked, unlock_height, delegation_info));
+                    }
+                    Ok(delegations)
+                })
+            })
+            .map_err(|_| net_error::NotFoundError)?;
+
+        let delegations = match data {
+            Some(Ok(res)) => res,
+            Some(Err(Unchecked(CheckErrors::CostBalanceExceeded(..)))) => {
+                return Err(net_error::ClientError(ClientError::Message(format!(
+                    "Checking the delegations to {} exceeds this node's read-only call budget",
+                    delegated_to
+                ))));
+            }
+            Some(Err(e)) => {
+                return Err(net_error::ChainstateError(format!(
+                    "Failed to query PoX state: {:?}",
+                    &e
+                )));
+            }
+            None => return Err(net_error::NotFoundError),
+        };
+
+        let mut delegators = vec![];
+        let mut total_delegated_ustx: u128 = 0;
+        let mut total_locked_ustx: u128 = 0;
+        for (stacker, locked, unlock_height, delegation_info) in delegations.into_iter() {
+            let info = match delegation_info.expect_optional() {
+                Some(info) => info.expect_tuple(),
+                None => continue,
+            };
+            let current_delegate = info
+                .get("delegated-to")
+                .expect("FATAL: no 'delegated-to'")
+                .to_owned()
+                .expect_principal();
+            if current_delegate != *delegated_to {
+                // since re-delegated elsewhere
+                continue;
+            }
+            let amount_ustx = info
+                .get("amount-ustx")
+                .expect("FATAL: no 'amount-ustx'")
+                .to_owned()
+                .expect_u128();
+            // a delegation's amount is not bounded by the stacker's balance
+            total_delegated_ustx =
+                total_delegated_ustx
+                    .checked_add(amount_ustx)
+                    .ok_or_else(|| {
+                        net_error::OverflowError(format!(
+                            "Delegations to {} overflow",
+                            delegated_to
+                        ))
+                    })?;
+            total_locked_ustx = total_locked_ustx.checked_add(locked).ok_or_else(|| {
+                net_error::OverflowError(format!("Locked STX of {} overflow", delegated_to))
+            })?;
+            delegators.push(RPCDelegatorInfo {
+                stacker: stacker.to_string(),
+                amount_ustx,
+                until_burn_height: info
+                    .get("until-burn-ht")
+                    .expect("FATAL: no 'until-burn-ht'")
+                    .to_owned()
+                    .expect_optional()
+                    .map(|v| v.expect_u128()),
+                pox_address: info
+                    .get("pox-addr")
+                    .expect("FATAL: no 'pox-addr'")
+                    .to_owned()
+                    .expect_optional()
+                    .and_then(|v| boot::pox_addr_tuple_to_b58(mainnet, &v.expect_tuple())),
+                locked_ustx: locked,
+                unlock_height,
+            });
+        }
+
+        Ok(RPCDelegationsData {
+            delegated_to: delegated_to.to_string(),
+            total_delegated_ustx,
+            total_locked_ustx,
+            delegators,
+        })
+    }
+}
+
 impl ConversationHttp {
     pub fn new(
         network_id: u32,
@@ -483,12 +1282,14 @@ impl ConversationHttp {
     ) -> ConversationHttp {
         let mut stacks_http = StacksHttp::new(peer_addr.clone());
         stacks_http.maximum_call_argument_size = conn_opts.maximum_call_argument_size;
+        stacks_http.max_response_size = conn_opts.max_http_response_size;
         ConversationHttp {
             network_id: network_id,
             connection: ConnectionHttp::new(stacks_http, conn_opts, None),
             conn_id: conn_id,
             timeout: conn_opts.timeout,
             reply_streams: VecDeque::new(),
+            pending_readonly_call: None,
             peer_addr: peer_addr,
             outbound_url: outbound_url,
             peer_host: peer_host,
@@ -719,6 +1520,9 @@ impl ConversationHttp {
         let mut pages = vec![];
 
         for page_index in pages_indexes.iter() {
+            if ConversationHttp::handle_request_deadline(http, fd, req)? {
+                return Ok(());
+            }
             match atlasdb.get_attachments_available_at_page_index(*page_index, &index_block_hash) {
                 Ok(inventory) => {
                     pages.push(AttachmentPage {
@@ -842,6 +1646,9 @@ impl ConversationHttp {
                 response.send(http, fd).and_then(|_| Ok(None))
             }
             Ok(true) => {
+                if ConversationHttp::handle_request_deadline(http, fd, req)? {
+                    return Ok(None);
+                }
                 // yup! start streaming it back
                 let stream = BlockStreamData::new_block(index_block_hash.clone());
                 let response = HttpResponseType::BlockStream(response_metadata);
@@ -915,6 +1722,9 @@ impl ConversationHttp {
                 );
             }
             Ok(Some(tail_index_microblock_hash)) => {
+                if ConversationHttp::handle_request_deadline(http, fd, req)? {
+                    return Ok(None);
+                }
                 let (response, stream_opt) = match BlockStreamData::new_microblock_confirmed(
                     chainstate,
                     tail_index_microblock_hash.clone(),
@@ -1000,6 +1810,9 @@ impl ConversationHttp {
                 );
             }
             Ok(true) => {
+                if ConversationHttp::handle_request_deadline(http, fd, req)? {
+                    return Ok(None);
+                }
                 // yup! start streaming it back
                 let (response, stream_opt) = match BlockStreamData::new_microblock_confirmed(
                     chainstate,
@@ -1119,2271 +1932,5492 @@ impl ConversationHttp {
         response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a GET on a smart contract's data map, given the current chain tip.  Optionally
-    /// supplies a MARF proof for the value.
-    fn handle_get_map_entry<W: Write>(
+    /// Handle a GET on what an account sponsors in the mempool, given the current chain tip.
+    fn handle_get_sponsorship<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
         sortdb: &SortitionDB,
         chainstate: &mut StacksChainState,
+        mempool: &MemPoolDB,
         tip: &StacksBlockId,
-        contract_addr: &StacksAddress,
-        contract_name: &ContractName,
-        map_name: &ClarityName,
-        key: &Value,
-        with_proof: bool,
+        sponsor: &StacksAddress,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let contract_identifier =
-            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
 
-        let response =
-            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
-                clarity_tx.with_clarity_db_readonly(|clarity_db| {
-                    let key = ClarityDatabase::make_key_for_data_map_entry(
-                        &contract_identifier,
-                        map_name,
-                        key,
-                    );
-                    let (value, marf_proof) = clarity_db
-                        .get_with_proof::<Value>(&key)
-                        .map(|(a, b)| (a, format!("0x{}", b.to_hex())))
-                        .unwrap_or_else(|| {
-                            test_debug!("No value for '{}' in {}", &key, tip);
-                            (Value::none(), "".into())
-                        });
-                    let marf_proof = if with_proof {
-                        test_debug!(
-                            "Return a MARF proof of '{}' of {} bytes",
-                            &key,
-                            marf_proof.as_bytes().len()
-                        );
-                        Some(marf_proof)
-                    } else {
-                        None
-                    };
-
-                    let data = format!("0x{}", value.serialize());
-                    MapEntryResponse { data, marf_proof }
-                })
-            }) {
-                Ok(Some(data)) => HttpResponseType::GetMapEntry(response_metadata, data),
-                Ok(None) | Err(_) => {
-                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
-                }
-            };
+        let response = match RPCSponsorshipData::from_db(sortdb, chainstate, mempool, tip, sponsor)
+        {
+            Ok(data) => HttpResponseType::GetSponsorship(response_metadata, data),
+            Err(net_error::NotFoundError) => {
+                HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+            }
+            Err(e) => {
+                warn!("Failed to get sponsorship {:?}: {:?}", req, &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to query sponsorship".to_string(),
+                )
+            }
+        };
 
         response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a POST to run a read-only function call with the given parameters on the given chain
-    /// tip.  Returns the result of the function call.  Returns a CallReadOnlyResponse on success.
-    fn handle_readonly_function_call<W: Write>(
+    /// Handle a GET on an account's balances and vesting schedule, given the current chain tip.
+    fn handle_get_account_balance<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
         sortdb: &SortitionDB,
         chainstate: &mut StacksChainState,
         tip: &StacksBlockId,
-        contract_addr: &StacksAddress,
-        contract_name: &ContractName,
-        function: &ClarityName,
-        sender: &PrincipalData,
-        args: &[Value],
-        options: &ConnectionOptions,
+        principal: &PrincipalData,
+        unlock_heights: Option<&BTreeSet<u64>>,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let contract_identifier =
-            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
-
-        let args: Vec<_> = args
-            .iter()
-            .map(|x| SymbolicExpression::atom_value(x.clone()))
-            .collect();
-        let mainnet = chainstate.mainnet;
-        let mut cost_limit = options.read_only_call_limit.clone();
-        cost_limit.write_length = 0;
-        cost_limit.write_count = 0;
-
-        let data_opt_res =
-            chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
-                let cost_track = clarity_tx
-                    .with_clarity_db_readonly(|clarity_db| {
-                        LimitedCostTracker::new_mid_block(mainnet, cost_limit, clarity_db)
-                    })
-                    .map_err(|_| {
-                        ClarityRuntimeError::from(InterpreterError::CostContractLoadFailure)
-                    })?;
-
-                clarity_tx.with_readonly_clarity_env(mainnet, sender.clone(), cost_track, |env| {
-                    // we want to execute any function as long as no actual writes are made as
-                    // opposed to be limited to purely calling `define-read-only` functions,
-                    // so use `read_only = false`.  This broadens the number of functions that
-                    // can be called, and also circumvents limitations on `define-read-only`
-                    // functions that can not use `contrac-call?`, even when calling other
-                    // read-only functions
-                    env.execute_contract(&contract_identifier, function.as_str(), &args, false)
-                })
-            });
 
-        let response = match data_opt_res {
-            Ok(Some(Ok(data))) => HttpResponseType::CallReadOnlyFunction(
-                response_metadata,
-                CallReadOnlyResponse {
-                    okay: true,
-                    result: Some(format!("0x{}", data.serialize())),
-                    cause: None,
-                },
-            ),
-            Ok(Some(Err(e))) => match e {
-                Unchecked(CheckErrors::CostBalanceExceeded(actual_cost, _))
-                    if actual_cost.write_count > 0 =>
-                {
-                    HttpResponseType::CallReadOnlyFunction(
-                        response_metadata,
-                        CallReadOnlyResponse {
-                            okay: false,
-                            result: None,
-                            cause: Some("NotReadOnly".to_string()),
-                        },
-                    )
-                }
-                _ => HttpResponseType::CallReadOnlyFunction(
-                    response_metadata,
-                    CallReadOnlyResponse {
-                        okay: false,
-                        result: None,
-                        cause: Some(e.to_string()),
-                    },
-                ),
-            },
-            Ok(None) | Err(_) => {
+        let response = match RPCAccountBalanceData::from_db(
+            sortdb,
+            chainstate,
+            tip,
+            principal,
+            unlock_heights,
+        ) {
+            Ok(data) => HttpResponseType::GetAccountBalance(response_metadata, data),
+            Err(net_error::NotFoundError) => {
                 HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
             }
+            Err(e) => {
+                warn!("Failed to get account balance {:?}: {:?}", req, &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to query account balance".to_string(),
+                )
+            }
         };
+
         response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a GET to fetch a contract's source code, given the chain tip.  Optionally returns a
-    /// MARF proof as well.
-    fn handle_get_contract_src<W: Write>(
+    /// Handle a GET on a principal's stacking status, given the current chain tip.
+    fn handle_get_stacking_status<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
         sortdb: &SortitionDB,
         chainstate: &mut StacksChainState,
         tip: &StacksBlockId,
-        contract_addr: &StacksAddress,
-        contract_name: &ContractName,
-        with_proof: bool,
+        principal: &PrincipalData,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let contract_identifier =
-            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
 
-        let response =
-            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
-                clarity_tx.with_clarity_db_readonly(|db| {
-                    let source = db.get_contract_src(&contract_identifier)?;
-                    let contract_commit_key = make_contract_hash_key(&contract_identifier);
-                    let (contract_commit, proof) = db
-                        .get_with_proof::<ContractCommitment>(&contract_commit_key)
-                        .expect("BUG: obtained source, but couldn't get MARF proof.");
-                    let marf_proof = if with_proof {
-                        Some(proof.to_hex())
-                    } else {
-                        None
-                    };
-                    let publish_height = contract_commit.block_height;
-                    Some(ContractSrcResponse {
-                        source,
-                        publish_height,
-                        marf_proof,
-                    })
-                })
-            }) {
-                Ok(Some(Some(data))) => HttpResponseType::GetContractSrc(response_metadata, data),
-                Ok(Some(None)) => HttpResponseType::NotFound(
+        let response = match RPCStackingStatusData::from_db(sortdb, chainstate, tip, principal) {
+            Ok(data) => HttpResponseType::GetStackingStatus(response_metadata, data),
+            Err(net_error::NotFoundError) => {
+                HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+            }
+            Err(e) => {
+                warn!("Failed to get stacking status {:?}: {:?}", req, &e);
+                HttpResponseType::ServerError(
                     response_metadata,
-                    "No contract source data found".into(),
-                ),
-                Ok(None) | Err(_) => {
-                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
-                }
-            };
+                    "Failed to query stacking status".to_string(),
+                )
+            }
+        };
 
         response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a GET to fetch whether or not a contract implements a certain trait
-    fn handle_get_is_trait_implemented<W: Write>(
+    /// Handle a GET on a page of an account's transaction history, in the given chain tip's fork.
+    /// Served only if the node maintains the account transaction index.
+    fn handle_get_account_transactions<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        sortdb: &SortitionDB,
         chainstate: &mut StacksChainState,
         tip: &StacksBlockId,
-        contract_addr: &StacksAddress,
-        contract_name: &ContractName,
-        trait_id: &TraitIdentifier,
+        principal: &PrincipalData,
+        before: Option<(u64, u32)>,
+        limit: u64,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let contract_identifier =
-            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
+        if !chainstate.indexes.account_txs {
+            let response = HttpResponseType::NotFound(
+                response_metadata,
+                "Account transaction index is not enabled on this node".to_string(),
+            );
+            return response.send(http, fd).map(|_| ());
+        }
 
-        let response =
-            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
-                clarity_tx.with_clarity_db_readonly(|db| {
-                    let analysis = db.load_contract_analysis(&contract_identifier)?;
-                    if analysis.implemented_traits.contains(trait_id) {
-                        Some(GetIsTraitImplementedResponse {
-                            is_implemented: true,
+        let response = match chainstate.get_account_transactions(tip, principal, before, limit) {
+            Ok(entries) => {
+                // a full page may be followed by another
+                let next = match entries.last() {
+                    Some(last) if entries.len() as u64 >= limit => {
+                        Some(RPCAccountTransactionsNextPage {
+                            before_height: last.block_height,
+                            before_tx_index: last.tx_index,
                         })
-                    } else {
-                        let trait_defining_contract =
-                            db.load_contract_analysis(&trait_id.contract_identifier)?;
-                        let trait_definition =
-                            trait_defining_contract.get_defined_trait(&trait_id.name)?;
-                        let is_implemented = analysis
-                            .check_trait_compliance(trait_id, trait_definition)
-                            .is_ok();
-                        Some(GetIsTraitImplementedResponse { is_implemented })
                     }
-                })
-            }) {
-                Ok(Some(Some(data))) => {
-                    HttpResponseType::GetIsTraitImplemented(response_metadata, data)
-                }
-                Ok(Some(None)) => HttpResponseType::NotFound(
+                    _ => None,
+                };
+                HttpResponseType::GetAccountTransactions(
                     response_metadata,
-                    "No contract analysis found or trait definition not found".into(),
-                ),
-                Ok(None) | Err(_) => {
-                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
-                }
-            };
+                    RPCAccountTransactionsData {
+                        limit,
+                        results: entries
+                            .into_iter()
+                            .map(|entry| RPCAccountTransaction {
+                                txid: entry.txid.to_string(),
+                                index_block_hash: entry.index_block_hash.to_string(),
+                                block_height: entry.block_height,
+                                tx_index: entry.tx_index,
+                                tx: entry.tx_hex,
+                                result: format!("0x{}", entry.result_hex),
+                            })
+                            .collect(),
+                        next,
+                    },
+                )
+            }
+            Err(chain_error::NoSuchBlockError) => {
+                HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+            }
+            Err(e) => {
+                warn!("Failed to get account transactions {:?}: {:?}", req, &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to query account transactions".to_string(),
+                )
+            }
+        };
 
         response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a GET to fetch a contract's analysis data, given the chain tip.  Note that this isn't
-    /// something that's anchored to the blockchain, and can be different across different versions
-    /// of Stacks -- callers must trust the Stacks node to return correct analysis data.
-    /// Callers who don't trust the Stacks node should just fetch the contract source
-    /// code and analyze it offline.
-    fn handle_get_contract_abi<W: Write>(
+    /// Handle a GET on a page of the values a contract printed, in the given chain tip's fork.
+    /// Served only if the node maintains the print event index.
+    fn handle_get_contract_events<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        sortdb: &SortitionDB,
         chainstate: &mut StacksChainState,
         tip: &StacksBlockId,
-        contract_addr: &StacksAddress,
-        contract_name: &ContractName,
+        contract_identifier: &QualifiedContractIdentifier,
+        topic: Option<&ClarityName>,
+        before: Option<(u64, u32, u32)>,
+        limit: u64,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let contract_identifier =
-            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
+        if !chainstate.indexes.print_events {
+            let response = HttpResponseType::NotFound(
+                response_metadata,
+                "Print event index is not enabled on this node".to_string(),
+            );
+            return response.send(http, fd).map(|_| ());
+        }
 
         let response =
-            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
-                clarity_tx.with_analysis_db_readonly(|db| {
-                    let contract = db.load_contract(&contract_identifier)?;
-                    contract.contract_interface
-                })
-            }) {
-                Ok(Some(Some(data))) => HttpResponseType::GetContractABI(response_metadata, data),
-                Ok(Some(None)) => HttpResponseType::NotFound(
-                    response_metadata,
-                    "No contract interface data found".into(),
-                ),
-                Ok(None) | Err(_) => {
+            match chainstate.get_print_events(tip, contract_identifier, topic, before, limit) {
+                Ok(entries) => {
+                    // a full page may be followed by another
+                    let next = match entries.last() {
+                        Some(last) if entries.len() as u64 >= limit => {
+                            Some(RPCContractEventsNextPage {
+                                before_height: last.block_height,
+                                before_tx_index: last.tx_index,
+                                before_event_index: last.event_index,
+                            })
+                        }
+                        _ => None,
+                    };
+                    HttpResponseType::GetContractEvents(
+                        response_metadata,
+                        RPCContractEventsData {
+                            limit,
+                            results: entries
+                                .into_iter()
+                                .map(|entry| RPCContractEvent {
+                                    txid: entry.txid.to_string(),
+                                    index_block_hash: entry.index_block_hash.to_string(),
+                                    block_height: entry.block_height,
+                                    tx_index: entry.tx_index,
+                                    event_index: entry.event_index,
+                                    topic: entry.topic,
+                                    value: format!("0x{}", entry.value_hex),
+                                })
+                                .collect(),
+                            next,
+                        },
+                    )
+                }
+                Err(chain_error::NoSuchBlockError) => {
                     HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
                 }
+                Err(e) => {
+                    warn!("Failed to get contract events {:?}: {:?}", req, &e);
+                    HttpResponseType::ServerError(
+                        response_metadata,
+                        "Failed to query contract events".to_string(),
+                    )
+                }
             };
 
         response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a GET unconfirmed microblock stream.  Start streaming the reply.
-    /// The response's preamble (but not the block data) will be synchronously written to the fd
-    /// (so use a fd that can buffer!)
-    /// Return a BlockStreamData struct for the block that we're sending, so we can continue to
-    /// make progress sending it.
-    fn handle_getmicroblocks_unconfirmed<W: Write>(
+    /// Handle a GET on the receipt of a transaction mined in the given chain tip's fork.  Served
+    /// only if the node stores transaction receipts.
+    fn handle_get_transaction_receipt<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        index_anchor_block_hash: &StacksBlockId,
-        min_seq: u16,
-        chainstate: &StacksChainState,
-    ) -> Result<Option<BlockStreamData>, net_error> {
-        let response_metadata = HttpResponseMetadata::from(req);
-
-        // do we have this unconfirmed microblock stream?
-        match chainstate.has_any_staging_microblock_indexed(index_anchor_block_hash, min_seq) {
-            Ok(false) => {
-                // nope
-                let response = HttpResponseType::NotFound(
-                    response_metadata,
-                    format!(
-                        "No such unconfirmed microblock stream for {} at or after {}",
-                        index_anchor_block_hash.to_hex(),
-                        min_seq
-                    ),
-                );
-                response.send(http, fd).and_then(|_| Ok(None))
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        txid: &Txid,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        if !chainstate.indexes.tx_receipts {
+            let response = HttpResponseType::NotFound(
+                response_metadata,
+                "Transaction receipt index is not enabled on this node".to_string(),
+            );
+            return response.send(http, fd).map(|_| ());
+        }
+
+        let response = match chainstate.get_transaction_receipt(tip, txid) {
+            Ok(Some(entry)) => HttpResponseType::GetTransactionReceipt(
+                response_metadata,
+                RPCTransactionReceiptData {
+                    txid: entry.txid.to_string(),
+                    index_block_hash: entry.index_block_hash.to_string(),
+                    block_height: entry.block_height,
+                    tx_index: entry.tx_index,
+                    result: format!("0x{}", entry.result_hex),
+                    post_condition_aborted: entry.post_condition_aborted,
+                    stx_burned: entry.stx_burned.to_string(),
+                    execution_cost: entry.execution_cost,
+                    vm_error: entry.vm_error.map(|vm_error| vm_error.json_serialize()),
+                    events: entry.events,
+                },
+            ),
+            Ok(None) => HttpResponseType::NotFound(
+                response_metadata,
+                format!("No receipt for transaction {}", txid),
+            ),
+            Err(chain_error::NoSuchBlockError) => {
+                HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
             }
             Err(e) => {
-                // nope
-                warn!(
-                    "Failed to serve confirmed microblock stream {:?}: {:?}",
-                    req, &e
-                );
-                let response = HttpResponseType::ServerError(
+                warn!("Failed to get transaction receipt {:?}: {:?}", req, &e);
+                HttpResponseType::ServerError(
                     response_metadata,
-                    format!(
-                        "Failed to query unconfirmed microblock stream for {} at or after {}",
-                        index_anchor_block_hash.to_hex(),
-                        min_seq
-                    ),
-                );
-                response.send(http, fd).and_then(|_| Ok(None))
-            }
-            Ok(true) => {
-                // yup! start streaming it back
-                let (response, stream_opt) = match BlockStreamData::new_microblock_unconfirmed(
-                    chainstate,
-                    index_anchor_block_hash.clone(),
-                    min_seq,
-                ) {
-                    Ok(stream) => (
-                        HttpResponseType::MicroblockStream(response_metadata),
-                        Some(stream),
-                    ),
-                    Err(chain_error::NoSuchBlockError) => (
-                        HttpResponseType::NotFound(
-                            response_metadata,
-                            format!(
-                                "No such unconfirmed microblock stream starting with {}",
-                                index_anchor_block_hash.to_hex()
-                            ),
-                        ),
-                        None,
-                    ),
-                    Err(_e) => {
-                        debug!(
-                            "Failed to load unconfirmed microblock stream {}: {:?}",
-                            &index_anchor_block_hash, &_e
-                        );
-                        (
-                            HttpResponseType::ServerError(
-                                response_metadata,
-                                format!(
-                                    "Failed to query unconfirmed microblock stream {}",
-                                    index_anchor_block_hash.to_hex()
-                                ),
-                            ),
-                            None,
-                        )
-                    }
-                };
-                response.send(http, fd).and_then(|_| Ok(stream_opt))
+                    "Failed to query transaction receipt".to_string(),
+                )
             }
-        }
+        };
+
+        response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a GET unconfirmed transaction.
-    /// The response will be synchronously written to the fd.
-    fn handle_gettransaction_unconfirmed<W: Write>(
+    /// Handle a GET on the principals delegating to a delegatee, given the current chain tip.
+    fn handle_get_delegations<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        chainstate: &StacksChainState,
-        mempool: &MemPoolDB,
-        txid: &Txid,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        delegated_to: &PrincipalData,
+        options: &ConnectionOptions,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
 
-        // present in the unconfirmed state?
-        if let Some(ref unconfirmed) = chainstate.unconfirmed_state.as_ref() {
-            if let Some((transaction, mblock_hash, seq)) =
-                unconfirmed.get_unconfirmed_transaction(txid)
-            {
-                let response = HttpResponseType::UnconfirmedTransaction(
+        let response = match RPCDelegationsData::from_db(
+            sortdb,
+            chainstate,
+            tip,
+            delegated_to,
+            options.read_only_call_limit.clone(),
+        ) {
+            Ok(data) => HttpResponseType::GetDelegations(response_metadata, data),
+            Err(net_error::NotFoundError) => {
+                HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+            }
+            Err(net_error::ClientError(ClientError::Message(msg))) => {
+                HttpResponseType::BadRequest(response_metadata, msg)
+            }
+            Err(e) => {
+                warn!("Failed to get delegations {:?}: {:?}", req, &e);
+                HttpResponseType::ServerError(
                     response_metadata,
-                    UnconfirmedTransactionResponse {
-                        status: UnconfirmedTransactionStatus::Microblock {
-                            block_hash: mblock_hash,
-                            seq: seq,
-                        },
-                        tx: to_hex(&transaction.serialize_to_vec()),
-                    },
-                );
-                return response.send(http, fd).map(|_| ());
+                    "Failed to query delegations".to_string(),
+                )
             }
-        }
+        };
 
-        // present in the mempool?
-        if let Some(txinfo) = MemPoolDB::get_tx(mempool.conn(), txid)? {
-            let response = HttpResponseType::UnconfirmedTransaction(
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Handle a GET on the reward set of a reward cycle.
+    fn handle_get_reward_set<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        burnchain: &Burnchain,
+        reward_cycle: u64,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        if burnchain
+            .checked_reward_cycle_to_block_height(reward_cycle)
+            .is_none()
+        {
+            let response = HttpResponseType::BadRequest(
                 response_metadata,
-                UnconfirmedTransactionResponse {
-                    status: UnconfirmedTransactionStatus::Mempool,
-                    tx: to_hex(&txinfo.tx.serialize_to_vec()),
-                },
+                format!("Reward cycle {} is out of range", reward_cycle),
             );
             return response.send(http, fd).map(|_| ());
         }
 
-        // not found
-        let response = HttpResponseType::NotFound(
-            response_metadata,
-            format!("No such unconfirmed transaction {}", txid),
-        );
-        return response.send(http, fd).map(|_| ());
+        let response = match RPCRewardSetData::from_db(sortdb, chainstate, burnchain, reward_cycle)
+        {
+            Ok(Some(data)) => HttpResponseType::GetRewardSet(response_metadata, data),
+            Ok(None) => HttpResponseType::NotFound(
+                response_metadata,
+                format!("No reward set for reward cycle {}", reward_cycle),
+            ),
+            Err(e) => {
+                warn!("Failed to get reward set {:?}: {:?}", req, &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to compute reward set".to_string(),
+                )
+            }
+        };
+
+        response.send(http, fd).map(|_| ())
     }
 
-    /// Load up the canonical Stacks chain tip.  Note that this is subject to both burn chain block
-    /// Stacks block availability -- different nodes with different partial replicas of the Stacks chain state
-    /// will return different values here.
-    /// tip_opt is given by the HTTP request as the optional query parameter for the chain tip
-    /// hash.  It will be None if there was no paramter given.
-    /// The order of chain tips this method prefers is as follows:
-    /// * tip_opt, if it's Some(..),
-    /// * the unconfirmed canonical stacks chain tip, if initialized
-    /// * the confirmed canonical stacks chain tip
-    fn handle_load_stacks_chain_tip<W: Write>(
+    /// Handle a GET on a sortition, and the Stacks block that won it
+    fn handle_get_sortition<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        tip_opt: Option<&StacksBlockId>,
         sortdb: &SortitionDB,
         chainstate: &StacksChainState,
-    ) -> Result<Option<StacksBlockId>, net_error> {
-        match tip_opt {
-            Some(tip) => Ok(Some(*tip).clone()),
-            None => match chainstate.get_stacks_chain_tip(sortdb)? {
-                Some(tip) => Ok(Some(StacksBlockHeader::make_index_block_hash(
-                    &tip.consensus_hash,
-                    &tip.anchored_block_hash,
-                ))),
-                None => {
-                    let response_metadata = HttpResponseMetadata::from(req);
-                    warn!("Failed to load Stacks chain tip");
-                    let response = HttpResponseType::ServerError(
+        lookup: &SortitionLookup,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        let response = match RPCSortitionData::from_db(sortdb, chainstate, lookup) {
+            Ok(Some(data)) => HttpResponseType::GetSortition(response_metadata, data),
+            Ok(None) => HttpResponseType::NotFound(response_metadata, "No such sortition".into()),
+            Err(e) => {
+                warn!("Failed to get sortition {:?}: {:?}", req, &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to query sortition".to_string(),
+                )
+            }
+        };
+
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Check the admin token an admin endpoint request presents.  The admin endpoints are served
+    /// only if the node is configured with an admin token, and only to requests that present it.
+    /// Returns the response to send instead of serving the request, if the request may not be
+    /// served.
+    fn check_admin_token(
+        req: &HttpRequestType,
+        handler_args: &RPCHandlerArgs,
+        token: Option<&BearerToken>,
+    ) -> Option<HttpResponseType> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        match handler_args.admin_auth_token {
+            Some(admin_auth_token) => {
+                if token.map(|t| t.matches(admin_auth_token)).unwrap_or(false) {
+                    None
+                } else {
+                    warn!("Rejected unauthorized admin request {:?}", req);
+                    Some(HttpResponseType::Unauthorized(
                         response_metadata,
-                        format!("Failed to load Stacks chain tip"),
-                    );
-                    response.send(http, fd).and_then(|_| Ok(None))
+                        "Missing or invalid admin token".to_string(),
+                    ))
                 }
-            },
+            }
+            None => Some(HttpResponseType::NotFound(
+                response_metadata,
+                "Admin endpoints are not enabled on this node".to_string(),
+            )),
         }
     }
 
-    fn handle_load_stacks_chain_tip_hashes<W: Write>(
+    /// Handle a GET or POST on the node's runtime toggles
+    fn handle_admin_toggles<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        tip_opt: Option<&StacksBlockId>,
-        sortdb: &SortitionDB,
-        chainstate: &StacksChainState,
-    ) -> Result<Option<(ConsensusHash, BlockHeaderHash)>, net_error> {
-        match tip_opt {
-            Some(tip) => match chainstate.get_block_header_hashes(&tip)? {
-                Some((ch, bl)) => {
-                    return Ok(Some((ch, bl)));
-                }
-                None => {}
-            },
-            None => match chainstate.get_stacks_chain_tip(sortdb)? {
-                Some(tip) => {
-                    return Ok(Some((tip.consensus_hash, tip.anchored_block_hash)));
-                }
-                None => {}
-            },
-        }
+        handler_args: &RPCHandlerArgs,
+        token: Option<&BearerToken>,
+        update: Option<&RPCAdminTogglesUpdate>,
+    ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        warn!("Failed to load Stacks chain tip");
-        let response = HttpResponseType::ServerError(
-            response_metadata,
-            format!("Failed to load Stacks chain tip"),
-        );
-        response.send(http, fd).and_then(|_| Ok(None))
+
+        let response = match (
+            ConversationHttp::check_admin_token(req, handler_args, token),
+            handler_args.runtime_toggles,
+        ) {
+            (Some(response), _) => response,
+            (None, Some(toggles)) => {
+                if let Some(update) = update {
+                    info!(
+                        "Admin: update runtime toggles";
+                        "mining_paused" => ?update.mining_paused,
+                        "block_downloads_paused" => ?update.block_downloads_paused,
+                        "tx_relay_paused" => ?update.tx_relay_paused,
+                        "debug_subsystems" => ?update.debug_subsystems
+                    );
+                    toggles.apply(update);
+                }
+                HttpResponseType::AdminToggles(response_metadata, toggles.snapshot())
+            }
+            (None, None) => HttpResponseType::NotFound(
+                response_metadata,
+                "Admin endpoints are not enabled on this node".to_string(),
+            ),
+        };
+
+        response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a transaction.  Directly submit it to the mempool so the client can see any
-    /// rejection reasons up-front (different from how the peer network handles it).  Indicate
-    /// whether or not the transaction was accepted (and thus needs to be forwarded) in the return
-    /// value.
-    fn handle_post_transaction<W: Write>(
+    /// Handle a POST asking the node to send STX from its burnchain signer with a transfer-STX
+    /// burn operation.  The request is checked against the signer's unlocked balance at the
+    /// canonical tip, since an operation that overspends is accepted on the burnchain but does
+    /// nothing, and then queued: the bitcoind calls to fund and broadcast it are made by the
+    /// submitter's own thread, not this one.
+    fn handle_post_transfer_stx_op<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
+        handler_args: &RPCHandlerArgs,
+        token: Option<&BearerToken>,
+        request: &RPCTransferStxOpRequest,
+        sortdb: &SortitionDB,
         chainstate: &mut StacksChainState,
-        consensus_hash: ConsensusHash,
-        block_hash: BlockHeaderHash,
-        mempool: &mut MemPoolDB,
-        tx: StacksTransaction,
-        atlasdb: &mut AtlasDB,
-        attachment: Option<Attachment>,
-        event_observer: Option<&dyn MemPoolEventDispatcher>,
-    ) -> Result<bool, net_error> {
-        let txid = tx.txid();
+    ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let (response, accepted) = if mempool.has_tx(&txid) {
-            (
-                HttpResponseType::TransactionID(response_metadata, txid),
-                false,
-            )
-        } else {
-            match mempool.submit(
-                chainstate,
-                &consensus_hash,
-                &block_hash,
-                &tx,
-                event_observer,
-            ) {
-                Ok(_) => (
-                    HttpResponseType::TransactionID(response_metadata, txid),
-                    true,
-                ),
-                Err(e) => (
-                    HttpResponseType::BadRequestJSON(response_metadata, e.into_json(&txid)),
-                    false,
-                ),
+        let submitter = match handler_args.burn_op_submitter {
+            Some(submitter) => submitter,
+            None => {
+                let response = HttpResponseType::NotFound(
+                    response_metadata,
+                    "Burn operation submission is not enabled on this node".to_string(),
+                );
+                return response.send(http, fd).map(|_| ());
             }
         };
+        if let Some(response) = ConversationHttp::check_admin_token(req, handler_args, token) {
+            return response.send(http, fd).map(|_| ());
+        }
 
-        if let Some(ref attachment) = attachment {
-            if let TransactionPayload::ContractCall(ref contract_call) = tx.payload {
-                if atlasdb
-                    .should_keep_attachment(&contract_call.to_clarity_contract_id(), &attachment)
-                {
-                    atlasdb
-                        .insert_uninstantiated_attachment(attachment)
-                        .map_err(|e| net_error::DBError(e))?;
-                }
+        let request = match TransferStxOpRequest::from_rpc(request, chainstate.mainnet) {
+            Ok(request) => request,
+            Err(msg) => {
+                let response = HttpResponseType::BadRequest(response_metadata, msg);
+                return response.send(http, fd).map(|_| ());
+            }
+        };
+
+        let tip = match chainstate.get_stacks_chain_tip(sortdb)? {
+            Some(tip) => StacksBlockHeader::make_index_block_hash(
+                &tip.consensus_hash,
+                &tip.anchored_block_hash,
+            ),
+            None => {
+                let response =
+                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into());
+                return response.send(http, fd).map(|_| ());
             }
+        };
+        let sender = submitter.sender();
+        if let Err(msg) = request.check_sender(&sender) {
+            let response = HttpResponseType::BadRequest(response_metadata, msg);
+            return response.send(http, fd).map(|_| ());
         }
+        let sender_principal = sender.to_account_principal();
+        let unlocked_opt =
+            chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), &tip, |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly(|clarity_db| {
+                    let burn_block_height = clarity_db.get_current_burnchain_block_height() as u64;
+                    clarity_db
+                        .get_account_stx_balance(&sender_principal)
+                        .get_available_balance_at_burn_block(burn_block_height)
+                })
+            });
 
-        response.send(http, fd).and_then(|_| Ok(accepted))
+        let response = match unlocked_opt {
+            Ok(Some(unlocked)) if unlocked < request.amount_ustx => HttpResponseType::BadRequest(
+                response_metadata,
+                format!(
+                    "Sender {} has {} unlocked uSTX; cannot transfer {}",
+                    &sender, unlocked, request.amount_ustx
+                ),
+            ),
+            Ok(Some(_)) => {
+                info!(
+                    "Admin: queue transfer-STX operation";
+                    "sender" => %sender,
+                    "recipient" => %request.recipient,
+                    "amount_ustx" => request.amount_ustx
+                );
+                let submission = RPCBurnOpSubmissionData {
+                    sender: sender.to_string(),
+                    recipient: request.recipient.to_string(),
+                    amount_ustx: request.amount_ustx as u64,
+                };
+                match submitter.queue_transfer_stx(request) {
+                    Ok(()) => HttpResponseType::BurnOpSubmitted(response_metadata, submission),
+                    Err(msg) => {
+                        warn!("Failed to queue transfer-STX operation: {}", &msg);
+                        HttpResponseType::ServerError(response_metadata, msg)
+                    }
+                }
+            }
+            Ok(None) | Err(_) => {
+                HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+            }
+        };
+
+        response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a block.  Directly submit a Stacks block to this node's chain state.
-    /// Indicate whether or not the block was accepted (i.e. it was new, and valid)
-    fn handle_post_block<W: Write>(
+    /// Handle a GET on the miner's burnchain wallet, as the node last read it from its burnchain
+    fn handle_get_miner_wallet<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        sortdb: &SortitionDB,
-        chainstate: &mut StacksChainState,
-        consensus_hash: &ConsensusHash,
-        block: &StacksBlock,
-    ) -> Result<bool, net_error> {
+        handler_args: &RPCHandlerArgs,
+        token: Option<&BearerToken>,
+    ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        // is this a consensus hash we recognize?
-        let (response, accepted) =
-            match SortitionDB::get_sortition_id_by_consensus(&sortdb.conn(), consensus_hash) {
-                Ok(Some(_)) => {
-                    // we recognize this consensus hash
-                    let ic = sortdb.index_conn();
-                    match Relayer::process_new_anchored_block(
-                        &ic,
-                        chainstate,
-                        consensus_hash,
-                        block,
-                        0,
-                    ) {
-                        Ok(true) => {
-                            debug!(
-                                "Accepted Stacks block {}/{}",
-                                consensus_hash,
-                                &block.block_hash()
-                            );
-                            (
-                                HttpResponseType::StacksBlockAccepted(
-                                    response_metadata,
-                                    StacksBlockHeader::make_index_block_hash(
-                                        consensus_hash,
-                                        &block.block_hash(),
-                                    ),
-                                    true,
-                                ),
-                                true,
-                            )
-                        }
-                        Ok(false) => {
-                            debug!(
-                                "Did not accept Stacks block {}/{}",
-                                consensus_hash,
-                                &block.block_hash()
-                            );
-                            (
-                                HttpResponseType::StacksBlockAccepted(
-                                    response_metadata,
-                                    StacksBlockHeader::make_index_block_hash(
-                                        consensus_hash,
-                                        &block.block_hash(),
-                                    ),
-                                    false,
-                                ),
-                                false,
-                            )
-                        }
-                        Err(e) => {
-                            error!(
-                                "Failed to process anchored block {}/{}: {:?}",
-                                consensus_hash,
-                                &block.block_hash(),
-                                &e
-                            );
-                            (
-                                HttpResponseType::ServerError(
-                                    response_metadata,
-                                    format!(
-                                        "Failed to process anchored block {}/{}: {:?}",
-                                        consensus_hash,
-                                        &block.block_hash(),
-                                        &e
-                                    ),
-                                ),
-                                false,
-                            )
-                        }
+        let response = match handler_args.miner_wallet_reader {
+            None => HttpResponseType::NotFound(
+                response_metadata,
+                "This node does not report a miner wallet".to_string(),
+            ),
+            Some(reader) => match ConversationHttp::check_admin_token(req, handler_args, token) {
+                Some(response) => response,
+                None => match reader.read_miner_wallet() {
+                    Ok(wallet) => HttpResponseType::MinerWallet(response_metadata, wallet.to_rpc()),
+                    Err(msg) => {
+                        warn!("Failed to read the miner's burnchain wallet: {}", &msg);
+                        HttpResponseType::ServerError(response_metadata, msg)
                     }
-                }
-                Ok(None) => {
-                    debug!(
-                        "Unrecognized consensus hash {} for block {}",
-                        consensus_hash,
-                        &block.block_hash()
-                    );
-                    (
-                        HttpResponseType::NotFound(
-                            response_metadata,
-                            format!("No such consensus hash '{}'", consensus_hash),
-                        ),
-                        false,
-                    )
-                }
-                Err(e) => {
-                    error!(
-                        "Failed to query sortition ID by consensus '{}'",
-                        consensus_hash
-                    );
-                    (
-                        HttpResponseType::ServerError(
-                            response_metadata,
-                            format!(
-                                "Failed to query sortition ID for consensus hash '{}': {:?}",
-                                consensus_hash, &e
-                            ),
-                        ),
-                        false,
-                    )
-                }
-            };
+                },
+            },
+        };
 
-        response.send(http, fd).and_then(|_| Ok(accepted))
+        response.send(http, fd).map(|_| ())
     }
 
-    /// Handle a microblock.  Directly submit it to the microblock store so the client can see any
-    /// rejection reasons up-front (different from how the peer network handles it).  Indicate
-    /// whether or not the microblock was accepted (and thus needs to be forwarded) in the return
-    /// value.
-    fn handle_post_microblock<W: Write>(
+    /// Handle a GET on the Clarity profiles of the most recently processed blocks
+    fn handle_get_clarity_profiles<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        consensus_hash: &ConsensusHash,
-        block_hash: &BlockHeaderHash,
-        chainstate: &mut StacksChainState,
-        microblock: &StacksMicroblock,
-    ) -> Result<bool, net_error> {
+        handler_args: &RPCHandlerArgs,
+        token: Option<&BearerToken>,
+    ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
-        let (response, accepted) =
-            match chainstate.preprocess_streamed_microblock(consensus_hash, block_hash, microblock)
-            {
-                Ok(accepted) => {
-                    if accepted {
-                        debug!(
-                            "Accepted uploaded microblock {}/{}-{}",
-                            &consensus_hash,
-                            &block_hash,
-                            &microblock.block_hash()
-                        );
-                    } else {
-                        debug!(
-                            "Did not accept microblock {}/{}-{}",
-                            &consensus_hash,
-                            &block_hash,
-                            &microblock.block_hash()
-                        );
-                    }
-
-                    (
-                        HttpResponseType::MicroblockHash(
-                            response_metadata,
-                            microblock.block_hash(),
-                        ),
-                        accepted,
-                    )
-                }
-                Err(e) => (
-                    HttpResponseType::BadRequestJSON(response_metadata, e.into_json()),
-                    false,
-                ),
-            };
+        let response = match ConversationHttp::check_admin_token(req, handler_args, token) {
+            Some(response) => response,
+            None if !profiler::is_enabled() => HttpResponseType::NotFound(
+                response_metadata,
+                "This node is not profiling Clarity".to_string(),
+            ),
+            None => HttpResponseType::ClarityProfiles(
+                response_metadata,
+                profiler::get_recent_profiles(),
+            ),
+        };
 
-        response.send(http, fd).and_then(|_| Ok(accepted))
+        response.send(http, fd).map(|_| ())
     }
 
-    /// Handle an external HTTP request.
-    /// Some requests, such as those for blocks, will create new reply streams.  This method adds
-    /// those new streams into the `reply_streams` set.
-    /// Returns a StacksMessageType option -- it's Some(...) if we need to forward a message to the
-    /// peer network (like a transaction or a block or microblock)
-    pub fn handle_request(
-        &mut self,
-        req: HttpRequestType,
-        chain_view: &BurnchainView,
-        peers: &PeerMap,
+    /// Handle a POST of a block proposal.  The block is checked against the chainstate as if this
+    /// node were about to mine it, and never stored or relayed.  Checking it runs every
+    /// transaction in it, which is why this is an admin endpoint.
+    fn handle_post_block_proposal<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        handler_args: &RPCHandlerArgs,
+        token: Option<&BearerToken>,
+        request: &RPCBlockProposalRequest,
         sortdb: &SortitionDB,
-        peerdb: &PeerDB,
-        atlasdb: &mut AtlasDB,
-        chainstate: &mut StacksChainState,
-        mempool: &mut MemPoolDB,
-        handler_opts: &RPCHandlerArgs,
-    ) -> Result<Option<StacksMessageType>, net_error> {
-        let mut reply = self.connection.make_relay_handle(self.conn_id)?;
-        let keep_alive = req.metadata().keep_alive;
-        let mut ret = None;
+        chainstate: &StacksChainState,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        if let Some(response) = ConversationHttp::check_admin_token(req, handler_args, token) {
+            return response.send(http, fd).map(|_| ());
+        }
 
-        let stream_opt = match req {
-            HttpRequestType::GetInfo(ref _md) => {
-                ConversationHttp::handle_getinfo(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    &self.burnchain,
-                    sortdb,
-                    chainstate,
-                    peerdb,
-                    handler_opts,
-                )?;
-                None
-            }
-            HttpRequestType::GetPoxInfo(ref _md, ref tip_opt) => {
-                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    tip_opt.as_ref(),
-                    sortdb,
-                    chainstate,
-                )? {
-                    ConversationHttp::handle_getpoxinfo(
-                        &mut self.connection.protocol,
-                        &mut reply,
-                        &req,
-                        sortdb,
-                        chainstate,
-                        &tip,
-                        &self.burnchain,
-                    )?;
-                }
-                None
-            }
-            HttpRequestType::GetNeighbors(ref _md) => {
-                ConversationHttp::handle_getneighbors(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    self.network_id,
-                    chain_view,
-                    peers,
-                    peerdb,
-                )?;
-                None
-            }
-            HttpRequestType::GetBlock(ref _md, ref index_block_hash) => {
-                ConversationHttp::handle_getblock(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    index_block_hash,
-                    chainstate,
-                )?
+        let block_opt = hex_bytes(&request.block)
+            .ok()
+            .and_then(|bytes| StacksBlock::consensus_deserialize(&mut &bytes[..]).ok());
+        let block = match block_opt {
+            Some(block) => block,
+            None => {
+                let response = HttpResponseType::BadRequest(
+                    response_metadata,
+                    "Failed to decode the proposed block".to_string(),
+                );
+                return response.send(http, fd).map(|_| ());
             }
-            HttpRequestType::GetMicroblocksIndexed(ref _md, ref index_head_hash) => {
-                ConversationHttp::handle_getmicroblocks_indexed(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    index_head_hash,
-                    chainstate,
-                )?
+        };
+
+        let response = match StacksBlockBuilder::validate_block_proposal(
+            chainstate,
+            &sortdb.index_conn(),
+            &request.parent_consensus_hash,
+            &block,
+        ) {
+            Ok(validation) => {
+                info!(
+                    "Admin: validated block proposal";
+                    "block_hash" => %block.block_hash(),
+                    "parent_consensus_hash" => %request.parent_consensus_hash,
+                    "accepted" => validation.accepted()
+                );
+                HttpResponseType::BlockProposalValidated(
+                    response_metadata,
+                    RPCBlockProposalData::from_validation(&block, &validation),
+                )
             }
-            HttpRequestType::GetMicroblocksConfirmed(ref _md, ref anchor_index_block_hash) => {
-                ConversationHttp::handle_getmicroblocks_confirmed(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    anchor_index_block_hash,
-                    chainstate,
-                )?
+            Err(e) => {
+                warn!("Failed to validate block proposal: {:?}", &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    format!("Failed to validate block proposal: {:?}", &e),
+                )
             }
-            HttpRequestType::GetMicroblocksUnconfirmed(
-                ref _md,
-                ref index_anchor_block_hash,
-                ref min_seq,
-            ) => ConversationHttp::handle_getmicroblocks_unconfirmed(
-                &mut self.connection.protocol,
-                &mut reply,
-                &req,
-                index_anchor_block_hash,
-                *min_seq,
-                chainstate,
-            )?,
-            HttpRequestType::GetTransactionUnconfirmed(ref _md, ref txid) => {
-                ConversationHttp::handle_gettransaction_unconfirmed(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    chainstate,
-                    mempool,
-                    txid,
-                )?;
-                None
+        };
+
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Handle a GET on the consensus schedule
+    fn handle_get_consensus_schedule<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        burnchain: &Burnchain,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        let response = match RPCConsensusScheduleData::from_db(sortdb, burnchain) {
+            Ok(data) => HttpResponseType::GetConsensusSchedule(response_metadata, data),
+            Err(e) => {
+                warn!("Failed to get consensus schedule {:?}: {:?}", req, &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to load burnchain tip".to_string(),
+                )
             }
-            HttpRequestType::GetAccount(ref _md, ref principal, ref tip_opt, ref with_proof) => {
-                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    tip_opt.as_ref(),
-                    sortdb,
-                    chainstate,
-                )? {
-                    ConversationHttp::handle_get_account_entry(
-                        &mut self.connection.protocol,
-                        &mut reply,
-                        &req,
-                        sortdb,
-                        chainstate,
-                        &tip,
-                        principal,
-                        *with_proof,
-                    )?;
-                }
-                None
+        };
+
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Handle a GET on the microblock equivocations this node has detected
+    fn handle_get_microblock_equivocations<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        chainstate: &StacksChainState,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        let response = match RPCMicroblockEquivocationsData::from_db(chainstate) {
+            Ok(data) => HttpResponseType::GetMicroblockEquivocations(response_metadata, data),
+            Err(e) => {
+                warn!("Failed to get microblock equivocations {:?}: {:?}", req, &e);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to load microblock equivocations".to_string(),
+                )
             }
-            HttpRequestType::GetMapEntry(
-                ref _md,
-                ref contract_addr,
-                ref contract_name,
-                ref map_name,
-                ref key,
-                ref tip_opt,
-                ref with_proof,
-            ) => {
-                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    tip_opt.as_ref(),
-                    sortdb,
-                    chainstate,
-                )? {
-                    ConversationHttp::handle_get_map_entry(
-                        &mut self.connection.protocol,
-                        &mut reply,
-                        &req,
-                        sortdb,
-                        chainstate,
-                        &tip,
-                        contract_addr,
-                        contract_name,
+        };
+
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Handle a GET on a smart contract's data map, given the current chain tip.  Optionally
+    /// supplies a MARF proof for the value.
+    fn handle_get_map_entry<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        contract_addr: &StacksAddress,
+        contract_name: &ContractName,
+        map_name: &ClarityName,
+        key: &Value,
+        with_proof: bool,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let contract_identifier =
+            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
+
+        let response =
+            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly(|clarity_db| {
+                    let key = ClarityDatabase::make_key_for_data_map_entry(
+                        &contract_identifier,
                         map_name,
                         key,
-                        *with_proof,
-                    )?;
+                    );
+                    let (value, marf_proof) = clarity_db
+                        .get_with_proof::<Value>(&key)
+                        .map(|(a, b)| (a, format!("0x{}", b.to_hex())))
+                        .unwrap_or_else(|| {
+                            test_debug!("No value for '{}' in {}", &key, tip);
+                            (Value::none(), "".into())
+                        });
+                    let marf_proof = if with_proof {
+                        test_debug!(
+                            "Return a MARF proof of '{}' of {} bytes",
+                            &key,
+                            marf_proof.as_bytes().len()
+                        );
+                        Some(marf_proof)
+                    } else {
+                        None
+                    };
+
+                    // don't render a value that is too big to send
+                    let data = format!("0x{}", value.serialize());
+                    if let Some(response) =
+                        http.check_response_size(&response_metadata, data.len() as u64)
+                    {
+                        return Err(response);
+                    }
+                    let data_json = Some(value.to_json());
+                    Ok(MapEntryResponse {
+                        data,
+                        data_json,
+                        marf_proof,
+                    })
+                })
+            }) {
+                Ok(Some(Ok(data))) => HttpResponseType::GetMapEntry(response_metadata, data),
+                Ok(Some(Err(too_large))) => too_large,
+                Ok(None) | Err(_) => {
+                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
                 }
-                None
-            }
-            HttpRequestType::GetTransferCost(ref _md) => {
-                ConversationHttp::handle_token_transfer_cost(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                )?;
-                None
-            }
-            HttpRequestType::GetContractABI(
-                ref _md,
-                ref contract_addr,
-                ref contract_name,
-                ref tip_opt,
-            ) => {
-                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    tip_opt.as_ref(),
-                    sortdb,
-                    chainstate,
-                )? {
-                    ConversationHttp::handle_get_contract_abi(
-                        &mut self.connection.protocol,
-                        &mut reply,
-                        &req,
-                        sortdb,
-                        chainstate,
-                        &tip,
-                        contract_addr,
-                        contract_name,
-                    )?;
+            };
+
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Handle a POST to run a read-only function call with the given parameters on the given chain
+    /// tip.  Returns the result of the function call.  Returns a CallReadOnlyResponse on success.
+    fn handle_readonly_function_call<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        contract_addr: &StacksAddress,
+        contract_name: &ContractName,
+        function: &ClarityName,
+        sender: &PrincipalData,
+        args: &[Value],
+        options: &ConnectionOptions,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let contract_identifier =
+            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
+
+        let response = ConversationHttp::eval_readonly_function_call(
+            sortdb,
+            chainstate,
+            tip,
+            &contract_identifier,
+            function,
+            sender,
+            args,
+            options.read_only_call_limit.clone(),
+            response_metadata,
+        );
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Evaluate a read-only function call on the given chain tip, and build the
+    /// CallReadOnlyFunction response for it.  Used both by the RPC handler and by the read-only
+    /// call pool's workers.
+    pub fn eval_readonly_function_call(
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        contract_identifier: &QualifiedContractIdentifier,
+        function: &ClarityName,
+        sender: &PrincipalData,
+        args: &[Value],
+        mut cost_limit: ExecutionCost,
+        response_metadata: HttpResponseMetadata,
+    ) -> HttpResponseType {
+        let args: Vec<_> = args
+            .iter()
+            .map(|x| SymbolicExpression::atom_value(x.clone()))
+            .collect();
+        let mainnet = chainstate.mainnet;
+        cost_limit.write_length = 0;
+        cost_limit.write_count = 0;
+
+        let data_opt_res =
+            chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                let cost_track = clarity_tx
+                    .with_clarity_db_readonly(|clarity_db| {
+                        LimitedCostTracker::new_mid_block(mainnet, cost_limit, clarity_db)
+                    })
+                    .map_err(|_| {
+                        ClarityRuntimeError::from(InterpreterError::CostContractLoadFailure)
+                    })?;
+
+                clarity_tx.with_readonly_clarity_env(mainnet, sender.clone(), cost_track, |env| {
+                    // we want to execute any function as long as no actual writes are made as
+                    // opposed to be limited to purely calling `define-read-only` functions,
+                    // so use `read_only = false`.  This broadens the number of functions that
+                    // can be called, and also circumvents limitations on `define-read-only`
+                    // functions that can not use `contrac-call?`, even when calling other
+                    // read-only functions
+                    env.execute_contract(contract_identifier, function.as_str(), &args, false)
+                })
+            });
+
+        match data_opt_res {
+            Ok(Some(Ok(data))) => HttpResponseType::CallReadOnlyFunction(
+                response_metadata,
+                CallReadOnlyResponse {
+                    okay: true,
+                    result: Some(format!("0x{}", data.serialize())),
+                    result_json: Some(data.to_json()),
+                    cause: None,
+                },
+            ),
+            Ok(Some(Err(e))) => match e {
+                Unchecked(CheckErrors::CostBalanceExceeded(actual_cost, _))
+                    if actual_cost.write_count > 0 =>
+                {
+                    HttpResponseType::CallReadOnlyFunction(
+                        response_metadata,
+                        CallReadOnlyResponse {
+                            okay: false,
+                            result: None,
+                            result_json: None,
+                            cause: Some("NotReadOnly".to_string()),
+                        },
+                    )
                 }
-                None
+                _ => HttpResponseType::CallReadOnlyFunction(
+                    response_metadata,
+                    CallReadOnlyResponse {
+                        okay: false,
+                        result: None,
+                        result_json: None,
+                        cause: Some(e.to_string()),
+                    },
+                ),
+            },
+            Ok(None) | Err(_) => {
+                HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
             }
-            HttpRequestType::CallReadOnlyFunction(
-                ref _md,
-                ref ctrct_addr,
-                ref ctrct_name,
-                ref as_sender,
-                ref func_name,
-                ref args,
-                ref tip_opt,
-            ) => {
-                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    tip_opt.as_ref(),
-                    sortdb,
-                    chainstate,
-                )? {
-                    ConversationHttp::handle_readonly_function_call(
-                        &mut self.connection.protocol,
-                        &mut reply,
-                        &req,
-                        sortdb,
-                        chainstate,
-                        &tip,
-                        ctrct_addr,
-                        ctrct_name,
-                        func_name,
-                        as_sender,
-                        args,
-                        &self.connection.options,
+        }
+    }
+
+    /// Is the given tip the readable unconfirmed chain tip of this chainstate?
+    /// Is every answer to this request but an error the same, no matter when it is asked?
+    fn is_immutable_request(chainstate: &StacksChainState, req: &HttpRequestType) -> bool {
+        let unconfirmed_tip = chainstate
+            .unconfirmed_state
+            .as_ref()
+            .map(|unconfirmed_state| &unconfirmed_state.unconfirmed_chain_tip);
+        req.is_immutable(unconfirmed_tip)
+    }
+
+    fn is_unconfirmed_tip(chainstate: &StacksChainState, tip: &StacksBlockId) -> bool {
+        match chainstate.unconfirmed_state {
+            Some(ref unconfirmed_state) => {
+                *tip == unconfirmed_state.unconfirmed_chain_tip && unconfirmed_state.is_readable()
+            }
+            None => false,
+        }
+    }
+
+    /// Apply a list of state overrides to a scratch Clarity database.  The database must be
+    /// rolled back by the caller.
+    fn apply_state_overrides(
+        clarity_db: &mut ClarityDatabase,
+        overrides: &[ClarityStateOverride],
+    ) -> Result<(), ClarityRuntimeError> {
+        for state_override in overrides.iter() {
+            match state_override {
+                ClarityStateOverride::DataVar(contract_id, var_name, value) => {
+                    clarity_db.set_variable_unknown_descriptor(
+                        contract_id,
+                        var_name.as_str(),
+                        value.clone(),
                     )?;
                 }
-                None
-            }
-            HttpRequestType::GetContractSrc(
-                ref _md,
-                ref contract_addr,
-                ref contract_name,
-                ref tip_opt,
-                ref with_proof,
-            ) => {
-                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    tip_opt.as_ref(),
-                    sortdb,
-                    chainstate,
-                )? {
-                    ConversationHttp::handle_get_contract_src(
-                        &mut self.connection.protocol,
-                        &mut reply,
-                        &req,
-                        sortdb,
-                        chainstate,
-                        &tip,
-                        contract_addr,
-                        contract_name,
-                        *with_proof,
+                ClarityStateOverride::MapEntry(contract_id, map_name, key, value) => {
+                    clarity_db.set_entry_unknown_descriptor(
+                        contract_id,
+                        map_name.as_str(),
+                        key.clone(),
+                        value.clone(),
                     )?;
                 }
-                None
-            }
-            HttpRequestType::PostTransaction(ref _md, ref tx, ref attachment) => {
-                match chainstate.get_stacks_chain_tip(sortdb)? {
-                    Some(tip) => {
-                        let accepted = ConversationHttp::handle_post_transaction(
-                            &mut self.connection.protocol,
-                            &mut reply,
-                            &req,
-                            chainstate,
-                            tip.consensus_hash,
-                            tip.anchored_block_hash,
-                            mempool,
-                            tx.clone(),
-                            atlasdb,
-                            attachment.clone(),
-                            handler_opts.event_observer.as_deref(),
-                        )?;
-                        if accepted {
-                            // forward to peer network
-                            ret = Some(StacksMessageType::Transaction(tx.clone()));
-                        }
-                    }
-                    None => {
-                        let response_metadata = HttpResponseMetadata::from(&req);
-                        warn!("Failed to load Stacks chain tip");
-                        let response = HttpResponseType::ServerError(
-                            response_metadata,
-                            format!("Failed to load Stacks chain tip"),
-                        );
-                        response.send(&mut self.connection.protocol, &mut reply)?;
-                    }
+                ClarityStateOverride::STXBalance(principal, amount) => {
+                    let mut balance = clarity_db.get_account_stx_balance(principal);
+                    balance.amount_unlocked = *amount;
+                    clarity_db.put(
+                        &ClarityDatabase::make_key_for_account_balance(principal),
+                        &balance,
+                    );
                 }
-                None
-            }
-            HttpRequestType::GetAttachment(ref _md, ref content_hash) => {
-                ConversationHttp::handle_getattachment(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    atlasdb,
-                    content_hash.clone(),
-                )?;
-                None
-            }
-            HttpRequestType::GetAttachmentsInv(
-                ref _md,
-                ref index_block_hash,
-                ref pages_indexes,
-            ) => {
-                ConversationHttp::handle_getattachmentsinv(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    atlasdb,
-                    &index_block_hash,
-                    pages_indexes,
-                    &self.connection.options,
-                )?;
-                None
             }
-            HttpRequestType::PostBlock(ref _md, ref consensus_hash, ref block) => {
-                let accepted = ConversationHttp::handle_post_block(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    sortdb,
-                    chainstate,
-                    consensus_hash,
-                    block,
-                )?;
-                if accepted {
-                    // inform the peer network so it can announce its presence
-                    ret = Some(StacksMessageType::Blocks(BlocksData {
-                        blocks: vec![(consensus_hash.clone(), block.clone())],
-                    }));
-                }
-                None
-            }
-            HttpRequestType::PostMicroblock(ref _md, ref mblock, ref tip_opt) => {
-                if let Some((consensus_hash, block_hash)) =
-                    ConversationHttp::handle_load_stacks_chain_tip_hashes(
-                        &mut self.connection.protocol,
-                        &mut reply,
-                        &req,
-                        tip_opt.as_ref(),
-                        sortdb,
-                        chainstate,
-                    )?
-                {
-                    let accepted = ConversationHttp::handle_post_microblock(
-                        &mut self.connection.protocol,
-                        &mut reply,
-                        &req,
-                        &consensus_hash,
-                        &block_hash,
-                        chainstate,
-                        mblock,
-                    )?;
-                    if accepted {
-                        // forward to peer network
-                        let tip =
-                            StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_hash);
-                        ret = Some(StacksMessageType::Microblocks(MicroblocksData {
-                            index_anchor_block: tip,
-                            microblocks: vec![(*mblock).clone()],
-                        }));
+        }
+        Ok(())
+    }
+
+    /// Handle a POST to simulate a contract call on the given chain tip, after applying the given
+    /// state overrides.  Everything happens in a scratch fork of the chain state that is
+    /// discarded afterwards, so unlike a read-only call, the function is permitted to write.  It
+    /// is held to the same cost limit as a read-only call, though.  Returns a
+    /// SimulateContractCallResponse on success.
+    fn handle_simulate_contract_call<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        contract_addr: &StacksAddress,
+        contract_name: &ContractName,
+        function: &ClarityName,
+        sender: &PrincipalData,
+        args: &[Value],
+        overrides: &[ClarityStateOverride],
+        options: &ConnectionOptions,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let contract_identifier =
+            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
+
+        let args: Vec<_> = args
+            .iter()
+            .map(|x| SymbolicExpression::atom_value(x.clone()))
+            .collect();
+        let mainnet = chainstate.mainnet;
+        let cost_limit = options.read_only_call_limit.clone();
+
+        let data_opt_res =
+            chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly_owned(|mut clarity_db| {
+                    if let Err(e) =
+                        ConversationHttp::apply_state_overrides(&mut clarity_db, overrides)
+                    {
+                        return (Err(e), clarity_db);
                     }
-                }
-                None
+
+                    let cost_track = match LimitedCostTracker::new_mid_block(
+                        mainnet,
+                        cost_limit,
+                        &mut clarity_db,
+                    ) {
+                        Ok(cost_track) => cost_track,
+                        Err(_) => {
+                            return (
+                                Err(ClarityRuntimeError::from(
+                                    InterpreterError::CostContractLoadFailure,
+                                )),
+                                clarity_db,
+                            );
+                        }
+                    };
+
+                    let mut vm_env =
+                        OwnedEnvironment::new_cost_limited(mainnet, clarity_db, cost_track);
+                    let result = vm_env.execute_in_env(sender.clone(), |env| {
+                        env.execute_contract(&contract_identifier, function.as_str(), &args, false)
+                    });
+                    let (clarity_db, cost_track) = vm_env
+                        .destruct()
+                        .expect("Failed to recover database reference after executing transaction");
+
+                    let simulation =
+                        result.map(|(value, _, events)| (value, events, cost_track.get_total()));
+                    (simulation, clarity_db)
+                })
+            });
+
+        let response = match data_opt_res {
+            Ok(Some(Ok((data, events, execution_cost)))) => {
+                let events = events
+                    .iter()
+                    .enumerate()
+                    .map(|(i, event)| {
+                        let mut event_json = event.json_serialize(i, &Txid([0u8; 32]), true);
+                        // there is no transaction to attribute these events to
+                        if let Some(event_obj) = event_json.as_object_mut() {
+                            event_obj.remove("txid");
+                        }
+                        event_json
+                    })
+                    .collect();
+
+                HttpResponseType::SimulateContractCall(
+                    response_metadata,
+                    SimulateContractCallResponse {
+                        okay: true,
+                        result: Some(format!("0x{}", data.serialize())),
+                        cause: None,
+                        events,
+                        execution_cost,
+                    },
+                )
             }
-            HttpRequestType::OptionsPreflight(ref _md, ref _path) => {
-                let response_metadata = HttpResponseMetadata::from(&req);
-                let response = HttpResponseType::OptionsPreflight(response_metadata);
-                response
-                    .send(&mut self.connection.protocol, &mut reply)
-                    .map(|_| ())?;
-                None
+            Ok(Some(Err(e))) => HttpResponseType::SimulateContractCall(
+                response_metadata,
+                SimulateContractCallResponse {
+                    okay: false,
+                    result: None,
+                    cause: Some(e.to_string()),
+                    events: vec![],
+                    execution_cost: ExecutionCost::zero(),
+                },
+            ),
+            Ok(None) | Err(_) => {
+                HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
             }
-            HttpRequestType::GetIsTraitImplemented(
-                ref _md,
-                ref contract_addr,
-                ref contract_name,
-                ref trait_id,
-                ref tip_opt,
-            ) => {
-                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
-                    &mut self.connection.protocol,
-                    &mut reply,
-                    &req,
-                    tip_opt.as_ref(),
-                    sortdb,
-                    chainstate,
-                )? {
-                    ConversationHttp::handle_get_is_trait_implemented(
-                        &mut self.connection.protocol,
-                        &mut reply,
-                        &req,
-                        sortdb,
-                        chainstate,
-                        &tip,
-                        contract_addr,
-                        contract_name,
-                        trait_id,
-                    )?;
-                }
-                None
+        };
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Handle a POST to preview how a transaction's post-conditions would fare on the given chain
+    /// tip.  The transaction's contract call or contract deployment runs in a scratch fork of the
+    /// chain state, as in a contract-call simulation, and each post-condition is then checked
+    /// against the assets it moved.  Nothing else about the transaction is checked: it need not be
+    /// signed, and its nonce and fee are ignored.
+    fn handle_preview_postconditions<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        tx: &StacksTransaction,
+        options: &ConnectionOptions,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let mainnet = chainstate.mainnet;
+        if tx.is_mainnet() != mainnet {
+            let response = HttpResponseType::BadRequest(
+                response_metadata,
+                "Transaction is not for this network".to_string(),
+            );
+            return response.send(http, fd).map(|_| ());
+        }
+
+        let mut preview = RPCPostConditionsPreviewData {
+            txid: tx.txid().to_string(),
+            okay: false,
+            cause: None,
+            result: None,
+            would_abort: false,
+            post_condition_mode: match tx.post_condition_mode {
+                TransactionPostConditionMode::Allow => "allow".to_string(),
+                TransactionPostConditionMode::Deny => "deny".to_string(),
+            },
+            post_conditions: vec![],
+            unchecked_assets: vec![],
+        };
+
+        // only contract calls and contract deployments have their post-conditions checked
+        match tx.payload {
+            TransactionPayload::ContractCall(_) | TransactionPayload::SmartContract(_) => {}
+            TransactionPayload::TokenTransfer(..) if tx.post_conditions.len() > 0 => {
+                preview.cause =
+                    Some("TokenTransfer transactions do not support post-conditions".to_string());
+                let response = HttpResponseType::PostConditionsPreview(response_metadata, preview);
+                return response.send(http, fd).map(|_| ());
             }
-            HttpRequestType::ClientError(ref _md, ref err) => {
-                let response_metadata = HttpResponseMetadata::from(&req);
-                let response = match err {
-                    ClientError::Message(s) => HttpResponseType::BadRequestJSON(
-                        response_metadata,
-                        serde_json::Value::String(s.to_string()),
-                    ),
-                    ClientError::NotFound(path) => {
-                        HttpResponseType::NotFound(response_metadata, path.clone())
+            _ => {
+                preview.okay = true;
+                let response = HttpResponseType::PostConditionsPreview(response_metadata, preview);
+                return response.send(http, fd).map(|_| ());
+            }
+        }
+
+        let origin = tx.origin_address();
+        let origin_principal = origin.to_account_principal();
+        // the transaction's writes go to a scratch fork that is thrown away, so they are bounded
+        // by the block limit; everything else gets the read-only call budget
+        let mut cost_limit = options.read_only_call_limit.clone();
+        cost_limit.write_length = chainstate.block_limit.write_length;
+        cost_limit.write_count = chainstate.block_limit.write_count;
+
+        let data_opt_res =
+            chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly_owned(|mut clarity_db| {
+                    let cost_track = match LimitedCostTracker::new_mid_block(
+                        mainnet,
+                        cost_limit,
+                        &mut clarity_db,
+                    ) {
+                        Ok(cost_track) => cost_track,
+                        Err(_) => {
+                            return (
+                                Err(ClarityRuntimeError::from(
+                                    InterpreterError::CostContractLoadFailure,
+                                )),
+                                clarity_db,
+                            );
+                        }
+                    };
+
+                    let mut vm_env =
+                        OwnedEnvironment::new_cost_limited(mainnet, clarity_db, cost_track);
+                    let result =
+                        vm_env.execute_in_env(origin_principal.clone(), |env| match tx.payload {
+                            TransactionPayload::ContractCall(ref contract_call) => {
+                                let args: Vec<_> = contract_call
+                                    .function_args
+                                    .iter()
+                                    .map(|x| SymbolicExpression::atom_value(x.clone()))
+                                    .collect();
+                                env.execute_contract(
+                                    &contract_call.to_clarity_contract_id(),
+                                    contract_call.function_name.as_str(),
+                                    &args,
+                                    false,
+                                )
+                                .map(Some)
+                            }
+                            TransactionPayload::SmartContract(ref smart_contract) => {
+                                let contract_id = QualifiedContractIdentifier::new(
+                                    StandardPrincipalData::from(origin.clone()),
+                                    smart_contract.name.clone(),
+                                );
+                                env.initialize_contract(
+                                    contract_id,
+                                    &smart_contract.code_body.to_string(),
+                                )
+                                .map(|_| None)
+                            }
+                            _ => unreachable!("BUG: previewing an unsupported payload"),
+                        });
+                    let (clarity_db, _) = vm_env
+                        .destruct()
+                        .expect("Failed to recover database reference after executing transaction");
+
+                    (
+                        result.map(|(value, asset_map, _)| (value, asset_map)),
+                        clarity_db,
+                    )
+                })
+            });
+
+        let response = match data_opt_res {
+            Ok(Some(Ok((value_opt, asset_map)))) => {
+                let check = StacksChainState::evaluate_transaction_postconditions(
+                    &tx.post_conditions,
+                    &tx.post_condition_mode,
+                    &origin_principal,
+                    &asset_map,
+                );
+                preview.okay = true;
+                preview.result = value_opt.map(|value| format!("0x{}", value.serialize()));
+                preview.would_abort = !check.passed();
+                preview.post_conditions = check
+                    .condition_results
+                    .iter()
+                    .enumerate()
+                    .map(|(index, passed)| RPCPostConditionResult {
+                        index,
+                        passed: *passed,
+                    })
+                    .collect();
+                preview.unchecked_assets = check.unchecked_assets;
+                HttpResponseType::PostConditionsPreview(response_metadata, preview)
+            }
+            Ok(Some(Err(e))) => {
+                preview.cause = Some(e.to_string());
+                HttpResponseType::PostConditionsPreview(response_metadata, preview)
+            }
+            Ok(None) | Err(_) => {
+                HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+            }
+        };
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Handle a GET to fetch a contract's source code, given the chain tip.  Optionally returns a
+    /// MARF proof as well.
+    fn handle_get_contract_src<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        contract_addr: &StacksAddress,
+        contract_name: &ContractName,
+        with_proof: bool,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let contract_identifier =
+            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
+
+        let response =
+            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly(|db| {
+                    // don't load a source that is too big to send
+                    let source_size = db.get_contract_src_size(&contract_identifier)?;
+                    if let Some(response) =
+                        http.check_response_size(&response_metadata, source_size)
+                    {
+                        return Some(Err(response));
                     }
-                };
+                    let source = db.get_contract_src(&contract_identifier)?;
+                    let contract_commit_key = make_contract_hash_key(&contract_identifier);
+                    let (contract_commit, proof) = db
+                        .get_with_proof::<ContractCommitment>(&contract_commit_key)
+                        .expect("BUG: obtained source, but couldn't get MARF proof.");
+                    let marf_proof = if with_proof {
+                        Some(proof.to_hex())
+                    } else {
+                        None
+                    };
+                    let publish_height = contract_commit.block_height;
+                    Some(Ok(ContractSrcResponse {
+                        source,
+                        publish_height,
+                        marf_proof,
+                    }))
+                })
+            }) {
+                Ok(Some(Some(Ok(data)))) => {
+                    HttpResponseType::GetContractSrc(response_metadata, data)
+                }
+                Ok(Some(Some(Err(too_large)))) => too_large,
+                Ok(Some(None)) => HttpResponseType::NotFound(
+                    response_metadata,
+                    "No contract source data found".into(),
+                ),
+                Ok(None) | Err(_) => {
+                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+                }
+            };
+
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Handle a GET to fetch whether or not a contract implements a certain trait
+    fn handle_get_is_trait_implemented<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        contract_addr: &StacksAddress,
+        contract_name: &ContractName,
+        trait_id: &TraitIdentifier,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let contract_identifier =
+            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
+
+        let response =
+            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly(|db| {
+                    let analysis = db.load_contract_analysis(&contract_identifier)?;
+                    if analysis.implemented_traits.contains(trait_id) {
+                        Some(GetIsTraitImplementedResponse {
+                            is_implemented: true,
+                        })
+                    } else {
+                        let trait_defining_contract =
+                            db.load_contract_analysis(&trait_id.contract_identifier)?;
+                        let trait_definition =
+                            trait_defining_contract.get_defined_trait(&trait_id.name)?;
+                        let is_implemented = analysis
+                            .check_trait_compliance(trait_id, trait_definition)
+                            .is_ok();
+                        Some(GetIsTraitImplementedResponse { is_implemented })
+                    }
+                })
+            }) {
+                Ok(Some(Some(data))) => {
+                    HttpResponseType::GetIsTraitImplemented(response_metadata, data)
+                }
+                Ok(Some(None)) => HttpResponseType::NotFound(
+                    response_metadata,
+                    "No contract analysis found or trait definition not found".into(),
+                ),
+                Ok(None) | Err(_) => {
+                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+                }
+            };
+
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Handle a GET to fetch a contract's analysis data, given the chain tip.  Note that this isn't
+    /// something that's anchored to the blockchain, and can be different across different versions
+    /// of Stacks -- callers must trust the Stacks node to return correct analysis data.
+    /// Callers who don't trust the Stacks node should just fetch the contract source
+    /// code and analyze it offline.
+    fn handle_get_contract_abi<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        contract_addr: &StacksAddress,
+        contract_name: &ContractName,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let contract_identifier =
+            QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
+
+        let response =
+            match chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                clarity_tx.with_analysis_db_readonly(|db| {
+                    let contract = db.load_contract(&contract_identifier)?;
+                    contract.contract_interface
+                })
+            }) {
+                Ok(Some(Some(data))) => HttpResponseType::GetContractABI(response_metadata, data),
+                Ok(Some(None)) => HttpResponseType::NotFound(
+                    response_metadata,
+                    "No contract interface data found".into(),
+                ),
+                Ok(None) | Err(_) => {
+                    HttpResponseType::NotFound(response_metadata, "Chain tip not found".into())
+                }
+            };
+
+        response.send(http, fd).map(|_| ())
+    }
+
+    /// Handle a GET unconfirmed microblock stream.  Start streaming the reply.
+    /// The response's preamble (but not the block data) will be synchronously written to the fd
+    /// (so use a fd that can buffer!)
+    /// Return a BlockStreamData struct for the block that we're sending, so we can continue to
+    /// make progress sending it.
+    fn handle_getmicroblocks_unconfirmed<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        index_anchor_block_hash: &StacksBlockId,
+        min_seq: u16,
+        chainstate: &StacksChainState,
+    ) -> Result<Option<BlockStreamData>, net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        // do we have this unconfirmed microblock stream?
+        match chainstate.has_any_staging_microblock_indexed(index_anchor_block_hash, min_seq) {
+            Ok(false) => {
+                // nope
+                let response = HttpResponseType::NotFound(
+                    response_metadata,
+                    format!(
+                        "No such unconfirmed microblock stream for {} at or after {}",
+                        index_anchor_block_hash.to_hex(),
+                        min_seq
+                    ),
+                );
+                response.send(http, fd).and_then(|_| Ok(None))
+            }
+            Err(e) => {
+                // nope
+                warn!(
+                    "Failed to serve confirmed microblock stream {:?}: {:?}",
+                    req, &e
+                );
+                let response = HttpResponseType::ServerError(
+                    response_metadata,
+                    format!(
+                        "Failed to query unconfirmed microblock stream for {} at or after {}",
+                        index_anchor_block_hash.to_hex(),
+                        min_seq
+                    ),
+                );
+                response.send(http, fd).and_then(|_| Ok(None))
+            }
+            Ok(true) => {
+                if ConversationHttp::handle_request_deadline(http, fd, req)? {
+                    return Ok(None);
+                }
+                // yup! start streaming it back
+                let (response, stream_opt) = match BlockStreamData::new_microblock_unconfirmed(
+                    chainstate,
+                    index_anchor_block_hash.clone(),
+                    min_seq,
+                ) {
+                    Ok(stream) => (
+                        HttpResponseType::MicroblockStream(response_metadata),
+                        Some(stream),
+                    ),
+                    Err(chain_error::NoSuchBlockError) => (
+                        HttpResponseType::NotFound(
+                            response_metadata,
+                            format!(
+                                "No such unconfirmed microblock stream starting with {}",
+                                index_anchor_block_hash.to_hex()
+                            ),
+                        ),
+                        None,
+                    ),
+                    Err(_e) => {
+                        debug!(
+                            "Failed to load unconfirmed microblock stream {}: {:?}",
+                            &index_anchor_block_hash, &_e
+                        );
+                        (
+                            HttpResponseType::ServerError(
+                                response_metadata,
+                                format!(
+                                    "Failed to query unconfirmed microblock stream {}",
+                                    index_anchor_block_hash.to_hex()
+                                ),
+                            ),
+                            None,
+                        )
+                    }
+                };
+                response.send(http, fd).and_then(|_| Ok(stream_opt))
+            }
+        }
+    }
+
+    /// Handle a GET unconfirmed transaction.
+    /// The response will be synchronously written to the fd.
+    fn handle_gettransaction_unconfirmed<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        chainstate: &StacksChainState,
+        mempool: &MemPoolDB,
+        txid: &Txid,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        // present in the unconfirmed state?
+        if let Some(ref unconfirmed) = chainstate.unconfirmed_state.as_ref() {
+            if let Some((transaction, mblock_hash, seq)) =
+                unconfirmed.get_unconfirmed_transaction(txid)
+            {
+                let response = HttpResponseType::UnconfirmedTransaction(
+                    response_metadata,
+                    UnconfirmedTransactionResponse {
+                        status: UnconfirmedTransactionStatus::Microblock {
+                            block_hash: mblock_hash,
+                            seq: seq,
+                        },
+                        tx: to_hex(&transaction.serialize_to_vec()),
+                    },
+                );
+                return response.send(http, fd).map(|_| ());
+            }
+        }
+
+        // present in the mempool?
+        if let Some(txinfo) = MemPoolDB::get_tx(mempool.conn(), txid)? {
+            let response = HttpResponseType::UnconfirmedTransaction(
+                response_metadata,
+                UnconfirmedTransactionResponse {
+                    status: UnconfirmedTransactionStatus::Mempool,
+                    tx: to_hex(&txinfo.tx.serialize_to_vec()),
+                },
+            );
+            return response.send(http, fd).map(|_| ());
+        }
+
+        // not found
+        let response = HttpResponseType::NotFound(
+            response_metadata,
+            format!("No such unconfirmed transaction {}", txid),
+        );
+        return response.send(http, fd).map(|_| ());
+    }
+
+    /// Load up the canonical Stacks chain tip.  Note that this is subject to both burn chain block
+    /// Stacks block availability -- different nodes with different partial replicas of the Stacks chain state
+    /// will return different values here.
+    /// tip_opt is given by the HTTP request as the optional query parameter for the chain tip
+    /// hash.  It will be None if there was no paramter given.
+    /// The order of chain tips this method prefers is as follows:
+    /// * tip_opt, if it's Some(..),
+    /// * the unconfirmed canonical stacks chain tip, if initialized
+    /// * the confirmed canonical stacks chain tip
+    fn handle_load_stacks_chain_tip<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        tip_opt: Option<&StacksBlockId>,
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+    ) -> Result<Option<StacksBlockId>, net_error> {
+        let tip = match tip_opt {
+            Some(tip) => tip.clone(),
+            None => match chainstate.get_stacks_chain_tip(sortdb)? {
+                Some(tip) => StacksBlockHeader::make_index_block_hash(
+                    &tip.consensus_hash,
+                    &tip.anchored_block_hash,
+                ),
+                None => {
+                    let response_metadata = HttpResponseMetadata::from(req);
+                    warn!("Failed to load Stacks chain tip");
+                    let response = HttpResponseType::ServerError(
+                        response_metadata,
+                        format!("Failed to load Stacks chain tip"),
+                    );
+                    return response.send(http, fd).and_then(|_| Ok(None));
+                }
+            },
+        };
+        if ConversationHttp::handle_request_deadline(http, fd, req)? {
+            return Ok(None);
+        }
+        Ok(Some(tip))
+    }
+
+    /// If the request being handled has missed its deadline, answer it with a 503, and return
+    /// true.  Handlers check this between stages, so that they stop instead of doing more work
+    /// for a response that is too late.  Nothing checks the deadline within a stage: Clarity
+    /// evaluation and MARF proof generation run to completion once started.
+    fn handle_request_deadline<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+    ) -> Result<bool, net_error> {
+        match http.take_expired_request_deadline(&HttpResponseMetadata::from(req)) {
+            Some(response) => {
+                response.send(http, fd)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Resolve a request's `tip` to the chain tip to answer it against.  `tip=unconfirmed` is the
+    /// tip of the canonical chain tip's processed microblock stream, if this node has readable
+    /// unconfirmed state for it, and the canonical anchored chain tip otherwise.
+    fn handle_load_tip_request<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        tip_req: &TipRequest,
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+    ) -> Result<Option<StacksBlockId>, net_error> {
+        let tip_opt = match tip_req {
+            TipRequest::SpecificTip(tip) => Some(tip),
+            TipRequest::UseLatestAnchoredTip | TipRequest::UseLatestUnconfirmedTip => None,
+        };
+        let tip = match ConversationHttp::handle_load_stacks_chain_tip(
+            http, fd, req, tip_opt, sortdb, chainstate,
+        )? {
+            Some(tip) => tip,
+            None => {
+                return Ok(None);
+            }
+        };
+        if *tip_req != TipRequest::UseLatestUnconfirmedTip {
+            return Ok(Some(tip));
+        }
+        match chainstate.unconfirmed_state {
+            Some(ref unconfirmed_state)
+                if unconfirmed_state.confirmed_chain_tip == tip
+                    && unconfirmed_state.is_readable() =>
+            {
+                Ok(Some(unconfirmed_state.unconfirmed_chain_tip.clone()))
+            }
+            _ => Ok(Some(tip)),
+        }
+    }
+
+    fn handle_load_stacks_chain_tip_hashes<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        tip_opt: Option<&StacksBlockId>,
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+    ) -> Result<Option<(ConsensusHash, BlockHeaderHash)>, net_error> {
+        match tip_opt {
+            Some(tip) => match chainstate.get_block_header_hashes(&tip)? {
+                Some((ch, bl)) => {
+                    return Ok(Some((ch, bl)));
+                }
+                None => {}
+            },
+            None => match chainstate.get_stacks_chain_tip(sortdb)? {
+                Some(tip) => {
+                    return Ok(Some((tip.consensus_hash, tip.anchored_block_hash)));
+                }
+                None => {}
+            },
+        }
+        let response_metadata = HttpResponseMetadata::from(req);
+        warn!("Failed to load Stacks chain tip");
+        let response = HttpResponseType::ServerError(
+            response_metadata,
+            format!("Failed to load Stacks chain tip"),
+        );
+        response.send(http, fd).and_then(|_| Ok(None))
+    }
+
+    /// Handle a transaction.  Directly submit it to the mempool so the client can see any
+    /// rejection reasons up-front (different from how the peer network handles it).  Indicate
+    /// whether or not the transaction was accepted (and thus needs to be forwarded) in the return
+    /// value.  If `result` is set, an admitted transaction is answered with whether it was new to
+    /// the mempool, instead of with just its txid.
+    fn handle_post_transaction<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        chainstate: &mut StacksChainState,
+        consensus_hash: ConsensusHash,
+        block_hash: BlockHeaderHash,
+        mempool: &mut MemPoolDB,
+        tx: StacksTransaction,
+        atlasdb: &mut AtlasDB,
+        attachment: Option<Attachment>,
+        result: bool,
+        event_observer: Option<&dyn MemPoolEventDispatcher>,
+    ) -> Result<bool, net_error> {
+        let txid = tx.txid();
+        let response_metadata = HttpResponseMetadata::from(req);
+        let admitted = |response_metadata, txid, accepted| {
+            if result {
+                HttpResponseType::TransactionAccepted(response_metadata, txid, accepted)
+            } else {
+                HttpResponseType::TransactionID(response_metadata, txid)
+            }
+        };
+        let (response, accepted) = if mempool.has_tx(&txid) {
+            (admitted(response_metadata, txid, false), false)
+        } else {
+            match mempool.submit(
+                chainstate,
+                &consensus_hash,
+                &block_hash,
+                &tx,
+                event_observer,
+            ) {
+                Ok(_) => (admitted(response_metadata, txid, true), true),
+                Err(e) => (
+                    HttpResponseType::BadRequestJSON(response_metadata, e.into_json(&txid)),
+                    false,
+                ),
+            }
+        };
+
+        if let Some(ref attachment) = attachment {
+            if let TransactionPayload::ContractCall(ref contract_call) = tx.payload {
+                if atlasdb
+                    .should_keep_attachment(&contract_call.to_clarity_contract_id(), &attachment)
+                {
+                    atlasdb
+                        .insert_uninstantiated_attachment(attachment)
+                        .map_err(|e| net_error::DBError(e))?;
+                }
+            }
+        }
+
+        response.send(http, fd).and_then(|_| Ok(accepted))
+    }
+
+    /// Handle a block.  Directly submit a Stacks block to this node's chain state.
+    /// Indicate whether or not the block was accepted (i.e. it was new, and valid)
+    fn handle_post_block<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        consensus_hash: &ConsensusHash,
+        block: &StacksBlock,
+    ) -> Result<bool, net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        // is this a consensus hash we recognize?
+        let (response, accepted) =
+            match SortitionDB::get_sortition_id_by_consensus(&sortdb.conn(), consensus_hash) {
+                Ok(Some(_)) => {
+                    // we recognize this consensus hash
+                    let ic = sortdb.index_conn();
+                    match Relayer::process_new_anchored_block(
+                        &ic,
+                        chainstate,
+                        consensus_hash,
+                        block,
+                        0,
+                    ) {
+                        Ok(true) => {
+                            debug!(
+                                "Accepted Stacks block {}/{}",
+                                consensus_hash,
+                                &block.block_hash()
+                            );
+                            (
+                                HttpResponseType::StacksBlockAccepted(
+                                    response_metadata,
+                                    StacksBlockHeader::make_index_block_hash(
+                                        consensus_hash,
+                                        &block.block_hash(),
+                                    ),
+                                    true,
+                                ),
+                                true,
+                            )
+                        }
+                        Ok(false) => {
+                            debug!(
+                                "Did not accept Stacks block {}/{}",
+                                consensus_hash,
+                                &block.block_hash()
+                            );
+                            (
+                                HttpResponseType::StacksBlockAccepted(
+                                    response_metadata,
+                                    StacksBlockHeader::make_index_block_hash(
+                                        consensus_hash,
+                                        &block.block_hash(),
+                                    ),
+                                    false,
+                                ),
+                                false,
+                            )
+                        }
+                        Err(e) => {
+                            error!(
+                                "Failed to process anchored block {}/{}: {:?}",
+                                consensus_hash,
+                                &block.block_hash(),
+                                &e
+                            );
+                            (
+                                HttpResponseType::ServerError(
+                                    response_metadata,
+                                    format!(
+                                        "Failed to process anchored block {}/{}: {:?}",
+                                        consensus_hash,
+                                        &block.block_hash(),
+                                        &e
+                                    ),
+                                ),
+                                false,
+                            )
+                        }
+                    }
+                }
+                Ok(None) => {
+                    debug!(
+                        "Unrecognized consensus hash {} for block {}",
+                        consensus_hash,
+                        &block.block_hash()
+                    );
+                    (
+                        HttpResponseType::NotFound(
+                            response_metadata,
+                            format!("No such consensus hash '{}'", consensus_hash),
+                        ),
+                        false,
+                    )
+                }
+                Err(e) => {
+                    error!(
+                        "Failed to query sortition ID by consensus '{}'",
+                        consensus_hash
+                    );
+                    (
+                        HttpResponseType::ServerError(
+                            response_metadata,
+                            format!(
+                                "Failed to query sortition ID for consensus hash '{}': {:?}",
+                                consensus_hash, &e
+                            ),
+                        ),
+                        false,
+                    )
+                }
+            };
+
+        response.send(http, fd).and_then(|_| Ok(accepted))
+    }
+
+    /// Handle a microblock.  Directly submit it to the microblock store so the client can see any
+    /// rejection reasons up-front (different from how the peer network handles it).  Indicate
+    /// whether or not the microblock was accepted (and thus needs to be forwarded) in the return
+    /// value.
+    fn handle_post_microblock<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        consensus_hash: &ConsensusHash,
+        block_hash: &BlockHeaderHash,
+        chainstate: &mut StacksChainState,
+        microblock: &StacksMicroblock,
+    ) -> Result<bool, net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+        let (response, accepted) =
+            match chainstate.preprocess_streamed_microblock(consensus_hash, block_hash, microblock)
+            {
+                Ok(accepted) => {
+                    if accepted {
+                        debug!(
+                            "Accepted uploaded microblock {}/{}-{}",
+                            &consensus_hash,
+                            &block_hash,
+                            &microblock.block_hash()
+                        );
+                    } else {
+                        debug!(
+                            "Did not accept microblock {}/{}-{}",
+                            &consensus_hash,
+                            &block_hash,
+                            &microblock.block_hash()
+                        );
+                    }
+
+                    (
+                        HttpResponseType::MicroblockHash(
+                            response_metadata,
+                            microblock.block_hash(),
+                        ),
+                        accepted,
+                    )
+                }
+                Err(e) => (
+                    HttpResponseType::BadRequestJSON(response_metadata, e.into_json()),
+                    false,
+                ),
+            };
+
+        response.send(http, fd).and_then(|_| Ok(accepted))
+    }
+
+    /// Handle an external HTTP request.
+    /// Some requests, such as those for blocks, will create new reply streams.  This method adds
+    /// those new streams into the `reply_streams` set.
+    /// Returns a StacksMessageType option -- it's Some(...) if we need to forward a message to the
+    /// peer network (like a transaction or a block or microblock)
+    ///
+    /// Read-only requests are answered from a snapshot of the sortition DB, chainstate, Clarity
+    /// state, mempool and Atlas DB, taken when the request begins, so a request never sees some of
+    /// a block that another thread is in the middle of committing.
+    pub fn handle_request(
+        &mut self,
+        mut req: HttpRequestType,
+        chain_view: &BurnchainView,
+        peers: &PeerMap,
+        sortdb: &SortitionDB,
+        peerdb: &PeerDB,
+        atlasdb: &mut AtlasDB,
+        chainstate: &mut StacksChainState,
+        mempool: &mut MemPoolDB,
+        handler_opts: &RPCHandlerArgs,
+    ) -> Result<Option<StacksMessageType>, net_error> {
+        let class = req.endpoint_class();
+        let deadline = RPCRequestDeadline::new(
+            class,
+            self.connection.options.rpc_deadline_ms(class),
+            get_epoch_time_ms(),
+        );
+        self.connection.protocol.set_request_deadline(deadline);
+        if self.connection.options.http_mirror_mode
+            && ConversationHttp::is_immutable_request(chainstate, &req)
+        {
+            req.metadata_mut().cache_max_age =
+                Some(self.connection.options.http_mirror_cache_max_age);
+        }
+
+        let res = if !req.is_read_only() {
+            self.dispatch_request(
+                req,
+                chain_view,
+                peers,
+                sortdb,
+                peerdb,
+                atlasdb,
+                chainstate,
+                mempool,
+                handler_opts,
+            )
+        } else {
+            self.dispatch_readonly_request(
+                req,
+                chain_view,
+                peers,
+                sortdb,
+                peerdb,
+                atlasdb,
+                chainstate,
+                mempool,
+                handler_opts,
+            )
+        };
+        self.connection.protocol.set_request_deadline(None);
+        res
+    }
+
+    /// Handle a read-only external HTTP request, on a consistent view of the databases
+    fn dispatch_readonly_request(
+        &mut self,
+        req: HttpRequestType,
+        chain_view: &BurnchainView,
+        peers: &PeerMap,
+        sortdb: &SortitionDB,
+        peerdb: &PeerDB,
+        atlasdb: &mut AtlasDB,
+        chainstate: &mut StacksChainState,
+        mempool: &mut MemPoolDB,
+        handler_opts: &RPCHandlerArgs,
+    ) -> Result<Option<StacksMessageType>, net_error> {
+        let snapshot = RPCReadSnapshot::begin(sortdb, chainstate, Some(atlasdb), Some(mempool))?;
+        let res = self.dispatch_request(
+            req,
+            chain_view,
+            peers,
+            sortdb,
+            peerdb,
+            atlasdb,
+            chainstate,
+            mempool,
+            handler_opts,
+        );
+        snapshot.end(sortdb, chainstate, Some(atlasdb), Some(mempool))?;
+        res
+    }
+
+    /// Handle an external HTTP request, on whatever state the databases are in
+    fn dispatch_request(
+        &mut self,
+        req: HttpRequestType,
+        chain_view: &BurnchainView,
+        peers: &PeerMap,
+        sortdb: &SortitionDB,
+        peerdb: &PeerDB,
+        atlasdb: &mut AtlasDB,
+        chainstate: &mut StacksChainState,
+        mempool: &mut MemPoolDB,
+        handler_opts: &RPCHandlerArgs,
+    ) -> Result<Option<StacksMessageType>, net_error> {
+        let mut reply = self.connection.make_relay_handle(self.conn_id)?;
+        let keep_alive = req.metadata().keep_alive;
+        let mut ret = None;
+        let mut deferred_reply = None;
+
+        let low_disk_space = handler_opts
+            .runtime_toggles
+            .map(|toggles| toggles.low_disk_space())
+            .unwrap_or(false);
+
+        let stream_opt = match req {
+            _ if self.connection.options.read_only_replica && !req.is_read_only() => {
+                let response = HttpResponseType::ServiceUnavailable(
+                    HttpResponseMetadata::from(&req),
+                    "This node is a read-only replica and does not accept new data".to_string(),
+                );
+                response.send(&mut self.connection.protocol, &mut reply)?;
+                None
+            }
+            _ if self.connection.options.http_mirror_mode
+                && !ConversationHttp::is_immutable_request(chainstate, &req) =>
+            {
+                let response = HttpResponseType::NotFound(
+                    HttpResponseMetadata::from(&req),
+                    "This node is an HTTP mirror, and serves only blocks, microblocks, attachments, and state as of a given block"
+                        .to_string(),
+                );
+                response.send(&mut self.connection.protocol, &mut reply)?;
+                None
+            }
+            HttpRequestType::PostTransaction(..)
+            | HttpRequestType::PostBlock(..)
+            | HttpRequestType::PostMicroblock(..)
+                if low_disk_space =>
+            {
+                let response = HttpResponseType::ServiceUnavailable(
+                    HttpResponseMetadata::from(&req),
+                    "Node is low on disk space and is not accepting new data".to_string(),
+                );
+                response.send(&mut self.connection.protocol, &mut reply)?;
+                None
+            }
+            HttpRequestType::PostMicroblock(..)
+                if !self
+                    .connection
+                    .options
+                    .microblock_acceptance
+                    .accepts_pushed() =>
+            {
+                let response = HttpResponseType::Forbidden(
+                    HttpResponseMetadata::from(&req),
+                    "This node does not accept unconfirmed microblocks".to_string(),
+                );
+                response.send(&mut self.connection.protocol, &mut reply)?;
+                None
+            }
+            HttpRequestType::GetInfo(ref _md) => {
+                ConversationHttp::handle_getinfo(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    &self.burnchain,
+                    sortdb,
+                    chainstate,
+                    peerdb,
+                    handler_opts,
+                )?;
+                None
+            }
+            HttpRequestType::GetPoxInfo(ref _md, ref tip_opt) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_opt.as_ref(),
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_getpoxinfo(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        &self.burnchain,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetNeighbors(ref _md) => {
+                ConversationHttp::handle_getneighbors(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    self.network_id,
+                    chain_view,
+                    peers,
+                    peerdb,
+                )?;
+                None
+            }
+            HttpRequestType::GetBlock(ref _md, ref index_block_hash) => {
+                ConversationHttp::handle_getblock(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    index_block_hash,
+                    chainstate,
+                )?
+            }
+            HttpRequestType::GetMicroblocksIndexed(ref _md, ref index_head_hash) => {
+                ConversationHttp::handle_getmicroblocks_indexed(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    index_head_hash,
+                    chainstate,
+                )?
+            }
+            HttpRequestType::GetMicroblocksConfirmed(ref _md, ref anchor_index_block_hash) => {
+                ConversationHttp::handle_getmicroblocks_confirmed(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    anchor_index_block_hash,
+                    chainstate,
+                )?
+            }
+            HttpRequestType::GetMicroblocksUnconfirmed(
+                ref _md,
+                ref index_anchor_block_hash,
+                ref min_seq,
+            ) => ConversationHttp::handle_getmicroblocks_unconfirmed(
+                &mut self.connection.protocol,
+                &mut reply,
+                &req,
+                index_anchor_block_hash,
+                *min_seq,
+                chainstate,
+            )?,
+            HttpRequestType::GetTransactionUnconfirmed(ref _md, ref txid) => {
+                ConversationHttp::handle_gettransaction_unconfirmed(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    chainstate,
+                    mempool,
+                    txid,
+                )?;
+                None
+            }
+            HttpRequestType::GetAccount(ref _md, ref principal, ref tip_req, ref with_proof) => {
+                if let Some(tip) = ConversationHttp::handle_load_tip_request(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_account_entry(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        principal,
+                        *with_proof,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetRewardSet(ref _md, ref reward_cycle) => {
+                ConversationHttp::handle_get_reward_set(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    sortdb,
+                    chainstate,
+                    &self.burnchain,
+                    *reward_cycle,
+                )?;
+                None
+            }
+            HttpRequestType::GetSortition(ref _md, ref lookup) => {
+                ConversationHttp::handle_get_sortition(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    sortdb,
+                    chainstate,
+                    lookup,
+                )?;
+                None
+            }
+            HttpRequestType::GetConsensusSchedule(ref _md) => {
+                ConversationHttp::handle_get_consensus_schedule(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    sortdb,
+                    &self.burnchain,
+                )?;
+                None
+            }
+            HttpRequestType::GetMicroblockEquivocations(ref _md) => {
+                ConversationHttp::handle_get_microblock_equivocations(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    chainstate,
+                )?;
+                None
+            }
+            HttpRequestType::GetAdminToggles(ref _md, ref token) => {
+                ConversationHttp::handle_admin_toggles(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    handler_opts,
+                    token.as_ref(),
+                    None,
+                )?;
+                None
+            }
+            HttpRequestType::PostAdminToggles(ref _md, ref token, ref update) => {
+                ConversationHttp::handle_admin_toggles(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    handler_opts,
+                    token.as_ref(),
+                    Some(update),
+                )?;
+                None
+            }
+            HttpRequestType::PostTransferStxOp(ref _md, ref token, ref request) => {
+                ConversationHttp::handle_post_transfer_stx_op(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    handler_opts,
+                    token.as_ref(),
+                    request,
+                    sortdb,
+                    chainstate,
+                )?;
+                None
+            }
+            HttpRequestType::GetMinerWallet(ref _md, ref token) => {
+                ConversationHttp::handle_get_miner_wallet(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    handler_opts,
+                    token.as_ref(),
+                )?;
+                None
+            }
+            HttpRequestType::GetClarityProfiles(ref _md, ref token) => {
+                ConversationHttp::handle_get_clarity_profiles(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    handler_opts,
+                    token.as_ref(),
+                )?;
+                None
+            }
+            HttpRequestType::PostBlockProposal(ref _md, ref token, ref request) => {
+                ConversationHttp::handle_post_block_proposal(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    handler_opts,
+                    token.as_ref(),
+                    request,
+                    sortdb,
+                    chainstate,
+                )?;
+                None
+            }
+            HttpRequestType::GetAccountTransactions(
+                ref _md,
+                ref principal,
+                ref before,
+                ref limit,
+                ref tip_opt,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_opt.as_ref(),
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_account_transactions(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        chainstate,
+                        &tip,
+                        principal,
+                        before.clone(),
+                        *limit,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetTransactionReceipt(ref _md, ref txid, ref tip_opt) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_opt.as_ref(),
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_transaction_receipt(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        chainstate,
+                        &tip,
+                        txid,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetSponsorship(ref _md, ref sponsor, ref tip_opt) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_opt.as_ref(),
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_sponsorship(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        mempool,
+                        &tip,
+                        sponsor,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetAccountBalance(ref _md, ref principal, ref tip_opt) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_opt.as_ref(),
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_account_balance(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        principal,
+                        handler_opts.genesis_unlock_heights,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetStackingStatus(ref _md, ref principal, ref tip_opt) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_opt.as_ref(),
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_stacking_status(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        principal,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetDelegations(ref _md, ref principal, ref tip_opt) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_opt.as_ref(),
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_delegations(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        principal,
+                        &self.connection.options,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetMapEntry(
+                ref _md,
+                ref contract_addr,
+                ref contract_name,
+                ref map_name,
+                ref key,
+                ref tip_req,
+                ref with_proof,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_tip_request(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_map_entry(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        contract_addr,
+                        contract_name,
+                        map_name,
+                        key,
+                        *with_proof,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetTransferCost(ref _md) => {
+                ConversationHttp::handle_token_transfer_cost(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                )?;
+                None
+            }
+            HttpRequestType::GetContractABI(
+                ref _md,
+                ref contract_addr,
+                ref contract_name,
+                ref tip_opt,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_opt.as_ref(),
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_contract_abi(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        contract_addr,
+                        contract_name,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetContractEvents(
+                ref _md,
+                ref contract_addr,
+                ref contract_name,
+                ref topic_opt,
+                ref before,
+                ref limit,
+                ref tip_opt,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_opt.as_ref(),
+                    sortdb,
+                    chainstate,
+                )? {
+                    let contract_identifier = QualifiedContractIdentifier::new(
+                        contract_addr.clone().into(),
+                        contract_name.clone(),
+                    );
+                    ConversationHttp::handle_get_contract_events(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        chainstate,
+                        &tip,
+                        &contract_identifier,
+                        topic_opt.as_ref(),
+                        before.clone(),
+                        *limit,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::CallReadOnlyFunction(
+                ref _md,
+                ref ctrct_addr,
+                ref ctrct_name,
+                ref as_sender,
+                ref func_name,
+                ref args,
+                ref tip_req,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_tip_request(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    match handler_opts.readonly_call_pool {
+                        // the pool's workers can't see the unconfirmed state, so calls against
+                        // the unconfirmed tip are always evaluated here
+                        Some(pool) if !ConversationHttp::is_unconfirmed_tip(chainstate, &tip) => {
+                            let response_metadata = HttpResponseMetadata::from(&req);
+                            let call = ReadOnlyCall {
+                                tip,
+                                contract_identifier: QualifiedContractIdentifier::new(
+                                    ctrct_addr.clone().into(),
+                                    ctrct_name.clone(),
+                                ),
+                                function: func_name.clone(),
+                                sender: as_sender.clone(),
+                                args: args.clone(),
+                                cost_limit: self.connection.options.read_only_call_limit.clone(),
+                                response_metadata: response_metadata.clone(),
+                                deadline: self.connection.protocol.request_deadline().cloned(),
+                            };
+                            deferred_reply = Some((pool.submit(call)?, response_metadata));
+                        }
+                        _ => {
+                            ConversationHttp::handle_readonly_function_call(
+                                &mut self.connection.protocol,
+                                &mut reply,
+                                &req,
+                                sortdb,
+                                chainstate,
+                                &tip,
+                                ctrct_addr,
+                                ctrct_name,
+                                func_name,
+                                as_sender,
+                                args,
+                                &self.connection.options,
+                            )?;
+                        }
+                    }
+                }
+                None
+            }
+            HttpRequestType::SimulateContractCall(
+                ref _md,
+                ref ctrct_addr,
+                ref ctrct_name,
+                ref as_sender,
+                ref func_name,
+                ref args,
+                ref overrides,
+                ref tip_opt,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_opt.as_ref(),
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_simulate_contract_call(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        ctrct_addr,
+                        ctrct_name,
+                        func_name,
+                        as_sender,
+                        args,
+                        overrides,
+                        &self.connection.options,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::PreviewPostConditions(ref _md, ref tx, ref tip_req) => {
+                if let Some(tip) = ConversationHttp::handle_load_tip_request(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_req,
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_preview_postconditions(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        tx,
+                        &self.connection.options,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::GetContractSrc(
+                ref _md,
+                ref contract_addr,
+                ref contract_name,
+                ref tip_opt,
+                ref with_proof,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_opt.as_ref(),
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_contract_src(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        contract_addr,
+                        contract_name,
+                        *with_proof,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::PostTransaction(ref _md, ref tx, ref attachment, result) => {
+                match chainstate.get_stacks_chain_tip(sortdb)? {
+                    Some(tip) => {
+                        let accepted = ConversationHttp::handle_post_transaction(
+                            &mut self.connection.protocol,
+                            &mut reply,
+                            &req,
+                            chainstate,
+                            tip.consensus_hash,
+                            tip.anchored_block_hash,
+                            mempool,
+                            tx.clone(),
+                            atlasdb,
+                            attachment.clone(),
+                            result,
+                            handler_opts.event_observer.as_deref(),
+                        )?;
+                        if accepted {
+                            // forward to peer network
+                            ret = Some(StacksMessageType::Transaction(tx.clone()));
+                        }
+                    }
+                    None => {
+                        let response_metadata = HttpResponseMetadata::from(&req);
+                        warn!("Failed to load Stacks chain tip");
+                        let response = HttpResponseType::ServerError(
+                            response_metadata,
+                            format!("Failed to load Stacks chain tip"),
+                        );
+                        response.send(&mut self.connection.protocol, &mut reply)?;
+                    }
+                }
+                None
+            }
+            HttpRequestType::GetAttachment(ref _md, ref content_hash) => {
+                ConversationHttp::handle_getattachment(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    atlasdb,
+                    content_hash.clone(),
+                )?;
+                None
+            }
+            HttpRequestType::GetAttachmentsInv(
+                ref _md,
+                ref index_block_hash,
+                ref pages_indexes,
+            ) => {
+                ConversationHttp::handle_getattachmentsinv(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    atlasdb,
+                    &index_block_hash,
+                    pages_indexes,
+                    &self.connection.options,
+                )?;
+                None
+            }
+            HttpRequestType::PostBlock(ref _md, ref consensus_hash, ref block) => {
+                let accepted = ConversationHttp::handle_post_block(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    sortdb,
+                    chainstate,
+                    consensus_hash,
+                    block,
+                )?;
+                if accepted {
+                    // inform the peer network so it can announce its presence
+                    ret = Some(StacksMessageType::Blocks(BlocksData {
+                        blocks: vec![(consensus_hash.clone(), block.clone())],
+                    }));
+                }
+                None
+            }
+            HttpRequestType::PostMicroblock(ref _md, ref mblock, ref tip_opt) => {
+                if let Some((consensus_hash, block_hash)) =
+                    ConversationHttp::handle_load_stacks_chain_tip_hashes(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        tip_opt.as_ref(),
+                        sortdb,
+                        chainstate,
+                    )?
+                {
+                    let accepted = ConversationHttp::handle_post_microblock(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        &consensus_hash,
+                        &block_hash,
+                        chainstate,
+                        mblock,
+                    )?;
+                    if accepted {
+                        // forward to peer network
+                        let tip =
+                            StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_hash);
+                        ret = Some(StacksMessageType::Microblocks(MicroblocksData {
+                            index_anchor_block: tip,
+                            microblocks: vec![(*mblock).clone()],
+                        }));
+                    }
+                }
+                None
+            }
+            HttpRequestType::OptionsPreflight(ref _md, ref _path) => {
+                let response_metadata = HttpResponseMetadata::from(&req);
+                let response = HttpResponseType::OptionsPreflight(response_metadata);
+                response
+                    .send(&mut self.connection.protocol, &mut reply)
+                    .map(|_| ())?;
+                None
+            }
+            HttpRequestType::GetIsTraitImplemented(
+                ref _md,
+                ref contract_addr,
+                ref contract_name,
+                ref trait_id,
+                ref tip_opt,
+            ) => {
+                if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    tip_opt.as_ref(),
+                    sortdb,
+                    chainstate,
+                )? {
+                    ConversationHttp::handle_get_is_trait_implemented(
+                        &mut self.connection.protocol,
+                        &mut reply,
+                        &req,
+                        sortdb,
+                        chainstate,
+                        &tip,
+                        contract_addr,
+                        contract_name,
+                        trait_id,
+                    )?;
+                }
+                None
+            }
+            HttpRequestType::ClientError(ref _md, ref err) => {
+                let response_metadata = HttpResponseMetadata::from(&req);
+                let response = match err {
+                    ClientError::Message(s) => HttpResponseType::BadRequestJSON(
+                        response_metadata,
+                        serde_json::Value::String(s.to_string()),
+                    ),
+                    ClientError::NotFound(path) => {
+                        HttpResponseType::NotFound(response_metadata, path.clone())
+                    }
+                };
+
+                response
+                    .send(&mut self.connection.protocol, &mut reply)
+                    .map(|_| ())?;
+                None
+            }
+        };
+
+        if let Some((receiver, response_metadata)) = deferred_reply {
+            // reply will be written once the read-only call pool gets back to us
+            let deadline = self.connection.protocol.request_deadline().cloned();
+            self.pending_readonly_call = Some((reply, receiver, response_metadata, deadline));
+            return Ok(ret);
+        }
+
+        match stream_opt {
+            None => {
+                self.reply_streams.push_back((reply, None, keep_alive));
+            }
+            Some(stream) => {
+                self.reply_streams.push_back((
+                    reply,
+                    Some((
+                        HttpChunkedTransferWriterState::new(STREAM_CHUNK_SIZE as usize),
+                        stream,
+                    )),
+                    keep_alive,
+                ));
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Make progress on outbound requests.
+    /// Return true if the connection should be kept alive after all messages are drained.
+    /// If we process a request with "Connection: close", then return false (indicating that the
+    /// connection should be severed once the conversation is drained)
+    fn send_outbound_responses(
+        &mut self,
+        chainstate: &mut StacksChainState,
+    ) -> Result<(), net_error> {
+        // send out streamed responses in the order they were requested
+        let mut drained_handle = false;
+        let mut drained_stream = false;
+        let mut broken = false;
+        let mut do_keep_alive = true;
+
+        test_debug!(
+            "{:?}: {} HTTP replies pending",
+            &self,
+            self.reply_streams.len()
+        );
+        match self.reply_streams.front_mut() {
+            Some((ref mut reply, ref mut stream_opt, ref keep_alive)) => {
+                do_keep_alive = *keep_alive;
+
+                // if we're streaming, make some progress on the stream
+                match stream_opt {
+                    Some((ref mut http_chunk_state, ref mut stream)) => {
+                        let mut encoder =
+                            HttpChunkedTransferWriter::from_writer_state(reply, http_chunk_state);
+                        match stream.stream_to(chainstate, &mut encoder, STREAM_CHUNK_SIZE) {
+                            Ok(nw) => {
+                                test_debug!("streamed {} bytes", nw);
+                                if nw == 0 {
+                                    // EOF -- finish chunk and stop sending.
+                                    if !encoder.corked() {
+                                        encoder.flush().map_err(|e| {
+                                            test_debug!("Write error on encoder flush: {:?}", &e);
+                                            net_error::WriteError(e)
+                                        })?;
+
+                                        encoder.cork();
+
+                                        test_debug!("stream indicates EOF");
+                                    }
+
+                                    // try moving some data to the connection only once we're done
+                                    // streaming
+                                    match reply.try_flush() {
+                                        Ok(res) => {
+                                            test_debug!("Streamed reply is drained");
+                                            drained_handle = res;
+                                        }
+                                        Err(e) => {
+                                            // dead
+                                            warn!("Broken HTTP connection: {:?}", &e);
+                                            broken = true;
+                                        }
+                                    }
+                                    drained_stream = true;
+                                }
+                            }
+                            Err(e) => {
+                                // broken -- terminate the stream.
+                                // For example, if we're streaming an unconfirmed block or
+                                // microblock, the data can get moved to the chunk store out from
+                                // under the stream.
+                                warn!("Failed to send to HTTP connection: {:?}", &e);
+                                broken = true;
+                            }
+                        }
+                    }
+                    None => {
+                        // not streamed; all data is buffered
+                        drained_stream = true;
+
+                        // try moving some data to the connection
+                        match reply.try_flush() {
+                            Ok(res) => {
+                                test_debug!("Reply is drained");
+                                drained_handle = res;
+                            }
+                            Err(e) => {
+                                // dead
+                                warn!("Broken HTTP connection: {:?}", &e);
+                                broken = true;
+                            }
+                        }
+                    }
+                }
+            }
+            None => {}
+        }
+
+        if broken || (drained_handle && drained_stream) {
+            // done with this stream
+            test_debug!(
+                "{:?}: done with stream (broken={}, drained_handle={}, drained_stream={})",
+                &self,
+                broken,
+                drained_handle,
+                drained_stream
+            );
+            self.total_reply_count += 1;
+            self.reply_streams.pop_front();
+
+            if !do_keep_alive {
+                // encountered "Connection: close"
+                self.keep_alive = false;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn try_send_recv_response(
+        req: ReplyHandleHttp,
+    ) -> Result<HttpResponseType, Result<ReplyHandleHttp, net_error>> {
+        match req.try_send_recv() {
+            Ok(message) => match message {
+                StacksHttpMessage::Request(_) => {
+                    warn!("Received response: not a HTTP response");
+                    return Err(Err(net_error::InvalidMessage));
+                }
+                StacksHttpMessage::Response(http_response) => Ok(http_response),
+            },
+            Err(res) => Err(res),
+        }
+    }
+
+    /// Make progress on our request/response
+    fn recv_inbound_response(&mut self) -> Result<(), net_error> {
+        // make progress on our pending request (if it exists).
+        let inprogress = self.pending_request.is_some();
+        let is_pending = self.pending_response.is_none();
+
+        let pending_request = self.pending_request.take();
+        let response = match pending_request {
+            None => Ok(self.pending_response.take()),
+            Some(req) => match ConversationHttp::try_send_recv_response(req) {
+                Ok(response) => Ok(Some(response)),
+                Err(res) => match res {
+                    Ok(handle) => {
+                        // try again
+                        self.pending_request = Some(handle);
+                        Ok(self.pending_response.take())
+                    }
+                    Err(e) => Err(e),
+                },
+            },
+        }?;
+
+        self.pending_response = response;
+
+        if inprogress && self.pending_request.is_none() {
+            test_debug!(
+                "{:?},id={}: HTTP request finished",
+                &self.peer_host,
+                self.conn_id
+            );
+        }
+
+        if is_pending && self.pending_response.is_some() {
+            test_debug!(
+                "{:?},id={}: HTTP response finished",
+                &self.peer_host,
+                self.conn_id
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Try to get our response
+    pub fn try_get_response(&mut self) -> Option<HttpResponseType> {
+        self.pending_response.take()
+    }
+
+    /// If the read-only call pool has finished evaluating our pending read-only call, then write
+    /// its reply and queue it up to be sent.
+    fn poll_readonly_call(&mut self) -> Result<(), net_error> {
+        let (mut reply, receiver, response_metadata, deadline) =
+            match self.pending_readonly_call.take() {
+                Some(pending) => pending,
+                None => {
+                    return Ok(());
+                }
+            };
+
+        let keep_alive = response_metadata.client_keep_alive;
+        let response = match receiver.try_recv() {
+            Ok(response) => response,
+            Err(TryRecvError::Empty) => match deadline {
+                Some(deadline) if deadline.is_expired(get_epoch_time_ms()) => {
+                    // stop waiting, so the requests queued up behind this one can go ahead
+                    debug!("{:?}: read-only call missed its deadline", &self);
+                    monitoring::increment_rpc_request_timeouts(deadline.class.as_str());
+                    HttpResponseType::ServiceUnavailable(
+                        response_metadata,
+                        RPC_DEADLINE_EXCEEDED_MESSAGE.to_string(),
+                    )
+                }
+                _ => {
+                    self.pending_readonly_call =
+                        Some((reply, receiver, response_metadata, deadline));
+                    return Ok(());
+                }
+            },
+            Err(TryRecvError::Disconnected) => {
+                warn!("{:?}: read-only call worker hung up", &self);
+                HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to evaluate read-only function call".into(),
+                )
+            }
+        };
+
+        response.send(&mut self.connection.protocol, &mut reply)?;
+        self.reply_streams.push_back((reply, None, keep_alive));
+        Ok(())
+    }
+
+    /// Make progress on in-flight messages.
+    pub fn try_flush(&mut self, chainstate: &mut StacksChainState) -> Result<(), net_error> {
+        self.poll_readonly_call()?;
+        self.send_outbound_responses(chainstate)?;
+        self.recv_inbound_response()?;
+        Ok(())
+    }
+
+    /// Is the connection idle?
+    pub fn is_idle(&self) -> bool {
+        self.pending_response.is_none()
+            && self.connection.inbox_len() == 0
+            && self.connection.outbox_len() == 0
+            && self.reply_streams.len() == 0
+            && self.pending_readonly_call.is_none()
+    }
+
+    /// Is the conversation out of pending data?
+    /// Don't consider it drained if we haven't received anything yet
+    pub fn is_drained(&self) -> bool {
+        ((self.total_request_count > 0 && self.total_reply_count > 0)
+            || self.pending_error_response.is_some())
+            && self.is_idle()
+    }
+
+    /// Should the connection be kept alive even if drained?
+    pub fn is_keep_alive(&self) -> bool {
+        self.keep_alive
+    }
+
+    /// When was the last time we got an inbound request?
+    pub fn get_last_request_time(&self) -> u64 {
+        self.last_request_timestamp
+    }
+
+    /// When was the last time we sent data as part of an outbound response?
+    pub fn get_last_response_time(&self) -> u64 {
+        self.last_response_timestamp
+    }
+
+    /// When was this converation conencted?
+    pub fn get_connection_time(&self) -> u64 {
+        self.connection_time
+    }
+
+    /// How many bytes are set aside in this conversation's read and write buffers?
+    pub fn buffered_bytes(&self) -> usize {
+        self.connection.buffered_bytes()
+    }
+
+    /// Make progress on in-flight requests and replies.
+    /// Returns the list of transactions we'll need to forward to the peer network
+    pub fn chat(
+        &mut self,
+        chain_view: &BurnchainView,
+        peers: &PeerMap,
+        sortdb: &SortitionDB,
+        peerdb: &PeerDB,
+        atlasdb: &mut AtlasDB,
+        chainstate: &mut StacksChainState,
+        mempool: &mut MemPoolDB,
+        handler_args: &RPCHandlerArgs,
+    ) -> Result<Vec<StacksMessageType>, net_error> {
+        // if we have an in-flight error, then don't take any more requests.
+        if self.pending_error_response.is_some() {
+            return Ok(vec![]);
+        }
+
+        // replies must go out in request order, so don't take any more requests while a read-only
+        // call is still being evaluated
+        self.poll_readonly_call()?;
+        if self.pending_readonly_call.is_some() {
+            return Ok(vec![]);
+        }
+
+        // handle in-bound HTTP request(s)
+        let num_inbound = self.connection.inbox_len();
+        let mut ret = vec![];
+        test_debug!("{:?}: {} HTTP requests pending", &self, num_inbound);
+
+        for _i in 0..num_inbound {
+            if self.pending_readonly_call.is_some() {
+                break;
+            }
+            let msg = match self.connection.next_inbox_message() {
+                None => {
+                    continue;
+                }
+                Some(m) => m,
+            };
+
+            match msg {
+                StacksHttpMessage::Request(req) => {
+                    // new request
+                    self.total_request_count += 1;
+                    self.last_request_timestamp = get_epoch_time_secs();
+                    debug!("{:?}: Handle HTTP request", &self;
+                           "request_id" => %format!("{}-{}", self.conn_id, self.total_request_count),
+                           "path" => %req.request_path());
+                    let msg_opt = monitoring::instrument_http_request_handler(req, |req| {
+                        self.handle_request(
+                            req,
+                            chain_view,
+                            peers,
+                            sortdb,
+                            peerdb,
+                            atlasdb,
+                            chainstate,
+                            mempool,
+                            handler_args,
+                        )
+                    })?;
+                    if let Some(msg) = msg_opt {
+                        ret.push(msg);
+                    }
+                }
+                StacksHttpMessage::Response(resp) => {
+                    // Is there someone else waiting for this message?  If so, pass it along.
+                    // (this _should_ be our pending_request handle)
+                    match self
+                        .connection
+                        .fulfill_request(StacksHttpMessage::Response(resp))
+                    {
+                        None => {
+                            test_debug!("{:?}: Fulfilled pending HTTP request", &self);
+                        }
+                        Some(_msg) => {
+                            // unsolicited; discard
+                            test_debug!("{:?}: Dropping unsolicited HTTP response", &self);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Remove all timed-out messages, and ding the remote peer as unhealthy
+    pub fn clear_timeouts(&mut self) -> () {
+        self.connection.drain_timeouts();
+    }
+
+    /// Load data into our HTTP connection
+    pub fn recv<R: Read>(&mut self, r: &mut R) -> Result<usize, net_error> {
+        let mut total_recv = 0;
+        loop {
+            let nrecv = match self.connection.recv_data(r) {
+                Ok(nr) => nr,
+                Err(e) => {
+                    debug!("{:?}: failed to recv: {:?}", self, &e);
+                    return Err(e);
+                }
+            };
+
+            total_recv += nrecv;
+            if nrecv > 0 {
+                self.last_request_timestamp = get_epoch_time_secs();
+            } else {
+                break;
+            }
+        }
+        monitoring::update_inbound_rpc_bandwidth(total_recv as i64);
+        Ok(total_recv)
+    }
+
+    /// Write data out of our HTTP connection.  Write as much as we can
+    pub fn send<W: Write>(
+        &mut self,
+        w: &mut W,
+        chainstate: &mut StacksChainState,
+    ) -> Result<usize, net_error> {
+        let mut total_sz = 0;
+        loop {
+            // prime the Write
+            self.try_flush(chainstate)?;
+
+            let sz = match self.connection.send_data(w) {
+                Ok(sz) => sz,
+                Err(e) => {
+                    info!("{:?}: failed to send on HTTP conversation: {:?}", self, &e);
+                    return Err(e);
+                }
+            };
+
+            total_sz += sz;
+            if sz > 0 {
+                self.last_response_timestamp = get_epoch_time_secs();
+            } else {
+                break;
+            }
+        }
+        monitoring::update_inbound_rpc_bandwidth(total_sz as i64);
+        Ok(total_sz)
+    }
+
+    /// Make a new getinfo request to this endpoint
+    pub fn new_getinfo(&self) -> HttpRequestType {
+        HttpRequestType::GetInfo(HttpRequestMetadata::from_host(self.peer_host.clone()))
+    }
+
+    /// Make a new getinfo request to this endpoint
+    pub fn new_getpoxinfo(&self, tip_opt: Option<StacksBlockId>) -> HttpRequestType {
+        HttpRequestType::GetPoxInfo(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            tip_opt,
+        )
+    }
+
+    /// Make a new getneighbors request to this endpoint
+    pub fn new_getneighbors(&self) -> HttpRequestType {
+        HttpRequestType::GetNeighbors(HttpRequestMetadata::from_host(self.peer_host.clone()))
+    }
+
+    /// Make a new getblock request to this endpoint
+    pub fn new_getblock(&self, index_block_hash: StacksBlockId) -> HttpRequestType {
+        HttpRequestType::GetBlock(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            index_block_hash,
+        )
+    }
+
+    /// Make a new get-microblocks request to this endpoint
+    pub fn new_getmicroblocks_indexed(
+        &self,
+        index_microblock_hash: StacksBlockId,
+    ) -> HttpRequestType {
+        HttpRequestType::GetMicroblocksIndexed(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            index_microblock_hash,
+        )
+    }
+
+    /// Make a new get-microblocks-confirmed request to this endpoint
+    pub fn new_getmicroblocks_confirmed(
+        &self,
+        index_anchor_block_hash: StacksBlockId,
+    ) -> HttpRequestType {
+        HttpRequestType::GetMicroblocksConfirmed(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            index_anchor_block_hash,
+        )
+    }
+
+    /// Make a new get-microblocks request for unconfirmed microblocks
+    pub fn new_getmicroblocks_unconfirmed(
+        &self,
+        anchored_index_block_hash: StacksBlockId,
+        min_seq: u16,
+    ) -> HttpRequestType {
+        HttpRequestType::GetMicroblocksUnconfirmed(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            anchored_index_block_hash,
+            min_seq,
+        )
+    }
+
+    /// Make a new get-unconfirmed-tx request
+    pub fn new_gettransaction_unconfirmed(&self, txid: Txid) -> HttpRequestType {
+        HttpRequestType::GetTransactionUnconfirmed(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            txid,
+        )
+    }
+
+    /// Make a new post-transaction request
+    pub fn new_post_transaction(&self, tx: StacksTransaction) -> HttpRequestType {
+        HttpRequestType::PostTransaction(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            tx,
+            None,
+            false,
+        )
+    }
+
+    /// Make a new post-block request
+    pub fn new_post_block(&self, ch: ConsensusHash, block: StacksBlock) -> HttpRequestType {
+        HttpRequestType::PostBlock(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            ch,
+            block,
+        )
+    }
+
+    /// Make a new post-microblock request
+    pub fn new_post_microblock(
+        &self,
+        mblock: StacksMicroblock,
+        tip_opt: Option<StacksBlockId>,
+    ) -> HttpRequestType {
+        HttpRequestType::PostMicroblock(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            mblock,
+            tip_opt,
+        )
+    }
+
+    /// Make a new request for an account
+    pub fn new_getaccount(
+        &self,
+        principal: PrincipalData,
+        tip_req: TipRequest,
+        with_proof: bool,
+    ) -> HttpRequestType {
+        HttpRequestType::GetAccount(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            principal,
+            tip_req,
+            with_proof,
+        )
+    }
+
+    /// Make a new request for a page of an account's transaction history
+    pub fn new_getaccounttransactions(
+        &self,
+        principal: PrincipalData,
+        before: Option<(u64, u32)>,
+        limit: u64,
+        tip_opt: Option<StacksBlockId>,
+    ) -> HttpRequestType {
+        HttpRequestType::GetAccountTransactions(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            principal,
+            before,
+            limit,
+            tip_opt,
+        )
+    }
+
+    /// Make a new request for a page of the values a contract printed
+    pub fn new_getcontractevents(
+        &self,
+        contract_addr: StacksAddress,
+        contract_name: ContractName,
+        topic_opt: Option<ClarityName>,
+        before: Option<(u64, u32, u32)>,
+        limit: u64,
+        tip_opt: Option<StacksBlockId>,
+    ) -> HttpRequestType {
+        HttpRequestType::GetContractEvents(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            contract_addr,
+            contract_name,
+            topic_opt,
+            before,
+            limit,
+            tip_opt,
+        )
+    }
+
+    /// Make a new request for a transaction's receipt
+    pub fn new_gettransactionreceipt(
+        &self,
+        txid: Txid,
+        tip_opt: Option<StacksBlockId>,
+    ) -> HttpRequestType {
+        HttpRequestType::GetTransactionReceipt(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            txid,
+            tip_opt,
+        )
+    }
+
+    /// Make a new request for what an account sponsors in the mempool
+    pub fn new_getsponsorship(
+        &self,
+        sponsor: StacksAddress,
+        tip_opt: Option<StacksBlockId>,
+    ) -> HttpRequestType {
+        HttpRequestType::GetSponsorship(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            sponsor,
+            tip_opt,
+        )
+    }
+
+    /// Make a new request for an account's balances and vesting schedule
+    pub fn new_getaccountbalance(
+        &self,
+        principal: PrincipalData,
+        tip_opt: Option<StacksBlockId>,
+    ) -> HttpRequestType {
+        HttpRequestType::GetAccountBalance(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            principal,
+            tip_opt,
+        )
+    }
+
+    /// Make a new request for a principal's stacking status
+    pub fn new_getstackingstatus(
+        &self,
+        principal: PrincipalData,
+        tip_opt: Option<StacksBlockId>,
+    ) -> HttpRequestType {
+        HttpRequestType::GetStackingStatus(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            principal,
+            tip_opt,
+        )
+    }
+
+    /// Make a new request for the principals delegating to a delegatee
+    pub fn new_getdelegations(
+        &self,
+        delegated_to: PrincipalData,
+        tip_opt: Option<StacksBlockId>,
+    ) -> HttpRequestType {
+        HttpRequestType::GetDelegations(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            delegated_to,
+            tip_opt,
+        )
+    }
+
+    /// Make a new request for a reward cycle's reward set
+    pub fn new_getrewardset(&self, reward_cycle: u64) -> HttpRequestType {
+        HttpRequestType::GetRewardSet(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            reward_cycle,
+        )
+    }
+
+    /// Make a new request for a sortition, and the Stacks block that won it
+    pub fn new_getsortition(&self, lookup: SortitionLookup) -> HttpRequestType {
+        HttpRequestType::GetSortition(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            lookup,
+        )
+    }
+
+    /// Make a new request for the consensus schedule
+    pub fn new_getconsensusschedule(&self) -> HttpRequestType {
+        HttpRequestType::GetConsensusSchedule(HttpRequestMetadata::from_host(
+            self.peer_host.clone(),
+        ))
+    }
+
+    /// Make a new request for the microblock equivocations the node has detected
+    pub fn new_getmicroblockequivocations(&self) -> HttpRequestType {
+        HttpRequestType::GetMicroblockEquivocations(HttpRequestMetadata::from_host(
+            self.peer_host.clone(),
+        ))
+    }
+
+    /// Make a new request for the node's runtime toggles
+    pub fn new_getadmintoggles(&self, token: Option<String>) -> HttpRequestType {
+        HttpRequestType::GetAdminToggles(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            token.map(BearerToken),
+        )
+    }
+
+    /// Make a new request to set some of the node's runtime toggles
+    pub fn new_postadmintoggles(
+        &self,
+        token: Option<String>,
+        update: RPCAdminTogglesUpdate,
+    ) -> HttpRequestType {
+        HttpRequestType::PostAdminToggles(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            token.map(BearerToken),
+            update,
+        )
+    }
+
+    /// Make a new request to submit a transfer-STX burn operation
+    pub fn new_posttransferstxop(
+        &self,
+        token: Option<String>,
+        request: RPCTransferStxOpRequest,
+    ) -> HttpRequestType {
+        HttpRequestType::PostTransferStxOp(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            token.map(BearerToken),
+            request,
+        )
+    }
+
+    /// Make a new request for the miner's burnchain wallet
+    pub fn new_getminerwallet(&self, token: Option<String>) -> HttpRequestType {
+        HttpRequestType::GetMinerWallet(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            token.map(BearerToken),
+        )
+    }
+
+    /// Make a new request for the Clarity profiles of recent blocks
+    pub fn new_getclarityprofiles(&self, token: Option<String>) -> HttpRequestType {
+        HttpRequestType::GetClarityProfiles(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            token.map(BearerToken),
+        )
+    }
+
+    /// Make a new request to check a block proposal
+    pub fn new_postblockproposal(
+        &self,
+        token: Option<String>,
+        request: RPCBlockProposalRequest,
+    ) -> HttpRequestType {
+        HttpRequestType::PostBlockProposal(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            token.map(BearerToken),
+            request,
+        )
+    }
+
+    /// Make a new request for a data map
+    pub fn new_getmapentry(
+        &self,
+        contract_addr: StacksAddress,
+        contract_name: ContractName,
+        map_name: ClarityName,
+        key: Value,
+        tip_req: TipRequest,
+        with_proof: bool,
+    ) -> HttpRequestType {
+        HttpRequestType::GetMapEntry(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            contract_addr,
+            contract_name,
+            map_name,
+            key,
+            tip_req,
+            with_proof,
+        )
+    }
+
+    /// Make a new request to get a contract's source
+    pub fn new_getcontractsrc(
+        &self,
+        contract_addr: StacksAddress,
+        contract_name: ContractName,
+        tip_opt: Option<StacksBlockId>,
+        with_proof: bool,
+    ) -> HttpRequestType {
+        HttpRequestType::GetContractSrc(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            contract_addr,
+            contract_name,
+            tip_opt,
+            with_proof,
+        )
+    }
+
+    /// Make a new request to get a contract's ABI
+    pub fn new_getcontractabi(
+        &self,
+        contract_addr: StacksAddress,
+        contract_name: ContractName,
+        tip_opt: Option<StacksBlockId>,
+    ) -> HttpRequestType {
+        HttpRequestType::GetContractABI(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            contract_addr,
+            contract_name,
+            tip_opt,
+        )
+    }
+
+    /// Make a new request to run a read-only function
+    pub fn new_callreadonlyfunction(
+        &self,
+        contract_addr: StacksAddress,
+        contract_name: ContractName,
+        sender: PrincipalData,
+        function_name: ClarityName,
+        function_args: Vec<Value>,
+        tip_req: TipRequest,
+    ) -> HttpRequestType {
+        HttpRequestType::CallReadOnlyFunction(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            contract_addr,
+            contract_name,
+            sender,
+            function_name,
+            function_args,
+            tip_req,
+        )
+    }
+
+    /// Make a new request to simulate a contract call with state overrides
+    pub fn new_simulatecontractcall(
+        &self,
+        contract_addr: StacksAddress,
+        contract_name: ContractName,
+        sender: PrincipalData,
+        function_name: ClarityName,
+        function_args: Vec<Value>,
+        overrides: Vec<ClarityStateOverride>,
+        tip_opt: Option<StacksBlockId>,
+    ) -> HttpRequestType {
+        HttpRequestType::SimulateContractCall(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            contract_addr,
+            contract_name,
+            sender,
+            function_name,
+            function_args,
+            overrides,
+            tip_opt,
+        )
+    }
+
+    /// Make a new request to preview a transaction's post-conditions
+    pub fn new_previewpostconditions(
+        &self,
+        tx: StacksTransaction,
+        tip_req: TipRequest,
+    ) -> HttpRequestType {
+        HttpRequestType::PreviewPostConditions(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            tx,
+            tip_req,
+        )
+    }
+
+    /// Make a new request for attachment inventory page
+    pub fn new_getattachmentsinv(
+        &self,
+        index_block_hash: StacksBlockId,
+        pages_indexes: HashSet<u32>,
+    ) -> HttpRequestType {
+        HttpRequestType::GetAttachmentsInv(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            index_block_hash,
+            pages_indexes,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::convert::TryInto;
+    use std::iter::FromIterator;
+
+    use rusqlite::types::ToSql;
+
+    use address::*;
+    use burnchains::Burnchain;
+    use burnchains::BurnchainView;
+    use burnchains::*;
+    use chainstate::burn::ConsensusHash;
+    use chainstate::stacks::db::blocks::test::*;
+    use chainstate::stacks::db::BlockStreamData;
+    use chainstate::stacks::db::ChainstateIndexes;
+    use chainstate::stacks::db::StacksChainState;
+    use chainstate::stacks::miner::*;
+    use chainstate::stacks::test::*;
+    use chainstate::stacks::Error as chain_error;
+    use chainstate::stacks::*;
+    use net::codec::*;
+    use net::http::*;
+    use net::test::*;
+    use net::*;
+    use util::db::u64_to_sql;
+    use util::get_epoch_time_secs;
+    use util::hash::hex_bytes;
+    use util::pipe::*;
+    use vm::types::*;
+
+    use crate::types::chainstate::BlockHeaderHash;
+    use crate::types::chainstate::BurnchainHeaderHash;
+    use chainstate::stacks::C32_ADDRESS_VERSION_TESTNET_SINGLESIG;
+    use net::relay::MicroblockAcceptance;
+
+    use super::*;
+
+    const TEST_CONTRACT: &'static str = "
+        (define-data-var bar int 0)
+        (define-map unit-map { account: principal } { units: int })
+        (define-public (get-bar) (ok (var-get bar)))
+        (define-public (set-bar (x int) (y int))
+          (begin (var-set bar (/ x y)) (ok (var-get bar))))
+        (define-public (add-unit)
+          (begin
+            (map-set unit-map { account: tx-sender } { units: 1 } )
+            (print { unit-added: tx-sender })
+            (ok 1)))
+        (begin
+          (map-set unit-map { account: 'ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R } { units: 123 }))";
+
+    fn convo_send_recv(
+        sender: &mut ConversationHttp,
+        sender_chainstate: &mut StacksChainState,
+        receiver: &mut ConversationHttp,
+        receiver_chainstate: &mut StacksChainState,
+    ) -> () {
+        let (mut pipe_read, mut pipe_write) = Pipe::new();
+        pipe_read.set_nonblocking(true);
+
+        loop {
+            let res = true;
+
+            sender.try_flush(sender_chainstate).unwrap();
+            receiver.try_flush(receiver_chainstate).unwrap();
+
+            pipe_write.try_flush().unwrap();
+
+            let all_relays_flushed =
+                receiver.num_pending_outbound() == 0 && sender.num_pending_outbound() == 0;
+
+            let nw = sender.send(&mut pipe_write, sender_chainstate).unwrap();
+            let nr = receiver.recv(&mut pipe_read).unwrap();
+
+            test_debug!(
+                "res = {}, all_relays_flushed = {} ({},{}), nr = {}, nw = {}",
+                res,
+                all_relays_flushed,
+                receiver.num_pending_outbound(),
+                sender.num_pending_outbound(),
+                nr,
+                nw
+            );
+            if res && all_relays_flushed && nr == 0 && nw == 0 {
+                test_debug!("Breaking send_recv");
+                break;
+            }
+        }
+    }
+
+    fn test_rpc<F, C>(
+        test_name: &str,
+        peer_1_p2p: u16,
+        peer_1_http: u16,
+        peer_2_p2p: u16,
+        peer_2_http: u16,
+        make_request: F,
+        check_result: C,
+    ) -> ()
+    where
+        F: FnOnce(
+            &mut TestPeer,
+            &mut ConversationHttp,
+            &mut TestPeer,
+            &mut ConversationHttp,
+        ) -> HttpRequestType,
+        C: FnOnce(&HttpRequestType, &HttpResponseType, &mut TestPeer, &mut TestPeer) -> bool,
+    {
+        test_rpc_with_handler_args(
+            test_name,
+            peer_1_p2p,
+            peer_1_http,
+            peer_2_p2p,
+            peer_2_http,
+            &RPCHandlerArgs::default(),
+            make_request,
+            check_result,
+        )
+    }
+
+    /// Like `test_rpc`, but the server handles the request with `server_handler_args`
+    fn test_rpc_with_handler_args<F, C>(
+        test_name: &str,
+        peer_1_p2p: u16,
+        peer_1_http: u16,
+        peer_2_p2p: u16,
+        peer_2_http: u16,
+        server_handler_args: &RPCHandlerArgs,
+        make_request: F,
+        check_result: C,
+    ) -> ()
+    where
+        F: FnOnce(
+            &mut TestPeer,
+            &mut ConversationHttp,
+            &mut TestPeer,
+            &mut ConversationHttp,
+        ) -> HttpRequestType,
+        C: FnOnce(&HttpRequestType, &HttpResponseType, &mut TestPeer, &mut TestPeer) -> bool,
+    {
+        let mut peer_1_config = TestPeerConfig::new(test_name, peer_1_p2p, peer_1_http);
+        let mut peer_2_config = TestPeerConfig::new(test_name, peer_2_p2p, peer_2_http);
 
-                response
-                    .send(&mut self.connection.protocol, &mut reply)
-                    .map(|_| ())?;
-                None
-            }
-        };
+        // ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R
+        let privk1 = StacksPrivateKey::from_hex(
+            "9f1f85a512a96a244e4c0d762788500687feb97481639572e3bffbd6860e6ab001",
+        )
+        .unwrap();
 
-        match stream_opt {
-            None => {
-                self.reply_streams.push_back((reply, None, keep_alive));
-            }
-            Some(stream) => {
-                self.reply_streams.push_back((
-                    reply,
-                    Some((
-                        HttpChunkedTransferWriterState::new(STREAM_CHUNK_SIZE as usize),
-                        stream,
-                    )),
-                    keep_alive,
-                ));
-            }
-        }
-        Ok(ret)
-    }
+        // STVN97YYA10MY5F6KQJHKNYJNM24C4A1AT39WRW
+        let privk2 = StacksPrivateKey::from_hex(
+            "94c319327cc5cd04da7147d32d836eb2e4c44f4db39aa5ede7314a761183d0c701",
+        )
+        .unwrap();
+        let microblock_privkey = StacksPrivateKey::new();
+        let microblock_pubkeyhash =
+            Hash160::from_node_public_key(&StacksPublicKey::from_private(&microblock_privkey));
 
-    /// Make progress on outbound requests.
-    /// Return true if the connection should be kept alive after all messages are drained.
-    /// If we process a request with "Connection: close", then return false (indicating that the
-    /// connection should be severed once the conversation is drained)
-    fn send_outbound_responses(
-        &mut self,
-        chainstate: &mut StacksChainState,
-    ) -> Result<(), net_error> {
-        // send out streamed responses in the order they were requested
-        let mut drained_handle = false;
-        let mut drained_stream = false;
-        let mut broken = false;
-        let mut do_keep_alive = true;
+        let addr1 = StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(&privk1)],
+        )
+        .unwrap();
+        let addr2 = StacksAddress::from_public_keys(
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+            &AddressHashMode::SerializeP2PKH,
+            1,
+            &vec![StacksPublicKey::from_private(&privk2)],
+        )
+        .unwrap();
 
-        test_debug!(
-            "{:?}: {} HTTP replies pending",
-            &self,
-            self.reply_streams.len()
+        peer_1_config.initial_balances = vec![
+            (addr1.to_account_principal(), 1000000000),
+            (addr2.to_account_principal(), 1000000000),
+        ];
+
+        peer_2_config.initial_balances = vec![
+            (addr1.to_account_principal(), 1000000000),
+            (addr2.to_account_principal(), 1000000000),
+        ];
+
+        peer_1_config.add_neighbor(&peer_2_config.to_neighbor());
+        peer_2_config.add_neighbor(&peer_1_config.to_neighbor());
+
+        let mut peer_1 = TestPeer::new(peer_1_config);
+        let mut peer_2 = TestPeer::new(peer_2_config);
+
+        // maintain every optional index, so the endpoints that read them can be tested
+        let indexes = ChainstateIndexes {
+            account_txs: true,
+            tx_receipts: true,
+            print_events: true,
+        };
+        peer_1.set_chainstate_indexes(indexes);
+        peer_2.set_chainstate_indexes(indexes);
+
+        // mine one block with a contract in it
+        // first the coinbase
+        // make a coinbase for this miner
+        let mut tx_coinbase = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&privk1).unwrap(),
+            TransactionPayload::Coinbase(CoinbasePayload([0x00; 32])),
         );
-        match self.reply_streams.front_mut() {
-            Some((ref mut reply, ref mut stream_opt, ref keep_alive)) => {
-                do_keep_alive = *keep_alive;
+        tx_coinbase.chain_id = 0x80000000;
+        tx_coinbase.anchor_mode = TransactionAnchorMode::OnChainOnly;
+        tx_coinbase.auth.set_origin_nonce(0);
 
-                // if we're streaming, make some progress on the stream
-                match stream_opt {
-                    Some((ref mut http_chunk_state, ref mut stream)) => {
-                        let mut encoder =
-                            HttpChunkedTransferWriter::from_writer_state(reply, http_chunk_state);
-                        match stream.stream_to(chainstate, &mut encoder, STREAM_CHUNK_SIZE) {
-                            Ok(nw) => {
-                                test_debug!("streamed {} bytes", nw);
-                                if nw == 0 {
-                                    // EOF -- finish chunk and stop sending.
-                                    if !encoder.corked() {
-                                        encoder.flush().map_err(|e| {
-                                            test_debug!("Write error on encoder flush: {:?}", &e);
-                                            net_error::WriteError(e)
-                                        })?;
+        let mut tx_signer = StacksTransactionSigner::new(&tx_coinbase);
+        tx_signer.sign_origin(&privk1).unwrap();
+        let tx_coinbase_signed = tx_signer.get_tx().unwrap();
 
-                                        encoder.cork();
+        // next the contract
+        let contract = TEST_CONTRACT.clone();
+        let mut tx_contract = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&privk1).unwrap(),
+            TransactionPayload::new_smart_contract(&format!("hello-world"), &contract.to_string())
+                .unwrap(),
+        );
 
-                                        test_debug!("stream indicates EOF");
-                                    }
+        tx_contract.chain_id = 0x80000000;
+        tx_contract.auth.set_origin_nonce(1);
+        tx_contract.set_tx_fee(0);
 
-                                    // try moving some data to the connection only once we're done
-                                    // streaming
-                                    match reply.try_flush() {
-                                        Ok(res) => {
-                                            test_debug!("Streamed reply is drained");
-                                            drained_handle = res;
-                                        }
-                                        Err(e) => {
-                                            // dead
-                                            warn!("Broken HTTP connection: {:?}", &e);
-                                            broken = true;
-                                        }
-                                    }
-                                    drained_stream = true;
-                                }
-                            }
-                            Err(e) => {
-                                // broken -- terminate the stream.
-                                // For example, if we're streaming an unconfirmed block or
-                                // microblock, the data can get moved to the chunk store out from
-                                // under the stream.
-                                warn!("Failed to send to HTTP connection: {:?}", &e);
-                                broken = true;
-                            }
-                        }
-                    }
-                    None => {
-                        // not streamed; all data is buffered
-                        drained_stream = true;
+        let mut tx_signer = StacksTransactionSigner::new(&tx_contract);
+        tx_signer.sign_origin(&privk1).unwrap();
+        let tx_contract_signed = tx_signer.get_tx().unwrap();
 
-                        // try moving some data to the connection
-                        match reply.try_flush() {
-                            Ok(res) => {
-                                test_debug!("Reply is drained");
-                                drained_handle = res;
-                            }
-                            Err(e) => {
-                                // dead
-                                warn!("Broken HTTP connection: {:?}", &e);
-                                broken = true;
-                            }
-                        }
-                    }
-                }
-            }
-            None => {}
-        }
+        // update account and state in a microblock that will be unconfirmed
+        let mut tx_cc = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&privk1).unwrap(),
+            TransactionPayload::new_contract_call(addr1.clone(), "hello-world", "add-unit", vec![])
+                .unwrap(),
+        );
 
-        if broken || (drained_handle && drained_stream) {
-            // done with this stream
-            test_debug!(
-                "{:?}: done with stream (broken={}, drained_handle={}, drained_stream={})",
-                &self,
-                broken,
-                drained_handle,
-                drained_stream
-            );
-            self.total_reply_count += 1;
-            self.reply_streams.pop_front();
+        tx_cc.chain_id = 0x80000000;
+        tx_cc.auth.set_origin_nonce(2);
+        tx_cc.set_tx_fee(123);
 
-            if !do_keep_alive {
-                // encountered "Connection: close"
-                self.keep_alive = false;
-            }
-        }
+        let mut tx_signer = StacksTransactionSigner::new(&tx_cc);
+        tx_signer.sign_origin(&privk1).unwrap();
+        let tx_cc_signed = tx_signer.get_tx().unwrap();
+        let tx_cc_len = {
+            let mut bytes = vec![];
+            tx_cc_signed.consensus_serialize(&mut bytes).unwrap();
+            bytes.len() as u64
+        };
 
-        Ok(())
-    }
+        // make an unconfirmed contract
+        let unconfirmed_contract = "(define-read-only (ro-test) (ok 1))";
+        let mut tx_unconfirmed_contract = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(&privk1).unwrap(),
+            TransactionPayload::new_smart_contract(
+                &format!("hello-world-unconfirmed"),
+                &unconfirmed_contract.to_string(),
+            )
+            .unwrap(),
+        );
 
-    pub fn try_send_recv_response(
-        req: ReplyHandleHttp,
-    ) -> Result<HttpResponseType, Result<ReplyHandleHttp, net_error>> {
-        match req.try_send_recv() {
-            Ok(message) => match message {
-                StacksHttpMessage::Request(_) => {
-                    warn!("Received response: not a HTTP response");
-                    return Err(Err(net_error::InvalidMessage));
-                }
-                StacksHttpMessage::Response(http_response) => Ok(http_response),
-            },
-            Err(res) => Err(res),
-        }
-    }
+        tx_unconfirmed_contract.chain_id = 0x80000000;
+        tx_unconfirmed_contract.auth.set_origin_nonce(3);
+        tx_unconfirmed_contract.set_tx_fee(0);
 
-    /// Make progress on our request/response
-    fn recv_inbound_response(&mut self) -> Result<(), net_error> {
-        // make progress on our pending request (if it exists).
-        let inprogress = self.pending_request.is_some();
-        let is_pending = self.pending_response.is_none();
+        let mut tx_signer = StacksTransactionSigner::new(&tx_unconfirmed_contract);
+        tx_signer.sign_origin(&privk1).unwrap();
+        let tx_unconfirmed_contract_signed = tx_signer.get_tx().unwrap();
+        let tx_unconfirmed_contract_len = {
+            let mut bytes = vec![];
+            tx_unconfirmed_contract_signed
+                .consensus_serialize(&mut bytes)
+                .unwrap();
+            bytes.len() as u64
+        };
 
-        let pending_request = self.pending_request.take();
-        let response = match pending_request {
-            None => Ok(self.pending_response.take()),
-            Some(req) => match ConversationHttp::try_send_recv_response(req) {
-                Ok(response) => Ok(Some(response)),
-                Err(res) => match res {
-                    Ok(handle) => {
-                        // try again
-                        self.pending_request = Some(handle);
-                        Ok(self.pending_response.take())
-                    }
-                    Err(e) => Err(e),
-                },
-            },
-        }?;
+        let tip =
+            SortitionDB::get_canonical_burn_chain_tip(&peer_1.sortdb.as_ref().unwrap().conn())
+                .unwrap();
+        let mut anchor_cost = ExecutionCost::zero();
+        let mut anchor_size = 0;
 
-        self.pending_response = response;
+        // make a block and a microblock.
+        // Put the coinbase and smart-contract in the anchored block.
+        // Put the contract-call in the microblock
+        let (burn_ops, stacks_block, microblocks) = peer_1.make_tenure(
+            |ref mut miner, ref mut sortdb, ref mut chainstate, vrf_proof, ref parent_opt, _| {
+                let parent_tip = match parent_opt {
+                    None => StacksChainState::get_genesis_header_info(chainstate.db()).unwrap(),
+                    Some(block) => {
+                        let ic = sortdb.index_conn();
+                        let snapshot = SortitionDB::get_block_snapshot_for_winning_stacks_block(
+                            &ic,
+                            &tip.sortition_id,
+                            &block.block_hash(),
+                        )
+                        .unwrap()
+                        .unwrap(); // succeeds because we don't fork
+                        StacksChainState::get_anchored_block_header_info(
+                            chainstate.db(),
+                            &snapshot.consensus_hash,
+                            &snapshot.winning_stacks_block_hash,
+                        )
+                        .unwrap()
+                        .unwrap()
+                    }
+                };
 
-        if inprogress && self.pending_request.is_none() {
-            test_debug!(
-                "{:?},id={}: HTTP request finished",
-                &self.peer_host,
-                self.conn_id
-            );
-        }
+                let block_builder = StacksBlockBuilder::make_regtest_block_builder(
+                    &parent_tip,
+                    vrf_proof,
+                    tip.total_burn,
+                    microblock_pubkeyhash,
+                )
+                .unwrap();
+                let (anchored_block, anchored_block_size, anchored_block_cost) =
+                    StacksBlockBuilder::make_anchored_block_from_txs(
+                        block_builder,
+                        chainstate,
+                        &sortdb.index_conn(),
+                        vec![tx_coinbase_signed.clone(), tx_contract_signed.clone()],
+                    )
+                    .unwrap();
 
-        if is_pending && self.pending_response.is_some() {
-            test_debug!(
-                "{:?},id={}: HTTP response finished",
-                &self.peer_host,
-                self.conn_id
-            );
-        }
+                anchor_size = anchored_block_size;
+                anchor_cost = anchored_block_cost;
 
-        Ok(())
-    }
+                (anchored_block, vec![])
+            },
+        );
 
-    /// Try to get our response
-    pub fn try_get_response(&mut self) -> Option<HttpResponseType> {
-        self.pending_response.take()
-    }
+        let (_, _, consensus_hash) = peer_1.next_burnchain_block(burn_ops.clone());
+        peer_2.next_burnchain_block(burn_ops.clone());
 
-    /// Make progress on in-flight messages.
-    pub fn try_flush(&mut self, chainstate: &mut StacksChainState) -> Result<(), net_error> {
-        self.send_outbound_responses(chainstate)?;
-        self.recv_inbound_response()?;
-        Ok(())
-    }
+        peer_1.process_stacks_epoch_at_tip(&stacks_block, &vec![]);
+        peer_2.process_stacks_epoch_at_tip(&stacks_block, &vec![]);
 
-    /// Is the connection idle?
-    pub fn is_idle(&self) -> bool {
-        self.pending_response.is_none()
-            && self.connection.inbox_len() == 0
-            && self.connection.outbox_len() == 0
-            && self.reply_streams.len() == 0
-    }
+        // build 1-block microblock stream with the contract-call and the unconfirmed contract
+        let microblock = {
+            let sortdb = peer_1.sortdb.take().unwrap();
+            Relayer::setup_unconfirmed_state(peer_1.chainstate(), &sortdb).unwrap();
+            let mblock = {
+                let sort_iconn = sortdb.index_conn();
+                let mut microblock_builder = StacksMicroblockBuilder::new(
+                    stacks_block.block_hash(),
+                    consensus_hash.clone(),
+                    peer_1.chainstate(),
+                    &sort_iconn,
+                )
+                .unwrap();
+                let microblock = microblock_builder
+                    .mine_next_microblock_from_txs(
+                        vec![
+                            (tx_cc_signed, tx_cc_len),
+                            (tx_unconfirmed_contract_signed, tx_unconfirmed_contract_len),
+                        ],
+                        &microblock_privkey,
+                    )
+                    .unwrap();
+                microblock
+            };
+            peer_1.sortdb = Some(sortdb);
+            mblock
+        };
 
-    /// Is the conversation out of pending data?
-    /// Don't consider it drained if we haven't received anything yet
-    pub fn is_drained(&self) -> bool {
-        ((self.total_request_count > 0 && self.total_reply_count > 0)
-            || self.pending_error_response.is_some())
-            && self.is_idle()
-    }
+        // store microblock stream
+        peer_1
+            .chainstate()
+            .preprocess_streamed_microblock(
+                &consensus_hash,
+                &stacks_block.block_hash(),
+                &microblock,
+            )
+            .unwrap();
+        peer_2
+            .chainstate()
+            .preprocess_streamed_microblock(
+                &consensus_hash,
+                &stacks_block.block_hash(),
+                &microblock,
+            )
+            .unwrap();
 
-    /// Should the connection be kept alive even if drained?
-    pub fn is_keep_alive(&self) -> bool {
-        self.keep_alive
-    }
+        // process microblock stream to generate unconfirmed state
+        let canonical_tip =
+            StacksBlockHeader::make_index_block_hash(&consensus_hash, &stacks_block.block_hash());
+        let sortdb1 = peer_1.sortdb.take().unwrap();
+        let sortdb2 = peer_2.sortdb.take().unwrap();
+        peer_1
+            .chainstate()
+            .reload_unconfirmed_state(&sortdb1.index_conn(), canonical_tip.clone())
+            .unwrap();
+        peer_2
+            .chainstate()
+            .reload_unconfirmed_state(&sortdb2.index_conn(), canonical_tip.clone())
+            .unwrap();
+        peer_1.sortdb = Some(sortdb1);
+        peer_2.sortdb = Some(sortdb2);
 
-    /// When was the last time we got an inbound request?
-    pub fn get_last_request_time(&self) -> u64 {
-        self.last_request_timestamp
-    }
+        let view_1 = peer_1.get_burnchain_view().unwrap();
+        let view_2 = peer_2.get_burnchain_view().unwrap();
 
-    /// When was the last time we sent data as part of an outbound response?
-    pub fn get_last_response_time(&self) -> u64 {
-        self.last_response_timestamp
-    }
+        let mut convo_1 = ConversationHttp::new(
+            peer_1.config.network_id,
+            &peer_1.config.burnchain,
+            format!("127.0.0.1:{}", peer_1_http)
+                .parse::<SocketAddr>()
+                .unwrap(),
+            Some(UrlString::try_from(format!("http://peer1.com")).unwrap()),
+            peer_1.to_peer_host(),
+            &peer_1.config.connection_opts,
+            0,
+        );
 
-    /// When was this converation conencted?
-    pub fn get_connection_time(&self) -> u64 {
-        self.connection_time
-    }
+        let mut convo_2 = ConversationHttp::new(
+            peer_2.config.network_id,
+            &peer_2.config.burnchain,
+            format!("127.0.0.1:{}", peer_2_http)
+                .parse::<SocketAddr>()
+                .unwrap(),
+            Some(UrlString::try_from(format!("http://peer2.com")).unwrap()),
+            peer_2.to_peer_host(),
+            &peer_2.config.connection_opts,
+            1,
+        );
 
-    /// Make progress on in-flight requests and replies.
-    /// Returns the list of transactions we'll need to forward to the peer network
-    pub fn chat(
-        &mut self,
-        chain_view: &BurnchainView,
-        peers: &PeerMap,
-        sortdb: &SortitionDB,
-        peerdb: &PeerDB,
-        atlasdb: &mut AtlasDB,
-        chainstate: &mut StacksChainState,
-        mempool: &mut MemPoolDB,
-        handler_args: &RPCHandlerArgs,
-    ) -> Result<Vec<StacksMessageType>, net_error> {
-        // if we have an in-flight error, then don't take any more requests.
-        if self.pending_error_response.is_some() {
-            return Ok(vec![]);
-        }
+        let req = make_request(&mut peer_1, &mut convo_1, &mut peer_2, &mut convo_2);
 
-        // handle in-bound HTTP request(s)
-        let num_inbound = self.connection.inbox_len();
-        let mut ret = vec![];
-        test_debug!("{:?}: {} HTTP requests pending", &self, num_inbound);
+        convo_1.send_request(req.clone()).unwrap();
 
-        for _i in 0..num_inbound {
-            let msg = match self.connection.next_inbox_message() {
-                None => {
-                    continue;
-                }
-                Some(m) => m,
-            };
+        test_debug!("convo1 sends to convo2");
+        convo_send_recv(
+            &mut convo_1,
+            peer_1.chainstate(),
+            &mut convo_2,
+            peer_2.chainstate(),
+        );
 
-            match msg {
-                StacksHttpMessage::Request(req) => {
-                    // new request
-                    self.total_request_count += 1;
-                    self.last_request_timestamp = get_epoch_time_secs();
-                    let msg_opt = monitoring::instrument_http_request_handler(req, |req| {
-                        self.handle_request(
-                            req,
-                            chain_view,
-                            peers,
-                            sortdb,
-                            peerdb,
-                            atlasdb,
-                            chainstate,
-                            mempool,
-                            handler_args,
-                        )
-                    })?;
-                    if let Some(msg) = msg_opt {
-                        ret.push(msg);
-                    }
-                }
-                StacksHttpMessage::Response(resp) => {
-                    // Is there someone else waiting for this message?  If so, pass it along.
-                    // (this _should_ be our pending_request handle)
-                    match self
-                        .connection
-                        .fulfill_request(StacksHttpMessage::Response(resp))
-                    {
-                        None => {
-                            test_debug!("{:?}: Fulfilled pending HTTP request", &self);
-                        }
-                        Some(_msg) => {
-                            // unsolicited; discard
-                            test_debug!("{:?}: Dropping unsolicited HTTP response", &self);
-                        }
-                    }
-                }
-            }
-        }
+        // hack around the borrow-checker
+        let mut peer_1_sortdb = peer_1.sortdb.take().unwrap();
+        let mut peer_1_stacks_node = peer_1.stacks_node.take().unwrap();
+        let mut peer_1_mempool = peer_1.mempool.take().unwrap();
 
-        Ok(ret)
-    }
+        Relayer::setup_unconfirmed_state(&mut peer_1_stacks_node.chainstate, &peer_1_sortdb)
+            .unwrap();
 
-    /// Remove all timed-out messages, and ding the remote peer as unhealthy
-    pub fn clear_timeouts(&mut self) -> () {
-        self.connection.drain_timeouts();
-    }
+        convo_1
+            .chat(
+                &view_1,
+                &PeerMap::new(),
+                &mut peer_1_sortdb,
+                &peer_1.network.peerdb,
+                &mut peer_1.network.atlasdb,
+                &mut peer_1_stacks_node.chainstate,
+                &mut peer_1_mempool,
+                &RPCHandlerArgs::default(),
+            )
+            .unwrap();
 
-    /// Load data into our HTTP connection
-    pub fn recv<R: Read>(&mut self, r: &mut R) -> Result<usize, net_error> {
-        let mut total_recv = 0;
-        loop {
-            let nrecv = match self.connection.recv_data(r) {
-                Ok(nr) => nr,
-                Err(e) => {
-                    debug!("{:?}: failed to recv: {:?}", self, &e);
-                    return Err(e);
-                }
-            };
+        peer_1.sortdb = Some(peer_1_sortdb);
+        peer_1.stacks_node = Some(peer_1_stacks_node);
+        peer_1.mempool = Some(peer_1_mempool);
 
-            total_recv += nrecv;
-            if nrecv > 0 {
-                self.last_request_timestamp = get_epoch_time_secs();
-            } else {
-                break;
-            }
-        }
-        monitoring::update_inbound_rpc_bandwidth(total_recv as i64);
-        Ok(total_recv)
-    }
+        test_debug!("convo2 sends to convo1");
 
-    /// Write data out of our HTTP connection.  Write as much as we can
-    pub fn send<W: Write>(
-        &mut self,
-        w: &mut W,
-        chainstate: &mut StacksChainState,
-    ) -> Result<usize, net_error> {
-        let mut total_sz = 0;
-        loop {
-            // prime the Write
-            self.try_flush(chainstate)?;
+        // hack around the borrow-checker
+        let mut peer_2_sortdb = peer_2.sortdb.take().unwrap();
+        let mut peer_2_stacks_node = peer_2.stacks_node.take().unwrap();
+        let mut peer_2_mempool = peer_2.mempool.take().unwrap();
 
-            let sz = match self.connection.send_data(w) {
-                Ok(sz) => sz,
-                Err(e) => {
-                    info!("{:?}: failed to send on HTTP conversation: {:?}", self, &e);
-                    return Err(e);
-                }
-            };
+        Relayer::setup_unconfirmed_state(&mut peer_2_stacks_node.chainstate, &peer_2_sortdb)
+            .unwrap();
 
-            total_sz += sz;
-            if sz > 0 {
-                self.last_response_timestamp = get_epoch_time_secs();
-            } else {
-                break;
-            }
-        }
-        monitoring::update_inbound_rpc_bandwidth(total_sz as i64);
-        Ok(total_sz)
-    }
+        convo_2
+            .chat(
+                &view_2,
+                &PeerMap::new(),
+                &mut peer_2_sortdb,
+                &peer_2.network.peerdb,
+                &mut peer_2.network.atlasdb,
+                &mut peer_2_stacks_node.chainstate,
+                &mut peer_2_mempool,
+                server_handler_args,
+            )
+            .unwrap();
 
-    /// Make a new getinfo request to this endpoint
-    pub fn new_getinfo(&self) -> HttpRequestType {
-        HttpRequestType::GetInfo(HttpRequestMetadata::from_host(self.peer_host.clone()))
-    }
+        peer_2.sortdb = Some(peer_2_sortdb);
+        peer_2.stacks_node = Some(peer_2_stacks_node);
+        peer_2.mempool = Some(peer_2_mempool);
 
-    /// Make a new getinfo request to this endpoint
-    pub fn new_getpoxinfo(&self, tip_opt: Option<StacksBlockId>) -> HttpRequestType {
-        HttpRequestType::GetPoxInfo(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            tip_opt,
-        )
-    }
+        convo_send_recv(
+            &mut convo_2,
+            peer_2.chainstate(),
+            &mut convo_1,
+            peer_1.chainstate(),
+        );
 
-    /// Make a new getneighbors request to this endpoint
-    pub fn new_getneighbors(&self) -> HttpRequestType {
-        HttpRequestType::GetNeighbors(HttpRequestMetadata::from_host(self.peer_host.clone()))
-    }
+        test_debug!("flush convo1");
 
-    /// Make a new getblock request to this endpoint
-    pub fn new_getblock(&self, index_block_hash: StacksBlockId) -> HttpRequestType {
-        HttpRequestType::GetBlock(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            index_block_hash,
-        )
-    }
+        // hack around the borrow-checker
+        convo_send_recv(
+            &mut convo_1,
+            peer_1.chainstate(),
+            &mut convo_2,
+            peer_2.chainstate(),
+        );
 
-    /// Make a new get-microblocks request to this endpoint
-    pub fn new_getmicroblocks_indexed(
-        &self,
-        index_microblock_hash: StacksBlockId,
-    ) -> HttpRequestType {
-        HttpRequestType::GetMicroblocksIndexed(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            index_microblock_hash,
-        )
-    }
+        let mut peer_1_sortdb = peer_1.sortdb.take().unwrap();
+        let mut peer_1_stacks_node = peer_1.stacks_node.take().unwrap();
+        let mut peer_1_mempool = peer_1.mempool.take().unwrap();
 
-    /// Make a new get-microblocks-confirmed request to this endpoint
-    pub fn new_getmicroblocks_confirmed(
-        &self,
-        index_anchor_block_hash: StacksBlockId,
-    ) -> HttpRequestType {
-        HttpRequestType::GetMicroblocksConfirmed(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            index_anchor_block_hash,
-        )
-    }
+        Relayer::setup_unconfirmed_state(&mut peer_1_stacks_node.chainstate, &peer_1_sortdb)
+            .unwrap();
 
-    /// Make a new get-microblocks request for unconfirmed microblocks
-    pub fn new_getmicroblocks_unconfirmed(
-        &self,
-        anchored_index_block_hash: StacksBlockId,
-        min_seq: u16,
-    ) -> HttpRequestType {
-        HttpRequestType::GetMicroblocksUnconfirmed(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            anchored_index_block_hash,
-            min_seq,
-        )
-    }
+        convo_1
+            .chat(
+                &view_1,
+                &PeerMap::new(),
+                &mut peer_1_sortdb,
+                &peer_1.network.peerdb,
+                &mut peer_1.network.atlasdb,
+                &mut peer_1_stacks_node.chainstate,
+                &mut peer_1_mempool,
+                &RPCHandlerArgs::default(),
+            )
+            .unwrap();
 
-    /// Make a new get-unconfirmed-tx request
-    pub fn new_gettransaction_unconfirmed(&self, txid: Txid) -> HttpRequestType {
-        HttpRequestType::GetTransactionUnconfirmed(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            txid,
-        )
-    }
+        peer_1.sortdb = Some(peer_1_sortdb);
+        peer_1.stacks_node = Some(peer_1_stacks_node);
+        peer_1.mempool = Some(peer_1_mempool);
 
-    /// Make a new post-transaction request
-    pub fn new_post_transaction(&self, tx: StacksTransaction) -> HttpRequestType {
-        HttpRequestType::PostTransaction(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            tx,
-            None,
-        )
-    }
+        convo_1.try_flush(peer_1.chainstate()).unwrap();
 
-    /// Make a new post-block request
-    pub fn new_post_block(&self, ch: ConsensusHash, block: StacksBlock) -> HttpRequestType {
-        HttpRequestType::PostBlock(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            ch,
-            block,
-        )
-    }
+        // should have gotten a reply
+        let resp_opt = convo_1.try_get_response();
+        assert!(resp_opt.is_some());
 
-    /// Make a new post-microblock request
-    pub fn new_post_microblock(
-        &self,
-        mblock: StacksMicroblock,
-        tip_opt: Option<StacksBlockId>,
-    ) -> HttpRequestType {
-        HttpRequestType::PostMicroblock(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            mblock,
-            tip_opt,
-        )
+        let resp = resp_opt.unwrap();
+        assert!(check_result(&req, &resp, &mut peer_1, &mut peer_2));
     }
 
-    /// Make a new request for an account
-    pub fn new_getaccount(
-        &self,
-        principal: PrincipalData,
-        tip_opt: Option<StacksBlockId>,
-        with_proof: bool,
-    ) -> HttpRequestType {
-        HttpRequestType::GetAccount(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            principal,
-            tip_opt,
-            with_proof,
-        )
-    }
+    #[test]
+    #[ignore]
+    fn test_rpc_getinfo() {
+        let peer_server_info = RefCell::new(None);
+        test_rpc(
+            "test_rpc_getinfo",
+            40000,
+            40001,
+            50000,
+            50001,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let peer_info = RPCPeerInfoData::from_db(
+                    &peer_server.config.burnchain,
+                    peer_server.sortdb.as_mut().unwrap(),
+                    &peer_server.stacks_node.as_ref().unwrap().chainstate,
+                    &peer_server.network.peerdb,
+                    &None,
+                    &Sha256Sum::zero(),
+                )
+                .unwrap();
 
-    /// Make a new request for a data map
-    pub fn new_getmapentry(
-        &self,
-        contract_addr: StacksAddress,
-        contract_name: ContractName,
-        map_name: ClarityName,
-        key: Value,
-        tip_opt: Option<StacksBlockId>,
-        with_proof: bool,
-    ) -> HttpRequestType {
-        HttpRequestType::GetMapEntry(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            contract_addr,
-            contract_name,
-            map_name,
-            key,
-            tip_opt,
-            with_proof,
-        )
-    }
+                *peer_server_info.borrow_mut() = Some(peer_info);
 
-    /// Make a new request to get a contract's source
-    pub fn new_getcontractsrc(
-        &self,
-        contract_addr: StacksAddress,
-        contract_name: ContractName,
-        tip_opt: Option<StacksBlockId>,
-        with_proof: bool,
-    ) -> HttpRequestType {
-        HttpRequestType::GetContractSrc(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            contract_addr,
-            contract_name,
-            tip_opt,
-            with_proof,
-        )
+                convo_client.new_getinfo()
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::PeerInfo(response_md, peer_data) => {
+                        assert_eq!(Some((*peer_data).clone()), *peer_server_info.borrow());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
     }
 
-    /// Make a new request to get a contract's ABI
-    pub fn new_getcontractabi(
-        &self,
-        contract_addr: StacksAddress,
-        contract_name: ContractName,
-        tip_opt: Option<StacksBlockId>,
-    ) -> HttpRequestType {
-        HttpRequestType::GetContractABI(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            contract_addr,
-            contract_name,
-            tip_opt,
-        )
+    #[test]
+    #[ignore]
+    fn test_rpc_getpoxinfo() {
+        let pox_server_info = RefCell::new(None);
+        test_rpc(
+            "test_rpc_getpoxinfo",
+            40000,
+            40001,
+            50000,
+            50001,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let mut sortdb = peer_server.sortdb.as_mut().unwrap();
+                let chainstate = &mut peer_server.stacks_node.as_mut().unwrap().chainstate;
+                let stacks_block_id = {
+                    let tip = chainstate.get_stacks_chain_tip(sortdb).unwrap().unwrap();
+                    StacksBlockHeader::make_index_block_hash(
+                        &tip.consensus_hash,
+                        &tip.anchored_block_hash,
+                    )
+                };
+                let pox_info = RPCPoxInfoData::from_db(
+                    &mut sortdb,
+                    chainstate,
+                    &stacks_block_id,
+                    &peer_client.config.burnchain,
+                )
+                .unwrap();
+                *pox_server_info.borrow_mut() = Some(pox_info);
+                convo_client.new_getpoxinfo(None)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::PoxInfo(response_md, pox_data) => {
+                        assert_eq!(Some((*pox_data).clone()), *pox_server_info.borrow());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
     }
 
-    /// Make a new request to run a read-only function
-    pub fn new_callreadonlyfunction(
-        &self,
-        contract_addr: StacksAddress,
-        contract_name: ContractName,
-        sender: PrincipalData,
-        function_name: ClarityName,
-        function_args: Vec<Value>,
-        tip_opt: Option<StacksBlockId>,
-    ) -> HttpRequestType {
-        HttpRequestType::CallReadOnlyFunction(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            contract_addr,
-            contract_name,
-            sender,
-            function_name,
-            function_args,
-            tip_opt,
-        )
+    #[test]
+    #[ignore]
+    fn test_rpc_getneighbors() {
+        test_rpc(
+            "test_rpc_getneighbors",
+            40010,
+            40011,
+            50010,
+            50011,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| { convo_client.new_getneighbors() },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::Neighbors(response_md, neighbor_info) => {
+                        assert_eq!(neighbor_info.sample.len(), 1);
+                        assert_eq!(neighbor_info.sample[0].port, peer_client.config.server_port); // we see ourselves as the neighbor
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
     }
 
-    /// Make a new request for attachment inventory page
-    pub fn new_getattachmentsinv(
-        &self,
-        index_block_hash: StacksBlockId,
-        pages_indexes: HashSet<u32>,
-    ) -> HttpRequestType {
-        HttpRequestType::GetAttachmentsInv(
-            HttpRequestMetadata::from_host(self.peer_host.clone()),
-            index_block_hash,
-            pages_indexes,
-        )
-    }
-}
+    #[test]
+    #[ignore]
+    fn test_rpc_unconfirmed_getblock() {
+        let server_block_cell = RefCell::new(None);
 
-#[cfg(test)]
-mod test {
-    use std::cell::RefCell;
-    use std::convert::TryInto;
-    use std::iter::FromIterator;
+        test_rpc(
+            "test_rpc_unconfirmed_getblock",
+            40020,
+            40021,
+            50020,
+            50021,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                // have "server" peer store a block to staging
+                let peer_server_block = make_codec_test_block(25);
+                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
+                let index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &peer_server_consensus_hash,
+                    &peer_server_block.block_hash(),
+                );
+
+                test_debug!("Store peer server index block {:?}", &index_block_hash);
+                store_staging_block(
+                    peer_server.chainstate(),
+                    &peer_server_consensus_hash,
+                    &peer_server_block,
+                    &ConsensusHash([0x03; 20]),
+                    456,
+                    123,
+                );
 
-    use address::*;
-    use burnchains::Burnchain;
-    use burnchains::BurnchainView;
-    use burnchains::*;
-    use chainstate::burn::ConsensusHash;
-    use chainstate::stacks::db::blocks::test::*;
-    use chainstate::stacks::db::BlockStreamData;
-    use chainstate::stacks::db::StacksChainState;
-    use chainstate::stacks::miner::*;
-    use chainstate::stacks::test::*;
-    use chainstate::stacks::Error as chain_error;
-    use chainstate::stacks::*;
-    use net::codec::*;
-    use net::http::*;
-    use net::test::*;
-    use net::*;
-    use util::get_epoch_time_secs;
-    use util::hash::hex_bytes;
-    use util::pipe::*;
-    use vm::types::*;
+                *server_block_cell.borrow_mut() = Some(peer_server_block);
 
-    use crate::types::chainstate::BlockHeaderHash;
-    use crate::types::chainstate::BurnchainHeaderHash;
-    use chainstate::stacks::C32_ADDRESS_VERSION_TESTNET_SINGLESIG;
+                // now ask for it
+                convo_client.new_getblock(index_block_hash)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::Block(response_md, block_info) => {
+                        assert_eq!(
+                            block_info.block_hash(),
+                            (*server_block_cell.borrow()).as_ref().unwrap().block_hash()
+                        );
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-    use super::*;
+    #[test]
+    #[ignore]
+    fn test_rpc_confirmed_getblock() {
+        let server_block_cell = RefCell::new(None);
 
-    const TEST_CONTRACT: &'static str = "
-        (define-data-var bar int 0)
-        (define-map unit-map { account: principal } { units: int })
-        (define-public (get-bar) (ok (var-get bar)))
-        (define-public (set-bar (x int) (y int))
-          (begin (var-set bar (/ x y)) (ok (var-get bar))))
-        (define-public (add-unit)
-          (begin
-            (map-set unit-map { account: tx-sender } { units: 1 } )
-            (ok 1)))
-        (begin
-          (map-set unit-map { account: 'ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R } { units: 123 }))";
+        test_rpc(
+            "test_rpc_confirmed_getblock",
+            40030,
+            40031,
+            50030,
+            50031,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                // have "server" peer store a block to staging
+                let peer_server_block = make_codec_test_block(25);
+                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
+                let index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &peer_server_consensus_hash,
+                    &peer_server_block.block_hash(),
+                );
 
-    fn convo_send_recv(
-        sender: &mut ConversationHttp,
-        sender_chainstate: &mut StacksChainState,
-        receiver: &mut ConversationHttp,
-        receiver_chainstate: &mut StacksChainState,
-    ) -> () {
-        let (mut pipe_read, mut pipe_write) = Pipe::new();
-        pipe_read.set_nonblocking(true);
+                test_debug!("Store peer server index block {:?}", &index_block_hash);
+                store_staging_block(
+                    peer_server.chainstate(),
+                    &peer_server_consensus_hash,
+                    &peer_server_block,
+                    &ConsensusHash([0x03; 20]),
+                    456,
+                    123,
+                );
+                set_block_processed(
+                    peer_server.chainstate(),
+                    &peer_server_consensus_hash,
+                    &peer_server_block.block_hash(),
+                    true,
+                );
 
-        loop {
-            let res = true;
+                *server_block_cell.borrow_mut() = Some(peer_server_block);
 
-            sender.try_flush(sender_chainstate).unwrap();
-            receiver.try_flush(receiver_chainstate).unwrap();
+                // now ask for it
+                convo_client.new_getblock(index_block_hash)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::Block(response_md, block_info) => {
+                        assert_eq!(
+                            block_info.block_hash(),
+                            (*server_block_cell.borrow()).as_ref().unwrap().block_hash()
+                        );
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-            pipe_write.try_flush().unwrap();
+    #[test]
+    #[ignore]
+    fn test_rpc_get_indexed_microblocks() {
+        let server_microblocks_cell = RefCell::new(vec![]);
 
-            let all_relays_flushed =
-                receiver.num_pending_outbound() == 0 && sender.num_pending_outbound() == 0;
+        test_rpc(
+            "test_rpc_indexed_microblocks",
+            40040,
+            40041,
+            50040,
+            50041,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let privk = StacksPrivateKey::from_hex(
+                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+                )
+                .unwrap();
 
-            let nw = sender.send(&mut pipe_write, sender_chainstate).unwrap();
-            let nr = receiver.recv(&mut pipe_read).unwrap();
+                let parent_block = make_codec_test_block(25);
+                let parent_consensus_hash = ConsensusHash([0x02; 20]);
+                let parent_index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &parent_consensus_hash,
+                    &parent_block.block_hash(),
+                );
 
-            test_debug!(
-                "res = {}, all_relays_flushed = {} ({},{}), nr = {}, nw = {}",
-                res,
-                all_relays_flushed,
-                receiver.num_pending_outbound(),
-                sender.num_pending_outbound(),
-                nr,
-                nw
-            );
-            if res && all_relays_flushed && nr == 0 && nw == 0 {
-                test_debug!("Breaking send_recv");
-                break;
-            }
-        }
-    }
+                let mut mblocks = make_sample_microblock_stream(&privk, &parent_block.block_hash());
+                mblocks.truncate(15);
 
-    fn test_rpc<F, C>(
-        test_name: &str,
-        peer_1_p2p: u16,
-        peer_1_http: u16,
-        peer_2_p2p: u16,
-        peer_2_http: u16,
-        make_request: F,
-        check_result: C,
-    ) -> ()
-    where
-        F: FnOnce(
-            &mut TestPeer,
-            &mut ConversationHttp,
-            &mut TestPeer,
-            &mut ConversationHttp,
-        ) -> HttpRequestType,
-        C: FnOnce(&HttpRequestType, &HttpResponseType, &mut TestPeer, &mut TestPeer) -> bool,
-    {
-        let mut peer_1_config = TestPeerConfig::new(test_name, peer_1_p2p, peer_1_http);
-        let mut peer_2_config = TestPeerConfig::new(test_name, peer_2_p2p, peer_2_http);
+                let mut child_block = make_codec_test_block(25);
+                let child_consensus_hash = ConsensusHash([0x03; 20]);
 
-        // ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R
-        let privk1 = StacksPrivateKey::from_hex(
-            "9f1f85a512a96a244e4c0d762788500687feb97481639572e3bffbd6860e6ab001",
-        )
-        .unwrap();
+                child_block.header.parent_block = parent_block.block_hash();
+                child_block.header.parent_microblock =
+                    mblocks.last().as_ref().unwrap().block_hash();
+                child_block.header.parent_microblock_sequence =
+                    mblocks.last().as_ref().unwrap().header.sequence;
 
-        // STVN97YYA10MY5F6KQJHKNYJNM24C4A1AT39WRW
-        let privk2 = StacksPrivateKey::from_hex(
-            "94c319327cc5cd04da7147d32d836eb2e4c44f4db39aa5ede7314a761183d0c701",
-        )
-        .unwrap();
-        let microblock_privkey = StacksPrivateKey::new();
-        let microblock_pubkeyhash =
-            Hash160::from_node_public_key(&StacksPublicKey::from_private(&microblock_privkey));
+                store_staging_block(
+                    peer_server.chainstate(),
+                    &parent_consensus_hash,
+                    &parent_block,
+                    &ConsensusHash([0x01; 20]),
+                    456,
+                    123,
+                );
+                set_block_processed(
+                    peer_server.chainstate(),
+                    &parent_consensus_hash,
+                    &parent_block.block_hash(),
+                    true,
+                );
 
-        let addr1 = StacksAddress::from_public_keys(
-            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
-            &AddressHashMode::SerializeP2PKH,
-            1,
-            &vec![StacksPublicKey::from_private(&privk1)],
-        )
-        .unwrap();
-        let addr2 = StacksAddress::from_public_keys(
-            C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
-            &AddressHashMode::SerializeP2PKH,
-            1,
-            &vec![StacksPublicKey::from_private(&privk2)],
-        )
-        .unwrap();
+                store_staging_block(
+                    peer_server.chainstate(),
+                    &child_consensus_hash,
+                    &child_block,
+                    &parent_consensus_hash,
+                    456,
+                    123,
+                );
+                set_block_processed(
+                    peer_server.chainstate(),
+                    &child_consensus_hash,
+                    &child_block.block_hash(),
+                    true,
+                );
 
-        peer_1_config.initial_balances = vec![
-            (addr1.to_account_principal(), 1000000000),
-            (addr2.to_account_principal(), 1000000000),
-        ];
+                let index_microblock_hash = StacksBlockHeader::make_index_block_hash(
+                    &parent_consensus_hash,
+                    &mblocks.last().as_ref().unwrap().block_hash(),
+                );
 
-        peer_2_config.initial_balances = vec![
-            (addr1.to_account_principal(), 1000000000),
-            (addr2.to_account_principal(), 1000000000),
-        ];
+                for mblock in mblocks.iter() {
+                    store_staging_microblock(
+                        peer_server.chainstate(),
+                        &parent_consensus_hash,
+                        &parent_block.block_hash(),
+                        &mblock,
+                    );
+                }
 
-        peer_1_config.add_neighbor(&peer_2_config.to_neighbor());
-        peer_2_config.add_neighbor(&peer_1_config.to_neighbor());
+                set_microblocks_processed(
+                    peer_server.chainstate(),
+                    &child_consensus_hash,
+                    &child_block.block_hash(),
+                    &mblocks.last().as_ref().unwrap().block_hash(),
+                );
 
-        let mut peer_1 = TestPeer::new(peer_1_config);
-        let mut peer_2 = TestPeer::new(peer_2_config);
+                *server_microblocks_cell.borrow_mut() = mblocks;
 
-        // mine one block with a contract in it
-        // first the coinbase
-        // make a coinbase for this miner
-        let mut tx_coinbase = StacksTransaction::new(
-            TransactionVersion::Testnet,
-            TransactionAuth::from_p2pkh(&privk1).unwrap(),
-            TransactionPayload::Coinbase(CoinbasePayload([0x00; 32])),
+                convo_client.new_getmicroblocks_indexed(index_microblock_hash)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match (*http_response).clone() {
+                    HttpResponseType::Microblocks(_, mut microblocks) => {
+                        microblocks.reverse();
+                        assert_eq!(microblocks.len(), (*server_microblocks_cell.borrow()).len());
+                        assert_eq!(microblocks, *server_microblocks_cell.borrow());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", http_response);
+                        false
+                    }
+                }
+            },
         );
-        tx_coinbase.chain_id = 0x80000000;
-        tx_coinbase.anchor_mode = TransactionAnchorMode::OnChainOnly;
-        tx_coinbase.auth.set_origin_nonce(0);
+    }
 
-        let mut tx_signer = StacksTransactionSigner::new(&tx_coinbase);
-        tx_signer.sign_origin(&privk1).unwrap();
-        let tx_coinbase_signed = tx_signer.get_tx().unwrap();
+    #[test]
+    #[ignore]
+    fn test_rpc_get_confirmed_microblocks() {
+        let server_microblocks_cell = RefCell::new(vec![]);
 
-        // next the contract
-        let contract = TEST_CONTRACT.clone();
-        let mut tx_contract = StacksTransaction::new(
-            TransactionVersion::Testnet,
-            TransactionAuth::from_p2pkh(&privk1).unwrap(),
-            TransactionPayload::new_smart_contract(&format!("hello-world"), &contract.to_string())
-                .unwrap(),
-        );
+        test_rpc(
+            "test_rpc_confirmed_microblocks",
+            40042,
+            40043,
+            50042,
+            50043,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let privk = StacksPrivateKey::from_hex(
+                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+                )
+                .unwrap();
 
-        tx_contract.chain_id = 0x80000000;
-        tx_contract.auth.set_origin_nonce(1);
-        tx_contract.set_tx_fee(0);
+                let parent_block = make_codec_test_block(25);
+                let parent_consensus_hash = ConsensusHash([0x02; 20]);
 
-        let mut tx_signer = StacksTransactionSigner::new(&tx_contract);
-        tx_signer.sign_origin(&privk1).unwrap();
-        let tx_contract_signed = tx_signer.get_tx().unwrap();
+                let mut mblocks = make_sample_microblock_stream(&privk, &parent_block.block_hash());
+                mblocks.truncate(15);
 
-        // update account and state in a microblock that will be unconfirmed
-        let mut tx_cc = StacksTransaction::new(
-            TransactionVersion::Testnet,
-            TransactionAuth::from_p2pkh(&privk1).unwrap(),
-            TransactionPayload::new_contract_call(addr1.clone(), "hello-world", "add-unit", vec![])
-                .unwrap(),
-        );
+                let mut child_block = make_codec_test_block(25);
+                let child_consensus_hash = ConsensusHash([0x03; 20]);
 
-        tx_cc.chain_id = 0x80000000;
-        tx_cc.auth.set_origin_nonce(2);
-        tx_cc.set_tx_fee(123);
+                child_block.header.parent_block = parent_block.block_hash();
+                child_block.header.parent_microblock =
+                    mblocks.last().as_ref().unwrap().block_hash();
+                child_block.header.parent_microblock_sequence =
+                    mblocks.last().as_ref().unwrap().header.sequence;
 
-        let mut tx_signer = StacksTransactionSigner::new(&tx_cc);
-        tx_signer.sign_origin(&privk1).unwrap();
-        let tx_cc_signed = tx_signer.get_tx().unwrap();
-        let tx_cc_len = {
-            let mut bytes = vec![];
-            tx_cc_signed.consensus_serialize(&mut bytes).unwrap();
-            bytes.len() as u64
-        };
+                let child_index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &child_consensus_hash,
+                    &child_block.block_hash(),
+                );
 
-        // make an unconfirmed contract
-        let unconfirmed_contract = "(define-read-only (ro-test) (ok 1))";
-        let mut tx_unconfirmed_contract = StacksTransaction::new(
-            TransactionVersion::Testnet,
-            TransactionAuth::from_p2pkh(&privk1).unwrap(),
-            TransactionPayload::new_smart_contract(
-                &format!("hello-world-unconfirmed"),
-                &unconfirmed_contract.to_string(),
-            )
-            .unwrap(),
-        );
+                store_staging_block(
+                    peer_server.chainstate(),
+                    &parent_consensus_hash,
+                    &parent_block,
+                    &ConsensusHash([0x01; 20]),
+                    456,
+                    123,
+                );
+                set_block_processed(
+                    peer_server.chainstate(),
+                    &parent_consensus_hash,
+                    &parent_block.block_hash(),
+                    true,
+                );
 
-        tx_unconfirmed_contract.chain_id = 0x80000000;
-        tx_unconfirmed_contract.auth.set_origin_nonce(3);
-        tx_unconfirmed_contract.set_tx_fee(0);
+                store_staging_block(
+                    peer_server.chainstate(),
+                    &child_consensus_hash,
+                    &child_block,
+                    &parent_consensus_hash,
+                    456,
+                    123,
+                );
+                set_block_processed(
+                    peer_server.chainstate(),
+                    &child_consensus_hash,
+                    &child_block.block_hash(),
+                    true,
+                );
 
-        let mut tx_signer = StacksTransactionSigner::new(&tx_unconfirmed_contract);
-        tx_signer.sign_origin(&privk1).unwrap();
-        let tx_unconfirmed_contract_signed = tx_signer.get_tx().unwrap();
-        let tx_unconfirmed_contract_len = {
-            let mut bytes = vec![];
-            tx_unconfirmed_contract_signed
-                .consensus_serialize(&mut bytes)
-                .unwrap();
-            bytes.len() as u64
-        };
+                for mblock in mblocks.iter() {
+                    store_staging_microblock(
+                        peer_server.chainstate(),
+                        &parent_consensus_hash,
+                        &parent_block.block_hash(),
+                        &mblock,
+                    );
+                }
 
-        let tip =
-            SortitionDB::get_canonical_burn_chain_tip(&peer_1.sortdb.as_ref().unwrap().conn())
-                .unwrap();
-        let mut anchor_cost = ExecutionCost::zero();
-        let mut anchor_size = 0;
+                set_microblocks_processed(
+                    peer_server.chainstate(),
+                    &child_consensus_hash,
+                    &child_block.block_hash(),
+                    &mblocks.last().as_ref().unwrap().block_hash(),
+                );
 
-        // make a block and a microblock.
-        // Put the coinbase and smart-contract in the anchored block.
-        // Put the contract-call in the microblock
-        let (burn_ops, stacks_block, microblocks) = peer_1.make_tenure(
-            |ref mut miner, ref mut sortdb, ref mut chainstate, vrf_proof, ref parent_opt, _| {
-                let parent_tip = match parent_opt {
-                    None => StacksChainState::get_genesis_header_info(chainstate.db()).unwrap(),
-                    Some(block) => {
-                        let ic = sortdb.index_conn();
-                        let snapshot = SortitionDB::get_block_snapshot_for_winning_stacks_block(
-                            &ic,
-                            &tip.sortition_id,
-                            &block.block_hash(),
-                        )
-                        .unwrap()
-                        .unwrap(); // succeeds because we don't fork
-                        StacksChainState::get_anchored_block_header_info(
-                            chainstate.db(),
-                            &snapshot.consensus_hash,
-                            &snapshot.winning_stacks_block_hash,
-                        )
-                        .unwrap()
-                        .unwrap()
+                *server_microblocks_cell.borrow_mut() = mblocks;
+
+                convo_client.new_getmicroblocks_confirmed(child_index_block_hash)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match (*http_response).clone() {
+                    HttpResponseType::Microblocks(_, mut microblocks) => {
+                        microblocks.reverse();
+                        assert_eq!(microblocks.len(), (*server_microblocks_cell.borrow()).len());
+                        assert_eq!(microblocks, *server_microblocks_cell.borrow());
+                        true
                     }
-                };
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-                let block_builder = StacksBlockBuilder::make_regtest_block_builder(
-                    &parent_tip,
-                    vrf_proof,
-                    tip.total_burn,
-                    microblock_pubkeyhash,
+    #[test]
+    #[ignore]
+    fn test_rpc_unconfirmed_microblocks() {
+        let server_microblocks_cell = RefCell::new(vec![]);
+
+        test_rpc(
+            "test_rpc_unconfirmed_microblocks",
+            40050,
+            40051,
+            50050,
+            50051,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let privk = StacksPrivateKey::from_hex(
+                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
                 )
                 .unwrap();
-                let (anchored_block, anchored_block_size, anchored_block_cost) =
-                    StacksBlockBuilder::make_anchored_block_from_txs(
-                        block_builder,
-                        chainstate,
-                        &sortdb.index_conn(),
-                        vec![tx_coinbase_signed.clone(), tx_contract_signed.clone()],
-                    )
-                    .unwrap();
 
-                anchor_size = anchored_block_size;
-                anchor_cost = anchored_block_cost;
+                let consensus_hash = ConsensusHash([0x02; 20]);
+                let anchored_block_hash = BlockHeaderHash([0x03; 32]);
+                let index_block_hash =
+                    StacksBlockHeader::make_index_block_hash(&consensus_hash, &anchored_block_hash);
 
-                (anchored_block, vec![])
+                let mut mblocks = make_sample_microblock_stream(&privk, &anchored_block_hash);
+                mblocks.truncate(15);
+
+                for mblock in mblocks.iter() {
+                    store_staging_microblock(
+                        peer_server.chainstate(),
+                        &consensus_hash,
+                        &anchored_block_hash,
+                        &mblock,
+                    );
+                }
+
+                *server_microblocks_cell.borrow_mut() = mblocks;
+
+                // start at seq 5
+                convo_client.new_getmicroblocks_unconfirmed(index_block_hash, 5)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::Microblocks(response_md, microblocks) => {
+                        assert_eq!(microblocks.len(), 10);
+                        assert_eq!(
+                            *microblocks,
+                            (*server_microblocks_cell.borrow())[5..].to_vec()
+                        );
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
             },
         );
+    }
 
-        let (_, _, consensus_hash) = peer_1.next_burnchain_block(burn_ops.clone());
-        peer_2.next_burnchain_block(burn_ops.clone());
-
-        peer_1.process_stacks_epoch_at_tip(&stacks_block, &vec![]);
-        peer_2.process_stacks_epoch_at_tip(&stacks_block, &vec![]);
+    #[test]
+    #[ignore]
+    fn test_rpc_unconfirmed_transaction() {
+        let last_txid = RefCell::new(Txid([0u8; 32]));
+        let last_mblock = RefCell::new(BlockHeaderHash([0u8; 32]));
 
-        // build 1-block microblock stream with the contract-call and the unconfirmed contract
-        let microblock = {
-            let sortdb = peer_1.sortdb.take().unwrap();
-            Relayer::setup_unconfirmed_state(peer_1.chainstate(), &sortdb).unwrap();
-            let mblock = {
-                let sort_iconn = sortdb.index_conn();
-                let mut microblock_builder = StacksMicroblockBuilder::new(
-                    stacks_block.block_hash(),
-                    consensus_hash.clone(),
-                    peer_1.chainstate(),
-                    &sort_iconn,
+        test_rpc(
+            "test_rpc_unconfirmed_transaction",
+            40052,
+            40053,
+            50052,
+            50053,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let privk = StacksPrivateKey::from_hex(
+                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
                 )
                 .unwrap();
-                let microblock = microblock_builder
-                    .mine_next_microblock_from_txs(
-                        vec![
-                            (tx_cc_signed, tx_cc_len),
-                            (tx_unconfirmed_contract_signed, tx_unconfirmed_contract_len),
-                        ],
-                        &microblock_privkey,
-                    )
-                    .unwrap();
-                microblock
-            };
-            peer_1.sortdb = Some(sortdb);
-            mblock
-        };
 
-        // store microblock stream
-        peer_1
-            .chainstate()
-            .preprocess_streamed_microblock(
-                &consensus_hash,
-                &stacks_block.block_hash(),
-                &microblock,
-            )
-            .unwrap();
-        peer_2
-            .chainstate()
-            .preprocess_streamed_microblock(
-                &consensus_hash,
-                &stacks_block.block_hash(),
-                &microblock,
-            )
-            .unwrap();
+                let sortdb = peer_server.sortdb.take().unwrap();
+                Relayer::setup_unconfirmed_state(peer_server.chainstate(), &sortdb).unwrap();
+                peer_server.sortdb = Some(sortdb);
 
-        // process microblock stream to generate unconfirmed state
-        let canonical_tip =
-            StacksBlockHeader::make_index_block_hash(&consensus_hash, &stacks_block.block_hash());
-        let sortdb1 = peer_1.sortdb.take().unwrap();
-        let sortdb2 = peer_2.sortdb.take().unwrap();
-        peer_1
-            .chainstate()
-            .reload_unconfirmed_state(&sortdb1.index_conn(), canonical_tip.clone())
-            .unwrap();
-        peer_2
-            .chainstate()
-            .reload_unconfirmed_state(&sortdb2.index_conn(), canonical_tip.clone())
-            .unwrap();
-        peer_1.sortdb = Some(sortdb1);
-        peer_2.sortdb = Some(sortdb2);
+                assert!(peer_server.chainstate().unconfirmed_state.is_some());
+                let (txid, mblock_hash) = match peer_server.chainstate().unconfirmed_state {
+                    Some(ref unconfirmed) => {
+                        assert!(unconfirmed.mined_txs.len() > 0);
+                        let mut txid = Txid([0u8; 32]);
+                        let mut mblock_hash = BlockHeaderHash([0u8; 32]);
+                        for (next_txid, (_, mbh, ..)) in unconfirmed.mined_txs.iter() {
+                            txid = next_txid.clone();
+                            mblock_hash = mbh.clone();
+                            break;
+                        }
+                        (txid, mblock_hash)
+                    }
+                    None => {
+                        panic!("No unconfirmed state");
+                    }
+                };
 
-        let view_1 = peer_1.get_burnchain_view().unwrap();
-        let view_2 = peer_2.get_burnchain_view().unwrap();
+                *last_txid.borrow_mut() = txid.clone();
+                *last_mblock.borrow_mut() = mblock_hash.clone();
 
-        let mut convo_1 = ConversationHttp::new(
-            peer_1.config.network_id,
-            &peer_1.config.burnchain,
-            format!("127.0.0.1:{}", peer_1_http)
-                .parse::<SocketAddr>()
-                .unwrap(),
-            Some(UrlString::try_from(format!("http://peer1.com")).unwrap()),
-            peer_1.to_peer_host(),
-            &peer_1.config.connection_opts,
-            0,
+                convo_client.new_gettransaction_unconfirmed(txid)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::UnconfirmedTransaction(response_md, unconfirmed_resp) => {
+                        assert_eq!(
+                            unconfirmed_resp.status,
+                            UnconfirmedTransactionStatus::Microblock {
+                                block_hash: (*last_mblock.borrow()).clone(),
+                                seq: 0
+                            }
+                        );
+                        let tx = StacksTransaction::consensus_deserialize(
+                            &mut &hex_bytes(&unconfirmed_resp.tx).unwrap()[..],
+                        )
+                        .unwrap();
+                        assert_eq!(tx.txid(), *last_txid.borrow());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
         );
+    }
 
-        let mut convo_2 = ConversationHttp::new(
-            peer_2.config.network_id,
-            &peer_2.config.burnchain,
-            format!("127.0.0.1:{}", peer_2_http)
-                .parse::<SocketAddr>()
-                .unwrap(),
-            Some(UrlString::try_from(format!("http://peer2.com")).unwrap()),
-            peer_2.to_peer_host(),
-            &peer_2.config.connection_opts,
-            1,
+    #[test]
+    #[ignore]
+    fn test_rpc_missing_getblock() {
+        test_rpc(
+            "test_rpc_missing_getblock",
+            40060,
+            40061,
+            50060,
+            50061,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let peer_server_block_hash = BlockHeaderHash([0x04; 32]);
+                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
+                let index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &peer_server_consensus_hash,
+                    &peer_server_block_hash,
+                );
+
+                // now ask for it
+                convo_client.new_getblock(index_block_hash)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::NotFound(response_md, msg) => true,
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
         );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_missing_index_getmicroblocks() {
+        test_rpc(
+            "test_rpc_missing_index_getmicroblocks",
+            40070,
+            40071,
+            50070,
+            50071,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let peer_server_block_hash = BlockHeaderHash([0x04; 32]);
+                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
+                let index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &peer_server_consensus_hash,
+                    &peer_server_block_hash,
+                );
 
-        let req = make_request(&mut peer_1, &mut convo_1, &mut peer_2, &mut convo_2);
+                // now ask for it
+                convo_client.new_getmicroblocks_indexed(index_block_hash)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::NotFound(response_md, msg) => true,
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        convo_1.send_request(req.clone()).unwrap();
+    #[test]
+    #[ignore]
+    fn test_rpc_missing_confirmed_getmicroblocks() {
+        test_rpc(
+            "test_rpc_missing_confirmed_getmicroblocks",
+            40070,
+            40071,
+            50070,
+            50071,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let peer_server_block_hash = BlockHeaderHash([0x04; 32]);
+                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
+                let index_block_hash = StacksBlockHeader::make_index_block_hash(
+                    &peer_server_consensus_hash,
+                    &peer_server_block_hash,
+                );
 
-        test_debug!("convo1 sends to convo2");
-        convo_send_recv(
-            &mut convo_1,
-            peer_1.chainstate(),
-            &mut convo_2,
-            peer_2.chainstate(),
+                // now ask for it
+                convo_client.new_getmicroblocks_confirmed(index_block_hash)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::NotFound(response_md, msg) => true,
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
         );
+    }
 
-        // hack around the borrow-checker
-        let mut peer_1_sortdb = peer_1.sortdb.take().unwrap();
-        let mut peer_1_stacks_node = peer_1.stacks_node.take().unwrap();
-        let mut peer_1_mempool = peer_1.mempool.take().unwrap();
+    #[test]
+    #[ignore]
+    fn test_rpc_missing_unconfirmed_microblocks() {
+        let server_microblocks_cell = RefCell::new(vec![]);
 
-        Relayer::setup_unconfirmed_state(&mut peer_1_stacks_node.chainstate, &peer_1_sortdb)
-            .unwrap();
+        test_rpc(
+            "test_rpc_missing_unconfirmed_microblocks",
+            40080,
+            40081,
+            50080,
+            50081,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let privk = StacksPrivateKey::from_hex(
+                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+                )
+                .unwrap();
 
-        convo_1
-            .chat(
-                &view_1,
-                &PeerMap::new(),
-                &mut peer_1_sortdb,
-                &peer_1.network.peerdb,
-                &mut peer_1.network.atlasdb,
-                &mut peer_1_stacks_node.chainstate,
-                &mut peer_1_mempool,
-                &RPCHandlerArgs::default(),
-            )
-            .unwrap();
+                let consensus_hash = ConsensusHash([0x02; 20]);
+                let anchored_block_hash = BlockHeaderHash([0x03; 32]);
+                let index_block_hash =
+                    StacksBlockHeader::make_index_block_hash(&consensus_hash, &anchored_block_hash);
 
-        peer_1.sortdb = Some(peer_1_sortdb);
-        peer_1.stacks_node = Some(peer_1_stacks_node);
-        peer_1.mempool = Some(peer_1_mempool);
+                let mut mblocks = make_sample_microblock_stream(&privk, &anchored_block_hash);
+                mblocks.truncate(15);
 
-        test_debug!("convo2 sends to convo1");
+                for mblock in mblocks.iter() {
+                    store_staging_microblock(
+                        peer_server.chainstate(),
+                        &consensus_hash,
+                        &anchored_block_hash,
+                        &mblock,
+                    );
+                }
 
-        // hack around the borrow-checker
-        let mut peer_2_sortdb = peer_2.sortdb.take().unwrap();
-        let mut peer_2_stacks_node = peer_2.stacks_node.take().unwrap();
-        let mut peer_2_mempool = peer_2.mempool.take().unwrap();
+                *server_microblocks_cell.borrow_mut() = mblocks;
 
-        Relayer::setup_unconfirmed_state(&mut peer_2_stacks_node.chainstate, &peer_2_sortdb)
-            .unwrap();
+                // start at seq 16 (which doesn't exist)
+                convo_client.new_getmicroblocks_unconfirmed(index_block_hash, 16)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::NotFound(response_md, msg) => true,
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        convo_2
-            .chat(
-                &view_2,
-                &PeerMap::new(),
-                &mut peer_2_sortdb,
-                &peer_2.network.peerdb,
-                &mut peer_2.network.atlasdb,
-                &mut peer_2_stacks_node.chainstate,
-                &mut peer_2_mempool,
-                &RPCHandlerArgs::default(),
-            )
-            .unwrap();
+    #[test]
+    #[ignore]
+    fn test_rpc_get_contract_src() {
+        test_rpc(
+            "test_rpc_get_contract_src",
+            40090,
+            40091,
+            50090,
+            50091,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_getcontractsrc(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    None,
+                    false,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::GetContractSrc(response_md, data) => {
+                        assert_eq!(data.source, TEST_CONTRACT);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        peer_2.sortdb = Some(peer_2_sortdb);
-        peer_2.stacks_node = Some(peer_2_stacks_node);
-        peer_2.mempool = Some(peer_2_mempool);
+    #[test]
+    #[ignore]
+    fn test_rpc_get_contract_src_too_large() {
+        test_rpc(
+            "test_rpc_get_contract_src_too_large",
+            40310,
+            40311,
+            50310,
+            50311,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_server.connection.protocol.max_response_size = 16;
+                convo_client.new_getcontractsrc(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    None,
+                    false,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                match http_response {
+                    HttpResponseType::Error(_, 413, msg) => {
+                        assert_eq!(msg, "Response is larger than this node's limit of 16 bytes");
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        convo_send_recv(
-            &mut convo_2,
-            peer_2.chainstate(),
-            &mut convo_1,
-            peer_1.chainstate(),
+    #[test]
+    #[ignore]
+    fn test_rpc_get_contract_src_unconfirmed() {
+        test_rpc(
+            "test_rpc_get_contract_src_unconfirmed",
+            40100,
+            40101,
+            50100,
+            50101,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let unconfirmed_tip = peer_client
+                    .chainstate()
+                    .unconfirmed_state
+                    .as_ref()
+                    .unwrap()
+                    .unconfirmed_chain_tip
+                    .clone();
+                convo_client.new_getcontractsrc(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    Some(unconfirmed_tip),
+                    false,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::GetContractSrc(response_md, data) => {
+                        assert_eq!(data.source, TEST_CONTRACT);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
         );
+    }
 
-        test_debug!("flush convo1");
+    #[test]
+    #[ignore]
+    fn test_rpc_get_account() {
+        test_rpc(
+            "test_rpc_get_account",
+            40110,
+            40111,
+            50110,
+            50111,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_getaccount(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    TipRequest::UseLatestAnchoredTip,
+                    false,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::GetAccount(response_md, data) => {
+                        assert_eq!(data.nonce, 2);
+                        let balance = u128::from_str_radix(&data.balance[2..], 16).unwrap();
+                        assert_eq!(balance, 1000000000);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        // hack around the borrow-checker
-        convo_send_recv(
-            &mut convo_1,
-            peer_1.chainstate(),
-            &mut convo_2,
-            peer_2.chainstate(),
+    #[test]
+    #[ignore]
+    fn test_rpc_get_sponsorship() {
+        test_rpc(
+            "test_rpc_get_sponsorship",
+            40270,
+            40271,
+            50270,
+            50271,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_getsponsorship(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    None,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                match http_response {
+                    HttpResponseType::GetSponsorship(_, data) => {
+                        assert_eq!(data.principal, "ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R");
+                        assert_eq!(data.pending_sponsored_txs, 0);
+                        assert_eq!(data.reserved_fees, 0);
+                        assert_eq!(data.next_sponsor_nonce, data.nonce);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
         );
+    }
 
-        let mut peer_1_sortdb = peer_1.sortdb.take().unwrap();
-        let mut peer_1_stacks_node = peer_1.stacks_node.take().unwrap();
-        let mut peer_1_mempool = peer_1.mempool.take().unwrap();
+    #[test]
+    #[ignore]
+    fn test_rpc_get_account_balance() {
+        test_rpc(
+            "test_rpc_get_account_balance",
+            40280,
+            40281,
+            50280,
+            50281,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_getaccountbalance(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    None,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                match http_response {
+                    HttpResponseType::GetAccountBalance(_, data) => {
+                        assert_eq!(data.principal, "ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R");
+                        assert_eq!(data.locked, format!("0x{}", to_hex(&0u128.to_be_bytes())));
+                        // the test peers are not given the genesis lockup schedule
+                        assert_eq!(data.vesting, None);
+                        assert_eq!(data.vesting_ustx, 0);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        Relayer::setup_unconfirmed_state(&mut peer_1_stacks_node.chainstate, &peer_1_sortdb)
-            .unwrap();
+    #[test]
+    #[ignore]
+    fn test_rpc_get_stacking_status() {
+        test_rpc(
+            "test_rpc_get_stacking_status",
+            40210,
+            40211,
+            50210,
+            50211,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_getstackingstatus(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    None,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::GetStackingStatus(response_md, data) => {
+                        assert_eq!(data.principal, "ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R");
+                        assert!(!data.is_stacking);
+                        assert_eq!(data.locked_ustx, 0);
+                        assert_eq!(data.unlock_height, 0);
+                        assert!(data.pox_address.is_none());
+                        assert!(data.delegation.is_none());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        convo_1
-            .chat(
-                &view_1,
-                &PeerMap::new(),
-                &mut peer_1_sortdb,
-                &peer_1.network.peerdb,
-                &mut peer_1.network.atlasdb,
-                &mut peer_1_stacks_node.chainstate,
-                &mut peer_1_mempool,
-                &RPCHandlerArgs::default(),
-            )
-            .unwrap();
+    #[test]
+    #[ignore]
+    fn test_rpc_get_delegations() {
+        test_rpc(
+            "test_rpc_get_delegations",
+            40230,
+            40231,
+            50230,
+            50231,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_getdelegations(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    None,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::GetDelegations(response_md, data) => {
+                        assert_eq!(
+                            data.delegated_to,
+                            "ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R"
+                        );
+                        assert_eq!(data.total_delegated_ustx, 0);
+                        assert_eq!(data.total_locked_ustx, 0);
+                        assert!(data.delegators.is_empty());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        peer_1.sortdb = Some(peer_1_sortdb);
-        peer_1.stacks_node = Some(peer_1_stacks_node);
-        peer_1.mempool = Some(peer_1_mempool);
+    #[test]
+    #[ignore]
+    fn test_rpc_get_reward_set_missing() {
+        test_rpc(
+            "test_rpc_get_reward_set_missing",
+            40220,
+            40221,
+            50220,
+            50221,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| { convo_client.new_getrewardset(1000) },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::NotFound(response_md, msg) => {
+                        assert_eq!(msg, "No reward set for reward cycle 1000");
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        convo_1.try_flush(peer_1.chainstate()).unwrap();
+    #[test]
+    #[ignore]
+    fn test_rpc_get_reward_set_out_of_range() {
+        test_rpc(
+            "test_rpc_get_reward_set_out_of_range",
+            40222,
+            40223,
+            50222,
+            50223,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| { convo_client.new_getrewardset(u64::MAX) },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::BadRequest(response_md, msg) => {
+                        assert_eq!(msg, &format!("Reward cycle {} is out of range", u64::MAX));
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        // should have gotten a reply
-        let resp_opt = convo_1.try_get_response();
-        assert!(resp_opt.is_some());
+    #[test]
+    #[ignore]
+    fn test_rpc_get_sortition() {
+        test_rpc(
+            "test_rpc_get_sortition",
+            40290,
+            40291,
+            50290,
+            50291,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let (consensus_hash, block_hash) =
+                    SortitionDB::get_canonical_stacks_chain_tip_hash(
+                        peer_server.sortdb.as_ref().unwrap().conn(),
+                    )
+                    .unwrap();
+                convo_client.new_getsortition(SortitionLookup::StacksBlock(
+                    StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_hash),
+                ))
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let (consensus_hash, block_hash) =
+                    SortitionDB::get_canonical_stacks_chain_tip_hash(
+                        peer_server.sortdb.as_ref().unwrap().conn(),
+                    )
+                    .unwrap();
+                match http_response {
+                    HttpResponseType::GetSortition(_, data) => {
+                        assert_eq!(data.consensus_hash, consensus_hash);
+                        assert!(data.canonical);
+                        assert!(data.sortition);
+                        assert_eq!(data.stacks_block_hash, Some(block_hash));
+                        assert!(data.stacks_block_height.is_some());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-        let resp = resp_opt.unwrap();
-        assert!(check_result(&req, &resp, &mut peer_1, &mut peer_2));
+    #[test]
+    #[ignore]
+    fn test_rpc_get_consensus_schedule() {
+        test_rpc(
+            "test_rpc_get_consensus_schedule",
+            40250,
+            40251,
+            50250,
+            50251,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| { convo_client.new_getconsensusschedule() },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::GetConsensusSchedule(response_md, data) => {
+                        assert_eq!(data.schedule[0].name, "stacks-2.0");
+                        assert_eq!(data.active.as_ref().unwrap().name, "stacks-2.0");
+                        assert!(!data.upgrade_required);
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
     }
 
     #[test]
     #[ignore]
-    fn test_rpc_getinfo() {
-        let peer_server_info = RefCell::new(None);
+    fn test_rpc_get_microblock_equivocations() {
         test_rpc(
-            "test_rpc_getinfo",
-            40000,
-            40001,
-            50000,
-            50001,
+            "test_rpc_get_microblock_equivocations",
+            40252,
+            40253,
+            50252,
+            50253,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
-             ref mut convo_server| {
-                let peer_info = RPCPeerInfoData::from_db(
-                    &peer_server.config.burnchain,
-                    peer_server.sortdb.as_mut().unwrap(),
-                    &peer_server.stacks_node.as_ref().unwrap().chainstate,
-                    &peer_server.network.peerdb,
-                    &None,
-                    &Sha256Sum::zero(),
-                )
-                .unwrap();
-
-                *peer_server_info.borrow_mut() = Some(peer_info);
-
-                convo_client.new_getinfo()
-            },
+             ref mut convo_server| { convo_client.new_getmicroblockequivocations() },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::PeerInfo(response_md, peer_data) => {
-                        assert_eq!(Some((*peer_data).clone()), *peer_server_info.borrow());
+                    HttpResponseType::GetMicroblockEquivocations(response_md, data) => {
+                        // the test chain has no forked microblock streams
+                        assert_eq!(data.equivocations.len(), 0);
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -3393,46 +7427,33 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_getpoxinfo() {
-        let pox_server_info = RefCell::new(None);
+    fn test_rpc_admin_toggles_disabled() {
         test_rpc(
-            "test_rpc_getpoxinfo",
-            40000,
-            40001,
-            50000,
-            50001,
+            "test_rpc_admin_toggles_disabled",
+            40240,
+            40241,
+            50240,
+            50241,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let mut sortdb = peer_server.sortdb.as_mut().unwrap();
-                let chainstate = &mut peer_server.stacks_node.as_mut().unwrap().chainstate;
-                let stacks_block_id = {
-                    let tip = chainstate.get_stacks_chain_tip(sortdb).unwrap().unwrap();
-                    StacksBlockHeader::make_index_block_hash(
-                        &tip.consensus_hash,
-                        &tip.anchored_block_hash,
-                    )
-                };
-                let pox_info = RPCPoxInfoData::from_db(
-                    &mut sortdb,
-                    chainstate,
-                    &stacks_block_id,
-                    &peer_client.config.burnchain,
+                convo_client.new_postadmintoggles(
+                    Some("s3cret".to_string()),
+                    RPCAdminTogglesUpdate {
+                        mining_paused: Some(true),
+                        ..RPCAdminTogglesUpdate::default()
+                    },
                 )
-                .unwrap();
-                *pox_server_info.borrow_mut() = Some(pox_info);
-                convo_client.new_getpoxinfo(None)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::PoxInfo(response_md, pox_data) => {
-                        assert_eq!(Some((*pox_data).clone()), *pox_server_info.borrow());
+                    HttpResponseType::NotFound(_, msg) => {
+                        assert_eq!(msg, "Admin endpoints are not enabled on this node");
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -3442,84 +7463,85 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_getneighbors() {
-        test_rpc(
-            "test_rpc_getneighbors",
-            40010,
-            40011,
-            50010,
-            50011,
+    fn test_rpc_admin_toggles() {
+        let toggles = RuntimeToggles::default();
+        let handler_args = RPCHandlerArgs {
+            admin_auth_token: Some("s3cret"),
+            runtime_toggles: Some(&toggles),
+            ..RPCHandlerArgs::default()
+        };
+        test_rpc_with_handler_args(
+            "test_rpc_admin_toggles",
+            40314,
+            40315,
+            50314,
+            50315,
+            &handler_args,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
-             ref mut convo_server| { convo_client.new_getneighbors() },
+             ref mut convo_server| {
+                convo_client.new_postadmintoggles(
+                    Some("s3cret".to_string()),
+                    RPCAdminTogglesUpdate {
+                        mining_paused: Some(true),
+                        tx_relay_paused: Some(true),
+                        ..RPCAdminTogglesUpdate::default()
+                    },
+                )
+            },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::Neighbors(response_md, neighbor_info) => {
-                        assert_eq!(neighbor_info.sample.len(), 1);
-                        assert_eq!(neighbor_info.sample[0].port, peer_client.config.server_port); // we see ourselves as the neighbor
+                    HttpResponseType::AdminToggles(_, data) => {
+                        assert!(data.mining_paused);
+                        assert!(!data.block_downloads_paused);
+                        assert!(data.tx_relay_paused);
+                        assert!(!data.low_disk_space);
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
             },
         );
+
+        // the update stuck
+        assert!(toggles.mining_paused());
+        assert!(!toggles.downloads_paused());
+        assert!(toggles.tx_relay_paused());
     }
 
     #[test]
     #[ignore]
-    fn test_rpc_unconfirmed_getblock() {
-        let server_block_cell = RefCell::new(None);
-
+    fn test_rpc_block_proposal_disabled() {
         test_rpc(
-            "test_rpc_unconfirmed_getblock",
-            40020,
-            40021,
-            50020,
-            50021,
+            "test_rpc_block_proposal_disabled",
+            40300,
+            40301,
+            50300,
+            50301,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                // have "server" peer store a block to staging
-                let peer_server_block = make_codec_test_block(25);
-                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
-                let index_block_hash = StacksBlockHeader::make_index_block_hash(
-                    &peer_server_consensus_hash,
-                    &peer_server_block.block_hash(),
-                );
-
-                test_debug!("Store peer server index block {:?}", &index_block_hash);
-                store_staging_block(
-                    peer_server.chainstate(),
-                    &peer_server_consensus_hash,
-                    &peer_server_block,
-                    &ConsensusHash([0x03; 20]),
-                    456,
-                    123,
-                );
-
-                *server_block_cell.borrow_mut() = Some(peer_server_block);
-
-                // now ask for it
-                convo_client.new_getblock(index_block_hash)
+                convo_client.new_postblockproposal(
+                    Some("s3cret".to_string()),
+                    RPCBlockProposalRequest {
+                        block: "00".to_string(),
+                        parent_consensus_hash: ConsensusHash([0x11; 20]),
+                    },
+                )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::Block(response_md, block_info) => {
-                        assert_eq!(
-                            block_info.block_hash(),
-                            (*server_block_cell.borrow()).as_ref().unwrap().block_hash()
-                        );
+                    HttpResponseType::NotFound(_, msg) => {
+                        assert_eq!(msg, "Admin endpoints are not enabled on this node");
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -3529,60 +7551,27 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_confirmed_getblock() {
-        let server_block_cell = RefCell::new(None);
-
+    fn test_rpc_miner_wallet_disabled() {
         test_rpc(
-            "test_rpc_confirmed_getblock",
-            40030,
-            40031,
-            50030,
-            50031,
+            "test_rpc_miner_wallet_disabled",
+            40264,
+            40265,
+            50264,
+            50265,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                // have "server" peer store a block to staging
-                let peer_server_block = make_codec_test_block(25);
-                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
-                let index_block_hash = StacksBlockHeader::make_index_block_hash(
-                    &peer_server_consensus_hash,
-                    &peer_server_block.block_hash(),
-                );
-
-                test_debug!("Store peer server index block {:?}", &index_block_hash);
-                store_staging_block(
-                    peer_server.chainstate(),
-                    &peer_server_consensus_hash,
-                    &peer_server_block,
-                    &ConsensusHash([0x03; 20]),
-                    456,
-                    123,
-                );
-                set_block_processed(
-                    peer_server.chainstate(),
-                    &peer_server_consensus_hash,
-                    &peer_server_block.block_hash(),
-                    true,
-                );
-
-                *server_block_cell.borrow_mut() = Some(peer_server_block);
-
-                // now ask for it
-                convo_client.new_getblock(index_block_hash)
+                convo_client.new_getminerwallet(Some("s3cret".to_string()))
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::Block(response_md, block_info) => {
-                        assert_eq!(
-                            block_info.block_hash(),
-                            (*server_block_cell.borrow()).as_ref().unwrap().block_hash()
-                        );
+                    HttpResponseType::NotFound(_, msg) => {
+                        assert_eq!(msg, "This node does not report a miner wallet");
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -3592,109 +7581,65 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_indexed_microblocks() {
-        let server_microblocks_cell = RefCell::new(vec![]);
-
+    fn test_rpc_clarity_profiles_disabled() {
         test_rpc(
-            "test_rpc_indexed_microblocks",
-            40040,
-            40041,
-            50040,
-            50041,
+            "test_rpc_clarity_profiles_disabled",
+            40302,
+            40303,
+            50302,
+            50303,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let privk = StacksPrivateKey::from_hex(
-                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
-                )
-                .unwrap();
-
-                let parent_block = make_codec_test_block(25);
-                let parent_consensus_hash = ConsensusHash([0x02; 20]);
-                let parent_index_block_hash = StacksBlockHeader::make_index_block_hash(
-                    &parent_consensus_hash,
-                    &parent_block.block_hash(),
-                );
-
-                let mut mblocks = make_sample_microblock_stream(&privk, &parent_block.block_hash());
-                mblocks.truncate(15);
-
-                let mut child_block = make_codec_test_block(25);
-                let child_consensus_hash = ConsensusHash([0x03; 20]);
-
-                child_block.header.parent_block = parent_block.block_hash();
-                child_block.header.parent_microblock =
-                    mblocks.last().as_ref().unwrap().block_hash();
-                child_block.header.parent_microblock_sequence =
-                    mblocks.last().as_ref().unwrap().header.sequence;
-
-                store_staging_block(
-                    peer_server.chainstate(),
-                    &parent_consensus_hash,
-                    &parent_block,
-                    &ConsensusHash([0x01; 20]),
-                    456,
-                    123,
-                );
-                set_block_processed(
-                    peer_server.chainstate(),
-                    &parent_consensus_hash,
-                    &parent_block.block_hash(),
-                    true,
-                );
-
-                store_staging_block(
-                    peer_server.chainstate(),
-                    &child_consensus_hash,
-                    &child_block,
-                    &parent_consensus_hash,
-                    456,
-                    123,
-                );
-                set_block_processed(
-                    peer_server.chainstate(),
-                    &child_consensus_hash,
-                    &child_block.block_hash(),
-                    true,
-                );
-
-                let index_microblock_hash = StacksBlockHeader::make_index_block_hash(
-                    &parent_consensus_hash,
-                    &mblocks.last().as_ref().unwrap().block_hash(),
-                );
-
-                for mblock in mblocks.iter() {
-                    store_staging_microblock(
-                        peer_server.chainstate(),
-                        &parent_consensus_hash,
-                        &parent_block.block_hash(),
-                        &mblock,
-                    );
-                }
-
-                set_microblocks_processed(
-                    peer_server.chainstate(),
-                    &child_consensus_hash,
-                    &child_block.block_hash(),
-                    &mblocks.last().as_ref().unwrap().block_hash(),
-                );
-
-                *server_microblocks_cell.borrow_mut() = mblocks;
+                convo_client.new_getclarityprofiles(Some("s3cret".to_string()))
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                match http_response {
+                    HttpResponseType::NotFound(_, msg) => {
+                        assert_eq!(msg, "Admin endpoints are not enabled on this node");
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
 
-                convo_client.new_getmicroblocks_indexed(index_microblock_hash)
+    #[test]
+    #[ignore]
+    fn test_rpc_transfer_stx_op_disabled() {
+        test_rpc(
+            "test_rpc_transfer_stx_op_disabled",
+            40256,
+            40257,
+            50256,
+            50257,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_posttransferstxop(
+                    Some("s3cret".to_string()),
+                    RPCTransferStxOpRequest {
+                        recipient: "ST2QKZ4FKHAH1NQKYKYAYZPY440FEPK7GZ1R5HBP2".to_string(),
+                        amount_ustx: 123,
+                        memo: None,
+                        fee_rate: None,
+                    },
+                )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
-                match (*http_response).clone() {
-                    HttpResponseType::Microblocks(_, mut microblocks) => {
-                        microblocks.reverse();
-                        assert_eq!(microblocks.len(), (*server_microblocks_cell.borrow()).len());
-                        assert_eq!(microblocks, *server_microblocks_cell.borrow());
+                match http_response {
+                    HttpResponseType::NotFound(_, msg) => {
+                        assert_eq!(msg, "Burn operation submission is not enabled on this node");
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -3704,105 +7649,43 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_confirmed_microblocks() {
-        let server_microblocks_cell = RefCell::new(vec![]);
-
+    fn test_rpc_get_account_unconfirmed() {
         test_rpc(
-            "test_rpc_confirmed_microblocks",
-            40042,
-            40043,
-            50042,
-            50043,
+            "test_rpc_get_account_unconfirmed",
+            40120,
+            40121,
+            50120,
+            50121,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let privk = StacksPrivateKey::from_hex(
-                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+                let unconfirmed_tip = peer_client
+                    .chainstate()
+                    .unconfirmed_state
+                    .as_ref()
+                    .unwrap()
+                    .unconfirmed_chain_tip
+                    .clone();
+                convo_client.new_getaccount(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    TipRequest::SpecificTip(unconfirmed_tip),
+                    false,
                 )
-                .unwrap();
-
-                let parent_block = make_codec_test_block(25);
-                let parent_consensus_hash = ConsensusHash([0x02; 20]);
-
-                let mut mblocks = make_sample_microblock_stream(&privk, &parent_block.block_hash());
-                mblocks.truncate(15);
-
-                let mut child_block = make_codec_test_block(25);
-                let child_consensus_hash = ConsensusHash([0x03; 20]);
-
-                child_block.header.parent_block = parent_block.block_hash();
-                child_block.header.parent_microblock =
-                    mblocks.last().as_ref().unwrap().block_hash();
-                child_block.header.parent_microblock_sequence =
-                    mblocks.last().as_ref().unwrap().header.sequence;
-
-                let child_index_block_hash = StacksBlockHeader::make_index_block_hash(
-                    &child_consensus_hash,
-                    &child_block.block_hash(),
-                );
-
-                store_staging_block(
-                    peer_server.chainstate(),
-                    &parent_consensus_hash,
-                    &parent_block,
-                    &ConsensusHash([0x01; 20]),
-                    456,
-                    123,
-                );
-                set_block_processed(
-                    peer_server.chainstate(),
-                    &parent_consensus_hash,
-                    &parent_block.block_hash(),
-                    true,
-                );
-
-                store_staging_block(
-                    peer_server.chainstate(),
-                    &child_consensus_hash,
-                    &child_block,
-                    &parent_consensus_hash,
-                    456,
-                    123,
-                );
-                set_block_processed(
-                    peer_server.chainstate(),
-                    &child_consensus_hash,
-                    &child_block.block_hash(),
-                    true,
-                );
-
-                for mblock in mblocks.iter() {
-                    store_staging_microblock(
-                        peer_server.chainstate(),
-                        &parent_consensus_hash,
-                        &parent_block.block_hash(),
-                        &mblock,
-                    );
-                }
-
-                set_microblocks_processed(
-                    peer_server.chainstate(),
-                    &child_consensus_hash,
-                    &child_block.block_hash(),
-                    &mblocks.last().as_ref().unwrap().block_hash(),
-                );
-
-                *server_microblocks_cell.borrow_mut() = mblocks;
-
-                convo_client.new_getmicroblocks_confirmed(child_index_block_hash)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
-                match (*http_response).clone() {
-                    HttpResponseType::Microblocks(_, mut microblocks) => {
-                        microblocks.reverse();
-                        assert_eq!(microblocks.len(), (*server_microblocks_cell.borrow()).len());
-                        assert_eq!(microblocks, *server_microblocks_cell.borrow());
+                match http_response {
+                    HttpResponseType::GetAccount(response_md, data) => {
+                        assert_eq!(data.nonce, 4);
+                        let balance = u128::from_str_radix(&data.balance[2..], 16).unwrap();
+                        assert_eq!(balance, 1000000000 - 123);
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -3812,59 +7695,37 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_unconfirmed_microblocks() {
-        let server_microblocks_cell = RefCell::new(vec![]);
-
+    fn test_rpc_get_account_tip_unconfirmed() {
+        // same as test_rpc_get_account_unconfirmed, but without having to know the tip
         test_rpc(
-            "test_rpc_unconfirmed_microblocks",
-            40050,
-            40051,
-            50050,
-            50051,
+            "test_rpc_get_account_tip_unconfirmed",
+            40254,
+            40255,
+            50254,
+            50255,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let privk = StacksPrivateKey::from_hex(
-                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+                convo_client.new_getaccount(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    TipRequest::UseLatestUnconfirmedTip,
+                    false,
                 )
-                .unwrap();
-
-                let consensus_hash = ConsensusHash([0x02; 20]);
-                let anchored_block_hash = BlockHeaderHash([0x03; 32]);
-                let index_block_hash =
-                    StacksBlockHeader::make_index_block_hash(&consensus_hash, &anchored_block_hash);
-
-                let mut mblocks = make_sample_microblock_stream(&privk, &anchored_block_hash);
-                mblocks.truncate(15);
-
-                for mblock in mblocks.iter() {
-                    store_staging_microblock(
-                        peer_server.chainstate(),
-                        &consensus_hash,
-                        &anchored_block_hash,
-                        &mblock,
-                    );
-                }
-
-                *server_microblocks_cell.borrow_mut() = mblocks;
-
-                // start at seq 5
-                convo_client.new_getmicroblocks_unconfirmed(index_block_hash, 5)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::Microblocks(response_md, microblocks) => {
-                        assert_eq!(microblocks.len(), 10);
-                        assert_eq!(
-                            *microblocks,
-                            (*server_microblocks_cell.borrow())[5..].to_vec()
-                        );
+                    HttpResponseType::GetAccount(response_md, data) => {
+                        assert_eq!(data.nonce, 4);
+                        let balance = u128::from_str_radix(&data.balance[2..], 16).unwrap();
+                        assert_eq!(balance, 1000000000 - 123);
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -3874,72 +7735,50 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_unconfirmed_transaction() {
-        let last_txid = RefCell::new(Txid([0u8; 32]));
-        let last_mblock = RefCell::new(BlockHeaderHash([0u8; 32]));
-
+    fn test_rpc_get_map_entry() {
         test_rpc(
-            "test_rpc_unconfirmed_transaction",
-            40052,
-            40053,
-            50052,
-            50053,
+            "test_rpc_get_map_entry",
+            40130,
+            40131,
+            50130,
+            50131,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let privk = StacksPrivateKey::from_hex(
-                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+                let principal =
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal();
+                convo_client.new_getmapentry(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    "unit-map".try_into().unwrap(),
+                    Value::Tuple(
+                        TupleData::from_data(vec![("account".into(), Value::Principal(principal))])
+                            .unwrap(),
+                    ),
+                    TipRequest::UseLatestAnchoredTip,
+                    false,
                 )
-                .unwrap();
-
-                let sortdb = peer_server.sortdb.take().unwrap();
-                Relayer::setup_unconfirmed_state(peer_server.chainstate(), &sortdb).unwrap();
-                peer_server.sortdb = Some(sortdb);
-
-                assert!(peer_server.chainstate().unconfirmed_state.is_some());
-                let (txid, mblock_hash) = match peer_server.chainstate().unconfirmed_state {
-                    Some(ref unconfirmed) => {
-                        assert!(unconfirmed.mined_txs.len() > 0);
-                        let mut txid = Txid([0u8; 32]);
-                        let mut mblock_hash = BlockHeaderHash([0u8; 32]);
-                        for (next_txid, (_, mbh, ..)) in unconfirmed.mined_txs.iter() {
-                            txid = next_txid.clone();
-                            mblock_hash = mbh.clone();
-                            break;
-                        }
-                        (txid, mblock_hash)
-                    }
-                    None => {
-                        panic!("No unconfirmed state");
-                    }
-                };
-
-                *last_txid.borrow_mut() = txid.clone();
-                *last_mblock.borrow_mut() = mblock_hash.clone();
-
-                convo_client.new_gettransaction_unconfirmed(txid)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::UnconfirmedTransaction(response_md, unconfirmed_resp) => {
+                    HttpResponseType::GetMapEntry(response_md, data) => {
                         assert_eq!(
-                            unconfirmed_resp.status,
-                            UnconfirmedTransactionStatus::Microblock {
-                                block_hash: (*last_mblock.borrow()).clone(),
-                                seq: 0
-                            }
+                            Value::try_deserialize_hex_untyped(&data.data).unwrap(),
+                            Value::some(Value::Tuple(
+                                TupleData::from_data(vec![("units".into(), Value::Int(123))])
+                                    .unwrap()
+                            ))
+                            .unwrap()
                         );
-                        let tx = StacksTransaction::consensus_deserialize(
-                            &mut &hex_bytes(&unconfirmed_resp.tx).unwrap()[..],
-                        )
-                        .unwrap();
-                        assert_eq!(tx.txid(), *last_txid.borrow());
                         true
                     }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -3949,33 +7788,57 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_missing_getblock() {
+    fn test_rpc_get_map_entry_unconfirmed() {
         test_rpc(
-            "test_rpc_missing_getblock",
-            40060,
-            40061,
-            50060,
-            50061,
+            "test_rpc_get_map_entry_unconfirmed",
+            40140,
+            40141,
+            50140,
+            50141,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let peer_server_block_hash = BlockHeaderHash([0x04; 32]);
-                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
-                let index_block_hash = StacksBlockHeader::make_index_block_hash(
-                    &peer_server_consensus_hash,
-                    &peer_server_block_hash,
-                );
-
-                // now ask for it
-                convo_client.new_getblock(index_block_hash)
+                let unconfirmed_tip = peer_client
+                    .chainstate()
+                    .unconfirmed_state
+                    .as_ref()
+                    .unwrap()
+                    .unconfirmed_chain_tip
+                    .clone();
+                let principal =
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal();
+                convo_client.new_getmapentry(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    "unit-map".try_into().unwrap(),
+                    Value::Tuple(
+                        TupleData::from_data(vec![("account".into(), Value::Principal(principal))])
+                            .unwrap(),
+                    ),
+                    TipRequest::SpecificTip(unconfirmed_tip),
+                    false,
+                )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::NotFound(response_md, msg) => true,
+                    HttpResponseType::GetMapEntry(response_md, data) => {
+                        assert_eq!(
+                            Value::try_deserialize_hex_untyped(&data.data).unwrap(),
+                            Value::some(Value::Tuple(
+                                TupleData::from_data(vec![("units".into(), Value::Int(1))])
+                                    .unwrap()
+                            ))
+                            .unwrap()
+                        );
+                        true
+                    }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -3985,33 +7848,33 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_missing_index_getmicroblocks() {
+    fn test_rpc_get_contract_abi() {
         test_rpc(
-            "test_rpc_missing_index_getmicroblocks",
-            40070,
-            40071,
-            50070,
-            50071,
+            "test_rpc_get_contract_abi",
+            40150,
+            40151,
+            50150,
+            50151,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let peer_server_block_hash = BlockHeaderHash([0x04; 32]);
-                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
-                let index_block_hash = StacksBlockHeader::make_index_block_hash(
-                    &peer_server_consensus_hash,
-                    &peer_server_block_hash,
-                );
-
-                // now ask for it
-                convo_client.new_getmicroblocks_indexed(index_block_hash)
+                convo_client.new_getcontractabi(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world-unconfirmed".try_into().unwrap(),
+                    None,
+                )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::NotFound(response_md, msg) => true,
+                    HttpResponseType::NotFound(..) => {
+                        // not confirmed yet
+                        true
+                    }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -4021,33 +7884,37 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_missing_confirmed_getmicroblocks() {
+    fn test_rpc_get_contract_abi_unconfirmed() {
         test_rpc(
-            "test_rpc_missing_confirmed_getmicroblocks",
-            40070,
-            40071,
-            50070,
-            50071,
+            "test_rpc_get_contract_abi_unconfirmed",
+            40160,
+            40161,
+            50160,
+            50161,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let peer_server_block_hash = BlockHeaderHash([0x04; 32]);
-                let peer_server_consensus_hash = ConsensusHash([0x02; 20]);
-                let index_block_hash = StacksBlockHeader::make_index_block_hash(
-                    &peer_server_consensus_hash,
-                    &peer_server_block_hash,
-                );
-
-                // now ask for it
-                convo_client.new_getmicroblocks_confirmed(index_block_hash)
+                let unconfirmed_tip = peer_client
+                    .chainstate()
+                    .unconfirmed_state
+                    .as_ref()
+                    .unwrap()
+                    .unconfirmed_chain_tip
+                    .clone();
+                convo_client.new_getcontractabi(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world-unconfirmed".try_into().unwrap(),
+                    Some(unconfirmed_tip),
+                )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::NotFound(response_md, msg) => true,
+                    HttpResponseType::GetContractABI(response_md, data) => true,
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -4057,52 +7924,41 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_missing_unconfirmed_microblocks() {
-        let server_microblocks_cell = RefCell::new(vec![]);
-
+    fn test_rpc_call_read_only() {
         test_rpc(
-            "test_rpc_missing_unconfirmed_microblocks",
-            40080,
-            40081,
-            50080,
-            50081,
+            "test_rpc_call_read_only",
+            40170,
+            40171,
+            50170,
+            50171,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let privk = StacksPrivateKey::from_hex(
-                    "eb05c83546fdd2c79f10f5ad5434a90dd28f7e3acb7c092157aa1bc3656b012c01",
+                convo_client.new_callreadonlyfunction(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world-unconfirmed".try_into().unwrap(),
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    "ro-test".try_into().unwrap(),
+                    vec![],
+                    TipRequest::UseLatestAnchoredTip,
                 )
-                .unwrap();
-
-                let consensus_hash = ConsensusHash([0x02; 20]);
-                let anchored_block_hash = BlockHeaderHash([0x03; 32]);
-                let index_block_hash =
-                    StacksBlockHeader::make_index_block_hash(&consensus_hash, &anchored_block_hash);
-
-                let mut mblocks = make_sample_microblock_stream(&privk, &anchored_block_hash);
-                mblocks.truncate(15);
-
-                for mblock in mblocks.iter() {
-                    store_staging_microblock(
-                        peer_server.chainstate(),
-                        &consensus_hash,
-                        &anchored_block_hash,
-                        &mblock,
-                    );
-                }
-
-                *server_microblocks_cell.borrow_mut() = mblocks;
-
-                // start at seq 16 (which doesn't exist)
-                convo_client.new_getmicroblocks_unconfirmed(index_block_hash, 16)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::NotFound(response_md, msg) => true,
+                    HttpResponseType::CallReadOnlyFunction(response_md, data) => {
+                        assert!(data.cause.is_some());
+                        assert!(data.cause.clone().unwrap().find("NoSuchContract").is_some());
+                        assert!(!data.okay);
+                        assert!(data.result.is_none());
+                        true
+                    }
                     _ => {
-                        error!("Invalid response: {:?}", &http_response);
+                        error!("Invalid response; {:?}", &http_response);
                         false
                     }
                 }
@@ -4112,30 +7968,47 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_contract_src() {
+    fn test_rpc_call_read_only_unconfirmed() {
         test_rpc(
-            "test_rpc_get_contract_src",
-            40090,
-            40091,
-            50090,
-            50091,
+            "test_rpc_call_read_only_unconfirmed",
+            40180,
+            40181,
+            50180,
+            50181,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                convo_client.new_getcontractsrc(
+                let unconfirmed_tip = peer_client
+                    .chainstate()
+                    .unconfirmed_state
+                    .as_ref()
+                    .unwrap()
+                    .unconfirmed_chain_tip
+                    .clone();
+                convo_client.new_callreadonlyfunction(
                     StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
                         .unwrap(),
-                    "hello-world".try_into().unwrap(),
-                    None,
-                    false,
+                    "hello-world-unconfirmed".try_into().unwrap(),
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    "ro-test".try_into().unwrap(),
+                    vec![],
+                    TipRequest::SpecificTip(unconfirmed_tip),
                 )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::GetContractSrc(response_md, data) => {
-                        assert_eq!(data.source, TEST_CONTRACT);
+                    HttpResponseType::CallReadOnlyFunction(response_md, data) => {
+                        assert!(data.okay);
+                        assert_eq!(
+                            Value::try_deserialize_hex_untyped(&data.result.clone().unwrap())
+                                .unwrap(),
+                            Value::okay(Value::Int(1)).unwrap()
+                        );
+                        assert!(data.cause.is_none());
                         true
                     }
                     _ => {
@@ -4149,37 +8022,50 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_contract_src_unconfirmed() {
+    fn test_rpc_simulate_contract_call() {
         test_rpc(
-            "test_rpc_get_contract_src_unconfirmed",
-            40100,
-            40101,
-            50100,
-            50101,
+            "test_rpc_simulate_contract_call",
+            40200,
+            40201,
+            50200,
+            50201,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let unconfirmed_tip = peer_client
-                    .chainstate()
-                    .unconfirmed_state
-                    .as_ref()
-                    .unwrap()
-                    .unconfirmed_chain_tip
-                    .clone();
-                convo_client.new_getcontractsrc(
+                let contract_addr =
                     StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
+                        .unwrap();
+                let contract_id = QualifiedContractIdentifier::new(
+                    contract_addr.clone().into(),
                     "hello-world".try_into().unwrap(),
-                    Some(unconfirmed_tip),
-                    false,
+                );
+                convo_client.new_simulatecontractcall(
+                    contract_addr.clone(),
+                    "hello-world".try_into().unwrap(),
+                    contract_addr.to_account_principal(),
+                    "get-bar".try_into().unwrap(),
+                    vec![],
+                    vec![ClarityStateOverride::DataVar(
+                        contract_id,
+                        "bar".try_into().unwrap(),
+                        Value::Int(42),
+                    )],
+                    None,
                 )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::GetContractSrc(response_md, data) => {
-                        assert_eq!(data.source, TEST_CONTRACT);
+                    HttpResponseType::SimulateContractCall(response_md, data) => {
+                        assert!(data.okay);
+                        assert_eq!(
+                            Value::try_deserialize_hex_untyped(&data.result.clone().unwrap())
+                                .unwrap(),
+                            Value::okay(Value::Int(42)).unwrap()
+                        );
+                        assert!(data.cause.is_none());
+                        assert!(data.execution_cost.runtime > 0);
                         true
                     }
                     _ => {
@@ -4193,32 +8079,29 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_account() {
+    fn test_rpc_get_account_transactions() {
         test_rpc(
-            "test_rpc_get_account",
-            40110,
-            40111,
-            50110,
-            50111,
+            "test_rpc_get_account_transactions",
+            40260,
+            40261,
+            50260,
+            50261,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                convo_client.new_getaccount(
+                let principal =
                     StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
                         .unwrap()
-                        .to_account_principal(),
-                    None,
-                    false,
-                )
+                        .to_account_principal();
+                convo_client.new_getaccounttransactions(principal, None, 20, None)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::GetAccount(response_md, data) => {
-                        assert_eq!(data.nonce, 2);
-                        let balance = u128::from_str_radix(&data.balance[2..], 16).unwrap();
-                        assert_eq!(balance, 1000000000);
+                    HttpResponseType::GetAccountTransactions(_, data) => {
+                        // the contract deployment and the contract call
+                        assert!(data.results.len() >= 2);
+                        assert!(data.results[0].block_height >= data.results[1].block_height);
                         true
                     }
                     _ => {
@@ -4232,39 +8115,67 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_account_unconfirmed() {
+    fn test_rpc_get_transaction_receipt() {
         test_rpc(
-            "test_rpc_get_account_unconfirmed",
-            40120,
-            40121,
-            50120,
-            50121,
+            "test_rpc_get_transaction_receipt",
+            40306,
+            40307,
+            50306,
+            50307,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let unconfirmed_tip = peer_client
-                    .chainstate()
-                    .unconfirmed_state
-                    .as_ref()
-                    .unwrap()
-                    .unconfirmed_chain_tip
-                    .clone();
-                convo_client.new_getaccount(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap()
-                        .to_account_principal(),
-                    Some(unconfirmed_tip),
-                    false,
+                // the contract deployment in the tip block
+                let sortdb = peer_server.sortdb.as_mut().unwrap();
+                let chainstate = &mut peer_server.stacks_node.as_mut().unwrap().chainstate;
+                let tip = chainstate.get_stacks_chain_tip(sortdb).unwrap().unwrap();
+                let block = StacksChainState::load_block(
+                    &chainstate.blocks_path,
+                    &tip.consensus_hash,
+                    &tip.anchored_block_hash,
                 )
+                .unwrap()
+                .unwrap();
+                convo_client.new_gettransactionreceipt(block.txs[1].txid(), None)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
+                let sortdb = peer_server.sortdb.as_mut().unwrap();
+                let chainstate = &mut peer_server.stacks_node.as_mut().unwrap().chainstate;
+                let tip = chainstate.get_stacks_chain_tip(sortdb).unwrap().unwrap();
+                let block = StacksChainState::load_block(
+                    &chainstate.blocks_path,
+                    &tip.consensus_hash,
+                    &tip.anchored_block_hash,
+                )
+                .unwrap()
+                .unwrap();
+                let expected = RPCTransactionReceiptData {
+                    txid: block.txs[1].txid().to_string(),
+                    index_block_hash: StacksBlockHeader::make_index_block_hash(
+                        &tip.consensus_hash,
+                        &tip.anchored_block_hash,
+                    )
+                    .to_string(),
+                    block_height: tip.height,
+                    tx_index: 1,
+                    result: format!("0x{}", ClaritySerializable::serialize(&Value::okay_true())),
+                    post_condition_aborted: false,
+                    stx_burned: "0".to_string(),
+                    // what deploying the test contract costs
+                    execution_cost: ExecutionCost {
+                        write_length: 1080,
+                        write_count: 6,
+                        read_length: 1,
+                        read_count: 2,
+                        runtime: 7084000,
+                    },
+                    vm_error: None,
+                    events: vec![],
+                };
                 match http_response {
-                    HttpResponseType::GetAccount(response_md, data) => {
-                        assert_eq!(data.nonce, 4);
-                        let balance = u128::from_str_radix(&data.balance[2..], 16).unwrap();
-                        assert_eq!(balance, 1000000000 - 123);
+                    HttpResponseType::GetTransactionReceipt(_, data) => {
+                        assert_eq!(data, &expected);
                         true
                     }
                     _ => {
@@ -4278,46 +8189,66 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_map_entry() {
+    fn test_rpc_get_contract_events() {
         test_rpc(
-            "test_rpc_get_map_entry",
-            40130,
-            40131,
-            50130,
-            50131,
+            "test_rpc_get_contract_events",
+            40308,
+            40309,
+            50308,
+            50309,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let principal =
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap()
-                        .to_account_principal();
-                convo_client.new_getmapentry(
+                // the call to add-unit is only in an unconfirmed microblock, so index two of the
+                // values it prints in the confirmed tip, along with one without a topic
+                let sortdb = peer_server.sortdb.as_mut().unwrap();
+                let chainstate = &mut peer_server.stacks_node.as_mut().unwrap().chainstate;
+                let tip = chainstate.get_stacks_chain_tip(sortdb).unwrap().unwrap();
+                let tip_block_id = StacksBlockHeader::make_index_block_hash(
+                    &tip.consensus_hash,
+                    &tip.anchored_block_hash,
+                );
+                for (event_index, topic) in [
+                    (0u32, Some("unit-added")),
+                    (1, None),
+                    (2, Some("unit-added")),
+                ]
+                .iter()
+                {
+                    let params: &[&dyn ToSql] = &[
+                        &"ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R.hello-world",
+                        &topic.map(|topic| topic.to_string()),
+                        &Txid([0x01; 32]),
+                        &tip_block_id,
+                        &u64_to_sql(tip.height).unwrap(),
+                        &1u32,
+                        event_index,
+                        &ClaritySerializable::serialize(&Value::UInt(*event_index as u128)),
+                    ];
+                    chainstate.db().execute("INSERT INTO print_events (contract_identifier, topic, txid, index_block_hash, block_height, tx_index, event_index, value_hex) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)", params).unwrap();
+                }
+                convo_client.new_getcontractevents(
                     StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
                         .unwrap(),
                     "hello-world".try_into().unwrap(),
-                    "unit-map".try_into().unwrap(),
-                    Value::Tuple(
-                        TupleData::from_data(vec![("account".into(), Value::Principal(principal))])
-                            .unwrap(),
-                    ),
+                    Some("unit-added".try_into().unwrap()),
+                    None,
+                    1,
                     None,
-                    false,
                 )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::GetMapEntry(response_md, data) => {
-                        assert_eq!(
-                            Value::try_deserialize_hex_untyped(&data.data).unwrap(),
-                            Value::some(Value::Tuple(
-                                TupleData::from_data(vec![("units".into(), Value::Int(123))])
-                                    .unwrap()
-                            ))
-                            .unwrap()
-                        );
+                    HttpResponseType::GetContractEvents(_, data) => {
+                        assert_eq!(data.limit, 1);
+                        assert_eq!(data.results.len(), 1);
+                        assert_eq!(data.results[0].topic.as_deref(), Some("unit-added"));
+                        assert_eq!(data.results[0].event_index, 2);
+                        // the next page picks up with the first unit-added
+                        let next = data.next.as_ref().unwrap();
+                        assert_eq!(next.before_tx_index, 1);
+                        assert_eq!(next.before_event_index, 2);
                         true
                     }
                     _ => {
@@ -4331,53 +8262,63 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_map_entry_unconfirmed() {
+    fn test_rpc_preview_postconditions() {
         test_rpc(
-            "test_rpc_get_map_entry_unconfirmed",
-            40140,
-            40141,
-            50140,
-            50141,
+            "test_rpc_preview_postconditions",
+            40258,
+            40259,
+            50258,
+            50259,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let unconfirmed_tip = peer_client
-                    .chainstate()
-                    .unconfirmed_state
-                    .as_ref()
-                    .unwrap()
-                    .unconfirmed_chain_tip
-                    .clone();
-                let principal =
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap()
-                        .to_account_principal();
-                convo_client.new_getmapentry(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world".try_into().unwrap(),
-                    "unit-map".try_into().unwrap(),
-                    Value::Tuple(
-                        TupleData::from_data(vec![("account".into(), Value::Principal(principal))])
-                            .unwrap(),
-                    ),
-                    Some(unconfirmed_tip),
-                    false,
+                let privk = StacksPrivateKey::from_hex(
+                    "9f1f85a512a96a244e4c0d762788500687feb97481639572e3bffbd6860e6ab001",
                 )
+                .unwrap();
+                let mut tx = StacksTransaction::new(
+                    TransactionVersion::Testnet,
+                    TransactionAuth::from_p2pkh(&privk).unwrap(),
+                    TransactionPayload::new_smart_contract(
+                        "preview-transfer",
+                        "(stx-transfer? u100 tx-sender 'STVN97YYA10MY5F6KQJHKNYJNM24C4A1AT39WRW)",
+                    )
+                    .unwrap(),
+                );
+                tx.post_condition_mode = TransactionPostConditionMode::Deny;
+                tx.add_post_condition(TransactionPostCondition::STX(
+                    PostConditionPrincipal::Origin,
+                    FungibleConditionCode::SentEq,
+                    100,
+                ));
+                tx.add_post_condition(TransactionPostCondition::STX(
+                    PostConditionPrincipal::Origin,
+                    FungibleConditionCode::SentLt,
+                    50,
+                ));
+                convo_client.new_previewpostconditions(tx, TipRequest::UseLatestAnchoredTip)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::GetMapEntry(response_md, data) => {
+                    HttpResponseType::PostConditionsPreview(_, data) => {
+                        assert!(data.okay, "{:?}", &data.cause);
+                        assert!(data.would_abort);
+                        assert_eq!(data.post_condition_mode, "deny");
                         assert_eq!(
-                            Value::try_deserialize_hex_untyped(&data.data).unwrap(),
-                            Value::some(Value::Tuple(
-                                TupleData::from_data(vec![("units".into(), Value::Int(1))])
-                                    .unwrap()
-                            ))
-                            .unwrap()
+                            data.post_conditions,
+                            vec![
+                                RPCPostConditionResult {
+                                    index: 0,
+                                    passed: true
+                                },
+                                RPCPostConditionResult {
+                                    index: 1,
+                                    passed: false
+                                },
+                            ]
                         );
+                        assert!(data.unchecked_assets.is_empty());
                         true
                     }
                     _ => {
@@ -4391,29 +8332,39 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_contract_abi() {
+    fn test_rpc_preview_postconditions_over_budget() {
         test_rpc(
-            "test_rpc_get_contract_abi",
-            40150,
-            40151,
-            50150,
-            50151,
+            "test_rpc_preview_postconditions_over_budget",
+            40312,
+            40313,
+            50312,
+            50313,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                convo_client.new_getcontractabi(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world-unconfirmed".try_into().unwrap(),
-                    None,
+                convo_server.connection.options.read_only_call_limit.runtime = 1;
+                let privk = StacksPrivateKey::from_hex(
+                    "9f1f85a512a96a244e4c0d762788500687feb97481639572e3bffbd6860e6ab001",
                 )
+                .unwrap();
+                let tx = StacksTransaction::new(
+                    TransactionVersion::Testnet,
+                    TransactionAuth::from_p2pkh(&privk).unwrap(),
+                    TransactionPayload::new_smart_contract(
+                        "preview-transfer",
+                        "(stx-transfer? u100 tx-sender 'STVN97YYA10MY5F6KQJHKNYJNM24C4A1AT39WRW)",
+                    )
+                    .unwrap(),
+                );
+                convo_client.new_previewpostconditions(tx, TipRequest::UseLatestAnchoredTip)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::NotFound(..) => {
-                        // not confirmed yet
+                    HttpResponseType::PostConditionsPreview(_, data) => {
+                        assert!(!data.okay);
+                        let cause = data.cause.as_ref().unwrap();
+                        assert!(cause.contains("CostBalanceExceeded"), "{}", cause);
                         true
                     }
                     _ => {
@@ -4427,35 +8378,29 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_get_contract_abi_unconfirmed() {
+    fn test_rpc_mirror_serves_only_immutable_data() {
         test_rpc(
-            "test_rpc_get_contract_abi_unconfirmed",
-            40160,
-            40161,
-            50160,
-            50161,
+            "test_rpc_mirror_serves_only_immutable_data",
+            40304,
+            40305,
+            50304,
+            50305,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let unconfirmed_tip = peer_client
-                    .chainstate()
-                    .unconfirmed_state
-                    .as_ref()
-                    .unwrap()
-                    .unconfirmed_chain_tip
-                    .clone();
-                convo_client.new_getcontractabi(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world-unconfirmed".try_into().unwrap(),
-                    Some(unconfirmed_tip),
-                )
+                convo_server.connection.options.http_mirror_mode = true;
+                convo_client.new_getinfo()
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::GetContractABI(response_md, data) => true,
+                    HttpResponseType::NotFound(_, msg) => {
+                        assert_eq!(
+                            msg,
+                            "This node is an HTTP mirror, and serves only blocks, microblocks, attachments, and state as of a given block"
+                        );
+                        true
+                    }
                     _ => {
                         error!("Invalid response; {:?}", &http_response);
                         false
@@ -4467,37 +8412,44 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_call_read_only() {
+    fn test_rpc_replica_rejects_writes() {
         test_rpc(
-            "test_rpc_call_read_only",
-            40170,
-            40171,
-            50170,
-            50171,
+            "test_rpc_replica_rejects_writes",
+            40262,
+            40263,
+            50262,
+            50263,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                convo_client.new_callreadonlyfunction(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world-unconfirmed".try_into().unwrap(),
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap()
-                        .to_account_principal(),
-                    "ro-test".try_into().unwrap(),
-                    vec![],
-                    None,
+                convo_server.connection.options.read_only_replica = true;
+
+                let privk = StacksPrivateKey::from_hex(
+                    "9f1f85a512a96a244e4c0d762788500687feb97481639572e3bffbd6860e6ab001",
                 )
+                .unwrap();
+                let mut tx = StacksTransaction::new(
+                    TransactionVersion::Testnet,
+                    TransactionAuth::from_p2pkh(&privk).unwrap(),
+                    TransactionPayload::TokenTransfer(
+                        StacksAddress::from_string("STVN97YYA10MY5F6KQJHKNYJNM24C4A1AT39WRW")
+                            .unwrap()
+                            .to_account_principal(),
+                        100,
+                        TokenTransferMemo([0u8; 34]),
+                    ),
+                );
+                tx.set_tx_fee(1000);
+                convo_client.new_post_transaction(tx)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::CallReadOnlyFunction(response_md, data) => {
-                        assert!(data.cause.is_some());
-                        assert!(data.cause.clone().unwrap().find("NoSuchContract").is_some());
-                        assert!(!data.okay);
-                        assert!(data.result.is_none());
+                    HttpResponseType::ServiceUnavailable(_, msg) => {
+                        assert_eq!(
+                            msg,
+                            "This node is a read-only replica and does not accept new data"
+                        );
                         true
                     }
                     _ => {
@@ -4511,47 +8463,43 @@ mod test {
 
     #[test]
     #[ignore]
-    fn test_rpc_call_read_only_unconfirmed() {
+    fn test_rpc_post_microblock_refused_when_acceptance_off() {
         test_rpc(
-            "test_rpc_call_read_only_unconfirmed",
-            40180,
-            40181,
-            50180,
-            50181,
+            "test_rpc_post_microblock_refused_when_acceptance_off",
+            40316,
+            40317,
+            50316,
+            50317,
             |ref mut peer_client,
              ref mut convo_client,
              ref mut peer_server,
              ref mut convo_server| {
-                let unconfirmed_tip = peer_client
-                    .chainstate()
-                    .unconfirmed_state
-                    .as_ref()
-                    .unwrap()
-                    .unconfirmed_chain_tip
-                    .clone();
-                convo_client.new_callreadonlyfunction(
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap(),
-                    "hello-world-unconfirmed".try_into().unwrap(),
-                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
-                        .unwrap()
-                        .to_account_principal(),
-                    "ro-test".try_into().unwrap(),
-                    vec![],
-                    Some(unconfirmed_tip),
+                convo_server.connection.options.microblock_acceptance = MicroblockAcceptance::Off;
+
+                let privk = StacksPrivateKey::from_hex(
+                    "9f1f85a512a96a244e4c0d762788500687feb97481639572e3bffbd6860e6ab001",
                 )
+                .unwrap();
+                let mut tx = StacksTransaction::new(
+                    TransactionVersion::Testnet,
+                    TransactionAuth::from_p2pkh(&privk).unwrap(),
+                    TransactionPayload::TokenTransfer(
+                        StacksAddress::from_string("STVN97YYA10MY5F6KQJHKNYJNM24C4A1AT39WRW")
+                            .unwrap()
+                            .to_account_principal(),
+                        100,
+                        TokenTransferMemo([0u8; 34]),
+                    ),
+                );
+                tx.set_tx_fee(1000);
+                let mblock =
+                    StacksMicroblock::first_unsigned(&BlockHeaderHash([0x11; 32]), vec![tx]);
+                convo_client.new_post_microblock(mblock, None)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
-                let req_md = http_request.metadata().clone();
                 match http_response {
-                    HttpResponseType::CallReadOnlyFunction(response_md, data) => {
-                        assert!(data.okay);
-                        assert_eq!(
-                            Value::try_deserialize_hex_untyped(&data.result.clone().unwrap())
-                                .unwrap(),
-                            Value::okay(Value::Int(1)).unwrap()
-                        );
-                        assert!(data.cause.is_none());
+                    HttpResponseType::Forbidden(_, msg) => {
+                        assert_eq!(msg, "This node does not accept unconfirmed microblocks");
                         true
                     }
                     _ => {
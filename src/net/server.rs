@@ -1238,6 +1238,7 @@ mod test {
                     )),
                     signed_contract_tx,
                     None,
+                    false,
                 );
                 request.metadata_mut().keep_alive = false;
 
@@ -0,0 +1,135 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A Bloom filter over byte strings.  Each filter carries a random seed that is mixed into its
+//! hashes, so that an attacker can't pick items that collide in every node's filter.
+
+use rand::thread_rng;
+use rand::RngCore;
+
+use util::hash::Sha512Trunc256Sum;
+
+/// Most hash functions a filter may use.  Each one takes 4 bytes of a single SHA512/256 digest.
+pub const BLOOM_MAX_HASHES: u8 = 8;
+/// Bits per item a filter is sized with.  With 7 hashes, about 1 in 120 items not in the filter
+/// will appear to be in it.
+pub const BLOOM_BITS_PER_ITEM: usize = 10;
+/// Hashes used by filters made with `BloomFilter::new`
+pub const BLOOM_DEFAULT_HASHES: u8 = 7;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BloomFilter {
+    pub seed: u32,
+    pub num_hashes: u8,
+    pub bits: Vec<u8>,
+}
+
+impl BloomFilter {
+    /// An empty filter sized for `num_items` items, with a random seed
+    pub fn new(num_items: usize) -> BloomFilter {
+        let num_bytes = (num_items.max(1) * BLOOM_BITS_PER_ITEM + 7) / 8;
+        BloomFilter::from_parts(thread_rng().next_u32(), BLOOM_DEFAULT_HASHES, num_bytes)
+    }
+
+    /// An empty filter with the given seed, hash count and size in bytes
+    pub fn from_parts(seed: u32, num_hashes: u8, num_bytes: usize) -> BloomFilter {
+        assert!(num_hashes > 0 && num_hashes <= BLOOM_MAX_HASHES);
+        assert!(num_bytes > 0);
+        BloomFilter {
+            seed,
+            num_hashes,
+            bits: vec![0u8; num_bytes],
+        }
+    }
+
+    /// Bit indexes that `item` maps to
+    fn bit_indexes(&self, item: &[u8]) -> Vec<usize> {
+        let mut preimage = Vec::with_capacity(4 + item.len());
+        preimage.extend_from_slice(&self.seed.to_be_bytes());
+        preimage.extend_from_slice(item);
+        let digest = Sha512Trunc256Sum::from_data(&preimage);
+
+        let num_bits = (self.bits.len() as u64) * 8;
+        digest
+            .as_bytes()
+            .chunks(4)
+            .take(self.num_hashes as usize)
+            .map(|chunk| {
+                let mut word = [0u8; 4];
+                word.copy_from_slice(chunk);
+                ((u32::from_be_bytes(word) as u64) % num_bits) as usize
+            })
+            .collect()
+    }
+
+    pub fn insert(&mut self, item: &[u8]) {
+        for index in self.bit_indexes(item) {
+            self.bits[index / 8] |= 1 << (index % 8);
+        }
+    }
+
+    /// Whether `item` is probably in the filter.  Never false for an item that was inserted.
+    pub fn contains(&self, item: &[u8]) -> bool {
+        if self.bits.is_empty() || self.num_hashes == 0 {
+            return false;
+        }
+        self.bit_indexes(item)
+            .into_iter()
+            .all(|index| self.bits[index / 8] & (1 << (index % 8)) != 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bloom_filter_membership() {
+        let mut filter = BloomFilter::new(100);
+        assert_eq!(filter.bits.len(), 125);
+        for i in 0..100u32 {
+            filter.insert(&i.to_be_bytes());
+        }
+        for i in 0..100u32 {
+            assert!(filter.contains(&i.to_be_bytes()));
+        }
+
+        // about 1% false positives; allow plenty of slack
+        let false_positives = (100..10100u32)
+            .filter(|i| filter.contains(&i.to_be_bytes()))
+            .count();
+        assert!(false_positives < 500, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn bloom_filter_seeds() {
+        let mut a = BloomFilter::from_parts(1, 7, 64);
+        let mut b = BloomFilter::from_parts(2, 7, 64);
+        a.insert(b"hello");
+        b.insert(b"hello");
+        assert_ne!(a.bits, b.bits);
+        assert!(a.contains(b"hello"));
+        assert!(b.contains(b"hello"));
+
+        // a filter decoded with no bits or hashes contains nothing
+        let empty = BloomFilter {
+            seed: 0,
+            num_hashes: 0,
+            bits: vec![],
+        };
+        assert!(!empty.contains(b"hello"));
+    }
+}
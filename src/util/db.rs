@@ -24,12 +24,13 @@ use std::io::Error as IOError;
 use std::ops::Deref;
 use std::ops::DerefMut;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use util::hash::to_hex;
 use util::sleep_ms;
 
 use types::chainstate::BlockHeaderHash;
-use vm::types::QualifiedContractIdentifier;
+use vm::types::{PrincipalData, QualifiedContractIdentifier};
 
 use rusqlite::types::{
     FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, Value as RusqliteValue,
@@ -37,6 +38,8 @@ use rusqlite::types::{
 };
 use rusqlite::Connection;
 use rusqlite::Error as sqlite_error;
+use rusqlite::OpenFlags;
+use rusqlite::OptionalExtension;
 use rusqlite::Row;
 use rusqlite::Transaction;
 use rusqlite::TransactionBehavior;
@@ -203,6 +206,13 @@ impl FromColumn<QualifiedContractIdentifier> for QualifiedContractIdentifier {
     }
 }
 
+impl FromColumn<PrincipalData> for PrincipalData {
+    fn from_column<'a>(row: &'a Row, column_name: &str) -> Result<PrincipalData, Error> {
+        let value: String = row.get_unwrap(column_name);
+        PrincipalData::parse(&value).map_err(|_| Error::ParseError)
+    }
+}
+
 pub fn u64_to_sql(x: u64) -> Result<i64, Error> {
     if x > (i64::max_value() as u64) {
         return Err(Error::ParseError);
@@ -388,6 +398,55 @@ pub fn sql_pragma(conn: &Connection, pragma_stmt: &str) -> Result<(), Error> {
     conn.query_row_and_then(pragma_stmt, NO_PARAMS, |_row| Ok(()))
 }
 
+/// Copy the contents of a database's write-ahead log into the database file and truncate the log,
+/// so that the database file is complete on its own.  Databases not in WAL mode are unaffected.
+/// Fails if another connection is still using the log.
+pub fn sql_wal_checkpoint(path: &str) -> Result<(), Error> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+    // don't wait indefinitely on readers, as tx_busy_handler would
+    conn.busy_timeout(Duration::from_secs(5))?;
+    let busy: i64 = conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", NO_PARAMS, |row| {
+        row.get(0)
+    })?;
+    if busy != 0 {
+        return Err(Error::Other(format!(
+            "Could not checkpoint {}: database is in use",
+            path
+        )));
+    }
+    Ok(())
+}
+
+/// The journal mode of the database on the other end of `conn`, e.g. "wal" or "delete"
+pub fn sql_journal_mode(conn: &Connection) -> Result<String, Error> {
+    let mode: String = conn.query_row("PRAGMA journal_mode", NO_PARAMS, |row| row.get(0))?;
+    Ok(mode.to_lowercase())
+}
+
+/// The schema version recorded in the `db_config` table of the database at `path`, which is
+/// opened read-only.  Returns None if the database has no `db_config` table.
+pub fn sql_schema_version(path: &str) -> Result<Option<String>, Error> {
+    if fs::metadata(path).is_err() {
+        return Err(Error::NoDBError);
+    }
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    conn.busy_handler(Some(tx_busy_handler))?;
+    let has_config: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'db_config'",
+        NO_PARAMS,
+        |row| row.get(0),
+    )?;
+    if has_config == 0 {
+        return Ok(None);
+    }
+    let version = conn
+        .query_row("SELECT version FROM db_config LIMIT 1", NO_PARAMS, |row| {
+            row.get(0)
+        })
+        .optional()?;
+    Ok(version)
+}
+
 /// Set up an on-disk database with a MARF index if they don't exist yet.
 /// Either way, returns (db path, MARF path)
 pub fn db_mkdirs(path_str: &str) -> Result<(String, String), Error> {
@@ -512,6 +571,31 @@ pub fn tx_begin_immediate<'a>(conn: &'a mut Connection) -> Result<DBTx<'a>, Erro
     Ok(tx)
 }
 
+/// Begin a read transaction on `conn`, so that every read on it sees the database as it was when
+/// the transaction began, whatever other connections commit in the meantime, until
+/// `end_read_snapshot()` is called.  In WAL mode, this does not block writers.  Returns false,
+/// and does nothing, if `conn` is already in a transaction.
+pub fn begin_read_snapshot(conn: &Connection) -> Result<bool, Error> {
+    if !conn.is_autocommit() {
+        return Ok(false);
+    }
+    conn.execute_batch("BEGIN DEFERRED")?;
+    // a deferred transaction doesn't pin its snapshot until its first read
+    if let Err(e) = conn.query_row("SELECT COUNT(*) FROM sqlite_master", NO_PARAMS, |_row| {
+        Ok(())
+    }) {
+        let _ = conn.execute_batch("ROLLBACK");
+        return Err(e.into());
+    }
+    Ok(true)
+}
+
+/// End a read transaction begun with `begin_read_snapshot()`.
+pub fn end_read_snapshot(conn: &Connection) -> Result<(), Error> {
+    conn.execute_batch("COMMIT")?;
+    Ok(())
+}
+
 /// Get the ancestor block hash of a block of a given height, given a descendent block hash.
 pub fn get_ancestor_block_hash<T: MarfTrieId>(
     index: &MARF<T>,
@@ -746,3 +830,59 @@ impl<'a, C: Clone, T: MarfTrieId> Drop for IndexDBTx<'a, C, T> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+
+    #[test]
+    fn read_snapshot_isolation() {
+        let path = "/tmp/stacks-read-snapshot-test.sqlite";
+        if fs::metadata(path).is_ok() {
+            fs::remove_file(path).unwrap();
+        }
+        let writer = Connection::open(path).unwrap();
+        sql_pragma(&writer, "PRAGMA journal_mode = WAL;").unwrap();
+        writer
+            .execute_batch("CREATE TABLE t (x INTEGER); INSERT INTO t VALUES (1);")
+            .unwrap();
+        let reader = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).unwrap();
+        let count = |conn: &Connection| -> i64 {
+            conn.query_row("SELECT COUNT(*) FROM t", NO_PARAMS, |row| row.get(0))
+                .unwrap()
+        };
+
+        assert!(begin_read_snapshot(&reader).unwrap());
+        // already in a transaction
+        assert!(!begin_read_snapshot(&reader).unwrap());
+
+        writer.execute_batch("INSERT INTO t VALUES (2);").unwrap();
+        assert_eq!(count(&writer), 2);
+        assert_eq!(count(&reader), 1);
+
+        end_read_snapshot(&reader).unwrap();
+        assert_eq!(count(&reader), 2);
+    }
+
+    #[test]
+    fn schema_version() {
+        let path = "/tmp/stacks-schema-version-test.sqlite";
+        if fs::metadata(path).is_ok() {
+            fs::remove_file(path).unwrap();
+        }
+        match sql_schema_version(path) {
+            Err(Error::NoDBError) => {}
+            x => panic!("Expected NoDBError, got {:?}", &x),
+        }
+
+        let conn = Connection::open(path).unwrap();
+        conn.execute_batch("CREATE TABLE t (x INTEGER);").unwrap();
+        assert_eq!(sql_schema_version(path).unwrap(), None);
+
+        conn.execute_batch("CREATE TABLE db_config (version TEXT NOT NULL); INSERT INTO db_config (version) VALUES ('2');")
+            .unwrap();
+        assert_eq!(sql_schema_version(path).unwrap(), Some("2".to_string()));
+    }
+}
@@ -15,18 +15,36 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use chrono::prelude::*;
-use slog::{BorrowedKV, Drain, FnValue, Level, Logger, OwnedKVList, Record, KV};
+use slog::{
+    BorrowedKV, Drain, FnValue, Level, Logger, OwnedKVList, Record, SendSyncRefUnwindSafeDrain, KV,
+};
 use slog_term::{CountingWriter, Decorator, RecordDecorator, Serializer};
 use std::env;
 use std::io;
 use std::io::Write;
-use std::sync::Mutex;
+use std::panic::UnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, RwLock};
 use std::thread;
 use std::time::{Duration, SystemTime};
 
 lazy_static! {
     pub static ref LOGGER: Logger = make_logger();
+    /// Subsystems that log at debug level regardless of the log level
+    static ref DEBUG_SUBSYSTEMS: RwLock<Vec<String>> = RwLock::new(vec![]);
 }
+
+/// Whether `DEBUG_SUBSYSTEMS` is non-empty, so the log macros need not take its lock
+static HAS_DEBUG_SUBSYSTEMS: AtomicBool = AtomicBool::new(false);
+
+/// The log level set with `set_loglevel()`, as a `slog::Level::as_usize()`, or 0 if unset
+static LOGLEVEL_OVERRIDE: AtomicUsize = AtomicUsize::new(0);
+
+/// The log format set with `set_log_json()`, or 0 if unset
+static LOG_FORMAT_OVERRIDE: AtomicUsize = AtomicUsize::new(0);
+const LOG_FORMAT_TEXT: usize = 1;
+const LOG_FORMAT_JSON: usize = 2;
+
 struct TermFormat<D: Decorator> {
     decorator: D,
     pretty_print: bool,
@@ -179,9 +197,39 @@ impl<D: Decorator> TermFormat<D> {
     }
 }
 
+type BoxedDrain = Box<dyn SendSyncRefUnwindSafeDrain<Ok = (), Err = slog::Never> + UnwindSafe>;
+
+/// A drain that writes each record as JSON or as text, per `is_log_json()`
+struct LogFormatSwitch {
+    text: BoxedDrain,
+    json: Option<BoxedDrain>,
+}
+
+impl Drain for LogFormatSwitch {
+    type Ok = ();
+    type Err = slog::Never;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> Result<Self::Ok, Self::Err> {
+        match self.json {
+            Some(ref json) if is_log_json() => json.log(record, values),
+            _ => self.text.log(record, values),
+        }
+    }
+}
+
+/// The subsystem a record was logged from: its module path, without the crate name
+fn log_subsystem(module: &str) -> &str {
+    module.splitn(2, "::").last().unwrap_or(module)
+}
+
+/// Each JSON record has `ts`, `level`, `msg`, `subsystem`, `file`, `line`, and `thread` fields,
+/// and a field for each key/value pair logged with it (such as `block_hash` or `request_id`).
 #[cfg(feature = "slog_json")]
-fn make_json_logger() -> Logger {
-    let def_keys = o!("file" => FnValue(move |info| {
+fn make_json_drain() -> Option<BoxedDrain> {
+    let def_keys = o!("subsystem" => FnValue(move |info| {
+                          log_subsystem(info.module()).to_string()
+                      }),
+                      "file" => FnValue(move |info| {
                           info.file()
                       }),
                       "line" => FnValue(move |info| {
@@ -195,47 +243,96 @@ fn make_json_logger() -> Logger {
                       }),
     );
 
-    let drain = Mutex::new(slog_json::Json::default(std::io::stderr())).map(slog::Fuse);
-    let filtered_drain = slog::LevelFilter::new(drain, get_loglevel()).fuse();
-    slog::Logger::root(filtered_drain, def_keys)
+    let json = slog_json::Json::new(std::io::stderr())
+        .add_default_keys()
+        .add_key_value(def_keys)
+        .build();
+    let drain = Mutex::new(json).map(slog::Fuse);
+    // filter on each record, since the log level can change at runtime
+    let filtered_drain = drain
+        .filter(|record| {
+            record.level().is_at_least(get_loglevel())
+                || (record.level() == Level::Debug && is_debug_subsystem(record.module()))
+        })
+        .fuse();
+    Some(Box::new(filtered_drain))
 }
 
 #[cfg(not(feature = "slog_json"))]
-fn make_json_logger() -> Logger {
-    panic!("Tried to construct JSON logger, but stacks-blockchain built without slog_json feature enabled.")
+fn make_json_drain() -> Option<BoxedDrain> {
+    if is_log_json() {
+        panic!("Tried to construct JSON logger, but stacks-blockchain built without slog_json feature enabled.")
+    }
+    None
 }
 
 #[cfg(not(test))]
 fn make_logger() -> Logger {
-    if env::var("STACKS_LOG_JSON") == Ok("1".into()) {
-        make_json_logger()
-    } else {
-        let debug = env::var("STACKS_LOG_DEBUG") == Ok("1".into());
-        let pretty_print = env::var("STACKS_LOG_PP") == Ok("1".into());
-        let decorator = slog_term::PlainSyncDecorator::new(std::io::stderr());
-        let atty = isatty(Stream::Stderr);
-        let drain = TermFormat::new(decorator, pretty_print, debug, atty);
-        let logger = Logger::root(drain.fuse(), o!());
-        logger
-    }
+    let debug = env::var("STACKS_LOG_DEBUG") == Ok("1".into());
+    let pretty_print = env::var("STACKS_LOG_PP") == Ok("1".into());
+    let decorator = slog_term::PlainSyncDecorator::new(std::io::stderr());
+    let atty = isatty(Stream::Stderr);
+    let drain = TermFormat::new(decorator, pretty_print, debug, atty);
+    let switch = LogFormatSwitch {
+        text: Box::new(drain.fuse()),
+        json: make_json_drain(),
+    };
+    Logger::root(switch, o!())
 }
 
 #[cfg(test)]
 fn make_logger() -> Logger {
-    if env::var("STACKS_LOG_JSON") == Ok("1".into()) {
-        make_json_logger()
-    } else {
-        let debug = env::var("STACKS_LOG_DEBUG") == Ok("1".into());
-        let plain = slog_term::PlainSyncDecorator::new(slog_term::TestStdoutWriter);
-        let isatty = isatty(Stream::Stdout);
-        let drain = TermFormat::new(plain, false, debug, isatty);
-        let logger = Logger::root(drain.fuse(), o!());
-        logger
+    let debug = env::var("STACKS_LOG_DEBUG") == Ok("1".into());
+    let plain = slog_term::PlainSyncDecorator::new(slog_term::TestStdoutWriter);
+    let isatty = isatty(Stream::Stdout);
+    let drain = TermFormat::new(plain, false, debug, isatty);
+    let switch = LogFormatSwitch {
+        text: Box::new(drain.fuse()),
+        json: make_json_drain(),
+    };
+    Logger::root(switch, o!())
+}
+
+/// Whether log records are written as JSON: set by `set_log_json()`, or else by
+/// `STACKS_LOG_JSON=1`.
+pub fn is_log_json() -> bool {
+    match LOG_FORMAT_OVERRIDE.load(Ordering::SeqCst) {
+        LOG_FORMAT_TEXT => false,
+        LOG_FORMAT_JSON => true,
+        _ => env::var("STACKS_LOG_JSON") == Ok("1".into()),
     }
 }
 
+/// Write log records as JSON (one object per line) or as text, regardless of `STACKS_LOG_JSON`.
+/// JSON needs the `slog_json` feature.
+pub fn set_log_json(json: bool) -> Result<(), String> {
+    if json && !cfg!(feature = "slog_json") {
+        return Err(
+            "JSON logging requires stacks-blockchain to be built with the `slog_json` feature"
+                .to_string(),
+        );
+    }
+    LOG_FORMAT_OVERRIDE.store(
+        if json {
+            LOG_FORMAT_JSON
+        } else {
+            LOG_FORMAT_TEXT
+        },
+        Ordering::SeqCst,
+    );
+    Ok(())
+}
+
+/// Log at `level` instead of the level given by the environment, or go back to the
+/// environment's level if `level` is `None`.
+pub fn set_loglevel(level: Option<slog::Level>) {
+    LOGLEVEL_OVERRIDE.store(level.map(|l| l.as_usize()).unwrap_or(0), Ordering::SeqCst);
+}
+
 pub fn get_loglevel() -> slog::Level {
-    if env::var("STACKS_LOG_TRACE") == Ok("1".into()) {
+    if let Some(level) = slog::Level::from_usize(LOGLEVEL_OVERRIDE.load(Ordering::SeqCst)) {
+        level
+    } else if env::var("STACKS_LOG_TRACE") == Ok("1".into()) {
         slog::Level::Trace
     } else if env::var("STACKS_LOG_DEBUG") == Ok("1".into()) {
         slog::Level::Debug
@@ -246,6 +343,41 @@ pub fn get_loglevel() -> slog::Level {
     }
 }
 
+/// Log debug messages from each of `subsystems` (module paths without the crate name, such as
+/// `net::download`), along with their submodules, even when the log level is higher than debug.
+/// An empty list turns this off.
+pub fn set_debug_subsystems(subsystems: Vec<String>) {
+    let mut debug_subsystems = DEBUG_SUBSYSTEMS
+        .write()
+        .expect("FATAL: debug subsystems lock poisoned");
+    HAS_DEBUG_SUBSYSTEMS.store(subsystems.len() > 0, Ordering::SeqCst);
+    *debug_subsystems = subsystems;
+}
+
+pub fn get_debug_subsystems() -> Vec<String> {
+    DEBUG_SUBSYSTEMS
+        .read()
+        .expect("FATAL: debug subsystems lock poisoned")
+        .clone()
+}
+
+/// Whether debug messages logged from `module` (a `module_path!()`) were turned on with
+/// `set_debug_subsystems()`.
+pub fn is_debug_subsystem(module: &str) -> bool {
+    if !HAS_DEBUG_SUBSYSTEMS.load(Ordering::SeqCst) {
+        return false;
+    }
+    let subsystem = log_subsystem(module);
+    DEBUG_SUBSYSTEMS
+        .read()
+        .expect("FATAL: debug subsystems lock poisoned")
+        .iter()
+        .any(|s| {
+            subsystem == s.as_str()
+                || (subsystem.starts_with(s.as_str()) && subsystem[s.len()..].starts_with("::"))
+        })
+}
+
 #[macro_export]
 macro_rules! trace {
     ($($arg:tt)*) => ({
@@ -290,7 +422,9 @@ macro_rules! info {
 macro_rules! debug {
     ($($arg:tt)*) => ({
         let cur_level = ::util::log::get_loglevel();
-        if slog::Level::Debug.is_at_least(cur_level) {
+        if slog::Level::Debug.is_at_least(cur_level)
+            || ::util::log::is_debug_subsystem(module_path!())
+        {
             slog_debug!($crate::util::log::LOGGER, $($arg)*)
         }
     })
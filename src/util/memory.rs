@@ -0,0 +1,167 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A global memory budget.  The node's biggest memory consumers are SQLite's page caches (for
+//! the MARF and the mempool) and the p2p connections' read and write buffers.  Given a total
+//! budget, `MemoryBudget` gives each of them a fixed share, and leaves the rest as headroom for
+//! Clarity evaluation, block assembly, and everything else.  Once installed, new MARF and
+//! mempool connections size their page caches from the budget, and SQLite as a whole is held to
+//! its share with a soft heap limit.  Without a budget, SQLite's defaults apply.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use rusqlite::ffi;
+use rusqlite::Connection;
+
+use util::db::Error as db_error;
+
+/// Percentage of the budget given to the MARF page caches
+const MARF_CACHE_SHARE: u64 = 30;
+/// Percentage of the budget given to the mempool page caches
+const MEMPOOL_CACHE_SHARE: u64 = 10;
+/// Percentage of the budget given to p2p and HTTP connection buffers
+const CONNECTION_BUFFER_SHARE: u64 = 25;
+
+/// About how many MARF connections are open at once outside of the read-only handle pool
+/// (chainstate, sortition DB, Clarity state), to split the MARF share between, along with the
+/// pool's handles
+const MARF_CONNECTIONS: u64 = 8;
+/// About how many mempool connections are open at once (relayer, miner, RPC)
+const MEMPOOL_CONNECTIONS: u64 = 4;
+
+/// Page cache sizes, in KiB, for new connections.  0 means no budget is installed.
+static MARF_CACHE_KIB: AtomicU64 = AtomicU64::new(0);
+static MEMPOOL_CACHE_KIB: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryBudget {
+    pub total_bytes: u64,
+    /// Shared by all MARF connections' page caches
+    pub marf_cache_bytes: u64,
+    /// How many MARF connections can be open at once, counting every pooled read-only handle
+    pub marf_connections: u64,
+    /// Shared by all mempool connections' page caches
+    pub mempool_cache_bytes: u64,
+    /// Shared by all p2p and HTTP connections' read and write buffers
+    pub connection_buffer_bytes: u64,
+}
+
+impl MemoryBudget {
+    /// Split a total budget, given the cap on pooled read-only MARF handles (see
+    /// `chainstate::stacks::index::handles`)
+    pub fn from_total_bytes(total_bytes: u64, max_marf_handles: u64) -> MemoryBudget {
+        MemoryBudget {
+            total_bytes,
+            marf_cache_bytes: total_bytes / 100 * MARF_CACHE_SHARE,
+            marf_connections: MARF_CONNECTIONS + max_marf_handles,
+            mempool_cache_bytes: total_bytes / 100 * MEMPOOL_CACHE_SHARE,
+            connection_buffer_bytes: total_bytes / 100 * CONNECTION_BUFFER_SHARE,
+        }
+    }
+
+    /// Page cache size, in KiB, for each MARF connection
+    pub fn marf_cache_kib_per_connection(&self) -> u64 {
+        (self.marf_cache_bytes / self.marf_connections / 1024).max(1)
+    }
+
+    /// Page cache size, in KiB, for each mempool connection
+    pub fn mempool_cache_kib_per_connection(&self) -> u64 {
+        (self.mempool_cache_bytes / MEMPOOL_CONNECTIONS / 1024).max(1)
+    }
+
+    /// Apply this budget to SQLite, and to the MARF and mempool connections opened from here on.
+    /// Connections that are already open keep their page cache sizes.
+    pub fn install(&self) {
+        MARF_CACHE_KIB.store(self.marf_cache_kib_per_connection(), Ordering::SeqCst);
+        MEMPOOL_CACHE_KIB.store(self.mempool_cache_kib_per_connection(), Ordering::SeqCst);
+        let sqlite_limit = self.marf_cache_bytes + self.mempool_cache_bytes;
+        unsafe {
+            ffi::sqlite3_soft_heap_limit64(sqlite_limit as i64);
+        }
+        info!(
+            "Memory budget: {} MB in total, {} MB for SQLite, {} MB for connection buffers",
+            self.total_bytes / (1024 * 1024),
+            sqlite_limit / (1024 * 1024),
+            self.connection_buffer_bytes / (1024 * 1024)
+        );
+    }
+}
+
+fn set_cache_size(conn: &Connection, cache_kib: u64) -> Result<(), db_error> {
+    if cache_kib == 0 {
+        return Ok(());
+    }
+    // a negative cache_size is in KiB, rather than in pages
+    conn.execute_batch(&format!("PRAGMA cache_size = -{};", cache_kib))
+        .map_err(db_error::SqliteError)
+}
+
+/// Size a newly-opened MARF connection's page cache from the installed budget, if any
+pub fn apply_marf_cache_size(conn: &Connection) -> Result<(), db_error> {
+    set_cache_size(conn, MARF_CACHE_KIB.load(Ordering::SeqCst))
+}
+
+/// Size a newly-opened mempool connection's page cache from the installed budget, if any
+pub fn apply_mempool_cache_size(conn: &Connection) -> Result<(), db_error> {
+    set_cache_size(conn, MEMPOOL_CACHE_KIB.load(Ordering::SeqCst))
+}
+
+/// Bytes SQLite currently has allocated, across all connections
+pub fn sqlite_memory_used() -> i64 {
+    unsafe { ffi::sqlite3_memory_used() }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rusqlite::NO_PARAMS;
+
+    #[test]
+    fn budget_shares() {
+        let budget = MemoryBudget::from_total_bytes(4096 * 1024 * 1024, 256);
+        assert!(
+            budget.marf_cache_bytes + budget.mempool_cache_bytes + budget.connection_buffer_bytes
+                < budget.total_bytes
+        );
+        assert_eq!(
+            budget.marf_cache_kib_per_connection(),
+            budget.marf_cache_bytes / (MARF_CONNECTIONS + 256) / 1024
+        );
+
+        // fewer pooled handles leave more page cache for each connection
+        let fewer_handles = MemoryBudget::from_total_bytes(4096 * 1024 * 1024, 8);
+        assert!(
+            fewer_handles.marf_cache_kib_per_connection() > budget.marf_cache_kib_per_connection()
+        );
+
+        // a tiny budget still gets a (tiny) page cache, rather than none
+        let tiny = MemoryBudget::from_total_bytes(1024, 256);
+        assert_eq!(tiny.marf_cache_kib_per_connection(), 1);
+        assert_eq!(tiny.mempool_cache_kib_per_connection(), 1);
+    }
+
+    #[test]
+    fn cache_size_pragma() {
+        let conn = Connection::open_in_memory().unwrap();
+        set_cache_size(&conn, 1234).unwrap();
+        let cache_size: i64 = conn
+            .query_row("PRAGMA cache_size", NO_PARAMS, |row| row.get(0))
+            .unwrap();
+        assert_eq!(cache_size, -1234);
+        assert!(sqlite_memory_used() > 0);
+    }
+}
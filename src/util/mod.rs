@@ -20,8 +20,10 @@ pub mod log;
 pub mod macros;
 #[macro_use]
 pub mod db;
+pub mod bloom;
 pub mod boot;
 pub mod hash;
+pub mod memory;
 pub mod pair;
 pub mod pipe;
 pub mod retry;
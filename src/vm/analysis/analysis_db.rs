@@ -18,6 +18,7 @@ use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 use vm::analysis::errors::{CheckError, CheckErrors, CheckResult};
 use vm::analysis::type_checker::ContractAnalysis;
+use vm::database::contract_cache::{cache_analysis, get_cached_analysis, invalidate_contract};
 use vm::database::{
     ClarityBackingStore, ClarityDeserializable, ClaritySerializable, RollbackWrapper,
 };
@@ -87,12 +88,23 @@ impl<'a> AnalysisDatabase<'a> {
         &mut self,
         contract_identifier: &QualifiedContractIdentifier,
     ) -> Option<ContractAnalysis> {
-        self.store
+        let cache_key = self.store.get_contract_cache_key(contract_identifier);
+        if let Some(analysis) = cache_key.as_ref().and_then(get_cached_analysis) {
+            return Some(analysis);
+        }
+
+        let analysis = self
+            .store
             .get_metadata(contract_identifier, AnalysisDatabase::storage_key())
             // treat NoSuchContract error thrown by get_metadata as an Option::None --
             //    the analysis will propagate that as a CheckError anyways.
             .ok()?
-            .map(|x| ContractAnalysis::deserialize(&x))
+            .map(|x| ContractAnalysis::deserialize(&x))?;
+
+        if let Some(cache_key) = cache_key {
+            cache_analysis(cache_key, &analysis);
+        }
+        Some(analysis)
     }
 
     pub fn insert_contract(
@@ -107,6 +119,7 @@ impl<'a> AnalysisDatabase<'a> {
 
         self.store
             .insert_metadata(contract_identifier, key, &contract.serialize());
+        invalidate_contract(contract_identifier);
         Ok(())
     }
 
@@ -25,6 +25,7 @@ use vm::analysis::errors::CheckErrors;
 use vm::contexts::ContractContext;
 use vm::costs::cost_functions::ClarityCostFunction;
 use vm::errors::{check_argument_count, Error, InterpreterResult as Result};
+use vm::profiler;
 use vm::representations::{ClarityName, SymbolicExpression};
 use vm::types::Value::UInt;
 use vm::types::{
@@ -114,6 +115,20 @@ impl DefinedFunction {
     }
 
     pub fn execute_apply(&self, args: &[Value], env: &mut Environment) -> Result<Value> {
+        if !profiler::is_profiling() {
+            return self.execute_apply_unprofiled(args, env);
+        }
+        profiler::enter_function(
+            &env.contract_context.contract_identifier,
+            &self.name,
+            &env.global_context.cost_track.get_total(),
+        );
+        let result = self.execute_apply_unprofiled(args, env);
+        profiler::exit_function(&env.global_context.cost_track.get_total());
+        result
+    }
+
+    fn execute_apply_unprofiled(&self, args: &[Value], env: &mut Environment) -> Result<Value> {
         runtime_cost(
             ClarityCostFunction::UserFunctionApplication,
             env,
@@ -54,6 +54,24 @@ use vm::costs::cost_functions::ClarityCostFunction;
 
 pub const MAX_CONTEXT_DEPTH: u16 = 256;
 
+/// The outcome of checking that a contract can be dynamically dispatched to through a trait.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraitResolution {
+    /// The contract explicitly implements the trait with `impl-trait`, so it was checked when
+    /// it was published.
+    Explicit,
+    /// The contract's function conforms to the trait, and its return value must be admitted by
+    /// this type.
+    Conforming(TypeSignature),
+}
+
+/// Resolved trait targets, keyed by (callee contract, trait, function name).  Resolution depends
+/// on the code of the callee and of the trait-defining contract.  Published code never changes,
+/// but a contract published by a transaction that is later rolled back never existed, so a
+/// resolution only outlives the context that made it once that context commits.
+pub type TraitResolutionCache =
+    HashMap<(QualifiedContractIdentifier, TraitIdentifier, ClarityName), TraitResolution>;
+
 // TODO:
 //    hide the environment's instance variables.
 //     we don't want many of these changing after instantiation.
@@ -191,6 +209,11 @@ pub struct GlobalContext<'a> {
     read_only: Vec<bool>,
     pub cost_track: LimitedCostTracker,
     pub mainnet: bool,
+    /// Trait resolutions made by earlier, committed transactions in the block
+    trait_resolutions: TraitResolutionCache,
+    /// Trait resolutions made in this context, staged per nesting level.  The first entry holds
+    /// the resolutions of committed top-level contexts.
+    staged_trait_resolutions: Vec<TraitResolutionCache>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -726,6 +749,26 @@ impl<'a> OwnedEnvironment<'a> {
         self.context.cost_track.get_total()
     }
 
+    /// Seed this environment with trait resolutions from earlier transactions in the block.
+    pub fn set_trait_resolutions(&mut self, trait_resolutions: TraitResolutionCache) {
+        self.context.trait_resolutions = trait_resolutions;
+    }
+
+    /// Take back the trait resolutions this environment was seeded with, along with the
+    /// resolutions made by its committed contexts.  The caller must only merge the latter into
+    /// the former if the transaction they were made in commits.
+    pub fn take_trait_resolutions(&mut self) -> (TraitResolutionCache, TraitResolutionCache) {
+        let trait_resolutions = replace(&mut self.context.trait_resolutions, HashMap::new());
+        let committed = replace(
+            self.context
+                .staged_trait_resolutions
+                .first_mut()
+                .expect("BUG: no staged trait resolutions"),
+            HashMap::new(),
+        );
+        (trait_resolutions, committed)
+    }
+
     /// Destroys this environment, returning ownership of its database reference.
     ///  If the context wasn't top-level (i.e., it had uncommitted data), return None,
     ///   because the database is not guaranteed to be in a sane state.
@@ -961,7 +1004,7 @@ impl<'a, 'b> Environment<'a, 'b> {
 
             match res {
                 Ok(value) => {
-                    handle_contract_call_special_cases(&mut self.global_context, self.sender.as_ref(), contract_identifier, tx_name, &value)?;
+                    handle_contract_call_special_cases(&mut self.global_context, self.sender.as_ref(), contract_identifier, tx_name, &args, &value)?;
                     Ok(value)
                 },
                 Err(e) => Err(e)
@@ -1349,9 +1392,36 @@ impl<'a> GlobalContext<'a> {
             asset_maps: Vec::new(),
             event_batches: Vec::new(),
             mainnet,
+            trait_resolutions: HashMap::new(),
+            staged_trait_resolutions: vec![HashMap::new()],
         }
     }
 
+    /// Look up a trait resolution made in this context or by an earlier transaction.
+    pub fn get_trait_resolution(
+        &self,
+        key: &(QualifiedContractIdentifier, TraitIdentifier, ClarityName),
+    ) -> Option<&TraitResolution> {
+        self.staged_trait_resolutions
+            .iter()
+            .rev()
+            .find_map(|staged| staged.get(key))
+            .or_else(|| self.trait_resolutions.get(key))
+    }
+
+    /// Stage a trait resolution in the current context.  It is discarded if the context rolls
+    /// back.
+    pub fn stage_trait_resolution(
+        &mut self,
+        key: (QualifiedContractIdentifier, TraitIdentifier, ClarityName),
+        resolution: TraitResolution,
+    ) {
+        self.staged_trait_resolutions
+            .last_mut()
+            .expect("BUG: no staged trait resolutions")
+            .insert(key, resolution);
+    }
+
     pub fn is_top_level(&self) -> bool {
         self.asset_maps.len() == 0
     }
@@ -1424,6 +1494,7 @@ impl<'a> GlobalContext<'a> {
         self.database.begin();
         let read_only = self.is_read_only();
         self.read_only.push(read_only);
+        self.staged_trait_resolutions.push(HashMap::new());
     }
 
     pub fn begin_read_only(&mut self) {
@@ -1431,6 +1502,7 @@ impl<'a> GlobalContext<'a> {
         self.event_batches.push(EventBatch::new());
         self.database.begin();
         self.read_only.push(true);
+        self.staged_trait_resolutions.push(HashMap::new());
     }
 
     pub fn commit(&mut self) -> Result<(Option<AssetMap>, Option<EventBatch>)> {
@@ -1444,6 +1516,10 @@ impl<'a> GlobalContext<'a> {
             .event_batches
             .pop()
             .expect("ERROR: Committed non-nested context.");
+        let trait_resolutions = self
+            .staged_trait_resolutions
+            .pop()
+            .expect("ERROR: Committed non-nested context.");
 
         let out_map = match self.asset_maps.last_mut() {
             Some(tail_back) => {
@@ -1464,6 +1540,11 @@ impl<'a> GlobalContext<'a> {
             None => Some(event_batch),
         };
 
+        self.staged_trait_resolutions
+            .last_mut()
+            .expect("BUG: no staged trait resolutions")
+            .extend(trait_resolutions);
+
         self.database.commit();
         Ok((out_map, out_batch))
     }
@@ -1475,6 +1556,8 @@ impl<'a> GlobalContext<'a> {
         assert!(popped.is_some());
         let popped = self.event_batches.pop();
         assert!(popped.is_some());
+        let popped = self.staged_trait_resolutions.pop();
+        assert!(popped.is_some());
 
         self.database.roll_back();
     }
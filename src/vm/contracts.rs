@@ -23,7 +23,7 @@ use vm::representations::SymbolicExpression;
 use vm::types::QualifiedContractIdentifier;
 use vm::{apply, eval_all, Value};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Contract {
     pub contract_context: ContractContext,
 }
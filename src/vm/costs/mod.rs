@@ -23,6 +23,7 @@ use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
 use serde::{Deserialize, Serialize};
 
 use crate::util::boot::boot_code_id;
+use vm::analysis::ContractAnalysis;
 use vm::ast::ContractAST;
 use vm::contexts::{ContractContext, Environment, GlobalContext, OwnedEnvironment};
 use vm::costs::cost_functions::ClarityCostFunction;
@@ -278,6 +279,41 @@ pub enum CostErrors {
     CostContractLoadFailure,
 }
 
+/// Check whether or not a contract can be installed as the boot `costs` contract.  Private and
+///  test networks can replace the default cost schedule at genesis, but every Clarity cost
+///  function must still be defined as a read-only or private function that takes a single
+///  `uint` and returns a cost tuple, and the contract must pass the arithmetic-only checks.
+pub fn check_boot_costs_contract(analysis: &ContractAnalysis) -> std::result::Result<(), String> {
+    if !analysis.is_cost_contract_eligible {
+        return Err("costs contract uses non-arithmetic or otherwise illegal operations".into());
+    }
+    for cost_function in ClarityCostFunction::ALL.iter() {
+        let function_name = ClarityName::try_from(cost_function.get_name())
+            .expect("BUG: cost function name is not a valid Clarity name");
+        match analysis
+            .read_only_function_types
+            .get(&function_name)
+            .or_else(|| analysis.private_function_types.get(&function_name))
+        {
+            Some(FunctionType::Fixed(function_type)) => {
+                if function_type.args.len() != 1
+                    || function_type.args[0].signature != TypeSignature::UIntType
+                    || function_type.returns != *COST_TUPLE_TYPE_SIGNATURE
+                {
+                    return Err(format!(
+                        "cost function '{}' must take a single uint and return a cost tuple",
+                        cost_function
+                    ));
+                }
+            }
+            _ => {
+                return Err(format!("cost function '{}' is not defined", cost_function));
+            }
+        }
+    }
+    Ok(())
+}
+
 fn load_state_summary(mainnet: bool, clarity_db: &mut ClarityDatabase) -> Result<CostStateSummary> {
     let cost_voting_contract = boot_code_id("cost-voting", mainnet);
 
@@ -26,6 +26,9 @@ use util::hash::{to_hex, Hash160, Sha256Sum, Sha512Trunc256Sum};
 use vm::analysis::{AnalysisDatabase, ContractAnalysis};
 use vm::contracts::Contract;
 use vm::costs::CostOverflowingMath;
+use vm::database::contract_cache::{
+    cache_analysis, cache_contract, get_cached_analysis, get_cached_contract, invalidate_contract,
+};
 use vm::database::structures::{
     ClarityDeserializable, ClaritySerializable, ContractMetadata, DataMapMetadata,
     DataVariableMetadata, FungibleTokenMetadata, NonFungibleTokenMetadata, STXBalance,
@@ -352,6 +355,17 @@ impl<'a> ClarityDatabase<'a> {
             .flatten()
     }
 
+    /// Length of a contract's source code in bytes, read without loading the source
+    pub fn get_contract_src_size(
+        &mut self,
+        contract_identifier: &QualifiedContractIdentifier,
+    ) -> Option<u64> {
+        let key = ClarityDatabase::make_metadata_key(StoreType::Contract, "contract-size");
+        self.fetch_metadata(contract_identifier, &key)
+            .ok()
+            .flatten()
+    }
+
     pub fn set_metadata(
         &mut self,
         contract_identifier: &QualifiedContractIdentifier,
@@ -413,12 +427,23 @@ impl<'a> ClarityDatabase<'a> {
         &mut self,
         contract_identifier: &QualifiedContractIdentifier,
     ) -> Option<ContractAnalysis> {
-        self.store
+        let cache_key = self.store.get_contract_cache_key(contract_identifier);
+        if let Some(analysis) = cache_key.as_ref().and_then(get_cached_analysis) {
+            return Some(analysis);
+        }
+
+        let analysis = self
+            .store
             .get_metadata(contract_identifier, AnalysisDatabase::storage_key())
             // treat NoSuchContract error thrown by get_metadata as an Option::None --
             //    the analysis will propagate that as a CheckError anyways.
             .ok()?
-            .map(|x| ContractAnalysis::deserialize(&x))
+            .map(|x| ContractAnalysis::deserialize(&x))?;
+
+        if let Some(cache_key) = cache_key {
+            cache_analysis(cache_key, &analysis);
+        }
+        Some(analysis)
     }
 
     pub fn get_contract_size(
@@ -462,6 +487,7 @@ impl<'a> ClarityDatabase<'a> {
     ) {
         let key = ClarityDatabase::make_metadata_key(StoreType::Contract, "contract");
         self.insert_metadata(contract_identifier, &key, &contract);
+        invalidate_contract(contract_identifier);
     }
 
     pub fn has_contract(&mut self, contract_identifier: &QualifiedContractIdentifier) -> bool {
@@ -473,10 +499,19 @@ impl<'a> ClarityDatabase<'a> {
         &mut self,
         contract_identifier: &QualifiedContractIdentifier,
     ) -> Result<Contract> {
+        let cache_key = self.store.get_contract_cache_key(contract_identifier);
+        if let Some(contract) = cache_key.as_ref().and_then(get_cached_contract) {
+            return Ok(contract);
+        }
+
         let key = ClarityDatabase::make_metadata_key(StoreType::Contract, "contract");
-        let data = self.fetch_metadata(contract_identifier, &key)?.expect(
+        let data: Contract = self.fetch_metadata(contract_identifier, &key)?.expect(
             "Failed to read non-consensus contract metadata, even though contract exists in MARF.",
         );
+
+        if let Some(cache_key) = cache_key {
+            cache_contract(cache_key, &data);
+        }
         Ok(data)
     }
 
@@ -0,0 +1,202 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Process-wide caches of deserialized contracts and contract analyses.
+//!
+//! Contract metadata is stored in the side store, keyed by the block that deployed the contract.
+//! Loading it means reading it out of sqlite and deserializing it from JSON on every contract
+//! call, which dominates the cost of calling into large contracts.  Since a contract's metadata
+//! is fully determined by its identifier, its source, and the block that deployed it, we can keep
+//! the deserialized structures around, keyed by (deploying block, contract, source hash).  A
+//! re-deploy of the same contract in a different fork (or in a new instance of a mined block)
+//! produces a different key, so cached entries never go stale.  Entries are still dropped
+//! whenever a contract is (re-)inserted, so a cache never outlives the data it was read from.
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::sync::Mutex;
+
+use util::hash::Sha512Trunc256Sum;
+use vm::analysis::ContractAnalysis;
+use vm::contracts::Contract;
+use vm::types::QualifiedContractIdentifier;
+
+use crate::types::chainstate::StacksBlockId;
+
+/// Maximum number of contracts (and, separately, contract analyses) to keep in memory.
+pub const CONTRACT_CACHE_SIZE: usize = 256;
+
+/// Identifies a particular deployment of a contract: the block that deployed it, the contract
+/// identifier, and the hash of its source code.
+pub type ContractCacheKey = (
+    StacksBlockId,
+    QualifiedContractIdentifier,
+    Sha512Trunc256Sum,
+);
+
+/// A bounded least-recently-used map.
+pub struct LruCache<K: Hash + Eq + Clone, V: Clone> {
+    capacity: usize,
+    /// key --> (value, last-used counter)
+    entries: HashMap<K, (V, u64)>,
+    /// last-used counter --> key, so the least-recently-used key comes first
+    order: BTreeMap<u64, K>,
+    counter: u64,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> LruCache<K, V> {
+    pub fn new(capacity: usize) -> LruCache<K, V> {
+        LruCache {
+            capacity,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            counter: 0,
+        }
+    }
+
+    fn next_counter(&mut self) -> u64 {
+        self.counter += 1;
+        self.counter
+    }
+
+    /// Look up a value, marking it as most-recently used
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        let next = self.next_counter();
+        let (value, last_used) = self.entries.get_mut(key)?;
+        self.order.remove(&*last_used);
+        *last_used = next;
+        self.order.insert(next, key.clone());
+        Some(value.clone())
+    }
+
+    /// Insert a value, evicting the least-recently used value if the cache is full
+    pub fn insert(&mut self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        let next = self.next_counter();
+        if let Some((_, last_used)) = self.entries.insert(key.clone(), (value, next)) {
+            self.order.remove(&last_used);
+        }
+        self.order.insert(next, key);
+
+        while self.entries.len() > self.capacity {
+            let oldest = match self.order.keys().next() {
+                Some(oldest) => *oldest,
+                None => break,
+            };
+            if let Some(evicted) = self.order.remove(&oldest) {
+                self.entries.remove(&evicted);
+            }
+        }
+    }
+
+    /// Drop every entry whose key matches the given predicate
+    pub fn retain<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&K) -> bool,
+    {
+        let order = &mut self.order;
+        self.entries.retain(|key, (_, last_used)| {
+            if keep(key) {
+                true
+            } else {
+                order.remove(&*last_used);
+                false
+            }
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+lazy_static! {
+    static ref CONTRACT_CACHE: Mutex<LruCache<ContractCacheKey, Contract>> =
+        Mutex::new(LruCache::new(CONTRACT_CACHE_SIZE));
+    static ref ANALYSIS_CACHE: Mutex<LruCache<ContractCacheKey, ContractAnalysis>> =
+        Mutex::new(LruCache::new(CONTRACT_CACHE_SIZE));
+}
+
+pub fn get_cached_contract(key: &ContractCacheKey) -> Option<Contract> {
+    CONTRACT_CACHE.lock().ok()?.get(key)
+}
+
+pub fn cache_contract(key: ContractCacheKey, contract: &Contract) {
+    if let Ok(mut cache) = CONTRACT_CACHE.lock() {
+        cache.insert(key, contract.clone());
+    }
+}
+
+pub fn get_cached_analysis(key: &ContractCacheKey) -> Option<ContractAnalysis> {
+    ANALYSIS_CACHE.lock().ok()?.get(key)
+}
+
+pub fn cache_analysis(key: ContractCacheKey, analysis: &ContractAnalysis) {
+    if let Ok(mut cache) = ANALYSIS_CACHE.lock() {
+        cache.insert(key, analysis.clone());
+    }
+}
+
+/// Drop all cached data for the given contract.  Called whenever the contract is deployed.
+pub fn invalidate_contract(contract_identifier: &QualifiedContractIdentifier) {
+    if let Ok(mut cache) = CONTRACT_CACHE.lock() {
+        cache.retain(|(_, id, _)| id != contract_identifier);
+    }
+    if let Ok(mut cache) = ANALYSIS_CACHE.lock() {
+        cache.retain(|(_, id, _)| id != contract_identifier);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_lru_cache_eviction() {
+        let mut cache = LruCache::new(3);
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        cache.insert(3, "c");
+        assert_eq!(cache.len(), 3);
+
+        // touch 1, so 2 is now the least-recently used
+        assert_eq!(cache.get(&1), Some("a"));
+        cache.insert(4, "d");
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&1), Some("a"));
+        assert_eq!(cache.get(&3), Some("c"));
+        assert_eq!(cache.get(&4), Some("d"));
+
+        // re-inserting replaces the value and refreshes the entry
+        cache.insert(1, "aa");
+        cache.insert(5, "e");
+        assert_eq!(cache.get(&1), Some("aa"));
+        assert_eq!(cache.get(&3), None);
+        assert_eq!(cache.order.len(), cache.len());
+
+        cache.retain(|k| *k != 4);
+        assert_eq!(cache.get(&4), None);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.order.len(), 2);
+
+        let mut empty = LruCache::new(0);
+        empty.insert(1, "a");
+        assert_eq!(empty.get(&1), None);
+    }
+}
@@ -19,6 +19,7 @@ use std::{clone::Clone, cmp::Eq, hash::Hash};
 
 use util::hash::Sha512Trunc256Sum;
 use vm::database::clarity_store::make_contract_hash_key;
+use vm::database::contract_cache::ContractCacheKey;
 use vm::errors::InterpreterResult as Result;
 use vm::types::{QualifiedContractIdentifier, TypeSignature};
 use vm::Value;
@@ -471,6 +472,21 @@ impl<'a> RollbackWrapper<'a> {
         }
     }
 
+    /// Get the key under which this contract's deserialized metadata may be cached.  Returns
+    /// None if the contract does not exist, or if it was deployed in a pending (uncommitted)
+    /// context, since its metadata could still be rolled back.
+    pub fn get_contract_cache_key(
+        &mut self,
+        contract: &QualifiedContractIdentifier,
+    ) -> Option<ContractCacheKey> {
+        let commitment_key = make_contract_hash_key(contract);
+        if self.query_pending_data && self.lookup_map.contains_key(&commitment_key) {
+            return None;
+        }
+        let (bhh, contract_hash) = self.store.get_contract_hash(contract).ok()?;
+        Some((bhh, contract.clone(), contract_hash))
+    }
+
     pub fn has_entry(&mut self, key: &str) -> bool {
         self.stack
             .last()
@@ -30,6 +30,7 @@ pub use self::structures::{
 
 mod clarity_db;
 pub mod clarity_store;
+pub mod contract_cache;
 mod key_value_wrapper;
 mod sqlite;
 mod structures;
@@ -21,6 +21,7 @@ use vm::functions::tuples;
 
 use crate::types::chainstate::StacksBlockId;
 use vm::callables::DefineType;
+use vm::contexts::TraitResolution;
 use vm::costs::{
     constants as cost_constants, cost_functions, runtime_cost, CostTracker, MemoryConsumer,
 };
@@ -28,16 +29,89 @@ use vm::errors::{
     check_argument_count, check_arguments_at_least, CheckErrors, InterpreterError,
     InterpreterResult as Result, RuntimeErrorType,
 };
-use vm::representations::{SymbolicExpression, SymbolicExpressionType};
+use vm::representations::{ClarityName, SymbolicExpression, SymbolicExpressionType};
 use vm::types::{
-    BlockInfoProperty, BuffData, OptionalData, PrincipalData, SequenceData, TypeSignature, Value,
-    BUFF_32,
+    BlockInfoProperty, BuffData, OptionalData, PrincipalData, QualifiedContractIdentifier,
+    SequenceData, TraitIdentifier, TypeSignature, Value, BUFF_32,
 };
 use vm::{eval, Environment, LocalContext};
 
 use vm::costs::cost_functions::ClarityCostFunction;
 use vm::functions::special::handle_contract_call_special_cases;
 
+/// Check that `contract_identifier` can be called through `trait_identifier` with
+/// `function_name`, and determine what (if anything) its return value must be checked against.
+fn resolve_trait_target(
+    env: &mut Environment,
+    contract_identifier: &QualifiedContractIdentifier,
+    trait_identifier: &TraitIdentifier,
+    function_name: &ClarityName,
+) -> Result<TraitResolution> {
+    let contract_to_check = env
+        .global_context
+        .database
+        .get_contract(contract_identifier)
+        .map_err(|_e| CheckErrors::NoSuchContract(contract_identifier.to_string()))?;
+    let contract_context_to_check = contract_to_check.contract_context;
+
+    // Attempt to short circuit the dynamic dispatch checks:
+    // If the contract is explicitely implementing the trait with `impl-trait`,
+    // then we can simply rely on the analysis performed at publish time.
+    if contract_context_to_check.is_explicitly_implementing_trait(trait_identifier) {
+        return Ok(TraitResolution::Explicit);
+    }
+
+    let trait_name = trait_identifier.name.to_string();
+
+    // Retrieve, from the trait definition, the expected method signature
+    let contract_defining_trait = env
+        .global_context
+        .database
+        .get_contract(&trait_identifier.contract_identifier)
+        .map_err(|_e| {
+            CheckErrors::NoSuchContract(trait_identifier.contract_identifier.to_string())
+        })?;
+    let contract_context_defining_trait = contract_defining_trait.contract_context;
+
+    // Retrieve the function that will be invoked
+    let function_to_check = contract_context_to_check
+        .lookup_function(function_name)
+        .ok_or(CheckErrors::BadTraitImplementation(
+            trait_name.clone(),
+            function_name.to_string(),
+        ))?;
+
+    // Check read/write compatibility
+    if env.global_context.is_read_only() {
+        return Err(CheckErrors::TraitBasedContractCallInReadOnly.into());
+    }
+
+    // Check visibility
+    if function_to_check.define_type == DefineType::Private {
+        return Err(CheckErrors::NoSuchPublicFunction(
+            contract_identifier.to_string(),
+            function_name.to_string(),
+        )
+        .into());
+    }
+
+    function_to_check
+        .check_trait_expectations(&contract_context_defining_trait, trait_identifier)?;
+
+    // Retrieve the expected method signature
+    let constraining_trait = contract_context_defining_trait
+        .lookup_trait_definition(&trait_name)
+        .ok_or(CheckErrors::TraitReferenceUnknown(trait_name.clone()))?;
+    let expected_sig =
+        constraining_trait
+            .get(function_name)
+            .ok_or(CheckErrors::TraitMethodUnknown(
+                trait_name,
+                function_name.to_string(),
+            ))?;
+    Ok(TraitResolution::Conforming(expected_sig.returns.clone()))
+}
+
 pub fn special_contract_call(
     args: &[SymbolicExpression],
     env: &mut Environment,
@@ -78,72 +152,46 @@ pub fn special_contract_call(
                         .into());
                     }
 
-                    let contract_to_check = env
+                    let resolution_key = (
+                        contract_identifier.clone(),
+                        trait_identifier.clone(),
+                        function_name.clone(),
+                    );
+                    let cached_resolution = env
                         .global_context
-                        .database
-                        .get_contract(contract_identifier)
-                        .map_err(|_e| {
-                            CheckErrors::NoSuchContract(contract_identifier.to_string())
-                        })?;
-                    let contract_context_to_check = contract_to_check.contract_context;
-
-                    // Attempt to short circuit the dynamic dispatch checks:
-                    // If the contract is explicitely implementing the trait with `impl-trait`,
-                    // then we can simply rely on the analysis performed at publish time.
-                    if contract_context_to_check.is_explicitly_implementing_trait(&trait_identifier)
-                    {
-                        (contract_identifier, None)
-                    } else {
-                        let trait_name = trait_identifier.name.to_string();
-
-                        // Retrieve, from the trait definition, the expected method signature
-                        let contract_defining_trait = env
-                            .global_context
-                            .database
-                            .get_contract(&trait_identifier.contract_identifier)
-                            .map_err(|_e| {
-                                CheckErrors::NoSuchContract(
-                                    trait_identifier.contract_identifier.to_string(),
-                                )
-                            })?;
-                        let contract_context_defining_trait =
-                            contract_defining_trait.contract_context;
-
-                        // Retrieve the function that will be invoked
-                        let function_to_check = contract_context_to_check
-                            .lookup_function(function_name)
-                            .ok_or(CheckErrors::BadTraitImplementation(
-                                trait_name.clone(),
-                                function_name.to_string(),
-                            ))?;
-
-                        // Check read/write compatibility
-                        if env.global_context.is_read_only() {
-                            return Err(CheckErrors::TraitBasedContractCallInReadOnly.into());
+                        .get_trait_resolution(&resolution_key)
+                        .cloned();
+                    let resolution = match cached_resolution {
+                        Some(resolution) => {
+                            // already resolved this target earlier in the block, but we still
+                            // need to check read/write compatibility in this context
+                            if let TraitResolution::Conforming(_) = resolution {
+                                if env.global_context.is_read_only() {
+                                    return Err(
+                                        CheckErrors::TraitBasedContractCallInReadOnly.into()
+                                    );
+                                }
+                            }
+                            resolution
                         }
-
-                        // Check visibility
-                        if function_to_check.define_type == DefineType::Private {
-                            return Err(CheckErrors::NoSuchPublicFunction(
-                                contract_identifier.to_string(),
-                                function_name.to_string(),
-                            )
-                            .into());
+                        None => {
+                            let resolution = resolve_trait_target(
+                                env,
+                                contract_identifier,
+                                trait_identifier,
+                                function_name,
+                            )?;
+                            env.global_context
+                                .stage_trait_resolution(resolution_key, resolution.clone());
+                            resolution
                         }
+                    };
 
-                        function_to_check.check_trait_expectations(
-                            &contract_context_defining_trait,
-                            &trait_identifier,
-                        )?;
-
-                        // Retrieve the expected method signature
-                        let constraining_trait = contract_context_defining_trait
-                            .lookup_trait_definition(&trait_name)
-                            .ok_or(CheckErrors::TraitReferenceUnknown(trait_name.clone()))?;
-                        let expected_sig = constraining_trait.get(function_name).ok_or(
-                            CheckErrors::TraitMethodUnknown(trait_name, function_name.to_string()),
-                        )?;
-                        (contract_identifier, Some(expected_sig.returns.clone()))
+                    match resolution {
+                        TraitResolution::Explicit => (contract_identifier, None),
+                        TraitResolution::Conforming(returns_type_signature) => {
+                            (contract_identifier, Some(returns_type_signature))
+                        }
                     }
                 }
                 _ => return Err(CheckErrors::ContractCallExpectName.into()),
@@ -30,8 +30,13 @@ use vm::types::{
 
 use crate::types::chainstate::StacksMicroblockHeader;
 use crate::util::boot::boot_code_id;
+use chainstate::stacks::boot::pox_addr_tuple_to_b58;
 use chainstate::stacks::db::StacksChainState;
-use chainstate::stacks::events::{STXEventType, STXLockEventData, StacksTransactionEvent};
+use chainstate::stacks::events::{
+    PoxAddressEventData, PoxDelegateStackStxEventData, PoxDelegateStxEventData, PoxEventType,
+    PoxStackAggregationCommitEventData, PoxStackStxEventData, PoxUnlockEventData, STXEventType,
+    STXLockEventData, StacksTransactionEvent,
+};
 
 use util::hash::Hash160;
 
@@ -70,11 +75,123 @@ fn parse_pox_stacking_result(
     }
 }
 
+/// Decode a PoX address tuple argument for an event
+fn pox_addr_event_data(mainnet: bool, pox_addr: &Value) -> PoxAddressEventData {
+    let tuple_data = pox_addr.clone().expect_tuple();
+    let version = tuple_data
+        .get("version")
+        .expect("FATAL: no 'version' field in pox-addr")
+        .to_owned()
+        .expect_buff_padded(1, 0)[0];
+    let hashbytes = tuple_data
+        .get("hashbytes")
+        .expect("FATAL: no 'hashbytes' field in pox-addr")
+        .to_owned()
+        .expect_buff(20);
+    PoxAddressEventData {
+        version,
+        hashbytes,
+        address: pox_addr_tuple_to_b58(mainnet, &tuple_data),
+    }
+}
+
+/// Decode the arguments of a successful stack-stx or delegate-stack-stx call into its PoX
+/// event.  `sender` is the delegator, for delegate-stack-stx.
+fn pox_lock_event(
+    mainnet: bool,
+    sender_opt: Option<&PrincipalData>,
+    function_name: &str,
+    args: &[Value],
+    stacker: &PrincipalData,
+    locked_amount: u128,
+    unlock_height: u64,
+) -> Option<PoxEventType> {
+    if function_name == "stack-stx" {
+        // (stack-stx (amount-ustx uint) (pox-addr tuple) (start-burn-ht uint) (lock-period uint))
+        if args.len() != 4 {
+            return None;
+        }
+        Some(PoxEventType::StackStx(PoxStackStxEventData {
+            stacker: stacker.clone(),
+            locked_amount,
+            pox_addr: pox_addr_event_data(mainnet, &args[1]),
+            start_burn_height: args[2].clone().expect_u128(),
+            lock_period: args[3].clone().expect_u128(),
+            unlock_height,
+        }))
+    } else {
+        // (delegate-stack-stx (stacker principal) (amount-ustx uint) (pox-addr tuple)
+        //                     (start-burn-ht uint) (lock-period uint))
+        if args.len() != 5 {
+            return None;
+        }
+        Some(PoxEventType::DelegateStackStx(
+            PoxDelegateStackStxEventData {
+                delegator: sender_opt?.clone(),
+                stacker: stacker.clone(),
+                locked_amount,
+                pox_addr: pox_addr_event_data(mainnet, &args[2]),
+                start_burn_height: args[3].clone().expect_u128(),
+                lock_period: args[4].clone().expect_u128(),
+                unlock_height,
+            },
+        ))
+    }
+}
+
+/// Decode the arguments of a successful delegate-stx or stack-aggregation-commit call into its
+/// PoX event.  Neither locks any tokens, so this is all there is to do for them.
+fn pox_delegation_event(
+    mainnet: bool,
+    sender_opt: Option<&PrincipalData>,
+    function_name: &str,
+    args: &[Value],
+) -> Option<PoxEventType> {
+    // both functions act on behalf of tx-sender
+    let sender = sender_opt?.clone();
+    if function_name == "delegate-stx" {
+        // (delegate-stx (amount-ustx uint) (delegate-to principal)
+        //               (until-burn-ht (optional uint)) (pox-addr (optional tuple)))
+        if args.len() != 4 {
+            return None;
+        }
+        Some(PoxEventType::DelegateStx(PoxDelegateStxEventData {
+            stacker: sender,
+            amount_ustx: args[0].clone().expect_u128(),
+            delegate_to: args[1].clone().expect_principal(),
+            until_burn_height: args[2].clone().expect_optional().map(|ht| ht.expect_u128()),
+            pox_addr: args[3]
+                .clone()
+                .expect_optional()
+                .map(|addr| pox_addr_event_data(mainnet, &addr)),
+        }))
+    } else {
+        // (stack-aggregation-commit (pox-addr tuple) (reward-cycle uint))
+        if args.len() != 2 {
+            return None;
+        }
+        Some(PoxEventType::StackAggregationCommit(
+            PoxStackAggregationCommitEventData {
+                delegator: sender,
+                pox_addr: pox_addr_event_data(mainnet, &args[0]),
+                reward_cycle: args[1].clone().expect_u128(),
+            },
+        ))
+    }
+}
+
+fn push_pox_event(global_context: &mut GlobalContext, event: PoxEventType) {
+    if let Some(batch) = global_context.event_batches.last_mut() {
+        batch.events.push(StacksTransactionEvent::PoxEvent(event));
+    }
+}
+
 /// Handle special cases when calling into the PoX API contract
 fn handle_pox_api_contract_call(
     global_context: &mut GlobalContext,
-    _sender_opt: Option<&PrincipalData>,
+    sender_opt: Option<&PrincipalData>,
     function_name: &str,
+    args: &[Value],
     value: &Value,
 ) -> Result<()> {
     if function_name == "stack-stx" || function_name == "delegate-stack-stx" {
@@ -94,6 +211,20 @@ fn handle_pox_api_contract_call(
 
         match parse_pox_stacking_result(value) {
             Ok((stacker, locked_amount, unlock_height)) => {
+                // if the stacker's previous lock has expired, locking again will fold those
+                // tokens back into their unlocked balance.  Report that as an unlock.
+                let expired_lock = {
+                    let snapshot = global_context.database.get_stx_balance_snapshot(&stacker);
+                    if snapshot.has_unlockable_tokens() {
+                        Some((
+                            snapshot.balance().amount_locked,
+                            snapshot.balance().unlock_height,
+                        ))
+                    } else {
+                        None
+                    }
+                };
+
                 // if this fails, then there's a bug in the contract (since it already does
                 // the necessary checks)
                 match StacksChainState::pox_lock(
@@ -103,6 +234,25 @@ fn handle_pox_api_contract_call(
                     unlock_height as u64,
                 ) {
                     Ok(_) => {
+                        if let Some((unlocked_amount, expired_unlock_height)) = expired_lock {
+                            push_pox_event(
+                                global_context,
+                                PoxEventType::Unlock(PoxUnlockEventData {
+                                    stacker: stacker.clone(),
+                                    unlocked_amount,
+                                    unlock_height: expired_unlock_height,
+                                }),
+                            );
+                        }
+                        let pox_event = pox_lock_event(
+                            global_context.mainnet,
+                            sender_opt,
+                            function_name,
+                            args,
+                            &stacker,
+                            locked_amount,
+                            unlock_height,
+                        );
                         if let Some(batch) = global_context.event_batches.last_mut() {
                             batch.events.push(StacksTransactionEvent::STXEvent(
                                 STXEventType::STXLockEvent(STXLockEventData {
@@ -112,6 +262,9 @@ fn handle_pox_api_contract_call(
                                 }),
                             ));
                         }
+                        if let Some(pox_event) = pox_event {
+                            push_pox_event(global_context, pox_event);
+                        }
                     }
                     Err(e) => {
                         panic!(
@@ -128,6 +281,17 @@ fn handle_pox_api_contract_call(
                 return Ok(());
             }
         }
+    } else if function_name == "delegate-stx" || function_name == "stack-aggregation-commit" {
+        if value.clone().expect_result().is_err() {
+            // nothing to do -- the function failed
+            return Ok(());
+        }
+        if let Some(pox_event) =
+            pox_delegation_event(global_context.mainnet, sender_opt, function_name, args)
+        {
+            push_pox_event(global_context, pox_event);
+        }
+        return Ok(());
     }
     // nothing to do
     Ok(())
@@ -139,10 +303,11 @@ pub fn handle_contract_call_special_cases(
     sender: Option<&PrincipalData>,
     contract_id: &QualifiedContractIdentifier,
     function_name: &str,
+    args: &[Value],
     result: &Value,
 ) -> Result<()> {
     if *contract_id == boot_code_id("pox", global_context.mainnet) {
-        return handle_pox_api_contract_call(global_context, sender, function_name, result);
+        return handle_pox_api_contract_call(global_context, sender, function_name, args, result);
     }
     // TODO: insert more special cases here, as needed
     Ok(())
@@ -29,6 +29,7 @@ pub mod contracts;
 pub mod ast;
 pub mod contexts;
 pub mod database;
+pub mod profiler;
 pub mod representations;
 
 mod callables;
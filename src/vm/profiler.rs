@@ -0,0 +1,495 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A call-graph profiler for Clarity.  Once started, it records, for each block the node
+//! processes, how much time and execution cost every contract function took, and which functions
+//! called which, so that operators can tell which contracts make blocks slow.
+//!
+//! Calls are only counted on the thread processing a block, between `begin_block()` and
+//! `finish_block()`: read-only calls served over RPC, and blocks the miner assembles, are not
+//! profiled.  The most recent block profiles are kept in memory for the admin RPC interface, and
+//! can also be appended to a file, one line of JSON per block.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use vm::costs::ExecutionCost;
+use vm::types::QualifiedContractIdentifier;
+
+use crate::types::chainstate::StacksBlockId;
+
+/// How many block profiles are kept in memory
+pub const MAX_RECENT_PROFILES: usize = 32;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref RECENT_PROFILES: Mutex<VecDeque<BlockProfile>> = Mutex::new(VecDeque::new());
+    static ref OUTPUT_PATH: Mutex<Option<String>> = Mutex::new(None);
+}
+
+thread_local! {
+    /// The profile of the block this thread is processing, if any
+    static COLLECTOR: RefCell<Option<Collector>> = RefCell::new(None);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct FunctionKey {
+    pub contract: String,
+    pub function: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionProfile {
+    pub contract: String,
+    pub function: String,
+    pub calls: u64,
+    /// microseconds spent in the function, including the functions it called
+    pub total_time_us: u64,
+    /// microseconds spent in the function itself
+    pub self_time_us: u64,
+    pub total_cost: ExecutionCost,
+    pub self_cost: ExecutionCost,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractProfile {
+    pub contract: String,
+    /// calls to the contract's functions, from any caller
+    pub calls: u64,
+    /// microseconds spent in the contract's own code
+    pub time_us: u64,
+    pub cost: ExecutionCost,
+}
+
+impl ContractProfile {
+    fn new(contract: &str) -> ContractProfile {
+        ContractProfile {
+            contract: contract.to_string(),
+            calls: 0,
+            time_us: 0,
+            cost: ExecutionCost::zero(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallEdge {
+    /// `None` for calls made by a transaction itself
+    pub caller: Option<FunctionKey>,
+    pub callee: FunctionKey,
+    pub calls: u64,
+    pub total_time_us: u64,
+    pub total_cost: ExecutionCost,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockProfile {
+    pub index_block_hash: StacksBlockId,
+    pub block_height: u64,
+    /// microseconds it took to process the block, contract calls or not
+    pub processing_time_us: u64,
+    /// slowest first
+    pub contracts: Vec<ContractProfile>,
+    /// slowest first
+    pub functions: Vec<FunctionProfile>,
+    /// slowest first
+    pub calls: Vec<CallEdge>,
+}
+
+#[derive(Debug, Clone)]
+struct CallStats {
+    calls: u64,
+    total_time: Duration,
+    self_time: Duration,
+    total_cost: ExecutionCost,
+    self_cost: ExecutionCost,
+}
+
+impl CallStats {
+    fn new() -> CallStats {
+        CallStats {
+            calls: 0,
+            total_time: Duration::from_secs(0),
+            self_time: Duration::from_secs(0),
+            total_cost: ExecutionCost::zero(),
+            self_cost: ExecutionCost::zero(),
+        }
+    }
+
+    fn add(
+        &mut self,
+        total_time: Duration,
+        self_time: Duration,
+        total_cost: &ExecutionCost,
+        self_cost: &ExecutionCost,
+    ) {
+        self.calls += 1;
+        self.total_time += total_time;
+        self.self_time += self_time;
+        let _ = self.total_cost.add(total_cost);
+        let _ = self.self_cost.add(self_cost);
+    }
+}
+
+/// A function call in progress
+struct Frame {
+    key: FunctionKey,
+    started: Instant,
+    cost_at_start: ExecutionCost,
+    child_time: Duration,
+    child_cost: ExecutionCost,
+}
+
+struct Collector {
+    started: Instant,
+    stack: Vec<Frame>,
+    functions: HashMap<FunctionKey, CallStats>,
+    edges: HashMap<(Option<FunctionKey>, FunctionKey), CallStats>,
+}
+
+/// Cost accrued from `start` to `end`.  Each dimension saturates at 0, in case the cost tracker
+/// was reset in between.
+fn cost_between(start: &ExecutionCost, end: &ExecutionCost) -> ExecutionCost {
+    ExecutionCost {
+        write_length: end.write_length.saturating_sub(start.write_length),
+        write_count: end.write_count.saturating_sub(start.write_count),
+        read_length: end.read_length.saturating_sub(start.read_length),
+        read_count: end.read_count.saturating_sub(start.read_count),
+        runtime: end.runtime.saturating_sub(start.runtime),
+    }
+}
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros() as u64
+}
+
+impl Collector {
+    fn new() -> Collector {
+        Collector {
+            started: Instant::now(),
+            stack: vec![],
+            functions: HashMap::new(),
+            edges: HashMap::new(),
+        }
+    }
+
+    fn enter(&mut self, key: FunctionKey, cost: &ExecutionCost) {
+        self.stack.push(Frame {
+            key,
+            started: Instant::now(),
+            cost_at_start: cost.clone(),
+            child_time: Duration::from_secs(0),
+            child_cost: ExecutionCost::zero(),
+        });
+    }
+
+    fn exit(&mut self, cost: &ExecutionCost) {
+        let frame = match self.stack.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        let total_time = frame.started.elapsed();
+        let self_time = total_time
+            .checked_sub(frame.child_time)
+            .unwrap_or(Duration::from_secs(0));
+        let total_cost = cost_between(&frame.cost_at_start, cost);
+        let self_cost = cost_between(&frame.child_cost, &total_cost);
+
+        self.functions
+            .entry(frame.key.clone())
+            .or_insert_with(CallStats::new)
+            .add(total_time, self_time, &total_cost, &self_cost);
+
+        let caller = self.stack.last().map(|parent| parent.key.clone());
+        self.edges
+            .entry((caller, frame.key))
+            .or_insert_with(CallStats::new)
+            .add(total_time, self_time, &total_cost, &self_cost);
+
+        if let Some(parent) = self.stack.last_mut() {
+            parent.child_time += total_time;
+            let _ = parent.child_cost.add(&total_cost);
+        }
+    }
+
+    fn into_profile(self, index_block_hash: &StacksBlockId, block_height: u64) -> BlockProfile {
+        let mut contracts: HashMap<String, ContractProfile> = HashMap::new();
+        let mut functions = vec![];
+        for (key, stats) in self.functions.into_iter() {
+            let contract = contracts
+                .entry(key.contract.clone())
+                .or_insert_with(|| ContractProfile::new(&key.contract));
+            contract.calls += stats.calls;
+            contract.time_us += micros(stats.self_time);
+            let _ = contract.cost.add(&stats.self_cost);
+
+            functions.push(FunctionProfile {
+                contract: key.contract,
+                function: key.function,
+                calls: stats.calls,
+                total_time_us: micros(stats.total_time),
+                self_time_us: micros(stats.self_time),
+                total_cost: stats.total_cost,
+                self_cost: stats.self_cost,
+            });
+        }
+        let mut contracts: Vec<ContractProfile> = contracts.into_iter().map(|(_, c)| c).collect();
+        let mut calls: Vec<CallEdge> = self
+            .edges
+            .into_iter()
+            .map(|((caller, callee), stats)| CallEdge {
+                caller,
+                callee,
+                calls: stats.calls,
+                total_time_us: micros(stats.total_time),
+                total_cost: stats.total_cost,
+            })
+            .collect();
+
+        contracts.sort_by(|a, b| b.time_us.cmp(&a.time_us));
+        functions.sort_by(|a, b| b.self_time_us.cmp(&a.self_time_us));
+        calls.sort_by(|a, b| b.total_time_us.cmp(&a.total_time_us));
+
+        BlockProfile {
+            index_block_hash: index_block_hash.clone(),
+            block_height,
+            processing_time_us: micros(self.started.elapsed()),
+            contracts,
+            functions,
+            calls,
+        }
+    }
+}
+
+/// Start profiling the blocks this node processes.  If `output_path` is given, each block's
+/// profile is also appended to it.
+pub fn start(output_path: Option<String>) {
+    *OUTPUT_PATH
+        .lock()
+        .expect("BUG: profiler output path lock poisoned") = output_path;
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Start recording the calls this thread makes, if the profiler is on.  Whatever an earlier
+/// block left unfinished is thrown away.
+pub fn begin_block() {
+    if !is_enabled() {
+        return;
+    }
+    COLLECTOR.with(|collector| *collector.borrow_mut() = Some(Collector::new()));
+}
+
+/// Throw away the calls recorded since `begin_block()`, for a block that turned out invalid
+pub fn discard_block() {
+    COLLECTOR.with(|collector| *collector.borrow_mut() = None);
+}
+
+/// Finish the profile of the block this thread has been processing, and keep it.  Returns None if
+/// no block was being profiled.
+pub fn finish_block(index_block_hash: &StacksBlockId, block_height: u64) -> Option<BlockProfile> {
+    let collector = COLLECTOR.with(|collector| collector.borrow_mut().take())?;
+    let profile = collector.into_profile(index_block_hash, block_height);
+
+    let output_path = OUTPUT_PATH
+        .lock()
+        .expect("BUG: profiler output path lock poisoned")
+        .clone();
+    if let Some(path) = output_path {
+        if let Err(e) = append_profile(&path, &profile) {
+            warn!("Failed to write Clarity profile to {}: {}", &path, &e);
+        }
+    }
+
+    let mut recent = RECENT_PROFILES
+        .lock()
+        .expect("BUG: recent profiles lock poisoned");
+    if recent.len() >= MAX_RECENT_PROFILES {
+        recent.pop_back();
+    }
+    recent.push_front(profile.clone());
+    Some(profile)
+}
+
+fn append_profile(path: &str, profile: &BlockProfile) -> Result<(), String> {
+    let mut line = serde_json::to_vec(profile).map_err(|e| e.to_string())?;
+    line.push(b'\n');
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    file.write_all(&line).map_err(|e| e.to_string())
+}
+
+/// The profiles of the most recently processed blocks, newest first
+pub fn get_recent_profiles() -> Vec<BlockProfile> {
+    RECENT_PROFILES
+        .lock()
+        .expect("BUG: recent profiles lock poisoned")
+        .iter()
+        .cloned()
+        .collect()
+}
+
+/// Is this thread recording calls?
+pub fn is_profiling() -> bool {
+    is_enabled() && COLLECTOR.with(|collector| collector.borrow().is_some())
+}
+
+/// A contract function was called.  `cost` is the cost tracker's total so far.
+pub fn enter_function(
+    contract: &QualifiedContractIdentifier,
+    function: &str,
+    cost: &ExecutionCost,
+) {
+    COLLECTOR.with(|collector| {
+        if let Some(collector) = collector.borrow_mut().as_mut() {
+            collector.enter(
+                FunctionKey {
+                    contract: contract.to_string(),
+                    function: function.to_string(),
+                },
+                cost,
+            );
+        }
+    });
+}
+
+/// The innermost function called returned, or failed.  `cost` is the cost tracker's total so far.
+pub fn exit_function(cost: &ExecutionCost) {
+    COLLECTOR.with(|collector| {
+        if let Some(collector) = collector.borrow_mut().as_mut() {
+            collector.exit(cost);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Turn the profiler back off, so that the other tests in this process don't profile
+    fn reset() {
+        ENABLED.store(false, Ordering::SeqCst);
+        *OUTPUT_PATH.lock().unwrap() = None;
+        RECENT_PROFILES.lock().unwrap().clear();
+    }
+
+    fn key(contract: &str, function: &str) -> FunctionKey {
+        FunctionKey {
+            contract: contract.to_string(),
+            function: function.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_block_profile_call_graph() {
+        start(None);
+        let block_id = StacksBlockId([0x11; 32]);
+
+        // nothing is recorded outside of a block
+        let outer = QualifiedContractIdentifier::local("outer").unwrap();
+        let inner = QualifiedContractIdentifier::local("inner").unwrap();
+        enter_function(&outer, "ignored", &ExecutionCost::zero());
+        assert!(!is_profiling());
+        assert!(finish_block(&block_id, 1).is_none());
+
+        begin_block();
+        assert!(is_profiling());
+
+        // outer.run calls inner.helper twice; then a transaction calls inner.helper directly
+        enter_function(&outer, "run", &ExecutionCost::runtime(100));
+        enter_function(&inner, "helper", &ExecutionCost::runtime(110));
+        exit_function(&ExecutionCost::runtime(120));
+        enter_function(&inner, "helper", &ExecutionCost::runtime(130));
+        exit_function(&ExecutionCost::runtime(150));
+        exit_function(&ExecutionCost::runtime(200));
+        enter_function(&inner, "helper", &ExecutionCost::runtime(300));
+        exit_function(&ExecutionCost::runtime(305));
+
+        let profile = finish_block(&block_id, 7).unwrap();
+        assert!(!is_profiling());
+        assert_eq!(profile.index_block_hash, block_id);
+        assert_eq!(profile.block_height, 7);
+
+        let run = profile
+            .functions
+            .iter()
+            .find(|f| f.function == "run")
+            .unwrap();
+        assert_eq!(run.calls, 1);
+        assert_eq!(run.total_cost, ExecutionCost::runtime(100));
+        assert_eq!(run.self_cost, ExecutionCost::runtime(70));
+        assert!(run.self_time_us <= run.total_time_us);
+
+        let helper = profile
+            .functions
+            .iter()
+            .find(|f| f.function == "helper")
+            .unwrap();
+        assert_eq!(helper.calls, 3);
+        assert_eq!(helper.total_cost, ExecutionCost::runtime(35));
+        assert_eq!(helper.self_cost, ExecutionCost::runtime(35));
+
+        let inner_contract = profile
+            .contracts
+            .iter()
+            .find(|c| c.contract == inner.to_string())
+            .unwrap();
+        assert_eq!(inner_contract.calls, 3);
+        assert_eq!(inner_contract.cost, ExecutionCost::runtime(35));
+
+        assert_eq!(profile.calls.len(), 3);
+        let nested = profile
+            .calls
+            .iter()
+            .find(|edge| edge.caller == Some(key(&outer.to_string(), "run")))
+            .unwrap();
+        assert_eq!(nested.callee, key(&inner.to_string(), "helper"));
+        assert_eq!(nested.calls, 2);
+        assert_eq!(nested.total_cost, ExecutionCost::runtime(30));
+        let direct: Vec<_> = profile
+            .calls
+            .iter()
+            .filter(|edge| edge.caller.is_none())
+            .collect();
+        assert_eq!(direct.len(), 2);
+
+        assert!(get_recent_profiles()
+            .iter()
+            .any(|p| p.index_block_hash == block_id));
+
+        // a discarded block leaves nothing behind
+        begin_block();
+        enter_function(&outer, "run", &ExecutionCost::zero());
+        discard_block();
+        assert!(!is_profiling());
+        assert!(finish_block(&block_id, 8).is_none());
+
+        reset();
+        assert!(!is_enabled());
+    }
+}
@@ -19,17 +19,21 @@ use crate::types::chainstate::StacksBlockHeader;
 use crate::types::chainstate::StacksBlockId;
 use crate::types::proof::ClarityMarfTrieId;
 use crate::util::boot::boot_code_id;
+use chainstate::stacks::boot::BOOT_CODE_COSTS;
 use chainstate::stacks::events::StacksTransactionEvent;
 use chainstate::stacks::index::storage::TrieFileStorage;
 use clarity_vm::clarity::ClarityInstance;
 use core::FIRST_BURNCHAIN_CONSENSUS_HASH;
 use core::FIRST_STACKS_BLOCK_HASH;
 use util::hash::hex_bytes;
+use vm::analysis::mem_type_check;
 use vm::contexts::Environment;
 use vm::contexts::{AssetMap, AssetMapEntry, GlobalContext, OwnedEnvironment};
 use vm::contracts::Contract;
 use vm::costs::cost_functions::ClarityCostFunction;
-use vm::costs::{ClarityCostFunctionReference, ExecutionCost, LimitedCostTracker};
+use vm::costs::{
+    check_boot_costs_contract, ClarityCostFunctionReference, ExecutionCost, LimitedCostTracker,
+};
 use vm::database::{ClarityDatabase, NULL_BURN_STATE_DB, NULL_HEADER_DB};
 use vm::errors::{CheckErrors, Error, RuntimeErrorType};
 use vm::execute as vm_execute;
@@ -250,6 +254,47 @@ fn test_all() {
     }
 }
 
+#[test]
+fn test_check_boot_costs_contract() {
+    let analysis = mem_type_check(BOOT_CODE_COSTS).unwrap().1;
+    check_boot_costs_contract(&analysis).unwrap();
+
+    // a more expensive schedule is fine
+    let expensive_costs = BOOT_CODE_COSTS.replacen(
+        "(define-read-only (cost_analysis_visit (n uint))\n    (runtime u1000))",
+        "(define-read-only (cost_analysis_visit (n uint))\n    (runtime u2000))",
+        1,
+    );
+    assert_ne!(expensive_costs, BOOT_CODE_COSTS);
+    let analysis = mem_type_check(&expensive_costs).unwrap().1;
+    check_boot_costs_contract(&analysis).unwrap();
+
+    // every cost function must be defined
+    let missing_costs = BOOT_CODE_COSTS.replacen(
+        "(define-read-only (cost_analysis_visit (n uint))",
+        "(define-read-only (cost_analysis_visit_renamed (n uint))",
+        1,
+    );
+    let analysis = mem_type_check(&missing_costs).unwrap().1;
+    assert!(check_boot_costs_contract(&analysis)
+        .unwrap_err()
+        .contains("cost_analysis_visit"));
+
+    // cost functions must return cost tuples
+    let bad_return_costs = BOOT_CODE_COSTS.replacen(
+        "(define-read-only (cost_analysis_visit (n uint))\n    (runtime u1000))",
+        "(define-read-only (cost_analysis_visit (n uint))\n    u1000)",
+        1,
+    );
+    let analysis = mem_type_check(&bad_return_costs).unwrap().1;
+    assert!(check_boot_costs_contract(&analysis).is_err());
+
+    // cost contracts must be arithmetic-only
+    let stateful_costs = format!("{}\n(define-data-var foo uint u1)", BOOT_CODE_COSTS);
+    let analysis = mem_type_check(&stateful_costs).unwrap().1;
+    assert!(check_boot_costs_contract(&analysis).is_err());
+}
+
 #[test]
 fn test_cost_contract_short_circuits() {
     let marf_kv = MarfedKV::temporary();
@@ -16,7 +16,7 @@
 
 use std::convert::TryInto;
 use vm::analysis::errors::CheckError;
-use vm::contexts::{Environment, GlobalContext, OwnedEnvironment};
+use vm::contexts::{Environment, GlobalContext, OwnedEnvironment, TraitResolution};
 use vm::errors::{CheckErrors, Error, RuntimeErrorType};
 use vm::execute as vm_execute;
 use vm::types::{PrincipalData, QualifiedContractIdentifier, ResponseData, TypeSignature, Value};
@@ -38,6 +38,7 @@ fn test_all() {
         test_reentrant_dynamic_dispatch,
         test_readwrite_dynamic_dispatch,
         test_readwrite_violation_dynamic_dispatch,
+        test_cached_trait_resolution_dynamic_dispatch,
         test_bad_call_with_trait,
         test_good_call_with_trait,
         test_good_call_2_with_trait,
@@ -659,6 +660,116 @@ fn test_readwrite_violation_dynamic_dispatch(owned_env: &mut OwnedEnvironment) {
     }
 }
 
+fn test_cached_trait_resolution_dynamic_dispatch(owned_env: &mut OwnedEnvironment) {
+    let dispatching_contract = "(define-trait trait-1 (
+            (get-1 (uint) (response uint uint))))
+        (define-public (wrapped-get-1 (contract <trait-1>))
+            (contract-call? contract get-1 u0))
+        (define-read-only (ro-wrapped-get-1 (contract <trait-1>))
+            (contract-call? contract get-1 u0))
+        (define-public (failing-wrapped-get-1 (contract <trait-1>))
+            (begin (try! (contract-call? contract get-1 u0)) (err u2)))";
+    let target_contract = "(define-public (get-1 (x uint)) (ok u1))";
+    let other_target_contract = "(define-public (get-1 (x uint)) (ok u3))";
+
+    let p1 = execute("'SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR");
+
+    {
+        let mut env = owned_env.get_exec_environment(None);
+        env.initialize_contract(
+            QualifiedContractIdentifier::local("dispatching-contract").unwrap(),
+            dispatching_contract,
+        )
+        .unwrap();
+        env.initialize_contract(
+            QualifiedContractIdentifier::local("target-contract").unwrap(),
+            target_contract,
+        )
+        .unwrap();
+        env.initialize_contract(
+            QualifiedContractIdentifier::local("other-target-contract").unwrap(),
+            other_target_contract,
+        )
+        .unwrap();
+    }
+
+    let target_contract = Value::from(PrincipalData::Contract(
+        QualifiedContractIdentifier::local("target-contract").unwrap(),
+    ));
+    let other_target_contract = Value::from(PrincipalData::Contract(
+        QualifiedContractIdentifier::local("other-target-contract").unwrap(),
+    ));
+
+    // a resolution made in a context that rolls back is not kept
+    {
+        let mut env = owned_env.get_exec_environment(Some(p1.clone().expect_principal()));
+        assert_eq!(
+            env.execute_contract(
+                &QualifiedContractIdentifier::local("dispatching-contract").unwrap(),
+                "failing-wrapped-get-1",
+                &symbols_from_values(vec![other_target_contract]),
+                false
+            )
+            .unwrap(),
+            Value::error(Value::UInt(2)).unwrap()
+        );
+    }
+
+    // resolve once, and then again from the cache
+    for _ in 0..2 {
+        let mut env = owned_env.get_exec_environment(Some(p1.clone().expect_principal()));
+        assert_eq!(
+            env.execute_contract(
+                &QualifiedContractIdentifier::local("dispatching-contract").unwrap(),
+                "wrapped-get-1",
+                &symbols_from_values(vec![target_contract.clone()]),
+                false
+            )
+            .unwrap(),
+            Value::okay(Value::UInt(1)).unwrap()
+        );
+    }
+
+    // resolutions are only handed back once the outermost context commits
+    let (_, trait_resolutions) = owned_env.take_trait_resolutions();
+    assert!(trait_resolutions.is_empty());
+    owned_env.commit().unwrap();
+
+    let (seeded_resolutions, trait_resolutions) = owned_env.take_trait_resolutions();
+    assert!(seeded_resolutions.is_empty());
+    assert_eq!(trait_resolutions.len(), 1);
+    assert_eq!(
+        trait_resolutions.keys().next().unwrap().0,
+        QualifiedContractIdentifier::local("target-contract").unwrap()
+    );
+    let resolution = trait_resolutions.values().next().unwrap().clone();
+    assert_eq!(
+        resolution,
+        TraitResolution::Conforming(
+            TypeSignature::new_response(TypeSignature::UIntType, TypeSignature::UIntType).unwrap()
+        )
+    );
+    owned_env.set_trait_resolutions(trait_resolutions);
+    owned_env.begin();
+
+    // a cached resolution still has to respect the read-only context
+    {
+        let mut env = owned_env.get_exec_environment(Some(p1.clone().expect_principal()));
+        let err_result = env
+            .execute_contract(
+                &QualifiedContractIdentifier::local("dispatching-contract").unwrap(),
+                "ro-wrapped-get-1",
+                &symbols_from_values(vec![target_contract]),
+                false,
+            )
+            .unwrap_err();
+        match err_result {
+            Error::Unchecked(CheckErrors::TraitBasedContractCallInReadOnly) => {}
+            _ => panic!("{:?}", err_result),
+        }
+    }
+}
+
 fn test_bad_call_with_trait(owned_env: &mut OwnedEnvironment) {
     // This set of contracts should be working in this context,
     // the analysis is not being performed.
@@ -648,6 +648,147 @@ impl Value {
     }
 }
 
+/// Canonical JSON encoding of Clarity values, for RPC clients that don't have a Clarity
+/// serialization library.  Every value is encoded as a `{"type": ..., "value": ...}` object, so
+/// it can be decoded without knowing its type ahead of time:
+///
+/// * `int` and `uint` values are decimal strings (they do not fit in a JSON number)
+/// * `bool` values are JSON booleans
+/// * `buff` values are 0x-prefixed hex strings
+/// * `string-ascii` and `string-utf8` values are JSON strings
+/// * `principal` values are address strings, with a `.contract-name` suffix for contracts
+/// * `none` has no `value`; `some`, `ok`, and `err` wrap the JSON encoding of their inner value
+/// * `list` values are JSON arrays, and `tuple` values are JSON objects keyed by field name
+impl Value {
+    pub fn to_json(&self) -> JSONValue {
+        match self {
+            Value::Int(value) => json!({ "type": "int", "value": value.to_string() }),
+            Value::UInt(value) => json!({ "type": "uint", "value": value.to_string() }),
+            Value::Bool(value) => json!({ "type": "bool", "value": value }),
+            Value::Principal(principal) => {
+                json!({ "type": "principal", "value": principal.to_string() })
+            }
+            Value::Optional(OptionalData { data: None }) => json!({ "type": "none" }),
+            Value::Optional(OptionalData { data: Some(value) }) => {
+                json!({ "type": "some", "value": value.to_json() })
+            }
+            Value::Response(ResponseData { committed, data }) => json!({
+                "type": if *committed { "ok" } else { "err" },
+                "value": data.to_json()
+            }),
+            Value::Sequence(SequenceData::Buffer(buff)) => {
+                json!({ "type": "buff", "value": format!("0x{}", to_hex(&buff.data)) })
+            }
+            Value::Sequence(SequenceData::String(CharType::ASCII(ascii))) => json!({
+                "type": "string-ascii",
+                "value": String::from_utf8_lossy(&ascii.data)
+            }),
+            Value::Sequence(SequenceData::String(CharType::UTF8(utf8))) => {
+                let bytes: Vec<u8> = utf8.data.iter().flatten().cloned().collect();
+                json!({ "type": "string-utf8", "value": String::from_utf8_lossy(&bytes) })
+            }
+            Value::Sequence(SequenceData::List(list)) => {
+                let items: Vec<JSONValue> = list.data.iter().map(|item| item.to_json()).collect();
+                json!({ "type": "list", "value": items })
+            }
+            Value::Tuple(tuple) => {
+                let mut fields = serde_json::Map::new();
+                for (name, value) in tuple.data_map.iter() {
+                    fields.insert(name.to_string(), value.to_json());
+                }
+                json!({ "type": "tuple", "value": fields })
+            }
+        }
+    }
+
+    /// Decode a Clarity value from its canonical JSON encoding (see `to_json()`).
+    pub fn try_from_json(json: &JSONValue) -> Result<Value, SerializationError> {
+        let expect_str = |value: Option<&JSONValue>| -> Result<String, SerializationError> {
+            value
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_string())
+                .ok_or_else(|| "Expected a JSON string \"value\"".into())
+        };
+        let expect_inner = |value: Option<&JSONValue>| -> Result<Value, SerializationError> {
+            Value::try_from_json(value.ok_or_else(|| "Missing \"value\"")?)
+        };
+
+        let type_name = json
+            .get("type")
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| "Expected a JSON object with a \"type\"")?;
+        let value = json.get("value");
+
+        let result = match type_name {
+            "int" => Value::Int(
+                expect_str(value)?
+                    .parse::<i128>()
+                    .map_err(|_| "Bad int value")?,
+            ),
+            "uint" => Value::UInt(
+                expect_str(value)?
+                    .parse::<u128>()
+                    .map_err(|_| "Bad uint value")?,
+            ),
+            "bool" => Value::Bool(
+                value
+                    .and_then(|v| v.as_bool())
+                    .ok_or_else(|| "Bad bool value")?,
+            ),
+            "principal" => Value::Principal(
+                PrincipalData::parse(&expect_str(value)?).map_err(|_| "Bad principal value")?,
+            ),
+            "none" => Value::none(),
+            "some" => Value::some(expect_inner(value)?).map_err(|_| "Value too large")?,
+            "ok" => Value::okay(expect_inner(value)?).map_err(|_| "Value too large")?,
+            "err" => Value::error(expect_inner(value)?).map_err(|_| "Value too large")?,
+            "buff" => {
+                let hex = expect_str(value)?;
+                let hex = if hex.starts_with("0x") {
+                    hex[2..].to_string()
+                } else {
+                    hex
+                };
+                Value::buff_from(hex_bytes(&hex).map_err(|_| "Bad buff value")?)
+                    .map_err(|_| "Bad buffer")?
+            }
+            "string-ascii" => Value::string_ascii_from_bytes(expect_str(value)?.into_bytes())
+                .map_err(|_| "Bad string")?,
+            "string-utf8" => Value::string_utf8_from_bytes(expect_str(value)?.into_bytes())
+                .map_err(|_| "Illegal string_utf8 type")?,
+            "list" => {
+                let items = value
+                    .and_then(|v| v.as_array())
+                    .ok_or_else(|| "Expected a JSON array \"value\"")?
+                    .iter()
+                    .map(|item| Value::try_from_json(item))
+                    .collect::<Result<Vec<Value>, SerializationError>>()?;
+                Value::list_from(items).map_err(|_| "Illegal list type")?
+            }
+            "tuple" => {
+                let mut fields = vec![];
+                for (name, field) in value
+                    .and_then(|v| v.as_object())
+                    .ok_or_else(|| "Expected a JSON object \"value\"")?
+                    .iter()
+                {
+                    let name =
+                        ClarityName::try_from(name.clone()).map_err(|_| "Bad tuple field name")?;
+                    fields.push((name, Value::try_from_json(field)?));
+                }
+                Value::from(TupleData::from_data(fields).map_err(|_| "Illegal tuple type")?)
+            }
+            other => {
+                return Err(SerializationError::DeserializationError(format!(
+                    "Unknown Clarity value type \"{}\"",
+                    other
+                )));
+            }
+        };
+        Ok(result)
+    }
+}
+
 impl ClaritySerializable for Value {
     fn serialize(&self) -> String {
         let mut byte_serialization = Vec::new();
@@ -667,6 +808,8 @@ impl ClarityDeserializable<Value> for Value {
 mod tests {
     use std::io::Write;
 
+    use serde_json::Value as JSONValue;
+
     use vm::database::ClaritySerializable;
     use vm::errors::Error;
     use vm::types::TypeSignature::{BoolType, IntType};
@@ -1073,4 +1216,88 @@ mod tests {
         test_bad_expectation(contract_p2, TypeSignature::BoolType);
         test_bad_expectation(standard_p, TypeSignature::BoolType);
     }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let issuer =
+            PrincipalData::parse_standard_principal("SM2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQVX8X0G")
+                .unwrap();
+        let contract_identifier = QualifiedContractIdentifier::new(issuer.clone(), "foo".into());
+
+        let values = vec![
+            Value::Int(-170141183460469231731687303715884105728),
+            Value::UInt(340282366920938463463374607431768211455),
+            Value::Bool(true),
+            Value::from(issuer),
+            Value::from(PrincipalData::Contract(contract_identifier)),
+            Value::none(),
+            Value::some(Value::Int(1)).unwrap(),
+            Value::okay(Value::UInt(2)).unwrap(),
+            Value::error(Value::Bool(false)).unwrap(),
+            Value::buff_from(vec![0xde, 0xad, 0xbe, 0xef]).unwrap(),
+            Value::string_ascii_from_bytes("hello world".as_bytes().to_vec()).unwrap(),
+            Value::string_utf8_from_bytes("hello \u{1F98A}".as_bytes().to_vec()).unwrap(),
+            Value::list_from(vec![Value::Int(1), Value::Int(2)]).unwrap(),
+            Value::list_from(vec![]).unwrap(),
+            Value::from(
+                TupleData::from_data(vec![
+                    ("a".into(), Value::Int(1)),
+                    ("b".into(), Value::some(Value::UInt(2)).unwrap()),
+                ])
+                .unwrap(),
+            ),
+        ];
+
+        for value in values.into_iter() {
+            let json = value.to_json();
+            assert_eq!(Value::try_from_json(&json).unwrap(), value);
+
+            // survives a trip through a JSON string, too
+            let json_str = serde_json::to_string(&json).unwrap();
+            let parsed: JSONValue = serde_json::from_str(&json_str).unwrap();
+            assert_eq!(Value::try_from_json(&parsed).unwrap(), value);
+        }
+
+        assert_eq!(
+            Value::UInt(10).to_json(),
+            json!({ "type": "uint", "value": "10" })
+        );
+        assert_eq!(
+            Value::okay(Value::buff_from(vec![0x01, 0x02]).unwrap())
+                .unwrap()
+                .to_json(),
+            json!({ "type": "ok", "value": { "type": "buff", "value": "0x0102" } })
+        );
+        assert_eq!(
+            Value::try_from_json(&json!({
+                "type": "tuple",
+                "value": { "x": { "type": "int", "value": "-3" } }
+            }))
+            .unwrap(),
+            Value::from(TupleData::from_data(vec![("x".into(), Value::Int(-3))]).unwrap())
+        );
+
+        let bad_inputs = vec![
+            json!("0x0100000000000000000000000000000001"),
+            json!({ "value": "1" }),
+            json!({ "type": "uint", "value": 1 }),
+            json!({ "type": "uint", "value": "-1" }),
+            json!({ "type": "int", "value": "170141183460469231731687303715884105728" }),
+            json!({ "type": "bool", "value": "true" }),
+            json!({ "type": "buff", "value": "0xzz" }),
+            json!({ "type": "principal", "value": "not-a-principal" }),
+            json!({ "type": "some" }),
+            json!({ "type": "string-ascii", "value": "\u{1F98A}" }),
+            json!({ "type": "list", "value": [{ "type": "int", "value": "1" }, { "type": "uint", "value": "1" }] }),
+            json!({ "type": "tuple", "value": { "bad name!": { "type": "int", "value": "1" } } }),
+            json!({ "type": "float", "value": "1.0" }),
+        ];
+        for bad_input in bad_inputs.iter() {
+            assert!(
+                Value::try_from_json(bad_input).is_err(),
+                "Decoded {:?}",
+                bad_input
+            );
+        }
+    }
 }
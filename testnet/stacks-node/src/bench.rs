@@ -0,0 +1,601 @@
+//! Built-in benchmarks for sizing hardware and catching performance regressions
+//! (`stacks-node bench ...`).
+//!
+//! Each benchmark exercises the same code paths the node uses -- the MARF, the Clarity VM, and the
+//! miner's block assembly -- on the exact binary being deployed.  All inputs (keys, block IDs,
+//! transactions, contract arguments) are derived from fixed seeds, so two runs of the same binary
+//! at the same `--scale` do the same work, and their timings can be compared directly.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Instant;
+
+use pico_args::Arguments;
+
+use stacks::address::AddressHashMode;
+use stacks::burnchains::PoxConstants;
+use stacks::chainstate::burn::db::sortdb::SortitionDB;
+use stacks::chainstate::stacks::db::{ChainStateBootData, StacksChainState};
+use stacks::chainstate::stacks::index::marf::{MarfConnection, MARF};
+use stacks::chainstate::stacks::{
+    CoinbasePayload, StacksBlockBuilder, StacksPrivateKey, StacksPublicKey, StacksTransaction,
+    StacksTransactionSigner, TokenTransferMemo, TransactionAnchorMode, TransactionAuth,
+    TransactionPayload, TransactionVersion, C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+};
+use stacks::clarity_vm::clarity::ClarityInstance;
+use stacks::clarity_vm::database::marf::MarfedKV;
+use stacks::core::mempool::MemPoolDB;
+use stacks::core::{
+    BLOCK_LIMIT_MAINNET, CHAIN_ID_TESTNET, FIRST_BURNCHAIN_CONSENSUS_HASH, FIRST_STACKS_BLOCK_HASH,
+};
+use stacks::types::chainstate::{BurnchainHeaderHash, MARFValue, StacksAddress, StacksBlockId};
+use stacks::types::proof::ClarityMarfTrieId;
+use stacks::util::hash::{Hash160, Sha512Trunc256Sum};
+use stacks::util::vrf::VRFProof;
+use stacks::vm::costs::ExecutionCost;
+use stacks::vm::database::{NULL_BURN_STATE_DB, NULL_HEADER_DB};
+use stacks::vm::types::{PrincipalData, QualifiedContractIdentifier, StandardPrincipalData};
+use stacks::vm::Value;
+
+/// Sizes of each benchmark at `--scale 1`
+const MARF_BLOCKS: u64 = 50;
+const MARF_KEYS_PER_BLOCK: u64 = 1000;
+const MARF_READS: u64 = 10_000;
+const CLARITY_CALLS: u64 = 500;
+const BLOCK_ASSEMBLY_TXS: u64 = 1000;
+const BLOCK_ASSEMBLY_TXS_PER_SENDER: u64 = 5;
+
+/// Reference contracts for the Clarity benchmark, with the public function each call invokes.
+const REFERENCE_CONTRACTS: &[(&str, &str, &str)] = &[
+    (
+        "bench-token",
+        "transfer",
+        "(define-fungible-token bench-token)
+         (define-public (transfer (amount uint) (recipient principal))
+           (begin
+             (try! (ft-mint? bench-token amount tx-sender))
+             (ft-transfer? bench-token amount tx-sender recipient)))",
+    ),
+    (
+        "bench-map",
+        "put",
+        "(define-map entries { key: uint } { value: (buff 32), count: uint })
+         (define-data-var total uint u0)
+         (define-public (put (key uint) (value (buff 32)))
+           (let ((count (default-to u0 (get count (map-get? entries { key: key })))))
+             (map-set entries { key: key } { value: value, count: (+ count u1) })
+             (var-set total (+ (var-get total) u1))
+             (ok (var-get total))))",
+    ),
+    (
+        "bench-compute",
+        "crunch",
+        "(define-private (step (i uint) (acc (buff 32)))
+           (sha256 (concat acc (sha256 i))))
+         (define-public (crunch (seed (buff 32)))
+           (ok (fold step (list u0 u1 u2 u3 u4 u5 u6 u7 u8 u9 u10 u11 u12 u13 u14 u15
+                                u16 u17 u18 u19 u20 u21 u22 u23 u24 u25 u26 u27 u28 u29 u30 u31)
+                     seed)))",
+    ),
+];
+
+/// The outcome of one measured operation
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub name: String,
+    pub ops: u64,
+    pub elapsed_ms: u128,
+    pub notes: String,
+}
+
+impl BenchResult {
+    fn new(name: &str, ops: u64, start: Instant, notes: String) -> BenchResult {
+        BenchResult {
+            name: name.to_string(),
+            ops,
+            elapsed_ms: start.elapsed().as_millis(),
+            notes,
+        }
+    }
+
+    fn ops_per_sec(&self) -> u128 {
+        if self.elapsed_ms == 0 {
+            // too fast to measure
+            return 0;
+        }
+        (self.ops as u128) * 1000 / self.elapsed_ms
+    }
+}
+
+/// Deterministically derive 32 bytes from a label and an index
+fn bench_bytes(label: &str, i: u64) -> [u8; 32] {
+    Sha512Trunc256Sum::from_data(format!("stacks-node-bench-{}-{}", label, i).as_bytes()).0
+}
+
+fn bench_block_id(label: &str, i: u64) -> StacksBlockId {
+    StacksBlockId(bench_bytes(label, i))
+}
+
+fn bench_private_key(label: &str, i: u64) -> StacksPrivateKey {
+    let mut privk = StacksPrivateKey::from_slice(&bench_bytes(label, i))
+        .expect("FATAL: derived an invalid private key");
+    privk.set_compress_public(true);
+    privk
+}
+
+fn bench_principal(label: &str, i: u64) -> PrincipalData {
+    let mut hash_bytes = [0u8; 20];
+    hash_bytes.copy_from_slice(&bench_bytes(label, i)[0..20]);
+    StandardPrincipalData(C32_ADDRESS_VERSION_TESTNET_SINGLESIG, hash_bytes).into()
+}
+
+fn format_cost(cost: &ExecutionCost) -> String {
+    format!(
+        "runtime={} read_count={} read_length={} write_count={} write_length={}",
+        cost.runtime, cost.read_count, cost.read_length, cost.write_count, cost.write_length
+    )
+}
+
+/// Write `num_blocks` blocks of `keys_per_block` keys each into a fresh MARF, and then read back
+/// `num_reads` of them from the chain tip.
+pub fn bench_marf(
+    working_dir: &Path,
+    num_blocks: u64,
+    keys_per_block: u64,
+    num_reads: u64,
+) -> Result<Vec<BenchResult>, String> {
+    let marf_path = working_dir.join("marf.sqlite");
+    let mut marf: MARF<StacksBlockId> = MARF::from_path(&marf_path.to_string_lossy())
+        .map_err(|e| format!("Failed to open MARF: {:?}", &e))?;
+
+    let start = Instant::now();
+    let mut parent = StacksBlockId::sentinel();
+    for i in 0..num_blocks {
+        let block_id = bench_block_id("marf-block", i);
+        let mut tx = marf
+            .begin_tx()
+            .map_err(|e| format!("Failed to open MARF transaction: {:?}", &e))?;
+        tx.begin(&parent, &block_id)
+            .map_err(|e| format!("Failed to begin MARF block: {:?}", &e))?;
+
+        let keys: Vec<String> = (0..keys_per_block)
+            .map(|k| format!("bench::{}::{}", i, k))
+            .collect();
+        let values: Vec<MARFValue> = (0..keys_per_block)
+            .map(|k| {
+                MARFValue::from_value_hash_bytes(&bench_bytes("marf-value", i * keys_per_block + k))
+            })
+            .collect();
+        tx.insert_batch(&keys, values)
+            .map_err(|e| format!("Failed to insert into MARF: {:?}", &e))?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit MARF block: {:?}", &e))?;
+        parent = block_id;
+    }
+    let writes = BenchResult::new(
+        "marf-write",
+        num_blocks * keys_per_block,
+        start,
+        format!("{} blocks of {} keys", num_blocks, keys_per_block),
+    );
+
+    let start = Instant::now();
+    for r in 0..num_reads {
+        let seed = bench_bytes("marf-read", r);
+        let mut index_bytes = [0u8; 8];
+        index_bytes.copy_from_slice(&seed[0..8]);
+        let index = u64::from_le_bytes(index_bytes) % (num_blocks * keys_per_block);
+        let key = format!(
+            "bench::{}::{}",
+            index / keys_per_block,
+            index % keys_per_block
+        );
+        marf.get(&parent, &key)
+            .map_err(|e| format!("Failed to read from MARF: {:?}", &e))?
+            .ok_or_else(|| format!("MARF is missing key {}", &key))?;
+    }
+    let reads = BenchResult::new(
+        "marf-read",
+        num_reads,
+        start,
+        format!("random keys at a tip {} blocks deep", num_blocks),
+    );
+
+    Ok(vec![writes, reads])
+}
+
+/// Deploy each reference contract, and call its public function `num_calls` times, each in its
+/// own transaction.
+pub fn bench_clarity(working_dir: &Path, num_calls: u64) -> Result<Vec<BenchResult>, String> {
+    let marf = MarfedKV::open(&working_dir.join("clarity").to_string_lossy(), None)
+        .map_err(|e| format!("Failed to open Clarity MARF: {:?}", &e))?;
+    let mut clarity_instance = ClarityInstance::new(false, marf, ExecutionCost::max_value());
+
+    let genesis_id = bench_block_id("clarity-block", 0);
+    let conn = clarity_instance.begin_test_genesis_block(
+        &StacksBlockId::sentinel(),
+        &genesis_id,
+        &NULL_HEADER_DB,
+        &NULL_BURN_STATE_DB,
+    );
+    conn.commit_to_block(&genesis_id);
+
+    let deployer = StandardPrincipalData(C32_ADDRESS_VERSION_TESTNET_SINGLESIG, [0x01; 20]);
+    let mut results = vec![];
+    let mut parent_id = genesis_id;
+    for (i, (contract_name, function_name, code)) in REFERENCE_CONTRACTS.iter().enumerate() {
+        let contract_id =
+            QualifiedContractIdentifier::new(deployer.clone(), (*contract_name).into());
+
+        let deploy_id = bench_block_id("clarity-block", 2 * (i as u64) + 1);
+        let mut conn = clarity_instance.begin_block(
+            &parent_id,
+            &deploy_id,
+            &NULL_HEADER_DB,
+            &NULL_BURN_STATE_DB,
+        );
+        let start = Instant::now();
+        conn.as_transaction(|tx| -> Result<(), String> {
+            let (ast, analysis) = tx
+                .analyze_smart_contract(&contract_id, code)
+                .map_err(|e| format!("Failed to analyze {}: {:?}", &contract_id, &e))?;
+            tx.initialize_smart_contract(&contract_id, &ast, code, |_, _| false)
+                .map_err(|e| format!("Failed to deploy {}: {:?}", &contract_id, &e))?;
+            tx.save_analysis(&contract_id, &analysis)
+                .map_err(|e| format!("Failed to store analysis of {}: {:?}", &contract_id, &e))
+        })?;
+        let deploy_cost = conn.commit_to_block(&deploy_id).get_total();
+        results.push(BenchResult::new(
+            &format!("clarity-deploy-{}", contract_name),
+            1,
+            start,
+            format_cost(&deploy_cost),
+        ));
+
+        let calls_id = bench_block_id("clarity-block", 2 * (i as u64) + 2);
+        let mut conn = clarity_instance.begin_block(
+            &deploy_id,
+            &calls_id,
+            &NULL_HEADER_DB,
+            &NULL_BURN_STATE_DB,
+        );
+        let start = Instant::now();
+        for c in 0..num_calls {
+            let sender = bench_principal("clarity-sender", c);
+            let args = match *function_name {
+                "transfer" => vec![
+                    Value::UInt(1 + (c as u128)),
+                    Value::from(bench_principal("clarity-recipient", c)),
+                ],
+                "put" => vec![
+                    Value::UInt((c % 100) as u128),
+                    Value::buff_from(bench_bytes("clarity-value", c).to_vec())
+                        .map_err(|e| format!("Failed to make buffer: {:?}", &e))?,
+                ],
+                _ => vec![Value::buff_from(bench_bytes("clarity-value", c).to_vec())
+                    .map_err(|e| format!("Failed to make buffer: {:?}", &e))?],
+            };
+            let (result, _, _) = conn
+                .as_transaction(|tx| {
+                    tx.run_contract_call(&sender, &contract_id, function_name, &args, |_, _| false)
+                })
+                .map_err(|e| format!("Failed to call {}: {:?}", &contract_id, &e))?;
+            if let Value::Response(ref response) = result {
+                if !response.committed {
+                    return Err(format!(
+                        "Call to {}.{} failed: {}",
+                        &contract_id, function_name, &result
+                    ));
+                }
+            }
+        }
+        let calls_cost = conn.commit_to_block(&calls_id).get_total();
+        results.push(BenchResult::new(
+            &format!("clarity-call-{}", contract_name),
+            num_calls,
+            start,
+            format_cost(&calls_cost),
+        ));
+
+        parent_id = calls_id;
+    }
+
+    Ok(results)
+}
+
+fn make_signed_tx(
+    privk: &StacksPrivateKey,
+    payload: TransactionPayload,
+    nonce: u64,
+    fee: u64,
+) -> Result<StacksTransaction, String> {
+    let auth = TransactionAuth::from_p2pkh(privk)
+        .ok_or_else(|| "Failed to make transaction auth".to_string())?;
+    let mut tx = StacksTransaction::new(TransactionVersion::Testnet, auth, payload);
+    tx.chain_id = CHAIN_ID_TESTNET;
+    tx.anchor_mode = TransactionAnchorMode::OnChainOnly;
+    tx.set_tx_fee(fee);
+    tx.set_origin_nonce(nonce);
+
+    let mut signer = StacksTransactionSigner::new(&tx);
+    signer
+        .sign_origin(privk)
+        .map_err(|e| format!("Failed to sign transaction: {:?}", &e))?;
+    signer
+        .get_tx()
+        .ok_or_else(|| "Failed to sign transaction".to_string())
+}
+
+/// Boot a fresh chainstate, fill its mempool with `num_txs` STX transfers, and mine an anchored
+/// block from it with the mainnet block limit.
+pub fn bench_block_assembly(working_dir: &Path, num_txs: u64) -> Result<Vec<BenchResult>, String> {
+    let chainstate_path = working_dir.join("chainstate").to_string_lossy().to_string();
+    let sortdb_path = working_dir.join("sortition").to_string_lossy().to_string();
+
+    let num_senders = (num_txs + BLOCK_ASSEMBLY_TXS_PER_SENDER - 1) / BLOCK_ASSEMBLY_TXS_PER_SENDER;
+    let senders: Vec<_> = (0..num_senders)
+        .map(|i| bench_private_key("block-assembly-sender", i))
+        .collect();
+    let initial_balances = senders
+        .iter()
+        .map(|privk| {
+            let addr = StacksAddress::from_public_keys(
+                C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+                &AddressHashMode::SerializeP2PKH,
+                1,
+                &vec![StacksPublicKey::from_private(privk)],
+            )
+            .expect("FATAL: failed to make address");
+            (PrincipalData::from(addr), 1_000_000_000)
+        })
+        .collect();
+
+    let start = Instant::now();
+    let mut boot_data = ChainStateBootData {
+        initial_balances,
+        first_burnchain_block_hash: BurnchainHeaderHash::zero(),
+        first_burnchain_block_height: 0,
+        first_burnchain_block_timestamp: 0,
+        pox_constants: PoxConstants::testnet_default(),
+        post_flight_callback: None,
+        get_bulk_initial_lockups: None,
+        get_bulk_initial_balances: None,
+        get_bulk_initial_namespaces: None,
+        get_bulk_initial_names: None,
+        custom_costs_contract: None,
+    };
+    let (mut chainstate, _) = StacksChainState::open_and_exec(
+        false,
+        CHAIN_ID_TESTNET,
+        &chainstate_path,
+        Some(&mut boot_data),
+        BLOCK_LIMIT_MAINNET.clone(),
+    )
+    .map_err(|e| format!("Failed to boot chainstate: {:?}", &e))?;
+    let boot = BenchResult::new(
+        "block-assembly-boot",
+        1,
+        start,
+        format!("genesis with {} funded accounts", num_senders),
+    );
+
+    let sortdb = SortitionDB::connect(&sortdb_path, 0, &BurnchainHeaderHash::zero(), 0, true)
+        .map_err(|e| format!("Failed to open sortition DB: {:?}", &e))?;
+    let mut mempool = MemPoolDB::open(false, CHAIN_ID_TESTNET, &chainstate_path)
+        .map_err(|e| format!("Failed to open mempool: {:?}", &e))?;
+
+    let mut txs = vec![];
+    for i in 0..num_txs {
+        let sender_index = i / BLOCK_ASSEMBLY_TXS_PER_SENDER;
+        let payload = TransactionPayload::TokenTransfer(
+            bench_principal("block-assembly-recipient", i),
+            1 + i,
+            TokenTransferMemo([0u8; 34]),
+        );
+        txs.push(make_signed_tx(
+            &senders[sender_index as usize],
+            payload,
+            i % BLOCK_ASSEMBLY_TXS_PER_SENDER,
+            1000 + (i % 100),
+        )?);
+    }
+
+    let start = Instant::now();
+    for tx in txs.iter() {
+        mempool
+            .submit(
+                &mut chainstate,
+                &FIRST_BURNCHAIN_CONSENSUS_HASH,
+                &FIRST_STACKS_BLOCK_HASH,
+                tx,
+                None,
+            )
+            .map_err(|e| format!("Mempool rejected {}: {:?}", &tx.txid(), &e))?;
+    }
+    let admission = BenchResult::new(
+        "block-assembly-mempool-admit",
+        num_txs,
+        start,
+        "STX transfers".to_string(),
+    );
+
+    let parent_header = StacksChainState::get_genesis_header_info(chainstate.db())
+        .map_err(|e| format!("Failed to load genesis header: {:?}", &e))?;
+    let miner_key = bench_private_key("block-assembly-miner", 0);
+    let coinbase_tx = make_signed_tx(
+        &miner_key,
+        TransactionPayload::Coinbase(CoinbasePayload([0u8; 32])),
+        0,
+        0,
+    )?;
+
+    let start = Instant::now();
+    let (block, block_cost, block_size) = StacksBlockBuilder::build_anchored_block(
+        &chainstate,
+        &sortdb.index_conn(),
+        &mut mempool,
+        &parent_header,
+        0,
+        VRFProof::empty(),
+        Hash160([0u8; 20]),
+        &coinbase_tx,
+        BLOCK_LIMIT_MAINNET.clone(),
+        None,
+    )
+    .map_err(|e| format!("Failed to assemble block: {:?}", &e))?;
+    let assembly = BenchResult::new(
+        "block-assembly-mine",
+        block.txs.len() as u64,
+        start,
+        format!(
+            "{} of {} txs included, {} bytes, {}",
+            block.txs.len() - 1,
+            num_txs,
+            block_size,
+            format_cost(&block_cost)
+        ),
+    );
+
+    Ok(vec![boot, admission, assembly])
+}
+
+fn print_results(results: &[BenchResult]) {
+    for result in results.iter() {
+        println!(
+            "{}, {}, {}, {}, {}",
+            &result.name,
+            result.ops,
+            result.elapsed_ms,
+            result.ops_per_sec(),
+            &result.notes
+        );
+    }
+}
+
+/// Run one benchmark in its own fresh subdirectory of `working_dir`
+fn run_bench<F>(working_dir: &Path, name: &str, bench: F) -> Result<Vec<BenchResult>, String>
+where
+    F: FnOnce(&Path) -> Result<Vec<BenchResult>, String>,
+{
+    let bench_dir = working_dir.join(name);
+    if bench_dir.exists() {
+        fs::remove_dir_all(&bench_dir)
+            .map_err(|e| format!("Failed to clear {}: {}", bench_dir.display(), &e))?;
+    }
+    fs::create_dir_all(&bench_dir)
+        .map_err(|e| format!("Failed to create {}: {}", bench_dir.display(), &e))?;
+
+    let results = bench(&bench_dir);
+    let _ = fs::remove_dir_all(&bench_dir);
+    results
+}
+
+/// Entry point for `stacks-node bench`
+pub fn run_bench_command(mut args: Arguments) -> Result<(), String> {
+    let which = args
+        .subcommand()
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "all".to_string());
+    let scale: u64 = args
+        .opt_value_from_str("--scale")
+        .map_err(|e| e.to_string())?
+        .unwrap_or(1);
+    let working_dir: Option<String> = args
+        .opt_value_from_str("--working-dir")
+        .map_err(|e| e.to_string())?;
+    let working_dir = working_dir.map(PathBuf::from).unwrap_or_else(|| {
+        std::env::temp_dir().join(format!("stacks-node-bench-{}", process::id()))
+    });
+    args.finish().map_err(|e| e.to_string())?;
+
+    if scale == 0 {
+        return Err("--scale must be at least 1".to_string());
+    }
+
+    let run_marf = which == "all" || which == "marf";
+    let run_clarity = which == "all" || which == "clarity";
+    let run_block_assembly = which == "all" || which == "block-assembly";
+    if !run_marf && !run_clarity && !run_block_assembly {
+        return Err(format!(
+            "Unknown benchmark '{}': expected one of `marf`, `clarity`, `block-assembly`, or `all`",
+            &which
+        ));
+    }
+
+    println!("{}", crate::version());
+    println!("Benchmark, Operations, Time (ms), Ops/sec, Notes");
+    if run_marf {
+        print_results(&run_bench(&working_dir, "marf", |dir| {
+            bench_marf(
+                dir,
+                MARF_BLOCKS * scale,
+                MARF_KEYS_PER_BLOCK,
+                MARF_READS * scale,
+            )
+        })?);
+    }
+    if run_clarity {
+        print_results(&run_bench(&working_dir, "clarity", |dir| {
+            bench_clarity(dir, CLARITY_CALLS * scale)
+        })?);
+    }
+    if run_block_assembly {
+        print_results(&run_bench(&working_dir, "block-assembly", |dir| {
+            bench_block_assembly(dir, BLOCK_ASSEMBLY_TXS * scale)
+        })?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let path = PathBuf::from(format!("/tmp/stacks-node-tests/bench-{}", name));
+        if path.exists() {
+            fs::remove_dir_all(&path).unwrap();
+        }
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn bench_inputs_are_reproducible() {
+        assert_eq!(bench_bytes("a", 1), bench_bytes("a", 1));
+        assert_ne!(bench_bytes("a", 1), bench_bytes("a", 2));
+        assert_ne!(bench_bytes("a", 1), bench_bytes("b", 1));
+        assert_eq!(
+            bench_private_key("key", 0).to_hex(),
+            bench_private_key("key", 0).to_hex()
+        );
+    }
+
+    #[test]
+    fn bench_marf_small() {
+        let results = bench_marf(&test_dir("marf"), 3, 10, 20).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].name, "marf-write");
+        assert_eq!(results[0].ops, 30);
+        assert_eq!(results[1].name, "marf-read");
+        assert_eq!(results[1].ops, 20);
+    }
+
+    #[test]
+    fn bench_clarity_small() {
+        let results = bench_clarity(&test_dir("clarity"), 3).unwrap();
+        assert_eq!(results.len(), 2 * REFERENCE_CONTRACTS.len());
+        for result in results.iter() {
+            assert!(result.name.starts_with("clarity-"));
+        }
+    }
+
+    #[test]
+    fn bench_block_assembly_small() {
+        let results = bench_block_assembly(&test_dir("block-assembly"), 12).unwrap();
+        assert_eq!(results.len(), 3);
+        // coinbase plus every transfer
+        assert_eq!(results[2].ops, 13);
+    }
+}
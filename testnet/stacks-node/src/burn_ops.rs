@@ -0,0 +1,303 @@
+//! Submitting burnchain operations (`stacks-node burn-op`, and
+//! `POST /v2/admin/burn_ops/transfer_stx`), and reading the miner's burnchain wallet
+//! (`GET /v2/admin/miner/wallet`).  The wallet is read from bitcoind by the relayer thread, which
+//! caches it for the p2p thread to report.
+//!
+//! A transfer-STX operation takes two Bitcoin transactions: a pre-STX transaction, paid for by the
+//! sender's burnchain address, whose second output goes back to that address, and a transfer-STX
+//! transaction that spends that output.  The node builds both, picks the UTXO to fund them with
+//! from its bitcoind, pays `burnchain.satoshis_per_byte` (or the fee rate given), and broadcasts
+//! them.  The sender's burnchain address must be funded, and watched by bitcoind.
+//!
+//! From the command line, the sender is whoever's key is in `--key-file`.  Over RPC, the sender is
+//! always the node's own burnchain signer (the one derived from `node.seed`), and the request is
+//! handed off to the relayer thread, which makes the bitcoind calls; the p2p thread never blocks
+//! on them, and no key is ever sent over HTTP.
+//!
+//! Delegate-STX operations are not supported: they are not part of the consensus rules this node
+//! implements.
+
+use std::path::Path;
+use std::sync::mpsc::{SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+
+use pico_args::Arguments;
+
+use stacks::burnchains::Address;
+use stacks::net::admin::{
+    transfer_stx_sender, BurnOpSubmitter, MinerWallet, MinerWalletReader, TransferStxOpRequest,
+};
+use stacks::types::chainstate::StacksAddress;
+use stacks::util::hash::hex_bytes;
+use stacks::util::secp256k1::Secp256k1PublicKey;
+
+use crate::burnchains::BitcoinRegtestController;
+use crate::identity;
+use crate::neon_node::RelayerDirective;
+use crate::operations::BurnchainOpSigner;
+use crate::Keychain;
+use crate::{Config, ConfigFile};
+
+/// Build, fund and broadcast a transfer-STX operation from `signer` through the node's bitcoind.
+/// This blocks on bitcoind, so it must not be called from the p2p thread.  Returns the txids of
+/// the pre-STX and transfer-STX transactions.
+pub fn submit_transfer_stx(
+    config: &Config,
+    request: &TransferStxOpRequest,
+    signer: &BurnchainOpSigner,
+) -> Result<(String, String), String> {
+    let mut config = config.clone();
+    if let Some(fee_rate) = request.fee_rate {
+        config.burnchain.satoshis_per_byte = fee_rate;
+    }
+    BitcoinRegtestController::new_dummy(config).submit_transfer_stx(request, signer)
+}
+
+/// Queues RPC burn operation requests for the relayer thread, which sends them from the node's
+/// burnchain signer
+pub struct RelayerBurnOpSubmitter {
+    sender: StacksAddress,
+    relay_channel: SyncSender<RelayerDirective>,
+}
+
+impl RelayerBurnOpSubmitter {
+    /// Returns None if the node has no bitcoind to submit through
+    pub fn from_config(
+        config: &Config,
+        relay_channel: SyncSender<RelayerDirective>,
+    ) -> Option<RelayerBurnOpSubmitter> {
+        if config.burnchain.mode == "mocknet" {
+            return None;
+        }
+        let public_key = Keychain::default(config.node.seed.clone())
+            .generate_op_signer()
+            .get_public_key();
+        Some(RelayerBurnOpSubmitter {
+            sender: transfer_stx_sender(&public_key, config.is_mainnet()),
+            relay_channel,
+        })
+    }
+}
+
+impl BurnOpSubmitter for RelayerBurnOpSubmitter {
+    fn sender(&self) -> StacksAddress {
+        self.sender.clone()
+    }
+
+    fn queue_transfer_stx(&self, request: TransferStxOpRequest) -> Result<(), String> {
+        self.relay_channel
+            .try_send(RelayerDirective::SubmitTransferStx(request))
+            .map_err(|e| match e {
+                TrySendError::Full(_) => "The relayer is busy; try again later".to_string(),
+                TrySendError::Disconnected(_) => "The relayer has stopped".to_string(),
+            })
+    }
+}
+
+/// The miner's burnchain wallet, as the relayer thread last read it from the node's bitcoind.
+/// The relayer refreshes it when it starts and after each tenure it runs; the p2p thread only
+/// reads the cached copy, so it never blocks on bitcoind.
+#[derive(Clone)]
+pub struct MinerWalletCache {
+    config: Config,
+    public_key: Secp256k1PublicKey,
+    wallet: Arc<Mutex<Option<Result<MinerWallet, String>>>>,
+}
+
+impl MinerWalletCache {
+    /// Returns None unless the node mines, with real block-commits
+    pub fn from_config(config: &Config) -> Option<MinerWalletCache> {
+        if !config.node.miner || config.node.mock_mining || config.burnchain.mode == "mocknet" {
+            return None;
+        }
+        let public_key = Keychain::default(config.node.seed.clone())
+            .generate_op_signer()
+            .get_public_key();
+        Some(MinerWalletCache {
+            config: config.clone(),
+            public_key,
+            wallet: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Re-read the wallet from bitcoind.  This blocks, so it must not be called from the p2p
+    /// thread.
+    pub fn refresh(&self) {
+        let wallet = BitcoinRegtestController::new_dummy(self.config.clone())
+            .get_miner_wallet(&self.public_key);
+        if let Err(ref msg) = wallet {
+            debug!("Failed to read the miner's burnchain wallet: {}", msg);
+        }
+        self.set(wallet);
+    }
+
+    fn set(&self, wallet: Result<MinerWallet, String>) {
+        *self
+            .wallet
+            .lock()
+            .expect("BUG: miner wallet cache lock poisoned") = Some(wallet);
+    }
+}
+
+impl MinerWalletReader for MinerWalletCache {
+    fn read_miner_wallet(&self) -> Result<MinerWallet, String> {
+        self.wallet
+            .lock()
+            .expect("BUG: miner wallet cache lock poisoned")
+            .clone()
+            .unwrap_or_else(|| Err("The miner has not read its burnchain wallet yet".to_string()))
+    }
+}
+
+pub fn run_burn_op_command(mut args: Arguments) -> Result<(), String> {
+    let subcommand = args
+        .subcommand()
+        .map_err(|e| format!("{}", e))?
+        .unwrap_or_default();
+    let config_path: Option<String> = args
+        .opt_value_from_str("--config")
+        .map_err(|e| format!("{}", e))?;
+    let config_path = config_path.ok_or("`burn-op` must be passed a config file via `--config`")?;
+    let profile: Option<String> = args
+        .opt_value_from_str("--profile")
+        .map_err(|e| format!("{}", e))?;
+    let config = Config::from_config_file(ConfigFile::from_path_with_profile(
+        &config_path,
+        profile.as_deref(),
+    )?);
+
+    match subcommand.as_str() {
+        "transfer-stx" => {
+            let key_file: String = args
+                .value_from_str("--key-file")
+                .map_err(|e| format!("{}", e))?;
+            let recipient: String = args
+                .value_from_str("--recipient")
+                .map_err(|e| format!("{}", e))?;
+            let amount: u128 = args
+                .value_from_str("--amount")
+                .map_err(|e| format!("{}", e))?;
+            let memo: Option<String> = args
+                .opt_value_from_str("--memo")
+                .map_err(|e| format!("{}", e))?;
+            let fee_rate: Option<u64> = args
+                .opt_value_from_str("--fee-rate")
+                .map_err(|e| format!("{}", e))?;
+            args.finish().map_err(|e| format!("{}", e))?;
+
+            let sender_key = identity::read_identity_key(Path::new(&key_file))?;
+            let recipient = StacksAddress::from_string(&recipient)
+                .ok_or_else(|| format!("{} is not a Stacks address", &recipient))?;
+            let memo = match memo {
+                Some(memo) => hex_bytes(&memo).map_err(|_| "--memo must be hex".to_string())?,
+                None => vec![],
+            };
+            let request =
+                TransferStxOpRequest::new(recipient, amount, memo, fee_rate, config.is_mainnet())?;
+            if config.burnchain.mode == "mocknet" {
+                return Err("A mocknet node has no burnchain to submit operations to".to_string());
+            }
+
+            let signer = BurnchainOpSigner::new(sender_key, false);
+            let (pre_stx_txid, transfer_stx_txid) =
+                submit_transfer_stx(&config, &request, &signer)?;
+            println!("pre-STX txid: {}", &pre_stx_txid);
+            println!("transfer-STX txid: {}", &transfer_stx_txid);
+            eprintln!(
+                "Transfer of {} uSTX from {} to {} takes effect once both transactions confirm",
+                request.amount_ustx,
+                transfer_stx_sender(
+                    &Secp256k1PublicKey::from_private(&sender_key),
+                    config.is_mainnet()
+                ),
+                &request.recipient
+            );
+            Ok(())
+        }
+        _ => Err(format!(
+            "Unrecognized `burn-op` subcommand '{}': expected `transfer-stx`",
+            &subcommand
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::mpsc::sync_channel;
+
+    #[test]
+    fn burn_op_submitter_needs_a_burnchain() {
+        let (relay_send, relay_recv) = sync_channel(1);
+        let mut config = Config::default();
+        config.burnchain.mode = "mocknet".to_string();
+        assert!(RelayerBurnOpSubmitter::from_config(&config, relay_send.clone()).is_none());
+
+        config.burnchain.mode = "xenon".to_string();
+        let submitter = RelayerBurnOpSubmitter::from_config(&config, relay_send).unwrap();
+        let public_key = Keychain::default(config.node.seed.clone())
+            .generate_op_signer()
+            .get_public_key();
+        assert_eq!(
+            submitter.sender(),
+            transfer_stx_sender(&public_key, config.is_mainnet())
+        );
+
+        // requests are queued for the relayer, and refused once it's backed up
+        let request = TransferStxOpRequest::new(
+            StacksAddress::from_string("ST2QKZ4FKHAH1NQKYKYAYZPY440FEPK7GZ1R5HBP2").unwrap(),
+            1000,
+            vec![],
+            None,
+            false,
+        )
+        .unwrap();
+        submitter.queue_transfer_stx(request.clone()).unwrap();
+        assert!(submitter.queue_transfer_stx(request.clone()).is_err());
+        match relay_recv.try_recv() {
+            Ok(RelayerDirective::SubmitTransferStx(queued)) => assert_eq!(queued, request),
+            _ => panic!("transfer-STX request was not queued"),
+        }
+    }
+
+    #[test]
+    fn miner_wallet_cache_needs_a_miner() {
+        let mut config = Config::default();
+        config.burnchain.mode = "xenon".to_string();
+        config.node.miner = false;
+        assert!(MinerWalletCache::from_config(&config).is_none());
+
+        config.node.miner = true;
+        assert!(MinerWalletCache::from_config(&config).is_some());
+
+        config.node.mock_mining = true;
+        assert!(MinerWalletCache::from_config(&config).is_none());
+    }
+
+    #[test]
+    fn miner_wallet_cache_is_shared() {
+        let mut config = Config::default();
+        config.burnchain.mode = "xenon".to_string();
+        config.node.miner = true;
+        let relayer_cache = MinerWalletCache::from_config(&config).unwrap();
+        let rpc_cache = relayer_cache.clone();
+
+        // nothing to report until the relayer has read the wallet
+        assert!(rpc_cache.read_miner_wallet().is_err());
+
+        let wallet = MinerWallet {
+            address: "mkvJ3xUHbjMFa3qjaXSRKtGbyrxe3JMeoi".to_string(),
+            utxo_amounts: vec![1000, 2000],
+            commit_cost: 500,
+        };
+        relayer_cache.set(Ok(wallet.clone()));
+        assert_eq!(rpc_cache.read_miner_wallet(), Ok(wallet));
+
+        relayer_cache.set(Err("bitcoind is down".to_string()));
+        assert_eq!(
+            rpc_cache.read_miner_wallet(),
+            Err("bitcoind is down".to_string())
+        );
+    }
+}
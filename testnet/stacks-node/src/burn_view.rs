@@ -0,0 +1,307 @@
+//! Burn-view consistency checks (`stacks-node burn-view`).
+//!
+//! Compares the consensus hashes of this node's sortitions at the last few burnchain heights to
+//! the ones other nodes report at the same heights over `GET /v2/sortitions/burn_height/:height`.
+//! Two nodes with the same consensus hash at a height processed the same burnchain history, under
+//! the same rules, up to that height.  If every peer disagrees with us, we are most likely the one
+//! on a fork (e.g. our bitcoind is, or we run different consensus rules); if some peers agree with
+//! us and others don't, the network itself is split.
+//!
+//! The peers compared to are the RPC URLs in `node.burn_view_peers`, or those passed with
+//! `--peers`.  Heights a peer has not processed yet are skipped, so a peer that is behind is not
+//! mistaken for one that diverged; a peer that has processed none of them is left out of the
+//! verdict.  Our sortition DB is only opened read-only, so this can be run alongside the node.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use pico_args::Arguments;
+
+use stacks::chainstate::burn::db::sortdb::SortitionDB;
+use stacks::chainstate::burn::ConsensusHash;
+use stacks::net::RPCSortitionData;
+
+use crate::{Config, ConfigFile};
+
+/// How many burnchain heights to compare by default, counting back from our burnchain tip
+const DEFAULT_BURN_VIEW_DEPTH: u64 = 10;
+
+/// How long to wait on each peer
+const BURN_VIEW_TIMEOUT_SECS: u64 = 10;
+
+/// How a peer's sortitions at the compared heights line up with ours
+#[derive(Debug, Clone, PartialEq)]
+pub enum PeerComparison {
+    /// The peer has the same consensus hash as we do at each of these many heights (at least
+    /// one), and has not processed the rest yet
+    Agrees(usize),
+    /// The peer has not processed any of the compared heights yet, so there is nothing to compare
+    NoOverlap,
+    /// The peer first has a different consensus hash at this height: (height, ours, theirs)
+    Diverges(u64, ConsensusHash, ConsensusHash),
+    /// The peer could not be asked, for this reason
+    Unreachable(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BurnViewVerdict {
+    /// Every peer that answered agrees with us
+    Consistent,
+    /// Every peer that answered disagrees with us: this node is likely forked
+    LocalFork,
+    /// Some peers agree with us and some don't: the network is likely forked
+    NetworkSplit,
+    /// No peer answered at any of the compared heights
+    Unknown,
+}
+
+/// Compare what a peer reported at each height to our consensus hashes.  A height missing from
+/// `theirs` is one the peer has not processed yet; a peer missing all of them is `NoOverlap`.
+pub fn compare_peer(
+    ours: &BTreeMap<u64, ConsensusHash>,
+    theirs: &BTreeMap<u64, ConsensusHash>,
+) -> PeerComparison {
+    let mut agreed = 0;
+    for (height, our_hash) in ours.iter() {
+        match theirs.get(height) {
+            Some(their_hash) if their_hash == our_hash => agreed += 1,
+            Some(their_hash) => {
+                return PeerComparison::Diverges(*height, our_hash.clone(), their_hash.clone())
+            }
+            None => {}
+        }
+    }
+    if agreed == 0 {
+        PeerComparison::NoOverlap
+    } else {
+        PeerComparison::Agrees(agreed)
+    }
+}
+
+pub fn verdict(comparisons: &[PeerComparison]) -> BurnViewVerdict {
+    let mut agree = 0;
+    let mut diverge = 0;
+    for comparison in comparisons.iter() {
+        match comparison {
+            PeerComparison::Agrees(..) => agree += 1,
+            PeerComparison::Diverges(..) => diverge += 1,
+            PeerComparison::NoOverlap | PeerComparison::Unreachable(..) => {}
+        }
+    }
+    match (agree, diverge) {
+        (0, 0) => BurnViewVerdict::Unknown,
+        (_, 0) => BurnViewVerdict::Consistent,
+        (0, _) => BurnViewVerdict::LocalFork,
+        _ => BurnViewVerdict::NetworkSplit,
+    }
+}
+
+/// Our consensus hashes at the last `depth` burnchain heights, on the canonical sortition fork
+fn local_view(config: &Config, depth: u64) -> Result<BTreeMap<u64, ConsensusHash>, String> {
+    let sortdb = SortitionDB::open_readonly(&config.get_burn_db_file_path())
+        .map_err(|e| format!("Failed to open sortition DB: {}", &e))?;
+    let tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())
+        .map_err(|e| format!("Failed to read burnchain tip: {}", &e))?;
+    let handle = sortdb.index_handle(&tip.sortition_id);
+
+    let mut view = BTreeMap::new();
+    let lowest = tip.block_height.saturating_sub(depth.saturating_sub(1));
+    for height in lowest..=tip.block_height {
+        if let Some(snapshot) = handle
+            .get_block_snapshot_by_height(height)
+            .map_err(|e| format!("Failed to read sortition at {}: {}", height, &e))?
+        {
+            view.insert(height, snapshot.consensus_hash);
+        }
+    }
+    Ok(view)
+}
+
+/// The consensus hashes a peer reports at the given heights.  Heights it has no sortition at
+/// yet are left out.
+fn peer_view(
+    client: &reqwest::blocking::Client,
+    peer: &str,
+    heights: &[u64],
+) -> Result<BTreeMap<u64, ConsensusHash>, String> {
+    let mut view = BTreeMap::new();
+    for height in heights.iter() {
+        let url = format!(
+            "{}/v2/sortitions/burn_height/{}",
+            peer.trim_end_matches('/'),
+            height
+        );
+        let resp = client
+            .get(&url)
+            .send()
+            .map_err(|e| format!("Failed to fetch {}: {}", &url, &e))?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            continue;
+        }
+        let sortition: RPCSortitionData = resp
+            .error_for_status()
+            .and_then(|resp| resp.json())
+            .map_err(|e| format!("Failed to fetch {}: {}", &url, &e))?;
+        view.insert(*height, sortition.consensus_hash);
+    }
+    Ok(view)
+}
+
+pub fn run_burn_view_command(mut args: Arguments) -> Result<(), String> {
+    let config_path: Option<String> = args
+        .opt_value_from_str("--config")
+        .map_err(|e| format!("{}", e))?;
+    let config_path =
+        config_path.ok_or("`burn-view` must be passed a config file via `--config`")?;
+    let profile: Option<String> = args
+        .opt_value_from_str("--profile")
+        .map_err(|e| format!("{}", e))?;
+    let peers_arg: Option<String> = args
+        .opt_value_from_str("--peers")
+        .map_err(|e| format!("{}", e))?;
+    let depth: Option<u64> = args
+        .opt_value_from_str("--depth")
+        .map_err(|e| format!("{}", e))?;
+    args.finish().map_err(|e| format!("{}", e))?;
+
+    let config = Config::from_config_file(ConfigFile::from_path_with_profile(
+        &config_path,
+        profile.as_deref(),
+    )?);
+    let peers: Vec<String> = match peers_arg {
+        Some(peers) => peers
+            .split(',')
+            .map(|peer| peer.trim().to_string())
+            .filter(|peer| !peer.is_empty())
+            .collect(),
+        None => config.node.burn_view_peers.clone(),
+    };
+    if peers.is_empty() {
+        return Err(
+            "No peers to compare to: set `node.burn_view_peers`, or pass `--peers`".to_string(),
+        );
+    }
+    let depth = depth.unwrap_or(DEFAULT_BURN_VIEW_DEPTH);
+    if depth == 0 {
+        return Err("`--depth` must be at least 1".to_string());
+    }
+
+    let ours = local_view(&config, depth)?;
+    let heights: Vec<u64> = ours.keys().cloned().collect();
+    match (heights.first(), heights.last()) {
+        (Some(lowest), Some(highest)) => println!(
+            "Comparing sortitions at burn heights {}-{} to {} peer(s)",
+            lowest,
+            highest,
+            peers.len()
+        ),
+        _ => return Err("This node has no sortitions to compare yet".to_string()),
+    }
+
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(BURN_VIEW_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", &e))?;
+    let mut comparisons = vec![];
+    for peer in peers.iter() {
+        let comparison = match peer_view(&client, peer, &heights) {
+            Ok(theirs) => compare_peer(&ours, &theirs),
+            Err(e) => PeerComparison::Unreachable(e),
+        };
+        match comparison {
+            PeerComparison::Agrees(agreed) => println!(
+                "{}: agrees at {} of {} height(s)",
+                peer,
+                agreed,
+                heights.len()
+            ),
+            PeerComparison::Diverges(ref height, ref our_hash, ref their_hash) => println!(
+                "{}: DIVERGES at burn height {} (ours: {}, theirs: {})",
+                peer, height, our_hash, their_hash
+            ),
+            PeerComparison::NoOverlap => println!(
+                "{}: has not processed any of these heights yet; not counted",
+                peer
+            ),
+            PeerComparison::Unreachable(ref e) => println!("{}: unreachable: {}", peer, e),
+        }
+        comparisons.push(comparison);
+    }
+
+    match verdict(&comparisons) {
+        BurnViewVerdict::Consistent => {
+            println!("Consistent: every peer that answered agrees with this node");
+            Ok(())
+        }
+        BurnViewVerdict::LocalFork => Err(
+            "Local fork: every peer that answered disagrees with this node, so this node is likely forked"
+                .to_string(),
+        ),
+        BurnViewVerdict::NetworkSplit => Err(
+            "Network split: some peers agree with this node and some don't, so the network is likely forked"
+                .to_string(),
+        ),
+        BurnViewVerdict::Unknown => Err(
+            "Unknown: no peer answered at any of the compared heights".to_string(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn view(hashes: &[(u64, u8)]) -> BTreeMap<u64, ConsensusHash> {
+        hashes
+            .iter()
+            .map(|(height, byte)| (*height, ConsensusHash([*byte; 20])))
+            .collect()
+    }
+
+    #[test]
+    fn test_burn_view_comparison() {
+        let ours = view(&[(100, 1), (101, 2), (102, 3)]);
+
+        assert_eq!(compare_peer(&ours, &ours), PeerComparison::Agrees(3));
+        // a peer that is behind agrees as far as it has gotten
+        assert_eq!(
+            compare_peer(&ours, &view(&[(100, 1), (101, 2)])),
+            PeerComparison::Agrees(2)
+        );
+        // a peer that has not reached any of our heights has nothing to agree with
+        assert_eq!(
+            compare_peer(&ours, &view(&[(99, 7)])),
+            PeerComparison::NoOverlap
+        );
+        assert_eq!(compare_peer(&ours, &view(&[])), PeerComparison::NoOverlap);
+        assert_eq!(
+            compare_peer(&ours, &view(&[(100, 1), (101, 9), (102, 9)])),
+            PeerComparison::Diverges(101, ConsensusHash([2; 20]), ConsensusHash([9; 20]))
+        );
+
+        let agrees = PeerComparison::Agrees(3);
+        let diverges =
+            PeerComparison::Diverges(101, ConsensusHash([2; 20]), ConsensusHash([9; 20]));
+        let unreachable = PeerComparison::Unreachable("timed out".to_string());
+        let no_overlap = PeerComparison::NoOverlap;
+        assert_eq!(
+            verdict(&[agrees.clone(), unreachable.clone()]),
+            BurnViewVerdict::Consistent
+        );
+        assert_eq!(
+            verdict(&[diverges.clone(), diverges.clone(), unreachable.clone()]),
+            BurnViewVerdict::LocalFork
+        );
+        assert_eq!(
+            verdict(&[diverges.clone(), no_overlap.clone()]),
+            BurnViewVerdict::LocalFork
+        );
+        assert_eq!(
+            verdict(&[no_overlap.clone(), unreachable.clone()]),
+            BurnViewVerdict::Unknown
+        );
+        assert_eq!(verdict(&[agrees, diverges]), BurnViewVerdict::NetworkSplit);
+        assert_eq!(verdict(&[unreachable]), BurnViewVerdict::Unknown);
+        assert_eq!(verdict(&[]), BurnViewVerdict::Unknown);
+    }
+}
@@ -54,8 +54,8 @@ use stacks::util::sleep_ms;
 
 use stacks::monitoring::{increment_btc_blocks_received_counter, increment_btc_ops_sent_counter};
 
-#[cfg(test)]
 use stacks::chainstate::burn::Opcodes;
+use stacks::net::admin::{transfer_stx_sender, MinerWallet, TransferStxOpRequest};
 use stacks::types::chainstate::BurnchainHeaderHash;
 
 /// The number of bitcoin blocks that can have
@@ -63,6 +63,11 @@ use stacks::types::chainstate::BurnchainHeaderHash;
 ///  the cache is force-reset.
 const UTXO_CACHE_STALENESS_LIMIT: u64 = 6;
 const DUST_UTXO_LIMIT: u64 = 5500;
+/// The most a pre-STX transaction with one input and a change output can weigh
+const PRE_STX_MAX_TX_SIZE: u64 = 280;
+/// The most a transfer-STX transaction can weigh, with a full 61-byte memo and a change output.
+/// The pre-STX output it spends must cover the fee for this many bytes.
+const TRANSFER_STX_MAX_TX_SIZE: u64 = 320;
 
 pub struct BitcoinRegtestController {
     config: Config,
@@ -232,6 +237,7 @@ impl BitcoinRegtestController {
                 spv_headers_path: config.get_spv_headers_file_path(),
                 first_block: burnchain_params.first_block_height,
                 magic_bytes: burnchain_config.magic_bytes,
+                header_checkpoints: burnchain_config.header_checkpoints,
             }
         };
 
@@ -268,6 +274,7 @@ impl BitcoinRegtestController {
                 spv_headers_path: config.get_spv_headers_file_path(),
                 first_block: burnchain_params.first_block_height,
                 magic_bytes: burnchain_config.magic_bytes,
+                header_checkpoints: burnchain_config.header_checkpoints,
             }
         };
 
@@ -285,19 +292,9 @@ impl BitcoinRegtestController {
     }
 
     fn default_burnchain(&self) -> Burnchain {
-        let (network_name, _network_type) = self.config.burnchain.get_bitcoin_network();
         match &self.burnchain_config {
             Some(burnchain) => burnchain.clone(),
-            None => {
-                let working_dir = self.config.get_burn_db_path();
-                match Burnchain::new(&working_dir, &self.config.burnchain.chain, &network_name) {
-                    Ok(burnchain) => burnchain,
-                    Err(e) => {
-                        error!("Failed to instantiate burnchain: {}", e);
-                        panic!()
-                    }
-                }
-            }
+            None => self.config.get_burnchain(),
         }
     }
 
@@ -740,14 +737,132 @@ impl BitcoinRegtestController {
         Some(tx)
     }
 
-    #[cfg(not(test))]
-    fn build_transfer_stacks_tx(
+    /// Read the wallet that `public_key`'s block-commits are funded from: its UTXOs, and what a
+    /// block-commit costs at the configured burn fee cap and fee rate
+    pub fn get_miner_wallet(&self, public_key: &Secp256k1PublicKey) -> Result<MinerWallet, String> {
+        let (_, network_id) = self.config.burnchain.get_bitcoin_network();
+        let address = BitcoinAddress::from_bytes(
+            network_id,
+            BitcoinAddressType::PublicKeyHash,
+            &Hash160::from_data(&public_key.to_bytes()).to_bytes(),
+        )
+        .map_err(|e| format!("Failed to make burnchain address for miner: {:?}", &e))?;
+
+        let utxos = BitcoinRPCRequest::list_unspent(
+            &self.config,
+            vec![address.to_b58()],
+            false,
+            1,
+            &None,
+            0,
+        )
+        .map_err(|e| format!("Bitcoin RPC failure: error listing UTXOs: {:?}", &e))?;
+
+        Ok(MinerWallet {
+            address: address.to_b58(),
+            utxo_amounts: utxos.utxos.iter().map(|utxo| utxo.amount).collect(),
+            commit_cost: self.config.burnchain.burn_fee_cap
+                + self.config.burnchain.block_commit_tx_estimated_size
+                    * self.config.burnchain.satoshis_per_byte,
+        })
+    }
+
+    /// Put a transfer-STX operation for `request` on the burnchain, sent from `signer`: a pre-STX
+    /// transaction, funded from one of the signer's UTXOs, and a transfer-STX transaction spending
+    /// the pre-STX output.  Both are broadcast right away, so they can confirm in the same
+    /// burnchain block.  Unlike `get_utxos`, this asks bitcoind for UTXOs only once, and reports
+    /// why it could not go ahead.  Pays `burnchain.satoshis_per_byte`; returns the txids of the
+    /// two transactions.
+    pub fn submit_transfer_stx(
         &mut self,
-        _payload: TransferStxOp,
-        _signer: &mut BurnchainOpSigner,
-        _utxo: Option<UTXO>,
-    ) -> Option<Transaction> {
-        unimplemented!()
+        request: &TransferStxOpRequest,
+        signer: &BurnchainOpSigner,
+    ) -> Result<(String, String), String> {
+        let fee_rate = self.config.burnchain.satoshis_per_byte;
+        let public_key = signer.clone().get_public_key();
+        let sender = transfer_stx_sender(&public_key, self.config.is_mainnet());
+        request.check_sender(&sender)?;
+        let (_, network_id) = self.config.burnchain.get_bitcoin_network();
+        let address = BitcoinAddress::from_bytes(
+            network_id,
+            BitcoinAddressType::PublicKeyHash,
+            &Hash160::from_data(&public_key.to_bytes()).to_bytes(),
+        )
+        .map_err(|e| format!("Failed to make burnchain address for sender: {:?}", &e))?;
+
+        let total_required =
+            DUST_UTXO_LIMIT + (PRE_STX_MAX_TX_SIZE + TRANSFER_STX_MAX_TX_SIZE) * fee_rate;
+        let utxos = BitcoinRPCRequest::list_unspent(
+            &self.config,
+            vec![address.to_b58()],
+            false,
+            total_required,
+            &None,
+            0,
+        )
+        .map_err(|e| format!("Bitcoin RPC failure: error listing UTXOs: {:?}", &e))?;
+        if utxos.is_empty() {
+            return Err(format!(
+                "{} has no UTXO worth at least {} sats.  If it is funded, bitcoind may not be watching it: run `bitcoin-cli importaddress {}`",
+                address.to_b58(),
+                total_required,
+                address.to_b58()
+            ));
+        }
+
+        let pre_stx = PreStxOp {
+            output: sender.clone(),
+            txid: Txid([0u8; 32]),
+            vtxindex: 0,
+            block_height: 0,
+            burn_header_hash: BurnchainHeaderHash::zero(),
+        };
+        let pre_stx_tx = self
+            .build_pre_stacks_tx(pre_stx, &mut signer.clone(), Some(utxos))
+            .filter(|tx| !tx.input.is_empty())
+            .ok_or("Failed to build the pre-STX transaction".to_string())?;
+
+        let pre_stx_output = UTXO {
+            txid: pre_stx_tx.txid(),
+            vout: 1,
+            script_pub_key: pre_stx_tx.output[1].script_pubkey.clone(),
+            amount: pre_stx_tx.output[1].value,
+            confirmations: 0,
+        };
+        let transfer_stx = TransferStxOp {
+            sender,
+            recipient: request.recipient.clone(),
+            transfered_ustx: request.amount_ustx,
+            memo: request.memo.clone(),
+            txid: Txid([0u8; 32]),
+            vtxindex: 0,
+            block_height: 0,
+            burn_header_hash: BurnchainHeaderHash::zero(),
+        };
+        let transfer_stx_tx = self
+            .build_transfer_stacks_tx(transfer_stx, &mut signer.clone(), Some(pre_stx_output))
+            .filter(|tx| !tx.input.is_empty())
+            .ok_or("Failed to build the transfer-STX transaction".to_string())?;
+
+        let pre_stx_txid = pre_stx_tx.txid().be_hex_string();
+        BitcoinRPCRequest::send_raw_transaction(
+            &self.config,
+            SerializedTx::new(pre_stx_tx).to_hex(),
+        )
+        .map_err(|e| format!("Failed to send pre-STX transaction: {:?}", &e))?;
+        let transfer_stx_txid = transfer_stx_tx.txid().be_hex_string();
+        BitcoinRPCRequest::send_raw_transaction(
+            &self.config,
+            SerializedTx::new(transfer_stx_tx).to_hex(),
+        )
+        .map_err(|e| {
+            format!(
+                "Sent pre-STX transaction {}, but failed to send transfer-STX transaction: {:?}",
+                &pre_stx_txid, &e
+            )
+        })?;
+
+        Ok((pre_stx_txid, transfer_stx_txid))
     }
 
     #[cfg(test)]
@@ -765,7 +880,7 @@ impl BitcoinRegtestController {
                 unimplemented!();
             }
             BlockstackOperationType::PreStx(payload) => {
-                self.build_pre_stacks_tx(payload, op_signer)
+                self.build_pre_stacks_tx(payload, op_signer, None)
             }
             BlockstackOperationType::TransferStx(payload) => {
                 self.build_transfer_stacks_tx(payload, op_signer, utxo)
@@ -781,13 +896,10 @@ impl BitcoinRegtestController {
         }
     }
 
-    #[cfg(test)]
     /// Build a transfer stacks tx.
-    ///   this *only* works if the only existant UTXO is from a PreStx Op
-    ///   this is okay for testing, but obviously not okay for actual use.
-    ///   The reason for this constraint is that the bitcoin_regtest_controller's UTXO
-    ///     and signing logic are fairly intertwined, and untangling the two seems excessive
-    ///     for a functionality that won't be implemented for production via this controller.
+    ///   If `utxo_to_use` is given, it must be the pre-STX output of the sender, and it is the
+    ///   only UTXO spent.  Otherwise, this *only* works if the only existant UTXO is from a PreStx
+    ///   Op, which is okay for testing, but not for actual use.
     fn build_transfer_stacks_tx(
         &mut self,
         payload: TransferStxOp,
@@ -859,26 +971,21 @@ impl BitcoinRegtestController {
         Some(tx)
     }
 
-    #[cfg(not(test))]
-    fn build_pre_stacks_tx(
-        &mut self,
-        _payload: PreStxOp,
-        _signer: &mut BurnchainOpSigner,
-    ) -> Option<Transaction> {
-        unimplemented!()
-    }
-
-    #[cfg(test)]
+    /// Build a pre stacks tx.  Its output carries enough to pay for a transfer stacks tx that
+    /// spends it.
     fn build_pre_stacks_tx(
         &mut self,
         payload: PreStxOp,
         signer: &mut BurnchainOpSigner,
+        utxos_to_include: Option<UTXOSet>,
     ) -> Option<Transaction> {
         let public_key = signer.get_public_key();
-        let max_tx_size = 280;
+        let max_tx_size = PRE_STX_MAX_TX_SIZE;
 
-        let output_amt = DUST_UTXO_LIMIT + max_tx_size * self.config.burnchain.satoshis_per_byte;
-        let (mut tx, mut utxos) = self.prepare_tx(&public_key, output_amt, None, None, 0)?;
+        let output_amt =
+            DUST_UTXO_LIMIT + TRANSFER_STX_MAX_TX_SIZE * self.config.burnchain.satoshis_per_byte;
+        let (mut tx, mut utxos) =
+            self.prepare_tx(&public_key, output_amt, utxos_to_include, None, 0)?;
 
         // Serialize the payload
         let op_bytes = {
@@ -1519,7 +1626,7 @@ impl BurnchainController for BitcoinRegtestController {
                 self.build_user_burn_support_tx(payload, op_signer, attempt)
             }
             BlockstackOperationType::PreStx(payload) => {
-                self.build_pre_stacks_tx(payload, op_signer)
+                self.build_pre_stacks_tx(payload, op_signer, None)
             }
             BlockstackOperationType::TransferStx(payload) => {
                 self.build_transfer_stacks_tx(payload, op_signer, None)
@@ -0,0 +1,112 @@
+//! Chainstate migrations on upgrade.
+//!
+//! If this version of the node writes the chainstate in a different format than the one on disk,
+//! the chainstate is either converted all at once before the node starts, or, with
+//! `node.background_chainstate_migration`, converted a batch of blocks at a time on a thread of
+//! its own while the node keeps running on the old format.  In the latter case, the chains
+//! coordinator switches over to the new format as part of processing a block, once the converter
+//! has caught up.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use stacks::chainstate::stacks::db::migrations::{
+    self, ChainstateMigration, MIGRATION_SWITCH_OVER_MAX_BLOCKS,
+};
+use stacks::chainstate::stacks::db::StacksChainState;
+
+use crate::Config;
+
+/// How many blocks the background converter converts in each of its transactions
+const MIGRATION_BATCH_BLOCKS: u64 = 100;
+
+/// How long the background converter pauses between batches, so the node's own writes get in
+const MIGRATION_BATCH_PAUSE_MS: u64 = 100;
+
+/// Start, or run, the chainstate's pending migration, if it has one.  Run just after the
+/// chainstate is opened, before the node starts processing blocks.
+pub fn start(
+    config: &Config,
+    chainstate: &mut StacksChainState,
+    should_keep_running: Arc<AtomicBool>,
+) -> Result<(), String> {
+    let db_version = migrations::chainstate_version(chainstate.db())
+        .map_err(|e| format!("Failed to read the chainstate version: {:?}", &e))?;
+    let migration = match migrations::pending_migration(&db_version) {
+        Some(migration) => migration,
+        None => return Ok(()),
+    };
+
+    if !config.node.background_chainstate_migration {
+        info!(
+            "Migrating chainstate from version {} to {}; this may take a while",
+            migration.from_version(),
+            migration.to_version()
+        );
+        return migrations::migrate_offline(chainstate, migration)
+            .map_err(|e| format!("Failed to migrate chainstate: {:?}", &e));
+    }
+
+    info!(
+        "Migrating chainstate from version {} to {} in the background",
+        migration.from_version(),
+        migration.to_version()
+    );
+    let mainnet = config.is_mainnet();
+    let chain_id = config.burnchain.chain_id;
+    let chainstate_path = config.get_chainstate_path_str();
+    thread::Builder::new()
+        .name("chainstate-migration".to_string())
+        .spawn(move || {
+            let mut chainstate = match StacksChainState::open(mainnet, chain_id, &chainstate_path) {
+                Ok((chainstate, _)) => chainstate,
+                Err(e) => {
+                    error!("Failed to open chainstate to migrate it: {:?}", &e);
+                    return;
+                }
+            };
+            convert_until_finished(&mut chainstate, migration, &should_keep_running);
+        })
+        .map_err(|e| format!("Failed to start the chainstate migration thread: {}", &e))?;
+    Ok(())
+}
+
+/// Convert batches of blocks, keeping up with the blocks the node processes, until the chains
+/// coordinator finishes the migration
+fn convert_until_finished(
+    chainstate: &mut StacksChainState,
+    migration: &dyn ChainstateMigration,
+    should_keep_running: &AtomicBool,
+) {
+    let mut caught_up = false;
+    while should_keep_running.load(Ordering::SeqCst) {
+        match migrations::migrate_step(chainstate, migration, MIGRATION_BATCH_BLOCKS) {
+            Ok(None) => return,
+            Ok(Some(remaining)) if remaining <= MIGRATION_SWITCH_OVER_MAX_BLOCKS => {
+                if !caught_up {
+                    info!(
+                        "Chainstate migration to version {} has caught up; it will be finished along with the next block",
+                        migration.to_version()
+                    );
+                    caught_up = true;
+                }
+            }
+            Ok(Some(remaining)) => {
+                debug!(
+                    "Chainstate migration to version {}: {} blocks left",
+                    migration.to_version(),
+                    remaining
+                );
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to convert blocks for the chainstate migration: {:?}",
+                    &e
+                );
+            }
+        }
+        thread::sleep(Duration::from_millis(MIGRATION_BATCH_PAUSE_MS));
+    }
+}
@@ -1,20 +1,34 @@
 use std::convert::TryInto;
+use std::fmt;
 use std::fs;
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use rand::RngCore;
 
+use stacks::burnchains::bitcoin::address::BitcoinAddress;
 use stacks::burnchains::bitcoin::BitcoinNetworkType;
-use stacks::burnchains::{MagicBytes, BLOCKSTACK_MAGIC_MAINNET};
+use stacks::burnchains::{Burnchain, MagicBytes, PoxConstants, BLOCKSTACK_MAGIC_MAINNET};
+use stacks::chainstate::stacks::db::blocks::DEFAULT_SIGNATURE_VERIFICATION_THREADS;
+use stacks::chainstate::stacks::index::handles::DEFAULT_MAX_OPEN_HANDLES;
+use stacks::core::mempool::ContractDeployLimits;
 use stacks::core::{
     BLOCK_LIMIT_MAINNET, CHAIN_ID_MAINNET, CHAIN_ID_TESTNET, HELIUM_BLOCK_LIMIT,
     PEER_VERSION_MAINNET, PEER_VERSION_TESTNET,
 };
+use stacks::deps::bitcoin::util::hash::Sha256dHash;
+use stacks::net::atlas::validation::{TextEncodingValidator, ZonefileValidator};
+use stacks::net::atlas::AtlasConfig;
 use stacks::net::connection::ConnectionOptions;
+use stacks::net::relay::{
+    BlockPushOrder, MicroblockAcceptance, BLOCK_PUSH_ORDER_NAMES, MICROBLOCK_ACCEPTANCE_NAMES,
+    TX_RELAY_PAYLOAD_NAMES,
+};
 use stacks::net::{Neighbor, NeighborKey, PeerAddress};
 use stacks::util::get_epoch_time_ms;
-use stacks::util::hash::hex_bytes;
+use stacks::util::hash::{hex_bytes, Sha256Sum};
+use stacks::util::memory::MemoryBudget;
 use stacks::util::secp256k1::Secp256k1PrivateKey;
 use stacks::util::secp256k1::Secp256k1PublicKey;
 use stacks::vm::costs::ExecutionCost;
@@ -26,14 +40,16 @@ const DEFAULT_RBF_FEE_RATE_INCREMENT: u64 = 5;
 const LEADER_KEY_TX_ESTIM_SIZE: u64 = 290;
 const BLOCK_COMMIT_TX_ESTIM_SIZE: u64 = 350;
 const INV_REWARD_CYCLES_TESTNET: u64 = 6;
+const DEFAULT_STACKER_TX_FEE: u64 = 1000;
 
-#[derive(Clone, Deserialize, Default)]
+#[derive(Clone, Serialize, Deserialize, Default)]
 pub struct ConfigFile {
     pub burnchain: Option<BurnchainConfigFile>,
     pub node: Option<NodeConfigFile>,
     pub ustx_balance: Option<Vec<InitialBalanceFile>>,
     pub events_observer: Option<Vec<EventObserverConfigFile>>,
     pub connection_options: Option<ConnectionOptionsFile>,
+    pub stacker: Option<StackerConfigFile>,
 }
 
 #[derive(Clone, Deserialize, Default)]
@@ -88,6 +104,686 @@ mod tests {
             "STRYYQQ9M8KAF4NS7WNZQYY59X93XEKR31JP64CP"
         );
     }
+
+    #[test]
+    fn should_load_pox_overrides() {
+        let config = Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [burnchain]
+            mode = "mocknet"
+            pox_reward_length = 20
+            pox_prepare_length = 4
+            "#,
+        ));
+        let pox_constants = config.burnchain.get_pox_constants();
+        assert_eq!(pox_constants.reward_cycle_length, 20);
+        assert_eq!(pox_constants.prepare_length, 4);
+        assert_eq!(pox_constants.anchor_threshold, 3);
+        assert_eq!(
+            pox_constants.sunset_start,
+            PoxConstants::regtest_default().sunset_start
+        );
+        // every burnchain controller and the helium node get their burnchain with them applied
+        assert_eq!(config.get_burnchain().pox_constants, pox_constants);
+
+        // no overrides means the network's defaults
+        let config = Config::from_config_file(ConfigFile::from_str(""));
+        assert_eq!(
+            config.burnchain.get_pox_constants(),
+            PoxConstants::regtest_default()
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn should_reject_pox_overrides_on_mainnet() {
+        Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [burnchain]
+            mode = "mainnet"
+            pox_reward_length = 20
+            "#,
+        ));
+    }
+
+    #[test]
+    #[should_panic]
+    fn should_reject_inconsistent_pox_overrides() {
+        Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [burnchain]
+            mode = "mocknet"
+            pox_reward_length = 20
+            pox_prepare_length = 4
+            pox_anchor_threshold = 2
+            "#,
+        ));
+    }
+
+    #[test]
+    fn should_report_unknown_keys() {
+        let unknown = ConfigFile::unknown_keys(
+            r#"
+            typo_section = 1
+
+            [node]
+            miner = true
+            minr = false
+
+            [burnchain]
+            mode = "mocknet"
+
+            [[ustx_balance]]
+            address = "ST2QKZ4FKHAH1NQKYKYAYZPY440FEPK7GZ1R5HBP2"
+            amount = 10000000000000000
+            amnt = 1
+
+            [[mstx_balance]]
+            address = "ST2QKZ4FKHAH1NQKYKYAYZPY440FEPK7GZ1R5HBP2"
+            amount = 10000000000000000
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            unknown,
+            vec![
+                "node.minr".to_string(),
+                "typo_section".to_string(),
+                "ustx_balance[0].amnt".to_string()
+            ]
+        );
+
+        assert!(ConfigFile::unknown_keys("[node\nminer = true").is_err());
+    }
+
+    #[test]
+    fn should_check_config_consistency() {
+        let config_file = ConfigFile::from_str(
+            r#"
+            [node]
+            miner = true
+            rpc_bind = "0.0.0.0:20443"
+            p2p_bind = "0.0.0.0:20443"
+
+            [burnchain]
+            mode = "xenon"
+            "#,
+        );
+        let config = Config::from_config_file(config_file.clone());
+        let (errors, warnings) = config.check_consistency(&config_file);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("burnchain.username"));
+        assert!(errors[1].contains("node.p2p_bind"));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("node.seed"));
+
+        let config_file = ConfigFile::from_str(
+            r#"
+            [node]
+            mock_mining = true
+            seed = "0000000000000000000000000000000000000000000000000000000000000001"
+
+            [burnchain]
+            mode = "mocknet"
+            "#,
+        );
+        let config = Config::from_config_file(config_file.clone());
+        let (errors, warnings) = config.check_consistency(&config_file);
+        assert!(errors.is_empty());
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("node.mock_mining"));
+
+        let telemetry_file = ConfigFile::from_str(
+            r#"
+            [node]
+            telemetry_endpoint = "example.com/telemetry"
+            telemetry_interval_secs = 10
+
+            [burnchain]
+            mode = "mocknet"
+            "#,
+        );
+        let telemetry_config = Config::from_config_file(telemetry_file.clone());
+        let (errors, _) = telemetry_config.check_consistency(&telemetry_file);
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].contains("node.telemetry_endpoint"));
+        assert!(errors[1].contains("node.telemetry_interval_secs"));
+
+        // secrets don't get printed
+        let redacted = format!("{:?}", config.redacted());
+        assert!(!redacted.contains(&format!("{:?}", &config.node.seed)));
+        let mut config = config;
+        config.node.admin_auth_token = Some("admin-secret".to_string());
+        assert!(!format!("{:?}", config.redacted()).contains("admin-secret"));
+    }
+
+    #[test]
+    fn should_apply_memory_budget() {
+        use stacks::net::connection::CONNECTION_BUFFER_BYTES;
+
+        let config = Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [node]
+            memory_budget_mb = 2048
+
+            [burnchain]
+            mode = "mocknet"
+            "#,
+        ));
+        let budget = config.node.memory_budget().unwrap();
+        assert_eq!(budget.total_bytes, 2048 * 1024 * 1024);
+        assert!(budget.marf_connections > config.node.max_marf_handles);
+
+        let opts = &config.connection_options;
+        assert!(opts.num_clients < HELIUM_DEFAULT_CONNECTION_OPTIONS.num_clients);
+        assert!(
+            (opts.num_neighbors + opts.num_clients + opts.max_http_clients)
+                * CONNECTION_BUFFER_BYTES
+                <= budget.connection_buffer_bytes
+        );
+
+        let config_file = ConfigFile::from_str(
+            r#"
+            [node]
+            memory_budget_mb = 0
+
+            [burnchain]
+            mode = "mocknet"
+            "#,
+        );
+        let config = Config::from_config_file(config_file.clone());
+        let (errors, _) = config.check_consistency(&config_file);
+        assert!(errors.iter().any(|e| e.contains("node.memory_budget_mb")));
+
+        // pooled MARF handles can't be budgeted for without a cap on them
+        let config_file = ConfigFile::from_str(
+            r#"
+            [node]
+            memory_budget_mb = 2048
+            max_marf_handles = 0
+
+            [burnchain]
+            mode = "mocknet"
+            "#,
+        );
+        let config = Config::from_config_file(config_file.clone());
+        let (errors, _) = config.check_consistency(&config_file);
+        assert!(errors.iter().any(|e| e.contains("node.max_marf_handles")));
+    }
+
+    #[test]
+    fn should_load_header_checkpoints() {
+        let config = Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [burnchain]
+            mode = "xenon"
+            "#,
+        ));
+        assert!(config.burnchain.header_checkpoints.is_empty());
+
+        let config = Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [burnchain]
+            mode = "xenon"
+
+            [[burnchain.header_checkpoints]]
+            height = 0
+            hash = "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943"
+
+            [[burnchain.header_checkpoints]]
+            height = 1
+            hash = "00000000b873e79784647a6c82962c70d228557d24a747ea4d1b8bbe878e1206"
+            "#,
+        ));
+        assert_eq!(
+            config.burnchain.header_checkpoints,
+            vec![
+                (
+                    0,
+                    Sha256dHash::from_hex(
+                        "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943"
+                    )
+                    .unwrap()
+                ),
+                (
+                    1,
+                    Sha256dHash::from_hex(
+                        "00000000b873e79784647a6c82962c70d228557d24a747ea4d1b8bbe878e1206"
+                    )
+                    .unwrap()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn should_load_microblock_acceptance() {
+        let config = Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [burnchain]
+            mode = "mocknet"
+            "#,
+        ));
+        assert_eq!(
+            config.connection_options.microblock_acceptance,
+            MicroblockAcceptance::Full
+        );
+
+        let config_file = ConfigFile::from_str(
+            r#"
+            [node]
+            miner = true
+
+            [burnchain]
+            mode = "mocknet"
+
+            [connection_options]
+            microblock_acceptance = "headers-only"
+            "#,
+        );
+        let config = Config::from_config_file(config_file.clone());
+        assert_eq!(
+            config.connection_options.microblock_acceptance,
+            MicroblockAcceptance::HeadersOnly
+        );
+        let (errors, _) = config.check_consistency(&config_file);
+        assert!(errors
+            .iter()
+            .any(|e| e.contains("connection_options.microblock_acceptance")));
+    }
+
+    #[test]
+    fn should_load_mined_block_push_options() {
+        let config = Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [burnchain]
+            mode = "mocknet"
+            "#,
+        ));
+        assert_eq!(
+            config.connection_options.mined_block_push_order,
+            BlockPushOrder::Relay
+        );
+        assert_eq!(config.connection_options.mined_block_push_fanout, 0);
+
+        let config_file = ConfigFile::from_str(
+            r#"
+            [burnchain]
+            mode = "mocknet"
+
+            [connection_options]
+            mined_block_push_order = "latency"
+            mined_block_push_stagger_ms = 250
+            "#,
+        );
+        let config = Config::from_config_file(config_file.clone());
+        assert_eq!(
+            config.connection_options.mined_block_push_order,
+            BlockPushOrder::Latency
+        );
+        assert_eq!(config.connection_options.mined_block_push_stagger_ms, 250);
+        let (_, warnings) = config.check_consistency(&config_file);
+        assert!(warnings
+            .iter()
+            .any(|w| w.contains("connection_options.mined_block_push_stagger_ms")));
+        assert!(warnings
+            .iter()
+            .any(|w| w.contains("connection_options.mined_block_push_*")));
+    }
+
+    #[test]
+    fn should_load_atlas_config() {
+        let config = Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [node]
+            atlas_max_storage_mb = 512
+            atlas_evict_unreferenced = true
+            atlas_validate_text = true
+            atlas_validate_zonefiles = true
+
+            [burnchain]
+            mode = "mocknet"
+            "#,
+        ));
+        let atlas_config = config.make_atlas_config();
+        assert_eq!(
+            atlas_config.max_attachments_storage_bytes,
+            Some(512 * 1024 * 1024)
+        );
+        assert!(atlas_config.evict_unreferenced_attachments);
+        let rules: Vec<_> = atlas_config
+            .attachment_validators
+            .iter()
+            .map(|v| v.name())
+            .collect();
+        assert_eq!(rules, vec!["encoding", "zonefile"]);
+
+        let config_file = ConfigFile::from_str(
+            r#"
+            [node]
+            atlas_max_storage_mb = 0
+
+            [burnchain]
+            mode = "mocknet"
+            "#,
+        );
+        let config = Config::from_config_file(config_file.clone());
+        let (errors, _) = config.check_consistency(&config_file);
+        assert!(errors
+            .iter()
+            .any(|e| e.contains("node.atlas_max_storage_mb")));
+        let atlas_config = Config::from_config_file(ConfigFile::from_str("")).make_atlas_config();
+        assert_eq!(atlas_config.max_attachments_storage_bytes, None);
+        assert!(!atlas_config.evict_unreferenced_attachments);
+        assert!(atlas_config.attachment_validators.is_empty());
+    }
+
+    #[test]
+    fn should_load_stacker_config() {
+        let config = Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [stacker]
+            seed = "539e35c740079b79f931036651ad01f76d8fe1496dbd840ba9e62c7e7b355db001"
+            amount_ustx = 100000000000
+            pox_address = "n1htkoYKuLXzPbkn9avC2DJxt7X85qVNCK"
+            lock_period = 6
+            dry_run = true
+            "#,
+        ));
+        let stacker = config
+            .stacker
+            .expect("Failed to parse stacker config from toml");
+        assert_eq!(stacker.amount_ustx, 100000000000);
+        assert_eq!(
+            stacker.pox_address.to_b58(),
+            "n1htkoYKuLXzPbkn9avC2DJxt7X85qVNCK"
+        );
+        assert_eq!(stacker.lock_period, 6);
+        assert_eq!(stacker.tx_fee, DEFAULT_STACKER_TX_FEE);
+        assert!(stacker.dry_run);
+
+        let config = Config::from_config_file(ConfigFile::from_str(""));
+        assert!(config.stacker.is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn should_reject_stacker_lock_period() {
+        Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [stacker]
+            seed = "539e35c740079b79f931036651ad01f76d8fe1496dbd840ba9e62c7e7b355db001"
+            amount_ustx = 100000000000
+            pox_address = "n1htkoYKuLXzPbkn9avC2DJxt7X85qVNCK"
+            lock_period = 13
+            "#,
+        ));
+    }
+
+    #[test]
+    fn should_interpolate_env_vars() {
+        let lookup = |name: &str| match name {
+            "RPC_PORT" => Some("30443".to_string()),
+            "EMPTY" => Some("".to_string()),
+            _ => None,
+        };
+        let cases = vec![
+            ("0.0.0.0:${RPC_PORT}", "0.0.0.0:30443"),
+            ("${RPC_PORT}${RPC_PORT}", "3044330443"),
+            ("0.0.0.0:${P2P_PORT:-20444}", "0.0.0.0:20444"),
+            ("${EMPTY:-fallback}", "fallback"),
+            ("${UNSET:-}", ""),
+            ("cost $${RPC_PORT}", "cost ${RPC_PORT}"),
+            ("no variables $here", "no variables $here"),
+        ];
+        for (input, expected) in cases.into_iter() {
+            assert_eq!(
+                interpolate_env_vars_str(input, &lookup).unwrap(),
+                expected,
+                "{}",
+                input
+            );
+        }
+        assert!(interpolate_env_vars_str("${UNSET}", &lookup)
+            .unwrap_err()
+            .contains("UNSET"));
+        assert!(interpolate_env_vars_str("${RPC_PORT", &lookup).is_err());
+        assert!(interpolate_env_vars_str("${}", &lookup).is_err());
+
+        // only strings, anywhere in the config
+        let mut input: toml::Value = toml::from_str(
+            r#"
+            [node]
+            rpc_bind = "0.0.0.0:${RPC_PORT}"
+            max_microblocks = 5
+
+            [[events_observer]]
+            endpoint = "localhost:${RPC_PORT}"
+            events_keys = ["${EVENTS:-*}"]
+            "#,
+        )
+        .unwrap();
+        interpolate_env_vars(&mut input, &lookup).unwrap();
+        let config: ConfigFile = input.try_into().unwrap();
+        assert_eq!(
+            config.node.unwrap().rpc_bind,
+            Some("0.0.0.0:30443".to_string())
+        );
+        let observers = config.events_observer.unwrap();
+        assert_eq!(observers[0].endpoint, "localhost:30443");
+        assert_eq!(observers[0].events_keys, vec!["*".to_string()]);
+    }
+
+    #[test]
+    fn should_select_profiles() {
+        let content = r#"
+            [node]
+            rpc_bind = "0.0.0.0:20443"
+            miner = false
+
+            [burnchain]
+            chain = "bitcoin"
+            mode = "mocknet"
+
+            [[events_observer]]
+            endpoint = "localhost:3700"
+            events_keys = ["*"]
+
+            [profile.testnet.node]
+            miner = true
+
+            [profile.testnet.burnchain]
+            mode = "xenon"
+            peer_host = "bitcoind.xenon.blockstack.org"
+
+            [profile.mainnet.burnchain]
+            mode = "mainnet"
+
+            [[profile.mainnet.events_observer]]
+            endpoint = "localhost:3701"
+            events_keys = ["stx"]
+            "#;
+
+        let shared = ConfigFile::from_str(&ConfigFile::select_profile(content, None).unwrap());
+        let shared_burnchain = shared.burnchain.unwrap();
+        assert_eq!(shared_burnchain.mode, Some("mocknet".to_string()));
+        assert_eq!(shared.node.unwrap().miner, Some(false));
+
+        let testnet =
+            ConfigFile::from_str(&ConfigFile::select_profile(content, Some("testnet")).unwrap());
+        let testnet_node = testnet.node.unwrap();
+        let testnet_burnchain = testnet.burnchain.unwrap();
+        assert_eq!(testnet_node.miner, Some(true));
+        // shared settings carry through
+        assert_eq!(testnet_node.rpc_bind, Some("0.0.0.0:20443".to_string()));
+        assert_eq!(testnet_burnchain.chain, Some("bitcoin".to_string()));
+        assert_eq!(testnet_burnchain.mode, Some("xenon".to_string()));
+        assert_eq!(
+            testnet_burnchain.peer_host,
+            Some("bitcoind.xenon.blockstack.org".to_string())
+        );
+        assert_eq!(testnet.events_observer.unwrap().len(), 1);
+
+        // arrays of tables are replaced, not appended to
+        let mainnet =
+            ConfigFile::from_str(&ConfigFile::select_profile(content, Some("mainnet")).unwrap());
+        let observers = mainnet.events_observer.unwrap();
+        assert_eq!(observers.len(), 1);
+        assert_eq!(observers[0].endpoint, "localhost:3701");
+
+        let err = ConfigFile::select_profile(content, Some("regtest")).unwrap_err();
+        assert!(err.contains("`mainnet`") && err.contains("`testnet`"));
+        assert!(ConfigFile::select_profile("", Some("testnet")).is_err());
+
+        // the profiles themselves aren't settings
+        let selected = ConfigFile::select_profile(content, Some("testnet")).unwrap();
+        assert!(ConfigFile::unknown_keys(&selected).unwrap().is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn should_reject_stacker_pox_address_network() {
+        // a mainnet address, on a mocknet node
+        Config::from_config_file(ConfigFile::from_str(
+            r#"
+            [stacker]
+            seed = "539e35c740079b79f931036651ad01f76d8fe1496dbd840ba9e62c7e7b355db001"
+            amount_ustx = 100000000000
+            pox_address = "1FzTxL9Mxnm2fdmnQEArfhzJHevwbvcH6d"
+            "#,
+        ));
+    }
+
+    #[test]
+    fn should_parse_watched_principal_keys() {
+        let principal = "ST1HB1T8WRNBYB0Y3T7WXZS38NKKPTBR3EG9EPJKR";
+        match EventKeyType::from_string(&format!("watch:{}", principal)) {
+            Some(EventKeyType::WatchedPrincipal(watched)) => {
+                assert_eq!(watched, PrincipalData::parse(principal).unwrap())
+            }
+            other => panic!("Expected a watched principal, got {:?}", other),
+        }
+        let contract = format!("{}.exchange-wallet", principal);
+        match EventKeyType::from_string(&format!("watch:{}", &contract)) {
+            Some(EventKeyType::WatchedPrincipal(watched)) => {
+                assert_eq!(watched, PrincipalData::parse(&contract).unwrap())
+            }
+            other => panic!("Expected a watched principal, got {:?}", other),
+        }
+        assert!(EventKeyType::from_string("watch:not-a-principal").is_none());
+    }
+}
+
+/// Substitute environment variables into every string in a config (see `ConfigFile::from_str()`).
+fn interpolate_env_vars(
+    value: &mut toml::Value,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Result<(), String> {
+    match value {
+        toml::Value::String(s) => {
+            *s = interpolate_env_vars_str(s, lookup)?;
+        }
+        toml::Value::Array(items) => {
+            for item in items.iter_mut() {
+                interpolate_env_vars(item, lookup)?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (_, item) in table.iter_mut() {
+                interpolate_env_vars(item, lookup)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn interpolate_env_vars_str(
+    s: &str,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> Result<String, String> {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            // escaped
+            out.push_str(&rest[..start - 1]);
+            out.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("Unterminated `${{` in config value '{}'", s))?;
+        let expr = &rest[start + 2..start + end];
+        let (name, default) = match expr.find(":-") {
+            Some(i) => (&expr[..i], Some(&expr[i + 2..])),
+            None => (expr, None),
+        };
+        if name.is_empty() {
+            return Err(format!("Empty `${{}}` in config value '{}'", s));
+        }
+        match (lookup(name).filter(|v| !v.is_empty()), default) {
+            (Some(v), _) => out.push_str(&v),
+            (None, Some(default)) => out.push_str(default),
+            (None, None) => {
+                return Err(format!(
+                    "Config refers to environment variable `{}`, which is not set",
+                    name
+                ))
+            }
+        }
+        rest = &rest[start + end + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Merge `overlay` into `base`: tables key by key, and anything else by replacement.
+fn merge_toml(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base_table), toml::Value::Table(overlay_table)) => {
+            for (key, value) in overlay_table.into_iter() {
+                match base_table.get_mut(&key) {
+                    Some(base_value) => merge_toml(base_value, value),
+                    None => {
+                        base_table.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn collect_unknown_keys(
+    prefix: &str,
+    input: &toml::Value,
+    known: &serde_json::Value,
+    unknown: &mut Vec<String>,
+) {
+    match (input, known) {
+        (toml::Value::Table(table), serde_json::Value::Object(known_table)) => {
+            for (key, value) in table.iter() {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                match known_table.get(key) {
+                    Some(known_value) => collect_unknown_keys(&path, value, known_value, unknown),
+                    None => unknown.push(path),
+                }
+            }
+        }
+        (toml::Value::Array(items), serde_json::Value::Array(known_items)) => {
+            for (i, (item, known_item)) in items.iter().zip(known_items.iter()).enumerate() {
+                collect_unknown_keys(&format!("{}[{}]", prefix, i), item, known_item, unknown);
+            }
+        }
+        _ => {}
+    }
 }
 
 impl ConfigFile {
@@ -96,9 +792,63 @@ impl ConfigFile {
         Self::from_str(&content_str)
     }
 
+    /// Load a config file, using the settings of its profile `profile` (see `select_profile()`).
+    pub fn from_path_with_profile(path: &str, profile: Option<&str>) -> Result<ConfigFile, String> {
+        let content =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, &e))?;
+        let content = Self::select_profile(&content, profile)
+            .map_err(|e| format!("Failed to load {}: {}", path, &e))?;
+        Ok(Self::from_str(&content))
+    }
+
+    /// Resolve the profiles in a config file.  A file can hold settings for several networks:
+    /// its top-level settings are shared, and the settings under `[profile.<name>]` (such as
+    /// `[profile.testnet.burnchain]`) override them when `<name>` is selected.  Tables are merged
+    /// key by key, but any other value -- including an array of tables, like
+    /// `[[events_observer]]` -- replaces the shared value outright.  With no profile selected,
+    /// only the shared settings are used.  Returns the selected config, as TOML.
+    pub fn select_profile(content: &str, profile: Option<&str>) -> Result<String, String> {
+        let mut input: toml::Value = toml::from_str(content).map_err(|e| e.to_string())?;
+        let profiles = match input.as_table_mut() {
+            Some(table) => table.remove("profile"),
+            None => None,
+        };
+
+        if let Some(name) = profile {
+            let mut profiles = match profiles {
+                Some(toml::Value::Table(profiles)) => profiles,
+                Some(_) => return Err("`profile` must be a table of profiles".to_string()),
+                None => return Err(format!("No profile `{}`: the config has no profiles", name)),
+            };
+            let selected = match profiles.remove(name) {
+                Some(selected @ toml::Value::Table(_)) => selected,
+                Some(_) => return Err(format!("Profile `{}` must be a table", name)),
+                None => {
+                    let names: Vec<String> =
+                        profiles.keys().map(|name| format!("`{}`", name)).collect();
+                    return Err(format!(
+                        "No profile `{}`: the config's profiles are {}",
+                        name,
+                        names.join(", ")
+                    ));
+                }
+            };
+            merge_toml(&mut input, selected);
+        }
+
+        toml::to_string(&input).map_err(|e| e.to_string())
+    }
+
+    /// Parse a config file.  In any string value, `${VAR}` is replaced with the value of the
+    /// environment variable `VAR`, and `${VAR:-default}` likewise, but with `default` when `VAR`
+    /// is unset or empty.  Write `$${` for a literal `${`.
     pub fn from_str(content: &str) -> ConfigFile {
-        let mut config: ConfigFile = toml::from_str(content).unwrap();
-        let legacy_config: LegacyMstxConfigFile = toml::from_str(content).unwrap();
+        let mut input: toml::Value = toml::from_str(content).unwrap();
+        if let Err(e) = interpolate_env_vars(&mut input, &|name| std::env::var(name).ok()) {
+            panic!("{}", e);
+        }
+        let mut config: ConfigFile = input.clone().try_into().unwrap();
+        let legacy_config: LegacyMstxConfigFile = input.try_into().unwrap();
         if let Some(mstx_balance) = legacy_config.mstx_balance {
             warn!("'mstx_balance' inside toml config is deprecated, replace with 'ustx_balance'");
             config.ustx_balance = match config.ustx_balance {
@@ -109,6 +859,23 @@ impl ConfigFile {
         config
     }
 
+    /// List the keys in the given TOML config that the node does not recognize, and would
+    /// otherwise silently ignore.
+    pub fn unknown_keys(content: &str) -> Result<Vec<String>, String> {
+        let input: toml::Value = toml::from_str(content).map_err(|e| e.to_string())?;
+        let config: ConfigFile = input.clone().try_into().map_err(|e| e.to_string())?;
+
+        // every field of every section that was given is in here, set or not
+        let known = serde_json::to_value(&config).map_err(|e| e.to_string())?;
+
+        let mut unknown = vec![];
+        collect_unknown_keys("", &input, &known, &mut unknown);
+
+        // handled by from_str()
+        unknown.retain(|key| key != "mstx_balance");
+        Ok(unknown)
+    }
+
     pub fn neon() -> ConfigFile {
         let burnchain = BurnchainConfigFile {
             mode: Some("neon".to_string()),
@@ -398,7 +1165,7 @@ impl ConfigFile {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Config {
     pub burnchain: BurnchainConfig,
     pub node: NodeConfig,
@@ -406,6 +1173,7 @@ pub struct Config {
     pub events_observers: Vec<EventObserverConfig>,
     pub connection_options: ConnectionOptions,
     pub block_limit: ExecutionCost,
+    pub stacker: Option<StackerConfig>,
 }
 
 lazy_static! {
@@ -435,6 +1203,8 @@ lazy_static! {
         dns_timeout: 15_000,
         max_inflight_blocks: 6,
         max_inflight_attachments: 6,
+        max_inbound_handshakes_per_host: 20, // how many inbound p2p connections a host can open per minute
+        reserved_inbound_slots: 16,     // how many inbound p2p connections are held back for allowed peers (at most half of num_clients)
         .. std::default::Default::default()
     };
 }
@@ -483,11 +1253,83 @@ impl Config {
                     wait_time_for_microblocks: node
                         .wait_time_for_microblocks
                         .unwrap_or(default_node_config.wait_time_for_microblocks),
+                    mine_poison_microblocks: node
+                        .mine_poison_microblocks
+                        .unwrap_or(default_node_config.mine_poison_microblocks),
                     prometheus_bind: node.prometheus_bind,
                     pox_sync_sample_secs: node
                         .pox_sync_sample_secs
                         .unwrap_or(default_node_config.pox_sync_sample_secs),
                     use_test_genesis_chainstate: node.use_test_genesis_chainstate,
+                    costs_contract_path: node.costs_contract_path,
+                    log_level: node.log_level.as_ref().map(|level| {
+                        level.parse::<slog::Level>().expect(&format!(
+                            "Invalid `node.log_level` '{}': expected one of trace, debug, info, warn, error, critical",
+                            level
+                        ))
+                    }),
+                    log_json: node.log_format.as_ref().map(|format| match format.as_str() {
+                        "json" => true,
+                        "text" => false,
+                        _ => panic!(
+                            "Invalid `node.log_format` '{}': expected `text` or `json`",
+                            format
+                        ),
+                    }),
+                    otlp_endpoint: node.otlp_endpoint,
+                    otlp_sample_ratio: node
+                        .otlp_sample_ratio
+                        .unwrap_or(default_node_config.otlp_sample_ratio),
+                    shutdown_timeout_secs: node
+                        .shutdown_timeout_secs
+                        .unwrap_or(default_node_config.shutdown_timeout_secs),
+                    admin_auth_token: node.admin_auth_token,
+                    low_disk_space_mb: node
+                        .low_disk_space_mb
+                        .unwrap_or(default_node_config.low_disk_space_mb),
+                    critical_disk_space_mb: node
+                        .critical_disk_space_mb
+                        .unwrap_or(default_node_config.critical_disk_space_mb),
+                    memory_budget_mb: node.memory_budget_mb,
+                    max_marf_handles: node
+                        .max_marf_handles
+                        .unwrap_or(default_node_config.max_marf_handles),
+                    signature_verification_threads: node
+                        .signature_verification_threads
+                        .unwrap_or(default_node_config.signature_verification_threads),
+                    identity_key_path: node.identity_key_path,
+                    tip_notify_socket: node.tip_notify_socket,
+                    tip_hook: node.tip_hook,
+                    burn_block_hook: node.burn_block_hook,
+                    reorg_hook: node.reorg_hook,
+                    hook_timeout_secs: node
+                        .hook_timeout_secs
+                        .unwrap_or(default_node_config.hook_timeout_secs),
+                    telemetry_endpoint: node.telemetry_endpoint,
+                    telemetry_interval_secs: node
+                        .telemetry_interval_secs
+                        .unwrap_or(default_node_config.telemetry_interval_secs),
+                    persist_metrics: node
+                        .persist_metrics
+                        .unwrap_or(default_node_config.persist_metrics),
+                    burn_view_peers: node.burn_view_peers.unwrap_or_default(),
+                    atlas_max_storage_mb: node.atlas_max_storage_mb,
+                    atlas_evict_unreferenced: node
+                        .atlas_evict_unreferenced
+                        .unwrap_or(default_node_config.atlas_evict_unreferenced),
+                    atlas_validate_text: node
+                        .atlas_validate_text
+                        .unwrap_or(default_node_config.atlas_validate_text),
+                    atlas_validate_zonefiles: node
+                        .atlas_validate_zonefiles
+                        .unwrap_or(default_node_config.atlas_validate_zonefiles),
+                    profile_clarity: node
+                        .profile_clarity
+                        .unwrap_or(default_node_config.profile_clarity),
+                    clarity_profile_path: node.clarity_profile_path,
+                    background_chainstate_migration: node
+                        .background_chainstate_migration
+                        .unwrap_or(default_node_config.background_chainstate_migration),
                 };
                 (node_config, node.bootstrap_node, node.deny_nodes)
             }
@@ -521,6 +1363,9 @@ impl Config {
                     if node.use_test_genesis_chainstate == Some(true) {
                         panic!("Attempted to run mainnet node with `use_test_genesis_chainstate`");
                     }
+                    if node.costs_contract_path.is_some() {
+                        panic!("Attempted to run mainnet node with `costs_contract_path`");
+                    }
                     if let Some(ref balances) = config_file.ustx_balance {
                         if balances.len() > 0 {
                             panic!(
@@ -603,6 +1448,27 @@ impl Config {
                     rbf_fee_increment: burnchain
                         .rbf_fee_increment
                         .unwrap_or(default_burnchain_config.rbf_fee_increment),
+                    pox_reward_length: burnchain.pox_reward_length,
+                    pox_prepare_length: burnchain.pox_prepare_length,
+                    pox_anchor_threshold: burnchain.pox_anchor_threshold,
+                    header_checkpoints: burnchain
+                        .header_checkpoints
+                        .map(|checkpoints| {
+                            checkpoints
+                                .iter()
+                                .map(|checkpoint| {
+                                    let hash = Sha256dHash::from_hex(&checkpoint.hash)
+                                        .unwrap_or_else(|_| {
+                                            panic!(
+                                                "Invalid block hash '{}' in `burnchain.header_checkpoints`",
+                                                &checkpoint.hash
+                                            )
+                                        });
+                                    (checkpoint.height, hash)
+                                })
+                                .collect()
+                        })
+                        .unwrap_or(default_burnchain_config.header_checkpoints),
                 }
             }
             None => default_burnchain_config,
@@ -623,6 +1489,8 @@ impl Config {
             panic!("Config is missing the setting `burnchain.local_mining_public_key` (mandatory for helium)")
         }
 
+        burnchain.check_pox_overrides();
+
         if let Some(bootstrap_node) = bootstrap_node {
             node.set_bootstrap_nodes(bootstrap_node, burnchain.chain_id, burnchain.peer_version);
         } else {
@@ -687,7 +1555,7 @@ impl Config {
             _ => (),
         };
 
-        let connection_options = match config_file.connection_options {
+        let mut connection_options = match config_file.connection_options {
             Some(opts) => {
                 let ip_addr = match opts.public_ip_address {
                     Some(public_ip_address) => {
@@ -715,8 +1583,38 @@ impl Config {
                 opts.read_only_call_limit_runtime.map(|x| {
                     read_only_call_limit.runtime = x;
                 });
+                if let Some(ref payloads) = opts.tx_relay_allowed_payloads {
+                    for payload in payloads.iter() {
+                        if !TX_RELAY_PAYLOAD_NAMES.contains(&payload.as_str()) {
+                            panic!(
+                                "Unknown transaction type '{}' in tx_relay_allowed_payloads (expected one of {:?})",
+                                payload, TX_RELAY_PAYLOAD_NAMES
+                            );
+                        }
+                    }
+                }
+                let microblock_acceptance = match opts.microblock_acceptance {
+                    Some(ref name) => MicroblockAcceptance::from_name(name).unwrap_or_else(|| {
+                        panic!(
+                            "Unknown microblock_acceptance '{}' (expected one of {:?})",
+                            name, MICROBLOCK_ACCEPTANCE_NAMES
+                        )
+                    }),
+                    None => HELIUM_DEFAULT_CONNECTION_OPTIONS.microblock_acceptance,
+                };
+                let mined_block_push_order = match opts.mined_block_push_order {
+                    Some(ref name) => BlockPushOrder::from_name(name).unwrap_or_else(|| {
+                        panic!(
+                            "Unknown mined_block_push_order '{}' (expected one of {:?})",
+                            name, BLOCK_PUSH_ORDER_NAMES
+                        )
+                    }),
+                    None => HELIUM_DEFAULT_CONNECTION_OPTIONS.mined_block_push_order,
+                };
                 ConnectionOptions {
                     read_only_call_limit,
+                    microblock_acceptance,
+                    mined_block_push_order,
                     inbox_maxlen: opts
                         .inbox_maxlen
                         .unwrap_or_else(|| HELIUM_DEFAULT_CONNECTION_OPTIONS.inbox_maxlen.clone()),
@@ -803,6 +1701,77 @@ impl Config {
                                 .clone()
                         },
                     ),
+                    read_only_call_threads: opts.read_only_call_threads.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.read_only_call_threads
+                    }),
+                    rpc_metadata_deadline_ms: opts.rpc_metadata_deadline_ms.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.rpc_metadata_deadline_ms
+                    }),
+                    rpc_chain_state_deadline_ms: opts.rpc_chain_state_deadline_ms.unwrap_or_else(
+                        || HELIUM_DEFAULT_CONNECTION_OPTIONS.rpc_chain_state_deadline_ms,
+                    ),
+                    rpc_evaluation_deadline_ms: opts.rpc_evaluation_deadline_ms.unwrap_or_else(
+                        || HELIUM_DEFAULT_CONNECTION_OPTIONS.rpc_evaluation_deadline_ms,
+                    ),
+                    rpc_bulk_deadline_ms: opts
+                        .rpc_bulk_deadline_ms
+                        .unwrap_or_else(|| HELIUM_DEFAULT_CONNECTION_OPTIONS.rpc_bulk_deadline_ms),
+                    max_http_response_size: opts.max_http_response_size.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.max_http_response_size
+                    }),
+                    tx_filter_interval: opts
+                        .tx_filter_interval
+                        .unwrap_or_else(|| HELIUM_DEFAULT_CONNECTION_OPTIONS.tx_filter_interval),
+                    tx_relay_min_fee_rate: opts
+                        .tx_relay_min_fee_rate
+                        .unwrap_or_else(|| HELIUM_DEFAULT_CONNECTION_OPTIONS.tx_relay_min_fee_rate),
+                    tx_relay_max_len: opts
+                        .tx_relay_max_len
+                        .unwrap_or_else(|| HELIUM_DEFAULT_CONNECTION_OPTIONS.tx_relay_max_len),
+                    tx_relay_allowed_payloads: opts.tx_relay_allowed_payloads.clone(),
+                    contract_deploy_max_len: opts.contract_deploy_max_len.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.contract_deploy_max_len
+                    }),
+                    contract_deploy_max_depth: opts.contract_deploy_max_depth.unwrap_or_else(
+                        || HELIUM_DEFAULT_CONNECTION_OPTIONS.contract_deploy_max_depth,
+                    ),
+                    contract_deploy_max_analysis_runtime: opts
+                        .contract_deploy_max_analysis_runtime
+                        .unwrap_or_else(|| {
+                            HELIUM_DEFAULT_CONNECTION_OPTIONS.contract_deploy_max_analysis_runtime
+                        }),
+                    max_queued_pushes: opts
+                        .max_queued_pushes
+                        .unwrap_or_else(|| HELIUM_DEFAULT_CONNECTION_OPTIONS.max_queued_pushes),
+                    max_pushes_per_pass: opts
+                        .max_pushes_per_pass
+                        .unwrap_or_else(|| HELIUM_DEFAULT_CONNECTION_OPTIONS.max_pushes_per_pass),
+                    mined_block_push_fanout: opts.mined_block_push_fanout.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.mined_block_push_fanout
+                    }),
+                    mined_block_push_stagger_ms: opts.mined_block_push_stagger_ms.unwrap_or_else(
+                        || HELIUM_DEFAULT_CONNECTION_OPTIONS.mined_block_push_stagger_ms,
+                    ),
+                    http_mirror_mode: opts
+                        .http_mirror_mode
+                        .unwrap_or_else(|| HELIUM_DEFAULT_CONNECTION_OPTIONS.http_mirror_mode),
+                    http_mirror_cache_max_age: opts.http_mirror_cache_max_age.unwrap_or_else(
+                        || HELIUM_DEFAULT_CONNECTION_OPTIONS.http_mirror_cache_max_age,
+                    ),
+                    max_inbound_handshakes_per_host: opts
+                        .max_inbound_handshakes_per_host
+                        .unwrap_or_else(|| {
+                            HELIUM_DEFAULT_CONNECTION_OPTIONS.max_inbound_handshakes_per_host
+                        }),
+                    inbound_handshake_window: opts.inbound_handshake_window.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.inbound_handshake_window
+                    }),
+                    reserved_inbound_slots: opts.reserved_inbound_slots.unwrap_or_else(|| {
+                        HELIUM_DEFAULT_CONNECTION_OPTIONS.reserved_inbound_slots
+                    }),
+                    stale_tip_burn_blocks: opts
+                        .stale_tip_burn_blocks
+                        .unwrap_or_else(|| HELIUM_DEFAULT_CONNECTION_OPTIONS.stale_tip_burn_blocks),
                     download_interval: opts.download_interval.unwrap_or_else(|| {
                         HELIUM_DEFAULT_CONNECTION_OPTIONS.download_interval.clone()
                     }),
@@ -839,9 +1808,16 @@ impl Config {
             }
             None => HELIUM_DEFAULT_CONNECTION_OPTIONS.clone(),
         };
+        if let Some(budget) = node.memory_budget() {
+            connection_options.limit_buffer_memory(budget.connection_buffer_bytes);
+        }
 
         let block_limit = BLOCK_LIMIT_MAINNET.clone();
 
+        let stacker = config_file
+            .stacker
+            .map(|stacker| StackerConfig::from_config_file(stacker, burnchain.mode == "mainnet"));
+
         Config {
             node,
             burnchain,
@@ -849,9 +1825,200 @@ impl Config {
             events_observers,
             connection_options,
             block_limit,
+            stacker,
         }
     }
 
+    /// Check for settings that are each valid on their own, but don't make sense together.
+    /// Returns the problems that will stop the node from working as configured, and the ones
+    /// that merely look like mistakes.
+    pub fn check_consistency(&self, config_file: &ConfigFile) -> (Vec<String>, Vec<String>) {
+        let mut errors = vec![];
+        let mut warnings = vec![];
+        let node_file = config_file.node.clone().unwrap_or_default();
+
+        if self.node.miner {
+            // the miner finds its UTXOs and sends its operations through bitcoind
+            if self.burnchain.mode != "mocknet"
+                && (self.burnchain.username.is_none() || self.burnchain.password.is_none())
+            {
+                errors.push("`node.miner` requires the bitcoind RPC credentials `burnchain.username` and `burnchain.password`".to_string());
+            }
+            if node_file.seed.is_none() {
+                warnings.push("`node.miner` is set without a `node.seed`, so the miner will use a new key (and Bitcoin address) every time it starts".to_string());
+            }
+        } else {
+            if node_file.mock_mining == Some(true) {
+                warnings.push(
+                    "`node.mock_mining` has no effect unless `node.miner` is set".to_string(),
+                );
+            }
+            if node_file.mine_microblocks == Some(true) {
+                warnings.push(
+                    "`node.mine_microblocks` has no effect unless `node.miner` is set".to_string(),
+                );
+            }
+        }
+
+        if self.node.rpc_bind == self.node.p2p_bind {
+            errors.push(format!(
+                "`node.rpc_bind` and `node.p2p_bind` are both {}",
+                &self.node.rpc_bind
+            ));
+        }
+
+        if self.node.log_json == Some(true) && !cfg!(feature = "slog_json") {
+            errors.push(
+                "`node.log_format = \"json\"` requires a stacks-node built with the `slog_json` feature"
+                    .to_string(),
+            );
+        }
+
+        if self.node.otlp_endpoint.is_some() && !cfg!(feature = "monitoring_otlp") {
+            errors.push(
+                "`node.otlp_endpoint` requires a stacks-node built with the `monitoring_otlp` feature"
+                    .to_string(),
+            );
+        }
+        if !(self.node.otlp_sample_ratio >= 0.0 && self.node.otlp_sample_ratio <= 1.0) {
+            errors.push(format!(
+                "`node.otlp_sample_ratio` must be between 0 and 1, not {}",
+                self.node.otlp_sample_ratio
+            ));
+        }
+
+        if self.node.critical_disk_space_mb > self.node.low_disk_space_mb
+            && self.node.low_disk_space_mb > 0
+        {
+            errors.push(format!(
+                "`node.critical_disk_space_mb` ({}) must not be more than `node.low_disk_space_mb` ({})",
+                self.node.critical_disk_space_mb, self.node.low_disk_space_mb
+            ));
+        }
+
+        if let Some(ref token) = self.node.admin_auth_token {
+            if token.trim().is_empty() {
+                errors.push("`node.admin_auth_token` must not be empty".to_string());
+            }
+        }
+
+        if let Some(ref endpoint) = self.node.telemetry_endpoint {
+            if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                errors.push(format!(
+                    "`node.telemetry_endpoint` must be an http:// or https:// URL, not '{}'",
+                    endpoint
+                ));
+            }
+            if self.node.telemetry_interval_secs < 60 {
+                errors.push(format!(
+                    "`node.telemetry_interval_secs` must be at least 60, not {}",
+                    self.node.telemetry_interval_secs
+                ));
+            }
+        }
+
+        for peer in self.node.burn_view_peers.iter() {
+            if !peer.starts_with("http://") && !peer.starts_with("https://") {
+                errors.push(format!(
+                    "`node.burn_view_peers` must hold http:// or https:// URLs, not '{}'",
+                    peer
+                ));
+            }
+        }
+
+        if self.node.tip_notify_socket.is_some() && !cfg!(unix) {
+            errors.push(
+                "`node.tip_notify_socket` is only supported on UNIX-like platforms".to_string(),
+            );
+        }
+
+        if self.node.tip_hook.is_some()
+            || self.node.burn_block_hook.is_some()
+            || self.node.reorg_hook.is_some()
+        {
+            if !cfg!(unix) {
+                errors.push("Hook commands are only supported on UNIX-like platforms".to_string());
+            }
+            if self.node.hook_timeout_secs == 0 {
+                errors.push("`node.hook_timeout_secs` must be more than 0".to_string());
+            }
+        }
+
+        if self.node.atlas_max_storage_mb == Some(0) {
+            errors.push("`node.atlas_max_storage_mb` must be more than 0".to_string());
+        }
+
+        if self.node.memory_budget_mb == Some(0) {
+            errors.push("`node.memory_budget_mb` must be more than 0".to_string());
+        } else if self.node.memory_budget_mb.is_some() && self.node.max_marf_handles == 0 {
+            errors.push(
+                "`node.memory_budget_mb` needs a cap on `node.max_marf_handles` to budget their page caches"
+                    .to_string(),
+            );
+        } else if self.node.memory_budget_mb.is_some() && self.connection_options.num_clients == 0 {
+            warnings.push(
+                "`node.memory_budget_mb` is too small to leave room for any inbound p2p connections"
+                    .to_string(),
+            );
+        }
+
+        if self.node.miner
+            && !self
+                .connection_options
+                .microblock_acceptance
+                .builds_unconfirmed_state()
+        {
+            errors.push(
+                "A miner must set `connection_options.microblock_acceptance` to \"full\", to mine off of unconfirmed microblocks"
+                    .to_string(),
+            );
+        }
+
+        if self.connection_options.mined_block_push_stagger_ms > 0
+            && self.connection_options.mined_block_push_fanout == 0
+        {
+            warnings.push("`connection_options.mined_block_push_stagger_ms` has no effect unless `connection_options.mined_block_push_fanout` is set".to_string());
+        }
+        if !self.node.miner
+            && (self.connection_options.mined_block_push_order != BlockPushOrder::Relay
+                || self.connection_options.mined_block_push_fanout > 0)
+        {
+            warnings.push(
+                "`connection_options.mined_block_push_*` have no effect on a node that does not mine"
+                    .to_string(),
+            );
+        }
+
+        if self.stacker.is_some() && self.burnchain.mode == "mocknet" {
+            warnings.push("`[stacker]` has no effect on a mocknet node".to_string());
+        }
+
+        for observer in self.events_observers.iter() {
+            if observer.events_keys.is_empty() {
+                warnings.push(format!(
+                    "Event observer {} is not subscribed to any events",
+                    &observer.endpoint
+                ));
+            }
+        }
+
+        (errors, warnings)
+    }
+
+    /// A copy of this config without its secrets, for printing
+    pub fn redacted(&self) -> Config {
+        let mut config = self.clone();
+        config.node.seed = vec![];
+        config.node.local_peer_seed = vec![];
+        if config.burnchain.password.is_some() {
+            config.burnchain.password = Some("<redacted>".to_string());
+        }
+        if config.node.admin_auth_token.is_some() {
+            config.node.admin_auth_token = Some("<redacted>".to_string());
+        }
+        config
+    }
+
     fn get_burnchain_path(&self) -> PathBuf {
         let mut path = PathBuf::from(&self.node.working_dir);
         path.push(&self.burnchain.mode);
@@ -880,6 +2047,25 @@ impl Config {
             .to_string()
     }
 
+    /// The burnchain this node runs on, with the PoX constants from
+    /// `BurnchainConfig::get_pox_constants`, so that any overrides are applied
+    pub fn get_burnchain(&self) -> Burnchain {
+        let (network_name, _) = self.burnchain.get_bitcoin_network();
+        let mut burnchain = match Burnchain::new(
+            &self.get_burn_db_path(),
+            &self.burnchain.chain,
+            &network_name,
+        ) {
+            Ok(burnchain) => burnchain,
+            Err(e) => {
+                error!("Failed to instantiate burnchain: {}", e);
+                panic!()
+            }
+        };
+        burnchain.pox_constants = self.burnchain.get_pox_constants();
+        burnchain
+    }
+
     pub fn get_burn_db_path(&self) -> String {
         self.get_burnchain_path()
             .to_str()
@@ -905,12 +2091,51 @@ impl Config {
         path.to_str().expect("Unable to produce path").to_string()
     }
 
+    pub fn get_identity_key_path(&self) -> String {
+        match self.node.identity_key_path {
+            Some(ref path) => path.clone(),
+            None => {
+                let mut path = self.get_chainstate_path();
+                path.set_file_name("identity.key");
+                path.to_str().expect("Unable to produce path").to_string()
+            }
+        }
+    }
+
     pub fn get_atlas_db_file_path(&self) -> String {
         let mut path = self.get_chainstate_path();
         path.set_file_name("atlas.sqlite");
         path.to_str().expect("Unable to produce path").to_string()
     }
 
+    pub fn make_atlas_config(&self) -> AtlasConfig {
+        let mut atlas_config = AtlasConfig::default(self.is_mainnet());
+        atlas_config.max_attachments_storage_bytes = self
+            .node
+            .atlas_max_storage_mb
+            .map(|mb| mb.saturating_mul(1024 * 1024));
+        atlas_config.evict_unreferenced_attachments = self.node.atlas_evict_unreferenced;
+        if self.node.atlas_validate_text {
+            atlas_config
+                .attachment_validators
+                .push(Arc::new(TextEncodingValidator));
+        }
+        if self.node.atlas_validate_zonefiles {
+            atlas_config
+                .attachment_validators
+                .push(Arc::new(ZonefileValidator));
+        }
+        atlas_config
+    }
+
+    pub fn make_contract_deploy_limits(&self) -> ContractDeployLimits {
+        ContractDeployLimits {
+            max_len: self.connection_options.contract_deploy_max_len,
+            max_depth: self.connection_options.contract_deploy_max_depth,
+            max_analysis_runtime: self.connection_options.contract_deploy_max_analysis_runtime,
+        }
+    }
+
     pub fn add_initial_balance(&mut self, address: String, amount: u64) {
         let new_balance = InitialBalance {
             address: PrincipalData::parse_standard_principal(&address)
@@ -929,6 +2154,16 @@ impl Config {
         total
     }
 
+    /// Load the custom boot `costs` contract, if one is configured
+    pub fn get_custom_costs_contract(&self) -> Option<String> {
+        self.node.costs_contract_path.as_ref().map(|path| {
+            fs::read_to_string(path).expect(&format!(
+                "FATAL: failed to read custom costs contract from '{}'",
+                path
+            ))
+        })
+    }
+
     pub fn is_mainnet(&self) -> bool {
         match self.burnchain.mode.as_str() {
             "mainnet" => true,
@@ -962,6 +2197,7 @@ impl std::default::Default for Config {
             events_observers: vec![],
             connection_options,
             block_limit,
+            stacker: None,
         }
     }
 }
@@ -990,6 +2226,12 @@ pub struct BurnchainConfig {
     pub leader_key_tx_estimated_size: u64,
     pub block_commit_tx_estimated_size: u64,
     pub rbf_fee_increment: u64,
+    /// overrides for the network's PoX constants.  Only permitted on regtest networks.
+    pub pox_reward_length: Option<u32>,
+    pub pox_prepare_length: Option<u32>,
+    pub pox_anchor_threshold: Option<u32>,
+    /// trusted (height, hash) pairs the Bitcoin headers must match
+    pub header_checkpoints: Vec<(u64, Sha256dHash)>,
 }
 
 impl BurnchainConfig {
@@ -1017,6 +2259,10 @@ impl BurnchainConfig {
             leader_key_tx_estimated_size: LEADER_KEY_TX_ESTIM_SIZE,
             block_commit_tx_estimated_size: BLOCK_COMMIT_TX_ESTIM_SIZE,
             rbf_fee_increment: DEFAULT_RBF_FEE_RATE_INCREMENT,
+            pox_reward_length: None,
+            pox_prepare_length: None,
+            pox_anchor_threshold: None,
+            header_checkpoints: vec![],
         }
     }
 
@@ -1046,9 +2292,87 @@ impl BurnchainConfig {
             _ => panic!("Invalid bitcoin mode -- expected mainnet, testnet, or regtest"),
         }
     }
+
+    fn has_pox_overrides(&self) -> bool {
+        self.pox_reward_length.is_some()
+            || self.pox_prepare_length.is_some()
+            || self.pox_anchor_threshold.is_some()
+    }
+
+    /// The PoX constants this node runs with: the network's defaults, with any overrides from the
+    /// config file applied.  The boot code bakes these in when the chainstate is instantiated, so
+    /// changing them requires starting from an empty working directory.
+    pub fn get_pox_constants(&self) -> PoxConstants {
+        let defaults = match self.get_bitcoin_network() {
+            (_, BitcoinNetworkType::Mainnet) => PoxConstants::mainnet_default(),
+            (_, BitcoinNetworkType::Testnet) => PoxConstants::testnet_default(),
+            (_, BitcoinNetworkType::Regtest) => PoxConstants::regtest_default(),
+        };
+        if !self.has_pox_overrides() {
+            return defaults;
+        }
+
+        let reward_cycle_length = self
+            .pox_reward_length
+            .unwrap_or(defaults.reward_cycle_length);
+        let prepare_length = self.pox_prepare_length.unwrap_or(defaults.prepare_length);
+        // a shorter or longer prepare phase needs a matching threshold
+        let anchor_threshold = self.pox_anchor_threshold.unwrap_or_else(|| {
+            if self.pox_prepare_length.is_some() {
+                prepare_length / 2 + 1
+            } else {
+                defaults.anchor_threshold
+            }
+        });
+
+        PoxConstants::new(
+            reward_cycle_length,
+            prepare_length,
+            anchor_threshold,
+            defaults.pox_rejection_fraction,
+            defaults.pox_participation_threshold_pct,
+            defaults.sunset_start,
+            defaults.sunset_end,
+        )
+    }
+
+    /// Make sure any PoX overrides are internally consistent, and that they're only used where
+    /// they can't split the node off of a public network.
+    fn check_pox_overrides(&self) {
+        if !self.has_pox_overrides() {
+            return;
+        }
+        match self.get_bitcoin_network() {
+            (_, BitcoinNetworkType::Regtest) => {}
+            _ => panic!(
+                "PoX parameters can only be overridden on regtest networks -- a {} node would not reach consensus with its peers",
+                &self.mode
+            ),
+        }
+
+        let defaults = PoxConstants::regtest_default();
+        let reward_cycle_length = self
+            .pox_reward_length
+            .unwrap_or(defaults.reward_cycle_length);
+        let prepare_length = self.pox_prepare_length.unwrap_or(defaults.prepare_length);
+        if prepare_length == 0 || prepare_length >= reward_cycle_length {
+            panic!(
+                "'pox_prepare_length' ({}) must be positive and less than 'pox_reward_length' ({})",
+                prepare_length, reward_cycle_length
+            );
+        }
+        if let Some(anchor_threshold) = self.pox_anchor_threshold {
+            if anchor_threshold <= prepare_length / 2 || anchor_threshold > prepare_length {
+                panic!(
+                    "'pox_anchor_threshold' ({}) must be more than half of, and at most, 'pox_prepare_length' ({})",
+                    anchor_threshold, prepare_length
+                );
+            }
+        }
+    }
 }
 
-#[derive(Clone, Deserialize, Default)]
+#[derive(Clone, Serialize, Deserialize, Default)]
 pub struct BurnchainConfigFile {
     pub chain: Option<String>,
     pub burn_fee_cap: Option<u64>,
@@ -1070,6 +2394,10 @@ pub struct BurnchainConfigFile {
     pub block_commit_tx_estimated_size: Option<u64>,
     pub rbf_fee_increment: Option<u64>,
     pub max_rbf: Option<u64>,
+    pub pox_reward_length: Option<u32>,
+    pub pox_prepare_length: Option<u32>,
+    pub pox_anchor_threshold: Option<u32>,
+    pub header_checkpoints: Option<Vec<HeaderCheckpointFile>>,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -1090,9 +2418,86 @@ pub struct NodeConfig {
     pub microblock_frequency: u64,
     pub max_microblocks: u64,
     pub wait_time_for_microblocks: u64,
+    /// Have the miner report forks in its parent's microblock stream with `PoisonMicroblock`
+    /// transactions, to claim part of the forking leader's coinbase
+    pub mine_poison_microblocks: bool,
     pub prometheus_bind: Option<String>,
     pub pox_sync_sample_secs: u64,
     pub use_test_genesis_chainstate: Option<bool>,
+    /// Path to a Clarity file to install as the boot `costs` contract at genesis, instead of the
+    /// default cost schedule.  Only used on private networks.
+    pub costs_contract_path: Option<String>,
+    /// Log at this level, instead of the level given by the `STACKS_LOG_*` environment variables.
+    /// Can be changed without restarting the node.
+    pub log_level: Option<slog::Level>,
+    /// Write logs as JSON (true) or text (false), instead of as `STACKS_LOG_JSON` says
+    pub log_json: Option<bool>,
+    /// OTLP/HTTP collector to export trace spans to, like `http://localhost:4318`.  Requires the
+    /// `monitoring_otlp` feature.
+    pub otlp_endpoint: Option<String>,
+    /// Fraction of traces to record, between 0 and 1
+    pub otlp_sample_ratio: f64,
+    /// How long to wait, after SIGTERM or SIGINT, for the node to shut down cleanly before exiting
+    /// anyway
+    pub shutdown_timeout_secs: u64,
+    /// Token that requests to the admin RPC endpoints must present as `Authorization: Bearer`.
+    /// The admin endpoints are disabled if this is not set.
+    pub admin_auth_token: Option<String>,
+    /// Stop accepting new blocks and transactions, and stop mining, while there are fewer than
+    /// this many MB free on the chainstate volume (0 to never)
+    pub low_disk_space_mb: u64,
+    /// Shut down cleanly once there are fewer than this many MB free on the chainstate volume (0
+    /// to never)
+    pub critical_disk_space_mb: u64,
+    /// Size SQLite's page caches and the number of p2p and HTTP connections to fit in about this
+    /// many MB.  Unbounded if not set.
+    pub memory_budget_mb: Option<u64>,
+    /// Keep at most this many read-only MARF handles open at once, closing idle ones as needed
+    /// (0 for no cap)
+    pub max_marf_handles: u64,
+    /// Verify a block's transaction signatures across this many threads before running them (0
+    /// or 1 to verify each one as it runs)
+    pub signature_verification_threads: usize,
+    /// File holding the node's p2p identity key.  Defaults to `identity.key` next to the peer DB.
+    pub identity_key_path: Option<String>,
+    /// UNIX socket to announce new Stacks and burnchain tips on, one line of JSON each
+    pub tip_notify_socket: Option<String>,
+    /// Shell command to run each time the canonical Stacks tip changes
+    pub tip_hook: Option<String>,
+    /// Shell command to run on each new burnchain block
+    pub burn_block_hook: Option<String>,
+    /// Shell command to run when the canonical Stacks tip moves to a block that does not descend
+    /// from the previous one
+    pub reorg_hook: Option<String>,
+    /// Kill hook commands that run for longer than this
+    pub hook_timeout_secs: u64,
+    /// URL to POST anonymous telemetry reports to.  No telemetry is reported if this is not set.
+    pub telemetry_endpoint: Option<String>,
+    /// How often to report telemetry
+    pub telemetry_interval_secs: u64,
+    /// Save the totals of the Prometheus counters to the working directory, and carry them over
+    /// to the next run
+    pub persist_metrics: bool,
+    /// RPC URLs of the nodes `stacks-node burn-view` compares our sortitions to
+    pub burn_view_peers: Vec<String>,
+    /// Evict BNS zonefiles and other attachments once they take up more than this many MB,
+    /// starting with the ones no name refers to any more.  Unbounded if not set.
+    pub atlas_max_storage_mb: Option<u64>,
+    /// Evict attachments no BNS name refers to any more, even while under
+    /// `atlas_max_storage_mb`.  Peers can no longer fetch those attachments from this node.
+    pub atlas_evict_unreferenced: bool,
+    /// Only store attachments that are UTF-8 text without NUL bytes
+    pub atlas_validate_text: bool,
+    /// Only store attachments that look like DNS zonefiles
+    pub atlas_validate_zonefiles: bool,
+    /// Record how much time and cost each contract function takes in each block, for the admin
+    /// RPC endpoint `GET /v2/admin/clarity_profiles`
+    pub profile_clarity: bool,
+    /// Also append each block's Clarity profile to this file, one line of JSON per block
+    pub clarity_profile_path: Option<String>,
+    /// If the chainstate needs to be migrated to this version's format, convert it while the
+    /// node keeps running, instead of all at once before the node starts
+    pub background_chainstate_migration: bool,
 }
 
 impl NodeConfig {
@@ -1131,12 +2536,48 @@ impl NodeConfig {
             microblock_frequency: 30_000,
             max_microblocks: u16::MAX as u64,
             wait_time_for_microblocks: 30_000,
+            mine_poison_microblocks: true,
             prometheus_bind: None,
             pox_sync_sample_secs: 30,
             use_test_genesis_chainstate: None,
+            costs_contract_path: None,
+            log_level: None,
+            log_json: None,
+            otlp_endpoint: None,
+            otlp_sample_ratio: 1.0,
+            shutdown_timeout_secs: 60,
+            admin_auth_token: None,
+            low_disk_space_mb: 1024,
+            critical_disk_space_mb: 256,
+            memory_budget_mb: None,
+            max_marf_handles: DEFAULT_MAX_OPEN_HANDLES,
+            signature_verification_threads: DEFAULT_SIGNATURE_VERIFICATION_THREADS,
+            identity_key_path: None,
+            tip_notify_socket: None,
+            tip_hook: None,
+            burn_block_hook: None,
+            reorg_hook: None,
+            hook_timeout_secs: 30,
+            telemetry_endpoint: None,
+            telemetry_interval_secs: 3600,
+            persist_metrics: false,
+            burn_view_peers: vec![],
+            atlas_max_storage_mb: None,
+            atlas_evict_unreferenced: false,
+            atlas_validate_text: false,
+            atlas_validate_zonefiles: false,
+            profile_clarity: false,
+            clarity_profile_path: None,
+            background_chainstate_migration: false,
         }
     }
 
+    pub fn memory_budget(&self) -> Option<MemoryBudget> {
+        self.memory_budget_mb.map(|mb| {
+            MemoryBudget::from_total_bytes(mb.saturating_mul(1024 * 1024), self.max_marf_handles)
+        })
+    }
+
     fn default_neighbor(
         addr: SocketAddr,
         pubk: Secp256k1PublicKey,
@@ -1162,19 +2603,54 @@ impl NodeConfig {
         }
     }
 
-    pub fn add_bootstrap_node(&mut self, bootstrap_node: &str, chain_id: u32, peer_version: u32) {
+    /// The private key this node uses to identify itself to its peers.  The `local_peer_seed` is
+    /// re-hashed until it is a valid secp256k1 key.
+    pub fn local_peer_private_key(&self) -> Secp256k1PrivateKey {
+        let mut re_hashed_seed = self.local_peer_seed.clone();
+        loop {
+            match Secp256k1PrivateKey::from_slice(&re_hashed_seed[..]) {
+                Ok(sk) => break sk,
+                Err(_) => {
+                    re_hashed_seed = Sha256Sum::from_data(&re_hashed_seed[..])
+                        .as_bytes()
+                        .to_vec()
+                }
+            }
+        }
+    }
+
+    /// Parse a peer given as PUBKEY@HOST:PORT, as in the `bootstrap_node` setting.
+    pub fn parse_bootstrap_node(
+        bootstrap_node: &str,
+        chain_id: u32,
+        peer_version: u32,
+    ) -> Result<Neighbor, String> {
         let parts: Vec<&str> = bootstrap_node.split("@").collect();
         if parts.len() != 2 {
-            panic!(
+            return Err(format!(
                 "Invalid bootstrap node '{}': expected PUBKEY@IP:PORT",
                 bootstrap_node
-            );
+            ));
         }
         let (pubkey_str, hostport) = (parts[0], parts[1]);
         let pubkey = Secp256k1PublicKey::from_hex(pubkey_str)
-            .expect(&format!("Invalid public key '{}'", pubkey_str));
-        let sockaddr = hostport.to_socket_addrs().unwrap().next().unwrap();
-        let neighbor = NodeConfig::default_neighbor(sockaddr, pubkey, chain_id, peer_version);
+            .map_err(|e| format!("Invalid public key '{}': {:?}", pubkey_str, e))?;
+        let sockaddr = hostport
+            .to_socket_addrs()
+            .map_err(|e| format!("Invalid address '{}': {}", hostport, e))?
+            .next()
+            .ok_or(format!("Address '{}' did not resolve", hostport))?;
+        Ok(NodeConfig::default_neighbor(
+            sockaddr,
+            pubkey,
+            chain_id,
+            peer_version,
+        ))
+    }
+
+    pub fn add_bootstrap_node(&mut self, bootstrap_node: &str, chain_id: u32, peer_version: u32) {
+        let neighbor = NodeConfig::parse_bootstrap_node(bootstrap_node, chain_id, peer_version)
+            .unwrap_or_else(|e| panic!("{}", e));
         self.bootstrap_node.push(neighbor);
     }
 
@@ -1213,7 +2689,7 @@ impl NodeConfig {
     }
 }
 
-#[derive(Clone, Default, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct ConnectionOptionsFile {
     pub inbox_maxlen: Option<usize>,
     pub outbox_maxlen: Option<usize>,
@@ -1244,6 +2720,31 @@ pub struct ConnectionOptionsFile {
     pub read_only_call_limit_read_count: Option<u64>,
     pub read_only_call_limit_runtime: Option<u64>,
     pub maximum_call_argument_size: Option<u32>,
+    pub read_only_call_threads: Option<usize>,
+    pub rpc_metadata_deadline_ms: Option<u64>,
+    pub rpc_chain_state_deadline_ms: Option<u64>,
+    pub rpc_evaluation_deadline_ms: Option<u64>,
+    pub rpc_bulk_deadline_ms: Option<u64>,
+    pub max_http_response_size: Option<u64>,
+    pub tx_filter_interval: Option<u64>,
+    pub tx_relay_min_fee_rate: Option<u64>,
+    pub tx_relay_max_len: Option<u64>,
+    pub tx_relay_allowed_payloads: Option<Vec<String>>,
+    pub contract_deploy_max_len: Option<u64>,
+    pub contract_deploy_max_depth: Option<u64>,
+    pub contract_deploy_max_analysis_runtime: Option<u64>,
+    pub microblock_acceptance: Option<String>,
+    pub stale_tip_burn_blocks: Option<u64>,
+    pub max_queued_pushes: Option<u64>,
+    pub max_pushes_per_pass: Option<u64>,
+    pub mined_block_push_order: Option<String>,
+    pub mined_block_push_fanout: Option<u64>,
+    pub mined_block_push_stagger_ms: Option<u64>,
+    pub http_mirror_mode: Option<bool>,
+    pub http_mirror_cache_max_age: Option<u64>,
+    pub max_inbound_handshakes_per_host: Option<u64>,
+    pub inbound_handshake_window: Option<u64>,
+    pub reserved_inbound_slots: Option<u64>,
     pub download_interval: Option<u64>,
     pub inv_sync_interval: Option<u64>,
     pub full_inv_sync_interval: Option<u64>,
@@ -1256,7 +2757,7 @@ pub struct ConnectionOptionsFile {
     pub antientropy_public: Option<bool>,
 }
 
-#[derive(Clone, Deserialize, Default)]
+#[derive(Clone, Serialize, Deserialize, Default)]
 pub struct NodeConfigFile {
     pub name: Option<String>,
     pub seed: Option<String>,
@@ -1274,24 +2775,139 @@ pub struct NodeConfigFile {
     pub microblock_frequency: Option<u64>,
     pub max_microblocks: Option<u64>,
     pub wait_time_for_microblocks: Option<u64>,
+    pub mine_poison_microblocks: Option<bool>,
     pub prometheus_bind: Option<String>,
     pub pox_sync_sample_secs: Option<u64>,
     pub use_test_genesis_chainstate: Option<bool>,
+    pub costs_contract_path: Option<String>,
+    pub log_level: Option<String>,
+    pub log_format: Option<String>,
+    pub otlp_endpoint: Option<String>,
+    pub otlp_sample_ratio: Option<f64>,
+    pub shutdown_timeout_secs: Option<u64>,
+    pub admin_auth_token: Option<String>,
+    pub low_disk_space_mb: Option<u64>,
+    pub critical_disk_space_mb: Option<u64>,
+    pub memory_budget_mb: Option<u64>,
+    pub max_marf_handles: Option<u64>,
+    pub signature_verification_threads: Option<usize>,
+    pub identity_key_path: Option<String>,
+    pub tip_notify_socket: Option<String>,
+    pub tip_hook: Option<String>,
+    pub burn_block_hook: Option<String>,
+    pub reorg_hook: Option<String>,
+    pub hook_timeout_secs: Option<u64>,
+    pub telemetry_endpoint: Option<String>,
+    pub telemetry_interval_secs: Option<u64>,
+    pub persist_metrics: Option<bool>,
+    pub burn_view_peers: Option<Vec<String>>,
+    pub atlas_max_storage_mb: Option<u64>,
+    pub atlas_evict_unreferenced: Option<bool>,
+    pub atlas_validate_text: Option<bool>,
+    pub atlas_validate_zonefiles: Option<bool>,
+    pub profile_clarity: Option<bool>,
+    pub clarity_profile_path: Option<String>,
+    pub background_chainstate_migration: Option<bool>,
 }
 
-#[derive(Clone, Deserialize, Default)]
+#[derive(Clone, Serialize, Deserialize, Default)]
+pub struct StackerConfigFile {
+    pub seed: Option<String>,
+    pub amount_ustx: Option<u64>,
+    pub pox_address: Option<String>,
+    pub lock_period: Option<u64>,
+    pub tx_fee: Option<u64>,
+    pub dry_run: Option<bool>,
+}
+
+/// Parameters for stacker mode, in which the node stacks its own STX every time its previous
+/// lock expires.
+#[derive(Clone)]
+pub struct StackerConfig {
+    /// key that signs (and pays for) the stack-stx transactions
+    pub private_key: Secp256k1PrivateKey,
+    pub amount_ustx: u128,
+    /// where the PoX reward for this stacker's slots gets paid
+    pub pox_address: BitcoinAddress,
+    /// number of reward cycles to lock for, each time
+    pub lock_period: u64,
+    pub tx_fee: u64,
+    /// if true, only log the transactions that would have been sent
+    pub dry_run: bool,
+}
+
+impl fmt::Debug for StackerConfig {
+    /// leaves out the private key
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StackerConfig")
+            .field("amount_ustx", &self.amount_ustx)
+            .field("pox_address", &self.pox_address.to_b58())
+            .field("lock_period", &self.lock_period)
+            .field("tx_fee", &self.tx_fee)
+            .field("dry_run", &self.dry_run)
+            .finish()
+    }
+}
+
+impl StackerConfig {
+    fn from_config_file(stacker: StackerConfigFile, mainnet: bool) -> StackerConfig {
+        let private_key = Secp256k1PrivateKey::from_hex(
+            &stacker
+                .seed
+                .expect("Stacker mode requires a 'seed' (hex-encoded private key)"),
+        )
+        .expect("Stacker 'seed' should be a hex encoded private key");
+
+        let amount_ustx = stacker
+            .amount_ustx
+            .expect("Stacker mode requires an 'amount_ustx'");
+        if amount_ustx == 0 {
+            panic!("Stacker 'amount_ustx' must be positive");
+        }
+
+        let pox_address_str = stacker
+            .pox_address
+            .expect("Stacker mode requires a 'pox_address'");
+        let pox_address = BitcoinAddress::from_b58(&pox_address_str).expect(&format!(
+            "Stacker 'pox_address' '{}' is not a valid base58check Bitcoin address",
+            &pox_address_str
+        ));
+        if mainnet != (pox_address.network_id == BitcoinNetworkType::Mainnet) {
+            panic!(
+                "Stacker 'pox_address' '{}' is not an address for this node's network",
+                &pox_address_str
+            );
+        }
+
+        let lock_period = stacker.lock_period.unwrap_or(1);
+        if lock_period < 1 || lock_period > 12 {
+            panic!("Stacker 'lock_period' must be between 1 and 12 reward cycles");
+        }
+
+        StackerConfig {
+            private_key,
+            amount_ustx: amount_ustx as u128,
+            pox_address,
+            lock_period,
+            tx_fee: stacker.tx_fee.unwrap_or(DEFAULT_STACKER_TX_FEE),
+            dry_run: stacker.dry_run.unwrap_or(false),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Default)]
 pub struct EventObserverConfigFile {
     pub endpoint: String,
     pub events_keys: Vec<String>,
 }
 
-#[derive(Clone, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct EventObserverConfig {
     pub endpoint: String,
     pub events_keys: Vec<EventKeyType>,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub enum EventKeyType {
     SmartContractEvent((QualifiedContractIdentifier, String)),
     AssetEvent(AssetIdentifier),
@@ -1300,6 +2916,8 @@ pub enum EventKeyType {
     Microblocks,
     AnyEvent,
     BurnchainBlocks,
+    /// Activity of one principal, sent to `/watched_address_activity`
+    WatchedPrincipal(PrincipalData),
 }
 
 impl EventKeyType {
@@ -1324,6 +2942,12 @@ impl EventKeyType {
             return Some(EventKeyType::Microblocks);
         }
 
+        if raw_key.starts_with("watch:") {
+            return PrincipalData::parse(&raw_key["watch:".len()..])
+                .ok()
+                .map(EventKeyType::WatchedPrincipal);
+        }
+
         let comps: Vec<_> = raw_key.split("::").collect();
         if comps.len() == 1 {
             let split: Vec<_> = comps[0].split(".").collect();
@@ -1367,8 +2991,14 @@ pub struct InitialBalance {
     pub amount: u64,
 }
 
-#[derive(Clone, Deserialize, Default)]
+#[derive(Clone, Serialize, Deserialize, Default)]
 pub struct InitialBalanceFile {
     pub address: String,
     pub amount: u64,
 }
+
+#[derive(Clone, Serialize, Deserialize, Default)]
+pub struct HeaderCheckpointFile {
+    pub height: u64,
+    pub hash: String,
+}
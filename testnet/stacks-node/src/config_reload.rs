@@ -0,0 +1,580 @@
+//! Reloading part of a running node's config.
+//!
+//! Restarting a node costs minutes of sync (and, for a miner, missed tenures), so a few settings
+//! can be changed in place.  On SIGHUP, a node that was started with `--config` re-reads that
+//! file and applies these settings:
+//!
+//! * `node.log_level`, right away.
+//! * `node.deny_nodes`.  Newly-denied peers are disconnected, and peers no longer on the list
+//!   are allowed again.
+//! * The RPC limits in `[connection_options]`: `max_http_clients`, the `read_only_call_limit_*`
+//!   settings, and `maximum_call_argument_size`.  These apply to HTTP connections opened after
+//!   the reload.
+//! * The `[[events_observer]]` list.  Events dispatched after the reload go to the new observers.
+//! * The identity key in `node.identity_key_path` (see `identity`).  The node re-handshakes with
+//!   its peers to tell them about a new key.
+//!
+//! Every other setting is only read at startup.  A config that fails to load, or that
+//! `check_consistency()` finds errors in, is rejected as a whole, and the node keeps running with
+//! the settings it has.
+
+use std::cell::Cell;
+use std::fs;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use stacks::net::connection::ConnectionOptions;
+use stacks::net::db::PeerDB;
+use stacks::net::p2p::PeerNetwork;
+use stacks::net::Neighbor;
+use stacks::util::get_epoch_time_secs;
+use stacks::util::log::set_loglevel;
+use stacks::util::secp256k1::{Secp256k1PrivateKey, Secp256k1PublicKey};
+use stacks::vm::costs::ExecutionCost;
+
+use crate::config::EventObserverConfig;
+use crate::identity;
+use crate::{Config, ConfigFile, EventDispatcher};
+
+/// How long a peer in `node.deny_nodes` is denied for (the same as at startup)
+const DENY_NODE_DURATION: u64 = 24 * 365 * 3600;
+
+thread_local! {
+    static LOADING_CONFIG: Cell<bool> = Cell::new(false);
+}
+
+/// Whether this thread is in `load_config_str()`
+pub fn is_loading_config() -> bool {
+    LOADING_CONFIG.with(|loading| loading.get())
+}
+
+/// Wrap the panic hook installed so far in one that lets the panics of `load_config_str()`
+/// through, so that a bad config is reported instead of aborting the node.  Every other panic is
+/// handed to the previous hook.
+pub fn chain_panic_hook() {
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |panic_info| {
+        if is_loading_config() {
+            // a bad config, which the loader reports
+            return;
+        }
+        previous_hook(panic_info);
+    }));
+}
+
+/// Load a config the same way `start` does, but return an error instead of panicking on the
+/// first invalid setting.
+pub fn load_config_str(content: &str) -> Result<(ConfigFile, Config), String> {
+    LOADING_CONFIG.with(|loading| loading.set(true));
+    let loaded = panic::catch_unwind(|| {
+        let config_file = ConfigFile::from_str(content);
+        let config = Config::from_config_file(config_file.clone());
+        (config_file, config)
+    });
+    LOADING_CONFIG.with(|loading| loading.set(false));
+
+    loaded.map_err(|payload| {
+        payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_else(|| "unknown error".to_string())
+    })
+}
+
+pub fn load_config(
+    config_path: &str,
+    profile: Option<&str>,
+) -> Result<(ConfigFile, Config), String> {
+    let content = fs::read_to_string(config_path)
+        .map_err(|e| format!("Failed to read {}: {}", config_path, &e))?;
+    let content = ConfigFile::select_profile(&content, profile)?;
+    load_config_str(&content)
+}
+
+/// The settings that can be changed without restarting the node
+#[derive(Clone, Debug)]
+pub struct ReloadableSettings {
+    pub log_level: Option<slog::Level>,
+    pub deny_nodes: Vec<Neighbor>,
+    pub max_http_clients: u64,
+    pub read_only_call_limit: ExecutionCost,
+    pub maximum_call_argument_size: u32,
+    pub events_observers: Vec<EventObserverConfig>,
+    /// None if the node has not written out its identity key yet
+    pub identity_key: Option<Secp256k1PrivateKey>,
+}
+
+impl ReloadableSettings {
+    pub fn from_config(config: &Config) -> ReloadableSettings {
+        ReloadableSettings {
+            log_level: config.node.log_level,
+            deny_nodes: config.node.deny_nodes.clone(),
+            max_http_clients: config.connection_options.max_http_clients,
+            read_only_call_limit: config.connection_options.read_only_call_limit.clone(),
+            maximum_call_argument_size: config.connection_options.maximum_call_argument_size,
+            events_observers: config.events_observers.clone(),
+            identity_key: identity::read_identity_key_if_present(&config.get_identity_key_path())
+                .unwrap_or(None),
+        }
+    }
+
+    pub fn apply_rpc_limits(&self, opts: &mut ConnectionOptions) {
+        opts.max_http_clients = self.max_http_clients;
+        opts.read_only_call_limit = self.read_only_call_limit.clone();
+        opts.maximum_call_argument_size = self.maximum_call_argument_size;
+    }
+
+    /// A description of each setting that differs from `previous`
+    pub fn changes_from(&self, previous: &ReloadableSettings) -> Vec<String> {
+        let mut changes = vec![];
+        if self.log_level != previous.log_level {
+            changes.push(format!(
+                "node.log_level: {:?} -> {:?}",
+                &previous.log_level, &self.log_level
+            ));
+        }
+        let deny_keys = |settings: &ReloadableSettings| -> Vec<String> {
+            settings
+                .deny_nodes
+                .iter()
+                .map(|neighbor| format!("{}", &neighbor.addr))
+                .collect()
+        };
+        if deny_keys(self) != deny_keys(previous) {
+            changes.push(format!(
+                "node.deny_nodes: {:?} -> {:?}",
+                deny_keys(previous),
+                deny_keys(self)
+            ));
+        }
+        if self.max_http_clients != previous.max_http_clients {
+            changes.push(format!(
+                "connection_options.max_http_clients: {} -> {}",
+                previous.max_http_clients, self.max_http_clients
+            ));
+        }
+        if self.read_only_call_limit != previous.read_only_call_limit {
+            changes.push(format!(
+                "connection_options.read_only_call_limit: {:?} -> {:?}",
+                &previous.read_only_call_limit, &self.read_only_call_limit
+            ));
+        }
+        if self.maximum_call_argument_size != previous.maximum_call_argument_size {
+            changes.push(format!(
+                "connection_options.maximum_call_argument_size: {} -> {}",
+                previous.maximum_call_argument_size, self.maximum_call_argument_size
+            ));
+        }
+        if self.observers_changed(previous) {
+            let endpoints = |settings: &ReloadableSettings| -> Vec<String> {
+                settings
+                    .events_observers
+                    .iter()
+                    .map(|observer| observer.endpoint.clone())
+                    .collect()
+            };
+            changes.push(format!(
+                "events_observer: {:?} -> {:?}",
+                endpoints(previous),
+                endpoints(self)
+            ));
+        }
+        if let Some(ref key) = self.identity_key {
+            if previous.identity_key.as_ref() != Some(key) {
+                changes.push(format!(
+                    "identity key: now {}",
+                    Secp256k1PublicKey::from_private(key).to_hex()
+                ));
+            }
+        }
+        changes
+    }
+
+    fn observers_changed(&self, previous: &ReloadableSettings) -> bool {
+        // EventKeyType has no PartialEq
+        format!("{:?}", &self.events_observers) != format!("{:?}", &previous.events_observers)
+    }
+
+    /// Apply the settings that live in the p2p thread: the RPC limits, the deny list, and the
+    /// identity key.  `previous` are the settings that were in effect until now.
+    pub fn apply_to_peer_network(&self, previous: &ReloadableSettings, network: &mut PeerNetwork) {
+        self.apply_rpc_limits(&mut network.connection_opts);
+        self.apply_rpc_limits(&mut network.http.connection_opts);
+
+        if let Some(ref key) = self.identity_key {
+            if let Err(e) = network.set_local_private_key(key) {
+                warn!("Failed to switch identity key: {:?}", &e);
+            }
+        }
+
+        let newly_denied: Vec<_> = self
+            .deny_nodes
+            .iter()
+            .filter(|denied| !previous.deny_nodes.iter().any(|n| n.addr == denied.addr))
+            .collect();
+        let no_longer_denied: Vec<_> = previous
+            .deny_nodes
+            .iter()
+            .filter(|denied| !self.deny_nodes.iter().any(|n| n.addr == denied.addr))
+            .collect();
+        if newly_denied.is_empty() && no_longer_denied.is_empty() {
+            return;
+        }
+
+        let res = network.peerdb.tx_begin().and_then(|mut tx| {
+            for denied in newly_denied.iter() {
+                PeerDB::set_deny_peer(
+                    &mut tx,
+                    denied.addr.network_id,
+                    &denied.addr.addrbytes,
+                    denied.addr.port,
+                    get_epoch_time_secs() + DENY_NODE_DURATION,
+                )?;
+            }
+            for allowed in no_longer_denied.iter() {
+                PeerDB::set_deny_peer(
+                    &mut tx,
+                    allowed.addr.network_id,
+                    &allowed.addr.addrbytes,
+                    allowed.addr.port,
+                    0,
+                )?;
+            }
+            tx.commit().map_err(|e| e.into())
+        });
+        if let Err(e) = res {
+            warn!("Failed to update denied peers: {:?}", &e);
+            return;
+        }
+
+        for denied in newly_denied.iter() {
+            network.deregister_neighbor(&denied.addr);
+        }
+    }
+}
+
+/// The latest reloadable settings, with a generation number that goes up on each reload.  Clones
+/// share the same settings, so a thread can hold one and poll it for changes.
+#[derive(Clone)]
+pub struct ReloadHandle {
+    latest: Arc<RwLock<(u64, ReloadableSettings)>>,
+}
+
+impl ReloadHandle {
+    pub fn new(config: &Config) -> ReloadHandle {
+        ReloadHandle {
+            latest: Arc::new(RwLock::new((0, ReloadableSettings::from_config(config)))),
+        }
+    }
+
+    pub fn current(&self) -> ReloadableSettings {
+        self.latest
+            .read()
+            .expect("FATAL: reloadable settings lock poisoned")
+            .1
+            .clone()
+    }
+
+    /// The latest settings, if they were reloaded since `generation`.  Advances `generation`.
+    pub fn poll(&self, generation: &mut u64) -> Option<ReloadableSettings> {
+        let latest = self
+            .latest
+            .read()
+            .expect("FATAL: reloadable settings lock poisoned");
+        if latest.0 == *generation {
+            return None;
+        }
+        *generation = latest.0;
+        Some(latest.1.clone())
+    }
+
+    fn publish(&self, settings: ReloadableSettings) {
+        let mut latest = self
+            .latest
+            .write()
+            .expect("FATAL: reloadable settings lock poisoned");
+        latest.0 += 1;
+        latest.1 = settings;
+    }
+}
+
+pub struct ConfigReloader {
+    config_path: String,
+    profile: Option<String>,
+    handle: ReloadHandle,
+    event_dispatcher: EventDispatcher,
+    should_keep_running: Arc<AtomicBool>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        config_path: &str,
+        profile: Option<&str>,
+        handle: ReloadHandle,
+        event_dispatcher: EventDispatcher,
+        should_keep_running: Arc<AtomicBool>,
+    ) -> ConfigReloader {
+        ConfigReloader {
+            config_path: config_path.to_string(),
+            profile: profile.map(|p| p.to_string()),
+            handle,
+            event_dispatcher,
+            should_keep_running,
+        }
+    }
+
+    /// Re-read the config file, and apply whichever reloadable settings changed.  Returns a
+    /// description of each change.
+    pub fn reload(&self) -> Result<Vec<String>, String> {
+        let (config_file, config) = load_config(&self.config_path, self.profile.as_deref())?;
+        let (errors, _warnings) = config.check_consistency(&config_file);
+        if !errors.is_empty() {
+            return Err(errors.join("; "));
+        }
+        // a key file that can't be used rejects the reload, rather than being skipped
+        identity::read_identity_key_if_present(&config.get_identity_key_path())?;
+
+        let settings = ReloadableSettings::from_config(&config);
+        let previous = self.handle.current();
+        let changes = settings.changes_from(&previous);
+        if changes.is_empty() {
+            return Ok(changes);
+        }
+
+        set_loglevel(settings.log_level);
+        if settings.observers_changed(&previous) {
+            self.event_dispatcher
+                .replace_observers(&settings.events_observers, self.should_keep_running.clone());
+        }
+        // the p2p thread picks up the rest
+        self.handle.publish(settings);
+        Ok(changes)
+    }
+
+    /// Reload the config each time the node gets a SIGHUP, until the node shuts down.
+    pub fn spawn_sighup_watcher(self) -> JoinHandle<()> {
+        install_sighup_handler();
+        thread::Builder::new()
+            .name("config-reload".to_string())
+            .spawn(move || {
+                while self.should_keep_running.load(Ordering::SeqCst) {
+                    thread::sleep(Duration::from_millis(500));
+                    if !SIGHUP_RECEIVED.swap(false, Ordering::SeqCst) {
+                        continue;
+                    }
+                    info!("Got SIGHUP; reloading config from {}", &self.config_path);
+                    match self.reload() {
+                        Ok(changes) => {
+                            if changes.is_empty() {
+                                info!("Config reloaded; no reloadable settings changed");
+                            }
+                            for change in changes.iter() {
+                                info!("Config reloaded: {}", change);
+                            }
+                        }
+                        Err(e) => {
+                            warn!("Not reloading config from {}: {}", &self.config_path, &e);
+                        }
+                    }
+                }
+            })
+            .unwrap()
+    }
+}
+
+static SIGHUP_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn handle_sighup(_signal: libc::c_int) {
+    SIGHUP_RECEIVED.store(true, Ordering::SeqCst);
+}
+
+#[cfg(unix)]
+fn install_sighup_handler() {
+    unsafe {
+        libc::signal(libc::SIGHUP, handle_sighup as libc::sighandler_t);
+    }
+}
+
+#[cfg(not(unix))]
+fn install_sighup_handler() {
+    warn!("Reloading the config on SIGHUP is only supported on unix");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn settings_for(content: &str) -> ReloadableSettings {
+        let (_, config) = load_config_str(content).unwrap();
+        ReloadableSettings::from_config(&config)
+    }
+
+    #[test]
+    fn reloadable_settings_changes() {
+        let base = settings_for("");
+        assert!(base.changes_from(&base).is_empty());
+
+        let changed = settings_for(
+            r#"
+            [node]
+            log_level = "debug"
+            deny_nodes = "127.0.0.1:20444"
+
+            [connection_options]
+            max_http_clients = 7
+            maximum_call_argument_size = 100
+
+            [[events_observer]]
+            endpoint = "localhost:3700"
+            events_keys = ["*"]
+            "#,
+        );
+        assert_eq!(changed.log_level, Some(slog::Level::Debug));
+        let changes = changed.changes_from(&base);
+        assert_eq!(changes.len(), 5);
+        assert!(changes[0].starts_with("node.log_level"));
+        assert!(changes[1].starts_with("node.deny_nodes"));
+        assert!(changes[2].starts_with("connection_options.max_http_clients"));
+        assert!(changes[3].starts_with("connection_options.maximum_call_argument_size"));
+        assert!(changes[4].starts_with("events_observer"));
+
+        let mut opts = ConnectionOptions::default();
+        changed.apply_rpc_limits(&mut opts);
+        assert_eq!(opts.max_http_clients, 7);
+        assert_eq!(opts.maximum_call_argument_size, 100);
+    }
+
+    #[test]
+    fn bad_config_is_rejected() {
+        let err = load_config_str(
+            r#"
+            [node]
+            log_level = "loud"
+            "#,
+        )
+        .err()
+        .unwrap();
+        assert!(err.contains("node.log_level"));
+        assert!(!is_loading_config());
+
+        let err = load_config_str(
+            r#"
+            [node]
+            log_format = "xml"
+            "#,
+        )
+        .err()
+        .unwrap();
+        assert!(err.contains("node.log_format"));
+    }
+
+    #[test]
+    fn panic_hook_chains_to_the_previous_hook() {
+        thread_local! {
+            static PANICS_SEEN: Cell<u32> = Cell::new(0);
+        }
+        let previous_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |panic_info| {
+            PANICS_SEEN.with(|seen| seen.set(seen.get() + 1));
+            previous_hook(panic_info);
+        }));
+        chain_panic_hook();
+
+        assert!(load_config_str("[node]\nlog_level = \"loud\"\n").is_err());
+        assert_eq!(PANICS_SEEN.with(|seen| seen.get()), 0);
+
+        assert!(panic::catch_unwind(|| panic!("not a config error")).is_err());
+        assert_eq!(PANICS_SEEN.with(|seen| seen.get()), 1);
+    }
+
+    #[test]
+    fn log_format_setting() {
+        let (_, config) = load_config_str("").unwrap();
+        assert_eq!(config.node.log_json, None);
+
+        let (config_file, config) = load_config_str(
+            r#"
+            [node]
+            log_format = "json"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.node.log_json, Some(true));
+        let (errors, _) = config.check_consistency(&config_file);
+        assert_eq!(
+            errors
+                .iter()
+                .any(|e| e.contains("node.log_format") && e.contains("slog_json")),
+            !cfg!(feature = "slog_json")
+        );
+
+        let (_, config) = load_config_str(
+            r#"
+            [node]
+            log_format = "text"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.node.log_json, Some(false));
+    }
+
+    #[test]
+    fn otlp_settings() {
+        let (_, config) = load_config_str("").unwrap();
+        assert_eq!(config.node.otlp_endpoint, None);
+        assert_eq!(config.node.otlp_sample_ratio, 1.0);
+
+        let (config_file, config) = load_config_str(
+            r#"
+            [node]
+            otlp_endpoint = "http://localhost:4318"
+            otlp_sample_ratio = 1.5
+            "#,
+        )
+        .unwrap();
+        let (errors, _) = config.check_consistency(&config_file);
+        assert!(errors.iter().any(|e| e.contains("node.otlp_sample_ratio")));
+        assert_eq!(
+            errors.iter().any(|e| e.contains("monitoring_otlp")),
+            !cfg!(feature = "monitoring_otlp")
+        );
+    }
+
+    #[test]
+    fn reload_handle_polling() {
+        let (_, config) = load_config_str("").unwrap();
+        let handle = ReloadHandle::new(&config);
+        let mut generation = 0;
+        assert!(handle.poll(&mut generation).is_none());
+
+        let mut settings = handle.current();
+        settings.max_http_clients = 1;
+        handle.clone().publish(settings);
+        assert_eq!(handle.poll(&mut generation).unwrap().max_http_clients, 1);
+        assert_eq!(generation, 1);
+        assert!(handle.poll(&mut generation).is_none());
+    }
+
+    #[test]
+    fn replace_event_observers() {
+        let dispatcher = EventDispatcher::new();
+        let observers = vec![EventObserverConfig {
+            endpoint: "localhost:3700".to_string(),
+            events_keys: vec![],
+        }];
+        let clone = dispatcher.clone();
+        dispatcher.replace_observers(&observers, Arc::new(AtomicBool::new(true)));
+        assert_eq!(
+            clone.observer_endpoints(),
+            vec!["localhost:3700".to_string()]
+        );
+        dispatcher.replace_observers(&[], Arc::new(AtomicBool::new(true)));
+        assert!(clone.observer_endpoints().is_empty());
+    }
+}
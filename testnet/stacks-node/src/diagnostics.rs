@@ -0,0 +1,363 @@
+//! Diagnostics bundles for bug reports (`stacks-node diagnostics`).
+//!
+//! A bundle is a single tar archive holding what is usually asked for when a node misbehaves:
+//!
+//! * `diagnostics.json`: the node's version and network, the schema version of each of its
+//!   databases, its burnchain and Stacks tips, a summary of its peer DB, and the metric totals it
+//!   last saved (see `node.persist_metrics`);
+//! * `config.txt`: the effective config, with its secrets redacted as in `check-config`;
+//! * `metrics.txt`: a scrape of the node's Prometheus endpoint, if it has one and is running;
+//! * `node.log`: the end of the node's log, if passed with `--log=<file>`.
+//!
+//! The databases are only ever opened read-only, so a bundle can be made while the node runs, or
+//! after it has crashed, without changing its state.  A part that can't be collected -- e.g. a
+//! database that doesn't exist yet -- is reported in the bundle as an error, rather than
+//! failing the whole bundle.  No keys or peer identities are included.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use pico_args::Arguments;
+
+use stacks::chainstate::burn::db::sortdb::SortitionDB;
+use stacks::net::db::PeerDB;
+use stacks::util::db::sql_schema_version;
+use stacks::util::get_epoch_time_secs;
+
+use crate::metrics_state::MetricsState;
+use crate::peers;
+use crate::{Config, ConfigFile};
+
+/// Include at most this much of the end of the node's log
+const LOG_TAIL_BYTES: u64 = 8 * 1024 * 1024;
+
+/// How long to wait for the node's Prometheus endpoint
+const METRICS_SCRAPE_TIMEOUT_SECS: u64 = 5;
+
+/// Size of a tar header and of the blocks file contents are padded to
+const TAR_BLOCK_SIZE: usize = 512;
+
+pub fn run_diagnostics_command(mut args: Arguments) -> Result<(), String> {
+    let config_path: Option<String> = args
+        .opt_value_from_str("--config")
+        .map_err(|e| format!("{}", e))?;
+    let config_path =
+        config_path.ok_or("`diagnostics` must be passed a config file via `--config`")?;
+    let profile: Option<String> = args
+        .opt_value_from_str("--profile")
+        .map_err(|e| format!("{}", e))?;
+    let log_path: Option<String> = args
+        .opt_value_from_str("--log")
+        .map_err(|e| format!("{}", e))?;
+    let output: Option<String> = args
+        .opt_value_from_str("--output")
+        .map_err(|e| format!("{}", e))?;
+    args.finish().map_err(|e| format!("{}", e))?;
+
+    let config = Config::from_config_file(ConfigFile::from_path_with_profile(
+        &config_path,
+        profile.as_deref(),
+    )?);
+    let now = get_epoch_time_secs();
+    let output = output.unwrap_or_else(|| format!("stacks-node-diagnostics-{}.tar", now));
+
+    let mut entries: Vec<(&str, Vec<u8>)> = vec![];
+    let summary = serde_json::to_vec_pretty(&summarize(&config, now))
+        .map_err(|e| format!("Failed to serialize diagnostics: {}", &e))?;
+    entries.push(("diagnostics.json", summary));
+    entries.push((
+        "config.txt",
+        format!("{:#?}\n", &config.redacted()).into_bytes(),
+    ));
+    if let Some(metrics) = scrape_metrics(&config) {
+        entries.push(("metrics.txt", metrics.into_bytes()));
+    }
+    if let Some(log_path) = log_path {
+        entries.push(("node.log", read_log_tail(&log_path, LOG_TAIL_BYTES)?));
+    }
+
+    let mut archive = vec![];
+    for (name, contents) in entries.iter() {
+        append_tar_entry(&mut archive, name, contents, now)?;
+    }
+    finish_tar(&mut archive)?;
+    fs::write(&output, &archive).map_err(|e| format!("Failed to write {}: {}", &output, &e))?;
+    println!("Wrote diagnostics to {}", &output);
+    Ok(())
+}
+
+/// Everything in `diagnostics.json`
+fn summarize(config: &Config, now: u64) -> serde_json::Value {
+    json!({
+        "version": crate::version(),
+        "generated_at": now,
+        "network": config.burnchain.mode,
+        "chain_id": config.burnchain.chain_id,
+        "schema_versions": schema_versions(config),
+        "tip": tip_state(config).unwrap_or_else(|e| json!({ "error": e })),
+        "peers": peer_summary(config, now).unwrap_or_else(|e| json!({ "error": e })),
+        "saved_metrics": saved_metrics(config).unwrap_or_else(|e| json!({ "error": e })),
+    })
+}
+
+/// The schema version of each of the node's databases, or why it could not be read
+fn schema_versions(config: &Config) -> BTreeMap<&'static str, String> {
+    let chainstate_path = config.get_chainstate_path_str();
+    let burn_db_path = config.get_burn_db_path();
+    let sortition_path = config.get_burn_db_file_path();
+    let dbs = vec![
+        (
+            "chainstate",
+            Path::new(&chainstate_path).join("vm").join("index.sqlite"),
+        ),
+        ("sortition", Path::new(&sortition_path).join("marf.sqlite")),
+        (
+            "burnchain",
+            Path::new(&burn_db_path).join("burnchain.sqlite"),
+        ),
+        ("spv_headers", config.get_spv_headers_file_path().into()),
+        ("peer", config.get_peer_db_file_path().into()),
+        ("atlas", config.get_atlas_db_file_path().into()),
+    ];
+
+    dbs.into_iter()
+        .map(|(name, path)| {
+            let version = match sql_schema_version(&path.to_string_lossy()) {
+                Ok(Some(version)) => version,
+                Ok(None) => "none recorded".to_string(),
+                Err(e) => format!("error: {}", &e),
+            };
+            (name, version)
+        })
+        .collect()
+}
+
+/// The canonical burnchain and Stacks tips, as the sortition DB has them
+fn tip_state(config: &Config) -> Result<serde_json::Value, String> {
+    let sortdb = SortitionDB::open_readonly(&config.get_burn_db_file_path())
+        .map_err(|e| format!("Failed to open sortition DB: {}", &e))?;
+    let tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())
+        .map_err(|e| format!("Failed to read burnchain tip: {}", &e))?;
+    Ok(json!({
+        "burn_block_height": tip.block_height,
+        "burn_header_hash": format!("{}", &tip.burn_header_hash),
+        "consensus_hash": format!("{}", &tip.consensus_hash),
+        "stacks_tip_height": tip.canonical_stacks_tip_height,
+        "stacks_tip": format!("{}", &tip.canonical_stacks_tip_hash),
+        "stacks_tip_consensus_hash": format!("{}", &tip.canonical_stacks_tip_consensus_hash),
+    }))
+}
+
+/// How many peers the peer DB knows of, and how many of them are recent and not denied
+fn peer_summary(config: &Config, now: u64) -> Result<serde_json::Value, String> {
+    let peerdb = PeerDB::open_readonly(&config.get_peer_db_file_path())
+        .map_err(|e| format!("Failed to open peer DB: {}", &e))?;
+    let all_peers = PeerDB::get_all_peers(peerdb.conn())
+        .map_err(|e| format!("Failed to read peers: {}", &e))?;
+    let total = all_peers.len();
+    let on_network = all_peers
+        .iter()
+        .filter(|peer| peer.addr.network_id == config.burnchain.chain_id)
+        .count();
+    let denied = all_peers
+        .iter()
+        .filter(|peer| peer.denied < 0 || peer.denied > (now as i64))
+        .count();
+    let healthy = peers::healthy_peers(
+        all_peers,
+        config.burnchain.chain_id,
+        now,
+        peers::DEFAULT_MAX_AGE_SECS,
+    )
+    .len();
+    Ok(json!({
+        "total": total,
+        "on_network": on_network,
+        "denied": denied,
+        "healthy": healthy,
+    }))
+}
+
+/// The metric totals the node last saved, if it persists them
+fn saved_metrics(config: &Config) -> Result<serde_json::Value, String> {
+    let path = MetricsState::path(config);
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(json!(null)),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), &e)),
+    };
+    serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), &e))
+}
+
+/// The current metrics of the running node, if it serves them
+fn scrape_metrics(config: &Config) -> Option<String> {
+    let bind = config.node.prometheus_bind.as_ref()?;
+    let addr = bind.replacen("0.0.0.0:", "127.0.0.1:", 1);
+    let url = format!("http://{}/metrics", &addr);
+    let scraped = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(METRICS_SCRAPE_TIMEOUT_SECS))
+        .build()
+        .and_then(|client| client.get(&url).send())
+        .and_then(|resp| resp.error_for_status())
+        .and_then(|resp| resp.text());
+    match scraped {
+        Ok(text) => Some(text),
+        Err(e) => {
+            eprintln!(
+                "Not including current metrics: failed to fetch {}: {}",
+                &url, &e
+            );
+            None
+        }
+    }
+}
+
+/// The last `max_bytes` of a log file, starting at a line boundary
+fn read_log_tail(path: &str, max_bytes: u64) -> Result<Vec<u8>, String> {
+    let mut file = fs::File::open(path).map_err(|e| format!("Failed to open {}: {}", path, &e))?;
+    let len = file
+        .metadata()
+        .map_err(|e| format!("Failed to read {}: {}", path, &e))?
+        .len();
+    let start = len.saturating_sub(max_bytes);
+    file.seek(SeekFrom::Start(start))
+        .map_err(|e| format!("Failed to read {}: {}", path, &e))?;
+    let mut tail = vec![];
+    file.read_to_end(&mut tail)
+        .map_err(|e| format!("Failed to read {}: {}", path, &e))?;
+    if start > 0 {
+        // drop the partial first line
+        match tail.iter().position(|b| *b == b'\n') {
+            Some(i) => {
+                tail.drain(0..=i);
+            }
+            None => tail.clear(),
+        }
+    }
+    Ok(tail)
+}
+
+/// Write `value` into a tar header field as a zero-padded octal number, ending in a NUL
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+}
+
+/// Append a regular file to a tar archive, in the ustar format
+fn append_tar_entry<W: Write>(
+    out: &mut W,
+    name: &str,
+    contents: &[u8],
+    mtime: u64,
+) -> Result<(), String> {
+    if name.len() >= 100 {
+        return Err(format!("Archive entry name is too long: {}", name));
+    }
+    let mut header = [0u8; TAR_BLOCK_SIZE];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    write_octal(&mut header[100..108], 0o644);
+    write_octal(&mut header[108..116], 0);
+    write_octal(&mut header[116..124], 0);
+    write_octal(&mut header[124..136], contents.len() as u64);
+    write_octal(&mut header[136..148], mtime);
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    // the checksum is taken as if its own field were blank
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u64 = header.iter().map(|b| *b as u64).sum();
+    let checksum = format!("{:06o}\0 ", checksum);
+    header[148..156].copy_from_slice(checksum.as_bytes());
+
+    let padding = (TAR_BLOCK_SIZE - contents.len() % TAR_BLOCK_SIZE) % TAR_BLOCK_SIZE;
+    out.write_all(&header)
+        .and_then(|_| out.write_all(contents))
+        .and_then(|_| out.write_all(&vec![0u8; padding]))
+        .map_err(|e| format!("Failed to write archive: {}", &e))
+}
+
+/// End a tar archive
+fn finish_tar<W: Write>(out: &mut W) -> Result<(), String> {
+    out.write_all(&[0u8; 2 * TAR_BLOCK_SIZE])
+        .map_err(|e| format!("Failed to write archive: {}", &e))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Read back the names and contents of the files in an archive, checking each header
+    fn read_tar(archive: &[u8]) -> Vec<(String, Vec<u8>)> {
+        let mut files = vec![];
+        let mut offset = 0;
+        loop {
+            let header = &archive[offset..offset + TAR_BLOCK_SIZE];
+            if header.iter().all(|b| *b == 0) {
+                assert!(archive[offset + TAR_BLOCK_SIZE..].iter().all(|b| *b == 0));
+                return files;
+            }
+            let field = |range: std::ops::Range<usize>| {
+                String::from_utf8(header[range].to_vec())
+                    .unwrap()
+                    .trim_end_matches(|c| c == '\0' || c == ' ')
+                    .to_string()
+            };
+            let mut blank = header.to_vec();
+            blank[148..156].copy_from_slice(b"        ");
+            let checksum: u64 = blank.iter().map(|b| *b as u64).sum();
+            assert_eq!(u64::from_str_radix(&field(148..156), 8).unwrap(), checksum);
+            assert_eq!(&header[257..263], b"ustar\0");
+
+            let size = u64::from_str_radix(&field(124..136), 8).unwrap() as usize;
+            let start = offset + TAR_BLOCK_SIZE;
+            files.push((field(0..100), archive[start..start + size].to_vec()));
+            offset = start + (size + TAR_BLOCK_SIZE - 1) / TAR_BLOCK_SIZE * TAR_BLOCK_SIZE;
+        }
+    }
+
+    #[test]
+    fn test_diagnostics_archive() {
+        let long = vec![b'x'; TAR_BLOCK_SIZE + 1];
+        let mut archive = vec![];
+        append_tar_entry(&mut archive, "diagnostics.json", b"{}", 1234).unwrap();
+        append_tar_entry(&mut archive, "node.log", &long, 1234).unwrap();
+        append_tar_entry(&mut archive, "empty.txt", b"", 1234).unwrap();
+        finish_tar(&mut archive).unwrap();
+        assert_eq!(archive.len() % TAR_BLOCK_SIZE, 0);
+
+        assert_eq!(
+            read_tar(&archive),
+            vec![
+                ("diagnostics.json".to_string(), b"{}".to_vec()),
+                ("node.log".to_string(), long),
+                ("empty.txt".to_string(), vec![]),
+            ]
+        );
+
+        let too_long = "x".repeat(100);
+        assert!(append_tar_entry(&mut vec![], &too_long, b"", 0).is_err());
+    }
+
+    #[test]
+    fn test_diagnostics_log_tail() {
+        let path = "/tmp/stacks-node-diagnostics-log-tail.log";
+        fs::write(path, b"first line\nsecond line\nthird line\n").unwrap();
+
+        // the whole log fits
+        assert_eq!(
+            read_log_tail(path, 1024).unwrap(),
+            b"first line\nsecond line\nthird line\n".to_vec()
+        );
+        // a partial line is dropped
+        assert_eq!(read_log_tail(path, 15).unwrap(), b"third line\n".to_vec());
+        assert_eq!(read_log_tail(path, 5).unwrap(), Vec::<u8>::new());
+
+        assert!(read_log_tail("/tmp/stacks-node-diagnostics-no-such.log", 1024).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+//! Low-disk-space detection.  The p2p thread checks the free space on the volume holding the
+//! node's chainstate every few seconds.  Below `node.low_disk_space_mb`, the node stops taking in
+//! new blocks, microblocks and transactions, and stops mining, until space is freed up.  Below
+//! `node.critical_disk_space_mb`, it shuts down cleanly, as if it had received SIGTERM.  Either
+//! way, SQLite never has to cope with the disk filling up in the middle of a write.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::time::Duration;
+
+use stacks::monitoring;
+use stacks::net::admin::RuntimeToggles;
+use stacks::util::get_epoch_time_secs;
+
+use crate::shutdown;
+use crate::Config;
+
+/// How often to check the free disk space
+const DISK_SPACE_CHECK_INTERVAL_SECS: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiskSpaceLevel {
+    Ok,
+    Low,
+    Critical,
+}
+
+/// Bytes available to the node on the volume holding `path`
+#[cfg(unix)]
+pub fn free_disk_space(path: &Path) -> io::Result<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stats) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((stats.f_bavail as u64).saturating_mul(stats.f_frsize as u64))
+}
+
+#[cfg(not(unix))]
+pub fn free_disk_space(_path: &Path) -> io::Result<u64> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "free disk space checks are not supported on this platform",
+    ))
+}
+
+pub struct DiskSpaceMonitor {
+    path: PathBuf,
+    /// Stop taking in new data below this many free bytes (0 to never)
+    low_bytes: u64,
+    /// Shut down below this many free bytes (0 to never)
+    critical_bytes: u64,
+    shutdown_deadline: Duration,
+    level: DiskSpaceLevel,
+    last_check: u64,
+}
+
+impl DiskSpaceMonitor {
+    /// Returns None if the node is configured not to check its free disk space
+    pub fn from_config(config: &Config) -> Option<DiskSpaceMonitor> {
+        if config.node.low_disk_space_mb == 0 && config.node.critical_disk_space_mb == 0 {
+            return None;
+        }
+        Some(DiskSpaceMonitor {
+            path: PathBuf::from(&config.node.working_dir).join(&config.burnchain.mode),
+            low_bytes: config.node.low_disk_space_mb.saturating_mul(1024 * 1024),
+            critical_bytes: config
+                .node
+                .critical_disk_space_mb
+                .saturating_mul(1024 * 1024),
+            shutdown_deadline: Duration::from_secs(config.node.shutdown_timeout_secs),
+            level: DiskSpaceLevel::Ok,
+            last_check: 0,
+        })
+    }
+
+    pub fn level_for(&self, free_bytes: u64) -> DiskSpaceLevel {
+        if free_bytes < self.critical_bytes {
+            DiskSpaceLevel::Critical
+        } else if free_bytes < self.low_bytes {
+            DiskSpaceLevel::Low
+        } else {
+            DiskSpaceLevel::Ok
+        }
+    }
+
+    /// Check the free disk space, if it is time to, and act on it
+    pub fn check(&mut self, toggles: &RuntimeToggles, should_keep_running: &AtomicBool) {
+        let now = get_epoch_time_secs();
+        if self.last_check + DISK_SPACE_CHECK_INTERVAL_SECS > now {
+            return;
+        }
+        self.last_check = now;
+
+        match free_disk_space(&self.path) {
+            Ok(free_bytes) => {
+                monitoring::update_disk_free_bytes(free_bytes as i64);
+                self.update(free_bytes, toggles, should_keep_running);
+            }
+            Err(e) => {
+                warn!("Failed to get free disk space for {:?}: {}", &self.path, &e);
+            }
+        }
+    }
+
+    fn update(
+        &mut self,
+        free_bytes: u64,
+        toggles: &RuntimeToggles,
+        should_keep_running: &AtomicBool,
+    ) {
+        let level = self.level_for(free_bytes);
+        if level == self.level {
+            return;
+        }
+        let free_mb = free_bytes / (1024 * 1024);
+        match level {
+            DiskSpaceLevel::Ok => {
+                info!(
+                    "Free disk space recovered ({} MB); accepting new blocks and transactions again",
+                    free_mb
+                );
+                toggles.set_low_disk_space(false);
+            }
+            DiskSpaceLevel::Low => {
+                warn!(
+                    "Low on disk space ({} MB free); no longer accepting new blocks and transactions, and not mining",
+                    free_mb
+                );
+                toggles.set_low_disk_space(true);
+            }
+            DiskSpaceLevel::Critical => {
+                error!(
+                    "Critically low on disk space ({} MB free); shutting down",
+                    free_mb
+                );
+                toggles.set_low_disk_space(true);
+                shutdown::request_shutdown(should_keep_running, self.shutdown_deadline);
+            }
+        }
+        self.level = level;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::atomic::Ordering;
+
+    const MB: u64 = 1024 * 1024;
+
+    #[test]
+    fn free_disk_space_is_known() {
+        if cfg!(unix) {
+            assert!(free_disk_space(Path::new("/tmp")).unwrap() > 0);
+            assert!(free_disk_space(Path::new("/no/such/directory")).is_err());
+        }
+    }
+
+    #[test]
+    fn disk_space_levels() {
+        let mut config = Config::default();
+        config.node.low_disk_space_mb = 0;
+        config.node.critical_disk_space_mb = 0;
+        assert!(DiskSpaceMonitor::from_config(&config).is_none());
+
+        config.node.low_disk_space_mb = 100;
+        config.node.critical_disk_space_mb = 10;
+        let mut monitor = DiskSpaceMonitor::from_config(&config).unwrap();
+        assert_eq!(monitor.level_for(200 * MB), DiskSpaceLevel::Ok);
+        assert_eq!(monitor.level_for(50 * MB), DiskSpaceLevel::Low);
+        assert_eq!(monitor.level_for(5 * MB), DiskSpaceLevel::Critical);
+
+        let toggles = RuntimeToggles::default();
+        let should_keep_running = AtomicBool::new(true);
+
+        monitor.update(50 * MB, &toggles, &should_keep_running);
+        assert!(toggles.low_disk_space());
+        assert!(should_keep_running.load(Ordering::SeqCst));
+
+        monitor.update(200 * MB, &toggles, &should_keep_running);
+        assert!(!toggles.low_disk_space());
+        assert!(should_keep_running.load(Ordering::SeqCst));
+
+        // pretend a shutdown is already under way, so the test process is not given a deadline
+        should_keep_running.store(false, Ordering::SeqCst);
+        monitor.update(5 * MB, &toggles, &should_keep_running);
+        assert!(toggles.low_disk_space());
+        assert!(!should_keep_running.load(Ordering::SeqCst));
+    }
+}
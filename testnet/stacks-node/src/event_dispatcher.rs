@@ -5,7 +5,7 @@ use std::{
     collections::{HashMap, HashSet},
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc, Mutex,
+        Arc, Mutex, RwLock, RwLockReadGuard,
     },
 };
 
@@ -16,6 +16,7 @@ use serde_json::json;
 
 use stacks::burnchains::Txid;
 use stacks::chainstate::coordinator::BlockEventDispatcher;
+use stacks::chainstate::stacks::db::blocks::MicroblockEquivocation;
 use stacks::chainstate::stacks::db::StacksHeaderInfo;
 use stacks::chainstate::stacks::events::{
     FTEventType, NFTEventType, STXEventType, StacksTransactionEvent, StacksTransactionReceipt,
@@ -31,10 +32,13 @@ use stacks::net::atlas::{Attachment, AttachmentInstance};
 use stacks::types::chainstate::{BurnchainHeaderHash, StacksAddress, StacksBlockId};
 use stacks::util::hash::bytes_to_hex;
 use stacks::vm::analysis::contract_interface_builder::build_contract_interface;
-use stacks::vm::types::{AssetIdentifier, QualifiedContractIdentifier, Value};
+use stacks::vm::types::{AssetIdentifier, PrincipalData, QualifiedContractIdentifier, Value};
 
 use super::config::{EventKeyType, EventObserverConfig};
 use super::node::ChainTip;
+use crate::tip_hooks::TipHooks;
+use crate::tip_notifier::TipNotifier;
+use crate::watched_addresses::{collect_watched_events, WatchedActivity};
 use stacks::chainstate::stacks::db::unconfirmed::ProcessedUnconfirmedState;
 
 #[derive(Debug, Clone)]
@@ -49,6 +53,7 @@ struct ReceiptPayloadInfo<'a> {
     raw_result: String,
     raw_tx: String,
     contract_interface_json: serde_json::Value,
+    vm_error_json: serde_json::Value,
 }
 
 const STATUS_RESP_TRUE: &str = "success";
@@ -57,11 +62,13 @@ const STATUS_RESP_POST_CONDITION: &str = "abort_by_post_condition";
 
 /// Update `serve()` in `neon_integrations.rs` with any new paths that need to be tested
 pub const PATH_MICROBLOCK_SUBMIT: &str = "new_microblocks";
+pub const PATH_MICROBLOCK_EQUIVOCATIONS: &str = "microblock_equivocations";
 pub const PATH_MEMPOOL_TX_SUBMIT: &str = "new_mempool_tx";
 pub const PATH_MEMPOOL_TX_DROP: &str = "drop_mempool_tx";
 pub const PATH_BURN_BLOCK_SUBMIT: &str = "new_burn_block";
 pub const PATH_BLOCK_PROCESSED: &str = "new_block";
 pub const PATH_ATTACHMENT_PROCESSED: &str = "attachments/new";
+pub const PATH_WATCHED_ADDRESS_ACTIVITY: &str = "watched_address_activity";
 
 impl EventObserver {
     fn send_payload(&self, payload: &serde_json::Value, path: &str) {
@@ -173,7 +180,7 @@ impl EventObserver {
         })
     }
 
-    /// Returns tuple of (txid, success, raw_result, raw_tx, contract_interface_json)
+    /// Returns tuple of (txid, success, raw_result, raw_tx, contract_interface_json, vm_error_json)
     fn generate_payload_info_for_receipt(receipt: &StacksTransactionReceipt) -> ReceiptPayloadInfo {
         let tx = &receipt.transaction;
 
@@ -208,12 +215,17 @@ impl EventObserver {
                 None => json!(null),
             }
         };
+        let vm_error_json = match receipt.vm_error {
+            Some(ref vm_error) => vm_error.json_serialize(),
+            None => json!(null),
+        };
         ReceiptPayloadInfo {
             txid,
             success,
             raw_result,
             raw_tx,
             contract_interface_json,
+            vm_error_json,
         }
     }
 
@@ -232,6 +244,7 @@ impl EventObserver {
             "raw_tx": format!("0x{}", &receipt_payload_info.raw_tx),
             "contract_abi": receipt_payload_info.contract_interface_json,
             "execution_cost": receipt.execution_cost,
+            "vm_error": receipt_payload_info.vm_error_json,
         })
     }
 
@@ -251,6 +264,7 @@ impl EventObserver {
             "raw_tx": format!("0x{}", &receipt_payload_info.raw_tx),
             "contract_abi": receipt_payload_info.contract_interface_json,
             "execution_cost": receipt.execution_cost,
+            "vm_error": receipt_payload_info.vm_error_json,
             "sequence": sequence,
         })
     }
@@ -302,10 +316,40 @@ impl EventObserver {
         self.send_payload(&payload, PATH_MICROBLOCK_SUBMIT);
     }
 
+    fn send_microblock_equivocations(&self, payload: &serde_json::Value) {
+        self.send_payload(payload, PATH_MICROBLOCK_EQUIVOCATIONS);
+    }
+
+    fn make_microblock_equivocations_payload(
+        equivocations: &[MicroblockEquivocation],
+    ) -> serde_json::Value {
+        let equivocations: Vec<_> = equivocations
+            .iter()
+            .map(|equivocation| {
+                let header_1 = equivocation.header_1.serialize_to_vec();
+                let header_2 = equivocation.header_2.serialize_to_vec();
+                json!({
+                    "parent_index_block_hash": format!("0x{}", &equivocation.parent_index_block_hash),
+                    "sequence": equivocation.sequence,
+                    "microblock_hash_1": format!("0x{}", equivocation.header_1.block_hash()),
+                    "microblock_hash_2": format!("0x{}", equivocation.header_2.block_hash()),
+                    "header_1": format!("0x{}", bytes_to_hex(&header_1)),
+                    "header_2": format!("0x{}", bytes_to_hex(&header_2)),
+                    "detected_at": equivocation.detected_at,
+                })
+            })
+            .collect();
+        json!({ "equivocations": equivocations })
+    }
+
     fn send_dropped_mempool_txs(&self, payload: &serde_json::Value) {
         self.send_payload(payload, PATH_MEMPOOL_TX_DROP);
     }
 
+    fn send_watched_address_activity(&self, payload: &serde_json::Value) {
+        self.send_payload(payload, PATH_WATCHED_ADDRESS_ACTIVITY);
+    }
+
     fn send_new_burn_block(&self, payload: &serde_json::Value) {
         self.send_payload(payload, PATH_BURN_BLOCK_SUBMIT);
     }
@@ -358,8 +402,9 @@ impl EventObserver {
     }
 }
 
-#[derive(Clone)]
-pub struct EventDispatcher {
+/// The registered event observers, and the events each one is subscribed to
+#[derive(Default)]
+struct EventObservers {
     registered_observers: Vec<EventObserver>,
     contract_events_observers_lookup: HashMap<(QualifiedContractIdentifier, String), HashSet<u16>>,
     assets_observers_lookup: HashMap<AssetIdentifier, HashSet<u16>>,
@@ -368,7 +413,18 @@ pub struct EventDispatcher {
     microblock_observers_lookup: HashSet<u16>,
     stx_observers_lookup: HashSet<u16>,
     any_event_observers_lookup: HashSet<u16>,
+    watched_principals_lookup: HashMap<PrincipalData, HashSet<u16>>,
+}
+
+#[derive(Clone)]
+pub struct EventDispatcher {
+    /// Shared by every clone of this dispatcher, so that replacing the observers (e.g. when the
+    /// config is reloaded) takes effect in every thread that dispatches events.
+    observers: Arc<RwLock<EventObservers>>,
     boot_receipts: Arc<Mutex<Option<Vec<StacksTransactionReceipt>>>>,
+    tip_notifier: Option<TipNotifier>,
+    tip_hooks: Option<TipHooks>,
+    watched_activity: WatchedActivity,
 }
 
 impl MemPoolEventDispatcher for EventDispatcher {
@@ -424,23 +480,48 @@ impl BlockEventDispatcher for EventDispatcher {
     fn dispatch_boot_receipts(&mut self, receipts: Vec<StacksTransactionReceipt>) {
         self.process_boot_receipts(receipts)
     }
+
+    fn announce_canonical_stacks_tip(
+        &self,
+        tip: &StacksHeaderInfo,
+        previous: Option<(&StacksBlockId, u64)>,
+        reorg: bool,
+    ) {
+        if let Some(hooks) = self.tip_hooks.as_ref() {
+            hooks.on_stacks_tip(tip, previous, reorg);
+        }
+    }
 }
 
 impl EventDispatcher {
     pub fn new() -> EventDispatcher {
         EventDispatcher {
-            registered_observers: vec![],
-            contract_events_observers_lookup: HashMap::new(),
-            assets_observers_lookup: HashMap::new(),
-            stx_observers_lookup: HashSet::new(),
-            any_event_observers_lookup: HashSet::new(),
-            burn_block_observers_lookup: HashSet::new(),
-            mempool_observers_lookup: HashSet::new(),
-            microblock_observers_lookup: HashSet::new(),
+            observers: Arc::new(RwLock::new(EventObservers::default())),
             boot_receipts: Arc::new(Mutex::new(None)),
+            tip_notifier: None,
+            tip_hooks: None,
+            watched_activity: WatchedActivity::default(),
         }
     }
 
+    /// Also announce new Stacks and burnchain tips on `notifier`.  Only clones of this dispatcher
+    /// made after this call share the notifier.
+    pub fn set_tip_notifier(&mut self, notifier: TipNotifier) {
+        self.tip_notifier = Some(notifier);
+    }
+
+    /// Also run `hooks` on new Stacks and burnchain tips.  Only clones of this dispatcher made
+    /// after this call share the hooks.
+    pub fn set_tip_hooks(&mut self, hooks: TipHooks) {
+        self.tip_hooks = Some(hooks);
+    }
+
+    fn observers(&self) -> RwLockReadGuard<EventObservers> {
+        self.observers
+            .read()
+            .expect("FATAL: event observers lock poisoned")
+    }
+
     pub fn process_burn_block(
         &self,
         burn_block: &BurnchainHeaderHash,
@@ -449,14 +530,26 @@ impl EventDispatcher {
         burns: u64,
         recipient_info: Vec<StacksAddress>,
     ) {
+        if let Some(notifier) = self.tip_notifier.as_ref() {
+            notifier.notify_burn_tip(burn_block, burn_block_height);
+        }
+        if let Some(hooks) = self.tip_hooks.as_ref() {
+            hooks.on_burn_block(burn_block, burn_block_height);
+        }
+
+        let observers = self.observers();
         // lazily assemble payload only if we have observers
-        let interested_observers: Vec<_> = self
+        let interested_observers: Vec<_> = observers
             .registered_observers
             .iter()
             .enumerate()
             .filter(|(obs_id, _observer)| {
-                self.burn_block_observers_lookup.contains(&(*obs_id as u16))
-                    || self.any_event_observers_lookup.contains(&(*obs_id as u16))
+                observers
+                    .burn_block_observers_lookup
+                    .contains(&(*obs_id as u16))
+                    || observers
+                        .any_event_observers_lookup
+                        .contains(&(*obs_id as u16))
             })
             .collect();
         if interested_observers.len() < 1 {
@@ -476,105 +569,6 @@ impl EventDispatcher {
         }
     }
 
-    /// Iterates through tx receipts, and then the events corresponding to each receipt to
-    /// generate a dispatch matrix & event vector.
-    ///
-    /// # Returns
-    /// - dispatch_matrix: a vector where each index corresponds to the hashset of event indexes
-    ///     that each respective event observer is subscribed to
-    /// - events: a vector of all events from all the tx receipts
-    fn create_dispatch_matrix_and_event_vector<'a>(
-        &self,
-        receipts: &'a Vec<StacksTransactionReceipt>,
-    ) -> (
-        Vec<HashSet<usize>>,
-        Vec<(bool, Txid, &'a StacksTransactionEvent)>,
-    ) {
-        let mut dispatch_matrix: Vec<HashSet<usize>> = self
-            .registered_observers
-            .iter()
-            .map(|_| HashSet::new())
-            .collect();
-        let mut events: Vec<(bool, Txid, &StacksTransactionEvent)> = vec![];
-        let mut i: usize = 0;
-
-        for receipt in receipts {
-            let tx_hash = receipt.transaction.txid();
-            for event in receipt.events.iter() {
-                match event {
-                    StacksTransactionEvent::SmartContractEvent(event_data) => {
-                        if let Some(observer_indexes) =
-                            self.contract_events_observers_lookup.get(&event_data.key)
-                        {
-                            for o_i in observer_indexes {
-                                dispatch_matrix[*o_i as usize].insert(i);
-                            }
-                        }
-                    }
-                    StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(_))
-                    | StacksTransactionEvent::STXEvent(STXEventType::STXMintEvent(_))
-                    | StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(_))
-                    | StacksTransactionEvent::STXEvent(STXEventType::STXLockEvent(_)) => {
-                        for o_i in &self.stx_observers_lookup {
-                            dispatch_matrix[*o_i as usize].insert(i);
-                        }
-                    }
-                    StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(
-                        event_data,
-                    )) => {
-                        self.update_dispatch_matrix_if_observer_subscribed(
-                            &event_data.asset_identifier,
-                            i,
-                            &mut dispatch_matrix,
-                        );
-                    }
-                    StacksTransactionEvent::NFTEvent(NFTEventType::NFTMintEvent(event_data)) => {
-                        self.update_dispatch_matrix_if_observer_subscribed(
-                            &event_data.asset_identifier,
-                            i,
-                            &mut dispatch_matrix,
-                        );
-                    }
-                    StacksTransactionEvent::NFTEvent(NFTEventType::NFTBurnEvent(event_data)) => {
-                        self.update_dispatch_matrix_if_observer_subscribed(
-                            &event_data.asset_identifier,
-                            i,
-                            &mut dispatch_matrix,
-                        );
-                    }
-                    StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(event_data)) => {
-                        self.update_dispatch_matrix_if_observer_subscribed(
-                            &event_data.asset_identifier,
-                            i,
-                            &mut dispatch_matrix,
-                        );
-                    }
-                    StacksTransactionEvent::FTEvent(FTEventType::FTMintEvent(event_data)) => {
-                        self.update_dispatch_matrix_if_observer_subscribed(
-                            &event_data.asset_identifier,
-                            i,
-                            &mut dispatch_matrix,
-                        );
-                    }
-                    StacksTransactionEvent::FTEvent(FTEventType::FTBurnEvent(event_data)) => {
-                        self.update_dispatch_matrix_if_observer_subscribed(
-                            &event_data.asset_identifier,
-                            i,
-                            &mut dispatch_matrix,
-                        );
-                    }
-                }
-                events.push((!receipt.post_condition_aborted, tx_hash, event));
-                for o_i in &self.any_event_observers_lookup {
-                    dispatch_matrix[*o_i as usize].insert(i);
-                }
-                i += 1;
-            }
-        }
-
-        (dispatch_matrix, events)
-    }
-
     pub fn process_chain_tip(
         &self,
         chain_tip: &ChainTip,
@@ -583,6 +577,11 @@ impl EventDispatcher {
         mature_rewards: Vec<MinerReward>,
         mature_rewards_info: Option<MinerRewardInfo>,
     ) {
+        if let Some(notifier) = self.tip_notifier.as_ref() {
+            notifier.notify_stacks_tip(&chain_tip.metadata);
+        }
+
+        let observers = self.observers();
         let boot_receipts = if chain_tip.metadata.block_height == 1 {
             let mut boot_receipts_result = self
                 .boot_receipts
@@ -603,7 +602,8 @@ impl EventDispatcher {
             .chain(boot_receipts.iter().cloned())
             .collect();
 
-        let (dispatch_matrix, events) = self.create_dispatch_matrix_and_event_vector(&receipts);
+        let (dispatch_matrix, events) =
+            observers.create_dispatch_matrix_and_event_vector(&receipts);
 
         if dispatch_matrix.len() > 0 {
             let mature_rewards_vec = if let Some(rewards_info) = mature_rewards_info {
@@ -634,7 +634,7 @@ impl EventDispatcher {
                     .map(|event_id| (*event_id, &events[*event_id]))
                     .collect();
 
-                self.registered_observers[observer_id].send(
+                observers.registered_observers[observer_id].send(
                     filtered_events,
                     chain_tip,
                     parent_index_hash,
@@ -644,6 +644,75 @@ impl EventDispatcher {
                 );
             }
         }
+
+        self.process_watched_addresses(&observers, chain_tip, &receipts);
+    }
+
+    /// Sends each observer of watched principals what happened to them in this block, if
+    /// anything did.
+    fn process_watched_addresses(
+        &self,
+        observers: &EventObservers,
+        chain_tip: &ChainTip,
+        receipts: &Vec<StacksTransactionReceipt>,
+    ) {
+        if observers.watched_principals_lookup.is_empty() {
+            return;
+        }
+        let watched: HashSet<PrincipalData> = observers
+            .watched_principals_lookup
+            .keys()
+            .cloned()
+            .collect();
+        let events = collect_watched_events(&watched, receipts);
+        let index_block_hash = chain_tip.metadata.index_block_hash();
+        self.watched_activity.record(
+            &watched,
+            &events,
+            chain_tip.metadata.block_height,
+            &index_block_hash,
+        );
+        if events.is_empty() {
+            return;
+        }
+
+        for (observer_id, observer) in observers.registered_observers.iter().enumerate() {
+            let observer_id = observer_id as u16;
+            let is_watched_by_observer = |principal: &PrincipalData| {
+                observers
+                    .watched_principals_lookup
+                    .get(principal)
+                    .map(|ids| ids.contains(&observer_id))
+                    .unwrap_or(false)
+            };
+            let filtered_events: Vec<_> = events
+                .iter()
+                .filter(|event| is_watched_by_observer(event.principal()))
+                .collect();
+            if filtered_events.is_empty() {
+                continue;
+            }
+
+            let mut summaries = serde_json::Map::new();
+            for event in filtered_events.iter() {
+                let principal = event.principal();
+                if let Some(summary) = self.watched_activity.summary(principal) {
+                    summaries.insert(format!("{}", principal), summary.json_serialize());
+                }
+            }
+            let payload = json!({
+                "block_hash": format!("0x{}", chain_tip.block.block_hash()),
+                "block_height": chain_tip.metadata.block_height,
+                "index_block_hash": format!("0x{}", &index_block_hash),
+                "burn_block_height": chain_tip.metadata.burn_header_height,
+                "events": filtered_events
+                    .iter()
+                    .map(|event| event.json_serialize())
+                    .collect::<Vec<_>>(),
+                "summaries": summaries,
+            });
+            observer.send_watched_address_activity(&payload);
+        }
     }
 
     /// Creates a list of observers that are interested in the new microblocks event,
@@ -654,14 +723,19 @@ impl EventDispatcher {
         parent_index_block_hash: StacksBlockId,
         processed_unconfirmed_state: ProcessedUnconfirmedState,
     ) {
+        let observers = self.observers();
         // lazily assemble payload only if we have observers
-        let interested_observers: Vec<_> = self
+        let interested_observers: Vec<_> = observers
             .registered_observers
             .iter()
             .enumerate()
             .filter(|(obs_id, _observer)| {
-                self.microblock_observers_lookup.contains(&(*obs_id as u16))
-                    || self.any_event_observers_lookup.contains(&(*obs_id as u16))
+                observers
+                    .microblock_observers_lookup
+                    .contains(&(*obs_id as u16))
+                    || observers
+                        .any_event_observers_lookup
+                        .contains(&(*obs_id as u16))
             })
             .collect();
         if interested_observers.len() < 1 {
@@ -673,7 +747,7 @@ impl EventDispatcher {
             .flat_map(|(_, r)| r.clone())
             .collect();
         let (dispatch_matrix, events) =
-            self.create_dispatch_matrix_and_event_vector(&flattened_receipts);
+            observers.create_dispatch_matrix_and_event_vector(&flattened_receipts);
 
         // Serialize receipts
         let mut tx_index;
@@ -707,15 +781,49 @@ impl EventDispatcher {
         }
     }
 
+    /// Sends microblock equivocations (pairs of conflicting microblocks signed by the same
+    /// leader) to the observers that are interested in microblocks.
+    pub fn process_microblock_equivocations(&self, equivocations: &[MicroblockEquivocation]) {
+        let observers = self.observers();
+        // lazily assemble payload only if we have observers
+        let interested_observers: Vec<_> = observers
+            .registered_observers
+            .iter()
+            .enumerate()
+            .filter(|(obs_id, _observer)| {
+                observers
+                    .microblock_observers_lookup
+                    .contains(&(*obs_id as u16))
+                    || observers
+                        .any_event_observers_lookup
+                        .contains(&(*obs_id as u16))
+            })
+            .collect();
+        if interested_observers.len() < 1 {
+            return;
+        }
+
+        let payload = EventObserver::make_microblock_equivocations_payload(equivocations);
+
+        for (_, observer) in interested_observers.iter() {
+            observer.send_microblock_equivocations(&payload);
+        }
+    }
+
     pub fn process_new_mempool_txs(&self, txs: Vec<StacksTransaction>) {
+        let observers = self.observers();
         // lazily assemble payload only if we have observers
-        let interested_observers: Vec<_> = self
+        let interested_observers: Vec<_> = observers
             .registered_observers
             .iter()
             .enumerate()
             .filter(|(obs_id, _observer)| {
-                self.mempool_observers_lookup.contains(&(*obs_id as u16))
-                    || self.any_event_observers_lookup.contains(&(*obs_id as u16))
+                observers
+                    .mempool_observers_lookup
+                    .contains(&(*obs_id as u16))
+                    || observers
+                        .any_event_observers_lookup
+                        .contains(&(*obs_id as u16))
             })
             .collect();
         if interested_observers.len() < 1 {
@@ -730,14 +838,19 @@ impl EventDispatcher {
     }
 
     pub fn process_dropped_mempool_txs(&self, txs: Vec<Txid>, reason: MemPoolDropReason) {
+        let observers = self.observers();
         // lazily assemble payload only if we have observers
-        let interested_observers: Vec<_> = self
+        let interested_observers: Vec<_> = observers
             .registered_observers
             .iter()
             .enumerate()
             .filter(|(obs_id, _observer)| {
-                self.mempool_observers_lookup.contains(&(*obs_id as u16))
-                    || self.any_event_observers_lookup.contains(&(*obs_id as u16))
+                observers
+                    .mempool_observers_lookup
+                    .contains(&(*obs_id as u16))
+                    || observers
+                        .any_event_observers_lookup
+                        .contains(&(*obs_id as u16))
             })
             .collect();
         if interested_observers.len() < 1 {
@@ -760,7 +873,9 @@ impl EventDispatcher {
     }
 
     pub fn process_new_attachments(&self, attachments: &Vec<(AttachmentInstance, Attachment)>) {
-        let interested_observers: Vec<_> = self.registered_observers.iter().enumerate().collect();
+        let observers = self.observers();
+        let interested_observers: Vec<_> =
+            observers.registered_observers.iter().enumerate().collect();
         if interested_observers.len() < 1 {
             return;
         }
@@ -780,6 +895,145 @@ impl EventDispatcher {
         self.boot_receipts = Arc::new(Mutex::new(Some(receipts)));
     }
 
+    pub fn register_observer(
+        &mut self,
+        conf: &EventObserverConfig,
+        should_keep_running: Arc<AtomicBool>,
+    ) {
+        self.observers
+            .write()
+            .expect("FATAL: event observers lock poisoned")
+            .register(conf, should_keep_running);
+    }
+
+    /// Replace the registered observers with those in `confs`.  Events dispatched after this
+    /// returns go to the new observers only.
+    pub fn replace_observers(
+        &self,
+        confs: &[EventObserverConfig],
+        should_keep_running: Arc<AtomicBool>,
+    ) {
+        let mut observers = EventObservers::default();
+        for conf in confs.iter() {
+            observers.register(conf, should_keep_running.clone());
+        }
+        *self
+            .observers
+            .write()
+            .expect("FATAL: event observers lock poisoned") = observers;
+    }
+
+    /// The endpoints of the registered observers
+    pub fn observer_endpoints(&self) -> Vec<String> {
+        self.observers()
+            .registered_observers
+            .iter()
+            .map(|observer| observer.endpoint.clone())
+            .collect()
+    }
+}
+
+impl EventObservers {
+    /// Iterates through tx receipts, and then the events corresponding to each receipt to
+    /// generate a dispatch matrix & event vector.
+    ///
+    /// # Returns
+    /// - dispatch_matrix: a vector where each index corresponds to the hashset of event indexes
+    ///     that each respective event observer is subscribed to
+    /// - events: a vector of all events from all the tx receipts
+    fn create_dispatch_matrix_and_event_vector<'a>(
+        &self,
+        receipts: &'a Vec<StacksTransactionReceipt>,
+    ) -> (
+        Vec<HashSet<usize>>,
+        Vec<(bool, Txid, &'a StacksTransactionEvent)>,
+    ) {
+        let mut dispatch_matrix: Vec<HashSet<usize>> = self
+            .registered_observers
+            .iter()
+            .map(|_| HashSet::new())
+            .collect();
+        let mut events: Vec<(bool, Txid, &StacksTransactionEvent)> = vec![];
+        let mut i: usize = 0;
+
+        for receipt in receipts {
+            let tx_hash = receipt.transaction.txid();
+            for event in receipt.events.iter() {
+                match event {
+                    StacksTransactionEvent::SmartContractEvent(event_data) => {
+                        if let Some(observer_indexes) =
+                            self.contract_events_observers_lookup.get(&event_data.key)
+                        {
+                            for o_i in observer_indexes {
+                                dispatch_matrix[*o_i as usize].insert(i);
+                            }
+                        }
+                    }
+                    StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(_))
+                    | StacksTransactionEvent::STXEvent(STXEventType::STXMintEvent(_))
+                    | StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(_))
+                    | StacksTransactionEvent::STXEvent(STXEventType::STXLockEvent(_))
+                    | StacksTransactionEvent::PoxEvent(_) => {
+                        for o_i in &self.stx_observers_lookup {
+                            dispatch_matrix[*o_i as usize].insert(i);
+                        }
+                    }
+                    StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(
+                        event_data,
+                    )) => {
+                        self.update_dispatch_matrix_if_observer_subscribed(
+                            &event_data.asset_identifier,
+                            i,
+                            &mut dispatch_matrix,
+                        );
+                    }
+                    StacksTransactionEvent::NFTEvent(NFTEventType::NFTMintEvent(event_data)) => {
+                        self.update_dispatch_matrix_if_observer_subscribed(
+                            &event_data.asset_identifier,
+                            i,
+                            &mut dispatch_matrix,
+                        );
+                    }
+                    StacksTransactionEvent::NFTEvent(NFTEventType::NFTBurnEvent(event_data)) => {
+                        self.update_dispatch_matrix_if_observer_subscribed(
+                            &event_data.asset_identifier,
+                            i,
+                            &mut dispatch_matrix,
+                        );
+                    }
+                    StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(event_data)) => {
+                        self.update_dispatch_matrix_if_observer_subscribed(
+                            &event_data.asset_identifier,
+                            i,
+                            &mut dispatch_matrix,
+                        );
+                    }
+                    StacksTransactionEvent::FTEvent(FTEventType::FTMintEvent(event_data)) => {
+                        self.update_dispatch_matrix_if_observer_subscribed(
+                            &event_data.asset_identifier,
+                            i,
+                            &mut dispatch_matrix,
+                        );
+                    }
+                    StacksTransactionEvent::FTEvent(FTEventType::FTBurnEvent(event_data)) => {
+                        self.update_dispatch_matrix_if_observer_subscribed(
+                            &event_data.asset_identifier,
+                            i,
+                            &mut dispatch_matrix,
+                        );
+                    }
+                }
+                events.push((!receipt.post_condition_aborted, tx_hash, event));
+                for o_i in &self.any_event_observers_lookup {
+                    dispatch_matrix[*o_i as usize].insert(i);
+                }
+                i += 1;
+            }
+        }
+
+        (dispatch_matrix, events)
+    }
+
     fn update_dispatch_matrix_if_observer_subscribed(
         &self,
         asset_identifier: &AssetIdentifier,
@@ -793,11 +1047,7 @@ impl EventDispatcher {
         }
     }
 
-    pub fn register_observer(
-        &mut self,
-        conf: &EventObserverConfig,
-        should_keep_running: Arc<AtomicBool>,
-    ) {
+    fn register(&mut self, conf: &EventObserverConfig, should_keep_running: Arc<AtomicBool>) {
         info!("Registering event observer at: {}", conf.endpoint);
         let event_observer = EventObserver {
             endpoint: conf.endpoint.clone(),
@@ -850,6 +1100,12 @@ impl EventDispatcher {
                 EventKeyType::AnyEvent => {
                     self.any_event_observers_lookup.insert(observer_index);
                 }
+                EventKeyType::WatchedPrincipal(principal) => {
+                    self.watched_principals_lookup
+                        .entry(principal.clone())
+                        .or_insert_with(HashSet::new)
+                        .insert(observer_index);
+                }
             }
         }
 
@@ -0,0 +1,329 @@
+//! The node's p2p identity key (`stacks-node identity`).
+//!
+//! The private key a node signs its p2p messages with is kept in its own file,
+//! `node.identity_key_path` (by default, `identity.key` next to the peer DB), as a hex string.
+//! The file must not be readable by other users.  At startup the node uses the key in the file,
+//! whatever the peer DB holds.  If there is no file yet, the node keeps the key its peer DB
+//! already has -- or, on a new node, derives one from `node.local_peer_seed` -- and writes it
+//! out, so a node's identity survives config changes and can be backed up on its own.
+//!
+//! `export` prints the key, `import` replaces it with a key from another file, and `rotate`
+//! replaces it with a new random key.  A running node switches to an imported or rotated key on
+//! SIGHUP, and re-handshakes with its peers so that they learn the new key.  When the key expires
+//! (see `connection_options.private_key_lifetime`), the node rekeys itself and writes the new key
+//! out to the file, unless the file has been changed in the meantime -- an imported or rotated key
+//! that is waiting for a SIGHUP is never overwritten.
+
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+use pico_args::Arguments;
+
+use stacks::net::db::PeerDB;
+use stacks::util::secp256k1::{Secp256k1PrivateKey, Secp256k1PublicKey};
+
+use crate::peers;
+use crate::{Config, ConfigFile};
+
+pub fn run_identity_command(mut args: Arguments) -> Result<(), String> {
+    let subcommand = args
+        .subcommand()
+        .map_err(|e| format!("{}", e))?
+        .unwrap_or_default();
+    let config_path: Option<String> = args
+        .opt_value_from_str("--config")
+        .map_err(|e| format!("{}", e))?;
+    let config_path =
+        config_path.ok_or("`identity` must be passed a config file via `--config`")?;
+    let profile: Option<String> = args
+        .opt_value_from_str("--profile")
+        .map_err(|e| format!("{}", e))?;
+    let config = Config::from_config_file(ConfigFile::from_path_with_profile(
+        &config_path,
+        profile.as_deref(),
+    )?);
+    let key_path = config.get_identity_key_path();
+
+    match subcommand.as_str() {
+        "export" => {
+            let output: Option<String> = args
+                .opt_value_from_str("--output")
+                .map_err(|e| format!("{}", e))?;
+            args.finish().map_err(|e| format!("{}", e))?;
+
+            // a node that has not run since upgrading has its key only in the peer DB
+            let key = match read_identity_key_if_present(&key_path)? {
+                Some(key) => key,
+                None => {
+                    peers::open_peer_db(&config)?;
+                    read_identity_key(Path::new(&key_path))?
+                }
+            };
+            match output {
+                Some(path) => write_identity_key(Path::new(&path), &key)?,
+                None => println!("{}", key.to_hex()),
+            }
+            eprintln!("Public key: {}", public_key_hex(&key));
+            Ok(())
+        }
+        "import" => {
+            let free_args = args.free().map_err(|e| format!("{}", e))?;
+            let source = free_args
+                .first()
+                .ok_or("`identity import` must be passed a key file")?;
+            let key = read_identity_key(Path::new(source))?;
+            write_identity_key(Path::new(&key_path), &key)?;
+            println!(
+                "Imported identity key {} into {}",
+                public_key_hex(&key),
+                &key_path
+            );
+            Ok(())
+        }
+        "rotate" => {
+            args.finish().map_err(|e| format!("{}", e))?;
+            let key = Secp256k1PrivateKey::new();
+            write_identity_key(Path::new(&key_path), &key)?;
+            println!(
+                "New identity key {} written to {}",
+                public_key_hex(&key),
+                &key_path
+            );
+            println!("Send a running node SIGHUP to switch to it");
+            Ok(())
+        }
+        _ => Err(format!(
+            "Unrecognized `identity` subcommand '{}': expected `export`, `import` or `rotate`",
+            &subcommand
+        )),
+    }
+}
+
+fn public_key_hex(key: &Secp256k1PrivateKey) -> String {
+    Secp256k1PublicKey::from_private(key).to_hex()
+}
+
+/// Read an identity key file, refusing one that other users could read.
+pub fn read_identity_key(path: &Path) -> Result<Secp256k1PrivateKey, String> {
+    let metadata =
+        fs::metadata(path).map_err(|e| format!("Failed to read {}: {}", path.display(), &e))?;
+    check_permissions(path, &metadata)?;
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), &e))?;
+    Secp256k1PrivateKey::from_hex(text.trim())
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+/// Like `read_identity_key`, but a missing file is not an error.
+pub fn read_identity_key_if_present(path: &str) -> Result<Option<Secp256k1PrivateKey>, String> {
+    match fs::metadata(path) {
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        _ => read_identity_key(Path::new(path)).map(Some),
+    }
+}
+
+#[cfg(unix)]
+fn check_permissions(path: &Path, metadata: &fs::Metadata) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = metadata.permissions().mode();
+    if mode & 0o077 != 0 {
+        return Err(format!(
+            "{} is accessible by other users (mode {:o}); run `chmod 600 {}`",
+            path.display(),
+            mode & 0o777,
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_path: &Path, _metadata: &fs::Metadata) -> Result<(), String> {
+    Ok(())
+}
+
+/// Write an identity key file that only this user can read.  The key is written to a temporary
+/// file first, so a crash never leaves a half-written key behind.
+pub fn write_identity_key(path: &Path, key: &Secp256k1PrivateKey) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, &e))?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(&tmp_path)
+        .map_err(|e| format!("Failed to create {}: {}", tmp_path.display(), &e))?;
+    file.write_all(format!("{}\n", key.to_hex()).as_bytes())
+        .and_then(|_| file.sync_all())
+        .map_err(|e| format!("Failed to write {}: {}", tmp_path.display(), &e))?;
+    fs::rename(&tmp_path, path).map_err(|e| format!("Failed to write {}: {}", path.display(), &e))
+}
+
+/// The key to create a new peer DB with: the identity key file's, or else one derived from
+/// `node.local_peer_seed`.
+pub fn initial_identity_key(config: &Config) -> Result<Secp256k1PrivateKey, String> {
+    let key_opt = read_identity_key_if_present(&config.get_identity_key_path())?;
+    Ok(key_opt.unwrap_or_else(|| config.node.local_peer_private_key()))
+}
+
+/// Make the peer DB and the identity key file agree, once the peer DB is open.  The file wins if
+/// there is one; otherwise the peer DB's key is written out to it.  Returns the key.
+pub fn persist_identity_key(
+    key_path: &str,
+    peerdb: &mut PeerDB,
+) -> Result<Secp256k1PrivateKey, String> {
+    let local_peer = PeerDB::get_local_peer(peerdb.conn())
+        .map_err(|e| format!("Failed to read local peer: {:?}", &e))?;
+    match read_identity_key_if_present(key_path)? {
+        Some(key) => {
+            if key != local_peer.private_key {
+                info!(
+                    "Switching to identity key {} from {}",
+                    public_key_hex(&key),
+                    key_path
+                );
+                let mut tx = peerdb
+                    .tx_begin()
+                    .map_err(|e| format!("Failed to begin transaction: {:?}", &e))?;
+                PeerDB::set_local_private_key(&mut tx, &key, local_peer.private_key_expire)
+                    .map_err(|e| format!("Failed to set identity key: {:?}", &e))?;
+                tx.commit()
+                    .map_err(|e| format!("Failed to commit peer DB: {:?}", &e))?;
+            }
+            Ok(key)
+        }
+        None => {
+            write_identity_key(Path::new(key_path), &local_peer.private_key)?;
+            info!(
+                "Wrote identity key {} to {}",
+                public_key_hex(&local_peer.private_key),
+                key_path
+            );
+            Ok(local_peer.private_key)
+        }
+    }
+}
+
+/// Write out the key the peer network is using, if it switched to a new one on its own when its
+/// old one expired.  `persisted` is the key the file held as of the last call, and is updated to
+/// `key`.
+pub fn persist_rekeyed_identity_key(
+    key_path: &str,
+    key: &Secp256k1PrivateKey,
+    persisted: &mut Secp256k1PrivateKey,
+) {
+    if key == persisted {
+        return;
+    }
+    match read_identity_key_if_present(key_path) {
+        Ok(Some(ref file_key)) if file_key != persisted && file_key != key => {
+            warn!(
+                "Identity key {} was replaced in {}; not overwriting it with the rekeyed identity key {}",
+                public_key_hex(file_key),
+                key_path,
+                public_key_hex(key)
+            );
+        }
+        Ok(_) | Err(_) => match write_identity_key(Path::new(key_path), key) {
+            Ok(()) => {
+                info!(
+                    "Wrote rekeyed identity key {} to {}",
+                    public_key_hex(key),
+                    key_path
+                );
+            }
+            Err(e) => {
+                error!("Failed to persist rekeyed identity key: {}", &e);
+            }
+        },
+    }
+    *persisted = key.clone();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::process;
+
+    fn test_dir(name: &str) -> String {
+        let dir = format!("/tmp/stacks-node-tests/identity-{}-{}", name, process::id());
+        if Path::new(&dir).exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn identity_key_file_round_trip() {
+        let dir = test_dir("round-trip");
+        let path = format!("{}/identity.key", &dir);
+        assert_eq!(read_identity_key_if_present(&path).unwrap(), None);
+
+        let key = Secp256k1PrivateKey::new();
+        write_identity_key(Path::new(&path), &key).unwrap();
+        assert_eq!(read_identity_key_if_present(&path).unwrap(), Some(key));
+
+        // rotating replaces the key
+        let new_key = Secp256k1PrivateKey::new();
+        write_identity_key(Path::new(&path), &new_key).unwrap();
+        assert_eq!(read_identity_key(Path::new(&path)).unwrap(), new_key);
+
+        fs::write(&path, "not a key").unwrap();
+        assert!(read_identity_key(Path::new(&path)).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn identity_key_file_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = test_dir("permissions");
+        let path = format!("{}/identity.key", &dir);
+        write_identity_key(Path::new(&path), &Secp256k1PrivateKey::new()).unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        let err = read_identity_key(Path::new(&path)).unwrap_err();
+        assert!(err.contains("chmod 600"));
+    }
+
+    #[test]
+    fn rekeyed_identity_key_is_persisted() {
+        let dir = test_dir("rekeyed");
+        let path = format!("{}/identity.key", &dir);
+        let key = Secp256k1PrivateKey::new();
+        write_identity_key(Path::new(&path), &key).unwrap();
+        let mut persisted = key.clone();
+
+        // no change, nothing to do
+        persist_rekeyed_identity_key(&path, &key, &mut persisted);
+        assert_eq!(read_identity_key(Path::new(&path)).unwrap(), key);
+
+        // the network rekeyed itself
+        let rekeyed = Secp256k1PrivateKey::new();
+        persist_rekeyed_identity_key(&path, &rekeyed, &mut persisted);
+        assert_eq!(persisted, rekeyed);
+        assert_eq!(read_identity_key(Path::new(&path)).unwrap(), rekeyed);
+
+        // a rotated key waiting for a SIGHUP is left alone
+        let rotated = Secp256k1PrivateKey::new();
+        write_identity_key(Path::new(&path), &rotated).unwrap();
+        let rekeyed_again = Secp256k1PrivateKey::new();
+        persist_rekeyed_identity_key(&path, &rekeyed_again, &mut persisted);
+        assert_eq!(read_identity_key(Path::new(&path)).unwrap(), rotated);
+    }
+}
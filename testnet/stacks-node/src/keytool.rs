@@ -0,0 +1,427 @@
+//! Offline key management for node operators (`stacks-node keychain ...`).
+//!
+//! Generates BIP39 mnemonics, derives keys from them along BIP32 paths, and prints the Stacks and
+//! Bitcoin addresses for a key.  By default, keys are derived along the same paths that Stacks
+//! wallets use (`m/44'/5757'/0'/0/<index>`), and along the BIP44 Bitcoin paths, so the keys
+//! derived here match the ones an operator's wallet shows for the same mnemonic.  Any derived
+//! private key can be used as the `seed` for the node's miner or for the `[stacker]` section.
+
+use bip39::{Language, Mnemonic, MnemonicType, Seed};
+use hmac::{Hmac, Mac};
+use pico_args::Arguments;
+use secp256k1::SecretKey;
+use sha2::Sha512;
+
+use stacks::address::b58;
+use stacks::address::AddressHashMode;
+use stacks::burnchains::bitcoin::address::{BitcoinAddress, BitcoinAddressType};
+use stacks::burnchains::bitcoin::BitcoinNetworkType;
+use stacks::burnchains::PrivateKey;
+use stacks::chainstate::stacks::{
+    StacksPrivateKey, StacksPublicKey, C32_ADDRESS_VERSION_MAINNET_SINGLESIG,
+    C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+};
+use stacks::types::chainstate::StacksAddress;
+use stacks::util::hash::{hex_bytes, Hash160};
+
+type HmacSha512 = Hmac<Sha512>;
+
+/// Index offset for hardened BIP32 derivation
+const BIP32_HARDENED: u32 = 0x8000_0000;
+
+/// WIF version bytes
+const WIF_VERSION_MAINNET: u8 = 0x80;
+const WIF_VERSION_TESTNET: u8 = 0xef;
+
+/// Derivation path prefix used by Stacks wallets
+pub const STACKS_DERIVATION_PATH: &str = "m/44'/5757'/0'/0";
+/// BIP44 derivation path prefixes for Bitcoin keys
+pub const BITCOIN_MAINNET_DERIVATION_PATH: &str = "m/44'/0'/0'/0";
+pub const BITCOIN_TESTNET_DERIVATION_PATH: &str = "m/44'/1'/0'/0";
+
+/// A BIP32 extended private key
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtendedPrivateKey {
+    pub private_key: StacksPrivateKey,
+    pub chain_code: [u8; 32],
+}
+
+fn hmac_sha512(key: &[u8], data: &[u8]) -> [u8; 64] {
+    let mut mac = HmacSha512::new_varkey(key).expect("FATAL: HMAC-SHA512 accepts keys of any size");
+    mac.input(data);
+    let mut out = [0u8; 64];
+    out.copy_from_slice(&mac.result().code());
+    out
+}
+
+impl ExtendedPrivateKey {
+    fn from_hmac_output(
+        output: &[u8; 64],
+        parent: Option<&StacksPrivateKey>,
+    ) -> Result<ExtendedPrivateKey, String> {
+        let mut secret_key = SecretKey::from_slice(&output[0..32])
+            .map_err(|_| "Derived key is out of range".to_string())?;
+        if let Some(parent) = parent {
+            // child key is (IL + parent key) mod n
+            secret_key
+                .add_assign(&parent.to_bytes()[0..32])
+                .map_err(|_| "Derived key is out of range".to_string())?;
+        }
+
+        let mut private_key = StacksPrivateKey::from_slice(&secret_key[..])
+            .map_err(|e| format!("Failed to load derived key: {}", e))?;
+        private_key.set_compress_public(true);
+
+        let mut chain_code = [0u8; 32];
+        chain_code.copy_from_slice(&output[32..64]);
+        Ok(ExtendedPrivateKey {
+            private_key,
+            chain_code,
+        })
+    }
+
+    /// Make the BIP32 master key for a seed
+    pub fn from_seed(seed: &[u8]) -> Result<ExtendedPrivateKey, String> {
+        ExtendedPrivateKey::from_hmac_output(&hmac_sha512(b"Bitcoin seed", seed), None)
+    }
+
+    /// Derive the child key at `index`.  Indexes at or above `BIP32_HARDENED` are hardened.
+    pub fn derive_child(&self, index: u32) -> Result<ExtendedPrivateKey, String> {
+        let mut data = Vec::with_capacity(37);
+        if index >= BIP32_HARDENED {
+            data.push(0);
+            data.extend_from_slice(&self.private_key.to_bytes()[0..32]);
+        } else {
+            let mut public_key = StacksPublicKey::from_private(&self.private_key);
+            public_key.set_compressed(true);
+            data.extend_from_slice(&public_key.to_bytes_compressed());
+        }
+        data.extend_from_slice(&index.to_be_bytes());
+
+        ExtendedPrivateKey::from_hmac_output(
+            &hmac_sha512(&self.chain_code, &data),
+            Some(&self.private_key),
+        )
+    }
+
+    /// Derive the key at the given path (e.g. `m/44'/5757'/0'/0/0`)
+    pub fn derive_path(&self, path: &str) -> Result<ExtendedPrivateKey, String> {
+        let mut key = self.clone();
+        for index in parse_derivation_path(path)? {
+            key = key.derive_child(index)?;
+        }
+        Ok(key)
+    }
+}
+
+/// Parse a BIP32 derivation path into its child indexes.  Hardened indexes are marked with `'`
+/// or `h`.
+pub fn parse_derivation_path(path: &str) -> Result<Vec<u32>, String> {
+    let mut parts = path.split('/');
+    if parts.next() != Some("m") {
+        return Err(format!("Derivation path '{}' must start with 'm'", path));
+    }
+
+    parts
+        .map(|part| {
+            let (digits, offset) = if part.ends_with('\'') || part.ends_with('h') {
+                (&part[0..part.len() - 1], BIP32_HARDENED)
+            } else {
+                (part, 0)
+            };
+            match digits.parse::<u32>() {
+                Ok(index) if index < BIP32_HARDENED => Ok(index + offset),
+                _ => Err(format!(
+                    "Invalid index '{}' in derivation path '{}'",
+                    part, path
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Generate a new English mnemonic with the given number of words
+pub fn generate_mnemonic(num_words: usize) -> Result<String, String> {
+    let mnemonic_type = MnemonicType::for_word_count(num_words)
+        .map_err(|_| format!("Unsupported mnemonic length: {} words", num_words))?;
+    Ok(Mnemonic::new(mnemonic_type, Language::English)
+        .phrase()
+        .to_string())
+}
+
+/// Validate an English mnemonic and compute its BIP39 seed
+pub fn seed_from_mnemonic(phrase: &str, passphrase: &str) -> Result<Vec<u8>, String> {
+    let mnemonic = Mnemonic::from_phrase(phrase.trim(), Language::English)
+        .map_err(|e| format!("Invalid mnemonic: {}", e))?;
+    Ok(Seed::new(&mnemonic, passphrase).as_bytes().to_vec())
+}
+
+/// Parse a private key given either as hex (as in the node's `seed` option) or as WIF
+pub fn parse_private_key(key: &str) -> Result<StacksPrivateKey, String> {
+    if let Ok(bytes) = hex_bytes(key) {
+        return StacksPrivateKey::from_slice(&bytes).map_err(|e| e.to_string());
+    }
+
+    let bytes = b58::from_check(key).map_err(|_| "Not a hex or WIF private key".to_string())?;
+    if bytes.len() < 1 || (bytes[0] != WIF_VERSION_MAINNET && bytes[0] != WIF_VERSION_TESTNET) {
+        return Err("Unrecognized WIF version byte".to_string());
+    }
+    StacksPrivateKey::from_slice(&bytes[1..]).map_err(|e| e.to_string())
+}
+
+/// Encode a private key as WIF
+pub fn to_wif(private_key: &StacksPrivateKey, mainnet: bool) -> String {
+    let mut bytes = private_key.to_bytes();
+    bytes.insert(
+        0,
+        if mainnet {
+            WIF_VERSION_MAINNET
+        } else {
+            WIF_VERSION_TESTNET
+        },
+    );
+    b58::check_encode_slice(&bytes)
+}
+
+/// The single-signature Stacks and Bitcoin addresses for a private key
+pub fn key_addresses(
+    private_key: &StacksPrivateKey,
+    mainnet: bool,
+) -> (StacksAddress, BitcoinAddress) {
+    let public_key = StacksPublicKey::from_private(private_key);
+    let stacks_address = StacksAddress::from_public_keys(
+        if mainnet {
+            C32_ADDRESS_VERSION_MAINNET_SINGLESIG
+        } else {
+            C32_ADDRESS_VERSION_TESTNET_SINGLESIG
+        },
+        &AddressHashMode::SerializeP2PKH,
+        1,
+        &vec![public_key.clone()],
+    )
+    .expect("FATAL: failed to make an address from a single public key");
+    let bitcoin_address = BitcoinAddress {
+        addrtype: BitcoinAddressType::PublicKeyHash,
+        network_id: if mainnet {
+            BitcoinNetworkType::Mainnet
+        } else {
+            BitcoinNetworkType::Testnet
+        },
+        bytes: Hash160::from_node_public_key(&public_key),
+    };
+    (stacks_address, bitcoin_address)
+}
+
+fn print_key(private_key: &StacksPrivateKey, derivation_path: Option<&str>, mainnet: bool) {
+    let (stacks_address, bitcoin_address) = key_addresses(private_key, mainnet);
+    if let Some(path) = derivation_path {
+        println!("Derivation path:    {}", path);
+    }
+    println!("Private key (hex):  {}", private_key.to_hex());
+    println!("Private key (WIF):  {}", to_wif(private_key, mainnet));
+    println!(
+        "Public key:         {}",
+        StacksPublicKey::from_private(private_key).to_hex()
+    );
+    println!("Stacks address:     {}", stacks_address);
+    println!("Bitcoin address:    {}", bitcoin_address.to_b58());
+    println!();
+}
+
+fn print_derived_keys(
+    seed: &[u8],
+    path: Option<String>,
+    index: u32,
+    mainnet: bool,
+) -> Result<(), String> {
+    let master = ExtendedPrivateKey::from_seed(seed)?;
+    let paths = match path {
+        Some(path) => vec![path],
+        None => vec![
+            format!("{}/{}", STACKS_DERIVATION_PATH, index),
+            format!(
+                "{}/{}",
+                if mainnet {
+                    BITCOIN_MAINNET_DERIVATION_PATH
+                } else {
+                    BITCOIN_TESTNET_DERIVATION_PATH
+                },
+                index
+            ),
+        ],
+    };
+    for path in paths.iter() {
+        let key = master.derive_path(path)?;
+        print_key(&key.private_key, Some(path), mainnet);
+    }
+    Ok(())
+}
+
+/// Run `stacks-node keychain <generate|derive|inspect>`
+pub fn run_keychain_command(mut args: Arguments) -> Result<(), String> {
+    let action = args
+        .subcommand()
+        .map_err(|e| e.to_string())?
+        .unwrap_or_default();
+    let mainnet = !args.contains("--testnet");
+
+    match action.as_str() {
+        "generate" => {
+            let num_words: usize = args
+                .opt_value_from_str("--words")
+                .map_err(|e| e.to_string())?
+                .unwrap_or(24);
+            let passphrase: String = args
+                .opt_value_from_str("--passphrase")
+                .map_err(|e| e.to_string())?
+                .unwrap_or_default();
+            args.finish().map_err(|e| e.to_string())?;
+
+            let phrase = generate_mnemonic(num_words)?;
+            println!("Mnemonic:           {}", &phrase);
+            println!();
+            print_derived_keys(&seed_from_mnemonic(&phrase, &passphrase)?, None, 0, mainnet)
+        }
+        "derive" => {
+            let phrase: String = args
+                .value_from_str("--mnemonic")
+                .map_err(|e| e.to_string())?;
+            let passphrase: String = args
+                .opt_value_from_str("--passphrase")
+                .map_err(|e| e.to_string())?
+                .unwrap_or_default();
+            let path: Option<String> = args
+                .opt_value_from_str("--path")
+                .map_err(|e| e.to_string())?;
+            let index: u32 = args
+                .opt_value_from_str("--index")
+                .map_err(|e| e.to_string())?
+                .unwrap_or(0);
+            args.finish().map_err(|e| e.to_string())?;
+
+            print_derived_keys(
+                &seed_from_mnemonic(&phrase, &passphrase)?,
+                path,
+                index,
+                mainnet,
+            )
+        }
+        "inspect" => {
+            let free_args = args.free().map_err(|e| e.to_string())?;
+            let key = free_args
+                .first()
+                .ok_or_else(|| "`keychain inspect` must be passed a private key".to_string())?;
+            print_key(&parse_private_key(key)?, None, mainnet);
+            Ok(())
+        }
+        _ => Err(format!(
+            "Unknown keychain command '{}': expected one of `generate`, `derive`, or `inspect`",
+            &action
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use stacks::util::hash::to_hex;
+
+    #[test]
+    fn test_parse_derivation_path() {
+        assert_eq!(
+            parse_derivation_path("m/44'/5757'/0'/0/1").unwrap(),
+            vec![
+                44 + BIP32_HARDENED,
+                5757 + BIP32_HARDENED,
+                BIP32_HARDENED,
+                0,
+                1
+            ]
+        );
+        assert_eq!(
+            parse_derivation_path("m/0h/2").unwrap(),
+            vec![BIP32_HARDENED, 2]
+        );
+        assert_eq!(parse_derivation_path("m").unwrap(), Vec::<u32>::new());
+
+        assert!(parse_derivation_path("44'/0").is_err());
+        assert!(parse_derivation_path("m/x").is_err());
+        assert!(parse_derivation_path("m/2147483648").is_err());
+        assert!(parse_derivation_path("m//1").is_err());
+    }
+
+    #[test]
+    fn test_bip32_vectors() {
+        // test vector 1 from BIP32
+        let master =
+            ExtendedPrivateKey::from_seed(&hex_bytes("000102030405060708090a0b0c0d0e0f").unwrap())
+                .unwrap();
+        assert_eq!(
+            to_hex(&master.private_key.to_bytes()[0..32]),
+            "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35"
+        );
+        assert_eq!(
+            to_hex(&master.chain_code),
+            "873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508"
+        );
+
+        let child = master.derive_path("m/0'").unwrap();
+        assert_eq!(
+            to_hex(&child.private_key.to_bytes()[0..32]),
+            "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea"
+        );
+
+        let child = master.derive_path("m/0'/1/2'/2/1000000000").unwrap();
+        assert_eq!(
+            to_hex(&child.private_key.to_bytes()[0..32]),
+            "471b76e389e528d6de6d816857e012c5455051cad6660850e58372a6c3e6e7c8"
+        );
+    }
+
+    #[test]
+    fn test_mnemonics() {
+        let phrase = generate_mnemonic(24).unwrap();
+        assert_eq!(phrase.split(' ').count(), 24);
+        let seed = seed_from_mnemonic(&phrase, "").unwrap();
+        assert_eq!(seed.len(), 64);
+
+        // passphrases change the seed
+        assert_ne!(seed, seed_from_mnemonic(&phrase, "hunter2").unwrap());
+
+        // bad checksum
+        assert!(seed_from_mnemonic(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon",
+            ""
+        )
+        .is_err());
+        assert!(generate_mnemonic(13).is_err());
+    }
+
+    #[test]
+    fn test_private_key_encodings() {
+        let private_key = StacksPrivateKey::from_hex(
+            "9e446f6b0c6a96cf2190e54bcd5a8569c3e386f091605499464389b8d4e0bfc201",
+        )
+        .unwrap();
+
+        for mainnet in [true, false].iter() {
+            let wif = to_wif(&private_key, *mainnet);
+            assert_eq!(parse_private_key(&wif).unwrap(), private_key);
+        }
+        assert_eq!(
+            parse_private_key(&private_key.to_hex()).unwrap(),
+            private_key
+        );
+        assert!(parse_private_key("not a key").is_err());
+
+        // both addresses commit to the same public key hash
+        let (stacks_address, bitcoin_address) = key_addresses(&private_key, true);
+        assert_eq!(stacks_address.bytes, bitcoin_address.bytes);
+        assert_eq!(
+            stacks_address.version,
+            C32_ADDRESS_VERSION_MAINNET_SINGLESIG
+        );
+        assert_eq!(bitcoin_address.network_id, BitcoinNetworkType::Mainnet);
+    }
+}
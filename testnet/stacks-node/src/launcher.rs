@@ -0,0 +1,278 @@
+//! Running a node in-process.  `NodeBuilder` takes a config and starts the run loop for its
+//! burnchain mode (helium and mocknet, or neon, xenon, krypton and mainnet), either on the current
+//! thread, as `stacks-node start` does, or on a thread of its own, returning a `NodeHandle` to
+//! watch and stop it with.  Integration tests drive nodes this way instead of spawning the binary
+//! and parsing its logs.
+//!
+//! A spawned node does not install a SIGTERM handler; it runs until its handle stops it (or, for
+//! helium and mocknet, until it has run its rounds).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use stacks::burnchains::Burnchain;
+use stacks::chainstate::coordinator::comm::CoordinatorChannels;
+
+use crate::{helium, neon, Config};
+
+/// The burnchain modes the helium run loop runs
+const HELIUM_MODES: &[&str] = &["helium", "mocknet"];
+
+/// The burnchain modes the neon run loop runs
+const NEON_MODES: &[&str] = &["neon", "xenon", "krypton", "mainnet"];
+
+pub struct NodeBuilder {
+    config: Config,
+    config_path: Option<(String, Option<String>)>,
+    burnchain: Option<Burnchain>,
+    mine_start: u64,
+    num_rounds: u64,
+}
+
+impl NodeBuilder {
+    pub fn new(config: Config) -> NodeBuilder {
+        NodeBuilder {
+            config,
+            config_path: None,
+            burnchain: None,
+            mine_start: 0,
+            num_rounds: 0,
+        }
+    }
+
+    /// Re-read the reloadable settings from this config file on SIGHUP (neon only)
+    pub fn config_path(mut self, path: &str, profile: Option<&str>) -> NodeBuilder {
+        self.config_path = Some((path.to_string(), profile.map(|p| p.to_string())));
+        self
+    }
+
+    /// Use these burnchain parameters instead of the ones for the config's mode (neon only)
+    pub fn burnchain(mut self, burnchain: Burnchain) -> NodeBuilder {
+        self.burnchain = Some(burnchain);
+        self
+    }
+
+    /// Don't mine until the Stacks chain reaches this height (neon only)
+    pub fn mine_start(mut self, height: u64) -> NodeBuilder {
+        self.mine_start = height;
+        self
+    }
+
+    /// Stop after this many rounds, or never if 0 (helium and mocknet only)
+    pub fn num_rounds(mut self, num_rounds: u64) -> NodeBuilder {
+        self.num_rounds = num_rounds;
+        self
+    }
+
+    fn check_mode(&self) -> Result<(), String> {
+        let mode = self.config.burnchain.mode.as_str();
+        if HELIUM_MODES.contains(&mode) || NEON_MODES.contains(&mode) {
+            Ok(())
+        } else {
+            Err(format!("Burnchain mode '{}' not supported", mode))
+        }
+    }
+
+    fn is_helium(&self) -> bool {
+        HELIUM_MODES.contains(&self.config.burnchain.mode.as_str())
+    }
+
+    /// Without a `termination_switch`, the node runs until it has run its rounds
+    fn run_helium(self, termination_switch: Option<Arc<AtomicBool>>) -> Result<(), String> {
+        let mut run_loop = helium::RunLoop::new(self.config);
+        if let Some(switch) = termination_switch {
+            run_loop.set_termination_switch(switch);
+        }
+        run_loop
+            .start(self.num_rounds)
+            .map_err(|e| format!("Helium runloop exited: {}", e))
+    }
+
+    fn make_neon_run_loop(&self, termination_switch: Option<Arc<AtomicBool>>) -> neon::RunLoop {
+        let mut run_loop = neon::RunLoop::new(self.config.clone());
+        if let Some((path, profile)) = self.config_path.as_ref() {
+            run_loop.set_config_path(path, profile.as_deref());
+        }
+        if let Some(switch) = termination_switch {
+            run_loop.set_termination_switch(switch);
+        }
+        run_loop
+    }
+
+    /// Run the node on this thread until it exits.  A neon node shuts down on SIGTERM or SIGINT.
+    pub fn run(self) -> Result<(), String> {
+        self.check_mode()?;
+        if self.is_helium() {
+            return self.run_helium(None);
+        }
+        let mut run_loop = self.make_neon_run_loop(None);
+        run_loop.start(self.burnchain, self.mine_start);
+        Ok(())
+    }
+
+    /// Start the node on a thread of its own
+    pub fn spawn(self) -> Result<NodeHandle, String> {
+        self.check_mode()?;
+        let config = self.config.clone();
+        let termination_switch = Arc::new(AtomicBool::new(true));
+        let switch = termination_switch.clone();
+
+        let (coordinator, body): (_, Box<dyn FnOnce() -> Result<(), String> + Send>) =
+            if self.is_helium() {
+                (None, Box::new(move || self.run_helium(Some(switch))))
+            } else {
+                // made here, so the caller gets its coordinator channels before it starts
+                let mut run_loop = self.make_neon_run_loop(Some(switch));
+                let coordinator = run_loop.get_coordinator_channel();
+                let burnchain = self.burnchain;
+                let mine_start = self.mine_start;
+                (
+                    coordinator,
+                    Box::new(move || {
+                        run_loop.start(burnchain, mine_start);
+                        Ok(())
+                    }),
+                )
+            };
+
+        let exited = Arc::new(AtomicBool::new(false));
+        let exit_flag = ExitFlag(exited.clone());
+        let thread = thread::Builder::new()
+            .name("stacks-node".to_string())
+            .spawn(move || {
+                let _exit_flag = exit_flag;
+                body()
+            })
+            .map_err(|e| format!("Failed to start the node thread: {}", e))?;
+        Ok(NodeHandle {
+            config,
+            coordinator,
+            termination_switch,
+            exited,
+            thread: Some(thread),
+        })
+    }
+}
+
+/// Set once the node thread exits, even if it panics
+struct ExitFlag(Arc<AtomicBool>);
+
+impl Drop for ExitFlag {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+/// A node running on a thread of its own.  Dropping the handle stops the node, without waiting
+/// for it to finish.
+pub struct NodeHandle {
+    config: Config,
+    coordinator: Option<CoordinatorChannels>,
+    termination_switch: Arc<AtomicBool>,
+    exited: Arc<AtomicBool>,
+    thread: Option<JoinHandle<Result<(), String>>>,
+}
+
+impl NodeHandle {
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Base URL of the node's RPC interface
+    pub fn rpc_url(&self) -> String {
+        format!("http://{}", &self.config.node.rpc_bind)
+    }
+
+    /// The chains coordinator's channels, which count the sortitions and Stacks blocks it has
+    /// processed (neon only)
+    pub fn coordinator_channels(&self) -> Option<&CoordinatorChannels> {
+        self.coordinator.as_ref()
+    }
+
+    /// Whether the node is still running
+    pub fn is_running(&self) -> bool {
+        !self.exited.load(Ordering::SeqCst)
+    }
+
+    /// Wait until `done` returns true, checking every `interval` for up to `timeout`.  Returns
+    /// false if it timed out, or if the node exited first.
+    pub fn wait_for<F: FnMut() -> bool>(
+        &self,
+        mut done: F,
+        interval: Duration,
+        timeout: Duration,
+    ) -> bool {
+        let mut waited = Duration::from_secs(0);
+        loop {
+            if done() {
+                return true;
+            }
+            if waited >= timeout || !self.is_running() {
+                return false;
+            }
+            thread::sleep(interval);
+            waited += interval;
+        }
+    }
+
+    /// Wait for the node to exit on its own
+    pub fn join(mut self) -> Result<(), String> {
+        match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .map_err(|_| "The node thread panicked".to_string())?,
+            None => Ok(()),
+        }
+    }
+
+    /// Shut the node down cleanly, and wait for it to exit
+    pub fn stop(self) -> Result<(), String> {
+        self.termination_switch.store(false, Ordering::SeqCst);
+        self.join()
+    }
+}
+
+impl Drop for NodeHandle {
+    fn drop(&mut self) {
+        self.termination_switch.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::tests::new_test_conf;
+
+    #[test]
+    fn unsupported_modes() {
+        let mut conf = new_test_conf();
+        conf.burnchain.mode = "argon".to_string();
+        assert!(NodeBuilder::new(conf.clone()).spawn().is_err());
+        assert!(NodeBuilder::new(conf).run().is_err());
+    }
+
+    #[test]
+    fn mocknet_node_runs_and_stops() {
+        let mut conf = new_test_conf();
+        conf.burnchain.mode = "mocknet".to_string();
+
+        // a node with a fixed number of rounds exits on its own
+        let node = NodeBuilder::new(conf.clone())
+            .num_rounds(3)
+            .spawn()
+            .unwrap();
+        assert!(node.rpc_url().starts_with("http://127.0.0.1:"));
+        assert_eq!(node.join(), Ok(()));
+
+        // a node without one runs until it is stopped
+        conf.node.working_dir = format!("{}-unbounded", &conf.node.working_dir);
+        let node = NodeBuilder::new(conf).spawn().unwrap();
+        thread::sleep(Duration::from_millis(500));
+        assert!(node.is_running());
+        assert_eq!(node.stop(), Ok(()));
+    }
+}
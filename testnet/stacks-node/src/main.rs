@@ -20,17 +20,36 @@ use stacks::util::hash::hex_bytes;
 
 pub mod monitoring;
 
+pub mod bench;
+pub mod burn_ops;
+pub mod burn_view;
 pub mod burnchains;
+pub mod chainstate_migration;
 pub mod config;
+pub mod config_reload;
+pub mod diagnostics;
+pub mod disk_space;
 pub mod event_dispatcher;
 pub mod genesis_data;
+pub mod identity;
 pub mod keychain;
+pub mod keytool;
+pub mod launcher;
+pub mod metrics_state;
 pub mod neon_node;
 pub mod node;
 pub mod operations;
+pub mod peers;
+pub mod replica;
 pub mod run_loop;
+pub mod shutdown;
+pub mod stacker;
 pub mod syncctl;
+pub mod telemetry;
 pub mod tenure;
+pub mod tip_hooks;
+pub mod tip_notifier;
+pub mod watched_addresses;
 
 pub use self::burnchains::{
     BitcoinRegtestController, BurnchainController, BurnchainTip, MocknetController,
@@ -38,6 +57,7 @@ pub use self::burnchains::{
 pub use self::config::{Config, ConfigFile};
 pub use self::event_dispatcher::EventDispatcher;
 pub use self::keychain::Keychain;
+pub use self::launcher::{NodeBuilder, NodeHandle};
 pub use self::neon_node::{InitializedNeonNode, NeonGenesisNode};
 pub use self::node::{ChainTip, Node};
 pub use self::run_loop::{helium, neon};
@@ -47,6 +67,7 @@ use pico_args::Arguments;
 use std::env;
 
 use std::convert::TryInto;
+use std::fs;
 use std::panic;
 use std::process;
 
@@ -74,6 +95,7 @@ fn main() {
         // just in case
         process::exit(1);
     }));
+    config_reload::chain_panic_hook();
 
     let mut args = Arguments::from_env();
     let subcommand = args.subcommand().unwrap().unwrap_or_default();
@@ -91,6 +113,8 @@ fn main() {
         );
     }
 
+    let mut config_path = None;
+    let mut config_profile = None;
     let config_file = match subcommand.as_str() {
         "mocknet" => {
             args.finish().unwrap();
@@ -121,20 +145,49 @@ fn main() {
             ConfigFile::mainnet()
         }
         "start" => {
-            let config_path: String = args.value_from_str("--config").unwrap();
+            let path: String = args.value_from_str("--config").unwrap();
+            let profile: Option<String> = args.opt_value_from_str("--profile").unwrap();
             args.finish().unwrap();
-            info!("Loading config at path {}", config_path);
-            ConfigFile::from_path(&config_path)
+            match profile {
+                Some(ref profile) => info!("Loading config at path {} (profile {})", path, profile),
+                None => info!("Loading config at path {}", path),
+            }
+            let config_file = match ConfigFile::from_path_with_profile(&path, profile.as_deref()) {
+                Ok(config_file) => config_file,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                }
+            };
+            config_path = Some(path);
+            config_profile = profile;
+            config_file
         }
         "version" => {
             println!("{}", &version());
             return;
         }
+        "check-config" => {
+            let profile: Option<String> = args.opt_value_from_str("--profile").unwrap();
+            let free_args = args.free().unwrap();
+            let config_path = free_args
+                .first()
+                .expect("`check-config` must be passed the path of a config file");
+            process::exit(if check_config(config_path, profile.as_deref()) {
+                0
+            } else {
+                1
+            });
+        }
         "key-for-seed" => {
             let seed = {
                 let config_path: Option<String> = args.opt_value_from_str("--config").unwrap();
+                let profile: Option<String> = args.opt_value_from_str("--profile").unwrap();
                 if let Some(config_path) = config_path {
-                    let conf = Config::from_config_file(ConfigFile::from_path(&config_path));
+                    let conf = Config::from_config_file(
+                        ConfigFile::from_path_with_profile(&config_path, profile.as_deref())
+                            .unwrap_or_else(|e| panic!("{}", e)),
+                    );
                     args.finish().unwrap();
                     conf.node.seed
                 } else {
@@ -156,6 +209,62 @@ fn main() {
             );
             return;
         }
+        "keychain" => {
+            if let Err(e) = keytool::run_keychain_command(args) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+            return;
+        }
+        "bench" => {
+            if let Err(e) = bench::run_bench_command(args) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+            return;
+        }
+        "peers" => {
+            if let Err(e) = peers::run_peers_command(args) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+            return;
+        }
+        "burn-view" => {
+            if let Err(e) = burn_view::run_burn_view_command(args) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+            return;
+        }
+        "diagnostics" => {
+            if let Err(e) = diagnostics::run_diagnostics_command(args) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+            return;
+        }
+        "identity" => {
+            if let Err(e) = identity::run_identity_command(args) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+            return;
+        }
+        "burn-op" => {
+            if let Err(e) = burn_ops::run_burn_op_command(args) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+            return;
+        }
+        "replica" => {
+            if let Err(e) = replica::run_replica_command(args) {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+            return;
+        }
         _ => {
             print_help();
             return;
@@ -168,24 +277,74 @@ fn main() {
     debug!("connection configuration {:?}", &conf.connection_options);
     debug!("block_limit {:?}", &conf.block_limit);
 
-    let num_round: u64 = 0; // Infinite number of rounds
+    if conf.node.log_level.is_some() {
+        util::log::set_loglevel(conf.node.log_level);
+    }
+    if let Some(log_json) = conf.node.log_json {
+        if let Err(e) = util::log::set_log_json(log_json) {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+
+    let mut node = NodeBuilder::new(conf).mine_start(mine_start.unwrap_or(0));
+    if let Some(config_path) = config_path {
+        node = node.config_path(&config_path, config_profile.as_deref());
+    }
+    if let Err(e) = node.run() {
+        warn!("{}", e);
+    }
+}
 
-    if conf.burnchain.mode == "helium" || conf.burnchain.mode == "mocknet" {
-        let mut run_loop = helium::RunLoop::new(conf);
-        if let Err(e) = run_loop.start(num_round) {
-            warn!("Helium runloop exited: {}", e);
-            return;
+/// Load the config at `config_path` the same way `start` would, and report everything wrong with
+/// it.  Returns true if the node would start with it.
+fn check_config(config_path: &str, profile: Option<&str>) -> bool {
+    let content = match fs::read_to_string(config_path) {
+        Ok(content) => content,
+        Err(e) => {
+            println!("ERROR: Failed to read {}: {}", config_path, &e);
+            return false;
         }
-    } else if conf.burnchain.mode == "neon"
-        || conf.burnchain.mode == "xenon"
-        || conf.burnchain.mode == "krypton"
-        || conf.burnchain.mode == "mainnet"
-    {
-        let mut run_loop = neon::RunLoop::new(conf);
-        run_loop.start(None, mine_start.unwrap_or(0));
-    } else {
-        println!("Burnchain mode '{}' not supported", conf.burnchain.mode);
+    };
+    let content = match ConfigFile::select_profile(&content, profile) {
+        Ok(content) => content,
+        Err(e) => {
+            println!("ERROR: Failed to load {}: {}", config_path, &e);
+            return false;
+        }
+    };
+
+    match ConfigFile::unknown_keys(&content) {
+        Ok(unknown_keys) => {
+            for key in unknown_keys.iter() {
+                println!("WARN: Unknown setting `{}` will be ignored", key);
+            }
+        }
+        Err(e) => {
+            println!("ERROR: Failed to parse {}: {}", config_path, &e);
+            return false;
+        }
+    }
+
+    let (config_file, config) = match config_reload::load_config_str(&content) {
+        Ok(loaded) => loaded,
+        Err(msg) => {
+            println!("ERROR: {}", msg);
+            return false;
+        }
+    };
+
+    let (errors, warnings) = config.check_consistency(&config_file);
+    for warning in warnings.iter() {
+        println!("WARN: {}", warning);
+    }
+    for error in errors.iter() {
+        println!("ERROR: {}", error);
     }
+
+    println!("Effective configuration (secrets omitted):");
+    println!("{:#?}", &config.redacted());
+    errors.is_empty()
 }
 
 fn version() -> String {
@@ -228,8 +387,21 @@ xenon\t\tStart a node that will join and stream blocks from the public xenon tes
 start\t\tStart a node with a config of your own. Can be used for joining a network, starting new chain, etc.
 \t\tArguments:
 \t\t  --config: path of the config (such as https://github.com/blockstack/stacks-blockchain/blob/master/testnet/stacks-node/conf/testnet-follower-conf.toml).
+\t\t  --profile: (optional) the profile of the config to run with. A config's top-level settings are shared by
+\t\t    all of its profiles, and the settings under `[profile.<name>]` (e.g. `[profile.testnet.burnchain]`)
+\t\t    override them for profile <name>.
+\t\tString values in the config can refer to environment variables as `${{VAR}}`, or as `${{VAR:-default}}`
+\t\tto fall back to `default` when VAR is unset or empty.
+\t\tExample:
+\t\t  stacks-node start --config=/path/to/config.toml --profile=testnet
+\t\tSend the node a SIGHUP to reload `node.log_level`, `node.deny_nodes`, the RPC limits in
+\t\t`[connection_options]`, and `[[events_observer]]` from the config, and the identity key from its file,
+\t\twithout restarting it.
+
+check-config\tValidate a config file, and print the configuration the node would run with,
+\t\tincluding defaults. Pass `--profile=<name>` to check one of the config's profiles.
 \t\tExample:
-\t\t  stacks-node start --config=/path/to/config.toml
+\t\t  stacks-node check-config --profile=testnet /path/to/config.toml
 
 version\t\tDisplay information about the current version and our release cycle.
 
@@ -237,6 +409,82 @@ key-for-seed\tOutput the associated secret key for a burnchain signer created wi
 \t\tCan be passed a config file for the seed via the `--config=<file>` option *or* by supplying the hex seed on
 \t\tthe command line directly.
 
+keychain\tGenerate, derive, and inspect the keys used for mining and stacking.
+\t\tSubcommands:
+\t\t  generate [--words=<12|15|18|21|24>] [--passphrase=<passphrase>]: make a new BIP39 mnemonic, and
+\t\t    print its first Stacks and Bitcoin keys.
+\t\t  derive --mnemonic=\"<words>\" [--passphrase=<passphrase>] [--index=<n>] [--path=<path>]: print
+\t\t    the keys at the Stacks and Bitcoin wallet paths m/44'/5757'/0'/0/<n> and m/44'/0'/0'/0/<n>,
+\t\t    or just the key at <path>.
+\t\t  inspect <hex or WIF private key>: print the Stacks and Bitcoin addresses of a key.
+\t\tPass `--testnet` for testnet keys and addresses (and the Bitcoin path m/44'/1'/0'/0/<n>).
+\t\tThe hex private key can be used as the `seed` for mining or stacking.
+
+bench\t\tRun reproducible benchmarks of this binary, for sizing hardware and spotting regressions.
+\t\tArguments:
+\t\t  [marf|clarity|block-assembly|all]: which benchmarks to run (default: all).
+\t\t  --scale=<n>: multiply the amount of work each benchmark does (default: 1).
+\t\t  --working-dir=<path>: where to put the scratch databases (default: a temporary directory).
+\t\tExample:
+\t\t  stacks-node bench clarity --scale=10
+
+peers\t\tExport or import lists of peers, to help a new node find the network when the bootstrap nodes are busy.
+\t\tPeers are written one per line as PUBKEY@IP:PORT, the same format as `bootstrap_node`.
+\t\tSubcommands:
+\t\t  export --config=<file> [--output=<file>] [--max-age=<secs>] [--all]: print the peers in this node's peer DB
+\t\t    that it has heard from in the last <secs> seconds (default: 86400), or all of them with --all.
+\t\t  import --config=<file> <file or URL>: add the peers in a list to this node's peer DB, creating the DB
+\t\t    if the node has not run yet.
+\t\tBoth take `--profile=<name>` to select a profile of the config, as `start` does.
+\t\tExample:
+\t\t  stacks-node peers import --config=/path/to/config.toml https://example.com/peers.txt
+
+identity\tManage the private key this node identifies itself to its peers with, kept in `node.identity_key_path`
+\t\t(default: identity.key next to the peer DB), readable only by its owner.
+\t\tSubcommands:
+\t\t  export --config=<file> [--output=<file>]: print the key, or write it to <file>.
+\t\t  import --config=<file> <file>: replace the key with the one in <file>.
+\t\t  rotate --config=<file>: replace the key with a new random one.
+\t\tAll take `--profile=<name>` to select a profile of the config, as `start` does. Send a running node
+\t\tSIGHUP to switch to an imported or rotated key; it re-handshakes with its peers so they learn the new key.
+
+burn-op\t\tSubmit a burnchain operation through this node's bitcoind, funded from the sender's burnchain address.
+\t\tSubcommands:
+\t\t  transfer-stx --config=<file> --key-file=<file> --recipient=<address> --amount=<ustx>
+\t\t    [--memo=<hex>] [--fee-rate=<sats/byte>]: send STX with a pre-STX and a transfer-STX transaction,
+\t\t    signed with the hex private key in <file> (readable only by its owner).
+\t\tTakes `--profile=<name>` to select a profile of the config, as `start` does.
+\t\tExample:
+\t\t  stacks-node burn-op transfer-stx --config=/path/to/config.toml --key-file=sender.key \\
+\t\t    --recipient=SP2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKNRV9EJ7 --amount=1000000
+
+replica\t\tServe the RPC interface read-only from the databases of a node running on this host, without
+\t\ttalking to peers.  Endpoints that store new data answer 503.  Requires --config=<file>, the primary's
+\t\tconfig, and --rpc-bind=<addr:port>, which must differ from the primary's. Takes `--profile=<name>`.
+\t\tExample:
+\t\t  stacks-node replica --config=/path/to/config.toml --rpc-bind=0.0.0.0:20445
+
+burn-view\tCompare this node's sortitions at recent burn heights to the ones other nodes report, to tell
+\t\twhether this node is forked (every peer disagrees) or the network is (only some peers do).
+\t\tExits with an error unless every peer that answered agrees.
+\t\tArguments:
+\t\t  --config=<file>: the node's config.  Takes `--profile=<name>`, as `start` does.
+\t\t  --peers=<url>[,<url>...]: RPC URLs of the nodes to compare to (default: `node.burn_view_peers`).
+\t\t  --depth=<n>: how many burn heights to compare, back from this node's burnchain tip (default: 10).
+\t\tExample:
+\t\t  stacks-node burn-view --config=/path/to/config.toml --peers=http://node.example.com:20443
+
+diagnostics\tBundle what a bug report needs into one tar archive: the node's version, database schema versions,
+\t\tburnchain and Stacks tips, a summary of its peer DB, its saved and current metrics, its config with
+\t\tsecrets redacted, and optionally the end of its log.  Databases are opened read-only, so this can be run
+\t\twhile the node runs or after it has crashed.
+\t\tArguments:
+\t\t  --config=<file>: the node's config.  Takes `--profile=<name>`, as `start` does.
+\t\t  --log=<file>: the node's log file, of which the last 8 MiB are included.
+\t\t  --output=<file>: where to write the archive (default: stacks-node-diagnostics-<timestamp>.tar).
+\t\tExample:
+\t\t  stacks-node diagnostics --config=/path/to/config.toml --log=/var/log/stacks-node.log
+
 help\t\tDisplay this help.
 
 OPTIONAL ARGUMENTS:
@@ -0,0 +1,183 @@
+//! Persisted metrics.  Prometheus counters like blocks processed and transactions received start
+//! from zero each time the node starts, which breaks dashboards that chart totals over months.
+//! With `node.persist_metrics` set, the node saves the totals of those counters to
+//! `metrics_state.json` in its working directory every minute and when it shuts down, and adds
+//! them back in when it starts, so the totals it exports keep growing across restarts.
+//!
+//! Only the counters `stacks::monitoring::get_persisted_counter_totals()` lists are saved; gauges
+//! and histograms still start over.  If the node crashes, whatever it counted since the last save
+//! is lost.  Delete the file to start the totals over.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use stacks::monitoring;
+
+use crate::Config;
+
+/// Where in the working directory the totals are saved
+pub const METRICS_STATE_FILE: &str = "metrics_state.json";
+
+/// How often to save the totals while the node runs
+const METRICS_SAVE_INTERVAL_SECS: u64 = 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SavedCounters {
+    counters: BTreeMap<String, u64>,
+}
+
+#[derive(Clone)]
+pub struct MetricsState {
+    path: PathBuf,
+}
+
+impl MetricsState {
+    /// Returns None unless the node is configured to persist its metrics.  Otherwise, adds the
+    /// totals the last run saved back into the counters.
+    pub fn from_config(config: &Config) -> Option<MetricsState> {
+        if !config.node.persist_metrics {
+            return None;
+        }
+        if monitoring::get_persisted_counter_totals().is_empty() {
+            warn!("`node.persist_metrics` is set, but this node was built without the `monitoring_prom` feature");
+            return None;
+        }
+        let state = MetricsState {
+            path: MetricsState::path(config),
+        };
+        match state.load() {
+            Ok(totals) => {
+                if !totals.is_empty() {
+                    info!("Restoring metric totals from {}", state.path.display());
+                }
+                monitoring::restore_persisted_counter_totals(&totals);
+            }
+            Err(e) => warn!("Metric totals start over: {}", &e),
+        }
+        Some(state)
+    }
+
+    /// Where the given node saves its totals
+    pub fn path(config: &Config) -> PathBuf {
+        PathBuf::from(&config.node.working_dir)
+            .join(&config.burnchain.mode)
+            .join(METRICS_STATE_FILE)
+    }
+
+    /// The totals the last run saved, or none if it saved none
+    fn load(&self) -> Result<Vec<(String, u64)>, String> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(format!("Failed to read {}: {}", self.path.display(), &e)),
+        };
+        let saved: SavedCounters = serde_json::from_str(&contents)
+            .map_err(|e| format!("Failed to parse {}: {}", self.path.display(), &e))?;
+        Ok(saved.counters.into_iter().collect())
+    }
+
+    /// Save the counters' current totals
+    pub fn save(&self) -> Result<(), String> {
+        self.save_totals(monitoring::get_persisted_counter_totals())
+    }
+
+    /// The totals are written to a file of their own, then renamed over the last save, so a
+    /// crash in the middle of a write leaves the last save intact.
+    fn save_totals(&self, totals: Vec<(String, u64)>) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {}", dir.display(), &e))?;
+        }
+        let saved = SavedCounters {
+            counters: totals.into_iter().collect(),
+        };
+        let bytes = serde_json::to_vec(&saved).map_err(|e| e.to_string())?;
+        let tmp_path = self.path.with_extension("json.tmp");
+        fs::write(&tmp_path, &bytes)
+            .map_err(|e| format!("Failed to write {}: {}", tmp_path.display(), &e))?;
+        fs::rename(&tmp_path, &self.path)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), &e))
+    }
+
+    /// Start a thread that saves the totals every minute until `should_keep_running` is cleared.
+    /// The final save, once the node has stopped counting, is up to the caller.
+    pub fn start_saving(&self, should_keep_running: Arc<AtomicBool>) {
+        let state = self.clone();
+        let spawned = thread::Builder::new()
+            .name("metrics-state".to_string())
+            .spawn(move || {
+                let interval = Duration::from_secs(METRICS_SAVE_INTERVAL_SECS);
+                let mut last_save = Instant::now();
+                while should_keep_running.load(Ordering::SeqCst) {
+                    thread::sleep(Duration::from_secs(1));
+                    if last_save.elapsed() >= interval {
+                        if let Err(e) = state.save() {
+                            warn!("Failed to save metric totals: {}", &e);
+                        }
+                        last_save = Instant::now();
+                    }
+                }
+            });
+        if let Err(e) = spawned {
+            error!("Failed to start the metrics state thread: {}", &e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::process;
+
+    #[test]
+    fn saved_totals_roundtrip() {
+        let dir = format!("/tmp/stacks-node-tests/metrics-state-{}", process::id());
+        let _ = fs::remove_dir_all(&dir);
+        let state = MetricsState {
+            path: PathBuf::from(&dir).join(METRICS_STATE_FILE),
+        };
+
+        // nothing saved yet
+        assert_eq!(state.load(), Ok(vec![]));
+
+        let totals = vec![
+            ("stacks_node_stx_blocks_processed_total".to_string(), 123),
+            ("stacks_node_transactions_received_total".to_string(), 4567),
+        ];
+        state.save_totals(totals.clone()).unwrap();
+        assert_eq!(state.load(), Ok(totals));
+
+        // a later save replaces the earlier one
+        state
+            .save_totals(vec![(
+                "stacks_node_stx_blocks_processed_total".to_string(),
+                124,
+            )])
+            .unwrap();
+        assert_eq!(
+            state.load(),
+            Ok(vec![(
+                "stacks_node_stx_blocks_processed_total".to_string(),
+                124
+            )])
+        );
+        assert!(!state.path.with_extension("json.tmp").exists());
+
+        fs::write(&state.path, "not json").unwrap();
+        assert!(state.load().is_err());
+    }
+
+    #[test]
+    fn persistence_is_opt_in() {
+        let config = Config::default();
+        assert!(!config.node.persist_metrics);
+        assert!(MetricsState::from_config(&config).is_none());
+    }
+}
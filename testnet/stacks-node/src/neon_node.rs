@@ -38,10 +38,12 @@ use stacks::core::mempool::MemPoolDB;
 use stacks::core::FIRST_BURNCHAIN_CONSENSUS_HASH;
 use stacks::monitoring::{increment_stx_blocks_mined_counter, update_active_miners_count_gauge};
 use stacks::net::{
+    admin::{BurnOpSubmitter, MinerWalletReader, TransferStxOpRequest},
     atlas::{AtlasConfig, AtlasDB, AttachmentInstance},
     db::{LocalPeer, PeerDB},
     dns::DNSResolver,
     p2p::PeerNetwork,
+    readonly_pool::ReadOnlyCallPool,
     relay::Relayer,
     rpc::RPCHandlerArgs,
     Error as NetError, NetworkResult, PeerAddress,
@@ -58,9 +60,15 @@ use stacks::util::strings::{UrlString, VecDisplay};
 use stacks::util::vrf::VRFPublicKey;
 use stacks::vm::costs::ExecutionCost;
 
+use crate::burn_ops::{self, MinerWalletCache, RelayerBurnOpSubmitter};
 use crate::burnchains::bitcoin_regtest_controller::BitcoinRegtestController;
+use crate::config_reload::{ReloadHandle, ReloadableSettings};
+use crate::disk_space::DiskSpaceMonitor;
+use crate::identity;
+use crate::node::{get_genesis_unlock_heights, use_test_genesis_chainstate};
 use crate::run_loop::RegisteredKey;
 use crate::syncctl::PoxSyncWatchdogComms;
+use crate::telemetry::TelemetryReporter;
 use crate::ChainTip;
 
 use super::{BurnchainController, BurnchainTip, Config, EventDispatcher, Keychain};
@@ -85,12 +93,13 @@ struct MicroblockMinerState {
     cost_so_far: ExecutionCost,
 }
 
-enum RelayerDirective {
+pub enum RelayerDirective {
     HandleNetResult(NetworkResult),
     ProcessTenure(ConsensusHash, BurnchainHeaderHash, BlockHeaderHash),
     RunTenure(RegisteredKey, BlockSnapshot, u128), // (vrf key, chain tip, time of issuance in ms)
     RegisterKey(BlockSnapshot),
-    RunMicroblockTenure(u128), // time of issuance in ms
+    RunMicroblockTenure(u128),               // time of issuance in ms
+    SubmitTransferStx(TransferStxOpRequest), // sent from the node's burnchain signer
     Exit,
 }
 
@@ -579,6 +588,37 @@ fn recv_unconfirmed_txs(
     }
 }
 
+/// Start up the read-only call pool, if the node is configured to evaluate read-only calls off of
+/// the p2p thread.
+fn make_readonly_call_pool(
+    is_mainnet: bool,
+    config: &Config,
+    stacks_chainstate_path: &str,
+    burn_db_path: &str,
+) -> Option<ReadOnlyCallPool> {
+    let num_threads = config.connection_options.read_only_call_threads;
+    if num_threads == 0 {
+        return None;
+    }
+    match ReadOnlyCallPool::new(
+        is_mainnet,
+        config.burnchain.chain_id,
+        stacks_chainstate_path,
+        burn_db_path,
+        config.connection_options.read_only_call_limit.clone(),
+        num_threads,
+    ) {
+        Ok(pool) => Some(pool),
+        Err(e) => {
+            warn!(
+                "Failed to start read-only call pool; evaluating read-only calls on the p2p thread: {:?}",
+                &e
+            );
+            None
+        }
+    }
+}
+
 fn spawn_peer(
     is_mainnet: bool,
     mut this: PeerNetwork,
@@ -592,11 +632,14 @@ fn spawn_peer(
     unconfirmed_txs: Arc<Mutex<UnconfirmedTxMap>>,
     event_observer: EventDispatcher,
     should_keep_running: Arc<AtomicBool>,
+    reload_handle: Option<ReloadHandle>,
+    miner_wallet: Option<MinerWalletCache>,
 ) -> Result<JoinHandle<()>, NetError> {
     let burn_db_path = config.get_burn_db_file_path();
     let stacks_chainstate_path = config.get_chainstate_path_str();
     let block_limit = config.block_limit.clone();
     let exit_at_block_height = config.burnchain.process_exit_at_block_height;
+    let runtime_toggles = this.runtime_toggles.clone();
 
     this.bind(p2p_sock, rpc_sock).unwrap();
     let (mut dns_resolver, mut dns_client) = DNSResolver::new(10);
@@ -616,6 +659,7 @@ fn spawn_peer(
         &stacks_chainstate_path,
     )
     .map_err(NetError::DBError)?;
+    mem_pool.set_contract_deploy_limits(config.make_contract_deploy_limits());
 
     // buffer up blocks to store without stalling the p2p thread
     let mut results_with_data = VecDeque::new();
@@ -623,11 +667,31 @@ fn spawn_peer(
     let server_thread = thread::Builder::new()
         .name("p2p".to_string())
         .spawn(move || {
+            let readonly_call_pool = make_readonly_call_pool(
+                is_mainnet,
+                &config,
+                &stacks_chainstate_path,
+                &burn_db_path,
+            );
+            let burn_op_submitter =
+                RelayerBurnOpSubmitter::from_config(&config, relay_channel.clone());
+            let genesis_unlock_heights =
+                get_genesis_unlock_heights(use_test_genesis_chainstate(&config));
             let handler_args = RPCHandlerArgs {
                 exit_at_block_height: exit_at_block_height.as_ref(),
                 genesis_chainstate_hash: Sha256Sum::from_hex(stx_genesis::GENESIS_CHAINSTATE_HASH)
                     .unwrap(),
                 event_observer: Some(&event_observer),
+                readonly_call_pool: readonly_call_pool.as_ref(),
+                admin_auth_token: config.node.admin_auth_token.as_deref(),
+                runtime_toggles: Some(&runtime_toggles),
+                burn_op_submitter: burn_op_submitter
+                    .as_ref()
+                    .map(|submitter| submitter as &dyn BurnOpSubmitter),
+                miner_wallet_reader: miner_wallet
+                    .as_ref()
+                    .map(|wallet| wallet as &dyn MinerWalletReader),
+                genesis_unlock_heights: Some(&genesis_unlock_heights),
                 ..RPCHandlerArgs::default()
             };
 
@@ -636,9 +700,46 @@ fn spawn_peer(
             let mut num_download_passes = 0;
             let mut mblock_deadline = 0;
 
+            let mut reload_generation = 0;
+            let mut applied_settings = ReloadableSettings::from_config(&config);
+            let mut disk_space_monitor = DiskSpaceMonitor::from_config(&config);
+            let mut telemetry_reporter = TelemetryReporter::from_config(&config);
+            let identity_key_path = config.get_identity_key_path();
+            let mut persisted_identity_key = this.local_peer.private_key.clone();
+
             while should_keep_running.load(Ordering::SeqCst) {
+                if let Some(reload_handle) = reload_handle.as_ref() {
+                    if let Some(settings) = reload_handle.poll(&mut reload_generation) {
+                        settings.apply_to_peer_network(&applied_settings, &mut this);
+                        applied_settings = settings;
+                    }
+                }
+
+                if let Some(monitor) = disk_space_monitor.as_mut() {
+                    monitor.check(&runtime_toggles, &should_keep_running);
+                }
+
                 // initial block download?
                 let ibd = sync_comms.get_ibd();
+
+                if let Some(reporter) = telemetry_reporter.as_mut() {
+                    let now = get_epoch_time_secs();
+                    if reporter.is_due(now) {
+                        let stacks_tip_height =
+                            SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())
+                                .map(|sn| sn.canonical_stacks_tip_height)
+                                .unwrap_or(0);
+                        let report = reporter.make_report(
+                            now,
+                            this.chain_view.burn_block_height,
+                            stacks_tip_height,
+                            ibd,
+                            this.peers.len() as u64,
+                        );
+                        reporter.report(report);
+                    }
+                }
+
                 let download_backpressure = results_with_data.len() > 0;
                 let poll_ms = if !download_backpressure && this.has_more_downloads() {
                     // keep getting those blocks -- drive the downloader state-machine
@@ -660,7 +761,13 @@ fn spawn_peer(
                     }
                 };
 
-                let _ = Relayer::setup_unconfirmed_state_readonly(&mut chainstate, &sortdb);
+                if config
+                    .connection_options
+                    .microblock_acceptance
+                    .builds_unconfirmed_state()
+                {
+                    let _ = Relayer::setup_unconfirmed_state_readonly(&mut chainstate, &sortdb);
+                }
                 recv_unconfirmed_txs(&mut chainstate, unconfirmed_txs.clone());
 
                 match this.run(
@@ -716,6 +823,11 @@ fn spawn_peer(
                         }
                     }
                 };
+                identity::persist_rekeyed_identity_key(
+                    &identity_key_path,
+                    &this.local_peer.private_key,
+                    &mut persisted_identity_key,
+                );
 
                 while let Some(next_result) = results_with_data.pop_front() {
                     // have blocks, microblocks, and/or transactions (don't care about anything else),
@@ -747,7 +859,17 @@ fn spawn_peer(
                 }
             }
 
-            relay_channel.try_send(RelayerDirective::Exit).unwrap();
+            // close our sockets, and hand the relayer everything we downloaded before telling it
+            // to exit (it finishes whatever it is doing first)
+            this.shutdown();
+            for directive in results_with_data.into_iter() {
+                if let RelayerDirective::HandleNetResult(_) = directive {
+                    if relay_channel.send(directive).is_err() {
+                        break;
+                    }
+                }
+            }
+            let _ = relay_channel.send(RelayerDirective::Exit);
             debug!("P2P thread exit!");
         })
         .unwrap();
@@ -778,6 +900,8 @@ fn spawn_miner_relayer(
     burnchain: Burnchain,
     coord_comms: CoordinatorChannels,
     unconfirmed_txs: Arc<Mutex<UnconfirmedTxMap>>,
+    should_keep_running: Arc<AtomicBool>,
+    miner_wallet: Option<MinerWalletCache>,
 ) -> Result<JoinHandle<()>, NetError> {
     // Note: the chainstate coordinator is *the* block processor, it is responsible for writes to
     // the chainstate -- eventually, no other codepaths should be writing to it.
@@ -797,6 +921,7 @@ fn spawn_miner_relayer(
 
     let mut mem_pool = MemPoolDB::open(is_mainnet, chain_id, &stacks_chainstate_path)
         .map_err(NetError::DBError)?;
+    mem_pool.set_contract_deploy_limits(config.make_contract_deploy_limits());
 
     let mut last_mined_blocks: HashMap<
         BurnchainHeaderHash,
@@ -812,7 +937,13 @@ fn spawn_miner_relayer(
     let mut last_microblock_tenure_time = 0;
     let mut last_tenure_issue_time = 0;
 
+    let mut last_microblock_equivocation_id =
+        StacksChainState::get_last_microblock_equivocation_id(chainstate.db()).unwrap_or(0);
+
     let relayer_handle = thread::Builder::new().name("relayer".to_string()).spawn(move || {
+        if let Some(miner_wallet) = miner_wallet.as_ref() {
+            miner_wallet.refresh();
+        }
         while let Ok(mut directive) = relay_channel.recv() {
             match directive {
                 RelayerDirective::HandleNetResult(ref mut net_result) => {
@@ -844,6 +975,20 @@ fn spawn_miner_relayer(
                         }
                     }
 
+                    // Dispatch microblock equivocations detected since the last network result,
+                    // whether the microblocks came in over p2p or the RPC interface.
+                    match StacksChainState::get_microblock_equivocations_after(chainstate.db(), last_microblock_equivocation_id, u32::MAX) {
+                        Ok(equivocations) => {
+                            if let Some(last) = equivocations.last() {
+                                last_microblock_equivocation_id = last.id;
+                                event_dispatcher.process_microblock_equivocations(&equivocations);
+                            }
+                        }
+                        Err(e) => {
+                            warn!("Relayer: failed to load microblock equivocations: {:?}", &e);
+                        }
+                    }
+
                     // Dispatch retrieved attachments, if any.
                     if net_result.has_attachments() {
                         event_dispatcher.process_new_attachments(&net_result.attachments);
@@ -939,7 +1084,7 @@ fn spawn_miner_relayer(
                                     let bh = mined_block.block_hash();
 
                                     if let Err(e) = relayer
-                                        .broadcast_block(snapshot.consensus_hash, mined_block)
+                                        .push_mined_block(snapshot.consensus_hash, mined_block)
                                     {
                                         warn!("Failed to push new block: {}", e);
                                     }
@@ -967,6 +1112,16 @@ fn spawn_miner_relayer(
                     if last_tenure_issue_time > issue_timestamp_ms {
                         continue;
                     }
+                    if !should_keep_running.load(Ordering::SeqCst) {
+                        debug!("Relayer: shutting down, so not starting a tenure");
+                        continue;
+                    }
+                    if relayer.runtime_toggles().mining_paused()
+                        || relayer.runtime_toggles().low_disk_space()
+                    {
+                        debug!("Relayer: mining is paused or disk space is low, so not starting a tenure");
+                        continue;
+                    }
 
                     let burn_header_hash = last_burn_block.burn_header_hash.clone();
                     debug!(
@@ -1015,9 +1170,18 @@ fn spawn_miner_relayer(
                     }
                     last_mined_blocks.insert(burn_header_hash, last_mined_blocks_vec);
 
+                    // the tenure's block-commit, if any, spent from the wallet
+                    if let Some(miner_wallet) = miner_wallet.as_ref() {
+                        miner_wallet.refresh();
+                    }
+
                     last_tenure_issue_time = get_epoch_time_ms();
                 }
                 RelayerDirective::RegisterKey(ref last_burn_block) => {
+                    if !should_keep_running.load(Ordering::SeqCst) {
+                        debug!("Relayer: shutting down, so not registering a key");
+                        continue;
+                    }
                     rotate_vrf_and_register(
                         is_mainnet,
                         &mut keychain,
@@ -1031,6 +1195,15 @@ fn spawn_miner_relayer(
                         // stale request
                         continue;
                     }
+                    if !should_keep_running.load(Ordering::SeqCst) {
+                        continue;
+                    }
+                    if relayer.runtime_toggles().mining_paused()
+                        || relayer.runtime_toggles().low_disk_space()
+                    {
+                        debug!("Relayer: mining is paused or disk space is low, so not running a microblock tenure");
+                        continue;
+                    }
 
                     debug!("Relayer: run microblock tenure");
 
@@ -1069,6 +1242,23 @@ fn spawn_miner_relayer(
                         microblock_miner_state = None;
                     }
                 }
+                RelayerDirective::SubmitTransferStx(ref request) => {
+                    let signer = keychain.generate_op_signer();
+                    match burn_ops::submit_transfer_stx(&config, request, &signer) {
+                        Ok((pre_stx_txid, transfer_stx_txid)) => {
+                            info!(
+                                "Relayer: sent transfer-STX operation";
+                                "recipient" => %request.recipient,
+                                "amount_ustx" => request.amount_ustx,
+                                "pre_stx_txid" => %pre_stx_txid,
+                                "transfer_stx_txid" => %transfer_stx_txid
+                            );
+                        }
+                        Err(e) => {
+                            warn!("Relayer: failed to send transfer-STX operation: {}", &e);
+                        }
+                    }
+                }
                 RelayerDirective::Exit => break
             }
         }
@@ -1100,6 +1290,7 @@ impl InitializedNeonNode {
         attachments_rx: Receiver<HashSet<AttachmentInstance>>,
         atlas_config: AtlasConfig,
         should_keep_running: Arc<AtomicBool>,
+        reload_handle: Option<ReloadHandle>,
     ) -> InitializedNeonNode {
         // we can call _open_ here rather than _connect_, since connect is first called in
         //   make_genesis_block
@@ -1136,20 +1327,8 @@ impl InitializedNeonNode {
             "Failed to parse socket: {}",
             &config.node.p2p_address
         ));
-        let node_privkey = {
-            let mut re_hashed_seed = config.node.local_peer_seed.clone();
-            let my_private_key = loop {
-                match Secp256k1PrivateKey::from_slice(&re_hashed_seed[..]) {
-                    Ok(sk) => break sk,
-                    Err(_) => {
-                        re_hashed_seed = Sha256Sum::from_data(&re_hashed_seed[..])
-                            .as_bytes()
-                            .to_vec()
-                    }
-                }
-            };
-            my_private_key
-        };
+        let node_privkey = identity::initial_identity_key(&config)
+            .unwrap_or_else(|e| panic!("Failed to load identity key: {}", e));
 
         let mut peerdb = PeerDB::connect(
             &config.get_peer_db_file_path(),
@@ -1173,6 +1352,8 @@ impl InitializedNeonNode {
             panic!();
         })
         .unwrap();
+        identity::persist_identity_key(&config.get_identity_key_path(), &mut peerdb)
+            .unwrap_or_else(|e| panic!("Failed to persist identity key: {}", e));
 
         {
             // bootstrap nodes *always* allowed
@@ -1248,6 +1429,7 @@ impl InitializedNeonNode {
 
         let relayer = Relayer::from_p2p(&mut p2p_net);
         let shared_unconfirmed_txs = Arc::new(Mutex::new(UnconfirmedTxMap::new()));
+        let miner_wallet = MinerWalletCache::from_config(&config);
 
         let leader_key_registration_state = if config.node.mock_mining {
             // mock mining, pretend to have a registered key
@@ -1278,6 +1460,8 @@ impl InitializedNeonNode {
             burnchain,
             coord_comms,
             shared_unconfirmed_txs.clone(),
+            should_keep_running.clone(),
+            miner_wallet.clone(),
         )
         .expect("Failed to initialize mine/relay thread");
 
@@ -1294,6 +1478,8 @@ impl InitializedNeonNode {
             shared_unconfirmed_txs,
             event_dispatcher,
             should_keep_running,
+            reload_handle,
+            miner_wallet,
         )
         .expect("Failed to initialize p2p thread");
 
@@ -1304,7 +1490,7 @@ impl InitializedNeonNode {
 
         let is_miner = miner;
 
-        let atlas_config = AtlasConfig::default(config.is_mainnet());
+        let atlas_config = config.make_atlas_config();
         InitializedNeonNode {
             config,
             relay_channel: relay_send,
@@ -1677,14 +1863,16 @@ impl InitializedNeonNode {
             config.burnchain.chain_id,
         );
 
+        let parent_index_hash = StacksBlockHeader::make_index_block_hash(
+            &parent_consensus_hash,
+            &stacks_parent_header.anchored_header.block_hash(),
+        );
+
         // find the longest microblock tail we can build off of
         let microblock_info_opt =
             match StacksChainState::load_descendant_staging_microblock_stream_with_poison(
                 chain_state.db(),
-                &StacksBlockHeader::make_index_block_hash(
-                    &parent_consensus_hash,
-                    &stacks_parent_header.anchored_header.block_hash(),
-                ),
+                &parent_index_hash,
                 0,
                 u16::MAX,
             ) {
@@ -1709,7 +1897,7 @@ impl InitializedNeonNode {
                 }
             };
 
-        if let Some((ref microblocks, ref poison_opt)) = &microblock_info_opt {
+        if let Some((ref microblocks, _)) = &microblock_info_opt {
             if let Some(ref tail) = microblocks.last() {
                 debug!(
                     "Confirm microblock stream tailed at {} (seq {})",
@@ -1722,12 +1910,33 @@ impl InitializedNeonNode {
             // be too long; we'll try again if that happens).
             stacks_parent_header.microblock_tail =
                 microblocks.last().clone().map(|blk| blk.header.clone());
+        }
+
+        // report a fork in the parent's microblock stream, if we found one while loading it or
+        // detected one while storing its microblocks
+        if config.node.mine_poison_microblocks {
+            let poison_opt = match &microblock_info_opt {
+                Some((_, Some(poison_payload))) => Some(poison_payload.clone()),
+                _ => StacksChainState::get_microblock_equivocations_for_block(
+                    chain_state.db(),
+                    &parent_index_hash,
+                )
+                .unwrap_or_else(|e| {
+                    warn!(
+                        "Failed to load microblock equivocations off of {}: {:?}",
+                        &parent_index_hash, &e
+                    );
+                    vec![]
+                })
+                .first()
+                .map(|equivocation| equivocation.poison_payload()),
+            };
 
             if let Some(poison_payload) = poison_opt {
                 let poison_microblock_tx = inner_generate_poison_microblock_tx(
                     keychain,
                     coinbase_nonce + 1,
-                    poison_payload.clone(),
+                    poison_payload,
                     config.is_mainnet(),
                     config.burnchain.chain_id,
                 );
@@ -2038,6 +2247,7 @@ impl NeonGenesisNode {
         attachments_rx: Receiver<HashSet<AttachmentInstance>>,
         atlas_config: AtlasConfig,
         should_keep_running: Arc<AtomicBool>,
+        reload_handle: Option<ReloadHandle>,
     ) -> InitializedNeonNode {
         let config = self.config;
         let keychain = self.keychain;
@@ -2057,6 +2267,7 @@ impl NeonGenesisNode {
             attachments_rx,
             atlas_config,
             should_keep_running,
+            reload_handle,
         )
     }
 
@@ -2070,6 +2281,7 @@ impl NeonGenesisNode {
         attachments_rx: Receiver<HashSet<AttachmentInstance>>,
         atlas_config: AtlasConfig,
         should_keep_running: Arc<AtomicBool>,
+        reload_handle: Option<ReloadHandle>,
     ) -> InitializedNeonNode {
         let config = self.config;
         let keychain = self.keychain;
@@ -2089,6 +2301,7 @@ impl NeonGenesisNode {
             attachments_rx,
             atlas_config,
             should_keep_running,
+            reload_handle,
         )
     }
 }
@@ -1,3 +1,4 @@
+use std::collections::BTreeSet;
 use std::convert::TryFrom;
 use std::default::Default;
 use std::net::SocketAddr;
@@ -37,22 +38,20 @@ use stacks::types::chainstate::{
 use stacks::types::proof::TrieHash;
 use stacks::util::get_epoch_time_secs;
 use stacks::util::hash::Sha256Sum;
-use stacks::util::secp256k1::Secp256k1PrivateKey;
 use stacks::util::strings::UrlString;
 use stacks::util::vrf::VRFPublicKey;
 use stacks::{
-    burnchains::{Burnchain, Txid},
+    burnchains::Txid,
     chainstate::stacks::db::{
         ChainstateAccountBalance, ChainstateAccountLockup, ChainstateBNSName,
         ChainstateBNSNamespace,
     },
 };
 
+use crate::identity;
 use crate::{genesis_data::USE_TEST_GENESIS_CHAINSTATE, run_loop::RegisteredKey};
 
 use super::{BurnchainController, BurnchainTip, Config, EventDispatcher, Keychain, Tenure};
-use stacks::burnchains::bitcoin::BitcoinNetworkType;
-use stacks::burnchains::PoxConstants;
 
 #[derive(Debug, Clone)]
 pub struct ChainTip {
@@ -109,6 +108,13 @@ pub fn get_account_lockups(
     )
 }
 
+/// The Stacks block heights the genesis lockups unlock at, for reporting vesting schedules
+pub fn get_genesis_unlock_heights(use_test_chainstate_data: bool) -> BTreeSet<u64> {
+    get_account_lockups(use_test_chainstate_data)
+        .map(|lockup| lockup.block_height)
+        .collect()
+}
+
 pub fn get_account_balances(
     use_test_chainstate_data: bool,
 ) -> Box<dyn Iterator<Item = ChainstateAccountBalance>> {
@@ -166,6 +172,7 @@ fn spawn_peer(
     genesis_chainstate_hash: Sha256Sum,
     poll_timeout: u64,
     attachments_rx: Receiver<HashSet<AttachmentInstance>>,
+    identity_key_path: String,
 ) -> Result<JoinHandle<()>, NetError> {
     this.bind(p2p_sock, rpc_sock).unwrap();
     let server_thread = thread::spawn(move || {
@@ -174,6 +181,7 @@ fn spawn_peer(
             genesis_chainstate_hash: genesis_chainstate_hash,
             ..RPCHandlerArgs::default()
         };
+        let mut persisted_identity_key = this.local_peer.private_key.clone();
 
         loop {
             let sortdb = match SortitionDB::open(&burn_db_path, false) {
@@ -225,6 +233,11 @@ fn spawn_peer(
                     &mut expected_attachments,
                 )
                 .unwrap();
+            identity::persist_rekeyed_identity_key(
+                &identity_key_path,
+                &this.local_peer.private_key,
+                &mut persisted_identity_key,
+            );
             if net_result.has_transactions() {
                 event_dispatcher.process_new_mempool_txs(net_result.transactions())
             }
@@ -269,11 +282,7 @@ impl Node {
             .iter()
             .map(|e| (e.address.clone(), e.amount))
             .collect();
-        let pox_constants = match config.burnchain.get_bitcoin_network() {
-            (_, BitcoinNetworkType::Mainnet) => PoxConstants::mainnet_default(),
-            (_, BitcoinNetworkType::Testnet) => PoxConstants::testnet_default(),
-            (_, BitcoinNetworkType::Regtest) => PoxConstants::regtest_default(),
-        };
+        let pox_constants = config.burnchain.get_pox_constants();
 
         let mut boot_data = ChainStateBootData {
             initial_balances,
@@ -292,6 +301,7 @@ impl Node {
                 get_namespaces(use_test_genesis_data)
             })),
             get_bulk_initial_names: Some(Box::new(move || get_names(use_test_genesis_data))),
+            custom_costs_contract: config.get_custom_costs_contract(),
         };
 
         let chain_state_result = StacksChainState::open_and_exec(
@@ -412,7 +422,7 @@ impl Node {
         let sortdb = SortitionDB::open(&self.config.get_burn_db_file_path(), true)
             .expect("Error while instantiating burnchain db");
 
-        let burnchain = Burnchain::regtest(&self.config.get_burn_db_path());
+        let burnchain = self.config.get_burnchain();
 
         let view = {
             let sortition_tip = SortitionDB::get_canonical_burn_chain_tip(&sortdb.conn())
@@ -439,20 +449,8 @@ impl Node {
             "Failed to parse socket: {}",
             &self.config.node.p2p_address
         ));
-        let node_privkey = {
-            let mut re_hashed_seed = self.config.node.local_peer_seed.clone();
-            let my_private_key = loop {
-                match Secp256k1PrivateKey::from_slice(&re_hashed_seed[..]) {
-                    Ok(sk) => break sk,
-                    Err(_) => {
-                        re_hashed_seed = Sha256Sum::from_data(&re_hashed_seed[..])
-                            .as_bytes()
-                            .to_vec()
-                    }
-                }
-            };
-            my_private_key
-        };
+        let node_privkey = identity::initial_identity_key(&self.config)
+            .unwrap_or_else(|e| panic!("Failed to load identity key: {}", e));
 
         let mut peerdb = PeerDB::connect(
             &self.config.get_peer_db_file_path(),
@@ -468,6 +466,8 @@ impl Node {
             Some(&initial_neighbors),
         )
         .unwrap();
+        identity::persist_identity_key(&self.config.get_identity_key_path(), &mut peerdb)
+            .unwrap_or_else(|e| panic!("Failed to persist identity key: {}", e));
 
         println!("DENY NEIGHBORS {:?}", &self.config.node.deny_nodes);
         {
@@ -518,6 +518,7 @@ impl Node {
             Sha256Sum::from_hex(stx_genesis::GENESIS_CHAINSTATE_HASH).unwrap(),
             1000,
             attachments_rx,
+            self.config.get_identity_key_path(),
         )
         .unwrap();
 
@@ -936,7 +937,7 @@ impl Node {
             ),
         };
 
-        let burnchain = Burnchain::regtest(&self.config.get_burn_db_path());
+        let burnchain = self.config.get_burnchain();
         let commit_outs = if burnchain_tip.block_snapshot.block_height + 1
             < burnchain.pox_constants.sunset_end
             && !burnchain.is_in_prepare_phase(burnchain_tip.block_snapshot.block_height + 1)
@@ -1,6 +1,7 @@
 use stacks::util::secp256k1::{MessageSignature, Secp256k1PrivateKey, Secp256k1PublicKey};
 use stacks::{burnchains::PrivateKey, util::hash::hex_bytes};
 
+#[derive(Clone)]
 pub struct BurnchainOpSigner {
     secret_key: Secp256k1PrivateKey,
     is_one_off: bool,
@@ -0,0 +1,313 @@
+//! Peer list export and import (`stacks-node peers`).
+//!
+//! A peer list is a text file with one peer per line, written as `PUBKEY@IP:PORT` -- the same
+//! format as the `bootstrap_node` setting.  Blank lines and lines starting with `#` are ignored,
+//! and a line may hold several comma-separated peers.  `export` writes the peers in this node's
+//! peer DB that it has heard from recently (or all of them, with `--all`), and `import` seeds a (possibly new) node's peer DB
+//! from such a list, read from a file or fetched from an HTTP(S) URL.  This gives a new node more
+//! peers to walk from than just the configured bootstrap nodes.
+
+use std::convert::TryFrom;
+use std::fs;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::Path;
+
+use pico_args::Arguments;
+
+use stacks::burnchains::Burnchain;
+use stacks::net::db::PeerDB;
+use stacks::net::{Neighbor, PeerAddress};
+use stacks::util::get_epoch_time_secs;
+use stacks::util::strings::UrlString;
+
+use crate::config::NodeConfig;
+use crate::identity;
+use crate::{Config, ConfigFile};
+
+/// By default, only export peers we have talked to in the last day
+pub const DEFAULT_MAX_AGE_SECS: u64 = 24 * 3600;
+
+pub fn run_peers_command(mut args: Arguments) -> Result<(), String> {
+    let subcommand = args
+        .subcommand()
+        .map_err(|e| format!("{}", e))?
+        .unwrap_or_default();
+    let config_path: Option<String> = args
+        .opt_value_from_str("--config")
+        .map_err(|e| format!("{}", e))?;
+    let config_path = config_path.ok_or("`peers` must be passed a config file via `--config`")?;
+    let profile: Option<String> = args
+        .opt_value_from_str("--profile")
+        .map_err(|e| format!("{}", e))?;
+    let config = Config::from_config_file(ConfigFile::from_path_with_profile(
+        &config_path,
+        profile.as_deref(),
+    )?);
+
+    match subcommand.as_str() {
+        "export" => {
+            let output: Option<String> = args
+                .opt_value_from_str("--output")
+                .map_err(|e| format!("{}", e))?;
+            let max_age: Option<u64> = args
+                .opt_value_from_str("--max-age")
+                .map_err(|e| format!("{}", e))?;
+            let all = args.contains("--all");
+            args.finish().map_err(|e| format!("{}", e))?;
+
+            let peerdb = open_peer_db(&config)?;
+            let all_peers = PeerDB::get_all_peers(peerdb.conn())
+                .map_err(|e| format!("Failed to read peers: {:?}", &e))?;
+            let peers = if all {
+                all_peers
+                    .into_iter()
+                    .filter(|peer| peer.addr.network_id == config.burnchain.chain_id)
+                    .collect()
+            } else {
+                healthy_peers(
+                    all_peers,
+                    config.burnchain.chain_id,
+                    get_epoch_time_secs(),
+                    max_age.unwrap_or(DEFAULT_MAX_AGE_SECS),
+                )
+            };
+            let text = format_peer_list(&peers);
+            match output {
+                Some(path) => fs::write(&path, text.as_bytes())
+                    .map_err(|e| format!("Failed to write {}: {}", &path, &e))?,
+                None => std::io::stdout()
+                    .write_all(text.as_bytes())
+                    .map_err(|e| format!("Failed to write peer list: {}", &e))?,
+            }
+            eprintln!("Exported {} peer(s)", peers.len());
+            Ok(())
+        }
+        "import" => {
+            let free_args = args.free().map_err(|e| format!("{}", e))?;
+            let source = free_args
+                .first()
+                .ok_or("`peers import` must be passed a peer list file or URL")?;
+            let text = read_peer_list_source(source)?;
+            let peers = parse_peer_list(
+                &text,
+                config.burnchain.chain_id,
+                config.burnchain.peer_version,
+            )?;
+
+            let mut peerdb = open_peer_db(&config)?;
+            let mut tx = peerdb
+                .tx_begin()
+                .map_err(|e| format!("Failed to begin transaction: {:?}", &e))?;
+            let added = PeerDB::import_peers(&mut tx, &peers)
+                .map_err(|e| format!("Failed to insert peers: {:?}", &e))?;
+            tx.commit()
+                .map_err(|e| format!("Failed to commit peer DB: {:?}", &e))?;
+            println!(
+                "Imported {} of {} peer(s) into {}",
+                added,
+                peers.len(),
+                &config.get_peer_db_file_path()
+            );
+            Ok(())
+        }
+        _ => Err(format!(
+            "Unrecognized `peers` subcommand '{}': expected `export` or `import`",
+            &subcommand
+        )),
+    }
+}
+
+/// Open (creating if need be) the peer DB the same way the node does when it boots, so that a DB
+/// seeded here is the DB the node goes on to use.
+pub fn open_peer_db(config: &Config) -> Result<PeerDB, String> {
+    let (network_name, _) = config.burnchain.get_bitcoin_network();
+    let burnchain = Burnchain::new(
+        &config.get_burn_db_path(),
+        &config.burnchain.chain,
+        &network_name,
+    )
+    .map_err(|e| format!("Failed to instantiate burnchain: {:?}", &e))?;
+    let data_url = UrlString::try_from(format!("{}", &config.node.data_url))
+        .map_err(|e| format!("Invalid data_url '{}': {:?}", &config.node.data_url, &e))?;
+    let p2p_sock: SocketAddr = config
+        .node
+        .p2p_bind
+        .parse()
+        .map_err(|_| format!("Failed to parse socket: {}", &config.node.p2p_bind))?;
+    let p2p_addr: SocketAddr = config
+        .node
+        .p2p_address
+        .parse()
+        .map_err(|_| format!("Failed to parse socket: {}", &config.node.p2p_address))?;
+
+    let peer_db_path = config.get_peer_db_file_path();
+    if let Some(dir) = Path::new(&peer_db_path).parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, &e))?;
+    }
+
+    let mut peerdb = PeerDB::connect(
+        &peer_db_path,
+        true,
+        config.burnchain.chain_id,
+        burnchain.network_id,
+        Some(identity::initial_identity_key(config)?),
+        config.connection_options.private_key_lifetime.clone(),
+        PeerAddress::from_socketaddr(&p2p_addr),
+        p2p_sock.port(),
+        data_url,
+        &vec![],
+        Some(&config.node.bootstrap_node),
+    )
+    .map_err(|e| format!("Failed to open {}: {:?}", &peer_db_path, &e))?;
+    identity::persist_identity_key(&config.get_identity_key_path(), &mut peerdb)?;
+    Ok(peerdb)
+}
+
+/// Read a peer list from a local file, or fetch it from an http:// or https:// URL.
+fn read_peer_list_source(source: &str) -> Result<String, String> {
+    if source.starts_with("https://") || source.starts_with("http://") {
+        reqwest::blocking::get(source)
+            .and_then(|resp| resp.error_for_status())
+            .and_then(|resp| resp.text())
+            .map_err(|e| format!("Failed to fetch {}: {}", source, &e))
+    } else {
+        fs::read_to_string(source).map_err(|e| format!("Failed to read {}: {}", source, &e))
+    }
+}
+
+/// Select the peers worth handing to another node: those on our network that are not denied and
+/// that we have heard from in the last `max_age` seconds.  A negative deny deadline means the
+/// peer is denied forever.
+pub fn healthy_peers(peers: Vec<Neighbor>, chain_id: u32, now: u64, max_age: u64) -> Vec<Neighbor> {
+    peers
+        .into_iter()
+        .filter(|peer| {
+            peer.addr.network_id == chain_id
+                && peer.denied >= 0
+                && peer.denied <= (now as i64)
+                && peer.last_contact_time > 0
+                && peer.last_contact_time + max_age >= now
+        })
+        .collect()
+}
+
+/// Write out peers, one `PUBKEY@IP:PORT` per line.
+pub fn format_peer_list(peers: &[Neighbor]) -> String {
+    let mut text = format!(
+        "# {} peer(s), exported by stacks-node at {}\n",
+        peers.len(),
+        get_epoch_time_secs()
+    );
+    for peer in peers.iter() {
+        text.push_str(&format!(
+            "{}@{}\n",
+            peer.public_key.to_hex(),
+            peer.addr.addrbytes.to_socketaddr(peer.addr.port)
+        ));
+    }
+    text
+}
+
+/// Parse a peer list, as written by `format_peer_list` or as given in `bootstrap_node`.
+pub fn parse_peer_list(
+    text: &str,
+    chain_id: u32,
+    peer_version: u32,
+) -> Result<Vec<Neighbor>, String> {
+    let mut peers = vec![];
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("#") {
+            continue;
+        }
+        for entry in line.split(",") {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+            let peer = NodeConfig::parse_bootstrap_node(entry, chain_id, peer_version)
+                .map_err(|e| format!("Line {}: {}", i + 1, e))?;
+            peers.push(peer);
+        }
+    }
+    Ok(peers)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use stacks::core::{CHAIN_ID_TESTNET, PEER_VERSION_TESTNET};
+    use stacks::util::secp256k1::{Secp256k1PrivateKey, Secp256k1PublicKey};
+
+    fn make_peer(port: u16, last_contact_time: u64, denied: i64) -> Neighbor {
+        let pubk = Secp256k1PublicKey::from_private(&Secp256k1PrivateKey::new());
+        let mut peer = NodeConfig::parse_bootstrap_node(
+            &format!("{}@127.0.0.1:{}", pubk.to_hex(), port),
+            CHAIN_ID_TESTNET,
+            PEER_VERSION_TESTNET,
+        )
+        .unwrap();
+        peer.last_contact_time = last_contact_time;
+        peer.denied = denied;
+        peer
+    }
+
+    #[test]
+    fn peer_list_round_trip() {
+        let peers = vec![make_peer(20444, 1000, 0), make_peer(20445, 2000, 0)];
+        let text = format_peer_list(&peers);
+        let parsed = parse_peer_list(&text, CHAIN_ID_TESTNET, PEER_VERSION_TESTNET).unwrap();
+        assert_eq!(parsed.len(), 2);
+        for (peer, parsed) in peers.iter().zip(parsed.iter()) {
+            assert_eq!(peer.addr, parsed.addr);
+            assert_eq!(peer.public_key, parsed.public_key);
+        }
+    }
+
+    #[test]
+    fn parse_peer_list_formats() {
+        let pubk = Secp256k1PublicKey::from_private(&Secp256k1PrivateKey::new()).to_hex();
+        let text = format!(
+            "# comment\n\n{}@127.0.0.1:1, {}@127.0.0.1:2\n  {}@[::1]:3  \n",
+            &pubk, &pubk, &pubk
+        );
+        let parsed = parse_peer_list(&text, CHAIN_ID_TESTNET, PEER_VERSION_TESTNET).unwrap();
+        assert_eq!(
+            parsed.iter().map(|p| p.addr.port).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+
+        let err = parse_peer_list(
+            &format!("{}@127.0.0.1:1\nnot-a-peer\n", &pubk),
+            CHAIN_ID_TESTNET,
+            PEER_VERSION_TESTNET,
+        )
+        .unwrap_err();
+        assert!(err.starts_with("Line 2:"));
+    }
+
+    #[test]
+    fn healthy_peer_selection() {
+        let now = 100_000;
+        let peers = vec![
+            make_peer(1, now - 10, 0),
+            // stale
+            make_peer(2, now - DEFAULT_MAX_AGE_SECS - 1, 0),
+            // never contacted
+            make_peer(3, 0, 0),
+            // denied
+            make_peer(4, now - 10, (now + 100) as i64),
+            // denied forever
+            make_peer(5, now - 10, -1),
+            // deny expired
+            make_peer(6, now - 10, (now - 100) as i64),
+        ];
+        let healthy = healthy_peers(peers, CHAIN_ID_TESTNET, now, DEFAULT_MAX_AGE_SECS);
+        assert_eq!(
+            healthy.iter().map(|p| p.addr.port).collect::<Vec<_>>(),
+            vec![1, 6]
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! Read-only replicas (`stacks-node replica`).  A replica is a second process that opens a
+//! node's databases read-only and serves the RPC interface from them, so operators can add
+//! capacity for read traffic without syncing and storing another copy of the chain.
+//!
+//! The replica neither talks to peers nor writes anything: it serves whatever the primary node
+//! has stored, as of the last transaction the primary committed.  Every database is in WAL mode,
+//! so the replica's readers neither block nor are blocked by the primary's writers; the replica
+//! refuses to start if one is not.  Endpoints that would store new data (posting transactions,
+//! blocks and microblocks, and the admin endpoints) answer 503.  Queries against the unconfirmed
+//! microblock state are answered from the latest anchored block instead, since only the primary
+//! maintains that state.
+//!
+//! While a replica is reading, the WAL checkpoint the primary runs when it shuts down may be
+//! unable to finish.  That is harmless: the primary logs a warning, and the next checkpoint
+//! picks up where it left off.
+
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fs;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use pico_args::Arguments;
+
+use stacks::chainstate::burn::db::sortdb::SortitionDB;
+use stacks::chainstate::stacks::db::StacksChainState;
+use stacks::chainstate::stacks::index::handles;
+use stacks::chainstate::stacks::index::marf::MarfConnection;
+use stacks::core::mempool::MemPoolDB;
+use stacks::net::atlas::AtlasDB;
+use stacks::net::connection::ConnectionOptions;
+use stacks::net::db::PeerDB;
+use stacks::net::p2p::PeerNetwork;
+use stacks::net::rpc::RPCHandlerArgs;
+use stacks::util::db::{sql_journal_mode, DBConn};
+use stacks::util::hash::Sha256Sum;
+use stacks::util::strings::UrlString;
+
+use crate::burnchains::BitcoinRegtestController;
+use crate::node::{get_genesis_unlock_heights, use_test_genesis_chainstate};
+use crate::shutdown;
+use crate::{Config, ConfigFile};
+
+/// How long the replica waits for RPC requests in each pass of its event loop, in milliseconds
+const REPLICA_POLL_TIMEOUT_MS: u64 = 1000;
+
+/// Connection options for a replica: the node's own, with every p2p state machine turned off and
+/// RPC writes refused
+pub fn replica_connection_options(opts: &ConnectionOptions) -> ConnectionOptions {
+    let mut opts = opts.clone();
+    opts.read_only_replica = true;
+    opts.disable_neighbor_walk = true;
+    opts.disable_chat_neighbors = true;
+    opts.disable_inv_sync = true;
+    opts.disable_inv_chat = true;
+    opts.disable_block_download = true;
+    opts.disable_block_advertisement = true;
+    opts.disable_pingbacks = true;
+    opts.disable_inbound_walks = true;
+    opts.disable_inbound_handshakes = true;
+    opts.disable_natpunch = true;
+    opts
+}
+
+/// Err unless the database behind `conn` is in WAL mode
+fn check_wal(name: &str, conn: &DBConn) -> Result<(), String> {
+    let mode = sql_journal_mode(conn)
+        .map_err(|e| format!("Failed to read the journal mode of the {}: {:?}", name, &e))?;
+    if mode != "wal" {
+        return Err(format!(
+            "The {} is in '{}' journal mode, not WAL; start the primary node to convert it",
+            name, &mode
+        ));
+    }
+    Ok(())
+}
+
+pub fn run_replica_command(mut args: Arguments) -> Result<(), String> {
+    let config_path: Option<String> = args
+        .opt_value_from_str("--config")
+        .map_err(|e| format!("{}", e))?;
+    let config_path = config_path.ok_or("`replica` must be passed a config file via `--config`")?;
+    let profile: Option<String> = args
+        .opt_value_from_str("--profile")
+        .map_err(|e| format!("{}", e))?;
+    let rpc_bind: String = args
+        .value_from_str("--rpc-bind")
+        .map_err(|e| format!("{}", e))?;
+    args.finish().map_err(|e| format!("{}", e))?;
+
+    let config = Config::from_config_file(ConfigFile::from_path_with_profile(
+        &config_path,
+        profile.as_deref(),
+    )?);
+    let rpc_sock: SocketAddr = rpc_bind
+        .parse()
+        .map_err(|_| format!("Failed to parse socket: {}", &rpc_bind))?;
+    if rpc_bind == config.node.rpc_bind {
+        return Err("The replica must not bind the primary node's RPC address".to_string());
+    }
+
+    let chainstate_path = config.get_chainstate_path_str();
+    if fs::metadata(&chainstate_path).is_err() {
+        return Err(format!(
+            "No chainstate at {}; start the primary node first",
+            &chainstate_path
+        ));
+    }
+
+    handles::set_max_open_handles(config.node.max_marf_handles);
+    let sortdb = SortitionDB::open_readonly(&config.get_burn_db_file_path())
+        .map_err(|e| format!("Failed to open the sortition DB: {:?}", &e))?;
+    let mut chainstate = StacksChainState::open_readonly(
+        config.is_mainnet(),
+        config.burnchain.chain_id,
+        &chainstate_path,
+        config.connection_options.read_only_call_limit.clone(),
+    )
+    .map_err(|e| format!("Failed to open the chainstate: {:?}", &e))?;
+    let mut mempool = MemPoolDB::open_readonly(&chainstate_path)
+        .map_err(|e| format!("Failed to open the mempool: {:?}", &e))?;
+    let atlasdb = AtlasDB::connect(
+        config.make_atlas_config(),
+        &config.get_atlas_db_file_path(),
+        false,
+    )
+    .map_err(|e| format!("Failed to open the Atlas DB: {:?}", &e))?;
+
+    check_wal("sortition DB", sortdb.conn())?;
+    check_wal("chainstate DB", chainstate.db())?;
+    chainstate
+        .clarity_state
+        .with_marf(|marf| check_wal("Clarity DB", marf.sqlite_conn()))?;
+    check_wal("mempool DB", mempool.conn())?;
+    check_wal("Atlas DB", &atlasdb.conn)?;
+
+    // the replica gets its own identity, since it never talks to the primary's peers
+    let burnchain = BitcoinRegtestController::new_dummy(config.clone()).get_burnchain();
+    let peerdb = PeerDB::connect_memory(
+        config.burnchain.chain_id,
+        burnchain.network_id,
+        config.connection_options.private_key_lifetime,
+        UrlString::try_from(format!("{}", &config.node.data_url))
+            .map_err(|e| format!("Invalid data URL: {:?}", &e))?,
+        &vec![],
+        &vec![],
+    )
+    .map_err(|e| format!("Failed to create the replica's peer DB: {:?}", &e))?;
+    let local_peer = PeerDB::get_local_peer(peerdb.conn())
+        .map_err(|e| format!("Failed to load the replica's identity: {:?}", &e))?;
+
+    let sortition_tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())
+        .map_err(|e| format!("Failed to load the sortition tip: {:?}", &e))?;
+    let view = SortitionDB::get_burnchain_view(sortdb.conn(), &burnchain, &sortition_tip)
+        .map_err(|e| format!("Failed to load the burnchain view: {:?}", &e))?;
+
+    let mut network = PeerNetwork::new(
+        peerdb,
+        atlasdb,
+        local_peer,
+        config.burnchain.peer_version,
+        burnchain,
+        view,
+        replica_connection_options(&config.connection_options),
+    );
+    let p2p_sock: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    network
+        .bind(&p2p_sock, &rpc_sock)
+        .map_err(|e| format!("Failed to bind {}: {:?}", &rpc_sock, &e))?;
+    info!(
+        "Serving RPC read-only from {} on {}",
+        &chainstate_path, &rpc_sock
+    );
+
+    let should_keep_running = Arc::new(AtomicBool::new(true));
+    shutdown::install_termination_handler(
+        should_keep_running.clone(),
+        Duration::from_secs(config.node.shutdown_timeout_secs),
+    );
+
+    let exit_at_block_height = config.burnchain.process_exit_at_block_height;
+    let genesis_unlock_heights = get_genesis_unlock_heights(use_test_genesis_chainstate(&config));
+    let handler_args = RPCHandlerArgs {
+        exit_at_block_height: exit_at_block_height.as_ref(),
+        genesis_chainstate_hash: Sha256Sum::from_hex(stx_genesis::GENESIS_CHAINSTATE_HASH).unwrap(),
+        genesis_unlock_heights: Some(&genesis_unlock_heights),
+        ..RPCHandlerArgs::default()
+    };
+
+    while should_keep_running.load(Ordering::SeqCst) {
+        if let Err(e) = network.run(
+            &sortdb,
+            &mut chainstate,
+            &mut mempool,
+            None,
+            false,
+            false,
+            REPLICA_POLL_TIMEOUT_MS,
+            &handler_args,
+            &mut HashSet::new(),
+        ) {
+            warn!("Replica: failed to serve RPC requests: {:?}", &e);
+        }
+    }
+    info!("Replica shut down");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn replica_connection_options_turn_off_p2p() {
+        let opts = replica_connection_options(&ConnectionOptions::default());
+        assert!(opts.read_only_replica);
+        assert!(opts.disable_neighbor_walk);
+        assert!(opts.disable_inv_sync);
+        assert!(opts.disable_block_download);
+        assert!(opts.disable_inbound_handshakes);
+        assert!(!ConnectionOptions::default().read_only_replica);
+    }
+}
@@ -7,7 +7,9 @@ use stacks::chainstate::stacks::db::ClarityTx;
 use stacks::net::atlas::AttachmentInstance;
 use stacks::types::chainstate::BurnchainHeaderHash;
 use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::Arc;
 
 /// RunLoop is coordinating a simulated burnchain and some simulated nodes
 /// taking turns in producing blocks.
@@ -16,6 +18,8 @@ pub struct RunLoop {
     pub node: Node,
     pub callbacks: RunLoopCallbacks,
     attachments_rx: Option<Receiver<HashSet<AttachmentInstance>>>,
+    /// cleared to stop the run loop before it has run all its rounds
+    termination_switch: Option<Arc<AtomicBool>>,
 }
 
 impl RunLoop {
@@ -38,9 +42,22 @@ impl RunLoop {
             node,
             callbacks: RunLoopCallbacks::new(),
             attachments_rx: Some(attachments_rx),
+            termination_switch: None,
         }
     }
 
+    /// Stop at the start of the next round once `switch` is cleared
+    pub fn set_termination_switch(&mut self, switch: Arc<AtomicBool>) {
+        self.termination_switch = Some(switch);
+    }
+
+    fn should_keep_running(&self) -> bool {
+        self.termination_switch
+            .as_ref()
+            .map(|switch| switch.load(Ordering::SeqCst))
+            .unwrap_or(true)
+    }
+
     /// Starts the testnet runloop.
     ///
     /// This function will block by looping infinitely.
@@ -152,7 +169,7 @@ impl RunLoop {
         // Start the runloop
         round_index = 1;
         loop {
-            if expected_num_rounds == round_index {
+            if expected_num_rounds == round_index || !self.should_keep_running() {
                 return Ok(());
             }
 
@@ -3,8 +3,7 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::sync_channel;
 use std::sync::Arc;
 use std::thread;
-
-use ctrlc as termination;
+use std::time::Duration;
 
 use stacks::burnchains::bitcoin::address::BitcoinAddress;
 use stacks::burnchains::bitcoin::address::BitcoinAddressType;
@@ -14,14 +13,24 @@ use stacks::chainstate::coordinator::comm::{CoordinatorChannels, CoordinatorRece
 use stacks::chainstate::coordinator::{
     BlockEventDispatcher, ChainsCoordinator, CoordinatorCommunication,
 };
+use stacks::chainstate::stacks::db::blocks;
 use stacks::chainstate::stacks::db::{ChainStateBootData, ClarityTx, StacksChainState};
+use stacks::chainstate::stacks::index::handles;
+use stacks::core::schedule;
 use stacks::net::atlas::{AtlasConfig, Attachment};
 use stacks::vm::types::{PrincipalData, Value};
 use stx_genesis::GenesisData;
 
+use crate::chainstate_migration;
+use crate::config_reload::{ConfigReloader, ReloadHandle};
+use crate::metrics_state::MetricsState;
 use crate::monitoring::start_serving_monitoring_metrics;
 use crate::node::use_test_genesis_chainstate;
+use crate::shutdown;
+use crate::stacker::Stacker;
 use crate::syncctl::PoxSyncWatchdog;
+use crate::tip_hooks::TipHooks;
+use crate::tip_notifier::TipNotifier;
 use crate::{
     node::{get_account_balances, get_account_lockups, get_names, get_namespaces},
     util, BitcoinRegtestController, BurnchainController, Config, EventDispatcher, Keychain,
@@ -38,6 +47,12 @@ pub struct RunLoop {
     blocks_processed: std::sync::Arc<std::sync::atomic::AtomicU64>,
     microblocks_processed: std::sync::Arc<std::sync::atomic::AtomicU64>,
     coordinator_channels: Option<(CoordinatorReceivers, CoordinatorChannels)>,
+    /// the config file this node was started with, which it re-reads on SIGHUP
+    config_path: Option<String>,
+    /// the profile of that config file this node runs with
+    config_profile: Option<String>,
+    /// cleared to shut the node down, if the embedding program controls shutdown
+    termination_switch: Option<Arc<AtomicBool>>,
 }
 
 #[cfg(not(test))]
@@ -45,6 +60,12 @@ pub struct RunLoop {
     config: Config,
     pub callbacks: RunLoopCallbacks,
     coordinator_channels: Option<(CoordinatorReceivers, CoordinatorChannels)>,
+    /// the config file this node was started with, which it re-reads on SIGHUP
+    config_path: Option<String>,
+    /// the profile of that config file this node runs with
+    config_profile: Option<String>,
+    /// cleared to shut the node down, if the embedding program controls shutdown
+    termination_switch: Option<Arc<AtomicBool>>,
 }
 
 impl RunLoop {
@@ -56,6 +77,9 @@ impl RunLoop {
             config,
             coordinator_channels: Some(channels),
             callbacks: RunLoopCallbacks::new(),
+            config_path: None,
+            config_profile: None,
+            termination_switch: None,
         }
     }
 
@@ -66,11 +90,27 @@ impl RunLoop {
             config,
             coordinator_channels: Some(channels),
             callbacks: RunLoopCallbacks::new(),
+            config_path: None,
+            config_profile: None,
+            termination_switch: None,
             blocks_processed: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0)),
             microblocks_processed: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0)),
         }
     }
 
+    /// Reload the reloadable settings from `config_path` (and its profile `profile`) whenever
+    /// the node gets a SIGHUP
+    pub fn set_config_path(&mut self, config_path: &str, profile: Option<&str>) {
+        self.config_path = Some(config_path.to_string());
+        self.config_profile = profile.map(|p| p.to_string());
+    }
+
+    /// Shut down once `switch` is cleared, instead of on SIGTERM or SIGINT.  A node started this
+    /// way does not install a signal handler.
+    pub fn set_termination_switch(&mut self, switch: Arc<AtomicBool>) {
+        self.termination_switch = Some(switch);
+    }
+
     pub fn get_coordinator_channel(&self) -> Option<CoordinatorChannels> {
         self.coordinator_channels.as_ref().map(|x| x.1.clone())
     }
@@ -112,16 +152,24 @@ impl RunLoop {
             .take()
             .expect("Run loop already started, can only start once after initialization.");
 
-        let should_keep_running = Arc::new(AtomicBool::new(true));
-        let keep_running_writer = should_keep_running.clone();
+        let should_keep_running = match self.termination_switch.as_ref() {
+            Some(switch) => switch.clone(),
+            None => {
+                let should_keep_running = Arc::new(AtomicBool::new(true));
+                shutdown::install_termination_handler(
+                    should_keep_running.clone(),
+                    Duration::from_secs(self.config.node.shutdown_timeout_secs),
+                );
+                should_keep_running
+            }
+        };
 
-        let install = termination::set_handler(move || {
-            info!("Graceful termination request received, will complete the ongoing runloop cycles and terminate");
-            keep_running_writer.store(false, Ordering::SeqCst);
-        });
-        if let Err(e) = install {
-            error!("Error setting termination handler - {}", e);
+        // before any databases are opened, so that they all size their caches from it
+        if let Some(budget) = self.config.node.memory_budget() {
+            budget.install();
         }
+        handles::set_max_open_handles(self.config.node.max_marf_handles);
+        blocks::set_signature_verification_threads(self.config.node.signature_verification_threads);
 
         // Initialize and start the burnchain.
         let mut burnchain = BitcoinRegtestController::with_burnchain(
@@ -167,6 +215,7 @@ impl RunLoop {
 
         let burnchain_config = burnchain.get_burnchain();
         let mut target_burnchain_block_height = 1.max(burnchain_config.first_block_height);
+        let consensus_schedule = schedule::consensus_schedule(&burnchain_config);
 
         info!("Start syncing Bitcoin headers, feel free to grab a cup of coffee, this can take a while");
         match burnchain.start(Some(target_burnchain_block_height)) {
@@ -192,6 +241,34 @@ impl RunLoop {
         for observer in self.config.events_observers.iter() {
             event_dispatcher.register_observer(observer, should_keep_running.clone());
         }
+        if let Some(path) = self.config.node.tip_notify_socket.as_ref() {
+            match TipNotifier::bind(path, should_keep_running.clone()) {
+                Ok(notifier) => {
+                    info!("Announcing new tips on {}", path);
+                    event_dispatcher.set_tip_notifier(notifier);
+                }
+                Err(e) => error!(
+                    "Failed to listen for tip notification clients on {}: {}",
+                    path, &e
+                ),
+            }
+        }
+        if let Some(hooks) = TipHooks::from_config(&self.config.node) {
+            info!("Running hook commands on new tips");
+            event_dispatcher.set_tip_hooks(hooks);
+        }
+
+        let reload_handle = ReloadHandle::new(&self.config);
+        if let Some(config_path) = self.config_path.as_ref() {
+            ConfigReloader::new(
+                config_path,
+                self.config_profile.as_deref(),
+                reload_handle.clone(),
+                event_dispatcher.clone(),
+                should_keep_running.clone(),
+            )
+            .spawn_sighup_watcher();
+        }
 
         let use_test_genesis_data = use_test_genesis_chainstate(&self.config);
 
@@ -227,9 +304,10 @@ impl RunLoop {
                 get_namespaces(use_test_genesis_data)
             })),
             get_bulk_initial_names: Some(Box::new(move || get_names(use_test_genesis_data))),
+            custom_costs_contract: self.config.get_custom_costs_contract(),
         };
 
-        let (chain_state_db, receipts) = StacksChainState::open_and_exec(
+        let (mut chain_state_db, receipts) = StacksChainState::open_and_exec(
             mainnet,
             chainid,
             &chainstate_path,
@@ -238,8 +316,14 @@ impl RunLoop {
         )
         .unwrap();
         coordinator_dispatcher.dispatch_boot_receipts(receipts);
+        chainstate_migration::start(
+            &self.config,
+            &mut chain_state_db,
+            should_keep_running.clone(),
+        )
+        .expect("Unable to migrate chainstate");
 
-        let atlas_config = AtlasConfig::default(mainnet);
+        let atlas_config = self.config.make_atlas_config();
         let moved_atlas_config = atlas_config.clone();
 
         let coordinator_thread_handle = thread::Builder::new()
@@ -295,6 +379,7 @@ impl RunLoop {
                 attachments_rx,
                 atlas_config,
                 should_keep_running.clone(),
+                Some(reload_handle),
             )
         } else {
             node.into_initialized_node(
@@ -306,16 +391,26 @@ impl RunLoop {
                 attachments_rx,
                 atlas_config,
                 should_keep_running.clone(),
+                Some(reload_handle),
             )
         };
 
         // TODO (hack) instantiate the sortdb in the burnchain
         let _ = burnchain.sortdb_mut();
 
+        // stack this node's STX, if so configured
+        let mut stacker = Stacker::new(&self.config);
+
         // Start the runloop
         trace!("Begin run loop");
         self.bump_blocks_processed();
 
+        // restore the saved metric totals before anything can scrape them
+        let metrics_state = MetricsState::from_config(&self.config);
+        if let Some(metrics_state) = metrics_state.as_ref() {
+            metrics_state.start_saving(should_keep_running.clone());
+        }
+
         let prometheus_bind = self.config.node.prometheus_bind.clone();
         if let Some(prometheus_bind) = prometheus_bind {
             thread::Builder::new()
@@ -326,6 +421,21 @@ impl RunLoop {
                 .unwrap();
         }
 
+        if let Some(otlp_endpoint) = self.config.node.otlp_endpoint.as_ref() {
+            if let Err(e) = stacks::monitoring::start_otlp_exporter(
+                otlp_endpoint,
+                "stacks-node",
+                self.config.node.otlp_sample_ratio,
+            ) {
+                error!("Failed to start trace exporter: {}", &e);
+            }
+        }
+
+        if self.config.node.profile_clarity {
+            info!("Profiling Clarity calls in each processed block");
+            stacks::vm::profiler::start(self.config.node.clarity_profile_path.clone());
+        }
+
         let mut block_height = 1.max(burnchain_config.first_block_height);
 
         let mut burnchain_height = block_height;
@@ -340,6 +450,7 @@ impl RunLoop {
             if !should_keep_running.load(Ordering::SeqCst) {
                 // The p2p thread relies on the same atomic_bool, it will
                 // discontinue its execution after completing its ongoing runloop epoch.
+                // The relayer stops mining, but finishes whatever it is doing.
                 info!("Terminating p2p process");
                 info!("Terminating relayer");
                 info!("Terminating chains-coordinator");
@@ -349,6 +460,14 @@ impl RunLoop {
                 node.relayer_thread_handle.join().unwrap();
                 node.p2p_thread_handle.join().unwrap();
 
+                shutdown::flush_databases(&self.config);
+
+                if let Some(metrics_state) = metrics_state.as_ref() {
+                    if let Err(e) = metrics_state.save() {
+                        warn!("Failed to save metric totals: {}", &e);
+                    }
+                }
+
                 info!("Exiting stacks-node");
                 break;
             }
@@ -409,6 +528,17 @@ impl RunLoop {
                     next_height, block_height
                 );
 
+                if let Some(change) =
+                    schedule::upcoming_unsupported_change(&consensus_schedule, next_height)
+                {
+                    warn!(
+                        "This node does not support {}, which activates at burnchain height {} ({} blocks from now); upgrade before then",
+                        &change.name,
+                        change.activation_height,
+                        change.activation_height - next_height
+                    );
+                }
+
                 let mut sort_count = 0;
 
                 // first, let's process all blocks in (block_height, next_height]
@@ -470,6 +600,10 @@ impl RunLoop {
                     //  this prevents a possible corner case in the event of a PoX fork.
                     mine_start = 0;
 
+                    if let Some(ref mut stacker) = stacker {
+                        stacker.run(&burnchain_config, burnchain.sortdb_ref());
+                    }
+
                     // at tip, and not downloading. proceed to mine.
                     info!(
                         "Synchronized full burnchain up to height {}. Proceeding to mine blocks",
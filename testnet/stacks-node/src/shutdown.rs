@@ -0,0 +1,162 @@
+//! Coordinated shutdown.  On the first SIGTERM or SIGINT, the node clears `should_keep_running`,
+//! which each runloop thread checks between units of work: the miner stops starting tenures, the
+//! relayer finishes what it is doing (including an in-flight block commit), the p2p thread closes
+//! its sockets, and the run loop joins everything and checkpoints the databases.  If that takes
+//! longer than `node.shutdown_timeout_secs`, or if a second signal arrives, the process exits
+//! right away.
+
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use stacks::core::mempool::MemPoolDB;
+use stacks::util::db::sql_wal_checkpoint;
+
+use crate::Config;
+
+pub fn install_termination_handler(should_keep_running: Arc<AtomicBool>, deadline: Duration) {
+    let install = ctrlc::set_handler(move || {
+        if !request_shutdown(&should_keep_running, deadline) {
+            warn!("Termination requested again while shutting down; exiting now");
+            process::exit(1);
+        }
+        info!(
+            "Graceful termination request received, will complete the ongoing runloop cycles and terminate (within {}s)",
+            deadline.as_secs()
+        );
+    });
+    if let Err(e) = install {
+        error!("Error setting termination handler - {}", e);
+    }
+}
+
+/// Begin a clean shutdown, as if the node had received SIGTERM.  Returns false if a shutdown was
+/// already under way.
+pub fn request_shutdown(should_keep_running: &AtomicBool, deadline: Duration) -> bool {
+    if !should_keep_running.swap(false, Ordering::SeqCst) {
+        return false;
+    }
+    start_shutdown_deadline(deadline);
+    true
+}
+
+fn start_shutdown_deadline(deadline: Duration) {
+    let spawned = thread::Builder::new()
+        .name("shutdown-deadline".to_string())
+        .spawn(move || {
+            thread::sleep(deadline);
+            error!(
+                "Failed to shut down within {}s; exiting now",
+                deadline.as_secs()
+            );
+            process::exit(1);
+        });
+    if let Err(e) = spawned {
+        error!("Failed to start shutdown deadline thread: {}", e);
+    }
+}
+
+/// The databases the node keeps in WAL mode: the sortition DB, the chainstate's MARFs (the block
+/// headers index and the Clarity state), the mempool, and the peer DB
+fn wal_databases(config: &Config) -> Vec<PathBuf> {
+    let chainstate_path = config.get_chainstate_path_str();
+    let vm_path = PathBuf::from(&chainstate_path).join("vm");
+    let mut databases = vec![
+        PathBuf::from(config.get_burn_db_file_path()).join("marf.sqlite"),
+        vm_path.join("index.sqlite"),
+        vm_path.join("clarity").join("marf.sqlite"),
+    ];
+    if let Ok(mempool_path) = MemPoolDB::db_path(&chainstate_path) {
+        databases.push(PathBuf::from(mempool_path));
+    }
+    databases.push(PathBuf::from(config.get_peer_db_file_path()));
+    databases
+}
+
+/// Checkpoint every database the node keeps in WAL mode, so the database files are complete on
+/// their own once the node exits.
+pub fn flush_databases(config: &Config) {
+    let mut flushed = 0;
+    for path in wal_databases(config).iter() {
+        // not every node creates every database (e.g. before its first block)
+        if !path.is_file() {
+            continue;
+        }
+        let path = path.to_string_lossy();
+        match sql_wal_checkpoint(&path) {
+            Ok(_) => {
+                debug!("Checkpointed {}", &path);
+                flushed += 1;
+            }
+            Err(e) => warn!("Failed to checkpoint {}: {:?}", &path, &e),
+        }
+    }
+    info!("Flushed {} database(s)", flushed);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::fs;
+    use std::path::Path;
+
+    use rusqlite::{Connection, NO_PARAMS};
+
+    fn open_wal_database(path: &Path) -> Connection {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let conn = Connection::open(path).unwrap();
+        conn.query_row("PRAGMA journal_mode = WAL", NO_PARAMS, |_row| Ok(()))
+            .unwrap();
+        conn.execute("CREATE TABLE t (x INTEGER)", NO_PARAMS)
+            .unwrap();
+        conn.execute("INSERT INTO t (x) VALUES (1)", NO_PARAMS)
+            .unwrap();
+        conn
+    }
+
+    fn wal_len(path: &Path) -> u64 {
+        let mut wal_path = path.as_os_str().to_owned();
+        wal_path.push("-wal");
+        fs::metadata(&wal_path).unwrap().len()
+    }
+
+    #[test]
+    fn flush_wal_databases() {
+        let mut config = Config::default();
+        config.node.working_dir = format!("/tmp/stacks-node-tests/shutdown-{}", process::id());
+        let dir = PathBuf::from(&config.node.working_dir);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+
+        // connections that are still open, as the run loop's would be
+        let index_path = PathBuf::from(config.get_chainstate_path_str())
+            .join("vm")
+            .join("index.sqlite");
+        let index_conn = open_wal_database(&index_path);
+        let peer_path = PathBuf::from(config.get_peer_db_file_path());
+        let _peer_conn = open_wal_database(&peer_path);
+
+        // a database the node does not know about is left alone
+        let other_path = PathBuf::from(config.get_chainstate_path_str()).join("other.sqlite");
+        let _other_conn = open_wal_database(&other_path);
+
+        assert!(wal_databases(&config).contains(&index_path));
+        assert!(wal_len(&index_path) > 0);
+        assert!(wal_len(&peer_path) > 0);
+
+        flush_databases(&config);
+        assert_eq!(wal_len(&index_path), 0);
+        assert_eq!(wal_len(&peer_path), 0);
+        assert!(wal_len(&other_path) > 0);
+
+        let x: i64 = index_conn
+            .query_row("SELECT x FROM t", NO_PARAMS, |row| row.get(0))
+            .unwrap();
+        assert_eq!(x, 1);
+    }
+}
@@ -0,0 +1,574 @@
+//! Stacker mode: the node stacks the STX of an account it holds the key for.
+//!
+//! Once the node has synced to the chain tip, the run loop calls `Stacker::run()` on each pass.
+//! If the configured account is neither stacking nor delegating, this builds and signs a
+//! `stack-stx` for the configured amount, reward address, and lock period, checks it against
+//! `can-stack-stx`, and submits it to this node's own RPC interface (or, in dry-run mode, just
+//! logs it).  PoX only lets an account stack again once its previous lock has expired, so the
+//! account re-stacks every `lock_period` reward cycles, sitting out the cycle in which it unlocks.
+//!
+//! The stacker reads the account nonce from the chain tip, so the key should not be used to send
+//! other transactions while stacker mode is on.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+use async_h1::client;
+use async_std::net::TcpStream;
+use http_types::{Method, Request, Url};
+
+use stacks::address::AddressHashMode;
+use stacks::burnchains::bitcoin::address::{BitcoinAddress, BitcoinAddressType};
+use stacks::burnchains::Burnchain;
+use stacks::chainstate::burn::db::sortdb::SortitionDB;
+use stacks::chainstate::stacks::db::StacksChainState;
+use stacks::chainstate::stacks::{
+    StacksPrivateKey, StacksPublicKey, StacksTransaction, StacksTransactionSigner,
+    TransactionAnchorMode, TransactionAuth, TransactionContractCall, TransactionPostConditionMode,
+    TransactionSpendingCondition, TransactionVersion, C32_ADDRESS_VERSION_MAINNET_SINGLESIG,
+    C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+};
+use stacks::clarity_vm::clarity::ClarityConnection;
+use stacks::codec::StacksMessageCodec;
+use stacks::net::RPCStackingStatusData;
+use stacks::types::chainstate::{StacksAddress, StacksBlockHeader, StacksBlockId};
+use stacks::util::boot::boot_code_id;
+use stacks::util::hash::to_hex;
+use stacks::vm::costs::LimitedCostTracker;
+use stacks::vm::types::{PrincipalData, TupleData};
+use stacks::vm::{ClarityName, ContractName, Value};
+
+use crate::config::StackerConfig;
+use crate::Config;
+
+/// Why the stacker did not submit a `stack-stx` on a given pass
+#[derive(Debug, Clone, PartialEq)]
+pub enum StackerSkip {
+    /// the account's STX are locked until the given burn block height
+    AlreadyStacking(u64),
+    /// the account has delegated to the given principal
+    Delegating(String),
+    /// we're in the prepare phase, so a `stack-stx` might not make it into the next reward set
+    PreparePhase,
+    /// we already sent a `stack-stx` during this reward cycle
+    AlreadySubmitted(u64),
+    /// the account can't cover the amount to stack plus the transaction fee
+    InsufficientBalance { balance: u128, needed: u128 },
+    /// the PoX contract would reject the `stack-stx`, with the given error code
+    Rejected(i128),
+}
+
+/// The state of the stacker's account, as of the canonical chain tip
+#[derive(Debug, Clone)]
+pub struct StackerAccountState {
+    pub burn_block_height: u64,
+    pub reward_cycle: u64,
+    pub in_prepare_phase: bool,
+    pub nonce: u64,
+    pub unlocked_balance: u128,
+    pub stacking_status: RPCStackingStatusData,
+}
+
+pub struct Stacker {
+    config: StackerConfig,
+    mainnet: bool,
+    chain_id: u32,
+    principal: PrincipalData,
+    /// this node's RPC endpoint, to which we submit our transactions
+    rpc_endpoint: SocketAddr,
+    chainstate: StacksChainState,
+    /// the reward cycle in which we last sent a `stack-stx`
+    last_submitted_cycle: Option<u64>,
+}
+
+impl Stacker {
+    /// Instantiate the stacker, if stacker mode is configured
+    pub fn new(config: &Config) -> Option<Stacker> {
+        let stacker_config = config.stacker.clone()?;
+        let mainnet = config.is_mainnet();
+        let chain_id = config.burnchain.chain_id;
+
+        let (chainstate, _) =
+            StacksChainState::open(mainnet, chain_id, &config.get_chainstate_path_str())
+                .expect("FATAL: stacker failed to open the chainstate");
+
+        let mut rpc_endpoint: SocketAddr = config
+            .node
+            .rpc_bind
+            .parse()
+            .expect("FATAL: could not parse the node's rpc_bind address");
+        if rpc_endpoint.ip().is_unspecified() {
+            rpc_endpoint.set_ip(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        }
+
+        let principal = PrincipalData::from(stacker_address(mainnet, &stacker_config.private_key));
+
+        info!(
+            "Stacker mode is on";
+            "stacker" => %principal,
+            "amount_ustx" => %stacker_config.amount_ustx,
+            "pox_address" => stacker_config.pox_address.to_b58(),
+            "lock_period" => stacker_config.lock_period,
+            "dry_run" => stacker_config.dry_run,
+        );
+
+        Some(Stacker {
+            config: stacker_config,
+            mainnet,
+            chain_id,
+            principal,
+            rpc_endpoint,
+            chainstate,
+            last_submitted_cycle: None,
+        })
+    }
+
+    /// Stack the account, if it's not already stacking and it's able to.
+    pub fn run(&mut self, burnchain: &Burnchain, sortdb: &SortitionDB) {
+        let (tip, state) = match self.load_account_state(burnchain, sortdb) {
+            Ok(Some(x)) => x,
+            Ok(None) => {
+                debug!("Stacker: no Stacks chain tip yet");
+                return;
+            }
+            Err(e) => {
+                warn!("Stacker: failed to load account state: {}", &e);
+                return;
+            }
+        };
+
+        if let Err(reason) = check_can_stack(&self.config, &state, self.last_submitted_cycle) {
+            debug!("Stacker: not stacking"; "reason" => ?reason, "reward_cycle" => state.reward_cycle);
+            return;
+        }
+
+        match self.check_pox_accepts(sortdb, &tip, &state) {
+            Ok(Ok(())) => {}
+            Ok(Err(reason)) => {
+                // most likely a misconfiguration, e.g. an amount below the stacking minimum
+                warn!("Stacker: PoX would reject stack-stx"; "reason" => ?reason, "reward_cycle" => state.reward_cycle);
+                return;
+            }
+            Err(e) => {
+                warn!("Stacker: failed to evaluate can-stack-stx: {}", &e);
+                return;
+            }
+        }
+
+        let tx = make_stack_stx_tx(
+            self.mainnet,
+            self.chain_id,
+            &self.config,
+            state.nonce,
+            state.burn_block_height,
+        );
+        let txid = tx.txid();
+
+        if self.config.dry_run {
+            info!(
+                "Stacker (dry run): would submit stack-stx";
+                "txid" => %txid,
+                "reward_cycle" => state.reward_cycle + 1,
+                "tx" => to_hex(&tx.serialize_to_vec()),
+            );
+            self.last_submitted_cycle = Some(state.reward_cycle);
+            return;
+        }
+
+        match self.submit_tx(&tx) {
+            Ok(()) => {
+                info!(
+                    "Stacker: submitted stack-stx";
+                    "txid" => %txid,
+                    "stacker" => %self.principal,
+                    "amount_ustx" => %self.config.amount_ustx,
+                    "first_reward_cycle" => state.reward_cycle + 1,
+                    "lock_period" => self.config.lock_period,
+                );
+                self.last_submitted_cycle = Some(state.reward_cycle);
+            }
+            Err(e) => {
+                warn!("Stacker: failed to submit stack-stx {}: {}", &txid, &e);
+            }
+        }
+    }
+
+    fn load_account_state(
+        &mut self,
+        burnchain: &Burnchain,
+        sortdb: &SortitionDB,
+    ) -> Result<Option<(StacksBlockId, StackerAccountState)>, String> {
+        let tip = match self
+            .chainstate
+            .get_stacks_chain_tip(sortdb)
+            .map_err(|e| format!("{:?}", &e))?
+        {
+            Some(tip) => StacksBlockHeader::make_index_block_hash(
+                &tip.consensus_hash,
+                &tip.anchored_block_hash,
+            ),
+            None => return Ok(None),
+        };
+
+        let burn_block_height = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())
+            .map_err(|e| format!("{:?}", &e))?
+            .block_height;
+        let reward_cycle = burnchain
+            .block_height_to_reward_cycle(burn_block_height)
+            .ok_or_else(|| format!("Burn height {} precedes PoX", burn_block_height))?;
+
+        let principal = self.principal.clone();
+        let (nonce, unlocked_balance) = match self
+            .chainstate
+            .maybe_read_only_clarity_tx(&sortdb.index_conn(), &tip, |clarity_tx| {
+                clarity_tx.with_clarity_db_readonly(|clarity_db| {
+                    let burn_height = clarity_db.get_current_burnchain_block_height() as u64;
+                    let balance = clarity_db.get_account_stx_balance(&principal);
+                    (
+                        clarity_db.get_account_nonce(&principal),
+                        balance.get_available_balance_at_burn_block(burn_height),
+                    )
+                })
+            })
+            .map_err(|e| format!("{:?}", &e))?
+        {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+
+        let stacking_status =
+            RPCStackingStatusData::from_db(sortdb, &mut self.chainstate, &tip, &self.principal)
+                .map_err(|e| format!("{:?}", &e))?;
+
+        Ok(Some((
+            tip,
+            StackerAccountState {
+                burn_block_height,
+                reward_cycle,
+                in_prepare_phase: burnchain.is_in_prepare_phase(burn_block_height),
+                nonce,
+                unlocked_balance,
+                stacking_status,
+            },
+        )))
+    }
+
+    /// Ask the PoX contract whether it would accept our `stack-stx` for the next reward cycle
+    fn check_pox_accepts(
+        &mut self,
+        sortdb: &SortitionDB,
+        tip: &StacksBlockId,
+        state: &StackerAccountState,
+    ) -> Result<Result<(), StackerSkip>, String> {
+        let mainnet = self.mainnet;
+        let pox_contract = boot_code_id("pox", mainnet);
+        let code = format!(
+            "(can-stack-stx {} u{} u{} u{})",
+            pox_addr_value(&self.config.pox_address),
+            self.config.amount_ustx,
+            state.reward_cycle + 1,
+            self.config.lock_period
+        );
+        let sender = self.principal.clone();
+
+        let result = self
+            .chainstate
+            .maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                clarity_tx.with_readonly_clarity_env(
+                    mainnet,
+                    sender,
+                    LimitedCostTracker::new_free(),
+                    |env| env.eval_read_only(&pox_contract, &code),
+                )
+            })
+            .map_err(|e| format!("{:?}", &e))?
+            .ok_or_else(|| format!("Chain tip {} not found", tip))?
+            .map_err(|e| format!("{:?}", &e))?;
+
+        match result.expect_result() {
+            Ok(_) => Ok(Ok(())),
+            Err(code) => Ok(Err(StackerSkip::Rejected(code.expect_i128()))),
+        }
+    }
+
+    /// POST a transaction to this node's /v2/transactions endpoint
+    fn submit_tx(&self, tx: &StacksTransaction) -> Result<(), String> {
+        let url = Url::parse(&format!("http://{}/v2/transactions", &self.rpc_endpoint))
+            .map_err(|e| format!("{:?}", &e))?;
+        let mut req = Request::new(Method::Post, url);
+        req.append_header("Content-Type", "application/octet-stream")
+            .map_err(|e| format!("{:?}", &e))?;
+        req.set_body(tx.serialize_to_vec());
+
+        async_std::task::block_on(async {
+            let stream = TcpStream::connect(self.rpc_endpoint)
+                .await
+                .map_err(|e| format!("connection failed: {:?}", &e))?;
+            let response = client::connect(stream, req)
+                .await
+                .map_err(|e| format!("request failed: {:?}", &e))?;
+            let status = response.status();
+            if status.is_success() {
+                Ok(())
+            } else {
+                let body = response.body_string().await.unwrap_or_default();
+                Err(format!("node rejected it ({}): {}", status, body))
+            }
+        })
+    }
+}
+
+/// The single-sig address of the stacker's key
+pub fn stacker_address(mainnet: bool, private_key: &StacksPrivateKey) -> StacksAddress {
+    let version = if mainnet {
+        C32_ADDRESS_VERSION_MAINNET_SINGLESIG
+    } else {
+        C32_ADDRESS_VERSION_TESTNET_SINGLESIG
+    };
+    StacksAddress::from_public_keys(
+        version,
+        &AddressHashMode::SerializeP2PKH,
+        1,
+        &vec![StacksPublicKey::from_private(private_key)],
+    )
+    .expect("FATAL: failed to make an address from the stacker's public key")
+}
+
+/// The `pox-addr` tuple for a reward address
+pub fn pox_addr_value(pox_address: &BitcoinAddress) -> Value {
+    let hash_mode = match pox_address.addrtype {
+        BitcoinAddressType::PublicKeyHash => AddressHashMode::SerializeP2PKH,
+        BitcoinAddressType::ScriptHash => AddressHashMode::SerializeP2SH,
+    };
+    Value::Tuple(
+        TupleData::from_data(vec![
+            (
+                "version".into(),
+                Value::buff_from(vec![hash_mode as u8]).unwrap(),
+            ),
+            (
+                "hashbytes".into(),
+                Value::buff_from(pox_address.bytes.as_bytes().to_vec()).unwrap(),
+            ),
+        ])
+        .unwrap(),
+    )
+}
+
+/// Decide whether the account should send a `stack-stx` now, before asking the PoX contract
+pub fn check_can_stack(
+    config: &StackerConfig,
+    state: &StackerAccountState,
+    last_submitted_cycle: Option<u64>,
+) -> Result<(), StackerSkip> {
+    if state.stacking_status.is_stacking {
+        return Err(StackerSkip::AlreadyStacking(
+            state.stacking_status.unlock_height,
+        ));
+    }
+    if let Some(ref delegation) = state.stacking_status.delegation {
+        return Err(StackerSkip::Delegating(delegation.delegated_to.clone()));
+    }
+    if last_submitted_cycle == Some(state.reward_cycle) {
+        return Err(StackerSkip::AlreadySubmitted(state.reward_cycle));
+    }
+    if state.in_prepare_phase {
+        return Err(StackerSkip::PreparePhase);
+    }
+    let needed = config.amount_ustx + (config.tx_fee as u128);
+    if state.unlocked_balance < needed {
+        return Err(StackerSkip::InsufficientBalance {
+            balance: state.unlocked_balance,
+            needed,
+        });
+    }
+    Ok(())
+}
+
+/// Build and sign a `stack-stx` that starts at the given burn block height
+pub fn make_stack_stx_tx(
+    mainnet: bool,
+    chain_id: u32,
+    config: &StackerConfig,
+    nonce: u64,
+    start_burn_height: u64,
+) -> StacksTransaction {
+    let payload = TransactionContractCall {
+        address: boot_code_id("pox", mainnet).issuer.into(),
+        contract_name: ContractName::from("pox"),
+        function_name: ClarityName::from("stack-stx"),
+        function_args: vec![
+            Value::UInt(config.amount_ustx),
+            pox_addr_value(&config.pox_address),
+            Value::UInt(start_burn_height as u128),
+            Value::UInt(config.lock_period as u128),
+        ],
+    };
+
+    let mut spending_condition = TransactionSpendingCondition::new_singlesig_p2pkh(
+        StacksPublicKey::from_private(&config.private_key),
+    )
+    .expect("FATAL: failed to create p2pkh spending condition from public key");
+    spending_condition.set_nonce(nonce);
+    spending_condition.set_tx_fee(config.tx_fee);
+
+    let version = if mainnet {
+        TransactionVersion::Mainnet
+    } else {
+        TransactionVersion::Testnet
+    };
+    let mut unsigned_tx = StacksTransaction::new(
+        version,
+        TransactionAuth::Standard(spending_condition),
+        payload.into(),
+    );
+    unsigned_tx.anchor_mode = TransactionAnchorMode::OnChainOnly;
+    unsigned_tx.post_condition_mode = TransactionPostConditionMode::Allow;
+    unsigned_tx.chain_id = chain_id;
+
+    let mut tx_signer = StacksTransactionSigner::new(&unsigned_tx);
+    tx_signer
+        .sign_origin(&config.private_key)
+        .expect("FATAL: failed to sign stack-stx");
+    tx_signer
+        .get_tx()
+        .expect("FATAL: failed to get signed stack-stx")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use stacks::chainstate::stacks::TransactionPayload;
+    use stacks::core::CHAIN_ID_TESTNET;
+    use stacks::net::RPCDelegationInfo;
+
+    fn stacker_config() -> StackerConfig {
+        StackerConfig {
+            private_key: StacksPrivateKey::from_hex(
+                "539e35c740079b79f931036651ad01f76d8fe1496dbd840ba9e62c7e7b355db001",
+            )
+            .unwrap(),
+            amount_ustx: 100_000_000_000,
+            pox_address: BitcoinAddress::from_b58("n1htkoYKuLXzPbkn9avC2DJxt7X85qVNCK").unwrap(),
+            lock_period: 6,
+            tx_fee: 1000,
+            dry_run: false,
+        }
+    }
+
+    fn account_state() -> StackerAccountState {
+        StackerAccountState {
+            burn_block_height: 1000,
+            reward_cycle: 5,
+            in_prepare_phase: false,
+            nonce: 3,
+            unlocked_balance: 200_000_000_000,
+            stacking_status: RPCStackingStatusData {
+                principal: "ST2ZRX0K27GW0SP3GJCEMHD95TQGJMKB7G9Y0X1MH".into(),
+                is_stacking: false,
+                locked_ustx: 0,
+                unlock_height: 0,
+                first_reward_cycle: None,
+                lock_period: None,
+                pox_address: None,
+                delegation: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_check_can_stack() {
+        let config = stacker_config();
+        assert_eq!(check_can_stack(&config, &account_state(), None), Ok(()));
+        assert_eq!(check_can_stack(&config, &account_state(), Some(4)), Ok(()));
+        assert_eq!(
+            check_can_stack(&config, &account_state(), Some(5)),
+            Err(StackerSkip::AlreadySubmitted(5))
+        );
+
+        let mut state = account_state();
+        state.stacking_status.is_stacking = true;
+        state.stacking_status.unlock_height = 1200;
+        assert_eq!(
+            check_can_stack(&config, &state, None),
+            Err(StackerSkip::AlreadyStacking(1200))
+        );
+
+        let mut state = account_state();
+        state.stacking_status.delegation = Some(RPCDelegationInfo {
+            delegated_to: "ST1HB1T8WRNBYB0Y3T7WXZS38NKKPTBR3EG9EPJKR".into(),
+            amount_ustx: 100,
+            until_burn_height: None,
+            pox_address: None,
+        });
+        assert_eq!(
+            check_can_stack(&config, &state, None),
+            Err(StackerSkip::Delegating(
+                "ST1HB1T8WRNBYB0Y3T7WXZS38NKKPTBR3EG9EPJKR".into()
+            ))
+        );
+
+        let mut state = account_state();
+        state.in_prepare_phase = true;
+        assert_eq!(
+            check_can_stack(&config, &state, None),
+            Err(StackerSkip::PreparePhase)
+        );
+
+        // must be able to pay the fee on top of the stacked amount
+        let mut state = account_state();
+        state.unlocked_balance = config.amount_ustx;
+        assert_eq!(
+            check_can_stack(&config, &state, None),
+            Err(StackerSkip::InsufficientBalance {
+                balance: config.amount_ustx,
+                needed: config.amount_ustx + 1000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_make_stack_stx_tx() {
+        let config = stacker_config();
+        let tx = make_stack_stx_tx(false, CHAIN_ID_TESTNET, &config, 3, 1000);
+
+        assert_eq!(tx.chain_id, CHAIN_ID_TESTNET);
+        assert_eq!(tx.version, TransactionVersion::Testnet);
+        assert_eq!(tx.get_origin_nonce(), 3);
+        assert_eq!(tx.get_tx_fee(), 1000);
+        assert_eq!(
+            tx.origin_address(),
+            stacker_address(false, &config.private_key)
+        );
+        tx.verify().unwrap();
+
+        match tx.payload {
+            TransactionPayload::ContractCall(ref cc) => {
+                assert_eq!(cc.address, boot_code_id("pox", false).issuer.into());
+                assert_eq!(cc.contract_name.as_str(), "pox");
+                assert_eq!(cc.function_name.as_str(), "stack-stx");
+                assert_eq!(
+                    cc.function_args,
+                    vec![
+                        Value::UInt(100_000_000_000),
+                        pox_addr_value(&config.pox_address),
+                        Value::UInt(1000),
+                        Value::UInt(6),
+                    ]
+                );
+            }
+            _ => panic!("Not a contract call"),
+        }
+
+        // version 0x00 (p2pkh) plus the address's hash160
+        let pox_addr = pox_addr_value(&config.pox_address).expect_tuple();
+        assert_eq!(
+            pox_addr.get("version").unwrap().clone(),
+            Value::buff_from_byte(0x00)
+        );
+        assert_eq!(
+            pox_addr.get("hashbytes").unwrap().clone(),
+            Value::buff_from(config.pox_address.bytes.as_bytes().to_vec()).unwrap()
+        );
+    }
+}
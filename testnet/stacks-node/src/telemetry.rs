@@ -0,0 +1,185 @@
+//! Opt-in telemetry.  Nothing is reported unless `node.telemetry_endpoint` is set.  If it is, the
+//! p2p thread POSTs a small JSON report to it every `node.telemetry_interval_secs`: the node's
+//! version and network, how long it has been up, its burnchain and Stacks tip heights, whether it
+//! is still syncing, and how many peers it has.
+//!
+//! Reports carry no keys, addresses, or peer identities.  Each one has a `report_id` that is
+//! picked at random when the node starts, so that reports from one run can be told apart from
+//! another node's, but runs of the same node cannot be linked.  Reports are sent from their own
+//! thread, and dropped if the endpoint falls behind, so a slow endpoint never holds up the node.
+
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::thread;
+use std::time::Duration;
+
+use rand::RngCore;
+
+use stacks::util::get_epoch_time_secs;
+use stacks::util::hash::to_hex;
+
+use crate::Config;
+
+/// How long to wait for the endpoint to accept a report
+const TELEMETRY_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TelemetryReport {
+    pub report_id: String,
+    pub version: String,
+    pub network: String,
+    pub uptime_secs: u64,
+    pub burn_block_height: u64,
+    pub stacks_tip_height: u64,
+    pub syncing: bool,
+    pub peer_count: u64,
+}
+
+pub struct TelemetryReporter {
+    report_id: String,
+    network: String,
+    interval_secs: u64,
+    start_time: u64,
+    last_report: u64,
+    sender: SyncSender<TelemetryReport>,
+}
+
+impl TelemetryReporter {
+    /// Returns None unless the node is configured to report telemetry
+    pub fn from_config(config: &Config) -> Option<TelemetryReporter> {
+        let endpoint = config.node.telemetry_endpoint.clone()?;
+        let (sender, receiver) = sync_channel(1);
+        let spawned = thread::Builder::new()
+            .name("telemetry".to_string())
+            .spawn(move || send_reports(&endpoint, receiver));
+        if let Err(e) = spawned {
+            error!("Failed to start telemetry thread: {}", e);
+            return None;
+        }
+        info!(
+            "Reporting telemetry every {}s",
+            config.node.telemetry_interval_secs
+        );
+        Some(TelemetryReporter::new(
+            &config.burnchain.mode,
+            config.node.telemetry_interval_secs,
+            get_epoch_time_secs(),
+            sender,
+        ))
+    }
+
+    fn new(
+        network: &str,
+        interval_secs: u64,
+        now: u64,
+        sender: SyncSender<TelemetryReport>,
+    ) -> TelemetryReporter {
+        let mut id_bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut id_bytes);
+        TelemetryReporter {
+            report_id: to_hex(&id_bytes),
+            network: network.to_string(),
+            interval_secs,
+            start_time: now,
+            last_report: now,
+            sender,
+        }
+    }
+
+    /// Whether it is time to send another report.  The first report goes out one interval after
+    /// startup, once the node has had a chance to find peers.
+    pub fn is_due(&self, now: u64) -> bool {
+        self.last_report + self.interval_secs <= now
+    }
+
+    pub fn make_report(
+        &self,
+        now: u64,
+        burn_block_height: u64,
+        stacks_tip_height: u64,
+        syncing: bool,
+        peer_count: u64,
+    ) -> TelemetryReport {
+        TelemetryReport {
+            report_id: self.report_id.clone(),
+            version: crate::version(),
+            network: self.network.clone(),
+            uptime_secs: now.saturating_sub(self.start_time),
+            burn_block_height,
+            stacks_tip_height,
+            syncing,
+            peer_count,
+        }
+    }
+
+    /// Hand a report to the telemetry thread, unless it is still busy with the last one
+    pub fn report(&mut self, report: TelemetryReport) {
+        self.last_report = get_epoch_time_secs();
+        match self.sender.try_send(report) {
+            Ok(_) => {}
+            Err(TrySendError::Full(_)) => {
+                debug!("Telemetry endpoint is falling behind; dropping report");
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                debug!("Telemetry thread has exited; dropping report");
+            }
+        }
+    }
+}
+
+/// POST each report to `endpoint`, until the reporter goes away
+fn send_reports(endpoint: &str, receiver: Receiver<TelemetryReport>) {
+    let client = match reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(TELEMETRY_TIMEOUT_SECS))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Failed to create telemetry client: {}", &e);
+            return;
+        }
+    };
+    while let Ok(report) = receiver.recv() {
+        let res = client
+            .post(endpoint)
+            .json(&report)
+            .send()
+            .and_then(|resp| resp.error_for_status());
+        if let Err(e) = res {
+            debug!("Failed to send telemetry report to {}: {}", endpoint, &e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn telemetry_reports() {
+        let (sender, receiver) = sync_channel(1);
+        let mut reporter = TelemetryReporter::new("mainnet", 600, 1000, sender);
+        assert!(!reporter.is_due(1000));
+        assert!(!reporter.is_due(1599));
+        assert!(reporter.is_due(1600));
+
+        let report = reporter.make_report(1600, 700_000, 30_000, true, 12);
+        assert_eq!(report.uptime_secs, 600);
+        assert_eq!(report.network, "mainnet");
+        assert_eq!(report.report_id.len(), 32);
+        assert!(report.syncing);
+        reporter.report(report.clone());
+        assert!(!reporter.is_due(get_epoch_time_secs()));
+        assert_eq!(receiver.try_recv().unwrap(), report);
+
+        // a report the telemetry thread can't take yet is dropped, not queued
+        reporter.report(report.clone());
+        reporter.report(report.clone());
+        assert!(receiver.try_recv().is_ok());
+        assert!(receiver.try_recv().is_err());
+
+        // each run gets its own ID
+        let (sender, _receiver) = sync_channel(1);
+        let other = TelemetryReporter::new("mainnet", 600, 1000, sender);
+        assert_ne!(other.report_id, reporter.report_id);
+    }
+}
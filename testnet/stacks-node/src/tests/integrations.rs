@@ -1,4 +1,3 @@
-use std::collections::HashMap;
 use std::fmt::Write;
 use std::sync::Mutex;
 
@@ -425,12 +424,22 @@ fn integration_test_get_info() {
                 let res = client.post(&path)
                     .json(&key.serialize())
                     .send()
-                    .unwrap().json::<HashMap<String, String>>().unwrap();
-                let result_data = Value::try_deserialize_hex_untyped(&res["data"][2..]).unwrap();
+                    .unwrap().json::<serde_json::Value>().unwrap();
+                let result_data = Value::try_deserialize_hex_untyped(&res["data"].as_str().unwrap()[2..]).unwrap();
                 let expected_data = chain_state.clarity_eval_read_only(burn_dbconn, bhh, &contract_identifier,
                                                                        "(some (get-exotic-data-info u3))");
                 assert!(res.get("proof").is_some());
 
+                assert_eq!(result_data, expected_data);
+                assert_eq!(Value::try_from_json(&res["data_json"]).unwrap(), expected_data);
+
+                // the key can also be given in its JSON encoding
+                eprintln!("Test: POST {}", path);
+                let res = client.post(&path)
+                    .json(&key.to_json())
+                    .send()
+                    .unwrap().json::<serde_json::Value>().unwrap();
+                let result_data = Value::try_deserialize_hex_untyped(&res["data"].as_str().unwrap()[2..]).unwrap();
                 assert_eq!(result_data, expected_data);
 
                 let key: Value = TupleData::from_data(vec![("height".into(), Value::UInt(100))])
@@ -440,8 +449,8 @@ fn integration_test_get_info() {
                 let res = client.post(&path)
                     .json(&key.serialize())
                     .send()
-                    .unwrap().json::<HashMap<String, String>>().unwrap();
-                let result_data = Value::try_deserialize_hex_untyped(&res["data"][2..]).unwrap();
+                    .unwrap().json::<serde_json::Value>().unwrap();
+                let result_data = Value::try_deserialize_hex_untyped(&res["data"].as_str().unwrap()[2..]).unwrap();
                 assert_eq!(result_data, Value::none());
 
                 let sender_addr = to_addr(&StacksPrivateKey::from_hex(SK_3).unwrap());
@@ -457,10 +466,10 @@ fn integration_test_get_info() {
                 let res = client.post(&path)
                     .json(&key.serialize())
                     .send()
-                    .unwrap().json::<HashMap<String, String>>().unwrap();
+                    .unwrap().json::<serde_json::Value>().unwrap();
 
                 assert!(res.get("proof").is_none());
-                let result_data = Value::try_deserialize_hex_untyped(&res["data"][2..]).unwrap();
+                let result_data = Value::try_deserialize_hex_untyped(&res["data"].as_str().unwrap()[2..]).unwrap();
                 let expected_data = chain_state.clarity_eval_read_only(burn_dbconn, bhh, &contract_identifier,
                                                                        "(some (get-exotic-data-info u3))");
                 eprintln!("{}", serde_json::to_string(&res).unwrap());
@@ -478,10 +487,10 @@ fn integration_test_get_info() {
                 let res = client.post(&path)
                     .json(&key.serialize())
                     .send()
-                    .unwrap().json::<HashMap<String, String>>().unwrap();
+                    .unwrap().json::<serde_json::Value>().unwrap();
 
                 assert!(res.get("proof").is_some());
-                let result_data = Value::try_deserialize_hex_untyped(&res["data"][2..]).unwrap();
+                let result_data = Value::try_deserialize_hex_untyped(&res["data"].as_str().unwrap()[2..]).unwrap();
                 let expected_data = chain_state.clarity_eval_read_only(burn_dbconn, bhh, &contract_identifier,
                                                                        "(some (get-exotic-data-info u3))");
                 eprintln!("{}", serde_json::to_string(&res).unwrap());
@@ -603,7 +612,7 @@ fn integration_test_get_info() {
 
                 let body = CallReadOnlyRequestBody {
                     sender: "'SP139Q3N9RXCJCD1XVA4N5RYWQ5K9XQ0T9PKQ8EE5".into(),
-                    arguments: vec![Value::UInt(3).serialize()]
+                    arguments: vec![Value::UInt(3).serialize().into()]
                 };
 
                 let res = client.post(&path)
@@ -666,9 +675,10 @@ fn integration_test_get_info() {
                                    "get-exotic-data-info%3F");
                 eprintln!("Test: POST {}", path);
 
+                // ...with a JSON-encoded argument
                 let body = CallReadOnlyRequestBody {
                     sender: "'SP139Q3N9RXCJCD1XVA4N5RYWQ5K9XQ0T9PKQ8EE5".into(),
-                    arguments: vec![Value::UInt(3).serialize()]
+                    arguments: vec![Value::UInt(3).to_json()]
                 };
 
                 let res = client.post(&path)
@@ -683,6 +693,7 @@ fn integration_test_get_info() {
                 let expected_data = chain_state.clarity_eval_read_only(burn_dbconn, bhh, &contract_identifier,
                                                                        "(get-exotic-data-info? u3)");
                 assert_eq!(result_data, expected_data);
+                assert_eq!(Value::try_from_json(&res["result_json"]).unwrap(), expected_data);
 
                 // let's have a runtime error!
                 let path = format!("{}/v2/contracts/call-read/{}/{}/{}", &http_origin, &contract_addr, "get-info", "get-exotic-data-info");
@@ -690,7 +701,7 @@ fn integration_test_get_info() {
 
                 let body = CallReadOnlyRequestBody {
                     sender: "'SP139Q3N9RXCJCD1XVA4N5RYWQ5K9XQ0T9PKQ8EE5".into(),
-                    arguments: vec![Value::UInt(100).serialize()]
+                    arguments: vec![Value::UInt(100).serialize().into()]
                 };
 
                 let res = client.post(&path)
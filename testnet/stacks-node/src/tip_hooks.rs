@@ -0,0 +1,333 @@
+//! Hook commands.  Operators can have the node run a shell command each time its canonical Stacks
+//! tip changes (`node.tip_hook`), it processes a new burnchain block (`node.burn_block_hook`), or
+//! its canonical Stacks tip moves to a block that does not descend from the previous one
+//! (`node.reorg_hook`), without writing an event observer.  The Stacks tip hooks run once the
+//! node has processed the blocks it has, so blocks processed together (for example, while the
+//! node syncs) are announced once, by their tip, and blocks off the canonical fork are not
+//! announced at all.  Each command is run with `sh -c`, in a process group of its own, with the
+//! block it is about in its environment:
+//!
+//! * `STACKS_HOOK`: `tip`, `burn_block` or `reorg`
+//! * `STACKS_BLOCK_HEIGHT`, `STACKS_INDEX_BLOCK_HASH`, `STACKS_BLOCK_HASH`,
+//!   `STACKS_CONSENSUS_HASH`: the new Stacks tip (`tip` and `reorg` only)
+//! * `STACKS_PREVIOUS_BLOCK_HEIGHT`, `STACKS_PREVIOUS_INDEX_BLOCK_HASH`: the tip it replaces
+//!   (`reorg` only)
+//! * `STACKS_BURN_BLOCK_HEIGHT`, `STACKS_BURN_BLOCK_HASH`: the burnchain block
+//!
+//! Hashes are hex, without a `0x` prefix.  Output goes to the node's own stdout and stderr.
+//!
+//! Commands run one at a time, in order, on a thread of their own, so a slow command holds up
+//! the commands after it but never block processing.  A command still running after
+//! `node.hook_timeout_secs` is killed, along with everything it started.  If too many runs are waiting, new ones are dropped.
+//! Runs and failures are counted in the `stacks_node_hook_runs` and `stacks_node_hook_failures`
+//! metrics.
+
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use stacks::chainstate::stacks::db::StacksHeaderInfo;
+use stacks::monitoring;
+use stacks::types::chainstate::{BurnchainHeaderHash, StacksBlockId};
+
+use crate::config::NodeConfig;
+
+/// How many hook runs can wait for the ones before them to finish
+const MAX_QUEUED_HOOK_RUNS: usize = 64;
+
+/// How often a running command is checked on
+const HOOK_POLL_INTERVAL_MS: u64 = 50;
+
+struct HookRun {
+    hook: &'static str,
+    command: String,
+    env: Vec<(&'static str, String)>,
+}
+
+#[derive(Clone)]
+pub struct TipHooks {
+    tip_hook: Option<String>,
+    burn_block_hook: Option<String>,
+    reorg_hook: Option<String>,
+    runs: SyncSender<HookRun>,
+}
+
+impl TipHooks {
+    /// Returns None if no hooks are configured.  Otherwise, starts the thread the hooks run on,
+    /// which exits once every clone of the returned value has been dropped.
+    pub fn from_config(config: &NodeConfig) -> Option<TipHooks> {
+        if config.tip_hook.is_none()
+            && config.burn_block_hook.is_none()
+            && config.reorg_hook.is_none()
+        {
+            return None;
+        }
+        let (runs, receiver) = sync_channel(MAX_QUEUED_HOOK_RUNS);
+        let timeout = Duration::from_secs(config.hook_timeout_secs);
+        let spawned = thread::Builder::new()
+            .name("tip-hooks".to_string())
+            .spawn(move || run_hooks(receiver, timeout));
+        if let Err(e) = spawned {
+            error!("Failed to start the hook thread: {}", &e);
+            return None;
+        }
+        Some(TipHooks {
+            tip_hook: config.tip_hook.clone(),
+            burn_block_hook: config.burn_block_hook.clone(),
+            reorg_hook: config.reorg_hook.clone(),
+            runs,
+        })
+    }
+
+    /// Run the hooks for a new canonical Stacks tip.  `previous` is the canonical tip it
+    /// replaces, and `reorg` is true if `metadata` does not descend from it.
+    pub fn on_stacks_tip(
+        &self,
+        metadata: &StacksHeaderInfo,
+        previous: Option<(&StacksBlockId, u64)>,
+        reorg: bool,
+    ) {
+        let index_block_hash = metadata.index_block_hash();
+        let env = vec![
+            ("STACKS_BLOCK_HEIGHT", metadata.block_height.to_string()),
+            ("STACKS_INDEX_BLOCK_HASH", format!("{}", &index_block_hash)),
+            (
+                "STACKS_BLOCK_HASH",
+                format!("{}", metadata.anchored_header.block_hash()),
+            ),
+            (
+                "STACKS_CONSENSUS_HASH",
+                format!("{}", &metadata.consensus_hash),
+            ),
+            (
+                "STACKS_BURN_BLOCK_HEIGHT",
+                metadata.burn_header_height.to_string(),
+            ),
+            (
+                "STACKS_BURN_BLOCK_HASH",
+                format!("{}", &metadata.burn_header_hash),
+            ),
+        ];
+        if let (true, Some((previous_tip, previous_height))) = (reorg, previous) {
+            if let Some(command) = self.reorg_hook.as_ref() {
+                let mut reorg_env = env.clone();
+                reorg_env.push(("STACKS_PREVIOUS_BLOCK_HEIGHT", previous_height.to_string()));
+                reorg_env.push((
+                    "STACKS_PREVIOUS_INDEX_BLOCK_HASH",
+                    format!("{}", previous_tip),
+                ));
+                self.queue("reorg", command, reorg_env);
+            }
+        }
+        if let Some(command) = self.tip_hook.as_ref() {
+            self.queue("tip", command, env);
+        }
+    }
+
+    pub fn on_burn_block(&self, burn_block: &BurnchainHeaderHash, burn_block_height: u64) {
+        if let Some(command) = self.burn_block_hook.as_ref() {
+            self.queue(
+                "burn_block",
+                command,
+                vec![
+                    ("STACKS_BURN_BLOCK_HEIGHT", burn_block_height.to_string()),
+                    ("STACKS_BURN_BLOCK_HASH", format!("{}", burn_block)),
+                ],
+            );
+        }
+    }
+
+    fn queue(&self, hook: &'static str, command: &str, env: Vec<(&'static str, String)>) {
+        let run = HookRun {
+            hook,
+            command: command.to_string(),
+            env,
+        };
+        match self.runs.try_send(run) {
+            Ok(_) => {}
+            Err(TrySendError::Full(_)) => {
+                warn!(
+                    "Too many hook runs waiting; dropping a run of the {} hook",
+                    hook
+                );
+                monitoring::increment_hook_failures(hook, "dropped");
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                warn!("Hook thread is gone; not running the {} hook", hook);
+                monitoring::increment_hook_failures(hook, "dropped");
+            }
+        }
+    }
+}
+
+fn run_hooks(runs: Receiver<HookRun>, timeout: Duration) {
+    while let Ok(run) = runs.recv() {
+        monitoring::increment_hook_runs(run.hook);
+        if let Err(reason) = run_hook(&run, timeout) {
+            monitoring::increment_hook_failures(run.hook, reason);
+        }
+    }
+}
+
+/// Run one hook to completion, or until `timeout`.  Err with the reason it failed.
+fn run_hook(run: &HookRun, timeout: Duration) -> Result<(), &'static str> {
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(&run.command)
+        .envs(run.env.iter().cloned())
+        .env("STACKS_HOOK", run.hook)
+        .stdin(Stdio::null());
+    let mut child = in_own_process_group(&mut command).spawn().map_err(|e| {
+        warn!(
+            "Failed to run the {} hook `{}`: {}",
+            run.hook, &run.command, &e
+        );
+        "spawn"
+    })?;
+    match wait_with_timeout(&mut child, timeout) {
+        Some(status) if status.success() => Ok(()),
+        Some(status) => {
+            warn!(
+                "The {} hook `{}` failed: {}",
+                run.hook, &run.command, status
+            );
+            Err("exit")
+        }
+        None => {
+            warn!(
+                "The {} hook `{}` did not finish within {}s; killing it",
+                run.hook,
+                &run.command,
+                timeout.as_secs()
+            );
+            kill_process_group(&mut child);
+            let _ = child.wait();
+            Err("timeout")
+        }
+    }
+}
+
+/// Start `command` in a process group of its own, so that whatever it starts can be killed
+/// along with it
+#[cfg(unix)]
+fn in_own_process_group(command: &mut Command) -> &mut Command {
+    use std::os::unix::process::CommandExt;
+    command.process_group(0)
+}
+
+#[cfg(not(unix))]
+fn in_own_process_group(command: &mut Command) -> &mut Command {
+    command
+}
+
+/// Kill `child` and the rest of its process group.  Killing only the `sh` wrapper would leave
+/// the command it runs behind.
+#[cfg(unix)]
+fn kill_process_group(child: &mut Child) {
+    let pgid = child.id() as libc::pid_t;
+    if unsafe { libc::kill(-pgid, libc::SIGKILL) } != 0 {
+        let _ = child.kill();
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(child: &mut Child) {
+    let _ = child.kill();
+}
+
+/// The child's exit status, or None if it is still running after `timeout`
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> Option<std::process::ExitStatus> {
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Some(status),
+            Ok(None) => {}
+            Err(e) => {
+                warn!("Failed to check on a hook command: {}", &e);
+                return None;
+            }
+        }
+        if started.elapsed() >= timeout {
+            return None;
+        }
+        thread::sleep(Duration::from_millis(HOOK_POLL_INTERVAL_MS));
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn hook_runs() {
+        let dir = format!("/tmp/stacks-node-tests/tip-hooks-{}", process::id());
+        fs::create_dir_all(&dir).unwrap();
+        let out = format!("{}/out", &dir);
+
+        let run = HookRun {
+            hook: "burn_block",
+            command: format!("echo $STACKS_HOOK $STACKS_BURN_BLOCK_HEIGHT > {}", &out),
+            env: vec![("STACKS_BURN_BLOCK_HEIGHT", "123".to_string())],
+        };
+        assert_eq!(run_hook(&run, Duration::from_secs(10)), Ok(()));
+        assert_eq!(fs::read_to_string(&out).unwrap(), "burn_block 123\n");
+
+        let run = HookRun {
+            hook: "tip",
+            command: "exit 3".to_string(),
+            env: vec![],
+        };
+        assert_eq!(run_hook(&run, Duration::from_secs(10)), Err("exit"));
+
+        let run = HookRun {
+            hook: "tip",
+            command: "sleep 10".to_string(),
+            env: vec![],
+        };
+        let started = Instant::now();
+        assert_eq!(run_hook(&run, Duration::from_millis(200)), Err("timeout"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    /// Whether `pid` is a process that has not exited
+    #[cfg(target_os = "linux")]
+    fn is_running(pid: &str) -> bool {
+        match fs::read_to_string(format!("/proc/{}/stat", pid)) {
+            // the state follows the parenthesized command name
+            Ok(stat) => !stat.rsplitn(2, ')').next().unwrap().trim().starts_with('Z'),
+            Err(_) => false,
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn hook_timeout_kills_what_it_started() {
+        let dir = format!("/tmp/stacks-node-tests/tip-hooks-kill-{}", process::id());
+        fs::create_dir_all(&dir).unwrap();
+        let pid_file = format!("{}/pid", &dir);
+
+        let run = HookRun {
+            hook: "tip",
+            command: format!("sleep 30 & echo $! > {}; wait", &pid_file),
+            env: vec![],
+        };
+        assert_eq!(run_hook(&run, Duration::from_millis(500)), Err("timeout"));
+
+        let pid = fs::read_to_string(&pid_file).unwrap();
+        let pid = pid.trim();
+        let started = Instant::now();
+        while is_running(pid) {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "hook's child {} outlived the hook",
+                pid
+            );
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Local tip notifications.  With `node.tip_notify_socket` set, the node listens on a UNIX domain
+//! socket at that path, and writes one line of JSON to every connected client each time it
+//! processes a new Stacks block or burnchain block.  Processes on the same machine (signers,
+//! indexers, scripts) can wait on the socket instead of polling `/v2/info`.
+//!
+//! Notifications are best-effort: a client that is not keeping up (its socket buffer is full) is
+//! disconnected rather than allowed to hold up block processing.
+
+use std::io;
+use std::sync::Arc;
+
+use stacks::chainstate::stacks::db::StacksHeaderInfo;
+use stacks::types::chainstate::BurnchainHeaderHash;
+
+#[cfg(unix)]
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::sync::Mutex;
+#[cfg(unix)]
+use std::thread;
+#[cfg(unix)]
+use std::time::Duration;
+
+/// How often the listener thread checks for new clients
+#[cfg(unix)]
+const ACCEPT_INTERVAL_MS: u64 = 100;
+
+#[derive(Clone)]
+pub struct TipNotifier {
+    #[cfg(unix)]
+    clients: Arc<Mutex<Vec<UnixStream>>>,
+}
+
+impl TipNotifier {
+    /// Listen on a UNIX socket at `path`, replacing a socket left behind by a previous run.  The
+    /// listener thread exits once `should_keep_running` is cleared.
+    #[cfg(unix)]
+    pub fn bind(path: &str, should_keep_running: Arc<AtomicBool>) -> io::Result<TipNotifier> {
+        use std::fs;
+        use std::os::unix::fs::FileTypeExt;
+
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path),
+                ));
+            }
+            fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+
+        let notifier = TipNotifier {
+            clients: Arc::new(Mutex::new(vec![])),
+        };
+        let clients = notifier.clients.clone();
+        let path = path.to_string();
+        thread::Builder::new()
+            .name("tip-notifier".to_string())
+            .spawn(move || {
+                while should_keep_running.load(Ordering::SeqCst) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            if let Err(e) = stream.set_nonblocking(true) {
+                                warn!("Failed to set up tip notification client: {}", &e);
+                                continue;
+                            }
+                            debug!("New tip notification client on {}", &path);
+                            clients
+                                .lock()
+                                .expect("FATAL: tip notifier lock poisoned")
+                                .push(stream);
+                        }
+                        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            thread::sleep(Duration::from_millis(ACCEPT_INTERVAL_MS));
+                        }
+                        Err(e) => {
+                            warn!("Failed to accept tip notification client: {}", &e);
+                            thread::sleep(Duration::from_millis(ACCEPT_INTERVAL_MS));
+                        }
+                    }
+                }
+                let _ = fs::remove_file(&path);
+            })?;
+        Ok(notifier)
+    }
+
+    #[cfg(not(unix))]
+    pub fn bind(
+        _path: &str,
+        _should_keep_running: Arc<std::sync::atomic::AtomicBool>,
+    ) -> io::Result<TipNotifier> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "tip notification sockets are not supported on this platform",
+        ))
+    }
+
+    pub fn notify_stacks_tip(&self, metadata: &StacksHeaderInfo) {
+        self.notify(&json!({
+            "event": "stacks_tip",
+            "block_height": metadata.block_height,
+            "index_block_hash": format!("0x{}", metadata.index_block_hash()),
+            "block_hash": format!("0x{}", metadata.anchored_header.block_hash()),
+            "consensus_hash": format!("0x{}", metadata.consensus_hash),
+            "burn_block_hash": format!("0x{}", metadata.burn_header_hash),
+            "burn_block_height": metadata.burn_header_height,
+        }));
+    }
+
+    pub fn notify_burn_tip(&self, burn_block: &BurnchainHeaderHash, burn_block_height: u64) {
+        self.notify(&json!({
+            "event": "burn_tip",
+            "burn_block_hash": format!("0x{}", burn_block),
+            "burn_block_height": burn_block_height,
+        }));
+    }
+
+    /// Write `payload` as one line to every client, dropping the ones that fail
+    #[cfg(unix)]
+    fn notify(&self, payload: &serde_json::Value) {
+        let line = format!("{}\n", payload);
+        let mut clients = self
+            .clients
+            .lock()
+            .expect("FATAL: tip notifier lock poisoned");
+        clients.retain(|client| {
+            let mut client: &UnixStream = client;
+            match client.write_all(line.as_bytes()) {
+                Ok(_) => true,
+                Err(e) => {
+                    debug!("Dropping tip notification client: {}", &e);
+                    false
+                }
+            }
+        });
+    }
+
+    #[cfg(not(unix))]
+    fn notify(&self, _payload: &serde_json::Value) {}
+
+    #[cfg(unix)]
+    pub fn num_clients(&self) -> usize {
+        self.clients
+            .lock()
+            .expect("FATAL: tip notifier lock poisoned")
+            .len()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+
+    use std::fs;
+    use std::io::{BufRead, BufReader};
+    use std::process;
+
+    #[test]
+    fn tip_notifications() {
+        let dir = format!("/tmp/stacks-node-tests/tip-notifier-{}", process::id());
+        fs::create_dir_all(&dir).unwrap();
+        let path = format!("{}/tips.sock", &dir);
+
+        // a socket left over from a previous run is replaced, but other files are not
+        let should_keep_running = Arc::new(AtomicBool::new(true));
+        TipNotifier::bind(&path, should_keep_running.clone()).unwrap();
+        let notifier = TipNotifier::bind(&path, should_keep_running.clone()).unwrap();
+        let other_path = format!("{}/not-a-socket", &dir);
+        fs::write(&other_path, "").unwrap();
+        assert!(TipNotifier::bind(&other_path, should_keep_running.clone()).is_err());
+
+        let client = UnixStream::connect(&path).unwrap();
+        while notifier.num_clients() == 0 {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        notifier.notify_burn_tip(&BurnchainHeaderHash([0x11; 32]), 123);
+        let mut reader = BufReader::new(client);
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let event: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(event["event"], "burn_tip");
+        assert_eq!(event["burn_block_height"], 123);
+        assert_eq!(
+            event["burn_block_hash"],
+            format!("0x{}", BurnchainHeaderHash([0x11; 32]))
+        );
+
+        // a client that went away is dropped
+        drop(reader);
+        notifier.notify_burn_tip(&BurnchainHeaderHash([0x22; 32]), 124);
+        notifier.notify_burn_tip(&BurnchainHeaderHash([0x33; 32]), 125);
+        assert_eq!(notifier.num_clients(), 0);
+
+        should_keep_running.store(false, Ordering::SeqCst);
+    }
+}
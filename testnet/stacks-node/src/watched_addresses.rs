@@ -0,0 +1,532 @@
+//! Watched addresses.  An event observer subscribed to `watch:<principal>` keys (e.g. an
+//! exchange's deposit monitor) is sent, for each Stacks block that involves one of its watched
+//! principals, a short list of what happened to them instead of the whole block:
+//!
+//! * `balance_change`: the STX the principal received (including mints), and the STX it sent,
+//!   burned and paid in fees, and the net change in its balance
+//! * `nonce_change`: the principal's next nonce, after the transactions it sent or sponsored
+//! * `incoming_transfer`: an STX, fungible token or NFT transfer to the principal
+//!
+//! Along with the events, the node keeps a summary of each watched principal's activity since it
+//! started (`AddressActivity`), and sends the summaries of the principals in each payload.
+//!
+//! Only anchored blocks (and the microblocks they confirm) are considered; activity in
+//! unconfirmed microblocks is not reported until a block confirms it.  A principal's events for a
+//! block on a fork that is later abandoned are not retracted; consumers that need certainty should
+//! wait for confirmations as usual.  Locking STX for stacking does not change the balance, and
+//! miner rewards are not reported.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use serde_json::json;
+
+use stacks::burnchains::Txid;
+use stacks::chainstate::stacks::events::{
+    FTEventType, NFTEventType, STXEventType, StacksTransactionEvent, StacksTransactionReceipt,
+    TransactionOrigin,
+};
+use stacks::codec::StacksMessageCodec;
+use stacks::types::chainstate::StacksBlockId;
+use stacks::util::hash::to_hex;
+use stacks::vm::types::{AssetIdentifier, PrincipalData, Value};
+
+/// What was transferred to a watched principal
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransferredAsset {
+    Stx(u128),
+    FungibleToken(AssetIdentifier, u128),
+    NonFungibleToken(AssetIdentifier, Value),
+}
+
+/// What happened to a watched principal in a block
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchedAddressEvent {
+    BalanceChange {
+        principal: PrincipalData,
+        received: u128,
+        sent: u128,
+        txids: Vec<Txid>,
+    },
+    NonceChange {
+        principal: PrincipalData,
+        nonce: u64,
+        txid: Txid,
+    },
+    IncomingTransfer {
+        principal: PrincipalData,
+        sender: PrincipalData,
+        asset: TransferredAsset,
+        txid: Txid,
+    },
+}
+
+impl WatchedAddressEvent {
+    pub fn principal(&self) -> &PrincipalData {
+        match self {
+            WatchedAddressEvent::BalanceChange { principal, .. }
+            | WatchedAddressEvent::NonceChange { principal, .. }
+            | WatchedAddressEvent::IncomingTransfer { principal, .. } => principal,
+        }
+    }
+
+    pub fn json_serialize(&self) -> serde_json::Value {
+        match self {
+            WatchedAddressEvent::BalanceChange {
+                principal,
+                received,
+                sent,
+                txids,
+            } => json!({
+                "type": "balance_change",
+                "principal": format!("{}", principal),
+                "received": format!("{}", received),
+                "sent": format!("{}", sent),
+                "delta": format!("{}", (*received as i128).wrapping_sub(*sent as i128)),
+                "txids": txids.iter().map(|txid| format!("0x{}", txid)).collect::<Vec<_>>(),
+            }),
+            WatchedAddressEvent::NonceChange {
+                principal,
+                nonce,
+                txid,
+            } => json!({
+                "type": "nonce_change",
+                "principal": format!("{}", principal),
+                "nonce": nonce,
+                "txid": format!("0x{}", txid),
+            }),
+            WatchedAddressEvent::IncomingTransfer {
+                principal,
+                sender,
+                asset,
+                txid,
+            } => {
+                let mut payload = json!({
+                    "type": "incoming_transfer",
+                    "principal": format!("{}", principal),
+                    "sender": format!("{}", sender),
+                    "txid": format!("0x{}", txid),
+                });
+                match asset {
+                    TransferredAsset::Stx(amount) => {
+                        payload["asset"] = json!("stx");
+                        payload["amount"] = json!(format!("{}", amount));
+                    }
+                    TransferredAsset::FungibleToken(asset_identifier, amount) => {
+                        payload["asset"] = json!(format!("{}", asset_identifier));
+                        payload["amount"] = json!(format!("{}", amount));
+                    }
+                    TransferredAsset::NonFungibleToken(asset_identifier, value) => {
+                        payload["asset"] = json!(format!("{}", asset_identifier));
+                        payload["raw_value"] =
+                            json!(format!("0x{}", to_hex(&value.serialize_to_vec())));
+                    }
+                }
+                payload
+            }
+        }
+    }
+}
+
+/// A watched principal's activity since the node started
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AddressActivity {
+    /// The principal's next nonce, if it has sent or sponsored a transaction
+    pub nonce: Option<u64>,
+    pub stx_received: u128,
+    /// STX sent and burned, and fees paid
+    pub stx_sent: u128,
+    pub incoming_transfers: u64,
+    pub last_block_height: u64,
+    pub last_index_block_hash: Option<StacksBlockId>,
+}
+
+impl AddressActivity {
+    pub fn json_serialize(&self) -> serde_json::Value {
+        json!({
+            "nonce": self.nonce,
+            "stx_received": format!("{}", self.stx_received),
+            "stx_sent": format!("{}", self.stx_sent),
+            "incoming_transfers": self.incoming_transfers,
+            "last_block_height": self.last_block_height,
+            "last_index_block_hash": self
+                .last_index_block_hash
+                .as_ref()
+                .map(|hash| format!("0x{}", hash)),
+        })
+    }
+}
+
+/// A watched principal's running totals within one block
+#[derive(Default)]
+struct BlockActivity {
+    received: u128,
+    sent: u128,
+    txids: Vec<Txid>,
+    nonce: Option<(u64, Txid)>,
+}
+
+impl BlockActivity {
+    fn touch(&mut self, txid: &Txid) {
+        if !self.txids.contains(txid) {
+            self.txids.push(txid.clone());
+        }
+    }
+
+    fn observe_nonce(&mut self, nonce: u64, txid: &Txid) {
+        // the next nonce is one past the highest one used
+        let next = nonce.saturating_add(1);
+        if self.nonce.as_ref().map(|(n, _)| next > *n).unwrap_or(true) {
+            self.nonce = Some((next, txid.clone()));
+        }
+    }
+}
+
+/// What happened to the `watched` principals in a block with these receipts.  The balance and
+/// nonce changes come first, one of each per principal, ordered by principal; the incoming
+/// transfers follow, in the order they happened.
+pub fn collect_watched_events(
+    watched: &HashSet<PrincipalData>,
+    receipts: &[StacksTransactionReceipt],
+) -> Vec<WatchedAddressEvent> {
+    let mut activity: HashMap<PrincipalData, BlockActivity> = HashMap::new();
+    let mut transfers = vec![];
+
+    for receipt in receipts.iter() {
+        let txid = receipt.transaction.txid();
+        if let TransactionOrigin::Stacks(tx) = &receipt.transaction {
+            let origin = tx.origin_address().to_account_principal();
+            let sponsor = tx.sponsor_address().map(|addr| addr.to_account_principal());
+
+            if watched.contains(&origin) {
+                activity
+                    .entry(origin.clone())
+                    .or_default()
+                    .observe_nonce(tx.get_origin_nonce(), &txid);
+            }
+            if let (Some(sponsor), Some(sponsor_nonce)) = (sponsor.as_ref(), tx.get_sponsor_nonce())
+            {
+                if watched.contains(sponsor) {
+                    activity
+                        .entry(sponsor.clone())
+                        .or_default()
+                        .observe_nonce(sponsor_nonce, &txid);
+                }
+            }
+
+            let payer = sponsor.unwrap_or(origin);
+            let fee = tx.get_tx_fee();
+            if fee > 0 && watched.contains(&payer) {
+                let payer_activity = activity.entry(payer).or_default();
+                payer_activity.sent += fee as u128;
+                payer_activity.touch(&txid);
+            }
+        }
+
+        // the rest of an aborted transaction's effects were rolled back
+        if receipt.post_condition_aborted {
+            continue;
+        }
+        for event in receipt.events.iter() {
+            let (debit, credit, transfer) = match event {
+                StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(data)) => (
+                    Some((&data.sender, data.amount)),
+                    Some((&data.recipient, data.amount)),
+                    Some((
+                        &data.sender,
+                        &data.recipient,
+                        TransferredAsset::Stx(data.amount),
+                    )),
+                ),
+                StacksTransactionEvent::STXEvent(STXEventType::STXMintEvent(data)) => {
+                    (None, Some((&data.recipient, data.amount)), None)
+                }
+                StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(data)) => {
+                    (Some((&data.sender, data.amount)), None, None)
+                }
+                StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(data)) => (
+                    None,
+                    None,
+                    Some((
+                        &data.sender,
+                        &data.recipient,
+                        TransferredAsset::FungibleToken(data.asset_identifier.clone(), data.amount),
+                    )),
+                ),
+                StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(data)) => (
+                    None,
+                    None,
+                    Some((
+                        &data.sender,
+                        &data.recipient,
+                        TransferredAsset::NonFungibleToken(
+                            data.asset_identifier.clone(),
+                            data.value.clone(),
+                        ),
+                    )),
+                ),
+                _ => (None, None, None),
+            };
+
+            if let Some((sender, amount)) = debit {
+                if watched.contains(sender) {
+                    let sender_activity = activity.entry(sender.clone()).or_default();
+                    sender_activity.sent += amount;
+                    sender_activity.touch(&txid);
+                }
+            }
+            if let Some((recipient, amount)) = credit {
+                if watched.contains(recipient) {
+                    let recipient_activity = activity.entry(recipient.clone()).or_default();
+                    recipient_activity.received += amount;
+                    recipient_activity.touch(&txid);
+                }
+            }
+            if let Some((sender, recipient, asset)) = transfer {
+                if watched.contains(recipient) {
+                    transfers.push(WatchedAddressEvent::IncomingTransfer {
+                        principal: recipient.clone(),
+                        sender: sender.clone(),
+                        asset,
+                        txid: txid.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    let mut activity: Vec<_> = activity.into_iter().collect();
+    activity.sort_by_cached_key(|(principal, _)| principal.to_string());
+
+    let mut events = vec![];
+    for (principal, block_activity) in activity.into_iter() {
+        if block_activity.received != 0 || block_activity.sent != 0 {
+            events.push(WatchedAddressEvent::BalanceChange {
+                principal: principal.clone(),
+                received: block_activity.received,
+                sent: block_activity.sent,
+                txids: block_activity.txids.clone(),
+            });
+        }
+        if let Some((nonce, txid)) = block_activity.nonce {
+            events.push(WatchedAddressEvent::NonceChange {
+                principal,
+                nonce,
+                txid,
+            });
+        }
+    }
+    events.extend(transfers);
+    events
+}
+
+/// The activity summaries of the watched principals, shared by every clone of the event
+/// dispatcher
+#[derive(Clone, Default)]
+pub struct WatchedActivity {
+    summaries: Arc<Mutex<HashMap<PrincipalData, AddressActivity>>>,
+}
+
+impl WatchedActivity {
+    /// Fold a block's events into the summaries.  Summaries of principals that are no longer
+    /// `watched` are dropped.
+    pub fn record(
+        &self,
+        watched: &HashSet<PrincipalData>,
+        events: &[WatchedAddressEvent],
+        block_height: u64,
+        index_block_hash: &StacksBlockId,
+    ) {
+        let mut summaries = self
+            .summaries
+            .lock()
+            .expect("FATAL: watched address summaries lock poisoned");
+        summaries.retain(|principal, _| watched.contains(principal));
+
+        let mut touched = HashSet::new();
+        for event in events.iter() {
+            let summary = summaries.entry(event.principal().clone()).or_default();
+            match event {
+                WatchedAddressEvent::BalanceChange { received, sent, .. } => {
+                    summary.stx_received = summary.stx_received.saturating_add(*received);
+                    summary.stx_sent = summary.stx_sent.saturating_add(*sent);
+                }
+                WatchedAddressEvent::NonceChange { nonce, .. } => {
+                    summary.nonce = Some(*nonce);
+                }
+                WatchedAddressEvent::IncomingTransfer { .. } => {
+                    summary.incoming_transfers += 1;
+                }
+            }
+            touched.insert(event.principal().clone());
+        }
+        for principal in touched.into_iter() {
+            if let Some(summary) = summaries.get_mut(&principal) {
+                summary.last_block_height = block_height;
+                summary.last_index_block_hash = Some(index_block_hash.clone());
+            }
+        }
+    }
+
+    pub fn summary(&self, principal: &PrincipalData) -> Option<AddressActivity> {
+        self.summaries
+            .lock()
+            .expect("FATAL: watched address summaries lock poisoned")
+            .get(principal)
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use stacks::chainstate::stacks::events::{FTTransferEventData, STXTransferEventData};
+    use stacks::chainstate::stacks::{StacksPrivateKey, StacksTransaction};
+    use stacks::vm::costs::ExecutionCost;
+    use stacks::vm::types::QualifiedContractIdentifier;
+
+    use crate::tests::{make_sponsored_stacks_transfer_on_testnet, make_stacks_transfer, to_addr};
+
+    fn transfer_receipt(
+        tx_bytes: Vec<u8>,
+        events: Vec<StacksTransactionEvent>,
+    ) -> StacksTransactionReceipt {
+        let tx = StacksTransaction::consensus_deserialize(&mut &tx_bytes[..]).unwrap();
+        StacksTransactionReceipt::from_stx_transfer(
+            tx,
+            events,
+            Value::okay_true(),
+            ExecutionCost::zero(),
+        )
+    }
+
+    fn stx_transfer_event(
+        sender: &PrincipalData,
+        recipient: &PrincipalData,
+        amount: u128,
+    ) -> StacksTransactionEvent {
+        StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(STXTransferEventData {
+            sender: sender.clone(),
+            recipient: recipient.clone(),
+            amount,
+        }))
+    }
+
+    #[test]
+    fn watched_events_and_summaries() {
+        let alice_sk = StacksPrivateKey::new();
+        let bob_sk = StacksPrivateKey::new();
+        let sponsor_sk = StacksPrivateKey::new();
+        let alice: PrincipalData = to_addr(&alice_sk).into();
+        let bob: PrincipalData = to_addr(&bob_sk).into();
+        let sponsor: PrincipalData = to_addr(&sponsor_sk).into();
+
+        let mut watched = HashSet::new();
+        watched.insert(bob.clone());
+        watched.insert(sponsor.clone());
+
+        // alice pays bob 1000, then bob pays 300 back, sponsored
+        let to_bob = transfer_receipt(
+            make_stacks_transfer(&alice_sk, 0, 180, &bob, 1000),
+            vec![stx_transfer_event(&alice, &bob, 1000)],
+        );
+        let to_alice = transfer_receipt(
+            make_sponsored_stacks_transfer_on_testnet(&bob_sk, &sponsor_sk, 4, 7, 200, &alice, 300),
+            vec![stx_transfer_event(&bob, &alice, 300)],
+        );
+        // a token transfer to bob that was aborted only costs its fee
+        let token = AssetIdentifier {
+            contract_identifier: QualifiedContractIdentifier::transient(),
+            asset_name: "token".into(),
+        };
+        let mut aborted = transfer_receipt(
+            make_stacks_transfer(&alice_sk, 1, 180, &bob, 1),
+            vec![StacksTransactionEvent::FTEvent(
+                FTEventType::FTTransferEvent(FTTransferEventData {
+                    asset_identifier: token,
+                    sender: alice.clone(),
+                    recipient: bob.clone(),
+                    amount: 5,
+                }),
+            )],
+        );
+        aborted.post_condition_aborted = true;
+
+        let to_bob_txid = to_bob.transaction.txid();
+        let to_alice_txid = to_alice.transaction.txid();
+        let events = collect_watched_events(&watched, &[to_bob, to_alice, aborted]);
+
+        let mut expected = vec![
+            (
+                bob.to_string(),
+                vec![
+                    WatchedAddressEvent::BalanceChange {
+                        principal: bob.clone(),
+                        received: 1000,
+                        sent: 300,
+                        txids: vec![to_bob_txid.clone(), to_alice_txid.clone()],
+                    },
+                    WatchedAddressEvent::NonceChange {
+                        principal: bob.clone(),
+                        nonce: 5,
+                        txid: to_alice_txid.clone(),
+                    },
+                ],
+            ),
+            (
+                sponsor.to_string(),
+                vec![
+                    WatchedAddressEvent::BalanceChange {
+                        principal: sponsor.clone(),
+                        received: 0,
+                        sent: 200,
+                        txids: vec![to_alice_txid.clone()],
+                    },
+                    WatchedAddressEvent::NonceChange {
+                        principal: sponsor.clone(),
+                        nonce: 8,
+                        txid: to_alice_txid.clone(),
+                    },
+                ],
+            ),
+        ];
+        expected.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut expected: Vec<_> = expected.into_iter().flat_map(|(_, e)| e).collect();
+        expected.push(WatchedAddressEvent::IncomingTransfer {
+            principal: bob.clone(),
+            sender: alice.clone(),
+            asset: TransferredAsset::Stx(1000),
+            txid: to_bob_txid.clone(),
+        });
+        assert_eq!(events, expected);
+
+        // nothing about alice, who is not watched
+        assert!(events.iter().all(|e| e.principal() != &alice));
+        let bob_balance = events
+            .iter()
+            .find(|e| e.principal() == &bob)
+            .unwrap()
+            .json_serialize();
+        assert_eq!(bob_balance["type"], "balance_change");
+        assert_eq!(bob_balance["delta"], "700");
+
+        let activity = WatchedActivity::default();
+        let tip = StacksBlockId([1; 32]);
+        activity.record(&watched, &events, 10, &tip);
+        let summary = activity.summary(&bob).unwrap();
+        assert_eq!(summary.stx_received, 1000);
+        assert_eq!(summary.stx_sent, 300);
+        assert_eq!(summary.nonce, Some(5));
+        assert_eq!(summary.incoming_transfers, 1);
+        assert_eq!(summary.last_block_height, 10);
+        assert_eq!(activity.summary(&sponsor).unwrap().stx_sent, 200);
+        assert!(activity.summary(&alice).is_none());
+
+        // unwatching a principal drops its summary
+        watched.remove(&sponsor);
+        activity.record(&watched, &[], 11, &tip);
+        assert!(activity.summary(&sponsor).is_none());
+        assert_eq!(activity.summary(&bob).unwrap().last_block_height, 10);
+    }
+}